@@ -1,7 +1,6 @@
 use chunkstream_pro::api::{create_api_server, StartTransferRequest};
 use chunkstream_pro::chunk::{ChunkManager, Priority};
 use chunkstream_pro::coordinator::TransferCoordinator;
-use chunkstream_pro::integrity::IntegrityVerifier;
 use chunkstream_pro::network::{ConnectionConfig, QuicTransport};
 use chunkstream_pro::priority::PriorityQueue;
 use chunkstream_pro::session::SessionStore;
@@ -19,14 +18,13 @@ async fn main() {
     println!("----------------------------------");
 
     let chunk_manager = ChunkManager::new(256 * 1024, 10, 3).unwrap();
-    let verifier = IntegrityVerifier;
     let config = ConnectionConfig::default();
     let transport = QuicTransport::new(config).await.unwrap();
     let queue = PriorityQueue::new(1_000_000);
     let session_store = SessionStore::new_in_memory().await.unwrap();
 
     let coordinator =
-        TransferCoordinator::new(chunk_manager, verifier, transport, queue, session_store);
+        TransferCoordinator::new(chunk_manager, transport, queue, session_store);
 
     let _app = create_api_server(coordinator.clone());
 
@@ -58,8 +56,12 @@ async fn main() {
 
     let _request = StartTransferRequest {
         file_path: file_path.to_string_lossy().to_string(),
-        priority: Priority::High,
+        priority: Some(Priority::High),
         receiver_addr: None,
+        local_bind_addr: None,
+        profile: None,
+        allow_duplicate: false,
+        tags: Default::default(),
     };
 
     println!("\nSimulating REST API call:");
@@ -153,15 +155,7 @@ async fn main() {
     println!("GET /api/v1/transfers/{}", session_id);
 
     if let Some(state) = coordinator.get_state(&session_id) {
-        let state_str = match state {
-            chunkstream_pro::coordinator::TransferState::Idle => "Idle",
-            chunkstream_pro::coordinator::TransferState::Preparing => "Preparing",
-            chunkstream_pro::coordinator::TransferState::Transferring { .. } => "Transferring",
-            chunkstream_pro::coordinator::TransferState::Paused { .. } => "Paused",
-            chunkstream_pro::coordinator::TransferState::Completing => "Completing",
-            chunkstream_pro::coordinator::TransferState::Completed => "Completed",
-            chunkstream_pro::coordinator::TransferState::Failed { .. } => "Failed",
-        };
+        let state_str = state.name();
 
         println!("\n✅ Response:");
         println!("{{");
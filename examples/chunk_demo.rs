@@ -42,7 +42,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     // Show chunk details
     println!("\n📦 Chunk details:");
-    for (_i, chunk) in chunks.iter().take(5).enumerate() {
+    for chunk in chunks.iter().take(5) {
         let chunk_type = if chunk.metadata.is_parity {
             "PARITY"
         } else {
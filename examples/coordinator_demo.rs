@@ -1,6 +1,5 @@
 use chunkstream_pro::chunk::{ChunkManager, Priority};
 use chunkstream_pro::coordinator::TransferCoordinator;
-use chunkstream_pro::integrity::IntegrityVerifier;
 use chunkstream_pro::network::{ConnectionConfig, QuicTransport};
 use chunkstream_pro::priority::PriorityQueue;
 use chunkstream_pro::session::SessionStore;
@@ -18,14 +17,13 @@ async fn main() {
     println!("----------------------------------");
 
     let chunk_manager = ChunkManager::new(256 * 1024, 10, 3).unwrap();
-    let verifier = IntegrityVerifier;
     let config = ConnectionConfig::default();
     let transport = QuicTransport::new(config).await.unwrap();
     let queue = PriorityQueue::new(1_000_000);
     let session_store = SessionStore::new_in_memory().await.unwrap();
 
     let coordinator =
-        TransferCoordinator::new(chunk_manager, verifier, transport, queue, session_store);
+        TransferCoordinator::new(chunk_manager, transport, queue, session_store);
 
     println!("✅ Coordinator created successfully");
     println!("   Components:");
@@ -128,15 +126,7 @@ async fn main() {
 
     for session_id in [&session_id1, &session_id2, &session_id3] {
         if let Some(state) = coordinator.get_state(session_id) {
-            let status = match state {
-                chunkstream_pro::coordinator::TransferState::Idle => "Idle",
-                chunkstream_pro::coordinator::TransferState::Preparing => "Preparing",
-                chunkstream_pro::coordinator::TransferState::Transferring { .. } => "Transferring",
-                chunkstream_pro::coordinator::TransferState::Paused { .. } => "Paused",
-                chunkstream_pro::coordinator::TransferState::Completing => "Completing",
-                chunkstream_pro::coordinator::TransferState::Completed => "Completed",
-                chunkstream_pro::coordinator::TransferState::Failed { .. } => "Failed",
-            };
+            let status = state.name();
             println!("   {} -> {}", &session_id[..8], status);
         }
     }
@@ -18,6 +18,12 @@ fn create_test_chunk(data: &[u8], sequence_number: u32, total_chunks: u32) -> Ch
             file_size: data.len() as u64 * total_chunks as u64,
             file_checksum: [0u8; 32],
             data_chunks: total_chunks,
+            zero_runs: Vec::new(),
+            attributes: None,
+            checksum_algorithm: Default::default(),
+            merkle_proof: None,
+            trace: None,
+            compression: None,
         },
         data: Bytes::from(data.to_vec()),
     }
@@ -170,6 +176,12 @@ async fn main() {
         file_size: 256 * 1024 * 10,
         file_checksum: [0u8; 32],
         data_chunks: 8,
+        zero_runs: Vec::new(),
+        attributes: None,
+        checksum_algorithm: Default::default(),
+        merkle_proof: None,
+        trace: None,
+        compression: None,
     };
 
     match IntegrityVerifier::verify_metadata(&valid_metadata) {
@@ -190,6 +202,12 @@ async fn main() {
         file_size: 256 * 1024 * 10,
         file_checksum: [0u8; 32],
         data_chunks: 8,
+        zero_runs: Vec::new(),
+        attributes: None,
+        checksum_algorithm: Default::default(),
+        merkle_proof: None,
+        trace: None,
+        compression: None,
     };
 
     match IntegrityVerifier::verify_metadata(&invalid_metadata) {
@@ -211,6 +229,13 @@ async fn main() {
         parity_chunks: 3,
         priority: Priority::Normal,
         checksum: [0u8; 32],
+        zero_runs: Vec::new(),
+        attributes: None,
+        checksum_algorithm: Default::default(),
+        erasure_profile: Default::default(),
+        schedule: None,
+        merkle_root: None,
+        compression: None,
     };
 
     println!("Manifest:");
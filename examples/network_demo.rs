@@ -19,6 +19,12 @@ fn create_test_chunk(data: &[u8], seq: u32) -> Chunk {
             file_size: data.len() as u64 * 10,
             file_checksum: [0u8; 32],
             data_chunks: 8,
+            zero_runs: Vec::new(),
+            attributes: None,
+            checksum_algorithm: Default::default(),
+            merkle_proof: None,
+            trace: None,
+            compression: None,
         },
         data: Bytes::from(data.to_vec()),
     }
@@ -105,7 +111,11 @@ async fn main() {
     let server_clone = server.clone();
     let receive_task = tokio::spawn(async move {
         let stream = server_conn.accept_uni().await.unwrap();
-        let received = server_clone.receive_chunk(stream).await.unwrap();
+        let received = server_clone
+            .receive_chunk(stream)
+            .await
+            .unwrap()
+            .into_chunk();
         println!("\n   Server received:");
         println!("   ✅ Chunk ID: {}", received.metadata.chunk_id);
         println!("   ✅ Data: {:?}", String::from_utf8_lossy(&received.data));
@@ -133,7 +143,7 @@ async fn main() {
                 tokio::time::timeout(Duration::from_secs(2), conn.accept_uni()).await
             {
                 if let Ok(chunk) = server_clone.receive_chunk(stream.unwrap()).await {
-                    received_chunks.push(chunk);
+                    received_chunks.push(chunk.into_chunk());
                 }
             }
         }
@@ -194,7 +204,11 @@ async fn main() {
     let receive_task = tokio::spawn(async move {
         let conn = server_clone.accept().await.unwrap();
         let stream = conn.accept_uni().await.unwrap();
-        server_clone.receive_chunk(stream).await.unwrap()
+        server_clone
+            .receive_chunk(stream)
+            .await
+            .unwrap()
+            .into_chunk()
     });
 
     tokio::time::sleep(Duration::from_millis(50)).await;
@@ -18,6 +18,12 @@ fn create_test_chunk(priority: Priority, seq: u32, data: &str) -> Chunk {
             file_size: 100 * data.len() as u64,
             file_checksum: [0u8; 32],
             data_chunks: 80,
+            zero_runs: Vec::new(),
+            attributes: None,
+            checksum_algorithm: Default::default(),
+            merkle_proof: None,
+            trace: None,
+            compression: None,
         },
         data: Bytes::from(data.to_owned()),
     }
@@ -146,9 +152,9 @@ async fn main() {
     println!("   Total enqueued: {}", stats.total_enqueued);
     println!("   Total processed: {}", stats.total_processed);
     println!("   Processing rate: {:.1}%", stats.processing_rate());
-    println!("   Critical pending: {}", stats.critical_pending);
-    println!("   High pending: {}", stats.high_pending);
-    println!("   Normal pending: {}", stats.normal_pending);
+    println!("   Critical pending: {}", stats.pending(Priority::Critical));
+    println!("   High pending: {}", stats.pending(Priority::High));
+    println!("   Normal pending: {}", stats.pending(Priority::Normal));
     println!("   Avg wait time: {}ms", stats.avg_wait_time_ms);
 
     // Demo 6: Bandwidth Allocation
@@ -161,21 +167,21 @@ async fn main() {
     println!("\nAllocations:");
     println!(
         "   🔴 Critical: {} bps ({:.1} Mbps) - {:.0}%",
-        allocation.critical_bps,
-        allocation.critical_bps as f64 / 1_000_000.0,
-        (allocation.critical_bps as f64 / allocation.total_bps as f64) * 100.0
+        allocation.for_priority(Priority::Critical),
+        allocation.for_priority(Priority::Critical) as f64 / 1_000_000.0,
+        (allocation.for_priority(Priority::Critical) as f64 / allocation.total_bps as f64) * 100.0
     );
     println!(
         "   🟡 High: {} bps ({:.1} Mbps) - {:.0}%",
-        allocation.high_bps,
-        allocation.high_bps as f64 / 1_000_000.0,
-        (allocation.high_bps as f64 / allocation.total_bps as f64) * 100.0
+        allocation.for_priority(Priority::High),
+        allocation.for_priority(Priority::High) as f64 / 1_000_000.0,
+        (allocation.for_priority(Priority::High) as f64 / allocation.total_bps as f64) * 100.0
     );
     println!(
         "   🟢 Normal: {} bps ({:.1} Mbps) - {:.0}%",
-        allocation.normal_bps,
-        allocation.normal_bps as f64 / 1_000_000.0,
-        (allocation.normal_bps as f64 / allocation.total_bps as f64) * 100.0
+        allocation.for_priority(Priority::Normal),
+        allocation.for_priority(Priority::Normal) as f64 / 1_000_000.0,
+        (allocation.for_priority(Priority::Normal) as f64 / allocation.total_bps as f64) * 100.0
     );
 
     // Demo 7: Dynamic Bandwidth Redistribution
@@ -193,18 +199,18 @@ async fn main() {
     println!("Scenario: Only Normal priority chunks present");
     println!(
         "   Critical: {} bps ({:.1} Mbps)",
-        allocation1.critical_bps,
-        allocation1.critical_bps as f64 / 1_000_000.0
+        allocation1.for_priority(Priority::Critical),
+        allocation1.for_priority(Priority::Critical) as f64 / 1_000_000.0
     );
     println!(
         "   High: {} bps ({:.1} Mbps)",
-        allocation1.high_bps,
-        allocation1.high_bps as f64 / 1_000_000.0
+        allocation1.for_priority(Priority::High),
+        allocation1.for_priority(Priority::High) as f64 / 1_000_000.0
     );
     println!(
         "   Normal: {} bps ({:.1} Mbps) - BOOSTED!",
-        allocation1.normal_bps,
-        allocation1.normal_bps as f64 / 1_000_000.0
+        allocation1.for_priority(Priority::Normal),
+        allocation1.for_priority(Priority::Normal) as f64 / 1_000_000.0
     );
 
     // Add critical chunks
@@ -216,18 +222,18 @@ async fn main() {
     println!("\nScenario: Critical chunks added");
     println!(
         "   Critical: {} bps ({:.1} Mbps) - ACTIVE!",
-        allocation2.critical_bps,
-        allocation2.critical_bps as f64 / 1_000_000.0
+        allocation2.for_priority(Priority::Critical),
+        allocation2.for_priority(Priority::Critical) as f64 / 1_000_000.0
     );
     println!(
         "   High: {} bps ({:.1} Mbps)",
-        allocation2.high_bps,
-        allocation2.high_bps as f64 / 1_000_000.0
+        allocation2.for_priority(Priority::High),
+        allocation2.for_priority(Priority::High) as f64 / 1_000_000.0
     );
     println!(
         "   Normal: {} bps ({:.1} Mbps)",
-        allocation2.normal_bps,
-        allocation2.normal_bps as f64 / 1_000_000.0
+        allocation2.for_priority(Priority::Normal),
+        allocation2.for_priority(Priority::Normal) as f64 / 1_000_000.0
     );
 
     // Demo 8: Queue Capacity Management
@@ -12,6 +12,13 @@ fn create_test_manifest(filename: &str, total_chunks: u32) -> FileManifest {
         parity_chunks: total_chunks - (total_chunks as f32 * 0.77) as u32,
         priority: Priority::Normal,
         checksum: [0u8; 32],
+        zero_runs: Vec::new(),
+        attributes: None,
+        checksum_algorithm: Default::default(),
+        erasure_profile: Default::default(),
+        schedule: None,
+        merkle_root: None,
+        compression: None,
     }
 }
 
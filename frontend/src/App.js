@@ -92,15 +92,17 @@ function App() {
   const loadTransfers = useCallback(async () => {
     try {
       const response = await api.listTransfers();
-      if (response.active_transfers && response.active_transfers.length > 0) {
-        const detailsPromises = response.active_transfers.map(id =>
-          api.getProgress(id).catch(() => null)
+      if (response.transfers && response.transfers.length > 0) {
+        const detailsPromises = response.transfers.map(t =>
+          api.getProgress(t.session_id).catch(() => null)
         );
         const details = await Promise.all(detailsPromises);
         setTransfers(details.filter(d => d !== null));
 
         const active = details.filter(d => d && d.status === 'Active').length;
-        const completed = details.filter(d => d && d.status === 'Completed').length;
+        const completed = details.filter(
+          d => d && (d.status === 'Completed' || d.status?.CompletedWithRepairs)
+        ).length;
         const failed = details.filter(d => d && d.status?.Failed).length;
         setStats({ active, completed, failed });
       } else {
@@ -22,6 +22,9 @@ function MetricsPanel({ metricsHistory, currentMetrics }) {
   const quicLost = currentMetrics ? (currentMetrics.quic_lost_packets || 0) : 0;
   const hasQuicData = quicSent > 0;
 
+  // What receivers report seeing at their end
+  const receivers = currentMetrics ? (currentMetrics.receivers || []) : [];
+
   // Gauge calculation for loss rate
   const lossRate = currentMetrics ? currentMetrics.loss_rate * 100 : 0;
   const maxLoss = 50;
@@ -121,6 +124,35 @@ function MetricsPanel({ metricsHistory, currentMetrics }) {
         </div>
       )}
 
+      {/* Receiver-side Stats */}
+      {receivers.map((r) => (
+        <div className="quic-stats-section" key={r.remote_addr}>
+          <span className="chart-title">Receiver {r.remote_addr}</span>
+          <div className="quic-stats-grid">
+            <div className="quic-stat">
+              <span className="quic-stat-value mono">{r.chunks_received}</span>
+              <span className="quic-stat-label">Chunks Received</span>
+            </div>
+            <div className="quic-stat">
+              <span className={`quic-stat-value mono ${r.checksum_failures > 0 ? 'warning-text' : ''}`}>
+                {r.checksum_failures}
+              </span>
+              <span className="quic-stat-label">Checksum Failures</span>
+            </div>
+            <div className="quic-stat">
+              <span className={`quic-stat-value mono ${r.corruption_rate > 0.05 ? 'danger' : ''}`}>
+                {(r.corruption_rate * 100).toFixed(2)}%
+              </span>
+              <span className="quic-stat-label">Corrupted</span>
+            </div>
+            <div className="quic-stat">
+              <span className="quic-stat-value mono">{r.decode_successes}</span>
+              <span className="quic-stat-label">Files Decoded</span>
+            </div>
+          </div>
+        </div>
+      ))}
+
       {/* Loss Rate / Recovery Timeline */}
       {lossData.length > 2 && (
         <div className="chart-section">
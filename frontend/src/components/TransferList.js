@@ -11,20 +11,32 @@ function TransferList({ transfers, onPause, onResume, onCancel }) {
     return Math.round(bytes / Math.pow(k, i) * 100) / 100 + ' ' + sizes[i];
   };
 
+  const isCompleted = (status) =>
+    status === 'Completed' || !!status?.CompletedWithRepairs;
+
+  const isFailed = (status) =>
+    typeof status === 'object' && status !== null && 'Failed' in status;
+
   const getStatusClass = (status) => {
     if (status === 'Active') return 'active';
-    if (status === 'Completed') return 'completed';
-    if (status === 'Paused') return 'paused';
-    if (status?.Failed) return 'failed';
+    if (isCompleted(status)) return 'completed';
+    if (status === 'Paused' || status?.PartiallyDelivered) return 'paused';
+    if (isFailed(status)) return 'failed';
     return '';
   };
 
   const getStatusText = (status) => {
-    if (typeof status === 'object' && status.Failed) return 'Failed';
+    if (isFailed(status)) return 'Failed';
     if (status === 'Active') return 'Transferring';
     if (status === 'Completed') return 'Completed';
+    if (status?.CompletedWithRepairs) {
+      return `Completed (${status.CompletedWithRepairs.repaired_chunks} repaired)`;
+    }
+    if (status?.PartiallyDelivered) {
+      return `Partial (${status.PartiallyDelivered.held_by_relay} via relay)`;
+    }
     if (status === 'Paused') return 'Paused';
-    return status || 'Unknown';
+    return typeof status === 'string' ? status : 'Unknown';
   };
 
   if (!transfers || transfers.length === 0) {
@@ -98,7 +110,7 @@ function TransferList({ transfers, onPause, onResume, onCancel }) {
                   <Play size={14} />
                 </button>
               )}
-              {transfer.status !== 'Completed' && !(typeof transfer.status === 'object' && transfer.status.Failed) && (
+              {!isCompleted(transfer.status) && !isFailed(transfer.status) && (
                 <button
                   className="icon-btn danger"
                   onClick={() => {
@@ -22,6 +22,25 @@ pub enum ApiError {
 impl IntoResponse for ApiError {
     fn into_response(self) -> Response {
         let (status, error_message, error_code) = match self {
+            ApiError::CoordinatorError(
+                e @ (crate::coordinator::CoordinatorError::ProfileNotFound(_)
+                | crate::coordinator::CoordinatorError::BenchmarkNotFound(_)
+                | crate::coordinator::CoordinatorError::NotInCatalog(_)),
+            ) => (StatusCode::NOT_FOUND, e.to_string(), "NOT_FOUND"),
+            ApiError::CoordinatorError(e @ crate::coordinator::CoordinatorError::ShuttingDown) => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                e.to_string(),
+                "SHUTTING_DOWN",
+            ),
+            ApiError::CoordinatorError(e @ crate::coordinator::CoordinatorError::Standby) => {
+                (StatusCode::SERVICE_UNAVAILABLE, e.to_string(), "STANDBY")
+            }
+            ApiError::CoordinatorError(e @ crate::coordinator::CoordinatorError::NotStandby) => {
+                (StatusCode::CONFLICT, e.to_string(), "NOT_STANDBY")
+            }
+            ApiError::CoordinatorError(
+                e @ crate::coordinator::CoordinatorError::Unsupported(_),
+            ) => (StatusCode::NOT_IMPLEMENTED, e.to_string(), "UNSUPPORTED"),
             ApiError::CoordinatorError(e) => {
                 (StatusCode::BAD_REQUEST, e.to_string(), "COORDINATOR_ERROR")
             }
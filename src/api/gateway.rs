@@ -0,0 +1,621 @@
+//! Browser gateway
+//!
+//! Browsers can't speak raw QUIC, so this WebSocket endpoint bridges them
+//! onto the chunk protocol. Uploads are streamed as binary frames and then
+//! handed to the coordinator, so they get the same manifest, FEC and
+//! integrity handling as any other transfer. Downloads are split by the
+//! chunk manager and streamed back chunk by chunk, each frame carrying the
+//! chunk's checksum for in-browser verification.
+//!
+//! An upload is buffered in memory until it ends, so the bytes announced by
+//! every upload in progress are charged against one shared budget, and a
+//! connection carries one upload at a time. Finished uploads never replace
+//! an existing file: a taken name gets a numeric suffix.
+
+use crate::api::types::*;
+use crate::chunk::{Chunk, Priority};
+use crate::coordinator::TransferCoordinator;
+use axum::{
+    extract::{
+        ws::{Message, WebSocket},
+        State, WebSocketUpgrade,
+    },
+    response::Response,
+};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Largest file accepted or served through the gateway
+pub const MAX_GATEWAY_FILE_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Most bytes buffered by uploads in progress, across all connections
+pub const MAX_GATEWAY_BUFFERED_BYTES: u64 = 4 * MAX_GATEWAY_FILE_SIZE;
+
+/// Budget shared by every gateway connection, in bytes
+static UPLOAD_BUDGET: LazyLock<Arc<Semaphore>> =
+    LazyLock::new(|| Arc::new(Semaphore::new(MAX_GATEWAY_BUFFERED_BYTES as usize)));
+
+pub async fn gateway_handler(
+    ws: WebSocketUpgrade,
+    State(coordinator): State<Arc<TransferCoordinator>>,
+) -> Response {
+    ws.on_upgrade(move |socket| handle_gateway(socket, coordinator))
+}
+
+async fn handle_gateway(mut socket: WebSocket, coordinator: Arc<TransferCoordinator>) {
+    let mut session = GatewaySession::new(
+        coordinator,
+        PathBuf::from("./uploads"),
+        UPLOAD_BUDGET.clone(),
+    );
+
+    while let Some(Ok(msg)) = socket.recv().await {
+        let replies = match msg {
+            Message::Text(text) => session.handle_text(&text).await,
+            Message::Binary(data) => session.handle_binary(&data),
+            Message::Close(_) => break,
+            _ => continue,
+        };
+
+        for reply in replies {
+            let frame = match reply {
+                GatewayFrame::Json(msg) => match serde_json::to_string(&msg) {
+                    Ok(json) => Message::Text(json),
+                    Err(_) => continue,
+                },
+                GatewayFrame::Binary(data) => Message::Binary(data),
+            };
+            if socket.send(frame).await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+/// Outgoing frame produced by the gateway
+// Frames are short-lived and mostly JSON, so boxing would only add an
+// allocation
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug)]
+enum GatewayFrame {
+    Json(GatewayServerMessage),
+    Binary(Vec<u8>),
+}
+
+struct PendingUpload {
+    filename: String,
+    expected_size: u64,
+    priority: Priority,
+    receiver_addr: Option<std::net::SocketAddr>,
+    checksum: Option<[u8; 32]>,
+    data: Vec<u8>,
+    /// The announced size, charged to the upload budget until dropped
+    _budget: OwnedSemaphorePermit,
+}
+
+/// Per-connection gateway state, independent of the socket for testability
+struct GatewaySession {
+    coordinator: Arc<TransferCoordinator>,
+    upload_dir: PathBuf,
+    budget: Arc<Semaphore>,
+    pending: Option<PendingUpload>,
+}
+
+impl GatewaySession {
+    fn new(
+        coordinator: Arc<TransferCoordinator>,
+        upload_dir: PathBuf,
+        budget: Arc<Semaphore>,
+    ) -> Self {
+        Self {
+            coordinator,
+            upload_dir,
+            budget,
+            pending: None,
+        }
+    }
+
+    async fn handle_text(&mut self, text: &str) -> Vec<GatewayFrame> {
+        let request: GatewayClientMessage = match serde_json::from_str(text) {
+            Ok(req) => req,
+            Err(e) => return vec![error_frame(format!("Invalid gateway message: {e}"))],
+        };
+
+        match request {
+            GatewayClientMessage::BeginUpload {
+                filename,
+                size,
+                priority,
+                receiver_addr,
+                checksum,
+            } => self.begin_upload(filename, size, priority, receiver_addr, checksum),
+            GatewayClientMessage::EndUpload => self.end_upload().await,
+            GatewayClientMessage::Fetch { filename } => self.fetch(filename).await,
+        }
+    }
+
+    fn handle_binary(&mut self, data: &[u8]) -> Vec<GatewayFrame> {
+        let Some(pending) = self.pending.as_mut() else {
+            return vec![error_frame(
+                "Binary data received before begin_upload".into(),
+            )];
+        };
+
+        if pending.data.len() as u64 + data.len() as u64 > pending.expected_size {
+            self.pending = None;
+            return vec![error_frame("Upload exceeds announced size".into())];
+        }
+
+        pending.data.extend_from_slice(data);
+        vec![]
+    }
+
+    fn begin_upload(
+        &mut self,
+        filename: String,
+        size: u64,
+        priority: Option<Priority>,
+        receiver_addr: Option<String>,
+        checksum: Option<String>,
+    ) -> Vec<GatewayFrame> {
+        if let Some(pending) = &self.pending {
+            return vec![error_frame(format!(
+                "Upload of {} already in progress; end it first",
+                pending.filename
+            ))];
+        }
+        let filename = match sanitize_filename(&filename) {
+            Some(name) => name,
+            None => return vec![error_frame(format!("Invalid filename: {filename}"))],
+        };
+        if size > MAX_GATEWAY_FILE_SIZE {
+            return vec![error_frame(format!(
+                "File too large for gateway ({size} bytes, max {MAX_GATEWAY_FILE_SIZE})"
+            ))];
+        }
+
+        let receiver_addr = match receiver_addr.as_deref().map(str::parse).transpose() {
+            Ok(addr) => addr,
+            Err(e) => return vec![error_frame(format!("Invalid receiver address: {e}"))],
+        };
+        let checksum = match checksum.as_deref().map(blake3::Hash::from_hex).transpose() {
+            Ok(hash) => hash.map(|h| *h.as_bytes()),
+            Err(e) => return vec![error_frame(format!("Invalid checksum: {e}"))],
+        };
+        // Checked against the file size limit above, so it fits a u32
+        let Ok(budget) = self.budget.clone().try_acquire_many_owned(size as u32) else {
+            return vec![error_frame(
+                "Gateway busy: too many uploads in progress, try again later".into(),
+            )];
+        };
+
+        self.pending = Some(PendingUpload {
+            filename: filename.clone(),
+            expected_size: size,
+            priority: priority.unwrap_or(Priority::Normal),
+            receiver_addr,
+            checksum,
+            data: Vec::with_capacity(size as usize),
+            _budget: budget,
+        });
+
+        vec![GatewayFrame::Json(GatewayServerMessage::UploadReady {
+            filename,
+        })]
+    }
+
+    async fn end_upload(&mut self) -> Vec<GatewayFrame> {
+        let Some(upload) = self.pending.take() else {
+            return vec![error_frame("No upload in progress".into())];
+        };
+
+        if upload.data.len() as u64 != upload.expected_size {
+            return vec![error_frame(format!(
+                "Upload incomplete: received {} of {} bytes",
+                upload.data.len(),
+                upload.expected_size
+            ))];
+        }
+        if let Some(expected) = upload.checksum {
+            if *blake3::hash(&upload.data).as_bytes() != expected {
+                return vec![error_frame("Upload checksum mismatch".into())];
+            }
+        }
+
+        let path = match save_upload(&self.upload_dir, &upload.filename, &upload.data).await {
+            Ok(path) => path,
+            Err(e) => return vec![error_frame(format!("Failed to write file: {e}"))],
+        };
+        let file_name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string());
+
+        match self
+            .coordinator
+            .send_file(path.clone(), upload.priority, upload.receiver_addr)
+            .await
+        {
+            Ok(session_id) => vec![GatewayFrame::Json(GatewayServerMessage::TransferStarted(
+                StartTransferResponse {
+                    session_id: session_id.clone(),
+                    message: format!("Gateway upload started transfer {session_id}"),
+                    file_path: Some(path.to_string_lossy().to_string()),
+                    file_name,
+                },
+            ))],
+            Err(e) => vec![error_frame(e.to_string())],
+        }
+    }
+
+    async fn fetch(&mut self, filename: String) -> Vec<GatewayFrame> {
+        let Some(filename) = sanitize_filename(&filename) else {
+            return vec![error_frame(format!("Invalid filename: {filename}"))];
+        };
+        let path = self.upload_dir.join(&filename);
+
+        match tokio::fs::metadata(&path).await {
+            Ok(meta) if meta.len() > MAX_GATEWAY_FILE_SIZE => {
+                return vec![error_frame(format!(
+                    "File too large for gateway ({} bytes, max {MAX_GATEWAY_FILE_SIZE})",
+                    meta.len()
+                ))];
+            }
+            Ok(_) => {}
+            Err(_) => return vec![error_frame(format!("File not found: {filename}"))],
+        }
+
+        let (manifest, chunks) = match self
+            .coordinator
+            .chunk_manager()
+            .split_file(&path, filename, Priority::Normal)
+            .await
+        {
+            Ok(split) => split,
+            Err(e) => return vec![error_frame(e.to_string())],
+        };
+
+        let mut frames = Vec::with_capacity(chunks.len() + 2);
+        frames.push(GatewayFrame::Json(GatewayServerMessage::Manifest(manifest)));
+        for chunk in &chunks {
+            match encode_chunk_frame(chunk) {
+                Ok(frame) => frames.push(GatewayFrame::Binary(frame)),
+                Err(e) => return vec![error_frame(e.to_string())],
+            }
+        }
+        frames.push(GatewayFrame::Json(GatewayServerMessage::FetchComplete {
+            chunks_sent: chunks.len() as u32,
+        }));
+        frames
+    }
+}
+
+/// Write an upload into `dir` without replacing an existing file
+///
+/// The data goes to a temporary file, which is then renamed onto the first
+/// free name of `filename`, `stem-1.ext`, `stem-2.ext`, and so on. Each
+/// name is claimed with `create_new`, so concurrent uploads can't both
+/// take it.
+async fn save_upload(dir: &Path, filename: &str, data: &[u8]) -> std::io::Result<PathBuf> {
+    tokio::fs::create_dir_all(dir).await?;
+    let tmp = dir.join(format!(".{filename}.{}.part", uuid::Uuid::new_v4()));
+    tokio::fs::write(&tmp, data).await?;
+
+    let saved = match claim_name(dir, filename).await {
+        Ok(path) => tokio::fs::rename(&tmp, &path).await.map(|()| path),
+        Err(e) => Err(e),
+    };
+    if saved.is_err() {
+        let _ = tokio::fs::remove_file(&tmp).await;
+    }
+    saved
+}
+
+/// Create an empty file at the first free variant of `filename` in `dir`
+async fn claim_name(dir: &Path, filename: &str) -> std::io::Result<PathBuf> {
+    let name = Path::new(filename);
+    let stem = name.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = name.extension().map(|ext| ext.to_string_lossy());
+    let mut suffix = 0u32;
+    loop {
+        let candidate = match (suffix, &extension) {
+            (0, _) => filename.to_string(),
+            (n, Some(ext)) => format!("{stem}-{n}.{ext}"),
+            (n, None) => format!("{stem}-{n}"),
+        };
+        let path = dir.join(candidate);
+        match tokio::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .await
+        {
+            Ok(_) => return Ok(path),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => suffix += 1,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Encode a chunk as `[u32 BE header length][JSON header][chunk data]`
+///
+/// JSON keeps the header readable from browser JavaScript without a bincode
+/// decoder.
+fn encode_chunk_frame(chunk: &Chunk) -> serde_json::Result<Vec<u8>> {
+    let header = GatewayChunkHeader {
+        sequence_number: chunk.metadata.sequence_number,
+        total_chunks: chunk.metadata.total_chunks,
+        is_parity: chunk.metadata.is_parity,
+        data_size: chunk.metadata.data_size,
+        checksum: hex::encode(chunk.metadata.checksum),
+        checksum_algorithm: chunk.metadata.checksum_algorithm,
+    };
+    let header_bytes = serde_json::to_vec(&header)?;
+
+    let mut frame = Vec::with_capacity(4 + header_bytes.len() + chunk.data.len());
+    frame.extend_from_slice(&(header_bytes.len() as u32).to_be_bytes());
+    frame.extend_from_slice(&header_bytes);
+    frame.extend_from_slice(&chunk.data);
+    Ok(frame)
+}
+
+/// Strip directories so browser clients can only touch the uploads folder
+fn sanitize_filename(name: &str) -> Option<String> {
+    let name = std::path::Path::new(name).file_name()?.to_string_lossy();
+    if name.is_empty() || name == "." || name == ".." {
+        None
+    } else {
+        Some(name.to_string())
+    }
+}
+
+fn error_frame(error: String) -> GatewayFrame {
+    GatewayFrame::Json(GatewayServerMessage::Error(ErrorResponse {
+        error,
+        code: "GATEWAY_ERROR".to_string(),
+    }))
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+    use super::*;
+    use crate::chunk::ChunkManager;
+    use crate::network::{ConnectionConfig, QuicTransport};
+    use crate::priority::PriorityQueue;
+    use crate::session::SessionStore;
+    use tempfile::TempDir;
+
+    async fn create_session(dir: &TempDir) -> GatewaySession {
+        let budget = Arc::new(Semaphore::new(MAX_GATEWAY_BUFFERED_BYTES as usize));
+        create_session_with_budget(dir, budget).await
+    }
+
+    async fn create_session_with_budget(dir: &TempDir, budget: Arc<Semaphore>) -> GatewaySession {
+        let chunk_manager = ChunkManager::new(256 * 1024, 10, 3).unwrap();
+        let transport = QuicTransport::new(ConnectionConfig::default())
+            .await
+            .unwrap();
+        let queue = PriorityQueue::new(1_000_000);
+        let session_store = SessionStore::new_in_memory().await.unwrap();
+        let coordinator = TransferCoordinator::new(
+            chunk_manager,
+            transport,
+            queue,
+            session_store,
+        );
+
+        GatewaySession::new(Arc::new(coordinator), dir.path().to_path_buf(), budget)
+    }
+
+    fn json_of(frame: &GatewayFrame) -> &GatewayServerMessage {
+        match frame {
+            GatewayFrame::Json(msg) => msg,
+            GatewayFrame::Binary(_) => panic!("expected JSON frame"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_upload_starts_transfer() {
+        let dir = TempDir::new().unwrap();
+        let mut session = create_session(&dir).await;
+        let data = vec![7u8; 4096];
+        let checksum = blake3::hash(&data).to_hex().to_string();
+
+        let begin = format!(
+            r#"{{"type":"begin_upload","data":{{"filename":"../evil/report.bin","size":4096,"checksum":"{checksum}"}}}}"#
+        );
+        let replies = session.handle_text(&begin).await;
+        assert!(matches!(
+            json_of(&replies[0]),
+            GatewayServerMessage::UploadReady { filename } if filename == "report.bin"
+        ));
+
+        assert!(session.handle_binary(&data[..1000]).is_empty());
+        assert!(session.handle_binary(&data[1000..]).is_empty());
+
+        let replies = session.handle_text(r#"{"type":"end_upload"}"#).await;
+        assert!(matches!(
+            json_of(&replies[0]),
+            GatewayServerMessage::TransferStarted(_)
+        ));
+        assert!(dir.path().join("report.bin").exists());
+        assert_eq!(session.coordinator.list_active().len(), 1);
+    }
+
+    async fn upload(
+        session: &mut GatewaySession,
+        filename: &str,
+        data: &[u8],
+    ) -> Vec<GatewayFrame> {
+        let begin = format!(
+            r#"{{"type":"begin_upload","data":{{"filename":"{filename}","size":{}}}}}"#,
+            data.len()
+        );
+        session.handle_text(&begin).await;
+        session.handle_binary(data);
+        session.handle_text(r#"{"type":"end_upload"}"#).await
+    }
+
+    #[tokio::test]
+    async fn test_upload_keeps_existing_files() {
+        let dir = TempDir::new().unwrap();
+        let mut session = create_session(&dir).await;
+        tokio::fs::write(dir.path().join("report.bin"), b"original")
+            .await
+            .unwrap();
+
+        let replies = upload(&mut session, "report.bin", b"first").await;
+        let GatewayServerMessage::TransferStarted(started) = json_of(&replies[0]) else {
+            panic!("expected transfer started");
+        };
+        assert_eq!(started.file_name.as_deref(), Some("report-1.bin"));
+        upload(&mut session, "report.bin", b"second").await;
+
+        let read = |name: &str| std::fs::read(dir.path().join(name)).unwrap();
+        assert_eq!(read("report.bin"), b"original");
+        assert_eq!(read("report-1.bin"), b"first");
+        assert_eq!(read("report-2.bin"), b"second");
+        // No temporary files are left behind
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_one_upload_at_a_time() {
+        let dir = TempDir::new().unwrap();
+        let mut session = create_session(&dir).await;
+
+        session
+            .handle_text(r#"{"type":"begin_upload","data":{"filename":"a.bin","size":3}}"#)
+            .await;
+        let replies = session
+            .handle_text(r#"{"type":"begin_upload","data":{"filename":"b.bin","size":3}}"#)
+            .await;
+        assert!(matches!(
+            json_of(&replies[0]),
+            GatewayServerMessage::Error(e) if e.error.contains("a.bin")
+        ));
+
+        // The first upload carries on
+        assert!(session.handle_binary(b"abc").is_empty());
+        let replies = session.handle_text(r#"{"type":"end_upload"}"#).await;
+        assert!(matches!(
+            json_of(&replies[0]),
+            GatewayServerMessage::TransferStarted(_)
+        ));
+        assert!(!dir.path().join("b.bin").exists());
+    }
+
+    #[tokio::test]
+    async fn test_uploads_share_a_buffer_budget() {
+        let dir = TempDir::new().unwrap();
+        let budget = Arc::new(Semaphore::new(10));
+        let mut first = create_session_with_budget(&dir, budget.clone()).await;
+        let mut second = create_session_with_budget(&dir, budget).await;
+
+        let begin = |size: u32| {
+            format!(r#"{{"type":"begin_upload","data":{{"filename":"a.bin","size":{size}}}}}"#)
+        };
+        let replies = first.handle_text(&begin(6)).await;
+        assert!(matches!(
+            json_of(&replies[0]),
+            GatewayServerMessage::UploadReady { .. }
+        ));
+        let replies = second.handle_text(&begin(6)).await;
+        assert!(matches!(
+            json_of(&replies[0]),
+            GatewayServerMessage::Error(e) if e.error.contains("busy")
+        ));
+
+        // Ending the first upload returns its share
+        first.handle_binary(b"abcdef");
+        first.handle_text(r#"{"type":"end_upload"}"#).await;
+        let replies = second.handle_text(&begin(6)).await;
+        assert!(matches!(
+            json_of(&replies[0]),
+            GatewayServerMessage::UploadReady { .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_upload_checksum_mismatch() {
+        let dir = TempDir::new().unwrap();
+        let mut session = create_session(&dir).await;
+        let checksum = blake3::hash(b"something else").to_hex().to_string();
+
+        let begin = format!(
+            r#"{{"type":"begin_upload","data":{{"filename":"a.bin","size":3,"checksum":"{checksum}"}}}}"#
+        );
+        session.handle_text(&begin).await;
+        session.handle_binary(b"abc");
+
+        let replies = session.handle_text(r#"{"type":"end_upload"}"#).await;
+        assert!(matches!(
+            json_of(&replies[0]),
+            GatewayServerMessage::Error(_)
+        ));
+        assert!(!dir.path().join("a.bin").exists());
+    }
+
+    #[tokio::test]
+    async fn test_upload_rejects_oversized_data() {
+        let dir = TempDir::new().unwrap();
+        let mut session = create_session(&dir).await;
+
+        session
+            .handle_text(r#"{"type":"begin_upload","data":{"filename":"a.bin","size":2}}"#)
+            .await;
+        let replies = session.handle_binary(b"abc");
+        assert!(matches!(
+            json_of(&replies[0]),
+            GatewayServerMessage::Error(_)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_streams_verifiable_chunks() {
+        let dir = TempDir::new().unwrap();
+        let mut session = create_session(&dir).await;
+        tokio::fs::write(dir.path().join("data.bin"), vec![42u8; 10_000])
+            .await
+            .unwrap();
+
+        let replies = session
+            .handle_text(r#"{"type":"fetch","data":{"filename":"data.bin"}}"#)
+            .await;
+
+        let GatewayServerMessage::Manifest(manifest) = json_of(&replies[0]) else {
+            panic!("expected manifest first");
+        };
+        let binary_frames: Vec<&Vec<u8>> = replies
+            .iter()
+            .filter_map(|f| match f {
+                GatewayFrame::Binary(b) => Some(b),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(binary_frames.len() as u32, manifest.total_chunks);
+
+        // Decode one frame the way a browser would
+        let frame = binary_frames[0];
+        let header_len = u32::from_be_bytes(frame[..4].try_into().unwrap()) as usize;
+        let header: GatewayChunkHeader = serde_json::from_slice(&frame[4..4 + header_len]).unwrap();
+        let payload = &frame[4 + header_len..];
+        assert_eq!(
+            hex::encode(header.checksum_algorithm.digest(payload)),
+            header.checksum
+        );
+
+        assert!(matches!(
+            json_of(replies.last().unwrap()),
+            GatewayServerMessage::FetchComplete { chunks_sent } if *chunks_sent == manifest.total_chunks
+        ));
+    }
+
+    #[test]
+    fn test_sanitize_filename() {
+        assert_eq!(sanitize_filename("a.txt").as_deref(), Some("a.txt"));
+        assert_eq!(sanitize_filename("/etc/passwd").as_deref(), Some("passwd"));
+        assert_eq!(sanitize_filename(".."), None);
+        assert_eq!(sanitize_filename(""), None);
+    }
+}
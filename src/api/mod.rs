@@ -1,12 +1,14 @@
 mod error;
+mod gateway;
 mod rest;
 mod types;
 mod websocket;
 
 pub use error::{ApiError, ApiResult};
+pub use gateway::{gateway_handler, MAX_GATEWAY_BUFFERED_BYTES, MAX_GATEWAY_FILE_SIZE};
 pub use rest::RestApi;
 pub use types::*;
-pub use websocket::websocket_handler;
+pub use websocket::{websocket_handler, DEFAULT_ROOM_BACKFILL};
 
 use crate::coordinator::TransferCoordinator;
 use axum::{extract::DefaultBodyLimit, routing::get, Router};
@@ -26,6 +28,7 @@ pub fn create_api_server(coordinator: TransferCoordinator) -> Router {
 
     let ws_router = Router::new()
         .route("/ws", get(websocket_handler))
+        .route("/ws/gateway", get(gateway_handler))
         .with_state(coordinator_arc);
 
     Router::new()
@@ -35,24 +38,22 @@ pub fn create_api_server(coordinator: TransferCoordinator) -> Router {
         .layer(cors)
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "sqlite"))]
 mod tests {
     use super::*;
     use crate::chunk::ChunkManager;
-    use crate::integrity::IntegrityVerifier;
     use crate::network::{ConnectionConfig, QuicTransport};
     use crate::priority::PriorityQueue;
     use crate::session::SessionStore;
 
     async fn create_test_coordinator() -> TransferCoordinator {
         let chunk_manager = ChunkManager::new(256 * 1024, 10, 3).unwrap();
-        let verifier = IntegrityVerifier;
         let config = ConnectionConfig::default();
         let transport = QuicTransport::new(config).await.unwrap();
         let queue = PriorityQueue::new(1_000_000);
         let session_store = SessionStore::new_in_memory().await.unwrap();
 
-        TransferCoordinator::new(chunk_manager, verifier, transport, queue, session_store)
+        TransferCoordinator::new(chunk_manager, transport, queue, session_store)
     }
 
     #[tokio::test]
@@ -1,8 +1,22 @@
 use crate::api::error::{ApiError, ApiResult};
 use crate::api::types::*;
-use crate::coordinator::TransferCoordinator;
+use crate::chunk::{Priority, TransferPlan};
+use crate::coordinator::{
+    ChunkingDefaults, CoordinatorError, ErasureDefaults, HealthReport, ResumeToken,
+    TransferCoordinator, VerifyStatus, VerifyTarget, DEFAULT_VERIFY_CONCURRENCY,
+    MAX_VERIFY_CONCURRENCY, MAX_VERIFY_TARGETS, SAMPLE_INTERVAL,
+};
+use crate::failover::{FailoverStatus, TakeoverReport};
+use crate::logging::{self, LogError, LogHandle};
+use crate::metrics;
+use crate::network::{CaptureSettings, DebugCapture};
+use crate::priority::QueueSnapshot;
+use crate::session::{
+    validate_tags, BenchmarkRecord, SessionQuery, SessionSearch, SessionStatus, TransferProfile,
+    TransferTags,
+};
 use axum::{
-    extract::{Multipart, Path, State},
+    extract::{Multipart, Path, Query, State},
     http::StatusCode,
     routing::{get, post},
     Json, Router,
@@ -23,32 +37,126 @@ impl RestApi {
     }
 
     pub fn router(&self) -> Router {
-        Router::new()
+        let router = Router::new()
             .route("/health", get(health_check))
+            .route("/health/live", get(liveness))
+            .route("/health/ready", get(readiness))
             .route("/api/v1/transfers", post(start_transfer))
             .route("/api/v1/upload", post(upload_and_transfer))
             .route("/api/v1/transfers", get(list_transfers))
+            .route("/api/v1/transfers/pending", get(list_pending_transfers))
+            .route("/api/v1/transfers/resume-token", post(import_resume_token))
             .route("/api/v1/transfers/:id", get(get_transfer))
             .route("/api/v1/transfers/:id/pause", post(pause_transfer))
             .route("/api/v1/transfers/:id/resume", post(resume_transfer))
             .route("/api/v1/transfers/:id/cancel", post(cancel_transfer))
             .route("/api/v1/transfers/:id/progress", get(get_progress))
+            .route("/api/v1/transfers/:id/timeseries", get(get_timeseries))
+            .route(
+                "/api/v1/transfers/:id/resume-token",
+                get(export_resume_token),
+            )
+            .route("/api/v1/sessions/search", get(search_sessions))
+            // Files receivers may pull
+            .route("/api/v1/catalog", get(get_catalog))
+            .route("/api/v1/catalog/request", post(request_catalog_file))
+            // Named transfer settings
+            .route("/api/v1/profiles", get(list_profiles).post(create_profile))
+            .route(
+                "/api/v1/profiles/:name",
+                get(get_profile).put(save_profile).delete(delete_profile),
+            )
+            // Benchmark reports for the dashboard's history
+            .route(
+                "/api/v1/benchmarks",
+                get(list_benchmarks).post(upload_benchmark),
+            )
+            .route(
+                "/api/v1/benchmarks/:id",
+                get(get_benchmark).delete(delete_benchmark),
+            )
+            // Defaults for new transfers, changeable at runtime
+            .route("/api/v1/config", get(get_effective_config))
+            .route(
+                "/api/v1/config/erasure",
+                get(get_erasure_defaults).put(update_erasure_defaults),
+            )
+            .route(
+                "/api/v1/config/chunking",
+                get(get_chunking_defaults).put(update_chunking_defaults),
+            )
+            // Active/standby pair
+            .route("/api/v1/failover", get(get_failover_status))
+            .route("/api/v1/failover/promote", post(promote_standby))
+            // Log levels of this process
+            .route("/api/v1/logging", get(get_logging).put(update_log_filter))
+            // TLS key and connection event capture for debugging
+            .route(
+                "/api/v1/network/capture",
+                get(get_capture).put(update_capture).delete(clear_capture),
+            )
+            // Open QUIC connections with live path stats
+            .route("/api/v1/network/connections", get(list_connections))
             // Metric endpoints
             .route("/api/v1/metrics/erasure", get(get_erasure_metrics))
             .route("/api/v1/metrics/network", get(get_network_metrics))
             .route("/api/v1/metrics/queue", get(get_queue_metrics))
+            .route("/api/v1/queue", get(inspect_queue))
+            .route("/api/v1/metrics/latency", get(get_latency_metrics))
+            .route("/api/v1/metrics/storage", get(get_storage_metrics))
             .route("/api/v1/metrics/summary", get(get_metrics_summary))
             // Simulation endpoints
             .route("/api/v1/simulate/packet-loss", post(simulate_packet_loss))
             .route("/api/v1/simulate/comparison", post(simulate_comparison))
+            .route("/api/v1/simulate/mesh", post(simulate_mesh))
+            .route("/api/v1/probe", post(probe_link))
+            .route("/api/v1/plan", get(plan_transfer))
+            .route("/api/v1/verify", post(verify_files))
             // Uploads listing
-            .route("/api/v1/uploads", get(list_uploads))
-            .with_state(self.coordinator.clone())
+            .route("/api/v1/uploads", get(list_uploads));
+
+        // Chaos testing only
+        #[cfg(feature = "fault-injection")]
+        let router = router.route(
+            "/api/v1/internal/faults",
+            get(get_faults).put(set_faults).delete(clear_faults),
+        );
+
+        router.with_state(self.coordinator.clone())
+    }
+}
+
+/// Plain-text readiness, for load balancers that only look at the status
+async fn health_check(
+    State(coordinator): State<Arc<TransferCoordinator>>,
+) -> (StatusCode, &'static str) {
+    if coordinator.stats().readiness().await.is_healthy() {
+        (StatusCode::OK, "OK")
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, "UNAVAILABLE")
     }
 }
 
-async fn health_check() -> &'static str {
-    "OK"
+async fn liveness(
+    State(coordinator): State<Arc<TransferCoordinator>>,
+) -> (StatusCode, Json<HealthReport>) {
+    health_response(coordinator.stats().liveness())
+}
+
+async fn readiness(
+    State(coordinator): State<Arc<TransferCoordinator>>,
+) -> (StatusCode, Json<HealthReport>) {
+    health_response(coordinator.stats().readiness().await)
+}
+
+/// 200 unless a component is down, then 503; the report says which
+fn health_response(report: HealthReport) -> (StatusCode, Json<HealthReport>) {
+    let status = if report.is_healthy() {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(report))
 }
 
 async fn upload_and_transfer(
@@ -56,8 +164,10 @@ async fn upload_and_transfer(
     mut multipart: Multipart,
 ) -> ApiResult<(StatusCode, Json<StartTransferResponse>)> {
     let mut file_path: Option<std::path::PathBuf> = None;
-    let mut priority = crate::chunk::Priority::Normal;
+    let mut priority: Option<crate::chunk::Priority> = None;
     let mut receiver_addr: Option<std::net::SocketAddr> = None;
+    let mut options = crate::session::TransferOptions::default();
+    let mut profile: Option<String> = None;
 
     // Create uploads directory if it doesn't exist
     let upload_dir = std::path::PathBuf::from("./uploads");
@@ -99,12 +209,12 @@ async fn upload_and_transfer(
                 .await
                 .map_err(|e| ApiError::InvalidRequest(format!("Failed to read priority: {e}")))?;
 
-            priority = match priority_str.as_str() {
-                "Critical" => crate::chunk::Priority::Critical,
-                "High" => crate::chunk::Priority::High,
-                "Normal" => crate::chunk::Priority::Normal,
-                _ => crate::chunk::Priority::Normal,
-            };
+            priority = Some(match priority_str.as_str() {
+                "Critical" => Priority::Critical,
+                "High" => Priority::High,
+                "Normal" => Priority::Normal,
+                level => level.parse().map_or(Priority::Normal, Priority::from_level),
+            });
         } else if name == "receiver_addr" {
             let addr_str = field.text().await.map_err(|e| {
                 ApiError::InvalidRequest(format!("Failed to read receiver address: {e}"))
@@ -114,6 +224,35 @@ async fn upload_and_transfer(
                 Some(addr_str.parse().map_err(|e| {
                     ApiError::InvalidRequest(format!("Invalid receiver address: {e}"))
                 })?);
+        } else if name == "local_bind_addr" {
+            let addr_str = field.text().await.map_err(|e| {
+                ApiError::InvalidRequest(format!("Failed to read local bind address: {e}"))
+            })?;
+
+            options.local_bind_addr = Some(addr_str.parse().map_err(|e| {
+                ApiError::InvalidRequest(format!("Invalid local bind address: {e}"))
+            })?);
+        } else if name == "allow_duplicate" {
+            let value = field.text().await.map_err(|e| {
+                ApiError::InvalidRequest(format!("Failed to read allow_duplicate: {e}"))
+            })?;
+
+            options.allow_duplicate = value
+                .parse()
+                .map_err(|e| ApiError::InvalidRequest(format!("Invalid allow_duplicate: {e}")))?;
+        } else if name == "tags" {
+            let value = field
+                .text()
+                .await
+                .map_err(|e| ApiError::InvalidRequest(format!("Failed to read tags: {e}")))?;
+
+            options.tags = serde_json::from_str(&value)
+                .map_err(|e| ApiError::InvalidRequest(format!("Invalid tags: {e}")))?;
+        } else if name == "profile" {
+            profile =
+                Some(field.text().await.map_err(|e| {
+                    ApiError::InvalidRequest(format!("Failed to read profile: {e}"))
+                })?);
         }
     }
 
@@ -127,10 +266,15 @@ async fn upload_and_transfer(
     let file_path_val =
         file_path.ok_or_else(|| ApiError::InvalidRequest("No file uploaded".to_string()))?;
 
-    let session_id = coordinator
-        .send_file(file_path_val.clone(), priority, receiver_addr)
-        .await
-        .map_err(ApiError::CoordinatorError)?;
+    let session_id = send_file(
+        &coordinator,
+        file_path_val,
+        profile.as_deref(),
+        priority,
+        receiver_addr,
+        options,
+    )
+    .await?;
 
     Ok((
         StatusCode::CREATED,
@@ -167,10 +311,27 @@ async fn start_transfer(
         None
     };
 
-    let session_id = coordinator
-        .send_file(file_path, req.priority, receiver_addr)
-        .await
-        .map_err(ApiError::CoordinatorError)?;
+    let options = crate::session::TransferOptions {
+        local_bind_addr: req
+            .local_bind_addr
+            .as_deref()
+            .map(str::parse)
+            .transpose()
+            .map_err(|e| ApiError::InvalidRequest(format!("Invalid local bind address: {e}")))?,
+        allow_duplicate: req.allow_duplicate,
+        tags: req.tags,
+        ..Default::default()
+    };
+
+    let session_id = send_file(
+        &coordinator,
+        file_path,
+        req.profile.as_deref(),
+        req.priority,
+        receiver_addr,
+        options,
+    )
+    .await?;
 
     Ok((
         StatusCode::CREATED,
@@ -185,16 +346,210 @@ async fn start_transfer(
     ))
 }
 
+async fn get_catalog(
+    State(coordinator): State<Arc<TransferCoordinator>>,
+) -> ApiResult<Json<CatalogResponse>> {
+    Ok(Json(CatalogResponse {
+        files: coordinator
+            .catalog()
+            .await
+            .map_err(ApiError::CoordinatorError)?,
+    }))
+}
+
+/// Push a catalog file to the receiver asking for it
+async fn request_catalog_file(
+    State(coordinator): State<Arc<TransferCoordinator>>,
+    Json(req): Json<CatalogTransferRequest>,
+) -> ApiResult<(StatusCode, Json<StartTransferResponse>)> {
+    let receiver_addr = req
+        .receiver_addr
+        .parse()
+        .map_err(|e| ApiError::InvalidRequest(format!("Invalid receiver address: {e}")))?;
+    let file_path = coordinator
+        .catalog_file(&req.id)
+        .await
+        .map_err(ApiError::CoordinatorError)?;
+
+    let session_id = send_file(
+        &coordinator,
+        file_path,
+        req.profile.as_deref(),
+        req.priority,
+        Some(receiver_addr),
+        Default::default(),
+    )
+    .await?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(StartTransferResponse {
+            session_id: session_id.clone(),
+            message: format!(
+                "Transfer of {} started with session ID: {session_id}",
+                req.id
+            ),
+            // Where the file lives on this host is none of the receiver's business
+            file_path: None,
+            file_name: req.id.rsplit('/').next().map(str::to_string),
+        }),
+    ))
+}
+
+/// Start a transfer, filling in what the request leaves unset from
+/// `profile` when one is named
+async fn send_file(
+    coordinator: &TransferCoordinator,
+    file_path: std::path::PathBuf,
+    profile: Option<&str>,
+    priority: Option<crate::chunk::Priority>,
+    receiver_addr: Option<std::net::SocketAddr>,
+    options: crate::session::TransferOptions,
+) -> ApiResult<String> {
+    let started = match profile {
+        Some(name) => {
+            coordinator
+                .send_file_with_profile(file_path, name, priority, receiver_addr, options)
+                .await
+        }
+        None => {
+            coordinator
+                .send_file_with_options(
+                    file_path,
+                    priority.unwrap_or(crate::chunk::Priority::Normal),
+                    receiver_addr,
+                    options,
+                )
+                .await
+        }
+    };
+    Ok(started?)
+}
+
+/// Default page size for transfer listings
+const DEFAULT_LIST_LIMIT: u32 = 50;
+/// Largest page size a client may request
+const MAX_LIST_LIMIT: u32 = 500;
+
+/// Tags from a `key:value,key:value` listing filter
+fn parse_tag_filter(filter: &str) -> ApiResult<TransferTags> {
+    let tags = filter
+        .split(',')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            pair.split_once(':')
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .ok_or_else(|| {
+                    ApiError::InvalidRequest(format!("Tag filter {pair:?} is not key:value"))
+                })
+        })
+        .collect::<ApiResult<TransferTags>>()?;
+    validate_tags(&tags)
+        .map_err(|e| ApiError::InvalidRequest(format!("Invalid tag filter: {e}")))?;
+    Ok(tags)
+}
+
 async fn list_transfers(
     State(coordinator): State<Arc<TransferCoordinator>>,
-) -> Json<ListTransfersResponse> {
-    // Return recent transfers (includes active, completed, and failed)
-    let active_transfers = coordinator.list_recent();
-    let count = active_transfers.len();
+    Query(params): Query<ListTransfersQuery>,
+) -> ApiResult<Json<ListTransfersResponse>> {
+    let status = params
+        .status
+        .as_deref()
+        .map(|s| {
+            SessionStatus::from_kind(s)
+                .ok_or_else(|| ApiError::InvalidRequest(format!("Unknown status filter: {s}")))
+        })
+        .transpose()?;
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_LIST_LIMIT)
+        .clamp(1, MAX_LIST_LIMIT);
+    let offset = params.offset.unwrap_or(0);
+    let tags = params
+        .tags
+        .as_deref()
+        .map(parse_tag_filter)
+        .transpose()?
+        .unwrap_or_default();
+
+    let page = coordinator
+        .list_sessions(&SessionQuery {
+            status,
+            file_id: None,
+            tags,
+            sort: params.sort,
+            limit: Some(limit),
+            offset,
+        })
+        .await?;
+
+    let transfers: Vec<TransferSummary> = page.sessions.iter().map(Into::into).collect();
+    Ok(Json(ListTransfersResponse {
+        count: transfers.len(),
+        transfers,
+        total: page.total,
+        limit,
+        offset,
+    }))
+}
+
+async fn search_sessions(
+    State(coordinator): State<Arc<TransferCoordinator>>,
+    Query(params): Query<SearchSessionsQuery>,
+) -> ApiResult<Json<SearchSessionsResponse>> {
+    let filename = params.filename.filter(|name| !name.is_empty());
+    let checksum = params
+        .checksum
+        .as_deref()
+        .map(|hex_digits| {
+            let mut checksum = [0u8; 32];
+            hex::decode_to_slice(hex_digits.trim(), &mut checksum).map_err(|_| {
+                ApiError::InvalidRequest(format!(
+                    "checksum must be 64 hex digits, got {hex_digits:?}"
+                ))
+            })?;
+            Ok::<_, ApiError>(checksum)
+        })
+        .transpose()?;
+    if filename.is_none() && checksum.is_none() {
+        return Err(ApiError::InvalidRequest(
+            "Search by filename, checksum or both".to_string(),
+        ));
+    }
+
+    let sessions = coordinator
+        .search_sessions(&SessionSearch {
+            filename,
+            checksum,
+            limit: Some(
+                params
+                    .limit
+                    .unwrap_or(DEFAULT_LIST_LIMIT)
+                    .clamp(1, MAX_LIST_LIMIT),
+            ),
+        })
+        .await?;
+    let sessions: Vec<SessionMatch> = sessions.iter().map(Into::into).collect();
+    Ok(Json(SearchSessionsResponse {
+        count: sessions.len(),
+        sessions,
+    }))
+}
 
-    Json(ListTransfersResponse {
-        active_transfers,
-        count,
+async fn list_pending_transfers(
+    State(coordinator): State<Arc<TransferCoordinator>>,
+) -> Json<PendingTransfersResponse> {
+    let pending = coordinator
+        .pending_transfers()
+        .iter()
+        .enumerate()
+        .map(|(position, p)| PendingTransferSummary::new(position, p))
+        .collect();
+    Json(PendingTransfersResponse {
+        pending,
+        active: coordinator.list_active().len(),
+        max_concurrent_transfers: coordinator.max_concurrent_transfers(),
     })
 }
 
@@ -203,25 +558,18 @@ async fn get_transfer(
     Path(session_id): Path<String>,
 ) -> ApiResult<Json<TransferStateResponse>> {
     let state = coordinator
-        .get_state(&session_id)
+        .get_transfer_state(&session_id)
+        .await
+        .map_err(ApiError::CoordinatorError)?
         .ok_or_else(|| ApiError::NotFound(format!("Transfer not found: {session_id}")))?;
 
-    let state_str = match state {
-        crate::coordinator::TransferState::Idle => "Idle",
-        crate::coordinator::TransferState::Preparing => "Preparing",
-        crate::coordinator::TransferState::Transferring { .. } => "Transferring",
-        crate::coordinator::TransferState::Paused { .. } => "Paused",
-        crate::coordinator::TransferState::Completing => "Completing",
-        crate::coordinator::TransferState::Completed => "Completed",
-        crate::coordinator::TransferState::Failed { .. } => "Failed",
-    };
-
     Ok(Json(TransferStateResponse {
         session_id,
-        state: state_str.to_string(),
+        state: state.name().to_string(),
         is_active: state.is_active(),
         is_paused: state.is_paused(),
         is_terminal: state.is_terminal(),
+        is_degraded: state.is_degraded(),
     }))
 }
 
@@ -276,18 +624,165 @@ async fn get_progress(
         .await
         .map_err(ApiError::CoordinatorError)?;
 
-    Ok(Json(TransferProgressResponse {
-        session_id: progress.session_id,
-        status: progress.status,
-        progress_percent: progress.progress_percent,
-        completed_chunks: progress.completed_chunks,
-        total_chunks: progress.total_chunks,
-        bytes_transferred: progress.bytes_transferred,
-        total_bytes: progress.total_bytes,
-        current_speed_bps: progress.current_speed_bps,
+    Ok(Json(progress.into()))
+}
+
+async fn get_timeseries(
+    State(coordinator): State<Arc<TransferCoordinator>>,
+    Path(session_id): Path<String>,
+) -> ApiResult<Json<TimeseriesResponse>> {
+    let samples = coordinator
+        .get_timeseries(&session_id)
+        .await
+        .map_err(ApiError::CoordinatorError)?;
+
+    Ok(Json(TimeseriesResponse {
+        session_id,
+        interval_ms: SAMPLE_INTERVAL.as_millis() as u64,
+        samples,
+    }))
+}
+
+async fn list_profiles(
+    State(coordinator): State<Arc<TransferCoordinator>>,
+) -> ApiResult<Json<ListProfilesResponse>> {
+    Ok(Json(ListProfilesResponse {
+        profiles: coordinator.list_profiles().await?,
+    }))
+}
+
+async fn create_profile(
+    State(coordinator): State<Arc<TransferCoordinator>>,
+    Json(profile): Json<TransferProfile>,
+) -> ApiResult<(StatusCode, Json<TransferProfile>)> {
+    if coordinator.profile(&profile.name).await.is_ok() {
+        return Err(ApiError::InvalidRequest(format!(
+            "Profile {} already exists",
+            profile.name
+        )));
+    }
+    Ok((
+        StatusCode::CREATED,
+        Json(coordinator.save_profile(profile).await?),
+    ))
+}
+
+async fn get_profile(
+    State(coordinator): State<Arc<TransferCoordinator>>,
+    Path(name): Path<String>,
+) -> ApiResult<Json<TransferProfile>> {
+    Ok(Json(coordinator.profile(&name).await?))
+}
+
+/// Create or replace the profile named in the path
+async fn save_profile(
+    State(coordinator): State<Arc<TransferCoordinator>>,
+    Path(name): Path<String>,
+    Json(mut profile): Json<TransferProfile>,
+) -> ApiResult<Json<TransferProfile>> {
+    profile.name = name;
+    Ok(Json(coordinator.save_profile(profile).await?))
+}
+
+async fn delete_profile(
+    State(coordinator): State<Arc<TransferCoordinator>>,
+    Path(name): Path<String>,
+) -> ApiResult<Json<SuccessResponse>> {
+    coordinator.delete_profile(&name).await?;
+    Ok(Json(SuccessResponse {
+        message: format!("Profile {name} deleted"),
+    }))
+}
+
+async fn list_benchmarks(
+    State(coordinator): State<Arc<TransferCoordinator>>,
+    Query(params): Query<ListBenchmarksQuery>,
+) -> ApiResult<Json<ListBenchmarksResponse>> {
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_LIST_LIMIT)
+        .clamp(1, MAX_LIST_LIMIT);
+    let benchmarks = coordinator.list_benchmarks(limit).await?;
+    Ok(Json(ListBenchmarksResponse {
+        count: benchmarks.len(),
+        benchmarks,
+    }))
+}
+
+async fn upload_benchmark(
+    State(coordinator): State<Arc<TransferCoordinator>>,
+    Json(req): Json<UploadBenchmarkRequest>,
+) -> ApiResult<(StatusCode, Json<BenchmarkRecord>)> {
+    let record = coordinator
+        .save_benchmark(req.label.as_deref(), &req.report)
+        .await?;
+    Ok((StatusCode::CREATED, Json(record)))
+}
+
+async fn get_benchmark(
+    State(coordinator): State<Arc<TransferCoordinator>>,
+    Path(id): Path<String>,
+) -> ApiResult<Json<BenchmarkRecord>> {
+    Ok(Json(coordinator.benchmark(&id).await?))
+}
+
+async fn delete_benchmark(
+    State(coordinator): State<Arc<TransferCoordinator>>,
+    Path(id): Path<String>,
+) -> ApiResult<Json<SuccessResponse>> {
+    coordinator.delete_benchmark(&id).await?;
+    Ok(Json(SuccessResponse {
+        message: format!("Benchmark report {id} deleted"),
     }))
 }
 
+async fn export_resume_token(
+    State(coordinator): State<Arc<TransferCoordinator>>,
+    Path(session_id): Path<String>,
+) -> ApiResult<Json<ResumeToken>> {
+    let token = coordinator
+        .export_resume_token(&session_id)
+        .await
+        .map_err(|e| match e {
+            CoordinatorError::TransferNotFound(id) => {
+                ApiError::NotFound(format!("Transfer not found: {id}"))
+            }
+            e => ApiError::CoordinatorError(e),
+        })?;
+
+    Ok(Json(token))
+}
+
+async fn import_resume_token(
+    State(coordinator): State<Arc<TransferCoordinator>>,
+    Json(req): Json<ImportResumeTokenRequest>,
+) -> ApiResult<(StatusCode, Json<StartTransferResponse>)> {
+    let file_path = std::path::PathBuf::from(&req.file_path);
+    if !file_path.exists() {
+        return Err(ApiError::InvalidRequest(format!(
+            "File not found: {}",
+            req.file_path
+        )));
+    }
+
+    let session_id = coordinator
+        .import_resume_token(req.token, file_path)
+        .await
+        .map_err(ApiError::CoordinatorError)?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(StartTransferResponse {
+            session_id: session_id.clone(),
+            message: format!("Transfer {session_id} resumed from token"),
+            file_path: Some(req.file_path.clone()),
+            file_name: std::path::Path::new(&req.file_path)
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string()),
+        }),
+    ))
+}
+
 // --- Metric endpoints ---
 
 async fn get_erasure_metrics(
@@ -306,6 +801,27 @@ async fn get_erasure_metrics(
         })
         .collect();
 
+    let erasure = coordinator.transfer_defaults().erasure;
+    let max_overhead = coordinator.chunk_manager().overhead_budget();
+    let priority_profiles = [Priority::Critical, Priority::High, Priority::Normal]
+        .into_iter()
+        .map(|priority| {
+            let profile = erasure.profiles.get(priority);
+            let parity_shards =
+                profile.parity_shards(erasure.data_shards, erasure.parity_shards, max_overhead);
+            PriorityErasure {
+                priority,
+                extra_parity_percent: profile.extra_parity_percent,
+                budgeted: profile.budgeted,
+                data_shards: erasure.data_shards,
+                parity_shards,
+                overhead_percent: parity_shards as f64
+                    / (erasure.data_shards + parity_shards) as f64
+                    * 100.0,
+            }
+        })
+        .collect();
+
     Json(ErasureMetricsResponse {
         data_shards: status.data_shards,
         parity_shards: status.parity_shards,
@@ -313,6 +829,9 @@ async fn get_erasure_metrics(
         overhead_percent: status.overhead_percent,
         recovery_capability: status.recovery_capability,
         thresholds,
+        max_overhead: coordinator.adaptive_coder().overhead_budget(),
+        budget_clamped_from: status.budget_clamped_from,
+        priority_profiles,
     })
 }
 
@@ -320,7 +839,7 @@ async fn get_network_metrics(
     State(coordinator): State<Arc<TransferCoordinator>>,
 ) -> Json<NetworkMetricsResponse> {
     let transport_stats = coordinator.transport().stats();
-    let quic = coordinator.last_quic_stats();
+    let quic = coordinator.stats().last_quic_stats();
 
     Json(NetworkMetricsResponse {
         total_bytes_sent: transport_stats.total_bytes_sent,
@@ -336,6 +855,21 @@ async fn get_network_metrics(
         quic_cwnd: quic.cwnd,
         quic_congestion_events: quic.congestion_events,
         quic_mtu: quic.current_mtu,
+        pacing_rate_bytes_per_sec: transport_stats.pacing_rate_bytes_per_sec,
+        paced_chunks_delayed: transport_stats.paced_chunks_delayed,
+        pacing_delay_ms: transport_stats.pacing_delay_ms,
+        flow_window_adjustments: transport_stats.flow_window_adjustments,
+        max_send_window: transport_stats.max_send_window,
+        receivers: coordinator
+            .transport()
+            .receiver_reports()
+            .into_iter()
+            .map(Into::into)
+            .collect(),
+        peers: coordinator
+            .quic_transport()
+            .map(|transport| transport.peer_rates())
+            .unwrap_or_default(),
     })
 }
 
@@ -346,9 +880,9 @@ async fn get_queue_metrics(
     let (used, _available, utilization) = coordinator.queue_capacity();
 
     Json(QueueMetricsResponse {
-        critical_pending: stats.critical_pending,
-        high_pending: stats.high_pending,
-        normal_pending: stats.normal_pending,
+        critical_pending: stats.pending(Priority::Critical),
+        high_pending: stats.pending(Priority::High),
+        normal_pending: stats.pending(Priority::Normal),
         total_processed: stats.total_processed,
         total_enqueued: stats.total_enqueued,
         avg_wait_time_ms: stats.avg_wait_time_ms,
@@ -356,9 +890,66 @@ async fn get_queue_metrics(
         capacity_used: used,
         capacity_total: 1_000_000,
         utilization_percent: utilization,
+        queued_bytes: stats.queued_bytes,
+        shed_enqueues: stats.shed_enqueues,
+        critical_latency: stats.latency(Priority::Critical).clone(),
+        high_latency: stats.latency(Priority::High).clone(),
+        normal_latency: stats.latency(Priority::Normal).clone(),
+        levels: stats.levels,
+    })
+}
+
+async fn inspect_queue(
+    State(coordinator): State<Arc<TransferCoordinator>>,
+    Query(params): Query<InspectQueueQuery>,
+) -> Json<QueueSnapshot> {
+    let next = params
+        .next
+        .unwrap_or(DEFAULT_LIST_LIMIT)
+        .min(MAX_LIST_LIMIT);
+    Json(coordinator.inspect_queue(next as usize))
+}
+
+/// How a file of the given size would be split, without sending anything
+async fn plan_transfer(
+    State(coordinator): State<Arc<TransferCoordinator>>,
+    Query(params): Query<PlanQuery>,
+) -> ApiResult<Json<TransferPlan>> {
+    let options = crate::session::TransferOptions {
+        chunk_size: params.chunk_size,
+        data_shards: params.data_shards,
+        parity_shards: params.parity_shards,
+        ..Default::default()
+    };
+    let priority = params.priority.unwrap_or(Priority::Normal);
+    Ok(Json(coordinator.plan_transfer(
+        params.file_size,
+        priority,
+        &options,
+    )?))
+}
+
+async fn get_latency_metrics() -> Json<LatencyMetricsResponse> {
+    Json(LatencyMetricsResponse {
+        stages: metrics::latency_summary(),
     })
 }
 
+async fn get_storage_metrics(
+    State(coordinator): State<Arc<TransferCoordinator>>,
+) -> ApiResult<Json<StorageMetricsResponse>> {
+    let policy = coordinator.maintenance_policy();
+    Ok(Json(StorageMetricsResponse {
+        stats: coordinator
+            .storage_stats()
+            .await
+            .map_err(ApiError::CoordinatorError)?,
+        maintenance_enabled: policy.enabled,
+        maintenance_interval_secs: policy.interval.as_secs(),
+        last_maintenance: coordinator.last_maintenance(),
+    }))
+}
+
 async fn get_metrics_summary(
     State(coordinator): State<Arc<TransferCoordinator>>,
 ) -> Json<MetricsSummaryResponse> {
@@ -376,10 +967,74 @@ async fn get_metrics_summary(
         total_bytes_transferred: 0,
         total_chunks_processed: queue_stats.total_processed,
         queue_depth: queue_stats.total_pending(),
-        uptime_seconds: coordinator.uptime_seconds(),
+        uptime_seconds: coordinator.stats().uptime_seconds(),
     })
 }
 
+/// Default and longest link probe
+const DEFAULT_PROBE_SECS: u64 = 3;
+const MAX_PROBE_SECS: u64 = 30;
+
+async fn probe_link(
+    State(coordinator): State<Arc<TransferCoordinator>>,
+    Json(req): Json<ProbeRequest>,
+) -> ApiResult<Json<ProbeResponse>> {
+    let receiver_addr = req
+        .receiver_addr
+        .parse()
+        .map_err(|e| ApiError::InvalidRequest(format!("Invalid receiver address: {e}")))?;
+    let seconds = req.duration_seconds.unwrap_or(DEFAULT_PROBE_SECS);
+    if seconds == 0 || seconds > MAX_PROBE_SECS {
+        return Err(ApiError::InvalidRequest(format!(
+            "duration_seconds must be between 1 and {MAX_PROBE_SECS}"
+        )));
+    }
+
+    let report = coordinator
+        .probe_link(receiver_addr, std::time::Duration::from_secs(seconds))
+        .await
+        .map_err(ApiError::CoordinatorError)?;
+
+    Ok(Json(ProbeResponse {
+        recommended_chunk_size: report.recommended_chunk_size(),
+        recommended_parity_shards: report.recommended_parity(coordinator.adaptive_coder().config()),
+        report,
+    }))
+}
+
+async fn verify_files(
+    State(coordinator): State<Arc<TransferCoordinator>>,
+    Json(req): Json<VerifyRequest>,
+) -> ApiResult<Json<VerifyResponse>> {
+    let targets: Vec<VerifyTarget> = req
+        .paths
+        .into_iter()
+        .map(|path| VerifyTarget::Path(path.into()))
+        .chain(req.session_ids.into_iter().map(VerifyTarget::Session))
+        .collect();
+    if targets.is_empty() || targets.len() > MAX_VERIFY_TARGETS {
+        return Err(ApiError::InvalidRequest(format!(
+            "between 1 and {MAX_VERIFY_TARGETS} paths or session_ids are required"
+        )));
+    }
+    let concurrency = req.concurrency.unwrap_or(DEFAULT_VERIFY_CONCURRENCY);
+    if concurrency == 0 || concurrency > MAX_VERIFY_CONCURRENCY {
+        return Err(ApiError::InvalidRequest(format!(
+            "concurrency must be between 1 and {MAX_VERIFY_CONCURRENCY}"
+        )));
+    }
+
+    let results = coordinator.verify_files(targets, concurrency).await;
+    let count = |status| results.iter().filter(|r| r.status == status).count();
+    Ok(Json(VerifyResponse {
+        passed: count(VerifyStatus::Passed),
+        failed: count(VerifyStatus::Failed),
+        no_manifest: count(VerifyStatus::NoManifest),
+        errors: count(VerifyStatus::Error),
+        results,
+    }))
+}
+
 async fn simulate_packet_loss(
     State(coordinator): State<Arc<TransferCoordinator>>,
     Json(req): Json<SimulationRequest>,
@@ -397,7 +1052,8 @@ async fn simulate_packet_loss(
         }
 
         let result = coordinator
-            .simulate_file_transfer(file_path, loss_rate)
+            .simulation()
+            .simulate_file_transfer(file_path, loss_rate, req.seed)
             .await
             .map_err(ApiError::CoordinatorError)?;
 
@@ -433,7 +1089,9 @@ async fn simulate_packet_loss(
     } else {
         // Fallback: abstract simulation without a file (original behavior)
         let num_samples = 100;
-        coordinator.simulate_packet_loss(loss_rate, num_samples);
+        coordinator
+            .simulation()
+            .simulate_packet_loss(loss_rate, num_samples);
 
         let status = coordinator.adaptive_coder().status();
 
@@ -463,6 +1121,32 @@ async fn simulate_packet_loss(
     }
 }
 
+async fn simulate_mesh(
+    State(coordinator): State<Arc<TransferCoordinator>>,
+    Json(req): Json<MeshSimulationRequest>,
+) -> ApiResult<Json<MeshSimulationResponse>> {
+    let file_path = std::path::PathBuf::from(&req.file_path);
+    if !file_path.exists() {
+        return Err(ApiError::InvalidRequest(format!(
+            "File not found: {}",
+            req.file_path
+        )));
+    }
+
+    let result = coordinator
+        .simulation()
+        .simulate_mesh(file_path, req.scenario)
+        .await
+        .map_err(ApiError::CoordinatorError)?;
+
+    Ok(Json(MeshSimulationResponse {
+        file_name: result.file_name,
+        file_size_bytes: result.file_size_bytes,
+        parity_chunks: result.parity_chunks,
+        report: result.report,
+    }))
+}
+
 async fn simulate_comparison(
     State(coordinator): State<Arc<TransferCoordinator>>,
     Json(req): Json<ComparisonRequest>,
@@ -478,7 +1162,8 @@ async fn simulate_comparison(
     let trials = req.trials_per_point.unwrap_or(20);
 
     let result = coordinator
-        .simulate_comparison(file_path, trials)
+        .simulation()
+        .simulate_comparison(file_path, trials, req.seed)
         .await
         .map_err(ApiError::CoordinatorError)?;
 
@@ -547,36 +1232,214 @@ async fn list_uploads() -> ApiResult<Json<ListUploadsResponse>> {
     Ok(Json(ListUploadsResponse { files }))
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::chunk::ChunkManager;
-    use crate::integrity::IntegrityVerifier;
-    use crate::network::{ConnectionConfig, QuicTransport};
-    use crate::priority::PriorityQueue;
-    use crate::session::SessionStore;
-    use axum::body::Body;
-    use axum::http::{Request, StatusCode};
-    use http_body_util::BodyExt;
-    use tower::Service;
+/// Who a config change is recorded against when the request doesn't say
+const ANONYMOUS: &str = "anonymous";
 
-    async fn create_test_api() -> RestApi {
-        let chunk_manager = ChunkManager::new(256 * 1024, 10, 3).unwrap();
-        let verifier = IntegrityVerifier;
-        let config = ConnectionConfig::default();
-        let transport = QuicTransport::new(config).await.unwrap();
-        let queue = PriorityQueue::new(1_000_000);
-        let session_store = SessionStore::new_in_memory().await.unwrap();
+#[cfg(feature = "fault-injection")]
+async fn get_faults() -> Json<FaultStateResponse> {
+    let injector = crate::fault::injector();
+    Json(FaultStateResponse {
+        config: injector.config(),
+        stats: injector.stats(),
+    })
+}
 
-        let coordinator =
-            TransferCoordinator::new(chunk_manager, verifier, transport, queue, session_store);
+#[cfg(feature = "fault-injection")]
+async fn set_faults(
+    Json(config): Json<crate::fault::FaultConfig>,
+) -> ApiResult<Json<FaultStateResponse>> {
+    let injector = crate::fault::injector();
+    injector
+        .configure(config)
+        .map_err(ApiError::InvalidRequest)?;
+    Ok(get_faults().await)
+}
 
-        RestApi::new(coordinator)
-    }
+#[cfg(feature = "fault-injection")]
+async fn clear_faults() -> Json<FaultStateResponse> {
+    crate::fault::injector().reset();
+    get_faults().await
+}
 
-    #[tokio::test]
-    async fn test_health_check() {
-        let api = create_test_api().await;
+async fn get_effective_config(
+    State(coordinator): State<Arc<TransferCoordinator>>,
+) -> Json<EffectiveConfigResponse> {
+    Json(EffectiveConfigResponse {
+        defaults: coordinator.transfer_defaults(),
+        changes: coordinator.config_changes(),
+    })
+}
+
+async fn get_erasure_defaults(
+    State(coordinator): State<Arc<TransferCoordinator>>,
+) -> Json<ErasureDefaults> {
+    Json(coordinator.transfer_defaults().erasure)
+}
+
+async fn update_erasure_defaults(
+    State(coordinator): State<Arc<TransferCoordinator>>,
+    Json(req): Json<UpdateErasureRequest>,
+) -> ApiResult<Json<ErasureDefaults>> {
+    let profiles = req
+        .profiles
+        .unwrap_or_else(|| coordinator.transfer_defaults().erasure.profiles);
+    let defaults = coordinator.set_erasure_defaults(
+        ErasureDefaults {
+            data_shards: req.data_shards,
+            parity_shards: req.parity_shards,
+            profiles,
+        },
+        req.changed_by.as_deref().unwrap_or(ANONYMOUS),
+    )?;
+    Ok(Json(defaults.erasure))
+}
+
+fn log_handle() -> ApiResult<LogHandle> {
+    logging::handle().ok_or_else(|| ApiError::InvalidRequest(LogError::NotInitialized.to_string()))
+}
+
+fn log_settings(handle: &LogHandle) -> LogSettingsResponse {
+    LogSettingsResponse {
+        filter: handle.filter().to_string(),
+        format: handle.format(),
+    }
+}
+
+async fn get_logging() -> ApiResult<Json<LogSettingsResponse>> {
+    Ok(Json(log_settings(&log_handle()?)))
+}
+
+async fn update_log_filter(
+    Json(req): Json<UpdateLogFilterRequest>,
+) -> ApiResult<Json<LogSettingsResponse>> {
+    let handle = log_handle()?;
+    handle
+        .set_filter(&req.filter)
+        .map_err(|e| ApiError::InvalidRequest(e.to_string()))?;
+    tracing::info!(filter = %handle.filter(), "Log filter changed");
+    Ok(Json(log_settings(&handle)))
+}
+
+async fn list_connections(
+    State(coordinator): State<Arc<TransferCoordinator>>,
+) -> Json<ListConnectionsResponse> {
+    Json(ListConnectionsResponse {
+        connections: coordinator
+            .quic_transport()
+            .map(|transport| transport.connections())
+            .unwrap_or_default(),
+    })
+}
+
+/// Debug capture of the coordinator's QUIC endpoint
+fn debug_capture(coordinator: &TransferCoordinator) -> ApiResult<Arc<DebugCapture>> {
+    coordinator
+        .quic_transport()
+        .map(|transport| transport.capture().clone())
+        .ok_or_else(|| {
+            CoordinatorError::Unsupported("debug capture needs a QUIC transport".into()).into()
+        })
+}
+
+async fn get_capture(
+    State(coordinator): State<Arc<TransferCoordinator>>,
+) -> ApiResult<Json<CaptureSettings>> {
+    Ok(Json(debug_capture(&coordinator)?.settings()))
+}
+
+async fn update_capture(
+    State(coordinator): State<Arc<TransferCoordinator>>,
+    Json(req): Json<UpdateCaptureRequest>,
+) -> ApiResult<Json<CaptureSettings>> {
+    let capture = debug_capture(&coordinator)?;
+    capture
+        .set(req.peer, req.flags)
+        .map_err(|e| ApiError::InvalidRequest(e.to_string()))?;
+    tracing::info!(peer = ?req.peer, flags = ?req.flags, "Debug capture changed");
+    Ok(Json(capture.settings()))
+}
+
+async fn clear_capture(
+    State(coordinator): State<Arc<TransferCoordinator>>,
+    Query(query): Query<ClearCaptureQuery>,
+) -> ApiResult<Json<CaptureSettings>> {
+    let capture = debug_capture(&coordinator)?;
+    match query.peer {
+        Some(peer) if !capture.clear(peer) => {
+            return Err(ApiError::NotFound(format!("no capture setting for {peer}")));
+        }
+        Some(_) => {}
+        None => capture.reset(),
+    }
+    tracing::info!(peer = ?query.peer, "Debug capture cleared");
+    Ok(Json(capture.settings()))
+}
+
+async fn get_failover_status(
+    State(coordinator): State<Arc<TransferCoordinator>>,
+) -> Json<FailoverStatus> {
+    Json(coordinator.failover_status())
+}
+
+/// Take over from the active; whoever calls this must know it is down
+async fn promote_standby(
+    State(coordinator): State<Arc<TransferCoordinator>>,
+) -> ApiResult<Json<TakeoverReport>> {
+    let report = coordinator
+        .promote()
+        .await
+        .map_err(ApiError::CoordinatorError)?;
+    Ok(Json(report))
+}
+
+async fn get_chunking_defaults(
+    State(coordinator): State<Arc<TransferCoordinator>>,
+) -> Json<ChunkingDefaults> {
+    Json(coordinator.transfer_defaults().chunking)
+}
+
+async fn update_chunking_defaults(
+    State(coordinator): State<Arc<TransferCoordinator>>,
+    Json(req): Json<UpdateChunkingRequest>,
+) -> ApiResult<Json<ChunkingDefaults>> {
+    let defaults = coordinator.set_chunking_defaults(
+        ChunkingDefaults {
+            chunk_size: req.chunk_size,
+            preserve_attributes: req.preserve_attributes,
+        },
+        req.changed_by.as_deref().unwrap_or(ANONYMOUS),
+    )?;
+    Ok(Json(defaults.chunking))
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+    use super::*;
+    use crate::chunk::ChunkManager;
+    use crate::coordinator::{CatalogShare, HealthPolicy, HealthStatus};
+    use crate::network::{ConnectionConfig, QuicTransport};
+    use crate::priority::PriorityQueue;
+    use crate::session::SessionStore;
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use http_body_util::BodyExt;
+    use tower::Service;
+
+    async fn create_test_api() -> RestApi {
+        let chunk_manager = ChunkManager::new(256 * 1024, 10, 3).unwrap();
+        let config = ConnectionConfig::default();
+        let transport = QuicTransport::new(config).await.unwrap();
+        let queue = PriorityQueue::new(1_000_000);
+        let session_store = SessionStore::new_in_memory().await.unwrap();
+
+        let coordinator = TransferCoordinator::new(chunk_manager, transport, queue, session_store);
+
+        RestApi::new(coordinator)
+    }
+
+    #[tokio::test]
+    async fn test_health_check() {
+        let api = create_test_api().await;
         let mut app = api.router();
 
         let request = Request::builder()
@@ -588,6 +1451,38 @@ mod tests {
         assert_eq!(response.status(), StatusCode::OK);
     }
 
+    #[tokio::test]
+    async fn test_liveness_and_readiness() {
+        let api = create_test_api().await;
+        let mut app = api.router();
+
+        let request = Request::builder()
+            .uri("/health/live")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.call(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        api.coordinator.stats().set_health_policy(HealthPolicy {
+            min_free_disk_bytes: u64::MAX,
+            ..Default::default()
+        });
+        let request = Request::builder()
+            .uri("/health/ready")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.call(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let report: HealthReport = serde_json::from_slice(&body).unwrap();
+        let disk = report.component("disk").unwrap();
+        assert_eq!(disk.status, HealthStatus::Down);
+        assert_eq!(
+            report.component("session_db").unwrap().status,
+            HealthStatus::Up
+        );
+    }
+
     #[tokio::test]
     async fn test_list_transfers_empty() {
         let api = create_test_api().await;
@@ -605,7 +1500,484 @@ mod tests {
         let list: ListTransfersResponse = serde_json::from_slice(&body).unwrap();
 
         assert_eq!(list.count, 0);
-        assert_eq!(list.active_transfers.len(), 0);
+        assert_eq!(list.total, 0);
+        assert_eq!(list.transfers.len(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_search_sessions_needs_valid_criteria() {
+        let api = create_test_api().await;
+        let mut app = api.router();
+
+        for (uri, status) in [
+            ("/api/v1/sessions/search", StatusCode::BAD_REQUEST),
+            (
+                "/api/v1/sessions/search?checksum=abc",
+                StatusCode::BAD_REQUEST,
+            ),
+            ("/api/v1/sessions/search?filename=report*", StatusCode::OK),
+        ] {
+            let request = Request::builder().uri(uri).body(Body::empty()).unwrap();
+            let response = app.call(request).await.unwrap();
+            assert_eq!(response.status(), status, "{uri}");
+        }
+
+        let uri = format!("/api/v1/sessions/search?checksum={}", "ab".repeat(32));
+        let request = Request::builder().uri(uri).body(Body::empty()).unwrap();
+        let response = app.call(request).await.unwrap();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let found: SearchSessionsResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(found.count, 0);
+    }
+
+    #[tokio::test]
+    async fn test_list_pending_transfers_empty() {
+        let api = create_test_api().await;
+        let mut app = api.router();
+
+        let request = Request::builder()
+            .uri("/api/v1/transfers/pending")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.call(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let pending: PendingTransfersResponse = serde_json::from_slice(&body).unwrap();
+        assert!(pending.pending.is_empty());
+        assert_eq!(pending.max_concurrent_transfers, 0);
+    }
+
+    #[tokio::test]
+    async fn test_inspect_queue_empty() {
+        let api = create_test_api().await;
+        let mut app = api.router();
+
+        let request = Request::builder()
+            .uri("/api/v1/queue?next=10")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.call(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let snapshot: QueueSnapshot = serde_json::from_slice(&body).unwrap();
+        assert!(snapshot.files.is_empty());
+        assert!(snapshot.next.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_list_transfers_rejects_unknown_status() {
+        let api = create_test_api().await;
+        let mut app = api.router();
+
+        let request = Request::builder()
+            .uri("/api/v1/transfers?status=bogus&limit=10")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.call(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_probe_rejects_excessive_duration() {
+        let api = create_test_api().await;
+        let mut app = api.router();
+
+        let request = Request::builder()
+            .method("POST")
+            .uri("/api/v1/probe")
+            .header("content-type", "application/json")
+            .body(Body::from(
+                r#"{"receiver_addr":"127.0.0.1:5001","duration_seconds":600}"#,
+            ))
+            .unwrap();
+        let response = app.call(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_verify_files() {
+        let api = create_test_api().await;
+        let mut app = api.router();
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("stored.bin");
+        tokio::fs::write(&path, b"stored bytes").await.unwrap();
+
+        let verify = |body: String| {
+            Request::builder()
+                .method("POST")
+                .uri("/api/v1/verify")
+                .header("content-type", "application/json")
+                .body(Body::from(body))
+                .unwrap()
+        };
+
+        let response = app.call(verify(r#"{"paths":[]}"#.into())).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let body = serde_json::json!({
+            "paths": [path],
+            "session_ids": ["no-such-session"],
+        });
+        let response = app.call(verify(body.to_string())).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let report: VerifyResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!((report.passed, report.failed), (0, 0));
+        assert_eq!((report.no_manifest, report.errors), (1, 1));
+        assert_eq!(
+            report.results[0].actual.as_deref(),
+            Some(blake3::hash(b"stored bytes").to_hex().as_str())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_receiver_pulls_catalog_file() {
+        let api = create_test_api().await;
+        let mut app = api.router();
+        let dir = tempfile::TempDir::new().unwrap();
+        tokio::fs::write(dir.path().join("map.bin"), vec![7u8; 4096])
+            .await
+            .unwrap();
+        api.coordinator
+            .set_catalog_shares(vec![CatalogShare::new("field", dir.path())])
+            .unwrap();
+
+        let request = Request::builder()
+            .uri("/api/v1/catalog")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.call(request).await.unwrap();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let catalog: CatalogResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(catalog.files.len(), 1);
+        assert_eq!(catalog.files[0].id, "field/map.bin");
+        assert_eq!(catalog.files[0].size, 4096);
+
+        let pull = |id: &str, receiver_addr: &str| {
+            let body = serde_json::json!({ "id": id, "receiver_addr": receiver_addr });
+            Request::builder()
+                .method("POST")
+                .uri("/api/v1/catalog/request")
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+        let response = app
+            .call(pull("field/../map.bin", "127.0.0.1:9"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let response = app.call(pull("field/map.bin", "nowhere")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = app
+            .call(pull("field/map.bin", "127.0.0.1:9"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let started: StartTransferResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(started.file_name.as_deref(), Some("map.bin"));
+        assert!(started.file_path.is_none());
+        assert!(api
+            .coordinator
+            .get_progress(&started.session_id)
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_update_erasure_defaults() {
+        let api = create_test_api().await;
+        let mut app = api.router();
+
+        let request = Request::builder()
+            .method("PUT")
+            .uri("/api/v1/config/erasure")
+            .header("content-type", "application/json")
+            .body(Body::from(
+                r#"{"data_shards":20,"parity_shards":6,"changed_by":"ops",
+                    "profiles":{"critical":{"extra_parity_percent":100,"budgeted":false}}}"#,
+            ))
+            .unwrap();
+        let response = app.call(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // Too many shards is rejected and leaves the defaults alone
+        let request = Request::builder()
+            .method("PUT")
+            .uri("/api/v1/config/erasure")
+            .header("content-type", "application/json")
+            .body(Body::from(r#"{"data_shards":250,"parity_shards":10}"#))
+            .unwrap();
+        let response = app.call(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let request = Request::builder()
+            .uri("/api/v1/config")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.call(request).await.unwrap();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let config: EffectiveConfigResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            (
+                config.defaults.erasure.data_shards,
+                config.defaults.erasure.parity_shards
+            ),
+            (20, 6)
+        );
+        let profiles = config.defaults.erasure.profiles;
+        assert_eq!(profiles.critical.extra_parity_percent, 100);
+        assert_eq!(profiles.normal, Default::default());
+        assert_eq!(config.defaults.chunking.chunk_size, 256 * 1024);
+        assert_eq!(config.changes.len(), 1);
+        assert_eq!(config.changes[0].changed_by, "ops");
+        assert_eq!(config.changes[0].before.erasure.data_shards, 10);
+    }
+
+    #[tokio::test]
+    async fn test_change_log_filter() {
+        let api = create_test_api().await;
+        let mut app = api.router();
+        let dir = tempfile::tempdir().unwrap();
+        // The only test that installs the process-wide subscriber
+        logging::init(&logging::LogConfig {
+            filter: "warn".into(),
+            format: logging::LogFormat::Json,
+            file: Some(dir.path().join("server.log")),
+            ..Default::default()
+        })
+        .unwrap();
+
+        let put = |body: &'static str| {
+            Request::builder()
+                .method("PUT")
+                .uri("/api/v1/logging")
+                .header("content-type", "application/json")
+                .body(Body::from(body))
+                .unwrap()
+        };
+        let response = app
+            .call(put(r#"{"filter":"warn,chunkstream_pro::network=debug"}"#))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = app
+            .call(put(r#"{"filter":"warn,quinn=loud"}"#))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let request = Request::builder()
+            .uri("/api/v1/logging")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.call(request).await.unwrap();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let settings: LogSettingsResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(settings.filter, "warn,chunkstream_pro::network=debug");
+        assert_eq!(settings.format, logging::LogFormat::Json);
+    }
+
+    #[tokio::test]
+    async fn test_switch_capture_per_peer() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = ConnectionConfig::default();
+        config.capture.event_dir = Some(dir.path().to_path_buf());
+        let coordinator = TransferCoordinator::new(
+            ChunkManager::new(256 * 1024, 10, 3).unwrap(),
+            QuicTransport::new(config).await.unwrap(),
+            PriorityQueue::new(1_000_000),
+            SessionStore::new_in_memory().await.unwrap(),
+        );
+        let mut app = RestApi::new(coordinator).router();
+
+        let put = |body: &'static str| {
+            Request::builder()
+                .method("PUT")
+                .uri("/api/v1/network/capture")
+                .header("content-type", "application/json")
+                .body(Body::from(body))
+                .unwrap()
+        };
+        let response = app
+            .call(put(r#"{"peer":"10.0.0.7:5000","events":true}"#))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let settings: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(settings["peers"]["10.0.0.7:5000"]["events"], true);
+        assert_eq!(settings["all"]["events"], false);
+
+        let request = Request::builder()
+            .method("DELETE")
+            .uri("/api/v1/network/capture?peer=10.0.0.7:5000")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.call(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let request = Request::builder()
+            .method("DELETE")
+            .uri("/api/v1/network/capture?peer=10.0.0.7:5000")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.call(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        // Nowhere to write events without a capture directory
+        let mut app = create_test_api().await.router();
+        let response = app.call(put(r#"{"events":true}"#)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_erasure_metrics_show_priority_profiles() {
+        let api = create_test_api().await;
+        let mut app = api.router();
+
+        let request = Request::builder()
+            .uri("/api/v1/metrics/erasure")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.call(request).await.unwrap();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let metrics: ErasureMetricsResponse = serde_json::from_slice(&body).unwrap();
+
+        let parity: Vec<_> = metrics
+            .priority_profiles
+            .iter()
+            .map(|p| (p.priority, p.parity_shards))
+            .collect();
+        assert_eq!(
+            parity,
+            [
+                (Priority::Critical, 5),
+                (Priority::High, 3),
+                (Priority::Normal, 3)
+            ]
+        );
+        assert!(!metrics.priority_profiles[0].budgeted);
+    }
+
+    #[tokio::test]
+    async fn test_latency_metrics_cover_every_stage() {
+        let api = create_test_api().await;
+        let mut app = api.router();
+        metrics::record_stage(metrics::Stage::Split, std::time::Duration::from_millis(3));
+
+        let request = Request::builder()
+            .uri("/api/v1/metrics/latency")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.call(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let metrics: LatencyMetricsResponse = serde_json::from_slice(&body).unwrap();
+
+        let stages: Vec<_> = metrics.stages.iter().map(|s| s.stage).collect();
+        assert_eq!(stages, metrics::Stage::ALL);
+        // Other tests split files too, so only a lower bound holds
+        assert!(metrics.stages[0].count >= 1);
+        assert!(metrics.stages[0].max_ms >= 3.0);
+    }
+
+    #[tokio::test]
+    async fn test_promote_requires_a_standby() {
+        let api = create_test_api().await;
+        let mut app = api.router();
+
+        let request = Request::builder()
+            .uri("/api/v1/failover")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.call(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let status: FailoverStatus = serde_json::from_slice(&body).unwrap();
+        assert_eq!(status.role, crate::failover::FailoverRole::Standalone);
+        assert!(status.replication.is_none() && status.standby.is_none());
+
+        let request = Request::builder()
+            .method("POST")
+            .uri("/api/v1/failover/promote")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.call(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+    }
+
+    #[tokio::test]
+    async fn test_storage_metrics_report_database_and_maintenance() {
+        let api = create_test_api().await;
+        let mut app = api.router();
+        let get_metrics = || {
+            Request::builder()
+                .uri("/api/v1/metrics/storage")
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let response = app.call(get_metrics()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let metrics: StorageMetricsResponse = serde_json::from_slice(&body).unwrap();
+        let stats = metrics.stats.unwrap();
+        assert_eq!(stats.sessions, 0);
+        assert!(stats.db_bytes > 0);
+        assert!(metrics.maintenance_enabled);
+        assert!(metrics.last_maintenance.is_none());
+
+        let report = api.coordinator.run_maintenance().await.unwrap().unwrap();
+        let response = app.call(get_metrics()).await.unwrap();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let metrics: StorageMetricsResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(metrics.last_maintenance, Some(report));
+    }
+
+    #[tokio::test]
+    async fn test_plan_previews_layout() {
+        let api = create_test_api().await;
+        let mut app = api.router();
+        let plan = |query: &str| {
+            Request::builder()
+                .uri(format!("/api/v1/plan?{query}"))
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let response = app
+            .call(plan("file_size=1048576&priority=Critical"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let plan_ok: TransferPlan = serde_json::from_slice(&body).unwrap();
+        assert!(plan_ok.feasible);
+        assert_eq!(plan_ok.priority, Priority::Critical);
+        assert_eq!(plan_ok.max_lost_chunks, plan_ok.parity_chunks);
+
+        // Fine for small files, too many shards for this one
+        let response = app
+            .call(plan("file_size=104857600&chunk_size=65536"))
+            .await
+            .unwrap();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let too_many: TransferPlan = serde_json::from_slice(&body).unwrap();
+        assert!(!too_many.feasible);
+        assert!(!too_many.warnings.is_empty());
+
+        // Settings that can't work at any size are refused
+        let response = app
+            .call(plan("file_size=1024&data_shards=0"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
     }
 
     #[tokio::test]
@@ -621,4 +1993,18 @@ mod tests {
 
         assert_eq!(response.status(), StatusCode::NOT_FOUND);
     }
+
+    #[tokio::test]
+    async fn test_resume_token_for_unknown_transfer() {
+        let api = create_test_api().await;
+        let mut app = api.router();
+
+        let request = Request::builder()
+            .uri("/api/v1/transfers/nonexistent-id/resume-token")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.call(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
 }
@@ -1,12 +1,57 @@
-use crate::chunk::Priority;
-use crate::session::SessionStatus;
+use crate::chunk::{ErasureProfiles, Priority};
+use crate::coordinator::{
+    CatalogEntry, ConfigChange, FileVerification, PendingTransfer, ResumeToken, SequencedEvent,
+    TransferDefaults, TransferProgress,
+};
+use crate::integrity::ChecksumType;
+use crate::logging::LogFormat;
+use crate::metrics::StageLatency;
+use crate::network::{CaptureFlags, ConnectionInfo, LinkReport, PeerRate, ReceiverStats};
+use crate::priority::{LatencyStats, LevelStats};
+use crate::relay::{MeshReport, MeshScenario};
+use crate::session::{
+    BenchmarkRecord, MaintenanceReport, ProgressSample, SessionSort, SessionState, SessionStatus,
+    StorageStats, TransferProfile, TransferTags,
+};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StartTransferRequest {
     pub file_path: String,
-    pub priority: Priority,
+    /// Defaults to the profile's priority, then Normal
+    #[serde(default)]
+    pub priority: Option<Priority>,
     pub receiver_addr: Option<String>, // Optional receiver address (e.g., "192.168.1.100:5001")
+    #[serde(default)]
+    pub local_bind_addr: Option<String>, // Optional local uplink to send from (e.g., "10.0.0.5:0")
+    /// Transfer profile supplying anything not set here
+    #[serde(default)]
+    pub profile: Option<String>,
+    /// Start even if the same file is already being sent
+    #[serde(default)]
+    pub allow_duplicate: bool,
+    /// Labels kept with the session and sent with its events
+    #[serde(default)]
+    pub tags: TransferTags,
+}
+
+/// A receiver asking for a catalog file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CatalogTransferRequest {
+    /// Catalog id, as listed by `GET /api/v1/catalog`
+    pub id: String,
+    /// QUIC address the file is pushed to
+    pub receiver_addr: String,
+    /// Defaults to the profile's priority, then Normal
+    #[serde(default)]
+    pub priority: Option<Priority>,
+    #[serde(default)]
+    pub profile: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CatalogResponse {
+    pub files: Vec<CatalogEntry>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -27,6 +72,35 @@ pub struct TransferProgressResponse {
     pub bytes_transferred: u64,
     pub total_bytes: u64,
     pub current_speed_bps: u64,
+    /// Chunks that failed and haven't been delivered since
+    #[serde(default)]
+    pub failed_chunks: Vec<u32>,
+}
+
+impl From<TransferProgress> for TransferProgressResponse {
+    fn from(progress: TransferProgress) -> Self {
+        Self {
+            session_id: progress.session_id,
+            status: progress.status,
+            progress_percent: progress.progress_percent,
+            completed_chunks: progress.completed_chunks,
+            total_chunks: progress.total_chunks,
+            bytes_transferred: progress.bytes_transferred,
+            total_bytes: progress.total_bytes,
+            current_speed_bps: progress.current_speed_bps,
+            failed_chunks: progress.failed_chunks,
+        }
+    }
+}
+
+/// Throughput over time of one transfer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimeseriesResponse {
+    pub session_id: String,
+    /// Time between samples while the transfer runs
+    pub interval_ms: u64,
+    /// Oldest first
+    pub samples: Vec<ProgressSample>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -36,12 +110,165 @@ pub struct TransferStateResponse {
     pub is_active: bool,
     pub is_paused: bool,
     pub is_terminal: bool,
+    /// Completed with FEC repairs, or partially delivered via a relay
+    #[serde(default)]
+    pub is_degraded: bool,
+}
+
+/// Query parameters for `GET /api/v1/transfers`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ListTransfersQuery {
+    /// Status name: initializing, active, paused, completed,
+    /// completed_with_repairs, partially_delivered or failed
+    pub status: Option<String>,
+    /// Tags every listed transfer carries, as `key:value` pairs separated
+    /// by commas
+    pub tags: Option<String>,
+    #[serde(default)]
+    pub sort: SessionSort,
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+}
+
+/// One transfer in a listing
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransferSummary {
+    pub session_id: String,
+    pub filename: String,
+    pub status: String,
+    /// Failure reason when `status` is `failed`
+    pub error: Option<String>,
+    /// Chunks the receiver rebuilds from parity (`completed_with_repairs`)
+    pub repaired_chunks: Option<u32>,
+    /// Chunks still held by a relay (`partially_delivered`)
+    pub held_by_relay: Option<u32>,
+    /// Completed without sending because the receiver already had the file
+    #[serde(default)]
+    pub skipped_duplicate: bool,
+    pub priority: Priority,
+    pub progress_percent: f32,
+    pub bytes_transferred: u64,
+    pub total_bytes: u64,
+    pub current_speed_bps: u64,
+    pub created_at: i64,
+    pub updated_at: i64,
+    #[serde(default)]
+    pub tags: TransferTags,
+}
+
+impl From<&SessionState> for TransferSummary {
+    fn from(state: &SessionState) -> Self {
+        Self {
+            session_id: state.session_id.clone(),
+            filename: state.manifest.filename.clone(),
+            status: state.status.kind().to_string(),
+            error: match &state.status {
+                SessionStatus::Failed(reason) => Some(reason.clone()),
+                _ => None,
+            },
+            repaired_chunks: match state.status {
+                SessionStatus::CompletedWithRepairs { repaired_chunks } => Some(repaired_chunks),
+                _ => None,
+            },
+            held_by_relay: match state.status {
+                SessionStatus::PartiallyDelivered { held_by_relay, .. } => Some(held_by_relay),
+                _ => None,
+            },
+            skipped_duplicate: state.metrics.skipped_duplicate,
+            priority: state.manifest.priority,
+            progress_percent: state.progress_percent(),
+            bytes_transferred: state.metrics.bytes_transferred,
+            total_bytes: state.manifest.total_size,
+            current_speed_bps: state.current_speed_bps(),
+            created_at: state.created_at,
+            updated_at: state.updated_at,
+            tags: state.options.tags.clone(),
+        }
+    }
+}
+
+/// Query parameters for `GET /api/v1/sessions/search`; at least one of
+/// `filename` and `checksum` is required
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SearchSessionsQuery {
+    /// File name, ignoring case; a trailing `*` matches names starting
+    /// with the rest
+    pub filename: Option<String>,
+    /// File checksum as 64 hex digits
+    pub checksum: Option<String>,
+    pub limit: Option<u32>,
+}
+
+/// A session that carried a matching file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionMatch {
+    #[serde(flatten)]
+    pub transfer: TransferSummary,
+    pub file_id: String,
+    /// File checksum from the manifest, hex
+    pub checksum: String,
+}
+
+impl From<&SessionState> for SessionMatch {
+    fn from(state: &SessionState) -> Self {
+        Self {
+            transfer: state.into(),
+            file_id: state.file_id.clone(),
+            checksum: hex::encode(state.manifest.checksum),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchSessionsResponse {
+    /// Most recently updated first
+    pub sessions: Vec<SessionMatch>,
+    pub count: usize,
+}
+
+/// A transfer waiting for a concurrency slot
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingTransferSummary {
+    pub session_id: String,
+    pub file_path: String,
+    pub priority: Priority,
+    pub receiver_addr: Option<String>,
+    /// Place in the admission queue (0 = next to start)
+    pub position: usize,
+    pub queued_at: i64,
+}
+
+impl PendingTransferSummary {
+    pub fn new(position: usize, pending: &PendingTransfer) -> Self {
+        Self {
+            session_id: pending.session_id.clone(),
+            file_path: pending.source.file_id(),
+            priority: pending.priority,
+            receiver_addr: pending.receiver_addr.map(|a| a.to_string()),
+            position,
+            queued_at: pending.queued_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingTransfersResponse {
+    pub pending: Vec<PendingTransferSummary>,
+    /// Transfers currently holding a slot
+    pub active: usize,
+    /// 0 = unlimited
+    pub max_concurrent_transfers: usize,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ListTransfersResponse {
-    pub active_transfers: Vec<String>,
+    pub transfers: Vec<TransferSummary>,
+    /// Transfers in this page
     pub count: usize,
+    /// Transfers matching the filter across all pages
+    pub total: u64,
+    pub limit: u32,
+    pub offset: u32,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -50,11 +277,140 @@ pub struct ErrorResponse {
     pub code: String,
 }
 
+/// Body of `POST /api/v1/transfers/resume-token`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportResumeTokenRequest {
+    /// Token from `GET /api/v1/transfers/:id/resume-token` on the original host
+    pub token: ResumeToken,
+    /// This host's copy of the file
+    pub file_path: String,
+}
+
+// --- Runtime configuration types ---
+
+/// Body of `PUT /api/v1/config/erasure`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdateErasureRequest {
+    pub data_shards: usize,
+    pub parity_shards: usize,
+    /// Per-priority profiles; the current ones are kept when omitted
+    #[serde(default)]
+    pub profiles: Option<ErasureProfiles>,
+    /// Recorded with the change
+    #[serde(default)]
+    pub changed_by: Option<String>,
+}
+
+/// Body of `PUT /api/v1/config/chunking`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdateChunkingRequest {
+    pub chunk_size: usize,
+    pub preserve_attributes: bool,
+    /// Recorded with the change
+    #[serde(default)]
+    pub changed_by: Option<String>,
+}
+
+/// Defaults new transfers use, and how they got there
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EffectiveConfigResponse {
+    pub defaults: TransferDefaults,
+    /// Changes since startup, oldest first
+    pub changes: Vec<ConfigChange>,
+}
+
+/// Log output of the server process
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogSettingsResponse {
+    /// Level directives in effect, e.g. `info,chunkstream_pro::network=debug`
+    pub filter: String,
+    pub format: LogFormat,
+}
+
+/// Body of `PUT /api/v1/logging`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdateLogFilterRequest {
+    pub filter: String,
+}
+
+/// Body of `PUT /api/v1/network/capture`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdateCaptureRequest {
+    /// Peer the setting is for; every peer without its own when unset
+    #[serde(default)]
+    pub peer: Option<std::net::SocketAddr>,
+    #[serde(flatten)]
+    pub flags: CaptureFlags,
+}
+
+/// Query parameters for `DELETE /api/v1/network/capture`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ClearCaptureQuery {
+    /// Peer whose own setting is dropped; all capture stops when unset
+    pub peer: Option<std::net::SocketAddr>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SuccessResponse {
     pub message: String,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListProfilesResponse {
+    pub profiles: Vec<TransferProfile>,
+}
+
+/// Body of `GET /api/v1/network/connections`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListConnectionsResponse {
+    pub connections: Vec<ConnectionInfo>,
+}
+
+/// Body of `POST /api/v1/benchmarks`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UploadBenchmarkRequest {
+    #[serde(default)]
+    pub label: Option<String>,
+    /// The report as the benchmark suite wrote it
+    pub report: serde_json::Value,
+}
+
+/// Query parameters for `GET /api/v1/queue`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct InspectQueueQuery {
+    /// How many of the next chunks to list
+    pub next: Option<u32>,
+}
+
+/// Query parameters for `GET /api/v1/plan`; unset layout settings take the
+/// coordinator's defaults
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlanQuery {
+    pub file_size: u64,
+    /// Defaults to Normal
+    #[serde(default)]
+    pub priority: Option<Priority>,
+    #[serde(default)]
+    pub chunk_size: Option<usize>,
+    #[serde(default)]
+    pub data_shards: Option<usize>,
+    #[serde(default)]
+    pub parity_shards: Option<usize>,
+}
+
+/// Query parameters for `GET /api/v1/benchmarks`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ListBenchmarksQuery {
+    pub limit: Option<u32>,
+}
+
+/// Stored benchmark reports, newest first
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListBenchmarksResponse {
+    pub count: usize,
+    pub benchmarks: Vec<BenchmarkRecord>,
+}
+
 // --- Metric response types ---
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -65,6 +421,26 @@ pub struct ErasureMetricsResponse {
     pub overhead_percent: f64,
     pub recovery_capability: f64,
     pub thresholds: Vec<ErasureThreshold>,
+    /// Most parity bytes per data byte, when an overhead budget is set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_overhead: Option<f64>,
+    /// Parity the loss rate calls for, when the budget holds it lower
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub budget_clamped_from: Option<usize>,
+    /// Parity new files of each priority get at the default shard counts
+    #[serde(default)]
+    pub priority_profiles: Vec<PriorityErasure>,
+}
+
+/// Erasure profile of one priority and the parity it results in
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PriorityErasure {
+    pub priority: Priority,
+    pub extra_parity_percent: u32,
+    pub budgeted: bool,
+    pub data_shards: usize,
+    pub parity_shards: usize,
+    pub overhead_percent: f64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -90,10 +466,43 @@ pub struct NetworkMetricsResponse {
     pub quic_cwnd: u64,
     pub quic_congestion_events: u64,
     pub quic_mtu: u16,
+    // Send pacing
+    pub pacing_rate_bytes_per_sec: u64,
+    pub paced_chunks_delayed: u64,
+    pub pacing_delay_ms: u64,
+    // Flow-control tuning
+    pub flow_window_adjustments: u64,
+    pub max_send_window: u64,
+    /// What each receiver last reported seeing, the far end of the link
+    #[serde(default)]
+    pub receivers: Vec<ReceiverReport>,
+    /// Each sender's receive rate and the rate it is held to
+    #[serde(default)]
+    pub peers: Vec<PeerRate>,
+}
+
+/// A receiver's latest stats report
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReceiverReport {
+    pub remote_addr: std::net::SocketAddr,
+    #[serde(flatten)]
+    pub stats: ReceiverStats,
+    pub corruption_rate: f64,
+}
+
+impl From<(std::net::SocketAddr, ReceiverStats)> for ReceiverReport {
+    fn from((remote_addr, stats): (std::net::SocketAddr, ReceiverStats)) -> Self {
+        Self {
+            remote_addr,
+            corruption_rate: stats.corruption_rate(),
+            stats,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct QueueMetricsResponse {
+    /// Chunks waiting at levels 0 to 2; `levels` has every level
     pub critical_pending: usize,
     pub high_pending: usize,
     pub normal_pending: usize,
@@ -104,6 +513,22 @@ pub struct QueueMetricsResponse {
     pub capacity_used: usize,
     pub capacity_total: usize,
     pub utilization_percent: f64,
+    /// Chunk data currently queued
+    #[serde(default)]
+    pub queued_bytes: u64,
+    /// Normal-priority enqueues turned away under memory pressure
+    #[serde(default)]
+    pub shed_enqueues: u64,
+    /// Enqueue-to-delivery latency per priority
+    #[serde(default)]
+    pub critical_latency: LatencyStats,
+    #[serde(default)]
+    pub high_latency: LatencyStats,
+    #[serde(default)]
+    pub normal_latency: LatencyStats,
+    /// Pending chunks, waits and latency per queue level, most urgent first
+    #[serde(default)]
+    pub levels: Vec<LevelStats>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -121,11 +546,31 @@ pub struct MetricsSummaryResponse {
     pub uptime_seconds: u64,
 }
 
+/// Latency distribution of each transfer stage since startup
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LatencyMetricsResponse {
+    pub stages: Vec<StageLatency>,
+}
+
+/// Session database size and upkeep
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StorageMetricsResponse {
+    /// `None` if the session store doesn't report its size
+    pub stats: Option<StorageStats>,
+    pub maintenance_enabled: bool,
+    pub maintenance_interval_secs: u64,
+    /// Latest maintenance pass since startup
+    pub last_maintenance: Option<MaintenanceReport>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SimulationRequest {
     pub loss_rate: f32,
     pub duration_seconds: Option<u64>,
     pub file_path: Option<String>,
+    /// Fixes the loss rolls of a file simulation, for repeatable runs
+    #[serde(default)]
+    pub seed: Option<u64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -168,6 +613,83 @@ pub enum WebSocketMessage {
         error: String,
     },
     Error(ErrorResponse),
+    /// Sent on joining a session's room, before any of its live events
+    SessionSnapshot {
+        session_id: String,
+        progress: TransferProgressResponse,
+        /// The session's latest events, oldest first
+        events: Vec<SequencedEvent>,
+    },
+    /// An event for a session whose room the client joined
+    SessionEvent(SequencedEvent),
+}
+
+/// Messages a client sends on `/ws`
+///
+/// Besides these, the text `ping` is answered with `pong`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", content = "data", rename_all = "snake_case")]
+pub enum WebSocketClientMessage {
+    /// Follow one session: a snapshot now, then its events as they happen
+    ///
+    /// Once a client has joined a room, progress updates cover only the
+    /// sessions it joined.
+    Join {
+        session_id: String,
+        /// How many past events the snapshot carries
+        #[serde(default)]
+        backfill: Option<usize>,
+    },
+    Leave {
+        session_id: String,
+    },
+}
+
+/// Messages a browser sends to the gateway (`/ws/gateway`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", content = "data", rename_all = "snake_case")]
+pub enum GatewayClientMessage {
+    /// Announce an upload; file bytes follow as binary frames
+    BeginUpload {
+        filename: String,
+        size: u64,
+        #[serde(default)]
+        priority: Option<Priority>,
+        #[serde(default)]
+        receiver_addr: Option<String>,
+        /// Hex Blake3 of the whole file, verified before the transfer starts
+        #[serde(default)]
+        checksum: Option<String>,
+    },
+    /// All binary frames for the current upload have been sent
+    EndUpload,
+    /// Stream a file from the uploads directory back as chunks
+    Fetch { filename: String },
+}
+
+/// Messages the gateway sends back to the browser
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", content = "data", rename_all = "snake_case")]
+pub enum GatewayServerMessage {
+    UploadReady { filename: String },
+    TransferStarted(StartTransferResponse),
+    Manifest(crate::chunk::FileManifest),
+    FetchComplete { chunks_sent: u32 },
+    Error(ErrorResponse),
+}
+
+/// Header preceding each chunk in a gateway binary frame
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GatewayChunkHeader {
+    pub sequence_number: u32,
+    pub total_chunks: u32,
+    pub is_parity: bool,
+    pub data_size: usize,
+    /// Hex checksum of the chunk payload, hashed with `checksum_algorithm`
+    pub checksum: String,
+    /// Blake3 unless the sender chose another algorithm
+    #[serde(default)]
+    pub checksum_algorithm: ChecksumType,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -189,6 +711,9 @@ pub struct MetricsSnapshotData {
     pub quic_loss_rate: f64,
     pub quic_sent_packets: u64,
     pub quic_lost_packets: u64,
+    /// Receivers' own view of the link
+    #[serde(default)]
+    pub receivers: Vec<ReceiverReport>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -209,6 +734,9 @@ pub struct ListUploadsResponse {
 pub struct ComparisonRequest {
     pub file_path: String,
     pub trials_per_point: Option<u32>,
+    /// Fixes the loss rolls, for repeatable runs
+    #[serde(default)]
+    pub seed: Option<u64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -231,3 +759,70 @@ pub struct ComparisonResponse {
     pub trials_per_point: u32,
     pub points: Vec<ComparisonPoint>,
 }
+
+// --- Fault injection types ---
+
+#[cfg(feature = "fault-injection")]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FaultStateResponse {
+    pub config: crate::fault::FaultConfig,
+    pub stats: crate::fault::FaultStats,
+}
+
+// --- Relay mesh simulation types ---
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MeshSimulationRequest {
+    pub file_path: String,
+    #[serde(flatten)]
+    pub scenario: MeshScenario,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MeshSimulationResponse {
+    pub file_name: String,
+    pub file_size_bytes: u64,
+    pub parity_chunks: usize,
+    #[serde(flatten)]
+    pub report: MeshReport,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProbeRequest {
+    pub receiver_addr: String,
+    /// How long to stream probe chunks (default 3s, at most 30s)
+    pub duration_seconds: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProbeResponse {
+    pub report: LinkReport,
+    pub recommended_chunk_size: usize,
+    pub recommended_parity_shards: usize,
+}
+
+/// Files to check against their stored manifests
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct VerifyRequest {
+    /// Checked against the newest session sent from each path
+    #[serde(default)]
+    pub paths: Vec<String>,
+    /// Checked against each session's own manifest and file path
+    #[serde(default)]
+    pub session_ids: Vec<String>,
+    /// Files hashed at once (default 4, at most 32)
+    #[serde(default)]
+    pub concurrency: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VerifyResponse {
+    pub passed: usize,
+    pub failed: usize,
+    /// Files no session records
+    pub no_manifest: usize,
+    /// Files or sessions that couldn't be read
+    pub errors: usize,
+    /// One report per path, then per session id, in request order
+    pub results: Vec<FileVerification>,
+}
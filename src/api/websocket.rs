@@ -1,5 +1,14 @@
+//! Live updates on `/ws`
+//!
+//! Every client gets progress for active transfers and a metrics snapshot
+//! every 500ms. A client can also join a session's room with
+//! [`WebSocketClientMessage::Join`]: it then gets a
+//! [`SessionSnapshot`](WebSocketMessage::SessionSnapshot) of the session's
+//! progress and latest events, followed by each new event for it, so a
+//! dashboard opened mid-transfer sees how the transfer got where it is.
+
 use crate::api::types::*;
-use crate::coordinator::TransferCoordinator;
+use crate::coordinator::{TransferCoordinator, EVENT_HISTORY};
 use axum::{
     extract::{
         ws::{Message, WebSocket},
@@ -7,9 +16,14 @@ use axum::{
     },
     response::Response,
 };
+use futures::StreamExt;
+use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::time::{interval, Duration};
 
+/// Past events in a room snapshot when the join doesn't say how many
+pub const DEFAULT_ROOM_BACKFILL: usize = 50;
+
 pub async fn websocket_handler(
     ws: WebSocketUpgrade,
     State(coordinator): State<Arc<TransferCoordinator>>,
@@ -19,30 +33,27 @@ pub async fn websocket_handler(
 
 async fn handle_websocket(mut socket: WebSocket, coordinator: Arc<TransferCoordinator>) {
     let mut tick = interval(Duration::from_millis(500));
+    // Subscribed before any join, so an event published after a room's
+    // snapshot was taken is always seen
+    let mut events = Box::pin(coordinator.subscribe_sequenced());
+    // Joined sessions and the last event number their snapshot covered
+    let mut rooms: HashMap<String, u64> = HashMap::new();
 
     loop {
         tokio::select! {
             _ = tick.tick() => {
-                // Send progress updates for all active transfers
-                let active_transfers = coordinator.list_active();
+                // Send progress updates for active transfers, only the
+                // joined ones once the client is in a room
+                let active_transfers = coordinator
+                    .list_active()
+                    .into_iter()
+                    .filter(|id| rooms.is_empty() || rooms.contains_key(id));
 
                 for session_id in active_transfers {
                     if let Ok(progress) = coordinator.get_progress(&session_id).await {
-                        let msg = WebSocketMessage::TransferProgress(TransferProgressResponse {
-                            session_id: progress.session_id.clone(),
-                            status: progress.status,
-                            progress_percent: progress.progress_percent,
-                            completed_chunks: progress.completed_chunks,
-                            total_chunks: progress.total_chunks,
-                            bytes_transferred: progress.bytes_transferred,
-                            total_bytes: progress.total_bytes,
-                            current_speed_bps: progress.current_speed_bps,
-                        });
-
-                        if let Ok(json) = serde_json::to_string(&msg) {
-                            if socket.send(Message::Text(json)).await.is_err() {
-                                return;
-                            }
+                        let msg = WebSocketMessage::TransferProgress(progress.into());
+                        if !send_json(&mut socket, &msg).await {
+                            return;
                         }
                     }
                 }
@@ -50,7 +61,7 @@ async fn handle_websocket(mut socket: WebSocket, coordinator: Arc<TransferCoordi
                 // Send metrics snapshot
                 let erasure_status = coordinator.adaptive_coder().status();
                 let queue_stats = coordinator.queue_stats();
-                let quic = coordinator.last_quic_stats();
+                let quic = coordinator.stats().last_quic_stats();
                 let transport_stats = coordinator.transport().stats();
 
                 let snapshot = WebSocketMessage::MetricsSnapshot(MetricsSnapshotData {
@@ -68,27 +79,49 @@ async fn handle_websocket(mut socket: WebSocket, coordinator: Arc<TransferCoordi
                     queue_depth: queue_stats.total_pending(),
                     chunks_sent: transport_stats.chunks_sent,
                     chunks_lost: quic.lost_packets,
-                    chunks_recovered: coordinator.sim_chunks_recovered(),
+                    chunks_recovered: coordinator.simulation().sim_chunks_recovered(),
                     quic_rtt_ms: quic.rtt_ms,
                     quic_loss_rate: quic.loss_rate,
                     quic_sent_packets: quic.sent_packets,
                     quic_lost_packets: quic.lost_packets,
+                    receivers: coordinator
+                        .transport()
+                        .receiver_reports()
+                        .into_iter()
+                        .map(Into::into)
+                        .collect(),
                 });
 
-                if let Ok(json) = serde_json::to_string(&snapshot) {
-                    if socket.send(Message::Text(json)).await.is_err() {
-                        return;
-                    }
+                if !send_json(&mut socket, &snapshot).await {
+                    return;
+                }
+            }
+            Some(event) = events.next() => {
+                let covered = event.event.session_id().and_then(|id| rooms.get(id));
+                if covered.is_some_and(|&covered| event.seq > covered)
+                    && !send_json(&mut socket, &WebSocketMessage::SessionEvent(event)).await
+                {
+                    return;
                 }
             }
             msg = socket.recv() => {
                 match msg {
-                    Some(Ok(Message::Text(text))) => {
-                        // Handle incoming commands (optional)
+                    Some(Ok(Message::Text(text)))
                         if text == "ping"
-                            && socket.send(Message::Text("pong".to_string())).await.is_err() {
+                            && socket.send(Message::Text("pong".to_string())).await.is_err() =>
+                    {
+                        return;
+                    }
+                    Some(Ok(Message::Text(text))) if text != "ping" => {
+                        let reply = match serde_json::from_str(&text) {
+                            Ok(command) => handle_command(&coordinator, &mut rooms, command).await,
+                            Err(e) => Some(error_message("INVALID_REQUEST", e.to_string())),
+                        };
+                        if let Some(reply) = reply {
+                            if !send_json(&mut socket, &reply).await {
                                 return;
                             }
+                        }
                     }
                     Some(Ok(Message::Close(_))) | None => {
                         break;
@@ -100,6 +133,52 @@ async fn handle_websocket(mut socket: WebSocket, coordinator: Arc<TransferCoordi
     }
 }
 
+/// Apply a client's join or leave, returning what to send back
+async fn handle_command(
+    coordinator: &TransferCoordinator,
+    rooms: &mut HashMap<String, u64>,
+    command: WebSocketClientMessage,
+) -> Option<WebSocketMessage> {
+    match command {
+        WebSocketClientMessage::Join {
+            session_id,
+            backfill,
+        } => {
+            let limit = backfill.unwrap_or(DEFAULT_ROOM_BACKFILL).min(EVENT_HISTORY);
+            match coordinator.session_backfill(&session_id, limit).await {
+                Ok(snapshot) => {
+                    rooms.insert(session_id.clone(), snapshot.last_seq);
+                    Some(WebSocketMessage::SessionSnapshot {
+                        session_id,
+                        progress: snapshot.progress.into(),
+                        events: snapshot.events,
+                    })
+                }
+                Err(e) => Some(error_message("COORDINATOR_ERROR", e.to_string())),
+            }
+        }
+        WebSocketClientMessage::Leave { session_id } => {
+            rooms.remove(&session_id);
+            None
+        }
+    }
+}
+
+fn error_message(code: &str, error: String) -> WebSocketMessage {
+    WebSocketMessage::Error(ErrorResponse {
+        error,
+        code: code.to_string(),
+    })
+}
+
+/// Send `msg` as JSON; false once the socket is gone
+async fn send_json(socket: &mut WebSocket, msg: &WebSocketMessage) -> bool {
+    match serde_json::to_string(msg) {
+        Ok(json) => socket.send(Message::Text(json)).await.is_ok(),
+        Err(_) => true,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -115,6 +194,7 @@ mod tests {
             bytes_transferred: 1000,
             total_bytes: 2000,
             current_speed_bps: 1000000,
+            failed_chunks: vec![],
         });
 
         let json = serde_json::to_string(&msg).unwrap();
@@ -132,4 +212,21 @@ mod tests {
         assert!(json.contains("TransferCompleted"));
         assert!(json.contains("test-456"));
     }
+
+    #[test]
+    fn test_client_messages_parse() {
+        let join: WebSocketClientMessage =
+            serde_json::from_str(r#"{"type":"join","data":{"session_id":"s1","backfill":10}}"#)
+                .unwrap();
+        assert!(matches!(
+            join,
+            WebSocketClientMessage::Join { session_id, backfill: Some(10) } if session_id == "s1"
+        ));
+        let join: WebSocketClientMessage =
+            serde_json::from_str(r#"{"type":"join","data":{"session_id":"s1"}}"#).unwrap();
+        assert!(matches!(
+            join,
+            WebSocketClientMessage::Join { backfill: None, .. }
+        ));
+    }
 }
@@ -0,0 +1,113 @@
+//! The transfer server as a system service
+//!
+//! Takes its sockets from systemd when socket activated, reports ready once
+//! transfers interrupted by the last stop are recovered, and on SIGTERM
+//! pauses running transfers so the next start can resume them.
+
+use chunkstream_pro::api::create_api_server;
+use chunkstream_pro::config::ConfigArgs;
+use chunkstream_pro::daemon::{self, ActivatedSockets};
+use chunkstream_pro::logging;
+use chunkstream_pro::metrics::start_metrics_server;
+use chunkstream_pro::CoordinatorBuilder;
+use tokio_util::sync::CancellationToken;
+
+#[tokio::main]
+async fn main() {
+    rustls::crypto::ring::default_provider()
+        .install_default()
+        .expect("Failed to install rustls crypto provider");
+
+    let args = ConfigArgs::from_env().unwrap_or_else(|e| exit_with("Invalid configuration", e));
+    let config = args
+        .load()
+        .unwrap_or_else(|e| exit_with("Invalid configuration", e));
+    logging::init(&config.logging).unwrap_or_else(|e| exit_with("Failed to set up logging", e));
+    let sockets =
+        ActivatedSockets::from_env().unwrap_or_else(|e| exit_with("Unusable activated sockets", e));
+    if !sockets.is_empty() {
+        tracing::info!(
+            api_inherited = sockets.api.is_some(),
+            quic_inherited = sockets.quic.is_some(),
+            "Socket activated"
+        );
+    }
+
+    if config.metrics.enabled {
+        start_metrics_server(config.metrics.metrics_config())
+            .unwrap_or_else(|e| exit_with("Failed to start metrics exporter", e));
+    }
+
+    let api_addr = config.api.bind_addr;
+    let mut builder = CoordinatorBuilder::from_config(config);
+    if let Some(socket) = sockets.quic {
+        builder = builder.quic_socket(socket);
+    }
+    let coordinator = builder
+        .build()
+        .await
+        .unwrap_or_else(|e| exit_with("Failed to build transfer coordinator", e));
+    let recovered = coordinator
+        .recover_sessions()
+        .await
+        .unwrap_or_else(|e| exit_with("Failed to recover sessions", e));
+    coordinator.receive().serve_repairs();
+
+    let listener = match sockets.api {
+        Some(listener) => listener
+            .set_nonblocking(true)
+            .and_then(|()| tokio::net::TcpListener::from_std(listener)),
+        None => tokio::net::TcpListener::bind(api_addr).await,
+    }
+    .unwrap_or_else(|e| exit_with("Failed to open the API listener", e));
+    let local_addr = listener.local_addr().ok();
+
+    if let Some(interval) = daemon::watchdog_interval() {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                let _ = daemon::notify("WATCHDOG=1");
+            }
+        });
+    }
+
+    // Pause transfers first, so their progress is saved before the API
+    // stops answering
+    let stopped = CancellationToken::new();
+    let app = create_api_server(coordinator.clone());
+    tokio::spawn({
+        let stopped = stopped.clone();
+        async move {
+            if let Err(e) = daemon::shutdown_signal().await {
+                tracing::error!(error = %e, "Can't wait for a stop signal, stopping now");
+            }
+            tracing::info!("Stopping");
+            let _ = daemon::notify_stopping();
+            match coordinator.shutdown().await {
+                Ok(paused) => tracing::info!(paused, "Transfers paused to resume after restart"),
+                Err(e) => tracing::error!(error = %e, "Pausing transfers failed"),
+            }
+            stopped.cancel();
+        }
+    });
+
+    let status = match local_addr {
+        Some(addr) => format!("API on {addr}, {recovered} interrupted transfers paused"),
+        None => format!("{recovered} interrupted transfers paused"),
+    };
+    tracing::info!(%status, "Ready");
+    if let Err(e) = daemon::notify_ready(&status) {
+        tracing::warn!(error = %e, "Could not notify the service manager");
+    }
+
+    axum::serve(listener, app)
+        .with_graceful_shutdown(stopped.cancelled_owned())
+        .await
+        .unwrap_or_else(|e| exit_with("Server error", e));
+}
+
+fn exit_with(context: &str, error: impl std::fmt::Display) -> ! {
+    eprintln!("{context}: {error}");
+    std::process::exit(2);
+}
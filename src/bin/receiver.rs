@@ -1,18 +1,41 @@
 use axum::{
-    extract::{Path as AxumPath, State},
+    extract::{
+        ws::{Message, WebSocket},
+        Path as AxumPath, State, WebSocketUpgrade,
+    },
     http::{header, StatusCode},
-    response::IntoResponse,
-    routing::get,
+    response::{IntoResponse, Response},
+    routing::{get, post},
     Json, Router,
 };
-use chunkstream_pro::chunk::{Chunk, ChunkManager, FileManifest};
-use chunkstream_pro::integrity::IntegrityVerifier;
-use chunkstream_pro::network::{ConnectionConfig, QuicTransport};
+use chunkstream_pro::chunk::{
+    ByteRange, ChunkManager, ChunkSpool, DecodeDiagnostics, FileManifest, HopStage, PartialFile,
+    Priority, ReorderConfig, SequenceAssembler,
+};
+use chunkstream_pro::config::{ConfigArgs, ConfigError};
+use chunkstream_pro::hooks::{
+    HeldFile, HookContext, HookError, HookPoint, HookRegistry, QuarantineArea, QuarantineError,
+    ReleaseStage,
+};
+use chunkstream_pro::integrity::{ChecksumType, IntegrityVerifier};
+use chunkstream_pro::logging;
+use chunkstream_pro::network::probe::is_probe_chunk;
+use chunkstream_pro::network::{
+    Capabilities, ChunkNack, ConnectionConfig, GroupFeedback, MemoryReservation, NetworkError,
+    OfferReply, PeerRate, QuicTransport, ReceivedChunk, ReceiverStats,
+};
+use chunkstream_pro::session::{InboundTransfer, SessionStore};
+use chunkstream_pro::sync::{
+    FileRepairer, RepairIndex, RetainedFile, RetentionCleaner, RetentionReport, StoredFile,
+    REPAIR_INDEX_FILE,
+};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::net::SocketAddr;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::sync::{broadcast, Mutex};
 use tower_http::cors::{Any, CorsLayer};
 
@@ -27,18 +50,25 @@ async fn main() {
     println!("║        ChunkStream Pro - File Receiver Agent                    ║");
     println!("╚══════════════════════════════════════════════════════════════════╝\n");
 
-    // Parse command line arguments
-    let args: Vec<String> = std::env::args().collect();
-    let bind_addr: SocketAddr = if args.len() > 1 {
-        args[1].parse().expect("Invalid bind address")
-    } else {
-        "0.0.0.0:5001".parse().unwrap()
-    };
+    // `--config` TOML file and RESILIENT_* overrides, as for the server;
+    // positional arguments override the bind address and save directory
+    let args = ConfigArgs::from_env().unwrap_or_else(|e| exit_with(e));
+    let config = args.load().unwrap_or_else(|e| exit_with(e));
+    if let Err(e) = logging::init(&config.logging) {
+        eprintln!("❌ Failed to set up logging: {}", e);
+        std::process::exit(2);
+    }
+    if let Some(path) = &args.path {
+        println!("⚙️  Config: {}", path.display());
+    }
 
-    let save_dir = if args.len() > 2 {
-        PathBuf::from(&args[2])
-    } else {
-        PathBuf::from("./received")
+    let bind_addr: SocketAddr = match args.rest.first() {
+        Some(addr) => addr.parse().expect("Invalid bind address"),
+        None => config.receiver.bind_addr,
+    };
+    let save_dir = match args.rest.get(1) {
+        Some(dir) => PathBuf::from(dir),
+        None => config.receiver.save_dir.clone(),
     };
 
     // Create save directory
@@ -51,17 +81,60 @@ async fn main() {
     println!("💾 Save Directory:  {}", save_dir.display());
     println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━\n");
 
+    let reorder = config.chunk.reorder_config();
+    println!(
+        "🔀 Reorder Window:  {} chunks, flushed in groups of {}",
+        reorder.window, reorder.group_size
+    );
+    let preview_partial = config.receiver.preview_partial;
+    let stats_interval = Duration::from_millis(config.receiver.stats_interval_ms);
+    if preview_partial {
+        println!("🖼️  Partial files:   written as groups complete (preview on)");
+    }
+
     // Initialize components (must match sender config)
-    let chunk_manager =
-        Arc::new(ChunkManager::new(512 * 1024, 50, 10).expect("Failed to create chunk manager"));
+    let chunk_manager = Arc::new(
+        ChunkManager::new(
+            config.chunk.chunk_size,
+            config.chunk.data_shards,
+            config.chunk.parity_shards,
+        )
+        .expect("Failed to create chunk manager")
+        .with_write_concurrency(config.chunk.write_concurrency)
+        .with_decode_workers(config.chunk.decode_workers)
+        .with_write_policy(config.chunk.write_policy)
+        .with_decode_diagnostics(true),
+    );
     let verifier = Arc::new(IntegrityVerifier);
 
-    let config = ConnectionConfig {
+    // Plugin hooks; files rejected by a quarantine-policy hook land here
+    let hooks = Arc::new(HookRegistry::with_quarantine_dir(
+        save_dir.join("quarantine"),
+    ));
+
+    // Files held back until released through the API
+    let quarantine = config.receiver.quarantine_dir.as_ref().map(|dir| {
+        Arc::new(QuarantineArea::open(dir).expect("Failed to open quarantine directory"))
+    });
+    if let Some(area) = &quarantine {
+        let held = area
+            .files()
+            .iter()
+            .filter(|f| f.stage == ReleaseStage::Held)
+            .count();
+        println!(
+            "🔐 Quarantine:      {} ({} file(s) awaiting release)",
+            area.dir().display(),
+            held
+        );
+    }
+
+    let connection = ConnectionConfig {
         bind_addr,
-        ..Default::default()
+        ..config.network.connection_config()
     };
     let transport = Arc::new(
-        QuicTransport::new(config)
+        QuicTransport::new(connection)
             .await
             .expect("Failed to create transport"),
     );
@@ -71,26 +144,96 @@ async fn main() {
     // Shared state for REST API
     let received_files: Arc<Mutex<Vec<ReceivedFileInfo>>> = Arc::new(Mutex::new(Vec::new()));
     let (tx, _rx) = broadcast::channel::<String>(100);
+    if let Some(area) = &quarantine {
+        tokio::spawn(forward_quarantine_events(area.clone(), tx.clone()));
+    }
+
+    // Files still arriving and which of their chunks are on disk, so a
+    // restarted receiver carries on instead of starting over
+    let inbound_url = format!(
+        "sqlite://{}?mode=rwc",
+        save_dir.join(INBOUND_DB_FILE).display()
+    );
+    let inbound = Arc::new(
+        SessionStore::new(&inbound_url)
+            .await
+            .expect("Failed to open inbound transfer store"),
+    );
 
     // Active transfers: session_id -> (manifest, chunks)
-    let active_transfers: Arc<Mutex<HashMap<String, (FileManifest, Vec<Chunk>)>>> =
-        Arc::new(Mutex::new(HashMap::new()));
+    let resumed = resume_inbound(&inbound, &save_dir, reorder, &transport).await;
+    if !resumed.is_empty() {
+        println!("♻️  Resumed {} partial transfer(s)\n", resumed.len());
+    }
+    let active_transfers: ActiveTransfers = Arc::new(Mutex::new(resumed));
 
-    // Start REST API server on port 8080
+    // Files already on disk, so senders can skip identical ones
+    let delivered_files: DeliveredFiles =
+        Arc::new(Mutex::new(index_existing_files(&save_dir).await));
+    println!(
+        "📚 Indexed {} existing file(s)\n",
+        delivered_files.lock().await.len()
+    );
+
+    // Verified deliveries, re-checked and patched from their sender
+    let repair_index = Arc::new(
+        RepairIndex::open(save_dir.join(REPAIR_INDEX_FILE)).expect("Failed to open repair index"),
+    );
+    let repair_interval = config.receiver.repair_interval_secs;
+    if repair_interval > 0 {
+        FileRepairer::new(repair_index.clone(), transport.clone())
+            .spawn(Duration::from_secs(repair_interval));
+        println!(
+            "🩹 Repair:          {} file(s) re-verified every {}s\n",
+            repair_index.len(),
+            repair_interval
+        );
+    }
+
+    // Delivered files, deleted once past the retention limits
+    let retention = Arc::new(
+        RetentionCleaner::open(&save_dir, config.receiver.retention.clone())
+            .expect("Failed to open retention index"),
+    );
+    if retention.policy().is_enabled() {
+        tokio::spawn(forward_retention_events(
+            retention.clone(),
+            tx.clone(),
+            received_files.clone(),
+            delivered_files.clone(),
+            repair_index.clone(),
+        ));
+        retention.clone().spawn();
+        println!(
+            "🧹 Retention:       checked every {}s\n",
+            retention.policy().interval_secs
+        );
+    }
+
+    // Start REST API server
+    let api_addr = config.receiver.api_addr;
     let api_state = ReceiverApiState {
         received_files: received_files.clone(),
         save_dir: save_dir.clone(),
         bind_addr,
         tx: tx.clone(),
+        hooks: hooks.clone(),
+        active_transfers: active_transfers.clone(),
+        inbound: inbound.clone(),
+        quarantine: quarantine.clone(),
+        delivered_files: delivered_files.clone(),
+        repair_index: repair_index.clone(),
+        retention: retention.clone(),
+        transport: transport.clone(),
     };
 
     tokio::spawn(async move {
-        if let Err(e) = start_api_server(api_state).await {
+        if let Err(e) = start_api_server(api_state, api_addr).await {
             eprintln!("❌ Failed to start API server: {}", e);
         }
     });
 
-    println!("🌐 REST API running on http://0.0.0.0:8080\n");
+    println!("🌐 REST API running on http://{}\n", api_addr);
 
     // Accept connections loop
     loop {
@@ -106,7 +249,22 @@ async fn main() {
                 let active_transfers_clone = active_transfers.clone();
                 let received_files_clone = received_files.clone();
                 let tx_clone = tx.clone();
+                let hooks_clone = hooks.clone();
+
+                // Answer file offers alongside the chunk streams
+                let stats = StatsReporter::new(stats_interval);
+                tokio::spawn(answer_offers(
+                    conn.clone(),
+                    delivered_files.clone(),
+                    transport.max_chunk_size(),
+                    stats.wanted.clone(),
+                ));
 
+                let delivered_clone = delivered_files.clone();
+                let repair_index_clone = repair_index.clone();
+                let inbound_clone = inbound.clone();
+                let quarantine_clone = quarantine.clone();
+                let retention_clone = retention.clone();
                 tokio::spawn(async move {
                     if let Err(e) = handle_transfer(
                         conn,
@@ -117,6 +275,15 @@ async fn main() {
                         active_transfers_clone,
                         received_files_clone,
                         tx_clone,
+                        hooks_clone,
+                        delivered_clone,
+                        repair_index_clone,
+                        inbound_clone,
+                        quarantine_clone,
+                        retention_clone,
+                        reorder,
+                        preview_partial,
+                        stats,
                     )
                     .await
                     {
@@ -132,41 +299,380 @@ async fn main() {
     }
 }
 
+/// Quiet time on a connection after which the sender is told which shards
+/// arrived, so it can resend what parity can't cover
+const GROUP_REPORT_IDLE: Duration = Duration::from_millis(500);
+
+/// A transfer whose chunks are still arriving
+struct PendingTransfer {
+    manifest: FileManifest,
+    /// Out-of-order chunks waiting for the rest of their group
+    assembler: SequenceAssembler,
+    /// Groups already flushed to disk
+    spool: ChunkSpool,
+    /// Memory charged for the chunks the assembler holds
+    memory: MemoryReservation,
+    /// Output file being filled in as groups complete, when previews are on
+    preview: Option<PartialFile>,
+    /// Shards that failed verification, until a good copy arrives
+    corrupt: BTreeSet<u32>,
+}
+
+impl PendingTransfer {
+    /// Which shards of the file's FEC group have arrived
+    fn group_feedback(&self) -> GroupFeedback {
+        GroupFeedback {
+            file_id: self.manifest.file_id.clone(),
+            data_chunks: self.manifest.data_chunks,
+            total_chunks: self.manifest.total_chunks,
+            received: self.assembler.received_sequences(),
+        }
+    }
+
+    /// Which shards are missing or corrupt, as data or parity
+    fn diagnostics(&self) -> DecodeDiagnostics {
+        DecodeDiagnostics::new(
+            &self.manifest,
+            self.assembler.received_sequences(),
+            self.corrupt.iter().copied(),
+        )
+    }
+}
+
+/// What this end has seen of one connection, reported to its sender
+struct StatsReporter {
+    stats: ReceiverStats,
+    sent: ReceiverStats,
+    last_sent: Instant,
+    interval: Duration,
+    /// Set once an offer shows the sender takes stats reports
+    wanted: Arc<AtomicBool>,
+}
+
+impl StatsReporter {
+    fn new(interval: Duration) -> Self {
+        Self {
+            stats: ReceiverStats::default(),
+            sent: ReceiverStats::default(),
+            last_sent: Instant::now(),
+            interval,
+            wanted: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Send the counts if they changed and the interval has passed, or
+    /// straight away with `now`
+    async fn report(&mut self, transport: &QuicTransport, conn: &quinn::Connection, now: bool) {
+        if self.interval.is_zero()
+            || !self.wanted.load(Ordering::Relaxed)
+            || self.stats == self.sent
+            || (!now && self.last_sent.elapsed() < self.interval)
+        {
+            return;
+        }
+        self.sent = self.stats;
+        self.last_sent = Instant::now();
+        if let Err(e) = transport.send_receiver_stats(conn, &self.stats).await {
+            eprintln!("   ⚠️  Could not send stats report: {}", e);
+        }
+    }
+}
+
+/// In-flight transfers keyed by session id
+type ActiveTransfers = Arc<Mutex<HashMap<String, PendingTransfer>>>;
+
+/// Database of inbound transfers, in the save directory
+const INBOUND_DB_FILE: &str = ".inbound.db";
+
+/// Where the chunks of a transfer are spooled until it can be rebuilt
+fn spool_path(save_dir: &std::path::Path, transfer_id: &str) -> PathBuf {
+    let safe_filename = transfer_id.replace(['/', '\\', ':'], "_");
+    save_dir
+        .join(".partial")
+        .join(format!("{}.spool", safe_filename))
+}
+
+/// Pick up the transfers a previous run left unfinished
+///
+/// Chunks recorded as on disk are not asked for again. A transfer whose
+/// spool is gone is forgotten and starts over when its chunks next arrive.
+async fn resume_inbound(
+    inbound: &SessionStore,
+    save_dir: &std::path::Path,
+    reorder: ReorderConfig,
+    transport: &QuicTransport,
+) -> HashMap<String, PendingTransfer> {
+    let mut resumed = HashMap::new();
+    let transfers = match inbound.list_inbound().await {
+        Ok(transfers) => transfers,
+        Err(e) => {
+            eprintln!("⚠️  Could not read inbound transfers: {}", e);
+            return resumed;
+        }
+    };
+    for transfer in transfers {
+        let spool = match ChunkSpool::open(spool_path(save_dir, &transfer.transfer_id)).await {
+            Ok(spool) => spool,
+            Err(e) => {
+                eprintln!(
+                    "⚠️  Dropping partial transfer {}: {}",
+                    transfer.transfer_id, e
+                );
+                let _ = inbound.delete_inbound(&transfer.transfer_id).await;
+                continue;
+            }
+        };
+        println!(
+            "   ♻️  {}: {}/{} chunks on disk, {} more needed",
+            transfer.transfer_id,
+            transfer.received(),
+            transfer.manifest.total_chunks,
+            transfer.shortfall()
+        );
+        resumed.insert(
+            transfer.transfer_id.clone(),
+            PendingTransfer {
+                assembler: SequenceAssembler::resume(
+                    transfer.manifest.total_chunks,
+                    reorder,
+                    &transfer.received_sequences(),
+                ),
+                manifest: transfer.manifest,
+                spool,
+                memory: transport.memory_budget().empty_reservation(),
+                preview: None,
+                corrupt: BTreeSet::new(),
+            },
+        );
+    }
+    resumed
+}
+
+/// Drop a transfer and everything kept for it
+async fn discard_transfer(
+    transfers: &mut HashMap<String, PendingTransfer>,
+    inbound: &SessionStore,
+    transfer_id: &str,
+) {
+    if let Some(transfer) = transfers.remove(transfer_id) {
+        let _ = transfer.spool.remove().await;
+    }
+    if let Err(e) = inbound.delete_inbound(transfer_id).await {
+        eprintln!("   ⚠️  Could not forget inbound transfer: {}", e);
+    }
+}
+
+/// Every complete file in the save directory, found by checksum
+///
+/// Offers name their checksum algorithm, so a file is only hashed with an
+/// algorithm once an offer asks for it.
+#[derive(Default)]
+struct DeliveredIndex {
+    /// Each file and the algorithms it has been hashed with
+    files: HashMap<PathBuf, HashSet<ChecksumType>>,
+    by_checksum: HashMap<(ChecksumType, [u8; 32]), PathBuf>,
+}
+
+type DeliveredFiles = Arc<Mutex<DeliveredIndex>>;
+
+impl DeliveredIndex {
+    fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    /// Track a file not yet hashed with any algorithm
+    fn add(&mut self, path: PathBuf) {
+        self.files.entry(path).or_default();
+    }
+
+    /// Track a delivered file whose checksum under `algorithm` is known,
+    /// replacing anything known about an earlier file at its path
+    fn record(&mut self, path: PathBuf, algorithm: ChecksumType, checksum: [u8; 32]) {
+        self.remove(&path);
+        self.insert(path, algorithm, checksum);
+    }
+
+    fn insert(&mut self, path: PathBuf, algorithm: ChecksumType, checksum: [u8; 32]) {
+        self.files
+            .entry(path.clone())
+            .or_default()
+            .insert(algorithm);
+        self.by_checksum.insert((algorithm, checksum), path);
+    }
+
+    fn remove(&mut self, path: &Path) {
+        self.files.remove(path);
+        self.by_checksum.retain(|_, p| p != path);
+    }
+
+    /// File whose `algorithm` checksum is `checksum`, first hashing the
+    /// files not yet hashed with `algorithm`
+    async fn find(&mut self, algorithm: ChecksumType, checksum: &[u8; 32]) -> Option<PathBuf> {
+        let unhashed: Vec<PathBuf> = self
+            .files
+            .iter()
+            .filter(|(_, hashed)| !hashed.contains(&algorithm))
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in unhashed {
+            match IntegrityVerifier::calculate_file_checksum_with(&path, algorithm).await {
+                Ok(digest) => self.insert(path, algorithm, digest),
+                // Gone or unreadable; nothing to match against
+                Err(_) => self.remove(&path),
+            }
+        }
+        self.by_checksum.get(&(algorithm, *checksum)).cloned()
+    }
+}
+
+/// List the files already in the save directory; they are hashed once an
+/// offer asks about them
+async fn index_existing_files(save_dir: &Path) -> DeliveredIndex {
+    let mut index = DeliveredIndex::default();
+    let Ok(mut entries) = tokio::fs::read_dir(save_dir).await else {
+        return index;
+    };
+
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let path = entry.path();
+        // Dotfiles are the receiver's own bookkeeping, like the repair index
+        let hidden = entry.file_name().to_string_lossy().starts_with('.');
+        if hidden || !path.is_file() {
+            continue;
+        }
+        index.add(path);
+    }
+    index
+}
+
+/// Reply "already have it" to offers for files we hold an identical copy of,
+/// and turn away offers whose chunks are over `max_chunk_size`
+async fn answer_offers(
+    conn: quinn::Connection,
+    delivered_files: DeliveredFiles,
+    max_chunk_size: usize,
+    stats_wanted: Arc<AtomicBool>,
+) {
+    while let Ok((offer, send_stream)) = QuicTransport::accept_offer(&conn).await {
+        if offer.capabilities.contains(Capabilities::RECEIVER_STATS) {
+            stats_wanted.store(true, Ordering::Relaxed);
+        }
+        let existing = delivered_files
+            .lock()
+            .await
+            .find(offer.checksum_algorithm, &offer.checksum)
+            .await;
+        let reply = match existing {
+            Some(path) if path.exists() => {
+                println!(
+                    "   ⏭️  Already have {} ({}), skipping transfer",
+                    offer.filename,
+                    path.display()
+                );
+                OfferReply::AlreadyHave
+            }
+            _ => offer
+                .negotiate_chunk_size(max_chunk_size, OfferReply::Accept(Capabilities::local())),
+        };
+        if let OfferReply::ChunkTooLarge { max_chunk_size } = reply {
+            println!(
+                "   🚫 Refusing {}: its {} byte chunks exceed our {} byte limit",
+                offer.filename, offer.max_chunk_size, max_chunk_size
+            );
+        }
+
+        if let Err(e) = QuicTransport::answer_offer(send_stream, reply).await {
+            eprintln!("   ⚠️  Failed to answer file offer: {}", e);
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 async fn handle_transfer(
     conn: quinn::Connection,
     transport: Arc<QuicTransport>,
     chunk_manager: Arc<ChunkManager>,
     _verifier: Arc<IntegrityVerifier>,
     save_dir: PathBuf,
-    active_transfers: Arc<Mutex<HashMap<String, (FileManifest, Vec<Chunk>)>>>,
+    active_transfers: ActiveTransfers,
     received_files: Arc<Mutex<Vec<ReceivedFileInfo>>>,
     tx: broadcast::Sender<String>,
+    hooks: Arc<HookRegistry>,
+    delivered_files: DeliveredFiles,
+    repair_index: Arc<RepairIndex>,
+    inbound: Arc<SessionStore>,
+    quarantine: Option<Arc<QuarantineArea>>,
+    retention: Arc<RetentionCleaner>,
+    reorder: ReorderConfig,
+    preview_partial: bool,
+    mut stats: StatsReporter,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let remote_addr = conn.remote_address();
     println!("   📦 Receiving chunks from {}...", remote_addr);
 
     let mut session_id: Option<String> = None;
     let mut chunk_count = 0;
+    // How this receiver appears in chunk traces
+    let node = transport
+        .local_addr()
+        .map_or_else(|_| "receiver".to_string(), |addr| addr.to_string());
+    // Chunks received when the sender was last sent a group report
+    let mut reported: Option<u64> = None;
 
     // Receive all chunks from this connection
     loop {
-        match conn.accept_uni().await {
+        // Time spent paused for memory or for the sender's receive rate
+        // doesn't count as the sender idling
+        transport.memory_budget().wait_for_capacity().await;
+        transport.peer_shaper().wait_turn(remote_addr).await;
+        stats.report(&transport, &conn, false).await;
+        let accepted = match tokio::time::timeout(GROUP_REPORT_IDLE, conn.accept_uni()).await {
+            Ok(accepted) => accepted,
+            Err(_) => {
+                // The sender has gone quiet: tell it what's still
+                // missing, once per change
+                let Some(id) = session_id.as_ref() else {
+                    continue;
+                };
+                let transfers = active_transfers.lock().await;
+                let Some(entry) = transfers.get(id) else {
+                    continue;
+                };
+                let received = entry.assembler.received();
+                if reported != Some(received) {
+                    reported = Some(received);
+                    let feedback = entry.group_feedback();
+                    println!(
+                        "   📨 Reporting {}/{} shards to sender ({} needed)",
+                        received, feedback.total_chunks, feedback.data_chunks
+                    );
+                    if let Err(e) = transport.send_group_feedback(&conn, &feedback).await {
+                        eprintln!("   ⚠️  Could not send group report: {}", e);
+                    }
+                }
+                continue;
+            }
+        };
+        match accepted {
             Ok(recv_stream) => {
                 // Receive chunk
-                match transport.receive_chunk(recv_stream).await {
-                    Ok(chunk) => {
+                // Chunks are checked here, before they are buffered
+                let received = transport.receive_verified_chunk(recv_stream).await;
+                if let Ok(received) = &received {
+                    transport
+                        .peer_shaper()
+                        .charge(remote_addr, received.chunk.data.len());
+                }
+                match received {
+                    Ok(received) if is_probe_chunk(&received.chunk) => {
+                        // Link probe traffic; receiving it is all that's needed
+                        continue;
+                    }
+                    Ok(ReceivedChunk { mut chunk, memory }) => {
                         chunk_count += 1;
-
-                        // Verify chunk integrity
-                        let calculated_checksum =
-                            IntegrityVerifier::calculate_checksum(&chunk.data);
-                        if calculated_checksum != chunk.metadata.checksum {
-                            eprintln!(
-                                "   ⚠️  Chunk {} failed verification!",
-                                chunk.metadata.sequence_number
-                            );
-                            continue;
-                        }
+                        stats.stats.chunks_received += 1;
+                        stats.stats.bytes_received += chunk.data.len() as u64;
+                        chunk.metadata.record_hop(&node, HopStage::Received);
 
                         let chunk_session_id = chunk.metadata.file_id.clone();
 
@@ -174,6 +680,9 @@ async fn handle_transfer(
                         if session_id.is_none() {
                             session_id = Some(chunk_session_id.clone());
                             println!("   📋 Transfer ID: {}", chunk_session_id);
+                            if let Some(trace_id) = chunk.metadata.trace_id() {
+                                println!("   🔎 Trace ID: {}", trace_id);
+                            }
                         }
 
                         println!(
@@ -182,72 +691,277 @@ async fn handle_transfer(
                             chunk.metadata.total_chunks,
                             format_bytes(chunk.data.len())
                         );
+                        // Relayed chunks show the path they took
+                        if let Some(trace) = chunk
+                            .metadata
+                            .trace
+                            .as_ref()
+                            .filter(|t| t.hops.iter().any(|hop| hop.stage == HopStage::Relayed))
+                        {
+                            println!("     ↪ {}", trace);
+                        }
 
                         // Store chunk
                         let mut transfers = active_transfers.lock().await;
-                        let entry =
-                            transfers
-                                .entry(chunk_session_id.clone())
-                                .or_insert_with(|| {
-                                    // Create manifest from chunk metadata
-                                    let manifest = FileManifest {
-                                        file_id: chunk.metadata.file_id.clone(),
-                                        filename: format!("file_{}", chunk.metadata.file_id),
-                                        total_size: chunk.metadata.file_size, // From chunk metadata
-                                        chunk_size: chunk.data.len(),
-                                        total_chunks: chunk.metadata.total_chunks,
-                                        data_chunks: chunk.metadata.data_chunks, // From chunk metadata
-                                        parity_chunks: chunk.metadata.total_chunks
-                                            - chunk.metadata.data_chunks,
-                                        checksum: chunk.metadata.file_checksum, // From chunk metadata
-                                        priority: chunk.metadata.priority,
-                                    };
-                                    (manifest, Vec::new())
-                                });
-
-                        entry.1.push(chunk.clone());
+                        let safe_filename = chunk_session_id.replace(['/', '\\', ':'], "_");
+                        if !transfers.contains_key(&chunk_session_id) {
+                            // Create manifest from chunk metadata
+                            let manifest = FileManifest {
+                                file_id: chunk.metadata.file_id.clone(),
+                                filename: format!("file_{}", chunk.metadata.file_id),
+                                total_size: chunk.metadata.file_size, // From chunk metadata
+                                chunk_size: chunk.data.len(),
+                                total_chunks: chunk.metadata.total_chunks,
+                                data_chunks: chunk.metadata.data_chunks, // From chunk metadata
+                                parity_chunks: chunk.metadata.total_chunks
+                                    - chunk.metadata.data_chunks,
+                                checksum: chunk.metadata.file_checksum, // From chunk metadata
+                                priority: chunk.metadata.priority,
+                                zero_runs: chunk.metadata.zero_runs.clone(),
+                                attributes: chunk.metadata.attributes.clone(),
+                                checksum_algorithm: chunk.metadata.checksum_algorithm,
+                                erasure_profile: Default::default(),
+                                schedule: None,
+                                merkle_root: None,
+                                compression: chunk.metadata.compression,
+                            };
+                            let spool =
+                                ChunkSpool::create(spool_path(&save_dir, &chunk_session_id))
+                                    .await?;
+                            let record = InboundTransfer::new(
+                                chunk_session_id.clone(),
+                                manifest.clone(),
+                                reorder.group_size,
+                            );
+                            if let Err(e) = inbound.save_inbound(&record).await {
+                                eprintln!("   ⚠️  Could not record inbound transfer: {}", e);
+                            }
+                            let preview = if preview_partial {
+                                let path = save_dir.join(format!("received_{}", safe_filename));
+                                match PartialFile::create(&manifest, &path).await {
+                                    Ok(preview) => {
+                                        println!("   🖼️  Preview: {}", path.display());
+                                        Some(preview)
+                                    }
+                                    Err(e) => {
+                                        eprintln!("   ⚠️  Could not create preview file: {}", e);
+                                        None
+                                    }
+                                }
+                            } else {
+                                None
+                            };
+                            transfers.insert(
+                                chunk_session_id.clone(),
+                                PendingTransfer {
+                                    assembler: SequenceAssembler::new(
+                                        manifest.total_chunks,
+                                        reorder,
+                                    ),
+                                    manifest,
+                                    spool,
+                                    memory: transport.memory_budget().empty_reservation(),
+                                    preview,
+                                    corrupt: BTreeSet::new(),
+                                },
+                            );
+                        }
+                        let Some(entry) = transfers.get_mut(&chunk_session_id) else {
+                            continue;
+                        };
+
+                        // Relays may deliver replicated copies of a chunk
+                        if !entry.assembler.insert(chunk) {
+                            continue;
+                        }
+                        // The chunk stays charged until it reaches the spool
+                        entry.memory.merge(memory);
+
+                        // Complete groups go to disk; the rest count against
+                        // the receive budget until they do
+                        let ready = entry.assembler.take_ready();
+                        if let Err(e) = entry.spool.append(&ready).await {
+                            eprintln!("   ❌ Failed to spool chunks: {}", e);
+                            discard_transfer(&mut transfers, &inbound, &chunk_session_id).await;
+                            break;
+                        }
+                        // Only chunks on disk survive a restart
+                        if !ready.is_empty() {
+                            let spooled: Vec<u32> =
+                                ready.iter().map(|c| c.metadata.sequence_number).collect();
+                            if let Err(e) = inbound
+                                .record_inbound_chunks(&chunk_session_id, &spooled)
+                                .await
+                            {
+                                eprintln!("   ⚠️  Could not record spooled chunks: {}", e);
+                            }
+                        }
+                        entry.memory.resize(entry.assembler.buffered_bytes());
+
+                        // The preview is a convenience; if it can't be
+                        // written the transfer carries on without it
+                        if let Some(preview) = entry.preview.as_mut() {
+                            if let Err(e) = preview.write(&ready).await {
+                                eprintln!("   ⚠️  Preview stopped: {}", e);
+                                entry.preview = None;
+                            }
+                        }
 
                         // Check if we have enough chunks to reconstruct
-                        let (manifest, chunks) = entry;
-                        if chunks.len() >= manifest.data_chunks as usize {
+                        let manifest = &entry.manifest;
+                        let received = entry.assembler.received();
+                        if received >= manifest.data_chunks as u64 {
                             // Need at least data_chunks
                             println!(
                                 "\n   🎯 Received {} chunks - attempting reconstruction...",
-                                chunks.len()
+                                received
                             );
 
-                            // Try to reconstruct - sanitize file_id to use as filename
-                            let safe_filename = manifest
-                                .file_id
-                                .replace('/', "_")
-                                .replace('\\', "_")
-                                .replace(':', "_");
+                            let mut chunks = match entry.spool.read_all().await {
+                                Ok(chunks) => chunks,
+                                Err(e) => {
+                                    eprintln!("   ❌ Failed to read spooled chunks: {}", e);
+                                    break;
+                                }
+                            };
+                            chunks.extend(entry.assembler.buffered().cloned());
+
                             let output_filename = format!("received_{}", safe_filename);
-                            let output_path = save_dir.join(output_filename);
+                            let output_path = match &quarantine {
+                                Some(area) => area.path_for(&output_filename),
+                                None => save_dir.join(&output_filename),
+                            };
 
-                            match chunk_manager
-                                .reconstruct_file(manifest, chunks.clone(), &output_path)
+                            // With a preview open, rebuild beside it and swap
+                            // in only a verified file, so a failed attempt
+                            // doesn't clobber the ranges viewers rely on
+                            let rebuild_path = match entry.preview {
+                                Some(_) => save_dir
+                                    .join(".partial")
+                                    .join(format!("{}.rebuild", safe_filename)),
+                                None => output_path.clone(),
+                            };
+                            let rebuilt = match chunk_manager
+                                .reconstruct_file(manifest, chunks, &rebuild_path)
                                 .await
                             {
+                                Ok(()) if rebuild_path != output_path => {
+                                    tokio::fs::rename(&rebuild_path, &output_path)
+                                        .await
+                                        .map_err(|e| e.into())
+                                }
+                                rebuilt => rebuilt,
+                            };
+
+                            match rebuilt {
                                 Ok(_) => {
                                     println!("   ✅ File reconstructed successfully!");
-                                    println!("   💾 Saved to: {}", output_path.display());
+                                    stats.stats.decode_successes += 1;
+
+                                    if let Some(area) = &quarantine {
+                                        let held = HeldFile {
+                                            id: safe_filename.clone(),
+                                            file_id: manifest.file_id.clone(),
+                                            filename: output_filename.clone(),
+                                            path: output_path.clone(),
+                                            size: manifest.total_size,
+                                            checksum: manifest.checksum,
+                                            checksum_algorithm: manifest.checksum_algorithm,
+                                            verified: false,
+                                            source: remote_addr,
+                                            block_size: manifest.chunk_size,
+                                            stage: ReleaseStage::Quarantined,
+                                            reason: None,
+                                            updated_at: 0,
+                                        };
+                                        if let Err(e) = area.admit(held).await {
+                                            eprintln!(
+                                                "   ⚠️  Could not record quarantined file: {}",
+                                                e
+                                            );
+                                        }
+                                    }
+
+                                    // Run scanners before the file is exposed
+                                    let hook_ctx = HookContext::new(
+                                        HookPoint::AfterReconstruct,
+                                        manifest.file_id.clone(),
+                                    )
+                                    .with_path(&output_path)
+                                    .with_session(chunk_session_id.clone())
+                                    .with_manifest(manifest.clone());
+                                    if let Err(e) = hooks.run(&hook_ctx).await {
+                                        eprintln!("   🚫 File blocked by hook: {}", e);
+                                        let moved_to = match &e {
+                                            HookError::Quarantined { path, .. } => path.clone(),
+                                            _ => None,
+                                        };
+                                        if let Some(area) = &quarantine {
+                                            if let Err(e) = area
+                                                .reject(&safe_filename, e.to_string(), moved_to)
+                                                .await
+                                            {
+                                                eprintln!(
+                                                    "   ⚠️  Could not record rejection: {}",
+                                                    e
+                                                );
+                                            }
+                                        } else if moved_to.is_none() {
+                                            // Never leave a blocked file among
+                                            // the received ones
+                                            if let Err(e) =
+                                                tokio::fs::remove_file(&output_path).await
+                                            {
+                                                eprintln!(
+                                                    "   ⚠️  Could not remove blocked file: {}",
+                                                    e
+                                                );
+                                            }
+                                        }
+                                        discard_transfer(
+                                            &mut transfers,
+                                            &inbound,
+                                            &chunk_session_id,
+                                        )
+                                        .await;
+                                        break;
+                                    }
+
+                                    match &quarantine {
+                                        Some(_) => println!(
+                                            "   🔐 Held in quarantine: {} (release with POST /api/v1/received/{}/release)",
+                                            output_path.display(),
+                                            safe_filename
+                                        ),
+                                        None => println!("   💾 Saved to: {}", output_path.display()),
+                                    }
                                     println!(
                                         "   📊 Total chunks used: {} (out of {} received)",
-                                        manifest.data_chunks,
-                                        chunks.len()
+                                        manifest.data_chunks, received
                                     );
+                                    let reorder = entry.assembler.stats();
+                                    if reorder.reordered > 0 {
+                                        println!(
+                                            "   🔀 {} chunks out of order (max depth {}, mean {:.1}, peak {} buffered)",
+                                            reorder.reordered,
+                                            reorder.max_depth,
+                                            reorder.mean_depth(),
+                                            reorder.peak_buffered
+                                        );
+                                    }
 
                                     // Calculate and display reconstructed file info
                                     if let Ok(file_data) = tokio::fs::read(&output_path).await {
-                                        let reconstructed_checksum =
-                                            IntegrityVerifier::calculate_checksum(&file_data);
                                         println!("   📏 File size: {} bytes", file_data.len());
 
                                         // Only verify if we have a real checksum from sender
                                         let zero_checksum = [0u8; 32];
                                         let verified = if manifest.checksum != zero_checksum {
-                                            if reconstructed_checksum == manifest.checksum {
+                                            if IntegrityVerifier::verify_file_data(
+                                                &file_data, manifest,
+                                            )
+                                            .is_ok()
+                                            {
                                                 println!("   🔒 File integrity verified! ✓");
                                                 true
                                             } else {
@@ -259,8 +973,21 @@ async fn handle_transfer(
                                             false
                                         };
 
+                                        let stage = match &quarantine {
+                                            Some(area) => area
+                                                .hold(&safe_filename, verified)
+                                                .await
+                                                .map(|held| held.stage)
+                                                .unwrap_or_else(|e| {
+                                                    eprintln!("   ⚠️  Could not hold quarantined file: {}", e);
+                                                    ReleaseStage::Quarantined
+                                                }),
+                                            None => ReleaseStage::Released,
+                                        };
+
                                         // Add to received files list
                                         let file_info = ReceivedFileInfo {
+                                            id: safe_filename.clone(),
                                             filename: output_path
                                                 .file_name()
                                                 .unwrap_or_default()
@@ -270,9 +997,31 @@ async fn handle_transfer(
                                             received_at: chrono::Utc::now().to_rfc3339(),
                                             verified,
                                             path: output_path.to_string_lossy().to_string(),
+                                            stage,
+                                            priority: manifest.priority,
                                         };
 
                                         received_files.lock().await.push(file_info.clone());
+                                        if stage == ReleaseStage::Released {
+                                            retain_file(&retention, &file_info).await;
+                                        }
+                                        // Held files are indexed once released
+                                        if verified && stage == ReleaseStage::Released {
+                                            record_delivery(
+                                                &delivered_files,
+                                                &repair_index,
+                                                StoredFile {
+                                                    path: output_path.clone(),
+                                                    file_id: manifest.file_id.clone(),
+                                                    checksum: manifest.checksum,
+                                                    checksum_algorithm: manifest.checksum_algorithm,
+                                                    source: remote_addr,
+                                                    block_size: manifest.chunk_size,
+                                                    verified_at: chrono::Utc::now().timestamp(),
+                                                },
+                                            )
+                                            .await;
+                                        }
 
                                         // Notify via broadcast
                                         let _ = tx.send(
@@ -280,16 +1029,53 @@ async fn handle_transfer(
                                         );
                                     }
 
+                                    // Let the sender stop waiting for losses
+                                    stats.report(&transport, &conn, true).await;
+                                    let feedback = entry.group_feedback();
+                                    if let Err(e) =
+                                        transport.send_group_feedback(&conn, &feedback).await
+                                    {
+                                        eprintln!("   ⚠️  Could not send group report: {}", e);
+                                    }
+
                                     // Clean up
-                                    transfers.remove(&chunk_session_id);
+                                    discard_transfer(&mut transfers, &inbound, &chunk_session_id)
+                                        .await;
                                     break;
                                 }
                                 Err(e) => {
+                                    stats.stats.decode_failures += 1;
                                     println!("   ⏳ Waiting for more chunks... (error: {})", e);
                                 }
                             }
                         }
                     }
+                    Err(NetworkError::CorruptChunk {
+                        file_id,
+                        sequence_number,
+                    }) => {
+                        eprintln!(
+                            "   ⚠️  Chunk {} failed verification, requesting resend",
+                            sequence_number
+                        );
+                        stats.stats.checksum_failures += 1;
+                        if let Some(entry) = active_transfers.lock().await.get_mut(&file_id) {
+                            entry.corrupt.insert(sequence_number);
+                        }
+                        let nack = ChunkNack {
+                            file_id,
+                            sequence_number,
+                        };
+                        if let Err(e) = transport.send_nack(&conn, &nack).await {
+                            eprintln!("   ⚠️  Could not send NACK: {}", e);
+                        }
+                        continue;
+                    }
+                    Err(e @ NetworkError::MemoryBudgetExceeded { .. }) => {
+                        // Drop this chunk; parity or a resend can cover it
+                        eprintln!("   ⚠️  {}", e);
+                        continue;
+                    }
                     Err(e) => {
                         eprintln!("   ❌ Failed to receive chunk: {}", e);
                         break;
@@ -312,6 +1098,79 @@ async fn handle_transfer(
     Ok(())
 }
 
+/// Make a verified file in the save directory known to offers and repairs
+async fn record_delivery(
+    delivered_files: &DeliveredFiles,
+    repair_index: &RepairIndex,
+    stored: StoredFile,
+) {
+    delivered_files.lock().await.record(
+        stored.path.clone(),
+        stored.checksum_algorithm,
+        stored.checksum,
+    );
+    if let Err(e) = repair_index.record(stored).await {
+        eprintln!("   ⚠️  Could not add file to repair index: {}", e);
+    }
+}
+
+/// Age a file in the save directory from now under the retention policy
+async fn retain_file(retention: &RetentionCleaner, info: &ReceivedFileInfo) {
+    let Some(filename) = std::path::Path::new(&info.path).file_name() else {
+        return;
+    };
+    let retained = RetainedFile::now(info.priority);
+    if let Err(e) = retention
+        .record(&filename.to_string_lossy(), retained)
+        .await
+    {
+        eprintln!("   ⚠️  Could not add file to retention index: {}", e);
+    }
+}
+
+/// Pass deletions on to event subscribers and forget the deleted files
+async fn forward_retention_events(
+    retention: Arc<RetentionCleaner>,
+    tx: broadcast::Sender<String>,
+    received_files: Arc<Mutex<Vec<ReceivedFileInfo>>>,
+    delivered_files: DeliveredFiles,
+    repair_index: Arc<RepairIndex>,
+) {
+    let mut events = retention.subscribe();
+    loop {
+        match events.recv().await {
+            Ok(event) => {
+                println!(
+                    "   🧹 Deleted {} ({:?})",
+                    event.path.display(),
+                    event.reason
+                );
+                let path = event.path.to_string_lossy().to_string();
+                received_files.lock().await.retain(|f| f.path != path);
+                delivered_files.lock().await.remove(&event.path);
+                let _ = repair_index.remove(&event.path).await;
+                let _ = tx.send(serde_json::to_string(&event).unwrap_or_default());
+            }
+            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(broadcast::error::RecvError::Closed) => return,
+        }
+    }
+}
+
+/// Pass quarantine stage changes on to event subscribers
+async fn forward_quarantine_events(area: Arc<QuarantineArea>, tx: broadcast::Sender<String>) {
+    let mut events = area.subscribe();
+    loop {
+        match events.recv().await {
+            Ok(event) => {
+                let _ = tx.send(serde_json::to_string(&event).unwrap_or_default());
+            }
+            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(broadcast::error::RecvError::Closed) => return,
+        }
+    }
+}
+
 fn format_bytes(bytes: usize) -> String {
     if bytes < 1024 {
         format!("{} B", bytes)
@@ -325,11 +1184,16 @@ fn format_bytes(bytes: usize) -> String {
 // REST API types
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct ReceivedFileInfo {
+    /// Transfer id, as used in the release URL
+    id: String,
     filename: String,
     size: u64,
     received_at: String,
     verified: bool,
     path: String,
+    /// `held` until released when quarantine is on
+    stage: ReleaseStage,
+    priority: Priority,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -343,18 +1207,137 @@ struct ReceiverStatus {
 #[derive(Clone)]
 struct ReceiverApiState {
     received_files: Arc<Mutex<Vec<ReceivedFileInfo>>>,
-    #[allow(dead_code)]
     save_dir: PathBuf,
     bind_addr: SocketAddr,
-    #[allow(dead_code)]
     tx: broadcast::Sender<String>,
+    hooks: Arc<HookRegistry>,
+    active_transfers: ActiveTransfers,
+    inbound: Arc<SessionStore>,
+    quarantine: Option<Arc<QuarantineArea>>,
+    delivered_files: DeliveredFiles,
+    repair_index: Arc<RepairIndex>,
+    retention: Arc<RetentionCleaner>,
+    transport: Arc<QuicTransport>,
+}
+
+/// Which parts of an in-progress file can be read
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PartialFileInfo {
+    /// Id used in the ranges URL
+    transfer_id: String,
+    filename: String,
+    path: String,
+    total_size: u64,
+    valid_bytes: u64,
+    /// Received data and known zeros, sorted; everything else is a hole
+    ranges: Vec<ByteRange>,
+}
+
+impl PartialFileInfo {
+    fn new(key: &str, preview: &PartialFile) -> Self {
+        Self {
+            transfer_id: key.replace(['/', '\\', ':'], "_"),
+            filename: preview
+                .path()
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string(),
+            path: preview.path().to_string_lossy().to_string(),
+            total_size: preview.total_size(),
+            valid_bytes: preview.valid_bytes(),
+            ranges: preview.valid_ranges().to_vec(),
+        }
+    }
+}
+
+/// What an in-progress transfer still needs before it decodes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TransferDiagnostics {
+    /// Id used in the diagnostics URL
+    transfer_id: String,
+    #[serde(flatten)]
+    diagnostics: DecodeDiagnostics,
+    decodable: bool,
+    shortfall: u32,
+    /// Fewest shards whose resend makes the file decodable
+    resend_plan: Vec<u32>,
+}
+
+impl TransferDiagnostics {
+    fn new(key: &str, transfer: &PendingTransfer) -> Self {
+        let diagnostics = transfer.diagnostics();
+        Self {
+            transfer_id: key.replace(['/', '\\', ':'], "_"),
+            decodable: diagnostics.is_decodable(),
+            shortfall: diagnostics.shortfall(),
+            resend_plan: diagnostics.resend_plan(),
+            diagnostics,
+        }
+    }
 }
 
-async fn start_api_server(state: ReceiverApiState) -> Result<(), Box<dyn std::error::Error>> {
+/// What the receiver holds of a file still arriving, as recorded on disk
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct InventoryEntry {
+    transfer_id: String,
+    filename: String,
+    total_chunks: u32,
+    data_chunks: u32,
+    /// Chunks on disk
+    received: u32,
+    complete_groups: u32,
+    total_groups: u32,
+    /// Further chunks needed before the file decodes
+    shortfall: u32,
+    missing: Vec<u32>,
+}
+
+impl InventoryEntry {
+    fn new(transfer: &InboundTransfer) -> Self {
+        Self {
+            transfer_id: transfer.transfer_id.clone(),
+            filename: transfer.manifest.filename.clone(),
+            total_chunks: transfer.manifest.total_chunks,
+            data_chunks: transfer.manifest.data_chunks,
+            received: transfer.received(),
+            complete_groups: transfer.complete_groups(),
+            total_groups: transfer.groups.len() as u32,
+            shortfall: transfer.shortfall(),
+            missing: transfer.missing_sequences(),
+        }
+    }
+}
+
+/// Report a configuration error, naming the offending key, and exit
+fn exit_with(error: ConfigError) -> ! {
+    eprintln!("❌ Invalid receiver configuration: {}", error);
+    std::process::exit(2);
+}
+
+async fn start_api_server(
+    state: ReceiverApiState,
+    addr: SocketAddr,
+) -> Result<(), Box<dyn std::error::Error>> {
     let app = Router::new()
         .route("/api/v1/receiver/status", get(get_receiver_status))
         .route("/api/v1/receiver/files", get(list_received_files))
         .route("/api/v1/receiver/files/:filename", get(download_file))
+        .route("/api/v1/receiver/partial", get(list_partial_files))
+        .route(
+            "/api/v1/receiver/partial/:id/ranges",
+            get(get_partial_ranges),
+        )
+        .route("/api/v1/receiver/diagnostics", get(list_diagnostics))
+        .route("/api/v1/receiver/diagnostics/:id", get(get_diagnostics))
+        .route("/api/v1/receiver/inventory", get(list_inventory))
+        .route("/api/v1/receiver/events", get(stream_events))
+        .route("/api/v1/receiver/retention", get(plan_retention))
+        .route("/api/v1/receiver/peers", get(list_peer_rates))
+        // Files held in quarantine
+        .route("/api/v1/received", get(list_quarantined))
+        .route("/api/v1/received/:id", get(get_quarantined))
+        .route("/api/v1/received/:id/release", post(release_file))
         .layer(
             CorsLayer::new()
                 .allow_origin(Any)
@@ -363,8 +1346,8 @@ async fn start_api_server(state: ReceiverApiState) -> Result<(), Box<dyn std::er
         )
         .with_state(state);
 
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:8080").await?;
-    println!("   🌐 API server listening on port 8080");
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    println!("   🌐 API server listening on {}", addr);
     axum::serve(listener, app).await?;
     Ok(())
 }
@@ -393,6 +1376,15 @@ async fn download_file(
     let files = state.received_files.lock().await;
 
     if let Some(file_info) = files.iter().find(|f| f.filename == filename) {
+        if file_info.stage != ReleaseStage::Released {
+            return (StatusCode::FORBIDDEN, "File is held in quarantine").into_response();
+        }
+        let hook_ctx = HookContext::new(HookPoint::BeforeDownload, filename.clone())
+            .with_path(&file_info.path);
+        if let Err(e) = state.hooks.run(&hook_ctx).await {
+            return (StatusCode::FORBIDDEN, e.to_string()).into_response();
+        }
+
         match tokio::fs::read(&file_info.path).await {
             Ok(contents) => {
                 let headers = [
@@ -410,3 +1402,181 @@ async fn download_file(
         (StatusCode::NOT_FOUND, "File not found").into_response()
     }
 }
+
+async fn list_partial_files(State(state): State<ReceiverApiState>) -> Json<Vec<PartialFileInfo>> {
+    let transfers = state.active_transfers.lock().await;
+    Json(
+        transfers
+            .iter()
+            .filter_map(|(key, t)| Some(PartialFileInfo::new(key, t.preview.as_ref()?)))
+            .collect(),
+    )
+}
+
+async fn get_partial_ranges(
+    State(state): State<ReceiverApiState>,
+    AxumPath(id): AxumPath<String>,
+) -> impl IntoResponse {
+    let transfers = state.active_transfers.lock().await;
+    transfers
+        .iter()
+        .filter_map(|(key, t)| Some(PartialFileInfo::new(key, t.preview.as_ref()?)))
+        .find(|info| info.transfer_id == id)
+        .map(Json)
+        .ok_or((StatusCode::NOT_FOUND, "No partial file for that transfer"))
+}
+
+async fn list_diagnostics(State(state): State<ReceiverApiState>) -> Json<Vec<TransferDiagnostics>> {
+    let transfers = state.active_transfers.lock().await;
+    Json(
+        transfers
+            .iter()
+            .map(|(key, t)| TransferDiagnostics::new(key, t))
+            .collect(),
+    )
+}
+
+async fn get_diagnostics(
+    State(state): State<ReceiverApiState>,
+    AxumPath(id): AxumPath<String>,
+) -> impl IntoResponse {
+    let transfers = state.active_transfers.lock().await;
+    transfers
+        .iter()
+        .map(|(key, t)| TransferDiagnostics::new(key, t))
+        .find(|info| info.transfer_id == id)
+        .map(Json)
+        .ok_or((
+            StatusCode::NOT_FOUND,
+            "No transfer in progress with that id",
+        ))
+}
+
+async fn list_inventory(State(state): State<ReceiverApiState>) -> impl IntoResponse {
+    match state.inbound.list_inbound().await {
+        Ok(transfers) => Json(
+            transfers
+                .iter()
+                .map(InventoryEntry::new)
+                .collect::<Vec<_>>(),
+        )
+        .into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+/// What a retention pass would delete now, deleting nothing
+async fn plan_retention(
+    State(state): State<ReceiverApiState>,
+) -> Result<Json<RetentionReport>, (StatusCode, String)> {
+    state
+        .retention
+        .plan()
+        .map(Json)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
+/// Each sender's receive rate and the rate it is held to
+async fn list_peer_rates(State(state): State<ReceiverApiState>) -> Json<Vec<PeerRate>> {
+    Json(state.transport.peer_rates())
+}
+
+/// Received files, quarantine stage changes and deletions, as JSON text
+/// messages
+async fn stream_events(ws: WebSocketUpgrade, State(state): State<ReceiverApiState>) -> Response {
+    let events = state.tx.subscribe();
+    ws.on_upgrade(move |socket| forward_events(socket, events))
+}
+
+async fn forward_events(mut socket: WebSocket, mut events: broadcast::Receiver<String>) {
+    loop {
+        let event = match events.recv().await {
+            Ok(event) => event,
+            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(broadcast::error::RecvError::Closed) => return,
+        };
+        if socket.send(Message::Text(event)).await.is_err() {
+            return;
+        }
+    }
+}
+
+/// The quarantine area, or a 404 saying it is off
+fn quarantine_area(state: &ReceiverApiState) -> Result<&QuarantineArea, (StatusCode, String)> {
+    state.quarantine.as_deref().ok_or((
+        StatusCode::NOT_FOUND,
+        "Quarantine is off; files are released on arrival".to_string(),
+    ))
+}
+
+fn quarantine_error(error: QuarantineError) -> (StatusCode, String) {
+    let status = match error {
+        QuarantineError::NotFound(_) => StatusCode::NOT_FOUND,
+        QuarantineError::NotReleasable { .. } | QuarantineError::DestinationExists(_) => {
+            StatusCode::CONFLICT
+        }
+        QuarantineError::Io(_) | QuarantineError::Index { .. } => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    (status, error.to_string())
+}
+
+async fn list_quarantined(
+    State(state): State<ReceiverApiState>,
+) -> Result<Json<Vec<HeldFile>>, (StatusCode, String)> {
+    Ok(Json(quarantine_area(&state)?.files()))
+}
+
+async fn get_quarantined(
+    State(state): State<ReceiverApiState>,
+    AxumPath(id): AxumPath<String>,
+) -> Result<Json<HeldFile>, (StatusCode, String)> {
+    let area = quarantine_area(&state)?;
+    area.get(&id)
+        .map(Json)
+        .ok_or_else(|| quarantine_error(QuarantineError::NotFound(id)))
+}
+
+/// Move a held file into the save directory
+async fn release_file(
+    State(state): State<ReceiverApiState>,
+    AxumPath(id): AxumPath<String>,
+) -> Result<Json<HeldFile>, (StatusCode, String)> {
+    let area = quarantine_area(&state)?;
+    let released = area
+        .release(&id, &state.save_dir)
+        .await
+        .map_err(quarantine_error)?;
+    println!("   🔓 Released {} to {}", id, released.path.display());
+
+    let info = state
+        .received_files
+        .lock()
+        .await
+        .iter_mut()
+        .find(|f| f.id == id)
+        .map(|info| {
+            info.path = released.path.to_string_lossy().to_string();
+            info.stage = released.stage;
+            info.clone()
+        });
+    if let Some(info) = info {
+        retain_file(&state.retention, &info).await;
+    }
+    if released.verified {
+        record_delivery(
+            &state.delivered_files,
+            &state.repair_index,
+            StoredFile {
+                path: released.path.clone(),
+                file_id: released.file_id.clone(),
+                checksum: released.checksum,
+                checksum_algorithm: released.checksum_algorithm,
+                source: released.source,
+                block_size: released.block_size,
+                verified_at: chrono::Utc::now().timestamp(),
+            },
+        )
+        .await;
+    }
+    Ok(Json(released))
+}
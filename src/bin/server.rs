@@ -1,10 +1,14 @@
 use chunkstream_pro::api::create_api_server;
-use chunkstream_pro::chunk::ChunkManager;
-use chunkstream_pro::coordinator::TransferCoordinator;
-use chunkstream_pro::integrity::IntegrityVerifier;
+use chunkstream_pro::config::ConfigArgs;
+use chunkstream_pro::logging;
+use chunkstream_pro::metrics::start_metrics_server;
 use chunkstream_pro::network::{ConnectionConfig, QuicTransport};
-use chunkstream_pro::priority::PriorityQueue;
-use chunkstream_pro::session::SessionStore;
+use chunkstream_pro::relay::{link, QuicLink, RelayNode};
+use chunkstream_pro::session::SessionBackend;
+use chunkstream_pro::CoordinatorBuilder;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
 
 #[tokio::main]
 async fn main() {
@@ -19,37 +23,96 @@ async fn main() {
 
     println!("🚀 Initializing system components...\n");
 
-    // Initialize Chunk Manager
-    // 512KB chunks with 50 data + 10 parity = supports up to 25MB files
-    println!("📦 Chunk Manager: 512KB chunks, 50 data + 10 parity shards");
-    let chunk_manager =
-        ChunkManager::new(512 * 1024, 50, 10).expect("Failed to create chunk manager");
+    // Load configuration: `--config` TOML file, then RESILIENT_* overrides
+    let args = ConfigArgs::from_env().unwrap_or_else(|e| exit_with(e));
+    let config = args.load().unwrap_or_else(|e| exit_with(e));
+    if let Err(e) = logging::init(&config.logging) {
+        eprintln!("❌ Failed to set up logging: {}", e);
+        std::process::exit(2);
+    }
+    if let Some(path) = &args.path {
+        println!("⚙️  Config: {}", path.display());
+    }
 
-    // Initialize Integrity Verifier
+    println!(
+        "📦 Chunk Manager: {}KB chunks, {} data + {} parity shards",
+        config.chunk.chunk_size / 1024,
+        config.chunk.data_shards,
+        config.chunk.parity_shards
+    );
     println!("🔒 Integrity Verifier: BLAKE3 hashing");
-    let verifier = IntegrityVerifier;
-
-    // Initialize QUIC Transport
     println!("🌐 Network Engine: QUIC transport with TLS 1.3");
-    let config = ConnectionConfig::default();
-    let transport = QuicTransport::new(config)
-        .await
-        .expect("Failed to create QUIC transport");
+    println!(
+        "⚡ Priority Queue: {} capacity, 3-level system",
+        config.queue.capacity
+    );
+    if config.session.backend == SessionBackend::Memory {
+        println!("💾 Session Store: In-memory (no database)");
+    } else if config.session.is_in_memory() {
+        println!("💾 Session Store: In-memory SQLite database");
+    } else {
+        println!("💾 Session Store: {}", config.session.db_path);
+    }
 
-    // Initialize Priority Queue
-    println!("⚡ Priority Queue: 1M capacity, 3-level system");
-    let queue = PriorityQueue::new(1_000_000);
+    if config.metrics.enabled {
+        start_metrics_server(config.metrics.metrics_config())
+            .expect("Failed to start metrics exporter");
+        println!(
+            "📈 Metrics: Prometheus exporter on {}",
+            config.metrics.listen_addr
+        );
+    }
 
-    // Initialize Session Store
-    println!("💾 Session Store: In-memory SQLite database");
-    let session_store = SessionStore::new_in_memory()
-        .await
-        .expect("Failed to create session store");
+    let api_addr = config.api.bind_addr;
+    let relay = config.relay.clone();
+    let relay_connection = ConnectionConfig {
+        bind_addr: relay.listen_addr,
+        ..config.network.connection_config()
+    };
 
     // Create Transfer Coordinator
     println!("🎯 Transfer Coordinator: Orchestrating all modules");
-    let coordinator =
-        TransferCoordinator::new(chunk_manager, verifier, transport, queue, session_store);
+    let coordinator = CoordinatorBuilder::from_config(config)
+        .build()
+        .await
+        .expect("Failed to build transfer coordinator");
+
+    // Receivers that find a delivered file damaged ask back for the chunks
+    coordinator.receive().serve_repairs();
+
+    // Optional store-and-forward relay; its expiry notices feed the coordinator
+    if relay.enabled {
+        let relay_config = relay.relay_config();
+        let node_id = relay_config.node_id.clone();
+        let (tx, rx) = mpsc::channel(256);
+        // Peers and receivers are reached over QUIC on the relay's address
+        let transport = Arc::new(
+            QuicTransport::new(relay_connection)
+                .await
+                .unwrap_or_else(|e| panic!("Failed to bind relay to {}: {}", relay.listen_addr, e)),
+        );
+        let node = Arc::new(
+            RelayNode::new(relay_config)
+                .expect("Failed to start relay node")
+                .with_events(tx)
+                .with_link(Arc::new(QuicLink::new(transport.clone()))),
+        );
+        link::serve(node.clone(), transport);
+        coordinator
+            .receive()
+            .forward_relay_events(node_id.clone(), rx);
+        // Critical transfers whose receiver doesn't answer in time go here
+        coordinator.set_fallback_relay(Some(node.clone()));
+        // Critical chunks that silently vanish from the relay are put back
+        if relay.audit_interval_secs > 0 {
+            coordinator.receive().spawn_relay_auditor(
+                vec![node.clone()],
+                Duration::from_secs(relay.audit_interval_secs),
+            );
+        }
+        node.start();
+        println!("🛰️  Relay Node: {} on {}", node_id, relay.listen_addr);
+    }
 
     // Create API server
     println!("🌐 API Layer: REST + WebSocket endpoints");
@@ -57,16 +120,16 @@ async fn main() {
 
     // Bind server
     println!("\n📡 Starting server...");
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:3000")
+    let listener = tokio::net::TcpListener::bind(api_addr)
         .await
-        .expect("Failed to bind to port 3000");
+        .unwrap_or_else(|e| panic!("Failed to bind to {}: {}", api_addr, e));
 
     println!("\n✅ ChunkStream Pro Server is running!\n");
     println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
-    println!("📍 Server Address:  http://localhost:3000");
-    println!("🏥 Health Check:    http://localhost:3000/health");
-    println!("📡 REST API:        http://localhost:3000/api/v1/transfers");
-    println!("🔌 WebSocket:       ws://localhost:3000/ws");
+    println!("📍 Server Address:  http://{}", api_addr);
+    println!("🏥 Health Check:    http://{}/health", api_addr);
+    println!("📡 REST API:        http://{}/api/v1/transfers", api_addr);
+    println!("🔌 WebSocket:       ws://{}/ws", api_addr);
     println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
     println!("\n📚 API Endpoints:");
     println!("   POST   /api/v1/transfers              - Start new transfer");
@@ -83,3 +146,9 @@ async fn main() {
     // Start serving
     axum::serve(listener, app).await.expect("Server error");
 }
+
+/// Report a configuration error, naming the offending key, and exit
+fn exit_with(error: chunkstream_pro::config::ConfigError) -> ! {
+    eprintln!("❌ Invalid server configuration: {}", error);
+    std::process::exit(2);
+}
@@ -1,7 +1,14 @@
 //! Adaptive erasure coding configuration
 //!
 //! Automatically adjusts parity shards based on observed network conditions
+//!
+//! Parity can be held down twice: by the coding speed this host sustains
+//! (the autotune cap) and by an overhead budget, a hard limit on parity
+//! bytes per data byte for links billed by volume. Under the budget the
+//! coder recovers less loss than the thresholds call for, but never sends
+//! more than the budget allows.
 
+use crate::chunk::autotune::AutotuneReport;
 use crate::chunk::ErasureCoder;
 use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::Arc;
@@ -17,6 +24,9 @@ pub struct AdaptiveErasureConfig {
     pub max_parity_shards: usize,
     /// Loss rate thresholds for adaptation
     pub thresholds: Vec<(f32, usize)>,
+    /// Most parity bytes per data byte, e.g. 0.2 for 20% extra; overrides
+    /// `min_parity_shards` but always leaves one parity shard
+    pub max_overhead: Option<f64>,
 }
 
 impl Default for AdaptiveErasureConfig {
@@ -33,6 +43,7 @@ impl Default for AdaptiveErasureConfig {
                 (0.20, 20), // 15-20% loss: 20 parity (29% overhead)
                 (1.00, 25), // 20%+ loss: 25 parity (33% overhead)
             ],
+            max_overhead: None,
         }
     }
 }
@@ -58,6 +69,12 @@ impl AdaptiveErasureConfig {
         self.max_parity_shards
     }
 
+    /// Most parity shards `max_overhead` allows, if set
+    pub fn budget_parity(&self) -> Option<usize> {
+        self.max_overhead
+            .map(|ratio| parity_within(self.data_shards, ratio))
+    }
+
     /// Calculate overhead percentage for given parity
     pub fn overhead_percent(&self, parity_shards: usize) -> f64 {
         parity_shards as f64 / (self.data_shards + parity_shards) as f64 * 100.0
@@ -69,6 +86,12 @@ impl AdaptiveErasureConfig {
     }
 }
 
+/// Most parity shards that keep `data_shards` within `ratio` overhead,
+/// never fewer than one
+pub(crate) fn parity_within(data_shards: usize, ratio: f64) -> usize {
+    ((data_shards as f64 * ratio.max(0.0)).floor() as usize).max(1)
+}
+
 /// Adaptive erasure coder that adjusts to network conditions
 pub struct AdaptiveErasureCoder {
     config: AdaptiveErasureConfig,
@@ -80,18 +103,133 @@ pub struct AdaptiveErasureCoder {
     sample_count: AtomicU32,
     /// Lost count for loss rate calculation
     lost_count: AtomicU32,
+    /// Most parity this host can code fast enough (see [`apply_autotune`](Self::apply_autotune))
+    parity_cap: AtomicU32,
+    /// See [`set_overhead_budget`](Self::set_overhead_budget)
+    max_overhead: std::sync::RwLock<Option<f64>>,
+    /// Parity the loss rate called for while the budget holds it lower; 0
+    /// when it doesn't
+    clamped_from: AtomicU32,
 }
 
 impl AdaptiveErasureCoder {
     /// Create a new adaptive coder
     pub fn new(config: AdaptiveErasureConfig) -> Self {
-        let initial_parity = config.min_parity_shards as u32;
-        Self {
+        let parity_cap = config.max_parity_shards as u32;
+        let max_overhead = config.max_overhead;
+        let coder = Self {
+            current_parity: AtomicU32::new(config.min_parity_shards as u32),
             config,
-            current_parity: AtomicU32::new(initial_parity),
             observed_loss_rate: Arc::new(std::sync::RwLock::new(0.0)),
             sample_count: AtomicU32::new(0),
             lost_count: AtomicU32::new(0),
+            parity_cap: AtomicU32::new(parity_cap),
+            max_overhead: std::sync::RwLock::new(None),
+            clamped_from: AtomicU32::new(0),
+        };
+        coder.set_overhead_budget(max_overhead);
+        coder
+    }
+
+    /// Limit parity to what the host can encode at `min_bytes_per_sec`
+    ///
+    /// Uses the benchmark closest to `chunk_size` for this coder's data
+    /// shard count. Never goes below `min_parity_shards`: a host too slow
+    /// even for that still gets the minimum protection. Returns the cap now
+    /// in effect.
+    pub fn apply_autotune(
+        &self,
+        report: &AutotuneReport,
+        chunk_size: usize,
+        min_bytes_per_sec: u64,
+    ) -> usize {
+        let data_shards = self.config.data_shards;
+        if !report.results.iter().any(|r| r.data_shards == data_shards) {
+            tracing::warn!(
+                "Autotune has no results for {} data shards, parity left uncapped",
+                data_shards
+            );
+            return self.parity_cap();
+        }
+
+        let cap = report
+            .max_parity_for(data_shards, chunk_size, min_bytes_per_sec)
+            .unwrap_or(self.config.min_parity_shards);
+        if cap < self.config.max_parity_shards {
+            tracing::info!(
+                "Erasure coding capped at {}+{} shards to sustain {} bytes/s",
+                data_shards,
+                cap,
+                min_bytes_per_sec
+            );
+        }
+        self.set_parity_cap(cap);
+        self.parity_cap()
+    }
+
+    /// Most parity shards the coder will choose
+    pub fn parity_cap(&self) -> usize {
+        self.parity_cap.load(Ordering::Relaxed) as usize
+    }
+
+    /// Change the parity cap, clamped to the configured shard bounds
+    pub fn set_parity_cap(&self, cap: usize) {
+        let cap = cap.clamp(self.config.min_parity_shards, self.config.max_parity_shards);
+        self.parity_cap.store(cap as u32, Ordering::Relaxed);
+        self.current_parity.fetch_min(cap as u32, Ordering::Relaxed);
+    }
+
+    /// Limit parity to `max_overhead` parity bytes per data byte, or lift
+    /// the limit with `None`
+    ///
+    /// Takes effect at once, clamping the current parity if needed.
+    pub fn set_overhead_budget(&self, max_overhead: Option<f64>) {
+        *self.max_overhead.write().unwrap() = max_overhead;
+        let parity = self.parity_for(self.observed_loss_rate());
+        self.current_parity.store(parity as u32, Ordering::Relaxed);
+    }
+
+    /// Most parity bytes per data byte the coder will send, if limited
+    pub fn overhead_budget(&self) -> Option<f64> {
+        *self.max_overhead.read().unwrap()
+    }
+
+    /// Most parity shards the overhead budget allows, if set
+    pub fn budget_parity(&self) -> Option<usize> {
+        self.overhead_budget()
+            .map(|ratio| parity_within(self.config.data_shards, ratio))
+    }
+
+    /// Parity for `loss_rate`, within the cap and the overhead budget
+    fn parity_for(&self, loss_rate: f32) -> usize {
+        let desired = self
+            .config
+            .parity_for_loss_rate(loss_rate)
+            .min(self.parity_cap());
+        match self.budget_parity() {
+            Some(budget) if desired > budget => {
+                // Log when the clamp starts or the wanted parity moves, not
+                // on every sample
+                if self.clamped_from.swap(desired as u32, Ordering::Relaxed) != desired as u32 {
+                    tracing::warn!(
+                        "Parity clamped from {} to {} shards by the {:.0}% overhead budget ({:.1}% loss)",
+                        desired,
+                        budget,
+                        self.overhead_budget().unwrap_or_default() * 100.0,
+                        loss_rate * 100.0
+                    );
+                }
+                budget
+            }
+            _ => {
+                if self.clamped_from.swap(0, Ordering::Relaxed) != 0 {
+                    tracing::info!(
+                        "Parity back within the overhead budget at {} shards",
+                        desired
+                    );
+                }
+                desired
+            }
         }
     }
 
@@ -108,6 +246,20 @@ impl AdaptiveErasureCoder {
         self.update_loss_rate();
     }
 
+    /// Record a batch of chunk outcomes, such as a receiver's report of
+    /// what arrived intact and what failed its checksum since the last one
+    pub fn record_outcomes(&self, delivered: u64, lost: u64) {
+        let total = delivered.saturating_add(lost);
+        if total == 0 {
+            return;
+        }
+        self.sample_count
+            .fetch_add(total.min(u32::MAX as u64) as u32, Ordering::Relaxed);
+        self.lost_count
+            .fetch_add(lost.min(u32::MAX as u64) as u32, Ordering::Relaxed);
+        self.update_loss_rate();
+    }
+
     /// Update the smoothed loss rate
     fn update_loss_rate(&self) {
         let samples = self.sample_count.load(Ordering::Relaxed);
@@ -122,7 +274,7 @@ impl AdaptiveErasureCoder {
             *rate = *rate * 0.7 + current_rate * 0.3;
 
             // Update parity based on new rate
-            let new_parity = self.config.parity_for_loss_rate(*rate);
+            let new_parity = self.parity_for(*rate);
             self.current_parity
                 .store(new_parity as u32, Ordering::Relaxed);
 
@@ -134,6 +286,11 @@ impl AdaptiveErasureCoder {
         }
     }
 
+    /// Thresholds and shard bounds this coder adapts within
+    pub fn config(&self) -> &AdaptiveErasureConfig {
+        &self.config
+    }
+
     /// Get the current recommended parity shards
     pub fn current_parity(&self) -> usize {
         self.current_parity.load(Ordering::Relaxed) as usize
@@ -152,7 +309,7 @@ impl AdaptiveErasureCoder {
             let mut r = self.observed_loss_rate.write().unwrap();
             *r = clamped;
         }
-        let new_parity = self.config.parity_for_loss_rate(clamped);
+        let new_parity = self.parity_for(clamped);
         self.current_parity
             .store(new_parity as u32, Ordering::Relaxed);
         // Reset sample counters so future record_success/record_loss
@@ -175,6 +332,10 @@ impl AdaptiveErasureCoder {
             observed_loss_rate: self.observed_loss_rate(),
             overhead_percent: self.config.overhead_percent(parity),
             recovery_capability: self.config.recovery_capability(parity),
+            budget_clamped_from: match self.clamped_from.load(Ordering::Relaxed) {
+                0 => None,
+                desired => Some(desired as usize),
+            },
         }
     }
 }
@@ -187,6 +348,9 @@ pub struct AdaptiveStatus {
     pub observed_loss_rate: f32,
     pub overhead_percent: f64,
     pub recovery_capability: f64,
+    /// Parity the loss rate called for, when the overhead budget holds
+    /// `parity_shards` lower
+    pub budget_clamped_from: Option<usize>,
 }
 
 impl std::fmt::Display for AdaptiveStatus {
@@ -242,6 +406,74 @@ mod tests {
         println!("Status: {}", coder.status());
     }
 
+    #[test]
+    fn test_batched_outcomes_move_parity() {
+        let coder = AdaptiveErasureCoder::new(AdaptiveErasureConfig::default());
+        coder.record_outcomes(0, 0);
+        assert_eq!(coder.observed_loss_rate(), 0.0);
+
+        // A receiver reporting 30 of 100 chunks corrupt
+        coder.record_outcomes(70, 30);
+        assert!((coder.observed_loss_rate() - 0.09).abs() < 1e-6);
+        assert_eq!(coder.current_parity(), 10);
+    }
+
+    #[test]
+    fn test_autotune_caps_parity() {
+        use crate::chunk::autotune::ErasureBenchmark;
+
+        let coder = AdaptiveErasureCoder::new(AdaptiveErasureConfig::default());
+        let report = AutotuneReport {
+            results: [(5, 900), (10, 600), (15, 300), (20, 200), (25, 100)]
+                .into_iter()
+                .map(|(parity_shards, rate)| ErasureBenchmark {
+                    data_shards: 50,
+                    parity_shards,
+                    chunk_size: 512 * 1024,
+                    encode_bytes_per_sec: rate,
+                    decode_bytes_per_sec: rate,
+                })
+                .collect(),
+            measured_at: 0,
+        };
+
+        assert_eq!(coder.apply_autotune(&report, 512 * 1024, 250), 15);
+        coder.set_loss_rate(0.5);
+        assert_eq!(coder.current_parity(), 15);
+
+        // Too slow for any level: keep the minimum protection
+        assert_eq!(coder.apply_autotune(&report, 512 * 1024, 10_000), 5);
+        assert_eq!(coder.current_parity(), 5);
+    }
+
+    #[test]
+    fn test_overhead_budget_clamps_parity() {
+        // 20% extra bytes on 50 data shards leaves room for 10 parity
+        let coder = AdaptiveErasureCoder::new(AdaptiveErasureConfig {
+            max_overhead: Some(0.2),
+            ..Default::default()
+        });
+        assert_eq!(coder.budget_parity(), Some(10));
+
+        coder.set_loss_rate(0.08);
+        assert_eq!(coder.current_parity(), 10);
+        assert_eq!(coder.status().budget_clamped_from, None);
+
+        coder.set_loss_rate(0.5);
+        assert_eq!(coder.current_parity(), 10);
+        assert_eq!(coder.status().budget_clamped_from, Some(25));
+
+        // The budget beats the minimum, but one parity shard always remains
+        coder.set_overhead_budget(Some(0.05));
+        assert_eq!(coder.current_parity(), 2);
+        coder.set_overhead_budget(Some(0.0));
+        assert_eq!(coder.current_parity(), 1);
+
+        coder.set_overhead_budget(None);
+        assert_eq!(coder.current_parity(), 25);
+        assert_eq!(coder.status().budget_clamped_from, None);
+    }
+
     #[test]
     fn test_overhead_calculation() {
         let config = AdaptiveErasureConfig::default();
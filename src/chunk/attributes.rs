@@ -0,0 +1,197 @@
+//! File attribute preservation
+//!
+//! A reconstructed file is written fresh by the receiver, so without help it
+//! gets the receiver's umask and the time of reconstruction. The sender
+//! captures [`FileAttributes`] at split time and the receiver applies them
+//! once the file has been verified.
+//!
+//! Unix modes map to the read-only flag on other platforms, and symlinks are
+//! only recreated on Unix; elsewhere the link's content is kept as a file.
+
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Permission bits a sender may set; setuid, setgid and sticky are dropped
+const MODE_MASK: u32 = 0o777;
+
+/// Mode reported on platforms without Unix permissions
+#[cfg(not(unix))]
+const DEFAULT_MODE: u32 = 0o644;
+
+/// Attributes of the source file, restored after reconstruction
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileAttributes {
+    /// Unix permission bits
+    pub mode: Option<u32>,
+    /// Modification time in seconds since the Unix epoch
+    pub mtime: Option<i64>,
+    /// Target of the source path when it was a symlink
+    pub symlink_target: Option<PathBuf>,
+}
+
+impl FileAttributes {
+    /// Read the attributes of `path`, following symlinks for mode and mtime
+    pub async fn capture(path: &Path) -> io::Result<Self> {
+        let link = tokio::fs::symlink_metadata(path).await?;
+        let symlink_target = if link.file_type().is_symlink() {
+            Some(tokio::fs::read_link(path).await?)
+        } else {
+            None
+        };
+
+        let metadata = tokio::fs::metadata(path).await?;
+        let mtime = metadata
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_secs() as i64);
+
+        Ok(Self {
+            mode: Some(mode_of(&metadata)),
+            mtime,
+            symlink_target,
+        })
+    }
+
+    /// Apply the attributes to a reconstructed file at `path`
+    pub async fn apply(&self, path: &Path) -> io::Result<()> {
+        if let Some(target) = &self.symlink_target {
+            if replace_with_symlink(target, path).await? {
+                return Ok(());
+            }
+        }
+
+        if let Some(modified) = self.modified() {
+            let file = std::fs::File::options().write(true).open(path)?;
+            file.set_modified(modified)?;
+        }
+
+        if let Some(mode) = self.mode {
+            let mut permissions = tokio::fs::metadata(path).await?.permissions();
+            set_mode(&mut permissions, mode & MODE_MASK);
+            tokio::fs::set_permissions(path, permissions).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Modification time as a `SystemTime`
+    pub fn modified(&self) -> Option<SystemTime> {
+        self.mtime
+            .map(|secs| UNIX_EPOCH + Duration::from_secs(secs.max(0) as u64))
+    }
+}
+
+#[cfg(unix)]
+fn mode_of(metadata: &std::fs::Metadata) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode() & 0o7777
+}
+
+#[cfg(not(unix))]
+fn mode_of(metadata: &std::fs::Metadata) -> u32 {
+    if metadata.permissions().readonly() {
+        DEFAULT_MODE & !0o222
+    } else {
+        DEFAULT_MODE
+    }
+}
+
+#[cfg(unix)]
+fn set_mode(permissions: &mut std::fs::Permissions, mode: u32) {
+    use std::os::unix::fs::PermissionsExt;
+    permissions.set_mode(mode);
+}
+
+#[cfg(not(unix))]
+fn set_mode(permissions: &mut std::fs::Permissions, mode: u32) {
+    permissions.set_readonly(mode & 0o222 == 0);
+}
+
+/// Swap the file at `path` for a symlink; false where symlinks aren't restored
+#[cfg(unix)]
+async fn replace_with_symlink(target: &Path, path: &Path) -> io::Result<bool> {
+    tokio::fs::remove_file(path).await?;
+    tokio::fs::symlink(target, path).await?;
+    Ok(true)
+}
+
+#[cfg(not(unix))]
+async fn replace_with_symlink(_target: &Path, _path: &Path) -> io::Result<bool> {
+    Ok(false)
+}
+
+/// Remove a symlink left at `path` by an earlier transfer, so a new file is
+/// written in its place rather than through it
+pub(crate) async fn remove_stale_symlink(path: &Path) -> io::Result<()> {
+    match tokio::fs::symlink_metadata(path).await {
+        Ok(metadata) if metadata.file_type().is_symlink() => tokio::fs::remove_file(path).await,
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_capture_and_apply_roundtrip() {
+        let temp_dir = TempDir::new().unwrap();
+        let source = temp_dir.path().join("source.txt");
+        tokio::fs::write(&source, b"hello").await.unwrap();
+
+        let modified = UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+        std::fs::File::options()
+            .write(true)
+            .open(&source)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+        let mut permissions = std::fs::metadata(&source).unwrap().permissions();
+        set_mode(&mut permissions, 0o600);
+        std::fs::set_permissions(&source, permissions).unwrap();
+
+        let attributes = FileAttributes::capture(&source).await.unwrap();
+        assert_eq!(attributes.mtime, Some(1_600_000_000));
+        assert!(attributes.symlink_target.is_none());
+
+        let copy = temp_dir.path().join("copy.txt");
+        tokio::fs::write(&copy, b"hello").await.unwrap();
+        attributes.apply(&copy).await.unwrap();
+
+        let metadata = std::fs::metadata(&copy).unwrap();
+        assert_eq!(metadata.modified().unwrap(), modified);
+        assert_eq!(mode_of(&metadata), attributes.mode.unwrap());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_symlink_is_recreated_and_setuid_dropped() {
+        let temp_dir = TempDir::new().unwrap();
+        let attributes = FileAttributes {
+            symlink_target: Some(PathBuf::from("target.txt")),
+            ..Default::default()
+        };
+        let received = temp_dir.path().join("received");
+        tokio::fs::write(&received, b"data").await.unwrap();
+        attributes.apply(&received).await.unwrap();
+        assert_eq!(
+            tokio::fs::read_link(&received).await.unwrap(),
+            PathBuf::from("target.txt")
+        );
+
+        let plain = temp_dir.path().join("plain");
+        tokio::fs::write(&plain, b"data").await.unwrap();
+        FileAttributes {
+            mode: Some(0o4755),
+            ..Default::default()
+        }
+        .apply(&plain)
+        .await
+        .unwrap();
+        assert_eq!(mode_of(&std::fs::metadata(&plain).unwrap()), 0o755);
+    }
+}
@@ -0,0 +1,235 @@
+//! Erasure coding autotune
+//!
+//! Reed-Solomon cost grows with the parity count, and how much parity a
+//! host can afford depends on its CPU. A Raspberry Pi relay encoding 50+25
+//! shards can fall well below link speed, at which point extra parity costs
+//! more throughput than the loss it covers. [`run`] measures encode and
+//! decode speed for a set of shard layouts and chunk sizes, and
+//! [`AutotuneReport::max_parity_for`] turns the results into a parity cap
+//! for the adaptive coder.
+
+use crate::chunk::error::{ChunkError, Result};
+use crate::chunk::ErasureCoder;
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Shard layouts and chunk sizes to benchmark
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AutotuneConfig {
+    /// (data, parity) shard combinations
+    pub layouts: Vec<(usize, usize)>,
+    /// Bytes per shard
+    pub chunk_sizes: Vec<usize>,
+    /// Timed runs per measurement; the fastest is kept
+    pub iterations: u32,
+}
+
+impl Default for AutotuneConfig {
+    fn default() -> Self {
+        // The parity levels the default adaptive thresholds move between
+        Self {
+            layouts: vec![(50, 5), (50, 10), (50, 15), (50, 20), (50, 25)],
+            chunk_sizes: vec![64 * 1024, 256 * 1024],
+            iterations: 3,
+        }
+    }
+}
+
+/// Measured speed of one shard layout at one chunk size
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ErasureBenchmark {
+    pub data_shards: usize,
+    pub parity_shards: usize,
+    pub chunk_size: usize,
+    /// Data bytes encoded per second
+    pub encode_bytes_per_sec: u64,
+    /// Data bytes recovered per second with `parity_shards` data shards lost
+    pub decode_bytes_per_sec: u64,
+}
+
+impl ErasureBenchmark {
+    /// The slower of encode and decode
+    pub fn bytes_per_sec(&self) -> u64 {
+        self.encode_bytes_per_sec.min(self.decode_bytes_per_sec)
+    }
+}
+
+/// Results of an autotune run on this host
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AutotuneReport {
+    pub results: Vec<ErasureBenchmark>,
+    /// Unix timestamp of the run
+    pub measured_at: i64,
+}
+
+impl AutotuneReport {
+    /// Most parity that keeps coding at or above `min_bytes_per_sec` for
+    /// `data_shards` data shards, measured at the chunk size closest to
+    /// `chunk_size`
+    ///
+    /// `None` when that layout wasn't benchmarked or no parity level is fast
+    /// enough.
+    pub fn max_parity_for(
+        &self,
+        data_shards: usize,
+        chunk_size: usize,
+        min_bytes_per_sec: u64,
+    ) -> Option<usize> {
+        let candidates: Vec<_> = self
+            .results
+            .iter()
+            .filter(|r| r.data_shards == data_shards)
+            .collect();
+        let nearest = candidates
+            .iter()
+            .map(|r| r.chunk_size)
+            .min_by_key(|&size| size.abs_diff(chunk_size))?;
+
+        candidates
+            .iter()
+            .filter(|r| r.chunk_size == nearest && r.bytes_per_sec() >= min_bytes_per_sec)
+            .map(|r| r.parity_shards)
+            .max()
+    }
+
+    /// Read results saved by [`save`](Self::save)
+    pub fn load(path: &Path) -> Result<Self> {
+        let data = std::fs::read(path)?;
+        serde_json::from_slice(&data).map_err(|e| {
+            ChunkError::Io(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("{}: {}", path.display(), e),
+            ))
+        })
+    }
+
+    /// Save results so later starts can skip the benchmark
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let data = serde_json::to_vec_pretty(self).map_err(std::io::Error::other)?;
+        std::fs::write(path, data)?;
+        Ok(())
+    }
+}
+
+/// Benchmark every layout at every chunk size
+///
+/// CPU-bound and takes a noticeable fraction of a second with the default
+/// config; run it off the async runtime.
+pub fn run(config: &AutotuneConfig) -> Result<AutotuneReport> {
+    let mut results = Vec::with_capacity(config.layouts.len() * config.chunk_sizes.len());
+    for &chunk_size in &config.chunk_sizes {
+        for &(data_shards, parity_shards) in &config.layouts {
+            results.push(benchmark(
+                data_shards,
+                parity_shards,
+                chunk_size,
+                config.iterations.max(1),
+            )?);
+        }
+    }
+
+    Ok(AutotuneReport {
+        results,
+        measured_at: chrono::Utc::now().timestamp(),
+    })
+}
+
+fn benchmark(
+    data_shards: usize,
+    parity_shards: usize,
+    chunk_size: usize,
+    iterations: u32,
+) -> Result<ErasureBenchmark> {
+    let coder = ErasureCoder::new(data_shards, parity_shards)?;
+    let data: Vec<Bytes> = (0..data_shards)
+        .map(|i| Bytes::from(vec![i as u8; chunk_size]))
+        .collect();
+    let data_bytes = (data_shards * chunk_size) as u64;
+
+    let mut encode = Duration::MAX;
+    let mut shards = Vec::new();
+    for _ in 0..iterations {
+        let start = Instant::now();
+        shards = coder.encode(data.clone())?;
+        encode = encode.min(start.elapsed());
+    }
+
+    // Worst recoverable case: as many data shards lost as there is parity
+    let lost = parity_shards.min(data_shards);
+    let mut decode = Duration::MAX;
+    for _ in 0..iterations {
+        let received: Vec<Option<Bytes>> = shards
+            .iter()
+            .enumerate()
+            .map(|(i, shard)| (i >= lost).then(|| shard.clone()))
+            .collect();
+        let start = Instant::now();
+        coder.decode(received)?;
+        decode = decode.min(start.elapsed());
+    }
+
+    Ok(ErasureBenchmark {
+        data_shards,
+        parity_shards,
+        chunk_size,
+        encode_bytes_per_sec: rate(data_bytes, encode),
+        decode_bytes_per_sec: rate(data_bytes, decode),
+    })
+}
+
+fn rate(bytes: u64, elapsed: Duration) -> u64 {
+    (bytes as f64 / elapsed.as_secs_f64().max(1e-9)) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(parity_shards: usize, chunk_size: usize, bytes_per_sec: u64) -> ErasureBenchmark {
+        ErasureBenchmark {
+            data_shards: 50,
+            parity_shards,
+            chunk_size,
+            encode_bytes_per_sec: bytes_per_sec,
+            decode_bytes_per_sec: bytes_per_sec * 2,
+        }
+    }
+
+    #[test]
+    fn test_max_parity_for_picks_fastest_enough() {
+        let report = AutotuneReport {
+            results: vec![
+                result(5, 64 * 1024, 400),
+                result(10, 64 * 1024, 200),
+                result(25, 64 * 1024, 80),
+                result(25, 1024 * 1024, 300),
+            ],
+            measured_at: 0,
+        };
+
+        assert_eq!(report.max_parity_for(50, 64 * 1024, 150), Some(10));
+        assert_eq!(report.max_parity_for(50, 100 * 1024, 50), Some(25));
+        assert_eq!(report.max_parity_for(50, 2 * 1024 * 1024, 250), Some(25));
+        assert_eq!(report.max_parity_for(50, 64 * 1024, 1_000), None);
+        assert_eq!(report.max_parity_for(10, 64 * 1024, 1), None);
+    }
+
+    #[test]
+    fn test_run_and_reload() {
+        let config = AutotuneConfig {
+            layouts: vec![(4, 2), (4, 4)],
+            chunk_sizes: vec![1024],
+            iterations: 1,
+        };
+        let report = run(&config).unwrap();
+        assert_eq!(report.results.len(), 2);
+        assert!(report.results.iter().all(|r| r.bytes_per_sec() > 0));
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("autotune.json");
+        report.save(&path).unwrap();
+        assert_eq!(AutotuneReport::load(&path).unwrap(), report);
+    }
+}
@@ -1,19 +1,65 @@
 //! Compression utilities for chunk data
 //!
-//! Provides LZ4 compression for reducing transfer sizes
+//! Provides LZ4 compression for reducing transfer sizes. Files are
+//! compressed whole before they are chunked ([`compress_stream`]), so every
+//! chunk is filled to the chunk size with compressed bytes and FEC groups
+//! and pacing see the sizes that actually go on the wire. Compressing chunk
+//! by chunk instead would leave each one a different size.
 
 use bytes::Bytes;
+use serde::{Deserialize, Serialize};
 
 /// Compression mode for chunk data
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
 pub enum CompressionMode {
     /// No compression (default)
     #[default]
+    #[serde(alias = "none")]
     None,
     /// LZ4 fast compression
+    #[serde(alias = "lz4")]
     Lz4,
 }
 
+impl std::str::FromStr for CompressionMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "none" | "off" => Ok(CompressionMode::None),
+            "lz4" => Ok(CompressionMode::Lz4),
+            other => Err(format!(
+                "unknown compression mode '{other}' (expected none or lz4)"
+            )),
+        }
+    }
+}
+
+/// How a file was compressed before it was cut into chunks
+///
+/// The chunks of such a file cover the compressed stream, `size` bytes
+/// long, rather than the file itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StreamCompression {
+    pub mode: CompressionMode,
+    pub size: u64,
+}
+
+/// Compress a whole file ahead of chunking, if that makes it smaller
+///
+/// Returns `None` for [`CompressionMode::None`] and for data that doesn't
+/// shrink; that data is chunked as it is.
+pub fn compress_stream(data: &[u8], mode: CompressionMode) -> Option<(Bytes, StreamCompression)> {
+    if mode == CompressionMode::None {
+        return None;
+    }
+    let compressed = compress(data, mode);
+    (compressed.len() < data.len()).then(|| {
+        let size = compressed.len() as u64;
+        (compressed, StreamCompression { mode, size })
+    })
+}
+
 /// Compress data using the specified mode
 pub fn compress(data: &[u8], mode: CompressionMode) -> Bytes {
     match mode {
@@ -105,6 +151,18 @@ mod tests {
         assert!(ratio > 0.9, "Expected >90% compression for zeros");
     }
 
+    #[test]
+    fn test_stream_falls_back_when_nothing_is_saved() {
+        let data = vec![7u8; 50_000];
+        let (compressed, layout) = compress_stream(&data, CompressionMode::Lz4).unwrap();
+        assert_eq!(layout.size, compressed.len() as u64);
+        assert_eq!(decompress(&compressed, layout.mode).unwrap(), data);
+
+        assert!(compress_stream(&data, CompressionMode::None).is_none());
+        assert!(compress_stream(b"tiny", CompressionMode::Lz4).is_none());
+        assert_eq!("LZ4".parse(), Ok(CompressionMode::Lz4));
+    }
+
     #[test]
     fn test_lz4_random_data() {
         // Random data (not very compressible)
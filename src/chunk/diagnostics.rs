@@ -0,0 +1,173 @@
+//! Why a file can't be rebuilt yet
+//!
+//! A file's data and parity chunks form one Reed-Solomon group: any
+//! `data_chunks` intact shards rebuild it. When fewer are at hand,
+//! [`InsufficientChunks`](super::ChunkError::InsufficientChunks) only says
+//! how many. [`DecodeDiagnostics`] names the sequence numbers that are
+//! missing or failed their checksum and whether each is a data or a parity
+//! shard, so an operator knows exactly what to resend.
+
+use super::types::FileManifest;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+/// Which shards of a file's group are unusable, and whether it decodes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DecodeDiagnostics {
+    pub file_id: String,
+    pub data_chunks: u32,
+    pub parity_chunks: u32,
+    /// Shards that arrived and passed their checksum
+    pub available: u32,
+    /// Data shards that never arrived
+    pub missing_data: Vec<u32>,
+    /// Parity shards that never arrived
+    pub missing_parity: Vec<u32>,
+    /// Data shards that arrived but failed their checksum
+    pub corrupt_data: Vec<u32>,
+    /// Parity shards that arrived but failed their checksum
+    pub corrupt_parity: Vec<u32>,
+}
+
+impl DecodeDiagnostics {
+    /// Diagnostics for `manifest` given the sequence numbers received
+    /// intact and those that failed their checksum
+    ///
+    /// A sequence number in both lists counts as intact: a good copy
+    /// arrived after the bad one.
+    pub fn new(
+        manifest: &FileManifest,
+        intact: impl IntoIterator<Item = u32>,
+        corrupt: impl IntoIterator<Item = u32>,
+    ) -> Self {
+        let total = manifest.total_chunks;
+        let intact: BTreeSet<u32> = intact.into_iter().filter(|&seq| seq < total).collect();
+        let corrupt: BTreeSet<u32> = corrupt
+            .into_iter()
+            .filter(|seq| *seq < total && !intact.contains(seq))
+            .collect();
+        let is_data = |seq: &u32| *seq < manifest.data_chunks;
+
+        let (missing_data, missing_parity) = (0..total)
+            .filter(|seq| !intact.contains(seq) && !corrupt.contains(seq))
+            .partition(is_data);
+        let (corrupt_data, corrupt_parity) = corrupt.into_iter().partition(is_data);
+
+        Self {
+            file_id: manifest.file_id.clone(),
+            data_chunks: manifest.data_chunks,
+            parity_chunks: manifest.parity_chunks,
+            available: intact.len() as u32,
+            missing_data,
+            missing_parity,
+            corrupt_data,
+            corrupt_parity,
+        }
+    }
+
+    /// Enough intact shards to rebuild the file
+    pub fn is_decodable(&self) -> bool {
+        self.available >= self.data_chunks
+    }
+
+    /// Intact shards still needed before the file decodes
+    pub fn shortfall(&self) -> u32 {
+        self.data_chunks.saturating_sub(self.available)
+    }
+
+    /// Every shard that is missing or corrupt, in sequence order
+    pub fn unusable(&self) -> Vec<u32> {
+        let mut seqs: Vec<u32> = [
+            &self.missing_data,
+            &self.missing_parity,
+            &self.corrupt_data,
+            &self.corrupt_parity,
+        ]
+        .into_iter()
+        .flatten()
+        .copied()
+        .collect();
+        seqs.sort_unstable();
+        seqs
+    }
+
+    /// The fewest shards whose resend makes the file decodable
+    ///
+    /// Data shards come first, since they need no reconstruction once they
+    /// arrive. Empty when the file already decodes.
+    pub fn resend_plan(&self) -> Vec<u32> {
+        let mut data: Vec<u32> = self
+            .missing_data
+            .iter()
+            .chain(&self.corrupt_data)
+            .copied()
+            .collect();
+        let mut parity: Vec<u32> = self
+            .missing_parity
+            .iter()
+            .chain(&self.corrupt_parity)
+            .copied()
+            .collect();
+        data.sort_unstable();
+        parity.sort_unstable();
+        data.into_iter()
+            .chain(parity)
+            .take(self.shortfall() as usize)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Priority;
+
+    fn manifest(data_chunks: u32, parity_chunks: u32) -> FileManifest {
+        FileManifest {
+            file_id: "f".into(),
+            filename: "f.bin".into(),
+            total_size: data_chunks as u64 * 4,
+            chunk_size: 4,
+            total_chunks: data_chunks + parity_chunks,
+            data_chunks,
+            parity_chunks,
+            priority: Priority::Normal,
+            checksum: [0u8; 32],
+            zero_runs: Vec::new(),
+            attributes: None,
+            checksum_algorithm: Default::default(),
+            erasure_profile: Default::default(),
+            schedule: None,
+            merkle_root: None,
+            compression: None,
+        }
+    }
+
+    #[test]
+    fn test_splits_unusable_shards_by_kind() {
+        let manifest = manifest(4, 2);
+        // 0 and 5 never arrived, 2 arrived corrupt, 3 arrived corrupt then intact
+        let diagnostics = DecodeDiagnostics::new(&manifest, [1, 3, 4], [2, 3]);
+
+        assert_eq!(diagnostics.available, 3);
+        assert_eq!(diagnostics.missing_data, vec![0]);
+        assert_eq!(diagnostics.missing_parity, vec![5]);
+        assert_eq!(diagnostics.corrupt_data, vec![2]);
+        assert!(diagnostics.corrupt_parity.is_empty());
+        assert!(!diagnostics.is_decodable());
+        assert_eq!(diagnostics.shortfall(), 1);
+        assert_eq!(diagnostics.unusable(), vec![0, 2, 5]);
+        assert_eq!(diagnostics.resend_plan(), vec![0]);
+    }
+
+    #[test]
+    fn test_decodable_group_needs_no_resend() {
+        let manifest = manifest(4, 2);
+        let diagnostics = DecodeDiagnostics::new(&manifest, [0, 2, 4, 5, 9], []);
+
+        assert_eq!(diagnostics.available, 4);
+        assert!(diagnostics.is_decodable());
+        assert!(diagnostics.resend_plan().is_empty());
+        assert_eq!(diagnostics.unusable(), vec![1, 3]);
+    }
+}
@@ -3,6 +3,12 @@ use reed_solomon_erasure::galois_8::ReedSolomon;
 
 use super::error::{ChunkError, Result};
 
+/// Reed-Solomon works over GF(2^8), so a group holds at most 256 shards
+pub const MAX_TOTAL_SHARDS: usize = 256;
+
+/// Shortest stripe worth handing to its own decode thread
+pub const MIN_DECODE_STRIPE: usize = 64 * 1024;
+
 pub struct ErasureCoder {
     data_shards: usize,   // e.g., 10
     parity_shards: usize, // e.g., 3
@@ -42,6 +48,32 @@ impl ErasureCoder {
         Ok(shards.into_iter().map(Bytes::from).collect())
     }
 
+    /// Pad `data_chunks` as `encode` does and append parity computed
+    /// earlier for the same data, skipping the encode
+    pub fn encode_with_parity(
+        &self,
+        data_chunks: Vec<Bytes>,
+        parity: Vec<Bytes>,
+    ) -> Result<Vec<Bytes>> {
+        if data_chunks.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let shard_size = data_chunks.iter().map(|c| c.len()).max().unwrap_or(0);
+        if parity.len() != self.parity_shards || parity.iter().any(|p| p.len() != shard_size) {
+            return Err(ChunkError::InvalidShardSize);
+        }
+
+        let mut shards: Vec<Bytes> = self
+            .prepare_shards(data_chunks, shard_size)?
+            .into_iter()
+            .take(self.data_shards)
+            .map(Bytes::from)
+            .collect();
+        shards.extend(parity);
+        Ok(shards)
+    }
+
     /// Decode chunks even with missing data
     pub fn decode(&self, chunks: Vec<Option<Bytes>>) -> Result<Vec<Bytes>> {
         if chunks.is_empty() {
@@ -78,6 +110,108 @@ impl ErasureCoder {
             .collect())
     }
 
+    /// [`Self::decode`] split across up to `workers` threads
+    ///
+    /// Each byte offset of a group is an independent codeword, so the
+    /// shards are cut into stripes at the same offsets and every stripe is
+    /// decoded on its own thread. Only missing data shards are rebuilt.
+    /// Groups too short to give each thread [`MIN_DECODE_STRIPE`] bytes use
+    /// fewer threads.
+    pub fn decode_parallel(
+        &self,
+        chunks: Vec<Option<Bytes>>,
+        workers: usize,
+    ) -> Result<Vec<Bytes>> {
+        let shard_len = chunks.iter().flatten().map(|c| c.len()).next().unwrap_or(0);
+        let workers = workers.min(shard_len / MIN_DECODE_STRIPE).max(1);
+        if workers == 1 {
+            return self.decode(chunks);
+        }
+
+        let rs = ReedSolomon::new(self.data_shards, self.parity_shards)
+            .map_err(|e| ChunkError::ErasureCoding(e.to_string()))?;
+        let present_count = chunks.iter().filter(|s| s.is_some()).count();
+        if present_count < self.data_shards {
+            return Err(ChunkError::InsufficientChunks {
+                needed: self.data_shards,
+                available: present_count,
+            });
+        }
+        if chunks.iter().flatten().any(|c| c.len() != shard_len) {
+            return Err(ChunkError::InvalidShardSize);
+        }
+
+        let present: Vec<bool> = chunks.iter().map(Option::is_some).collect();
+        let mut shards: Vec<Vec<u8>> = chunks
+            .into_iter()
+            .map(|chunk| chunk.map_or_else(|| vec![0u8; shard_len], |b| b.to_vec()))
+            .collect();
+
+        let stripe_len = shard_len.div_ceil(workers);
+        let mut stripes: Vec<Vec<(&mut [u8], bool)>> = (0..workers)
+            .map(|_| Vec::with_capacity(shards.len()))
+            .collect();
+        for (shard, &present) in shards.iter_mut().zip(&present) {
+            for (stripe, piece) in stripes.iter_mut().zip(shard.chunks_mut(stripe_len)) {
+                stripe.push((piece, present));
+            }
+        }
+
+        std::thread::scope(|scope| {
+            let rs = &rs;
+            let handles: Vec<_> = stripes
+                .into_iter()
+                .filter(|stripe| !stripe.is_empty())
+                .map(|mut stripe| scope.spawn(move || rs.reconstruct_data(&mut stripe)))
+                .collect();
+            handles.into_iter().try_for_each(|handle| {
+                handle
+                    .join()
+                    .map_err(|_| ChunkError::ErasureCoding("decode worker panicked".into()))?
+                    .map_err(|e| ChunkError::ErasureCoding(e.to_string()))
+            })
+        })?;
+
+        shards.truncate(self.data_shards);
+        Ok(shards.into_iter().map(Bytes::from).collect())
+    }
+
+    /// Rebuild the shards at `wanted`, data or parity, from those present
+    ///
+    /// Unlike [`Self::decode`] this returns the requested shards only, in
+    /// the order asked for.
+    pub fn regenerate(&self, chunks: Vec<Option<Bytes>>, wanted: &[usize]) -> Result<Vec<Bytes>> {
+        let rs = ReedSolomon::new(self.data_shards, self.parity_shards)
+            .map_err(|e| ChunkError::ErasureCoding(e.to_string()))?;
+
+        let mut shards: Vec<Option<Vec<u8>>> = chunks
+            .into_iter()
+            .map(|opt_chunk| opt_chunk.map(|b| b.to_vec()))
+            .collect();
+        let present_count = shards.iter().filter(|s| s.is_some()).count();
+        if present_count < self.data_shards {
+            return Err(ChunkError::InsufficientChunks {
+                needed: self.data_shards,
+                available: present_count,
+            });
+        }
+
+        rs.reconstruct(&mut shards)
+            .map_err(|e| ChunkError::ErasureCoding(e.to_string()))?;
+        wanted
+            .iter()
+            .map(|&index| {
+                shards
+                    .get(index)
+                    .and_then(|s| s.clone())
+                    .map(Bytes::from)
+                    .ok_or_else(|| {
+                        ChunkError::ErasureCoding(format!("no shard {index} in the group"))
+                    })
+            })
+            .collect()
+    }
+
     /// Prepare shards for encoding - pad to same size
     fn prepare_shards(&self, data_chunks: Vec<Bytes>, shard_size: usize) -> Result<Vec<Vec<u8>>> {
         let mut shards = Vec::with_capacity(self.data_shards + self.parity_shards);
@@ -178,6 +312,50 @@ mod tests {
         assert_eq!(decoded.len(), 4);
     }
 
+    #[test]
+    fn test_regenerate_evicted_shards() {
+        let coder = ErasureCoder::new(4, 2).unwrap();
+        let data = (0..4u8).map(|i| Bytes::from(vec![i; 16])).collect();
+        let encoded = coder.encode(data).unwrap();
+
+        // Data shard 1 and parity shard 5 are gone
+        let mut held: Vec<Option<Bytes>> = encoded.iter().cloned().map(Some).collect();
+        held[1] = None;
+        held[5] = None;
+        let rebuilt = coder.regenerate(held.clone(), &[5, 1]).unwrap();
+        assert_eq!(rebuilt, vec![encoded[5].clone(), encoded[1].clone()]);
+
+        held[0] = None;
+        held[4] = None;
+        assert!(coder.regenerate(held, &[0]).is_err());
+    }
+
+    #[test]
+    fn test_parallel_decode_matches_sequential() {
+        let coder = ErasureCoder::new(6, 3).unwrap();
+        // Not a multiple of the stripe length, so the last stripe is short
+        let len = 4 * MIN_DECODE_STRIPE + 123;
+        let data = (0..6u8)
+            .map(|i| Bytes::from((0..len).map(|b| (b as u8) ^ i).collect::<Vec<_>>()))
+            .collect::<Vec<_>>();
+        let encoded = coder.encode(data.clone()).unwrap();
+
+        let mut held: Vec<Option<Bytes>> = encoded.into_iter().map(Some).collect();
+        held[0] = None;
+        held[4] = None;
+        held[7] = None;
+        for workers in [1, 3, 16] {
+            assert_eq!(coder.decode_parallel(held.clone(), workers).unwrap(), data);
+        }
+
+        held[1] = None;
+        held[2] = None;
+        assert!(matches!(
+            coder.decode_parallel(held, 4),
+            Err(ChunkError::InsufficientChunks { .. })
+        ));
+    }
+
     #[test]
     fn test_decode_insufficient_chunks() {
         let coder = ErasureCoder::new(4, 2).unwrap();
@@ -1,3 +1,4 @@
+use super::diagnostics::DecodeDiagnostics;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -11,14 +12,29 @@ pub enum ChunkError {
     #[error("Insufficient chunks for reconstruction: need {needed}, have {available}")]
     InsufficientChunks { needed: usize, available: usize },
 
+    #[error(
+        "File {} can't be decoded: {} of {} intact shards, missing data {:?} parity {:?}, corrupt data {:?} parity {:?}",
+        .0.file_id, .0.available, .0.data_chunks, .0.missing_data, .0.missing_parity, .0.corrupt_data, .0.corrupt_parity
+    )]
+    Undecodable(Box<DecodeDiagnostics>),
+
     #[error("Invalid chunk size: {0}")]
     InvalidChunkSize(String),
 
     #[error("Checksum mismatch for file {file_id}")]
     ChecksumMismatch { file_id: String },
 
+    #[error("Compression error: {0}")]
+    Compression(#[from] super::compression::CompressionError),
+
     #[error("Invalid shard size: all shards must be the same size")]
     InvalidShardSize,
+
+    #[error("{} changed while being sent; start a new transfer to send the new version", .0.display())]
+    SourceModified(std::path::PathBuf),
+
+    #[error("{} is locked by a writer", .0.display())]
+    SourceLocked(std::path::PathBuf),
 }
 
 pub type Result<T> = std::result::Result<T, ChunkError>;
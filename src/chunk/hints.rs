@@ -0,0 +1,271 @@
+//! Content-aware send order
+//!
+//! Some formats are useless without a small part of the file: an MP4 can't
+//! play without its `moov` atom, which is often written last, and a ZIP
+//! can't be listed without the central directory at its end. At split time
+//! a [`HintProvider`] looks at the file's leading bytes and names the byte
+//! ranges worth sending first; the manifest records the data chunks that
+//! cover them as a [`ScheduleHint`] and the sender queues those on their own
+//! before the rest, so a partially delivered file is more likely to open.
+
+use super::preview::ByteRange;
+use super::types::ZeroRun;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Most data chunks a hint may move to the front
+pub const MAX_SEND_FIRST: usize = 256;
+
+/// Bytes at the end of a PDF holding its cross-reference trailer
+const PDF_TRAILER_BYTES: u64 = 64 * 1024;
+
+/// Longest ZIP archive comment, which may follow the end of central directory
+const ZIP_MAX_COMMENT: usize = 65_535;
+
+/// What a provider found out about a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContentHints {
+    /// Detected file type, e.g. `mp4`
+    pub kind: String,
+    /// Byte ranges to send before the rest, most important first
+    pub send_first: Vec<ByteRange>,
+}
+
+/// Send order recorded in a manifest
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScheduleHint {
+    /// Detected file type
+    pub kind: String,
+    /// Data chunks to send before the rest, by sequence number, in order
+    pub send_first: Vec<u32>,
+}
+
+/// Picks the parts of a file to send first from its content
+///
+/// Called once per split with the whole file; return `None` for files it
+/// has nothing to say about.
+pub trait HintProvider: Send + Sync {
+    fn hints(&self, filename: &str, data: &[u8]) -> Option<ContentHints>;
+}
+
+/// Recognizes formats by their magic bytes: MP4 and QuickTime send `ftyp`
+/// and `moov` first, ZIP archives their central directory, PDFs their
+/// header and trailer
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MagicHints;
+
+impl HintProvider for MagicHints {
+    fn hints(&self, _filename: &str, data: &[u8]) -> Option<ContentHints> {
+        let (kind, send_first) = if data.get(4..8) == Some(b"ftyp") {
+            ("mp4", mp4_boxes(data, &[b"ftyp", b"moov"]))
+        } else if data.starts_with(b"PK\x03\x04") {
+            ("zip", zip_directory(data)?)
+        } else if data.starts_with(b"%PDF-") {
+            let len = data.len() as u64;
+            let trailer = ByteRange {
+                start: len.saturating_sub(PDF_TRAILER_BYTES),
+                end: len,
+            };
+            ("pdf", vec![ByteRange { start: 0, end: 1 }, trailer])
+        } else {
+            return None;
+        };
+        Some(ContentHints {
+            kind: kind.to_string(),
+            send_first,
+        })
+    }
+}
+
+/// Top-level MP4 boxes of the given types, in `types` order
+fn mp4_boxes(data: &[u8], types: &[&[u8; 4]]) -> Vec<ByteRange> {
+    let len = data.len() as u64;
+    let mut found = HashMap::new();
+    let mut offset = 0u64;
+    while offset + 8 <= len {
+        let at = offset as usize;
+        let mut size = u32::from_be_bytes(data[at..at + 4].try_into().unwrap()) as u64;
+        let box_type = &data[at + 4..at + 8];
+        if size == 1 {
+            // 64-bit size after the type
+            let Some(large) = data.get(at + 8..at + 16) else {
+                break;
+            };
+            size = u64::from_be_bytes(large.try_into().unwrap());
+        } else if size == 0 {
+            // Runs to the end of the file
+            size = len - offset;
+        }
+        if size < 8 {
+            break;
+        }
+        let end = offset.saturating_add(size).min(len);
+        found
+            .entry(box_type.to_vec())
+            .or_insert(ByteRange { start: offset, end });
+        offset = end;
+    }
+    types
+        .iter()
+        .filter_map(|t| found.get(t.as_slice()).copied())
+        .collect()
+}
+
+/// The central directory and end-of-central-directory record of a ZIP
+fn zip_directory(data: &[u8]) -> Option<Vec<ByteRange>> {
+    const EOCD: &[u8] = b"PK\x05\x06";
+    let len = data.len();
+    let search_from = len.checked_sub(22)?;
+    let search_to = search_from.saturating_sub(ZIP_MAX_COMMENT);
+    let eocd = (search_to..=search_from)
+        .rev()
+        .find(|&at| &data[at..at + 4] == EOCD)?;
+    let tail = ByteRange {
+        start: eocd as u64,
+        end: len as u64,
+    };
+
+    let u32_at = |at: usize| u32::from_le_bytes(data[at..at + 4].try_into().unwrap()) as u64;
+    let (size, start) = (u32_at(eocd + 12), u32_at(eocd + 16));
+    // ZIP64 archives keep the real values elsewhere; the record alone
+    // still points a reader at them
+    if start == u32::MAX as u64 || start + size > eocd as u64 {
+        return Some(vec![tail]);
+    }
+    Some(vec![
+        ByteRange {
+            start,
+            end: start + size,
+        },
+        tail,
+    ])
+}
+
+/// File offset of each data chunk, by sequence number
+///
+/// Data chunks fill the chunk-sized pieces the zero runs don't cover, in
+/// file order.
+pub(crate) fn data_chunk_offsets(
+    total_size: u64,
+    chunk_size: u64,
+    zero_runs: &[ZeroRun],
+) -> Vec<u64> {
+    let chunk_size = chunk_size.max(1);
+    (0..total_size.div_ceil(chunk_size))
+        .map(|piece| piece * chunk_size)
+        .filter(|&offset| {
+            !zero_runs
+                .iter()
+                .any(|run| (run.offset..run.offset + run.length).contains(&offset))
+        })
+        .collect()
+}
+
+/// Data chunks covering `ranges`, in range order, without repeats, at most
+/// [`MAX_SEND_FIRST`]
+pub(crate) fn chunks_covering(ranges: &[ByteRange], offsets: &[u64], chunk_size: u64) -> Vec<u32> {
+    let mut chunks = Vec::new();
+    for range in ranges.iter().filter(|r| !r.is_empty()) {
+        for (seq, &offset) in offsets.iter().enumerate() {
+            let overlaps = offset < range.end && offset + chunk_size > range.start;
+            if overlaps && !chunks.contains(&(seq as u32)) {
+                if chunks.len() == MAX_SEND_FIRST {
+                    return chunks;
+                }
+                chunks.push(seq as u32);
+            }
+        }
+    }
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mp4_box(kind: &[u8; 4], body_len: usize) -> Vec<u8> {
+        let mut b = ((body_len + 8) as u32).to_be_bytes().to_vec();
+        b.extend_from_slice(kind);
+        b.resize(body_len + 8, 0xAB);
+        b
+    }
+
+    #[test]
+    fn test_detects_formats_and_ranges() {
+        // ftyp, a large mdat, then moov at the end
+        let mut mp4 = mp4_box(b"ftyp", 16);
+        mp4.extend(mp4_box(b"mdat", 1000));
+        mp4.extend(mp4_box(b"moov", 100));
+        let hints = MagicHints.hints("clip.mp4", &mp4).unwrap();
+        assert_eq!(hints.kind, "mp4");
+        assert_eq!(
+            hints.send_first,
+            [
+                ByteRange { start: 0, end: 24 },
+                ByteRange {
+                    start: 1032,
+                    end: 1140
+                }
+            ]
+        );
+
+        // One stored entry, its central directory, then the end record
+        let mut zip = b"PK\x03\x04".to_vec();
+        zip.resize(100, 0);
+        let directory_start = zip.len() as u32;
+        zip.extend(b"PK\x01\x02");
+        zip.resize(150, 0);
+        let eocd = zip.len() as u64;
+        zip.extend(b"PK\x05\x06");
+        zip.extend([0; 8]);
+        zip.extend(50u32.to_le_bytes());
+        zip.extend(directory_start.to_le_bytes());
+        zip.extend([0; 2]);
+        let hints = MagicHints.hints("logs.zip", &zip).unwrap();
+        assert_eq!(hints.kind, "zip");
+        assert_eq!(
+            hints.send_first,
+            [
+                ByteRange {
+                    start: 100,
+                    end: 150
+                },
+                ByteRange {
+                    start: eocd,
+                    end: zip.len() as u64
+                }
+            ]
+        );
+
+        assert_eq!(
+            MagicHints.hints("doc.pdf", b"%PDF-1.7 ...").unwrap().kind,
+            "pdf"
+        );
+        assert!(MagicHints.hints("notes.txt", b"plain text").is_none());
+        assert!(MagicHints.hints("short.zip", b"PK\x03\x04").is_none());
+    }
+
+    #[test]
+    fn test_ranges_map_to_chunks_around_zero_runs() {
+        // Ten 100-byte pieces; the third and fourth are a zero run
+        let zero_runs = [ZeroRun {
+            offset: 200,
+            length: 200,
+        }];
+        let offsets = data_chunk_offsets(1000, 100, &zero_runs);
+        assert_eq!(offsets, [0, 100, 400, 500, 600, 700, 800, 900]);
+
+        let ranges = [
+            ByteRange {
+                start: 950,
+                end: 1000,
+            },
+            ByteRange { start: 0, end: 10 },
+            ByteRange {
+                start: 350,
+                end: 450,
+            },
+        ];
+        assert_eq!(chunks_covering(&ranges, &offsets, 100), [7, 0, 2]);
+    }
+}
@@ -1,13 +1,25 @@
 use std::path::Path;
+use std::sync::Arc;
+use std::time::Instant;
 
-use blake3::Hasher;
 use bytes::Bytes;
 use tokio::fs::File;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::io::AsyncReadExt;
 
-use super::erasure::ErasureCoder;
+use super::attributes::{self, FileAttributes};
+use super::compression::{self, CompressionMode};
+use super::diagnostics::DecodeDiagnostics;
+use super::erasure::{ErasureCoder, MAX_TOTAL_SHARDS};
 use super::error::{ChunkError, Result};
-use super::types::{Chunk, ChunkMetadata, FileManifest, Priority};
+use super::hints::{chunks_covering, data_chunk_offsets, HintProvider, MagicHints, ScheduleHint};
+use super::parity_cache::{ParityCache, ParityCacheKey};
+use super::plan::TransferPlan;
+use super::profiles::{ErasureProfile, ErasureProfiles};
+use super::source::SourceSnapshot;
+use super::types::{Chunk, ChunkMetadata, FileManifest, Priority, ZeroRun};
+use super::writer::{self, Segment, WritePolicy};
+use crate::integrity::{ChecksumType, IntegrityVerifier, MerkleTree};
+use crate::metrics::latency::{record_stage, Stage};
 
 pub struct ChunkManager {
     erasure_coder: ErasureCoder,
@@ -15,6 +27,29 @@ pub struct ChunkManager {
     /// Parity ratio (parity_shards / data_shards) from the configured coder.
     /// Used to compute adaptive shard counts for smaller files.
     parity_ratio: f64,
+    /// Capture file attributes on split and restore them on reconstruct
+    preserve_attributes: bool,
+    /// Positioned writes in flight during reconstruction; 1 writes the
+    /// file front to back
+    write_concurrency: usize,
+    /// Threads that share one group's Reed-Solomon decode
+    decode_workers: usize,
+    /// Rate limit, fsync and O_DIRECT settings for reconstruction writes
+    write_policy: WritePolicy,
+    /// Algorithm for the chunk and file checksums of new splits
+    checksum_algorithm: ChecksumType,
+    /// Report which shards are unusable when a file can't be decoded
+    decode_diagnostics: bool,
+    /// Parity each priority gets relative to `parity_ratio`
+    erasure_profiles: ErasureProfiles,
+    /// Most parity bytes per data byte for budgeted profiles
+    max_overhead: Option<f64>,
+    /// Picks the chunks to send first from the file's content
+    hint_provider: Option<Arc<dyn HintProvider>>,
+    /// Parity kept from earlier splits of the same file
+    parity_cache: Option<Arc<ParityCache>>,
+    /// Compress files whole before chunking them
+    compression: CompressionMode,
 }
 
 impl ChunkManager {
@@ -25,9 +60,186 @@ impl ChunkManager {
             erasure_coder,
             chunk_size,
             parity_ratio,
+            preserve_attributes: true,
+            write_concurrency: 1,
+            decode_workers: 1,
+            write_policy: WritePolicy::default(),
+            checksum_algorithm: ChecksumType::default(),
+            decode_diagnostics: false,
+            erasure_profiles: ErasureProfiles::default(),
+            max_overhead: None,
+            hint_provider: Some(Arc::new(MagicHints)),
+            parity_cache: None,
+            compression: CompressionMode::None,
         })
     }
 
+    /// Manager that splits a file into the same chunks `manifest` describes
+    ///
+    /// Resumed transfers use this so they keep their original layout after
+    /// the default chunk size or shard counts change. The manifest's parity
+    /// already includes its erasure profile, so none is applied again.
+    pub fn for_manifest(manifest: &FileManifest) -> Result<Self> {
+        Ok(Self::new(
+            manifest.chunk_size,
+            manifest.data_chunks as usize,
+            manifest.parity_chunks as usize,
+        )?
+        .with_preserve_attributes(manifest.attributes.is_some())
+        .with_checksum_algorithm(manifest.checksum_algorithm)
+        .with_erasure_profiles(ErasureProfiles::uniform(ErasureProfile::default()))
+        .with_compression(
+            manifest
+                .compression
+                .map_or(CompressionMode::None, |c| c.mode),
+        ))
+    }
+
+    /// Take the write concurrency and policy, decode workers, checksum
+    /// algorithm, decode diagnostics, overhead budget, hint provider, parity
+    /// cache and compression from `other`, keeping this manager's layout and
+    /// erasure profiles
+    pub fn with_settings_of(self, other: &ChunkManager) -> Self {
+        self.with_write_concurrency(other.write_concurrency)
+            .with_decode_workers(other.decode_workers)
+            .with_write_policy(other.write_policy)
+            .with_checksum_algorithm(other.checksum_algorithm)
+            .with_decode_diagnostics(other.decode_diagnostics)
+            .with_overhead_budget(other.max_overhead)
+            .with_hint_provider(other.hint_provider.clone())
+            .with_parity_cache(other.parity_cache.clone())
+            .with_compression(other.compression)
+    }
+
+    /// Enable or disable attribute preservation (on by default)
+    pub fn with_preserve_attributes(mut self, preserve: bool) -> Self {
+        self.preserve_attributes = preserve;
+        self
+    }
+
+    pub fn preserve_attributes(&self) -> bool {
+        self.preserve_attributes
+    }
+
+    /// Write reconstructed files with up to `concurrency` positioned writes
+    /// in flight (1, the default, writes sequentially)
+    pub fn with_write_concurrency(mut self, concurrency: usize) -> Self {
+        self.write_concurrency = concurrency.max(1);
+        self
+    }
+
+    pub fn write_concurrency(&self) -> usize {
+        self.write_concurrency
+    }
+
+    /// Decode each group on up to `workers` threads (1, the default,
+    /// decodes on one; 0 uses one per core)
+    pub fn with_decode_workers(mut self, workers: usize) -> Self {
+        self.decode_workers = match workers {
+            0 => std::thread::available_parallelism().map_or(1, |n| n.get()),
+            n => n,
+        };
+        self
+    }
+
+    pub fn decode_workers(&self) -> usize {
+        self.decode_workers
+    }
+
+    /// Pace, sync and open reconstructed files as `policy` says (no limit,
+    /// no fsync by default)
+    pub fn with_write_policy(mut self, policy: WritePolicy) -> Self {
+        self.write_policy = policy;
+        self
+    }
+
+    pub fn write_policy(&self) -> WritePolicy {
+        self.write_policy
+    }
+
+    /// Checksum new splits with `algorithm` (BLAKE3 by default)
+    ///
+    /// Reconstruction always uses the algorithm recorded in the manifest.
+    pub fn with_checksum_algorithm(mut self, algorithm: ChecksumType) -> Self {
+        self.checksum_algorithm = algorithm;
+        self
+    }
+
+    pub fn checksum_algorithm(&self) -> ChecksumType {
+        self.checksum_algorithm
+    }
+
+    /// Fail an undecodable reconstruction with
+    /// [`ChunkError::Undecodable`], naming every missing and corrupt shard,
+    /// instead of [`ChunkError::InsufficientChunks`] (off by default)
+    pub fn with_decode_diagnostics(mut self, enabled: bool) -> Self {
+        self.decode_diagnostics = enabled;
+        self
+    }
+
+    pub fn decode_diagnostics(&self) -> bool {
+        self.decode_diagnostics
+    }
+
+    /// Give each priority's files the parity its profile asks for
+    /// ([`ErasureProfiles::default`] unless set)
+    pub fn with_erasure_profiles(mut self, profiles: ErasureProfiles) -> Self {
+        self.erasure_profiles = profiles;
+        self
+    }
+
+    pub fn erasure_profiles(&self) -> ErasureProfiles {
+        self.erasure_profiles
+    }
+
+    /// Hold budgeted profiles to `max_overhead` parity bytes per data byte
+    /// (unlimited by default)
+    pub fn with_overhead_budget(mut self, max_overhead: Option<f64>) -> Self {
+        self.max_overhead = max_overhead;
+        self
+    }
+
+    pub fn overhead_budget(&self) -> Option<f64> {
+        self.max_overhead
+    }
+
+    /// Record a send order for the file types `provider` recognizes
+    /// ([`MagicHints`] by default); `None` sends every file in sequence
+    pub fn with_hint_provider(mut self, provider: Option<Arc<dyn HintProvider>>) -> Self {
+        self.hint_provider = provider;
+        self
+    }
+
+    pub fn content_hints(&self) -> bool {
+        self.hint_provider.is_some()
+    }
+
+    /// Reuse parity from `cache` when a file is split again unchanged with
+    /// the same layout (none by default)
+    pub fn with_parity_cache(mut self, cache: Option<Arc<ParityCache>>) -> Self {
+        self.parity_cache = cache;
+        self
+    }
+
+    pub fn parity_cache(&self) -> Option<&Arc<ParityCache>> {
+        self.parity_cache.as_ref()
+    }
+
+    /// Compress each file whole and chunk the compressed stream, so chunks
+    /// are a uniform size on the wire (off by default)
+    ///
+    /// Files that don't shrink are chunked as they are. Compressed files
+    /// carry no zero runs or send-first hints, since their chunks don't map
+    /// to file offsets.
+    pub fn with_compression(mut self, mode: CompressionMode) -> Self {
+        self.compression = mode;
+        self
+    }
+
+    pub fn compression(&self) -> CompressionMode {
+        self.compression
+    }
+
     /// Split file into chunks with erasure coding.
     ///
     /// Adaptively sizes the erasure coding parameters based on the actual
@@ -39,27 +251,74 @@ impl ChunkManager {
         file_id: String,
         priority: Priority,
     ) -> Result<(FileManifest, Vec<Chunk>)> {
-        // 1. Read file and calculate file-level checksum
-        let mut file = File::open(file_path).await?;
-        let metadata = file.metadata().await?;
-        let total_size = metadata.len();
+        // A file written to while it is read would split into a mix
+        let snapshot = SourceSnapshot::capture(file_path).await?;
+        let file_data = tokio::fs::read(file_path).await?;
+        snapshot.verify(file_path).await?;
+
+        // Attributes are best effort; a file we could read is still sent
+        let attributes = if self.preserve_attributes {
+            FileAttributes::capture(file_path)
+                .await
+                .map_err(|e| tracing::warn!("Could not read attributes of {:?}: {}", file_path, e))
+                .ok()
+        } else {
+            None
+        };
 
-        let mut file_hasher = Hasher::new();
-        let mut file_data = Vec::new();
-        file.read_to_end(&mut file_data).await?;
-        file_hasher.update(&file_data);
-        let file_checksum = *file_hasher.finalize().as_bytes();
+        let filename = file_path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+        self.split_data(&file_data, filename, file_id, priority, attributes)
+    }
 
-        // 2. Split into raw data chunks
-        let mut data_chunks_vec = Vec::new();
-        let mut offset = 0;
+    /// Split data held in memory as if it were a file named `filename`
+    ///
+    /// The manifest carries no attributes, since there is no file to take
+    /// them from.
+    pub fn split_bytes(
+        &self,
+        data: &[u8],
+        filename: String,
+        file_id: String,
+        priority: Priority,
+    ) -> Result<(FileManifest, Vec<Chunk>)> {
+        self.split_data(data, filename, file_id, priority, None)
+    }
 
-        while offset < file_data.len() {
-            let end = std::cmp::min(offset + self.chunk_size, file_data.len());
-            let chunk_data = Bytes::from(file_data[offset..end].to_vec());
-            data_chunks_vec.push(chunk_data);
-            offset = end;
-        }
+    fn split_data(
+        &self,
+        file_data: &[u8],
+        filename: String,
+        file_id: String,
+        priority: Priority,
+        attributes: Option<FileAttributes>,
+    ) -> Result<(FileManifest, Vec<Chunk>)> {
+        let started = Instant::now();
+
+        // 1. Calculate file-level checksum
+        let total_size = file_data.len() as u64;
+        let file_checksum = self.checksum_algorithm.digest(file_data);
+
+        // 2. Split into raw data chunks. A compressed file is chunked as one
+        //    stream, every chunk filled to the chunk size. Otherwise all-zero
+        //    chunks are left out and recorded as zero runs, so sparse images
+        //    only send their data.
+        let compressed = compression::compress_stream(file_data, self.compression);
+        let stream = compressed.as_ref().map_or(file_data, |(data, _)| &data[..]);
+        let compression = compressed.as_ref().map(|(_, layout)| *layout);
+        let (data_chunks_vec, zero_runs) = match &compressed {
+            Some((data, _)) => (
+                (0..data.len())
+                    .step_by(self.chunk_size.max(1))
+                    .map(|start| data.slice(start..(start + self.chunk_size).min(data.len())))
+                    .collect(),
+                Vec::new(),
+            ),
+            None => split_sparse(file_data, self.chunk_size),
+        };
 
         let actual_data_chunks = data_chunks_vec.len();
 
@@ -67,41 +326,67 @@ impl ChunkManager {
         //    - Small files (< half configured): scale DOWN to avoid wasted padding
         //    - Normal files (fits within configured): use configured shards
         //    - Large files (> configured): scale UP to match actual chunk count
-        let configured_data = self.erasure_coder.data_shards();
-
-        let coder = if actual_data_chunks < configured_data / 2 {
-            // Scale down: keep the same parity ratio but match actual chunk count
-            let adaptive_data = actual_data_chunks.max(1);
-            let adaptive_parity =
-                ((adaptive_data as f64 * self.parity_ratio).ceil() as usize).max(1);
-            ErasureCoder::new(adaptive_data, adaptive_parity)?
-        } else if actual_data_chunks <= configured_data {
-            // Normal: file fits within configured shard count
-            ErasureCoder::new(configured_data, self.erasure_coder.parity_shards())?
-        } else {
-            // Scale up: file exceeds configured shard count, scale parity proportionally
-            let adaptive_parity =
-                ((actual_data_chunks as f64 * self.parity_ratio).ceil() as usize).max(1);
-            ErasureCoder::new(actual_data_chunks, adaptive_parity)?
+        //    The priority's profile then adds to (or budgets) the parity.
+        let (data_shards, parity_shards) = self.shard_counts(actual_data_chunks);
+        let erasure_profile = self.erasure_profiles.get(priority);
+        let coder = ErasureCoder::new(
+            data_shards,
+            erasure_profile.parity_shards(data_shards, parity_shards, self.max_overhead),
+        )?;
+
+        // 4. Apply erasure coding, or reuse the parity of an earlier split
+        let encode_started = Instant::now();
+        let cache = self
+            .parity_cache
+            .as_ref()
+            .filter(|_| actual_data_chunks > 0)
+            .map(|cache| {
+                let content = match (self.checksum_algorithm, compression) {
+                    (ChecksumType::Blake3, None) => file_checksum,
+                    _ => ChecksumType::Blake3.digest(stream),
+                };
+                let key = ParityCacheKey {
+                    content,
+                    chunk_size: self.chunk_size,
+                    data_shards: coder.data_shards(),
+                    parity_shards: coder.parity_shards(),
+                };
+                (cache, key)
+            });
+        let cached = cache.as_ref().and_then(|(cache, key)| cache.get(key));
+        let encoded_chunks = match cached {
+            Some(parity) => coder.encode_with_parity(data_chunks_vec, parity)?,
+            None => {
+                let encoded = coder.encode(data_chunks_vec)?;
+                if let Some((cache, key)) = &cache {
+                    let parity = &encoded[coder.data_shards()..];
+                    if let Err(e) = cache.put(key, parity) {
+                        tracing::warn!("Could not cache parity of {}: {}", file_id, e);
+                    }
+                }
+                encoded
+            }
         };
-
-        // 4. Apply erasure coding
-        let encoded_chunks = coder.encode(data_chunks_vec)?;
+        record_stage(Stage::Encode, encode_started.elapsed());
         let total_chunks = encoded_chunks.len();
         let data_chunks_count = coder.data_shards(); // may include padding shards
         let parity_chunks_count = coder.parity_shards();
 
-        // 5. Create chunks with metadata
+        // 5. Create chunks with metadata, each with its proof against the
+        //    Merkle root of all chunk checksums
         let mut chunks = Vec::new();
         let created_at = chrono::Utc::now().timestamp();
-
-        for (seq_num, chunk_data) in encoded_chunks.into_iter().enumerate() {
+        let checksums: Vec<[u8; 32]> = encoded_chunks
+            .iter()
+            .map(|chunk_data| self.checksum_algorithm.digest(chunk_data))
+            .collect();
+        let merkle = MerkleTree::new(self.checksum_algorithm, &checksums);
+
+        for (seq_num, (chunk_data, checksum)) in
+            encoded_chunks.into_iter().zip(checksums).enumerate()
+        {
             let is_parity = seq_num >= data_chunks_count;
 
-            let mut chunk_hasher = Hasher::new();
-            chunk_hasher.update(&chunk_data);
-            let checksum = *chunk_hasher.finalize().as_bytes();
-
             let metadata = ChunkMetadata {
                 chunk_id: uuid::Uuid::new_v4().as_u128() as u64,
                 file_id: file_id.clone(),
@@ -115,6 +400,12 @@ impl ChunkManager {
                 file_size: total_size,
                 file_checksum,
                 data_chunks: data_chunks_count as u32,
+                zero_runs: zero_runs.clone(),
+                attributes: attributes.clone(),
+                checksum_algorithm: self.checksum_algorithm,
+                merkle_proof: merkle.proof(seq_num),
+                trace: None,
+                compression,
             };
 
             chunks.push(Chunk {
@@ -123,14 +414,25 @@ impl ChunkManager {
             });
         }
 
-        // 6. Create manifest
+        // 6. Look for the parts that make a partial file usable
+        let schedule = self
+            .hint_provider
+            .as_ref()
+            .filter(|_| compression.is_none())
+            .and_then(|provider| provider.hints(&filename, file_data))
+            .map(|hints| {
+                let chunk_size = self.chunk_size.max(1) as u64;
+                let offsets = data_chunk_offsets(total_size, chunk_size, &zero_runs);
+                ScheduleHint {
+                    kind: hints.kind,
+                    send_first: chunks_covering(&hints.send_first, &offsets, chunk_size),
+                }
+            });
+
+        // 7. Create manifest
         let manifest = FileManifest {
             file_id: file_id.clone(),
-            filename: file_path
-                .file_name()
-                .unwrap_or_default()
-                .to_string_lossy()
-                .to_string(),
+            filename,
             total_size,
             chunk_size: self.chunk_size,
             total_chunks: total_chunks as u32,
@@ -138,11 +440,131 @@ impl ChunkManager {
             parity_chunks: parity_chunks_count as u32,
             priority,
             checksum: file_checksum,
+            zero_runs,
+            attributes,
+            checksum_algorithm: self.checksum_algorithm,
+            erasure_profile,
+            schedule,
+            merkle_root: Some(merkle.root()),
+            compression,
         };
 
+        record_stage(Stage::Split, started.elapsed());
         Ok((manifest, chunks))
     }
 
+    /// Data and parity shards for a file of `data_chunks` chunks, before
+    /// the priority's erasure profile applies
+    fn shard_counts(&self, data_chunks: usize) -> (usize, usize) {
+        let configured_data = self.erasure_coder.data_shards();
+        if data_chunks < configured_data / 2 {
+            // Scale down: keep the same parity ratio but match actual chunk count
+            let adaptive_data = data_chunks.max(1);
+            let adaptive_parity =
+                ((adaptive_data as f64 * self.parity_ratio).ceil() as usize).max(1);
+            (adaptive_data, adaptive_parity)
+        } else if data_chunks <= configured_data {
+            // Normal: file fits within configured shard count
+            (configured_data, self.erasure_coder.parity_shards())
+        } else {
+            // Scale up: file exceeds configured shard count, scale parity proportionally
+            let adaptive_parity = ((data_chunks as f64 * self.parity_ratio).ceil() as usize).max(1);
+            (data_chunks, adaptive_parity)
+        }
+    }
+
+    /// How a file of `file_size` bytes would be split, without reading one
+    ///
+    /// Sizes assume the file has no all-zero chunks and doesn't compress.
+    pub fn plan(&self, file_size: u64, priority: Priority) -> TransferPlan {
+        let chunk_size = self.chunk_size.max(1) as u64;
+        let data_chunks = file_size.div_ceil(chunk_size) as usize;
+        let profile = self.erasure_profiles.get(priority);
+        let shards_for = |data_chunks| {
+            let (data, parity) = self.shard_counts(data_chunks);
+            let wanted = profile.wanted_parity_shards(data, parity, self.max_overhead);
+            let parity = profile.parity_shards(data, parity, self.max_overhead);
+            (data, parity, wanted)
+        };
+        let mut plan = TransferPlan::empty(file_size, priority, self.chunk_size);
+        if data_chunks == 0 {
+            plan.warnings
+                .push("The file is empty; nothing is sent".into());
+            plan.summary = "Empty file, no chunks".into();
+            return plan;
+        }
+
+        let (data_shards, parity_shards, wanted_parity) = shards_for(data_chunks);
+        let total = data_shards + parity_shards;
+        let shard_size = file_size.min(chunk_size);
+        plan.shard_size = shard_size as usize;
+        plan.data_chunks = data_chunks as u32;
+        plan.padding_chunks = (data_shards - data_chunks) as u32;
+        plan.parity_chunks = parity_shards as u32;
+        plan.total_chunks = total as u32;
+        plan.groups = 1;
+        plan.overhead_bytes = total as u64 * shard_size - file_size;
+        plan.overhead_ratio = plan.overhead_bytes as f64 / file_size as f64;
+        plan.feasible = total <= MAX_TOTAL_SHARDS;
+
+        // Largest chunk count that still gets the profile's full parity
+        let full_parity_chunks = (1..MAX_TOTAL_SHARDS).rev().find(|&n| {
+            let (data, _, wanted) = shards_for(n);
+            data + wanted <= MAX_TOTAL_SHARDS
+        });
+        let suggestion = full_parity_chunks
+            .map(|n| file_size.div_ceil(n as u64))
+            .filter(|&size| size <= u32::MAX as u64)
+            .map(|size| format!("; chunks of at least {size} bytes would fit"))
+            .unwrap_or_default();
+
+        if !plan.feasible {
+            plan.warnings.push(format!(
+                "{data_chunks} chunks and their parity need {} shards, more than \
+                 Reed-Solomon's {MAX_TOTAL_SHARDS}{suggestion}",
+                data_shards + wanted_parity
+            ));
+        } else if parity_shards < wanted_parity {
+            plan.warnings.push(format!(
+                "Only {parity_shards} of the {wanted_parity} parity chunks the {priority:?} \
+                 profile asks for fit within Reed-Solomon's {MAX_TOTAL_SHARDS} shards{suggestion}"
+            ));
+        }
+        if plan.padding_chunks > 0 {
+            plan.warnings.push(format!(
+                "{} of {data_shards} data chunks are zero padding, sending {} bytes for nothing",
+                plan.padding_chunks,
+                plan.padding_chunks as u64 * shard_size
+            ));
+        }
+        if plan.overhead_bytes > file_size {
+            plan.warnings.push(format!(
+                "Sends {:.1}x the file's size",
+                (file_size + plan.overhead_bytes) as f64 / file_size as f64
+            ));
+        }
+        if self.compression != CompressionMode::None {
+            plan.warnings
+                .push("Compression is on; a compressible file needs fewer chunks".into());
+        }
+
+        plan.max_lost_chunks = if plan.feasible {
+            parity_shards as u32
+        } else {
+            0
+        };
+        plan.summary = match plan.feasible {
+            true => format!(
+                "{data_shards} data + {parity_shards} parity chunks of {shard_size} bytes; \
+                 survives losing any {parity_shards} of {total} ({:.0}%), {:.0}% overhead",
+                100.0 * parity_shards as f64 / total as f64,
+                100.0 * plan.overhead_ratio
+            ),
+            false => format!("Can't be split: {total} shards exceed {MAX_TOTAL_SHARDS}"),
+        };
+        plan
+    }
+
     /// Reconstruct file from chunks (even with missing chunks).
     ///
     /// Derives the erasure coder parameters from the manifest so that files
@@ -153,6 +575,8 @@ impl ChunkManager {
         chunks: Vec<Chunk>,
         output_path: &Path,
     ) -> Result<()> {
+        let started = Instant::now();
+
         // Derive coder from the manifest — the sender may have used adaptive
         // shard counts that differ from self.erasure_coder.
         let data_shards = manifest.data_chunks as usize;
@@ -160,7 +584,8 @@ impl ChunkManager {
         let coder = ErasureCoder::new(data_shards, parity_shards)?;
 
         // 1. Validate we have enough chunks
-        if chunks.len() < data_shards {
+        //    (diagnostics wait until checksums are known)
+        if !self.decode_diagnostics && chunks.len() < data_shards {
             return Err(ChunkError::InsufficientChunks {
                 needed: data_shards,
                 available: chunks.len(),
@@ -173,48 +598,84 @@ impl ChunkManager {
 
         // Create Option<Bytes> vector for all possible chunks
         let mut chunk_map: Vec<Option<Bytes>> = vec![None; manifest.total_chunks as usize];
+        let mut corrupt = Vec::new();
         for chunk in sorted_chunks {
             let seq = chunk.metadata.sequence_number as usize;
             if seq < chunk_map.len() {
-                // Verify chunk checksum
-                let mut hasher = Hasher::new();
-                hasher.update(&chunk.data);
-                let calculated_checksum = *hasher.finalize().as_bytes();
-
-                if calculated_checksum == chunk.metadata.checksum {
+                // Verify chunk checksum with the manifest's algorithm, so a
+                // chunk can't pick a weaker one for itself. When the
+                // manifest has a Merkle root, every chunk must prove against
+                // it; one without a proof could be anything.
+                let intact = match manifest.merkle_root {
+                    Some(_) => IntegrityVerifier::verify_chunk_proof(&chunk, manifest).is_ok(),
+                    None => {
+                        manifest.checksum_algorithm.digest(&chunk.data) == chunk.metadata.checksum
+                    }
+                };
+
+                if intact {
                     chunk_map[seq] = Some(chunk.data);
+                } else {
+                    corrupt.push(seq as u32);
                 }
             }
         }
 
-        // 3. Apply Reed-Solomon decoding if chunks are missing
-        let decoded = coder.decode(chunk_map)?;
-
-        // 4. Assemble chunks in order and write to file
-        let mut output_file = File::create(output_path).await?;
-        let mut file_hasher = Hasher::new();
-        let mut bytes_written = 0u64;
-
-        for chunk_data in decoded {
-            // Calculate how much to write from this chunk
-            let remaining = manifest.total_size - bytes_written;
-            let to_write = std::cmp::min(chunk_data.len() as u64, remaining) as usize;
-
-            if to_write > 0 {
-                output_file.write_all(&chunk_data[..to_write]).await?;
-                file_hasher.update(&chunk_data[..to_write]);
-                bytes_written += to_write as u64;
-            }
-
-            if bytes_written >= manifest.total_size {
-                break;
+        if self.decode_diagnostics {
+            let intact =
+                (0..chunk_map.len() as u32).filter(|&seq| chunk_map[seq as usize].is_some());
+            let diagnostics = DecodeDiagnostics::new(manifest, intact, corrupt);
+            if !diagnostics.is_decodable() {
+                return Err(ChunkError::Undecodable(Box::new(diagnostics)));
             }
         }
 
-        output_file.flush().await?;
+        // 3. Apply Reed-Solomon decoding if chunks are missing, off the
+        //    runtime since a large group keeps its workers busy
+        let workers = self.decode_workers;
+        let decoded =
+            tokio::task::spawn_blocking(move || coder.decode_parallel(chunk_map, workers))
+                .await
+                .map_err(|e| ChunkError::ErasureCoding(format!("decode task failed: {e}")))??;
+
+        // 4. Assemble chunks in order and write to file. Zero runs are
+        //    skipped, leaving holes on filesystems that support them.
+        attributes::remove_stale_symlink(output_path).await?;
+        let segments = match manifest.compression {
+            Some(layout) => {
+                let mut stream = Vec::with_capacity(layout.size as usize);
+                for chunk_data in decoded {
+                    let take = (layout.size as usize - stream.len()).min(chunk_data.len());
+                    stream.extend_from_slice(&chunk_data[..take]);
+                }
+                let data = compression::decompress(&stream, layout.mode)?;
+                vec![Segment::Data { offset: 0, data }]
+            }
+            None => writer::layout(manifest, decoded),
+        };
+        // O_DIRECT stages aligned blocks, which only a single cursor fills
+        let calculated_checksum = if self.write_concurrency > 1 && !self.write_policy.direct_io {
+            writer::write_parallel(
+                output_path,
+                segments,
+                manifest.total_size,
+                manifest.checksum_algorithm,
+                self.write_concurrency,
+                self.write_policy,
+            )
+            .await?
+        } else {
+            writer::write_sequential(
+                output_path,
+                segments,
+                manifest.total_size,
+                manifest.checksum_algorithm,
+                self.write_policy,
+            )
+            .await?
+        };
 
         // 5. Verify file-level checksum (skip if manifest checksum is all zeros/placeholder)
-        let calculated_checksum = *file_hasher.finalize().as_bytes();
         let zero_checksum = [0u8; 32];
         if manifest.checksum != zero_checksum && calculated_checksum != manifest.checksum {
             return Err(ChunkError::ChecksumMismatch {
@@ -222,6 +683,18 @@ impl ChunkManager {
             });
         }
 
+        // 6. Restore source attributes; a failure here leaves a valid file
+        if let Some(attributes) = manifest
+            .attributes
+            .as_ref()
+            .filter(|_| self.preserve_attributes)
+        {
+            if let Err(e) = attributes.apply(output_path).await {
+                tracing::warn!("Could not restore attributes on {:?}: {}", output_path, e);
+            }
+        }
+
+        record_stage(Stage::Reconstruct, started.elapsed());
         Ok(())
     }
 
@@ -280,11 +753,9 @@ impl ChunkManager {
         let metadata = file.metadata().await?;
         let total_size = metadata.len();
 
-        let mut file_hasher = Hasher::new();
         let mut file_data = Vec::new();
         file.read_to_end(&mut file_data).await?;
-        file_hasher.update(&file_data);
-        let file_checksum = *file_hasher.finalize().as_bytes();
+        let file_checksum = self.checksum_algorithm.digest(&file_data);
 
         // 2. Split into raw data chunks using the provided chunk size
         let mut data_chunks_vec = Vec::new();
@@ -316,17 +787,21 @@ impl ChunkManager {
         let data_chunks_count = coder.data_shards();
         let parity_chunks_count = coder.parity_shards();
 
-        // 5. Create chunks with metadata
+        // 5. Create chunks with metadata, each with its proof against the
+        //    Merkle root of all chunk checksums
         let mut chunks = Vec::new();
         let created_at = chrono::Utc::now().timestamp();
-
-        for (seq_num, chunk_data) in encoded_chunks.into_iter().enumerate() {
+        let checksums: Vec<[u8; 32]> = encoded_chunks
+            .iter()
+            .map(|chunk_data| self.checksum_algorithm.digest(chunk_data))
+            .collect();
+        let merkle = MerkleTree::new(self.checksum_algorithm, &checksums);
+
+        for (seq_num, (chunk_data, checksum)) in
+            encoded_chunks.into_iter().zip(checksums).enumerate()
+        {
             let is_parity = seq_num >= data_chunks_count;
 
-            let mut chunk_hasher = Hasher::new();
-            chunk_hasher.update(&chunk_data);
-            let checksum = *chunk_hasher.finalize().as_bytes();
-
             let metadata = ChunkMetadata {
                 chunk_id: uuid::Uuid::new_v4().as_u128() as u64,
                 file_id: file_id.clone(),
@@ -340,6 +815,12 @@ impl ChunkManager {
                 file_size: total_size,
                 file_checksum,
                 data_chunks: data_chunks_count as u32,
+                zero_runs: Vec::new(),
+                attributes: None,
+                checksum_algorithm: self.checksum_algorithm,
+                merkle_proof: merkle.proof(seq_num),
+                trace: None,
+                compression: None,
             };
 
             chunks.push(Chunk {
@@ -363,21 +844,68 @@ impl ChunkManager {
             parity_chunks: parity_chunks_count as u32,
             priority,
             checksum: file_checksum,
+            zero_runs: Vec::new(),
+            attributes: None,
+            checksum_algorithm: self.checksum_algorithm,
+            erasure_profile: ErasureProfile::default(),
+            schedule: None,
+            merkle_root: Some(merkle.root()),
+            compression: None,
         };
 
         Ok((manifest, chunks))
     }
 }
 
+/// Split `data` into `chunk_size` pieces, returning all-zero pieces as
+/// merged [`ZeroRun`]s instead of chunks.
+///
+/// At least one piece is always kept as a chunk, so an all-zero file still
+/// produces something to send.
+fn split_sparse(data: &[u8], chunk_size: usize) -> (Vec<Bytes>, Vec<ZeroRun>) {
+    let pieces: Vec<&[u8]> = data.chunks(chunk_size).collect();
+    let mut is_zero: Vec<bool> = pieces
+        .iter()
+        .map(|piece| piece.iter().all(|&b| b == 0))
+        .collect();
+    if is_zero.iter().all(|&zero| zero) {
+        if let Some(last) = is_zero.last_mut() {
+            *last = false;
+        }
+    }
+
+    let mut chunks = Vec::new();
+    let mut zero_runs: Vec<ZeroRun> = Vec::new();
+    for (index, (piece, zero)) in pieces.into_iter().zip(is_zero).enumerate() {
+        if !zero {
+            chunks.push(Bytes::copy_from_slice(piece));
+            continue;
+        }
+        let offset = (index * chunk_size) as u64;
+        match zero_runs.last_mut() {
+            Some(run) if run.offset + run.length == offset => run.length += piece.len() as u64,
+            _ => zero_runs.push(ZeroRun {
+                offset,
+                length: piece.len() as u64,
+            }),
+        }
+    }
+
+    (chunks, zero_runs)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use tempfile::TempDir;
+    use tokio::io::AsyncWriteExt;
 
     async fn create_test_file(path: &Path, size: usize) -> Result<()> {
         let mut file = File::create(path).await?;
         let data: Vec<u8> = (0..size).map(|i| (i % 256) as u8).collect();
         file.write_all(&data).await?;
+        // Tokio finishes writes in the background unless flushed
+        file.flush().await?;
         Ok(())
     }
 
@@ -427,6 +955,257 @@ mod tests {
         assert!(files_equal(&file_path, &output_path).await.unwrap());
     }
 
+    #[tokio::test]
+    async fn test_split_bytes_matches_split_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("readings.bin");
+        create_test_file(&file_path, 300 * 1024).await.unwrap();
+        let data = tokio::fs::read(&file_path).await.unwrap();
+
+        let manager = ChunkManager::new(64 * 1024, 4, 2)
+            .unwrap()
+            .with_preserve_attributes(false);
+        let (from_file, file_chunks) = manager
+            .split_file(&file_path, "f".into(), Priority::Normal)
+            .await
+            .unwrap();
+        let (from_bytes, byte_chunks) = manager
+            .split_bytes(&data, "readings.bin".into(), "f".into(), Priority::Normal)
+            .unwrap();
+
+        assert_eq!(from_bytes.filename, "readings.bin");
+        assert_eq!(from_bytes.checksum, from_file.checksum);
+        assert_eq!(from_bytes.total_chunks, from_file.total_chunks);
+        let payloads = |chunks: &[Chunk]| chunks.iter().map(|c| c.data.clone()).collect::<Vec<_>>();
+        assert_eq!(payloads(&byte_chunks), payloads(&file_chunks));
+
+        let output_path = temp_dir.path().join("out.bin");
+        manager
+            .reconstruct_file(&from_bytes, byte_chunks, &output_path)
+            .await
+            .unwrap();
+        assert!(files_equal(&file_path, &output_path).await.unwrap());
+    }
+
+    #[test]
+    fn test_plan_matches_split() {
+        let manager = ChunkManager::new(1024, 10, 3).unwrap();
+        for (size, priority) in [
+            (3 * 1024, Priority::Normal),
+            (7 * 1024 + 10, Priority::High),
+            (40 * 1024, Priority::Critical),
+        ] {
+            let data: Vec<u8> = (0..size).map(|i| (i % 251 + 1) as u8).collect();
+            let (manifest, chunks) = manager
+                .split_bytes(&data, "f".into(), "f".into(), priority)
+                .unwrap();
+            let plan = manager.plan(size as u64, priority);
+            assert!(plan.feasible);
+            assert_eq!(plan.total_chunks, manifest.total_chunks);
+            assert_eq!(plan.data_chunks + plan.padding_chunks, manifest.data_chunks);
+            assert_eq!(plan.max_lost_chunks, manifest.parity_chunks);
+            let sent: u64 = chunks.iter().map(|c| c.data.len() as u64).sum();
+            assert_eq!(plan.overhead_bytes, sent - size as u64);
+        }
+
+        // 7 chunks pad out to 10 data shards
+        let plan = manager.plan(7 * 1024, Priority::Normal);
+        assert_eq!(plan.padding_chunks, 3);
+        assert_eq!(plan.warnings.len(), 1, "{:?}", plan.warnings);
+    }
+
+    #[test]
+    fn test_plan_flags_layouts_that_cannot_work() {
+        let manager = ChunkManager::new(1024, 10, 3).unwrap();
+
+        // 300 chunks need more shards than Reed-Solomon has
+        let plan = manager.plan(300 * 1024, Priority::Normal);
+        assert!(!plan.feasible);
+        assert_eq!(plan.max_lost_chunks, 0);
+        assert!(
+            plan.warnings[0].contains("chunks of at least"),
+            "{:?}",
+            plan.warnings
+        );
+        assert!(manager
+            .split_bytes(
+                &vec![1u8; 300 * 1024],
+                "f".into(),
+                "f".into(),
+                Priority::Normal
+            )
+            .is_err());
+
+        // 240 chunks fit, but not with all their parity
+        let plan = manager.plan(240 * 1024, Priority::Normal);
+        assert!(plan.feasible);
+        assert_eq!(plan.parity_chunks, 16);
+        assert!(
+            plan.warnings[0].starts_with("Only 16 of the 72"),
+            "{:?}",
+            plan.warnings
+        );
+    }
+
+    #[tokio::test]
+    async fn test_compressed_split_has_uniform_chunks() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("sensor.log");
+        let log: String = (0..4000)
+            .map(|i| format!("{i:05} temp={} status=ok\n", 20 + i % 7))
+            .collect();
+        tokio::fs::write(&file_path, &log).await.unwrap();
+
+        let manager = ChunkManager::new(4096, 10, 3)
+            .unwrap()
+            .with_compression(CompressionMode::Lz4);
+        let (manifest, mut chunks) = manager
+            .split_file(&file_path, "log-1".into(), Priority::Normal)
+            .await
+            .unwrap();
+
+        let layout = manifest.compression.unwrap();
+        assert!(layout.size < manifest.total_size);
+        assert_eq!(manifest.chunked_size(), layout.size);
+        let (plain, _) = ChunkManager::new(4096, 10, 3)
+            .unwrap()
+            .split_file(&file_path, "log-1".into(), Priority::Normal)
+            .await
+            .unwrap();
+        assert!(manifest.total_chunks < plain.total_chunks);
+        assert!(chunks.iter().all(|c| c.data.len() == 4096));
+        assert!(chunks
+            .iter()
+            .all(|c| c.metadata.compression == Some(layout)));
+
+        // A resumed split reproduces the same chunks
+        let (_, resplit) = ChunkManager::for_manifest(&manifest)
+            .unwrap()
+            .split_file(&file_path, "log-1".into(), Priority::Normal)
+            .await
+            .unwrap();
+        let checksums =
+            |chunks: &[Chunk]| -> Vec<_> { chunks.iter().map(|c| c.metadata.checksum).collect() };
+        assert_eq!(checksums(&resplit), checksums(&chunks));
+
+        chunks.remove(0);
+        let output_path = temp_dir.path().join("restored.log");
+        manager
+            .reconstruct_file(&manifest, chunks, &output_path)
+            .await
+            .unwrap();
+        assert!(files_equal(&file_path, &output_path).await.unwrap());
+
+        // Incompressible data goes out as is
+        let noise: Vec<u8> = (0..400u32)
+            .flat_map(|i| ChecksumType::Blake3.digest(&i.to_le_bytes()))
+            .collect();
+        let (manifest, _) = manager
+            .split_bytes(&noise, "noise.bin".into(), "noise".into(), Priority::Normal)
+            .unwrap();
+        assert!(manifest.compression.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_parity_cache_reused_for_unchanged_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let cache = ParityCache::open(temp_dir.path().join("parity"), 1 << 30).unwrap();
+        let manager = ChunkManager::new(64 * 1024, 4, 2)
+            .unwrap()
+            .with_parity_cache(Some(Arc::new(cache)));
+        let mut data: Vec<u8> = (0..300 * 1024).map(|i| (i % 251) as u8).collect();
+
+        let (_, first) = manager
+            .split_bytes(&data, "form.pdf".into(), "a".into(), Priority::Normal)
+            .unwrap();
+        let (manifest, second) = manager
+            .split_bytes(&data, "form.pdf".into(), "b".into(), Priority::Normal)
+            .unwrap();
+        let payloads = |chunks: &[Chunk]| chunks.iter().map(|c| c.data.clone()).collect::<Vec<_>>();
+        assert_eq!(payloads(&first), payloads(&second));
+        let stats = manager.parity_cache().unwrap().stats();
+        assert_eq!((stats.entries, stats.hits, stats.misses), (1, 1, 1));
+
+        // Cached parity still repairs lost data chunks
+        let output_path = temp_dir.path().join("out.bin");
+        let kept: Vec<Chunk> = second.into_iter().skip(2).collect();
+        manager
+            .reconstruct_file(&manifest, kept, &output_path)
+            .await
+            .unwrap();
+        assert_eq!(tokio::fs::read(&output_path).await.unwrap(), data);
+
+        // A changed file is encoded afresh
+        data[0] ^= 1;
+        manager
+            .split_bytes(&data, "form.pdf".into(), "c".into(), Priority::Normal)
+            .unwrap();
+        let stats = manager.parity_cache().unwrap().stats();
+        assert_eq!((stats.entries, stats.hits, stats.misses), (2, 1, 2));
+    }
+
+    #[test]
+    fn test_erasure_profile_sets_parity_by_priority() {
+        let data = vec![5u8; 10 * 1024];
+        let manager = ChunkManager::new(1024, 10, 2)
+            .unwrap()
+            .with_overhead_budget(Some(0.1));
+        let split = |priority| {
+            manager
+                .split_bytes(&data, "f.bin".into(), "f".into(), priority)
+                .unwrap()
+        };
+
+        // Critical gets half as much parity again, whatever the budget
+        let (critical, chunks) = split(Priority::Critical);
+        assert_eq!((critical.data_chunks, critical.parity_chunks), (10, 3));
+        assert_eq!(chunks.len(), 13);
+        assert_eq!(critical.erasure_profile.extra_parity_percent, 50);
+
+        // Normal is held to the 10% budget
+        let (normal, _) = split(Priority::Normal);
+        assert_eq!(normal.parity_chunks, 1);
+        assert!(normal.erasure_profile.budgeted);
+
+        // A resumed transfer re-splits to the recorded layout
+        let (resplit, _) = ChunkManager::for_manifest(&critical)
+            .unwrap()
+            .split_bytes(&data, "f.bin".into(), "f".into(), Priority::Critical)
+            .unwrap();
+        assert_eq!(resplit.parity_chunks, critical.parity_chunks);
+    }
+
+    #[test]
+    fn test_split_records_chunks_to_send_first() {
+        // ftyp, a mostly zero mdat, then moov across the last two pieces
+        let mut data = vec![0u8; 8192];
+        data[..4].copy_from_slice(&16u32.to_be_bytes());
+        data[4..8].copy_from_slice(b"ftyp");
+        data[16..20].copy_from_slice(&(7000 - 16u32).to_be_bytes());
+        data[20..24].copy_from_slice(b"mdat");
+        data[2048..3072].fill(1);
+        data[7000..7004].copy_from_slice(&1192u32.to_be_bytes());
+        data[7004..7008].copy_from_slice(b"moov");
+        data[7008..].fill(7);
+        let manager = ChunkManager::new(1024, 4, 2).unwrap();
+        let split = |manager: &ChunkManager| {
+            manager
+                .split_bytes(&data, "clip.mp4".into(), "f".into(), Priority::Normal)
+                .unwrap()
+                .0
+        };
+
+        // Zero pieces take no sequence numbers: data chunks start at 0,
+        // 2048, 6144 and 7168, and moov spans the last two
+        let manifest = split(&manager);
+        assert_eq!(manifest.zero_runs.len(), 2);
+        let schedule = manifest.schedule.unwrap();
+        assert_eq!(schedule.kind, "mp4");
+        assert_eq!(schedule.send_first, [0, 2, 3]);
+
+        assert!(split(&manager.with_hint_provider(None)).schedule.is_none());
+    }
+
     #[tokio::test]
     async fn test_reconstruct_with_missing_chunks() {
         let temp_dir = TempDir::new().unwrap();
@@ -458,6 +1237,39 @@ mod tests {
         assert!(files_equal(&file_path, &output_path).await.unwrap());
     }
 
+    #[tokio::test]
+    async fn test_forged_chunk_fails_merkle_proof() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("test.bin");
+        create_test_file(&file_path, 512 * 1024).await.unwrap();
+
+        let manager = ChunkManager::new(128 * 1024, 4, 2).unwrap();
+        let (manifest, mut chunks) = manager
+            .split_file(&file_path, "test-merkle".into(), Priority::High)
+            .await
+            .unwrap();
+        assert!(manifest.merkle_root.is_some());
+
+        // A relay swaps a chunk's data and fixes up its checksum to match
+        let forged = Bytes::from(vec![0xAB; chunks[2].data.len()]);
+        chunks[2].metadata.checksum = manifest.checksum_algorithm.digest(&forged);
+        chunks[2].data = forged;
+
+        // So does one that drops its proof to dodge the root
+        let stripped = Bytes::from(vec![0xCD; chunks[3].data.len()]);
+        chunks[3].metadata.checksum = manifest.checksum_algorithm.digest(&stripped);
+        chunks[3].metadata.merkle_proof = None;
+        chunks[3].data = stripped;
+
+        // The forgeries are dropped as corrupt and parity covers for them
+        let output_path = temp_dir.path().join("reconstructed.bin");
+        manager
+            .reconstruct_file(&manifest, chunks, &output_path)
+            .await
+            .unwrap();
+        assert!(files_equal(&file_path, &output_path).await.unwrap());
+    }
+
     #[tokio::test]
     async fn test_insufficient_chunks_error() {
         let temp_dir = TempDir::new().unwrap();
@@ -487,6 +1299,216 @@ mod tests {
         ));
     }
 
+    #[tokio::test]
+    async fn test_decode_diagnostics_name_unusable_shards() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("test.bin");
+        create_test_file(&file_path, 512 * 1024).await.unwrap();
+
+        let manager = ChunkManager::new(128 * 1024, 4, 2)
+            .unwrap()
+            .with_decode_diagnostics(true);
+        let (manifest, mut chunks) = manager
+            .split_file(&file_path, "test-diag".into(), Priority::Normal)
+            .await
+            .unwrap();
+
+        // Lose data shard 1 and parity shard 5, corrupt data shard 3
+        chunks.retain(|c| ![1, 5].contains(&c.metadata.sequence_number));
+        let corrupted = chunks
+            .iter_mut()
+            .find(|c| c.metadata.sequence_number == 3)
+            .unwrap();
+        corrupted.data = Bytes::from(vec![0xEE; corrupted.data.len()]);
+
+        let output_path = temp_dir.path().join("reconstructed.bin");
+        let error = manager
+            .reconstruct_file(&manifest, chunks, &output_path)
+            .await
+            .unwrap_err();
+        let ChunkError::Undecodable(diagnostics) = error else {
+            panic!("expected diagnostics, got {error}");
+        };
+        assert_eq!(diagnostics.available, 3);
+        assert_eq!(diagnostics.missing_data, vec![1]);
+        assert_eq!(diagnostics.missing_parity, vec![5]);
+        assert_eq!(diagnostics.corrupt_data, vec![3]);
+        assert_eq!(diagnostics.resend_plan(), vec![1]);
+    }
+
+    #[tokio::test]
+    async fn test_sparse_file_skips_zero_chunks() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("disk.img");
+
+        // 64KB chunks: data, 3 zero chunks, data, then a zero tail
+        let chunk = 64 * 1024;
+        let mut data = vec![0u8; chunk * 7 + 100];
+        data[..chunk].fill(0xAB);
+        data[chunk * 4..chunk * 5].fill(0xCD);
+        tokio::fs::write(&file_path, &data).await.unwrap();
+
+        let manager = ChunkManager::new(chunk, 10, 3).unwrap();
+        let (manifest, mut chunks) = manager
+            .split_file(&file_path, "sparse".into(), Priority::Normal)
+            .await
+            .unwrap();
+
+        assert_eq!(manifest.data_chunks, 2);
+        assert_eq!(
+            manifest.zero_runs,
+            vec![
+                ZeroRun {
+                    offset: chunk as u64,
+                    length: chunk as u64 * 3,
+                },
+                ZeroRun {
+                    offset: chunk as u64 * 5,
+                    length: chunk as u64 * 2 + 100,
+                },
+            ]
+        );
+        assert_eq!(
+            manifest.sparse_bytes(),
+            data.len() as u64 - 2 * chunk as u64
+        );
+        assert_eq!(chunks[0].metadata.zero_runs, manifest.zero_runs);
+
+        // Still recoverable with a lost data chunk
+        chunks.remove(0);
+        let output_path = temp_dir.path().join("restored.img");
+        manager
+            .reconstruct_file(&manifest, chunks, &output_path)
+            .await
+            .unwrap();
+        assert!(files_equal(&file_path, &output_path).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_parallel_reconstruct_matches_source() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("volume.img");
+
+        // Enough data chunks for several write batches, with holes between
+        let chunk = 1024 * 1024;
+        let mut data: Vec<u8> = (0..chunk * 24 + 4321).map(|i| (i % 253) as u8).collect();
+        data[chunk * 3..chunk * 6].fill(0);
+        data[chunk * 20..chunk * 24 + 4321].fill(0);
+        tokio::fs::write(&file_path, &data).await.unwrap();
+
+        let manager = ChunkManager::new(chunk, 16, 4)
+            .unwrap()
+            .with_write_concurrency(4)
+            .with_decode_workers(4);
+        assert_eq!(manager.write_concurrency(), 4);
+        assert_eq!(manager.decode_workers(), 4);
+        let (manifest, mut chunks) = manager
+            .split_file(&file_path, "parallel".into(), Priority::Normal)
+            .await
+            .unwrap();
+        assert_eq!(manifest.zero_runs.len(), 2);
+
+        chunks.remove(7);
+        chunks.remove(2);
+        let output_path = temp_dir.path().join("restored.img");
+        manager
+            .reconstruct_file(&manifest, chunks, &output_path)
+            .await
+            .unwrap();
+        assert!(files_equal(&file_path, &output_path).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_checksum_algorithm_recorded_and_used() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("scan.tif");
+        create_test_file(&file_path, 300 * 1024).await.unwrap();
+
+        for algorithm in [ChecksumType::Sha256, ChecksumType::Crc32] {
+            let manager = ChunkManager::new(64 * 1024, 4, 2)
+                .unwrap()
+                .with_checksum_algorithm(algorithm);
+            let (manifest, mut chunks) = manager
+                .split_file(&file_path, "scan".into(), Priority::High)
+                .await
+                .unwrap();
+
+            let source = tokio::fs::read(&file_path).await.unwrap();
+            assert_eq!(manifest.checksum_algorithm, algorithm);
+            assert_eq!(manifest.checksum, algorithm.digest(&source));
+            assert!(chunks
+                .iter()
+                .all(|c| c.metadata.checksum_algorithm == algorithm
+                    && c.metadata.checksum == algorithm.digest(&c.data)));
+
+            // A default manager still decodes it, using the manifest's algorithm
+            chunks.remove(0);
+            let output_path = temp_dir.path().join(format!("{algorithm:?}.tif"));
+            ChunkManager::new(64 * 1024, 4, 2)
+                .unwrap()
+                .reconstruct_file(&manifest, chunks, &output_path)
+                .await
+                .unwrap();
+            assert!(files_equal(&file_path, &output_path).await.unwrap());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_attributes_restored_after_reconstruct() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("report.txt");
+        std::fs::write(&file_path, vec![7u8; 4096]).unwrap();
+        let modified = std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_500_000_000);
+        std::fs::File::options()
+            .write(true)
+            .open(&file_path)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+
+        let manager = ChunkManager::new(1024, 4, 2).unwrap();
+        let (manifest, chunks) = manager
+            .split_file(&file_path, "attrs".into(), Priority::Normal)
+            .await
+            .unwrap();
+        assert_eq!(
+            manifest.attributes.as_ref().unwrap().mtime,
+            Some(1_500_000_000)
+        );
+
+        let output_path = temp_dir.path().join("restored.txt");
+        manager
+            .reconstruct_file(&manifest, chunks.clone(), &output_path)
+            .await
+            .unwrap();
+        let restored = std::fs::metadata(&output_path).unwrap().modified().unwrap();
+        assert_eq!(restored, modified);
+
+        // Disabled on the receiving side: the file keeps its write time
+        let plain_path = temp_dir.path().join("plain.txt");
+        ChunkManager::new(1024, 4, 2)
+            .unwrap()
+            .with_preserve_attributes(false)
+            .reconstruct_file(&manifest, chunks, &plain_path)
+            .await
+            .unwrap();
+        let plain = std::fs::metadata(&plain_path).unwrap().modified().unwrap();
+        assert_ne!(plain, modified);
+    }
+
+    #[test]
+    fn test_all_zero_file_keeps_one_chunk() {
+        let (chunks, zero_runs) = split_sparse(&[0u8; 300], 100);
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(
+            zero_runs,
+            vec![ZeroRun {
+                offset: 0,
+                length: 200
+            }]
+        );
+    }
+
     #[test]
     fn test_adaptive_chunk_sizing() {
         let manager = ChunkManager::new(256 * 1024, 10, 3).unwrap();
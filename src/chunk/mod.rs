@@ -1,13 +1,42 @@
 pub mod adaptive;
+pub mod attributes;
+pub mod autotune;
 pub mod compression;
+pub mod diagnostics;
 pub mod erasure;
 pub mod error;
+pub mod hints;
 pub mod manager;
+pub mod parity_cache;
+pub mod plan;
+pub mod preview;
+pub mod profiles;
+pub mod reorder;
+pub mod source;
+pub mod spool;
 pub mod types;
+mod writer;
 
 pub use adaptive::{AdaptiveErasureCoder, AdaptiveErasureConfig, AdaptiveStatus};
-pub use compression::{compress, decompress, CompressionError, CompressionMode};
+pub use attributes::FileAttributes;
+pub use autotune::{AutotuneConfig, AutotuneReport, ErasureBenchmark};
+pub use compression::{
+    compress, compress_stream, decompress, CompressionError, CompressionMode, StreamCompression,
+};
+pub use diagnostics::DecodeDiagnostics;
 pub use erasure::ErasureCoder;
 pub use error::{ChunkError, Result};
+pub use hints::{ContentHints, HintProvider, MagicHints, ScheduleHint, MAX_SEND_FIRST};
 pub use manager::ChunkManager;
-pub use types::{Chunk, ChunkMetadata, FileManifest, Priority};
+pub use parity_cache::{ParityCache, ParityCacheKey, ParityCacheStats};
+pub use plan::TransferPlan;
+pub use preview::{ByteRange, PartialFile};
+pub use profiles::{ErasureProfile, ErasureProfiles, MAX_EXTRA_PARITY_PERCENT};
+pub use reorder::{ReorderConfig, ReorderStats, SequenceAssembler};
+pub use source::{SourceGuard, SourceSnapshot, SourceWatch};
+pub use spool::ChunkSpool;
+pub use types::{
+    Chunk, ChunkMetadata, ChunkTrace, FileManifest, HopStage, Priority, TraceHop, ZeroRun,
+    MAX_TRACE_HOPS,
+};
+pub use writer::WritePolicy;
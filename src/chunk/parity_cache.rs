@@ -0,0 +1,365 @@
+//! On-disk cache of parity shards for files that are sent again and again
+//!
+//! Standard forms and map tiles go out unchanged many times; encoding their
+//! parity once and reading it back on later splits saves the Reed-Solomon
+//! pass. Entries are keyed by a BLAKE3 digest of the file and the coding
+//! parameters, so a changed file or layout simply misses. Each entry file
+//! holds a header, a BLAKE3 digest of the parity and the parity shards
+//! back to back; an entry that fails its digest is dropped on read.
+//!
+//! The least recently used entries are evicted once the cache holds more
+//! than its byte limit. Recency survives restarts through entry mtimes.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use bytes::Bytes;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+use super::error::Result;
+use crate::integrity::ChecksumType;
+
+/// Extension of entry files under the cache directory
+const ENTRY_EXTENSION: &str = "parity";
+
+/// Bytes before the parity: shard count and shard size as little-endian
+/// `u32`s, then the parity digest
+const HEADER_LEN: usize = 8 + 32;
+
+/// What a cached parity set was encoded from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ParityCacheKey {
+    /// BLAKE3 digest of the whole file
+    pub content: [u8; 32],
+    pub chunk_size: usize,
+    pub data_shards: usize,
+    pub parity_shards: usize,
+}
+
+impl ParityCacheKey {
+    /// Key for `data` split into `chunk_size` chunks and coded
+    /// `data_shards` + `parity_shards`
+    pub fn new(data: &[u8], chunk_size: usize, data_shards: usize, parity_shards: usize) -> Self {
+        Self {
+            content: ChecksumType::Blake3.digest(data),
+            chunk_size,
+            data_shards,
+            parity_shards,
+        }
+    }
+
+    fn file_name(&self) -> String {
+        format!(
+            "{}-{}-{}-{}.{}",
+            hex::encode(self.content),
+            self.chunk_size,
+            self.data_shards,
+            self.parity_shards,
+            ENTRY_EXTENSION
+        )
+    }
+}
+
+/// Counters for a [`ParityCache`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ParityCacheStats {
+    pub entries: usize,
+    pub bytes: u64,
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+}
+
+#[derive(Debug)]
+struct Entry {
+    size: u64,
+    /// Higher is more recently used
+    last_used: u64,
+}
+
+#[derive(Debug, Default)]
+struct Index {
+    entries: HashMap<String, Entry>,
+    bytes: u64,
+    clock: u64,
+    hits: u64,
+    misses: u64,
+    evictions: u64,
+}
+
+impl Index {
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+
+    fn remove(&mut self, name: &str) -> Option<Entry> {
+        let entry = self.entries.remove(name)?;
+        self.bytes -= entry.size;
+        Some(entry)
+    }
+}
+
+/// Parity shards kept on disk between splits of the same file
+#[derive(Debug)]
+pub struct ParityCache {
+    dir: PathBuf,
+    max_bytes: u64,
+    index: Mutex<Index>,
+}
+
+impl ParityCache {
+    /// Open the cache in `dir`, creating it if needed, holding at most
+    /// `max_bytes` of entries
+    ///
+    /// Entries already there are ranked by mtime, oldest first out.
+    pub fn open(dir: impl Into<PathBuf>, max_bytes: u64) -> Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+
+        let mut found = Vec::new();
+        for item in fs::read_dir(&dir)? {
+            let item = item?;
+            let path = item.path();
+            if path.extension().and_then(|e| e.to_str()) != Some(ENTRY_EXTENSION) {
+                continue;
+            }
+            let meta = item.metadata()?;
+            let name = item.file_name().to_string_lossy().to_string();
+            let modified = meta.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            found.push((modified, name, meta.len()));
+        }
+        found.sort();
+
+        let mut index = Index::default();
+        for (_, name, size) in found {
+            let last_used = index.tick();
+            index.bytes += size;
+            index.entries.insert(name, Entry { size, last_used });
+        }
+
+        let cache = Self {
+            dir,
+            max_bytes,
+            index: Mutex::new(index),
+        };
+        cache.evict(&mut cache.index.lock());
+        Ok(cache)
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn max_bytes(&self) -> u64 {
+        self.max_bytes
+    }
+
+    /// Parity shards stored for `key`, or `None` if there are none intact
+    pub fn get(&self, key: &ParityCacheKey) -> Option<Vec<Bytes>> {
+        let name = key.file_name();
+        let path = self.dir.join(&name);
+
+        let mut index = self.index.lock();
+        if !index.entries.contains_key(&name) {
+            index.misses += 1;
+            return None;
+        }
+
+        let parity = fs::read(&path)
+            .ok()
+            .and_then(|data| decode_entry(&data, key.parity_shards));
+        let Some(parity) = parity else {
+            tracing::warn!(path = %path.display(), "dropping unreadable parity cache entry");
+            index.remove(&name);
+            index.misses += 1;
+            let _ = fs::remove_file(&path);
+            return None;
+        };
+
+        let now = index.tick();
+        if let Some(entry) = index.entries.get_mut(&name) {
+            entry.last_used = now;
+        }
+        index.hits += 1;
+        drop(index);
+
+        // Recency is best effort; a failed touch only affects eviction order
+        // after a restart
+        let _ = fs::File::options()
+            .write(true)
+            .open(&path)
+            .and_then(|f| f.set_modified(SystemTime::now()));
+        Some(parity)
+    }
+
+    /// Store `parity` for `key`, evicting older entries to stay in budget
+    ///
+    /// A set larger than the whole cache isn't stored.
+    pub fn put(&self, key: &ParityCacheKey, parity: &[Bytes]) -> Result<()> {
+        let data = encode_entry(parity);
+        let size = data.len() as u64;
+        if size > self.max_bytes {
+            return Ok(());
+        }
+
+        let name = key.file_name();
+        let path = self.dir.join(&name);
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, &data)?;
+        fs::rename(&tmp, &path)?;
+
+        let mut index = self.index.lock();
+        index.remove(&name);
+        let last_used = index.tick();
+        index.bytes += size;
+        index.entries.insert(name, Entry { size, last_used });
+        self.evict(&mut index);
+        Ok(())
+    }
+
+    pub fn stats(&self) -> ParityCacheStats {
+        let index = self.index.lock();
+        ParityCacheStats {
+            entries: index.entries.len(),
+            bytes: index.bytes,
+            hits: index.hits,
+            misses: index.misses,
+            evictions: index.evictions,
+        }
+    }
+
+    /// Drop least recently used entries until within `max_bytes`
+    fn evict(&self, index: &mut Index) {
+        while index.bytes > self.max_bytes {
+            let Some(oldest) = index
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(name, _)| name.clone())
+            else {
+                break;
+            };
+            index.remove(&oldest);
+            index.evictions += 1;
+            if let Err(e) = fs::remove_file(self.dir.join(&oldest)) {
+                tracing::warn!(entry = %oldest, "could not remove parity cache entry: {}", e);
+            }
+        }
+    }
+}
+
+fn encode_entry(parity: &[Bytes]) -> Vec<u8> {
+    let shard_size = parity.first().map_or(0, |p| p.len());
+    let mut body = Vec::with_capacity(parity.len() * shard_size);
+    for shard in parity {
+        body.extend_from_slice(shard);
+    }
+
+    let mut data = Vec::with_capacity(HEADER_LEN + body.len());
+    data.extend_from_slice(&(parity.len() as u32).to_le_bytes());
+    data.extend_from_slice(&(shard_size as u32).to_le_bytes());
+    data.extend_from_slice(&ChecksumType::Blake3.digest(&body));
+    data.extend_from_slice(&body);
+    data
+}
+
+/// Parity shards in an entry, if it holds `expected` intact shards
+fn decode_entry(data: &[u8], expected: usize) -> Option<Vec<Bytes>> {
+    if data.len() < HEADER_LEN {
+        return None;
+    }
+    let count = u32::from_le_bytes(data[0..4].try_into().ok()?) as usize;
+    let shard_size = u32::from_le_bytes(data[4..8].try_into().ok()?) as usize;
+    let body = &data[HEADER_LEN..];
+    if count != expected || body.len() != count * shard_size {
+        return None;
+    }
+    if ChecksumType::Blake3.digest(body) != data[8..HEADER_LEN] {
+        return None;
+    }
+
+    let body = Bytes::copy_from_slice(body);
+    Some(
+        (0..count)
+            .map(|i| body.slice(i * shard_size..(i + 1) * shard_size))
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn parity(fill: u8, shards: usize, size: usize) -> Vec<Bytes> {
+        (0..shards)
+            .map(|i| Bytes::from(vec![fill.wrapping_add(i as u8); size]))
+            .collect()
+    }
+
+    #[test]
+    fn test_round_trip_and_reopen() {
+        let dir = TempDir::new().unwrap();
+        let key = ParityCacheKey::new(b"standard form", 1024, 4, 2);
+        let cache = ParityCache::open(dir.path(), 1 << 20).unwrap();
+        assert!(cache.get(&key).is_none());
+
+        cache.put(&key, &parity(7, 2, 64)).unwrap();
+        assert_eq!(cache.get(&key), Some(parity(7, 2, 64)));
+
+        // Different coding parameters miss
+        let other = ParityCacheKey::new(b"standard form", 1024, 4, 3);
+        assert!(cache.get(&other).is_none());
+
+        let stats = cache.stats();
+        assert_eq!((stats.entries, stats.hits, stats.misses), (1, 1, 2));
+
+        let reopened = ParityCache::open(dir.path(), 1 << 20).unwrap();
+        assert_eq!(reopened.get(&key), Some(parity(7, 2, 64)));
+    }
+
+    #[test]
+    fn test_evicts_least_recently_used() {
+        let dir = TempDir::new().unwrap();
+        let entry = (HEADER_LEN + 2 * 100) as u64;
+        let cache = ParityCache::open(dir.path(), 2 * entry).unwrap();
+        let keys: Vec<_> = (0..3u8)
+            .map(|i| ParityCacheKey::new(&[i], 100, 2, 2))
+            .collect();
+
+        cache.put(&keys[0], &parity(0, 2, 100)).unwrap();
+        cache.put(&keys[1], &parity(1, 2, 100)).unwrap();
+        // Using the first makes the second the eviction candidate
+        assert!(cache.get(&keys[0]).is_some());
+        cache.put(&keys[2], &parity(2, 2, 100)).unwrap();
+
+        assert!(cache.get(&keys[0]).is_some());
+        assert!(cache.get(&keys[1]).is_none());
+        assert!(cache.get(&keys[2]).is_some());
+        let stats = cache.stats();
+        assert_eq!(stats.evictions, 1);
+        assert_eq!(stats.bytes, 2 * entry);
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn test_corrupt_entry_is_dropped() {
+        let dir = TempDir::new().unwrap();
+        let key = ParityCacheKey::new(b"map tile", 256, 2, 1);
+        let cache = ParityCache::open(dir.path(), 1 << 20).unwrap();
+        cache.put(&key, &parity(3, 1, 32)).unwrap();
+
+        let path = dir.path().join(key.file_name());
+        let mut data = fs::read(&path).unwrap();
+        *data.last_mut().unwrap() ^= 0xff;
+        fs::write(&path, data).unwrap();
+
+        assert!(cache.get(&key).is_none());
+        assert!(!path.exists());
+        assert_eq!(cache.stats().entries, 0);
+    }
+}
@@ -0,0 +1,64 @@
+//! Previews of how a file would be split
+//!
+//! Shard counts that suit one file size can fail for another: past 256
+//! shards Reed-Solomon can't code the file at all, and just under it parity
+//! is cut back. [`ChunkManager::plan`](super::ChunkManager::plan) works the
+//! layout out from the size alone, so it can be checked before a transfer
+//! starts.
+
+use super::types::Priority;
+use serde::{Deserialize, Serialize};
+
+/// Layout a file of a given size would be split into
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TransferPlan {
+    pub file_size: u64,
+    pub priority: Priority,
+    pub chunk_size: usize,
+    /// Bytes in every chunk sent; below `chunk_size` for files shorter
+    /// than one chunk
+    pub shard_size: usize,
+    /// Chunks holding file data
+    pub data_chunks: u32,
+    /// Zero-filled chunks making the data up to the coder's shard count
+    pub padding_chunks: u32,
+    pub parity_chunks: u32,
+    pub total_chunks: u32,
+    /// Reed-Solomon groups; a file is coded as one group
+    pub groups: u32,
+    /// Bytes sent beyond the file itself
+    pub overhead_bytes: u64,
+    /// `overhead_bytes` per byte of file
+    pub overhead_ratio: f64,
+    /// Chunks that can be lost with the file still rebuilt
+    pub max_lost_chunks: u32,
+    /// Whether the file can be split this way at all
+    pub feasible: bool,
+    /// One line describing the layout and what it survives
+    pub summary: String,
+    /// Problems with the layout, worst first
+    pub warnings: Vec<String>,
+}
+
+impl TransferPlan {
+    /// Plan with no chunks yet
+    pub(crate) fn empty(file_size: u64, priority: Priority, chunk_size: usize) -> Self {
+        Self {
+            file_size,
+            priority,
+            chunk_size,
+            shard_size: 0,
+            data_chunks: 0,
+            padding_chunks: 0,
+            parity_chunks: 0,
+            total_chunks: 0,
+            groups: 0,
+            overhead_bytes: 0,
+            overhead_ratio: 0.0,
+            max_lost_chunks: 0,
+            feasible: true,
+            summary: String::new(),
+            warnings: Vec::new(),
+        }
+    }
+}
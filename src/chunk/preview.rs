@@ -0,0 +1,219 @@
+//! Partially received files that can be opened before the transfer ends
+//!
+//! Data chunks carry file bytes verbatim, so once the
+//! [`SequenceAssembler`](crate::chunk::reorder::SequenceAssembler) releases
+//! a group its data chunks can go straight to their offset in the output
+//! file, with holes where nothing has arrived yet. [`PartialFile`] does
+//! that and tracks which byte ranges hold real content, so a viewer can
+//! render what is there. Zero runs are valid from the start. Parity chunks
+//! are skipped; whatever they recover is written by the final
+//! reconstruction, which replaces the preview.
+
+use crate::chunk::error::Result;
+use crate::chunk::hints::data_chunk_offsets;
+use crate::chunk::{Chunk, FileManifest};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tokio::fs::File;
+use tokio::io::{AsyncSeekExt, AsyncWriteExt};
+
+/// Half-open span of file bytes, `start..end`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ByteRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl ByteRange {
+    pub fn len(&self) -> u64 {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start >= self.end
+    }
+}
+
+/// Output file written chunk by chunk as data arrives
+#[derive(Debug)]
+pub struct PartialFile {
+    path: PathBuf,
+    file: File,
+    total_size: u64,
+    chunk_size: u64,
+    /// File offset of each data chunk, by sequence number
+    offsets: Vec<u64>,
+    written: Vec<bool>,
+    /// Valid ranges, sorted and merged
+    valid: Vec<ByteRange>,
+}
+
+impl PartialFile {
+    /// Create `path` at the manifest's full size, all holes
+    ///
+    /// Compressed files can't be previewed: their chunks only make sense
+    /// once the whole stream is decoded.
+    pub async fn create(manifest: &FileManifest, path: impl Into<PathBuf>) -> Result<Self> {
+        if manifest.compression.is_some() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "compressed transfers can't be previewed",
+            )
+            .into());
+        }
+        let path = path.into();
+        let file = File::create(&path).await?;
+        file.set_len(manifest.total_size).await?;
+
+        // Padding shards past the end have no offset
+        let chunk_size = manifest.chunk_size.max(1) as u64;
+        let offsets = data_chunk_offsets(manifest.total_size, chunk_size, &manifest.zero_runs);
+
+        let mut partial = Self {
+            path,
+            file,
+            total_size: manifest.total_size,
+            chunk_size,
+            written: vec![false; offsets.len()],
+            offsets,
+            valid: Vec::new(),
+        };
+        for run in &manifest.zero_runs {
+            partial.mark_valid(run.offset, run.offset + run.length);
+        }
+        Ok(partial)
+    }
+
+    /// Write the data chunks among `chunks` at their offsets; returns the
+    /// bytes written
+    ///
+    /// Parity chunks and chunks already written are skipped.
+    pub async fn write(&mut self, chunks: &[Chunk]) -> Result<u64> {
+        let mut bytes = 0;
+        for chunk in chunks {
+            let seq = chunk.metadata.sequence_number as usize;
+            if chunk.metadata.is_parity || self.written.get(seq) != Some(&false) {
+                continue;
+            }
+            let offset = self.offsets[seq];
+            let end = (offset + self.chunk_size).min(self.total_size);
+            let length = ((end - offset) as usize).min(chunk.data.len());
+
+            self.file.seek(std::io::SeekFrom::Start(offset)).await?;
+            self.file.write_all(&chunk.data[..length]).await?;
+            self.written[seq] = true;
+            self.mark_valid(offset, offset + length as u64);
+            bytes += length as u64;
+        }
+        self.file.flush().await?;
+        Ok(bytes)
+    }
+
+    /// Byte ranges holding received data or known zeros
+    pub fn valid_ranges(&self) -> &[ByteRange] {
+        &self.valid
+    }
+
+    pub fn valid_bytes(&self) -> u64 {
+        self.valid.iter().map(ByteRange::len).sum()
+    }
+
+    pub fn total_size(&self) -> u64 {
+        self.total_size
+    }
+
+    /// Whether every byte of the file is valid
+    pub fn is_complete(&self) -> bool {
+        self.valid_bytes() == self.total_size
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn mark_valid(&mut self, start: u64, end: u64) {
+        if start >= end {
+            return;
+        }
+        let mut range = ByteRange { start, end };
+        // Absorb every range that overlaps or touches the new one
+        self.valid.retain(|r| {
+            if r.end < range.start || r.start > range.end {
+                return true;
+            }
+            range.start = range.start.min(r.start);
+            range.end = range.end.max(r.end);
+            false
+        });
+        let index = self.valid.partition_point(|r| r.start < range.start);
+        self.valid.insert(index, range);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::{ChunkManager, Priority};
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_writes_data_chunks_and_tracks_ranges() {
+        let dir = TempDir::new().unwrap();
+        let source = dir.path().join("orthophoto.tif");
+        // Four 4 KB pieces with the second all zero, then a short tail
+        let chunk = 4096;
+        let mut data: Vec<u8> = (0..chunk * 4 + 100).map(|i| (i % 249 + 1) as u8).collect();
+        data[chunk..chunk * 2].fill(0);
+        tokio::fs::write(&source, &data).await.unwrap();
+
+        let manager = ChunkManager::new(chunk, 4, 2).unwrap();
+        let (manifest, chunks) = manager
+            .split_file(&source, "ortho".into(), Priority::High)
+            .await
+            .unwrap();
+        assert_eq!(manifest.data_chunks, 4);
+
+        let output = dir.path().join("preview.tif");
+        let mut partial = PartialFile::create(&manifest, &output).await.unwrap();
+        assert_eq!(
+            partial.valid_ranges(),
+            [ByteRange {
+                start: chunk as u64,
+                end: chunk as u64 * 2
+            }]
+        );
+
+        // The last data chunk (the tail) and a parity chunk arrive first
+        let written = partial
+            .write(&[chunks[3].clone(), chunks[5].clone()])
+            .await
+            .unwrap();
+        assert_eq!(written, 100);
+
+        // Then the first, joining the zero run
+        partial.write(&[chunks[0].clone()]).await.unwrap();
+        assert_eq!(
+            partial.valid_ranges(),
+            [
+                ByteRange {
+                    start: 0,
+                    end: chunk as u64 * 2
+                },
+                ByteRange {
+                    start: chunk as u64 * 4,
+                    end: chunk as u64 * 4 + 100
+                },
+            ]
+        );
+        assert!(!partial.is_complete());
+
+        let on_disk = tokio::fs::read(&output).await.unwrap();
+        assert_eq!(on_disk.len(), data.len());
+        assert_eq!(on_disk[..chunk * 2], data[..chunk * 2]);
+        assert_eq!(on_disk[chunk * 4..], data[chunk * 4..]);
+
+        partial.write(&chunks[..3]).await.unwrap();
+        assert!(partial.is_complete());
+        assert_eq!(tokio::fs::read(&output).await.unwrap(), data);
+    }
+}
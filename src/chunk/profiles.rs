@@ -0,0 +1,152 @@
+//! Per-priority erasure profiles
+//!
+//! At the same loss rate, losing a critical file costs more than losing a
+//! log bundle, so each priority can ask for parity beyond the configured
+//! ratio and choose whether the overhead budget applies to it. The profile
+//! a file was split with is recorded in its manifest.
+
+use super::adaptive::parity_within;
+use super::erasure::MAX_TOTAL_SHARDS;
+use super::error::{ChunkError, Result};
+use super::types::Priority;
+use serde::{Deserialize, Serialize};
+
+/// Most extra parity a profile may ask for, in percent
+pub const MAX_EXTRA_PARITY_PERCENT: u32 = 400;
+
+/// Parity one priority's files get, relative to the configured ratio
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ErasureProfile {
+    /// Parity shards added on top of the configured ratio, in percent; 50
+    /// gives at least one and a half times the parity
+    pub extra_parity_percent: u32,
+    /// Held to the overhead budget, if one is set
+    pub budgeted: bool,
+}
+
+impl Default for ErasureProfile {
+    /// The configured ratio, within the budget
+    fn default() -> Self {
+        Self {
+            extra_parity_percent: 0,
+            budgeted: true,
+        }
+    }
+}
+
+impl ErasureProfile {
+    /// Parity shards for `data_shards` when the configured ratio gives
+    /// `parity_shards`, kept within `max_overhead` parity bytes per data
+    /// byte if this profile is budgeted
+    ///
+    /// Never fewer than one shard, nor more than Reed-Solomon allows.
+    pub fn parity_shards(
+        &self,
+        data_shards: usize,
+        parity_shards: usize,
+        max_overhead: Option<f64>,
+    ) -> usize {
+        self.wanted_parity_shards(data_shards, parity_shards, max_overhead)
+            .min(MAX_TOTAL_SHARDS.saturating_sub(data_shards))
+            .max(1)
+    }
+
+    /// [`parity_shards`](Self::parity_shards) before Reed-Solomon's limit
+    pub(crate) fn wanted_parity_shards(
+        &self,
+        data_shards: usize,
+        parity_shards: usize,
+        max_overhead: Option<f64>,
+    ) -> usize {
+        let extra = (parity_shards * self.extra_parity_percent as usize).div_ceil(100);
+        let mut parity = parity_shards + extra;
+        if let Some(ratio) = max_overhead.filter(|_| self.budgeted) {
+            parity = parity.min(parity_within(data_shards, ratio));
+        }
+        parity.max(1)
+    }
+}
+
+/// Erasure profile of each priority
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ErasureProfiles {
+    pub critical: ErasureProfile,
+    pub high: ErasureProfile,
+    pub normal: ErasureProfile,
+}
+
+impl Default for ErasureProfiles {
+    /// Critical files get half as much parity again and ignore the budget;
+    /// the rest use the configured ratio within it
+    fn default() -> Self {
+        Self {
+            critical: ErasureProfile {
+                extra_parity_percent: 50,
+                budgeted: false,
+            },
+            high: ErasureProfile::default(),
+            normal: ErasureProfile::default(),
+        }
+    }
+}
+
+impl ErasureProfiles {
+    /// The same profile for every priority
+    pub fn uniform(profile: ErasureProfile) -> Self {
+        Self {
+            critical: profile,
+            high: profile,
+            normal: profile,
+        }
+    }
+
+    pub fn get(&self, priority: Priority) -> ErasureProfile {
+        match priority.class() {
+            Priority::Critical => self.critical,
+            Priority::High => self.high,
+            Priority::Normal | Priority::Level(_) => self.normal,
+        }
+    }
+
+    /// Fails naming the first priority whose profile asks for too much parity
+    pub fn validate(&self) -> Result<()> {
+        for priority in [Priority::Critical, Priority::High, Priority::Normal] {
+            let extra = self.get(priority).extra_parity_percent;
+            if extra > MAX_EXTRA_PARITY_PERCENT {
+                return Err(ChunkError::ErasureCoding(format!(
+                    "{priority:?} asks for {extra}% extra parity, more than {MAX_EXTRA_PARITY_PERCENT}%"
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profiles_scale_and_budget_parity() {
+        let profiles = ErasureProfiles::default();
+
+        // 10 data + 2 parity at the configured ratio, 10% budget
+        let critical = profiles.get(Priority::Critical);
+        assert_eq!(critical.parity_shards(10, 2, None), 3);
+        assert_eq!(critical.parity_shards(10, 2, Some(0.1)), 3);
+        let normal = profiles.get(Priority::Normal);
+        assert_eq!(normal.parity_shards(10, 2, None), 2);
+        assert_eq!(normal.parity_shards(10, 2, Some(0.1)), 1);
+
+        // Rounds up, and stays within Reed-Solomon's shard limit
+        assert_eq!(critical.parity_shards(50, 5, None), 8);
+        assert_eq!(critical.parity_shards(250, 10, None), 6);
+
+        let mut greedy = profiles;
+        greedy.high.extra_parity_percent = MAX_EXTRA_PARITY_PERCENT + 1;
+        assert!(greedy.validate().unwrap_err().to_string().contains("High"));
+        assert!(profiles.validate().is_ok());
+    }
+}
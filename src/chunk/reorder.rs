@@ -0,0 +1,349 @@
+//! Sequence-window reorder buffer for the receive path
+//!
+//! Chunks travel on independent QUIC streams, so on high-jitter links they
+//! arrive far out of order. [`SequenceAssembler`] splits a transfer's
+//! sequence space into fixed-size groups and holds chunks in memory until
+//! their group is complete, then hands the whole group back in sequence
+//! order to be flushed to disk. At most `window` chunks are held: past that
+//! the oldest group is flushed with its gaps, and chunks for it that turn up
+//! later go straight to disk. Gaps left at the end are parity's job.
+
+use crate::chunk::Chunk;
+use crate::metrics::recorder;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// How many out-of-order chunks to hold and how they are grouped
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReorderConfig {
+    /// Chunks held in memory before the oldest group is flushed incomplete
+    pub window: usize,
+    /// Consecutive sequence numbers flushed together
+    pub group_size: u32,
+}
+
+impl Default for ReorderConfig {
+    fn default() -> Self {
+        Self {
+            window: 256,
+            group_size: 16,
+        }
+    }
+}
+
+/// Reordering seen on one transfer
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReorderStats {
+    /// Chunks at or past the highest sequence number seen so far
+    pub in_order: u64,
+    /// Chunks that arrived behind a higher sequence number
+    pub reordered: u64,
+    /// Largest gap between the highest sequence seen and a late chunk
+    pub max_depth: u32,
+    /// Sum of reorder depths, for [`mean_depth`](Self::mean_depth)
+    pub total_depth: u64,
+    /// Chunks currently held in memory
+    pub buffered: usize,
+    pub peak_buffered: usize,
+    /// Groups flushed once every chunk in them had arrived
+    pub groups_flushed: u64,
+    /// Groups flushed with gaps because the window was full
+    pub forced_flushes: u64,
+    /// Chunks that arrived after their group was flushed
+    pub late_chunks: u64,
+    /// Repeated or out-of-range sequence numbers that were dropped
+    pub duplicates: u64,
+    /// Chunks already on disk when the transfer was resumed
+    pub resumed: u64,
+}
+
+impl ReorderStats {
+    /// Average depth of the chunks that arrived out of order
+    pub fn mean_depth(&self) -> f64 {
+        if self.reordered == 0 {
+            0.0
+        } else {
+            self.total_depth as f64 / self.reordered as f64
+        }
+    }
+}
+
+/// Buffers one transfer's chunks and releases them a group at a time
+#[derive(Debug)]
+pub struct SequenceAssembler {
+    config: ReorderConfig,
+    total_chunks: u32,
+    /// First group not yet flushed
+    next_group: u32,
+    pending: BTreeMap<u32, Chunk>,
+    seen: Vec<bool>,
+    highest_seen: Option<u32>,
+    ready: Vec<Chunk>,
+    stats: ReorderStats,
+}
+
+impl SequenceAssembler {
+    pub fn new(total_chunks: u32, config: ReorderConfig) -> Self {
+        Self {
+            config: ReorderConfig {
+                window: config.window.max(1),
+                group_size: config.group_size.max(1),
+            },
+            total_chunks,
+            next_group: 0,
+            pending: BTreeMap::new(),
+            seen: vec![false; total_chunks as usize],
+            highest_seen: None,
+            ready: Vec::new(),
+            stats: ReorderStats::default(),
+        }
+    }
+
+    /// Pick up a transfer whose chunks `spooled` are already on disk
+    ///
+    /// They count as received, and their groups complete as the rest arrive.
+    pub fn resume(total_chunks: u32, config: ReorderConfig, spooled: &[u32]) -> Self {
+        let mut assembler = Self::new(total_chunks, config);
+        for &seq in spooled {
+            if let Some(seen) = assembler.seen.get_mut(seq as usize) {
+                if !*seen {
+                    *seen = true;
+                    assembler.stats.resumed += 1;
+                }
+            }
+        }
+        assembler.advance();
+        assembler
+    }
+
+    /// Add a received chunk
+    ///
+    /// Returns false if the sequence number was already received or is out
+    /// of range; the chunk is dropped.
+    pub fn insert(&mut self, chunk: Chunk) -> bool {
+        let seq = chunk.metadata.sequence_number;
+        match self.seen.get_mut(seq as usize) {
+            Some(seen) if !*seen => *seen = true,
+            _ => {
+                self.stats.duplicates += 1;
+                return false;
+            }
+        }
+
+        match self.highest_seen {
+            Some(highest) if seq < highest => {
+                let depth = highest - seq;
+                self.stats.reordered += 1;
+                self.stats.total_depth += depth as u64;
+                self.stats.max_depth = self.stats.max_depth.max(depth);
+                recorder::record_reorder_depth(depth);
+            }
+            _ => {
+                self.stats.in_order += 1;
+                self.highest_seen = Some(seq);
+            }
+        }
+
+        if seq / self.config.group_size < self.next_group {
+            // Its group already went to disk with a gap; fill it there
+            self.stats.late_chunks += 1;
+            self.ready.push(chunk);
+        } else {
+            self.pending.insert(seq, chunk);
+        }
+
+        self.advance();
+        self.stats.buffered = self.pending.len();
+        self.stats.peak_buffered = self.stats.peak_buffered.max(self.pending.len());
+        true
+    }
+
+    /// Chunks ready to be flushed, in the order they should be written
+    pub fn take_ready(&mut self) -> Vec<Chunk> {
+        std::mem::take(&mut self.ready)
+    }
+
+    /// Chunks still held in memory
+    pub fn buffered(&self) -> impl Iterator<Item = &Chunk> {
+        self.pending.values()
+    }
+
+    /// Bytes of chunk data held in memory
+    pub fn buffered_bytes(&self) -> usize {
+        self.pending.values().map(|c| c.data.len()).sum()
+    }
+
+    /// Sequence numbers accepted so far, flushed or not
+    pub fn received_sequences(&self) -> Vec<u32> {
+        (0..self.total_chunks)
+            .filter(|&seq| self.seen[seq as usize])
+            .collect()
+    }
+
+    /// Distinct chunks accepted so far, flushed or not
+    pub fn received(&self) -> u64 {
+        self.stats.in_order + self.stats.reordered + self.stats.resumed
+    }
+
+    pub fn stats(&self) -> &ReorderStats {
+        &self.stats
+    }
+
+    fn group_range(&self, group: u32) -> std::ops::Range<u32> {
+        let start = group.saturating_mul(self.config.group_size);
+        start
+            ..start
+                .saturating_add(self.config.group_size)
+                .min(self.total_chunks)
+    }
+
+    fn flush_group(&mut self, group: u32) {
+        let range = self.group_range(group);
+        let rest = self.pending.split_off(&range.end);
+        self.ready
+            .extend(std::mem::replace(&mut self.pending, rest).into_values());
+        self.next_group += 1;
+    }
+
+    fn advance(&mut self) {
+        loop {
+            let range = self.group_range(self.next_group);
+            if range.is_empty() {
+                return;
+            }
+            if self.seen[range.start as usize..range.end as usize]
+                .iter()
+                .all(|&seen| seen)
+            {
+                self.flush_group(self.next_group);
+                self.stats.groups_flushed += 1;
+            } else if self.pending.len() > self.config.window {
+                self.flush_group(self.next_group);
+                self.stats.forced_flushes += 1;
+            } else {
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::{ChunkMetadata, Priority};
+    use bytes::Bytes;
+
+    fn chunk(seq: u32, total: u32) -> Chunk {
+        Chunk {
+            metadata: ChunkMetadata {
+                chunk_id: seq as u64,
+                file_id: "reorder".into(),
+                sequence_number: seq,
+                total_chunks: total,
+                data_size: 4,
+                checksum: [0; 32],
+                is_parity: false,
+                priority: Priority::Normal,
+                created_at: 0,
+                file_size: 0,
+                file_checksum: [0; 32],
+                data_chunks: total,
+                zero_runs: Vec::new(),
+                attributes: None,
+                checksum_algorithm: Default::default(),
+                merkle_proof: None,
+                trace: None,
+                compression: None,
+            },
+            data: Bytes::from_static(&[1, 2, 3, 4]),
+        }
+    }
+
+    fn seqs(chunks: &[Chunk]) -> Vec<u32> {
+        chunks.iter().map(|c| c.metadata.sequence_number).collect()
+    }
+
+    #[test]
+    fn test_flushes_groups_in_order() {
+        let config = ReorderConfig {
+            window: 16,
+            group_size: 4,
+        };
+        let mut assembler = SequenceAssembler::new(10, config);
+
+        for seq in [1, 0, 5, 3, 4, 6] {
+            assert!(assembler.insert(chunk(seq, 10)));
+        }
+        assert!(assembler.take_ready().is_empty());
+        assert_eq!(assembler.buffered().count(), 6);
+
+        // Completing group 0 releases it, and group 1 is still missing 7
+        assembler.insert(chunk(2, 10));
+        assert_eq!(seqs(&assembler.take_ready()), [0, 1, 2, 3]);
+
+        for seq in [9, 7, 8] {
+            assembler.insert(chunk(seq, 10));
+        }
+        // The short last group flushes as soon as its two chunks are in
+        assert_eq!(seqs(&assembler.take_ready()), [4, 5, 6, 7, 8, 9]);
+
+        let stats = assembler.stats();
+        assert_eq!(stats.groups_flushed, 3);
+        assert_eq!(stats.reordered, 6);
+        assert_eq!(stats.max_depth, 4);
+        assert_eq!(stats.buffered, 0);
+        assert_eq!(assembler.received(), 10);
+    }
+
+    #[test]
+    fn test_full_window_forces_flush() {
+        let config = ReorderConfig {
+            window: 3,
+            group_size: 2,
+        };
+        let mut assembler = SequenceAssembler::new(8, config);
+
+        // Chunk 0 is late: once four chunks are held, group 0 goes with a gap
+        for seq in [1, 2, 3, 4] {
+            assembler.insert(chunk(seq, 8));
+        }
+        assert_eq!(seqs(&assembler.take_ready()), [1, 2, 3]);
+        assert_eq!(assembler.stats().forced_flushes, 1);
+        assert!(assembler.stats().peak_buffered <= 3);
+
+        assembler.insert(chunk(0, 8));
+        assert_eq!(seqs(&assembler.take_ready()), [0]);
+        assert_eq!(assembler.stats().late_chunks, 1);
+    }
+
+    #[test]
+    fn test_resume_counts_spooled_chunks() {
+        let config = ReorderConfig {
+            window: 16,
+            group_size: 4,
+        };
+        // Group 0 made it to disk whole, group 1 in part
+        let mut assembler = SequenceAssembler::resume(8, config, &[0, 1, 2, 3, 4, 6]);
+        assert_eq!(assembler.received(), 6);
+        assert_eq!(assembler.stats().resumed, 6);
+        assert!(!assembler.insert(chunk(1, 8)));
+
+        assembler.insert(chunk(7, 8));
+        assert!(assembler.take_ready().is_empty());
+        // Only what wasn't on disk yet is released
+        assembler.insert(chunk(5, 8));
+        assert_eq!(seqs(&assembler.take_ready()), [5, 7]);
+        assert_eq!(assembler.received_sequences(), (0..8).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_rejects_duplicates() {
+        let mut assembler = SequenceAssembler::new(4, ReorderConfig::default());
+        assert!(assembler.insert(chunk(2, 4)));
+        assert!(!assembler.insert(chunk(2, 4)));
+        assert!(!assembler.insert(chunk(9, 4)));
+        assert_eq!(assembler.stats().duplicates, 2);
+        assert_eq!(assembler.received(), 1);
+        assert_eq!(assembler.received_sequences(), [2]);
+    }
+}
@@ -0,0 +1,185 @@
+//! Guarding a source file against changes while it is sent
+//!
+//! A file is read whole when it is split, so a change part way through a
+//! long transfer leaves the sender with chunks of the old version and, on
+//! resume, chunks of the new one. A [`SourceGuard`] records the file's size
+//! and mtime when the transfer starts and compares them as it runs, and can
+//! hold a shared OS lock so cooperating writers wait until it is done.
+
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use serde::{Deserialize, Serialize};
+
+use super::error::{ChunkError, Result};
+
+/// Size and modification time of a file at one moment
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceSnapshot {
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+}
+
+impl SourceSnapshot {
+    pub async fn capture(path: &Path) -> Result<Self> {
+        let meta = tokio::fs::metadata(path).await?;
+        Ok(Self {
+            size: meta.len(),
+            modified: meta.modified().ok(),
+        })
+    }
+
+    /// Fail with [`ChunkError::SourceModified`] if `path` no longer matches
+    pub async fn verify(&self, path: &Path) -> Result<()> {
+        let now = Self::capture(path)
+            .await
+            .map_err(|_| ChunkError::SourceModified(path.to_path_buf()))?;
+        if now != *self {
+            return Err(ChunkError::SourceModified(path.to_path_buf()));
+        }
+        Ok(())
+    }
+}
+
+/// How source files are watched while they are sent
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceWatch {
+    /// How often a running transfer re-checks its file (`None` = never)
+    pub check_interval: Option<Duration>,
+    /// Hold a shared OS lock on the file for the whole transfer
+    pub lock: bool,
+}
+
+impl Default for SourceWatch {
+    fn default() -> Self {
+        Self {
+            check_interval: Some(Duration::from_secs(1)),
+            lock: false,
+        }
+    }
+}
+
+/// A file being sent, with the snapshot it is checked against
+///
+/// Dropping the guard releases its lock.
+#[derive(Debug)]
+pub struct SourceGuard {
+    path: PathBuf,
+    snapshot: SourceSnapshot,
+    check_interval: Option<Duration>,
+    last_check: Instant,
+    lock: Option<File>,
+}
+
+impl SourceGuard {
+    /// Snapshot `path`, locking it first if `watch` says to
+    ///
+    /// Fails with [`ChunkError::SourceLocked`] if a writer holds an
+    /// exclusive lock on the file.
+    pub async fn acquire(path: &Path, watch: SourceWatch) -> Result<Self> {
+        let lock = if watch.lock {
+            Some(lock_shared(path)?)
+        } else {
+            None
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            snapshot: SourceSnapshot::capture(path).await?,
+            check_interval: watch.check_interval,
+            last_check: Instant::now(),
+            lock,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn snapshot(&self) -> SourceSnapshot {
+        self.snapshot
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.lock.is_some()
+    }
+
+    /// Re-check the file if the check interval has passed
+    pub async fn check_due(&mut self) -> Result<()> {
+        let Some(interval) = self.check_interval else {
+            return Ok(());
+        };
+        if self.last_check.elapsed() < interval {
+            return Ok(());
+        }
+        self.last_check = Instant::now();
+        self.snapshot.verify(&self.path).await
+    }
+}
+
+fn lock_shared(path: &Path) -> Result<File> {
+    let file = File::open(path)?;
+    match file.try_lock_shared() {
+        Ok(()) => Ok(file),
+        Err(std::fs::TryLockError::WouldBlock) => Err(ChunkError::SourceLocked(path.to_path_buf())),
+        Err(std::fs::TryLockError::Error(e)) => Err(e.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_detects_change() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("form.pdf");
+        std::fs::write(&path, b"version one").unwrap();
+
+        let watch = SourceWatch {
+            check_interval: Some(Duration::ZERO),
+            lock: false,
+        };
+        let mut guard = SourceGuard::acquire(&path, watch).await.unwrap();
+        guard.check_due().await.unwrap();
+
+        std::fs::write(&path, b"version two, longer").unwrap();
+        assert!(matches!(
+            guard.check_due().await,
+            Err(ChunkError::SourceModified(p)) if p == path
+        ));
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(guard.check_due().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_lock_excludes_writers() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("tile.png");
+        std::fs::write(&path, b"tile").unwrap();
+
+        let watch = SourceWatch {
+            check_interval: None,
+            lock: true,
+        };
+        let guard = SourceGuard::acquire(&path, watch).await.unwrap();
+        assert!(guard.is_locked());
+        // Readers share the lock; a cooperating writer has to wait
+        let other = SourceGuard::acquire(&path, watch).await.unwrap();
+        let writer = File::options().write(true).open(&path).unwrap();
+        assert!(writer.try_lock().is_err());
+
+        drop((guard, other));
+        assert!(writer.try_lock().is_ok());
+        writer.unlock().unwrap();
+
+        // A writer holding the lock keeps new transfers out
+        writer.lock().unwrap();
+        assert!(matches!(
+            SourceGuard::acquire(&path, watch).await,
+            Err(ChunkError::SourceLocked(_))
+        ));
+    }
+}
@@ -0,0 +1,221 @@
+//! On-disk spool for received chunks
+//!
+//! Groups released by the [`SequenceAssembler`](crate::chunk::reorder::SequenceAssembler)
+//! are appended here so a large transfer doesn't sit in memory until it
+//! can be reconstructed. Each record is the bincode-encoded metadata and
+//! the chunk data, both prefixed with a little-endian `u32` length.
+
+use crate::chunk::error::{ChunkError, Result};
+use crate::chunk::{Chunk, ChunkMetadata};
+use bytes::Bytes;
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+use tokio::fs::{File, OpenOptions};
+use tokio::io::{AsyncSeekExt, AsyncWriteExt};
+
+/// Append-only file of received chunks for one transfer
+#[derive(Debug)]
+pub struct ChunkSpool {
+    path: PathBuf,
+    file: File,
+    chunks: usize,
+}
+
+impl ChunkSpool {
+    /// Create or truncate the spool at `path`
+    pub async fn create(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&path)
+            .await?;
+        Ok(Self {
+            path,
+            file,
+            chunks: 0,
+        })
+    }
+
+    /// Reopen the spool a receiver left at `path` before it stopped
+    ///
+    /// A record cut short by the stop is dropped, so appends carry on from
+    /// the last complete one.
+    pub async fn open(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let data = tokio::fs::read(&path).await?;
+        let mut rest = data.as_slice();
+        let mut chunks = 0;
+        let mut valid = 0;
+        while !rest.is_empty() {
+            let complete = take_frame(&mut rest, &path)
+                .and_then(|metadata| {
+                    bincode::deserialize::<ChunkMetadata>(metadata)
+                        .map_err(|e| corrupt(&path, &e.to_string()))
+                })
+                .and_then(|_| take_frame(&mut rest, &path));
+            if complete.is_err() {
+                break;
+            }
+            chunks += 1;
+            valid = data.len() - rest.len();
+        }
+
+        let mut file = OpenOptions::new().write(true).open(&path).await?;
+        file.set_len(valid as u64).await?;
+        file.seek(SeekFrom::End(0)).await?;
+        Ok(Self { path, file, chunks })
+    }
+
+    /// Write `chunks` to the end of the spool
+    pub async fn append(&mut self, chunks: &[Chunk]) -> Result<()> {
+        if chunks.is_empty() {
+            return Ok(());
+        }
+
+        let mut buf = Vec::new();
+        for chunk in chunks {
+            let metadata = bincode::serialize(&chunk.metadata).map_err(std::io::Error::other)?;
+            buf.extend_from_slice(&(metadata.len() as u32).to_le_bytes());
+            buf.extend_from_slice(&metadata);
+            buf.extend_from_slice(&(chunk.data.len() as u32).to_le_bytes());
+            buf.extend_from_slice(&chunk.data);
+        }
+        self.file.write_all(&buf).await?;
+        self.file.flush().await?;
+        self.chunks += chunks.len();
+        Ok(())
+    }
+
+    /// Every chunk appended so far
+    pub async fn read_all(&self) -> Result<Vec<Chunk>> {
+        let data = tokio::fs::read(&self.path).await?;
+        let mut chunks = Vec::with_capacity(self.chunks);
+        let mut rest = data.as_slice();
+        while !rest.is_empty() {
+            let metadata = take_frame(&mut rest, &self.path)?;
+            let metadata: ChunkMetadata =
+                bincode::deserialize(metadata).map_err(|e| corrupt(&self.path, &e.to_string()))?;
+            let data = take_frame(&mut rest, &self.path)?;
+            chunks.push(Chunk {
+                metadata,
+                data: Bytes::copy_from_slice(data),
+            });
+        }
+        Ok(chunks)
+    }
+
+    /// Chunks appended so far
+    pub fn len(&self) -> usize {
+        self.chunks
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chunks == 0
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Delete the spool file
+    pub async fn remove(self) -> Result<()> {
+        drop(self.file);
+        tokio::fs::remove_file(&self.path).await?;
+        Ok(())
+    }
+}
+
+fn take_frame<'a>(rest: &mut &'a [u8], path: &Path) -> Result<&'a [u8]> {
+    let (len, tail) = rest
+        .split_first_chunk::<4>()
+        .ok_or_else(|| corrupt(path, "truncated length"))?;
+    let len = u32::from_le_bytes(*len) as usize;
+    if tail.len() < len {
+        return Err(corrupt(path, "truncated record"));
+    }
+    let (frame, tail) = tail.split_at(len);
+    *rest = tail;
+    Ok(frame)
+}
+
+fn corrupt(path: &Path, reason: &str) -> ChunkError {
+    ChunkError::Io(std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        format!("{}: {}", path.display(), reason),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::{ChunkManager, Priority};
+
+    #[tokio::test]
+    async fn test_append_and_read_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("source.bin");
+        tokio::fs::write(&source, vec![7u8; 10_000]).await.unwrap();
+        let manager = ChunkManager::new(1024, 10, 2).unwrap();
+        let (_, chunks) = manager
+            .split_file(&source, "spool".into(), Priority::Normal)
+            .await
+            .unwrap();
+
+        let mut spool = ChunkSpool::create(dir.path().join("partial/spool.bin"))
+            .await
+            .unwrap();
+        spool.append(&chunks[..5]).await.unwrap();
+        spool.append(&chunks[5..]).await.unwrap();
+        assert_eq!(spool.len(), chunks.len());
+
+        let read = spool.read_all().await.unwrap();
+        assert_eq!(read.len(), chunks.len());
+        for (read, chunk) in read.iter().zip(&chunks) {
+            assert_eq!(
+                read.metadata.sequence_number,
+                chunk.metadata.sequence_number
+            );
+            assert_eq!(read.metadata.checksum, chunk.metadata.checksum);
+            assert_eq!(read.data, chunk.data);
+        }
+
+        let path = spool.path().to_path_buf();
+        spool.remove().await.unwrap();
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_reopen_drops_torn_record() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("source.bin");
+        tokio::fs::write(&source, vec![3u8; 4_000]).await.unwrap();
+        let manager = ChunkManager::new(1024, 4, 2).unwrap();
+        let (_, chunks) = manager
+            .split_file(&source, "spool".into(), Priority::Normal)
+            .await
+            .unwrap();
+
+        let path = dir.path().join("spool.bin");
+        let mut spool = ChunkSpool::create(&path).await.unwrap();
+        spool.append(&chunks[..3]).await.unwrap();
+        drop(spool);
+        // The receiver stopped halfway through writing a fourth record
+        let full = tokio::fs::metadata(&path).await.unwrap().len();
+        let mut data = tokio::fs::read(&path).await.unwrap();
+        data.extend_from_slice(&[9, 0, 0, 0, 1, 2]);
+        tokio::fs::write(&path, data).await.unwrap();
+
+        let mut spool = ChunkSpool::open(&path).await.unwrap();
+        assert_eq!(spool.len(), 3);
+        assert_eq!(tokio::fs::metadata(&path).await.unwrap().len(), full);
+        spool.append(&chunks[3..]).await.unwrap();
+        let read = spool.read_all().await.unwrap();
+        let seqs: Vec<u32> = read.iter().map(|c| c.metadata.sequence_number).collect();
+        assert_eq!(seqs, (0..chunks.len() as u32).collect::<Vec<_>>());
+    }
+}
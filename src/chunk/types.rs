@@ -1,11 +1,85 @@
+use crate::chunk::attributes::FileAttributes;
+use crate::chunk::compression::StreamCompression;
+use crate::chunk::hints::ScheduleHint;
+use crate::chunk::profiles::ErasureProfile;
+use crate::integrity::{ChecksumType, MerkleProof};
 use bytes::Bytes;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+/// How urgent a chunk is; lower levels are sent first
+///
+/// `Critical`, `High` and `Normal` are levels 0, 1 and 2. Deployments that
+/// need finer control, such as per-agency levels 0-9, use `Level` for the
+/// levels past `Normal`, up to the queue's configured level count.
+/// Priorities compare by level, and `Level(0)` to `Level(2)` read back as
+/// the named priorities.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(from = "PriorityRepr")]
 pub enum Priority {
-    Critical = 0,
-    High = 1,
-    Normal = 2,
+    Critical,
+    High,
+    Normal,
+    /// A level below `Normal`; see [`Priority::from_level`]
+    Level(u8),
+}
+
+impl Priority {
+    /// The priority at `level`, by name for levels 0 to 2
+    pub fn from_level(level: u8) -> Self {
+        match level {
+            0 => Priority::Critical,
+            1 => Priority::High,
+            2 => Priority::Normal,
+            n => Priority::Level(n),
+        }
+    }
+
+    /// Position in the queue, 0 being the most urgent
+    pub fn level(self) -> u8 {
+        match self {
+            Priority::Critical => 0,
+            Priority::High => 1,
+            Priority::Normal => 2,
+            Priority::Level(n) => n,
+        }
+    }
+
+    /// The named priority treated alike: level 0 is `Critical`, 1 `High`
+    /// and anything from 2 down `Normal`
+    ///
+    /// Erasure profiles, latency targets, memory shedding and metric labels
+    /// go by class, so extra levels only change queue order.
+    pub fn class(self) -> Self {
+        Self::from_level(self.level().min(2))
+    }
+}
+
+impl PartialEq for Priority {
+    fn eq(&self, other: &Self) -> bool {
+        self.level() == other.level()
+    }
+}
+
+impl Eq for Priority {}
+
+// Priority as written, before levels 0 to 2 are given their names
+#[derive(Deserialize)]
+enum PriorityRepr {
+    Critical,
+    High,
+    Normal,
+    Level(u8),
+}
+
+impl From<PriorityRepr> for Priority {
+    fn from(repr: PriorityRepr) -> Self {
+        match repr {
+            PriorityRepr::Critical => Priority::Critical,
+            PriorityRepr::High => Priority::High,
+            PriorityRepr::Normal => Priority::Normal,
+            PriorityRepr::Level(n) => Priority::from_level(n),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -15,7 +89,7 @@ pub struct ChunkMetadata {
     pub sequence_number: u32,
     pub total_chunks: u32,
     pub data_size: usize,
-    pub checksum: [u8; 32], // BLAKE3 hash (chunk-level)
+    pub checksum: [u8; 32], // Chunk-level hash, see `checksum_algorithm`
     pub is_parity: bool,
     pub priority: Priority,
     pub created_at: i64,
@@ -23,9 +97,114 @@ pub struct ChunkMetadata {
     pub file_size: u64,
     pub file_checksum: [u8; 32],
     pub data_chunks: u32,
+    /// All-zero regions left out of the transfer (see [`FileManifest::zero_runs`])
+    #[serde(default)]
+    pub zero_runs: Vec<ZeroRun>,
+    /// Source file attributes (see [`FileManifest::attributes`])
+    #[serde(default)]
+    pub attributes: Option<FileAttributes>,
+    /// Algorithm behind `checksum` and `file_checksum`
+    #[serde(default)]
+    pub checksum_algorithm: ChecksumType,
+    /// Path from `checksum` to [`FileManifest::merkle_root`]
+    #[serde(default)]
+    pub merkle_proof: Option<MerkleProof>,
+    /// Transfer the chunk belongs to and where it has been handled
+    #[serde(default)]
+    pub trace: Option<ChunkTrace>,
+    /// Set when the file was compressed before it was chunked, so the
+    /// receiver knows to decompress what it decodes
+    #[serde(default)]
+    pub compression: Option<StreamCompression>,
+}
+
+impl ChunkMetadata {
+    /// Trace id shared by the chunks of the sender's transfer
+    pub fn trace_id(&self) -> Option<&str> {
+        self.trace.as_ref().map(|t| t.trace_id.as_str())
+    }
+
+    /// Note that `node` handled the chunk, if it is traced
+    pub fn record_hop(&mut self, node: &str, stage: HopStage) {
+        if let Some(trace) = &mut self.trace {
+            trace.record(node, stage);
+        }
+    }
+}
+
+/// Most hops a trace keeps; later hops replace the last
+pub const MAX_TRACE_HOPS: usize = 16;
+
+/// Correlates one chunk across sender, relay and receiver logs
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChunkTrace {
+    /// The sender's session id, so relay and receiver logs lead back to it
+    pub trace_id: String,
+    /// Handling points in the order the chunk passed them
+    pub hops: Vec<TraceHop>,
+}
+
+impl ChunkTrace {
+    pub fn new(trace_id: impl Into<String>) -> Self {
+        Self {
+            trace_id: trace_id.into(),
+            hops: Vec::new(),
+        }
+    }
+
+    pub fn record(&mut self, node: &str, stage: HopStage) {
+        if self.hops.len() == MAX_TRACE_HOPS {
+            self.hops.pop();
+        }
+        self.hops.push(TraceHop {
+            node: node.to_string(),
+            stage,
+            at_ms: chrono::Utc::now().timestamp_millis(),
+        });
+    }
 }
 
-#[derive(Debug, Clone)]
+impl std::fmt::Display for ChunkTrace {
+    /// `trace_id: node(stage) -> node(stage)`
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:", self.trace_id)?;
+        for (i, hop) in self.hops.iter().enumerate() {
+            let sep = if i == 0 { " " } else { " -> " };
+            write!(f, "{}{}({:?})", sep, hop.node, hop.stage)?;
+        }
+        Ok(())
+    }
+}
+
+/// One handling point on a chunk's path
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TraceHop {
+    /// Relay node id, or the socket address of a sender or receiver
+    pub node: String,
+    pub stage: HopStage,
+    /// Unix time in milliseconds, by the handling node's clock
+    pub at_ms: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HopStage {
+    Sent,
+    Relayed,
+    Received,
+}
+
+/// A chunk-aligned, all-zero region of a file
+///
+/// Zero runs are not erasure coded or sent; the receiver recreates them as
+/// holes in the output file.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ZeroRun {
+    pub offset: u64,
+    pub length: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Chunk {
     pub metadata: ChunkMetadata,
     pub data: Bytes,
@@ -42,4 +221,79 @@ pub struct FileManifest {
     pub parity_chunks: u32,
     pub priority: Priority,
     pub checksum: [u8; 32], // File-level checksum
+    /// All-zero regions, in file order, that carry no chunks
+    #[serde(default)]
+    pub zero_runs: Vec<ZeroRun>,
+    /// Mode, mtime and symlink target captured at split time
+    #[serde(default)]
+    pub attributes: Option<FileAttributes>,
+    /// Algorithm behind `checksum` and every chunk checksum
+    #[serde(default)]
+    pub checksum_algorithm: ChecksumType,
+    /// Profile of the file's priority that set `parity_chunks`
+    #[serde(default)]
+    pub erasure_profile: ErasureProfile,
+    /// Data chunks to send first, for file types with a part that makes
+    /// the rest usable
+    #[serde(default)]
+    pub schedule: Option<ScheduleHint>,
+    /// Root of the Merkle tree over every chunk checksum, so chunks can be
+    /// verified one at a time (see [`crate::integrity::MerkleTree`])
+    #[serde(default)]
+    pub merkle_root: Option<[u8; 32]>,
+    /// Set when the file was compressed before it was chunked; chunks then
+    /// cover the compressed stream and carry no zero runs
+    #[serde(default)]
+    pub compression: Option<StreamCompression>,
+}
+
+impl FileManifest {
+    /// Whether any part of the file is transferred as a hole
+    pub fn is_sparse(&self) -> bool {
+        !self.zero_runs.is_empty()
+    }
+
+    /// Bytes covered by zero runs
+    pub fn sparse_bytes(&self) -> u64 {
+        self.zero_runs.iter().map(|r| r.length).sum()
+    }
+
+    /// Length of what the data chunks cover: the compressed stream for a
+    /// compressed file, else the file
+    pub fn chunked_size(&self) -> u64 {
+        self.compression.map_or(self.total_size, |c| c.size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_named_levels_read_back_by_name() {
+        for (level, named) in [
+            (0, Priority::Critical),
+            (1, Priority::High),
+            (2, Priority::Normal),
+        ] {
+            assert_eq!(Priority::Level(level), named);
+
+            let json = serde_json::to_string(&Priority::Level(level)).unwrap();
+            let read: Priority = serde_json::from_str(&json).unwrap();
+            assert!(
+                !matches!(read, Priority::Level(_)),
+                "{json} read as {read:?}"
+            );
+            assert_eq!(read, named);
+
+            let bytes = bincode::serialize(&Priority::Level(level)).unwrap();
+            let read: Priority = bincode::deserialize(&bytes).unwrap();
+            assert!(!matches!(read, Priority::Level(_)));
+            assert_eq!(read, named);
+        }
+
+        let read: Priority = serde_json::from_str(r#"{"Level":7}"#).unwrap();
+        assert!(matches!(read, Priority::Level(7)));
+        assert_ne!(read, Priority::Normal);
+    }
 }
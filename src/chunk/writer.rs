@@ -0,0 +1,670 @@
+//! Writing a decoded file back to disk
+//!
+//! Reconstruction lays the decoded chunks out as [`Segment`]s: data at its
+//! byte offset, or a zero run left as a hole. With a write concurrency of 1
+//! they are written front to back through one file cursor. Above that, runs
+//! of consecutive data segments are batched and written at their offsets
+//! with positioned writes (`pwrite`) from blocking tasks, at most
+//! `concurrency` batches in flight, while another task hashes the segments
+//! in file order. On NVMe this keeps several writes queued at once, which
+//! a single cursor can't.
+//!
+//! Either way the writes follow a [`WritePolicy`]: an average rate cap,
+//! `fsync` every so many bytes instead of leaving a file's worth of dirty
+//! pages to the kernel, and optionally `O_DIRECT`. Cheap SD cards and eMMC
+//! in field hardware stall for seconds when a large writeback lands at
+//! once, and wear faster under it.
+
+use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinSet;
+
+use super::error::Result;
+use super::types::FileManifest;
+use crate::integrity::{ChecksumType, Hasher};
+use crate::metrics::recorder;
+
+/// Zeros fed to the file hasher in place of skipped holes
+static ZERO_BLOCK: [u8; 64 * 1024] = [0u8; 64 * 1024];
+
+/// Data bytes handed to one writer task at a time
+const WRITE_BATCH_BYTES: usize = 8 * 1024 * 1024;
+
+/// Offset, length and buffer alignment `O_DIRECT` writes need
+const DIRECT_ALIGN: usize = 4096;
+
+/// Bytes staged before each `O_DIRECT` write
+const DIRECT_BUFFER_BYTES: usize = 1024 * 1024;
+
+/// How reconstructed files are written to disk
+///
+/// The default leaves flushing to the kernel and writes as fast as the
+/// disk takes it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WritePolicy {
+    /// `fsync` after every this many bytes written (0 = never mid-file)
+    pub sync_every_bytes: u64,
+    /// `fsync` once the whole file is written
+    pub sync_on_complete: bool,
+    /// Bypass the page cache with `O_DIRECT` (Linux only); files are then
+    /// written front to back whatever the write concurrency
+    pub direct_io: bool,
+    /// Most bytes written per second, on average (0 = no limit)
+    pub max_bytes_per_sec: u64,
+}
+
+/// One stretch of the reconstructed file
+#[derive(Debug, Clone)]
+pub(crate) enum Segment {
+    /// Left as a hole
+    Zeros {
+        length: u64,
+    },
+    Data {
+        offset: u64,
+        data: Bytes,
+    },
+}
+
+/// Place decoded data chunks between the manifest's zero runs
+///
+/// A data chunk ends at the next zero run or the end of the file, so the
+/// erasure padding on the last chunk is dropped.
+pub(crate) fn layout(manifest: &FileManifest, decoded: Vec<Bytes>) -> Vec<Segment> {
+    let mut segments = Vec::new();
+    let mut decoded = decoded.into_iter();
+    let mut zero_runs = manifest.zero_runs.iter().peekable();
+    let mut offset = 0u64;
+
+    while offset < manifest.total_size {
+        if let Some(run) = zero_runs.next_if(|r| r.offset == offset) {
+            segments.push(Segment::Zeros { length: run.length });
+            offset += run.length;
+            continue;
+        }
+
+        let Some(chunk_data) = decoded.next() else {
+            break;
+        };
+
+        let segment_end = zero_runs
+            .peek()
+            .map_or(manifest.total_size, |r| r.offset)
+            .min(manifest.total_size);
+        let length = std::cmp::min(chunk_data.len() as u64, segment_end - offset) as usize;
+        segments.push(Segment::Data {
+            offset,
+            data: chunk_data.slice(..length),
+        });
+        offset += length as u64;
+    }
+
+    segments
+}
+
+/// Write `segments` in order through one cursor; returns the file checksum
+pub(crate) async fn write_sequential(
+    path: &Path,
+    segments: Vec<Segment>,
+    total_size: u64,
+    algorithm: ChecksumType,
+    policy: WritePolicy,
+) -> Result<[u8; 32]> {
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        let governor = WriteGovernor::new(policy);
+        let mut output = Output::create(&path, policy.direct_io)?;
+        let mut file_hasher = algorithm.hasher();
+
+        for segment in &segments {
+            match segment {
+                Segment::Zeros { length } => {
+                    output.skip(*length, &governor)?;
+                    hash_zeros(file_hasher.as_mut(), *length);
+                }
+                Segment::Data { data, .. } => {
+                    output.write(data, &governor)?;
+                    file_hasher.update(data);
+                }
+            }
+        }
+
+        let file = output.finish(total_size, &governor)?;
+        governor.finish(&file)?;
+        Ok(file_hasher.finalize())
+    })
+    .await
+    .map_err(std::io::Error::other)?
+}
+
+/// Write `segments` with up to `concurrency` positioned writes in flight;
+/// returns the file checksum
+pub(crate) async fn write_parallel(
+    path: &Path,
+    segments: Vec<Segment>,
+    total_size: u64,
+    algorithm: ChecksumType,
+    concurrency: usize,
+    policy: WritePolicy,
+) -> Result<[u8; 32]> {
+    let output_file = tokio::fs::File::create(path).await?;
+    // Sized up front so writes past a hole land at the right offset
+    output_file.set_len(total_size).await?;
+    let output_file = Arc::new(output_file.into_std().await);
+    let governor = Arc::new(WriteGovernor::new(policy));
+
+    let segments = Arc::new(segments);
+    let hashing = {
+        let segments = Arc::clone(&segments);
+        tokio::task::spawn_blocking(move || {
+            let mut file_hasher = algorithm.hasher();
+            for segment in segments.iter() {
+                match segment {
+                    Segment::Zeros { length } => hash_zeros(file_hasher.as_mut(), *length),
+                    Segment::Data { data, .. } => {
+                        file_hasher.update(data);
+                    }
+                }
+            }
+            file_hasher.finalize()
+        })
+    };
+
+    let mut writers = JoinSet::new();
+    for batch in batches(&segments) {
+        if writers.len() >= concurrency.max(1) {
+            if let Some(written) = writers.join_next().await {
+                written.map_err(std::io::Error::other)??;
+            }
+        }
+        let output_file = Arc::clone(&output_file);
+        let governor = Arc::clone(&governor);
+        writers.spawn_blocking(move || {
+            batch.iter().try_for_each(|(offset, data)| {
+                governor.write(&output_file, data.len(), || {
+                    write_all_at(&output_file, data, *offset)
+                })
+            })
+        });
+    }
+    while let Some(written) = writers.join_next().await {
+        written.map_err(std::io::Error::other)??;
+    }
+    tokio::task::spawn_blocking(move || governor.finish(&output_file))
+        .await
+        .map_err(std::io::Error::other)??;
+
+    Ok(hashing.await.map_err(std::io::Error::other)?)
+}
+
+/// Applies a [`WritePolicy`] to the writes of one file, from any thread
+struct WriteGovernor {
+    policy: WritePolicy,
+    pacer: Mutex<Pacer>,
+    /// Bytes written since the last `fsync`
+    unsynced: AtomicU64,
+}
+
+impl WriteGovernor {
+    fn new(policy: WritePolicy) -> Self {
+        Self {
+            policy,
+            pacer: Mutex::new(Pacer::new(policy.max_bytes_per_sec)),
+            unsynced: AtomicU64::new(0),
+        }
+    }
+
+    /// Wait for the rate limit, run `write` of `bytes` to `file`, and sync
+    /// if enough has built up
+    fn write(
+        &self,
+        file: &std::fs::File,
+        bytes: usize,
+        write: impl FnOnce() -> std::io::Result<()>,
+    ) -> std::io::Result<()> {
+        let delay = self.pacer.lock().delay_for(bytes as u64, Instant::now());
+        if !delay.is_zero() {
+            std::thread::sleep(delay);
+            recorder::record_disk_throttle(delay);
+        }
+
+        let started = Instant::now();
+        write()?;
+        recorder::record_disk_write(bytes as u64, started.elapsed());
+
+        let every = self.policy.sync_every_bytes;
+        if every > 0 {
+            let unsynced = self.unsynced.fetch_add(bytes as u64, Ordering::Relaxed) + bytes as u64;
+            if unsynced >= every {
+                self.unsynced.store(0, Ordering::Relaxed);
+                sync(file, false)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Final `fsync`, if the policy asks for one
+    fn finish(&self, file: &std::fs::File) -> std::io::Result<()> {
+        if self.policy.sync_on_complete {
+            sync(file, true)?;
+        }
+        Ok(())
+    }
+}
+
+fn sync(file: &std::fs::File, all: bool) -> std::io::Result<()> {
+    let started = Instant::now();
+    if all {
+        file.sync_all()?;
+    } else {
+        file.sync_data()?;
+    }
+    recorder::record_disk_sync(started.elapsed());
+    Ok(())
+}
+
+/// Spaces writes out so the average rate stays under a limit
+#[derive(Debug)]
+struct Pacer {
+    bytes_per_sec: u64,
+    started: Option<Instant>,
+    written: u64,
+}
+
+impl Pacer {
+    fn new(bytes_per_sec: u64) -> Self {
+        Self {
+            bytes_per_sec,
+            started: None,
+            written: 0,
+        }
+    }
+
+    /// How long to wait at `now` before writing `bytes` more
+    fn delay_for(&mut self, bytes: u64, now: Instant) -> Duration {
+        if self.bytes_per_sec == 0 {
+            return Duration::ZERO;
+        }
+        let started = *self.started.get_or_insert(now);
+        // When what has been written so far is paid for at the limit
+        let due = Duration::from_secs_f64(self.written as f64 / self.bytes_per_sec as f64);
+        self.written += bytes;
+        due.saturating_sub(now.saturating_duration_since(started))
+    }
+}
+
+/// The file a sequential write goes to
+enum Output {
+    /// Through the page cache, skipping holes with a seek
+    Buffered { file: std::fs::File, sparse: bool },
+    /// Staged into aligned blocks for `O_DIRECT`
+    Direct(DirectWriter),
+}
+
+impl Output {
+    /// Open `path` for writing; without `O_DIRECT` support the file is
+    /// written through the page cache instead
+    fn create(path: &Path, direct_io: bool) -> std::io::Result<Self> {
+        if direct_io {
+            match open_direct(path) {
+                Ok(file) => return Ok(Self::Direct(DirectWriter::new(file))),
+                Err(e) => tracing::warn!(
+                    path = %path.display(),
+                    error = %e,
+                    "O_DIRECT not available, writing through the page cache"
+                ),
+            }
+        }
+        Ok(Self::Buffered {
+            file: std::fs::File::create(path)?,
+            sparse: false,
+        })
+    }
+
+    fn write(&mut self, data: &[u8], governor: &WriteGovernor) -> std::io::Result<()> {
+        match self {
+            Self::Buffered { file, .. } => {
+                governor.write(file, data.len(), || (&*file).write_all(data))
+            }
+            Self::Direct(direct) => direct.write(data, governor),
+        }
+    }
+
+    fn skip(&mut self, length: u64, governor: &WriteGovernor) -> std::io::Result<()> {
+        match self {
+            Self::Buffered { file, sparse } => {
+                use std::io::Seek;
+                file.seek(std::io::SeekFrom::Current(length as i64))?;
+                *sparse = true;
+                Ok(())
+            }
+            Self::Direct(direct) => direct.skip(length, governor),
+        }
+    }
+
+    /// Write out what's left and size the file to `total_size`
+    fn finish(self, total_size: u64, governor: &WriteGovernor) -> std::io::Result<std::fs::File> {
+        match self {
+            Self::Buffered { file, sparse } => {
+                // A trailing hole has nothing written after it, so extend explicitly
+                if sparse {
+                    file.set_len(total_size)?;
+                }
+                Ok(file)
+            }
+            Self::Direct(mut direct) => {
+                direct.flush(governor)?;
+                // Drops the padding of the last block, or extends over a
+                // trailing hole
+                direct.file.set_len(total_size)?;
+                Ok(direct.file)
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn open_direct(path: &Path) -> std::io::Result<std::fs::File> {
+    use std::os::unix::fs::OpenOptionsExt;
+    std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .custom_flags(libc::O_DIRECT)
+        .open(path)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn open_direct(_path: &Path) -> std::io::Result<std::fs::File> {
+    Err(std::io::ErrorKind::Unsupported.into())
+}
+
+/// Collects writes into an aligned buffer and writes whole blocks at
+/// aligned offsets, as `O_DIRECT` requires
+struct DirectWriter {
+    file: std::fs::File,
+    /// Over-allocated so an aligned window of [`DIRECT_BUFFER_BYTES`] fits
+    buffer: Vec<u8>,
+    /// Start of the aligned window in `buffer`
+    start: usize,
+    /// Bytes staged in the window
+    staged: usize,
+    /// File offset of the window's first byte; always aligned
+    offset: u64,
+}
+
+impl DirectWriter {
+    fn new(file: std::fs::File) -> Self {
+        let buffer = vec![0u8; DIRECT_BUFFER_BYTES + DIRECT_ALIGN];
+        let start = buffer.as_ptr().align_offset(DIRECT_ALIGN);
+        Self {
+            file,
+            buffer,
+            start,
+            staged: 0,
+            offset: 0,
+        }
+    }
+
+    fn write(&mut self, mut data: &[u8], governor: &WriteGovernor) -> std::io::Result<()> {
+        while !data.is_empty() {
+            let n = data.len().min(DIRECT_BUFFER_BYTES - self.staged);
+            let at = self.start + self.staged;
+            self.buffer[at..at + n].copy_from_slice(&data[..n]);
+            self.staged += n;
+            data = &data[n..];
+            if self.staged == DIRECT_BUFFER_BYTES {
+                self.flush(governor)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Zeros within a block being filled are written; whole blocks of them
+    /// are skipped, leaving a hole
+    fn skip(&mut self, length: u64, governor: &WriteGovernor) -> std::io::Result<()> {
+        let align = DIRECT_ALIGN as u64;
+        let to_boundary = (align - self.staged as u64 % align) % align;
+        let mut remaining = length;
+        if self.staged > 0 {
+            let fill = remaining.min(to_boundary) as usize;
+            let at = self.start + self.staged;
+            self.buffer[at..at + fill].fill(0);
+            self.staged += fill;
+            remaining -= fill as u64;
+            if remaining == 0 {
+                return Ok(());
+            }
+            self.flush(governor)?;
+        }
+        self.offset += remaining / align * align;
+        let tail = (remaining % align) as usize;
+        self.buffer[self.start..self.start + tail].fill(0);
+        self.staged = tail;
+        Ok(())
+    }
+
+    /// Write the staged bytes, padded with zeros to a whole block
+    fn flush(&mut self, governor: &WriteGovernor) -> std::io::Result<()> {
+        if self.staged == 0 {
+            return Ok(());
+        }
+        let length = self.staged.div_ceil(DIRECT_ALIGN) * DIRECT_ALIGN;
+        let window = self.start..self.start + length;
+        self.buffer[self.start + self.staged..window.end].fill(0);
+        let (file, block, offset) = (&self.file, &self.buffer[window], self.offset);
+        governor.write(file, length, || write_all_at(file, block, offset))?;
+        self.offset += length as u64;
+        self.staged = 0;
+        Ok(())
+    }
+}
+
+/// Consecutive data segments grouped into batches of about
+/// [`WRITE_BATCH_BYTES`]
+fn batches(segments: &[Segment]) -> Vec<Vec<(u64, Bytes)>> {
+    let mut batches = Vec::new();
+    let mut batch = Vec::new();
+    let mut batch_bytes = 0;
+
+    for segment in segments {
+        let Segment::Data { offset, data } = segment else {
+            continue;
+        };
+        if batch_bytes + data.len() > WRITE_BATCH_BYTES && !batch.is_empty() {
+            batches.push(std::mem::take(&mut batch));
+            batch_bytes = 0;
+        }
+        batch_bytes += data.len();
+        batch.push((*offset, data.clone()));
+    }
+    if !batch.is_empty() {
+        batches.push(batch);
+    }
+    batches
+}
+
+#[cfg(unix)]
+fn write_all_at(file: &std::fs::File, data: &[u8], offset: u64) -> std::io::Result<()> {
+    use std::os::unix::fs::FileExt;
+    file.write_all_at(data, offset)
+}
+
+#[cfg(windows)]
+fn write_all_at(file: &std::fs::File, mut data: &[u8], mut offset: u64) -> std::io::Result<()> {
+    use std::os::windows::fs::FileExt;
+    while !data.is_empty() {
+        match file.seek_write(data, offset)? {
+            0 => return Err(std::io::ErrorKind::WriteZero.into()),
+            n => {
+                data = &data[n..];
+                offset += n as u64;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn hash_zeros(hasher: &mut dyn Hasher, mut length: u64) {
+    while length > 0 {
+        let n = std::cmp::min(length, ZERO_BLOCK.len() as u64) as usize;
+        hasher.update(&ZERO_BLOCK[..n]);
+        length -= n as u64;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn segments() -> Vec<Segment> {
+        vec![
+            Segment::Data {
+                offset: 0,
+                data: Bytes::from(vec![1u8; 5000]),
+            },
+            Segment::Zeros { length: 70_000 },
+            Segment::Data {
+                offset: 75_000,
+                data: Bytes::from(vec![2u8; WRITE_BATCH_BYTES - 5000]),
+            },
+            Segment::Data {
+                offset: 70_000 + WRITE_BATCH_BYTES as u64,
+                data: Bytes::from(vec![3u8; 100]),
+            },
+            Segment::Zeros { length: 300 },
+        ]
+    }
+
+    #[tokio::test]
+    async fn test_parallel_matches_sequential() {
+        let dir = TempDir::new().unwrap();
+        let total_size = 70_400 + WRITE_BATCH_BYTES as u64;
+
+        let sequential = dir.path().join("sequential.bin");
+        let expected = write_sequential(
+            &sequential,
+            segments(),
+            total_size,
+            ChecksumType::Sha256,
+            WritePolicy::default(),
+        )
+        .await
+        .unwrap();
+
+        let parallel = dir.path().join("parallel.bin");
+        let policy = WritePolicy {
+            sync_every_bytes: 1024 * 1024,
+            sync_on_complete: true,
+            ..Default::default()
+        };
+        let checksum = write_parallel(
+            &parallel,
+            segments(),
+            total_size,
+            ChecksumType::Sha256,
+            4,
+            policy,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(checksum, expected);
+        let written = std::fs::read(&parallel).unwrap();
+        assert_eq!(written.len() as u64, total_size);
+        assert_eq!(written, std::fs::read(&sequential).unwrap());
+        assert_eq!(ChecksumType::Sha256.digest(&written), expected);
+    }
+
+    #[tokio::test]
+    async fn test_direct_io_writes_the_same_file() {
+        let dir = TempDir::new().unwrap();
+        // Unaligned data and holes on both sides of block boundaries
+        let segments = vec![
+            Segment::Data {
+                offset: 0,
+                data: Bytes::from(vec![1u8; 5000]),
+            },
+            Segment::Zeros { length: 10_000 },
+            Segment::Data {
+                offset: 15_000,
+                data: Bytes::from(vec![2u8; DIRECT_BUFFER_BYTES + 123]),
+            },
+            Segment::Zeros { length: 3 },
+        ];
+        let total_size = 15_126 + DIRECT_BUFFER_BYTES as u64;
+
+        let buffered = dir.path().join("buffered.bin");
+        let expected = write_sequential(
+            &buffered,
+            segments.clone(),
+            total_size,
+            ChecksumType::Sha256,
+            WritePolicy::default(),
+        )
+        .await
+        .unwrap();
+
+        // Falls back to the page cache where the file system refuses O_DIRECT
+        let direct = dir.path().join("direct.bin");
+        let policy = WritePolicy {
+            direct_io: true,
+            sync_on_complete: true,
+            ..Default::default()
+        };
+        let checksum =
+            write_sequential(&direct, segments, total_size, ChecksumType::Sha256, policy)
+                .await
+                .unwrap();
+
+        assert_eq!(checksum, expected);
+        assert_eq!(
+            std::fs::read(&direct).unwrap(),
+            std::fs::read(&buffered).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_pacer_keeps_average_rate() {
+        let start = Instant::now();
+        let mut pacer = Pacer::new(1000);
+        assert_eq!(pacer.delay_for(500, start), Duration::ZERO);
+        // The first 500 bytes take half a second at 1000 B/s
+        assert_eq!(pacer.delay_for(500, start), Duration::from_millis(500));
+        assert_eq!(
+            pacer.delay_for(500, start + Duration::from_millis(200)),
+            Duration::from_millis(800)
+        );
+        // Idle time counts toward the limit
+        assert_eq!(
+            pacer.delay_for(500, start + Duration::from_secs(5)),
+            Duration::ZERO
+        );
+
+        let mut unlimited = Pacer::new(0);
+        assert_eq!(unlimited.delay_for(u64::MAX, start), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_batches_split_at_batch_size() {
+        let batches = batches(&segments());
+        let offsets: Vec<Vec<u64>> = batches
+            .iter()
+            .map(|batch| batch.iter().map(|(offset, _)| *offset).collect())
+            .collect();
+        // The first two fill a batch exactly; holes don't count toward it
+        assert_eq!(
+            offsets,
+            [vec![0, 75_000], vec![70_000 + WRITE_BATCH_BYTES as u64]]
+        );
+    }
+}
@@ -0,0 +1,342 @@
+//! Typed client for the REST and WebSocket API
+//!
+//! Downstream tools talk to a running server through [`ResilientClient`]
+//! instead of building requests by hand. It shares its request and response
+//! types with [`api`](crate::api), so a change to the server's types shows
+//! up here at compile time.
+//!
+//! ```no_run
+//! # async fn run() -> chunkstream_pro::client::ClientResult<()> {
+//! use chunkstream_pro::api::StartTransferRequest;
+//! use chunkstream_pro::chunk::Priority;
+//! use chunkstream_pro::client::ResilientClient;
+//! use futures::StreamExt;
+//!
+//! let client = ResilientClient::new("http://10.0.0.2:3000")?;
+//! let started = client
+//!     .start_transfer(&StartTransferRequest {
+//!         file_path: "/data/survey.tif".into(),
+//!         priority: Some(Priority::High),
+//!         receiver_addr: Some("10.0.0.9:5001".into()),
+//!         local_bind_addr: None,
+//!         profile: None,
+//!         allow_duplicate: false,
+//!         tags: Default::default(),
+//!     })
+//!     .await?;
+//!
+//! let mut updates = Box::pin(client.subscribe_progress().await?);
+//! while let Some(update) = updates.next().await {
+//!     println!("{:?}", update?);
+//! }
+//! # let _ = started;
+//! # Ok(())
+//! # }
+//! ```
+
+mod error;
+mod progress;
+mod rest;
+
+pub use error::{ClientError, ClientResult};
+pub use rest::ResilientClient;
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+    use super::*;
+    use crate::api::{
+        create_api_server, ListBenchmarksQuery, ListTransfersQuery, StartTransferRequest,
+        UploadBenchmarkRequest, WebSocketMessage,
+    };
+    use crate::chunk::{ChunkManager, Priority};
+    use crate::coordinator::{CoordinatorEvent, TransferCoordinator};
+    use crate::network::{ConnectionConfig, QuicTransport};
+    use crate::priority::PriorityQueue;
+    use crate::session::{SessionStore, TransferProfile};
+    use futures::StreamExt;
+    use std::io::Write;
+    use std::time::Duration;
+
+    async fn serve() -> ResilientClient {
+        let coordinator = TransferCoordinator::new(
+            ChunkManager::new(256 * 1024, 10, 3).unwrap(),
+            QuicTransport::new(ConnectionConfig::default())
+                .await
+                .unwrap(),
+            PriorityQueue::new(1_000_000),
+            SessionStore::new_in_memory().await.unwrap(),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, create_api_server(coordinator))
+                .await
+                .unwrap();
+        });
+        ResilientClient::new(format!("http://{addr}/")).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_client_round_trips_against_server() {
+        let client = serve().await;
+        assert!(client.health().await.unwrap());
+        assert!(client.liveness().await.unwrap().is_healthy());
+        assert_eq!(client.readiness().await.unwrap().components.len(), 4);
+
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(&[3u8; 4096]).unwrap();
+        let started = client
+            .start_transfer(&StartTransferRequest {
+                file_path: file.path().to_string_lossy().to_string(),
+                priority: Some(Priority::High),
+                receiver_addr: None,
+                local_bind_addr: None,
+                profile: None,
+                allow_duplicate: false,
+                tags: [("incident_id".to_string(), "42".to_string())].into(),
+            })
+            .await
+            .unwrap();
+
+        let state = client.get_transfer(&started.session_id).await.unwrap();
+        assert_eq!(state.session_id, started.session_id);
+        let progress = client.get_progress(&started.session_id).await.unwrap();
+        assert!(progress.total_chunks > 0);
+
+        let listing = client
+            .list_transfers(&ListTransfersQuery {
+                limit: Some(10),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(listing.transfers[0].session_id, started.session_id);
+        assert_eq!(listing.transfers[0].tags["incident_id"], "42");
+        for (filter, expected) in [("incident_id:42", 1), ("incident_id:7", 0)] {
+            let listing = client
+                .list_transfers(&ListTransfersQuery {
+                    tags: Some(filter.into()),
+                    ..Default::default()
+                })
+                .await
+                .unwrap();
+            assert_eq!(listing.total, expected, "{filter}");
+        }
+
+        // Server errors keep their status and code
+        let error = client.get_progress("no-such-session").await.unwrap_err();
+        assert!(matches!(error, ClientError::Api { .. }), "{error}");
+        let error = client
+            .start_transfer(&StartTransferRequest {
+                file_path: "/no/such/file".into(),
+                priority: Some(Priority::Normal),
+                receiver_addr: None,
+                local_bind_addr: None,
+                profile: None,
+                allow_duplicate: false,
+                tags: Default::default(),
+            })
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(&error, ClientError::Api { status: 400, code, .. } if code == "INVALID_REQUEST"),
+            "{error}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_benchmark_reports() {
+        let client = serve().await;
+        let report = serde_json::json!({
+            "summary": { "total_tests": 12, "passed": 12 },
+            "performance_curves": { "throughput_vs_loss": [[0.0, 41.5], [0.2, 30.1]] },
+        });
+
+        let first = client
+            .upload_benchmark(&UploadBenchmarkRequest {
+                label: Some("nightly".into()),
+                report: report.clone(),
+            })
+            .await
+            .unwrap();
+        assert_eq!(client.get_benchmark(&first.id).await.unwrap(), first);
+        let second = client
+            .upload_benchmark(&UploadBenchmarkRequest {
+                label: None,
+                report: report.clone(),
+            })
+            .await
+            .unwrap();
+
+        let error = client
+            .upload_benchmark(&UploadBenchmarkRequest {
+                label: None,
+                report: serde_json::json!([1, 2, 3]),
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(error.status(), Some(400));
+
+        let listed = client
+            .list_benchmarks(&ListBenchmarksQuery::default())
+            .await
+            .unwrap();
+        assert_eq!(listed.count, 2);
+        assert_eq!(listed.benchmarks[0].id, second.id);
+        assert_eq!(listed.benchmarks[1].report, report);
+
+        client.delete_benchmark(&first.id).await.unwrap();
+        assert!(client
+            .get_benchmark(&first.id)
+            .await
+            .unwrap_err()
+            .is_not_found());
+    }
+
+    #[tokio::test]
+    async fn test_transfer_profiles() {
+        let client = serve().await;
+        let mut profile = TransferProfile::new("field-bulk");
+        profile.priority = Some(Priority::High);
+        profile.options.chunk_size = Some(64 * 1024);
+        profile.options.data_shards = Some(4);
+        profile.options.parity_shards = Some(6);
+
+        let created = client.create_profile(&profile).await.unwrap();
+        assert!(created.created_at > 0);
+        let error = client.create_profile(&profile).await.unwrap_err();
+        assert_eq!(error.status(), Some(400));
+
+        profile.description = Some("Survey imagery".into());
+        client.save_profile(&profile).await.unwrap();
+        let listed = client.list_profiles().await.unwrap().profiles;
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].description.as_deref(), Some("Survey imagery"));
+
+        // Settings come from the profile
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(&[5u8; 256 * 1024]).unwrap();
+        let started = client
+            .start_transfer(&StartTransferRequest {
+                file_path: file.path().to_string_lossy().to_string(),
+                priority: None,
+                receiver_addr: None,
+                local_bind_addr: None,
+                profile: Some("field-bulk".into()),
+                allow_duplicate: false,
+                tags: Default::default(),
+            })
+            .await
+            .unwrap();
+        let progress = client.get_progress(&started.session_id).await.unwrap();
+        assert_eq!(progress.total_chunks, 4 + 6);
+
+        client.delete_profile("field-bulk").await.unwrap();
+        assert!(client
+            .get_profile("field-bulk")
+            .await
+            .unwrap_err()
+            .is_not_found());
+        let error = client
+            .start_transfer(&StartTransferRequest {
+                file_path: file.path().to_string_lossy().to_string(),
+                priority: None,
+                receiver_addr: None,
+                local_bind_addr: None,
+                profile: Some("field-bulk".into()),
+                allow_duplicate: false,
+                tags: Default::default(),
+            })
+            .await
+            .unwrap_err();
+        assert!(error.is_not_found(), "{error}");
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_progress_yields_server_updates() {
+        let client = serve().await;
+        let mut updates = Box::pin(client.subscribe_progress().await.unwrap());
+
+        let update = tokio::time::timeout(Duration::from_secs(5), updates.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert!(matches!(update, WebSocketMessage::MetricsSnapshot(_)));
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_session_starts_with_snapshot() {
+        let client = serve().await;
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(&[7u8; 4096]).unwrap();
+        let started = client
+            .start_transfer(&StartTransferRequest {
+                file_path: file.path().to_string_lossy().to_string(),
+                priority: Some(Priority::High),
+                receiver_addr: None,
+                local_bind_addr: None,
+                profile: None,
+                allow_duplicate: false,
+                tags: Default::default(),
+            })
+            .await
+            .unwrap();
+
+        // Joining after the transfer started still shows how it began
+        let mut updates = Box::pin(
+            client
+                .subscribe_session(&started.session_id, None)
+                .await
+                .unwrap(),
+        );
+        let snapshot = loop {
+            let update = tokio::time::timeout(Duration::from_secs(5), updates.next())
+                .await
+                .unwrap()
+                .unwrap()
+                .unwrap();
+            match update {
+                WebSocketMessage::SessionSnapshot {
+                    session_id,
+                    progress,
+                    events,
+                } => break (session_id, progress, events),
+                // Periodic updates go to every client, in or out of a room
+                WebSocketMessage::MetricsSnapshot(_) | WebSocketMessage::TransferProgress(_) => {}
+                other => panic!("update before the snapshot: {other:?}"),
+            }
+        };
+        assert_eq!(snapshot.0, started.session_id);
+        assert_eq!(snapshot.1.session_id, started.session_id);
+        assert!(matches!(
+            snapshot.2.first().map(|e| &e.event),
+            Some(CoordinatorEvent::TransferStarted { .. })
+        ));
+
+        let mut updates = Box::pin(
+            client
+                .subscribe_session("no-such-session", None)
+                .await
+                .unwrap(),
+        );
+        loop {
+            match updates.next().await.unwrap().unwrap() {
+                WebSocketMessage::Error(error) => {
+                    assert_eq!(error.code, "COORDINATOR_ERROR");
+                    break;
+                }
+                WebSocketMessage::MetricsSnapshot(_) | WebSocketMessage::TransferProgress(_) => {}
+                other => panic!("unexpected update: {other:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_rejects_non_http_urls() {
+        assert!(matches!(
+            ResilientClient::new("10.0.0.2:3000"),
+            Err(ClientError::InvalidUrl { .. })
+        ));
+    }
+}
@@ -0,0 +1,85 @@
+use crate::api::{WebSocketClientMessage, WebSocketMessage};
+use crate::client::error::{ClientError, ClientResult};
+use crate::client::rest::ResilientClient;
+use futures::{stream, SinkExt, Stream, StreamExt};
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+impl ResilientClient {
+    /// Live updates from the server's `/ws` endpoint
+    ///
+    /// Yields transfer progress and metrics snapshots as the server pushes
+    /// them, and ends when the server closes the socket. A message that
+    /// doesn't decode is yielded as an error and the stream carries on.
+    pub async fn subscribe_progress(
+        &self,
+    ) -> ClientResult<impl Stream<Item = ClientResult<WebSocketMessage>> + Send + 'static> {
+        let url = self.websocket_url("/ws");
+        let (socket, _) = tokio_tungstenite::connect_async(url.as_str()).await?;
+        Ok(updates(socket))
+    }
+
+    /// Live updates for one session, starting with where it stands
+    ///
+    /// The first message is a
+    /// [`SessionSnapshot`](WebSocketMessage::SessionSnapshot) with the
+    /// session's progress and up to `backfill` of its latest events (the
+    /// server's default when `None`), then
+    /// [`SessionEvent`](WebSocketMessage::SessionEvent)s as they happen,
+    /// interleaved with progress for this session and metrics snapshots. An
+    /// unknown session yields an [`Error`](WebSocketMessage::Error) message.
+    pub async fn subscribe_session(
+        &self,
+        session_id: &str,
+        backfill: Option<usize>,
+    ) -> ClientResult<impl Stream<Item = ClientResult<WebSocketMessage>> + Send + 'static> {
+        let url = self.websocket_url("/ws");
+        let (mut socket, _) = tokio_tungstenite::connect_async(url.as_str()).await?;
+        let join = WebSocketClientMessage::Join {
+            session_id: session_id.to_string(),
+            backfill,
+        };
+        socket
+            .send(Message::Text(serde_json::to_string(&join)?))
+            .await?;
+        Ok(updates(socket))
+    }
+
+    /// `path` on the server, over `ws://` or `wss://`
+    fn websocket_url(&self, path: &str) -> String {
+        let base = self.base_url();
+        let base = match base.strip_prefix("https://") {
+            Some(rest) => format!("wss://{rest}"),
+            None => format!("ws://{}", base.trim_start_matches("http://")),
+        };
+        format!("{base}{path}")
+    }
+}
+
+/// Server messages from `socket` until it closes
+fn updates(socket: Socket) -> impl Stream<Item = ClientResult<WebSocketMessage>> + Send + 'static {
+    stream::unfold(socket, |mut socket| async move {
+        loop {
+            let message = match socket.next().await? {
+                Ok(message) => message,
+                Err(e) => return Some((Err(e.into()), socket)),
+            };
+            match message {
+                Message::Text(text) => {
+                    let update = serde_json::from_str(&text).map_err(ClientError::from);
+                    return Some((update, socket));
+                }
+                Message::Ping(payload) => {
+                    if let Err(e) = socket.send(Message::Pong(payload)).await {
+                        return Some((Err(e.into()), socket));
+                    }
+                }
+                Message::Close(_) => return None,
+                _ => {}
+            }
+        }
+    })
+}
@@ -0,0 +1,448 @@
+use crate::api::*;
+use crate::chunk::Priority;
+use crate::client::error::{ClientError, ClientResult};
+use crate::coordinator::{ChunkingDefaults, ErasureDefaults, HealthReport, ResumeToken};
+use crate::failover::{FailoverStatus, TakeoverReport};
+use crate::session::{BenchmarkRecord, TransferProfile};
+use reqwest::{Method, RequestBuilder, Response};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::net::SocketAddr;
+use std::path::Path;
+
+/// Typed client for a server's REST API
+///
+/// Methods map one to one onto the routes in [`RestApi`](crate::api::RestApi)
+/// and use the same request and response types.
+#[derive(Debug, Clone)]
+pub struct ResilientClient {
+    http: reqwest::Client,
+    /// Server root without a trailing slash, e.g. `http://10.0.0.2:3000`
+    base_url: String,
+}
+
+impl ResilientClient {
+    /// Client for the server at `base_url` (`http://` or `https://`)
+    pub fn new(base_url: impl Into<String>) -> ClientResult<Self> {
+        Self::with_http_client(reqwest::Client::new(), base_url)
+    }
+
+    /// Client sending through a preconfigured [`reqwest::Client`], for
+    /// timeouts, proxies or extra headers
+    pub fn with_http_client(
+        http: reqwest::Client,
+        base_url: impl Into<String>,
+    ) -> ClientResult<Self> {
+        let base_url = base_url.into();
+        if !(base_url.starts_with("http://") || base_url.starts_with("https://")) {
+            return Err(ClientError::InvalidUrl {
+                url: base_url,
+                reason: "expected an http:// or https:// URL".to_string(),
+            });
+        }
+        Ok(Self {
+            http,
+            base_url: base_url.trim_end_matches('/').to_string(),
+        })
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Whether the server answers its health check
+    pub async fn health(&self) -> ClientResult<bool> {
+        let response = self.request(Method::GET, "/health").send().await?;
+        Ok(response.status().is_success())
+    }
+
+    /// Liveness report; a 503 still carries the report
+    pub async fn liveness(&self) -> ClientResult<HealthReport> {
+        self.health_report("/health/live").await
+    }
+
+    /// Readiness report with the status of each component
+    pub async fn readiness(&self) -> ClientResult<HealthReport> {
+        self.health_report("/health/ready").await
+    }
+
+    // --- Transfers ---
+
+    /// Send a file already on the server's disk
+    pub async fn start_transfer(
+        &self,
+        request: &StartTransferRequest,
+    ) -> ClientResult<StartTransferResponse> {
+        self.post("/api/v1/transfers", request).await
+    }
+
+    /// Files the server offers for receivers to pull
+    pub async fn catalog(&self) -> ClientResult<CatalogResponse> {
+        self.get("/api/v1/catalog").await
+    }
+
+    /// Ask the server to push a catalog file to the receiver named in
+    /// `request`
+    pub async fn request_catalog_file(
+        &self,
+        request: &CatalogTransferRequest,
+    ) -> ClientResult<StartTransferResponse> {
+        self.post("/api/v1/catalog/request", request).await
+    }
+
+    /// Upload a local file and send it
+    pub async fn upload(
+        &self,
+        path: impl AsRef<Path>,
+        priority: Priority,
+        receiver_addr: Option<SocketAddr>,
+    ) -> ClientResult<StartTransferResponse> {
+        let path = path.as_ref();
+        let file_name = path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+        let data = tokio::fs::read(path).await?;
+
+        let mut form = reqwest::multipart::Form::new()
+            .part(
+                "file",
+                reqwest::multipart::Part::bytes(data).file_name(file_name),
+            )
+            .text("priority", format!("{priority:?}"));
+        if let Some(addr) = receiver_addr {
+            form = form.text("receiver_addr", addr.to_string());
+        }
+        let request = self.request(Method::POST, "/api/v1/upload").multipart(form);
+        Self::json(request).await
+    }
+
+    pub async fn list_transfers(
+        &self,
+        query: &ListTransfersQuery,
+    ) -> ClientResult<ListTransfersResponse> {
+        Self::json(self.request(Method::GET, "/api/v1/transfers").query(query)).await
+    }
+
+    /// Sessions that carried a file with the given name or checksum
+    pub async fn search_sessions(
+        &self,
+        query: &SearchSessionsQuery,
+    ) -> ClientResult<SearchSessionsResponse> {
+        Self::json(
+            self.request(Method::GET, "/api/v1/sessions/search")
+                .query(query),
+        )
+        .await
+    }
+
+    /// Transfers waiting for a concurrency slot
+    pub async fn pending_transfers(&self) -> ClientResult<PendingTransfersResponse> {
+        self.get("/api/v1/transfers/pending").await
+    }
+
+    pub async fn get_transfer(&self, session_id: &str) -> ClientResult<TransferStateResponse> {
+        self.get(&format!("/api/v1/transfers/{session_id}")).await
+    }
+
+    pub async fn pause_transfer(&self, session_id: &str) -> ClientResult<SuccessResponse> {
+        self.post_empty(&format!("/api/v1/transfers/{session_id}/pause"))
+            .await
+    }
+
+    pub async fn resume_transfer(&self, session_id: &str) -> ClientResult<SuccessResponse> {
+        self.post_empty(&format!("/api/v1/transfers/{session_id}/resume"))
+            .await
+    }
+
+    pub async fn cancel_transfer(&self, session_id: &str) -> ClientResult<SuccessResponse> {
+        self.post_empty(&format!("/api/v1/transfers/{session_id}/cancel"))
+            .await
+    }
+
+    pub async fn get_progress(&self, session_id: &str) -> ClientResult<TransferProgressResponse> {
+        self.get(&format!("/api/v1/transfers/{session_id}/progress"))
+            .await
+    }
+
+    /// Throughput samples of a transfer, oldest first
+    pub async fn get_timeseries(&self, session_id: &str) -> ClientResult<TimeseriesResponse> {
+        self.get(&format!("/api/v1/transfers/{session_id}/timeseries"))
+            .await
+    }
+
+    /// Token for continuing the transfer on another host
+    pub async fn export_resume_token(&self, session_id: &str) -> ClientResult<ResumeToken> {
+        self.get(&format!("/api/v1/transfers/{session_id}/resume-token"))
+            .await
+    }
+
+    pub async fn import_resume_token(
+        &self,
+        request: &ImportResumeTokenRequest,
+    ) -> ClientResult<StartTransferResponse> {
+        self.post("/api/v1/transfers/resume-token", request).await
+    }
+
+    // --- Transfer profiles ---
+
+    pub async fn list_profiles(&self) -> ClientResult<ListProfilesResponse> {
+        self.get("/api/v1/profiles").await
+    }
+
+    /// Create a profile; fails if one with the same name exists
+    pub async fn create_profile(&self, profile: &TransferProfile) -> ClientResult<TransferProfile> {
+        self.post("/api/v1/profiles", profile).await
+    }
+
+    pub async fn get_profile(&self, name: &str) -> ClientResult<TransferProfile> {
+        self.get(&format!("/api/v1/profiles/{name}")).await
+    }
+
+    /// Create or replace the profile called `profile.name`
+    pub async fn save_profile(&self, profile: &TransferProfile) -> ClientResult<TransferProfile> {
+        Self::json(
+            self.request(Method::PUT, &format!("/api/v1/profiles/{}", profile.name))
+                .json(profile),
+        )
+        .await
+    }
+
+    pub async fn delete_profile(&self, name: &str) -> ClientResult<SuccessResponse> {
+        Self::json(self.request(Method::DELETE, &format!("/api/v1/profiles/{name}"))).await
+    }
+
+    // --- Benchmark reports ---
+
+    pub async fn list_benchmarks(
+        &self,
+        query: &ListBenchmarksQuery,
+    ) -> ClientResult<ListBenchmarksResponse> {
+        Self::json(self.request(Method::GET, "/api/v1/benchmarks").query(query)).await
+    }
+
+    /// Store a report the benchmark suite wrote, under a new id
+    pub async fn upload_benchmark(
+        &self,
+        request: &UploadBenchmarkRequest,
+    ) -> ClientResult<BenchmarkRecord> {
+        self.post("/api/v1/benchmarks", request).await
+    }
+
+    pub async fn get_benchmark(&self, id: &str) -> ClientResult<BenchmarkRecord> {
+        self.get(&format!("/api/v1/benchmarks/{id}")).await
+    }
+
+    pub async fn delete_benchmark(&self, id: &str) -> ClientResult<SuccessResponse> {
+        Self::json(self.request(Method::DELETE, &format!("/api/v1/benchmarks/{id}"))).await
+    }
+
+    // --- Runtime configuration ---
+
+    pub async fn effective_config(&self) -> ClientResult<EffectiveConfigResponse> {
+        self.get("/api/v1/config").await
+    }
+
+    pub async fn erasure_defaults(&self) -> ClientResult<ErasureDefaults> {
+        self.get("/api/v1/config/erasure").await
+    }
+
+    pub async fn set_erasure_defaults(
+        &self,
+        request: &UpdateErasureRequest,
+    ) -> ClientResult<ErasureDefaults> {
+        Self::json(
+            self.request(Method::PUT, "/api/v1/config/erasure")
+                .json(request),
+        )
+        .await
+    }
+
+    pub async fn chunking_defaults(&self) -> ClientResult<ChunkingDefaults> {
+        self.get("/api/v1/config/chunking").await
+    }
+
+    pub async fn set_chunking_defaults(
+        &self,
+        request: &UpdateChunkingRequest,
+    ) -> ClientResult<ChunkingDefaults> {
+        Self::json(
+            self.request(Method::PUT, "/api/v1/config/chunking")
+                .json(request),
+        )
+        .await
+    }
+
+    /// Log level directives and format of the server process
+    pub async fn logging(&self) -> ClientResult<LogSettingsResponse> {
+        self.get("/api/v1/logging").await
+    }
+
+    /// Change the server's log levels without restarting it
+    pub async fn set_log_filter(&self, filter: &str) -> ClientResult<LogSettingsResponse> {
+        Self::json(
+            self.request(Method::PUT, "/api/v1/logging")
+                .json(&UpdateLogFilterRequest {
+                    filter: filter.to_string(),
+                }),
+        )
+        .await
+    }
+
+    // --- Metrics ---
+
+    pub async fn erasure_metrics(&self) -> ClientResult<ErasureMetricsResponse> {
+        self.get("/api/v1/metrics/erasure").await
+    }
+
+    pub async fn network_metrics(&self) -> ClientResult<NetworkMetricsResponse> {
+        self.get("/api/v1/metrics/network").await
+    }
+
+    pub async fn queue_metrics(&self) -> ClientResult<QueueMetricsResponse> {
+        self.get("/api/v1/metrics/queue").await
+    }
+
+    /// The server's role in an active/standby pair and how replication is doing
+    pub async fn failover_status(&self) -> ClientResult<FailoverStatus> {
+        self.get("/api/v1/failover").await
+    }
+
+    /// Make a standby server take over the active's transfers; the active
+    /// must be down
+    pub async fn promote(&self) -> ClientResult<TakeoverReport> {
+        self.post_empty("/api/v1/failover/promote").await
+    }
+
+    /// Latency distribution of each transfer stage
+    pub async fn latency_metrics(&self) -> ClientResult<LatencyMetricsResponse> {
+        self.get("/api/v1/metrics/latency").await
+    }
+
+    /// Session database size, row counts and the latest maintenance pass
+    pub async fn storage_metrics(&self) -> ClientResult<StorageMetricsResponse> {
+        self.get("/api/v1/metrics/storage").await
+    }
+
+    pub async fn metrics_summary(&self) -> ClientResult<MetricsSummaryResponse> {
+        self.get("/api/v1/metrics/summary").await
+    }
+
+    // --- Simulation and diagnostics ---
+
+    pub async fn simulate_packet_loss(
+        &self,
+        request: &SimulationRequest,
+    ) -> ClientResult<SimulationResponse> {
+        self.post("/api/v1/simulate/packet-loss", request).await
+    }
+
+    pub async fn simulate_comparison(
+        &self,
+        request: &ComparisonRequest,
+    ) -> ClientResult<ComparisonResponse> {
+        self.post("/api/v1/simulate/comparison", request).await
+    }
+
+    pub async fn simulate_mesh(
+        &self,
+        request: &MeshSimulationRequest,
+    ) -> ClientResult<MeshSimulationResponse> {
+        self.post("/api/v1/simulate/mesh", request).await
+    }
+
+    /// Measure the link to a receiver
+    pub async fn probe(&self, request: &ProbeRequest) -> ClientResult<ProbeResponse> {
+        self.post("/api/v1/probe", request).await
+    }
+
+    /// Check stored files against the manifests they were sent with
+    pub async fn verify(&self, request: &VerifyRequest) -> ClientResult<VerifyResponse> {
+        self.post("/api/v1/verify", request).await
+    }
+
+    /// Files uploaded to the server
+    pub async fn list_uploads(&self) -> ClientResult<ListUploadsResponse> {
+        self.get("/api/v1/uploads").await
+    }
+
+    // --- Chaos testing ---
+
+    #[cfg(feature = "fault-injection")]
+    pub async fn faults(&self) -> ClientResult<FaultStateResponse> {
+        self.get("/api/v1/internal/faults").await
+    }
+
+    #[cfg(feature = "fault-injection")]
+    pub async fn set_faults(
+        &self,
+        config: &crate::fault::FaultConfig,
+    ) -> ClientResult<FaultStateResponse> {
+        Self::json(
+            self.request(Method::PUT, "/api/v1/internal/faults")
+                .json(config),
+        )
+        .await
+    }
+
+    #[cfg(feature = "fault-injection")]
+    pub async fn clear_faults(&self) -> ClientResult<FaultStateResponse> {
+        Self::json(self.request(Method::DELETE, "/api/v1/internal/faults")).await
+    }
+
+    /// Health endpoints answer 503 with the same body, so decode either way
+    async fn health_report(&self, path: &str) -> ClientResult<HealthReport> {
+        let response = self.request(Method::GET, path).send().await?;
+        if response.status() == reqwest::StatusCode::SERVICE_UNAVAILABLE {
+            return Ok(response.json().await?);
+        }
+        Ok(Self::check(response).await?.json().await?)
+    }
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        self.http
+            .request(method, format!("{}{}", self.base_url, path))
+    }
+
+    async fn get<T: DeserializeOwned>(&self, path: &str) -> ClientResult<T> {
+        Self::json(self.request(Method::GET, path)).await
+    }
+
+    async fn post<B: Serialize + ?Sized, T: DeserializeOwned>(
+        &self,
+        path: &str,
+        body: &B,
+    ) -> ClientResult<T> {
+        Self::json(self.request(Method::POST, path).json(body)).await
+    }
+
+    async fn post_empty<T: DeserializeOwned>(&self, path: &str) -> ClientResult<T> {
+        Self::json(self.request(Method::POST, path)).await
+    }
+
+    /// Send `request` and decode a successful JSON reply
+    async fn json<T: DeserializeOwned>(request: RequestBuilder) -> ClientResult<T> {
+        let response = Self::check(request.send().await?).await?;
+        Ok(response.json().await?)
+    }
+
+    /// Turn an error status into [`ClientError::Api`], with the server's
+    /// error code when the body carries one
+    async fn check(response: Response) -> ClientResult<Response> {
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+        let body = response.text().await.unwrap_or_default();
+        let (code, message) = match serde_json::from_str::<ErrorResponse>(&body) {
+            Ok(error) => (error.code, error.error),
+            Err(_) => ("HTTP_ERROR".to_string(), body),
+        };
+        Err(ClientError::Api {
+            status: status.as_u16(),
+            code,
+            message,
+        })
+    }
+}
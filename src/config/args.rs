@@ -0,0 +1,86 @@
+//! The `--config` flag shared by the daemons
+//!
+//! `--config <path>` (or `--config=<path>`) names the TOML file to load; the
+//! `RESILIENT_CONFIG` environment variable is used when the flag is absent.
+//! Other arguments are passed through to the daemon untouched.
+
+use crate::config::error::{ConfigError, ConfigResult};
+use crate::config::types::{ResilientConfig, ENV_PREFIX};
+use std::path::PathBuf;
+
+const FLAG: &str = "--config";
+
+/// Command-line arguments with the config file picked out
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfigArgs {
+    /// Config file to load, if any
+    pub path: Option<PathBuf>,
+    /// Remaining arguments, in order
+    pub rest: Vec<String>,
+}
+
+impl ConfigArgs {
+    /// Split `--config` out of `args` (without the program name)
+    pub fn parse(args: impl IntoIterator<Item = String>) -> ConfigResult<Self> {
+        let mut parsed = Self::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let path = if arg == FLAG {
+                args.next()
+            } else if let Some(path) = arg.strip_prefix("--config=") {
+                Some(path.to_string())
+            } else {
+                parsed.rest.push(arg);
+                continue;
+            };
+            match path {
+                Some(path) if !path.is_empty() => parsed.path = Some(PathBuf::from(path)),
+                _ => return Err(ConfigError::invalid(FLAG, "expects a file path")),
+            }
+        }
+        Ok(parsed)
+    }
+
+    /// Arguments of this process, falling back to `RESILIENT_CONFIG`
+    pub fn from_env() -> ConfigResult<Self> {
+        let mut parsed = Self::parse(std::env::args().skip(1))?;
+        if parsed.path.is_none() {
+            parsed.path = std::env::var(format!("{ENV_PREFIX}CONFIG"))
+                .ok()
+                .filter(|path| !path.is_empty())
+                .map(PathBuf::from);
+        }
+        Ok(parsed)
+    }
+
+    /// Load the named file (or the defaults), apply environment overrides,
+    /// and validate
+    pub fn load(&self) -> ConfigResult<ResilientConfig> {
+        ResilientConfig::load(self.path.as_deref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn test_config_flag_is_split_out() {
+        let parsed =
+            ConfigArgs::parse(args(&["0.0.0.0:5001", "--config", "a.toml", "out"])).unwrap();
+        assert_eq!(parsed.path, Some(PathBuf::from("a.toml")));
+        assert_eq!(parsed.rest, ["0.0.0.0:5001", "out"]);
+
+        let parsed = ConfigArgs::parse(args(&["--config=/etc/resilient.toml"])).unwrap();
+        assert_eq!(parsed.path, Some(PathBuf::from("/etc/resilient.toml")));
+        assert!(parsed.rest.is_empty());
+
+        assert_eq!(ConfigArgs::parse(args(&[])).unwrap(), ConfigArgs::default());
+        assert!(ConfigArgs::parse(args(&["--config"])).is_err());
+        assert!(ConfigArgs::parse(args(&["--config="])).is_err());
+    }
+}
@@ -0,0 +1,517 @@
+use crate::chunk::autotune::{self, AutotuneConfig, AutotuneReport};
+use crate::chunk::{ChunkManager, HintProvider, MagicHints, ParityCache, Priority};
+use crate::config::error::{ConfigError, ConfigResult};
+use crate::config::types::{AutotuneSettings, ResilientConfig};
+use crate::coordinator::{DuplicatePolicy, TransferCoordinator};
+use crate::failover::{FailoverRole, ReplicatingRepository, ReplicationLog};
+use crate::integrity::ChecksumType;
+use crate::network::{ConnectionConfig, QuicTransport};
+use crate::priority::PriorityQueue;
+#[cfg(feature = "sqlite")]
+use crate::session::SessionStore;
+use crate::session::{
+    MemoryRepository, SessionBackend, SessionRepository, WriteBehindPolicy, WriteBehindRepository,
+};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Validates a [`ResilientConfig`] and assembles a [`TransferCoordinator`]
+///
+/// ```no_run
+/// # async fn run() -> chunkstream_pro::config::ConfigResult<()> {
+/// use chunkstream_pro::CoordinatorBuilder;
+///
+/// let coordinator = CoordinatorBuilder::new()
+///     .chunk_size(256 * 1024)
+///     .shards(10, 3)
+///     .db_path("sessions.db")
+///     .build()
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CoordinatorBuilder {
+    config: ResilientConfig,
+    /// Bound UDP socket for the QUIC endpoint, instead of `bind_addr`
+    quic_socket: Option<Arc<std::net::UdpSocket>>,
+}
+
+impl CoordinatorBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_config(config: ResilientConfig) -> Self {
+        Self {
+            config,
+            quic_socket: None,
+        }
+    }
+
+    pub fn chunk_size(mut self, bytes: usize) -> Self {
+        self.config.chunk.chunk_size = bytes;
+        self
+    }
+
+    pub fn shards(mut self, data: usize, parity: usize) -> Self {
+        self.config.chunk.data_shards = data;
+        self.config.chunk.parity_shards = parity;
+        self
+    }
+
+    pub fn preserve_attributes(mut self, preserve: bool) -> Self {
+        self.config.chunk.preserve_attributes = preserve;
+        self
+    }
+
+    pub fn write_concurrency(mut self, concurrency: usize) -> Self {
+        self.config.chunk.write_concurrency = concurrency;
+        self
+    }
+
+    pub fn decode_workers(mut self, workers: usize) -> Self {
+        self.config.chunk.decode_workers = workers;
+        self
+    }
+
+    pub fn checksum_algorithm(mut self, algorithm: ChecksumType) -> Self {
+        self.config.chunk.checksum_algorithm = algorithm;
+        self
+    }
+
+    pub fn queue_capacity(mut self, capacity: usize) -> Self {
+        self.config.queue.capacity = capacity;
+        self
+    }
+
+    /// Most chunk data queued at once (0 = no limit)
+    pub fn queue_byte_budget(mut self, bytes: u64) -> Self {
+        self.config.queue.max_bytes = bytes;
+        self
+    }
+
+    /// Shed Normal-priority chunks as process RSS nears `bytes` (0 = off)
+    pub fn rss_limit(mut self, bytes: u64) -> Self {
+        self.config.queue.rss_limit_bytes = bytes;
+        self
+    }
+
+    /// Chunks one transfer may have queued at once (0 = the whole file)
+    pub fn session_window(mut self, chunks: usize) -> Self {
+        self.config.queue.session_window = chunks;
+        self
+    }
+
+    /// Alert when a priority class waits longer than `threshold` (zero = off)
+    pub fn starvation_threshold(mut self, threshold: Duration) -> Self {
+        self.config.queue.starvation_threshold_secs = threshold.as_secs();
+        self
+    }
+
+    /// Deliver Critical chunks within `target` of enqueueing them (zero = no
+    /// target)
+    pub fn critical_latency_target(mut self, target: Duration) -> Self {
+        self.config.queue.critical_latency_target_ms = target.as_millis() as u64;
+        self
+    }
+
+    pub fn db_path(mut self, path: impl Into<String>) -> Self {
+        self.config.session.db_path = path.into();
+        self
+    }
+
+    /// Keep sessions in SQLite or, for runs that need nothing to outlive
+    /// them, in process memory
+    pub fn session_backend(mut self, backend: SessionBackend) -> Self {
+        self.config.session.backend = backend;
+        self
+    }
+
+    pub fn bind_addr(mut self, addr: SocketAddr) -> Self {
+        self.config.network.bind_addr = addr;
+        self
+    }
+
+    /// Serve QUIC on `socket`, already bound, such as one from systemd
+    /// socket activation; `bind_addr` is then ignored
+    pub fn quic_socket(mut self, socket: std::net::UdpSocket) -> Self {
+        self.quic_socket = Some(Arc::new(socket));
+        self
+    }
+
+    pub fn client_bind_addr(mut self, addr: Option<SocketAddr>) -> Self {
+        self.config.network.client_bind_addr = addr;
+        self
+    }
+
+    pub fn max_recent_transfers(mut self, max: usize) -> Self {
+        self.config.retention.max_recent_transfers = max;
+        self
+    }
+
+    pub fn max_concurrent_transfers(mut self, max: usize) -> Self {
+        self.config.admission.max_concurrent_transfers = max;
+        self
+    }
+
+    pub fn duplicate_policy(mut self, policy: DuplicatePolicy) -> Self {
+        self.config.admission.duplicate_policy = policy;
+        self
+    }
+
+    /// Benchmark erasure coding at startup and cap parity to what this host
+    /// sustains
+    pub fn erasure_autotune(mut self, enabled: bool) -> Self {
+        self.config.autotune.enabled = enabled;
+        self
+    }
+
+    /// Cap parity at `percent` extra bytes over the data (`None` = no cap)
+    pub fn max_overhead_percent(mut self, percent: Option<u32>) -> Self {
+        self.config.chunk.max_overhead_percent = percent;
+        self
+    }
+
+    pub fn insecure_skip_verify(mut self, insecure: bool) -> Self {
+        self.config.network.insecure_skip_verify = insecure;
+        self
+    }
+
+    /// Shards resent per FEC group when the receiver can't decode it
+    /// (0 = never resend)
+    pub fn retransmit_budget(mut self, shards: u32) -> Self {
+        self.config.retransmit.budget_per_group = shards;
+        self
+    }
+
+    /// Extra attempts for a chunk whose sends failed, once the rest of the
+    /// transfer has gone out (0 = leave failed chunks to parity)
+    pub fn failed_chunk_retries(mut self, retries: u32) -> Self {
+        self.config.retransmit.failed_chunk_retries = retries;
+        self
+    }
+
+    /// Attempts beyond the first for a send retried on the spot, and
+    /// whether chunks are instead sent once while parity has headroom
+    pub fn in_flight_retries(mut self, retries: u32, adaptive: bool) -> Self {
+        self.config.retransmit.in_flight_retries = retries;
+        self.config.retransmit.adaptive_retries = adaptive;
+        self
+    }
+
+    /// Hold chunk progress in memory and write it to the session database
+    /// at least every `max_lag`, instead of once per chunk
+    pub fn session_write_behind(mut self, max_lag: Duration) -> Self {
+        self.config.session.write_behind.enabled = true;
+        self.config.session.write_behind.max_lag_ms = max_lag.as_millis() as u64;
+        self
+    }
+
+    /// Shared secret for signing and checking resume tokens
+    pub fn resume_token_secret(mut self, secret: Option<String>) -> Self {
+        self.config.network.resume_token_secret = secret;
+        self
+    }
+
+    /// Run as the active of a pair, serving session changes to standbys on
+    /// `addr`
+    pub fn replication_addr(mut self, addr: SocketAddr) -> Self {
+        self.config.failover.role = FailoverRole::Active;
+        self.config.failover.replication_addr = Some(addr);
+        self
+    }
+
+    /// Run as a standby of the active replicating on `active_addr`, taking
+    /// no transfers until promoted
+    pub fn standby_of(mut self, active_addr: SocketAddr) -> Self {
+        self.config.failover.role = FailoverRole::Standby;
+        self.config.failover.active_addr = Some(active_addr);
+        self
+    }
+
+    /// The configuration that `build` will use
+    pub fn config(&self) -> &ResilientConfig {
+        &self.config
+    }
+
+    /// Validate the configuration and construct every subsystem
+    pub async fn build(self) -> ConfigResult<TransferCoordinator> {
+        let config = self.config;
+        config.validate()?;
+
+        let parity_cache = match &config.chunk.parity_cache_dir {
+            Some(dir) => Some(Arc::new(ParityCache::open(
+                dir,
+                config.chunk.parity_cache_max_bytes,
+            )?)),
+            None => None,
+        };
+
+        let chunk_manager = ChunkManager::new(
+            config.chunk.chunk_size,
+            config.chunk.data_shards,
+            config.chunk.parity_shards,
+        )?
+        .with_preserve_attributes(config.chunk.preserve_attributes)
+        .with_write_concurrency(config.chunk.write_concurrency)
+        .with_decode_workers(config.chunk.decode_workers)
+        .with_write_policy(config.chunk.write_policy)
+        .with_checksum_algorithm(config.chunk.checksum_algorithm)
+        .with_erasure_profiles(config.chunk.erasure_profiles)
+        .with_overhead_budget(config.chunk.overhead_budget())
+        .with_hint_provider(
+            config
+                .chunk
+                .content_hints
+                .then(|| Arc::new(MagicHints) as Arc<dyn HintProvider>),
+        )
+        .with_parity_cache(parity_cache)
+        .with_compression(config.chunk.compression);
+        let transport = match self.quic_socket {
+            Some(socket) => {
+                QuicTransport::with_socket(config.network.connection_config(), socket.try_clone()?)
+                    .await?
+            }
+            None => QuicTransport::new(config.network.connection_config()).await?,
+        };
+        let mut queue = PriorityQueue::new(config.queue.capacity)
+            .with_levels(config.queue.levels)
+            .with_byte_budget(config.queue.max_bytes);
+        for level in 0..queue.levels() {
+            let priority = Priority::from_level(level as u8);
+            queue = queue.with_latency_target(priority, config.queue.latency_target(priority));
+        }
+        if let Some(monitor) = config.queue.memory_monitor() {
+            queue = queue.with_memory_monitor(monitor);
+        }
+        // An active logs every session write for its standbys, before any
+        // write-behind cache, so they see chunk progress as it happens
+        let replication_log = (config.failover.role == FailoverRole::Active)
+            .then(|| Arc::new(ReplicationLog::default()));
+        let write_behind = config.session.write_behind.policy();
+        let coordinator = match config.session.backend {
+            #[cfg(feature = "sqlite")]
+            SessionBackend::Sqlite => {
+                let session_store = SessionStore::with_options(
+                    &config.session.database_url(),
+                    config.session.store_options(),
+                )
+                .await?;
+                coordinator_with(
+                    chunk_manager,
+                    transport,
+                    queue,
+                    session_store,
+                    replication_log.as_ref(),
+                    write_behind,
+                )
+            }
+            #[cfg(not(feature = "sqlite"))]
+            SessionBackend::Sqlite => {
+                return Err(ConfigError::invalid(
+                    "session.backend",
+                    "this build has no SQLite support",
+                ))
+            }
+            SessionBackend::Memory => coordinator_with(
+                chunk_manager,
+                transport,
+                queue,
+                MemoryRepository::new(),
+                replication_log.as_ref(),
+                write_behind,
+            ),
+        };
+        coordinator.set_retention(config.retention.policy());
+        coordinator.set_maintenance_policy(config.session.maintenance.policy());
+        coordinator
+            .set_catalog_shares(config.catalog.shares.clone())
+            .map_err(|e| ConfigError::invalid("catalog.shares", e.to_string()))?;
+        coordinator.set_max_concurrent_transfers(config.admission.max_concurrent_transfers);
+        coordinator.set_duplicate_policy(config.admission.duplicate_policy);
+        coordinator.set_max_stream_bytes(config.admission.max_stream_bytes);
+        coordinator.set_session_window(config.queue.session_window);
+        coordinator.set_source_watch(config.chunk.source_watch());
+        coordinator.set_starvation_policy(config.queue.starvation_policy());
+        coordinator.set_resume_token_secret(config.network.resume_token_secret.as_deref());
+        coordinator.set_retransmit_policy(config.retransmit.policy());
+        coordinator.set_connect_policy(config.network.connect_policy());
+        coordinator
+            .stats()
+            .set_health_policy(config.health.policy(&config.session));
+        coordinator
+            .adaptive_coder()
+            .set_overhead_budget(config.chunk.overhead_budget());
+        if config.autotune.enabled {
+            let report = autotune_report(&config.autotune, config.chunk.chunk_size).await?;
+            coordinator.adaptive_coder().apply_autotune(
+                &report,
+                config.chunk.chunk_size,
+                config.autotune.min_bytes_per_sec,
+            );
+        }
+
+        let failover = &config.failover;
+        match (failover.role, replication_log) {
+            (FailoverRole::Active, Some(log)) => {
+                let replication = ConnectionConfig {
+                    // Checked by validate
+                    bind_addr: failover.replication_addr.unwrap(),
+                    ..config.network.connection_config()
+                };
+                coordinator
+                    .serve_replication(replication, log)
+                    .await
+                    .map_err(|e| {
+                        ConfigError::invalid("failover.replication_addr", e.to_string())
+                    })?;
+            }
+            (FailoverRole::Standby, _) => {
+                coordinator
+                    .follow_active(failover.active_addr.unwrap(), failover.reconnect_delay())
+                    .map_err(|e| ConfigError::invalid("failover.role", e.to_string()))?;
+            }
+            _ => {}
+        }
+        Ok(coordinator)
+    }
+}
+
+/// Coordinator over `repo`, wrapped for replication and write-behind as
+/// configured
+fn coordinator_with<R: SessionRepository + 'static>(
+    chunk_manager: ChunkManager,
+    transport: QuicTransport,
+    queue: PriorityQueue,
+    repo: R,
+    replication_log: Option<&Arc<ReplicationLog>>,
+    write_behind: Option<WriteBehindPolicy>,
+) -> TransferCoordinator {
+    match (replication_log, write_behind) {
+        (Some(log), Some(policy)) => TransferCoordinator::new(
+            chunk_manager,
+            transport,
+            queue,
+            ReplicatingRepository::new(WriteBehindRepository::new(repo, policy), log.clone()),
+        ),
+        (Some(log), None) => TransferCoordinator::new(
+            chunk_manager,
+            transport,
+            queue,
+            ReplicatingRepository::new(repo, log.clone()),
+        ),
+        (None, Some(policy)) => TransferCoordinator::new(
+            chunk_manager,
+            transport,
+            queue,
+            WriteBehindRepository::new(repo, policy),
+        ),
+        (None, None) => TransferCoordinator::new(chunk_manager, transport, queue, repo),
+    }
+}
+
+/// Saved autotune results, or a fresh benchmark at `chunk_size`
+async fn autotune_report(
+    settings: &AutotuneSettings,
+    chunk_size: usize,
+) -> ConfigResult<AutotuneReport> {
+    if let Some(path) = settings.results_path.as_deref().filter(|p| p.exists()) {
+        match AutotuneReport::load(path) {
+            Ok(report) => return Ok(report),
+            Err(e) => tracing::warn!("Ignoring saved autotune results: {}", e),
+        }
+    }
+
+    let config = AutotuneConfig {
+        chunk_sizes: vec![chunk_size],
+        ..Default::default()
+    };
+    let report = tokio::task::spawn_blocking(move || autotune::run(&config))
+        .await
+        .map_err(std::io::Error::other)??;
+    if let Some(path) = &settings.results_path {
+        report.save(path)?;
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ConfigError;
+
+    #[tokio::test]
+    async fn test_build_coordinator() {
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        let coordinator = CoordinatorBuilder::new()
+            .chunk_size(256 * 1024)
+            .shards(10, 3)
+            .queue_capacity(1000)
+            .max_overhead_percent(Some(30))
+            .bind_addr("127.0.0.1:0".parse().unwrap())
+            .build()
+            .await
+            .unwrap();
+
+        assert!(coordinator.list_active().is_empty());
+        assert_eq!(coordinator.adaptive_coder().overhead_budget(), Some(0.3));
+    }
+
+    #[tokio::test]
+    async fn test_build_with_memory_sessions_opens_no_database() {
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("sessions.db");
+        let coordinator = CoordinatorBuilder::new()
+            .db_path(db_path.display().to_string())
+            .session_backend(SessionBackend::Memory)
+            .bind_addr("127.0.0.1:0".parse().unwrap())
+            .build()
+            .await
+            .unwrap();
+
+        coordinator
+            .save_profile(crate::session::TransferProfile::new("field"))
+            .await
+            .unwrap();
+        assert_eq!(coordinator.list_profiles().await.unwrap().len(), 1);
+        assert!(!db_path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_build_rejects_invalid_config() {
+        let result = CoordinatorBuilder::new().shards(0, 3).build().await;
+        assert!(matches!(result, Err(ConfigError::Invalid { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_build_with_saved_autotune_results() {
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("autotune.json");
+        AutotuneReport {
+            results: vec![crate::chunk::ErasureBenchmark {
+                data_shards: 50,
+                parity_shards: 10,
+                chunk_size: 64 * 1024,
+                encode_bytes_per_sec: u64::MAX,
+                decode_bytes_per_sec: u64::MAX,
+            }],
+            measured_at: 0,
+        }
+        .save(&path)
+        .unwrap();
+
+        let mut builder = CoordinatorBuilder::new()
+            .chunk_size(64 * 1024)
+            .shards(10, 3)
+            .bind_addr("127.0.0.1:0".parse().unwrap())
+            .erasure_autotune(true);
+        builder.config.autotune.results_path = Some(path);
+        let coordinator = builder.build().await.unwrap();
+
+        assert_eq!(coordinator.adaptive_coder().parity_cap(), 10);
+    }
+}
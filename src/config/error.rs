@@ -0,0 +1,49 @@
+use std::path::PathBuf;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    #[error("Invalid config value for {field}: {reason}")]
+    Invalid { field: &'static str, reason: String },
+
+    #[error("Invalid environment variable {var}: {reason}")]
+    Env { var: String, reason: String },
+
+    #[error("Failed to parse config file: {0}")]
+    Parse(#[from] toml::de::Error),
+
+    #[error("Invalid config file {}: {source}", path.display())]
+    File {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+
+    #[error("Failed to read config file {}: {source}", path.display())]
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Chunk manager error: {0}")]
+    Chunk(#[from] crate::chunk::ChunkError),
+
+    #[error("Network error: {0}")]
+    Network(#[from] crate::network::NetworkError),
+
+    #[error("Session store error: {0}")]
+    Session(#[from] crate::session::SessionError),
+}
+
+impl ConfigError {
+    pub(crate) fn invalid(field: &'static str, reason: impl Into<String>) -> Self {
+        ConfigError::Invalid {
+            field,
+            reason: reason.into(),
+        }
+    }
+}
+
+pub type ConfigResult<T> = Result<T, ConfigError>;
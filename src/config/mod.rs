@@ -0,0 +1,22 @@
+//! Top-level configuration and coordinator construction
+//!
+//! [`ResilientConfig`] gathers the settings of every subsystem a
+//! [`TransferCoordinator`](crate::coordinator::TransferCoordinator) needs and
+//! can be loaded from TOML and `RESILIENT_*` environment variables; the
+//! daemons take the file from a `--config` flag via [`ConfigArgs`].
+//! [`CoordinatorBuilder`] validates it and assembles a ready coordinator.
+
+pub mod args;
+pub mod builder;
+pub mod error;
+pub mod types;
+
+pub use args::ConfigArgs;
+pub use builder::CoordinatorBuilder;
+pub use error::{ConfigError, ConfigResult};
+pub use types::{
+    AdmissionConfig, ApiConfig, AutotuneSettings, CatalogConfig, ChunkConfig, HealthConfig,
+    MaintenanceConfig, MetricsSettings, NetworkSettings, QueueConfig, ReceiverConfig,
+    RelayPeerConfig, RelaySettings, ResilientConfig, RetentionConfig, RetransmitConfig,
+    SessionConfig,
+};
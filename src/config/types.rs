@@ -0,0 +1,2262 @@
+use crate::chunk::erasure::MAX_TOTAL_SHARDS;
+use crate::chunk::{
+    CompressionMode, ErasureProfiles, Priority, ReorderConfig, SourceWatch, WritePolicy,
+};
+use crate::config::error::{ConfigError, ConfigResult};
+use crate::coordinator::{
+    CatalogShare, ConnectPolicy, DuplicatePolicy, HealthPolicy, MaintenancePolicy, RetentionPolicy,
+    RetransmitPolicy, DEFAULT_MAX_STREAM_BYTES, DEFAULT_SESSION_WINDOW,
+};
+use crate::failover::{FailoverConfig, FailoverRole};
+use crate::integrity::ChecksumType;
+use crate::logging::{LogConfig, LogFilter};
+use crate::metrics::{MetricsConfig, SamplingConfig};
+use crate::network::{
+    CaptureConfig, ConnectionConfig, FlowControlConfig, PacerConfig, PeerShapingConfig,
+    QuicTransport,
+};
+use crate::priority::{
+    AlertSink, MemoryMonitor, StarvationPolicy, DEFAULT_PRIORITY_LEVELS, DEFAULT_SHED_WATERMARK,
+    MAX_PRIORITY_LEVELS,
+};
+use crate::relay::identity::{AccessPolicy, NodePublicKey};
+use crate::relay::types::{
+    DestinationQuotas, FloodPolicy, ForwardingPolicy, PeerInfo, QuotaBreach, RelayConfig,
+};
+use crate::session::{
+    JournalMode, SessionBackend, SessionStoreOptions, SynchronousLevel, WriteBehindPolicy,
+};
+use crate::sync::FileRetentionPolicy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Prefix of the environment variables read by [`ResilientConfig::apply_env`]
+pub const ENV_PREFIX: &str = "RESILIENT_";
+
+/// Complete configuration for a transfer coordinator
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ResilientConfig {
+    pub chunk: ChunkConfig,
+    pub queue: QueueConfig,
+    pub session: SessionConfig,
+    pub network: NetworkSettings,
+    pub retention: RetentionConfig,
+    pub admission: AdmissionConfig,
+    pub autotune: AutotuneSettings,
+    pub retransmit: RetransmitConfig,
+    pub api: ApiConfig,
+    pub metrics: MetricsSettings,
+    pub health: HealthConfig,
+    pub relay: RelaySettings,
+    pub receiver: ReceiverConfig,
+    pub catalog: CatalogConfig,
+    pub logging: LogConfig,
+    pub failover: FailoverConfig,
+}
+
+/// Chunking and erasure coding
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ChunkConfig {
+    /// Bytes per data chunk
+    pub chunk_size: usize,
+    pub data_shards: usize,
+    pub parity_shards: usize,
+    /// Carry mode, mtime and symlink targets to the receiver
+    pub preserve_attributes: bool,
+    /// Out-of-order chunks a receiver holds in memory per transfer
+    pub reorder_window: usize,
+    /// Consecutive chunks a receiver flushes to disk together
+    pub reorder_group_size: u32,
+    /// Positioned writes in flight while a receiver rebuilds a file; 1
+    /// writes it front to back
+    pub write_concurrency: usize,
+    /// Threads decoding one group while a receiver rebuilds a file; 0 uses
+    /// one per core
+    pub decode_workers: usize,
+    /// Rate limit, periodic fsync and O_DIRECT for rebuilt files, for
+    /// receivers on SD cards and other slow flash
+    pub write_policy: WritePolicy,
+    /// Algorithm for chunk and file checksums on new transfers
+    pub checksum_algorithm: ChecksumType,
+    /// Most parity bytes sent, as a percentage of data bytes; adaptive
+    /// parity is clamped to fit even when loss calls for more
+    pub max_overhead_percent: Option<u32>,
+    /// Extra parity per priority, and whether the overhead budget binds it
+    pub erasure_profiles: ErasureProfiles,
+    /// Send the parts of recognized file types that make a partial file
+    /// usable (MP4 `moov`, ZIP central directory, PDF trailer) first
+    pub content_hints: bool,
+    /// Where parity of files sent before is kept for reuse (unset = not
+    /// kept)
+    pub parity_cache_dir: Option<PathBuf>,
+    /// Bytes of parity kept before the least recently used is dropped
+    pub parity_cache_max_bytes: u64,
+    /// How often a file being sent is checked for changes, failing its
+    /// transfer if it has changed (ms, 0 = never)
+    pub source_check_interval_ms: u64,
+    /// Hold a shared OS lock on files while they are sent, so writers that
+    /// lock them wait (advisory on Unix)
+    pub lock_source: bool,
+    /// Compress files whole before chunking them, so chunks are a uniform
+    /// size on the wire
+    pub compression: CompressionMode,
+}
+
+impl Default for ChunkConfig {
+    fn default() -> Self {
+        // 512KB chunks with 50 data + 10 parity = supports up to 25MB files
+        Self {
+            chunk_size: 512 * 1024,
+            data_shards: 50,
+            parity_shards: 10,
+            preserve_attributes: true,
+            reorder_window: 256,
+            reorder_group_size: 16,
+            write_concurrency: 1,
+            decode_workers: 0,
+            write_policy: WritePolicy::default(),
+            checksum_algorithm: ChecksumType::Blake3,
+            max_overhead_percent: None,
+            erasure_profiles: ErasureProfiles::default(),
+            content_hints: true,
+            parity_cache_dir: None,
+            parity_cache_max_bytes: 1024 * 1024 * 1024,
+            source_check_interval_ms: 1000,
+            lock_source: false,
+            compression: CompressionMode::None,
+        }
+    }
+}
+
+impl ChunkConfig {
+    /// `max_overhead_percent` as parity bytes per data byte
+    pub fn overhead_budget(&self) -> Option<f64> {
+        self.max_overhead_percent
+            .map(|percent| percent as f64 / 100.0)
+    }
+
+    pub fn source_watch(&self) -> SourceWatch {
+        SourceWatch {
+            check_interval: (self.source_check_interval_ms > 0)
+                .then(|| Duration::from_millis(self.source_check_interval_ms)),
+            lock: self.lock_source,
+        }
+    }
+
+    pub fn reorder_config(&self) -> ReorderConfig {
+        ReorderConfig {
+            window: self.reorder_window,
+            group_size: self.reorder_group_size,
+        }
+    }
+}
+
+/// Priority queue sizing and starvation alerts
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct QueueConfig {
+    /// Maximum chunks queued across all priorities
+    pub capacity: usize,
+    /// Maximum chunk data queued across all priorities (bytes, 0 = no limit)
+    pub max_bytes: u64,
+    /// Priority levels, at least 3; Critical, High and Normal are levels 0
+    /// to 2 and further levels queue after Normal
+    pub levels: usize,
+    /// Process RSS the queue tries to stay under (bytes, 0 = unmonitored)
+    pub rss_limit_bytes: u64,
+    /// Fraction of the RSS limit above which Normal chunks are shed
+    pub shed_watermark: f64,
+    /// Chunks one transfer may have queued at once (0 = the whole file)
+    pub session_window: usize,
+    /// Alert when a class's oldest chunk waits longer than this (0 = off)
+    pub starvation_threshold_secs: u64,
+    pub starvation_check_interval_secs: u64,
+    /// Where alerts go, e.g. `["log", { webhook = "http://host/path" }]`
+    pub starvation_sinks: Vec<AlertSink>,
+    /// Enqueue-to-delivery target per class (ms, 0 = none); late Critical
+    /// chunks raise an SLO violation event
+    pub critical_latency_target_ms: u64,
+    pub high_latency_target_ms: u64,
+    pub normal_latency_target_ms: u64,
+}
+
+impl Default for QueueConfig {
+    fn default() -> Self {
+        let alerts = StarvationPolicy::default();
+        Self {
+            capacity: 1_000_000,
+            max_bytes: 0,
+            levels: DEFAULT_PRIORITY_LEVELS,
+            rss_limit_bytes: 0,
+            shed_watermark: DEFAULT_SHED_WATERMARK,
+            session_window: DEFAULT_SESSION_WINDOW,
+            starvation_threshold_secs: 0,
+            starvation_check_interval_secs: alerts.check_interval.as_secs(),
+            starvation_sinks: alerts.sinks,
+            critical_latency_target_ms: 0,
+            high_latency_target_ms: 0,
+            normal_latency_target_ms: 0,
+        }
+    }
+}
+
+impl QueueConfig {
+    /// Starvation alert policy, or `None` when alerts are off
+    pub fn starvation_policy(&self) -> Option<StarvationPolicy> {
+        (self.starvation_threshold_secs > 0).then(|| StarvationPolicy {
+            threshold: Duration::from_secs(self.starvation_threshold_secs),
+            check_interval: Duration::from_secs(self.starvation_check_interval_secs),
+            sinks: self.starvation_sinks.clone(),
+        })
+    }
+
+    /// Delivery latency target for `priority`, or `None` when it has none
+    ///
+    /// Levels past Normal share its target.
+    pub fn latency_target(&self, priority: Priority) -> Option<Duration> {
+        let ms = match priority.class() {
+            Priority::Critical => self.critical_latency_target_ms,
+            Priority::High => self.high_latency_target_ms,
+            Priority::Normal | Priority::Level(_) => self.normal_latency_target_ms,
+        };
+        (ms > 0).then(|| Duration::from_millis(ms))
+    }
+
+    /// RSS monitor for the queue, or `None` when no limit is set
+    pub fn memory_monitor(&self) -> Option<MemoryMonitor> {
+        (self.rss_limit_bytes > 0)
+            .then(|| MemoryMonitor::new(self.rss_limit_bytes, self.shed_watermark))
+    }
+}
+
+/// Session persistence
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SessionConfig {
+    /// `memory` keeps sessions in the process without SQLite, for runs
+    /// that need nothing to outlive them; the settings below are then unused
+    pub backend: SessionBackend,
+    /// SQLite database: a file path, a `sqlite:` URL, or `:memory:`
+    pub db_path: String,
+    pub journal_mode: JournalMode,
+    pub synchronous: SynchronousLevel,
+    /// How long a write waits for the database lock before failing
+    pub busy_timeout_ms: u64,
+    pub max_connections: u32,
+    pub maintenance: MaintenanceConfig,
+    pub write_behind: WriteBehindConfig,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        let store = SessionStoreOptions::default();
+        Self {
+            backend: SessionBackend::default(),
+            db_path: ":memory:".into(),
+            journal_mode: store.journal_mode,
+            synchronous: store.synchronous,
+            busy_timeout_ms: store.busy_timeout.as_millis() as u64,
+            max_connections: store.max_connections,
+            maintenance: MaintenanceConfig::default(),
+            write_behind: WriteBehindConfig::default(),
+        }
+    }
+}
+
+impl SessionConfig {
+    /// Whether sessions live only in memory
+    pub fn is_in_memory(&self) -> bool {
+        self.backend == SessionBackend::Memory
+            || matches!(self.db_path.as_str(), ":memory:" | "sqlite::memory:")
+    }
+
+    /// Connection URL for the session store, creating the file if needed
+    pub fn database_url(&self) -> String {
+        if self.is_in_memory() {
+            "sqlite::memory:".into()
+        } else if self.db_path.starts_with("sqlite:") {
+            self.db_path.clone()
+        } else {
+            format!("sqlite://{}?mode=rwc", self.db_path)
+        }
+    }
+
+    /// Session store connection settings
+    pub fn store_options(&self) -> SessionStoreOptions {
+        SessionStoreOptions {
+            journal_mode: self.journal_mode,
+            synchronous: self.synchronous,
+            busy_timeout: Duration::from_millis(self.busy_timeout_ms),
+            max_connections: self.max_connections,
+        }
+    }
+}
+
+/// Periodic vacuum and analyze of the session database
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MaintenanceConfig {
+    pub enabled: bool,
+    pub interval_secs: u64,
+    /// Free pages reclaimed per pass; 0 reclaims all of them
+    pub vacuum_pages: u32,
+    pub analyze: bool,
+}
+
+impl Default for MaintenanceConfig {
+    fn default() -> Self {
+        let defaults = MaintenancePolicy::default();
+        Self {
+            enabled: defaults.enabled,
+            interval_secs: defaults.interval.as_secs(),
+            vacuum_pages: defaults.vacuum_pages,
+            analyze: defaults.analyze,
+        }
+    }
+}
+
+impl MaintenanceConfig {
+    /// Coordinator maintenance policy for these settings
+    pub fn policy(&self) -> MaintenancePolicy {
+        MaintenancePolicy {
+            enabled: self.enabled,
+            interval: Duration::from_secs(self.interval_secs),
+            vacuum_pages: self.vacuum_pages,
+            analyze: self.analyze,
+        }
+    }
+}
+
+/// Chunk progress cached in memory and written to the database behind the
+/// transfer
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WriteBehindConfig {
+    /// Off writes every chunk update before the transfer moves on
+    pub enabled: bool,
+    /// Chunk progress a crash may lose
+    pub max_lag_ms: u64,
+    /// Changed sessions that are written without waiting for `max_lag_ms`
+    pub max_dirty: usize,
+}
+
+impl Default for WriteBehindConfig {
+    fn default() -> Self {
+        let defaults = WriteBehindPolicy::default();
+        Self {
+            enabled: false,
+            max_lag_ms: defaults.max_lag.as_millis() as u64,
+            max_dirty: defaults.max_dirty,
+        }
+    }
+}
+
+impl WriteBehindConfig {
+    /// Write-behind policy, if enabled
+    pub fn policy(&self) -> Option<WriteBehindPolicy> {
+        self.enabled.then(|| WriteBehindPolicy {
+            max_lag: Duration::from_millis(self.max_lag_ms),
+            max_dirty: self.max_dirty,
+        })
+    }
+}
+
+/// Directories receivers may pull files from
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CatalogConfig {
+    pub shares: Vec<CatalogShare>,
+}
+
+/// How long finished transfers stay in the coordinator's memory
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetentionConfig {
+    pub max_recent_transfers: usize,
+    pub max_age_secs: u64,
+    pub sweep_interval_secs: u64,
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        let defaults = RetentionPolicy::default();
+        Self {
+            max_recent_transfers: defaults.max_entries,
+            max_age_secs: defaults.max_age.as_secs(),
+            sweep_interval_secs: defaults.sweep_interval.as_secs(),
+        }
+    }
+}
+
+impl RetentionConfig {
+    /// Coordinator retention policy for these settings
+    pub fn policy(&self) -> RetentionPolicy {
+        RetentionPolicy {
+            max_entries: self.max_recent_transfers,
+            max_age: Duration::from_secs(self.max_age_secs),
+            sweep_interval: Duration::from_secs(self.sweep_interval_secs),
+        }
+    }
+}
+
+/// Limits on how many transfers run at once, and on what they take in
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AdmissionConfig {
+    /// Transfers beyond this wait in a pending queue (0 = unlimited)
+    pub max_concurrent_transfers: usize,
+    /// Which running transfers refuse another of the same file
+    pub duplicate_policy: DuplicatePolicy,
+    /// Longest stream a transfer is sent from, in bytes; streams are read
+    /// into memory whole (0 = unlimited)
+    pub max_stream_bytes: u64,
+}
+
+impl Default for AdmissionConfig {
+    fn default() -> Self {
+        Self {
+            max_concurrent_transfers: 0,
+            duplicate_policy: DuplicatePolicy::default(),
+            max_stream_bytes: DEFAULT_MAX_STREAM_BYTES,
+        }
+    }
+}
+
+/// Resending shards the receiver reports lost beyond what parity covers
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetransmitConfig {
+    /// Shards resent per FEC group (0 = never resend)
+    pub budget_per_group: u32,
+    /// Wait for the receiver's group report after the last chunk
+    pub feedback_timeout_ms: u64,
+    /// Extra attempts for a chunk whose sends failed (0 = leave to parity)
+    pub failed_chunk_retries: u32,
+    /// Wait before the first retry pass; doubles each pass
+    pub retry_backoff_ms: u64,
+    pub max_retry_backoff_ms: u64,
+    /// Attempts beyond the first for a send retried on the spot
+    pub in_flight_retries: u32,
+    /// Send once and leave failures to parity while it has headroom
+    pub adaptive_retries: bool,
+}
+
+impl Default for RetransmitConfig {
+    fn default() -> Self {
+        let defaults = RetransmitPolicy::default();
+        Self {
+            budget_per_group: defaults.budget_per_group,
+            feedback_timeout_ms: defaults.feedback_timeout.as_millis() as u64,
+            failed_chunk_retries: defaults.failed_chunk_retries,
+            retry_backoff_ms: defaults.retry_backoff.as_millis() as u64,
+            max_retry_backoff_ms: defaults.max_retry_backoff.as_millis() as u64,
+            in_flight_retries: defaults.in_flight_retries,
+            adaptive_retries: defaults.adaptive_retries,
+        }
+    }
+}
+
+impl RetransmitConfig {
+    /// Coordinator retransmission policy for these settings
+    pub fn policy(&self) -> RetransmitPolicy {
+        RetransmitPolicy {
+            budget_per_group: self.budget_per_group,
+            feedback_timeout: Duration::from_millis(self.feedback_timeout_ms),
+            failed_chunk_retries: self.failed_chunk_retries,
+            retry_backoff: Duration::from_millis(self.retry_backoff_ms),
+            max_retry_backoff: Duration::from_millis(self.max_retry_backoff_ms),
+            in_flight_retries: self.in_flight_retries,
+            adaptive_retries: self.adaptive_retries,
+        }
+    }
+}
+
+/// Startup erasure coding benchmark
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AutotuneSettings {
+    /// Benchmark Reed-Solomon on this host and cap adaptive parity
+    pub enabled: bool,
+    /// Saved results reused on later starts; benchmarked and written if missing
+    pub results_path: Option<PathBuf>,
+    /// Coding speed the capped parity must sustain (bytes/s)
+    pub min_bytes_per_sec: u64,
+}
+
+impl Default for AutotuneSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            results_path: None,
+            min_bytes_per_sec: 50 * 1024 * 1024,
+        }
+    }
+}
+
+/// QUIC transport and TLS
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NetworkSettings {
+    /// Address the QUIC endpoint listens on
+    pub bind_addr: SocketAddr,
+    /// Local address outbound connections are pinned to
+    pub client_bind_addr: Option<SocketAddr>,
+    /// Skip TLS certificate verification (self-signed peers only)
+    pub insecure_skip_verify: bool,
+    pub max_idle_timeout_secs: u64,
+    pub keep_alive_interval_secs: u64,
+    /// Hard cap on chunk data held in memory while receiving (bytes)
+    pub receive_memory_limit: usize,
+    /// Fraction of the memory limit above which new streams aren't accepted
+    pub receive_high_watermark: f64,
+    /// Largest chunk payload accepted on one stream (bytes)
+    pub max_chunk_size: usize,
+    /// Bottleneck bandwidth chunk writes are paced to (bytes/s, 0 = off)
+    pub pacing_rate_bytes_per_sec: u64,
+    /// Bytes that may leave back-to-back before pacing applies
+    pub pacing_burst_bytes: usize,
+    /// Pace to the measured path bandwidth instead of a fixed rate
+    pub adaptive_pacing: bool,
+    /// Rate any one sender's chunks are received at (bytes/s, 0 =
+    /// unlimited)
+    pub peer_receive_rate_bytes_per_sec: u64,
+    /// Per-sender rates in place of `peer_receive_rate_bytes_per_sec`, by
+    /// source address (0 = unlimited)
+    pub peer_receive_rate_overrides: HashMap<IpAddr, u64>,
+    /// Receive rate shared fairly between active senders (bytes/s, 0 =
+    /// unlimited)
+    pub total_receive_rate_bytes_per_sec: u64,
+    /// Bytes a sender may deliver back-to-back before receive shaping
+    /// applies
+    pub peer_receive_burst_bytes: usize,
+    /// Grow each connection's flow-control windows to its measured
+    /// bandwidth-delay product
+    pub flow_auto_tune: bool,
+    /// Bytes a peer may have in flight on one stream
+    pub stream_receive_window: u64,
+    /// Bytes a peer may have in flight on a connection (unset = unlimited)
+    pub receive_window: Option<u64>,
+    /// Unacknowledged bytes buffered for sending; where tuning starts
+    pub send_window: u64,
+    /// Largest window tuning grows to
+    pub max_flow_window: u64,
+    /// Shared secret resume tokens are signed and checked with; tokens are
+    /// unsigned and accepted unchecked when unset
+    pub resume_token_secret: Option<String>,
+    /// TLS key log written while key capture is on (unset = `SSLKEYLOGFILE`)
+    pub keylog_path: Option<PathBuf>,
+    /// Directory per-connection event logs are written to while event
+    /// capture is on (unset = no event capture)
+    pub capture_dir: Option<PathBuf>,
+    /// How often event capture samples a connection (ms)
+    pub capture_interval_ms: u64,
+    /// Give up connecting to a receiver after this long, per class (ms, 0 =
+    /// wait as long as the transport does)
+    pub critical_connect_timeout_ms: u64,
+    pub high_connect_timeout_ms: u64,
+    pub normal_connect_timeout_ms: u64,
+    /// Hand a Critical transfer that times out to the local relay, when
+    /// one is enabled, instead of failing it
+    pub relay_fallback: bool,
+}
+
+impl Default for NetworkSettings {
+    fn default() -> Self {
+        let defaults = ConnectionConfig::default();
+        let connect = ConnectPolicy::default();
+        Self {
+            bind_addr: defaults.bind_addr,
+            client_bind_addr: defaults.client_bind_addr,
+            insecure_skip_verify: defaults.insecure_skip_verify,
+            max_idle_timeout_secs: defaults.max_idle_timeout.as_secs(),
+            keep_alive_interval_secs: defaults.keep_alive_interval.as_secs(),
+            receive_memory_limit: defaults.receive_memory_limit,
+            receive_high_watermark: defaults.receive_high_watermark,
+            max_chunk_size: defaults.max_chunk_size,
+            pacing_rate_bytes_per_sec: defaults.pacing.rate_bytes_per_sec,
+            pacing_burst_bytes: defaults.pacing.burst_bytes,
+            adaptive_pacing: defaults.pacing.adaptive,
+            peer_receive_rate_bytes_per_sec: defaults.peer_shaping.default_rate_bytes_per_sec,
+            peer_receive_rate_overrides: defaults.peer_shaping.overrides,
+            total_receive_rate_bytes_per_sec: defaults.peer_shaping.total_rate_bytes_per_sec,
+            peer_receive_burst_bytes: defaults.peer_shaping.burst_bytes,
+            flow_auto_tune: defaults.flow_control.auto_tune,
+            stream_receive_window: defaults.flow_control.stream_receive_window,
+            receive_window: defaults.flow_control.receive_window,
+            send_window: defaults.flow_control.send_window,
+            max_flow_window: defaults.flow_control.max_window,
+            resume_token_secret: None,
+            keylog_path: defaults.capture.keylog_path,
+            capture_dir: defaults.capture.event_dir,
+            capture_interval_ms: defaults.capture.sample_interval.as_millis() as u64,
+            critical_connect_timeout_ms: connect.critical_timeout.as_millis() as u64,
+            high_connect_timeout_ms: connect.high_timeout.as_millis() as u64,
+            normal_connect_timeout_ms: connect.normal_timeout.as_millis() as u64,
+            relay_fallback: connect.relay_fallback,
+        }
+    }
+}
+
+impl NetworkSettings {
+    /// Transport configuration for these settings
+    pub fn connection_config(&self) -> ConnectionConfig {
+        ConnectionConfig {
+            bind_addr: self.bind_addr,
+            client_bind_addr: self.client_bind_addr,
+            insecure_skip_verify: self.insecure_skip_verify,
+            max_idle_timeout: Duration::from_secs(self.max_idle_timeout_secs),
+            keep_alive_interval: Duration::from_secs(self.keep_alive_interval_secs),
+            receive_memory_limit: self.receive_memory_limit,
+            receive_high_watermark: self.receive_high_watermark,
+            max_chunk_size: self.max_chunk_size,
+            pacing: PacerConfig {
+                rate_bytes_per_sec: self.pacing_rate_bytes_per_sec,
+                burst_bytes: self.pacing_burst_bytes,
+                adaptive: self.adaptive_pacing,
+            },
+            flow_control: FlowControlConfig {
+                auto_tune: self.flow_auto_tune,
+                stream_receive_window: self.stream_receive_window,
+                receive_window: self.receive_window,
+                send_window: self.send_window,
+                max_window: self.max_flow_window,
+                ..FlowControlConfig::default()
+            },
+            capture: CaptureConfig {
+                keylog_path: self.keylog_path.clone(),
+                event_dir: self.capture_dir.clone(),
+                sample_interval: Duration::from_millis(self.capture_interval_ms),
+            },
+            peer_shaping: PeerShapingConfig {
+                default_rate_bytes_per_sec: self.peer_receive_rate_bytes_per_sec,
+                overrides: self.peer_receive_rate_overrides.clone(),
+                total_rate_bytes_per_sec: self.total_receive_rate_bytes_per_sec,
+                burst_bytes: self.peer_receive_burst_bytes,
+                ..PeerShapingConfig::default()
+            },
+            ..ConnectionConfig::default()
+        }
+    }
+
+    /// Coordinator connect timeouts for these settings
+    pub fn connect_policy(&self) -> ConnectPolicy {
+        ConnectPolicy {
+            critical_timeout: Duration::from_millis(self.critical_connect_timeout_ms),
+            high_timeout: Duration::from_millis(self.high_connect_timeout_ms),
+            normal_timeout: Duration::from_millis(self.normal_connect_timeout_ms),
+            relay_fallback: self.relay_fallback,
+        }
+    }
+}
+
+/// REST and WebSocket API of the server
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ApiConfig {
+    pub bind_addr: SocketAddr,
+}
+
+impl Default for ApiConfig {
+    fn default() -> Self {
+        Self {
+            bind_addr: "0.0.0.0:3000".parse().unwrap(),
+        }
+    }
+}
+
+/// Prometheus exporter
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MetricsSettings {
+    /// Serve metrics for scraping
+    pub enabled: bool,
+    pub listen_addr: SocketAddr,
+    pub endpoint: String,
+    pub include_process_metrics: bool,
+    /// Record one in this many chunk sent/received events, scaled up so
+    /// totals stay accurate; failures are always recorded
+    pub chunk_sample_every: u64,
+    /// Label chunk metrics with their transfer id; turn off to keep series
+    /// count flat with many transfers
+    pub per_transfer_labels: bool,
+}
+
+impl Default for MetricsSettings {
+    fn default() -> Self {
+        let defaults = MetricsConfig::default();
+        Self {
+            enabled: false,
+            listen_addr: defaults.listen_addr,
+            endpoint: defaults.endpoint,
+            include_process_metrics: defaults.include_process_metrics,
+            chunk_sample_every: defaults.sampling.chunk_sample_every,
+            per_transfer_labels: defaults.sampling.per_transfer_labels,
+        }
+    }
+}
+
+impl MetricsSettings {
+    /// Exporter configuration for these settings
+    pub fn metrics_config(&self) -> MetricsConfig {
+        MetricsConfig {
+            listen_addr: self.listen_addr,
+            endpoint: self.endpoint.clone(),
+            include_process_metrics: self.include_process_metrics,
+            sampling: SamplingConfig {
+                chunk_sample_every: self.chunk_sample_every,
+                per_transfer_labels: self.per_transfer_labels,
+            },
+        }
+    }
+}
+
+/// Liveness and readiness checks
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HealthConfig {
+    /// Seconds a queue holding chunks may go without dequeuing before the
+    /// process reports itself not live
+    pub queue_stall_secs: u64,
+    /// Free bytes needed on each disk path to report ready
+    pub min_free_disk_bytes: u64,
+    /// Filesystems to watch; empty means the working directory and the
+    /// session database's directory
+    pub disk_paths: Vec<PathBuf>,
+    /// Milliseconds the session database has to answer a readiness ping
+    pub db_timeout_ms: u64,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        let defaults = HealthPolicy::default();
+        Self {
+            queue_stall_secs: defaults.queue_stall.as_secs(),
+            min_free_disk_bytes: defaults.min_free_disk_bytes,
+            disk_paths: Vec::new(),
+            db_timeout_ms: defaults.db_timeout.as_millis() as u64,
+        }
+    }
+}
+
+impl HealthConfig {
+    /// Coordinator health policy, watching `session`'s database directory
+    /// when no disk paths are set
+    pub fn policy(&self, session: &SessionConfig) -> HealthPolicy {
+        let mut disk_paths = self.disk_paths.clone();
+        if disk_paths.is_empty() {
+            disk_paths.push(PathBuf::from("."));
+            if !session.is_in_memory() && !session.db_path.starts_with("sqlite:") {
+                let dir = Path::new(&session.db_path)
+                    .parent()
+                    .filter(|dir| !dir.as_os_str().is_empty());
+                disk_paths.extend(dir.map(Path::to_path_buf));
+            }
+        }
+        HealthPolicy {
+            queue_stall: Duration::from_secs(self.queue_stall_secs),
+            min_free_disk_bytes: self.min_free_disk_bytes,
+            disk_paths,
+            db_timeout: Duration::from_millis(self.db_timeout_ms),
+        }
+    }
+}
+
+/// A relay peer known at startup
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RelayPeerConfig {
+    pub node_id: String,
+    pub addr: SocketAddr,
+    /// Lower is preferred
+    #[serde(default = "default_peer_priority")]
+    pub priority: u8,
+    /// Expected key; a `Hello` signed with any other is rejected
+    #[serde(default)]
+    pub public_key: Option<NodePublicKey>,
+}
+
+fn default_peer_priority() -> u8 {
+    100
+}
+
+/// Store-and-forward relay run alongside the server
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RelaySettings {
+    pub enabled: bool,
+    /// Generated on each start when unset
+    pub node_id: Option<String>,
+    pub listen_addr: SocketAddr,
+    pub max_storage_bytes: u64,
+    /// Chunks held longer than this are dropped
+    pub max_hold_time_secs: u64,
+    /// How often stored chunks are retried
+    pub forward_interval_secs: u64,
+    /// Random delay of up to this much added to each retry cycle
+    pub forward_jitter_ms: u64,
+    pub max_forward_retries: u32,
+    /// Relays a chunk may pass through (0 = unlimited)
+    pub max_hops: u8,
+    /// Relays holding a copy of each critical chunk (0 or 1 = none)
+    pub replication_factor: usize,
+    /// Relay hops over which critical chunks are sent to every peer at
+    /// once (0 = never flood)
+    pub flood_ttl: u8,
+    pub peers: Vec<RelayPeerConfig>,
+    /// Directory stored chunks and relay state survive restarts in
+    pub persistence_path: Option<PathBuf>,
+    /// Pack stored chunks into segments past this many files (0 = never)
+    pub compaction_threshold: u64,
+    /// Where runtime policy changes are saved
+    pub policy_path: Option<PathBuf>,
+    /// Reject stores and hellos without a valid signature
+    pub require_auth: bool,
+    /// Node ids or hex public keys allowed to store chunks (empty = any
+    /// signed node)
+    pub allowlist: Vec<String>,
+    /// Node ids or hex public keys never allowed to store chunks
+    pub denylist: Vec<String>,
+    /// This relay's keypair file; defaults to `node_key.pk8` under
+    /// `persistence_path`
+    pub identity_path: Option<PathBuf>,
+    /// Bytes any one destination may hold (0 = no limit)
+    pub destination_quota_bytes: u64,
+    /// Per-destination limits in place of `destination_quota_bytes`
+    pub destination_quotas: HashMap<SocketAddr, u64>,
+    /// `reject` new chunks at quota, or `evict_oldest` of the destination's own
+    pub on_quota_breach: QuotaBreach,
+    /// How often critical transfers handed to the relay are audited for
+    /// chunks nobody holds (0 = never)
+    pub audit_interval_secs: u64,
+}
+
+impl Default for RelaySettings {
+    fn default() -> Self {
+        let defaults = RelayConfig::default();
+        Self {
+            enabled: false,
+            node_id: None,
+            listen_addr: defaults.listen_addr,
+            max_storage_bytes: defaults.max_storage_bytes,
+            max_hold_time_secs: defaults.max_hold_time.as_secs(),
+            forward_interval_secs: defaults.forward_interval.as_secs(),
+            forward_jitter_ms: defaults.forward_jitter.as_millis() as u64,
+            max_forward_retries: defaults.max_forward_retries,
+            max_hops: defaults.policy.max_hops,
+            replication_factor: defaults.policy.replication_factor,
+            flood_ttl: 0,
+            peers: Vec::new(),
+            persistence_path: None,
+            compaction_threshold: defaults.compaction_threshold,
+            policy_path: None,
+            require_auth: defaults.access.require_auth,
+            allowlist: Vec::new(),
+            denylist: Vec::new(),
+            identity_path: None,
+            destination_quota_bytes: defaults.quotas.default_bytes,
+            destination_quotas: HashMap::new(),
+            on_quota_breach: defaults.quotas.on_breach,
+            audit_interval_secs: 60,
+        }
+    }
+}
+
+impl RelaySettings {
+    /// Relay node configuration for these settings
+    pub fn relay_config(&self) -> RelayConfig {
+        let defaults = RelayConfig::default();
+        RelayConfig {
+            node_id: self.node_id.clone().unwrap_or(defaults.node_id),
+            listen_addr: self.listen_addr,
+            max_storage_bytes: self.max_storage_bytes,
+            max_hold_time: Duration::from_secs(self.max_hold_time_secs),
+            forward_interval: Duration::from_secs(self.forward_interval_secs),
+            forward_jitter: Duration::from_millis(self.forward_jitter_ms),
+            max_forward_retries: self.max_forward_retries,
+            peers: self
+                .peers
+                .iter()
+                .map(|peer| {
+                    let mut info =
+                        PeerInfo::new(peer.node_id.clone(), peer.addr).with_priority(peer.priority);
+                    info.public_key = peer.public_key;
+                    info
+                })
+                .collect(),
+            policy: ForwardingPolicy {
+                max_hops: self.max_hops,
+                replication_factor: self.replication_factor,
+                flood: (self.flood_ttl > 0).then(|| FloodPolicy {
+                    ttl: self.flood_ttl,
+                    ..FloodPolicy::default()
+                }),
+                ..ForwardingPolicy::default()
+            },
+            policy_path: self.policy_path.clone(),
+            persistence_path: self.persistence_path.clone(),
+            compaction_threshold: self.compaction_threshold,
+            access: AccessPolicy {
+                require_auth: self.require_auth,
+                allowlist: self.allowlist.clone(),
+                denylist: self.denylist.clone(),
+                identity_path: self.identity_path.clone(),
+            },
+            quotas: DestinationQuotas {
+                default_bytes: self.destination_quota_bytes,
+                overrides: self.destination_quotas.clone(),
+                on_breach: self.on_quota_breach,
+            },
+            ..defaults
+        }
+    }
+}
+
+/// The receiver daemon
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReceiverConfig {
+    /// Address the receiver's QUIC endpoint listens on
+    pub bind_addr: SocketAddr,
+    /// Address of the receiver's REST API
+    pub api_addr: SocketAddr,
+    /// Where received files are written
+    pub save_dir: PathBuf,
+    /// Write data chunks to the output file as their group completes, so
+    /// it can be opened before the transfer finishes
+    pub preview_partial: bool,
+    /// Re-verify delivered files this often and patch damaged ones from
+    /// their sender (0 = never)
+    pub repair_interval_secs: u64,
+    /// Rebuild files here and keep them until released through the API,
+    /// instead of writing them straight to `save_dir`; on the same
+    /// filesystem as `save_dir`
+    pub quarantine_dir: Option<PathBuf>,
+    /// Report what has arrived back to the sender this often (ms, 0 =
+    /// never)
+    pub stats_interval_ms: u64,
+    /// Delete delivered files past an age or total size
+    pub retention: FileRetentionPolicy,
+}
+
+impl Default for ReceiverConfig {
+    fn default() -> Self {
+        Self {
+            bind_addr: "0.0.0.0:5001".parse().unwrap(),
+            api_addr: "0.0.0.0:8080".parse().unwrap(),
+            save_dir: PathBuf::from("./received"),
+            preview_partial: false,
+            repair_interval_secs: 0,
+            quarantine_dir: None,
+            stats_interval_ms: 1000,
+            retention: FileRetentionPolicy::default(),
+        }
+    }
+}
+
+impl ResilientConfig {
+    /// Parse a TOML document (missing keys keep their defaults)
+    pub fn from_toml_str(s: &str) -> ConfigResult<Self> {
+        Ok(toml::from_str(s)?)
+    }
+
+    /// Read a TOML config file
+    pub fn from_file(path: impl AsRef<Path>) -> ConfigResult<Self> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path).map_err(|source| ConfigError::Read {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        toml::from_str(&contents).map_err(|source| ConfigError::File {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+
+    /// Load from an optional file, apply `RESILIENT_*` overrides, and validate
+    pub fn load(path: Option<&Path>) -> ConfigResult<Self> {
+        let mut config = match path {
+            Some(path) => Self::from_file(path)?,
+            None => Self::default(),
+        };
+        config.apply_env()?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Override settings from `RESILIENT_*` environment variables
+    pub fn apply_env(&mut self) -> ConfigResult<()> {
+        self.apply_env_from(|key| std::env::var(key).ok())
+    }
+
+    /// Override settings from a variable lookup (testable form of `apply_env`)
+    pub fn apply_env_from(&mut self, lookup: impl Fn(&str) -> Option<String>) -> ConfigResult<()> {
+        fn parse<T: std::str::FromStr>(var: String, value: String) -> ConfigResult<T>
+        where
+            T::Err: std::fmt::Display,
+        {
+            value.parse().map_err(|e: T::Err| ConfigError::Env {
+                var,
+                reason: e.to_string(),
+            })
+        }
+
+        let get = |name: &str| {
+            let var = format!("{ENV_PREFIX}{name}");
+            lookup(&var).map(|value| (var, value))
+        };
+
+        if let Some((var, v)) = get("CHUNK_SIZE") {
+            self.chunk.chunk_size = parse(var, v)?;
+        }
+        if let Some((var, v)) = get("DATA_SHARDS") {
+            self.chunk.data_shards = parse(var, v)?;
+        }
+        if let Some((var, v)) = get("PARITY_SHARDS") {
+            self.chunk.parity_shards = parse(var, v)?;
+        }
+        if let Some((var, v)) = get("PRESERVE_ATTRIBUTES") {
+            self.chunk.preserve_attributes = parse(var, v)?;
+        }
+        if let Some((var, v)) = get("SOURCE_CHECK_INTERVAL_MS") {
+            self.chunk.source_check_interval_ms = parse(var, v)?;
+        }
+        if let Some((var, v)) = get("LOCK_SOURCE") {
+            self.chunk.lock_source = parse(var, v)?;
+        }
+        if let Some((var, v)) = get("COMPRESSION") {
+            self.chunk.compression = parse(var, v)?;
+        }
+        if let Some((var, v)) = get("REORDER_WINDOW") {
+            self.chunk.reorder_window = parse(var, v)?;
+        }
+        if let Some((var, v)) = get("WRITE_CONCURRENCY") {
+            self.chunk.write_concurrency = parse(var, v)?;
+        }
+        if let Some((var, v)) = get("DECODE_WORKERS") {
+            self.chunk.decode_workers = parse(var, v)?;
+        }
+        if let Some((var, v)) = get("WRITE_MAX_BYTES_PER_SEC") {
+            self.chunk.write_policy.max_bytes_per_sec = parse(var, v)?;
+        }
+        if let Some((var, v)) = get("WRITE_SYNC_EVERY_BYTES") {
+            self.chunk.write_policy.sync_every_bytes = parse(var, v)?;
+        }
+        if let Some((var, v)) = get("CHECKSUM_ALGORITHM") {
+            self.chunk.checksum_algorithm = parse(var, v)?;
+        }
+        if let Some((var, v)) = get("MAX_OVERHEAD_PERCENT") {
+            self.chunk.max_overhead_percent = if v.is_empty() {
+                None
+            } else {
+                Some(parse(var, v)?)
+            };
+        }
+        if let Some((_, v)) = get("PARITY_CACHE_DIR") {
+            self.chunk.parity_cache_dir = (!v.is_empty()).then(|| PathBuf::from(v));
+        }
+        if let Some((var, v)) = get("QUEUE_CAPACITY") {
+            self.queue.capacity = parse(var, v)?;
+        }
+        if let Some((var, v)) = get("QUEUE_MAX_BYTES") {
+            self.queue.max_bytes = parse(var, v)?;
+        }
+        if let Some((var, v)) = get("QUEUE_LEVELS") {
+            self.queue.levels = parse(var, v)?;
+        }
+        if let Some((var, v)) = get("RSS_LIMIT_BYTES") {
+            self.queue.rss_limit_bytes = parse(var, v)?;
+        }
+        if let Some((var, v)) = get("SESSION_WINDOW") {
+            self.queue.session_window = parse(var, v)?;
+        }
+        if let Some((var, v)) = get("STARVATION_THRESHOLD_SECS") {
+            self.queue.starvation_threshold_secs = parse(var, v)?;
+        }
+        if let Some((var, v)) = get("CRITICAL_LATENCY_TARGET_MS") {
+            self.queue.critical_latency_target_ms = parse(var, v)?;
+        }
+        if let Some((var, v)) = get("DB_BACKEND") {
+            self.session.backend = parse(var, v)?;
+        }
+        if let Some((_, v)) = get("DB_PATH") {
+            self.session.db_path = v;
+        }
+        if let Some((var, v)) = get("DB_BUSY_TIMEOUT_MS") {
+            self.session.busy_timeout_ms = parse(var, v)?;
+        }
+        if let Some((var, v)) = get("DB_MAX_CONNECTIONS") {
+            self.session.max_connections = parse(var, v)?;
+        }
+        if let Some((var, v)) = get("DB_WRITE_BEHIND") {
+            self.session.write_behind.enabled = parse(var, v)?;
+        }
+        if let Some((var, v)) = get("DB_WRITE_BEHIND_LAG_MS") {
+            self.session.write_behind.max_lag_ms = parse(var, v)?;
+        }
+        if let Some((var, v)) = get("DB_MAINTENANCE_INTERVAL_SECS") {
+            self.session.maintenance.interval_secs = parse(var, v)?;
+        }
+        if let Some((var, v)) = get("BIND_ADDR") {
+            self.network.bind_addr = parse(var, v)?;
+        }
+        if let Some((var, v)) = get("CLIENT_BIND_ADDR") {
+            self.network.client_bind_addr = if v.is_empty() {
+                None
+            } else {
+                Some(parse(var, v)?)
+            };
+        }
+        if let Some((var, v)) = get("INSECURE_SKIP_VERIFY") {
+            self.network.insecure_skip_verify = parse(var, v)?;
+        }
+        if let Some((var, v)) = get("RECEIVE_MEMORY_LIMIT") {
+            self.network.receive_memory_limit = parse(var, v)?;
+        }
+        if let Some((var, v)) = get("MAX_CHUNK_SIZE") {
+            self.network.max_chunk_size = parse(var, v)?;
+        }
+        if let Some((var, v)) = get("PACING_RATE") {
+            self.network.pacing_rate_bytes_per_sec = parse(var, v)?;
+        }
+        if let Some((var, v)) = get("PEER_RECEIVE_RATE") {
+            self.network.peer_receive_rate_bytes_per_sec = parse(var, v)?;
+        }
+        if let Some((var, v)) = get("TOTAL_RECEIVE_RATE") {
+            self.network.total_receive_rate_bytes_per_sec = parse(var, v)?;
+        }
+        if let Some((var, v)) = get("FLOW_AUTO_TUNE") {
+            self.network.flow_auto_tune = parse(var, v)?;
+        }
+        if let Some((var, v)) = get("SEND_WINDOW") {
+            self.network.send_window = parse(var, v)?;
+        }
+        if let Some((var, v)) = get("MAX_FLOW_WINDOW") {
+            self.network.max_flow_window = parse(var, v)?;
+        }
+        if let Some((_, v)) = get("RESUME_TOKEN_SECRET") {
+            self.network.resume_token_secret = (!v.is_empty()).then_some(v);
+        }
+        if let Some((_, v)) = get("KEYLOG_PATH") {
+            self.network.keylog_path = (!v.is_empty()).then(|| PathBuf::from(v));
+        }
+        if let Some((_, v)) = get("CAPTURE_DIR") {
+            self.network.capture_dir = (!v.is_empty()).then(|| PathBuf::from(v));
+        }
+        if let Some((var, v)) = get("CRITICAL_CONNECT_TIMEOUT_MS") {
+            self.network.critical_connect_timeout_ms = parse(var, v)?;
+        }
+        if let Some((var, v)) = get("RELAY_FALLBACK") {
+            self.network.relay_fallback = parse(var, v)?;
+        }
+        if let Some((var, v)) = get("MAX_RECENT_TRANSFERS") {
+            self.retention.max_recent_transfers = parse(var, v)?;
+        }
+        if let Some((var, v)) = get("MAX_CONCURRENT_TRANSFERS") {
+            self.admission.max_concurrent_transfers = parse(var, v)?;
+        }
+        if let Some((var, v)) = get("DUPLICATE_POLICY") {
+            self.admission.duplicate_policy = parse(var, v)?;
+        }
+        if let Some((var, v)) = get("MAX_STREAM_BYTES") {
+            self.admission.max_stream_bytes = parse(var, v)?;
+        }
+        if let Some((var, v)) = get("ERASURE_AUTOTUNE") {
+            self.autotune.enabled = parse(var, v)?;
+        }
+        if let Some((var, v)) = get("RETRANSMIT_BUDGET") {
+            self.retransmit.budget_per_group = parse(var, v)?;
+        }
+        if let Some((var, v)) = get("FAILED_CHUNK_RETRIES") {
+            self.retransmit.failed_chunk_retries = parse(var, v)?;
+        }
+        if let Some((var, v)) = get("IN_FLIGHT_RETRIES") {
+            self.retransmit.in_flight_retries = parse(var, v)?;
+        }
+        if let Some((var, v)) = get("ADAPTIVE_RETRIES") {
+            self.retransmit.adaptive_retries = parse(var, v)?;
+        }
+        if let Some((var, v)) = get("API_ADDR") {
+            self.api.bind_addr = parse(var, v)?;
+        }
+        if let Some((var, v)) = get("METRICS_ENABLED") {
+            self.metrics.enabled = parse(var, v)?;
+        }
+        if let Some((var, v)) = get("METRICS_ADDR") {
+            self.metrics.listen_addr = parse(var, v)?;
+        }
+        if let Some((var, v)) = get("METRICS_SAMPLE_EVERY") {
+            self.metrics.chunk_sample_every = parse(var, v)?;
+        }
+        if let Some((var, v)) = get("HEALTH_MIN_FREE_DISK_BYTES") {
+            self.health.min_free_disk_bytes = parse(var, v)?;
+        }
+        if let Some((var, v)) = get("RELAY_ENABLED") {
+            self.relay.enabled = parse(var, v)?;
+        }
+        if let Some((_, v)) = get("RELAY_NODE_ID") {
+            self.relay.node_id = (!v.is_empty()).then_some(v);
+        }
+        if let Some((var, v)) = get("RELAY_LISTEN_ADDR") {
+            self.relay.listen_addr = parse(var, v)?;
+        }
+        if let Some((var, v)) = get("RELAY_MAX_STORAGE") {
+            self.relay.max_storage_bytes = parse(var, v)?;
+        }
+        if let Some((var, v)) = get("RELAY_REQUIRE_AUTH") {
+            self.relay.require_auth = parse(var, v)?;
+        }
+        if let Some((var, v)) = get("RELAY_DESTINATION_QUOTA") {
+            self.relay.destination_quota_bytes = parse(var, v)?;
+        }
+        if let Some((var, v)) = get("RELAY_AUDIT_INTERVAL_SECS") {
+            self.relay.audit_interval_secs = parse(var, v)?;
+        }
+        if let Some((var, v)) = get("RELAY_FORWARD_JITTER_MS") {
+            self.relay.forward_jitter_ms = parse(var, v)?;
+        }
+        if let Some((var, v)) = get("RELAY_FLOOD_TTL") {
+            self.relay.flood_ttl = parse(var, v)?;
+        }
+        if let Some((var, v)) = get("RECEIVER_BIND_ADDR") {
+            self.receiver.bind_addr = parse(var, v)?;
+        }
+        if let Some((var, v)) = get("RECEIVER_API_ADDR") {
+            self.receiver.api_addr = parse(var, v)?;
+        }
+        if let Some((_, v)) = get("RECEIVER_SAVE_DIR") {
+            self.receiver.save_dir = PathBuf::from(v);
+        }
+        if let Some((var, v)) = get("RECEIVER_PREVIEW") {
+            self.receiver.preview_partial = parse(var, v)?;
+        }
+        if let Some((var, v)) = get("RECEIVER_REPAIR_INTERVAL_SECS") {
+            self.receiver.repair_interval_secs = parse(var, v)?;
+        }
+        if let Some((var, v)) = get("RECEIVER_STATS_INTERVAL_MS") {
+            self.receiver.stats_interval_ms = parse(var, v)?;
+        }
+        if let Some((var, v)) = get("RECEIVER_RETENTION_MAX_AGE_SECS") {
+            self.receiver.retention.max_age_secs = parse(var, v)?;
+        }
+        if let Some((var, v)) = get("RECEIVER_RETENTION_MAX_TOTAL_BYTES") {
+            self.receiver.retention.max_total_bytes = parse(var, v)?;
+        }
+        if let Some((_, v)) = get("RECEIVER_QUARANTINE_DIR") {
+            self.receiver.quarantine_dir = if v.is_empty() {
+                None
+            } else {
+                Some(PathBuf::from(v))
+            };
+        }
+        if let Some((_, v)) = get("LOG") {
+            self.logging.filter = v;
+        }
+        if let Some((var, v)) = get("LOG_FORMAT") {
+            self.logging.format = parse(var, v)?;
+        }
+        if let Some((var, v)) = get("FAILOVER_ROLE") {
+            self.failover.role = parse(var, v)?;
+        }
+        if let Some((var, v)) = get("REPLICATION_ADDR") {
+            self.failover.replication_addr = if v.is_empty() {
+                None
+            } else {
+                Some(parse(var, v)?)
+            };
+        }
+        if let Some((var, v)) = get("ACTIVE_ADDR") {
+            self.failover.active_addr = if v.is_empty() {
+                None
+            } else {
+                Some(parse(var, v)?)
+            };
+        }
+
+        Ok(())
+    }
+
+    /// Check the settings are consistent before anything is constructed
+    pub fn validate(&self) -> ConfigResult<()> {
+        let chunk = &self.chunk;
+        if chunk.chunk_size == 0 {
+            return Err(ConfigError::invalid("chunk.chunk_size", "must be > 0"));
+        }
+        if chunk.chunk_size > self.network.max_chunk_size {
+            return Err(ConfigError::invalid(
+                "chunk.chunk_size",
+                format!(
+                    "{} exceeds network.max_chunk_size ({} bytes)",
+                    chunk.chunk_size, self.network.max_chunk_size
+                ),
+            ));
+        }
+        if chunk.data_shards == 0 || chunk.parity_shards == 0 {
+            return Err(ConfigError::invalid(
+                "chunk.data_shards/parity_shards",
+                "data and parity shards must both be > 0",
+            ));
+        }
+        if chunk.data_shards + chunk.parity_shards > MAX_TOTAL_SHARDS {
+            return Err(ConfigError::invalid(
+                "chunk.data_shards/parity_shards",
+                format!(
+                    "{} + {} shards exceeds the Reed-Solomon maximum of {}",
+                    chunk.data_shards, chunk.parity_shards, MAX_TOTAL_SHARDS
+                ),
+            ));
+        }
+        if let Err(e) = chunk.erasure_profiles.validate() {
+            return Err(ConfigError::invalid(
+                "chunk.erasure_profiles",
+                e.to_string(),
+            ));
+        }
+        if chunk.reorder_window == 0 || chunk.reorder_group_size == 0 {
+            return Err(ConfigError::invalid(
+                "chunk.reorder_window/reorder_group_size",
+                "must both be > 0",
+            ));
+        }
+        if chunk.write_concurrency == 0 {
+            return Err(ConfigError::invalid(
+                "chunk.write_concurrency",
+                "must be > 0",
+            ));
+        }
+        if let Some(budget) = chunk.max_overhead_percent {
+            if budget == 0 {
+                return Err(ConfigError::invalid(
+                    "chunk.max_overhead_percent",
+                    "must be > 0; leave unset for no budget",
+                ));
+            }
+            if chunk.parity_shards * 100 > budget as usize * chunk.data_shards {
+                return Err(ConfigError::invalid(
+                    "chunk.parity_shards",
+                    format!(
+                        "{} parity shards on {} data shards exceed the {}% overhead budget",
+                        chunk.parity_shards, chunk.data_shards, budget
+                    ),
+                ));
+            }
+        }
+
+        if chunk.parity_cache_dir.is_some() && chunk.parity_cache_max_bytes == 0 {
+            return Err(ConfigError::invalid(
+                "chunk.parity_cache_max_bytes",
+                "must be > 0 when parity_cache_dir is set",
+            ));
+        }
+
+        if self.queue.capacity == 0 {
+            return Err(ConfigError::invalid("queue.capacity", "must be > 0"));
+        }
+        if !(DEFAULT_PRIORITY_LEVELS..=MAX_PRIORITY_LEVELS).contains(&self.queue.levels) {
+            return Err(ConfigError::invalid(
+                "queue.levels",
+                format!("must be between {DEFAULT_PRIORITY_LEVELS} and {MAX_PRIORITY_LEVELS}"),
+            ));
+        }
+        if !(self.queue.shed_watermark > 0.0 && self.queue.shed_watermark <= 1.0) {
+            return Err(ConfigError::invalid(
+                "queue.shed_watermark",
+                "must be in (0, 1]",
+            ));
+        }
+        if self.autotune.enabled && self.autotune.min_bytes_per_sec == 0 {
+            return Err(ConfigError::invalid(
+                "autotune.min_bytes_per_sec",
+                "must be > 0 when autotune is enabled",
+            ));
+        }
+        if self.queue.starvation_threshold_secs > 0
+            && self.queue.starvation_check_interval_secs == 0
+        {
+            return Err(ConfigError::invalid(
+                "queue.starvation_check_interval_secs",
+                "must be > 0 when starvation alerts are on",
+            ));
+        }
+
+        self.validate_db_path()?;
+        if self.session.max_connections == 0 {
+            return Err(ConfigError::invalid(
+                "session.max_connections",
+                "must be > 0",
+            ));
+        }
+        if self.session.maintenance.enabled && self.session.maintenance.interval_secs == 0 {
+            return Err(ConfigError::invalid(
+                "session.maintenance.interval_secs",
+                "must be > 0 when maintenance is enabled",
+            ));
+        }
+        let write_behind = &self.session.write_behind;
+        if write_behind.enabled && (write_behind.max_lag_ms == 0 || write_behind.max_dirty == 0) {
+            return Err(ConfigError::invalid(
+                "session.write_behind",
+                "max_lag_ms and max_dirty must be > 0 when enabled",
+            ));
+        }
+
+        let net = &self.network;
+        if let Some(local) = net.client_bind_addr {
+            QuicTransport::validate_local_addr(local, None)?;
+        }
+        if net.max_idle_timeout_secs == 0 {
+            return Err(ConfigError::invalid(
+                "network.max_idle_timeout_secs",
+                "must be > 0",
+            ));
+        }
+        if net.keep_alive_interval_secs >= net.max_idle_timeout_secs {
+            return Err(ConfigError::invalid(
+                "network.keep_alive_interval_secs",
+                "keep-alive must be shorter than the idle timeout or connections will drop",
+            ));
+        }
+        if !(net.receive_high_watermark > 0.0 && net.receive_high_watermark <= 1.0) {
+            return Err(ConfigError::invalid(
+                "network.receive_high_watermark",
+                "must be in (0, 1]",
+            ));
+        }
+        if net.receive_memory_limit < chunk.chunk_size {
+            return Err(ConfigError::invalid(
+                "network.receive_memory_limit",
+                "must hold at least one chunk",
+            ));
+        }
+        let pacing_enabled = net.pacing_rate_bytes_per_sec > 0 || net.adaptive_pacing;
+        if pacing_enabled && net.pacing_burst_bytes == 0 {
+            return Err(ConfigError::invalid(
+                "network.pacing_burst_bytes",
+                "must be > 0 when pacing is enabled",
+            ));
+        }
+        if net.connection_config().peer_shaping.is_enabled() && net.peer_receive_burst_bytes == 0 {
+            return Err(ConfigError::invalid(
+                "network.peer_receive_burst_bytes",
+                "must be > 0 when receive shaping is enabled",
+            ));
+        }
+        if net.stream_receive_window == 0 || net.send_window == 0 || net.receive_window == Some(0) {
+            return Err(ConfigError::invalid(
+                "network.send_window",
+                "flow-control windows must be > 0",
+            ));
+        }
+        if net.flow_auto_tune
+            && net.max_flow_window < net.send_window.max(net.receive_window.unwrap_or(0))
+        {
+            return Err(ConfigError::invalid(
+                "network.max_flow_window",
+                "must be at least the starting send and receive windows",
+            ));
+        }
+        if net.capture_interval_ms == 0 {
+            return Err(ConfigError::invalid(
+                "network.capture_interval_ms",
+                "must be > 0",
+            ));
+        }
+        crate::coordinator::validate_shares(&self.catalog.shares)
+            .map_err(|e| ConfigError::invalid("catalog.shares", e.to_string()))?;
+        if let Some(share) = self.catalog.shares.iter().find(|s| !s.path.is_dir()) {
+            return Err(ConfigError::invalid(
+                "catalog.shares",
+                format!("{} is not a directory", share.path.display()),
+            ));
+        }
+        if self.retention.sweep_interval_secs == 0 {
+            return Err(ConfigError::invalid(
+                "retention.sweep_interval_secs",
+                "must be > 0",
+            ));
+        }
+        if self.retransmit.budget_per_group > 0 && self.retransmit.feedback_timeout_ms == 0 {
+            return Err(ConfigError::invalid(
+                "retransmit.feedback_timeout_ms",
+                "must be > 0 when retransmission is enabled",
+            ));
+        }
+        if self.retransmit.retry_backoff_ms > self.retransmit.max_retry_backoff_ms {
+            return Err(ConfigError::invalid(
+                "retransmit.retry_backoff_ms",
+                "must not exceed retransmit.max_retry_backoff_ms",
+            ));
+        }
+
+        let metrics = &self.metrics;
+        if metrics.chunk_sample_every == 0 {
+            return Err(ConfigError::invalid(
+                "metrics.chunk_sample_every",
+                "must be > 0 (1 records every chunk)",
+            ));
+        }
+        if metrics.enabled {
+            if !metrics.endpoint.starts_with('/') {
+                return Err(ConfigError::invalid(
+                    "metrics.endpoint",
+                    format!("{:?} must start with '/'", metrics.endpoint),
+                ));
+            }
+            if metrics.listen_addr == self.api.bind_addr {
+                return Err(ConfigError::invalid(
+                    "metrics.listen_addr",
+                    format!("{} is already used by api.bind_addr", metrics.listen_addr),
+                ));
+            }
+        }
+
+        if self.health.queue_stall_secs == 0 {
+            return Err(ConfigError::invalid(
+                "health.queue_stall_secs",
+                "must be > 0",
+            ));
+        }
+        if self.health.db_timeout_ms == 0 {
+            return Err(ConfigError::invalid("health.db_timeout_ms", "must be > 0"));
+        }
+
+        let relay = &self.relay;
+        if relay.enabled {
+            if relay
+                .node_id
+                .as_deref()
+                .is_some_and(|id| id.trim().is_empty())
+            {
+                return Err(ConfigError::invalid("relay.node_id", "must not be empty"));
+            }
+            if relay.max_storage_bytes == 0 {
+                return Err(ConfigError::invalid(
+                    "relay.max_storage_bytes",
+                    "must be > 0",
+                ));
+            }
+            if relay.max_hold_time_secs == 0 {
+                return Err(ConfigError::invalid(
+                    "relay.max_hold_time_secs",
+                    "must be > 0",
+                ));
+            }
+            if relay.forward_interval_secs == 0 {
+                return Err(ConfigError::invalid(
+                    "relay.forward_interval_secs",
+                    "must be > 0",
+                ));
+            }
+            if relay.forward_jitter_ms >= relay.forward_interval_secs.saturating_mul(1000) {
+                return Err(ConfigError::invalid(
+                    "relay.forward_jitter_ms",
+                    "must be shorter than relay.forward_interval_secs",
+                ));
+            }
+            if let Some(peer) = relay.peers.iter().find(|p| p.node_id.trim().is_empty()) {
+                return Err(ConfigError::invalid(
+                    "relay.peers.node_id",
+                    format!("peer at {} has no node id", peer.addr),
+                ));
+            }
+            for (key, list) in [
+                ("relay.allowlist", &relay.allowlist),
+                ("relay.denylist", &relay.denylist),
+            ] {
+                if list.iter().any(|entry| entry.trim().is_empty()) {
+                    return Err(ConfigError::invalid(key, "entries must not be empty"));
+                }
+            }
+        }
+
+        if self.receiver.save_dir.as_os_str().is_empty() {
+            return Err(ConfigError::invalid(
+                "receiver.save_dir",
+                "must not be empty",
+            ));
+        }
+        if let Some(dir) = &self.receiver.quarantine_dir {
+            if dir.as_os_str().is_empty() || *dir == self.receiver.save_dir {
+                return Err(ConfigError::invalid(
+                    "receiver.quarantine_dir",
+                    "must be a directory other than receiver.save_dir",
+                ));
+            }
+            // Previews expose data before hooks have seen the file
+            if self.receiver.preview_partial {
+                return Err(ConfigError::invalid(
+                    "receiver.preview_partial",
+                    "can't preview files held in quarantine",
+                ));
+            }
+        }
+        if self.receiver.retention.is_enabled() && self.receiver.retention.interval_secs == 0 {
+            return Err(ConfigError::invalid(
+                "receiver.retention.interval_secs",
+                "must be greater than 0",
+            ));
+        }
+
+        if let Err(e) = LogFilter::parse(&self.logging.filter) {
+            return Err(ConfigError::invalid("logging.filter", e.to_string()));
+        }
+        if self.logging.file.is_some() && self.logging.max_file_bytes == 0 {
+            return Err(ConfigError::invalid(
+                "logging.max_file_bytes",
+                "must be > 0 when logging to a file",
+            ));
+        }
+
+        let failover = &self.failover;
+        match failover.role {
+            FailoverRole::Active if failover.replication_addr.is_none() => {
+                return Err(ConfigError::invalid(
+                    "failover.replication_addr",
+                    "an active needs an address for standbys to connect to",
+                ));
+            }
+            FailoverRole::Active
+                if net.bind_addr.port() != 0
+                    && failover.replication_addr == Some(net.bind_addr) =>
+            {
+                return Err(ConfigError::invalid(
+                    "failover.replication_addr",
+                    "must differ from network.bind_addr",
+                ));
+            }
+            FailoverRole::Standby if failover.active_addr.is_none() => {
+                return Err(ConfigError::invalid(
+                    "failover.active_addr",
+                    "a standby needs the active's replication address",
+                ));
+            }
+            _ => {}
+        }
+
+        if net.insecure_skip_verify {
+            tracing::warn!("config: TLS certificate verification is disabled");
+        }
+
+        Ok(())
+    }
+
+    fn validate_db_path(&self) -> ConfigResult<()> {
+        let session = &self.session;
+        if session.backend == SessionBackend::Sqlite && !cfg!(feature = "sqlite") {
+            return Err(ConfigError::invalid(
+                "session.backend",
+                "this build has no SQLite support (the sqlite feature is off); use memory",
+            ));
+        }
+        if session.db_path.trim().is_empty() {
+            return Err(ConfigError::invalid("session.db_path", "must not be empty"));
+        }
+        if session.is_in_memory() || session.db_path.starts_with("sqlite:") {
+            return Ok(());
+        }
+
+        let path = PathBuf::from(&session.db_path);
+        if path.is_dir() {
+            return Err(ConfigError::invalid(
+                "session.db_path",
+                format!("{} is a directory", path.display()),
+            ));
+        }
+        match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() && !parent.is_dir() => {
+                Err(ConfigError::invalid(
+                    "session.db_path",
+                    format!("directory {} does not exist", parent.display()),
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logging::LogFormat;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_default_is_valid() {
+        ResilientConfig::default().validate().unwrap();
+    }
+
+    #[test]
+    fn test_toml_partial_override() {
+        let config = ResilientConfig::from_toml_str(
+            r#"
+            [chunk]
+            chunk_size = 262144
+            parity_shards = 5
+            content_hints = false
+            parity_cache_dir = "/var/cache/resilient/parity"
+
+            [chunk.erasure_profiles.high]
+            extra_parity_percent = 25
+
+            [chunk.write_policy]
+            sync_every_bytes = 4194304
+            max_bytes_per_sec = 10485760
+
+            [network]
+            bind_addr = "127.0.0.1:5001"
+            total_receive_rate_bytes_per_sec = 12500000
+            peer_receive_rate_overrides = { "10.0.0.7" = 1250000 }
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.chunk.chunk_size, 256 * 1024);
+        assert_eq!(config.chunk.parity_shards, 5);
+        assert_eq!(config.chunk.data_shards, 50);
+        let profiles = config.chunk.erasure_profiles;
+        assert_eq!(profiles.high.extra_parity_percent, 25);
+        assert!(profiles.high.budgeted);
+        assert_eq!(profiles.critical, ErasureProfiles::default().critical);
+        assert!(!config.chunk.content_hints);
+        assert_eq!(
+            config.chunk.parity_cache_dir,
+            Some(PathBuf::from("/var/cache/resilient/parity"))
+        );
+        assert_eq!(config.chunk.parity_cache_max_bytes, 1024 * 1024 * 1024);
+        let write = config.chunk.write_policy;
+        assert_eq!(write.sync_every_bytes, 4 * 1024 * 1024);
+        assert_eq!(write.max_bytes_per_sec, 10 * 1024 * 1024);
+        assert!(!write.direct_io && !write.sync_on_complete);
+        assert_eq!(config.network.bind_addr, "127.0.0.1:5001".parse().unwrap());
+        let shaping = config.network.connection_config().peer_shaping;
+        assert_eq!(shaping.total_rate_bytes_per_sec, 12_500_000);
+        assert_eq!(
+            shaping.limit_for("10.0.0.7".parse().unwrap()),
+            Some(1_250_000)
+        );
+        assert_eq!(shaping.limit_for("10.0.0.8".parse().unwrap()), None);
+        assert_eq!(config.queue, QueueConfig::default());
+    }
+
+    #[test]
+    fn test_daemon_sections() {
+        let config = ResilientConfig::from_toml_str(
+            r#"
+            [api]
+            bind_addr = "127.0.0.1:3100"
+
+            [metrics]
+            enabled = true
+            listen_addr = "127.0.0.1:9100"
+
+            [relay]
+            enabled = true
+            node_id = "relay-north"
+            max_hold_time_secs = 600
+            max_hops = 3
+            peers = [{ node_id = "relay-south", addr = "10.0.0.9:9000", public_key = "1111111111111111111111111111111111111111111111111111111111111111" }]
+            require_auth = true
+            denylist = ["relay-west"]
+            destination_quota_bytes = 104857600
+            on_quota_breach = "evict_oldest"
+            destination_quotas = { "10.0.0.20:5001" = 0 }
+
+            [receiver]
+            save_dir = "/srv/incoming"
+            "#,
+        )
+        .unwrap();
+        config.validate().unwrap();
+
+        assert_eq!(config.api.bind_addr, "127.0.0.1:3100".parse().unwrap());
+        let metrics = config.metrics.metrics_config();
+        assert_eq!(metrics.listen_addr, "127.0.0.1:9100".parse().unwrap());
+        assert_eq!(metrics.endpoint, "/metrics");
+
+        let relay = config.relay.relay_config();
+        assert_eq!(relay.node_id, "relay-north");
+        assert_eq!(relay.max_hold_time, Duration::from_secs(600));
+        assert_eq!(relay.policy.max_hops, 3);
+        assert_eq!(relay.peers[0].node_id, "relay-south");
+        assert_eq!(relay.peers[0].priority, 100);
+        assert_eq!(relay.peers[0].public_key.unwrap().as_bytes(), &[0x11; 32]);
+        assert!(relay.access.require_auth);
+        assert_eq!(relay.access.denylist, ["relay-west"]);
+        assert_eq!(relay.quotas.on_breach, QuotaBreach::EvictOldest);
+        assert_eq!(
+            relay.quotas.limit_for("10.0.0.21:5001".parse().unwrap()),
+            Some(100 * 1024 * 1024)
+        );
+        assert_eq!(
+            relay.quotas.limit_for("10.0.0.20:5001".parse().unwrap()),
+            None
+        );
+
+        assert_eq!(config.receiver.save_dir, PathBuf::from("/srv/incoming"));
+        assert_eq!(config.receiver.api_addr, ReceiverConfig::default().api_addr);
+    }
+
+    #[test]
+    fn test_errors_name_the_offending_key() {
+        let err = ResilientConfig::from_toml_str(
+            r#"
+            [relay]
+            max_hold_secs = 600
+            "#,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("max_hold_secs"), "{err}");
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("server.toml");
+        std::fs::write(&path, "[chunk]\nchunk_size = \"big\"\n").unwrap();
+        let err = ResilientConfig::from_file(&path).unwrap_err();
+        assert!(matches!(err, ConfigError::File { .. }));
+        let message = err.to_string();
+        assert!(message.contains("server.toml"), "{message}");
+        assert!(message.contains("chunk_size"), "{message}");
+
+        let err = ResilientConfig::from_file(dir.path().join("missing.toml")).unwrap_err();
+        assert!(err.to_string().contains("missing.toml"), "{err}");
+
+        let mut config = ResilientConfig::default();
+        config.metrics.enabled = true;
+        config.metrics.listen_addr = config.api.bind_addr;
+        assert!(matches!(
+            config.validate(),
+            Err(ConfigError::Invalid {
+                field: "metrics.listen_addr",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_session_store_settings() {
+        let config = ResilientConfig::from_toml_str(
+            r#"
+            [session]
+            db_path = "sessions.db"
+            journal_mode = "delete"
+            synchronous = "full"
+            busy_timeout_ms = 250
+
+            [session.maintenance]
+            interval_secs = 900
+            analyze = false
+            "#,
+        )
+        .unwrap();
+
+        let maintenance = config.session.maintenance.policy();
+        assert!(maintenance.enabled);
+        assert_eq!(maintenance.interval, Duration::from_secs(900));
+        assert_eq!(maintenance.vacuum_pages, 0);
+        assert!(!maintenance.analyze);
+
+        let options = config.session.store_options();
+        assert_eq!(options.journal_mode, JournalMode::Delete);
+        assert_eq!(options.synchronous, SynchronousLevel::Full);
+        assert_eq!(options.busy_timeout, Duration::from_millis(250));
+        assert_eq!(
+            options.max_connections,
+            SessionStoreOptions::default().max_connections
+        );
+        assert_eq!(
+            config.session.backend == SessionBackend::Sqlite,
+            cfg!(feature = "sqlite")
+        );
+        assert_eq!(config.session.is_in_memory(), !cfg!(feature = "sqlite"));
+
+        let memory = ResilientConfig::from_toml_str(
+            r#"
+            [session]
+            backend = "memory"
+            db_path = "sessions.db"
+            "#,
+        )
+        .unwrap();
+        assert!(memory.session.is_in_memory());
+        memory.validate().unwrap();
+    }
+
+    #[test]
+    fn test_env_overrides() {
+        let vars: HashMap<&str, &str> = [
+            ("RESILIENT_DATA_SHARDS", "20"),
+            ("RESILIENT_DB_PATH", "sqlite::memory:"),
+            ("RESILIENT_DB_BACKEND", "Memory"),
+            ("RESILIENT_INSECURE_SKIP_VERIFY", "false"),
+            ("RESILIENT_SEND_WINDOW", "33554432"),
+            ("RESILIENT_TOTAL_RECEIVE_RATE", "12500000"),
+            ("RESILIENT_CAPTURE_DIR", "/var/tmp/capture"),
+            ("RESILIENT_CRITICAL_CONNECT_TIMEOUT_MS", "3000"),
+            ("RESILIENT_REORDER_WINDOW", "64"),
+            ("RESILIENT_WRITE_CONCURRENCY", "8"),
+            ("RESILIENT_DECODE_WORKERS", "2"),
+            ("RESILIENT_DUPLICATE_POLICY", "per_file"),
+            ("RESILIENT_WRITE_MAX_BYTES_PER_SEC", "2097152"),
+            ("RESILIENT_CHECKSUM_ALGORITHM", "sha256"),
+            ("RESILIENT_MAX_OVERHEAD_PERCENT", "25"),
+            ("RESILIENT_PARITY_CACHE_DIR", "/var/cache/parity"),
+            ("RESILIENT_SOURCE_CHECK_INTERVAL_MS", "0"),
+            ("RESILIENT_LOCK_SOURCE", "true"),
+            ("RESILIENT_COMPRESSION", "lz4"),
+            ("RESILIENT_RETRANSMIT_BUDGET", "0"),
+            ("RESILIENT_FAILED_CHUNK_RETRIES", "5"),
+            ("RESILIENT_ADAPTIVE_RETRIES", "false"),
+            ("RESILIENT_DB_WRITE_BEHIND", "true"),
+            ("RESILIENT_DB_WRITE_BEHIND_LAG_MS", "250"),
+            ("RESILIENT_SESSION_WINDOW", "64"),
+            ("RESILIENT_QUEUE_MAX_BYTES", "268435456"),
+            ("RESILIENT_QUEUE_LEVELS", "10"),
+            ("RESILIENT_RSS_LIMIT_BYTES", "2147483648"),
+            ("RESILIENT_API_ADDR", "127.0.0.1:3100"),
+            ("RESILIENT_METRICS_SAMPLE_EVERY", "100"),
+            ("RESILIENT_HEALTH_MIN_FREE_DISK_BYTES", "1073741824"),
+            ("RESILIENT_RELAY_ENABLED", "true"),
+            ("RESILIENT_RELAY_REQUIRE_AUTH", "true"),
+            ("RESILIENT_RELAY_DESTINATION_QUOTA", "1048576"),
+            ("RESILIENT_RELAY_AUDIT_INTERVAL_SECS", "0"),
+            ("RESILIENT_RELAY_FORWARD_JITTER_MS", "250"),
+            ("RESILIENT_RELAY_FLOOD_TTL", "2"),
+            ("RESILIENT_RECEIVER_SAVE_DIR", "/srv/incoming"),
+            ("RESILIENT_RECEIVER_PREVIEW", "true"),
+            ("RESILIENT_RECEIVER_REPAIR_INTERVAL_SECS", "86400"),
+            ("RESILIENT_RECEIVER_STATS_INTERVAL_MS", "0"),
+            ("RESILIENT_RECEIVER_QUARANTINE_DIR", "/srv/held"),
+            ("RESILIENT_RECEIVER_RETENTION_MAX_AGE_SECS", "604800"),
+            ("RESILIENT_LOG", "warn,chunkstream_pro::network=debug"),
+            ("RESILIENT_LOG_FORMAT", "json"),
+            ("RESILIENT_FAILOVER_ROLE", "standby"),
+            ("RESILIENT_ACTIVE_ADDR", "10.0.0.1:5100"),
+        ]
+        .into_iter()
+        .collect();
+
+        let mut config = ResilientConfig::default();
+        config
+            .apply_env_from(|k| vars.get(k).map(|v| v.to_string()))
+            .unwrap();
+        assert_eq!(config.chunk.data_shards, 20);
+        assert!(config.session.is_in_memory());
+        assert_eq!(config.session.backend, SessionBackend::Memory);
+        assert_eq!(
+            config.session.write_behind.policy().unwrap().max_lag,
+            Duration::from_millis(250)
+        );
+        assert!(!config.network.insecure_skip_verify);
+        assert_eq!(
+            config.network.connection_config().flow_control.send_window,
+            32 * 1024 * 1024
+        );
+        assert_eq!(
+            config.network.connection_config().capture.event_dir,
+            Some(PathBuf::from("/var/tmp/capture"))
+        );
+        assert_eq!(
+            config
+                .network
+                .connection_config()
+                .peer_shaping
+                .total_rate_bytes_per_sec,
+            12_500_000
+        );
+        let connect = config.network.connect_policy();
+        assert_eq!(
+            connect.timeout(Priority::Critical),
+            Some(Duration::from_secs(3))
+        );
+        assert_eq!(connect.timeout(Priority::Normal), None);
+        assert!(connect.relay_fallback);
+        assert_eq!(config.chunk.reorder_config().window, 64);
+        assert_eq!(config.chunk.write_concurrency, 8);
+        assert_eq!(config.chunk.decode_workers, 2);
+        assert_eq!(config.admission.duplicate_policy, DuplicatePolicy::PerFile);
+        assert_eq!(config.chunk.write_policy.max_bytes_per_sec, 2 * 1024 * 1024);
+        assert_eq!(config.chunk.checksum_algorithm, ChecksumType::Sha256);
+        assert_eq!(config.chunk.max_overhead_percent, Some(25));
+        assert_eq!(
+            config.chunk.parity_cache_dir,
+            Some(PathBuf::from("/var/cache/parity"))
+        );
+        assert_eq!(
+            config.chunk.source_watch(),
+            SourceWatch {
+                check_interval: None,
+                lock: true
+            }
+        );
+        assert_eq!(config.chunk.compression, CompressionMode::Lz4);
+        assert_eq!(config.retransmit.policy().budget_per_group, 0);
+        assert_eq!(config.retransmit.policy().failed_chunk_retries, 5);
+        assert!(!config.retransmit.policy().adaptive_retries);
+        assert_eq!(config.queue.session_window, 64);
+        assert_eq!(config.queue.max_bytes, 256 * 1024 * 1024);
+        assert_eq!(config.queue.levels, 10);
+        assert_eq!(
+            config.queue.memory_monitor().unwrap().limit(),
+            2 * 1024 * 1024 * 1024
+        );
+        assert_eq!(config.api.bind_addr, "127.0.0.1:3100".parse().unwrap());
+        assert_eq!(
+            config.metrics.metrics_config().sampling.chunk_sample_every,
+            100
+        );
+        let health = config.health.policy(&config.session);
+        assert_eq!(health.min_free_disk_bytes, 1024 * 1024 * 1024);
+        assert_eq!(health.disk_paths, vec![PathBuf::from(".")]);
+        assert!(config.relay.enabled);
+        assert!(config.relay.require_auth);
+        assert_eq!(config.relay.destination_quota_bytes, 1024 * 1024);
+        assert_eq!(config.relay.audit_interval_secs, 0);
+        assert_eq!(config.relay.forward_jitter_ms, 250);
+        assert_eq!(
+            config.relay.relay_config().policy.flood.map(|f| f.ttl),
+            Some(2)
+        );
+        assert_eq!(config.receiver.save_dir, PathBuf::from("/srv/incoming"));
+        assert!(config.receiver.preview_partial);
+        assert_eq!(config.receiver.repair_interval_secs, 86400);
+        assert_eq!(config.receiver.stats_interval_ms, 0);
+        assert_eq!(config.receiver.retention.max_age_secs, 604800);
+        assert!(config.receiver.retention.is_enabled());
+        assert_eq!(
+            config.receiver.quarantine_dir,
+            Some(PathBuf::from("/srv/held"))
+        );
+        assert_eq!(config.logging.filter, "warn,chunkstream_pro::network=debug");
+        assert_eq!(config.logging.format, LogFormat::Json);
+        assert_eq!(config.failover.role, FailoverRole::Standby);
+        assert_eq!(
+            config.failover.active_addr,
+            Some("10.0.0.1:5100".parse().unwrap())
+        );
+
+        let err = ResilientConfig::default()
+            .apply_env_from(|k| (k == "RESILIENT_QUEUE_CAPACITY").then(|| "lots".to_string()))
+            .unwrap_err();
+        assert!(matches!(err, ConfigError::Env { .. }));
+    }
+
+    #[test]
+    fn test_validation_rejects_bad_values() {
+        let mut config = ResilientConfig::default();
+        config.chunk.data_shards = 250;
+        config.chunk.parity_shards = 10;
+        assert!(config.validate().is_err());
+
+        let mut config = ResilientConfig::default();
+        config.chunk.chunk_size = 64 * 1024 * 1024;
+        assert!(config.validate().is_err());
+        // Fine once receivers are configured to take chunks that large
+        config.network.max_chunk_size = 64 * 1024 * 1024;
+        config.network.receive_memory_limit = 512 * 1024 * 1024;
+        assert!(config.validate().is_ok());
+
+        let mut config = ResilientConfig::default();
+        config.queue.capacity = 0;
+        assert!(config.validate().is_err());
+
+        let mut config = ResilientConfig::default();
+        config.network.send_window = 512 * 1024 * 1024;
+        assert!(config.validate().is_err());
+        config.network.flow_auto_tune = false;
+        assert!(config.validate().is_ok());
+
+        let mut config = ResilientConfig::default();
+        config.network.peer_receive_burst_bytes = 0;
+        assert!(config.validate().is_ok());
+        config.network.peer_receive_rate_bytes_per_sec = 1_000_000;
+        assert!(config.validate().is_err());
+
+        let mut config = ResilientConfig::default();
+        config.queue.levels = 2;
+        assert!(config.validate().is_err());
+        config.queue.levels = 10;
+        assert!(config.validate().is_ok());
+
+        // SQLite needs the sqlite feature, and a directory for its file
+        let mut config = ResilientConfig::default();
+        config.session.backend = SessionBackend::Sqlite;
+        assert_eq!(config.validate().is_ok(), cfg!(feature = "sqlite"));
+        config.session.db_path = "/nonexistent-dir/sessions.db".into();
+        assert!(config.validate().is_err());
+
+        let mut config = ResilientConfig::default();
+        config.session.max_connections = 0;
+        assert!(config.validate().is_err());
+
+        let mut config = ResilientConfig::default();
+        let shared = std::env::temp_dir();
+        config.catalog.shares = vec![CatalogShare::new("field", &shared)];
+        assert!(config.validate().is_ok());
+        config
+            .catalog
+            .shares
+            .push(CatalogShare::new("field", &shared));
+        assert!(config.validate().is_err());
+        config.catalog.shares = vec![CatalogShare::new("field", "/nonexistent-dir")];
+        assert!(config.validate().is_err());
+
+        let mut config = ResilientConfig::default();
+        config.session.maintenance.interval_secs = 0;
+        assert!(config.validate().is_err());
+        config.session.maintenance.enabled = false;
+        assert!(config.validate().is_ok());
+        config.session.write_behind.max_lag_ms = 0;
+        assert!(config.validate().is_ok());
+        config.session.write_behind.enabled = true;
+        assert!(config.validate().is_err());
+
+        let mut config = ResilientConfig::default();
+        config.retransmit.feedback_timeout_ms = 0;
+        assert!(config.validate().is_err());
+
+        let mut config = ResilientConfig::default();
+        config.retransmit.retry_backoff_ms = config.retransmit.max_retry_backoff_ms + 1;
+        assert!(config.validate().is_err());
+
+        let mut config = ResilientConfig::default();
+        config.network.keep_alive_interval_secs = config.network.max_idle_timeout_secs;
+        assert!(config.validate().is_err());
+
+        let mut config = ResilientConfig::default();
+        config.retention.sweep_interval_secs = 0;
+        assert!(config.validate().is_err());
+
+        let mut config = ResilientConfig::default();
+        config.chunk.reorder_group_size = 0;
+        assert!(config.validate().is_err());
+
+        let mut config = ResilientConfig::default();
+        config.chunk.write_concurrency = 0;
+        assert!(config.validate().is_err());
+
+        let mut config = ResilientConfig::default();
+        config.chunk.erasure_profiles.critical.extra_parity_percent = 1000;
+        assert!(config.validate().is_err());
+
+        // 10 parity on 50 data is 20% extra
+        let mut config = ResilientConfig::default();
+        config.chunk.max_overhead_percent = Some(20);
+        assert!(config.validate().is_ok());
+        config.chunk.max_overhead_percent = Some(15);
+        assert!(config.validate().is_err());
+        config.chunk.max_overhead_percent = Some(0);
+        assert!(config.validate().is_err());
+
+        let mut config = ResilientConfig::default();
+        config.relay.enabled = true;
+        config.relay.forward_interval_secs = 0;
+        assert!(config.validate().is_err());
+
+        let mut config = ResilientConfig::default();
+        config.relay.enabled = true;
+        config.relay.forward_interval_secs = 5;
+        config.relay.forward_jitter_ms = 5_000;
+        assert!(config.validate().is_err());
+
+        let mut config = ResilientConfig::default();
+        config.queue.starvation_threshold_secs = 60;
+        config.queue.starvation_check_interval_secs = 0;
+        assert!(config.validate().is_err());
+
+        let mut config = ResilientConfig::default();
+        config.logging.filter = "info,chunkstream_pro=loud".into();
+        assert!(config.validate().is_err());
+
+        let mut config = ResilientConfig::default();
+        config.failover.role = FailoverRole::Active;
+        assert!(config.validate().is_err());
+        config.failover.replication_addr = Some("0.0.0.0:5100".parse().unwrap());
+        assert!(config.validate().is_ok());
+
+        let mut config = ResilientConfig::default();
+        config.receiver.quarantine_dir = Some(config.receiver.save_dir.clone());
+        assert!(config.validate().is_err());
+        config.receiver.quarantine_dir = Some("/srv/held".into());
+        assert!(config.validate().is_ok());
+        config.receiver.preview_partial = true;
+        assert!(config.validate().is_err());
+
+        let mut config = ResilientConfig::default();
+        config.receiver.retention.interval_secs = 0;
+        assert!(config.validate().is_ok());
+        config.receiver.retention.max_total_bytes = 1 << 30;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_starvation_alert_settings() {
+        assert!(QueueConfig::default().starvation_policy().is_none());
+
+        let config = ResilientConfig::from_toml_str(
+            r#"
+            [queue]
+            starvation_threshold_secs = 120
+            starvation_sinks = ["metric", { webhook = "http://ops.local/alerts" }]
+            "#,
+        )
+        .unwrap();
+
+        let policy = config.queue.starvation_policy().unwrap();
+        assert_eq!(policy.threshold, Duration::from_secs(120));
+        assert_eq!(config.queue.latency_target(Priority::Critical), None);
+        assert_eq!(
+            policy.sinks,
+            [
+                AlertSink::Metric,
+                AlertSink::Webhook("http://ops.local/alerts".into())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_latency_targets() {
+        let config = ResilientConfig::from_toml_str(
+            r#"
+            [queue]
+            critical_latency_target_ms = 250
+            high_latency_target_ms = 2000
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.queue.latency_target(Priority::Critical),
+            Some(Duration::from_millis(250))
+        );
+        assert_eq!(
+            config.queue.latency_target(Priority::High),
+            Some(Duration::from_secs(2))
+        );
+        assert_eq!(config.queue.latency_target(Priority::Normal), None);
+    }
+
+    #[test]
+    fn test_autotune_settings() {
+        assert!(!ResilientConfig::default().autotune.enabled);
+
+        let mut config = ResilientConfig::from_toml_str(
+            r#"
+            [autotune]
+            enabled = true
+            results_path = "/var/lib/resilient/autotune.json"
+            "#,
+        )
+        .unwrap();
+        assert!(config.autotune.enabled);
+        assert_eq!(
+            config.autotune.results_path.as_deref(),
+            Some(std::path::Path::new("/var/lib/resilient/autotune.json"))
+        );
+        assert!(config.validate().is_ok());
+
+        config.autotune.min_bytes_per_sec = 0;
+        assert!(config.validate().is_err());
+    }
+}
@@ -0,0 +1,171 @@
+//! Admission control for concurrent transfers
+//!
+//! Each transfer splits its file in memory and holds a connection, so
+//! starting hundreds at once exhausts the host. With a limit set, transfers
+//! beyond it wait in a pending queue and start as running ones finish,
+//! highest priority first and in arrival order within a priority.
+
+use super::types::TransferSource;
+use crate::chunk::Priority;
+use crate::session::TransferOptions;
+use parking_lot::Mutex;
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+
+/// A transfer waiting for a free slot
+#[derive(Debug, Clone)]
+pub struct PendingTransfer {
+    pub session_id: String,
+    /// Id the transfer is tracked by; see [`TransferSource::file_id`]
+    pub file_id: String,
+    pub source: TransferSource,
+    pub priority: Priority,
+    pub receiver_addr: Option<SocketAddr>,
+    pub options: TransferOptions,
+    /// Unix timestamp the transfer was queued at
+    pub queued_at: i64,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    /// 0 = unlimited
+    max_concurrent: usize,
+    /// Admitted transfers not yet registered as active
+    starting: usize,
+    next_seq: u64,
+    /// Keyed by (priority, arrival) so iteration is admission order
+    pending: BTreeMap<(u8, u64), PendingTransfer>,
+}
+
+/// Slot accounting and the pending queue
+#[derive(Debug, Default)]
+pub(crate) struct AdmissionQueue {
+    inner: Mutex<Inner>,
+}
+
+impl AdmissionQueue {
+    pub fn max_concurrent(&self) -> usize {
+        self.inner.lock().max_concurrent
+    }
+
+    pub fn set_max_concurrent(&self, max: usize) {
+        self.inner.lock().max_concurrent = max;
+    }
+
+    /// Take a slot for a new transfer if one is free and nobody is waiting
+    pub fn try_admit(&self, running: usize) -> bool {
+        let mut inner = self.inner.lock();
+        if inner.pending.is_empty() && inner.has_slot(running) {
+            inner.starting += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn enqueue(&self, transfer: PendingTransfer) {
+        let mut inner = self.inner.lock();
+        let key = (transfer.priority.level(), inner.next_seq);
+        inner.next_seq += 1;
+        inner.pending.insert(key, transfer);
+    }
+
+    /// Take a slot for the next pending transfer, if there is a free one
+    pub fn pop_next(&self, running: usize) -> Option<PendingTransfer> {
+        let mut inner = self.inner.lock();
+        if !inner.has_slot(running) {
+            return None;
+        }
+        let (_, transfer) = inner.pending.pop_first()?;
+        inner.starting += 1;
+        Some(transfer)
+    }
+
+    /// An admitted transfer is now active (or failed to start)
+    pub fn started(&self) {
+        let mut inner = self.inner.lock();
+        inner.starting = inner.starting.saturating_sub(1);
+    }
+
+    /// Withdraw a pending transfer
+    pub fn remove(&self, session_id: &str) -> Option<PendingTransfer> {
+        let mut inner = self.inner.lock();
+        let key = *inner
+            .pending
+            .iter()
+            .find(|(_, t)| t.session_id == session_id)?
+            .0;
+        inner.pending.remove(&key)
+    }
+
+    pub fn contains(&self, session_id: &str) -> bool {
+        self.inner
+            .lock()
+            .pending
+            .values()
+            .any(|t| t.session_id == session_id)
+    }
+
+    /// Pending transfers in the order they will start
+    pub fn pending(&self) -> Vec<PendingTransfer> {
+        self.inner.lock().pending.values().cloned().collect()
+    }
+}
+
+impl Inner {
+    fn has_slot(&self, running: usize) -> bool {
+        self.max_concurrent == 0 || running + self.starting < self.max_concurrent
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pending(session_id: &str, priority: Priority) -> PendingTransfer {
+        PendingTransfer {
+            session_id: session_id.into(),
+            file_id: session_id.into(),
+            source: TransferSource::File(session_id.into()),
+            priority,
+            receiver_addr: None,
+            options: TransferOptions::default(),
+            queued_at: 0,
+        }
+    }
+
+    #[test]
+    fn test_admission_order_and_slots() {
+        let queue = AdmissionQueue::default();
+        queue.set_max_concurrent(1);
+
+        assert!(queue.try_admit(0));
+        // The admitted transfer holds the slot until it is running
+        assert!(!queue.try_admit(0));
+        queue.started();
+        assert!(!queue.try_admit(1));
+
+        queue.enqueue(pending("normal-1", Priority::Normal));
+        queue.enqueue(pending("critical", Priority::Critical));
+        queue.enqueue(pending("normal-2", Priority::Normal));
+        assert!(queue.pop_next(1).is_none());
+
+        let order: Vec<String> = std::iter::from_fn(|| {
+            let next = queue.pop_next(0);
+            queue.started();
+            next
+        })
+        .map(|t| t.session_id)
+        .collect();
+        assert_eq!(order, ["critical", "normal-1", "normal-2"]);
+    }
+
+    #[test]
+    fn test_remove_pending() {
+        let queue = AdmissionQueue::default();
+        queue.enqueue(pending("a", Priority::High));
+        assert!(queue.contains("a"));
+        assert!(queue.remove("a").is_some());
+        assert!(queue.pending().is_empty());
+    }
+}
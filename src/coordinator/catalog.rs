@@ -0,0 +1,220 @@
+//! Files this host offers to receivers that ask for them
+//!
+//! A field unit that only connects now and then can't count on a sender
+//! pushing to it while it is reachable. Instead it lists the catalog of
+//! shared directories when it dials in and asks for files by id; the sender
+//! then pushes each one like any other transfer.
+
+use crate::coordinator::error::{CoordinatorError, CoordinatorResult};
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Component, Path, PathBuf};
+
+/// Most files one catalog listing returns
+pub const MAX_CATALOG_ENTRIES: usize = 10_000;
+
+/// A directory whose files receivers may ask for
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CatalogShare {
+    /// First part of the id of every file in the share
+    pub name: String,
+    pub path: PathBuf,
+}
+
+impl CatalogShare {
+    pub fn new(name: impl Into<String>, path: impl Into<PathBuf>) -> Self {
+        Self {
+            name: name.into(),
+            path: path.into(),
+        }
+    }
+}
+
+/// A file receivers may ask for
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CatalogEntry {
+    /// Share name and path within the share, separated by `/`
+    pub id: String,
+    pub size: u64,
+    /// Unix time the file last changed, where the platform reports it
+    pub modified_at: Option<i64>,
+}
+
+/// Fails unless share names are unique, non-empty and free of `/`
+pub(crate) fn validate_shares(shares: &[CatalogShare]) -> CoordinatorResult<()> {
+    let mut names = HashSet::new();
+    for share in shares {
+        if share.name.is_empty() || share.name.contains('/') {
+            return Err(CoordinatorError::InvalidConfig(format!(
+                "catalog share name {:?} must be non-empty and contain no '/'",
+                share.name
+            )));
+        }
+        if !names.insert(share.name.as_str()) {
+            return Err(CoordinatorError::InvalidConfig(format!(
+                "catalog share {} is listed twice",
+                share.name
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// The shared directories
+#[derive(Debug, Default)]
+pub(crate) struct Catalog {
+    shares: RwLock<Vec<CatalogShare>>,
+}
+
+impl Catalog {
+    pub(crate) fn shares(&self) -> Vec<CatalogShare> {
+        self.shares.read().clone()
+    }
+
+    pub(crate) fn set_shares(&self, shares: Vec<CatalogShare>) -> CoordinatorResult<()> {
+        validate_shares(&shares)?;
+        *self.shares.write() = shares;
+        Ok(())
+    }
+
+    /// Every regular file in the shares, by id
+    ///
+    /// Symbolic links are left out, so a share can't expose files outside
+    /// its directory. Stops at [`MAX_CATALOG_ENTRIES`].
+    pub(crate) async fn list(&self) -> CoordinatorResult<Vec<CatalogEntry>> {
+        let mut entries = Vec::new();
+        for share in self.shares() {
+            let mut dirs = vec![(share.path.clone(), share.name.clone())];
+            while let Some((dir, prefix)) = dirs.pop() {
+                let mut read = tokio::fs::read_dir(&dir).await?;
+                while let Some(entry) = read.next_entry().await? {
+                    let Some(name) = entry.file_name().to_str().map(str::to_string) else {
+                        continue;
+                    };
+                    let id = format!("{prefix}/{name}");
+                    let file_type = entry.file_type().await?;
+                    if file_type.is_dir() {
+                        dirs.push((entry.path(), id));
+                    } else if file_type.is_file() {
+                        let meta = entry.metadata().await?;
+                        entries.push(CatalogEntry {
+                            id,
+                            size: meta.len(),
+                            modified_at: meta
+                                .modified()
+                                .ok()
+                                .map(|t| chrono::DateTime::<chrono::Utc>::from(t).timestamp()),
+                        });
+                        if entries.len() == MAX_CATALOG_ENTRIES {
+                            tracing::warn!(
+                                "Catalog listing stopped at {} files",
+                                MAX_CATALOG_ENTRIES
+                            );
+                            entries.sort_by(|a, b| a.id.cmp(&b.id));
+                            return Ok(entries);
+                        }
+                    }
+                }
+            }
+        }
+        entries.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(entries)
+    }
+
+    /// Path of the file with catalog id `id`
+    ///
+    /// Fails with [`CoordinatorError::NotInCatalog`] unless it names a
+    /// regular file inside one of the shares.
+    pub(crate) async fn resolve(&self, id: &str) -> CoordinatorResult<PathBuf> {
+        let not_found = || CoordinatorError::NotInCatalog(id.to_string());
+        let (name, relative) = id.split_once('/').ok_or_else(not_found)?;
+        let relative = Path::new(relative);
+        if !relative
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)))
+        {
+            return Err(not_found());
+        }
+        let root = self
+            .shares
+            .read()
+            .iter()
+            .find(|share| share.name == name)
+            .map(|share| share.path.clone())
+            .ok_or_else(not_found)?;
+
+        // Resolve links on both sides, so a link inside the share can't
+        // point outside it
+        let root = tokio::fs::canonicalize(&root)
+            .await
+            .map_err(|_| not_found())?;
+        let path = tokio::fs::canonicalize(root.join(relative))
+            .await
+            .map_err(|_| not_found())?;
+        let is_file = tokio::fs::metadata(&path)
+            .await
+            .is_ok_and(|meta| meta.is_file());
+        if !path.starts_with(&root) || !is_file {
+            return Err(not_found());
+        }
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_lists_and_resolves_shared_files_only() {
+        let shared = TempDir::new().unwrap();
+        let private = TempDir::new().unwrap();
+        std::fs::create_dir(shared.path().join("logs")).unwrap();
+        std::fs::write(shared.path().join("map.bin"), b"map").unwrap();
+        std::fs::write(shared.path().join("logs/day1.log"), b"day one").unwrap();
+        std::fs::write(private.path().join("secret"), b"secret").unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(private.path(), shared.path().join("escape")).unwrap();
+
+        let catalog = Catalog::default();
+        catalog
+            .set_shares(vec![CatalogShare::new("field", shared.path())])
+            .unwrap();
+
+        let entries = catalog.list().await.unwrap();
+        let ids: Vec<_> = entries.iter().map(|e| e.id.as_str()).collect();
+        assert_eq!(ids, ["field/logs/day1.log", "field/map.bin"]);
+        assert_eq!(entries[0].size, 7);
+
+        let path = catalog.resolve("field/logs/day1.log").await.unwrap();
+        assert_eq!(std::fs::read(path).unwrap(), b"day one");
+        for id in [
+            "field/escape/secret",
+            "field/../secret",
+            "field/logs",
+            "field/missing",
+            "other/map.bin",
+            "field",
+        ] {
+            assert!(
+                matches!(
+                    catalog.resolve(id).await,
+                    Err(CoordinatorError::NotInCatalog(_))
+                ),
+                "{id}"
+            );
+        }
+
+        let twice = vec![
+            CatalogShare::new("field", shared.path()),
+            CatalogShare::new("field", private.path()),
+        ];
+        assert!(catalog.set_shares(twice).is_err());
+        assert!(catalog
+            .set_shares(vec![CatalogShare::new("a/b", shared.path())])
+            .is_err());
+    }
+}
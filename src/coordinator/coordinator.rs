@@ -1,121 +1,209 @@
-use crate::chunk::{AdaptiveErasureCoder, AdaptiveErasureConfig};
-use crate::chunk::{Chunk, ChunkManager, FileManifest, Priority};
+use crate::chunk::AdaptiveErasureCoder;
+use crate::chunk::{AdaptiveErasureConfig, ChunkManager, Priority, SourceWatch, TransferPlan};
+use crate::coordinator::admission::PendingTransfer;
+use crate::coordinator::catalog::{Catalog, CatalogEntry, CatalogShare};
+use crate::coordinator::defaults::{
+    self, ChunkingDefaults, ConfigChange, DefaultsSection, ErasureDefaults, LiveDefaults,
+    TransferDefaults,
+};
 use crate::coordinator::error::{CoordinatorError, CoordinatorResult};
-use crate::coordinator::state_machine::TransferStateMachine;
-use crate::coordinator::types::{TransferEvent, TransferProgress, TransferState};
-use crate::integrity::IntegrityVerifier;
-use crate::network::{QuicPathStats, QuicTransport};
-use crate::priority::PriorityQueue;
-use crate::session::{SessionState, SessionStatus, SessionStore};
-use dashmap::DashMap;
+use crate::coordinator::events::{CoordinatorEvent, EventBus, SequencedEvent, SessionBackfill};
+use crate::coordinator::failover::{self, Failover};
+use crate::coordinator::health::ResourceUsage;
+use crate::coordinator::maintenance::Maintenance;
+use crate::coordinator::profiles;
+use crate::coordinator::receive::{FeedbackReceiver, ReceiveService};
+use crate::coordinator::resume_token::ResumeToken;
+use crate::coordinator::retransmit::RetransmitPolicy;
+use crate::coordinator::send::{SendService, TransferSender};
+use crate::coordinator::simulation::{LossSimulator, SimulationService};
+use crate::coordinator::stats::{HealthTracker, StatsService};
+use crate::coordinator::transport::Transport;
+use crate::coordinator::types::{
+    ConnectPolicy, DuplicatePolicy, MaintenancePolicy, RetentionPolicy, TransferProgress,
+    TransferSource, TransferState,
+};
+use crate::coordinator::verify::{self, FileVerification, VerifyTarget};
+use crate::failover::{FailoverStatus, ReplicationLog, SkippedSession, TakeoverReport};
+use crate::hooks::HookRegistry;
+use crate::metrics::recorder;
+use crate::network::probe::PROBE_CHUNK_SIZE;
+use crate::network::{ConnectionConfig, LinkReport, QuicTransport};
+use crate::priority::{PriorityQueue, StarvationMonitor, StarvationPolicy};
+use crate::relay::RelayNode;
+use crate::session::{
+    BenchmarkRecord, MaintenanceReport, ProgressSample, SessionPage, SessionQuery,
+    SessionRepository, SessionSearch, SessionState, StorageStats, TransferOptions, TransferProfile,
+};
+use bytes::Bytes;
+use futures::Stream;
 use std::net::SocketAddr;
 use std::path::PathBuf;
-use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use std::time::{Duration, Instant};
-use tokio::time;
-
-/// Result of a file-based packet loss simulation (aggregated over multiple trials)
-#[derive(Debug, Clone)]
-pub struct SimulateFileResult {
-    pub file_name: String,
-    pub file_size_bytes: u64,
-    pub total_chunks: u32,
-    pub data_chunks: usize,
-    pub parity_chunks: usize,
-    // Aggregate stats over N trials
-    pub num_trials: u32,
-    pub successful_trials: u32,
-    pub success_rate: f64,
-    pub avg_chunks_lost: f64,
-    pub avg_chunks_recovered: f64,
-    pub min_chunks_lost: u32,
-    pub max_chunks_lost: u32,
-}
-
-/// Single data point in a TCP vs RESILIENT comparison sweep
-#[derive(Debug, Clone)]
-pub struct ComparisonPoint {
-    pub loss_percent: u32,
-    pub tcp_success_rate: f64,
-    pub resilient_success_rate: f64,
-    pub tcp_avg_chunks_lost: f64,
-    pub resilient_avg_chunks_lost: f64,
-    pub resilient_avg_recovered: f64,
-}
-
-/// Full comparison result across all loss rates
-#[derive(Debug, Clone)]
-pub struct ComparisonResult {
-    pub file_name: String,
-    pub file_size_bytes: u64,
-    pub total_chunks: u32,
-    pub data_chunks: usize,
-    pub parity_chunks: usize,
-    pub trials_per_point: u32,
-    pub points: Vec<ComparisonPoint>,
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::task::JoinHandle;
+
+/// Services a coordinator hands its work to, in place of the defaults
+///
+/// Any left `None` is built over the coordinator's own queue, session store
+/// and transport: [`TransferSender`], [`FeedbackReceiver`],
+/// [`LossSimulator`] and [`HealthTracker`]. The default sender records path
+/// stats with whichever stats service is used.
+#[derive(Default)]
+pub struct CoordinatorServices {
+    pub send: Option<Arc<dyn SendService>>,
+    pub receive: Option<Arc<dyn ReceiveService>>,
+    pub simulation: Option<Arc<dyn SimulationService>>,
+    pub stats: Option<Arc<dyn StatsService>>,
 }
 
+/// Entry point for sending: hands transfers to its [`SendService`], relay
+/// and repair traffic to its [`ReceiveService`], simulations to its
+/// [`SimulationService`] and health checks to its [`StatsService`]
+///
+/// What it keeps itself is shared by all of them (defaults, catalog,
+/// profiles, hooks, events) or is about the process as a whole (failover).
+#[derive(Clone)]
 pub struct TransferCoordinator {
-    chunk_manager: Arc<ChunkManager>,
-    verifier: Arc<IntegrityVerifier>,
-    transport: Arc<QuicTransport>,
+    // Chunking and erasure defaults, and the changes made to them
+    defaults: Arc<LiveDefaults>,
+    transport: Arc<dyn Transport>,
     queue: Arc<PriorityQueue>,
-    session_store: Arc<SessionStore>,
+    session_store: Arc<dyn SessionRepository>,
 
-    // Active transfers
-    active_transfers: Arc<DashMap<String, TransferStateMachine>>,
+    // Session database upkeep, run by a background task
+    maintenance: Arc<Maintenance>,
 
-    // Recent transfers (including completed/failed) - kept for display
-    recent_transfers: Arc<DashMap<String, TransferStateMachine>>,
+    // Background check for starving priority classes, when enabled
+    starvation_monitor: Arc<parking_lot::Mutex<Option<JoinHandle<()>>>>,
 
-    // Session ID mapping
-    file_to_session: Arc<DashMap<String, String>>,
+    // Directories receivers may ask for files from
+    catalog: Arc<Catalog>,
 
-    // Adaptive erasure coder for metrics & simulation
+    // Plugin hooks (scanners, content filters)
+    hooks: Arc<HookRegistry>,
+
+    // Notifications for embedding applications
+    events: EventBus,
+
+    // Parity sizing fed by receivers' reports and by simulations
     adaptive_coder: Arc<AdaptiveErasureCoder>,
 
-    // Simulation counters
-    sim_chunks_sent: Arc<AtomicU64>,
-    sim_chunks_lost: Arc<AtomicU64>,
-    sim_chunks_recovered: Arc<AtomicU64>,
+    // Starts, runs and settles transfers
+    send: Arc<dyn SendService>,
+
+    // Repair requests and relay reports coming back
+    receive: Arc<dyn ReceiveService>,
+
+    // Simulated transfers for the dashboard
+    simulation: Arc<dyn SimulationService>,
 
-    // Real QUIC path stats from the most recent transfer
-    last_quic_stats: Arc<parking_lot::RwLock<QuicPathStats>>,
+    // Uptime, path stats and health checks
+    stats: Arc<dyn StatsService>,
 
-    // Start time for uptime tracking
-    start_time: Instant,
+    // Replication to standbys, or the active this standby follows
+    failover: Arc<Failover>,
 }
 
 impl TransferCoordinator {
     pub fn new(
         chunk_manager: ChunkManager,
-        verifier: IntegrityVerifier,
-        transport: QuicTransport,
+        transport: impl Transport + 'static,
         queue: PriorityQueue,
-        session_store: SessionStore,
+        session_store: impl SessionRepository + 'static,
     ) -> Self {
-        let adaptive_config = AdaptiveErasureConfig::default();
-        let adaptive_coder = AdaptiveErasureCoder::new(adaptive_config);
+        Self::with_services(
+            chunk_manager,
+            transport,
+            queue,
+            session_store,
+            CoordinatorServices::default(),
+        )
+    }
+
+    /// Coordinator handing its work to `services` where given
+    pub fn with_services(
+        chunk_manager: ChunkManager,
+        transport: impl Transport + 'static,
+        queue: PriorityQueue,
+        session_store: impl SessionRepository + 'static,
+        services: CoordinatorServices,
+    ) -> Self {
+        let defaults = Arc::new(LiveDefaults::new(chunk_manager));
+        let transport: Arc<dyn Transport> = Arc::new(transport);
+        let queue = Arc::new(queue);
+        let session_store: Arc<dyn SessionRepository> = Arc::new(session_store);
+        let hooks = Arc::new(HookRegistry::new());
+        let events = EventBus::default();
+        let adaptive_coder = Arc::new(AdaptiveErasureCoder::new(AdaptiveErasureConfig::default()));
+
+        let maintenance = Arc::new(Maintenance::default());
+        if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+            runtime.spawn(
+                maintenance
+                    .clone()
+                    .run_periodically(Arc::downgrade(&session_store)),
+            );
+        }
+
+        let stats = services.stats.unwrap_or_else(|| {
+            Arc::new(HealthTracker::new(
+                queue.clone(),
+                session_store.clone(),
+                transport.clone(),
+            ))
+        });
+        let send = services.send.unwrap_or_else(|| {
+            Arc::new(TransferSender::new(
+                defaults.shared(),
+                transport.clone(),
+                queue.clone(),
+                session_store.clone(),
+                hooks.clone(),
+                events.clone(),
+                stats.clone(),
+                adaptive_coder.clone(),
+            ))
+        });
+        let receive = services.receive.unwrap_or_else(|| {
+            Arc::new(FeedbackReceiver::new(
+                transport.clone(),
+                session_store.clone(),
+                events.clone(),
+            ))
+        });
+        let simulation = services.simulation.unwrap_or_else(|| {
+            Arc::new(LossSimulator::new(
+                defaults.shared(),
+                adaptive_coder.clone(),
+            ))
+        });
 
         Self {
-            chunk_manager: Arc::new(chunk_manager),
-            verifier: Arc::new(verifier),
-            transport: Arc::new(transport),
-            queue: Arc::new(queue),
-            session_store: Arc::new(session_store),
-            active_transfers: Arc::new(DashMap::new()),
-            recent_transfers: Arc::new(DashMap::new()),
-            file_to_session: Arc::new(DashMap::new()),
-            adaptive_coder: Arc::new(adaptive_coder),
-            sim_chunks_sent: Arc::new(AtomicU64::new(0)),
-            sim_chunks_lost: Arc::new(AtomicU64::new(0)),
-            sim_chunks_recovered: Arc::new(AtomicU64::new(0)),
-            last_quic_stats: Arc::new(parking_lot::RwLock::new(QuicPathStats::default())),
-            start_time: Instant::now(),
+            defaults,
+            transport,
+            queue,
+            session_store,
+            maintenance,
+            starvation_monitor: Arc::new(parking_lot::Mutex::new(None)),
+            catalog: Arc::new(Catalog::default()),
+            hooks,
+            events,
+            adaptive_coder,
+            send,
+            receive,
+            simulation,
+            stats,
+            failover: Arc::new(Failover::default()),
         }
     }
 
+    /// Start building a coordinator from validated configuration
+    pub fn builder() -> crate::config::CoordinatorBuilder {
+        crate::config::CoordinatorBuilder::new()
+    }
+
     /// Start sending a file
     pub async fn send_file(
         &self,
@@ -123,186 +211,372 @@ impl TransferCoordinator {
         priority: Priority,
         receiver_addr: Option<SocketAddr>,
     ) -> CoordinatorResult<String> {
-        // Check if already in progress
-        let file_id = file_path.to_string_lossy().to_string();
-        if self.file_to_session.contains_key(&file_id) {
-            return Err(CoordinatorError::AlreadyInProgress(file_id));
-        }
+        self.send_file_with_options(
+            file_path,
+            priority,
+            receiver_addr,
+            TransferOptions::default(),
+        )
+        .await
+    }
 
-        // Split file into chunks
-        let (manifest, chunks) = self
-            .chunk_manager
-            .split_file(&file_path, file_id.clone(), priority)
-            .await?;
+    /// Start sending a file with per-transfer options (e.g. a pinned local uplink)
+    ///
+    /// When the concurrent transfer limit is reached the transfer is queued
+    /// and starts once a slot frees; the returned session id is valid either way.
+    pub async fn send_file_with_options(
+        &self,
+        file_path: PathBuf,
+        priority: Priority,
+        receiver_addr: Option<SocketAddr>,
+        options: TransferOptions,
+    ) -> CoordinatorResult<String> {
+        self.send_source(
+            TransferSource::File(file_path),
+            priority,
+            receiver_addr,
+            options,
+        )
+        .await
+    }
 
-        // Create session with receiver address and file path for resumable transfers
-        let session_id = uuid::Uuid::new_v4().to_string();
-        let session = SessionState::new_with_receiver(
-            session_id.clone(),
-            file_id.clone(),
-            manifest.clone(),
+    /// Start sending data produced in memory as a file called `name`
+    ///
+    /// The transfer is tracked under `memory:<name>`, so the duplicate
+    /// policy applies per name. The data isn't kept once the transfer
+    /// stops: to resume it, supply the same data again with
+    /// [`resume_bytes`](Self::resume_bytes).
+    pub async fn send_bytes(
+        &self,
+        name: &str,
+        data: Bytes,
+        priority: Priority,
+        receiver_addr: Option<SocketAddr>,
+        options: TransferOptions,
+    ) -> CoordinatorResult<String> {
+        self.send_source(
+            TransferSource::memory(name, data)?,
+            priority,
             receiver_addr,
-            Some(file_path.to_string_lossy().to_string()),
-        );
-        self.session_store.save(&session).await?;
+            options,
+        )
+        .await
+    }
 
-        // Update session status to active
-        self.session_store
-            .update_status(&session_id, SessionStatus::Active)
-            .await?;
+    /// Start sending everything `reader` yields as a file called `name`
+    ///
+    /// The stream is read to its end before the transfer starts, since the
+    /// file checksum and parity cover all of it, and refused once it runs
+    /// past [`max_stream_bytes`](Self::max_stream_bytes); otherwise this
+    /// behaves as [`send_bytes`](Self::send_bytes). To resume, supply the
+    /// same stream again with [`resume_stream`](Self::resume_stream).
+    pub async fn send_stream<R>(
+        &self,
+        name: &str,
+        reader: R,
+        priority: Priority,
+        receiver_addr: Option<SocketAddr>,
+        options: TransferOptions,
+    ) -> CoordinatorResult<String>
+    where
+        R: AsyncRead + Unpin,
+    {
+        let data = read_stream(reader, self.max_stream_bytes()).await?;
+        self.send_bytes(name, data, priority, receiver_addr, options)
+            .await
+    }
 
-        // Create state machine
-        let state_machine = TransferStateMachine::new();
-        state_machine.transition(TransferEvent::Start {
-            file_path: file_path.clone(),
-            priority,
-        })?;
+    async fn send_source(
+        &self,
+        source: TransferSource,
+        priority: Priority,
+        receiver_addr: Option<SocketAddr>,
+        options: TransferOptions,
+    ) -> CoordinatorResult<String> {
+        if self.is_shutting_down() {
+            return Err(CoordinatorError::ShuttingDown);
+        }
+        if self.is_standby() {
+            return Err(CoordinatorError::Standby);
+        }
+        self.send
+            .send(source, priority, receiver_addr, options)
+            .await
+    }
 
-        // Register transfer
-        self.active_transfers
-            .insert(session_id.clone(), state_machine.clone());
-        self.recent_transfers
-            .insert(session_id.clone(), state_machine);
-        self.file_to_session
-            .insert(file_id.clone(), session_id.clone());
-
-        // Start transfer worker
-        let coordinator = self.clone();
-        let worker_session_id = session_id.clone();
-        let worker_file_id = file_id;
-        tokio::spawn(async move {
-            if let Err(e) = coordinator
-                .transfer_worker(worker_session_id.clone(), manifest, chunks, receiver_addr)
-                .await
-            {
-                eprintln!("Transfer worker failed for {worker_session_id}: {e}");
-                // Mark as failed so the UI reflects the error
-                let _ = coordinator
-                    .session_store
-                    .update_status(&worker_session_id, SessionStatus::Failed(e.to_string()))
-                    .await;
-                coordinator.active_transfers.remove(&worker_session_id);
-                coordinator.file_to_session.remove(&worker_file_id);
-            }
-        });
+    /// Start sending a file with the settings of profile `name`
+    ///
+    /// `priority`, `receiver_addr` and any `options` given here take
+    /// precedence over the profile's.
+    pub async fn send_file_with_profile(
+        &self,
+        file_path: PathBuf,
+        name: &str,
+        priority: Option<Priority>,
+        receiver_addr: Option<SocketAddr>,
+        options: TransferOptions,
+    ) -> CoordinatorResult<String> {
+        let profile = self.profile(name).await?;
+        self.send_file_with_options(
+            file_path,
+            priority.or(profile.priority).unwrap_or(Priority::Normal),
+            receiver_addr.or(profile.receiver_addr),
+            options.or(profile.options),
+        )
+        .await
+    }
+
+    /// Which running transfers refuse another of the same file
+    pub fn duplicate_policy(&self) -> DuplicatePolicy {
+        self.send.policy().duplicates
+    }
+
+    pub fn set_duplicate_policy(&self, policy: DuplicatePolicy) {
+        self.send.update_policy(&mut |p| p.duplicates = policy);
+    }
+
+    /// Most bytes a stream source is read to (0 = unlimited)
+    pub fn max_stream_bytes(&self) -> u64 {
+        self.send.policy().max_stream_bytes
+    }
+
+    pub fn set_max_stream_bytes(&self, max: u64) {
+        self.send.update_policy(&mut |p| p.max_stream_bytes = max);
+    }
+
+    /// Transfers waiting for a slot, in the order they will start
+    pub fn pending_transfers(&self) -> Vec<PendingTransfer> {
+        self.send.pending_transfers()
+    }
+
+    /// Maximum transfers running at once (0 = unlimited)
+    pub fn max_concurrent_transfers(&self) -> usize {
+        self.send.max_concurrent_transfers()
+    }
+
+    /// Change the concurrent transfer limit; raising it starts queued transfers
+    pub fn set_max_concurrent_transfers(&self, max: usize) {
+        self.send.set_max_concurrent_transfers(max)
+    }
+
+    /// Measure the link to a receiver by streaming probe chunks for `duration`
+    ///
+    /// The report can seed chunk sizing and parity before a large transfer.
+    /// Needs a QUIC transport.
+    pub async fn probe_link(
+        &self,
+        receiver_addr: SocketAddr,
+        duration: Duration,
+    ) -> CoordinatorResult<LinkReport> {
+        let transport = self.quic_transport().ok_or_else(|| {
+            CoordinatorError::Unsupported("link probes need a QUIC transport".into())
+        })?;
+        let conn = transport.connect_from(receiver_addr, None).await?;
+        let report = transport
+            .probe_link(&conn, duration, PROBE_CHUNK_SIZE)
+            .await;
 
-        Ok(session_id)
+        self.stats
+            .record_quic_stats(QuicTransport::connection_stats(&conn));
+        conn.close(0u32.into(), b"probe complete");
+        Ok(report?)
     }
 
     /// Resume a paused transfer
+    ///
+    /// Transfers sent from memory can't be resumed this way; use
+    /// [`resume_bytes`](Self::resume_bytes).
     pub async fn resume_transfer(&self, session_id: &str) -> CoordinatorResult<()> {
-        // Load session
-        let session = self
-            .session_store
-            .load(session_id)
-            .await?
-            .ok_or_else(|| CoordinatorError::TransferNotFound(session_id.to_string()))?;
+        self.resume_with(session_id, None).await
+    }
+
+    /// Resume a paused transfer started with [`send_bytes`](Self::send_bytes)
+    /// or [`send_stream`](Self::send_stream), supplying its data again
+    ///
+    /// `data` must be exactly what the transfer started with; it is checked
+    /// against the manifest's checksum before anything is sent.
+    pub async fn resume_bytes(&self, session_id: &str, data: Bytes) -> CoordinatorResult<()> {
+        self.resume_with(session_id, Some(data)).await
+    }
+
+    /// Resume a transfer sent from memory with its data read from `reader`
+    pub async fn resume_stream<R>(&self, session_id: &str, reader: R) -> CoordinatorResult<()>
+    where
+        R: AsyncRead + Unpin,
+    {
+        let data = read_stream(reader, self.max_stream_bytes()).await?;
+        self.resume_bytes(session_id, data).await
+    }
 
-        // Check if can resume
-        let resume_info = self.session_store.get_resume_info(session_id).await?;
-        if !resume_info.can_resume {
-            return Err(CoordinatorError::CannotResume(
-                "Transfer is already complete or failed".into(),
-            ));
+    async fn resume_with(&self, session_id: &str, data: Option<Bytes>) -> CoordinatorResult<()> {
+        if self.is_shutting_down() {
+            return Err(CoordinatorError::ShuttingDown);
+        }
+        if self.is_standby() {
+            return Err(CoordinatorError::Standby);
         }
+        self.send.resume(session_id, data).await
+    }
 
-        // Get or create state machine
-        let state_machine = if let Some(sm) = self.active_transfers.get(session_id) {
-            sm.clone()
-        } else {
-            let sm = TransferStateMachine::new();
-            self.active_transfers
-                .insert(session_id.to_string(), sm.clone());
-            sm
-        };
+    /// Sign resume tokens with a key derived from `secret`, and only accept
+    /// tokens signed with it (`None` exports and accepts unsigned tokens)
+    pub fn set_resume_token_secret(&self, secret: Option<&str>) {
+        self.send.set_resume_token_secret(secret)
+    }
 
-        // Transition to resume
-        state_machine.send_event(TransferEvent::Resume)?;
+    /// Export a session so another host with the same file can finish it
+    pub async fn export_resume_token(&self, session_id: &str) -> CoordinatorResult<ResumeToken> {
+        self.send.export_resume_token(session_id).await
+    }
 
-        // Update session status
-        self.session_store
-            .update_status(session_id, SessionStatus::Active)
-            .await?;
-
-        // Re-read chunks from the original file if we have the file path
-        let chunks = if let Some(ref file_path_str) = session.file_path {
-            let file_path = PathBuf::from(file_path_str);
-            if file_path.exists() {
-                // Re-split the file to get chunks (only the remaining ones will be sent)
-                match self
-                    .chunk_manager
-                    .split_file(
-                        &file_path,
-                        session.file_id.clone(),
-                        session.manifest.priority,
-                    )
-                    .await
-                {
-                    Ok((_, chunks)) => chunks,
-                    Err(e) => {
-                        tracing::warn!("Failed to re-read file for resume: {}", e);
-                        vec![]
-                    }
-                }
-            } else {
-                tracing::warn!("Original file not found for resume: {}", file_path_str);
-                vec![]
-            }
-        } else {
-            tracing::warn!("No file path stored in session, cannot re-read chunks for resume");
-            vec![]
-        };
+    /// Continue a session exported by another host, sending from this
+    /// host's copy of the file at `file_path`
+    ///
+    /// The file must split into the same manifest as the token's; only the
+    /// chunks the receiver doesn't have yet are sent. Returns the session id,
+    /// which is the original one.
+    pub async fn import_resume_token(
+        &self,
+        token: ResumeToken,
+        file_path: PathBuf,
+    ) -> CoordinatorResult<String> {
+        self.send.import_resume_token(token, file_path).await
+    }
+
+    /// Pause a transfer
+    pub async fn pause_transfer(&self, session_id: &str) -> CoordinatorResult<()> {
+        self.send.pause(session_id).await
+    }
 
-        // Use stored receiver address for resume
-        let receiver_addr = session.receiver_addr;
+    /// Mark sessions a previous process left running as paused
+    ///
+    /// A transfer's worker dies with its process, so after a crash or
+    /// restart its session still reads active and nothing would pick it up.
+    /// Paused, it can be resumed. Returns how many sessions were recovered.
+    pub async fn recover_sessions(&self) -> CoordinatorResult<usize> {
+        // A standby's sessions are the active's, still running there
+        if self.is_standby() {
+            return Ok(0);
+        }
+        self.send.recover_sessions().await
+    }
 
-        // Start transfer worker
-        let coordinator = self.clone();
-        let session_id_str = session_id.to_string();
-        let manifest = session.manifest.clone();
+    /// Stop taking transfers and pause the running ones, so the next
+    /// process can resume them
+    ///
+    /// Transfers still waiting for a slot have no session yet and are
+    /// dropped. Returns how many transfers were paused.
+    pub async fn shutdown(&self) -> CoordinatorResult<usize> {
+        self.send.shutdown().await
+    }
 
-        tokio::spawn(async move {
-            if let Err(e) = coordinator
-                .transfer_worker(session_id_str.clone(), manifest, chunks, receiver_addr)
-                .await
-            {
-                eprintln!("Transfer worker failed for {session_id_str}: {e}");
-            }
-        });
+    /// Whether [`shutdown`](Self::shutdown) has been called
+    pub fn is_shutting_down(&self) -> bool {
+        self.send.is_shutting_down()
+    }
+
+    /// Stream session changes to standbys connecting on `config.bind_addr`
+    ///
+    /// Changes come from `log`, so the session storage must be a
+    /// [`ReplicatingRepository`](crate::failover::ReplicatingRepository)
+    /// writing to it. Returns the address served.
+    pub async fn serve_replication(
+        &self,
+        config: ConnectionConfig,
+        log: Arc<ReplicationLog>,
+    ) -> CoordinatorResult<SocketAddr> {
+        self.failover
+            .serve(config, log, self.session_store.clone())
+            .await
+    }
 
+    /// Mirror the active replicating on `active_addr`, retrying after
+    /// `reconnect` whenever it is lost
+    ///
+    /// Until [`promote`](Self::promote) is called no transfer starts or
+    /// resumes here. Needs a QUIC transport.
+    pub fn follow_active(
+        &self,
+        active_addr: SocketAddr,
+        reconnect: Duration,
+    ) -> CoordinatorResult<()> {
+        let transport = self.quic_transport().ok_or_else(|| {
+            CoordinatorError::Unsupported("following an active needs a QUIC transport".into())
+        })?;
+        self.failover.follow(
+            transport,
+            active_addr,
+            self.session_store.clone(),
+            reconnect,
+        );
         Ok(())
     }
 
-    /// Pause a transfer
-    pub async fn pause_transfer(&self, session_id: &str) -> CoordinatorResult<()> {
-        let state_machine = self
-            .active_transfers
-            .get(session_id)
-            .ok_or_else(|| CoordinatorError::TransferNotFound(session_id.to_string()))?;
+    /// Whether this is a standby that hasn't been promoted
+    pub fn is_standby(&self) -> bool {
+        self.failover.is_standby()
+    }
 
-        state_machine.transition(TransferEvent::Pause)?;
-        self.session_store
-            .update_status(session_id, SessionStatus::Paused)
-            .await?;
+    pub fn failover_status(&self) -> FailoverStatus {
+        self.failover.status()
+    }
 
-        Ok(())
+    /// Take over from the active: stop following it and resume every
+    /// transfer it was running
+    ///
+    /// The active must be down already; nothing here stops it. Transfers
+    /// that can't resume here, such as ones sent from memory, stay paused
+    /// and are listed with the reason.
+    pub async fn promote(&self) -> CoordinatorResult<TakeoverReport> {
+        let last_seq = self.failover.stop_following().await?;
+        let running = failover::running_sessions(self.session_store.as_ref()).await?;
+        self.recover_sessions().await?;
+
+        let mut report = TakeoverReport {
+            last_seq,
+            resumed: Vec::new(),
+            skipped: Vec::new(),
+        };
+        for session in running {
+            match self.resume_transfer(&session.session_id).await {
+                Ok(()) => report.resumed.push(session.session_id),
+                Err(e) => report.skipped.push(SkippedSession {
+                    session_id: session.session_id,
+                    reason: e.to_string(),
+                }),
+            }
+        }
+        tracing::info!(
+            last_seq = report.last_seq,
+            resumed = report.resumed.len(),
+            skipped = report.skipped.len(),
+            "Promoted to active"
+        );
+        Ok(report)
     }
 
     /// Cancel a transfer
     pub async fn cancel_transfer(&self, session_id: &str) -> CoordinatorResult<()> {
-        if let Some(state_machine) = self.active_transfers.get(session_id) {
-            state_machine.transition(TransferEvent::Cancel)?;
-        }
+        self.send.cancel(session_id).await
+    }
 
-        self.session_store
-            .update_status(
-                session_id,
-                SessionStatus::Failed("Cancelled by user".into()),
-            )
-            .await?;
-        self.active_transfers.remove(session_id);
+    /// Record that a relay took over the chunks the receiver hasn't got
+    ///
+    /// The transfer stops sending and is reported as partially delivered
+    /// until [`complete_relay_delivery`](Self::complete_relay_delivery).
+    pub async fn record_relay_handoff(
+        &self,
+        session_id: &str,
+        held_by_relay: u32,
+    ) -> CoordinatorResult<()> {
+        self.send
+            .record_relay_handoff(session_id, held_by_relay)
+            .await
+    }
 
-        Ok(())
+    /// Record that the relay delivered the rest of a partially delivered transfer
+    pub async fn complete_relay_delivery(&self, session_id: &str) -> CoordinatorResult<()> {
+        self.send.complete_relay_delivery(session_id).await
     }
 
     /// Get transfer progress
@@ -313,43 +587,120 @@ impl TransferCoordinator {
             .await?
             .ok_or_else(|| CoordinatorError::TransferNotFound(session_id.to_string()))?;
 
-        let completed = session.completed_chunks.len() as u32;
-        let total = session.manifest.total_chunks;
-        let speed = session.current_speed_bps();
-
-        Ok(TransferProgress {
-            session_id: session_id.to_string(),
-            completed_chunks: completed,
-            total_chunks: total,
-            bytes_transferred: session.metrics.bytes_transferred,
-            total_bytes: session.manifest.total_size,
-            progress_percent: session.progress_percent(),
-            status: session.status,
-            current_speed_bps: speed,
-        })
+        Ok(TransferProgress::of(
+            &session,
+            self.receive.pending_relay_resends(session_id),
+        ))
+    }
+
+    /// Throughput samples of a transfer, oldest first, taken every
+    /// [`SAMPLE_INTERVAL`](crate::coordinator::SAMPLE_INTERVAL) while it runs
+    pub async fn get_timeseries(&self, session_id: &str) -> CoordinatorResult<Vec<ProgressSample>> {
+        if let Some(samples) = self.send.live_timeseries(session_id) {
+            return Ok(samples);
+        }
+        if self.session_store.load(session_id).await?.is_none() {
+            return Err(CoordinatorError::TransferNotFound(session_id.to_string()));
+        }
+        Ok(self.session_store.load_timeseries(session_id).await?)
     }
 
     /// Get current state
     pub fn get_state(&self, session_id: &str) -> Option<TransferState> {
-        self.active_transfers
-            .get(session_id)
-            .map(|sm| sm.current_state())
+        self.send.state(session_id)
+    }
+
+    /// State of an active or recently finished transfer (keeps the outcome)
+    ///
+    /// Transfers waiting for a slot report `Idle`.
+    pub fn get_recent_state(&self, session_id: &str) -> Option<TransferState> {
+        self.send.recent_state(session_id)
+    }
+
+    /// State of a transfer, falling back to the session store once it has
+    /// been evicted from memory
+    pub async fn get_transfer_state(
+        &self,
+        session_id: &str,
+    ) -> CoordinatorResult<Option<TransferState>> {
+        if let Some(state) = self.get_recent_state(session_id) {
+            return Ok(Some(state));
+        }
+        let session = self.session_store.load(session_id).await?;
+        Ok(session.map(|s| TransferState::from(&s.status)))
+    }
+
+    /// Query persisted sessions with filtering, sorting and pagination
+    pub async fn list_sessions(&self, query: &SessionQuery) -> CoordinatorResult<SessionPage> {
+        Ok(self.session_store.query(query).await?)
+    }
+
+    /// Sessions that carried a file with the given name or checksum
+    pub async fn search_sessions(
+        &self,
+        search: &SessionSearch,
+    ) -> CoordinatorResult<Vec<SessionState>> {
+        Ok(self.session_store.search(search).await?)
+    }
+
+    /// Directories receivers may ask for files from
+    pub fn catalog_shares(&self) -> Vec<CatalogShare> {
+        self.catalog.shares()
+    }
+
+    /// Replace the shared directories; names must be unique, non-empty and
+    /// free of `/`
+    pub fn set_catalog_shares(&self, shares: Vec<CatalogShare>) -> CoordinatorResult<()> {
+        self.catalog.set_shares(shares)
+    }
+
+    /// Files receivers may ask for, sorted by id
+    pub async fn catalog(&self) -> CoordinatorResult<Vec<CatalogEntry>> {
+        self.catalog.list().await
+    }
+
+    /// Send the catalog file `id` to the receiver that asked for it
+    ///
+    /// Fails with [`CoordinatorError::NotInCatalog`] unless `id` names a
+    /// file in one of the shares; otherwise behaves as
+    /// [`send_file_with_options`](Self::send_file_with_options).
+    pub async fn send_catalog_file(
+        &self,
+        id: &str,
+        priority: Priority,
+        receiver_addr: SocketAddr,
+        options: TransferOptions,
+    ) -> CoordinatorResult<String> {
+        let path = self.catalog_file(id).await?;
+        self.send_file_with_options(path, priority, Some(receiver_addr), options)
+            .await
+    }
+
+    /// Path of the catalog file `id`
+    pub async fn catalog_file(&self, id: &str) -> CoordinatorResult<PathBuf> {
+        self.catalog.resolve(id).await
+    }
+
+    /// Check stored files against the manifests they were sent with,
+    /// hashing at most `concurrency` at a time
+    ///
+    /// Reports come back in the order of `targets`.
+    pub async fn verify_files(
+        &self,
+        targets: Vec<VerifyTarget>,
+        concurrency: usize,
+    ) -> Vec<FileVerification> {
+        verify::verify_files(self.session_store.as_ref(), targets, concurrency).await
     }
 
     /// List active transfers
     pub fn list_active(&self) -> Vec<String> {
-        self.active_transfers
-            .iter()
-            .map(|e| e.key().clone())
-            .collect()
+        self.send.list_active()
     }
 
     /// List all recent transfers (including completed/failed)
     pub fn list_recent(&self) -> Vec<String> {
-        self.recent_transfers
-            .iter()
-            .map(|e| e.key().clone())
-            .collect()
+        self.send.list_recent()
     }
 
     /// Get queue statistics
@@ -357,485 +708,412 @@ impl TransferCoordinator {
         self.queue.stats()
     }
 
+    /// Queued chunks per session and the next `next` chunks to be sent,
+    /// without dequeuing them
+    pub fn inspect_queue(&self, next: usize) -> crate::priority::QueueSnapshot {
+        let mut snapshot = self.queue.inspect(next);
+        for file in &mut snapshot.files {
+            file.session_id = self.send.session_for_file(&file.file_id);
+        }
+        for chunk in &mut snapshot.next {
+            chunk.session_id = self.send.session_for_file(&chunk.file_id);
+        }
+        snapshot
+    }
+
+    /// Alert when a priority class waits too long, replacing any earlier policy
+    ///
+    /// `None` stops alerting. Needs a Tokio runtime to run the monitor.
+    pub fn set_starvation_policy(&self, policy: Option<StarvationPolicy>) {
+        let mut monitor = self.starvation_monitor.lock();
+        if let Some(previous) = monitor.take() {
+            previous.abort();
+        }
+        let Some(policy) = policy else {
+            return;
+        };
+        match tokio::runtime::Handle::try_current() {
+            Ok(runtime) => {
+                let queue = Arc::downgrade(&self.queue);
+                *monitor = Some(runtime.spawn(StarvationMonitor::new(policy).run(queue)));
+            }
+            Err(_) => tracing::warn!("No runtime; starvation alerts disabled"),
+        }
+    }
+
     /// Get queue capacity info
     pub fn queue_capacity(&self) -> (usize, usize, f64) {
         self.queue.capacity_info()
     }
 
-    /// Get the internal chunk manager (for erasure config access)
-    pub fn chunk_manager(&self) -> &ChunkManager {
-        &self.chunk_manager
-    }
-
-    /// Count completed (terminal) transfers
-    pub fn count_completed(&self) -> usize {
-        self.recent_transfers
-            .iter()
-            .filter(|e| e.value().current_state().is_terminal())
-            .count()
+    /// Chunk manager new transfers are split with
+    pub fn chunk_manager(&self) -> Arc<ChunkManager> {
+        self.defaults.current()
     }
 
-    /// Get the adaptive erasure coder (for metrics/simulation)
-    pub fn adaptive_coder(&self) -> &AdaptiveErasureCoder {
-        &self.adaptive_coder
+    /// Chunk manager for a new transfer: the defaults, with any chunk size or
+    /// shard counts its options override
+    pub fn chunk_manager_for(
+        &self,
+        options: &TransferOptions,
+    ) -> CoordinatorResult<Arc<ChunkManager>> {
+        defaults::chunk_manager_for(
+            self.chunk_manager(),
+            options,
+            self.transport.max_chunk_size(),
+        )
     }
 
-    /// Get simulation counters
-    pub fn sim_chunks_sent(&self) -> u64 {
-        self.sim_chunks_sent.load(Ordering::Relaxed)
+    /// How a file of `file_size` bytes sent at `priority` with `options`
+    /// would be split, and what it would survive
+    ///
+    /// Fails like a transfer would if the options' layout is invalid on its
+    /// own; layouts that only fail for this size come back as a plan that
+    /// isn't feasible.
+    pub fn plan_transfer(
+        &self,
+        file_size: u64,
+        priority: Priority,
+        options: &TransferOptions,
+    ) -> CoordinatorResult<TransferPlan> {
+        Ok(self.chunk_manager_for(options)?.plan(file_size, priority))
     }
 
-    pub fn sim_chunks_lost(&self) -> u64 {
-        self.sim_chunks_lost.load(Ordering::Relaxed)
+    /// Create or replace a transfer profile, returning it as stored
+    pub async fn save_profile(
+        &self,
+        profile: TransferProfile,
+    ) -> CoordinatorResult<TransferProfile> {
+        profiles::validate_profile_name(&profile.name)?;
+        self.chunk_manager_for(&profile.options)?;
+        Ok(self.session_store.save_profile(&profile).await?)
     }
 
-    pub fn sim_chunks_recovered(&self) -> u64 {
-        self.sim_chunks_recovered.load(Ordering::Relaxed)
+    pub async fn profile(&self, name: &str) -> CoordinatorResult<TransferProfile> {
+        self.session_store
+            .load_profile(name)
+            .await?
+            .ok_or_else(|| CoordinatorError::ProfileNotFound(name.to_string()))
     }
 
-    /// Get the most recent real QUIC path stats (from an actual transfer)
-    pub fn last_quic_stats(&self) -> QuicPathStats {
-        self.last_quic_stats.read().clone()
+    /// Every transfer profile, by name
+    pub async fn list_profiles(&self) -> CoordinatorResult<Vec<TransferProfile>> {
+        Ok(self.session_store.list_profiles().await?)
     }
 
-    /// Get the transport layer (for reading network stats)
-    pub fn transport(&self) -> &QuicTransport {
-        &self.transport
-    }
-
-    /// Simulate packet loss at a given rate.
-    /// Directly sets the observed loss rate (no EMA smoothing) so the
-    /// dashboard immediately reflects the slider value.
-    pub fn simulate_packet_loss(&self, loss_rate: f32, num_samples: u32) {
-        // Directly set the loss rate — no smoothing lag
-        self.adaptive_coder.set_loss_rate(loss_rate);
-
-        let losses = (num_samples as f32 * loss_rate) as u32;
-        let successes = num_samples - losses;
-
-        // Update simulation counters for the data flow visualization
-        self.sim_chunks_sent
-            .fetch_add(num_samples as u64, Ordering::Relaxed);
-        self.sim_chunks_lost
-            .fetch_add(losses as u64, Ordering::Relaxed);
-
-        // Recovered = losses that could be recovered (up to parity capacity)
-        let status = self.adaptive_coder.status();
-        let max_recoverable = status.parity_shards as u32;
-        let recovered = losses.min(max_recoverable);
-        self.sim_chunks_recovered
-            .fetch_add(recovered as u64, Ordering::Relaxed);
-
-        // Also feed samples so future incremental updates work correctly
-        for _ in 0..successes {
-            self.adaptive_coder.record_success();
-        }
-        for _ in 0..losses {
-            self.adaptive_coder.record_loss();
+    pub async fn delete_profile(&self, name: &str) -> CoordinatorResult<()> {
+        if !self.session_store.delete_profile(name).await? {
+            return Err(CoordinatorError::ProfileNotFound(name.to_string()));
         }
+        Ok(())
     }
 
-    /// Simulate a file transfer with a given packet loss rate.
-    /// Runs multiple trials to produce statistically meaningful results.
-    pub async fn simulate_file_transfer(
+    /// Store an uploaded benchmark report for the dashboard
+    pub async fn save_benchmark(
         &self,
-        file_path: PathBuf,
-        loss_rate: f32,
-    ) -> CoordinatorResult<SimulateFileResult> {
-        use rand::Rng;
-
-        const NUM_TRIALS: u32 = 10;
-
-        // Set the adaptive coder to reflect the simulated loss rate
-        self.adaptive_coder.set_loss_rate(loss_rate);
-
-        // Get the adaptive parity level for this loss rate
-        let adaptive_parity = self.adaptive_coder.current_parity();
-
-        // Split the file into chunks using smart chunk sizing for simulation
-        let file_id = file_path.to_string_lossy().to_string();
-        let file_size = tokio::fs::metadata(&file_path).await?.len();
-        let sim_chunk_size = crate::chunk::ChunkManager::simulation_chunk_size(file_size);
-        let (manifest, chunks) = self
-            .chunk_manager
-            .split_file_with_chunk_size(
-                &file_path,
-                file_id,
-                Priority::Normal,
-                sim_chunk_size,
-                Some(adaptive_parity),
-            )
-            .await?;
-
-        let total_chunks = chunks.len() as u32;
-        let data_chunks = manifest.data_chunks as usize;
-        let parity_chunks = manifest.parity_chunks as usize;
-
-        let mut rng = rand::thread_rng();
-        let mut successful_trials: u32 = 0;
-        let mut total_lost: u32 = 0;
-        let mut total_recovered: u32 = 0;
-        let mut min_lost: u32 = u32::MAX;
-        let mut max_lost: u32 = 0;
-
-        for _ in 0..NUM_TRIALS {
-            let mut lost_count: u32 = 0;
-
-            for _ in &chunks {
-                let roll: f32 = rng.gen();
-                if roll < loss_rate {
-                    lost_count += 1;
-                }
-            }
-
-            let surviving = total_chunks - lost_count;
-            let recoverable = surviving as usize >= data_chunks;
+        label: Option<&str>,
+        report: &serde_json::Value,
+    ) -> CoordinatorResult<BenchmarkRecord> {
+        profiles::validate_benchmark(label, report)?;
+        Ok(self.session_store.save_benchmark(label, report).await?)
+    }
 
-            if recoverable {
-                successful_trials += 1;
-                total_recovered += lost_count; // all lost chunks effectively recovered
-            } else {
-                let max_rec = (parity_chunks as u32).min(lost_count);
-                total_recovered += max_rec;
-            }
+    pub async fn benchmark(&self, id: &str) -> CoordinatorResult<BenchmarkRecord> {
+        self.session_store
+            .load_benchmark(id)
+            .await?
+            .ok_or_else(|| CoordinatorError::BenchmarkNotFound(id.to_string()))
+    }
 
-            total_lost += lost_count;
-            min_lost = min_lost.min(lost_count);
-            max_lost = max_lost.max(lost_count);
-        }
+    /// The `limit` most recent benchmark reports, newest first
+    pub async fn list_benchmarks(&self, limit: u32) -> CoordinatorResult<Vec<BenchmarkRecord>> {
+        Ok(self.session_store.list_benchmarks(limit).await?)
+    }
 
-        if min_lost == u32::MAX {
-            min_lost = 0;
+    pub async fn delete_benchmark(&self, id: &str) -> CoordinatorResult<()> {
+        if !self.session_store.delete_benchmark(id).await? {
+            return Err(CoordinatorError::BenchmarkNotFound(id.to_string()));
         }
+        Ok(())
+    }
 
-        let avg_lost = total_lost as f64 / NUM_TRIALS as f64;
-        let avg_recovered = total_recovered as f64 / NUM_TRIALS as f64;
-        let success_rate = successful_trials as f64 / NUM_TRIALS as f64 * 100.0;
+    /// Chunking and erasure defaults in effect for new transfers
+    pub fn transfer_defaults(&self) -> TransferDefaults {
+        TransferDefaults::of(&self.defaults.current())
+    }
 
-        // Update simulation counters for the live dashboard (aggregate across all trials)
-        self.sim_chunks_sent.fetch_add(
-            (total_chunks as u64) * (NUM_TRIALS as u64),
-            Ordering::Relaxed,
-        );
-        self.sim_chunks_lost
-            .fetch_add(total_lost as u64, Ordering::Relaxed);
-        self.sim_chunks_recovered
-            .fetch_add(total_recovered as u64, Ordering::Relaxed);
-
-        let file_name = file_path
-            .file_name()
-            .unwrap_or_default()
-            .to_string_lossy()
-            .to_string();
-
-        Ok(SimulateFileResult {
-            file_name,
-            file_size_bytes: manifest.total_size,
-            total_chunks,
-            data_chunks,
-            parity_chunks,
-            num_trials: NUM_TRIALS,
-            successful_trials,
-            success_rate,
-            avg_chunks_lost: avg_lost,
-            avg_chunks_recovered: avg_recovered,
-            min_chunks_lost: min_lost,
-            max_chunks_lost: max_lost,
-        })
+    /// Change the shard counts new transfers start from
+    pub fn set_erasure_defaults(
+        &self,
+        erasure: ErasureDefaults,
+        changed_by: &str,
+    ) -> CoordinatorResult<TransferDefaults> {
+        self.defaults.update(
+            DefaultsSection::Erasure,
+            changed_by,
+            self.transport.max_chunk_size(),
+            |defaults| defaults.erasure = erasure,
+        )
     }
 
-    /// Run a comparison simulation: for each loss rate (0%..40%), run N trials
-    /// for both TCP-style (no FEC, any lost chunk = failure) and RESILIENT
-    /// (Reed-Solomon parity). Returns per-point success rates.
-    pub async fn simulate_comparison(
+    /// Change how new transfers are cut into chunks
+    pub fn set_chunking_defaults(
         &self,
-        file_path: PathBuf,
-        trials_per_point: u32,
-    ) -> CoordinatorResult<ComparisonResult> {
-        use rand::Rng;
-
-        let file_id = file_path.to_string_lossy().to_string();
-        let file_size = tokio::fs::metadata(&file_path).await?.len();
-        let sim_chunk_size = crate::chunk::ChunkManager::simulation_chunk_size(file_size);
-        // Use max parity (25 shards at severe loss) for comparison view so we
-        // show the full RESILIENT recovery capability across all loss rates.
-        let max_parity = 25_usize; // matches AdaptiveErasureConfig::max_parity_shards
-        let (manifest, chunks) = self
-            .chunk_manager
-            .split_file_with_chunk_size(
-                &file_path,
-                file_id,
-                Priority::Normal,
-                sim_chunk_size,
-                Some(max_parity),
-            )
-            .await?;
-
-        let total_chunks = chunks.len() as u32;
-        let data_chunks = manifest.data_chunks as usize;
-        let parity_chunks = manifest.parity_chunks as usize;
-
-        let file_name = file_path
-            .file_name()
-            .unwrap_or_default()
-            .to_string_lossy()
-            .to_string();
-
-        let mut rng = rand::thread_rng();
-        let mut points = Vec::new();
-
-        // Sweep loss from 0% to 40% in 1% steps
-        for loss_pct in 0..=40u32 {
-            let loss_rate = loss_pct as f32 / 100.0;
-
-            let mut tcp_successes: u32 = 0;
-            let mut resilient_successes: u32 = 0;
-            let mut tcp_total_lost: u32 = 0;
-            let mut resilient_total_lost: u32 = 0;
-            let mut resilient_total_recovered: u32 = 0;
-
-            for _ in 0..trials_per_point {
-                let mut lost: u32 = 0;
-                for _ in 0..total_chunks {
-                    if rng.gen::<f32>() < loss_rate {
-                        lost += 1;
-                    }
-                }
+        chunking: ChunkingDefaults,
+        changed_by: &str,
+    ) -> CoordinatorResult<TransferDefaults> {
+        self.defaults.update(
+            DefaultsSection::Chunking,
+            changed_by,
+            self.transport.max_chunk_size(),
+            |defaults| defaults.chunking = chunking,
+        )
+    }
 
-                // TCP: no FEC, any lost chunk means the file is incomplete
-                if lost == 0 {
-                    tcp_successes += 1;
-                }
-                tcp_total_lost += lost;
-
-                // RESILIENT: can tolerate up to parity_chunks lost
-                let surviving = total_chunks - lost;
-                if surviving as usize >= data_chunks {
-                    resilient_successes += 1;
-                    resilient_total_recovered += lost;
-                } else {
-                    let max_rec = (parity_chunks as u32).min(lost);
-                    resilient_total_recovered += max_rec;
-                }
-                resilient_total_lost += lost;
-            }
+    /// Changes to the defaults since startup, oldest first
+    pub fn config_changes(&self) -> Vec<ConfigChange> {
+        self.defaults.changes()
+    }
 
-            let t = trials_per_point as f64;
-            points.push(ComparisonPoint {
-                loss_percent: loss_pct,
-                tcp_success_rate: tcp_successes as f64 / t * 100.0,
-                resilient_success_rate: resilient_successes as f64 / t * 100.0,
-                tcp_avg_chunks_lost: tcp_total_lost as f64 / t,
-                resilient_avg_chunks_lost: resilient_total_lost as f64 / t,
-                resilient_avg_recovered: resilient_total_recovered as f64 / t,
-            });
-        }
+    /// Count completed (terminal) transfers
+    pub fn count_completed(&self) -> usize {
+        self.send.count_completed()
+    }
 
-        Ok(ComparisonResult {
-            file_name,
-            file_size_bytes: manifest.total_size,
-            total_chunks,
-            data_chunks,
-            parity_chunks,
-            trials_per_point,
-            points,
-        })
+    /// Current retention bounds for finished transfers
+    pub fn retention(&self) -> RetentionPolicy {
+        self.send.policy().retention
     }
 
-    /// Get uptime in seconds
-    pub fn uptime_seconds(&self) -> u64 {
-        self.start_time.elapsed().as_secs()
+    /// Change the retention bounds; the next sweep applies them
+    pub fn set_retention(&self, policy: RetentionPolicy) {
+        self.send.update_policy(&mut |p| p.retention = policy);
     }
 
-    /// Transfer worker - handles chunk transfer loop
-    async fn transfer_worker(
-        &self,
-        session_id: String,
-        manifest: FileManifest,
-        chunks: Vec<Chunk>,
-        receiver_addr: Option<SocketAddr>,
-    ) -> CoordinatorResult<()> {
-        let state_machine = self
-            .active_transfers
-            .get(&session_id)
-            .ok_or_else(|| CoordinatorError::TransferNotFound(session_id.clone()))?
-            .clone();
+    /// Chunks each transfer may have in the shared queue at once (0 = all)
+    pub fn session_window(&self) -> usize {
+        self.send.policy().session_window
+    }
 
-        // Get resume info
-        let session = self
-            .session_store
-            .load(&session_id)
-            .await?
-            .ok_or_else(|| CoordinatorError::TransferNotFound(session_id.clone()))?;
+    /// Change the per-transfer queue window; transfers started afterwards
+    /// use it
+    pub fn set_session_window(&self, chunks: usize) {
+        self.send.update_policy(&mut |p| p.session_window = chunks);
+    }
 
-        let completed_set = &session.completed_chunks;
-        let mut chunks_to_transfer: Vec<u32> = (0..manifest.total_chunks)
-            .filter(|n| !completed_set.contains(n))
-            .collect();
+    /// How files are re-checked and locked while they are sent
+    pub fn source_watch(&self) -> SourceWatch {
+        self.send.policy().source_watch
+    }
 
-        // Enqueue chunks (only if we have them)
-        for chunk in chunks {
-            if !completed_set.contains(&chunk.metadata.sequence_number) {
-                self.queue.enqueue(chunk)?;
-            }
-        }
+    /// Change how source files are watched; transfers started or resumed
+    /// afterwards use it
+    pub fn set_source_watch(&self, watch: SourceWatch) {
+        self.send.update_policy(&mut |p| p.source_watch = watch);
+    }
 
-        // Signal first chunk completed to transition state
-        if !chunks_to_transfer.is_empty() {
-            state_machine.transition(TransferEvent::ChunkCompleted { chunk_number: 0 })?;
-        }
+    /// Current budget for resending shards the receiver reports lost
+    pub fn retransmit_policy(&self) -> RetransmitPolicy {
+        self.send.policy().retransmit
+    }
 
-        // Establish connection once if receiver address provided
-        let connection = if let Some(addr) = receiver_addr {
-            println!("Connecting to receiver at {addr}...");
-            match self.transport.connect(addr).await {
-                Ok(conn) => {
-                    println!("Connected to receiver at {addr}");
-                    Some(conn)
-                }
-                Err(e) => {
-                    eprintln!("Failed to connect to receiver at {addr}: {e}");
-                    state_machine.transition(TransferEvent::NetworkFailure {
-                        path_id: "default".to_string(),
-                    })?;
-                    return Err(e.into());
-                }
-            }
-        } else {
-            None
-        };
+    /// Change the retransmission budget; transfers started afterwards use it
+    pub fn set_retransmit_policy(&self, policy: RetransmitPolicy) {
+        self.send.update_policy(&mut |p| p.retransmit = policy);
+    }
 
-        // Transfer loop
-        while !chunks_to_transfer.is_empty() {
-            // Check if paused or cancelled
-            let current_state = state_machine.current_state();
-            if current_state.is_paused() {
-                self.session_store
-                    .update_status(&session_id, SessionStatus::Paused)
-                    .await?;
-                break;
-            }
-            if current_state.is_terminal() {
-                break;
-            }
+    /// Current connect timeouts
+    pub fn connect_policy(&self) -> ConnectPolicy {
+        self.send.policy().connect
+    }
 
-            // Dequeue next chunk
-            match self.queue.dequeue() {
-                Ok(chunk) => {
-                    let chunk_num = chunk.metadata.sequence_number;
-                    let chunk_bytes = chunk.data.len() as u64;
-
-                    // Actually send chunk over network (if connection established)
-                    if let Some(ref conn) = connection {
-                        // Send with retry (max 3 attempts)
-                        if let Err(e) = self.transport.send_with_retry(conn, &chunk, 3).await {
-                            eprintln!("Failed to send chunk {chunk_num}: {e}");
-                            // Mark as failed but continue
-                            self.session_store
-                                .mark_chunk_failed(&session_id, chunk_num)
-                                .await?;
-                            continue;
-                        }
-                        // Update real QUIC stats after each chunk for live dashboard
-                        let quic_stats = QuicTransport::connection_stats(conn);
-                        *self.last_quic_stats.write() = quic_stats;
-                    } else {
-                        // No receiver address - simulate for local testing
-                        time::sleep(Duration::from_millis(10)).await;
-                    }
+    /// Change the connect timeouts; transfers started afterwards use them
+    pub fn set_connect_policy(&self, policy: ConnectPolicy) {
+        self.send.update_policy(&mut |p| p.connect = policy);
+    }
 
-                    // Mark as completed with actual bytes transferred
-                    self.session_store
-                        .mark_chunk_completed_with_bytes(&session_id, chunk_num, chunk_bytes)
-                        .await?;
+    /// Relay that Critical transfers whose receiver can't be reached in
+    /// time are handed to
+    pub fn set_fallback_relay(&self, relay: Option<Arc<RelayNode>>) {
+        self.send.set_fallback_relay(relay)
+    }
 
-                    // Update state
-                    state_machine.transition(TransferEvent::ChunkCompleted {
-                        chunk_number: chunk_num,
-                    })?;
+    /// Evict finished transfers beyond the retention bounds now
+    ///
+    /// Returns how many were evicted.
+    pub fn evict_finished(&self) -> usize {
+        self.send.evict_finished()
+    }
 
-                    // Remove from list
-                    chunks_to_transfer.retain(|n| *n != chunk_num);
+    /// Current schedule for session database maintenance
+    pub fn maintenance_policy(&self) -> MaintenancePolicy {
+        self.maintenance.policy()
+    }
 
-                    // Check if all chunks transferred
-                    if chunks_to_transfer.is_empty() {
-                        state_machine.transition(TransferEvent::TransferComplete)?;
-                        break;
-                    }
-                }
-                Err(crate::priority::QueueError::QueueEmpty) => {
-                    // No chunks available, wait a bit
-                    time::sleep(Duration::from_millis(100)).await;
-                }
-                Err(e) => return Err(e.into()),
-            }
-        }
+    /// Change the maintenance schedule; the next pass is due one new
+    /// interval from now
+    pub fn set_maintenance_policy(&self, policy: MaintenancePolicy) {
+        self.maintenance.set_policy(policy);
+    }
 
-        // Capture real QUIC stats from the connection after transfer
-        if let Some(ref conn) = connection {
-            let quic_stats = QuicTransport::connection_stats(conn);
-            *self.last_quic_stats.write() = quic_stats;
-        }
+    /// Maintain the session database now, with the current policy's settings
+    ///
+    /// Returns `None` if the session store needs no maintenance.
+    pub async fn run_maintenance(&self) -> CoordinatorResult<Option<MaintenanceReport>> {
+        Ok(self.maintenance.run(self.session_store.as_ref()).await?)
+    }
 
-        // Check if completed
-        let session = self
-            .session_store
-            .load(&session_id)
-            .await?
-            .ok_or_else(|| CoordinatorError::TransferNotFound(session_id.clone()))?;
-
-        if session.status == SessionStatus::Completed {
-            state_machine.transition(TransferEvent::TransferComplete)?;
-            self.active_transfers.remove(&session_id);
-            // Remove file-to-session mapping so the same file can be re-uploaded
-            self.file_to_session.remove(&session.file_id);
-            // Keep in recent_transfers for display
+    /// Result of the latest maintenance pass, scheduled or not
+    pub fn last_maintenance(&self) -> Option<MaintenanceReport> {
+        self.maintenance.last()
+    }
+
+    /// Size and row counts of the session database; `None` if the session
+    /// store doesn't report them
+    pub async fn storage_stats(&self) -> CoordinatorResult<Option<StorageStats>> {
+        let stats = self.session_store.storage_stats().await?;
+        if let Some(stats) = &stats {
+            recorder::set_session_db_stats(stats);
         }
+        Ok(stats)
+    }
 
-        Ok(())
+    /// Notifications of transfer, path and relay events from now on
+    ///
+    /// Each call returns an independent stream. A subscriber that falls
+    /// [`EVENT_BUFFER`](crate::coordinator::EVENT_BUFFER) events behind
+    /// misses the oldest ones.
+    pub fn subscribe(&self) -> impl Stream<Item = CoordinatorEvent> + Send + 'static {
+        self.events.subscribe()
     }
-}
 
-impl Clone for TransferCoordinator {
-    fn clone(&self) -> Self {
-        Self {
-            chunk_manager: self.chunk_manager.clone(),
-            verifier: self.verifier.clone(),
-            transport: self.transport.clone(),
-            queue: self.queue.clone(),
-            session_store: self.session_store.clone(),
-            active_transfers: self.active_transfers.clone(),
-            recent_transfers: self.recent_transfers.clone(),
-            file_to_session: self.file_to_session.clone(),
-            adaptive_coder: self.adaptive_coder.clone(),
-            sim_chunks_sent: self.sim_chunks_sent.clone(),
-            sim_chunks_lost: self.sim_chunks_lost.clone(),
-            sim_chunks_recovered: self.sim_chunks_recovered.clone(),
-            last_quic_stats: self.last_quic_stats.clone(),
-            start_time: self.start_time,
+    /// Like [`subscribe`](Self::subscribe), with each event's sequence number
+    pub fn subscribe_sequenced(&self) -> impl Stream<Item = SequencedEvent> + Send + 'static {
+        self.events.subscribe_sequenced()
+    }
+
+    /// Current progress of a transfer and up to `limit` of its latest events
+    ///
+    /// To follow a transfer that may already be running without missing or
+    /// repeating anything, call [`subscribe_sequenced`](Self::subscribe_sequenced)
+    /// first, then this, and skip live events numbered at or below
+    /// [`SessionBackfill::last_seq`]. Only the last
+    /// [`EVENT_HISTORY`](crate::coordinator::EVENT_HISTORY) events across all
+    /// transfers are kept.
+    pub async fn session_backfill(
+        &self,
+        session_id: &str,
+        limit: usize,
+    ) -> CoordinatorResult<SessionBackfill> {
+        let (last_seq, events) = self.events.recent(session_id, limit);
+        let progress = self.get_progress(session_id).await?;
+        Ok(SessionBackfill {
+            progress,
+            events,
+            last_seq,
+        })
+    }
+
+    /// Get the hook registry (register scanners and filters here)
+    pub fn hooks(&self) -> &HookRegistry {
+        &self.hooks
+    }
+
+    /// Get the transport layer (for reading network stats)
+    pub fn transport(&self) -> &dyn Transport {
+        self.transport.as_ref()
+    }
+
+    /// The QUIC endpoint transfers go over, unless the transport is another
+    pub fn quic_transport(&self) -> Option<Arc<QuicTransport>> {
+        self.transport.clone().quic()
+    }
+
+    /// Sends, resumes, pauses and cancels transfers
+    pub fn sender(&self) -> &dyn SendService {
+        self.send.as_ref()
+    }
+
+    /// Repair requests and relay reports for transfers this coordinator sent
+    pub fn receive(&self) -> &dyn ReceiveService {
+        self.receive.as_ref()
+    }
+
+    /// Simulated transfers and their counters
+    pub fn simulation(&self) -> &dyn SimulationService {
+        self.simulation.as_ref()
+    }
+
+    /// Uptime, path stats and health checks
+    pub fn stats(&self) -> &dyn StatsService {
+        self.stats.as_ref()
+    }
+
+    /// Adaptive erasure coder, for metrics and simulation
+    ///
+    /// Receivers' stats reports feed it while their transfers run.
+    pub fn adaptive_coder(&self) -> &Arc<AdaptiveErasureCoder> {
+        &self.adaptive_coder
+    }
+
+    /// Sizes of the coordinator's maps and queues, for spotting leaks
+    pub fn resource_usage(&self) -> ResourceUsage {
+        let (relay_resends, audit_suspects) = self.receive.tracked_sessions();
+        ResourceUsage {
+            relay_resends,
+            audit_suspects,
+            queued_chunks: self.queue.stats().total_pending(),
+            connections: self.transport.connection_count(),
+            ..self.send.resource_usage()
         }
     }
 }
 
-#[cfg(test)]
+/// Read `reader` to its end, refusing streams longer than `max` bytes
+/// (0 = no limit)
+async fn read_stream(reader: impl AsyncRead + Unpin, max: u64) -> CoordinatorResult<Bytes> {
+    let limit = if max == 0 {
+        u64::MAX
+    } else {
+        max.saturating_add(1)
+    };
+    let mut data = Vec::new();
+    reader.take(limit).read_to_end(&mut data).await?;
+    if max > 0 && data.len() as u64 > max {
+        return Err(CoordinatorError::InvalidSource(format!(
+            "stream is longer than {max} bytes"
+        )));
+    }
+    Ok(Bytes::from(data))
+}
+
+#[cfg(all(test, feature = "sqlite"))]
 mod tests {
     use super::*;
+    use crate::coordinator::health::{self, HealthPolicy};
+    use crate::coordinator::simulation::ComparisonResult;
+    use crate::coordinator::types::ResendRoute;
+    use crate::hooks::{HookContext, HookPoint};
+    use crate::network::quic_transport::STREAM_CANCELLED;
+    use crate::relay::node::RelayEvent;
+    use crate::relay::{ExpiredNotice, RouteInfo};
+    use crate::session::{SessionStatus, SessionStore, TransferTags};
     use std::io::Write;
-    #[allow(unused_imports)]
-    use std::path::PathBuf;
+    use std::time::Instant;
     use tempfile::NamedTempFile;
+    use tokio::sync::mpsc;
+    use tokio::time;
 
     async fn create_test_coordinator() -> TransferCoordinator {
         use crate::network::ConnectionConfig;
 
         let chunk_manager = ChunkManager::new(256 * 1024, 10, 3).unwrap();
-        let verifier = IntegrityVerifier;
         let config = ConnectionConfig::default();
         let transport = QuicTransport::new(config).await.unwrap();
         let queue = PriorityQueue::new(1_000_000);
         let session_store = SessionStore::new_in_memory().await.unwrap();
 
-        TransferCoordinator::new(chunk_manager, verifier, transport, queue, session_store)
+        TransferCoordinator::new(chunk_manager, transport, queue, session_store)
     }
 
     #[tokio::test]
@@ -863,6 +1141,585 @@ mod tests {
         assert_eq!(coordinator.list_active().len(), 1);
     }
 
+    #[tokio::test]
+    async fn test_subscribers_see_transfer_lifecycle() {
+        use futures::StreamExt;
+
+        let coordinator = create_test_coordinator().await;
+        let mut events = Box::pin(coordinator.subscribe());
+
+        let mut temp_file = NamedTempFile::new().unwrap();
+        temp_file.write_all(&[7u8; 4096]).unwrap();
+        temp_file.flush().unwrap();
+        let session_id = coordinator
+            .send_file(temp_file.path().to_path_buf(), Priority::High, None)
+            .await
+            .unwrap();
+
+        let mut progress = 0;
+        let completed = time::timeout(Duration::from_secs(10), async {
+            while let Some(event) = events.next().await {
+                match event {
+                    CoordinatorEvent::TransferStarted {
+                        session_id: id,
+                        priority,
+                        ..
+                    } => {
+                        assert_eq!(id, session_id);
+                        assert_eq!(priority, Priority::High);
+                    }
+                    CoordinatorEvent::TransferProgress { .. } => progress += 1,
+                    CoordinatorEvent::TransferCompleted { session_id: id, .. } => return id,
+                    other => panic!("unexpected event: {:?}", other),
+                }
+            }
+            panic!("event stream ended");
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(completed, session_id);
+        assert!(progress > 0);
+    }
+
+    #[tokio::test]
+    async fn test_tags_kept_with_session_and_events() {
+        use futures::StreamExt;
+
+        let coordinator = create_test_coordinator().await;
+        let mut events = Box::pin(coordinator.subscribe());
+        let mut temp_file = NamedTempFile::new().unwrap();
+        temp_file.write_all(&[8u8; 4096]).unwrap();
+        temp_file.flush().unwrap();
+
+        let tags: TransferTags = [
+            ("incident_id".to_string(), "INC-42".to_string()),
+            ("originating_unit".to_string(), "engine 7".to_string()),
+        ]
+        .into();
+        let invalid = TransferOptions {
+            tags: [("bad key".to_string(), "x".to_string())].into(),
+            ..Default::default()
+        };
+        assert!(matches!(
+            coordinator
+                .send_file_with_options(
+                    temp_file.path().to_path_buf(),
+                    Priority::High,
+                    None,
+                    invalid
+                )
+                .await,
+            Err(CoordinatorError::InvalidTags(_))
+        ));
+
+        let session_id = coordinator
+            .send_file_with_options(
+                temp_file.path().to_path_buf(),
+                Priority::High,
+                None,
+                TransferOptions {
+                    tags: tags.clone(),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        let (started, completed) = time::timeout(Duration::from_secs(10), async {
+            let mut started = None;
+            while let Some(event) = events.next().await {
+                match event {
+                    CoordinatorEvent::TransferStarted { tags, .. } => started = Some(tags),
+                    CoordinatorEvent::TransferCompleted { tags, .. } => {
+                        return (started.unwrap(), tags)
+                    }
+                    _ => {}
+                }
+            }
+            panic!("event stream ended");
+        })
+        .await
+        .unwrap();
+        assert_eq!(started, tags);
+        assert_eq!(completed, tags);
+
+        let session = coordinator
+            .session_store
+            .load(&session_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(session.options.tags, tags);
+    }
+
+    #[tokio::test]
+    async fn test_health_reports_failing_components() {
+        use crate::coordinator::HealthStatus;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("stuck.bin");
+        std::fs::write(&path, vec![4u8; 64 * 1024]).unwrap();
+
+        let coordinator = create_test_coordinator().await;
+        coordinator.stats().set_health_policy(HealthPolicy {
+            queue_stall: Duration::from_millis(50),
+            min_free_disk_bytes: 1,
+            disk_paths: vec![dir.path().to_path_buf()],
+            ..Default::default()
+        });
+        let ready = coordinator.stats().readiness().await;
+        assert!(ready.is_healthy(), "{ready:?}");
+        assert_eq!(ready.components.len(), 4);
+
+        // Chunks no worker drains wedge the queue
+        let (_, chunks) = coordinator
+            .chunk_manager()
+            .split_file(&path, "stuck".into(), Priority::Normal)
+            .await
+            .unwrap();
+        coordinator.queue.enqueue(chunks[0].clone()).unwrap();
+        assert!(coordinator.stats().liveness().is_healthy());
+        time::sleep(Duration::from_millis(60)).await;
+        let live = coordinator.stats().liveness();
+        assert!(!live.is_healthy());
+        assert_eq!(
+            live.component(health::QUEUE).unwrap().status,
+            HealthStatus::Down
+        );
+
+        coordinator.queue.clear();
+        coordinator.session_store.close().await;
+        let ready = coordinator.stats().readiness().await;
+        assert_eq!(
+            ready.component(health::SESSION_DB).unwrap().status,
+            HealthStatus::Down
+        );
+        assert_eq!(
+            ready.component(health::QUEUE).unwrap().status,
+            HealthStatus::Up
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cancel_closes_connection_mid_send() {
+        use crate::network::ConnectionConfig;
+
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        let receiver = Arc::new(
+            QuicTransport::new(ConnectionConfig {
+                bind_addr: "127.0.0.1:0".parse().unwrap(),
+                ..Default::default()
+            })
+            .await
+            .unwrap(),
+        );
+        let receiver_addr = receiver.local_addr().unwrap();
+        // Accepts the connection but never answers, so the sender is stuck
+        // waiting on the network when the cancel lands
+        let (closed_tx, closed_rx) = tokio::sync::oneshot::channel();
+        let accepting = receiver.clone();
+        tokio::spawn(async move {
+            let conn = QuicTransport::accept(&accepting).await.unwrap();
+            let _ = closed_tx.send(conn.closed().await);
+        });
+
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(&[6u8; 512 * 1024]).unwrap();
+        let coordinator = create_test_coordinator().await;
+        let session_id = coordinator
+            .send_file(
+                file.path().to_path_buf(),
+                Priority::Normal,
+                Some(receiver_addr),
+            )
+            .await
+            .unwrap();
+        time::sleep(Duration::from_millis(300)).await;
+
+        let cancelled = Instant::now();
+        coordinator.cancel_transfer(&session_id).await.unwrap();
+        let reason = time::timeout(Duration::from_secs(2), closed_rx)
+            .await
+            .expect("connection should close promptly")
+            .unwrap();
+        assert!(cancelled.elapsed() < Duration::from_secs(2));
+        assert!(
+            matches!(
+                &reason,
+                quinn::ConnectionError::ApplicationClosed(close)
+                    if close.error_code == STREAM_CANCELLED.into()
+            ),
+            "{reason:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_relay_events_are_forwarded() {
+        use futures::StreamExt;
+
+        let coordinator = create_test_coordinator().await;
+        let mut events = Box::pin(coordinator.subscribe());
+        let (tx, rx) = mpsc::channel(8);
+        coordinator
+            .receive()
+            .forward_relay_events("relay-1".into(), rx);
+
+        tx.send(RelayEvent::PeerConnected {
+            node_id: "peer-1".into(),
+        })
+        .await
+        .unwrap();
+        assert!(matches!(
+            events.next().await,
+            Some(CoordinatorEvent::Relay { node_id, event: RelayEvent::PeerConnected { .. } })
+                if node_id == "relay-1"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_changed_defaults_apply_to_new_transfers_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("survey.bin");
+        std::fs::write(&path, vec![9u8; 1024 * 1024]).unwrap();
+        let file_id = path.to_string_lossy().to_string();
+
+        let coordinator = create_test_coordinator().await;
+        let (original, _) = coordinator
+            .chunk_manager()
+            .split_file(&path, file_id.clone(), Priority::Normal)
+            .await
+            .unwrap();
+
+        let defaults = coordinator
+            .set_chunking_defaults(
+                ChunkingDefaults {
+                    chunk_size: 64 * 1024,
+                    preserve_attributes: false,
+                },
+                "ops",
+            )
+            .unwrap();
+        assert_eq!(defaults.chunking.chunk_size, 64 * 1024);
+        assert!(coordinator
+            .set_erasure_defaults(
+                ErasureDefaults {
+                    data_shards: 0,
+                    parity_shards: 4,
+                    profiles: Default::default(),
+                },
+                "ops",
+            )
+            .is_err());
+        assert_eq!(coordinator.transfer_defaults(), defaults);
+        assert_eq!(coordinator.config_changes().len(), 1);
+
+        let (manifest, _) = coordinator
+            .chunk_manager()
+            .split_file(&path, file_id.clone(), Priority::Normal)
+            .await
+            .unwrap();
+        assert_eq!(manifest.chunk_size, 64 * 1024);
+
+        // A transfer started before the change keeps its layout
+        let (resplit, _) = ChunkManager::for_manifest(&original)
+            .unwrap()
+            .split_file(&path, file_id, Priority::Normal)
+            .await
+            .unwrap();
+        assert_eq!(resplit.chunk_size, original.chunk_size);
+        assert_eq!(resplit.data_chunks, original.data_chunks);
+        assert_eq!(resplit.parity_chunks, original.parity_chunks);
+    }
+
+    #[tokio::test]
+    async fn test_relay_expiry_notice_marks_chunk_for_resend() {
+        use crate::relay::{ExpiryReason, RouteInfo};
+        use futures::StreamExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("survey.bin");
+        std::fs::write(&path, vec![3u8; 256 * 1024]).unwrap();
+
+        let coordinator = create_test_coordinator().await;
+        let file_id = path.to_string_lossy().to_string();
+        let (manifest, _) = coordinator
+            .chunk_manager()
+            .split_file(&path, file_id.clone(), Priority::Normal)
+            .await
+            .unwrap();
+        let mut session = SessionState::new_with_receiver(
+            "session-1".into(),
+            file_id.clone(),
+            manifest,
+            None,
+            Some(file_id),
+        );
+        for chunk in 0..4 {
+            session.mark_completed(chunk);
+        }
+        session.status = SessionStatus::PartiallyDelivered {
+            delivered_chunks: 2,
+            held_by_relay: 2,
+        };
+        coordinator.session_store.save(&session).await.unwrap();
+
+        let mut events = Box::pin(coordinator.subscribe());
+        let (tx, rx) = mpsc::channel(8);
+        coordinator
+            .receive()
+            .forward_relay_events("relay-1".into(), rx);
+
+        let mut route = RouteInfo::new("sender", "127.0.0.1:5001".parse().unwrap(), "session-1", 1)
+            .with_sequence(3);
+        route.add_hop("relay-1");
+        route.add_hop("relay-2");
+        let mut notice = ExpiredNotice::new("chunk-3", &route, "relay-2", ExpiryReason::Expired);
+        notice.return_path.clear();
+        tx.send(RelayEvent::ChunkUndeliverable { notice })
+            .await
+            .unwrap();
+
+        match events.next().await {
+            Some(CoordinatorEvent::RelayChunkExpired {
+                session_id,
+                chunk_number,
+                route,
+                ..
+            }) => {
+                assert_eq!(session_id, "session-1");
+                assert_eq!(chunk_number, 3);
+                assert_eq!(
+                    route,
+                    ResendRoute::Alternate {
+                        avoid: vec!["relay-2".into()]
+                    }
+                );
+            }
+            other => panic!("expected a relay expiry, got {other:?}"),
+        }
+
+        let progress = coordinator.get_progress("session-1").await.unwrap();
+        assert_eq!(progress.completed_chunks, 3);
+        assert_eq!(progress.relay_expired_chunks, 1);
+        assert_eq!(progress.pending_relay_resends, 1);
+        assert_eq!(
+            progress.status,
+            SessionStatus::PartiallyDelivered {
+                delivered_chunks: 2,
+                held_by_relay: 1,
+            }
+        );
+
+        // A second drop on the hop limit goes direct
+        let notice = ExpiredNotice::new("chunk-3", &route, "relay-3", ExpiryReason::HopLimit);
+        assert_eq!(
+            coordinator
+                .receive()
+                .handle_expired_notice(&notice)
+                .await
+                .unwrap(),
+            ResendRoute::Direct
+        );
+        assert_eq!(
+            coordinator.receive().take_relay_resends("session-1"),
+            [(3, ResendRoute::Direct)]
+        );
+        let progress = coordinator.get_progress("session-1").await.unwrap();
+        assert_eq!(progress.relay_expired_chunks, 2);
+        assert_eq!(progress.pending_relay_resends, 0);
+    }
+
+    #[tokio::test]
+    async fn test_connect_timeout_falls_back_to_relay() {
+        use crate::relay::node::RelayNodeBuilder;
+        use crate::relay::ForwardingPolicy;
+        use futures::StreamExt;
+
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        // Takes the handshake packets and never answers
+        let silent = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let receiver_addr = silent.local_addr().unwrap();
+
+        let coordinator = create_test_coordinator().await;
+        coordinator.set_connect_policy(ConnectPolicy {
+            critical_timeout: Duration::from_millis(200),
+            normal_timeout: Duration::from_millis(200),
+            ..Default::default()
+        });
+        let relay = Arc::new(
+            RelayNodeBuilder::new()
+                .node_id("relay-1")
+                .policy(ForwardingPolicy {
+                    forward_immediately: false,
+                    ..Default::default()
+                })
+                .build()
+                .unwrap(),
+        );
+        coordinator.set_fallback_relay(Some(relay.clone()));
+        let mut events = Box::pin(coordinator.subscribe());
+        async fn timed_out(
+            events: &mut (impl futures::Stream<Item = CoordinatorEvent> + Unpin),
+        ) -> (String, Option<String>) {
+            loop {
+                match time::timeout(Duration::from_secs(5), events.next()).await {
+                    Ok(Some(CoordinatorEvent::ConnectTimedOut {
+                        session_id, relay, ..
+                    })) => return (session_id, relay),
+                    Ok(Some(_)) => continue,
+                    other => panic!("expected a connect timeout, got {other:?}"),
+                }
+            }
+        }
+
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(&[7u8; 512 * 1024]).unwrap();
+        let session_id = coordinator
+            .send_file(
+                file.path().to_path_buf(),
+                Priority::Critical,
+                Some(receiver_addr),
+            )
+            .await
+            .unwrap();
+        let (timed_out_session, used) = timed_out(&mut events).await;
+        assert_eq!(timed_out_session, session_id);
+        assert_eq!(used.as_deref(), Some("relay-1"));
+
+        let session = coordinator
+            .session_store
+            .load(&session_id)
+            .await
+            .unwrap()
+            .unwrap();
+        let total = session.manifest.total_chunks;
+        assert_eq!(relay.holdings(&session_id).held.len() as u32, total);
+        assert_eq!(
+            session.status,
+            SessionStatus::PartiallyDelivered {
+                delivered_chunks: 0,
+                held_by_relay: total,
+            }
+        );
+
+        // Only Critical transfers fall back; the rest fail
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(&[8u8; 512 * 1024]).unwrap();
+        let session_id = coordinator
+            .send_file(
+                file.path().to_path_buf(),
+                Priority::Normal,
+                Some(receiver_addr),
+            )
+            .await
+            .unwrap();
+        let (timed_out_session, used) = timed_out(&mut events).await;
+        assert_eq!((timed_out_session, used), (session_id.clone(), None));
+        assert!(relay.holdings(&session_id).held.is_empty());
+        drop(silent);
+    }
+
+    #[tokio::test]
+    async fn test_relay_audit_reinjects_chunks_nobody_holds() {
+        use crate::relay::node::RelayNodeBuilder;
+        use crate::relay::ForwardingPolicy;
+        use futures::StreamExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("casualties.bin");
+        let data: Vec<u8> = (0..1024 * 1024).map(|i| (i % 251 + 1) as u8).collect();
+        std::fs::write(&path, data).unwrap();
+
+        let coordinator = create_test_coordinator().await;
+        let file_id = path.to_string_lossy().to_string();
+        let (manifest, chunks) = coordinator
+            .chunk_manager()
+            .split_file(&path, file_id.clone(), Priority::Critical)
+            .await
+            .unwrap();
+        let total = manifest.total_chunks;
+        let dest: SocketAddr = "127.0.0.1:5001".parse().unwrap();
+        let mut session = SessionState::new_with_receiver(
+            "session-1".into(),
+            file_id.clone(),
+            manifest,
+            Some(dest),
+            Some(file_id),
+        );
+        session.mark_completed(0);
+        session.mark_completed(1);
+        session.status = SessionStatus::PartiallyDelivered {
+            delivered_chunks: 2,
+            held_by_relay: total - 2,
+        };
+        coordinator.session_store.save(&session).await.unwrap();
+
+        // The relay holds chunk 2 and has delivered chunk 3; the rest are gone
+        let relay = Arc::new(
+            RelayNodeBuilder::new()
+                .node_id("relay-1")
+                .policy(ForwardingPolicy {
+                    forward_immediately: false,
+                    ..Default::default()
+                })
+                .build()
+                .unwrap(),
+        );
+        for chunk in &chunks[2..4] {
+            let seq = chunk.metadata.sequence_number;
+            let route = RouteInfo::new("sender", dest, "session-1", 0).with_sequence(seq);
+            relay
+                .receive_chunk(format!("chunk-{seq}"), route, chunk.clone())
+                .await
+                .unwrap();
+        }
+        relay.deliver_to(dest, &["chunk-3".to_string()]).await;
+        let relays = [relay.clone()];
+        let mut events = Box::pin(coordinator.subscribe());
+
+        // One audit only raises suspicion
+        let audits = coordinator.receive().audit_relays(&relays).await.unwrap();
+        assert_eq!(audits.len(), 1);
+        assert_eq!((audits[0].held, audits[0].delivered), (1, 1));
+        assert!(audits[0].missing.is_empty());
+        assert_eq!(coordinator.resource_usage().audit_suspects, 1);
+
+        // The second confirms it and puts the chunks back on the relay
+        let audits = coordinator.receive().audit_relays(&relays).await.unwrap();
+        let expected: Vec<u32> = (4..total).collect();
+        assert_eq!(audits[0].missing, expected);
+        assert_eq!(audits[0].reinjected, expected);
+        assert_eq!(relay.holdings("session-1").held.len() as u32, total - 3);
+        match events.next().await {
+            Some(CoordinatorEvent::RelayChunksReinjected {
+                session_id,
+                reinjected,
+                ..
+            }) => {
+                assert_eq!(session_id, "session-1");
+                assert_eq!(reinjected, expected);
+            }
+            other => panic!("expected a re-injection, got {other:?}"),
+        }
+        let progress = coordinator.get_progress("session-1").await.unwrap();
+        assert_eq!(progress.pending_relay_resends, 0);
+
+        let audits = coordinator.receive().audit_relays(&relays).await.unwrap();
+        assert!(audits[0].missing.is_empty());
+        assert_eq!(coordinator.resource_usage().audit_suspects, 0);
+
+        // With no relay to take them, missing chunks go back to the sender
+        let full = [Arc::new(
+            RelayNodeBuilder::new().max_storage(1).build().unwrap(),
+        )];
+        coordinator.receive().audit_relays(&full).await.unwrap();
+        let audits = coordinator.receive().audit_relays(&full).await.unwrap();
+        assert!(audits[0].reinjected.is_empty());
+        let progress = coordinator.get_progress("session-1").await.unwrap();
+        assert_eq!(progress.pending_relay_resends, total - 2);
+    }
+
     #[tokio::test]
     async fn test_get_progress() {
         let coordinator = create_test_coordinator().await;
@@ -886,6 +1743,256 @@ mod tests {
         assert!(progress.total_chunks > 0);
     }
 
+    #[tokio::test]
+    async fn test_timeseries_saved_when_transfer_stops() {
+        let coordinator = create_test_coordinator().await;
+
+        let mut temp_file = NamedTempFile::new().unwrap();
+        temp_file.write_all(&[3u8; 4096]).unwrap();
+        temp_file.flush().unwrap();
+        let session_id = coordinator
+            .send_file(temp_file.path().to_path_buf(), Priority::Normal, None)
+            .await
+            .unwrap();
+
+        tokio::time::timeout(Duration::from_secs(10), async {
+            while coordinator.count_completed() < 1
+                || coordinator.resource_usage().sampled_sessions > 0
+            {
+                time::sleep(Duration::from_millis(20)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        let samples = coordinator.get_timeseries(&session_id).await.unwrap();
+        assert!(!samples.is_empty());
+        let progress = coordinator.get_progress(&session_id).await.unwrap();
+        assert_eq!(
+            samples.last().unwrap().bytes_transferred,
+            progress.bytes_transferred
+        );
+
+        assert!(matches!(
+            coordinator.get_timeseries("unknown").await,
+            Err(CoordinatorError::TransferNotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_send_bytes_without_a_file() {
+        let coordinator = create_test_coordinator().await;
+        let data = Bytes::from((0..20_000).map(|i| (i % 251) as u8).collect::<Vec<_>>());
+
+        assert!(matches!(
+            coordinator
+                .send_bytes(
+                    "../readings.bin",
+                    data.clone(),
+                    Priority::Normal,
+                    None,
+                    TransferOptions::default()
+                )
+                .await,
+            Err(CoordinatorError::InvalidSource(_))
+        ));
+
+        // Streams past the limit are refused without being read to the end
+        coordinator.set_max_stream_bytes(data.len() as u64 - 1);
+        assert!(matches!(
+            coordinator
+                .send_stream(
+                    "readings.bin",
+                    &data[..],
+                    Priority::High,
+                    None,
+                    TransferOptions::default(),
+                )
+                .await,
+            Err(CoordinatorError::InvalidSource(_))
+        ));
+        coordinator.set_max_stream_bytes(data.len() as u64);
+
+        let session_id = coordinator
+            .send_stream(
+                "readings.bin",
+                &data[..],
+                Priority::High,
+                None,
+                TransferOptions::default(),
+            )
+            .await
+            .unwrap();
+        tokio::time::timeout(Duration::from_secs(10), async {
+            while coordinator.count_completed() < 1 {
+                time::sleep(Duration::from_millis(20)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        let session = coordinator
+            .session_store
+            .load(&session_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(session.file_id, "memory:readings.bin");
+        assert_eq!(session.file_path, None);
+        assert_eq!(session.manifest.filename, "readings.bin");
+        assert_eq!(session.manifest.total_size, data.len() as u64);
+        assert_eq!(
+            session.manifest.checksum,
+            session.manifest.checksum_algorithm.digest(&data)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_resume_from_memory_needs_the_same_data() {
+        let coordinator = create_test_coordinator().await;
+        let data = Bytes::from(vec![9u8; 20_000]);
+        let (manifest, _) = coordinator
+            .chunk_manager()
+            .split_bytes(
+                &data,
+                "readings.bin".into(),
+                "memory:readings.bin".into(),
+                Priority::Normal,
+            )
+            .unwrap();
+        let mut session = SessionState::new_with_receiver(
+            "session-1".into(),
+            "memory:readings.bin".into(),
+            manifest,
+            None,
+            None,
+        );
+        session.mark_completed(0);
+        session.status = SessionStatus::Paused;
+        coordinator.session_store.save(&session).await.unwrap();
+
+        assert!(matches!(
+            coordinator.resume_transfer("session-1").await,
+            Err(CoordinatorError::CannotResume(_))
+        ));
+        assert!(matches!(
+            coordinator
+                .resume_bytes("session-1", Bytes::from(vec![8u8; 20_000]))
+                .await,
+            Err(CoordinatorError::CannotResume(_))
+        ));
+        assert!(coordinator.get_state("session-1").is_none());
+
+        coordinator
+            .resume_stream("session-1", &data[..])
+            .await
+            .unwrap();
+        tokio::time::timeout(Duration::from_secs(10), async {
+            while coordinator.get_state("session-1").is_some() {
+                time::sleep(Duration::from_millis(20)).await;
+            }
+        })
+        .await
+        .unwrap();
+        let progress = coordinator.get_progress("session-1").await.unwrap();
+        assert_eq!(progress.completed_chunks, progress.total_chunks);
+    }
+
+    #[tokio::test]
+    async fn test_resume_refuses_changed_file() {
+        let coordinator = create_test_coordinator().await;
+        let mut temp_file = NamedTempFile::new().unwrap();
+        temp_file.write_all(&[4u8; 20_000]).unwrap();
+        temp_file.flush().unwrap();
+        let path = temp_file.path().to_path_buf();
+        let (manifest, _) = coordinator
+            .chunk_manager()
+            .split_file(&path, "form.bin".into(), Priority::Normal)
+            .await
+            .unwrap();
+        let mut session = SessionState::new_with_receiver(
+            "session-1".into(),
+            "form.bin".into(),
+            manifest,
+            None,
+            Some(path.to_string_lossy().to_string()),
+        );
+        session.mark_completed(0);
+        session.status = SessionStatus::Paused;
+        coordinator.session_store.save(&session).await.unwrap();
+
+        std::fs::write(&path, [5u8; 20_000]).unwrap();
+        let err = coordinator.resume_transfer("session-1").await.unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("changed since the transfer started"));
+        assert!(coordinator.get_state("session-1").is_none());
+
+        // Put back as it was, the file resumes
+        std::fs::write(&path, [4u8; 20_000]).unwrap();
+        coordinator.resume_transfer("session-1").await.unwrap();
+        tokio::time::timeout(Duration::from_secs(10), async {
+            while coordinator.get_state("session-1").is_some() {
+                time::sleep(Duration::from_millis(20)).await;
+            }
+        })
+        .await
+        .unwrap();
+        let progress = coordinator.get_progress("session-1").await.unwrap();
+        assert_eq!(progress.completed_chunks, progress.total_chunks);
+    }
+
+    #[tokio::test]
+    async fn test_recover_sessions_then_shut_down() {
+        let coordinator = create_test_coordinator().await;
+        let data = vec![3u8; 20_000];
+        let (manifest, _) = coordinator
+            .chunk_manager()
+            .split_bytes(
+                &data,
+                "log.bin".into(),
+                "memory:log.bin".into(),
+                Priority::Normal,
+            )
+            .unwrap();
+
+        // A transfer the previous process was running when it died
+        let mut session = SessionState::new_with_receiver(
+            "orphan".into(),
+            "memory:log.bin".into(),
+            manifest,
+            None,
+            None,
+        );
+        session.status = SessionStatus::Active;
+        coordinator.session_store.save(&session).await.unwrap();
+
+        assert_eq!(coordinator.recover_sessions().await.unwrap(), 1);
+        let recovered = coordinator.session_store.load("orphan").await.unwrap();
+        assert_eq!(recovered.unwrap().status, SessionStatus::Paused);
+        assert_eq!(coordinator.recover_sessions().await.unwrap(), 0);
+
+        // Nothing starts or resumes once shutdown begins
+        assert_eq!(coordinator.shutdown().await.unwrap(), 0);
+        assert!(coordinator.is_shutting_down());
+        assert!(matches!(
+            coordinator
+                .send_bytes(
+                    "log.bin",
+                    Bytes::from(data.clone()),
+                    Priority::Normal,
+                    None,
+                    TransferOptions::default()
+                )
+                .await,
+            Err(CoordinatorError::ShuttingDown)
+        ));
+        assert!(matches!(
+            coordinator.resume_bytes("orphan", Bytes::from(data)).await,
+            Err(CoordinatorError::ShuttingDown)
+        ));
+    }
+
     #[tokio::test]
     async fn test_pause_resume() {
         let coordinator = create_test_coordinator().await;
@@ -914,9 +2021,19 @@ mod tests {
 
             // Resume
             coordinator.resume_transfer(&session_id).await.unwrap();
+            assert!(coordinator.resume_transfer(&session_id).await.is_err());
 
-            // Wait a bit
-            tokio::time::sleep(Duration::from_millis(100)).await;
+            // The resumed worker carries the transfer through
+            for _ in 0..100 {
+                if coordinator.get_state(&session_id).is_none() {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+            assert!(coordinator
+                .get_recent_state(&session_id)
+                .unwrap()
+                .is_completed());
         }
     }
 
@@ -931,7 +2048,7 @@ mod tests {
 
         let file_path = temp_file.path().to_path_buf();
         let session_id = coordinator
-            .send_file(file_path, Priority::Normal, None)
+            .send_file(file_path.clone(), Priority::Normal, None)
             .await
             .unwrap();
 
@@ -940,6 +2057,13 @@ mod tests {
 
         // Should be removed from active transfers
         assert!(coordinator.get_state(&session_id).is_none());
+        assert_eq!(coordinator.resource_usage().file_sessions, 0);
+
+        // The file is free to send again
+        coordinator
+            .send_file(file_path, Priority::Normal, None)
+            .await
+            .unwrap();
     }
 
     #[tokio::test]
@@ -963,4 +2087,351 @@ mod tests {
             .await;
         assert!(result.is_err());
     }
+
+    #[tokio::test]
+    async fn test_same_file_to_two_receivers() {
+        let coordinator = create_test_coordinator().await;
+        let mut temp_file = NamedTempFile::new().unwrap();
+        temp_file.write_all(&[4u8; 1024]).unwrap();
+        temp_file.flush().unwrap();
+        let file_path = temp_file.path().to_path_buf();
+
+        let first = coordinator
+            .send_file(file_path.clone(), Priority::Normal, None)
+            .await
+            .unwrap();
+        let second = coordinator
+            .send_file(
+                file_path.clone(),
+                Priority::Normal,
+                Some("127.0.0.1:9".parse().unwrap()),
+            )
+            .await
+            .unwrap();
+        assert_ne!(first, second);
+
+        let session = coordinator
+            .session_store
+            .load(&second)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(session.file_id.starts_with(&*file_path.to_string_lossy()));
+        assert_eq!(
+            session.file_path.as_deref(),
+            Some(&*file_path.to_string_lossy())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_transfers_beyond_limit_wait_for_a_slot() {
+        let coordinator = create_test_coordinator().await;
+        coordinator.set_max_concurrent_transfers(1);
+
+        let files: Vec<NamedTempFile> = (0..2)
+            .map(|_| {
+                let mut f = NamedTempFile::new().unwrap();
+                f.write_all(&[7u8; 1024]).unwrap();
+                f
+            })
+            .collect();
+
+        let first = coordinator
+            .send_file(files[0].path().to_path_buf(), Priority::Normal, None)
+            .await
+            .unwrap();
+        let second = coordinator
+            .send_file(files[1].path().to_path_buf(), Priority::Normal, None)
+            .await
+            .unwrap();
+
+        assert_eq!(coordinator.list_active(), vec![first.clone()]);
+        assert_eq!(coordinator.pending_transfers()[0].session_id, second);
+        assert_eq!(
+            coordinator.get_recent_state(&second),
+            Some(TransferState::Idle)
+        );
+
+        // Freeing the slot starts the queued transfer
+        coordinator.cancel_transfer(&first).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(coordinator.pending_transfers().is_empty());
+        let state = coordinator.get_recent_state(&second).unwrap();
+        assert_ne!(state, TransferState::Idle);
+    }
+
+    #[tokio::test]
+    async fn test_session_windows_share_a_small_queue() {
+        use crate::network::ConnectionConfig;
+
+        // Two 13-chunk files through a queue that holds 16
+        let coordinator = TransferCoordinator::new(
+            ChunkManager::new(256 * 1024, 10, 3).unwrap(),
+            QuicTransport::new(ConnectionConfig::default())
+                .await
+                .unwrap(),
+            PriorityQueue::new(16),
+            SessionStore::new_in_memory().await.unwrap(),
+        );
+        coordinator.set_session_window(4);
+
+        let files: Vec<NamedTempFile> = (0..2u8)
+            .map(|i| {
+                let mut f = NamedTempFile::new().unwrap();
+                f.write_all(&vec![i + 1; 10 * 256 * 1024]).unwrap();
+                f
+            })
+            .collect();
+        let mut sessions = Vec::new();
+        for file in &files {
+            let session_id = coordinator
+                .send_file(file.path().to_path_buf(), Priority::Normal, None)
+                .await
+                .unwrap();
+            sessions.push(session_id);
+        }
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(coordinator.queue.total_pending() <= 8);
+
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !sessions
+            .iter()
+            .all(|id| coordinator.get_recent_state(id) == Some(TransferState::Completed))
+        {
+            assert!(Instant::now() < deadline, "transfers did not complete");
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert!(coordinator.queue.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_send_file_with_missing_local_addr() {
+        let coordinator = create_test_coordinator().await;
+
+        let mut temp_file = NamedTempFile::new().unwrap();
+        temp_file.write_all(&vec![0u8; 1024]).unwrap();
+        temp_file.flush().unwrap();
+
+        let options = TransferOptions {
+            local_bind_addr: Some("192.0.2.1:0".parse().unwrap()),
+            ..Default::default()
+        };
+        let result = coordinator
+            .send_file_with_options(
+                temp_file.path().to_path_buf(),
+                Priority::Normal,
+                Some("127.0.0.1:5001".parse().unwrap()),
+                options,
+            )
+            .await;
+
+        assert!(matches!(
+            result,
+            Err(CoordinatorError::NetworkError(
+                crate::network::NetworkError::LocalAddressUnavailable { .. }
+            ))
+        ));
+        assert_eq!(coordinator.list_active().len(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_retention_evicts_oldest_finished() {
+        let coordinator = create_test_coordinator().await;
+
+        let mut files = Vec::new();
+        let mut sessions = Vec::new();
+        for _ in 0..3 {
+            let mut temp_file = NamedTempFile::new().unwrap();
+            temp_file.write_all(&[1u8; 1024]).unwrap();
+            temp_file.flush().unwrap();
+            let session_id = coordinator
+                .send_file(temp_file.path().to_path_buf(), Priority::Normal, None)
+                .await
+                .unwrap();
+            sessions.push(session_id);
+            files.push(temp_file);
+        }
+
+        tokio::time::timeout(Duration::from_secs(10), async {
+            while coordinator.count_completed() < 3 {
+                time::sleep(Duration::from_millis(20)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        coordinator.set_retention(RetentionPolicy {
+            max_entries: 1,
+            ..Default::default()
+        });
+        assert_eq!(coordinator.evict_finished(), 2);
+        assert_eq!(coordinator.list_recent().len(), 1);
+        assert_eq!(coordinator.count_completed(), 3);
+
+        // Evicted transfers are still answered from the session store
+        for session_id in &sessions {
+            let state = coordinator.get_transfer_state(session_id).await.unwrap();
+            assert_eq!(state, Some(TransferState::Completed));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_before_enqueue_hook_rejects_file() {
+        use crate::hooks::{FailurePolicy, FileHook, HookResult, HookVerdict};
+        use futures::future::BoxFuture;
+
+        struct DenyAll;
+
+        impl FileHook for DenyAll {
+            fn name(&self) -> &str {
+                "deny-all"
+            }
+
+            fn points(&self) -> &[HookPoint] {
+                &[HookPoint::BeforeEnqueue]
+            }
+
+            fn check<'a>(
+                &'a self,
+                _ctx: &'a HookContext,
+            ) -> BoxFuture<'a, HookResult<HookVerdict>> {
+                Box::pin(async {
+                    Ok(HookVerdict::Deny {
+                        reason: "blocked".into(),
+                    })
+                })
+            }
+        }
+
+        let coordinator = create_test_coordinator().await;
+        coordinator
+            .hooks()
+            .register(Arc::new(DenyAll), FailurePolicy::Reject);
+
+        let mut temp_file = NamedTempFile::new().unwrap();
+        temp_file.write_all(&vec![0u8; 1024]).unwrap();
+        temp_file.flush().unwrap();
+
+        let result = coordinator
+            .send_file(temp_file.path().to_path_buf(), Priority::Normal, None)
+            .await;
+
+        assert!(matches!(result, Err(CoordinatorError::HookError(_))));
+        assert_eq!(coordinator.list_active().len(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_resume_token_moves_session_to_another_host() {
+        let dir = tempfile::tempdir().unwrap();
+        let data: Vec<u8> = (0..1024 * 1024).map(|i| (i % 251) as u8).collect();
+        let original = dir.path().join("laptop.bin");
+        let copy = dir.path().join("desktop.bin");
+        std::fs::write(&original, &data).unwrap();
+        std::fs::write(&copy, &data).unwrap();
+
+        // A paused session on the first host with some chunks delivered
+        let laptop = create_test_coordinator().await;
+        laptop.set_resume_token_secret(Some("field-team"));
+        let file_id = original.to_string_lossy().to_string();
+        let (manifest, _) = laptop
+            .chunk_manager()
+            .split_file(&original, file_id.clone(), Priority::High)
+            .await
+            .unwrap();
+        let mut session = SessionState::new_with_receiver(
+            "session-1".into(),
+            file_id.clone(),
+            manifest,
+            None,
+            Some(file_id.clone()),
+        );
+        for chunk in 0..3 {
+            session.mark_completed(chunk);
+        }
+        session.status = SessionStatus::Paused;
+        laptop.session_store.save(&session).await.unwrap();
+        let token = laptop.export_resume_token("session-1").await.unwrap();
+
+        // A host with a different secret refuses the token
+        let stranger = create_test_coordinator().await;
+        stranger.set_resume_token_secret(Some("someone-else"));
+        assert!(matches!(
+            stranger
+                .import_resume_token(token.clone(), copy.clone())
+                .await,
+            Err(CoordinatorError::InvalidResumeToken(_))
+        ));
+
+        // So does one whose file differs
+        let desktop = create_test_coordinator().await;
+        desktop.set_resume_token_secret(Some("field-team"));
+        let other = dir.path().join("other.bin");
+        std::fs::write(&other, vec![1u8; data.len()]).unwrap();
+        assert!(matches!(
+            desktop.import_resume_token(token.clone(), other).await,
+            Err(CoordinatorError::CannotResume(_))
+        ));
+
+        let session_id = desktop
+            .import_resume_token(token, copy.clone())
+            .await
+            .unwrap();
+        assert_eq!(session_id, "session-1");
+
+        let resumed = desktop
+            .session_store
+            .load("session-1")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(resumed.file_id, file_id);
+        assert_eq!(
+            resumed.file_path.as_deref(),
+            Some(copy.to_string_lossy().as_ref())
+        );
+        assert!((0..3).all(|n| resumed.completed_chunks.contains(&n)));
+    }
+
+    #[tokio::test]
+    async fn test_seeded_simulations_repeat() {
+        let coordinator = create_test_coordinator().await;
+        let mut temp_file = NamedTempFile::new().unwrap();
+        temp_file.write_all(&vec![3u8; 512 * 1024]).unwrap();
+        temp_file.flush().unwrap();
+        let path = temp_file.path().to_path_buf();
+
+        let runs = [
+            coordinator
+                .simulation()
+                .simulate_file_transfer(path.clone(), 0.3, Some(42))
+                .await
+                .unwrap(),
+            coordinator
+                .simulation()
+                .simulate_file_transfer(path.clone(), 0.3, Some(42))
+                .await
+                .unwrap(),
+        ];
+        assert_eq!(runs[0].successful_trials, runs[1].successful_trials);
+        assert_eq!(runs[0].avg_chunks_lost, runs[1].avg_chunks_lost);
+        assert_eq!(runs[0].min_chunks_lost, runs[1].min_chunks_lost);
+        assert_eq!(runs[0].max_chunks_lost, runs[1].max_chunks_lost);
+
+        let first = coordinator
+            .simulation()
+            .simulate_comparison(path.clone(), 5, Some(7))
+            .await
+            .unwrap();
+        let second = coordinator
+            .simulation()
+            .simulate_comparison(path, 5, Some(7))
+            .await
+            .unwrap();
+        let losses = |r: &ComparisonResult| -> Vec<f64> {
+            r.points.iter().map(|p| p.tcp_avg_chunks_lost).collect()
+        };
+        assert_eq!(losses(&first), losses(&second));
+        assert!(losses(&first).iter().any(|&lost| lost > 0.0));
+    }
 }
@@ -0,0 +1,306 @@
+//! Chunking and erasure defaults that can change while the coordinator runs
+//!
+//! New transfers are split with whatever defaults are in effect when they
+//! start; transfers already running, and resumed ones, keep the layout in
+//! their manifest. Every change is validated as a whole before it replaces
+//! the old defaults, and kept in a short history with who made it.
+
+use crate::chunk::erasure::MAX_TOTAL_SHARDS;
+use crate::chunk::{ChunkManager, ErasureProfiles};
+use crate::coordinator::error::{CoordinatorError, CoordinatorResult};
+use crate::session::TransferOptions;
+use parking_lot::{Mutex, RwLock};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+/// Changes kept in [`DefaultsHistory`]
+pub const MAX_CONFIG_CHANGES: usize = 100;
+
+/// Reed-Solomon shard counts new transfers start from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ErasureDefaults {
+    pub data_shards: usize,
+    pub parity_shards: usize,
+    /// Parity each priority gets on top of (or within) these counts
+    #[serde(default)]
+    pub profiles: ErasureProfiles,
+}
+
+impl ErasureDefaults {
+    pub fn validate(&self) -> CoordinatorResult<()> {
+        if self.data_shards == 0 || self.parity_shards == 0 {
+            return Err(CoordinatorError::InvalidConfig(
+                "data_shards and parity_shards must both be > 0".into(),
+            ));
+        }
+        if self.data_shards + self.parity_shards > MAX_TOTAL_SHARDS {
+            return Err(CoordinatorError::InvalidConfig(format!(
+                "{} + {} shards exceeds the Reed-Solomon maximum of {}",
+                self.data_shards, self.parity_shards, MAX_TOTAL_SHARDS
+            )));
+        }
+        self.profiles
+            .validate()
+            .map_err(|e| CoordinatorError::InvalidConfig(e.to_string()))
+    }
+}
+
+/// How new transfers are cut into chunks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChunkingDefaults {
+    /// Bytes per data chunk
+    pub chunk_size: usize,
+    /// Carry mode, mtime and symlink targets to the receiver
+    pub preserve_attributes: bool,
+}
+
+impl ChunkingDefaults {
+    /// Check the defaults, with chunks of at most `max_chunk_size` bytes
+    pub fn validate(&self, max_chunk_size: usize) -> CoordinatorResult<()> {
+        if self.chunk_size == 0 {
+            return Err(CoordinatorError::InvalidConfig(
+                "chunk_size must be > 0".into(),
+            ));
+        }
+        if self.chunk_size > max_chunk_size {
+            return Err(CoordinatorError::InvalidConfig(format!(
+                "chunk_size {} exceeds the {} byte per-stream limit",
+                self.chunk_size, max_chunk_size
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Defaults applied to transfers started from now on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransferDefaults {
+    pub erasure: ErasureDefaults,
+    pub chunking: ChunkingDefaults,
+}
+
+impl TransferDefaults {
+    /// Defaults a chunk manager was built with
+    pub fn of(manager: &ChunkManager) -> Self {
+        Self {
+            erasure: ErasureDefaults {
+                data_shards: manager.data_shards(),
+                parity_shards: manager.parity_shards(),
+                profiles: manager.erasure_profiles(),
+            },
+            chunking: ChunkingDefaults {
+                chunk_size: manager.chunk_size(),
+                preserve_attributes: manager.preserve_attributes(),
+            },
+        }
+    }
+
+    /// Validated chunk manager for these defaults, cutting chunks of at most
+    /// `max_chunk_size` bytes
+    pub fn chunk_manager(&self, max_chunk_size: usize) -> CoordinatorResult<ChunkManager> {
+        self.erasure.validate()?;
+        self.chunking.validate(max_chunk_size)?;
+        Ok(ChunkManager::new(
+            self.chunking.chunk_size,
+            self.erasure.data_shards,
+            self.erasure.parity_shards,
+        )?
+        .with_preserve_attributes(self.chunking.preserve_attributes)
+        .with_erasure_profiles(self.erasure.profiles))
+    }
+}
+
+/// Chunk manager for a new transfer: `base`, with any chunk size or shard
+/// counts `options` override
+pub(crate) fn chunk_manager_for(
+    base: Arc<ChunkManager>,
+    options: &TransferOptions,
+    max_chunk_size: usize,
+) -> CoordinatorResult<Arc<ChunkManager>> {
+    if !options.overrides_layout() {
+        return Ok(base);
+    }
+    let mut layout = TransferDefaults::of(&base);
+    if let Some(chunk_size) = options.chunk_size {
+        layout.chunking.chunk_size = chunk_size;
+    }
+    if let Some(data_shards) = options.data_shards {
+        layout.erasure.data_shards = data_shards;
+    }
+    if let Some(parity_shards) = options.parity_shards {
+        layout.erasure.parity_shards = parity_shards;
+    }
+    Ok(Arc::new(
+        layout
+            .chunk_manager(max_chunk_size)?
+            .with_settings_of(&base),
+    ))
+}
+
+/// Group of defaults changed together
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DefaultsSection {
+    Erasure,
+    Chunking,
+}
+
+/// One applied change to the defaults
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConfigChange {
+    pub section: DefaultsSection,
+    /// Who asked for the change, as they identified themselves
+    pub changed_by: String,
+    /// Unix timestamp the change took effect
+    pub changed_at: i64,
+    pub before: TransferDefaults,
+    pub after: TransferDefaults,
+}
+
+/// Most recent changes, oldest first
+#[derive(Debug, Default)]
+pub struct DefaultsHistory {
+    changes: VecDeque<ConfigChange>,
+}
+
+impl DefaultsHistory {
+    pub fn record(&mut self, change: ConfigChange) {
+        if self.changes.len() == MAX_CONFIG_CHANGES {
+            self.changes.pop_front();
+        }
+        self.changes.push_back(change);
+    }
+
+    pub fn changes(&self) -> Vec<ConfigChange> {
+        self.changes.iter().cloned().collect()
+    }
+}
+
+/// Chunk manager new transfers are split with, swapped whole on each change
+pub(crate) struct LiveDefaults {
+    manager: Arc<RwLock<Arc<ChunkManager>>>,
+    history: Mutex<DefaultsHistory>,
+}
+
+impl LiveDefaults {
+    pub(crate) fn new(manager: ChunkManager) -> Self {
+        Self {
+            manager: Arc::new(RwLock::new(Arc::new(manager))),
+            history: Mutex::new(DefaultsHistory::default()),
+        }
+    }
+
+    /// Handle services read the current chunk manager through
+    pub(crate) fn shared(&self) -> Arc<RwLock<Arc<ChunkManager>>> {
+        self.manager.clone()
+    }
+
+    pub(crate) fn current(&self) -> Arc<ChunkManager> {
+        self.manager.read().clone()
+    }
+
+    pub(crate) fn changes(&self) -> Vec<ConfigChange> {
+        self.history.lock().changes()
+    }
+
+    /// Validate and swap in new defaults, recording the change
+    ///
+    /// The chunk manager is replaced under its lock, so a transfer starting
+    /// concurrently sees either the old defaults or the new ones in full.
+    pub(crate) fn update(
+        &self,
+        section: DefaultsSection,
+        changed_by: &str,
+        max_chunk_size: usize,
+        change: impl FnOnce(&mut TransferDefaults),
+    ) -> CoordinatorResult<TransferDefaults> {
+        let mut manager = self.manager.write();
+        let before = TransferDefaults::of(&manager);
+        let mut after = before;
+        change(&mut after);
+        *manager = Arc::new(
+            after
+                .chunk_manager(max_chunk_size)?
+                .with_settings_of(&manager),
+        );
+
+        tracing::info!(
+            changed_by,
+            ?section,
+            ?before,
+            ?after,
+            "transfer defaults changed"
+        );
+        self.history.lock().record(ConfigChange {
+            section,
+            changed_by: changed_by.to_string(),
+            changed_at: chrono::Utc::now().timestamp(),
+            before,
+            after,
+        });
+        Ok(after)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::quic_transport::MAX_CHUNK_STREAM_SIZE;
+
+    fn defaults() -> TransferDefaults {
+        TransferDefaults {
+            erasure: ErasureDefaults {
+                data_shards: 50,
+                parity_shards: 10,
+                profiles: ErasureProfiles::default(),
+            },
+            chunking: ChunkingDefaults {
+                chunk_size: 512 * 1024,
+                preserve_attributes: true,
+            },
+        }
+    }
+
+    #[test]
+    fn test_round_trips_through_chunk_manager() {
+        let manager = defaults().chunk_manager(MAX_CHUNK_STREAM_SIZE).unwrap();
+        assert_eq!(TransferDefaults::of(&manager), defaults());
+    }
+
+    #[test]
+    fn test_rejects_invalid_defaults() {
+        let mut invalid = defaults();
+        invalid.erasure.parity_shards = 0;
+        assert!(invalid.chunk_manager(MAX_CHUNK_STREAM_SIZE).is_err());
+
+        let mut invalid = defaults();
+        invalid.erasure.data_shards = 250;
+        assert!(invalid.chunk_manager(MAX_CHUNK_STREAM_SIZE).is_err());
+
+        let mut invalid = defaults();
+        invalid.erasure.profiles.normal.extra_parity_percent = 1000;
+        assert!(invalid.chunk_manager(MAX_CHUNK_STREAM_SIZE).is_err());
+
+        let mut invalid = defaults();
+        invalid.chunking.chunk_size = MAX_CHUNK_STREAM_SIZE + 1;
+        assert!(invalid.chunk_manager(MAX_CHUNK_STREAM_SIZE).is_err());
+    }
+
+    #[test]
+    fn test_history_keeps_latest_changes() {
+        let mut history = DefaultsHistory::default();
+        for n in 0..MAX_CONFIG_CHANGES + 5 {
+            history.record(ConfigChange {
+                section: DefaultsSection::Erasure,
+                changed_by: format!("operator-{n}"),
+                changed_at: n as i64,
+                before: defaults(),
+                after: defaults(),
+            });
+        }
+        let changes = history.changes();
+        assert_eq!(changes.len(), MAX_CONFIG_CHANGES);
+        assert_eq!(changes[0].changed_by, "operator-5");
+    }
+}
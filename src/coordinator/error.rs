@@ -11,9 +11,45 @@ pub enum CoordinatorError {
     #[error("Cannot resume: {0}")]
     CannotResume(String),
 
+    #[error("Invalid resume token: {0}")]
+    InvalidResumeToken(String),
+
+    #[error("Invalid configuration: {0}")]
+    InvalidConfig(String),
+
+    #[error("Invalid expiry notice: {0}")]
+    InvalidExpiryNotice(String),
+
+    #[error("Transfer profile not found: {0}")]
+    ProfileNotFound(String),
+
+    #[error("Benchmark report not found: {0}")]
+    BenchmarkNotFound(String),
+
+    #[error("Not in the catalog: {0}")]
+    NotInCatalog(String),
+
+    #[error("Invalid transfer source: {0}")]
+    InvalidSource(String),
+
+    #[error("Invalid transfer tags: {0}")]
+    InvalidTags(String),
+
     #[error("Transfer already in progress: {0}")]
     AlreadyInProgress(String),
 
+    #[error("Shutting down, not taking transfers")]
+    ShuttingDown,
+
+    #[error("Standby, not taking transfers until promoted")]
+    Standby,
+
+    #[error("Not a standby")]
+    NotStandby,
+
+    #[error("Not supported by this transport: {0}")]
+    Unsupported(String),
+
     #[error("Chunk error: {0}")]
     ChunkError(#[from] crate::chunk::ChunkError),
 
@@ -29,6 +65,12 @@ pub enum CoordinatorError {
     #[error("Integrity error: {0}")]
     IntegrityError(#[from] crate::integrity::IntegrityError),
 
+    #[error("Relay error: {0}")]
+    RelayError(#[from] crate::relay::RelayError),
+
+    #[error("Hook error: {0}")]
+    HookError(#[from] crate::hooks::HookError),
+
     #[error("IO error: {0}")]
     IoError(#[from] std::io::Error),
 }
@@ -0,0 +1,372 @@
+//! Coordinator event bus
+//!
+//! Applications embedding the crate subscribe to [`CoordinatorEvent`]s
+//! instead of polling transfer state. Events are broadcast: every
+//! subscriber sees every event from the moment it subscribed, and a
+//! subscriber that falls more than [`EVENT_BUFFER`] events behind skips
+//! the oldest ones rather than holding up transfers.
+//!
+//! The bus also keeps the last [`EVENT_HISTORY`] events, each numbered in
+//! publish order, so a late subscriber can catch up on a session: subscribe
+//! first, then read the history and drop live events numbered at or below
+//! the last one it returned.
+
+use crate::chunk::Priority;
+use crate::coordinator::types::{ResendRoute, TransferProgress};
+use crate::relay::node::RelayEvent;
+use crate::relay::ExpiryReason;
+use crate::session::TransferTags;
+use futures::stream::{self, Stream, StreamExt};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::sync::broadcast;
+
+/// Events buffered per subscriber before the oldest are dropped
+pub const EVENT_BUFFER: usize = 1024;
+
+/// Most recent events kept for late subscribers, across all sessions
+pub const EVENT_HISTORY: usize = 1024;
+
+/// Something that happened to a transfer, its network path or a relay
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CoordinatorEvent {
+    /// A transfer was admitted and its worker started
+    TransferStarted {
+        session_id: String,
+        file_id: String,
+        priority: Priority,
+        total_chunks: u32,
+        total_size: u64,
+        /// Caller's labels from [`TransferOptions::tags`](crate::session::TransferOptions::tags)
+        #[serde(default)]
+        tags: TransferTags,
+    },
+
+    /// A chunk reached the receiver
+    TransferProgress {
+        session_id: String,
+        chunks_completed: u32,
+        total_chunks: u32,
+        bytes_transferred: u64,
+    },
+
+    TransferCompleted {
+        session_id: String,
+        /// Chunks the receiver rebuilds from parity
+        repaired_chunks: u32,
+        #[serde(default)]
+        tags: TransferTags,
+    },
+
+    TransferFailed {
+        session_id: String,
+        error: String,
+        #[serde(default)]
+        tags: TransferTags,
+    },
+
+    /// A chunk that didn't arrive is covered by parity
+    ChunkRecovered {
+        session_id: String,
+        chunk_number: u32,
+    },
+
+    /// A Critical chunk reached the receiver after its deadline
+    SloViolation {
+        session_id: String,
+        chunk_number: u32,
+        latency_ms: u64,
+        target_ms: u64,
+    },
+
+    /// The connection to the receiver migrated to a new remote address
+    PathChanged {
+        session_id: String,
+        from: SocketAddr,
+        to: SocketAddr,
+    },
+
+    /// Connecting to the receiver took longer than the transfer's priority
+    /// allows; `relay` names the relay the chunks were handed to instead,
+    /// if any
+    ConnectTimedOut {
+        session_id: String,
+        receiver: SocketAddr,
+        priority: Priority,
+        timeout_ms: u64,
+        relay: Option<String>,
+    },
+
+    /// A relay dropped a chunk before delivery and it is marked for resend
+    RelayChunkExpired {
+        session_id: String,
+        chunk_number: u32,
+        dropped_by: String,
+        reason: ExpiryReason,
+        route: ResendRoute,
+    },
+
+    /// An audit found relayed chunks nobody holds; `reinjected` were stored
+    /// on a relay again and the rest marked for a direct resend
+    RelayChunksReinjected {
+        session_id: String,
+        missing: Vec<u32>,
+        reinjected: Vec<u32>,
+    },
+
+    /// An event from a relay node attached with
+    /// [`forward_relay_events`](crate::coordinator::TransferCoordinator::forward_relay_events)
+    Relay { node_id: String, event: RelayEvent },
+}
+
+impl CoordinatorEvent {
+    /// Transfer the event belongs to; relay events belong to the transfer
+    /// named by their chunk's trace id, if any
+    pub fn session_id(&self) -> Option<&str> {
+        match self {
+            CoordinatorEvent::TransferStarted { session_id, .. }
+            | CoordinatorEvent::TransferProgress { session_id, .. }
+            | CoordinatorEvent::TransferCompleted { session_id, .. }
+            | CoordinatorEvent::TransferFailed { session_id, .. }
+            | CoordinatorEvent::ChunkRecovered { session_id, .. }
+            | CoordinatorEvent::SloViolation { session_id, .. }
+            | CoordinatorEvent::PathChanged { session_id, .. }
+            | CoordinatorEvent::ConnectTimedOut { session_id, .. }
+            | CoordinatorEvent::RelayChunkExpired { session_id, .. }
+            | CoordinatorEvent::RelayChunksReinjected { session_id, .. } => Some(session_id),
+            CoordinatorEvent::Relay { event, .. } => event.trace_id(),
+        }
+    }
+}
+
+/// An event and its place in publish order
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SequencedEvent {
+    /// Increases by one per published event, starting at 1
+    pub seq: u64,
+    pub event: CoordinatorEvent,
+}
+
+/// A session's progress and recent events, for a subscriber that joined
+/// after it started
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionBackfill {
+    pub progress: TransferProgress,
+    /// Oldest first
+    pub events: Vec<SequencedEvent>,
+    /// Live events numbered at or below this are already covered
+    pub last_seq: u64,
+}
+
+/// Broadcast channel behind [`TransferCoordinator::subscribe`](crate::coordinator::TransferCoordinator::subscribe)
+#[derive(Debug, Clone)]
+pub(crate) struct EventBus {
+    tx: broadcast::Sender<SequencedEvent>,
+    /// Last sequence number handed out and the events kept for backfill
+    history: Arc<Mutex<(u64, VecDeque<SequencedEvent>)>>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self {
+            tx: broadcast::channel(EVENT_BUFFER).0,
+            history: Arc::new(Mutex::new((0, VecDeque::with_capacity(EVENT_HISTORY)))),
+        }
+    }
+}
+
+impl EventBus {
+    /// Send an event to current subscribers and keep it in the history
+    pub fn publish(&self, event: CoordinatorEvent) {
+        // Numbering and sending under one lock keeps live order and
+        // history order the same
+        let mut history = self.history.lock();
+        history.0 += 1;
+        let event = SequencedEvent {
+            seq: history.0,
+            event,
+        };
+        if history.1.len() == EVENT_HISTORY {
+            history.1.pop_front();
+        }
+        history.1.push_back(event.clone());
+        let _ = self.tx.send(event);
+    }
+
+    /// Events published from now on
+    pub fn subscribe(&self) -> impl Stream<Item = CoordinatorEvent> + Send + 'static {
+        self.subscribe_sequenced().map(|event| event.event)
+    }
+
+    /// Events published from now on, with their sequence numbers
+    pub fn subscribe_sequenced(&self) -> impl Stream<Item = SequencedEvent> + Send + 'static {
+        stream::unfold(self.tx.subscribe(), |mut rx| async move {
+            loop {
+                match rx.recv().await {
+                    Ok(event) => return Some((event, rx)),
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        tracing::warn!("Event subscriber lagged, {} events skipped", skipped);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                }
+            }
+        })
+    }
+
+    /// Up to `limit` of the most recent kept events for `session_id`,
+    /// oldest first, and the sequence number of the last event published
+    /// anywhere when they were read
+    pub fn recent(&self, session_id: &str, limit: usize) -> (u64, Vec<SequencedEvent>) {
+        let history = self.history.lock();
+        let mut events: Vec<_> = history
+            .1
+            .iter()
+            .rev()
+            .filter(|e| e.event.session_id() == Some(session_id))
+            .take(limit)
+            .cloned()
+            .collect();
+        events.reverse();
+        (history.0, events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+
+    #[tokio::test]
+    async fn test_subscribers_each_receive_events() {
+        let bus = EventBus::default();
+        bus.publish(CoordinatorEvent::TransferFailed {
+            session_id: "before".into(),
+            error: "nobody listening".into(),
+            tags: Default::default(),
+        });
+
+        let mut first = Box::pin(bus.subscribe());
+        let mut second = Box::pin(bus.subscribe());
+        bus.publish(CoordinatorEvent::TransferCompleted {
+            session_id: "s1".into(),
+            repaired_chunks: 0,
+            tags: Default::default(),
+        });
+
+        for events in [&mut first, &mut second] {
+            assert!(matches!(
+                events.next().await,
+                Some(CoordinatorEvent::TransferCompleted { session_id, .. }) if session_id == "s1"
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_lagging_subscriber_skips_oldest() {
+        let bus = EventBus::default();
+        let mut events = Box::pin(bus.subscribe());
+        for chunk_number in 0..EVENT_BUFFER as u32 + 10 {
+            bus.publish(CoordinatorEvent::ChunkRecovered {
+                session_id: "s1".into(),
+                chunk_number,
+            });
+        }
+
+        assert!(matches!(
+            events.next().await,
+            Some(CoordinatorEvent::ChunkRecovered {
+                chunk_number: 10,
+                ..
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_history_backfills_one_session() {
+        let bus = EventBus::default();
+        for chunk_number in 0..5 {
+            for session_id in ["s1", "s2"] {
+                bus.publish(CoordinatorEvent::ChunkRecovered {
+                    session_id: session_id.into(),
+                    chunk_number,
+                });
+            }
+        }
+
+        let (last_seq, recent) = bus.recent("s1", 3);
+        let chunks: Vec<_> = recent
+            .iter()
+            .map(|e| match e.event {
+                CoordinatorEvent::ChunkRecovered { chunk_number, .. } => chunk_number,
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(chunks, vec![2, 3, 4]);
+        assert_eq!(recent.last().unwrap().seq, 9);
+        assert_eq!(last_seq, 10);
+        assert!(bus.recent("s3", 3).1.is_empty());
+
+        // Live events continue the numbering
+        let mut live = Box::pin(bus.subscribe_sequenced());
+        bus.publish(CoordinatorEvent::TransferCompleted {
+            session_id: "s1".into(),
+            repaired_chunks: 0,
+            tags: Default::default(),
+        });
+        assert_eq!(live.next().await.unwrap().seq, 11);
+    }
+
+    #[test]
+    fn test_traced_relay_events_join_session_history() {
+        let bus = EventBus::default();
+        bus.publish(CoordinatorEvent::Relay {
+            node_id: "relay-a".into(),
+            event: RelayEvent::ChunkStored {
+                chunk_id: "c1".into(),
+                size: 16,
+                trace_id: Some("s1".into()),
+            },
+        });
+        bus.publish(CoordinatorEvent::Relay {
+            node_id: "relay-a".into(),
+            event: RelayEvent::ChunkExpired {
+                chunk_id: "c2".into(),
+            },
+        });
+
+        let (_, recent) = bus.recent("s1", 10);
+        assert_eq!(recent.len(), 1);
+        assert_eq!(recent[0].event.session_id(), Some("s1"));
+    }
+
+    #[test]
+    fn test_history_is_bounded() {
+        let bus = EventBus::default();
+        for chunk_number in 0..EVENT_HISTORY as u32 + 10 {
+            bus.publish(CoordinatorEvent::ChunkRecovered {
+                session_id: "s1".into(),
+                chunk_number,
+            });
+        }
+        let (_, recent) = bus.recent("s1", usize::MAX);
+        assert_eq!(recent.len(), EVENT_HISTORY);
+        assert_eq!(recent[0].seq, 11);
+    }
+
+    #[test]
+    fn test_events_serialize_with_type_tag() {
+        let json = serde_json::to_value(CoordinatorEvent::TransferFailed {
+            session_id: "s1".into(),
+            error: "boom".into(),
+            tags: [("incident_id".to_string(), "42".to_string())].into(),
+        })
+        .unwrap();
+        assert_eq!(json["type"], "transfer_failed");
+        assert_eq!(json["session_id"], "s1");
+        assert_eq!(json["tags"]["incident_id"], "42");
+    }
+}
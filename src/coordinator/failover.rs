@@ -0,0 +1,100 @@
+//! The coordinator's side of active/standby failover
+//!
+//! An active streams its session changes to standbys; a standby follows one
+//! active until it is promoted. A coordinator that does neither is
+//! standalone.
+
+use crate::coordinator::error::{CoordinatorError, CoordinatorResult};
+use crate::failover::{
+    FailoverRole, FailoverStatus, ReplicationLog, ReplicationServer, StandbyReplica,
+};
+use crate::network::{ConnectionConfig, QuicTransport};
+use crate::session::{SessionQuery, SessionRepository, SessionState, SessionStatus};
+use parking_lot::Mutex;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Replication served to standbys, or the active being followed
+#[derive(Default)]
+pub(crate) struct Failover {
+    // Streams session changes to standbys, on an active
+    replication: Mutex<Option<ReplicationServer>>,
+    // Follows the active, on a standby until promoted
+    standby: Mutex<Option<Arc<StandbyReplica>>>,
+}
+
+impl Failover {
+    /// Serve `store`'s changes from `log` on `config.bind_addr`
+    pub(crate) async fn serve(
+        &self,
+        config: ConnectionConfig,
+        log: Arc<ReplicationLog>,
+        store: Arc<dyn SessionRepository>,
+    ) -> CoordinatorResult<SocketAddr> {
+        let transport = QuicTransport::new(config).await?;
+        let server = ReplicationServer::start(transport, log, store);
+        let addr = server.status().listen_addr;
+        *self.replication.lock() = Some(server);
+        tracing::info!(%addr, "Serving replication to standbys");
+        Ok(addr)
+    }
+
+    /// Mirror the active on `active_addr` into `store`
+    pub(crate) fn follow(
+        &self,
+        transport: Arc<QuicTransport>,
+        active_addr: SocketAddr,
+        store: Arc<dyn SessionRepository>,
+        reconnect: Duration,
+    ) {
+        let replica = StandbyReplica::start(transport, active_addr, store, reconnect);
+        *self.standby.lock() = Some(Arc::new(replica));
+    }
+
+    pub(crate) fn is_standby(&self) -> bool {
+        self.standby.lock().is_some()
+    }
+
+    pub(crate) fn status(&self) -> FailoverStatus {
+        let replication = self.replication.lock().as_ref().map(|s| s.status());
+        let standby = self.standby.lock().as_ref().map(|r| r.status());
+        let role = match (&replication, &standby) {
+            (_, Some(_)) => FailoverRole::Standby,
+            (Some(_), None) => FailoverRole::Active,
+            (None, None) => FailoverRole::Standalone,
+        };
+        FailoverStatus {
+            role,
+            replication,
+            standby,
+        }
+    }
+
+    /// Stop following the active, returning the last change replicated
+    pub(crate) async fn stop_following(&self) -> CoordinatorResult<u64> {
+        let replica = self
+            .standby
+            .lock()
+            .clone()
+            .ok_or(CoordinatorError::NotStandby)?;
+        let replicated = replica.stop().await;
+        self.standby.lock().take();
+        Ok(replicated.last_seq)
+    }
+}
+
+/// Sessions the active was running when it was last heard from
+pub(crate) async fn running_sessions(
+    store: &dyn SessionRepository,
+) -> CoordinatorResult<Vec<SessionState>> {
+    let mut running = Vec::new();
+    for status in [SessionStatus::Initializing, SessionStatus::Active] {
+        let query = SessionQuery {
+            status: Some(status),
+            ..Default::default()
+        };
+        running.extend(store.query(&query).await?.sessions);
+    }
+    Ok(running)
+}
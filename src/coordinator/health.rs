@@ -0,0 +1,287 @@
+//! Liveness and readiness checks
+//!
+//! Orchestrators restart a process that isn't live and stop routing to one
+//! that isn't ready. Liveness only covers what a restart fixes: a send queue
+//! that holds chunks but has stopped draining. Readiness also covers what
+//! the process depends on (the session database, the QUIC endpoint, free
+//! disk) so traffic moves elsewhere until they recover.
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Component names in a [`HealthReport`]
+pub const SESSION_DB: &str = "session_db";
+pub const QUIC_ENDPOINT: &str = "quic_endpoint";
+pub const QUEUE: &str = "queue";
+pub const DISK: &str = "disk";
+
+/// Thresholds for the health checks
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HealthPolicy {
+    /// A queue holding chunks that dequeues none for this long is wedged
+    pub queue_stall: Duration,
+    /// Below this many free bytes on any of `disk_paths` the process isn't ready
+    pub min_free_disk_bytes: u64,
+    /// Filesystems the process writes to
+    pub disk_paths: Vec<PathBuf>,
+    /// How long the session database has to answer a ping
+    pub db_timeout: Duration,
+}
+
+impl Default for HealthPolicy {
+    fn default() -> Self {
+        Self {
+            queue_stall: Duration::from_secs(60),
+            min_free_disk_bytes: 256 * 1024 * 1024,
+            disk_paths: vec![PathBuf::from(".")],
+            db_timeout: Duration::from_secs(2),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthStatus {
+    Up,
+    /// Working, but close to a limit
+    Degraded,
+    Down,
+}
+
+/// Result of one check
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ComponentHealth {
+    pub name: String,
+    pub status: HealthStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+impl ComponentHealth {
+    pub fn up(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            status: HealthStatus::Up,
+            detail: None,
+        }
+    }
+
+    pub fn degraded(name: &str, detail: impl Into<String>) -> Self {
+        Self {
+            status: HealthStatus::Degraded,
+            detail: Some(detail.into()),
+            ..Self::up(name)
+        }
+    }
+
+    pub fn down(name: &str, detail: impl Into<String>) -> Self {
+        Self {
+            status: HealthStatus::Down,
+            detail: Some(detail.into()),
+            ..Self::up(name)
+        }
+    }
+}
+
+/// Checks behind a liveness or readiness probe
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HealthReport {
+    /// Worst status of any component
+    pub status: HealthStatus,
+    pub components: Vec<ComponentHealth>,
+    pub uptime_seconds: u64,
+}
+
+impl HealthReport {
+    pub fn new(components: Vec<ComponentHealth>, uptime_seconds: u64) -> Self {
+        let status = components
+            .iter()
+            .map(|c| c.status)
+            .max()
+            .unwrap_or(HealthStatus::Up);
+        Self {
+            status,
+            components,
+            uptime_seconds,
+        }
+    }
+
+    /// No component is down; degraded components still pass
+    pub fn is_healthy(&self) -> bool {
+        self.status != HealthStatus::Down
+    }
+
+    pub fn component(&self, name: &str) -> Option<&ComponentHealth> {
+        self.components.iter().find(|c| c.name == name)
+    }
+}
+
+/// Sizes of what the coordinator holds in memory
+///
+/// Once transfers settle and the retention sweep has run, each of these
+/// should fall back to a baseline; one that keeps climbing is a leak.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResourceUsage {
+    pub active_transfers: usize,
+    /// Finished transfers kept for display, bounded by the retention policy
+    pub recent_transfers: usize,
+    /// File to session mappings
+    pub file_sessions: usize,
+    /// Sessions with relay-dropped chunks waiting to be resent
+    pub relay_resends: usize,
+    /// Relayed sessions with chunks the last audit couldn't account for
+    pub audit_suspects: usize,
+    /// Running transfers with throughput samples held in memory
+    pub sampled_sessions: usize,
+    pub pending_transfers: usize,
+    pub queued_chunks: usize,
+    /// Open QUIC connections
+    pub connections: usize,
+}
+
+/// Notices a queue that holds chunks but has stopped dequeuing
+#[derive(Debug)]
+pub(crate) struct QueueProgress {
+    /// Dequeued count last seen and when it last changed
+    last: Mutex<Option<(u64, Instant)>>,
+}
+
+impl QueueProgress {
+    pub(crate) fn new() -> Self {
+        Self {
+            last: Mutex::new(None),
+        }
+    }
+
+    /// How long the queue has gone without dequeuing while `pending` chunks
+    /// wait; zero when it is empty or moving
+    pub(crate) fn stalled_for(&self, processed: u64, pending: usize) -> Duration {
+        let mut last = self.last.lock();
+        match *last {
+            Some((seen, since)) if seen == processed && pending > 0 => since.elapsed(),
+            _ => {
+                *last = Some((processed, Instant::now()));
+                Duration::ZERO
+            }
+        }
+    }
+}
+
+/// Check free space on each of `paths` against `min_free`
+///
+/// Down below `min_free` on any path, degraded below twice that.
+pub fn check_disk(paths: &[PathBuf], min_free: u64) -> ComponentHealth {
+    let mut lowest: Option<(&Path, u64)> = None;
+    for path in paths {
+        match free_disk_bytes(path) {
+            Ok(free) => {
+                if lowest.is_none_or(|(_, least)| free < least) {
+                    lowest = Some((path, free));
+                }
+            }
+            // Nothing to check on platforms without statvfs
+            Err(e) if e.kind() == std::io::ErrorKind::Unsupported => {}
+            Err(e) => return ComponentHealth::down(DISK, format!("{}: {e}", path.display())),
+        }
+    }
+    match lowest {
+        Some((path, free)) if free < min_free => ComponentHealth::down(
+            DISK,
+            format!("{}: {free} bytes free, need {min_free}", path.display()),
+        ),
+        Some((path, free)) if free < min_free.saturating_mul(2) => {
+            ComponentHealth::degraded(DISK, format!("{}: {free} bytes free", path.display()))
+        }
+        _ => ComponentHealth::up(DISK),
+    }
+}
+
+/// Bytes available to unprivileged writers on the filesystem holding `path`
+#[cfg(unix)]
+pub fn free_disk_bytes(path: &Path) -> std::io::Result<u64> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = std::ffi::CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+    let mut stat = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: c_path is NUL-terminated and stat is only read after
+    // statvfs reports success, which means it filled the struct
+    let stat = unsafe {
+        if libc::statvfs(c_path.as_ptr(), stat.as_mut_ptr()) != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        stat.assume_init()
+    };
+    #[allow(clippy::unnecessary_cast)]
+    Ok(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+/// Free disk space isn't checked on this platform
+#[cfg(not(unix))]
+pub fn free_disk_bytes(_path: &Path) -> std::io::Result<u64> {
+    Err(std::io::ErrorKind::Unsupported.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_takes_worst_status() {
+        let report = HealthReport::new(
+            vec![
+                ComponentHealth::up(SESSION_DB),
+                ComponentHealth::degraded(QUEUE, "slow"),
+            ],
+            5,
+        );
+        assert_eq!(report.status, HealthStatus::Degraded);
+        assert!(report.is_healthy());
+
+        let report = HealthReport::new(
+            vec![
+                ComponentHealth::down(DISK, "full"),
+                ComponentHealth::up(QUEUE),
+            ],
+            5,
+        );
+        assert!(!report.is_healthy());
+        assert_eq!(
+            report.component(DISK).unwrap().detail.as_deref(),
+            Some("full")
+        );
+    }
+
+    #[test]
+    fn test_queue_stall_needs_pending_chunks() {
+        let progress = QueueProgress::new();
+        assert_eq!(progress.stalled_for(10, 5), Duration::ZERO);
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(progress.stalled_for(10, 5) >= Duration::from_millis(20));
+
+        // Draining or empty resets the clock
+        assert_eq!(progress.stalled_for(11, 5), Duration::ZERO);
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(progress.stalled_for(11, 0), Duration::ZERO);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_disk_check() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = vec![dir.path().to_path_buf()];
+        assert!(free_disk_bytes(dir.path()).unwrap() > 0);
+        assert_eq!(check_disk(&paths, 1).status, HealthStatus::Up);
+        assert_eq!(check_disk(&paths, u64::MAX).status, HealthStatus::Down);
+        let free = free_disk_bytes(dir.path()).unwrap();
+        assert_eq!(
+            check_disk(&paths, free / 2 + 4096 * 1024).status,
+            HealthStatus::Degraded
+        );
+
+        let missing = vec![dir.path().join("no/such/dir")];
+        assert_eq!(check_disk(&missing, 1).status, HealthStatus::Down);
+    }
+}
@@ -0,0 +1,121 @@
+//! Periodic upkeep of the session database
+//!
+//! Deleted sessions leave free pages behind that SQLite doesn't return to
+//! the file system by itself. A background task reclaims them and refreshes
+//! the query planner's statistics on the configured schedule, then updates
+//! the database size gauges.
+
+use crate::coordinator::types::MaintenancePolicy;
+use crate::metrics::recorder;
+use crate::session::{MaintenanceReport, SessionRepository, SessionResult};
+use parking_lot::{Mutex, RwLock};
+use std::sync::{Arc, Weak};
+use tokio::sync::Notify;
+use tokio::time;
+
+/// Maintenance schedule and the result of the latest pass
+#[derive(Debug, Default)]
+pub(crate) struct Maintenance {
+    policy: RwLock<MaintenancePolicy>,
+    // Wakes the task so a new interval applies at once
+    changed: Notify,
+    last: Mutex<Option<MaintenanceReport>>,
+}
+
+impl Maintenance {
+    pub(crate) fn policy(&self) -> MaintenancePolicy {
+        *self.policy.read()
+    }
+
+    pub(crate) fn set_policy(&self, policy: MaintenancePolicy) {
+        *self.policy.write() = policy;
+        self.changed.notify_one();
+    }
+
+    pub(crate) fn last(&self) -> Option<MaintenanceReport> {
+        *self.last.lock()
+    }
+
+    /// One pass with the current policy's settings, whether or not it is
+    /// enabled; `None` if the store needs no maintenance
+    pub(crate) async fn run(
+        &self,
+        store: &dyn SessionRepository,
+    ) -> SessionResult<Option<MaintenanceReport>> {
+        let policy = self.policy();
+        let report = store.maintain(policy.vacuum_pages, policy.analyze).await?;
+        if let Some(report) = report {
+            recorder::record_session_db_maintenance(&report);
+            *self.last.lock() = Some(report);
+        }
+        Ok(report)
+    }
+
+    /// Maintain the store every interval while enabled, until it is dropped
+    pub(crate) async fn run_periodically(self: Arc<Self>, store: Weak<dyn SessionRepository>) {
+        loop {
+            let interval = self.policy().interval;
+            tokio::select! {
+                _ = time::sleep(interval) => {}
+                // Start waiting again with the new interval
+                _ = self.changed.notified() => continue,
+            }
+
+            let Some(store) = store.upgrade() else {
+                return;
+            };
+            if !self.policy().enabled {
+                continue;
+            }
+            match self.run(store.as_ref()).await {
+                Ok(Some(report)) => tracing::info!(
+                    "Session database maintained in {}ms, reclaimed {} bytes",
+                    report.duration_ms,
+                    report.reclaimed_bytes
+                ),
+                Ok(None) => continue,
+                Err(e) => tracing::warn!("Session database maintenance failed: {}", e),
+            }
+            match store.storage_stats().await {
+                Ok(Some(stats)) => recorder::set_session_db_stats(&stats),
+                Ok(None) => {}
+                Err(e) => tracing::warn!("Reading session database size failed: {}", e),
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+    use super::*;
+    use crate::session::SessionStore;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_runs_on_schedule_and_picks_up_new_interval() {
+        let store: Arc<dyn SessionRepository> =
+            Arc::new(SessionStore::new_in_memory().await.unwrap());
+        let maintenance = Arc::new(Maintenance::default());
+        let task = tokio::spawn(maintenance.clone().run_periodically(Arc::downgrade(&store)));
+
+        // The default hour is cut short by the new interval
+        maintenance.set_policy(MaintenancePolicy {
+            interval: Duration::from_millis(20),
+            ..Default::default()
+        });
+        time::timeout(Duration::from_secs(5), async {
+            while maintenance.last().is_none() {
+                time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        // Stops once the store is gone
+        drop(store);
+        time::timeout(Duration::from_secs(5), task)
+            .await
+            .unwrap()
+            .unwrap();
+    }
+}
@@ -1,9 +1,62 @@
+mod admission;
+mod catalog;
+#[allow(clippy::module_inception)]
 mod coordinator;
+mod defaults;
 mod error;
+mod events;
+mod failover;
+pub mod health;
+mod maintenance;
+mod profiles;
+mod receive;
+mod relay_audit;
+mod resume_token;
+mod retransmit;
+mod send;
+mod simulation;
 mod state_machine;
+mod stats;
+mod timeseries;
+mod transport;
 mod types;
+mod verify;
+mod window;
 
-pub use coordinator::{ComparisonResult, SimulateFileResult, TransferCoordinator};
+pub use admission::PendingTransfer;
+pub(crate) use catalog::validate_shares;
+pub use catalog::{CatalogEntry, CatalogShare, MAX_CATALOG_ENTRIES};
+pub use coordinator::{CoordinatorServices, TransferCoordinator};
+pub use defaults::{
+    ChunkingDefaults, ConfigChange, DefaultsSection, ErasureDefaults, TransferDefaults,
+    MAX_CONFIG_CHANGES,
+};
 pub use error::{CoordinatorError, CoordinatorResult};
+pub use events::{CoordinatorEvent, SequencedEvent, SessionBackfill, EVENT_BUFFER, EVENT_HISTORY};
+pub use health::{ComponentHealth, HealthPolicy, HealthReport, HealthStatus, ResourceUsage};
+pub use receive::{FeedbackReceiver, ReceiveService};
+pub use relay_audit::RelayAudit;
+pub use resume_token::{ResumeToken, RESUME_TOKEN_VERSION};
+pub use retransmit::{
+    FailedChunkRetries, RetransmitDecision, RetransmitPlan, RetransmitPlanner, RetransmitPolicy,
+    RetryController, RetryDecision, RetryPass, ShardSource,
+};
+pub use send::{SendService, TransferSender};
+pub use simulation::{
+    ComparisonPoint, ComparisonResult, LossSimulator, MeshSimulationResult, SimulateFileResult,
+    SimulationService,
+};
 pub use state_machine::TransferStateMachine;
-pub use types::{TransferEvent, TransferProgress, TransferState};
+pub use stats::{HealthTracker, StatsService};
+pub use timeseries::{MAX_SAMPLES, SAMPLE_INTERVAL};
+pub use transport::{RepairAnswer, Transport, TransportLink};
+pub use types::{
+    ConnectPolicy, DuplicatePolicy, MaintenancePolicy, ResendRoute, RetentionPolicy, SendPolicy,
+    TransferEvent, TransferProgress, TransferSource, TransferState, DEFAULT_MAX_STREAM_BYTES,
+    MEMORY_FILE_ID_PREFIX,
+};
+pub use verify::{
+    FileVerification, VerifyStatus, VerifyTarget, DEFAULT_VERIFY_CONCURRENCY,
+    MAX_VERIFY_CONCURRENCY, MAX_VERIFY_TARGETS,
+};
+pub use window::{SessionWindow, DEFAULT_SESSION_WINDOW};
@@ -0,0 +1,42 @@
+//! Checks on transfer profiles and benchmark reports before they are stored
+
+use crate::coordinator::error::{CoordinatorError, CoordinatorResult};
+
+/// Longest transfer profile name
+const MAX_PROFILE_NAME_LEN: usize = 64;
+
+/// Longest benchmark report label
+const MAX_BENCHMARK_LABEL_LEN: usize = 128;
+
+/// Profile names are 1-64 ASCII letters, digits, '-', '_' or '.'
+pub(crate) fn validate_profile_name(name: &str) -> CoordinatorResult<()> {
+    let name_ok = !name.is_empty()
+        && name.len() <= MAX_PROFILE_NAME_LEN
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if !name_ok {
+        return Err(CoordinatorError::InvalidConfig(format!(
+            "profile name {name:?} must be 1-{MAX_PROFILE_NAME_LEN} letters, digits, '-', '_' or '.'"
+        )));
+    }
+    Ok(())
+}
+
+/// Benchmark reports are JSON objects with an optional short label
+pub(crate) fn validate_benchmark(
+    label: Option<&str>,
+    report: &serde_json::Value,
+) -> CoordinatorResult<()> {
+    if !report.is_object() {
+        return Err(CoordinatorError::InvalidConfig(
+            "benchmark report must be a JSON object".to_string(),
+        ));
+    }
+    if label.is_some_and(|l| l.len() > MAX_BENCHMARK_LABEL_LEN) {
+        return Err(CoordinatorError::InvalidConfig(format!(
+            "benchmark label must be at most {MAX_BENCHMARK_LABEL_LEN} bytes"
+        )));
+    }
+    Ok(())
+}
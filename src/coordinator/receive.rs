@@ -0,0 +1,496 @@
+//! What comes back to the sender once chunks are out
+//!
+//! Receivers ask for repairs of files that arrived damaged, and relays
+//! report chunks they had to drop. Both are answered here, along with the
+//! audits that catch chunks a relay lost without saying so.
+
+use crate::chunk::{ChunkManager, Priority};
+use crate::coordinator::error::{CoordinatorError, CoordinatorResult};
+use crate::coordinator::events::{CoordinatorEvent, EventBus};
+use crate::coordinator::relay_audit::{self, AuditSuspects, RelayAudit};
+use crate::coordinator::transport::Transport;
+use crate::coordinator::types::ResendRoute;
+use crate::metrics::recorder;
+use crate::network::{NetworkError, RepairReply, RepairRequest};
+use crate::relay::node::RelayEvent;
+use crate::relay::{ExpiredNotice, RelayNode, RouteInfo};
+use crate::session::{SessionQuery, SessionRepository, SessionState, SessionStatus};
+use crate::sync::DeltaBuilder;
+use dashmap::DashMap;
+use futures::future::BoxFuture;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time;
+
+/// Handles repair requests from receivers and chunk reports from relays
+pub trait ReceiveService: Send + Sync {
+    /// Chunks of a transfer relays dropped that haven't been taken for resend
+    fn pending_relay_resends(&self, session_id: &str) -> u32;
+
+    /// Sessions with chunks waiting for resend, and with audit suspects
+    fn tracked_sessions(&self) -> (usize, usize);
+
+    /// Mark a chunk a relay dropped for resend
+    ///
+    /// The chunk no longer counts as delivered, and its route is kept until
+    /// [`take_relay_resends`](Self::take_relay_resends). A partially
+    /// delivered transfer has one chunk fewer held by relays.
+    fn handle_expired_notice<'a>(
+        &'a self,
+        notice: &'a ExpiredNotice,
+    ) -> BoxFuture<'a, CoordinatorResult<ResendRoute>>;
+
+    /// Chunks of a transfer relays dropped, with how to resend each
+    ///
+    /// Returned chunks are no longer counted as pending.
+    fn take_relay_resends(&self, session_id: &str) -> Vec<(u32, ResendRoute)>;
+
+    /// Audit every critical transfer partially delivered through relays
+    ///
+    /// Each of `relays` is asked what it holds of the transfer and what it
+    /// has delivered. Chunks nobody accounts for on two audits in a row are
+    /// stored on the first relay that takes them, or marked for a direct
+    /// resend if none does.
+    fn audit_relays<'a>(
+        &'a self,
+        relays: &'a [Arc<RelayNode>],
+    ) -> BoxFuture<'a, CoordinatorResult<Vec<RelayAudit>>>;
+
+    /// Run [`audit_relays`](Self::audit_relays) every `interval` until the
+    /// task is aborted
+    fn spawn_relay_auditor(
+        &self,
+        relays: Vec<Arc<RelayNode>>,
+        interval: Duration,
+    ) -> JoinHandle<()>;
+
+    /// Republish a relay node's events to subscribers
+    ///
+    /// Pass the receiving end of the channel given to
+    /// [`RelayNode::with_events`](crate::relay::RelayNode::with_events).
+    /// Expiry notices the relay passes back are handled with
+    /// [`handle_expired_notice`](Self::handle_expired_notice) before being
+    /// republished. The task ends when the relay drops its sender.
+    fn forward_relay_events(
+        &self,
+        node_id: String,
+        relay_events: mpsc::Receiver<RelayEvent>,
+    ) -> JoinHandle<()>;
+
+    /// Answer receivers asking to repair files this coordinator sent
+    ///
+    /// Accepts links on the coordinator's transport until it closes. A request is answered with a patch only if the file at its
+    /// `file_id` path still has the checksum the receiver asks for, so a
+    /// peer has to know the file's checksum to learn anything about it.
+    fn serve_repairs(&self) -> JoinHandle<()>;
+}
+
+/// [`ReceiveService`] backed by the session store, publishing what it
+/// handles as coordinator events
+#[derive(Clone)]
+pub struct FeedbackReceiver {
+    transport: Arc<dyn Transport>,
+    session_store: Arc<dyn SessionRepository>,
+    events: EventBus,
+
+    // Chunks relays dropped, by session, waiting to be resent
+    relay_resends: Arc<DashMap<String, HashMap<u32, ResendRoute>>>,
+
+    // Chunks of relayed transfers the last audit couldn't account for
+    audit_suspects: Arc<AuditSuspects>,
+}
+
+impl FeedbackReceiver {
+    pub(crate) fn new(
+        transport: Arc<dyn Transport>,
+        session_store: Arc<dyn SessionRepository>,
+        events: EventBus,
+    ) -> Self {
+        Self {
+            transport,
+            session_store,
+            events,
+            relay_resends: Arc::new(DashMap::new()),
+            audit_suspects: Arc::new(AuditSuspects::default()),
+        }
+    }
+
+    async fn audit_relay_session(
+        &self,
+        mut session: SessionState,
+        relays: &[Arc<RelayNode>],
+    ) -> CoordinatorResult<RelayAudit> {
+        let session_id = session.session_id.clone();
+        let holdings: Vec<_> = relays.iter().map(|r| r.holdings(&session_id)).collect();
+        let pending: HashSet<u32> = self
+            .relay_resends
+            .get(&session_id)
+            .map(|resends| resends.keys().copied().collect())
+            .unwrap_or_default();
+        let unaccounted: Vec<u32> = relay_audit::unaccounted_chunks(
+            session.manifest.total_chunks,
+            &session.completed_chunks,
+            &holdings,
+        )
+        .into_iter()
+        .filter(|n| !pending.contains(n))
+        .collect();
+        let missing = self.audit_suspects.confirm(&session_id, &unaccounted);
+
+        let count = |f: fn(&crate::relay::TransferHoldings) -> &Vec<u32>| {
+            holdings.iter().flat_map(f).collect::<HashSet<_>>().len() as u32
+        };
+        let mut audit = RelayAudit {
+            session_id: session_id.clone(),
+            relays: holdings.len(),
+            held: count(|h| &h.held),
+            delivered: count(|h| &h.delivered),
+            missing,
+            reinjected: Vec::new(),
+        };
+        if audit.missing.is_empty() {
+            return Ok(audit);
+        }
+        self.audit_suspects.clear(&session_id);
+
+        audit.reinjected = self.reinject_chunks(&session, &audit.missing, relays).await;
+        let stranded: Vec<u32> = audit
+            .missing
+            .iter()
+            .copied()
+            .filter(|n| !audit.reinjected.contains(n))
+            .collect();
+        // Chunks no relay would take are left to a direct resend, and no
+        // longer count as held by relays
+        if !stranded.is_empty() {
+            let mut resends = self.relay_resends.entry(session_id.clone()).or_default();
+            for &chunk_number in &stranded {
+                resends.entry(chunk_number).or_insert(ResendRoute::Direct);
+                session.mark_failed(chunk_number);
+            }
+            drop(resends);
+            if let SessionStatus::PartiallyDelivered {
+                delivered_chunks,
+                held_by_relay,
+            } = session.status
+            {
+                session.status = SessionStatus::PartiallyDelivered {
+                    delivered_chunks,
+                    held_by_relay: held_by_relay.saturating_sub(stranded.len() as u32),
+                };
+            }
+            self.session_store.save(&session).await?;
+        }
+
+        tracing::warn!(
+            session_id,
+            missing = ?audit.missing,
+            reinjected = audit.reinjected.len(),
+            "relayed chunks went missing"
+        );
+        recorder::record_relay_chunks_reinjected(audit.reinjected.len() as u64);
+        self.events
+            .publish(CoordinatorEvent::RelayChunksReinjected {
+                session_id,
+                missing: audit.missing.clone(),
+                reinjected: audit.reinjected.clone(),
+            });
+        Ok(audit)
+    }
+
+    /// Re-read `chunk_numbers` from the session's file and store each on the
+    /// first of `relays` that takes it; returns those stored
+    async fn reinject_chunks(
+        &self,
+        session: &SessionState,
+        chunk_numbers: &[u32],
+        relays: &[Arc<RelayNode>],
+    ) -> Vec<u32> {
+        let session_id = &session.session_id;
+        let Some(destination) = session.receiver_addr else {
+            tracing::warn!(session_id, "No receiver address, cannot re-inject chunks");
+            return Vec::new();
+        };
+        let Some(file_path) = session.file_path.as_ref().map(PathBuf::from) else {
+            tracing::warn!(session_id, "No file path stored, cannot re-inject chunks");
+            return Vec::new();
+        };
+        let split = match ChunkManager::for_manifest(&session.manifest) {
+            Ok(manager) => {
+                manager
+                    .split_file(
+                        &file_path,
+                        session.file_id.clone(),
+                        session.manifest.priority,
+                    )
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+        let chunks = match split {
+            Ok((_, chunks)) => chunks,
+            Err(e) => {
+                tracing::warn!(session_id, "Failed to re-read file for re-injection: {}", e);
+                return Vec::new();
+            }
+        };
+
+        let mut reinjected = Vec::new();
+        for chunk in chunks {
+            let chunk_number = chunk.metadata.sequence_number;
+            if !chunk_numbers.contains(&chunk_number) {
+                continue;
+            }
+            let route = RouteInfo::new(
+                relay_audit::ORIGIN,
+                destination,
+                session_id.clone(),
+                session.manifest.priority.level(),
+            )
+            .with_sequence(chunk_number);
+            let chunk_id = format!("{session_id}:{chunk_number}");
+            for relay in relays {
+                match relay
+                    .receive_chunk(chunk_id.clone(), route.clone(), chunk.clone())
+                    .await
+                {
+                    Ok(()) => {
+                        reinjected.push(chunk_number);
+                        break;
+                    }
+                    Err(e) => {
+                        tracing::debug!(
+                            session_id,
+                            chunk_number,
+                            node_id = relay.node_id(),
+                            "Relay refused re-injected chunk: {}",
+                            e
+                        )
+                    }
+                }
+            }
+        }
+        reinjected
+    }
+
+    /// Patch turning the receiver's copy described by `request` back into
+    /// the file as sent
+    async fn repair_reply(&self, request: RepairRequest) -> RepairReply {
+        if request.signature.block_size == 0 {
+            return RepairReply::Unavailable("signature has no block size".into());
+        }
+        let data = match tokio::fs::read(&request.file_id).await {
+            Ok(data) => data,
+            Err(e) => return RepairReply::Unavailable(format!("{}: {}", request.file_id, e)),
+        };
+        if request.checksum_algorithm.digest(&data) != request.checksum {
+            return RepairReply::Unavailable(format!(
+                "{} has changed since it was sent",
+                request.file_id
+            ));
+        }
+
+        let file_id = request.file_id;
+        let patch = tokio::task::spawn_blocking(move || {
+            DeltaBuilder::new()
+                .block_size(request.signature.block_size as usize)
+                .build(&request.signature, &data)
+        })
+        .await;
+        match patch {
+            Ok(patch) => {
+                let stats = patch.stats();
+                tracing::info!(
+                    "Repairing {}: {} bytes resent, {} reused",
+                    file_id,
+                    stats.inserted_bytes,
+                    stats.copied_bytes
+                );
+                RepairReply::Patch(patch)
+            }
+            Err(e) => RepairReply::Unavailable(e.to_string()),
+        }
+    }
+}
+
+impl ReceiveService for FeedbackReceiver {
+    fn pending_relay_resends(&self, session_id: &str) -> u32 {
+        self.relay_resends
+            .get(session_id)
+            .map_or(0, |resends| resends.len() as u32)
+    }
+
+    fn tracked_sessions(&self) -> (usize, usize) {
+        (self.relay_resends.len(), self.audit_suspects.len())
+    }
+
+    fn handle_expired_notice<'a>(
+        &'a self,
+        notice: &'a ExpiredNotice,
+    ) -> BoxFuture<'a, CoordinatorResult<ResendRoute>> {
+        Box::pin(async move {
+            let session_id = &notice.transfer_id;
+            let chunk_number = notice.sequence_number.ok_or_else(|| {
+                CoordinatorError::InvalidExpiryNotice(format!(
+                    "{} has no sequence number",
+                    notice.chunk_id
+                ))
+            })?;
+            let mut session = self
+                .session_store
+                .load(session_id)
+                .await?
+                .ok_or_else(|| CoordinatorError::TransferNotFound(session_id.to_string()))?;
+
+            session.completed_chunks.remove(&chunk_number);
+            session.mark_failed(chunk_number);
+            session.metrics.relay_expired_chunks += 1;
+            if let SessionStatus::PartiallyDelivered {
+                delivered_chunks,
+                held_by_relay,
+            } = session.status
+            {
+                session.status = SessionStatus::PartiallyDelivered {
+                    delivered_chunks,
+                    held_by_relay: held_by_relay.saturating_sub(1),
+                };
+            }
+            self.session_store.save(&session).await?;
+
+            let mut resends = self.relay_resends.entry(session_id.clone()).or_default();
+            let mut route = ResendRoute::for_notice(notice);
+            if let Some(earlier) = resends.remove(&chunk_number) {
+                route = route.merge(earlier);
+            }
+            resends.insert(chunk_number, route.clone());
+            drop(resends);
+
+            tracing::warn!(
+                session_id,
+                chunk_number,
+                dropped_by = %notice.dropped_by,
+                reason = ?notice.reason,
+                ?route,
+                "relay dropped chunk, marked for resend"
+            );
+            self.events.publish(CoordinatorEvent::RelayChunkExpired {
+                session_id: session_id.clone(),
+                chunk_number,
+                dropped_by: notice.dropped_by.clone(),
+                reason: notice.reason,
+                route: route.clone(),
+            });
+            Ok(route)
+        })
+    }
+
+    fn take_relay_resends(&self, session_id: &str) -> Vec<(u32, ResendRoute)> {
+        let mut resends: Vec<_> = self
+            .relay_resends
+            .remove(session_id)
+            .map(|(_, resends)| resends.into_iter().collect())
+            .unwrap_or_default();
+        resends.sort_unstable_by_key(|(chunk_number, _)| *chunk_number);
+        resends
+    }
+
+    fn audit_relays<'a>(
+        &'a self,
+        relays: &'a [Arc<RelayNode>],
+    ) -> BoxFuture<'a, CoordinatorResult<Vec<RelayAudit>>> {
+        Box::pin(async move {
+            let page = self
+                .session_store
+                .query(&SessionQuery {
+                    status: Some(SessionStatus::PartiallyDelivered {
+                        delivered_chunks: 0,
+                        held_by_relay: 0,
+                    }),
+                    ..Default::default()
+                })
+                .await?;
+            let sessions: Vec<SessionState> = page
+                .sessions
+                .into_iter()
+                .filter(|s| s.manifest.priority == Priority::Critical)
+                .collect();
+            self.audit_suspects
+                .retain(&sessions.iter().map(|s| s.session_id.clone()).collect());
+
+            let mut audits = Vec::with_capacity(sessions.len());
+            for session in sessions {
+                audits.push(self.audit_relay_session(session, relays).await?);
+            }
+            Ok(audits)
+        })
+    }
+
+    fn spawn_relay_auditor(
+        &self,
+        relays: Vec<Arc<RelayNode>>,
+        interval: Duration,
+    ) -> JoinHandle<()> {
+        let service = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = time::interval(interval);
+            // The first tick fires at once; give relays an interval first
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                if let Err(e) = service.audit_relays(&relays).await {
+                    tracing::warn!("Relay audit failed: {}", e);
+                }
+            }
+        })
+    }
+
+    fn forward_relay_events(
+        &self,
+        node_id: String,
+        mut relay_events: mpsc::Receiver<RelayEvent>,
+    ) -> JoinHandle<()> {
+        let service = self.clone();
+        tokio::spawn(async move {
+            while let Some(event) = relay_events.recv().await {
+                if let RelayEvent::ChunkUndeliverable { notice } = &event {
+                    if let Err(e) = service.handle_expired_notice(notice).await {
+                        tracing::warn!(chunk_id = %notice.chunk_id, "ignoring expiry notice: {}", e);
+                    }
+                }
+                service.events.publish(CoordinatorEvent::Relay {
+                    node_id: node_id.clone(),
+                    event,
+                });
+            }
+        })
+    }
+
+    fn serve_repairs(&self) -> JoinHandle<()> {
+        let service = self.clone();
+        tokio::spawn(async move {
+            loop {
+                let link = match service.transport.clone().accept().await {
+                    Ok(link) => link,
+                    Err(NetworkError::ConnectionClosed(_)) => break,
+                    Err(e) => {
+                        tracing::debug!("Repair connection failed: {}", e);
+                        continue;
+                    }
+                };
+                let service = service.clone();
+                tokio::spawn(async move {
+                    while let Ok((request, answer)) = link.accept_repair().await {
+                        let reply = service.repair_reply(request).await;
+                        if let Err(e) = answer(reply).await {
+                            tracing::warn!("Could not answer repair request: {}", e);
+                        }
+                    }
+                });
+            }
+        })
+    }
+}
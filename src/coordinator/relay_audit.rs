@@ -0,0 +1,138 @@
+//! Audits of critical transfers handed to relays
+//!
+//! Once a transfer is partially delivered, the chunks the receiver hasn't
+//! got are the relays' to deliver. A relay that drops one sends an expiry
+//! notice back, but a relay that restarts without persistence, or a notice
+//! lost on the way, leaves a chunk that nobody holds and nobody will resend.
+//! The auditor closes that gap: it asks each relay which chunks of the
+//! transfer it holds and which it has delivered, and re-injects the rest.
+//!
+//! A chunk moving between relays is briefly held by neither, so a chunk
+//! only counts as missing once two audits in a row found it missing.
+
+use crate::relay::TransferHoldings;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// Route origin of re-injected chunks; expiry notices for them come back
+/// by transfer id, like any other
+pub(crate) const ORIGIN: &str = "sender";
+
+/// Outcome of auditing one transfer
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RelayAudit {
+    pub session_id: String,
+    /// Relays that answered
+    pub relays: usize,
+    /// Chunks at least one relay holds
+    pub held: u32,
+    /// Chunks relays report handing to the receiver
+    pub delivered: u32,
+    /// Chunks neither delivered nor held, confirmed by two audits
+    pub missing: Vec<u32>,
+    /// Missing chunks stored on a relay again
+    pub reinjected: Vec<u32>,
+}
+
+/// Chunks of a transfer neither acknowledged, delivered nor held by any relay
+pub(crate) fn unaccounted_chunks(
+    total_chunks: u32,
+    completed: &HashSet<u32>,
+    holdings: &[TransferHoldings],
+) -> Vec<u32> {
+    let accounted: HashSet<u32> = holdings
+        .iter()
+        .flat_map(|h| h.held.iter().chain(&h.delivered))
+        .copied()
+        .collect();
+    (0..total_chunks)
+        .filter(|n| !completed.contains(n) && !accounted.contains(n))
+        .collect()
+}
+
+/// Chunks each transfer's last audit found unaccounted for
+#[derive(Debug, Default)]
+pub(crate) struct AuditSuspects {
+    by_session: Mutex<HashMap<String, HashSet<u32>>>,
+}
+
+impl AuditSuspects {
+    /// Record this audit's unaccounted chunks and return those the previous
+    /// audit found too
+    pub(crate) fn confirm(&self, session_id: &str, unaccounted: &[u32]) -> Vec<u32> {
+        let mut by_session = self.by_session.lock();
+        let previous = by_session.remove(session_id).unwrap_or_default();
+        let confirmed = unaccounted
+            .iter()
+            .copied()
+            .filter(|n| previous.contains(n))
+            .collect();
+        if !unaccounted.is_empty() {
+            by_session.insert(
+                session_id.to_string(),
+                unaccounted.iter().copied().collect(),
+            );
+        }
+        confirmed
+    }
+
+    /// Forget sessions that are no longer audited
+    pub(crate) fn retain(&self, sessions: &HashSet<String>) {
+        self.by_session
+            .lock()
+            .retain(|session_id, _| sessions.contains(session_id));
+    }
+
+    /// Forget a session's suspects, such as chunks just re-injected
+    pub(crate) fn clear(&self, session_id: &str) {
+        self.by_session.lock().remove(session_id);
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.by_session.lock().len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unaccounted_chunks() {
+        let completed = HashSet::from([0, 1]);
+        let holdings = [
+            TransferHoldings {
+                held: vec![2, 3],
+                delivered: vec![4],
+                ..Default::default()
+            },
+            TransferHoldings {
+                held: vec![3],
+                delivered: vec![6],
+                ..Default::default()
+            },
+        ];
+        assert_eq!(unaccounted_chunks(8, &completed, &holdings), [5, 7]);
+        assert_eq!(unaccounted_chunks(8, &completed, &[]), [2, 3, 4, 5, 6, 7]);
+    }
+
+    #[test]
+    fn test_chunks_need_two_audits_to_be_missing() {
+        let suspects = AuditSuspects::default();
+        assert!(suspects.confirm("s1", &[3, 5]).is_empty());
+        // 3 turned up on a relay in the meantime
+        assert_eq!(suspects.confirm("s1", &[5, 7]), [5]);
+        assert_eq!(suspects.confirm("s1", &[5, 7]), [5, 7]);
+
+        assert!(suspects.confirm("s1", &[]).is_empty());
+        assert_eq!(suspects.len(), 0);
+
+        suspects.confirm("s1", &[1]);
+        suspects.confirm("s2", &[1]);
+        suspects.retain(&HashSet::from(["s2".to_string()]));
+        assert_eq!(suspects.len(), 1);
+        suspects.clear("s2");
+        assert_eq!(suspects.len(), 0);
+    }
+}
@@ -0,0 +1,189 @@
+//! Portable resume tokens
+//!
+//! A [`ResumeToken`] carries everything another sender needs to pick up a
+//! session: the manifest, which chunks the receiver already has and where
+//! the receiver is. The new host re-splits its own copy of the file, checks
+//! it produces the same manifest, and sends only the missing chunks under
+//! the original session and file ids, so the receiver keeps assembling the
+//! same transfer.
+//!
+//! Tokens are signed with a keyed Blake3 hash when the coordinator has a
+//! shared secret; a coordinator with a secret rejects tokens it can't
+//! verify.
+
+use crate::chunk::FileManifest;
+use crate::coordinator::error::{CoordinatorError, CoordinatorResult};
+use crate::session::SessionState;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::net::SocketAddr;
+
+/// Token format written by this version
+pub const RESUME_TOKEN_VERSION: u32 = 1;
+
+/// Context string for deriving the signing key from the shared secret
+const KEY_CONTEXT: &str = "chunkstream_pro 2024 resume token signing key";
+
+/// Exported state of a session, for resuming it on another host
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResumeToken {
+    pub version: u32,
+    pub session_id: String,
+    pub file_id: String,
+    pub manifest: FileManifest,
+    /// Bit `n` set when chunk `n` has reached the receiver
+    pub completed: Vec<u8>,
+    pub receiver_addr: Option<SocketAddr>,
+    /// Unix timestamp the token was exported
+    pub issued_at: i64,
+    /// Hex keyed Blake3 hash of the other fields; `None` when exported
+    /// without a secret
+    pub signature: Option<String>,
+}
+
+impl ResumeToken {
+    /// Token for `session`, signed if `key` is set
+    pub fn from_session(session: &SessionState, key: Option<&[u8; 32]>) -> Self {
+        let mut completed = vec![0u8; (session.manifest.total_chunks as usize).div_ceil(8)];
+        for &chunk in &session.completed_chunks {
+            if let Some(byte) = completed.get_mut(chunk as usize / 8) {
+                *byte |= 1 << (chunk % 8);
+            }
+        }
+
+        let mut token = Self {
+            version: RESUME_TOKEN_VERSION,
+            session_id: session.session_id.clone(),
+            file_id: session.file_id.clone(),
+            manifest: session.manifest.clone(),
+            completed,
+            receiver_addr: session.receiver_addr,
+            issued_at: chrono::Utc::now().timestamp(),
+            signature: None,
+        };
+        token.signature = key.map(|key| token.mac(key).to_hex().to_string());
+        token
+    }
+
+    /// Chunks the receiver already has
+    pub fn completed_chunks(&self) -> HashSet<u32> {
+        (0..self.manifest.total_chunks)
+            .filter(|&n| {
+                self.completed
+                    .get(n as usize / 8)
+                    .is_some_and(|byte| byte & (1 << (n % 8)) != 0)
+            })
+            .collect()
+    }
+
+    /// Check the version and, when `key` is set, the signature
+    pub fn verify(&self, key: Option<&[u8; 32]>) -> CoordinatorResult<()> {
+        if self.version != RESUME_TOKEN_VERSION {
+            return Err(CoordinatorError::InvalidResumeToken(format!(
+                "unsupported version {}",
+                self.version
+            )));
+        }
+        if self.completed.len() != (self.manifest.total_chunks as usize).div_ceil(8) {
+            return Err(CoordinatorError::InvalidResumeToken(
+                "completed bitmap doesn't match the manifest".into(),
+            ));
+        }
+
+        let Some(key) = key else {
+            return Ok(());
+        };
+        let signature = self
+            .signature
+            .as_deref()
+            .ok_or_else(|| CoordinatorError::InvalidResumeToken("token is unsigned".into()))?;
+        // Hash equality is constant time
+        match blake3::Hash::from_hex(signature) {
+            Ok(signature) if signature == self.mac(key) => Ok(()),
+            _ => Err(CoordinatorError::InvalidResumeToken(
+                "signature mismatch".into(),
+            )),
+        }
+    }
+
+    /// Signing key for a shared secret
+    pub fn derive_key(secret: &str) -> [u8; 32] {
+        blake3::derive_key(KEY_CONTEXT, secret.as_bytes())
+    }
+
+    fn mac(&self, key: &[u8; 32]) -> blake3::Hash {
+        let unsigned = Self {
+            signature: None,
+            ..self.clone()
+        };
+        let payload = serde_json::to_vec(&unsigned).expect("resume token serializes");
+        blake3::keyed_hash(key, &payload)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Priority;
+
+    fn session() -> SessionState {
+        let manifest = FileManifest {
+            file_id: "/data/survey.bin".into(),
+            filename: "survey.bin".into(),
+            total_size: 10 * 1024,
+            chunk_size: 1024,
+            total_chunks: 12,
+            data_chunks: 10,
+            parity_chunks: 2,
+            priority: Priority::High,
+            checksum: [7; 32],
+            zero_runs: Vec::new(),
+            attributes: None,
+            checksum_algorithm: Default::default(),
+            erasure_profile: Default::default(),
+            schedule: None,
+            merkle_root: None,
+            compression: None,
+        };
+        let mut session = SessionState::new_with_receiver(
+            "session-1".into(),
+            manifest.file_id.clone(),
+            manifest,
+            Some("10.0.0.2:5001".parse().unwrap()),
+            Some("/data/survey.bin".into()),
+        );
+        for chunk in [0, 3, 8, 11] {
+            session.mark_completed(chunk);
+        }
+        session
+    }
+
+    #[test]
+    fn test_round_trip_keeps_completed_chunks() {
+        let token = ResumeToken::from_session(&session(), None);
+        assert_eq!(token.completed.len(), 2);
+
+        let json = serde_json::to_string(&token).unwrap();
+        let parsed: ResumeToken = serde_json::from_str(&json).unwrap();
+        assert_eq!(serde_json::to_string(&parsed).unwrap(), json);
+        assert_eq!(parsed.completed_chunks(), HashSet::from([0, 3, 8, 11]));
+        assert!(parsed.verify(None).is_ok());
+    }
+
+    #[test]
+    fn test_signature_checked_with_secret() {
+        let key = ResumeToken::derive_key("field-team");
+        let token = ResumeToken::from_session(&session(), Some(&key));
+        assert!(token.verify(Some(&key)).is_ok());
+
+        let other = ResumeToken::derive_key("someone-else");
+        assert!(token.verify(Some(&other)).is_err());
+
+        let mut tampered = token.clone();
+        tampered.receiver_addr = Some("192.0.2.1:5001".parse().unwrap());
+        assert!(tampered.verify(Some(&key)).is_err());
+
+        let unsigned = ResumeToken::from_session(&session(), None);
+        assert!(unsigned.verify(Some(&key)).is_err());
+    }
+}
@@ -0,0 +1,569 @@
+//! Retransmission of shards FEC couldn't cover
+//!
+//! After the last chunk of a transfer goes out, the receiver reports which
+//! shards of the file's FEC group it holds. If it has fewer than the group's
+//! data shard count, parity alone can't rebuild the file, and the
+//! [`RetransmitPlanner`] picks the smallest set of missing shards that
+//! closes the gap. Each group has a fixed shard budget; a gap larger than
+//! what is left of it is not worth sending, because the group could not be
+//! decoded anyway.
+//!
+//! Any missing shard closes the gap as well as any other, so the sender
+//! isn't limited to the shards it still holds. While it holds at least a
+//! group's worth, it can regenerate the rest: parity by encoding, which is
+//! cheap, and data by reconstruction, which costs a decode. The planner
+//! picks the fewest bytes first, then held shards, then regenerated parity,
+//! then regenerated data.
+//!
+//! Chunks whose sends failed outright are a separate matter: the sender
+//! knows about them without asking. Once everything else has gone out,
+//! [`FailedChunkRetries`] schedules passes over them, waiting longer before
+//! each, until every chunk has had its retries.
+//!
+//! Before any of that, a send that fails is retried on the spot. On a lossy
+//! link those in-flight retries spend bytes parity could have covered, so
+//! the [`RetryController`] decides per chunk: while the parity not yet
+//! spoken for exceeds the losses expected in the rest of the transfer, a
+//! chunk is sent once and, if it fails, left to parity. Otherwise it gets
+//! its retries now and a place in the later passes.
+
+use crate::network::GroupFeedback;
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+/// How much a transfer may resend once the receiver reports its losses
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetransmitPolicy {
+    /// Shards resent per FEC group over the life of a transfer; 0 disables
+    /// retransmission and the sender doesn't wait for group reports
+    pub budget_per_group: u32,
+    /// How long to wait for a group report after the last chunk goes out
+    pub feedback_timeout: Duration,
+    /// Extra attempts for a chunk whose sends failed; 0 leaves failed
+    /// chunks to parity
+    pub failed_chunk_retries: u32,
+    /// Wait before the first pass over failed chunks; doubles each pass
+    pub retry_backoff: Duration,
+    /// Longest wait between passes
+    pub max_retry_backoff: Duration,
+    /// Attempts beyond the first for a chunk that is retried on the spot
+    pub in_flight_retries: u32,
+    /// Send chunks once and leave failures to parity while there is
+    /// headroom; off retries every chunk
+    pub adaptive_retries: bool,
+}
+
+impl Default for RetransmitPolicy {
+    fn default() -> Self {
+        Self {
+            budget_per_group: 16,
+            feedback_timeout: Duration::from_secs(2),
+            failed_chunk_retries: 3,
+            retry_backoff: Duration::from_millis(500),
+            max_retry_backoff: Duration::from_secs(8),
+            in_flight_retries: 3,
+            adaptive_retries: true,
+        }
+    }
+}
+
+/// One pass over a transfer's failed chunks
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryPass {
+    /// Wait this long, then requeue `chunks`
+    pub backoff: Duration,
+    pub chunks: Vec<u32>,
+}
+
+/// Chunks of one transfer whose sends failed, and the retries each has had
+#[derive(Debug, Clone)]
+pub struct FailedChunkRetries {
+    max_retries: u32,
+    backoff: Duration,
+    max_backoff: Duration,
+    passes: u32,
+    failed: HashSet<u32>,
+    attempts: HashMap<u32, u32>,
+}
+
+impl FailedChunkRetries {
+    pub fn new(policy: &RetransmitPolicy) -> Self {
+        Self {
+            max_retries: policy.failed_chunk_retries,
+            backoff: policy.retry_backoff,
+            max_backoff: policy.max_retry_backoff,
+            passes: 0,
+            failed: HashSet::new(),
+            attempts: HashMap::new(),
+        }
+    }
+
+    /// A send of `seq` failed
+    pub fn failed(&mut self, seq: u32) {
+        self.failed.insert(seq);
+    }
+
+    /// A send of `seq` got through
+    pub fn delivered(&mut self, seq: u32) {
+        self.failed.remove(&seq);
+    }
+
+    /// Plan the next pass over the failed chunks, charging each chunk in it
+    /// one attempt; `None` once none of them has a retry left
+    pub fn next_pass(&mut self) -> Option<RetryPass> {
+        let mut chunks: Vec<u32> = self
+            .failed
+            .iter()
+            .copied()
+            .filter(|seq| self.attempts(*seq) < self.max_retries)
+            .collect();
+        if chunks.is_empty() {
+            return None;
+        }
+        chunks.sort_unstable();
+        for &seq in &chunks {
+            *self.attempts.entry(seq).or_default() += 1;
+        }
+
+        let backoff = self
+            .backoff
+            .saturating_mul(2u32.saturating_pow(self.passes))
+            .min(self.max_backoff);
+        self.passes += 1;
+        Some(RetryPass { backoff, chunks })
+    }
+
+    /// Retries made so far for `seq`
+    pub fn attempts(&self, seq: u32) -> u32 {
+        self.attempts.get(&seq).copied().unwrap_or(0)
+    }
+
+    /// Chunks still failed, in order
+    pub fn pending(&self) -> Vec<u32> {
+        let mut pending: Vec<u32> = self.failed.iter().copied().collect();
+        pending.sort_unstable();
+        pending
+    }
+}
+
+/// How one chunk's send is handled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetryDecision {
+    /// Retry this many times on the spot, then in the later passes
+    Retry(u32),
+    /// Send once; parity covers the chunk if it is lost
+    LeaveToParity,
+}
+
+impl RetryDecision {
+    /// In-flight retries for the send
+    pub fn retries(&self) -> u32 {
+        match self {
+            Self::Retry(retries) => *retries,
+            Self::LeaveToParity => 0,
+        }
+    }
+
+    /// Metric label
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Retry(_) => "retry",
+            Self::LeaveToParity => "parity",
+        }
+    }
+}
+
+/// Chooses per chunk between retrying and relying on parity
+///
+/// Any `data_chunks` of a group rebuild it, so every lost shard, data or
+/// parity, uses up one parity shard. The loss expected over the chunks
+/// still to send is the worse of the link's measured packet loss and the
+/// share of this transfer's sends that failed.
+#[derive(Debug, Clone)]
+pub struct RetryController {
+    retries: u32,
+    adaptive: bool,
+    parity: u32,
+    unsent: u32,
+    left_to_parity: u32,
+    sent: u32,
+    failed: u32,
+}
+
+impl RetryController {
+    /// Controller for a transfer with `parity` parity shards and `unsent`
+    /// chunks still to go out
+    pub fn new(policy: &RetransmitPolicy, parity: u32, unsent: u32) -> Self {
+        Self {
+            retries: policy.in_flight_retries,
+            adaptive: policy.adaptive_retries,
+            parity,
+            unsent,
+            left_to_parity: 0,
+            sent: 0,
+            failed: 0,
+        }
+    }
+
+    /// Decide how to send the next chunk, given the link's current loss
+    /// rate
+    pub fn decide(&mut self, link_loss: f64) -> RetryDecision {
+        self.unsent = self.unsent.saturating_sub(1);
+        if self.adaptive && self.headroom(link_loss) > 0 {
+            RetryDecision::LeaveToParity
+        } else {
+            RetryDecision::Retry(self.retries)
+        }
+    }
+
+    /// Outcome of a send made under `decision`
+    pub fn record(&mut self, decision: RetryDecision, delivered: bool) {
+        self.sent += 1;
+        if !delivered {
+            self.failed += 1;
+            if decision == RetryDecision::LeaveToParity {
+                self.left_to_parity += 1;
+            }
+        }
+    }
+
+    /// Parity shards left once the chunks already left to parity and the
+    /// losses expected in the rest of the transfer are covered
+    pub fn headroom(&self, link_loss: f64) -> i64 {
+        let observed = if self.sent > 0 {
+            self.failed as f64 / self.sent as f64
+        } else {
+            0.0
+        };
+        let loss = link_loss.max(observed).clamp(0.0, 1.0);
+        let expected = (self.unsent as f64 * loss).ceil() as i64;
+        self.parity as i64 - self.left_to_parity as i64 - expected
+    }
+
+    /// Chunks whose failed sends were left to parity
+    pub fn left_to_parity(&self) -> u32 {
+        self.left_to_parity
+    }
+}
+
+/// Where the sender would get a missing shard from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShardSource {
+    /// Still in memory, this many bytes
+    Held(u64),
+    /// Rebuilt from the shards still held, this many bytes
+    Regenerate(u64),
+    Unavailable,
+}
+
+/// Shards to resend for one group report
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetransmitPlan {
+    /// Sequence numbers, cheapest first
+    pub shards: Vec<u32>,
+    pub bytes: u64,
+    /// Those of `shards` that must be regenerated before sending
+    pub regenerate: Vec<u32>,
+}
+
+/// What to do about one group report
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RetransmitDecision {
+    /// The receiver can already rebuild the file
+    Decodable,
+    Resend(RetransmitPlan),
+    /// Closing the gap would take more shards than are left in the budget,
+    /// or than the sender still holds
+    OverBudget {
+        needed: u32,
+        available: u32,
+    },
+}
+
+/// Plans resends for one transfer's FEC group within its budget
+#[derive(Debug, Clone)]
+pub struct RetransmitPlanner {
+    budget: u32,
+    spent: u32,
+}
+
+impl RetransmitPlanner {
+    pub fn new(budget: u32) -> Self {
+        Self { budget, spent: 0 }
+    }
+
+    /// Shards resent so far
+    pub fn spent(&self) -> u32 {
+        self.spent
+    }
+
+    /// Shards left in the budget
+    pub fn remaining(&self) -> u32 {
+        self.budget.saturating_sub(self.spent)
+    }
+
+    /// Pick the shards to resend for `report` from those the sender holds
+    ///
+    /// `shard_bytes` gives the size of a shard the sender can resend, or
+    /// `None` if it no longer holds it. See [`Self::plan_sources`].
+    pub fn plan(
+        &mut self,
+        report: &GroupFeedback,
+        shard_bytes: impl Fn(u32) -> Option<u64>,
+    ) -> RetransmitDecision {
+        self.plan_sources(report, |seq| {
+            shard_bytes(seq).map_or(ShardSource::Unavailable, ShardSource::Held)
+        })
+    }
+
+    /// Pick the shards to resend for `report`
+    ///
+    /// Any missing shard helps equally, so the smallest go first. At equal
+    /// size a held shard beats a regenerated one, and a held data shard
+    /// beats held parity, since the receiver then has less to decode.
+    /// Regenerated parity beats regenerated data, which needs a decode
+    /// rather than an encode. A planned resend is charged to the budget.
+    pub fn plan_sources(
+        &mut self,
+        report: &GroupFeedback,
+        source: impl Fn(u32) -> ShardSource,
+    ) -> RetransmitDecision {
+        let needed = report.shortfall();
+        if needed == 0 {
+            return RetransmitDecision::Decodable;
+        }
+
+        // (bytes, rank, seq, regenerate)
+        let mut candidates: Vec<(u64, u8, u32, bool)> = report
+            .missing()
+            .into_iter()
+            .filter_map(|seq| {
+                let parity = seq >= report.data_chunks;
+                match (source(seq), parity) {
+                    (ShardSource::Held(bytes), false) => Some((bytes, 0, seq, false)),
+                    (ShardSource::Held(bytes), true) => Some((bytes, 1, seq, false)),
+                    (ShardSource::Regenerate(bytes), true) => Some((bytes, 2, seq, true)),
+                    (ShardSource::Regenerate(bytes), false) => Some((bytes, 3, seq, true)),
+                    (ShardSource::Unavailable, _) => None,
+                }
+            })
+            .collect();
+        let available = self.remaining().min(candidates.len() as u32);
+        if needed > available {
+            return RetransmitDecision::OverBudget { needed, available };
+        }
+
+        candidates.sort_unstable();
+        candidates.truncate(needed as usize);
+        self.spent += needed;
+        RetransmitDecision::Resend(RetransmitPlan {
+            bytes: candidates.iter().map(|(bytes, ..)| bytes).sum(),
+            regenerate: candidates
+                .iter()
+                .filter(|(.., regenerate)| *regenerate)
+                .map(|(_, _, seq, _)| *seq)
+                .collect(),
+            shards: candidates.into_iter().map(|(_, _, seq, _)| seq).collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(received: impl IntoIterator<Item = u32>) -> GroupFeedback {
+        GroupFeedback {
+            file_id: "survey.bin".into(),
+            data_chunks: 6,
+            total_chunks: 9,
+            received: received.into_iter().collect(),
+        }
+    }
+
+    #[test]
+    fn test_resends_cheapest_missing_shards() {
+        let mut planner = RetransmitPlanner::new(8);
+        // 0, 2, 7 and 8 are missing and one more shard is needed; data
+        // beats parity at equal size
+        let decision = planner.plan(&report([1, 3, 4, 5, 6]), |_| Some(1024));
+        assert_eq!(
+            decision,
+            RetransmitDecision::Resend(RetransmitPlan {
+                shards: vec![0],
+                bytes: 1024,
+                regenerate: vec![],
+            })
+        );
+
+        let decision = planner.plan(&report([1, 3, 4, 6]), |_| Some(1024));
+        let RetransmitDecision::Resend(plan) = decision else {
+            panic!("expected a resend, got {decision:?}");
+        };
+        assert_eq!(plan.shards, [0, 2]);
+        assert_eq!(plan.bytes, 2048);
+        assert_eq!(planner.spent(), 3);
+        assert_eq!(planner.remaining(), 5);
+
+        assert_eq!(
+            planner.plan(&report(0..6), |_| Some(1024)),
+            RetransmitDecision::Decodable
+        );
+    }
+
+    #[test]
+    fn test_prefers_smaller_parity_over_data() {
+        let mut planner = RetransmitPlanner::new(8);
+        let sizes = |seq: u32| Some(if seq >= 6 { 512 } else { 1024 });
+        let decision = planner.plan(&report([1, 2, 3, 4, 5]), sizes);
+        let RetransmitDecision::Resend(plan) = decision else {
+            panic!("expected a resend, got {decision:?}");
+        };
+        assert_eq!(plan.shards, [6]);
+    }
+
+    #[test]
+    fn test_regenerates_parity_before_data() {
+        // Only data shards 3..6 are still held; 0 and 1 were evicted
+        let held = |seq: u32| match seq {
+            3..=5 => ShardSource::Held(1024),
+            _ => ShardSource::Regenerate(1024),
+        };
+        let mut planner = RetransmitPlanner::new(8);
+        let decision = planner.plan_sources(&report([2, 3, 4, 5]), held);
+        let RetransmitDecision::Resend(plan) = decision else {
+            panic!("expected a resend, got {decision:?}");
+        };
+        // Two short: parity is encoded rather than data decoded
+        assert_eq!(plan.shards, [6, 7]);
+        assert_eq!(plan.regenerate, [6, 7]);
+
+        // A held shard wins, and bytes outweigh everything
+        let mut planner = RetransmitPlanner::new(8);
+        let mixed = |seq: u32| match seq {
+            0 => ShardSource::Held(1024),
+            1 => ShardSource::Regenerate(256),
+            _ => ShardSource::Regenerate(1024),
+        };
+        let decision = planner.plan_sources(&report([2, 3, 4, 5]), mixed);
+        let RetransmitDecision::Resend(plan) = decision else {
+            panic!("expected a resend, got {decision:?}");
+        };
+        assert_eq!(plan.shards, [1, 0]);
+        assert_eq!(plan.regenerate, [1]);
+        assert_eq!(plan.bytes, 1280);
+
+        // Without a way to rebuild them, evicted shards can't close the gap
+        let mut planner = RetransmitPlanner::new(8);
+        let decision = planner.plan_sources(&report([3, 4, 5]), |seq| match seq {
+            0 => ShardSource::Held(1024),
+            _ => ShardSource::Unavailable,
+        });
+        assert_eq!(
+            decision,
+            RetransmitDecision::OverBudget {
+                needed: 3,
+                available: 1
+            }
+        );
+    }
+
+    #[test]
+    fn test_failed_chunks_retried_with_backoff_until_exhausted() {
+        let policy = RetransmitPolicy {
+            failed_chunk_retries: 2,
+            retry_backoff: Duration::from_millis(500),
+            max_retry_backoff: Duration::from_millis(800),
+            ..Default::default()
+        };
+        let mut retries = FailedChunkRetries::new(&policy);
+        assert_eq!(retries.next_pass(), None);
+
+        retries.failed(7);
+        retries.failed(3);
+        let pass = retries.next_pass().unwrap();
+        assert_eq!(pass.chunks, [3, 7]);
+        assert_eq!(pass.backoff, Duration::from_millis(500));
+
+        // 3 got through; 7 failed again alongside a newcomer
+        retries.delivered(3);
+        retries.failed(9);
+        let pass = retries.next_pass().unwrap();
+        assert_eq!(pass.chunks, [7, 9]);
+        assert_eq!(pass.backoff, Duration::from_millis(800));
+
+        // 7 is out of retries
+        let pass = retries.next_pass().unwrap();
+        assert_eq!(pass.chunks, [9]);
+        assert_eq!(retries.attempts(7), 2);
+        assert_eq!(retries.next_pass(), None);
+        assert_eq!(retries.pending(), [7, 9]);
+
+        let disabled = RetransmitPolicy {
+            failed_chunk_retries: 0,
+            ..Default::default()
+        };
+        let mut retries = FailedChunkRetries::new(&disabled);
+        retries.failed(1);
+        assert_eq!(retries.next_pass(), None);
+    }
+
+    #[test]
+    fn test_retry_controller_spends_parity_before_retrying() {
+        // 10 data + 3 parity shards over a clean link
+        let policy = RetransmitPolicy::default();
+        let mut controller = RetryController::new(&policy, 3, 13);
+        let first = controller.decide(0.0);
+        assert_eq!(first, RetryDecision::LeaveToParity);
+        assert_eq!(first.retries(), 0);
+        controller.record(first, false);
+
+        // One failure in two sends: half of what is left is expected lost
+        let decision = controller.decide(0.0);
+        controller.record(decision, true);
+        assert_eq!(controller.decide(0.0), RetryDecision::Retry(3));
+
+        // Measured link loss counts too
+        let mut lossy = RetryController::new(&policy, 3, 13);
+        assert_eq!(lossy.decide(0.1), RetryDecision::LeaveToParity);
+        assert_eq!(lossy.decide(0.3), RetryDecision::Retry(3));
+
+        // Parity used up: every chunk is retried
+        let mut spent = RetryController::new(&policy, 1, 4);
+        let decision = spent.decide(0.0);
+        spent.record(decision, false);
+        assert_eq!(spent.left_to_parity(), 1);
+        assert_eq!(spent.headroom(0.0), -3);
+        assert_eq!(spent.decide(0.0), RetryDecision::Retry(3));
+
+        let fixed = RetransmitPolicy {
+            adaptive_retries: false,
+            ..Default::default()
+        };
+        let mut fixed = RetryController::new(&fixed, 3, 13);
+        assert_eq!(fixed.decide(0.0), RetryDecision::Retry(3));
+    }
+
+    #[test]
+    fn test_gap_beyond_budget_is_not_sent() {
+        let mut planner = RetransmitPlanner::new(2);
+        assert_eq!(
+            planner.plan(&report([0, 1, 2]), |_| Some(1024)),
+            RetransmitDecision::OverBudget {
+                needed: 3,
+                available: 2
+            }
+        );
+        assert_eq!(planner.spent(), 0);
+
+        // Shards the sender no longer holds don't count either
+        let mut planner = RetransmitPlanner::new(8);
+        assert_eq!(
+            planner.plan(&report([0, 1, 2, 3]), |seq| (seq == 4).then_some(1024)),
+            RetransmitDecision::OverBudget {
+                needed: 2,
+                available: 1
+            }
+        );
+    }
+}
@@ -0,0 +1,2296 @@
+//! Starting, running and settling transfers
+//!
+//! [`TransferSender`] splits a source into chunks, queues them a window at
+//! a time and sends them over the coordinator's [`Transport`], resending
+//! what the receiver reports corrupt or lost. It keeps the state machines
+//! of running and recently finished transfers and admits queued transfers
+//! as slots free up.
+
+use crate::chunk::{
+    AdaptiveErasureCoder, Chunk, ChunkError, ChunkManager, ChunkMetadata, ChunkTrace, ErasureCoder,
+    FileManifest, HopStage, Priority, SourceGuard,
+};
+use crate::coordinator::admission::{AdmissionQueue, PendingTransfer};
+use crate::coordinator::defaults;
+use crate::coordinator::error::{CoordinatorError, CoordinatorResult};
+use crate::coordinator::events::{CoordinatorEvent, EventBus};
+use crate::coordinator::health::ResourceUsage;
+use crate::coordinator::relay_audit;
+use crate::coordinator::resume_token::ResumeToken;
+use crate::coordinator::retransmit::{
+    FailedChunkRetries, RetransmitDecision, RetransmitPlanner, RetransmitPolicy, RetryController,
+    RetryDecision, ShardSource,
+};
+use crate::coordinator::state_machine::TransferStateMachine;
+use crate::coordinator::stats::StatsService;
+use crate::coordinator::timeseries::ProgressSampler;
+use crate::coordinator::transport::{Transport, TransportLink};
+use crate::coordinator::types::{
+    DuplicatePolicy, RetentionPolicy, SendPolicy, TransferEvent, TransferSource, TransferState,
+    MEMORY_FILE_ID_PREFIX,
+};
+use crate::coordinator::window::SessionWindow;
+use crate::hooks::{HookContext, HookPoint, HookRegistry};
+use crate::integrity::MerkleTree;
+use crate::metrics::latency::{record_stage, Stage};
+use crate::metrics::recorder;
+use crate::network::{
+    FileOffer, GroupFeedback, NetworkError, OfferReply, ReceiverFeedback, TransferRateLimiter,
+};
+use crate::priority::starvation::priority_label;
+use crate::priority::{PriorityQueue, QueuedChunk};
+use crate::relay::{RelayNode, RouteInfo};
+use crate::session::validate_tags;
+use crate::session::{
+    ProgressSample, SessionQuery, SessionRepository, SessionState, SessionStatus, TransferOptions,
+    TransferTags,
+};
+use bytes::Bytes;
+use dashmap::DashMap;
+use futures::future::BoxFuture;
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time;
+
+/// Times one chunk is resent after the receiver reports it corrupt
+const MAX_CHUNK_RESENDS: u32 = 3;
+
+/// Shortest wait for late NACKs once every chunk has been sent
+const MIN_NACK_GRACE: Duration = Duration::from_millis(100);
+
+/// The transfer holding a file id, and what it sends where
+#[derive(Debug, Clone)]
+struct FileClaim {
+    session_id: String,
+    /// [`TransferSource::file_id`] of the data sent
+    source_id: String,
+    receiver_addr: Option<SocketAddr>,
+    /// Sent with the transfer's events
+    tags: TransferTags,
+}
+
+impl FileClaim {
+    fn new(session_id: &str, source: &TransferSource, receiver_addr: Option<SocketAddr>) -> Self {
+        Self {
+            session_id: session_id.to_string(),
+            source_id: source.file_id(),
+            receiver_addr,
+            tags: TransferTags::new(),
+        }
+    }
+
+    fn with_tags(mut self, tags: TransferTags) -> Self {
+        self.tags = tags;
+        self
+    }
+}
+
+/// Starts, runs and settles transfers
+///
+/// [`TransferCoordinator`](crate::coordinator::TransferCoordinator) hands
+/// every send, resume, pause and cancel to its send service, and reads
+/// transfer state back from it.
+pub trait SendService: Send + Sync {
+    /// Start sending `source`, returning its session id
+    ///
+    /// When the concurrent transfer limit is reached the transfer is queued
+    /// and starts once a slot frees; the returned session id is valid either way.
+    fn send(
+        &self,
+        source: TransferSource,
+        priority: Priority,
+        receiver_addr: Option<SocketAddr>,
+        options: TransferOptions,
+    ) -> BoxFuture<'_, CoordinatorResult<String>>;
+
+    /// Resume a paused transfer
+    ///
+    /// A transfer sent from memory needs `data`, exactly what it started
+    /// with; one sent from a file is re-read from it and takes no `data`.
+    fn resume<'a>(
+        &'a self,
+        session_id: &'a str,
+        data: Option<Bytes>,
+    ) -> BoxFuture<'a, CoordinatorResult<()>>;
+
+    /// Pause a transfer
+    fn pause<'a>(&'a self, session_id: &'a str) -> BoxFuture<'a, CoordinatorResult<()>>;
+
+    /// Cancel a transfer
+    fn cancel<'a>(&'a self, session_id: &'a str) -> BoxFuture<'a, CoordinatorResult<()>>;
+
+    /// Sign resume tokens with a key derived from `secret`, and only accept
+    /// tokens signed with it (`None` exports and accepts unsigned tokens)
+    fn set_resume_token_secret(&self, secret: Option<&str>);
+
+    /// Export a session so another host with the same file can finish it
+    fn export_resume_token<'a>(
+        &'a self,
+        session_id: &'a str,
+    ) -> BoxFuture<'a, CoordinatorResult<ResumeToken>>;
+
+    /// Continue a session exported by another host, sending from this
+    /// host's copy of the file at `file_path`
+    ///
+    /// The file must split into the same manifest as the token's; only the
+    /// chunks the receiver doesn't have yet are sent. Returns the session id,
+    /// which is the original one.
+    fn import_resume_token(
+        &self,
+        token: ResumeToken,
+        file_path: PathBuf,
+    ) -> BoxFuture<'_, CoordinatorResult<String>>;
+
+    /// Mark sessions a previous process left running as paused
+    ///
+    /// A transfer's worker dies with its process, so after a crash or
+    /// restart its session still reads active and nothing would pick it up.
+    /// Paused, it can be resumed. Returns how many sessions were recovered.
+    fn recover_sessions(&self) -> BoxFuture<'_, CoordinatorResult<usize>>;
+
+    /// Stop taking transfers and pause the running ones, so the next
+    /// process can resume them
+    ///
+    /// Transfers still waiting for a slot have no session yet and are
+    /// dropped. Returns how many transfers were paused.
+    fn shutdown(&self) -> BoxFuture<'_, CoordinatorResult<usize>>;
+
+    /// Whether [`shutdown`](Self::shutdown) has been called
+    fn is_shutting_down(&self) -> bool;
+
+    /// Record that a relay took over the chunks the receiver hasn't got
+    ///
+    /// The transfer stops sending and is reported as partially delivered
+    /// until [`complete_relay_delivery`](Self::complete_relay_delivery).
+    fn record_relay_handoff<'a>(
+        &'a self,
+        session_id: &'a str,
+        held_by_relay: u32,
+    ) -> BoxFuture<'a, CoordinatorResult<()>>;
+
+    /// Record that the relay delivered the rest of a partially delivered transfer
+    fn complete_relay_delivery<'a>(
+        &'a self,
+        session_id: &'a str,
+    ) -> BoxFuture<'a, CoordinatorResult<()>>;
+
+    /// State of a running transfer
+    fn state(&self, session_id: &str) -> Option<TransferState>;
+
+    /// State of an active or recently finished transfer (keeps the outcome)
+    ///
+    /// Transfers waiting for a slot report `Idle`.
+    fn recent_state(&self, session_id: &str) -> Option<TransferState>;
+
+    /// Throughput samples of a running transfer, not yet saved with its session
+    fn live_timeseries(&self, session_id: &str) -> Option<Vec<ProgressSample>>;
+
+    /// List active transfers
+    fn list_active(&self) -> Vec<String>;
+
+    /// List all recent transfers (including completed/failed)
+    fn list_recent(&self) -> Vec<String>;
+
+    /// Transfers waiting for a slot, in the order they will start
+    fn pending_transfers(&self) -> Vec<PendingTransfer>;
+
+    /// Session of the running or queued transfer tracked under `file_id`
+    fn session_for_file(&self, file_id: &str) -> Option<String>;
+
+    /// Maximum transfers running at once (0 = unlimited)
+    fn max_concurrent_transfers(&self) -> usize;
+
+    /// Change the concurrent transfer limit; raising it starts queued transfers
+    fn set_max_concurrent_transfers(&self, max: usize);
+
+    /// Count completed (terminal) transfers
+    fn count_completed(&self) -> usize;
+
+    /// Evict finished transfers beyond the retention bounds now
+    ///
+    /// Returns how many were evicted.
+    fn evict_finished(&self) -> usize;
+
+    /// Settings transfers are started with
+    fn policy(&self) -> SendPolicy;
+
+    /// Change the settings; transfers started or resumed afterwards use them,
+    /// and the next retention sweep applies the new bounds
+    fn update_policy(&self, change: &mut dyn FnMut(&mut SendPolicy));
+
+    /// Relay that Critical transfers whose receiver can't be reached in
+    /// time are handed to
+    fn set_fallback_relay(&self, relay: Option<Arc<RelayNode>>);
+
+    /// Sizes of the maps this service holds; fields it doesn't track are zero
+    fn resource_usage(&self) -> ResourceUsage;
+}
+
+/// [`SendService`] sending over a [`Transport`] and keeping sessions in a
+/// [`SessionRepository`]
+#[derive(Clone)]
+pub struct TransferSender {
+    // Shared with the coordinator, which replaces it when defaults change
+    chunk_manager: Arc<parking_lot::RwLock<Arc<ChunkManager>>>,
+    transport: Arc<dyn Transport>,
+    queue: Arc<PriorityQueue>,
+    session_store: Arc<dyn SessionRepository>,
+    hooks: Arc<HookRegistry>,
+    events: EventBus,
+
+    // Path stats of running transfers are recorded here
+    stats: Arc<dyn StatsService>,
+
+    // Receivers' stats reports feed it while their transfers run
+    adaptive_coder: Arc<AdaptiveErasureCoder>,
+
+    // Active transfers
+    active_transfers: Arc<DashMap<String, TransferStateMachine>>,
+
+    // Recent transfers (including completed/failed) - kept for display
+    recent_transfers: Arc<DashMap<String, TransferStateMachine>>,
+
+    // Finished transfers evicted from recent_transfers
+    evicted_finished: Arc<AtomicU64>,
+
+    // Transfers by the file id they are tracked under
+    file_to_session: Arc<DashMap<String, FileClaim>>,
+
+    // Concurrent transfer limit and transfers waiting for a slot
+    admission: Arc<AdmissionQueue>,
+
+    // Set by shutdown; no transfer starts or resumes afterwards
+    shutting_down: Arc<AtomicBool>,
+
+    // Duplicates, retention, queue window, source watch, retransmission
+    // and connect timeouts
+    policy: Arc<parking_lot::RwLock<SendPolicy>>,
+
+    // Key resume tokens are signed and checked with, when a secret is set
+    resume_key: Arc<parking_lot::RwLock<Option<[u8; 32]>>>,
+
+    // The relay Critical transfers fall back to when their connect times out
+    fallback_relay: Arc<parking_lot::RwLock<Option<Arc<RelayNode>>>>,
+
+    // Throughput samples of running transfers
+    sampler: Arc<ProgressSampler>,
+}
+
+impl TransferSender {
+    /// Sender over the coordinator's shared parts
+    ///
+    /// Finished transfers are swept by a background task while the sender
+    /// lives, if there is a runtime.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn new(
+        chunk_manager: Arc<parking_lot::RwLock<Arc<ChunkManager>>>,
+        transport: Arc<dyn Transport>,
+        queue: Arc<PriorityQueue>,
+        session_store: Arc<dyn SessionRepository>,
+        hooks: Arc<HookRegistry>,
+        events: EventBus,
+        stats: Arc<dyn StatsService>,
+        adaptive_coder: Arc<AdaptiveErasureCoder>,
+    ) -> Self {
+        let recent_transfers = Arc::new(DashMap::new());
+        let policy = Arc::new(parking_lot::RwLock::new(SendPolicy::default()));
+        let evicted_finished = Arc::new(AtomicU64::new(0));
+        if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+            runtime.spawn(Self::retention_sweeper(
+                Arc::downgrade(&recent_transfers),
+                policy.clone(),
+                evicted_finished.clone(),
+            ));
+        }
+
+        Self {
+            chunk_manager,
+            transport,
+            queue,
+            session_store,
+            hooks,
+            events,
+            stats,
+            adaptive_coder,
+            active_transfers: Arc::new(DashMap::new()),
+            recent_transfers,
+            evicted_finished,
+            file_to_session: Arc::new(DashMap::new()),
+            admission: Arc::new(AdmissionQueue::default()),
+            shutting_down: Arc::new(AtomicBool::new(false)),
+            policy,
+            resume_key: Arc::new(parking_lot::RwLock::new(None)),
+            fallback_relay: Arc::new(parking_lot::RwLock::new(None)),
+            sampler: Arc::new(ProgressSampler::default()),
+        }
+    }
+
+    /// Chunk manager for a new transfer with `options`
+    fn chunk_manager_for(&self, options: &TransferOptions) -> CoordinatorResult<Arc<ChunkManager>> {
+        let base = self.chunk_manager.read().clone();
+        defaults::chunk_manager_for(base, options, self.transport.max_chunk_size())
+    }
+
+    async fn send_source(
+        &self,
+        source: TransferSource,
+        priority: Priority,
+        receiver_addr: Option<SocketAddr>,
+        options: TransferOptions,
+    ) -> CoordinatorResult<String> {
+        if self.is_shutting_down() {
+            return Err(CoordinatorError::ShuttingDown);
+        }
+
+        validate_tags(&options.tags).map_err(CoordinatorError::InvalidTags)?;
+        let file_id = self.claim_file_id(&source, receiver_addr, options.allow_duplicate)?;
+
+        // Fail fast if the requested uplink doesn't exist on this host
+        if let Some(local_addr) = options.local_bind_addr {
+            self.transport
+                .validate_local_addr(local_addr, receiver_addr)?;
+        }
+        // Or a layout the file can't be split with
+        self.chunk_manager_for(&options)?;
+
+        let session_id = uuid::Uuid::new_v4().to_string();
+        if !self.admission.try_admit(self.active_transfers.len()) {
+            tracing::info!("Transfer limit reached, queueing {}", file_id);
+            self.file_to_session.insert(
+                file_id.clone(),
+                FileClaim::new(&session_id, &source, receiver_addr).with_tags(options.tags.clone()),
+            );
+            self.admission.enqueue(PendingTransfer {
+                session_id: session_id.clone(),
+                file_id,
+                source,
+                priority,
+                receiver_addr,
+                options,
+                queued_at: chrono::Utc::now().timestamp(),
+            });
+            return Ok(session_id);
+        }
+
+        let result = self
+            .start_transfer(
+                session_id.clone(),
+                file_id,
+                source,
+                priority,
+                receiver_addr,
+                options,
+            )
+            .await;
+        self.admission.started();
+        if result.is_err() {
+            self.admit_pending();
+        }
+        result.map(|_| session_id)
+    }
+
+    /// File id a new transfer of `source` to `receiver_addr` is tracked
+    /// under, unless the duplicate policy refuses it
+    fn claim_file_id(
+        &self,
+        source: &TransferSource,
+        receiver_addr: Option<SocketAddr>,
+        allow_duplicate: bool,
+    ) -> CoordinatorResult<String> {
+        let source_id = source.file_id();
+        let policy = self.policy().duplicates;
+        let refused = !allow_duplicate
+            && self.file_to_session.iter().any(|claim| {
+                claim.source_id == source_id
+                    && match policy {
+                        DuplicatePolicy::PerFile => true,
+                        DuplicatePolicy::PerReceiver => claim.receiver_addr == receiver_addr,
+                        DuplicatePolicy::Allow => false,
+                    }
+            });
+        if refused {
+            return Err(CoordinatorError::AlreadyInProgress(source_id));
+        }
+
+        if !self.file_to_session.contains_key(&source_id) {
+            return Ok(source_id);
+        }
+        Ok((2..)
+            .map(|n| format!("{source_id}#{n}"))
+            .find(|id| !self.file_to_session.contains_key(id))
+            .expect("some suffix is free"))
+    }
+
+    /// Tags of a running or queued transfer; empty once it has finished
+    fn session_tags(&self, session_id: &str) -> TransferTags {
+        self.file_to_session
+            .iter()
+            .find(|claim| claim.session_id == session_id)
+            .map(|claim| claim.tags.clone())
+            .unwrap_or_default()
+    }
+
+    /// Split, register and spawn the worker for an admitted transfer
+    async fn start_transfer(
+        &self,
+        session_id: String,
+        file_id: String,
+        source: TransferSource,
+        priority: Priority,
+        receiver_addr: Option<SocketAddr>,
+        options: TransferOptions,
+    ) -> CoordinatorResult<()> {
+        // Split file into chunks
+        let chunk_manager = self.chunk_manager_for(&options)?;
+        let (manifest, chunks, guard) = match &source {
+            TransferSource::File(file_path) => {
+                // Watched from before it is read, so no change goes unseen
+                let guard = SourceGuard::acquire(file_path, self.policy().source_watch).await?;
+                let (manifest, chunks) = chunk_manager
+                    .split_file(file_path, file_id.clone(), priority)
+                    .await?;
+                (manifest, chunks, Some(guard))
+            }
+            TransferSource::Memory { name, data } => {
+                let (manifest, chunks) =
+                    chunk_manager.split_bytes(data, name.clone(), file_id.clone(), priority)?;
+                (manifest, chunks, None)
+            }
+        };
+        if manifest.is_sparse() {
+            tracing::debug!(
+                "{} is sparse: {} of {} bytes sent as holes",
+                file_id,
+                manifest.sparse_bytes(),
+                manifest.total_size
+            );
+        }
+
+        // Give plugins a chance to veto the file before anything is queued
+        let mut hook_ctx = HookContext::new(HookPoint::BeforeEnqueue, file_id.clone())
+            .with_manifest(manifest.clone());
+        if let Some(file_path) = source.path() {
+            hook_ctx = hook_ctx.with_path(file_path);
+        }
+        self.hooks.run(&hook_ctx).await?;
+
+        // Create session with receiver address and file path for resumable transfers
+        let mut session = SessionState::new_with_receiver(
+            session_id.clone(),
+            file_id.clone(),
+            manifest.clone(),
+            receiver_addr,
+            source.path().map(|path| path.to_string_lossy().to_string()),
+        );
+        session.options = options.clone();
+        self.session_store.save(&session).await?;
+
+        // Update session status to active
+        self.session_store
+            .update_status(&session_id, SessionStatus::Active)
+            .await?;
+
+        // Create state machine
+        let state_machine = TransferStateMachine::new();
+        state_machine.transition(TransferEvent::Start {
+            file_path: PathBuf::from(&file_id),
+            priority,
+        })?;
+
+        // Register transfer
+        let worker = state_machine.claim_worker().await;
+        self.active_transfers
+            .insert(session_id.clone(), state_machine.clone());
+        self.recent_transfers
+            .insert(session_id.clone(), state_machine);
+        self.file_to_session.insert(
+            file_id.clone(),
+            FileClaim::new(&session_id, &source, receiver_addr).with_tags(options.tags.clone()),
+        );
+        self.events.publish(CoordinatorEvent::TransferStarted {
+            session_id: session_id.clone(),
+            file_id: file_id.clone(),
+            priority,
+            total_chunks: manifest.total_chunks,
+            total_size: manifest.total_size,
+            tags: options.tags.clone(),
+        });
+
+        self.spawn_worker(
+            worker,
+            session_id,
+            file_id,
+            manifest,
+            chunks,
+            guard,
+            receiver_addr,
+            options.local_bind_addr,
+        );
+        Ok(())
+    }
+
+    /// Run the transfer worker for a registered session, cleaning up if it fails
+    ///
+    /// `worker` is the transfer's claim from
+    /// [`TransferStateMachine::claim_worker`], held until the worker exits.
+    #[allow(clippy::too_many_arguments)]
+    fn spawn_worker(
+        &self,
+        worker: tokio::sync::OwnedMutexGuard<()>,
+        session_id: String,
+        file_id: String,
+        manifest: FileManifest,
+        mut chunks: Vec<Chunk>,
+        source: Option<SourceGuard>,
+        receiver_addr: Option<SocketAddr>,
+        local_addr: Option<SocketAddr>,
+    ) {
+        // Relays and the receiver log the session id as each chunk's trace
+        for chunk in &mut chunks {
+            chunk.metadata.trace = Some(ChunkTrace::new(&session_id));
+        }
+        let sender = self.clone();
+        let worker_session_id = session_id;
+        let worker_file_id = file_id;
+        let queued_file_id = manifest.file_id.clone();
+        let cancel = self
+            .active_transfers
+            .get(&worker_session_id)
+            .map(|sm| sm.cancellation());
+        tokio::spawn(async move {
+            let _worker = worker;
+            let result = sender
+                .transfer_worker(
+                    worker_session_id.clone(),
+                    manifest,
+                    chunks,
+                    source,
+                    receiver_addr,
+                    local_addr,
+                )
+                .await;
+            sender.save_timeseries(&worker_session_id).await;
+            if let Err(e) = result {
+                // A cancel already settled the transfer; whatever the worker
+                // tripped over while stopping isn't news
+                if !cancel.is_some_and(|cancel| cancel.is_cancelled()) {
+                    tracing::error!(session_id = %worker_session_id, error = %e, "Transfer worker failed");
+                    sender.events.publish(CoordinatorEvent::TransferFailed {
+                        session_id: worker_session_id.clone(),
+                        error: e.to_string(),
+                        tags: sender.session_tags(&worker_session_id),
+                    });
+                    // Mark as failed so the UI reflects the error
+                    let _ = sender
+                        .session_store
+                        .update_status(&worker_session_id, SessionStatus::Failed(e.to_string()))
+                        .await;
+                }
+                sender.queue.remove_file(&queued_file_id);
+                if let Some((_, state_machine)) = sender.active_transfers.remove(&worker_session_id)
+                {
+                    let _ = state_machine.transition(TransferEvent::TransferFailed {
+                        error: e.to_string(),
+                    });
+                }
+                // The file may already belong to a newer transfer
+                sender
+                    .file_to_session
+                    .remove_if(&worker_file_id, |_, claim| {
+                        claim.session_id == worker_session_id
+                    });
+                sender.admit_pending();
+            }
+        });
+    }
+
+    /// Save a stopped transfer's throughput samples with its session
+    ///
+    /// The samples stay in memory until saved, so the series can be read
+    /// throughout.
+    async fn save_timeseries(&self, session_id: &str) {
+        let Some(samples) = self.sampler.samples(session_id) else {
+            return;
+        };
+        if let Err(e) = self
+            .session_store
+            .save_timeseries(session_id, &samples)
+            .await
+        {
+            tracing::warn!(session_id, "Failed to save throughput samples: {e}");
+        }
+        self.sampler.forget(session_id);
+    }
+
+    /// Start queued transfers while slots are free
+    fn admit_pending(&self) {
+        while let Some(pending) = self.admission.pop_next(self.active_transfers.len()) {
+            let sender = self.clone();
+            tokio::spawn(async move {
+                let session_id = pending.session_id.clone();
+                let file_id = pending.file_id.clone();
+                let tags = pending.options.tags.clone();
+                let result = sender
+                    .start_transfer(
+                        pending.session_id,
+                        pending.file_id,
+                        pending.source,
+                        pending.priority,
+                        pending.receiver_addr,
+                        pending.options,
+                    )
+                    .await;
+                sender.admission.started();
+
+                if let Err(e) = result {
+                    tracing::warn!("Queued transfer {} failed to start: {}", session_id, e);
+                    sender.events.publish(CoordinatorEvent::TransferFailed {
+                        session_id: session_id.clone(),
+                        error: e.to_string(),
+                        tags,
+                    });
+                    let state_machine = TransferStateMachine::new();
+                    let _ = state_machine.transition(TransferEvent::TransferFailed {
+                        error: e.to_string(),
+                    });
+                    sender.recent_transfers.insert(session_id, state_machine);
+                    sender.file_to_session.remove(&file_id);
+                    sender.admit_pending();
+                }
+            });
+        }
+    }
+
+    async fn resume_with(&self, session_id: &str, data: Option<Bytes>) -> CoordinatorResult<()> {
+        if self.is_shutting_down() {
+            return Err(CoordinatorError::ShuttingDown);
+        }
+
+        // Load session
+        let session = self
+            .session_store
+            .load(session_id)
+            .await?
+            .ok_or_else(|| CoordinatorError::TransferNotFound(session_id.to_string()))?;
+
+        // Check if can resume
+        let resume_info = self.session_store.get_resume_info(session_id).await?;
+        if !resume_info.can_resume {
+            return Err(CoordinatorError::CannotResume(
+                "Transfer is already complete or failed".into(),
+            ));
+        }
+
+        // A transfer sent from memory needs its data again, unchanged,
+        // unless a resume token has since tied it to a file
+        let from_memory =
+            session.file_id.starts_with(MEMORY_FILE_ID_PREFIX) && session.file_path.is_none();
+        let memory_chunks = match (from_memory, data) {
+            (true, Some(data)) => {
+                let manifest = &session.manifest;
+                if manifest.checksum_algorithm.digest(&data) != manifest.checksum {
+                    return Err(CoordinatorError::CannotResume(
+                        "Supplied data differs from what the transfer started with".into(),
+                    ));
+                }
+                let (_, chunks) = ChunkManager::for_manifest(manifest)?.split_bytes(
+                    &data,
+                    manifest.filename.clone(),
+                    session.file_id.clone(),
+                    manifest.priority,
+                )?;
+                Some(chunks)
+            }
+            (true, None) => {
+                return Err(CoordinatorError::CannotResume(
+                    "Transfer was sent from memory; supply its data again to resume".into(),
+                ))
+            }
+            (false, Some(_)) => {
+                return Err(CoordinatorError::CannotResume(
+                    "Transfer reads from a file; resume it without data".into(),
+                ))
+            }
+            (false, None) => None,
+        };
+        let (chunks, source) = match memory_chunks {
+            Some(chunks) => (chunks, None),
+            None => self.reread_source(&session).await?,
+        };
+
+        // A paused transfer picks up where its worker stopped; one this
+        // process never ran (or no longer holds) starts afresh
+        let existing = self.active_transfers.get(session_id).map(|sm| sm.clone());
+        let worker = match existing {
+            Some(sm) => {
+                if !sm.current_state().is_paused() {
+                    return Err(CoordinatorError::CannotResume(
+                        "Transfer is not paused".into(),
+                    ));
+                }
+                // The paused worker exits at its next state check
+                let worker = sm.claim_worker().await;
+                sm.transition(TransferEvent::Resume)?;
+                worker
+            }
+            None => {
+                let sm = TransferStateMachine::new();
+                sm.transition(TransferEvent::Start {
+                    file_path: session.file_path.clone().unwrap_or_default().into(),
+                    priority: session.manifest.priority,
+                })?;
+                let worker = sm.claim_worker().await;
+                self.active_transfers.insert(session_id.to_string(), sm);
+                worker
+            }
+        };
+
+        // Update session status
+        self.session_store
+            .update_status(session_id, SessionStatus::Active)
+            .await?;
+
+        // Use stored receiver address and uplink for resume
+        let receiver_addr = session.receiver_addr;
+        let local_addr = session.options.local_bind_addr;
+
+        self.spawn_worker(
+            worker,
+            session_id.to_string(),
+            session.file_id.clone(),
+            session.manifest.clone(),
+            chunks,
+            source,
+            receiver_addr,
+            local_addr,
+        );
+        Ok(())
+    }
+
+    /// Re-read the chunks of a resumed transfer from its original file
+    ///
+    /// Only the remaining chunks will be sent, so the file must split into
+    /// the manifest the transfer started with; a file changed since then
+    /// would give the receiver a mix of both versions.
+    async fn reread_source(
+        &self,
+        session: &SessionState,
+    ) -> CoordinatorResult<(Vec<Chunk>, Option<SourceGuard>)> {
+        let Some(file_path_str) = &session.file_path else {
+            tracing::warn!("No file path stored in session, cannot re-read chunks for resume");
+            return Ok((vec![], None));
+        };
+        let file_path = PathBuf::from(file_path_str);
+        if !file_path.exists() {
+            tracing::warn!("Original file not found for resume: {}", file_path_str);
+            return Ok((vec![], None));
+        }
+
+        let source = SourceGuard::acquire(&file_path, self.policy().source_watch).await?;
+        let split = match ChunkManager::for_manifest(&session.manifest) {
+            Ok(manager) => {
+                manager
+                    .split_file(
+                        &file_path,
+                        session.file_id.clone(),
+                        session.manifest.priority,
+                    )
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+        match split {
+            Ok((manifest, _)) if manifest.checksum != session.manifest.checksum => {
+                Err(CoordinatorError::CannotResume(format!(
+                    "{} changed since the transfer started",
+                    file_path.display()
+                )))
+            }
+            Ok((_, chunks)) => Ok((chunks, Some(source))),
+            Err(e) => {
+                tracing::warn!("Failed to re-read file for resume: {}", e);
+                Ok((vec![], None))
+            }
+        }
+    }
+
+    /// Periodically evict finished transfers until the sender is dropped
+    async fn retention_sweeper(
+        recent_transfers: Weak<DashMap<String, TransferStateMachine>>,
+        policy: Arc<parking_lot::RwLock<SendPolicy>>,
+        evicted_finished: Arc<AtomicU64>,
+    ) {
+        loop {
+            let interval = policy.read().retention.sweep_interval;
+            time::sleep(interval).await;
+
+            let Some(recent) = recent_transfers.upgrade() else {
+                return;
+            };
+            let retention = policy.read().retention;
+            let evicted = evict_finished(&recent, &retention);
+            evicted_finished.fetch_add(evicted as u64, Ordering::Relaxed);
+        }
+    }
+
+    /// Transfer worker - handles chunk transfer loop
+    async fn transfer_worker(
+        &self,
+        session_id: String,
+        manifest: FileManifest,
+        chunks: Vec<Chunk>,
+        mut source: Option<SourceGuard>,
+        receiver_addr: Option<SocketAddr>,
+        local_addr: Option<SocketAddr>,
+    ) -> CoordinatorResult<()> {
+        let state_machine = self
+            .active_transfers
+            .get(&session_id)
+            .ok_or_else(|| CoordinatorError::TransferNotFound(session_id.clone()))?
+            .clone();
+
+        // Get resume info
+        let session = self
+            .session_store
+            .load(&session_id)
+            .await?
+            .ok_or_else(|| CoordinatorError::TransferNotFound(session_id.clone()))?;
+
+        let completed_set = &session.completed_chunks;
+        // A cap the transfer asked for, on top of the transport's own limits
+        let rate_limit = session
+            .options
+            .rate_limit_bytes_per_sec
+            .filter(|&rate| rate > 0)
+            .map(|rate| TransferRateLimiter::new(rate.min(u32::MAX as u64) as u32, 0));
+        let mut chunks_to_transfer: Vec<u32> = (0..manifest.total_chunks)
+            .filter(|n| !completed_set.contains(n))
+            .collect();
+
+        // Signal first chunk completed to transition state
+        if !chunks_to_transfer.is_empty() {
+            state_machine.transition(TransferEvent::ChunkCompleted { chunk_number: 0 })?;
+        }
+
+        // Fires when the transfer is cancelled, cutting short whatever send
+        // or wait is in progress
+        let cancel = state_machine.cancellation();
+
+        // Establish connection once if receiver address provided
+        let connection = if let Some(addr) = receiver_addr {
+            tracing::info!(%session_id, receiver = %addr, "Connecting to receiver");
+            let timeout = self.policy().connect.timeout(manifest.priority);
+            let connect = async {
+                let connect = self.transport.clone().connect(addr, local_addr);
+                match timeout {
+                    Some(timeout) => tokio::time::timeout(timeout, connect)
+                        .await
+                        .unwrap_or(Err(NetworkError::Timeout(timeout))),
+                    None => connect.await,
+                }
+            };
+            let connected = tokio::select! {
+                _ = cancel.cancelled() => return Ok(()),
+                connected = connect => connected,
+            };
+            if let (Err(NetworkError::Timeout(_)), Some(timeout)) = (&connected, timeout) {
+                return self
+                    .connect_timed_out(&session_id, &manifest, &chunks, addr, timeout)
+                    .await;
+            }
+            match connected {
+                Ok(conn) => {
+                    tracing::info!(%session_id, receiver = %addr, "Connected to receiver");
+                    conn.label(&session_id);
+                    Some(conn)
+                }
+                Err(e) => {
+                    tracing::warn!(%session_id, receiver = %addr, error = %e, "Failed to connect to receiver");
+                    state_machine.transition(TransferEvent::NetworkFailure {
+                        path_id: "default".to_string(),
+                    })?;
+                    return Err(e.into());
+                }
+            }
+        } else {
+            None
+        };
+
+        // A fresh transfer first offers the file; the receiver may already
+        // have an identical copy
+        if let Some(ref conn) = connection {
+            if completed_set.is_empty() && manifest.checksum != [0u8; 32] {
+                let offer = FileOffer::from_manifest(&manifest);
+                let reply = tokio::select! {
+                    _ = cancel.cancelled() => {
+                        conn.cancel();
+                        return Ok(());
+                    }
+                    reply = conn.offer_file(&offer) => reply,
+                };
+                match reply {
+                    Ok(OfferReply::AlreadyHave) => {
+                        return self
+                            .finish_skipped_duplicate(&session_id, &session.file_id, &state_machine)
+                            .await;
+                    }
+                    Ok(OfferReply::ChunkTooLarge { max_chunk_size }) => {
+                        return Err(NetworkError::ChunkTooLarge {
+                            size: manifest.chunk_size as u64,
+                            limit: max_chunk_size,
+                        }
+                        .into());
+                    }
+                    Ok(OfferReply::Send | OfferReply::Accept(_)) => {}
+                    Err(e) => {
+                        tracing::warn!(%session_id, error = %e, "File offer failed, sending anyway")
+                    }
+                }
+            }
+        }
+
+        // Keep a handle on outgoing chunks so corrupted ones can be resent
+        let mut resends = Resends::start(
+            connection.clone(),
+            &manifest.file_id,
+            &chunks,
+            completed_set,
+            self.adaptive_coder.clone(),
+        );
+
+        let retransmit = self.policy().retransmit;
+        let mut planner = RetransmitPlanner::new(retransmit.budget_per_group);
+        let mut retries = FailedChunkRetries::new(&retransmit);
+        let mut controller = RetryController::new(
+            &retransmit,
+            manifest.parity_chunks,
+            chunks_to_transfer.len() as u32,
+        );
+
+        let mut remote = connection.as_ref().map(|conn| conn.remote_address());
+        let mut bytes_transferred = 0u64;
+        // How this sender appears in chunk traces
+        let node = self
+            .transport
+            .local_addr()
+            .map_or_else(|_| "sender".to_string(), |addr| addr.to_string());
+
+        // Throughput samples carry on from where an earlier run stopped
+        let bytes_before = session.metrics.bytes_transferred;
+        self.sampler.start(
+            &session_id,
+            self.session_store.load_timeseries(&session_id).await?,
+            bytes_before,
+        );
+
+        // Chunks still to send (only if we have them) go into the shared
+        // queue a window at a time, so other transfers keep their share;
+        // any the manifest names to send first lead
+        let send_first = manifest
+            .schedule
+            .as_ref()
+            .map(|schedule| schedule.send_first.as_slice())
+            .unwrap_or_default();
+        let mut window = SessionWindow::new(
+            self.policy().session_window,
+            chunks
+                .into_iter()
+                .filter(|chunk| !completed_set.contains(&chunk.metadata.sequence_number)),
+        )
+        .with_send_first(send_first);
+
+        // Transfer loop
+        while !chunks_to_transfer.is_empty() {
+            // Check if paused or cancelled
+            let current_state = state_machine.current_state();
+            if current_state.is_paused() {
+                self.session_store
+                    .update_status(&session_id, SessionStatus::Paused)
+                    .await?;
+                break;
+            }
+            if current_state.is_terminal() || current_state.is_partially_delivered() {
+                break;
+            }
+            if let Some(source) = &mut source {
+                source.check_due().await?;
+            }
+            self.sampler.sample(
+                &session_id,
+                bytes_before + bytes_transferred,
+                connection.as_deref(),
+            );
+
+            let nacked: Vec<u32> = std::iter::from_fn(|| resends.nacks.try_recv().ok()).collect();
+            self.resend_nacked(
+                &session_id,
+                &mut resends,
+                nacked,
+                &mut window,
+                &mut chunks_to_transfer,
+            )
+            .await?;
+            window.fill(&self.queue)?;
+
+            // Dequeue next chunk
+            match self.queue.dequeue_file_queued(&manifest.file_id) {
+                Ok(mut queued) => {
+                    window.taken();
+                    queued.chunk.metadata.record_hop(&node, HopStage::Sent);
+                    let chunk = &queued.chunk;
+                    let chunk_num = chunk.metadata.sequence_number;
+                    tracing::trace!(trace_id = %session_id, chunk = chunk_num, "sending chunk");
+                    let chunk_bytes = chunk.data.len() as u64;
+
+                    // Actually send chunk over network (if connection established)
+                    if let Some(ref conn) = connection {
+                        if let Some(limiter) = &rate_limit {
+                            tokio::select! {
+                                _ = cancel.cancelled() => break,
+                                _ = limiter.wait_for_bytes(chunk.data.len()) => {}
+                            }
+                        }
+                        // Retry on the spot unless parity can cover a loss
+                        let decision = controller.decide(conn.path_stats().loss_rate);
+                        recorder::record_retry_decision(&session_id, decision.as_str());
+                        let send_started = Instant::now();
+                        let sent = conn.send_chunk(chunk, decision.retries(), &cancel).await;
+                        if !matches!(sent, Err(NetworkError::Cancelled)) {
+                            controller.record(decision, sent.is_ok());
+                        }
+                        if let Err(e) = sent {
+                            if matches!(e, NetworkError::Cancelled) {
+                                break;
+                            }
+                            tracing::warn!(%session_id, chunk = chunk_num, error = %e, "Failed to send chunk");
+
+                            // A pinned uplink that vanished won't come back by retrying
+                            if let Some(local) = local_addr {
+                                if let Err(e) = self.transport.validate_local_addr(local, None) {
+                                    state_machine.transition(TransferEvent::NetworkFailure {
+                                        path_id: local.to_string(),
+                                    })?;
+                                    return Err(e.into());
+                                }
+                            }
+
+                            // Mark as failed and move on; it is retried once
+                            // the rest has gone out unless left to parity
+                            if decision != RetryDecision::LeaveToParity {
+                                retries.failed(chunk_num);
+                            }
+                            recorder::record_chunk_failed(&session_id);
+                            self.session_store
+                                .mark_chunk_failed(&session_id, chunk_num)
+                                .await?;
+                            state_machine.transition(TransferEvent::ChunkFailed {
+                                chunk_number: chunk_num,
+                                error: e.to_string(),
+                            })?;
+                        } else {
+                            record_stage(Stage::Send, send_started.elapsed());
+                            // Update real QUIC stats after each chunk for live dashboard
+                            self.stats.record_quic_stats(conn.path_stats());
+                            bytes_transferred += chunk_bytes;
+                            retries.delivered(chunk_num);
+
+                            let now = conn.remote_address();
+                            if let Some(from) = remote.replace(now).filter(|from| *from != now) {
+                                self.events.publish(CoordinatorEvent::PathChanged {
+                                    session_id: session_id.clone(),
+                                    from,
+                                    to: now,
+                                });
+                            }
+                            self.record_chunk_delivered(&session_id, &state_machine, &queued)
+                                .await?;
+                        }
+                    } else {
+                        // No receiver address - simulate for local testing
+                        time::sleep(Duration::from_millis(10)).await;
+                        bytes_transferred += chunk_bytes;
+                        self.record_chunk_delivered(&session_id, &state_machine, &queued)
+                            .await?;
+                    }
+
+                    // Remove from list
+                    chunks_to_transfer.retain(|n| *n != chunk_num);
+                    self.events.publish(CoordinatorEvent::TransferProgress {
+                        session_id: session_id.clone(),
+                        chunks_completed: manifest.total_chunks - chunks_to_transfer.len() as u32,
+                        total_chunks: manifest.total_chunks,
+                        bytes_transferred,
+                    });
+
+                    // Give the receiver a moment to report corruption in
+                    // the last chunks before settling
+                    if chunks_to_transfer.is_empty() {
+                        if let Some(ref conn) = connection {
+                            let rtt = conn.path_stats().rtt_ms;
+                            let grace = MIN_NACK_GRACE.max(Duration::from_secs_f64(rtt / 500.0));
+                            if let Ok(Some(seq)) = time::timeout(grace, resends.nacks.recv()).await
+                            {
+                                self.resend_nacked(
+                                    &session_id,
+                                    &mut resends,
+                                    vec![seq],
+                                    &mut window,
+                                    &mut chunks_to_transfer,
+                                )
+                                .await?;
+                            }
+                        }
+                    }
+
+                    // Chunks whose sends failed get another go
+                    if chunks_to_transfer.is_empty() && connection.is_some() {
+                        self.retry_failed_chunks(
+                            &session_id,
+                            &mut retries,
+                            &resends,
+                            &mut window,
+                            &mut chunks_to_transfer,
+                        )
+                        .await?;
+                    }
+
+                    // Then ask whether FEC can cover what was lost
+                    if chunks_to_transfer.is_empty() && connection.is_some() {
+                        self.retransmit_lost(
+                            &session_id,
+                            &retransmit,
+                            &mut planner,
+                            &mut resends,
+                            &mut window,
+                            &mut chunks_to_transfer,
+                        )
+                        .await?;
+                    }
+
+                    // Check if all chunks transferred
+                    if chunks_to_transfer.is_empty() {
+                        state_machine.transition(TransferEvent::TransferComplete)?;
+                        break;
+                    }
+                }
+                Err(crate::priority::QueueError::QueueEmpty) => {
+                    // No chunks available, wait a bit
+                    time::sleep(Duration::from_millis(100)).await;
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        self.sampler.sample_final(
+            &session_id,
+            bytes_before + bytes_transferred,
+            connection.as_deref(),
+        );
+
+        // Paused, cancelled or handed to a relay: what is left would
+        // otherwise sit in the shared queue, and a resume queues it afresh
+        if !chunks_to_transfer.is_empty() {
+            self.queue.remove_file(&manifest.file_id);
+        }
+
+        // Capture real QUIC stats from the connection after transfer
+        if let Some(ref conn) = connection {
+            self.stats.record_quic_stats(conn.path_stats());
+            // The connection is this transfer's alone; closing it resets
+            // any stream still open to the receiver
+            if cancel.is_cancelled() {
+                conn.cancel();
+            }
+        }
+
+        // Every chunk has been attempted: settle the outcome
+        if chunks_to_transfer.is_empty() {
+            let session = self
+                .session_store
+                .load(&session_id)
+                .await?
+                .ok_or_else(|| CoordinatorError::TransferNotFound(session_id.clone()))?;
+
+            if session.is_complete() {
+                let repaired_chunks = session.failed_chunks.len() as u32;
+                let status = match repaired_chunks {
+                    0 => SessionStatus::Completed,
+                    repaired_chunks => SessionStatus::CompletedWithRepairs { repaired_chunks },
+                };
+                self.session_store
+                    .update_status(&session_id, status)
+                    .await?;
+                state_machine.transition(TransferEvent::TransferComplete)?;
+
+                let mut repaired: Vec<u32> = session.failed_chunks.iter().copied().collect();
+                repaired.sort_unstable();
+                for chunk_number in repaired {
+                    self.events.publish(CoordinatorEvent::ChunkRecovered {
+                        session_id: session_id.clone(),
+                        chunk_number,
+                    });
+                }
+                self.events.publish(CoordinatorEvent::TransferCompleted {
+                    session_id: session_id.clone(),
+                    repaired_chunks,
+                    tags: self.session_tags(&session_id),
+                });
+            } else {
+                let error = format!(
+                    "Only {} of {} data chunks delivered, too few to reconstruct",
+                    session.completed_chunks.len(),
+                    session.manifest.data_chunks
+                );
+                self.session_store
+                    .update_status(&session_id, SessionStatus::Failed(error.clone()))
+                    .await?;
+                state_machine.transition(TransferEvent::TransferFailed {
+                    error: error.clone(),
+                })?;
+                self.events.publish(CoordinatorEvent::TransferFailed {
+                    session_id: session_id.clone(),
+                    error,
+                    tags: self.session_tags(&session_id),
+                });
+            }
+
+            self.active_transfers.remove(&session_id);
+            // Remove file-to-session mapping so the same file can be re-uploaded
+            self.file_to_session.remove(&session.file_id);
+            self.admit_pending();
+            // Keep in recent_transfers for display
+        }
+
+        Ok(())
+    }
+
+    /// Settle a transfer whose receiver didn't answer within its connect
+    /// timeout: hand a Critical one to the fallback relay, fail the rest
+    async fn connect_timed_out(
+        &self,
+        session_id: &str,
+        manifest: &FileManifest,
+        chunks: &[Chunk],
+        receiver: SocketAddr,
+        timeout: Duration,
+    ) -> CoordinatorResult<()> {
+        let relay = match manifest.priority.class() {
+            Priority::Critical if self.policy().connect.relay_fallback => {
+                self.fallback_relay.read().clone()
+            }
+            _ => None,
+        };
+        let handed_off = match &relay {
+            Some(relay) => {
+                self.hand_off_to_relay(session_id, manifest, chunks, receiver, relay)
+                    .await?
+            }
+            None => 0,
+        };
+        let relay = relay.filter(|_| handed_off > 0);
+        if relay.is_some() {
+            self.record_relay_handoff(session_id, handed_off).await?;
+        }
+
+        tracing::warn!(
+            session_id,
+            %receiver,
+            timeout_ms = timeout.as_millis() as u64,
+            relay = relay.as_ref().map(|r| r.node_id()),
+            "Timed out connecting to receiver"
+        );
+        self.events.publish(CoordinatorEvent::ConnectTimedOut {
+            session_id: session_id.to_string(),
+            receiver,
+            priority: manifest.priority,
+            timeout_ms: timeout.as_millis() as u64,
+            relay: relay.as_ref().map(|r| r.node_id().to_string()),
+        });
+        match relay {
+            Some(_) => Ok(()),
+            None => Err(NetworkError::Timeout(timeout).into()),
+        }
+    }
+
+    /// Store the chunks the receiver hasn't acknowledged on `relay` for
+    /// delivery to `receiver`; returns how many it took
+    async fn hand_off_to_relay(
+        &self,
+        session_id: &str,
+        manifest: &FileManifest,
+        chunks: &[Chunk],
+        receiver: SocketAddr,
+        relay: &RelayNode,
+    ) -> CoordinatorResult<u32> {
+        let session = self
+            .session_store
+            .load(session_id)
+            .await?
+            .ok_or_else(|| CoordinatorError::TransferNotFound(session_id.to_string()))?;
+        let mut stored = 0;
+        for chunk in chunks {
+            let chunk_number = chunk.metadata.sequence_number;
+            if session.completed_chunks.contains(&chunk_number) {
+                continue;
+            }
+            let route = RouteInfo::new(
+                relay_audit::ORIGIN,
+                receiver,
+                session_id,
+                manifest.priority.level(),
+            )
+            .with_sequence(chunk_number);
+            let chunk_id = format!("{session_id}:{chunk_number}");
+            match relay.receive_chunk(chunk_id, route, chunk.clone()).await {
+                Ok(()) => stored += 1,
+                Err(e) => {
+                    tracing::warn!(session_id, chunk_number, "Relay refused chunk: {}", e)
+                }
+            }
+        }
+        Ok(stored)
+    }
+
+    /// Close out a transfer the receiver didn't need
+    async fn finish_skipped_duplicate(
+        &self,
+        session_id: &str,
+        file_id: &str,
+        state_machine: &TransferStateMachine,
+    ) -> CoordinatorResult<()> {
+        tracing::info!(
+            session_id,
+            "Receiver already has an identical file, skipping transfer"
+        );
+        self.session_store
+            .mark_skipped_duplicate(session_id)
+            .await?;
+
+        // Transferring -> Completing -> Completed
+        state_machine.transition(TransferEvent::TransferComplete)?;
+        state_machine.transition(TransferEvent::TransferComplete)?;
+        self.events.publish(CoordinatorEvent::TransferCompleted {
+            session_id: session_id.to_string(),
+            repaired_chunks: 0,
+            tags: self.session_tags(session_id),
+        });
+
+        self.active_transfers.remove(session_id);
+        self.file_to_session.remove(file_id);
+        self.admit_pending();
+        Ok(())
+    }
+
+    /// Put chunks the receiver reported corrupt back in the queue
+    async fn resend_nacked(
+        &self,
+        session_id: &str,
+        resends: &mut Resends,
+        nacked: Vec<u32>,
+        window: &mut SessionWindow,
+        chunks_to_transfer: &mut Vec<u32>,
+    ) -> CoordinatorResult<()> {
+        for seq in nacked {
+            if chunks_to_transfer.contains(&seq) {
+                continue;
+            }
+            let Some(chunk) = resends.take(seq) else {
+                tracing::warn!(
+                    "Chunk {} of {} still corrupt after {} resends, leaving it to parity",
+                    seq,
+                    session_id,
+                    MAX_CHUNK_RESENDS
+                );
+                continue;
+            };
+            tracing::warn!(
+                "Receiver reported chunk {} of {} corrupt, resending",
+                seq,
+                session_id
+            );
+            self.session_store
+                .mark_chunk_nacked(session_id, seq)
+                .await?;
+            window.requeue(chunk);
+            chunks_to_transfer.push(seq);
+        }
+        Ok(())
+    }
+
+    /// Requeue chunks whose sends failed and have retries left, after the
+    /// pass's backoff
+    ///
+    /// The transfer only settles, and possibly fails, once no failed chunk
+    /// has a retry left.
+    async fn retry_failed_chunks(
+        &self,
+        session_id: &str,
+        retries: &mut FailedChunkRetries,
+        resends: &Resends,
+        window: &mut SessionWindow,
+        chunks_to_transfer: &mut Vec<u32>,
+    ) -> CoordinatorResult<()> {
+        let Some(pass) = retries.next_pass() else {
+            return Ok(());
+        };
+
+        tracing::warn!(
+            "Retrying {} failed chunks of {} in {:?}: {:?}",
+            pass.chunks.len(),
+            session_id,
+            pass.backoff,
+            pass.chunks
+        );
+        time::sleep(pass.backoff).await;
+        for seq in pass.chunks {
+            if let Some(chunk) = resends.chunks.get(&seq) {
+                window.requeue(chunk.clone());
+                chunks_to_transfer.push(seq);
+            }
+        }
+        Ok(())
+    }
+
+    /// Wait for the receiver's group report and resend what it still needs
+    ///
+    /// Reports that arrived while chunks were still going out are stale,
+    /// unless they already say the file can be rebuilt. Without a fresh
+    /// report in time the transfer settles on what was sent, as before.
+    async fn retransmit_lost(
+        &self,
+        session_id: &str,
+        policy: &RetransmitPolicy,
+        planner: &mut RetransmitPlanner,
+        resends: &mut Resends,
+        window: &mut SessionWindow,
+        chunks_to_transfer: &mut Vec<u32>,
+    ) -> CoordinatorResult<()> {
+        if policy.budget_per_group == 0 {
+            return Ok(());
+        }
+        while let Ok(report) = resends.reports.try_recv() {
+            if report.is_decodable() {
+                return Ok(());
+            }
+        }
+        let Ok(Some(report)) = time::timeout(policy.feedback_timeout, resends.reports.recv()).await
+        else {
+            return Ok(());
+        };
+
+        let decision = planner.plan_sources(&report, |seq| resends.source(seq, &report));
+        let plan = match decision {
+            RetransmitDecision::Decodable => return Ok(()),
+            RetransmitDecision::Resend(plan) => plan,
+            RetransmitDecision::OverBudget { needed, available } => {
+                tracing::warn!(
+                    "Receiver of {} is {} shards short but only {} can be resent, giving up",
+                    session_id,
+                    needed,
+                    available
+                );
+                // What didn't arrive no longer counts as delivered, so the
+                // transfer settles as failed
+                self.session_store
+                    .mark_chunks_lost(session_id, &report.missing())
+                    .await?;
+                return Ok(());
+            }
+        };
+
+        tracing::warn!(
+            "Receiver of {} is {} shards short, resending {:?} ({} bytes, {} of budget left)",
+            session_id,
+            plan.shards.len(),
+            plan.shards,
+            plan.bytes,
+            planner.remaining()
+        );
+        self.session_store
+            .mark_chunks_lost(session_id, &report.missing())
+            .await?;
+        if !plan.regenerate.is_empty() {
+            if let Err(e) = resends.regenerate(&report, &plan.regenerate) {
+                tracing::warn!(
+                    "Could not regenerate shards {:?} of {}, giving up: {}",
+                    plan.regenerate,
+                    session_id,
+                    e
+                );
+                return Ok(());
+            }
+            recorder::record_shards_regenerated(session_id, plan.regenerate.len());
+        }
+        recorder::record_shards_retransmitted(session_id, plan.shards.len(), plan.bytes);
+        for seq in plan.shards {
+            if let Some(chunk) = resends.chunks.get(&seq) {
+                window.requeue(chunk.clone());
+                chunks_to_transfer.push(seq);
+            }
+        }
+        Ok(())
+    }
+
+    /// Persist and record one delivered chunk
+    async fn record_chunk_delivered(
+        &self,
+        session_id: &str,
+        state_machine: &TransferStateMachine,
+        queued: &QueuedChunk,
+    ) -> CoordinatorResult<()> {
+        let chunk_num = queued.chunk.metadata.sequence_number;
+        let chunk_bytes = queued.chunk.data.len() as u64;
+        let priority = queued.chunk.metadata.priority;
+        recorder::record_chunk_sent(session_id, chunk_bytes as usize, priority_label(priority));
+        if let Some(violation) = self.queue.record_delivery(queued) {
+            tracing::warn!(
+                "Session {}: critical chunk {} delivered in {}ms, target {}ms",
+                session_id,
+                chunk_num,
+                violation.latency_ms,
+                violation.target_ms
+            );
+            self.events.publish(CoordinatorEvent::SloViolation {
+                session_id: session_id.to_string(),
+                chunk_number: chunk_num,
+                latency_ms: violation.latency_ms,
+                target_ms: violation.target_ms,
+            });
+        }
+
+        // Mark as completed with actual bytes transferred
+        self.session_store
+            .mark_chunk_completed_with_bytes(session_id, chunk_num, chunk_bytes)
+            .await?;
+
+        // Update state
+        state_machine.transition(TransferEvent::ChunkCompleted {
+            chunk_number: chunk_num,
+        })?;
+        Ok(())
+    }
+}
+
+impl SendService for TransferSender {
+    fn send(
+        &self,
+        source: TransferSource,
+        priority: Priority,
+        receiver_addr: Option<SocketAddr>,
+        options: TransferOptions,
+    ) -> BoxFuture<'_, CoordinatorResult<String>> {
+        Box::pin(self.send_source(source, priority, receiver_addr, options))
+    }
+
+    fn resume<'a>(
+        &'a self,
+        session_id: &'a str,
+        data: Option<Bytes>,
+    ) -> BoxFuture<'a, CoordinatorResult<()>> {
+        Box::pin(self.resume_with(session_id, data))
+    }
+
+    fn pause<'a>(&'a self, session_id: &'a str) -> BoxFuture<'a, CoordinatorResult<()>> {
+        Box::pin(async move {
+            let state_machine = self
+                .active_transfers
+                .get(session_id)
+                .ok_or_else(|| CoordinatorError::TransferNotFound(session_id.to_string()))?;
+
+            state_machine.transition(TransferEvent::Pause)?;
+            self.session_store
+                .update_status(session_id, SessionStatus::Paused)
+                .await?;
+
+            Ok(())
+        })
+    }
+
+    fn cancel<'a>(&'a self, session_id: &'a str) -> BoxFuture<'a, CoordinatorResult<()>> {
+        Box::pin(async move {
+            if let Some(pending) = self.admission.remove(session_id) {
+                self.file_to_session.remove(&pending.file_id);
+                return Ok(());
+            }
+
+            if let Some(state_machine) = self.active_transfers.get(session_id) {
+                state_machine.transition(TransferEvent::Cancel)?;
+            }
+
+            self.session_store
+                .update_status(
+                    session_id,
+                    SessionStatus::Failed("Cancelled by user".into()),
+                )
+                .await?;
+            self.active_transfers.remove(session_id);
+            self.events.publish(CoordinatorEvent::TransferFailed {
+                session_id: session_id.to_string(),
+                error: "Cancelled by user".into(),
+                tags: self.session_tags(session_id),
+            });
+            // The worker stops without settling, so it won't free the file
+            self.file_to_session
+                .retain(|_, claim| claim.session_id != session_id);
+            self.admit_pending();
+
+            Ok(())
+        })
+    }
+
+    fn set_resume_token_secret(&self, secret: Option<&str>) {
+        *self.resume_key.write() = secret.map(ResumeToken::derive_key);
+    }
+
+    fn export_resume_token<'a>(
+        &'a self,
+        session_id: &'a str,
+    ) -> BoxFuture<'a, CoordinatorResult<ResumeToken>> {
+        Box::pin(async move {
+            let session = self
+                .session_store
+                .load(session_id)
+                .await?
+                .ok_or_else(|| CoordinatorError::TransferNotFound(session_id.to_string()))?;
+            if session.status.is_completed() {
+                return Err(CoordinatorError::CannotResume(
+                    "Transfer is already complete".into(),
+                ));
+            }
+
+            Ok(ResumeToken::from_session(
+                &session,
+                self.resume_key.read().as_ref(),
+            ))
+        })
+    }
+
+    fn import_resume_token(
+        &self,
+        token: ResumeToken,
+        file_path: PathBuf,
+    ) -> BoxFuture<'_, CoordinatorResult<String>> {
+        Box::pin(async move {
+            token.verify(self.resume_key.read().as_ref())?;
+            if self.active_transfers.contains_key(&token.session_id) {
+                return Err(CoordinatorError::AlreadyInProgress(token.session_id));
+            }
+            if self.file_to_session.contains_key(&token.file_id) {
+                return Err(CoordinatorError::AlreadyInProgress(token.file_id));
+            }
+
+            // Chunk under the original file id and layout so the receiver keeps
+            // assembling the same transfer
+            let guard = SourceGuard::acquire(&file_path, self.policy().source_watch).await?;
+            let (manifest, chunks) = ChunkManager::for_manifest(&token.manifest)?
+                .split_file(&file_path, token.file_id.clone(), token.manifest.priority)
+                .await?;
+            let expected = &token.manifest;
+            if manifest.checksum != expected.checksum
+                || manifest.chunk_size != expected.chunk_size
+                || manifest.data_chunks != expected.data_chunks
+                || manifest.parity_chunks != expected.parity_chunks
+            {
+                return Err(CoordinatorError::CannotResume(format!(
+                    "{} doesn't match the exported transfer",
+                    file_path.display()
+                )));
+            }
+
+            let hook_ctx = HookContext::new(HookPoint::BeforeEnqueue, token.file_id.clone())
+                .with_path(&file_path)
+                .with_manifest(manifest.clone());
+            self.hooks.run(&hook_ctx).await?;
+
+            let mut session = SessionState::new_with_receiver(
+                token.session_id.clone(),
+                token.file_id.clone(),
+                token.manifest.clone(),
+                token.receiver_addr,
+                Some(file_path.to_string_lossy().to_string()),
+            );
+            session.completed_chunks = token.completed_chunks();
+            session.status = SessionStatus::Active;
+            self.session_store.save(&session).await?;
+
+            let claim = FileClaim::new(
+                &token.session_id,
+                &TransferSource::File(file_path.clone()),
+                token.receiver_addr,
+            );
+            let state_machine = TransferStateMachine::new();
+            state_machine.transition(TransferEvent::Start {
+                file_path,
+                priority: manifest.priority,
+            })?;
+            let worker = state_machine.claim_worker().await;
+            self.active_transfers
+                .insert(token.session_id.clone(), state_machine.clone());
+            self.recent_transfers
+                .insert(token.session_id.clone(), state_machine);
+            self.file_to_session.insert(token.file_id.clone(), claim);
+            self.events.publish(CoordinatorEvent::TransferStarted {
+                session_id: token.session_id.clone(),
+                file_id: token.file_id.clone(),
+                priority: manifest.priority,
+                total_chunks: manifest.total_chunks,
+                total_size: manifest.total_size,
+                tags: TransferTags::new(),
+            });
+            tracing::info!(
+                "Resuming {} from token: {} of {} chunks already delivered",
+                token.session_id,
+                session.completed_chunks.len(),
+                manifest.total_chunks
+            );
+
+            self.spawn_worker(
+                worker,
+                token.session_id.clone(),
+                token.file_id,
+                token.manifest,
+                chunks,
+                Some(guard),
+                token.receiver_addr,
+                None,
+            );
+            Ok(token.session_id)
+        })
+    }
+
+    fn recover_sessions(&self) -> BoxFuture<'_, CoordinatorResult<usize>> {
+        Box::pin(async move {
+            let mut recovered = 0;
+            for status in [SessionStatus::Initializing, SessionStatus::Active] {
+                let query = SessionQuery {
+                    status: Some(status),
+                    ..Default::default()
+                };
+                for session in self.session_store.query(&query).await?.sessions {
+                    if self.active_transfers.contains_key(&session.session_id) {
+                        continue;
+                    }
+                    self.session_store
+                        .update_status(&session.session_id, SessionStatus::Paused)
+                        .await?;
+                    recovered += 1;
+                }
+            }
+            if recovered > 0 {
+                tracing::info!("Recovered {} interrupted transfers as paused", recovered);
+            }
+            Ok(recovered)
+        })
+    }
+
+    fn shutdown(&self) -> BoxFuture<'_, CoordinatorResult<usize>> {
+        Box::pin(async move {
+            self.shutting_down.store(true, Ordering::SeqCst);
+
+            let dropped = self.admission.pending();
+            for pending in &dropped {
+                self.cancel(&pending.session_id).await?;
+            }
+            if !dropped.is_empty() {
+                tracing::warn!("Dropped {} transfers waiting for a slot", dropped.len());
+            }
+
+            let mut paused = 0;
+            for session_id in self.list_active() {
+                let pausable = self
+                    .active_transfers
+                    .get(&session_id)
+                    .is_some_and(|machine| machine.current_state().is_active());
+                if !pausable {
+                    continue;
+                }
+                match self.pause(&session_id).await {
+                    Ok(()) => paused += 1,
+                    // It finished or failed in the meantime
+                    Err(e) => tracing::debug!("Not pausing {} on shutdown: {}", session_id, e),
+                }
+            }
+            tracing::info!("Shutdown paused {} transfers", paused);
+            // Chunk progress held back from storage goes out now
+            self.session_store.flush().await?;
+            Ok(paused)
+        })
+    }
+
+    fn is_shutting_down(&self) -> bool {
+        self.shutting_down.load(Ordering::SeqCst)
+    }
+
+    fn record_relay_handoff<'a>(
+        &'a self,
+        session_id: &'a str,
+        held_by_relay: u32,
+    ) -> BoxFuture<'a, CoordinatorResult<()>> {
+        Box::pin(async move {
+            let session = self
+                .session_store
+                .load(session_id)
+                .await?
+                .ok_or_else(|| CoordinatorError::TransferNotFound(session_id.to_string()))?;
+            let delivered_chunks = session.completed_chunks.len() as u32;
+
+            if let Some(state_machine) = self.recent_transfers.get(session_id) {
+                state_machine.transition(TransferEvent::PartialDelivery {
+                    delivered_chunks,
+                    held_by_relay,
+                })?;
+            }
+            self.session_store
+                .update_status(
+                    session_id,
+                    SessionStatus::PartiallyDelivered {
+                        delivered_chunks,
+                        held_by_relay,
+                    },
+                )
+                .await?;
+            Ok(())
+        })
+    }
+
+    fn complete_relay_delivery<'a>(
+        &'a self,
+        session_id: &'a str,
+    ) -> BoxFuture<'a, CoordinatorResult<()>> {
+        Box::pin(async move {
+            let session = self
+                .session_store
+                .load(session_id)
+                .await?
+                .ok_or_else(|| CoordinatorError::TransferNotFound(session_id.to_string()))?;
+            if !matches!(session.status, SessionStatus::PartiallyDelivered { .. }) {
+                return Err(CoordinatorError::InvalidStateTransition(format!(
+                    "Transfer {session_id} is not partially delivered"
+                )));
+            }
+
+            let repaired_chunks = match self.recent_transfers.get(session_id) {
+                Some(state_machine) => {
+                    match state_machine.transition(TransferEvent::TransferComplete)? {
+                        TransferState::CompletedWithRepairs { repaired_chunks } => repaired_chunks,
+                        _ => 0,
+                    }
+                }
+                None => 0,
+            };
+            let status = match repaired_chunks {
+                0 => SessionStatus::Completed,
+                repaired_chunks => SessionStatus::CompletedWithRepairs { repaired_chunks },
+            };
+            self.session_store.update_status(session_id, status).await?;
+            self.events.publish(CoordinatorEvent::TransferCompleted {
+                session_id: session_id.to_string(),
+                repaired_chunks,
+                tags: self.session_tags(session_id),
+            });
+            self.active_transfers.remove(session_id);
+            self.file_to_session.remove(&session.file_id);
+            self.admit_pending();
+            Ok(())
+        })
+    }
+
+    fn state(&self, session_id: &str) -> Option<TransferState> {
+        self.active_transfers
+            .get(session_id)
+            .map(|sm| sm.current_state())
+    }
+
+    fn recent_state(&self, session_id: &str) -> Option<TransferState> {
+        if self.admission.contains(session_id) {
+            return Some(TransferState::Idle);
+        }
+        self.state(session_id).or_else(|| {
+            self.recent_transfers
+                .get(session_id)
+                .map(|sm| sm.current_state())
+        })
+    }
+
+    fn live_timeseries(&self, session_id: &str) -> Option<Vec<ProgressSample>> {
+        self.sampler.samples(session_id)
+    }
+
+    fn list_active(&self) -> Vec<String> {
+        self.active_transfers
+            .iter()
+            .map(|e| e.key().clone())
+            .collect()
+    }
+
+    fn list_recent(&self) -> Vec<String> {
+        self.recent_transfers
+            .iter()
+            .map(|e| e.key().clone())
+            .collect()
+    }
+
+    fn pending_transfers(&self) -> Vec<PendingTransfer> {
+        self.admission.pending()
+    }
+
+    fn session_for_file(&self, file_id: &str) -> Option<String> {
+        self.file_to_session
+            .get(file_id)
+            .map(|claim| claim.session_id.clone())
+    }
+
+    fn max_concurrent_transfers(&self) -> usize {
+        self.admission.max_concurrent()
+    }
+
+    fn set_max_concurrent_transfers(&self, max: usize) {
+        self.admission.set_max_concurrent(max);
+        self.admit_pending();
+    }
+
+    fn count_completed(&self) -> usize {
+        let retained = self
+            .recent_transfers
+            .iter()
+            .filter(|e| e.value().current_state().is_terminal())
+            .count();
+        retained + self.evicted_finished.load(Ordering::Relaxed) as usize
+    }
+
+    fn evict_finished(&self) -> usize {
+        let evicted = evict_finished(&self.recent_transfers, &self.policy().retention);
+        self.evicted_finished
+            .fetch_add(evicted as u64, Ordering::Relaxed);
+        evicted
+    }
+
+    fn policy(&self) -> SendPolicy {
+        *self.policy.read()
+    }
+
+    fn update_policy(&self, change: &mut dyn FnMut(&mut SendPolicy)) {
+        change(&mut self.policy.write());
+    }
+
+    fn set_fallback_relay(&self, relay: Option<Arc<RelayNode>>) {
+        *self.fallback_relay.write() = relay;
+    }
+
+    fn resource_usage(&self) -> ResourceUsage {
+        ResourceUsage {
+            active_transfers: self.active_transfers.len(),
+            recent_transfers: self.recent_transfers.len(),
+            file_sessions: self.file_to_session.len(),
+            sampled_sessions: self.sampler.len(),
+            pending_transfers: self.admission.pending().len(),
+            ..ResourceUsage::default()
+        }
+    }
+}
+
+/// Outgoing chunks of one transfer that a receiver may NACK or report lost
+struct Resends {
+    nacks: mpsc::UnboundedReceiver<u32>,
+    reports: mpsc::UnboundedReceiver<GroupFeedback>,
+    chunks: HashMap<u32, Chunk>,
+    attempts: HashMap<u32, u32>,
+    /// Tree over every chunk of the file, so rebuilt shards carry proofs
+    merkle: Option<MerkleTree>,
+    listener: Option<JoinHandle<()>>,
+}
+
+impl Resends {
+    /// Listen for NACKs and group reports for `file_id` on `connection`
+    ///
+    /// The receiver's stats reports go to `coder` as chunk outcomes.
+    fn start(
+        connection: Option<Arc<dyn TransportLink>>,
+        file_id: &str,
+        chunks: &[Chunk],
+        completed: &HashSet<u32>,
+        coder: Arc<AdaptiveErasureCoder>,
+    ) -> Self {
+        let (tx, nacks) = mpsc::unbounded_channel();
+        let (report_tx, reports) = mpsc::unbounded_channel();
+        let Some(conn) = connection else {
+            return Self {
+                nacks,
+                reports,
+                chunks: HashMap::new(),
+                attempts: HashMap::new(),
+                merkle: None,
+                listener: None,
+            };
+        };
+
+        let file_id = file_id.to_string();
+        let listener = tokio::spawn(async move {
+            let mut last_stats = conn.receiver_stats().unwrap_or_default();
+            while let Ok(feedback) = conn.receive_feedback().await {
+                let delivered = match feedback {
+                    ReceiverFeedback::Stats(stats) => {
+                        let new = stats.since(&last_stats);
+                        last_stats = stats;
+                        coder.record_outcomes(new.chunks_received, new.checksum_failures);
+                        true
+                    }
+                    ReceiverFeedback::Nack(nack) if nack.file_id == file_id => {
+                        tx.send(nack.sequence_number).is_ok()
+                    }
+                    ReceiverFeedback::Group(report) if report.file_id == file_id => {
+                        report_tx.send(report).is_ok()
+                    }
+                    _ => true,
+                };
+                if !delivered {
+                    break;
+                }
+            }
+        });
+
+        Self {
+            nacks,
+            reports,
+            chunks: chunks
+                .iter()
+                .filter(|c| !completed.contains(&c.metadata.sequence_number))
+                .map(|c| (c.metadata.sequence_number, c.clone()))
+                .collect(),
+            attempts: HashMap::new(),
+            merkle: Self::merkle_tree(chunks),
+            listener: Some(listener),
+        }
+    }
+
+    /// Merkle tree over the checksums of `chunks`, if they carry proofs and
+    /// cover the whole file
+    fn merkle_tree(chunks: &[Chunk]) -> Option<MerkleTree> {
+        let first = chunks.first()?;
+        first.metadata.merkle_proof.as_ref()?;
+        let mut leaves = vec![None; first.metadata.total_chunks as usize];
+        for chunk in chunks {
+            if let Some(leaf) = leaves.get_mut(chunk.metadata.sequence_number as usize) {
+                *leaf = Some(chunk.metadata.checksum);
+            }
+        }
+        let leaves: Vec<[u8; 32]> = leaves.into_iter().collect::<Option<_>>()?;
+        Some(MerkleTree::new(first.metadata.checksum_algorithm, &leaves))
+    }
+
+    /// Where a resend of `seq` would come from
+    ///
+    /// A shard no longer held can be rebuilt while a group's worth of the
+    /// others still is; all shards of a group are the same size.
+    fn source(&self, seq: u32, report: &GroupFeedback) -> ShardSource {
+        if let Some(chunk) = self.chunks.get(&seq) {
+            return ShardSource::Held(chunk.data.len() as u64);
+        }
+        match self.chunks.values().next() {
+            Some(chunk) if self.chunks.len() >= report.data_chunks as usize => {
+                ShardSource::Regenerate(chunk.data.len() as u64)
+            }
+            _ => ShardSource::Unavailable,
+        }
+    }
+
+    /// Rebuild the shards at `seqs` from those held and hold them too
+    fn regenerate(&mut self, report: &GroupFeedback, seqs: &[u32]) -> crate::chunk::Result<()> {
+        let data_chunks = report.data_chunks as usize;
+        let template =
+            self.chunks
+                .values()
+                .next()
+                .cloned()
+                .ok_or(ChunkError::InsufficientChunks {
+                    needed: data_chunks,
+                    available: 0,
+                })?;
+        let coder = ErasureCoder::new(
+            data_chunks,
+            (report.total_chunks as usize).saturating_sub(data_chunks),
+        )?;
+        let held = (0..report.total_chunks)
+            .map(|seq| self.chunks.get(&seq).map(|c| c.data.clone()))
+            .collect();
+        let wanted: Vec<usize> = seqs.iter().map(|&seq| seq as usize).collect();
+        let rebuilt = coder.regenerate(held, &wanted)?;
+
+        // A rebuilt shard is the original, so it proves against the same root
+        for (&seq, data) in seqs.iter().zip(rebuilt) {
+            let metadata = &template.metadata;
+            let metadata = ChunkMetadata {
+                chunk_id: uuid::Uuid::new_v4().as_u128() as u64,
+                sequence_number: seq,
+                data_size: data.len(),
+                checksum: metadata.checksum_algorithm.digest(&data),
+                is_parity: seq >= report.data_chunks,
+                merkle_proof: self
+                    .merkle
+                    .as_ref()
+                    .and_then(|tree| tree.proof(seq as usize)),
+                trace: None,
+                compression: None,
+                ..metadata.clone()
+            };
+            self.chunks.insert(seq, Chunk { metadata, data });
+        }
+        Ok(())
+    }
+
+    /// The chunk to resend for `seq`, unless it has been resent too often
+    fn take(&mut self, seq: u32) -> Option<Chunk> {
+        let attempts = self.attempts.entry(seq).or_default();
+        if *attempts >= MAX_CHUNK_RESENDS {
+            return None;
+        }
+        *attempts += 1;
+        self.chunks.get(&seq).cloned()
+    }
+}
+
+impl Drop for Resends {
+    fn drop(&mut self) {
+        if let Some(listener) = self.listener.take() {
+            listener.abort();
+        }
+    }
+}
+
+/// Drop finished transfers older than `max_age`, then the oldest finished
+/// ones beyond `max_entries`. Transfers still running are never evicted.
+fn evict_finished(
+    recent: &DashMap<String, TransferStateMachine>,
+    policy: &RetentionPolicy,
+) -> usize {
+    let mut finished: Vec<(String, Instant)> = recent
+        .iter()
+        .filter_map(|e| e.value().finished_at().map(|at| (e.key().clone(), at)))
+        .collect();
+    finished.sort_by_key(|(_, at)| *at);
+
+    let expired = finished
+        .iter()
+        .take_while(|(_, at)| at.elapsed() > policy.max_age)
+        .count();
+    let over_limit = finished.len().saturating_sub(policy.max_entries);
+    let evict = expired.max(over_limit);
+
+    for (session_id, _) in &finished[..evict] {
+        recent.remove(session_id);
+    }
+    evict
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::AdaptiveErasureConfig;
+    use crate::coordinator::stats::HealthTracker;
+    use crate::integrity::IntegrityVerifier;
+    use crate::network::{ConnectionConfig, QuicTransport};
+    use crate::session::MemoryRepository;
+
+    async fn create_test_sender() -> TransferSender {
+        let chunk_manager = ChunkManager::new(256 * 1024, 10, 3).unwrap();
+        let transport: Arc<dyn Transport> = Arc::new(
+            QuicTransport::new(ConnectionConfig::default())
+                .await
+                .unwrap(),
+        );
+        let queue = Arc::new(PriorityQueue::new(1_000_000));
+        let session_store: Arc<dyn SessionRepository> = Arc::new(MemoryRepository::new());
+        let stats = Arc::new(HealthTracker::new(
+            queue.clone(),
+            session_store.clone(),
+            transport.clone(),
+        ));
+
+        TransferSender::new(
+            Arc::new(parking_lot::RwLock::new(Arc::new(chunk_manager))),
+            transport,
+            queue,
+            session_store,
+            Arc::new(HookRegistry::new()),
+            EventBus::default(),
+            stats,
+            Arc::new(AdaptiveErasureCoder::new(AdaptiveErasureConfig::default())),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_policy_keys_on_file_and_receiver() {
+        let sender = create_test_sender().await;
+        let source = TransferSource::File("/data/survey.tif".into());
+        let north: SocketAddr = "10.0.0.1:5001".parse().unwrap();
+        let south: SocketAddr = "10.0.0.2:5001".parse().unwrap();
+        sender.file_to_session.insert(
+            source.file_id(),
+            FileClaim::new("first", &source, Some(north)),
+        );
+
+        assert_eq!(sender.policy().duplicates, DuplicatePolicy::PerReceiver);
+        assert!(matches!(
+            sender.claim_file_id(&source, Some(north), false),
+            Err(CoordinatorError::AlreadyInProgress(_))
+        ));
+        assert_eq!(
+            sender.claim_file_id(&source, Some(south), false).unwrap(),
+            "/data/survey.tif#2"
+        );
+        // The override skips the check, not the unique id
+        assert_eq!(
+            sender.claim_file_id(&source, Some(north), true).unwrap(),
+            "/data/survey.tif#2"
+        );
+
+        sender.update_policy(&mut |p| p.duplicates = DuplicatePolicy::PerFile);
+        assert!(sender.claim_file_id(&source, Some(south), false).is_err());
+        sender.update_policy(&mut |p| p.duplicates = DuplicatePolicy::Allow);
+        assert!(sender.claim_file_id(&source, Some(north), false).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_regenerated_shards_prove_against_the_root() {
+        let manager = ChunkManager::new(1024, 4, 2).unwrap();
+        let data: Vec<u8> = (0..4096).map(|i| (i % 251) as u8).collect();
+        let (manifest, chunks) = manager
+            .split_bytes(&data, "data.bin".into(), "regen".into(), Priority::High)
+            .unwrap();
+
+        // Shards 1 and 5 were dropped once the receiver had them
+        let (_tx, nacks) = mpsc::unbounded_channel();
+        let (_report_tx, reports) = mpsc::unbounded_channel();
+        let mut resends = Resends {
+            nacks,
+            reports,
+            chunks: chunks
+                .iter()
+                .filter(|c| ![1, 5].contains(&c.metadata.sequence_number))
+                .map(|c| (c.metadata.sequence_number, c.clone()))
+                .collect(),
+            attempts: HashMap::new(),
+            merkle: Resends::merkle_tree(&chunks),
+            listener: None,
+        };
+        let report = GroupFeedback {
+            file_id: manifest.file_id.clone(),
+            data_chunks: manifest.data_chunks,
+            total_chunks: manifest.total_chunks,
+            received: vec![0, 2, 3],
+        };
+        resends.regenerate(&report, &[1, 5]).unwrap();
+
+        for seq in [1, 5] {
+            let rebuilt = resends.take(seq).unwrap();
+            assert_eq!(rebuilt.data, chunks[seq as usize].data);
+            IntegrityVerifier::verify_chunk_proof(&rebuilt, &manifest).unwrap();
+        }
+    }
+}
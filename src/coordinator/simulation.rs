@@ -0,0 +1,450 @@
+//! Simulated transfers, for the dashboard and for sizing parity
+//!
+//! Nothing here touches the network: files are split as they would be for
+//! a real transfer and chunk loss is rolled per chunk. The adaptive coder
+//! and the simulation counters back the dashboard's loss slider and data
+//! flow view. Loss rolls come from a seedable generator, so a run given a
+//! seed can be repeated exactly.
+
+use crate::chunk::{AdaptiveErasureCoder, ChunkManager, Priority};
+use crate::coordinator::error::CoordinatorResult;
+use crate::relay::{MeshReport, MeshScenario, MeshSimulation};
+use futures::future::BoxFuture;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Result of a file-based packet loss simulation (aggregated over multiple trials)
+#[derive(Debug, Clone)]
+pub struct SimulateFileResult {
+    pub file_name: String,
+    pub file_size_bytes: u64,
+    pub total_chunks: u32,
+    pub data_chunks: usize,
+    pub parity_chunks: usize,
+    // Aggregate stats over N trials
+    pub num_trials: u32,
+    pub successful_trials: u32,
+    pub success_rate: f64,
+    pub avg_chunks_lost: f64,
+    pub avg_chunks_recovered: f64,
+    pub min_chunks_lost: u32,
+    pub max_chunks_lost: u32,
+}
+
+/// Result of running a file through a simulated relay mesh
+#[derive(Debug, Clone)]
+pub struct MeshSimulationResult {
+    pub file_name: String,
+    pub file_size_bytes: u64,
+    pub parity_chunks: usize,
+    pub report: MeshReport,
+}
+
+/// Single data point in a TCP vs RESILIENT comparison sweep
+#[derive(Debug, Clone)]
+pub struct ComparisonPoint {
+    pub loss_percent: u32,
+    pub tcp_success_rate: f64,
+    pub resilient_success_rate: f64,
+    pub tcp_avg_chunks_lost: f64,
+    pub resilient_avg_chunks_lost: f64,
+    pub resilient_avg_recovered: f64,
+}
+
+/// Full comparison result across all loss rates
+#[derive(Debug, Clone)]
+pub struct ComparisonResult {
+    pub file_name: String,
+    pub file_size_bytes: u64,
+    pub total_chunks: u32,
+    pub data_chunks: usize,
+    pub parity_chunks: usize,
+    pub trials_per_point: u32,
+    pub points: Vec<ComparisonPoint>,
+}
+
+/// Runs simulated transfers and keeps the counters they add up to
+pub trait SimulationService: Send + Sync {
+    /// Get simulation counters
+    fn sim_chunks_sent(&self) -> u64;
+
+    fn sim_chunks_lost(&self) -> u64;
+
+    fn sim_chunks_recovered(&self) -> u64;
+
+    /// Simulate packet loss at a given rate.
+    /// Directly sets the observed loss rate (no EMA smoothing) so the
+    /// dashboard immediately reflects the slider value.
+    fn simulate_packet_loss(&self, loss_rate: f32, num_samples: u32);
+
+    /// Simulate a file transfer with a given packet loss rate.
+    /// Runs multiple trials to produce statistically meaningful results.
+    /// The same `seed` gives the same losses; without one they are random.
+    fn simulate_file_transfer(
+        &self,
+        file_path: PathBuf,
+        loss_rate: f32,
+        seed: Option<u64>,
+    ) -> BoxFuture<'_, CoordinatorResult<SimulateFileResult>>;
+
+    /// Run a comparison simulation: for each loss rate (0%..40%), run N trials
+    /// for both TCP-style (no FEC, any lost chunk = failure) and RESILIENT
+    /// (Reed-Solomon parity). Returns per-point success rates, which are
+    /// the same on every run with the same `seed`.
+    fn simulate_comparison(
+        &self,
+        file_path: PathBuf,
+        trials_per_point: u32,
+        seed: Option<u64>,
+    ) -> BoxFuture<'_, CoordinatorResult<ComparisonResult>>;
+
+    /// Run a file through a mesh of relays with per-link loss, latency and
+    /// bandwidth, reporting loss per hop, peak storage per relay and
+    /// end-to-end latency.
+    fn simulate_mesh(
+        &self,
+        file_path: PathBuf,
+        scenario: MeshScenario,
+    ) -> BoxFuture<'_, CoordinatorResult<MeshSimulationResult>>;
+}
+
+/// [`SimulationService`] splitting files as real transfers would and
+/// rolling loss per chunk
+#[derive(Clone)]
+pub struct LossSimulator {
+    // Shared with the coordinator, which replaces it when defaults change
+    chunk_manager: Arc<parking_lot::RwLock<Arc<ChunkManager>>>,
+
+    // Shared with the coordinator; simulations set its loss rate
+    adaptive_coder: Arc<AdaptiveErasureCoder>,
+
+    // Simulation counters
+    sim_chunks_sent: Arc<AtomicU64>,
+    sim_chunks_lost: Arc<AtomicU64>,
+    sim_chunks_recovered: Arc<AtomicU64>,
+}
+
+impl LossSimulator {
+    pub(crate) fn new(
+        chunk_manager: Arc<parking_lot::RwLock<Arc<ChunkManager>>>,
+        adaptive_coder: Arc<AdaptiveErasureCoder>,
+    ) -> Self {
+        Self {
+            chunk_manager,
+            adaptive_coder,
+            sim_chunks_sent: Arc::new(AtomicU64::new(0)),
+            sim_chunks_lost: Arc::new(AtomicU64::new(0)),
+            sim_chunks_recovered: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    fn chunk_manager(&self) -> Arc<ChunkManager> {
+        self.chunk_manager.read().clone()
+    }
+}
+
+impl SimulationService for LossSimulator {
+    fn sim_chunks_sent(&self) -> u64 {
+        self.sim_chunks_sent.load(Ordering::Relaxed)
+    }
+
+    fn sim_chunks_lost(&self) -> u64 {
+        self.sim_chunks_lost.load(Ordering::Relaxed)
+    }
+
+    fn sim_chunks_recovered(&self) -> u64 {
+        self.sim_chunks_recovered.load(Ordering::Relaxed)
+    }
+
+    fn simulate_packet_loss(&self, loss_rate: f32, num_samples: u32) {
+        // Directly set the loss rate — no smoothing lag
+        self.adaptive_coder.set_loss_rate(loss_rate);
+
+        let losses = (num_samples as f32 * loss_rate) as u32;
+        let successes = num_samples - losses;
+
+        // Update simulation counters for the data flow visualization
+        self.sim_chunks_sent
+            .fetch_add(num_samples as u64, Ordering::Relaxed);
+        self.sim_chunks_lost
+            .fetch_add(losses as u64, Ordering::Relaxed);
+
+        // Recovered = losses that could be recovered (up to parity capacity)
+        let status = self.adaptive_coder.status();
+        let max_recoverable = status.parity_shards as u32;
+        let recovered = losses.min(max_recoverable);
+        self.sim_chunks_recovered
+            .fetch_add(recovered as u64, Ordering::Relaxed);
+
+        // Also feed samples so future incremental updates work correctly
+        for _ in 0..successes {
+            self.adaptive_coder.record_success();
+        }
+        for _ in 0..losses {
+            self.adaptive_coder.record_loss();
+        }
+    }
+
+    fn simulate_file_transfer(
+        &self,
+        file_path: PathBuf,
+        loss_rate: f32,
+        seed: Option<u64>,
+    ) -> BoxFuture<'_, CoordinatorResult<SimulateFileResult>> {
+        Box::pin(async move {
+            const NUM_TRIALS: u32 = 10;
+
+            // Set the adaptive coder to reflect the simulated loss rate
+            self.adaptive_coder.set_loss_rate(loss_rate);
+
+            // Get the adaptive parity level for this loss rate
+            let adaptive_parity = self.adaptive_coder.current_parity();
+
+            // Split the file into chunks using smart chunk sizing for simulation
+            let file_id = file_path.to_string_lossy().to_string();
+            let file_size = tokio::fs::metadata(&file_path).await?.len();
+            let sim_chunk_size = crate::chunk::ChunkManager::simulation_chunk_size(file_size);
+            let (manifest, chunks) = self
+                .chunk_manager()
+                .split_file_with_chunk_size(
+                    &file_path,
+                    file_id,
+                    Priority::Normal,
+                    sim_chunk_size,
+                    Some(adaptive_parity),
+                )
+                .await?;
+
+            let total_chunks = chunks.len() as u32;
+            let data_chunks = manifest.data_chunks as usize;
+            let parity_chunks = manifest.parity_chunks as usize;
+
+            let mut rng = loss_rng(seed);
+            let mut successful_trials: u32 = 0;
+            let mut total_lost: u32 = 0;
+            let mut total_recovered: u32 = 0;
+            let mut min_lost: u32 = u32::MAX;
+            let mut max_lost: u32 = 0;
+
+            for _ in 0..NUM_TRIALS {
+                let mut lost_count: u32 = 0;
+
+                for _ in &chunks {
+                    let roll: f32 = rng.gen();
+                    if roll < loss_rate {
+                        lost_count += 1;
+                    }
+                }
+
+                let surviving = total_chunks - lost_count;
+                let recoverable = surviving as usize >= data_chunks;
+
+                if recoverable {
+                    successful_trials += 1;
+                    total_recovered += lost_count; // all lost chunks effectively recovered
+                } else {
+                    let max_rec = (parity_chunks as u32).min(lost_count);
+                    total_recovered += max_rec;
+                }
+
+                total_lost += lost_count;
+                min_lost = min_lost.min(lost_count);
+                max_lost = max_lost.max(lost_count);
+            }
+
+            if min_lost == u32::MAX {
+                min_lost = 0;
+            }
+
+            let avg_lost = total_lost as f64 / NUM_TRIALS as f64;
+            let avg_recovered = total_recovered as f64 / NUM_TRIALS as f64;
+            let success_rate = successful_trials as f64 / NUM_TRIALS as f64 * 100.0;
+
+            // Update simulation counters for the live dashboard (aggregate across all trials)
+            self.sim_chunks_sent.fetch_add(
+                (total_chunks as u64) * (NUM_TRIALS as u64),
+                Ordering::Relaxed,
+            );
+            self.sim_chunks_lost
+                .fetch_add(total_lost as u64, Ordering::Relaxed);
+            self.sim_chunks_recovered
+                .fetch_add(total_recovered as u64, Ordering::Relaxed);
+
+            let file_name = file_path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string();
+
+            Ok(SimulateFileResult {
+                file_name,
+                file_size_bytes: manifest.total_size,
+                total_chunks,
+                data_chunks,
+                parity_chunks,
+                num_trials: NUM_TRIALS,
+                successful_trials,
+                success_rate,
+                avg_chunks_lost: avg_lost,
+                avg_chunks_recovered: avg_recovered,
+                min_chunks_lost: min_lost,
+                max_chunks_lost: max_lost,
+            })
+        })
+    }
+
+    fn simulate_comparison(
+        &self,
+        file_path: PathBuf,
+        trials_per_point: u32,
+        seed: Option<u64>,
+    ) -> BoxFuture<'_, CoordinatorResult<ComparisonResult>> {
+        Box::pin(async move {
+            let file_id = file_path.to_string_lossy().to_string();
+            let file_size = tokio::fs::metadata(&file_path).await?.len();
+            let sim_chunk_size = crate::chunk::ChunkManager::simulation_chunk_size(file_size);
+            // Use max parity (25 shards at severe loss) for comparison view so we
+            // show the full RESILIENT recovery capability across all loss rates.
+            let max_parity = 25_usize; // matches AdaptiveErasureConfig::max_parity_shards
+            let (manifest, chunks) = self
+                .chunk_manager()
+                .split_file_with_chunk_size(
+                    &file_path,
+                    file_id,
+                    Priority::Normal,
+                    sim_chunk_size,
+                    Some(max_parity),
+                )
+                .await?;
+
+            let total_chunks = chunks.len() as u32;
+            let data_chunks = manifest.data_chunks as usize;
+            let parity_chunks = manifest.parity_chunks as usize;
+
+            let file_name = file_path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string();
+
+            let mut rng = loss_rng(seed);
+            let mut points = Vec::new();
+
+            // Sweep loss from 0% to 40% in 1% steps
+            for loss_pct in 0..=40u32 {
+                let loss_rate = loss_pct as f32 / 100.0;
+
+                let mut tcp_successes: u32 = 0;
+                let mut resilient_successes: u32 = 0;
+                let mut tcp_total_lost: u32 = 0;
+                let mut resilient_total_lost: u32 = 0;
+                let mut resilient_total_recovered: u32 = 0;
+
+                for _ in 0..trials_per_point {
+                    let mut lost: u32 = 0;
+                    for _ in 0..total_chunks {
+                        if rng.gen::<f32>() < loss_rate {
+                            lost += 1;
+                        }
+                    }
+
+                    // TCP: no FEC, any lost chunk means the file is incomplete
+                    if lost == 0 {
+                        tcp_successes += 1;
+                    }
+                    tcp_total_lost += lost;
+
+                    // RESILIENT: can tolerate up to parity_chunks lost
+                    let surviving = total_chunks - lost;
+                    if surviving as usize >= data_chunks {
+                        resilient_successes += 1;
+                        resilient_total_recovered += lost;
+                    } else {
+                        let max_rec = (parity_chunks as u32).min(lost);
+                        resilient_total_recovered += max_rec;
+                    }
+                    resilient_total_lost += lost;
+                }
+
+                let t = trials_per_point as f64;
+                points.push(ComparisonPoint {
+                    loss_percent: loss_pct,
+                    tcp_success_rate: tcp_successes as f64 / t * 100.0,
+                    resilient_success_rate: resilient_successes as f64 / t * 100.0,
+                    tcp_avg_chunks_lost: tcp_total_lost as f64 / t,
+                    resilient_avg_chunks_lost: resilient_total_lost as f64 / t,
+                    resilient_avg_recovered: resilient_total_recovered as f64 / t,
+                });
+            }
+
+            Ok(ComparisonResult {
+                file_name,
+                file_size_bytes: manifest.total_size,
+                total_chunks,
+                data_chunks,
+                parity_chunks,
+                trials_per_point,
+                points,
+            })
+        })
+    }
+
+    fn simulate_mesh(
+        &self,
+        file_path: PathBuf,
+        scenario: MeshScenario,
+    ) -> BoxFuture<'_, CoordinatorResult<MeshSimulationResult>> {
+        Box::pin(async move {
+            let mesh = MeshSimulation::new(scenario)?;
+
+            let file_id = file_path.to_string_lossy().to_string();
+            let file_size = tokio::fs::metadata(&file_path).await?.len();
+            let sim_chunk_size = crate::chunk::ChunkManager::simulation_chunk_size(file_size);
+            let (manifest, chunks) = self
+                .chunk_manager()
+                .split_file_with_chunk_size(
+                    &file_path,
+                    file_id,
+                    Priority::Normal,
+                    sim_chunk_size,
+                    None,
+                )
+                .await?;
+
+            let report = mesh.run(&chunks, manifest.data_chunks).await;
+
+            let lost = (report.total_chunks - report.delivered_chunks) as u64;
+            self.sim_chunks_sent
+                .fetch_add(report.total_chunks as u64, Ordering::Relaxed);
+            self.sim_chunks_lost.fetch_add(lost, Ordering::Relaxed);
+            if report.recoverable {
+                self.sim_chunks_recovered
+                    .fetch_add(lost.min(manifest.parity_chunks as u64), Ordering::Relaxed);
+            }
+
+            let file_name = file_path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string();
+
+            Ok(MeshSimulationResult {
+                file_name,
+                file_size_bytes: manifest.total_size,
+                parity_chunks: manifest.parity_chunks as usize,
+                report,
+            })
+        })
+    }
+}
+
+/// Generator for loss rolls, fixed by `seed` if there is one
+fn loss_rng(seed: Option<u64>) -> StdRng {
+    match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    }
+}
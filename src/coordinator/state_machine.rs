@@ -1,13 +1,26 @@
 use crate::coordinator::error::{CoordinatorError, CoordinatorResult};
 use crate::coordinator::types::{TransferEvent, TransferState};
 use parking_lot::RwLock;
+use std::collections::HashSet;
 use std::sync::Arc;
-use tokio::sync::mpsc;
+use std::time::Instant;
+use tokio::sync::{mpsc, Mutex, OwnedMutexGuard};
+use tokio_util::sync::CancellationToken;
 
 pub struct TransferStateMachine {
     state: Arc<RwLock<TransferState>>,
     event_tx: mpsc::UnboundedSender<TransferEvent>,
     event_rx: Arc<RwLock<Option<mpsc::UnboundedReceiver<TransferEvent>>>>,
+    /// Chunks that failed and haven't since been delivered; at completion
+    /// the receiver has to rebuild these from parity
+    failed_chunks: Arc<RwLock<HashSet<u32>>>,
+    /// When the transfer first reached a terminal state
+    finished_at: Arc<RwLock<Option<Instant>>>,
+    /// Fired by [`TransferEvent::Cancel`] to interrupt sends in flight
+    cancel: CancellationToken,
+    /// Held by the worker sending this transfer, so a resume can't start a
+    /// second one while a paused worker is still winding down
+    worker: Arc<Mutex<()>>,
 }
 
 impl Default for TransferStateMachine {
@@ -24,6 +37,10 @@ impl TransferStateMachine {
             state: Arc::new(RwLock::new(TransferState::Idle)),
             event_tx,
             event_rx: Arc::new(RwLock::new(Some(event_rx))),
+            failed_chunks: Arc::new(RwLock::new(HashSet::new())),
+            finished_at: Arc::new(RwLock::new(None)),
+            cancel: CancellationToken::new(),
+            worker: Arc::new(Mutex::new(())),
         }
     }
 
@@ -41,8 +58,29 @@ impl TransferStateMachine {
 
     /// Transition state based on event
     pub fn transition(&self, event: TransferEvent) -> CoordinatorResult<TransferState> {
+        // Failing and cancelling are left alone so a transfer can always be
+        // brought to rest
+        #[cfg(feature = "fault-injection")]
+        if !matches!(
+            event,
+            TransferEvent::TransferFailed { .. } | TransferEvent::Cancel
+        ) {
+            crate::fault::check(crate::fault::FaultPoint::StateTransition)
+                .map_err(|fault| CoordinatorError::InvalidStateTransition(fault.to_string()))?;
+        }
+
         let mut state = self.state.write();
 
+        match &event {
+            TransferEvent::ChunkCompleted { chunk_number } => {
+                self.failed_chunks.write().remove(chunk_number);
+            }
+            TransferEvent::ChunkFailed { chunk_number, .. } => {
+                self.failed_chunks.write().insert(*chunk_number);
+            }
+            _ => {}
+        }
+
         let new_state = match (&*state, &event) {
             // Starting transfer
             (TransferState::Idle, TransferEvent::Start { .. }) => TransferState::Preparing,
@@ -71,6 +109,14 @@ impl TransferStateMachine {
                 reason: "User requested".into(),
             },
 
+            // Sends already in flight when the pause landed still finish
+            (
+                TransferState::Paused { reason },
+                TransferEvent::ChunkCompleted { .. } | TransferEvent::ChunkFailed { .. },
+            ) => TransferState::Paused {
+                reason: reason.clone(),
+            },
+
             // Resume transfer
             (TransferState::Paused { .. }, TransferEvent::Resume) => {
                 TransferState::Transferring { progress: 0.0 }
@@ -91,18 +137,59 @@ impl TransferStateMachine {
                 TransferState::Transferring { progress: 0.0 }
             }
 
-            // Complete transfer
-            (TransferState::Transferring { .. }, TransferEvent::TransferComplete) => {
-                TransferState::Completing
+            // Complete transfer; a pause after the last send, or a resume
+            // that finds every chunk delivered, leaves nothing to wait for
+            (
+                TransferState::Transferring { .. }
+                | TransferState::Paused { .. }
+                | TransferState::Preparing,
+                TransferEvent::TransferComplete,
+            ) => TransferState::Completing,
+
+            // Settling finds too little delivered, or it completes
+            (TransferState::Completing, TransferEvent::TransferFailed { error }) => {
+                TransferState::Failed {
+                    error: error.clone(),
+                }
             }
+            // Nothing is left to pause or resume once the worker is settling
+            (TransferState::Completing, event)
+                if !matches!(event, TransferEvent::Pause | TransferEvent::Resume) =>
+            {
+                self.completed_state()
+            }
+
+            // Remainder handed to a relay
+            (
+                TransferState::Transferring { .. }
+                | TransferState::Paused { .. }
+                | TransferState::PartiallyDelivered { .. },
+                TransferEvent::PartialDelivery {
+                    delivered_chunks,
+                    held_by_relay,
+                },
+            ) => TransferState::PartiallyDelivered {
+                delivered_chunks: *delivered_chunks,
+                held_by_relay: *held_by_relay,
+            },
 
-            (TransferState::Completing, _) => TransferState::Completed,
+            // Relay finished delivering the remainder
+            (TransferState::PartiallyDelivered { .. }, TransferEvent::TransferComplete) => {
+                self.completed_state()
+            }
 
             // Cancel transfer
-            (_, TransferEvent::Cancel) => TransferState::Failed {
+            (state, TransferEvent::Cancel) if !state.is_completed() => TransferState::Failed {
                 error: "Cancelled by user".into(),
             },
 
+            // Unrecoverable failure
+            (state, TransferEvent::TransferFailed { error }) if !state.is_terminal() => {
+                TransferState::Failed {
+                    error: error.clone(),
+                }
+            }
+
             // Invalid transition
             _ => {
                 return Err(CoordinatorError::InvalidStateTransition(format!(
@@ -112,10 +199,50 @@ impl TransferStateMachine {
             }
         };
 
+        if new_state.is_terminal() {
+            self.finished_at.write().get_or_insert_with(Instant::now);
+        }
+        if matches!(event, TransferEvent::Cancel) {
+            self.cancel.cancel();
+        }
+
         *state = new_state.clone();
         Ok(new_state)
     }
 
+    /// When the transfer finished, if it has
+    pub fn finished_at(&self) -> Option<Instant> {
+        *self.finished_at.read()
+    }
+
+    /// Completed, or completed with repairs if any chunk never got through
+    fn completed_state(&self) -> TransferState {
+        let repaired = self.failed_chunks.read().len() as u32;
+        if repaired == 0 {
+            TransferState::Completed
+        } else {
+            TransferState::CompletedWithRepairs {
+                repaired_chunks: repaired,
+            }
+        }
+    }
+
+    /// Token that fires once the transfer is cancelled
+    pub fn cancellation(&self) -> CancellationToken {
+        self.cancel.clone()
+    }
+
+    /// Wait for any earlier worker to stop, then claim the transfer for a
+    /// new one until the guard is dropped
+    pub(crate) async fn claim_worker(&self) -> OwnedMutexGuard<()> {
+        self.worker.clone().lock_owned().await
+    }
+
+    /// Chunks currently counted as failed
+    pub fn failed_chunk_count(&self) -> u32 {
+        self.failed_chunks.read().len() as u32
+    }
+
     /// Take event receiver (can only be called once)
     pub fn take_receiver(&self) -> Option<mpsc::UnboundedReceiver<TransferEvent>> {
         self.event_rx.write().take()
@@ -130,6 +257,10 @@ impl Clone for TransferStateMachine {
             state: self.state.clone(),
             event_tx,
             event_rx: Arc::new(RwLock::new(Some(event_rx))),
+            failed_chunks: self.failed_chunks.clone(),
+            finished_at: self.finished_at.clone(),
+            cancel: self.cancel.clone(),
+            worker: self.worker.clone(),
         }
     }
 }
@@ -197,6 +328,23 @@ mod tests {
         assert!(sm.current_state().is_active());
     }
 
+    #[test]
+    fn test_pause_racing_the_worker() {
+        let sm = start_transferring();
+        sm.transition(TransferEvent::Pause).unwrap();
+
+        // A chunk in flight lands after the pause
+        sm.transition(TransferEvent::ChunkCompleted { chunk_number: 3 })
+            .unwrap();
+        assert!(sm.current_state().is_paused());
+
+        // ...and was the last one
+        sm.transition(TransferEvent::TransferComplete).unwrap();
+        assert!(sm.transition(TransferEvent::Pause).is_err());
+        let state = sm.transition(TransferEvent::TransferComplete).unwrap();
+        assert_eq!(state, TransferState::Completed);
+    }
+
     #[test]
     fn test_cancel() {
         let sm = TransferStateMachine::new();
@@ -207,7 +355,10 @@ mod tests {
         })
         .unwrap();
 
+        let cancel = sm.cancellation();
+        assert!(!cancel.is_cancelled());
         sm.transition(TransferEvent::Cancel).unwrap();
+        assert!(cancel.is_cancelled());
 
         match sm.current_state() {
             TransferState::Failed { error } => {
@@ -251,4 +402,101 @@ mod tests {
         .unwrap();
         assert!(sm.current_state().is_active());
     }
+
+    fn start_transferring() -> TransferStateMachine {
+        let sm = TransferStateMachine::new();
+        sm.transition(TransferEvent::Start {
+            file_path: PathBuf::from("test.bin"),
+            priority: Priority::Normal,
+        })
+        .unwrap();
+        sm.transition(TransferEvent::ChunkCompleted { chunk_number: 0 })
+            .unwrap();
+        sm
+    }
+
+    #[test]
+    fn test_completion_with_repairs() {
+        let sm = start_transferring();
+
+        sm.transition(TransferEvent::ChunkFailed {
+            chunk_number: 3,
+            error: "timeout".into(),
+        })
+        .unwrap();
+        sm.transition(TransferEvent::ChunkFailed {
+            chunk_number: 4,
+            error: "timeout".into(),
+        })
+        .unwrap();
+        // A retried chunk that gets through isn't a repair
+        sm.transition(TransferEvent::ChunkCompleted { chunk_number: 4 })
+            .unwrap();
+
+        sm.transition(TransferEvent::TransferComplete).unwrap();
+        let state = sm.transition(TransferEvent::TransferComplete).unwrap();
+
+        assert_eq!(
+            state,
+            TransferState::CompletedWithRepairs { repaired_chunks: 1 }
+        );
+        assert!(state.is_terminal());
+        assert!(state.is_completed());
+        assert!(state.is_degraded());
+        assert!(!state.is_active());
+    }
+
+    #[test]
+    fn test_clean_completion() {
+        let sm = start_transferring();
+        sm.transition(TransferEvent::TransferComplete).unwrap();
+        let state = sm.transition(TransferEvent::TransferComplete).unwrap();
+
+        assert_eq!(state, TransferState::Completed);
+        assert!(!state.is_degraded());
+    }
+
+    #[test]
+    fn test_failure_while_completing() {
+        let sm = start_transferring();
+        sm.transition(TransferEvent::ChunkFailed {
+            chunk_number: 1,
+            error: "send failed".into(),
+        })
+        .unwrap();
+        sm.transition(TransferEvent::TransferComplete).unwrap();
+
+        let state = sm
+            .transition(TransferEvent::TransferFailed {
+                error: "too few chunks".into(),
+            })
+            .unwrap();
+        assert!(matches!(state, TransferState::Failed { .. }));
+    }
+
+    #[test]
+    fn test_partial_delivery_then_relay_completes() {
+        let sm = start_transferring();
+
+        let state = sm
+            .transition(TransferEvent::PartialDelivery {
+                delivered_chunks: 6,
+                held_by_relay: 4,
+            })
+            .unwrap();
+        assert!(state.is_partially_delivered());
+        assert!(state.is_degraded());
+        assert!(!state.is_terminal());
+        assert!(!state.is_active());
+
+        let state = sm.transition(TransferEvent::TransferComplete).unwrap();
+        assert_eq!(state, TransferState::Completed);
+
+        // Terminal states ignore failure reports
+        assert!(sm
+            .transition(TransferEvent::TransferFailed {
+                error: "late".into()
+            })
+            .is_err());
+    }
 }
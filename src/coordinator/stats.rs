@@ -0,0 +1,158 @@
+//! Uptime, path stats and health checks
+//!
+//! [`HealthTracker`] reads the components the coordinator sends through
+//! (queue, session store, transport) without owning any of them, so health
+//! can be checked while transfers run.
+
+use crate::coordinator::health::{
+    self, ComponentHealth, HealthPolicy, HealthReport, QueueProgress,
+};
+use crate::coordinator::transport::Transport;
+use crate::network::QuicPathStats;
+use crate::priority::PriorityQueue;
+use crate::session::SessionRepository;
+use futures::future::BoxFuture;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::time;
+
+/// Reports how the coordinator and the components it uses are doing
+pub trait StatsService: Send + Sync {
+    /// Get the most recent real QUIC path stats (from an actual transfer)
+    fn last_quic_stats(&self) -> QuicPathStats;
+
+    /// Note the path stats of a running transfer's connection
+    fn record_quic_stats(&self, stats: QuicPathStats);
+
+    /// Get uptime in seconds
+    fn uptime_seconds(&self) -> u64;
+
+    fn health_policy(&self) -> HealthPolicy;
+
+    fn set_health_policy(&self, policy: HealthPolicy);
+
+    /// Whether the process should keep running
+    fn liveness(&self) -> HealthReport;
+
+    /// Whether the process can take transfers
+    fn readiness(&self) -> BoxFuture<'_, HealthReport>;
+}
+
+/// [`StatsService`] checking the queue, session store, transport and disk
+pub struct HealthTracker {
+    queue: Arc<PriorityQueue>,
+    session_store: Arc<dyn SessionRepository>,
+    transport: Arc<dyn Transport>,
+
+    // Real QUIC path stats from the most recent transfer
+    last_quic_stats: parking_lot::RwLock<QuicPathStats>,
+
+    // Thresholds for liveness and readiness, and queue progress between checks
+    health_policy: parking_lot::RwLock<HealthPolicy>,
+    queue_progress: QueueProgress,
+
+    // Start time for uptime tracking
+    start_time: Instant,
+}
+
+impl HealthTracker {
+    pub(crate) fn new(
+        queue: Arc<PriorityQueue>,
+        session_store: Arc<dyn SessionRepository>,
+        transport: Arc<dyn Transport>,
+    ) -> Self {
+        Self {
+            queue,
+            session_store,
+            transport,
+            last_quic_stats: parking_lot::RwLock::new(QuicPathStats::default()),
+            health_policy: parking_lot::RwLock::new(HealthPolicy::default()),
+            queue_progress: QueueProgress::new(),
+            start_time: Instant::now(),
+        }
+    }
+
+    fn check_queue(&self) -> ComponentHealth {
+        let stats = self.queue.stats();
+        let pending = stats.total_pending();
+        let stalled = self
+            .queue_progress
+            .stalled_for(stats.total_processed, pending);
+        let limit = self.health_policy.read().queue_stall;
+        if stalled >= limit {
+            ComponentHealth::down(
+                health::QUEUE,
+                format!(
+                    "{pending} chunks queued, none dequeued for {}s",
+                    stalled.as_secs()
+                ),
+            )
+        } else if stalled >= limit / 2 {
+            ComponentHealth::degraded(
+                health::QUEUE,
+                format!(
+                    "{pending} chunks queued, none dequeued for {}s",
+                    stalled.as_secs()
+                ),
+            )
+        } else {
+            ComponentHealth::up(health::QUEUE)
+        }
+    }
+}
+
+impl StatsService for HealthTracker {
+    fn last_quic_stats(&self) -> QuicPathStats {
+        self.last_quic_stats.read().clone()
+    }
+
+    fn record_quic_stats(&self, stats: QuicPathStats) {
+        *self.last_quic_stats.write() = stats;
+    }
+
+    fn uptime_seconds(&self) -> u64 {
+        self.start_time.elapsed().as_secs()
+    }
+
+    fn health_policy(&self) -> HealthPolicy {
+        self.health_policy.read().clone()
+    }
+
+    fn set_health_policy(&self, policy: HealthPolicy) {
+        *self.health_policy.write() = policy;
+    }
+
+    /// Fails only when the send queue holds chunks but has stopped draining
+    fn liveness(&self) -> HealthReport {
+        HealthReport::new(vec![self.check_queue()], self.uptime_seconds())
+    }
+
+    /// Needs the queue draining, the session database answering, the
+    /// transport bound and disk to write to
+    fn readiness(&self) -> BoxFuture<'_, HealthReport> {
+        Box::pin(async move {
+            let policy = self.health_policy();
+            let session_db = match time::timeout(policy.db_timeout, self.session_store.ping()).await
+            {
+                Ok(Ok(())) => ComponentHealth::up(health::SESSION_DB),
+                Ok(Err(e)) => ComponentHealth::down(health::SESSION_DB, e.to_string()),
+                Err(_) => ComponentHealth::down(
+                    health::SESSION_DB,
+                    format!("no answer within {:?}", policy.db_timeout),
+                ),
+            };
+            let endpoint = match self.transport.local_addr() {
+                Ok(addr) => ComponentHealth {
+                    detail: Some(format!("bound to {addr}")),
+                    ..ComponentHealth::up(health::QUIC_ENDPOINT)
+                },
+                Err(e) => ComponentHealth::down(health::QUIC_ENDPOINT, e.to_string()),
+            };
+            let disk = health::check_disk(&policy.disk_paths, policy.min_free_disk_bytes);
+            HealthReport::new(
+                vec![session_db, endpoint, self.check_queue(), disk],
+                self.uptime_seconds(),
+            )
+        })
+    }
+}
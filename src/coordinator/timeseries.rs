@@ -0,0 +1,212 @@
+//! Throughput-over-time samples of running transfers
+//!
+//! Each running transfer keeps its latest samples in a ring buffer. When its
+//! worker stops (finished, failed, paused or cancelled) the buffer is saved
+//! with the session and dropped from memory; a resumed transfer carries on
+//! from the saved samples.
+
+use crate::coordinator::transport::TransportLink;
+use crate::network::QuicPathStats;
+use crate::session::ProgressSample;
+use parking_lot::Mutex;
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+/// Time between samples of a running transfer
+pub const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Samples kept per transfer; older ones are dropped first
+pub const MAX_SAMPLES: usize = 3600;
+
+/// One transfer's samples and the readings the next sample is measured from
+#[derive(Debug)]
+struct Series {
+    samples: VecDeque<ProgressSample>,
+    last_at: Instant,
+    last_bytes: u64,
+    /// Packets sent and lost on the connection at the last sample
+    last_packets: Option<(u64, u64)>,
+}
+
+/// Samples of every running transfer
+#[derive(Debug, Default)]
+pub(crate) struct ProgressSampler {
+    by_session: Mutex<HashMap<String, Series>>,
+}
+
+impl ProgressSampler {
+    /// Start sampling a transfer that has sent `bytes_transferred` so far,
+    /// continuing from samples saved by an earlier run
+    pub(crate) fn start(
+        &self,
+        session_id: &str,
+        saved: Vec<ProgressSample>,
+        bytes_transferred: u64,
+    ) {
+        let mut samples = VecDeque::from(saved);
+        samples.drain(..samples.len().saturating_sub(MAX_SAMPLES));
+        self.by_session.lock().insert(
+            session_id.to_string(),
+            Series {
+                samples,
+                last_at: Instant::now(),
+                last_bytes: bytes_transferred,
+                last_packets: None,
+            },
+        );
+    }
+
+    /// Record a sample if [`SAMPLE_INTERVAL`] has passed since the last one
+    pub(crate) fn sample(
+        &self,
+        session_id: &str,
+        bytes_transferred: u64,
+        link: Option<&dyn TransportLink>,
+    ) {
+        self.sample_at(session_id, Instant::now(), bytes_transferred, false, || {
+            link.map(|link| link.path_stats())
+        });
+    }
+
+    /// Record a closing sample however recent the last one is
+    pub(crate) fn sample_final(
+        &self,
+        session_id: &str,
+        bytes_transferred: u64,
+        link: Option<&dyn TransportLink>,
+    ) {
+        self.sample_at(session_id, Instant::now(), bytes_transferred, true, || {
+            link.map(|link| link.path_stats())
+        });
+    }
+
+    fn sample_at(
+        &self,
+        session_id: &str,
+        now: Instant,
+        bytes_transferred: u64,
+        force: bool,
+        path: impl FnOnce() -> Option<QuicPathStats>,
+    ) {
+        let mut by_session = self.by_session.lock();
+        let Some(series) = by_session.get_mut(session_id) else {
+            return;
+        };
+        let elapsed = now.saturating_duration_since(series.last_at);
+        if elapsed < SAMPLE_INTERVAL && !(force && elapsed > Duration::ZERO) {
+            return;
+        }
+
+        let bytes = bytes_transferred.saturating_sub(series.last_bytes);
+        let speed_bps = (bytes as f64 / elapsed.as_secs_f64()) as u64;
+        let packets = path().map(|stats| (stats.sent_packets, stats.lost_packets));
+        let loss_rate = match (series.last_packets, packets) {
+            (Some((sent_before, lost_before)), Some((sent, lost))) if sent > sent_before => {
+                lost.saturating_sub(lost_before) as f64 / (sent - sent_before) as f64
+            }
+            (None, Some((sent, lost))) if sent > 0 => lost as f64 / sent as f64,
+            _ => 0.0,
+        };
+
+        if series.samples.len() == MAX_SAMPLES {
+            series.samples.pop_front();
+        }
+        series.samples.push_back(ProgressSample {
+            timestamp_ms: chrono::Utc::now().timestamp_millis(),
+            bytes_transferred,
+            speed_bps,
+            loss_rate,
+        });
+        series.last_at = now;
+        series.last_bytes = bytes_transferred;
+        series.last_packets = packets.or(series.last_packets);
+    }
+
+    /// Samples of a running transfer, oldest first
+    pub(crate) fn samples(&self, session_id: &str) -> Option<Vec<ProgressSample>> {
+        self.by_session
+            .lock()
+            .get(session_id)
+            .map(|series| series.samples.iter().copied().collect())
+    }
+
+    /// Stop sampling a transfer
+    pub(crate) fn forget(&self, session_id: &str) {
+        self.by_session.lock().remove(session_id);
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.by_session.lock().len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(sent_packets: u64, lost_packets: u64) -> Option<QuicPathStats> {
+        Some(QuicPathStats {
+            sent_packets,
+            lost_packets,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_samples_throughput_and_loss_per_interval() {
+        let sampler = ProgressSampler::default();
+        sampler.start("s1", Vec::new(), 1000);
+        let start = sampler.by_session.lock()["s1"].last_at;
+        let at = |secs: u64| start + Duration::from_secs(secs);
+
+        // Too soon after the start
+        sampler.sample_at("s1", start + SAMPLE_INTERVAL / 2, 1500, false, || {
+            path(10, 0)
+        });
+        assert!(sampler.samples("s1").unwrap().is_empty());
+
+        sampler.sample_at("s1", at(2), 5000, false, || path(100, 10));
+        sampler.sample_at("s1", at(3), 6000, false, || path(200, 15));
+        sampler.sample_at("s1", at(3) + Duration::from_millis(500), 6500, true, || {
+            None
+        });
+
+        let samples = sampler.samples("s1").unwrap();
+        assert_eq!(samples.len(), 3);
+        assert_eq!(samples[0].speed_bps, 2000);
+        assert_eq!(samples[0].loss_rate, 0.1);
+        assert_eq!(samples[1].speed_bps, 1000);
+        assert_eq!(samples[1].loss_rate, 0.05);
+        assert_eq!(samples[2].bytes_transferred, 6500);
+        assert_eq!(samples[2].speed_bps, 1000);
+
+        // Unknown sessions are ignored
+        sampler.sample_at("s2", at(5), 10, false, || path(1, 1));
+        assert!(sampler.samples("s2").is_none());
+        sampler.forget("s1");
+        assert_eq!(sampler.len(), 0);
+    }
+
+    #[test]
+    fn test_ring_keeps_latest_samples() {
+        let sampler = ProgressSampler::default();
+        let saved = (0..MAX_SAMPLES as i64 + 10)
+            .map(|n| ProgressSample {
+                timestamp_ms: n,
+                bytes_transferred: n as u64,
+                speed_bps: 0,
+                loss_rate: 0.0,
+            })
+            .collect();
+        sampler.start("s1", saved, 0);
+        let samples = sampler.samples("s1").unwrap();
+        assert_eq!(samples.len(), MAX_SAMPLES);
+        assert_eq!(samples[0].timestamp_ms, 10);
+
+        let start = sampler.by_session.lock()["s1"].last_at;
+        sampler.sample_at("s1", start + SAMPLE_INTERVAL, 1, false, || None);
+        let samples = sampler.samples("s1").unwrap();
+        assert_eq!(samples.len(), MAX_SAMPLES);
+        assert_eq!(samples[0].timestamp_ms, 11);
+    }
+}
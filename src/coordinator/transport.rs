@@ -0,0 +1,368 @@
+//! What the coordinator sends over
+//!
+//! A transfer needs little from the network: a connection to its receiver,
+//! a way to push chunks down it and the feedback the receiver sends back.
+//! [`Transport`] and [`TransportLink`] are that much of [`QuicTransport`],
+//! so a coordinator can be wired to another transport, or to an in-memory
+//! one in tests, without forking the send path.
+//!
+//! Link probes and standby replication speak QUIC directly and are only
+//! available when [`Transport::quic`] returns the endpoint.
+
+use crate::chunk::Chunk;
+use crate::network::quic_transport::STREAM_CANCELLED;
+use crate::network::{
+    FileOffer, NetworkError, NetworkResult, NetworkStats, OfferReply, QuicPathStats, QuicTransport,
+    ReceiverFeedback, ReceiverStats, RepairReply, RepairRequest,
+};
+use futures::future::BoxFuture;
+use quinn::Connection;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio_util::sync::CancellationToken;
+
+/// Sends the answer to a repair request taken from a [`TransportLink`]
+pub type RepairAnswer =
+    Box<dyn FnOnce(RepairReply) -> BoxFuture<'static, NetworkResult<()>> + Send>;
+
+/// Opens links to receivers and accepts links from them
+pub trait Transport: Send + Sync {
+    /// Connect to `remote`, through `local` if given
+    fn connect(
+        self: Arc<Self>,
+        remote: SocketAddr,
+        local: Option<SocketAddr>,
+    ) -> BoxFuture<'static, NetworkResult<Arc<dyn TransportLink>>>;
+
+    /// Wait for the next peer to connect; fails with
+    /// [`NetworkError::ConnectionClosed`] once the transport is closed
+    fn accept(self: Arc<Self>) -> BoxFuture<'static, NetworkResult<Arc<dyn TransportLink>>>;
+
+    fn local_addr(&self) -> NetworkResult<SocketAddr>;
+
+    /// Largest chunk a receiver on this transport takes
+    fn max_chunk_size(&self) -> usize;
+
+    fn stats(&self) -> NetworkStats;
+
+    /// Links currently open
+    fn connection_count(&self) -> usize;
+
+    /// Check that `local` can carry traffic to `remote` before a transfer
+    /// pinned to it starts
+    fn validate_local_addr(
+        &self,
+        _local: SocketAddr,
+        _remote: Option<SocketAddr>,
+    ) -> NetworkResult<()> {
+        Ok(())
+    }
+
+    /// Latest stats report from every receiver that has sent one
+    fn receiver_reports(&self) -> Vec<(SocketAddr, ReceiverStats)> {
+        Vec::new()
+    }
+
+    /// The QUIC endpoint underneath, if this is one
+    fn quic(self: Arc<Self>) -> Option<Arc<QuicTransport>> {
+        None
+    }
+}
+
+/// One open connection to a receiver
+pub trait TransportLink: Send + Sync {
+    fn remote_address(&self) -> SocketAddr;
+
+    /// Path stats read live from the connection
+    fn path_stats(&self) -> QuicPathStats {
+        QuicPathStats::default()
+    }
+
+    /// Note that `session_id` sends over this link
+    fn label(&self, _session_id: &str) {}
+
+    /// Ask the receiver whether it wants the file described by `offer`
+    fn offer_file<'a>(&'a self, offer: &'a FileOffer) -> BoxFuture<'a, NetworkResult<OfferReply>>;
+
+    /// Send `chunk`, retrying up to `retries` times, until `cancel` fires
+    fn send_chunk<'a>(
+        &'a self,
+        chunk: &'a Chunk,
+        retries: u32,
+        cancel: &'a CancellationToken,
+    ) -> BoxFuture<'a, NetworkResult<()>>;
+
+    /// Wait for the next NACK, group report or stats report from the receiver
+    fn receive_feedback(&self) -> BoxFuture<'_, NetworkResult<ReceiverFeedback>>;
+
+    /// Latest stats report from the receiver on this link
+    fn receiver_stats(&self) -> Option<ReceiverStats> {
+        None
+    }
+
+    /// Wait for the receiver's next repair request
+    ///
+    /// Links that can't carry repairs fail at once, which ends serving them.
+    fn accept_repair(&self) -> BoxFuture<'_, NetworkResult<(RepairRequest, RepairAnswer)>> {
+        Box::pin(async {
+            Err(NetworkError::ConnectionClosed(
+                "link does not carry repairs".into(),
+            ))
+        })
+    }
+
+    /// Close the link, resetting anything still in flight
+    fn cancel(&self);
+}
+
+impl Transport for QuicTransport {
+    fn connect(
+        self: Arc<Self>,
+        remote: SocketAddr,
+        local: Option<SocketAddr>,
+    ) -> BoxFuture<'static, NetworkResult<Arc<dyn TransportLink>>> {
+        Box::pin(async move {
+            let conn = self.connect_from(remote, local).await?;
+            Ok(QuicConnection::link(self, conn))
+        })
+    }
+
+    fn accept(self: Arc<Self>) -> BoxFuture<'static, NetworkResult<Arc<dyn TransportLink>>> {
+        Box::pin(async move {
+            let conn = QuicTransport::accept(&self).await?;
+            Ok(QuicConnection::link(self, conn))
+        })
+    }
+
+    fn local_addr(&self) -> NetworkResult<SocketAddr> {
+        QuicTransport::local_addr(self)
+    }
+
+    fn max_chunk_size(&self) -> usize {
+        QuicTransport::max_chunk_size(self)
+    }
+
+    fn stats(&self) -> NetworkStats {
+        QuicTransport::stats(self)
+    }
+
+    fn connection_count(&self) -> usize {
+        QuicTransport::connection_count(self)
+    }
+
+    fn validate_local_addr(
+        &self,
+        local: SocketAddr,
+        remote: Option<SocketAddr>,
+    ) -> NetworkResult<()> {
+        QuicTransport::validate_local_addr(local, remote)
+    }
+
+    fn receiver_reports(&self) -> Vec<(SocketAddr, ReceiverStats)> {
+        QuicTransport::receiver_reports(self)
+    }
+
+    fn quic(self: Arc<Self>) -> Option<Arc<QuicTransport>> {
+        Some(self)
+    }
+}
+
+/// [`TransportLink`] over one QUIC connection
+struct QuicConnection {
+    transport: Arc<QuicTransport>,
+    conn: Connection,
+}
+
+impl QuicConnection {
+    fn link(transport: Arc<QuicTransport>, conn: Connection) -> Arc<dyn TransportLink> {
+        Arc::new(Self { transport, conn })
+    }
+}
+
+impl TransportLink for QuicConnection {
+    fn remote_address(&self) -> SocketAddr {
+        self.conn.remote_address()
+    }
+
+    fn path_stats(&self) -> QuicPathStats {
+        QuicTransport::connection_stats(&self.conn)
+    }
+
+    fn label(&self, session_id: &str) {
+        self.transport.label_connection(&self.conn, session_id);
+    }
+
+    fn offer_file<'a>(&'a self, offer: &'a FileOffer) -> BoxFuture<'a, NetworkResult<OfferReply>> {
+        Box::pin(self.transport.offer_file(&self.conn, offer))
+    }
+
+    fn send_chunk<'a>(
+        &'a self,
+        chunk: &'a Chunk,
+        retries: u32,
+        cancel: &'a CancellationToken,
+    ) -> BoxFuture<'a, NetworkResult<()>> {
+        Box::pin(
+            self.transport
+                .send_with_retry_until(&self.conn, chunk, retries, cancel),
+        )
+    }
+
+    fn receive_feedback(&self) -> BoxFuture<'_, NetworkResult<ReceiverFeedback>> {
+        Box::pin(self.transport.receive_feedback(&self.conn))
+    }
+
+    fn receiver_stats(&self) -> Option<ReceiverStats> {
+        self.transport.receiver_stats(self.conn.remote_address())
+    }
+
+    fn accept_repair(&self) -> BoxFuture<'_, NetworkResult<(RepairRequest, RepairAnswer)>> {
+        Box::pin(async move {
+            let (request, reply) = QuicTransport::accept_repair(&self.conn).await?;
+            let codec = request.codec();
+            let answer: RepairAnswer = Box::new(move |answer| {
+                Box::pin(async move { QuicTransport::answer_repair(reply, codec, &answer).await })
+            });
+            Ok((request, answer))
+        })
+    }
+
+    fn cancel(&self) {
+        self.conn
+            .close(STREAM_CANCELLED.into(), b"transfer cancelled");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::{ChunkManager, Priority};
+    use crate::coordinator::{CoordinatorEvent, TransferCoordinator};
+    use crate::priority::PriorityQueue;
+    use crate::session::MemoryRepository;
+    use bytes::Bytes;
+    use futures::StreamExt;
+    use parking_lot::Mutex;
+    use std::time::Duration;
+
+    /// Takes every offer and keeps every chunk sent over it
+    #[derive(Default)]
+    struct MemoryTransport {
+        sent: Arc<Mutex<Vec<Chunk>>>,
+    }
+
+    struct MemoryLink {
+        remote: SocketAddr,
+        sent: Arc<Mutex<Vec<Chunk>>>,
+    }
+
+    impl Transport for MemoryTransport {
+        fn connect(
+            self: Arc<Self>,
+            remote: SocketAddr,
+            _local: Option<SocketAddr>,
+        ) -> BoxFuture<'static, NetworkResult<Arc<dyn TransportLink>>> {
+            Box::pin(async move {
+                let link: Arc<dyn TransportLink> = Arc::new(MemoryLink {
+                    remote,
+                    sent: self.sent.clone(),
+                });
+                Ok(link)
+            })
+        }
+
+        fn accept(self: Arc<Self>) -> BoxFuture<'static, NetworkResult<Arc<dyn TransportLink>>> {
+            Box::pin(futures::future::pending())
+        }
+
+        fn local_addr(&self) -> NetworkResult<SocketAddr> {
+            Ok(SocketAddr::from(([127, 0, 0, 1], 0)))
+        }
+
+        fn max_chunk_size(&self) -> usize {
+            1024 * 1024
+        }
+
+        fn stats(&self) -> NetworkStats {
+            NetworkStats::default()
+        }
+
+        fn connection_count(&self) -> usize {
+            0
+        }
+    }
+
+    impl TransportLink for MemoryLink {
+        fn remote_address(&self) -> SocketAddr {
+            self.remote
+        }
+
+        fn offer_file<'a>(
+            &'a self,
+            _offer: &'a FileOffer,
+        ) -> BoxFuture<'a, NetworkResult<OfferReply>> {
+            Box::pin(async { Ok(OfferReply::Send) })
+        }
+
+        fn send_chunk<'a>(
+            &'a self,
+            chunk: &'a Chunk,
+            _retries: u32,
+            _cancel: &'a CancellationToken,
+        ) -> BoxFuture<'a, NetworkResult<()>> {
+            self.sent.lock().push(chunk.clone());
+            Box::pin(async { Ok(()) })
+        }
+
+        fn receive_feedback(&self) -> BoxFuture<'_, NetworkResult<ReceiverFeedback>> {
+            Box::pin(futures::future::pending())
+        }
+
+        fn cancel(&self) {}
+    }
+
+    #[tokio::test]
+    async fn test_coordinator_sends_over_a_custom_transport() {
+        let transport = MemoryTransport::default();
+        let sent = transport.sent.clone();
+        let coordinator = TransferCoordinator::new(
+            ChunkManager::new(1024, 4, 2).unwrap(),
+            transport,
+            PriorityQueue::new(1_000),
+            MemoryRepository::new(),
+        );
+        assert!(coordinator.quic_transport().is_none());
+
+        let mut events = Box::pin(coordinator.subscribe());
+        let session_id = coordinator
+            .send_bytes(
+                "data.bin",
+                Bytes::from(vec![3u8; 4096]),
+                Priority::High,
+                Some("127.0.0.1:5001".parse().unwrap()),
+                Default::default(),
+            )
+            .await
+            .unwrap();
+
+        let completed = tokio::time::timeout(Duration::from_secs(10), async {
+            while let Some(event) = events.next().await {
+                if let CoordinatorEvent::TransferCompleted { session_id: id, .. } = event {
+                    return id;
+                }
+            }
+            panic!("event stream ended");
+        })
+        .await
+        .unwrap();
+        assert_eq!(completed, session_id);
+
+        let mut seqs: Vec<u32> = sent
+            .lock()
+            .iter()
+            .map(|chunk| chunk.metadata.sequence_number)
+            .collect();
+        seqs.sort_unstable();
+        assert_eq!(seqs, (0..6).collect::<Vec<_>>());
+    }
+}
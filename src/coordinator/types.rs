@@ -1,15 +1,40 @@
+use crate::chunk::{Priority, SourceWatch};
+use crate::coordinator::error::{CoordinatorError, CoordinatorResult};
+use crate::coordinator::retransmit::RetransmitPolicy;
+use crate::coordinator::window::DEFAULT_SESSION_WINDOW;
+use crate::relay::{ExpiredNotice, ExpiryReason};
+use crate::session::{SessionState, SessionStatus};
+use bytes::Bytes;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum TransferState {
     Idle,
     Preparing,
-    Transferring { progress: f32 },
-    Paused { reason: String },
+    Transferring {
+        progress: f32,
+    },
+    Paused {
+        reason: String,
+    },
     Completing,
+    /// Every chunk reached the receiver
     Completed,
-    Failed { error: String },
+    /// Delivered, but the receiver needs parity to rebuild `repaired_chunks`
+    CompletedWithRepairs {
+        repaired_chunks: u32,
+    },
+    /// Part of the file was delivered; a relay holds the rest for later delivery
+    PartiallyDelivered {
+        delivered_chunks: u32,
+        held_by_relay: u32,
+    },
+    Failed {
+        error: String,
+    },
 }
 
 impl TransferState {
@@ -21,12 +46,294 @@ impl TransferState {
         matches!(self, TransferState::Paused { .. })
     }
 
+    /// No further events change this state.
+    ///
+    /// `PartiallyDelivered` is not terminal: the relay may still finish it.
     pub fn is_terminal(&self) -> bool {
         matches!(
             self,
-            TransferState::Completed | TransferState::Failed { .. }
+            TransferState::Completed
+                | TransferState::CompletedWithRepairs { .. }
+                | TransferState::Failed { .. }
+        )
+    }
+
+    /// The file reached the receiver in full (possibly via FEC)
+    pub fn is_completed(&self) -> bool {
+        matches!(
+            self,
+            TransferState::Completed | TransferState::CompletedWithRepairs { .. }
+        )
+    }
+
+    /// Delivery succeeded or is progressing, but not cleanly
+    pub fn is_degraded(&self) -> bool {
+        matches!(
+            self,
+            TransferState::CompletedWithRepairs { .. } | TransferState::PartiallyDelivered { .. }
         )
     }
+
+    /// The sender has handed the remainder to a relay
+    pub fn is_partially_delivered(&self) -> bool {
+        matches!(self, TransferState::PartiallyDelivered { .. })
+    }
+
+    /// Short name used by the API
+    pub fn name(&self) -> &'static str {
+        match self {
+            TransferState::Idle => "Idle",
+            TransferState::Preparing => "Preparing",
+            TransferState::Transferring { .. } => "Transferring",
+            TransferState::Paused { .. } => "Paused",
+            TransferState::Completing => "Completing",
+            TransferState::Completed => "Completed",
+            TransferState::CompletedWithRepairs { .. } => "CompletedWithRepairs",
+            TransferState::PartiallyDelivered { .. } => "PartiallyDelivered",
+            TransferState::Failed { .. } => "Failed",
+        }
+    }
+}
+
+impl From<&SessionStatus> for TransferState {
+    /// Best reconstruction of a state from the persisted session status
+    fn from(status: &SessionStatus) -> Self {
+        match status {
+            SessionStatus::Initializing => TransferState::Preparing,
+            SessionStatus::Active => TransferState::Transferring { progress: 0.0 },
+            SessionStatus::Paused => TransferState::Paused {
+                reason: "Paused".into(),
+            },
+            SessionStatus::Completed => TransferState::Completed,
+            SessionStatus::CompletedWithRepairs { repaired_chunks } => {
+                TransferState::CompletedWithRepairs {
+                    repaired_chunks: *repaired_chunks,
+                }
+            }
+            SessionStatus::PartiallyDelivered {
+                delivered_chunks,
+                held_by_relay,
+            } => TransferState::PartiallyDelivered {
+                delivered_chunks: *delivered_chunks,
+                held_by_relay: *held_by_relay,
+            },
+            SessionStatus::Failed(error) => TransferState::Failed {
+                error: error.clone(),
+            },
+        }
+    }
+}
+
+/// How long finished transfers stay in the coordinator's in-memory list
+///
+/// Evicted transfers remain queryable from the session store.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    /// Finished transfers kept in memory; the oldest are evicted first
+    pub max_entries: usize,
+    /// How long a transfer is kept after it finishes
+    pub max_age: Duration,
+    /// How often the background task evicts
+    pub sweep_interval: Duration,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            max_entries: 1000,
+            max_age: Duration::from_secs(60 * 60),
+            sweep_interval: Duration::from_secs(60),
+        }
+    }
+}
+
+/// When and how the background task maintains the session database
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MaintenancePolicy {
+    pub enabled: bool,
+    /// Time between passes
+    pub interval: Duration,
+    /// Most free pages returned to the file system per pass (0 = all)
+    pub vacuum_pages: u32,
+    /// Refresh the query planner's statistics on each pass
+    pub analyze: bool,
+}
+
+impl Default for MaintenancePolicy {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval: Duration::from_secs(60 * 60),
+            vacuum_pages: 0,
+            analyze: true,
+        }
+    }
+}
+
+/// How long a transfer waits to connect to its receiver
+///
+/// A zero timeout waits as long as the transport does. A Critical transfer
+/// that times out is handed to the fallback relay when there is one and
+/// `relay_fallback` is on; any other transfer that times out fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConnectPolicy {
+    pub critical_timeout: Duration,
+    pub high_timeout: Duration,
+    /// Normal and the levels past it
+    pub normal_timeout: Duration,
+    pub relay_fallback: bool,
+}
+
+impl Default for ConnectPolicy {
+    fn default() -> Self {
+        Self {
+            critical_timeout: Duration::ZERO,
+            high_timeout: Duration::ZERO,
+            normal_timeout: Duration::ZERO,
+            relay_fallback: true,
+        }
+    }
+}
+
+impl ConnectPolicy {
+    /// Connect timeout for `priority`, or `None` when it has none
+    pub fn timeout(&self, priority: Priority) -> Option<Duration> {
+        let timeout = match priority.class() {
+            Priority::Critical => self.critical_timeout,
+            Priority::High => self.high_timeout,
+            Priority::Normal | Priority::Level(_) => self.normal_timeout,
+        };
+        (!timeout.is_zero()).then_some(timeout)
+    }
+}
+
+/// Which running transfers keep the same file from being sent again
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicatePolicy {
+    /// Any transfer of the file, whatever its receiver
+    PerFile,
+    /// A transfer of the file to the same receiver
+    #[default]
+    PerReceiver,
+    /// None; a file can be sent any number of times at once
+    Allow,
+}
+
+impl std::str::FromStr for DuplicatePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "per_file" => Ok(Self::PerFile),
+            "per_receiver" => Ok(Self::PerReceiver),
+            "allow" => Ok(Self::Allow),
+            other => Err(format!(
+                "unknown duplicate policy {other:?} (expected per_file, per_receiver or allow)"
+            )),
+        }
+    }
+}
+
+/// Settings a send service starts transfers with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SendPolicy {
+    /// Which running transfers refuse another of the same file
+    pub duplicates: DuplicatePolicy,
+    /// Bounds on finished transfers kept in memory
+    pub retention: RetentionPolicy,
+    /// Chunks each transfer may have in the shared queue at once (0 = all)
+    pub session_window: usize,
+    /// How files are re-checked and locked while they are sent
+    pub source_watch: SourceWatch,
+    /// Budget for resending shards the receiver reports lost
+    pub retransmit: RetransmitPolicy,
+    pub connect: ConnectPolicy,
+    /// Most bytes `send_stream` and `resume_stream` read into memory
+    /// (0 = unlimited)
+    pub max_stream_bytes: u64,
+}
+
+impl Default for SendPolicy {
+    fn default() -> Self {
+        Self {
+            duplicates: DuplicatePolicy::default(),
+            retention: RetentionPolicy::default(),
+            session_window: DEFAULT_SESSION_WINDOW,
+            source_watch: SourceWatch::default(),
+            retransmit: RetransmitPolicy::default(),
+            connect: ConnectPolicy::default(),
+            max_stream_bytes: DEFAULT_MAX_STREAM_BYTES,
+        }
+    }
+}
+
+/// Default for [`SendPolicy::max_stream_bytes`]
+pub const DEFAULT_MAX_STREAM_BYTES: u64 = 256 * 1024 * 1024;
+
+/// Prefix of the file id of a transfer sent from memory
+pub const MEMORY_FILE_ID_PREFIX: &str = "memory:";
+
+/// Where a transfer's data comes from
+#[derive(Clone, PartialEq, Eq)]
+pub enum TransferSource {
+    /// A file on disk, read again when the transfer resumes
+    File(PathBuf),
+    /// Data produced in memory, sent as a file called `name`
+    ///
+    /// Nothing is kept after the transfer stops, so resuming it needs the
+    /// same data supplied again.
+    Memory { name: String, data: Bytes },
+}
+
+impl TransferSource {
+    /// Source for data sent as a file called `name`
+    ///
+    /// The receiver only sees the last path component, as with files on disk.
+    pub(crate) fn memory(name: &str, data: Bytes) -> CoordinatorResult<Self> {
+        match Path::new(name).file_name() {
+            Some(file_name) if file_name == name => Ok(TransferSource::Memory {
+                name: name.to_string(),
+                data,
+            }),
+            _ => Err(CoordinatorError::InvalidSource(format!(
+                "{name:?} is not a file name"
+            ))),
+        }
+    }
+
+    /// Id the transfer's file is tracked by: its path, or the name
+    /// prefixed with [`MEMORY_FILE_ID_PREFIX`]
+    ///
+    /// A transfer started while another of the same source runs is tracked
+    /// as `<id>#2`, `<id>#3` and so on instead.
+    pub fn file_id(&self) -> String {
+        match self {
+            TransferSource::File(path) => path.to_string_lossy().to_string(),
+            TransferSource::Memory { name, .. } => format!("{MEMORY_FILE_ID_PREFIX}{name}"),
+        }
+    }
+
+    /// Path recorded with the session; memory sources have none
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            TransferSource::File(path) => Some(path),
+            TransferSource::Memory { .. } => None,
+        }
+    }
+}
+
+impl fmt::Debug for TransferSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TransferSource::File(path) => f.debug_tuple("File").field(path).finish(),
+            TransferSource::Memory { name, data } => f
+                .debug_struct("Memory")
+                .field("name", name)
+                .field("len", &data.len())
+                .finish(),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -52,6 +359,15 @@ pub enum TransferEvent {
         path_id: String,
     },
     TransferComplete,
+    /// A relay accepted the chunks the receiver hasn't got yet
+    PartialDelivery {
+        delivered_chunks: u32,
+        held_by_relay: u32,
+    },
+    /// The transfer can't complete (e.g. too few chunks to reconstruct)
+    TransferFailed {
+        error: String,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -64,4 +380,82 @@ pub struct TransferProgress {
     pub progress_percent: f32,
     pub status: crate::session::SessionStatus,
     pub current_speed_bps: u64,
+    /// Chunks relays reported dropping before delivery
+    #[serde(default)]
+    pub relay_expired_chunks: u32,
+    /// Of those, chunks not yet taken for a resend
+    #[serde(default)]
+    pub pending_relay_resends: u32,
+    /// Sequence numbers not yet delivered after failing; once the transfer
+    /// has settled, the ones that ran out of retries
+    #[serde(default)]
+    pub failed_chunks: Vec<u32>,
+}
+
+impl TransferProgress {
+    /// Progress recorded in `session`, with `pending_relay_resends` of its
+    /// relay-expired chunks still waiting to be resent
+    pub fn of(session: &SessionState, pending_relay_resends: u32) -> Self {
+        let mut failed_chunks: Vec<u32> = session.failed_chunks.iter().copied().collect();
+        failed_chunks.sort_unstable();
+
+        Self {
+            session_id: session.session_id.clone(),
+            completed_chunks: session.completed_chunks.len() as u32,
+            total_chunks: session.manifest.total_chunks,
+            bytes_transferred: session.metrics.bytes_transferred,
+            total_bytes: session.manifest.total_size,
+            progress_percent: session.progress_percent(),
+            status: session.status.clone(),
+            current_speed_bps: session.current_speed_bps(),
+            relay_expired_chunks: session.metrics.relay_expired_chunks,
+            pending_relay_resends,
+            failed_chunks,
+        }
+    }
+}
+
+/// How to resend a chunk a relay gave up on
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "via", rename_all = "snake_case")]
+pub enum ResendRoute {
+    /// Straight to the receiver
+    Direct,
+    /// Through relays other than the ones in `avoid`
+    Alternate { avoid: Vec<String> },
+}
+
+impl ResendRoute {
+    /// Route for a chunk dropped as described by `notice`
+    ///
+    /// A chunk that ran out of hops won't do better on another relay path;
+    /// one that sat on a relay too long, couldn't be forwarded from it, was
+    /// evicted by its quota or arrived there corrupted should avoid that
+    /// relay.
+    pub fn for_notice(notice: &ExpiredNotice) -> Self {
+        match notice.reason {
+            ExpiryReason::HopLimit => ResendRoute::Direct,
+            ExpiryReason::Expired
+            | ExpiryReason::RetriesExhausted
+            | ExpiryReason::QuotaEvicted
+            | ExpiryReason::Corrupted => ResendRoute::Alternate {
+                avoid: vec![notice.dropped_by.clone()],
+            },
+        }
+    }
+
+    /// Combine with the route from an earlier notice for the same chunk
+    pub fn merge(self, earlier: ResendRoute) -> Self {
+        match (self, earlier) {
+            (ResendRoute::Alternate { mut avoid }, ResendRoute::Alternate { avoid: earlier }) => {
+                for relay in earlier {
+                    if !avoid.contains(&relay) {
+                        avoid.push(relay);
+                    }
+                }
+                ResendRoute::Alternate { avoid }
+            }
+            _ => ResendRoute::Direct,
+        }
+    }
 }
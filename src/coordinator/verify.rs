@@ -0,0 +1,212 @@
+//! Batch checks of stored files against the manifests they were sent with
+//!
+//! A path is checked against the newest session sent from it, a session id
+//! against its own manifest and file path. Files are hashed concurrently,
+//! each with the algorithm its manifest records.
+
+use crate::integrity::{ChecksumType, IntegrityVerifier};
+use crate::session::{SessionQuery, SessionRepository, SessionSort};
+use futures::stream::{self, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Files hashed at once when the caller doesn't say
+pub const DEFAULT_VERIFY_CONCURRENCY: usize = 4;
+
+/// Most files hashed at once
+pub const MAX_VERIFY_CONCURRENCY: usize = 32;
+
+/// Most files one batch may check
+pub const MAX_VERIFY_TARGETS: usize = 1000;
+
+/// A file to check, by path or by the session that sent it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VerifyTarget {
+    Path(PathBuf),
+    Session(String),
+}
+
+/// Outcome of checking one file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VerifyStatus {
+    /// Checksum matches the manifest
+    Passed,
+    /// Checksum differs from the manifest
+    Failed,
+    /// File was hashed but no session records it
+    NoManifest,
+    /// File or session couldn't be read
+    Error,
+}
+
+/// Report for one file of a batch
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileVerification {
+    pub target: VerifyTarget,
+    pub status: VerifyStatus,
+    /// Session whose manifest the file was checked against
+    pub session_id: Option<String>,
+    pub path: Option<PathBuf>,
+    pub checksum_algorithm: Option<ChecksumType>,
+    /// Hex checksum from the manifest
+    pub expected: Option<String>,
+    /// Hex checksum of the file as stored
+    pub actual: Option<String>,
+    pub error: Option<String>,
+}
+
+impl FileVerification {
+    fn new(target: VerifyTarget) -> Self {
+        Self {
+            target,
+            status: VerifyStatus::Error,
+            session_id: None,
+            path: None,
+            checksum_algorithm: None,
+            expected: None,
+            actual: None,
+            error: None,
+        }
+    }
+
+    fn failed_with(mut self, error: impl ToString) -> Self {
+        self.status = VerifyStatus::Error;
+        self.error = Some(error.to_string());
+        self
+    }
+}
+
+/// Check each target, at most `concurrency` at a time, in the order given
+pub(crate) async fn verify_files(
+    store: &dyn SessionRepository,
+    targets: Vec<VerifyTarget>,
+    concurrency: usize,
+) -> Vec<FileVerification> {
+    stream::iter(targets)
+        .map(|target| verify_file(store, target))
+        .buffered(concurrency.clamp(1, MAX_VERIFY_CONCURRENCY))
+        .collect()
+        .await
+}
+
+async fn verify_file(store: &dyn SessionRepository, target: VerifyTarget) -> FileVerification {
+    let mut report = FileVerification::new(target.clone());
+    let (session, path) = match &target {
+        VerifyTarget::Session(session_id) => match store.load(session_id).await {
+            Ok(Some(session)) => match session.file_path.clone() {
+                Some(path) => (Some(session), PathBuf::from(path)),
+                None => return report.failed_with("session has no file path"),
+            },
+            Ok(None) => return report.failed_with(format!("session not found: {session_id}")),
+            Err(e) => return report.failed_with(e),
+        },
+        VerifyTarget::Path(path) => {
+            let query = SessionQuery {
+                file_id: Some(path.to_string_lossy().to_string()),
+                sort: SessionSort::UpdatedDesc,
+                limit: Some(1),
+                ..Default::default()
+            };
+            match store.query(&query).await {
+                Ok(page) => (page.sessions.into_iter().next(), path.clone()),
+                Err(e) => return report.failed_with(e),
+            }
+        }
+    };
+    report.path = Some(path.clone());
+
+    let algorithm = session
+        .as_ref()
+        .map(|s| s.manifest.checksum_algorithm)
+        .unwrap_or_default();
+    report.checksum_algorithm = Some(algorithm);
+    if let Some(session) = &session {
+        report.session_id = Some(session.session_id.clone());
+        report.expected = Some(hex::encode(session.manifest.checksum));
+    }
+
+    let actual = match IntegrityVerifier::calculate_file_checksum_with(&path, algorithm).await {
+        Ok(actual) => actual,
+        Err(e) => return report.failed_with(e),
+    };
+    report.actual = Some(hex::encode(actual));
+    report.status = match &session {
+        Some(session) if session.manifest.checksum == actual => VerifyStatus::Passed,
+        Some(_) => VerifyStatus::Failed,
+        None => VerifyStatus::NoManifest,
+    };
+    report
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+    use super::*;
+    use crate::chunk::{ChunkManager, Priority};
+    use crate::session::{SessionState, SessionStore};
+    use tempfile::TempDir;
+
+    async fn send_record(store: &SessionStore, session_id: &str, path: &std::path::Path) {
+        let file_id = path.to_string_lossy().to_string();
+        let (manifest, _) = ChunkManager::new(1024, 4, 2)
+            .unwrap()
+            .split_file(path, file_id.clone(), Priority::Normal)
+            .await
+            .unwrap();
+        let state = SessionState::new_with_receiver(
+            session_id.to_string(),
+            file_id,
+            manifest,
+            None,
+            Some(path.to_string_lossy().to_string()),
+        );
+        store.save(&state).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_verify_files_reports_each_target() {
+        let dir = TempDir::new().unwrap();
+        let store = SessionStore::new_in_memory().await.unwrap();
+        let intact = dir.path().join("intact.bin");
+        let changed = dir.path().join("changed.bin");
+        let unsent = dir.path().join("unsent.bin");
+        for path in [&intact, &changed, &unsent] {
+            tokio::fs::write(path, vec![7u8; 5000]).await.unwrap();
+        }
+        send_record(&store, "s-intact", &intact).await;
+        send_record(&store, "s-changed", &changed).await;
+        tokio::fs::write(&changed, vec![8u8; 5000]).await.unwrap();
+
+        let reports = verify_files(
+            &store,
+            vec![
+                VerifyTarget::Path(intact.clone()),
+                VerifyTarget::Session("s-changed".into()),
+                VerifyTarget::Path(unsent),
+                VerifyTarget::Session("s-unknown".into()),
+                VerifyTarget::Path(dir.path().join("gone.bin")),
+            ],
+            2,
+        )
+        .await;
+
+        let statuses: Vec<_> = reports.iter().map(|r| r.status).collect();
+        assert_eq!(
+            statuses,
+            [
+                VerifyStatus::Passed,
+                VerifyStatus::Failed,
+                VerifyStatus::NoManifest,
+                VerifyStatus::Error,
+                VerifyStatus::Error,
+            ]
+        );
+        assert_eq!(reports[0].session_id.as_deref(), Some("s-intact"));
+        assert_eq!(reports[0].expected, reports[0].actual);
+        assert_eq!(reports[1].path.as_ref(), Some(&changed));
+        assert_ne!(reports[1].expected, reports[1].actual);
+        assert!(reports[2].expected.is_none() && reports[2].actual.is_some());
+        assert!(reports[3].error.as_ref().unwrap().contains("s-unknown"));
+    }
+}
@@ -0,0 +1,215 @@
+//! Per-session flow control into the shared priority queue
+//!
+//! Queuing a whole file up front lets one large transfer fill the queue and
+//! turn every other session away with `QueueFull`. A [`SessionWindow`]
+//! instead holds a transfer's chunks back and keeps at most its window's
+//! worth in the queue, topping up as the worker takes them, so concurrent
+//! transfers share the queue's capacity.
+
+use crate::chunk::Chunk;
+use crate::priority::{PriorityQueue, QueueResult};
+use std::collections::VecDeque;
+
+/// Chunks queued per session by default
+pub const DEFAULT_SESSION_WINDOW: usize = 512;
+
+/// One transfer's chunks, fed into the queue a window at a time
+#[derive(Debug)]
+pub struct SessionWindow {
+    /// Most chunks in the queue at once; 0 queues everything
+    size: usize,
+    /// Chunks in the queue that the worker hasn't taken yet
+    queued: usize,
+    backlog: VecDeque<Chunk>,
+    /// While sending the manifest's first chunks, how many are still held
+    /// back
+    lead: Option<usize>,
+}
+
+impl SessionWindow {
+    pub fn new(size: usize, chunks: impl IntoIterator<Item = Chunk>) -> Self {
+        Self {
+            size,
+            queued: 0,
+            backlog: chunks.into_iter().collect(),
+            lead: None,
+        }
+    }
+
+    /// Send the chunks in `send_first` before the rest, in that order
+    ///
+    /// The queue orders a session's chunks by sequence number, so they go
+    /// in on their own and the rest wait until the worker has taken them.
+    pub fn with_send_first(mut self, send_first: &[u32]) -> Self {
+        let (mut first, rest): (VecDeque<Chunk>, VecDeque<Chunk>) = self
+            .backlog
+            .into_iter()
+            .partition(|chunk| send_first.contains(&chunk.metadata.sequence_number));
+        first.make_contiguous().sort_by_key(|chunk| {
+            send_first
+                .iter()
+                .position(|&seq| seq == chunk.metadata.sequence_number)
+        });
+        self.lead = (!first.is_empty()).then_some(first.len());
+        first.extend(rest);
+        self.backlog = first;
+        self
+    }
+
+    /// Queue held-back chunks until the window is full; returns how many
+    /// went in
+    ///
+    /// A queue full of other sessions' chunks, out of byte budget or
+    /// shedding under memory pressure is not an error: the rest wait for
+    /// the next call.
+    pub fn fill(&mut self, queue: &PriorityQueue) -> QueueResult<usize> {
+        let mut queued = 0;
+        while self.size == 0 || self.queued < self.size {
+            match self.lead {
+                Some(0) if self.queued > 0 => break,
+                Some(0) => self.lead = None,
+                _ => {}
+            }
+            let Some(chunk) = self.backlog.pop_front() else {
+                break;
+            };
+            match queue.enqueue(chunk.clone()) {
+                Ok(()) => {
+                    self.queued += 1;
+                    queued += 1;
+                    if let Some(lead) = &mut self.lead {
+                        *lead -= 1;
+                    }
+                }
+                Err(e) if e.is_backpressure() => {
+                    self.backlog.push_front(chunk);
+                    break;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(queued)
+    }
+
+    /// The worker took one of this session's chunks from the queue
+    pub fn taken(&mut self) {
+        self.queued = self.queued.saturating_sub(1);
+    }
+
+    /// Send `chunk` again, ahead of anything still held back
+    pub fn requeue(&mut self, chunk: Chunk) {
+        self.backlog.push_front(chunk);
+        if let Some(lead) = &mut self.lead {
+            *lead += 1;
+        }
+    }
+
+    /// Chunks in the queue
+    pub fn queued(&self) -> usize {
+        self.queued
+    }
+
+    /// Chunks held back
+    pub fn pending(&self) -> usize {
+        self.backlog.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::{ChunkMetadata, Priority};
+    use crate::integrity::{ChecksumType, MerkleTree};
+    use bytes::Bytes;
+
+    fn chunks(file_id: &str, count: u32) -> Vec<Chunk> {
+        let data = Bytes::from_static(&[1, 2, 3, 4]);
+        let algorithm = ChecksumType::default();
+        let checksum = algorithm.digest(&data);
+        let merkle = MerkleTree::new(algorithm, &vec![checksum; count as usize]);
+        (0..count)
+            .map(|seq| Chunk {
+                metadata: ChunkMetadata {
+                    chunk_id: seq as u64,
+                    file_id: file_id.to_string(),
+                    sequence_number: seq,
+                    total_chunks: count,
+                    data_size: 4,
+                    checksum,
+                    is_parity: false,
+                    priority: Priority::Normal,
+                    created_at: 0,
+                    file_size: count as u64 * 4,
+                    file_checksum: [0u8; 32],
+                    data_chunks: count,
+                    zero_runs: Vec::new(),
+                    attributes: None,
+                    checksum_algorithm: algorithm,
+                    merkle_proof: merkle.proof(seq as usize),
+                    trace: None,
+                    compression: None,
+                },
+                data: data.clone(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_window_shares_queue_between_sessions() {
+        let queue = PriorityQueue::new(8);
+        let mut large = SessionWindow::new(4, chunks("large", 100));
+        let mut small = SessionWindow::new(4, chunks("small", 3));
+
+        assert_eq!(large.fill(&queue).unwrap(), 4);
+        assert_eq!(small.fill(&queue).unwrap(), 3);
+        assert_eq!(large.pending(), 96);
+
+        // Taking a chunk frees one slot of the window, in file order
+        let chunk = queue.dequeue_file("large").unwrap();
+        assert_eq!(chunk.metadata.sequence_number, 0);
+        large.taken();
+        assert_eq!(large.fill(&queue).unwrap(), 1);
+        assert_eq!(large.queued(), 4);
+
+        // Resends jump the backlog
+        large.requeue(chunk);
+        let taken = queue.dequeue_file("large").unwrap();
+        large.taken();
+        large.fill(&queue).unwrap();
+        assert_eq!(taken.metadata.sequence_number, 1);
+        let order: Vec<u32> = std::iter::from_fn(|| queue.dequeue_file("large").ok())
+            .map(|c| c.metadata.sequence_number)
+            .collect();
+        assert_eq!(order, vec![0, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_send_first_chunks_go_in_on_their_own() {
+        let queue = PriorityQueue::new(16);
+        let mut window = SessionWindow::new(4, chunks("clip", 8)).with_send_first(&[7, 0, 6]);
+
+        // The rest of the window stays empty until the lead chunks are taken
+        assert_eq!(window.fill(&queue).unwrap(), 3);
+        let mut order = Vec::new();
+        while let Ok(chunk) = queue.dequeue_file("clip") {
+            window.taken();
+            order.push(chunk.metadata.sequence_number);
+            window.fill(&queue).unwrap();
+        }
+        assert_eq!(order, vec![0, 6, 7, 1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn test_full_queue_holds_chunks_back() {
+        let queue = PriorityQueue::new(2);
+        let mut window = SessionWindow::new(0, chunks("f", 5));
+
+        assert_eq!(window.fill(&queue).unwrap(), 2);
+        assert_eq!(window.pending(), 3);
+
+        queue.dequeue_file("f").unwrap();
+        window.taken();
+        assert_eq!(window.fill(&queue).unwrap(), 1);
+        assert_eq!(window.pending(), 2);
+    }
+}
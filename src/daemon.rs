@@ -0,0 +1,250 @@
+//! Running as a system service
+//!
+//! Under systemd the daemon can take its listening sockets from a socket
+//! unit (`LISTEN_FDS`), so clients that connect while it restarts wait in
+//! the socket's backlog instead of being refused. It tells the service
+//! manager it is ready once interrupted sessions are recovered, keeps the
+//! watchdog fed, and stops cleanly on SIGTERM. Outside systemd every call
+//! here does nothing.
+
+use std::io;
+use std::net::{TcpListener, UdpSocket};
+use std::time::Duration;
+
+/// `FileDescriptorName=` that marks the REST API socket
+pub const API_SOCKET_NAME: &str = "api";
+
+/// `FileDescriptorName=` that marks the QUIC socket
+pub const QUIC_SOCKET_NAME: &str = "quic";
+
+/// First descriptor systemd passes; 0-2 are stdio
+#[cfg(unix)]
+const LISTEN_FDS_START: i32 = 3;
+
+/// Sockets passed in by socket activation
+#[derive(Debug, Default)]
+pub struct ActivatedSockets {
+    /// Stream socket for the REST API
+    pub api: Option<TcpListener>,
+    /// Datagram socket for the QUIC endpoint
+    pub quic: Option<UdpSocket>,
+}
+
+impl ActivatedSockets {
+    /// Take the sockets systemd passed to this process, if any
+    ///
+    /// Sockets named [`API_SOCKET_NAME`] or [`QUIC_SOCKET_NAME`] go where
+    /// their name says; others by type, the stream socket serving the REST
+    /// API and the datagram socket QUIC. Child processes don't take them
+    /// too, since `LISTEN_PID` names this process only.
+    #[cfg(unix)]
+    pub fn from_env() -> io::Result<Self> {
+        let count = listen_fds(
+            std::env::var("LISTEN_PID").ok().as_deref(),
+            std::env::var("LISTEN_FDS").ok().as_deref(),
+            std::process::id(),
+        )?;
+        let names = std::env::var("LISTEN_FDNAMES").unwrap_or_default();
+        let names: Vec<&str> = names.split(':').collect();
+
+        let mut sockets = Vec::with_capacity(count);
+        for fd in (0..count as i32).map(|i| LISTEN_FDS_START + i) {
+            // SAFETY: systemd passed these descriptors to this process and
+            // nothing else in it has taken them; they arrive without
+            // close-on-exec, which is set before anything can fork
+            let socket = unsafe {
+                use std::os::fd::FromRawFd;
+                if libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                socket2::Socket::from_raw_fd(fd)
+            };
+            sockets.push(socket);
+        }
+        Self::from_sockets(sockets, &names)
+    }
+
+    /// Socket activation is only available on Unix
+    #[cfg(not(unix))]
+    pub fn from_env() -> io::Result<Self> {
+        Ok(Self::default())
+    }
+
+    /// Sort `sockets` by name or type; `names` line up with them
+    fn from_sockets(sockets: Vec<socket2::Socket>, names: &[&str]) -> io::Result<Self> {
+        let mut activated = Self::default();
+        for (i, socket) in sockets.into_iter().enumerate() {
+            let name = names.get(i).copied().unwrap_or_default();
+            let socket_type = socket.r#type()?;
+            let is_api = match name {
+                API_SOCKET_NAME => true,
+                QUIC_SOCKET_NAME => false,
+                _ => socket_type == socket2::Type::STREAM,
+            };
+            let expected = if is_api {
+                socket2::Type::STREAM
+            } else {
+                socket2::Type::DGRAM
+            };
+            if socket_type != expected {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("activated socket {} ({name:?}) has the wrong type", i),
+                ));
+            }
+
+            let duplicate = if is_api {
+                activated.api.replace(socket.into()).is_some()
+            } else {
+                activated.quic.replace(socket.into()).is_some()
+            };
+            if duplicate {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "more than one activated socket for {}",
+                        if is_api { "the API" } else { "QUIC" }
+                    ),
+                ));
+            }
+        }
+        Ok(activated)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.api.is_none() && self.quic.is_none()
+    }
+}
+
+/// Number of descriptors passed in, 0 unless they are meant for `own_pid`
+fn listen_fds(pid: Option<&str>, fds: Option<&str>, own_pid: u32) -> io::Result<usize> {
+    let (Some(pid), Some(fds)) = (pid, fds) else {
+        return Ok(0);
+    };
+    let invalid = |var: &str| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{var} is not a number"),
+        )
+    };
+    if pid.parse::<u32>().map_err(|_| invalid("LISTEN_PID"))? != own_pid {
+        return Ok(0);
+    }
+    fds.parse().map_err(|_| invalid("LISTEN_FDS"))
+}
+
+/// Send `state`, e.g. `READY=1`, to the service manager; `false` when not
+/// running under one
+pub fn notify(state: &str) -> io::Result<bool> {
+    match std::env::var_os("NOTIFY_SOCKET") {
+        Some(path) => notify_socket(&path, state).map(|()| true),
+        None => Ok(false),
+    }
+}
+
+/// Tell the service manager the daemon is up, with a status line
+pub fn notify_ready(status: &str) -> io::Result<bool> {
+    notify(&format!("READY=1\nSTATUS={status}"))
+}
+
+/// Tell the service manager the daemon is shutting down
+pub fn notify_stopping() -> io::Result<bool> {
+    notify("STOPPING=1")
+}
+
+#[cfg(unix)]
+fn notify_socket(path: &std::ffi::OsStr, state: &str) -> io::Result<()> {
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::net::UnixDatagram;
+
+    let socket = UnixDatagram::unbound()?;
+    match path.as_bytes().strip_prefix(b"@") {
+        #[cfg(target_os = "linux")]
+        Some(name) => {
+            use std::os::linux::net::SocketAddrExt;
+            let addr = std::os::unix::net::SocketAddr::from_abstract_name(name)?;
+            socket.send_to_addr(state.as_bytes(), &addr)?;
+        }
+        #[cfg(not(target_os = "linux"))]
+        Some(_) => return Err(io::ErrorKind::Unsupported.into()),
+        None => {
+            socket.send_to(state.as_bytes(), path)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn notify_socket(_path: &std::ffi::OsStr, _state: &str) -> io::Result<()> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+/// How often to send `WATCHDOG=1`, when the unit sets `WatchdogSec=`
+///
+/// Half the watchdog timeout, so one late tick doesn't get the daemon
+/// restarted.
+pub fn watchdog_interval() -> Option<Duration> {
+    let pid = std::env::var("WATCHDOG_PID").ok();
+    if pid.is_some_and(|pid| pid.parse() != Ok(std::process::id())) {
+        return None;
+    }
+    let usec: u64 = std::env::var("WATCHDOG_USEC").ok()?.parse().ok()?;
+    (usec > 0).then(|| Duration::from_micros(usec / 2))
+}
+
+/// Wait for SIGTERM, which systemd stops services with, or Ctrl+C
+pub async fn shutdown_signal() -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut terminate = signal(SignalKind::terminate())?;
+        tokio::select! {
+            _ = terminate.recv() => Ok(()),
+            result = tokio::signal::ctrl_c() => result,
+        }
+    }
+    #[cfg(not(unix))]
+    tokio::signal::ctrl_c().await
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::net::UnixDatagram;
+
+    #[test]
+    fn test_listen_fds_only_for_this_process() {
+        assert_eq!(listen_fds(Some("42"), Some("2"), 42).unwrap(), 2);
+        assert_eq!(listen_fds(Some("41"), Some("2"), 42).unwrap(), 0);
+        assert_eq!(listen_fds(None, Some("2"), 42).unwrap(), 0);
+        assert!(listen_fds(Some("42"), Some("two"), 42).is_err());
+    }
+
+    #[test]
+    fn test_sockets_sorted_by_name_then_type() {
+        let tcp = || socket2::Socket::from(TcpListener::bind("127.0.0.1:0").unwrap());
+        let udp = || socket2::Socket::from(UdpSocket::bind("127.0.0.1:0").unwrap());
+
+        let activated = ActivatedSockets::from_sockets(vec![udp(), tcp()], &[""]).unwrap();
+        assert!(activated.api.is_some() && activated.quic.is_some());
+
+        let named = ActivatedSockets::from_sockets(vec![udp()], &[QUIC_SOCKET_NAME]).unwrap();
+        assert!(named.api.is_none() && named.quic.is_some());
+
+        // A name that doesn't fit the socket, or two sockets for one role
+        assert!(ActivatedSockets::from_sockets(vec![udp()], &[API_SOCKET_NAME]).is_err());
+        assert!(ActivatedSockets::from_sockets(vec![tcp(), tcp()], &[]).is_err());
+    }
+
+    #[test]
+    fn test_notify_reaches_socket() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("notify");
+        let manager = UnixDatagram::bind(&path).unwrap();
+
+        notify_socket(path.as_os_str(), "READY=1\nSTATUS=up").unwrap();
+        let mut buf = [0u8; 64];
+        let len = manager.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"READY=1\nSTATUS=up");
+    }
+}
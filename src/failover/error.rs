@@ -0,0 +1,27 @@
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum FailoverError {
+    #[error("Replication frame of {0} bytes is larger than allowed")]
+    FrameTooLarge(usize),
+
+    #[error("Standby fell {0} changes behind and must take a new snapshot")]
+    Lagged(u64),
+
+    #[error("Replication stream ended")]
+    StreamEnded,
+
+    #[error("Network error: {0}")]
+    NetworkError(#[from] crate::network::NetworkError),
+
+    #[error("Session error: {0}")]
+    SessionError(#[from] crate::session::SessionError),
+
+    #[error("Serialization error: {0}")]
+    SerializationError(#[from] serde_json::Error),
+
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+}
+
+pub type FailoverResult<T> = Result<T, FailoverError>;
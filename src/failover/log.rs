@@ -0,0 +1,366 @@
+//! Session changes on the active, as they are made
+//!
+//! [`ReplicatingRepository`] sits between the coordinator and its session
+//! storage. Each write that succeeds is numbered and broadcast on a
+//! [`ReplicationLog`], which the replication server forwards to standbys.
+
+use super::types::{ReplicationRecord, SessionChange};
+use crate::session::{
+    BenchmarkRecord, MaintenanceReport, ProgressSample, ResumeInfo, SessionPage, SessionQuery,
+    SessionRepository, SessionResult, SessionSearch, SessionState, SessionStatus, StorageStats,
+    TransferProfile,
+};
+use futures::future::BoxFuture;
+use parking_lot::Mutex;
+use std::sync::Arc;
+use tokio::sync::broadcast;
+
+/// Changes a standby may fall behind by before it has to resync
+pub const REPLICATION_BACKLOG: usize = 16 * 1024;
+
+/// Numbered session changes, broadcast to whoever follows them
+#[derive(Debug)]
+pub struct ReplicationLog {
+    /// Latest sequence number; held while sending, so records go out in order
+    seq: Mutex<u64>,
+    sender: broadcast::Sender<ReplicationRecord>,
+}
+
+impl Default for ReplicationLog {
+    fn default() -> Self {
+        Self::new(REPLICATION_BACKLOG)
+    }
+}
+
+impl ReplicationLog {
+    pub fn new(backlog: usize) -> Self {
+        Self {
+            seq: Mutex::new(0),
+            sender: broadcast::channel(backlog.max(1)).0,
+        }
+    }
+
+    /// Number `change` and send it to every follower
+    pub fn publish(&self, change: SessionChange) {
+        let mut seq = self.seq.lock();
+        *seq += 1;
+        // No followers is fine; the change is in storage either way
+        let _ = self.sender.send(ReplicationRecord {
+            seq: *seq,
+            at_ms: chrono::Utc::now().timestamp_millis(),
+            change,
+        });
+    }
+
+    /// Follow changes after the returned sequence number
+    pub fn subscribe(&self) -> (u64, broadcast::Receiver<ReplicationRecord>) {
+        let seq = self.seq.lock();
+        (*seq, self.sender.subscribe())
+    }
+
+    pub fn last_seq(&self) -> u64 {
+        *self.seq.lock()
+    }
+}
+
+/// Session storage that logs every successful write to a [`ReplicationLog`]
+pub struct ReplicatingRepository<R> {
+    inner: R,
+    log: Arc<ReplicationLog>,
+}
+
+impl<R: SessionRepository> ReplicatingRepository<R> {
+    pub fn new(inner: R, log: Arc<ReplicationLog>) -> Self {
+        Self { inner, log }
+    }
+
+    pub fn log(&self) -> &Arc<ReplicationLog> {
+        &self.log
+    }
+
+    /// Run `write`, then log `change` if it succeeded
+    fn logged<'a, T: Send + 'a>(
+        &'a self,
+        write: BoxFuture<'a, SessionResult<T>>,
+        change: impl FnOnce(&T) -> Option<SessionChange> + Send + 'a,
+    ) -> BoxFuture<'a, SessionResult<T>> {
+        Box::pin(async move {
+            let result = write.await?;
+            if let Some(change) = change(&result) {
+                self.log.publish(change);
+            }
+            Ok(result)
+        })
+    }
+}
+
+impl<R: SessionRepository> SessionRepository for ReplicatingRepository<R> {
+    fn save<'a>(&'a self, state: &'a SessionState) -> BoxFuture<'a, SessionResult<()>> {
+        self.logged(self.inner.save(state), move |_| {
+            Some(SessionChange::Saved {
+                state: Box::new(state.clone()),
+            })
+        })
+    }
+
+    fn load<'a>(
+        &'a self,
+        session_id: &'a str,
+    ) -> BoxFuture<'a, SessionResult<Option<SessionState>>> {
+        self.inner.load(session_id)
+    }
+
+    fn mark_chunk_completed_with_bytes<'a>(
+        &'a self,
+        session_id: &'a str,
+        chunk_number: u32,
+        bytes_transferred: u64,
+    ) -> BoxFuture<'a, SessionResult<()>> {
+        self.logged(
+            self.inner
+                .mark_chunk_completed_with_bytes(session_id, chunk_number, bytes_transferred),
+            move |_| {
+                Some(SessionChange::ChunkCompleted {
+                    session_id: session_id.to_string(),
+                    chunk_number,
+                    bytes: bytes_transferred,
+                })
+            },
+        )
+    }
+
+    fn mark_skipped_duplicate<'a>(
+        &'a self,
+        session_id: &'a str,
+    ) -> BoxFuture<'a, SessionResult<()>> {
+        self.logged(self.inner.mark_skipped_duplicate(session_id), move |_| {
+            Some(SessionChange::SkippedDuplicate {
+                session_id: session_id.to_string(),
+            })
+        })
+    }
+
+    fn mark_chunk_failed<'a>(
+        &'a self,
+        session_id: &'a str,
+        chunk_number: u32,
+    ) -> BoxFuture<'a, SessionResult<()>> {
+        self.logged(
+            self.inner.mark_chunk_failed(session_id, chunk_number),
+            move |_| {
+                Some(SessionChange::ChunkFailed {
+                    session_id: session_id.to_string(),
+                    chunk_number,
+                })
+            },
+        )
+    }
+
+    fn mark_chunk_nacked<'a>(
+        &'a self,
+        session_id: &'a str,
+        chunk_number: u32,
+    ) -> BoxFuture<'a, SessionResult<()>> {
+        self.logged(
+            self.inner.mark_chunk_nacked(session_id, chunk_number),
+            move |_| {
+                Some(SessionChange::ChunkNacked {
+                    session_id: session_id.to_string(),
+                    chunk_number,
+                })
+            },
+        )
+    }
+
+    fn mark_chunks_lost<'a>(
+        &'a self,
+        session_id: &'a str,
+        chunk_numbers: &'a [u32],
+    ) -> BoxFuture<'a, SessionResult<()>> {
+        self.logged(
+            self.inner.mark_chunks_lost(session_id, chunk_numbers),
+            move |_| {
+                Some(SessionChange::ChunksLost {
+                    session_id: session_id.to_string(),
+                    chunk_numbers: chunk_numbers.to_vec(),
+                })
+            },
+        )
+    }
+
+    fn update_status<'a>(
+        &'a self,
+        session_id: &'a str,
+        status: SessionStatus,
+    ) -> BoxFuture<'a, SessionResult<()>> {
+        let change = SessionChange::Status {
+            session_id: session_id.to_string(),
+            status: status.clone(),
+        };
+        self.logged(self.inner.update_status(session_id, status), move |_| {
+            Some(change)
+        })
+    }
+
+    fn get_resume_info<'a>(
+        &'a self,
+        session_id: &'a str,
+    ) -> BoxFuture<'a, SessionResult<ResumeInfo>> {
+        self.inner.get_resume_info(session_id)
+    }
+
+    fn query<'a>(&'a self, query: &'a SessionQuery) -> BoxFuture<'a, SessionResult<SessionPage>> {
+        self.inner.query(query)
+    }
+
+    fn search<'a>(
+        &'a self,
+        search: &'a SessionSearch,
+    ) -> BoxFuture<'a, SessionResult<Vec<SessionState>>> {
+        self.inner.search(search)
+    }
+
+    fn save_timeseries<'a>(
+        &'a self,
+        session_id: &'a str,
+        samples: &'a [ProgressSample],
+    ) -> BoxFuture<'a, SessionResult<()>> {
+        self.logged(self.inner.save_timeseries(session_id, samples), move |_| {
+            Some(SessionChange::Timeseries {
+                session_id: session_id.to_string(),
+                samples: samples.to_vec(),
+            })
+        })
+    }
+
+    fn load_timeseries<'a>(
+        &'a self,
+        session_id: &'a str,
+    ) -> BoxFuture<'a, SessionResult<Vec<ProgressSample>>> {
+        self.inner.load_timeseries(session_id)
+    }
+
+    fn save_profile<'a>(
+        &'a self,
+        profile: &'a TransferProfile,
+    ) -> BoxFuture<'a, SessionResult<TransferProfile>> {
+        self.logged(self.inner.save_profile(profile), |stored| {
+            Some(SessionChange::ProfileSaved {
+                profile: stored.clone(),
+            })
+        })
+    }
+
+    fn load_profile<'a>(
+        &'a self,
+        name: &'a str,
+    ) -> BoxFuture<'a, SessionResult<Option<TransferProfile>>> {
+        self.inner.load_profile(name)
+    }
+
+    fn list_profiles(&self) -> BoxFuture<'_, SessionResult<Vec<TransferProfile>>> {
+        self.inner.list_profiles()
+    }
+
+    fn delete_profile<'a>(&'a self, name: &'a str) -> BoxFuture<'a, SessionResult<bool>> {
+        self.logged(self.inner.delete_profile(name), move |&deleted| {
+            deleted.then(|| SessionChange::ProfileDeleted {
+                name: name.to_string(),
+            })
+        })
+    }
+
+    // Benchmark reports are dashboard history, not transfer state, so
+    // they stay on the node they were uploaded to
+    fn save_benchmark<'a>(
+        &'a self,
+        label: Option<&'a str>,
+        report: &'a serde_json::Value,
+    ) -> BoxFuture<'a, SessionResult<BenchmarkRecord>> {
+        self.inner.save_benchmark(label, report)
+    }
+
+    fn load_benchmark<'a>(
+        &'a self,
+        id: &'a str,
+    ) -> BoxFuture<'a, SessionResult<Option<BenchmarkRecord>>> {
+        self.inner.load_benchmark(id)
+    }
+
+    fn list_benchmarks(&self, limit: u32) -> BoxFuture<'_, SessionResult<Vec<BenchmarkRecord>>> {
+        self.inner.list_benchmarks(limit)
+    }
+
+    fn delete_benchmark<'a>(&'a self, id: &'a str) -> BoxFuture<'a, SessionResult<bool>> {
+        self.inner.delete_benchmark(id)
+    }
+
+    fn ping(&self) -> BoxFuture<'_, SessionResult<()>> {
+        self.inner.ping()
+    }
+
+    fn storage_stats(&self) -> BoxFuture<'_, SessionResult<Option<StorageStats>>> {
+        self.inner.storage_stats()
+    }
+
+    fn maintain(
+        &self,
+        vacuum_pages: u32,
+        analyze: bool,
+    ) -> BoxFuture<'_, SessionResult<Option<MaintenanceReport>>> {
+        self.inner.maintain(vacuum_pages, analyze)
+    }
+
+    fn flush(&self) -> BoxFuture<'_, SessionResult<()>> {
+        self.inner.flush()
+    }
+
+    fn close(&self) -> BoxFuture<'_, ()> {
+        self.inner.close()
+    }
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+    use super::*;
+    use crate::chunk::{ChunkManager, Priority};
+    use crate::session::SessionStore;
+
+    #[tokio::test]
+    async fn test_only_successful_writes_are_logged() {
+        let log = Arc::new(ReplicationLog::default());
+        let (start, mut changes) = log.subscribe();
+        let repository =
+            ReplicatingRepository::new(SessionStore::new_in_memory().await.unwrap(), log.clone());
+
+        let (manifest, _) = ChunkManager::new(1024, 4, 2)
+            .unwrap()
+            .split_bytes(&[7u8; 4096], "a.bin".into(), "a".into(), Priority::Normal)
+            .unwrap();
+        let session = SessionState::new("s1".into(), "a".into(), manifest);
+        repository.save(&session).await.unwrap();
+        repository
+            .mark_chunk_completed_with_bytes("s1", 0, 1024)
+            .await
+            .unwrap();
+        // Unknown session: the write fails and nothing is logged
+        assert!(repository.mark_chunk_failed("s2", 1).await.is_err());
+        assert!(!repository.delete_profile("none").await.unwrap());
+
+        assert_eq!(start, 0);
+        assert_eq!(log.last_seq(), 2);
+        let first = changes.recv().await.unwrap();
+        assert!(matches!(first.change, SessionChange::Saved { .. }));
+        let second = changes.recv().await.unwrap();
+        assert_eq!(second.seq, 2);
+        assert!(matches!(
+            second.change,
+            SessionChange::ChunkCompleted {
+                chunk_number: 0,
+                bytes: 1024,
+                ..
+            }
+        ));
+        assert!(changes.try_recv().is_err());
+    }
+}
@@ -0,0 +1,37 @@
+//! Warm standby: an active coordinator mirrored by a standby that can take over
+//!
+//! The active wraps its session storage in a [`ReplicatingRepository`], so
+//! every session write is also logged, and serves the log to standbys over a
+//! QUIC endpoint of its own ([`ReplicationServer`]). A standby
+//! ([`StandbyReplica`]) takes a snapshot of the active's sessions and
+//! profiles on connecting, then applies each change as it is made, so its
+//! own database holds what the active's does, give or take the changes in
+//! flight.
+//!
+//! A standby takes no transfers. Promoting it
+//! ([`TransferCoordinator::promote`](crate::coordinator::TransferCoordinator::promote),
+//! `POST /api/v1/failover/promote`) stops following the active and resumes
+//! every transfer the active was running from the chunks recorded as
+//! acknowledged. Resuming reads the source files again, so they must be at
+//! the same paths on the standby (shared or mirrored storage); transfers
+//! sent from memory stay paused until their data is supplied again.
+//!
+//! Promotion doesn't fence the old active. Whatever promotes the standby
+//! must make sure the active is down first, or both send the same
+//! transfers. The replication endpoint is not authenticated: bind it to a
+//! private network.
+
+pub mod error;
+pub mod log;
+pub mod server;
+pub mod standby;
+pub mod types;
+
+pub use error::{FailoverError, FailoverResult};
+pub use log::{ReplicatingRepository, ReplicationLog, REPLICATION_BACKLOG};
+pub use server::{ReplicationServer, MAX_FRAME_SIZE};
+pub use standby::StandbyReplica;
+pub use types::{
+    FailoverConfig, FailoverRole, FailoverStatus, ReplicationFrame, ReplicationRecord,
+    ReplicationStatus, SessionChange, SkippedSession, StandbyStatus, TakeoverReport,
+};
@@ -0,0 +1,180 @@
+//! The active's side: a snapshot, then every change, to each standby
+//!
+//! Replication has a QUIC endpoint of its own, so standbys never reach the
+//! transfer endpoint. A standby connects; the active opens one
+//! unidirectional stream and writes length-prefixed JSON frames to it (see
+//! [`ReplicationFrame`]). A standby that falls more than the log's backlog
+//! behind is disconnected, and takes a fresh snapshot when it reconnects.
+
+use super::error::{FailoverError, FailoverResult};
+use super::log::ReplicationLog;
+use super::types::{ReplicationFrame, ReplicationStatus};
+use crate::network::{NetworkError, QuicTransport};
+use crate::session::{SessionQuery, SessionRepository};
+use quinn::{Connection, RecvStream, SendStream};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::task::JoinHandle;
+
+/// Largest frame accepted; a session holds its manifest and chunk sets
+pub const MAX_FRAME_SIZE: usize = 64 * 1024 * 1024;
+
+pub(crate) async fn write_frame(
+    stream: &mut SendStream,
+    frame: &ReplicationFrame,
+) -> FailoverResult<()> {
+    let body = serde_json::to_vec(frame)?;
+    if body.len() > MAX_FRAME_SIZE {
+        return Err(FailoverError::FrameTooLarge(body.len()));
+    }
+    stream
+        .write_all(&(body.len() as u32).to_be_bytes())
+        .await
+        .map_err(NetworkError::from)?;
+    stream.write_all(&body).await.map_err(NetworkError::from)?;
+    Ok(())
+}
+
+/// The next frame, or `None` once the active finished the stream
+pub(crate) async fn read_frame(
+    stream: &mut RecvStream,
+) -> FailoverResult<Option<ReplicationFrame>> {
+    let mut len = [0u8; 4];
+    match stream.read_exact(&mut len).await {
+        Ok(()) => {}
+        Err(quinn::ReadExactError::FinishedEarly(0)) => return Ok(None),
+        Err(e) => return Err(NetworkError::ReceiveFailed(e.to_string()).into()),
+    }
+    let len = u32::from_be_bytes(len) as usize;
+    if len > MAX_FRAME_SIZE {
+        return Err(FailoverError::FrameTooLarge(len));
+    }
+    let mut body = vec![0u8; len];
+    stream
+        .read_exact(&mut body)
+        .await
+        .map_err(|e| NetworkError::ReceiveFailed(e.to_string()))?;
+    Ok(Some(serde_json::from_slice(&body)?))
+}
+
+/// Serves standbys until dropped
+pub struct ReplicationServer {
+    transport: Arc<QuicTransport>,
+    log: Arc<ReplicationLog>,
+    standbys: Arc<AtomicUsize>,
+    task: JoinHandle<()>,
+}
+
+impl ReplicationServer {
+    /// Accept standbys on `transport`, snapshotting `repository` for each
+    /// before streaming `log`
+    pub fn start(
+        transport: QuicTransport,
+        log: Arc<ReplicationLog>,
+        repository: Arc<dyn SessionRepository>,
+    ) -> Self {
+        let transport = Arc::new(transport);
+        let standbys = Arc::new(AtomicUsize::new(0));
+        let task = tokio::spawn(Self::accept_loop(
+            transport.clone(),
+            log.clone(),
+            repository,
+            standbys.clone(),
+        ));
+        Self {
+            transport,
+            log,
+            standbys,
+            task,
+        }
+    }
+
+    pub fn status(&self) -> ReplicationStatus {
+        ReplicationStatus {
+            listen_addr: self
+                .local_addr()
+                .unwrap_or_else(|_| ([0, 0, 0, 0], 0).into()),
+            last_seq: self.log.last_seq(),
+            connected_standbys: self.standbys.load(Ordering::Relaxed),
+        }
+    }
+
+    pub fn local_addr(&self) -> FailoverResult<SocketAddr> {
+        Ok(self.transport.local_addr()?)
+    }
+
+    async fn accept_loop(
+        transport: Arc<QuicTransport>,
+        log: Arc<ReplicationLog>,
+        repository: Arc<dyn SessionRepository>,
+        standbys: Arc<AtomicUsize>,
+    ) {
+        loop {
+            let conn = match transport.accept().await {
+                Ok(conn) => conn,
+                Err(NetworkError::ConnectionClosed(_)) => return,
+                Err(e) => {
+                    tracing::warn!(error = %e, "Failed to accept a standby");
+                    continue;
+                }
+            };
+            let (log, repository, standbys) = (log.clone(), repository.clone(), standbys.clone());
+            tokio::spawn(async move {
+                let peer = conn.remote_address();
+                tracing::info!(%peer, "Standby connected");
+                standbys.fetch_add(1, Ordering::Relaxed);
+                let result = Self::replicate_to(&conn, &log, repository.as_ref()).await;
+                standbys.fetch_sub(1, Ordering::Relaxed);
+                match result {
+                    Ok(()) => tracing::info!(%peer, "Standby disconnected"),
+                    Err(e) => tracing::warn!(%peer, error = %e, "Stopped replicating to standby"),
+                }
+                conn.close(0u32.into(), b"replication ended");
+            });
+        }
+    }
+
+    /// Snapshot, then changes, until the standby goes away
+    async fn replicate_to(
+        conn: &Connection,
+        log: &ReplicationLog,
+        repository: &dyn SessionRepository,
+    ) -> FailoverResult<()> {
+        // Subscribe first, so no change falls between snapshot and stream
+        let (seq, mut changes) = log.subscribe();
+        let mut stream = conn.open_uni().await.map_err(NetworkError::from)?;
+
+        let sessions = repository.query(&SessionQuery::default()).await?.sessions;
+        for state in sessions {
+            let state = Box::new(state);
+            write_frame(&mut stream, &ReplicationFrame::Session { state }).await?;
+        }
+        for profile in repository.list_profiles().await? {
+            write_frame(&mut stream, &ReplicationFrame::Profile { profile }).await?;
+        }
+        write_frame(&mut stream, &ReplicationFrame::SnapshotDone { seq }).await?;
+
+        loop {
+            let record = tokio::select! {
+                _ = conn.closed() => return Ok(()),
+                record = changes.recv() => record,
+            };
+            match record {
+                Ok(record) => {
+                    write_frame(&mut stream, &ReplicationFrame::Change { record }).await?
+                }
+                Err(RecvError::Lagged(missed)) => return Err(FailoverError::Lagged(missed)),
+                Err(RecvError::Closed) => return Ok(()),
+            }
+        }
+    }
+}
+
+impl Drop for ReplicationServer {
+    fn drop(&mut self) {
+        self.task.abort();
+        self.transport.close();
+    }
+}
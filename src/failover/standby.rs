@@ -0,0 +1,239 @@
+//! The standby's side: follow the active and mirror its sessions
+//!
+//! The standby keeps connecting to the active. Each connection starts with a
+//! snapshot, which is saved over the standby's own sessions, and goes on
+//! with every change the active makes, applied in order. When the active is
+//! lost the standby keeps what it has and tries again after a delay.
+
+use super::error::{FailoverError, FailoverResult};
+use super::server::read_frame;
+use super::types::{ReplicationFrame, StandbyStatus};
+use crate::network::{NetworkError, QuicTransport};
+use crate::session::SessionRepository;
+use parking_lot::RwLock;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+/// Follows an active until stopped
+pub struct StandbyReplica {
+    status: Arc<RwLock<StandbyStatus>>,
+    cancel: CancellationToken,
+    task: parking_lot::Mutex<Option<JoinHandle<()>>>,
+}
+
+impl StandbyReplica {
+    /// Follow the active replicating on `active_addr`, writing its sessions
+    /// to `repository`
+    pub fn start(
+        transport: Arc<QuicTransport>,
+        active_addr: SocketAddr,
+        repository: Arc<dyn SessionRepository>,
+        reconnect: Duration,
+    ) -> Self {
+        let status = Arc::new(RwLock::new(StandbyStatus {
+            active_addr: Some(active_addr),
+            ..Default::default()
+        }));
+        let cancel = CancellationToken::new();
+        let task = tokio::spawn({
+            let (status, cancel) = (status.clone(), cancel.clone());
+            async move {
+                loop {
+                    let result = tokio::select! {
+                        _ = cancel.cancelled() => return,
+                        result = Self::follow(&transport, active_addr, repository.as_ref(), &status) => result,
+                    };
+                    {
+                        let mut status = status.write();
+                        status.connected = false;
+                        status.synced = false;
+                        status.last_error = result.err().map(|e| e.to_string());
+                    }
+                    tracing::warn!(
+                        %active_addr,
+                        error = status.read().last_error.as_deref().unwrap_or("stream ended"),
+                        "Lost the active, reconnecting"
+                    );
+                    tokio::select! {
+                        _ = cancel.cancelled() => return,
+                        _ = tokio::time::sleep(reconnect) => {}
+                    }
+                }
+            }
+        });
+        Self {
+            status,
+            cancel,
+            task: parking_lot::Mutex::new(Some(task)),
+        }
+    }
+
+    pub fn status(&self) -> StandbyStatus {
+        self.status.read().clone()
+    }
+
+    /// Stop following, waiting for the change being applied; returns where
+    /// the standby got to
+    pub async fn stop(&self) -> StandbyStatus {
+        self.cancel.cancel();
+        let task = self.task.lock().take();
+        if let Some(task) = task {
+            let _ = task.await;
+        }
+        let mut status = self.status.write();
+        status.connected = false;
+        status.clone()
+    }
+
+    /// One connection: snapshot, then changes until it drops
+    async fn follow(
+        transport: &QuicTransport,
+        active_addr: SocketAddr,
+        repository: &dyn SessionRepository,
+        status: &RwLock<StandbyStatus>,
+    ) -> FailoverResult<()> {
+        let conn = transport.connect(active_addr).await?;
+        let mut stream = conn.accept_uni().await.map_err(NetworkError::from)?;
+        {
+            let mut status = status.write();
+            status.connected = true;
+            status.connects += 1;
+            status.snapshot_sessions = 0;
+        }
+        tracing::info!(%active_addr, "Following the active");
+
+        let mut snapshot_seq = 0;
+        while let Some(frame) = read_frame(&mut stream).await? {
+            let now = chrono::Utc::now();
+            match frame {
+                ReplicationFrame::Session { state } => {
+                    repository.save(&state).await?;
+                    status.write().snapshot_sessions += 1;
+                }
+                ReplicationFrame::Profile { profile } => {
+                    repository.save_profile(&profile).await?;
+                }
+                ReplicationFrame::SnapshotDone { seq } => {
+                    snapshot_seq = seq;
+                    let mut status = status.write();
+                    status.synced = true;
+                    status.last_seq = seq;
+                    status.last_update_at = Some(now.timestamp());
+                    tracing::info!(
+                        sessions = status.snapshot_sessions,
+                        seq,
+                        "Snapshot from the active applied"
+                    );
+                }
+                ReplicationFrame::Change { record } => {
+                    // Already in the snapshot
+                    if record.seq <= snapshot_seq {
+                        continue;
+                    }
+                    record.change.apply(repository).await?;
+                    let mut status = status.write();
+                    status.last_seq = record.seq;
+                    status.changes_applied += 1;
+                    status.lag_ms = Some((now.timestamp_millis() - record.at_ms).max(0) as u64);
+                    status.last_update_at = Some(now.timestamp());
+                }
+            }
+        }
+        Err(FailoverError::StreamEnded)
+    }
+}
+
+impl Drop for StandbyReplica {
+    fn drop(&mut self) {
+        self.cancel.cancel();
+    }
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+    use super::*;
+    use crate::chunk::{ChunkManager, Priority};
+    use crate::failover::{ReplicatingRepository, ReplicationLog, ReplicationServer};
+    use crate::network::ConnectionConfig;
+    use crate::session::{SessionState, SessionStore};
+
+    async fn transport() -> QuicTransport {
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        QuicTransport::new(ConnectionConfig {
+            bind_addr: "127.0.0.1:0".parse().unwrap(),
+            ..Default::default()
+        })
+        .await
+        .unwrap()
+    }
+
+    /// Poll until `done` holds, for up to five seconds
+    async fn eventually<F, Fut>(mut done: F)
+    where
+        F: FnMut() -> Fut,
+        Fut: std::future::Future<Output = bool>,
+    {
+        for _ in 0..100 {
+            if done().await {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        panic!("standby didn't catch up");
+    }
+
+    #[tokio::test]
+    async fn test_standby_mirrors_snapshot_then_changes() {
+        let log = Arc::new(ReplicationLog::default());
+        let active: Arc<dyn SessionRepository> = Arc::new(ReplicatingRepository::new(
+            SessionStore::new_in_memory().await.unwrap(),
+            log.clone(),
+        ));
+        let (manifest, _) = ChunkManager::new(1024, 4, 2)
+            .unwrap()
+            .split_bytes(&[3u8; 4096], "a.bin".into(), "a".into(), Priority::High)
+            .unwrap();
+        // Made before the standby connects, so it arrives in the snapshot
+        active
+            .save(&SessionState::new("s1".into(), "a".into(), manifest))
+            .await
+            .unwrap();
+        let server = ReplicationServer::start(transport().await, log, active.clone());
+
+        let mirror: Arc<dyn SessionRepository> =
+            Arc::new(SessionStore::new_in_memory().await.unwrap());
+        let standby = StandbyReplica::start(
+            Arc::new(transport().await),
+            server.local_addr().unwrap(),
+            mirror.clone(),
+            Duration::from_millis(100),
+        );
+        eventually(|| async { standby.status().synced }).await;
+        assert_eq!(standby.status().snapshot_sessions, 1);
+        assert!(mirror.load("s1").await.unwrap().is_some());
+
+        active
+            .mark_chunk_completed_with_bytes("s1", 2, 1024)
+            .await
+            .unwrap();
+        eventually(|| async {
+            mirror
+                .load("s1")
+                .await
+                .unwrap()
+                .is_some_and(|s| s.completed_chunks.contains(&2))
+        })
+        .await;
+        assert_eq!(server.status().connected_standbys, 1);
+
+        let status = standby.stop().await;
+        assert!(!status.connected);
+        assert_eq!(status.last_seq, 2);
+        assert_eq!(status.changes_applied, 1);
+        let mirrored = mirror.load("s1").await.unwrap().unwrap();
+        assert_eq!(mirrored.metrics.bytes_transferred, 1024);
+    }
+}
@@ -0,0 +1,260 @@
+use crate::session::{
+    ProgressSample, SessionRepository, SessionResult, SessionState, SessionStatus, TransferProfile,
+};
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use std::time::Duration;
+
+/// Which side of an active/standby pair a coordinator is
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FailoverRole {
+    /// No pair; nothing is replicated
+    #[default]
+    Standalone,
+    /// Runs transfers and streams session changes to standbys
+    Active,
+    /// Mirrors the active's sessions and takes no transfers until promoted
+    Standby,
+}
+
+impl std::str::FromStr for FailoverRole {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "standalone" => Ok(Self::Standalone),
+            "active" => Ok(Self::Active),
+            "standby" => Ok(Self::Standby),
+            other => Err(format!(
+                "unknown role {other:?} (expected standalone, active or standby)"
+            )),
+        }
+    }
+}
+
+/// Active/standby replication settings
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FailoverConfig {
+    pub role: FailoverRole,
+    /// Where the active listens for standbys (QUIC)
+    pub replication_addr: Option<SocketAddr>,
+    /// The active's `replication_addr`, for a standby to follow
+    pub active_addr: Option<SocketAddr>,
+    /// Wait before a standby reconnects after losing the active
+    pub reconnect_secs: u64,
+}
+
+impl Default for FailoverConfig {
+    fn default() -> Self {
+        Self {
+            role: FailoverRole::Standalone,
+            replication_addr: None,
+            active_addr: None,
+            reconnect_secs: 2,
+        }
+    }
+}
+
+impl FailoverConfig {
+    pub fn reconnect_delay(&self) -> Duration {
+        Duration::from_secs(self.reconnect_secs)
+    }
+}
+
+/// One write to the active's session storage, as replayed on a standby
+///
+/// Each variant mirrors a [`SessionRepository`] write. Timeseries are only
+/// sent as they change; a snapshot leaves them out.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SessionChange {
+    Saved {
+        state: Box<SessionState>,
+    },
+    ChunkCompleted {
+        session_id: String,
+        chunk_number: u32,
+        bytes: u64,
+    },
+    SkippedDuplicate {
+        session_id: String,
+    },
+    ChunkFailed {
+        session_id: String,
+        chunk_number: u32,
+    },
+    ChunkNacked {
+        session_id: String,
+        chunk_number: u32,
+    },
+    ChunksLost {
+        session_id: String,
+        chunk_numbers: Vec<u32>,
+    },
+    Status {
+        session_id: String,
+        status: SessionStatus,
+    },
+    Timeseries {
+        session_id: String,
+        samples: Vec<ProgressSample>,
+    },
+    ProfileSaved {
+        profile: TransferProfile,
+    },
+    ProfileDeleted {
+        name: String,
+    },
+}
+
+impl SessionChange {
+    /// Make the same write to `repository`
+    pub async fn apply(&self, repository: &dyn SessionRepository) -> SessionResult<()> {
+        match self {
+            Self::Saved { state } => repository.save(state).await,
+            Self::ChunkCompleted {
+                session_id,
+                chunk_number,
+                bytes,
+            } => {
+                // Replayed over a snapshot that has it already; its bytes
+                // were counted there
+                let done = repository
+                    .load(session_id)
+                    .await?
+                    .is_some_and(|state| state.completed_chunks.contains(chunk_number));
+                if done {
+                    return Ok(());
+                }
+                repository
+                    .mark_chunk_completed_with_bytes(session_id, *chunk_number, *bytes)
+                    .await
+            }
+            Self::SkippedDuplicate { session_id } => {
+                repository.mark_skipped_duplicate(session_id).await
+            }
+            Self::ChunkFailed {
+                session_id,
+                chunk_number,
+            } => {
+                repository
+                    .mark_chunk_failed(session_id, *chunk_number)
+                    .await
+            }
+            Self::ChunkNacked {
+                session_id,
+                chunk_number,
+            } => {
+                repository
+                    .mark_chunk_nacked(session_id, *chunk_number)
+                    .await
+            }
+            Self::ChunksLost {
+                session_id,
+                chunk_numbers,
+            } => repository.mark_chunks_lost(session_id, chunk_numbers).await,
+            Self::Status { session_id, status } => {
+                repository.update_status(session_id, status.clone()).await
+            }
+            Self::Timeseries {
+                session_id,
+                samples,
+            } => repository.save_timeseries(session_id, samples).await,
+            Self::ProfileSaved { profile } => repository.save_profile(profile).await.map(|_| ()),
+            Self::ProfileDeleted { name } => repository.delete_profile(name).await.map(|_| ()),
+        }
+    }
+}
+
+/// A change with its place in the active's replication log
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplicationRecord {
+    /// Increases by one per change, starting at 1
+    pub seq: u64,
+    /// Unix time (ms) the active made the change
+    pub at_ms: i64,
+    pub change: SessionChange,
+}
+
+/// What the active sends a standby, in order: the snapshot (sessions, then
+/// profiles), its end, then changes as they happen
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "frame", rename_all = "snake_case")]
+pub enum ReplicationFrame {
+    Session {
+        state: Box<SessionState>,
+    },
+    Profile {
+        profile: TransferProfile,
+    },
+    /// The snapshot holds every change up to and including `seq`, and
+    /// maybe some after it, which are then sent again and replay harmlessly
+    SnapshotDone {
+        seq: u64,
+    },
+    Change {
+        record: ReplicationRecord,
+    },
+}
+
+/// The active's side of replication
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReplicationStatus {
+    pub listen_addr: SocketAddr,
+    /// Latest change logged
+    pub last_seq: u64,
+    pub connected_standbys: usize,
+}
+
+/// How far a standby has caught up with the active
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StandbyStatus {
+    pub active_addr: Option<SocketAddr>,
+    /// Receiving changes right now
+    pub connected: bool,
+    /// The latest snapshot has been applied in full
+    pub synced: bool,
+    /// Latest change applied
+    pub last_seq: u64,
+    /// Sessions in the latest snapshot
+    pub snapshot_sessions: usize,
+    /// Changes applied since the standby started
+    pub changes_applied: u64,
+    /// How long after the active made it the latest change was applied
+    pub lag_ms: Option<u64>,
+    /// Unix time of the latest snapshot or change
+    pub last_update_at: Option<i64>,
+    /// Connections made to the active, the first included
+    pub connects: u64,
+    pub last_error: Option<String>,
+}
+
+/// Where a coordinator stands in an active/standby pair
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FailoverStatus {
+    pub role: FailoverRole,
+    /// Set on an active
+    pub replication: Option<ReplicationStatus>,
+    /// Set on a standby
+    pub standby: Option<StandbyStatus>,
+}
+
+/// A transfer that didn't resume on takeover
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SkippedSession {
+    pub session_id: String,
+    pub reason: String,
+}
+
+/// What a standby did when promoted
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TakeoverReport {
+    /// Latest change from the old active that made it across
+    pub last_seq: u64,
+    /// Transfers the old active was running, now running here
+    pub resumed: Vec<String>,
+    /// Transfers the old active was running that stay paused
+    pub skipped: Vec<SkippedSession>,
+}
@@ -0,0 +1,144 @@
+use crate::fault::types::{FaultConfig, FaultPoint, FaultStats, InjectedFault, PointStats};
+use parking_lot::RwLock;
+use rand::Rng;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+
+static INJECTOR: OnceLock<FaultInjector> = OnceLock::new();
+
+/// The process-wide injector every fault point consults
+pub fn injector() -> &'static FaultInjector {
+    INJECTOR.get_or_init(FaultInjector::default)
+}
+
+/// Fail at `point` with its configured probability
+pub fn check(point: FaultPoint) -> Result<(), InjectedFault> {
+    injector().check(point)
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    checked: AtomicU64,
+    injected: AtomicU64,
+}
+
+impl Counters {
+    fn snapshot(&self) -> PointStats {
+        PointStats {
+            checked: self.checked.load(Ordering::Relaxed),
+            injected: self.injected.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Fault probabilities and what they have done so far
+#[derive(Debug, Default)]
+pub struct FaultInjector {
+    config: RwLock<FaultConfig>,
+    session_write: Counters,
+    quic_send: Counters,
+    state_transition: Counters,
+}
+
+impl FaultInjector {
+    /// Replace the probabilities; all must be within 0.0..=1.0
+    pub fn configure(&self, config: FaultConfig) -> Result<(), String> {
+        for point in FaultPoint::ALL {
+            let p = config.probability(point);
+            if !(0.0..=1.0).contains(&p) {
+                return Err(format!("{point} probability {p} is outside 0.0..=1.0"));
+            }
+        }
+        tracing::warn!(?config, "fault injection configured");
+        *self.config.write() = config;
+        Ok(())
+    }
+
+    /// Stop injecting and zero the counts
+    pub fn reset(&self) {
+        *self.config.write() = FaultConfig::default();
+        for point in FaultPoint::ALL {
+            let counters = self.counters(point);
+            counters.checked.store(0, Ordering::Relaxed);
+            counters.injected.store(0, Ordering::Relaxed);
+        }
+    }
+
+    pub fn config(&self) -> FaultConfig {
+        *self.config.read()
+    }
+
+    pub fn stats(&self) -> FaultStats {
+        FaultStats {
+            session_write: self.session_write.snapshot(),
+            quic_send: self.quic_send.snapshot(),
+            state_transition: self.state_transition.snapshot(),
+        }
+    }
+
+    /// Fail at `point` with its configured probability
+    pub fn check(&self, point: FaultPoint) -> Result<(), InjectedFault> {
+        let probability = self.config.read().probability(point);
+        let counters = self.counters(point);
+        counters.checked.fetch_add(1, Ordering::Relaxed);
+        if probability > 0.0 && rand::thread_rng().gen::<f32>() < probability {
+            counters.injected.fetch_add(1, Ordering::Relaxed);
+            tracing::debug!(%point, "injecting fault");
+            return Err(InjectedFault(point));
+        }
+        Ok(())
+    }
+
+    fn counters(&self, point: FaultPoint) -> &Counters {
+        match point {
+            FaultPoint::SessionWrite => &self.session_write,
+            FaultPoint::QuicSend => &self.quic_send,
+            FaultPoint::StateTransition => &self.state_transition,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_injects_at_configured_points() {
+        let injector = FaultInjector::default();
+        assert!(injector.check(FaultPoint::QuicSend).is_ok());
+
+        injector
+            .configure(FaultConfig {
+                quic_send: 1.0,
+                ..Default::default()
+            })
+            .unwrap();
+        for _ in 0..10 {
+            assert_eq!(
+                injector.check(FaultPoint::QuicSend),
+                Err(InjectedFault(FaultPoint::QuicSend))
+            );
+            assert!(injector.check(FaultPoint::SessionWrite).is_ok());
+        }
+
+        let stats = injector.stats();
+        assert_eq!(stats.quic_send.checked, 11);
+        assert_eq!(stats.quic_send.injected, 10);
+        assert_eq!(stats.session_write.injected, 0);
+
+        injector.reset();
+        assert!(!injector.config().is_active());
+        assert_eq!(injector.stats(), FaultStats::default());
+    }
+
+    #[test]
+    fn test_rejects_out_of_range_probability() {
+        let injector = FaultInjector::default();
+        let config = FaultConfig {
+            state_transition: 1.5,
+            ..Default::default()
+        };
+        assert!(injector.configure(config).is_err());
+        assert!(!injector.config().is_active());
+    }
+}
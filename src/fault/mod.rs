@@ -0,0 +1,19 @@
+//! Fault Injection Module
+//!
+//! Makes subsystems fail on purpose so chaos and stress tests can check the
+//! coordinator recovers from more than network loss. Only built with the
+//! `fault-injection` feature; release builds carry none of it.
+//!
+//! Faults can be injected at three points:
+//! - Session store writes, which fail as database errors
+//! - QUIC chunk sends, which fail as send errors
+//! - Transfer state machine transitions, which fail as invalid transitions
+//!
+//! Each point fails with its own probability, set process-wide through
+//! [`injector()`] or `PUT /api/v1/internal/faults`.
+
+pub mod injector;
+pub mod types;
+
+pub use injector::{check, injector, FaultInjector};
+pub use types::{FaultConfig, FaultPoint, FaultStats, InjectedFault};
@@ -0,0 +1,73 @@
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Where a fault can be injected
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FaultPoint {
+    SessionWrite,
+    QuicSend,
+    StateTransition,
+}
+
+impl FaultPoint {
+    pub const ALL: [FaultPoint; 3] = [
+        FaultPoint::SessionWrite,
+        FaultPoint::QuicSend,
+        FaultPoint::StateTransition,
+    ];
+}
+
+impl std::fmt::Display for FaultPoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            FaultPoint::SessionWrite => "session write",
+            FaultPoint::QuicSend => "QUIC send",
+            FaultPoint::StateTransition => "state transition",
+        })
+    }
+}
+
+/// Chance each call at a point fails (0.0 - 1.0)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FaultConfig {
+    pub session_write: f32,
+    pub quic_send: f32,
+    pub state_transition: f32,
+}
+
+impl FaultConfig {
+    pub fn probability(&self, point: FaultPoint) -> f32 {
+        match point {
+            FaultPoint::SessionWrite => self.session_write,
+            FaultPoint::QuicSend => self.quic_send,
+            FaultPoint::StateTransition => self.state_transition,
+        }
+    }
+
+    /// Whether any point can fail
+    pub fn is_active(&self) -> bool {
+        FaultPoint::ALL.iter().any(|&p| self.probability(p) > 0.0)
+    }
+}
+
+/// Calls seen and faults injected at one point
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PointStats {
+    pub checked: u64,
+    pub injected: u64,
+}
+
+/// Counts since the injector was last reset
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FaultStats {
+    pub session_write: PointStats,
+    pub quic_send: PointStats,
+    pub state_transition: PointStats,
+}
+
+/// A failure made up on purpose
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+#[error("injected {0} fault")]
+pub struct InjectedFault(pub FaultPoint);
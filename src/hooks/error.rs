@@ -0,0 +1,28 @@
+use std::path::PathBuf;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum HookError {
+    #[error("Hook '{hook}' rejected file {file_id}: {reason}")]
+    Rejected {
+        hook: String,
+        file_id: String,
+        reason: String,
+    },
+
+    #[error("Hook '{hook}' quarantined file {file_id} to {path:?}: {reason}")]
+    Quarantined {
+        hook: String,
+        file_id: String,
+        reason: String,
+        path: Option<PathBuf>,
+    },
+
+    #[error("Hook '{hook}' failed: {reason}")]
+    Failed { hook: String, reason: String },
+
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+pub type HookResult<T> = Result<T, HookError>;
@@ -0,0 +1,28 @@
+//! Chunk-level Hook Module
+//!
+//! Lets deployments plug their own checks (antivirus scanners, content
+//! filters, audit loggers) into the transfer pipeline.
+//!
+//! Hooks are invoked at three points:
+//! - Before a file's chunks are enqueued on the sender
+//! - After a file has been reconstructed on the receiver
+//! - Before a received file is served over the REST download endpoint
+//!
+//! Each hook is registered with a failure policy that decides what happens
+//! when it denies a file or errors out: reject, quarantine, or log only.
+//!
+//! A receiver can also hold every file back until it is released; see
+//! [`QuarantineArea`].
+
+pub mod error;
+pub mod quarantine;
+pub mod registry;
+pub mod types;
+
+pub use error::{HookError, HookResult};
+pub use quarantine::{
+    HeldFile, QuarantineArea, QuarantineError, QuarantineEvent, QuarantineResult, ReleaseStage,
+    QUARANTINE_INDEX_FILE,
+};
+pub use registry::{FileHook, HookRegistry};
+pub use types::{FailurePolicy, HookContext, HookOutcome, HookPoint, HookVerdict};
@@ -0,0 +1,383 @@
+//! Received files held back until someone releases them
+//!
+//! With a [`QuarantineArea`], a receiver rebuilds files into the area's
+//! directory instead of its save directory. Each file moves through
+//! [`ReleaseStage`]s: quarantined while `AfterReconstruct` hooks scan it,
+//! then held (or rejected by a hook), then released, which moves it to its
+//! final destination in one step. Stages are kept in an index next to the
+//! files, so held files survive a restart, and every change is broadcast as
+//! a [`QuarantineEvent`].
+//!
+//! This is separate from the hook registry's quarantine directory, which
+//! only ever receives files a hook denied.
+
+use crate::integrity::ChecksumType;
+use crate::sync::IndexFile;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+use tokio::sync::broadcast;
+
+/// File name of the index under the quarantine directory
+pub const QUARANTINE_INDEX_FILE: &str = ".quarantine.json";
+
+#[derive(Error, Debug)]
+pub enum QuarantineError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("No quarantined file {0}")]
+    NotFound(String),
+
+    #[error("File {id} is {stage:?} and can't be released")]
+    NotReleasable { id: String, stage: ReleaseStage },
+
+    #[error("Destination {0:?} already exists")]
+    DestinationExists(PathBuf),
+
+    #[error("Quarantine index {path}: {reason}")]
+    Index { path: PathBuf, reason: String },
+}
+
+pub type QuarantineResult<T> = Result<T, QuarantineError>;
+
+/// Where a received file is on its way out of quarantine
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReleaseStage {
+    /// Rebuilt into the quarantine directory; hooks haven't passed it yet
+    Quarantined,
+    /// Passed every hook; waiting to be released
+    Held,
+    /// Denied by a hook; never released
+    Rejected,
+    /// Moved to its destination
+    Released,
+}
+
+/// A received file and how far it has got
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HeldFile {
+    /// Transfer id, as used in the release URL
+    pub id: String,
+    /// The sender's id for the file
+    pub file_id: String,
+    /// Name in the quarantine directory and at the destination
+    pub filename: String,
+    /// Where the file is now
+    pub path: PathBuf,
+    pub size: u64,
+    /// Checksum the sender gave for the file, hashed with `checksum_algorithm`
+    pub checksum: [u8; 32],
+    /// Blake3 for files held before the algorithm was kept
+    #[serde(default)]
+    pub checksum_algorithm: ChecksumType,
+    /// The rebuilt file matched `checksum`
+    pub verified: bool,
+    /// Sender's QUIC address
+    pub source: SocketAddr,
+    /// The transfer's chunk size
+    pub block_size: usize,
+    pub stage: ReleaseStage,
+    /// Why the file was rejected
+    pub reason: Option<String>,
+    /// Unix time of the latest stage change
+    pub updated_at: i64,
+}
+
+/// A file reaching a new stage
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuarantineEvent {
+    pub id: String,
+    pub filename: String,
+    pub stage: ReleaseStage,
+    pub path: PathBuf,
+    pub reason: Option<String>,
+    /// Unix time of the change
+    pub at: i64,
+}
+
+impl From<&HeldFile> for QuarantineEvent {
+    fn from(file: &HeldFile) -> Self {
+        Self {
+            id: file.id.clone(),
+            filename: file.filename.clone(),
+            stage: file.stage,
+            path: file.path.clone(),
+            reason: file.reason.clone(),
+            at: file.updated_at,
+        }
+    }
+}
+
+/// A directory of received files awaiting release, with their stages
+#[derive(Debug)]
+pub struct QuarantineArea {
+    dir: PathBuf,
+    index: IndexFile,
+    files: Mutex<BTreeMap<String, HeldFile>>,
+    events: broadcast::Sender<QuarantineEvent>,
+}
+
+impl QuarantineArea {
+    /// Use `dir`, creating it if needed and loading the stages kept there
+    pub fn open(dir: impl Into<PathBuf>) -> QuarantineResult<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+        let index = dir.join(QUARANTINE_INDEX_FILE);
+        let files = if index.exists() {
+            let data = std::fs::read(&index)?;
+            let list: Vec<HeldFile> =
+                serde_json::from_slice(&data).map_err(|e| QuarantineError::Index {
+                    path: index.clone(),
+                    reason: e.to_string(),
+                })?;
+            list.into_iter().map(|f| (f.id.clone(), f)).collect()
+        } else {
+            BTreeMap::new()
+        };
+        Ok(Self {
+            dir,
+            index: IndexFile::new(index),
+            files: Mutex::new(files),
+            events: broadcast::channel(256).0,
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Where a file named `filename` is rebuilt
+    pub fn path_for(&self, filename: &str) -> PathBuf {
+        self.dir.join(filename)
+    }
+
+    /// Stage changes from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<QuarantineEvent> {
+        self.events.subscribe()
+    }
+
+    pub fn get(&self, id: &str) -> Option<HeldFile> {
+        self.files.lock().get(id).cloned()
+    }
+
+    /// Every file the area has seen, released ones included
+    pub fn files(&self) -> Vec<HeldFile> {
+        self.files.lock().values().cloned().collect()
+    }
+
+    /// Record a file just rebuilt into the directory, replacing any earlier
+    /// record with its id
+    pub async fn admit(&self, mut file: HeldFile) -> QuarantineResult<HeldFile> {
+        file.stage = ReleaseStage::Quarantined;
+        file.reason = None;
+        file.updated_at = chrono::Utc::now().timestamp();
+        self.files.lock().insert(file.id.clone(), file.clone());
+        self.save().await?;
+        self.notify(&file);
+        Ok(file)
+    }
+
+    /// The hooks passed `id`; hold it for release, noting whether it
+    /// matched the sender's checksum
+    pub async fn hold(&self, id: &str, verified: bool) -> QuarantineResult<HeldFile> {
+        self.advance(id, |file| {
+            file.stage = ReleaseStage::Held;
+            file.verified = verified;
+        })
+        .await
+    }
+
+    /// A hook denied `id`; `path` is where the hook left it, if it moved it
+    pub async fn reject(
+        &self,
+        id: &str,
+        reason: impl Into<String>,
+        path: Option<PathBuf>,
+    ) -> QuarantineResult<HeldFile> {
+        let reason = reason.into();
+        self.advance(id, |file| {
+            file.stage = ReleaseStage::Rejected;
+            file.reason = Some(reason);
+            if let Some(path) = path {
+                file.path = path;
+            }
+        })
+        .await
+    }
+
+    /// Move a held file into `dest_dir` under its name
+    ///
+    /// The file appears at its destination whole or not at all, and an
+    /// existing file there is never replaced. `dest_dir` must be on the same
+    /// filesystem as the quarantine directory.
+    pub async fn release(&self, id: &str, dest_dir: &Path) -> QuarantineResult<HeldFile> {
+        let file = self.move_to(id, dest_dir)?;
+        self.save().await?;
+        self.notify(&file);
+        Ok(file)
+    }
+
+    fn move_to(&self, id: &str, dest_dir: &Path) -> QuarantineResult<HeldFile> {
+        let mut files = self.files.lock();
+        let file = files
+            .get(id)
+            .ok_or_else(|| QuarantineError::NotFound(id.to_string()))?;
+        if file.stage != ReleaseStage::Held {
+            return Err(QuarantineError::NotReleasable {
+                id: id.to_string(),
+                stage: file.stage,
+            });
+        }
+
+        let target = dest_dir.join(&file.filename);
+        // A hard link fails if the target exists, where a rename would
+        // replace it
+        match std::fs::hard_link(&file.path, &target) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                return Err(QuarantineError::DestinationExists(target));
+            }
+            Err(e) => return Err(e.into()),
+        }
+        std::fs::remove_file(&file.path)?;
+
+        let file = files.get_mut(id).expect("looked up above");
+        file.path = target;
+        file.stage = ReleaseStage::Released;
+        file.updated_at = chrono::Utc::now().timestamp();
+        Ok(file.clone())
+    }
+
+    async fn advance(
+        &self,
+        id: &str,
+        update: impl FnOnce(&mut HeldFile),
+    ) -> QuarantineResult<HeldFile> {
+        let file = {
+            let mut files = self.files.lock();
+            let file = files
+                .get_mut(id)
+                .ok_or_else(|| QuarantineError::NotFound(id.to_string()))?;
+            update(file);
+            file.updated_at = chrono::Utc::now().timestamp();
+            file.clone()
+        };
+        self.save().await?;
+        self.notify(&file);
+        Ok(file)
+    }
+
+    fn notify(&self, file: &HeldFile) {
+        tracing::info!(
+            id = %file.id,
+            stage = ?file.stage,
+            path = %file.path.display(),
+            "Quarantined file changed stage"
+        );
+        // Nobody listening is fine; the index has the stage
+        let _ = self.events.send(QuarantineEvent::from(file));
+    }
+
+    async fn save(&self) -> QuarantineResult<()> {
+        self.index
+            .save(|| self.files.lock().values().cloned().collect::<Vec<_>>())
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn received(area: &QuarantineArea, id: &str, contents: &[u8]) -> HeldFile {
+        let filename = format!("received_{id}");
+        let path = area.path_for(&filename);
+        std::fs::write(&path, contents).unwrap();
+        HeldFile {
+            id: id.to_string(),
+            file_id: id.to_string(),
+            filename,
+            path,
+            size: contents.len() as u64,
+            checksum: [0; 32],
+            checksum_algorithm: ChecksumType::Blake3,
+            verified: true,
+            source: "127.0.0.1:5001".parse().unwrap(),
+            block_size: 1024,
+            stage: ReleaseStage::Released,
+            reason: None,
+            updated_at: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_release_moves_held_files_only() {
+        let save_dir = TempDir::new().unwrap();
+        let held_dir = save_dir.path().join(".held");
+        let area = QuarantineArea::open(&held_dir).unwrap();
+        let mut events = area.subscribe();
+
+        let file = area.admit(received(&area, "a", b"payload")).await.unwrap();
+        assert_eq!(file.stage, ReleaseStage::Quarantined);
+        // Still being scanned
+        assert!(matches!(
+            area.release("a", save_dir.path()).await,
+            Err(QuarantineError::NotReleasable {
+                stage: ReleaseStage::Quarantined,
+                ..
+            })
+        ));
+
+        area.hold("a", true).await.unwrap();
+        let released = area.release("a", save_dir.path()).await.unwrap();
+        let target = save_dir.path().join("received_a");
+        assert_eq!(released.stage, ReleaseStage::Released);
+        assert_eq!(released.path, target);
+        assert_eq!(std::fs::read(&target).unwrap(), b"payload");
+        assert!(!file.path.exists());
+
+        let stages: Vec<_> = std::iter::from_fn(|| events.try_recv().ok())
+            .map(|e| e.stage)
+            .collect();
+        assert_eq!(
+            stages,
+            [
+                ReleaseStage::Quarantined,
+                ReleaseStage::Held,
+                ReleaseStage::Released
+            ]
+        );
+
+        // Rejected files stay put, and an existing destination is kept
+        area.admit(received(&area, "b", b"bad")).await.unwrap();
+        area.reject("b", "infected", None).await.unwrap();
+        assert!(area.release("b", save_dir.path()).await.is_err());
+        std::fs::write(save_dir.path().join("received_c"), b"old").unwrap();
+        area.admit(received(&area, "c", b"new")).await.unwrap();
+        area.hold("c", false).await.unwrap();
+        assert!(matches!(
+            area.release("c", save_dir.path()).await,
+            Err(QuarantineError::DestinationExists(_))
+        ));
+        assert_eq!(
+            std::fs::read(save_dir.path().join("received_c")).unwrap(),
+            b"old"
+        );
+
+        // Stages survive reopening
+        let reopened = QuarantineArea::open(&held_dir).unwrap();
+        assert_eq!(reopened.get("a").unwrap().stage, ReleaseStage::Released);
+        assert_eq!(
+            reopened.get("b").unwrap().reason.as_deref(),
+            Some("infected")
+        );
+        assert_eq!(reopened.get("c").unwrap().stage, ReleaseStage::Held);
+    }
+}
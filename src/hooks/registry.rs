@@ -0,0 +1,408 @@
+use super::error::{HookError, HookResult};
+use super::types::{FailurePolicy, HookContext, HookOutcome, HookPoint, HookVerdict};
+use futures::future::BoxFuture;
+use parking_lot::RwLock;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// A pluggable check run against whole files at defined pipeline points
+///
+/// Implementations return a boxed future so that scanners can shell out,
+/// talk to a daemon, or read the file asynchronously.
+pub trait FileHook: Send + Sync {
+    /// Name used in logs and errors
+    fn name(&self) -> &str;
+
+    /// Points at which this hook should be invoked
+    fn points(&self) -> &[HookPoint];
+
+    /// Inspect the file described by `ctx`
+    fn check<'a>(&'a self, ctx: &'a HookContext) -> BoxFuture<'a, HookResult<HookVerdict>>;
+}
+
+struct RegisteredHook {
+    hook: Arc<dyn FileHook>,
+    policy: FailurePolicy,
+}
+
+/// Ordered set of hooks plus the quarantine location they share
+pub struct HookRegistry {
+    hooks: RwLock<Vec<RegisteredHook>>,
+    quarantine_dir: RwLock<Option<PathBuf>>,
+}
+
+impl HookRegistry {
+    pub fn new() -> Self {
+        Self {
+            hooks: RwLock::new(Vec::new()),
+            quarantine_dir: RwLock::new(None),
+        }
+    }
+
+    /// Create a registry that moves quarantined files into `dir`
+    pub fn with_quarantine_dir(dir: impl Into<PathBuf>) -> Self {
+        let registry = Self::new();
+        registry.set_quarantine_dir(dir);
+        registry
+    }
+
+    pub fn set_quarantine_dir(&self, dir: impl Into<PathBuf>) {
+        *self.quarantine_dir.write() = Some(dir.into());
+    }
+
+    pub fn quarantine_dir(&self) -> Option<PathBuf> {
+        self.quarantine_dir.read().clone()
+    }
+
+    /// Register a hook; hooks run in registration order
+    pub fn register(&self, hook: Arc<dyn FileHook>, policy: FailurePolicy) {
+        self.hooks.write().push(RegisteredHook { hook, policy });
+    }
+
+    /// Remove every hook with the given name, returning how many were removed
+    pub fn unregister(&self, name: &str) -> usize {
+        let mut hooks = self.hooks.write();
+        let before = hooks.len();
+        hooks.retain(|h| h.hook.name() != name);
+        before - hooks.len()
+    }
+
+    /// Names of registered hooks, in invocation order
+    pub fn hook_names(&self) -> Vec<String> {
+        self.hooks
+            .read()
+            .iter()
+            .map(|h| h.hook.name().to_string())
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.hooks.read().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hooks.read().is_empty()
+    }
+
+    /// Run every hook registered for `ctx.point`
+    ///
+    /// Stops at the first denial whose policy is `Reject` or `Quarantine`.
+    pub async fn run(&self, ctx: &HookContext) -> HookResult<HookOutcome> {
+        // Snapshot so the lock isn't held across awaits
+        let hooks: Vec<(Arc<dyn FileHook>, FailurePolicy)> = self
+            .hooks
+            .read()
+            .iter()
+            .filter(|h| h.hook.points().contains(&ctx.point))
+            .map(|h| (h.hook.clone(), h.policy))
+            .collect();
+
+        let mut outcome = HookOutcome::default();
+
+        for (hook, policy) in hooks {
+            outcome.hooks_run += 1;
+
+            let reason = match hook.check(ctx).await {
+                Ok(HookVerdict::Allow) => continue,
+                Ok(HookVerdict::Deny { reason }) => reason,
+                Err(e) => e.to_string(),
+            };
+
+            match policy {
+                FailurePolicy::LogOnly => {
+                    tracing::warn!(
+                        hook = hook.name(),
+                        file_id = %ctx.file_id,
+                        point = ?ctx.point,
+                        "Hook denied file (log-only): {}",
+                        reason
+                    );
+                    outcome
+                        .warnings
+                        .push(format!("{}: {}", hook.name(), reason));
+                }
+                FailurePolicy::Reject => {
+                    return Err(HookError::Rejected {
+                        hook: hook.name().to_string(),
+                        file_id: ctx.file_id.clone(),
+                        reason,
+                    });
+                }
+                FailurePolicy::Quarantine => {
+                    let path = match &ctx.path {
+                        Some(path) => self.quarantine(path, ctx).await?,
+                        None => None,
+                    };
+                    return Err(HookError::Quarantined {
+                        hook: hook.name().to_string(),
+                        file_id: ctx.file_id.clone(),
+                        reason,
+                        path,
+                    });
+                }
+            }
+        }
+
+        Ok(outcome)
+    }
+
+    /// Move a file into the quarantine directory, if one is configured
+    ///
+    /// A sender's file is the operator's own, so it is copied rather than
+    /// moved. The copy is named after the time and session it was
+    /// quarantined in, so files of the same name don't replace each other.
+    async fn quarantine(&self, path: &Path, ctx: &HookContext) -> HookResult<Option<PathBuf>> {
+        let Some(dir) = self.quarantine_dir() else {
+            return Ok(None);
+        };
+        let Some(file_name) = path.file_name() else {
+            return Ok(None);
+        };
+
+        tokio::fs::create_dir_all(&dir).await?;
+        let target = claim_target(&dir, &file_name.to_string_lossy(), ctx).await?;
+        if ctx.point.is_sender_side() {
+            tokio::fs::copy(path, &target).await?;
+        } else {
+            move_file(path, &target).await?;
+        }
+        Ok(Some(target))
+    }
+}
+
+/// Create an empty file in `dir` to quarantine `file_name` into, named
+/// `<time>-<session>-<file_name>`
+async fn claim_target(dir: &Path, file_name: &str, ctx: &HookContext) -> std::io::Result<PathBuf> {
+    let mut prefix = chrono::Utc::now().format("%Y%m%dT%H%M%S%.3fZ").to_string();
+    if let Some(session_id) = &ctx.session_id {
+        prefix.push('-');
+        prefix.push_str(&session_id.replace(['/', '\\', ':'], "_"));
+    }
+    let mut attempt = 0u32;
+    loop {
+        let name = match attempt {
+            0 => format!("{prefix}-{file_name}"),
+            n => format!("{prefix}-{n}-{file_name}"),
+        };
+        let target = dir.join(name);
+        match tokio::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&target)
+            .await
+        {
+            Ok(_) => return Ok(target),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => attempt += 1,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Rename `from` to `to`, copying and removing it when they are on
+/// different filesystems
+async fn move_file(from: &Path, to: &Path) -> std::io::Result<()> {
+    match tokio::fs::rename(from, to).await {
+        Err(e) if e.kind() == std::io::ErrorKind::CrossesDevices => {
+            tokio::fs::copy(from, to).await?;
+            tokio::fs::remove_file(from).await
+        }
+        moved => moved,
+    }
+}
+
+impl Default for HookRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tempfile::TempDir;
+
+    struct StaticHook {
+        name: &'static str,
+        points: Vec<HookPoint>,
+        verdict: HookVerdict,
+        calls: AtomicUsize,
+    }
+
+    impl StaticHook {
+        fn new(name: &'static str, points: Vec<HookPoint>, verdict: HookVerdict) -> Arc<Self> {
+            Arc::new(Self {
+                name,
+                points,
+                verdict,
+                calls: AtomicUsize::new(0),
+            })
+        }
+    }
+
+    impl FileHook for StaticHook {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        fn points(&self) -> &[HookPoint] {
+            &self.points
+        }
+
+        fn check<'a>(&'a self, _ctx: &'a HookContext) -> BoxFuture<'a, HookResult<HookVerdict>> {
+            Box::pin(async move {
+                self.calls.fetch_add(1, Ordering::SeqCst);
+                Ok(self.verdict.clone())
+            })
+        }
+    }
+
+    fn deny(reason: &str) -> HookVerdict {
+        HookVerdict::Deny {
+            reason: reason.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_allow_runs_matching_hooks_only() {
+        let registry = HookRegistry::new();
+        let enqueue = StaticHook::new(
+            "enqueue",
+            vec![HookPoint::BeforeEnqueue],
+            HookVerdict::Allow,
+        );
+        let download = StaticHook::new(
+            "download",
+            vec![HookPoint::BeforeDownload],
+            HookVerdict::Allow,
+        );
+        registry.register(enqueue.clone(), FailurePolicy::Reject);
+        registry.register(download.clone(), FailurePolicy::Reject);
+
+        let ctx = HookContext::new(HookPoint::BeforeEnqueue, "file");
+        let outcome = registry.run(&ctx).await.unwrap();
+
+        assert_eq!(outcome.hooks_run, 1);
+        assert!(outcome.is_clean());
+        assert_eq!(enqueue.calls.load(Ordering::SeqCst), 1);
+        assert_eq!(download.calls.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn test_reject_policy_stops_pipeline() {
+        let registry = HookRegistry::new();
+        let scanner = StaticHook::new("av", vec![HookPoint::AfterReconstruct], deny("EICAR"));
+        let later = StaticHook::new(
+            "later",
+            vec![HookPoint::AfterReconstruct],
+            HookVerdict::Allow,
+        );
+        registry.register(scanner, FailurePolicy::Reject);
+        registry.register(later.clone(), FailurePolicy::Reject);
+
+        let ctx = HookContext::new(HookPoint::AfterReconstruct, "file");
+        let result = registry.run(&ctx).await;
+
+        assert!(matches!(result, Err(HookError::Rejected { ref reason, .. }) if reason == "EICAR"));
+        assert_eq!(later.calls.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn test_log_only_policy_records_warning() {
+        let registry = HookRegistry::new();
+        registry.register(
+            StaticHook::new("filter", vec![HookPoint::BeforeDownload], deny("flagged")),
+            FailurePolicy::LogOnly,
+        );
+
+        let ctx = HookContext::new(HookPoint::BeforeDownload, "file");
+        let outcome = registry.run(&ctx).await.unwrap();
+
+        assert_eq!(outcome.hooks_run, 1);
+        assert_eq!(outcome.warnings, vec!["filter: flagged".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_quarantine_moves_file() {
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join("payload.bin");
+        tokio::fs::write(&file, b"bad bytes").await.unwrap();
+
+        let registry = HookRegistry::with_quarantine_dir(dir.path().join("quarantine"));
+        registry.register(
+            StaticHook::new("av", vec![HookPoint::AfterReconstruct], deny("infected")),
+            FailurePolicy::Quarantine,
+        );
+
+        let registry = &registry;
+        let quarantine = |session: &str| {
+            let ctx = HookContext::new(HookPoint::AfterReconstruct, "payload")
+                .with_path(&file)
+                .with_session(session);
+            async move {
+                match registry.run(&ctx).await {
+                    Err(HookError::Quarantined {
+                        path: Some(path), ..
+                    }) => path,
+                    other => panic!("expected quarantine, got {:?}", other),
+                }
+            }
+        };
+        let first = quarantine("session-1").await;
+        assert!(!file.exists());
+        assert_eq!(first.parent().unwrap(), dir.path().join("quarantine"));
+        let name = first.file_name().unwrap().to_string_lossy().to_string();
+        assert!(name.ends_with("-session-1-payload.bin"), "{name}");
+        assert_eq!(tokio::fs::read(&first).await.unwrap(), b"bad bytes");
+
+        // A second file of the same name doesn't replace the first
+        tokio::fs::write(&file, b"more bad bytes").await.unwrap();
+        let second = quarantine("session-1").await;
+        assert_ne!(first, second);
+        assert_eq!(tokio::fs::read(&first).await.unwrap(), b"bad bytes");
+        assert_eq!(tokio::fs::read(&second).await.unwrap(), b"more bad bytes");
+    }
+
+    #[tokio::test]
+    async fn test_quarantine_before_enqueue_keeps_source() {
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join("survey.bin");
+        tokio::fs::write(&file, b"operator data").await.unwrap();
+
+        let registry = HookRegistry::with_quarantine_dir(dir.path().join("quarantine"));
+        registry.register(
+            StaticHook::new("dlp", vec![HookPoint::BeforeEnqueue], deny("restricted")),
+            FailurePolicy::Quarantine,
+        );
+
+        let ctx = HookContext::new(HookPoint::BeforeEnqueue, "survey").with_path(&file);
+        match registry.run(&ctx).await {
+            Err(HookError::Quarantined {
+                path: Some(path), ..
+            }) => {
+                assert_eq!(tokio::fs::read(&file).await.unwrap(), b"operator data");
+                assert_eq!(tokio::fs::read(&path).await.unwrap(), b"operator data");
+            }
+            other => panic!("expected quarantine, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_register_and_unregister() {
+        let registry = HookRegistry::new();
+        assert!(registry.is_empty());
+
+        registry.register(
+            StaticHook::new("a", vec![HookPoint::BeforeEnqueue], HookVerdict::Allow),
+            FailurePolicy::Reject,
+        );
+        registry.register(
+            StaticHook::new("b", vec![HookPoint::BeforeEnqueue], HookVerdict::Allow),
+            FailurePolicy::LogOnly,
+        );
+
+        assert_eq!(registry.hook_names(), vec!["a", "b"]);
+        assert_eq!(registry.unregister("a"), 1);
+        assert_eq!(registry.len(), 1);
+    }
+}
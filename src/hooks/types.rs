@@ -0,0 +1,97 @@
+use crate::chunk::FileManifest;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Pipeline stage at which a hook is invoked
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HookPoint {
+    /// Sender side, after chunking and before chunks hit the priority queue
+    BeforeEnqueue,
+    /// Receiver side, after the file has been written to disk
+    AfterReconstruct,
+    /// Receiver side, before a file is streamed to a REST client
+    BeforeDownload,
+}
+
+impl HookPoint {
+    /// Whether the file at this point is the sender's source, which hooks
+    /// must leave in place
+    pub fn is_sender_side(&self) -> bool {
+        matches!(self, Self::BeforeEnqueue)
+    }
+}
+
+/// What to do when a hook denies a file or fails to run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailurePolicy {
+    /// Abort the operation
+    #[default]
+    Reject,
+    /// Move the file into the quarantine directory and abort; a sender's
+    /// file is copied there instead, leaving the source in place
+    Quarantine,
+    /// Record a warning and carry on
+    LogOnly,
+}
+
+/// Decision returned by a hook
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HookVerdict {
+    Allow,
+    Deny { reason: String },
+}
+
+/// Information handed to a hook
+#[derive(Debug, Clone)]
+pub struct HookContext {
+    pub point: HookPoint,
+    pub file_id: String,
+    /// Local file the hook should inspect, if one exists at this point
+    pub path: Option<PathBuf>,
+    pub session_id: Option<String>,
+    pub manifest: Option<FileManifest>,
+}
+
+impl HookContext {
+    pub fn new(point: HookPoint, file_id: impl Into<String>) -> Self {
+        Self {
+            point,
+            file_id: file_id.into(),
+            path: None,
+            session_id: None,
+            manifest: None,
+        }
+    }
+
+    pub fn with_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    pub fn with_session(mut self, session_id: impl Into<String>) -> Self {
+        self.session_id = Some(session_id.into());
+        self
+    }
+
+    pub fn with_manifest(mut self, manifest: FileManifest) -> Self {
+        self.manifest = Some(manifest);
+        self
+    }
+}
+
+/// Summary of running every hook registered for a point
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HookOutcome {
+    /// Number of hooks that ran
+    pub hooks_run: usize,
+    /// Denials or failures that were downgraded to warnings by `LogOnly`
+    pub warnings: Vec<String>,
+}
+
+impl HookOutcome {
+    pub fn is_clean(&self) -> bool {
+        self.warnings.is_empty()
+    }
+}
@@ -20,6 +20,9 @@ pub enum IntegrityError {
     #[error("Verification failed for chunk {chunk_id}: {reason}")]
     VerificationFailed { chunk_id: u64, reason: String },
 
+    #[error("Chunk {sequence} does not prove against the Merkle root: {reason}")]
+    MerkleProofInvalid { sequence: u32, reason: String },
+
     #[error("Batch verification failed: {passed} passed, {failed} failed")]
     BatchVerificationFailed { passed: usize, failed: usize },
 }
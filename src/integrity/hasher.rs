@@ -0,0 +1,127 @@
+//! Pluggable checksum algorithms
+//!
+//! Chunk and file checksums default to BLAKE3. SHA-256 is there for interop
+//! with tools that expect it, and CRC32 for links where speed matters more
+//! than collision resistance. All three pick their fastest implementation
+//! at runtime: BLAKE3 its SIMD backends, SHA-256 the SHA-NI / ARMv8 SHA
+//! extensions and CRC32 carry-less multiplication (PCLMULQDQ / PMULL).
+//!
+//! Every digest is stored in a 32-byte checksum field; a CRC32 is written
+//! big-endian into the first four bytes and the rest is left zero.
+
+use crate::integrity::types::ChecksumType;
+use sha2::Digest;
+
+/// Incremental checksum over a stream of bytes
+pub trait Hasher: Send {
+    fn update(&mut self, data: &[u8]);
+
+    /// Digest of everything passed to [`update`](Self::update)
+    fn finalize(self: Box<Self>) -> [u8; 32];
+}
+
+impl Hasher for blake3::Hasher {
+    fn update(&mut self, data: &[u8]) {
+        blake3::Hasher::update(self, data);
+    }
+
+    fn finalize(self: Box<Self>) -> [u8; 32] {
+        *blake3::Hasher::finalize(&self).as_bytes()
+    }
+}
+
+impl Hasher for sha2::Sha256 {
+    fn update(&mut self, data: &[u8]) {
+        Digest::update(self, data);
+    }
+
+    fn finalize(self: Box<Self>) -> [u8; 32] {
+        Digest::finalize(*self).into()
+    }
+}
+
+impl Hasher for crc32fast::Hasher {
+    fn update(&mut self, data: &[u8]) {
+        crc32fast::Hasher::update(self, data);
+    }
+
+    fn finalize(self: Box<Self>) -> [u8; 32] {
+        let mut digest = [0u8; 32];
+        digest[..4].copy_from_slice(&crc32fast::Hasher::finalize(*self).to_be_bytes());
+        digest
+    }
+}
+
+impl ChecksumType {
+    /// Fresh hasher for this algorithm
+    pub fn hasher(self) -> Box<dyn Hasher> {
+        match self {
+            ChecksumType::Blake3 => Box::new(blake3::Hasher::new()),
+            ChecksumType::Sha256 => Box::new(sha2::Sha256::new()),
+            ChecksumType::Crc32 => Box::new(crc32fast::Hasher::new()),
+        }
+    }
+
+    /// Checksum of `data` in one call
+    pub fn digest(self, data: &[u8]) -> [u8; 32] {
+        let mut hasher = self.hasher();
+        hasher.update(data);
+        hasher.finalize()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_digests_match_reference_values() {
+        let data = b"The quick brown fox jumps over the lazy dog";
+
+        assert_eq!(
+            ChecksumType::Blake3.digest(data),
+            *blake3::hash(data).as_bytes()
+        );
+        assert_eq!(
+            hex::encode(ChecksumType::Sha256.digest(data)),
+            "d7a8fbb307d7809469ca9abcb0082e4f8d5651e46d3cdb762d02d0bf37c9e592"
+        );
+
+        let crc = ChecksumType::Crc32.digest(data);
+        assert_eq!(crc[..4], 0x414f_a339u32.to_be_bytes());
+        assert!(crc[4..].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn test_streaming_matches_one_shot() {
+        let data: Vec<u8> = (0..100_000).map(|i| (i % 251) as u8).collect();
+        for algorithm in [
+            ChecksumType::Blake3,
+            ChecksumType::Sha256,
+            ChecksumType::Crc32,
+        ] {
+            let mut hasher = algorithm.hasher();
+            for piece in data.chunks(7919) {
+                hasher.update(piece);
+            }
+            assert_eq!(hasher.finalize(), algorithm.digest(&data), "{algorithm:?}");
+        }
+    }
+
+    #[test]
+    fn test_parses_algorithm_names() {
+        assert_eq!(
+            "blake3".parse::<ChecksumType>().unwrap(),
+            ChecksumType::Blake3
+        );
+        assert_eq!(
+            "SHA256".parse::<ChecksumType>().unwrap(),
+            ChecksumType::Sha256
+        );
+        assert_eq!(
+            "crc32".parse::<ChecksumType>().unwrap(),
+            ChecksumType::Crc32
+        );
+        assert!("md5".parse::<ChecksumType>().is_err());
+    }
+}
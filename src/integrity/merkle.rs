@@ -0,0 +1,197 @@
+//! Merkle trees over chunk checksums
+//!
+//! The leaves are the checksums of a file's chunks in sequence order,
+//! parity included, and the root goes in the manifest. A chunk that carries
+//! its [`MerkleProof`] can then be checked against the root on its own, so
+//! a relay or a receiver holding part of a file can tell good chunks from
+//! forged ones without the rest of the file.
+//!
+//! Leaves and inner nodes are hashed with distinct prefixes, so a leaf can
+//! never pass for an inner node. A node without a sibling is carried up a
+//! level unchanged rather than paired with itself. The tree uses the file's
+//! checksum algorithm; with CRC32 it detects damage but proves nothing.
+
+use crate::integrity::types::ChecksumType;
+use serde::{Deserialize, Serialize};
+
+const LEAF_PREFIX: u8 = 0x00;
+const NODE_PREFIX: u8 = 0x01;
+
+/// Path from one leaf to the root
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MerkleProof {
+    /// Position of the leaf, the chunk's sequence number
+    pub index: u32,
+    /// Leaves in the tree, which fixes where nodes lack a sibling
+    pub leaf_count: u32,
+    /// Sibling hashes from the leaf level up
+    pub siblings: Vec<[u8; 32]>,
+}
+
+impl MerkleProof {
+    /// Root implied by `leaf` at this proof's position, or `None` if the
+    /// proof has the wrong shape
+    pub fn root_for(&self, algorithm: ChecksumType, leaf: &[u8; 32]) -> Option<[u8; 32]> {
+        if self.index >= self.leaf_count {
+            return None;
+        }
+        let mut siblings = self.siblings.iter();
+        let mut node = leaf_hash(algorithm, leaf);
+        let mut index = self.index;
+        let mut width = self.leaf_count;
+        while width > 1 {
+            if index % 2 == 1 {
+                node = node_hash(algorithm, siblings.next()?, &node);
+            } else if index + 1 < width {
+                node = node_hash(algorithm, &node, siblings.next()?);
+            }
+            index /= 2;
+            width = width.div_ceil(2);
+        }
+        siblings.next().is_none().then_some(node)
+    }
+
+    /// Whether `leaf` at this proof's position hashes up to `root`
+    pub fn verify(&self, algorithm: ChecksumType, leaf: &[u8; 32], root: &[u8; 32]) -> bool {
+        self.root_for(algorithm, leaf).as_ref() == Some(root)
+    }
+}
+
+/// Every level of a Merkle tree, leaves first
+#[derive(Debug, Clone)]
+pub struct MerkleTree {
+    algorithm: ChecksumType,
+    levels: Vec<Vec<[u8; 32]>>,
+}
+
+impl MerkleTree {
+    /// Build the tree over `leaves`, the chunk checksums in sequence order
+    pub fn new(algorithm: ChecksumType, leaves: &[[u8; 32]]) -> Self {
+        let mut levels = vec![leaves
+            .iter()
+            .map(|leaf| leaf_hash(algorithm, leaf))
+            .collect::<Vec<_>>()];
+        while levels.last().is_some_and(|level| level.len() > 1) {
+            let next = levels
+                .last()
+                .unwrap()
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => node_hash(algorithm, left, right),
+                    [single] => *single,
+                    _ => unreachable!(),
+                })
+                .collect();
+            levels.push(next);
+        }
+        Self { algorithm, levels }
+    }
+
+    pub fn algorithm(&self) -> ChecksumType {
+        self.algorithm
+    }
+
+    pub fn leaf_count(&self) -> usize {
+        self.levels[0].len()
+    }
+
+    /// Root hash; an empty tree has the digest of no bytes
+    pub fn root(&self) -> [u8; 32] {
+        match self.levels.last().and_then(|level| level.first()) {
+            Some(root) => *root,
+            None => self.algorithm.digest(&[]),
+        }
+    }
+
+    /// Proof for the leaf at `index`
+    pub fn proof(&self, index: usize) -> Option<MerkleProof> {
+        if index >= self.leaf_count() {
+            return None;
+        }
+        let mut siblings = Vec::new();
+        let mut position = index;
+        for level in &self.levels[..self.levels.len() - 1] {
+            if let Some(sibling) = level.get(position ^ 1) {
+                siblings.push(*sibling);
+            }
+            position /= 2;
+        }
+        Some(MerkleProof {
+            index: index as u32,
+            leaf_count: self.leaf_count() as u32,
+            siblings,
+        })
+    }
+}
+
+fn leaf_hash(algorithm: ChecksumType, leaf: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = algorithm.hasher();
+    hasher.update(&[LEAF_PREFIX]);
+    hasher.update(leaf);
+    hasher.finalize()
+}
+
+fn node_hash(algorithm: ChecksumType, left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = algorithm.hasher();
+    hasher.update(&[NODE_PREFIX]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leaves(count: usize) -> Vec<[u8; 32]> {
+        (0..count)
+            .map(|i| ChecksumType::Blake3.digest(&(i as u64).to_le_bytes()))
+            .collect()
+    }
+
+    #[test]
+    fn test_every_leaf_proves_against_root() {
+        for count in 1..=17 {
+            let leaves = leaves(count);
+            let tree = MerkleTree::new(ChecksumType::Blake3, &leaves);
+            let root = tree.root();
+            for (index, leaf) in leaves.iter().enumerate() {
+                let proof = tree.proof(index).unwrap();
+                assert!(
+                    proof.verify(ChecksumType::Blake3, leaf, &root),
+                    "leaf {index} of {count}"
+                );
+            }
+            assert!(tree.proof(count).is_none());
+        }
+    }
+
+    #[test]
+    fn test_rejects_forged_leaves_and_proofs() {
+        let leaves = leaves(5);
+        let tree = MerkleTree::new(ChecksumType::Blake3, &leaves);
+        let root = tree.root();
+        let proof = tree.proof(2).unwrap();
+
+        // Another chunk's leaf, or this leaf at another position
+        assert!(!proof.verify(ChecksumType::Blake3, &leaves[3], &root));
+        let moved = MerkleProof {
+            index: 3,
+            ..proof.clone()
+        };
+        assert!(!moved.verify(ChecksumType::Blake3, &leaves[2], &root));
+
+        // A tampered sibling, or one too many
+        let mut tampered = proof.clone();
+        tampered.siblings[0][0] ^= 1;
+        assert!(!tampered.verify(ChecksumType::Blake3, &leaves[2], &root));
+        let mut padded = proof.clone();
+        padded.siblings.push([0u8; 32]);
+        assert!(!padded.verify(ChecksumType::Blake3, &leaves[2], &root));
+
+        // A different leaf set gives a different root
+        let mut other = leaves.clone();
+        other.push(other[4]);
+        assert_ne!(MerkleTree::new(ChecksumType::Blake3, &other).root(), root);
+    }
+}
@@ -1,7 +1,11 @@
 pub mod error;
+pub mod hasher;
+pub mod merkle;
 pub mod types;
 pub mod verifier;
 
 pub use error::{IntegrityError, IntegrityResult};
+pub use hasher::Hasher;
+pub use merkle::{MerkleProof, MerkleTree};
 pub use types::{ChecksumType, IntegrityCheck, VerificationResult};
 pub use verifier::{BatchVerificationSummary, FailedChunk, IntegrityVerifier};
@@ -1,9 +1,31 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+/// Algorithm behind a chunk or file checksum
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub enum ChecksumType {
+    #[default]
+    #[serde(alias = "blake3")]
     Blake3,
+    #[serde(alias = "sha256")]
     Sha256,
+    /// CRC32 (IEEE); catches corruption but not tampering
+    #[serde(alias = "crc32")]
+    Crc32,
+}
+
+impl std::str::FromStr for ChecksumType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "blake3" => Ok(ChecksumType::Blake3),
+            "sha256" | "sha-256" => Ok(ChecksumType::Sha256),
+            "crc32" => Ok(ChecksumType::Crc32),
+            other => Err(format!(
+                "unknown checksum algorithm '{other}' (expected blake3, sha256 or crc32)"
+            )),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -17,11 +17,19 @@ impl IntegrityVerifier {
 
     /// Calculate BLAKE3 checksum for file (streaming)
     pub async fn calculate_file_checksum(path: &Path) -> IntegrityResult<[u8; 32]> {
+        Self::calculate_file_checksum_with(path, ChecksumType::Blake3).await
+    }
+
+    /// Calculate a file checksum with the given algorithm (streaming)
+    pub async fn calculate_file_checksum_with(
+        path: &Path,
+        algorithm: ChecksumType,
+    ) -> IntegrityResult<[u8; 32]> {
         let mut file = tokio::fs::File::open(path)
             .await
             .map_err(|e| IntegrityError::FileNotFound(format!("{}: {}", path.display(), e)))?;
 
-        let mut hasher = Hasher::new();
+        let mut hasher = algorithm.hasher();
         let mut buffer = vec![0u8; 8192];
 
         loop {
@@ -32,12 +40,25 @@ impl IntegrityVerifier {
             hasher.update(&buffer[..n]);
         }
 
-        Ok(*hasher.finalize().as_bytes())
+        Ok(hasher.finalize())
     }
 
-    /// Verify chunk integrity
+    /// Verify a whole file's contents against its manifest's checksum,
+    /// hashed with the algorithm the sender chose
+    pub fn verify_file_data(data: &[u8], manifest: &FileManifest) -> IntegrityResult<()> {
+        let calculated = manifest.checksum_algorithm.digest(data);
+        if calculated != manifest.checksum {
+            return Err(IntegrityError::ChecksumMismatch {
+                expected: manifest.checksum,
+                actual: calculated,
+            });
+        }
+        Ok(())
+    }
+
+    /// Verify chunk integrity with the algorithm recorded in its metadata
     pub fn verify_chunk(chunk: &Chunk) -> IntegrityResult<()> {
-        let calculated = Self::calculate_checksum(&chunk.data);
+        let calculated = chunk.metadata.checksum_algorithm.digest(&chunk.data);
         if calculated != chunk.metadata.checksum {
             return Err(IntegrityError::ChecksumMismatch {
                 expected: chunk.metadata.checksum,
@@ -49,17 +70,14 @@ impl IntegrityVerifier {
 
     /// Verify chunk with detailed result
     pub fn verify_chunk_detailed(chunk: &Chunk) -> VerificationResult {
-        let calculated = Self::calculate_checksum(&chunk.data);
+        let algorithm = chunk.metadata.checksum_algorithm;
+        let calculated = algorithm.digest(&chunk.data);
         let expected = chunk.metadata.checksum;
 
         if calculated == expected {
-            VerificationResult::success(ChecksumType::Blake3, calculated.to_vec())
+            VerificationResult::success(algorithm, calculated.to_vec())
         } else {
-            VerificationResult::failure(
-                ChecksumType::Blake3,
-                expected.to_vec(),
-                calculated.to_vec(),
-            )
+            VerificationResult::failure(algorithm, expected.to_vec(), calculated.to_vec())
         }
     }
 
@@ -120,6 +138,89 @@ impl IntegrityVerifier {
         })
     }
 
+    /// Verify a chunk on its own against its manifest's Merkle root: its
+    /// data must match its checksum and its proof must lead from that
+    /// checksum, at its sequence number in a tree of the manifest's chunks,
+    /// to the root
+    pub fn verify_chunk_proof(chunk: &Chunk, manifest: &FileManifest) -> IntegrityResult<()> {
+        let sequence = chunk.metadata.sequence_number;
+        let invalid = |reason: &str| IntegrityError::MerkleProofInvalid {
+            sequence,
+            reason: reason.to_string(),
+        };
+        let root = manifest
+            .merkle_root
+            .ok_or_else(|| invalid("manifest has no Merkle root"))?;
+        let proof = chunk
+            .metadata
+            .merkle_proof
+            .as_ref()
+            .ok_or_else(|| invalid("no proof"))?;
+        if proof.index != sequence {
+            return Err(invalid("proof is for another chunk"));
+        }
+        if proof.leaf_count != manifest.total_chunks {
+            return Err(invalid("proof is for a tree of another size"));
+        }
+
+        let algorithm = manifest.checksum_algorithm;
+
+        let calculated = algorithm.digest(&chunk.data);
+        if calculated != chunk.metadata.checksum {
+            return Err(IntegrityError::ChecksumMismatch {
+                expected: chunk.metadata.checksum,
+                actual: calculated,
+            });
+        }
+        if !proof.verify(algorithm, &calculated, &root) {
+            return Err(invalid("root mismatch"));
+        }
+        Ok(())
+    }
+
+    /// Verify whichever chunks of a file are at hand against its manifest's
+    /// Merkle root, without needing the rest of the file
+    pub fn verify_partial(
+        manifest: &FileManifest,
+        chunks: &[Chunk],
+    ) -> IntegrityResult<BatchVerificationSummary> {
+        if manifest.merkle_root.is_none() {
+            return Err(IntegrityError::VerificationFailed {
+                chunk_id: 0,
+                reason: format!("manifest of {} has no Merkle root", manifest.file_id),
+            });
+        }
+
+        let failed_chunks: Vec<FailedChunk> = chunks
+            .iter()
+            .enumerate()
+            .filter_map(|(index, chunk)| {
+                Self::verify_chunk_proof(chunk, manifest)
+                    .err()
+                    .map(|e| FailedChunk {
+                        index,
+                        chunk_id: chunk.metadata.chunk_id,
+                        sequence_number: chunk.metadata.sequence_number,
+                        error: format!("{e}"),
+                    })
+            })
+            .collect();
+
+        let failed = failed_chunks.len();
+        let passed = chunks.len() - failed;
+        Ok(BatchVerificationSummary {
+            total: chunks.len(),
+            passed,
+            failed,
+            success_rate: if chunks.is_empty() {
+                100.0
+            } else {
+                (passed as f64 / chunks.len() as f64) * 100.0
+            },
+            failed_chunks,
+        })
+    }
+
     /// Verify chunk metadata consistency
     pub fn verify_metadata(metadata: &ChunkMetadata) -> IntegrityResult<()> {
         // Check sequence number is within bounds
@@ -159,7 +260,7 @@ impl IntegrityVerifier {
 
         // Check file size consistency
         let expected_size = manifest.data_chunks as u64 * manifest.chunk_size as u64;
-        if manifest.total_size > expected_size + manifest.chunk_size as u64 {
+        if manifest.chunked_size() > expected_size + manifest.chunk_size as u64 {
             return Err(IntegrityError::VerificationFailed {
                 chunk_id: 0,
                 reason: format!(
@@ -180,7 +281,7 @@ impl IntegrityVerifier {
 
     /// Verify data against integrity check
     pub fn verify_check(data: &[u8], check: &IntegrityCheck) -> IntegrityResult<()> {
-        let calculated = Self::calculate_checksum(data);
+        let calculated = check.checksum_type.digest(data);
 
         if check.value.len() != 32 {
             return Err(IntegrityError::InvalidChecksumLength(check.value.len()));
@@ -250,6 +351,12 @@ mod tests {
                 file_size: data.len() as u64,
                 file_checksum: [0u8; 32],
                 data_chunks: 1,
+                zero_runs: Vec::new(),
+                attributes: None,
+                checksum_algorithm: Default::default(),
+                merkle_proof: None,
+                trace: None,
+                compression: None,
             },
             data: Bytes::from(data.to_vec()),
         }
@@ -403,6 +510,12 @@ mod tests {
             file_size: 256 * 1024 * 10,
             file_checksum: [0u8; 32],
             data_chunks: 8,
+            zero_runs: Vec::new(),
+            attributes: None,
+            checksum_algorithm: Default::default(),
+            merkle_proof: None,
+            trace: None,
+            compression: None,
         };
 
         assert!(IntegrityVerifier::verify_metadata(&metadata).is_ok());
@@ -423,6 +536,12 @@ mod tests {
             file_size: 256 * 1024 * 10,
             file_checksum: [0u8; 32],
             data_chunks: 8,
+            zero_runs: Vec::new(),
+            attributes: None,
+            checksum_algorithm: Default::default(),
+            merkle_proof: None,
+            trace: None,
+            compression: None,
         };
 
         let result = IntegrityVerifier::verify_metadata(&metadata);
@@ -441,6 +560,13 @@ mod tests {
             parity_chunks: 3,
             priority: Priority::Normal,
             checksum: [0u8; 32],
+            zero_runs: Vec::new(),
+            attributes: None,
+            checksum_algorithm: Default::default(),
+            erasure_profile: Default::default(),
+            schedule: None,
+            merkle_root: None,
+            compression: None,
         };
 
         assert!(IntegrityVerifier::verify_manifest(&manifest).is_ok());
@@ -458,12 +584,51 @@ mod tests {
             parity_chunks: 3,
             priority: Priority::Normal,
             checksum: [0u8; 32],
+            zero_runs: Vec::new(),
+            attributes: None,
+            checksum_algorithm: Default::default(),
+            erasure_profile: Default::default(),
+            schedule: None,
+            merkle_root: None,
+            compression: None,
         };
 
         let result = IntegrityVerifier::verify_manifest(&manifest);
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_verify_partial_against_merkle_root() {
+        let manager = crate::chunk::ChunkManager::new(1024, 4, 2).unwrap();
+        let data: Vec<u8> = (0..4096).map(|i| (i % 251) as u8 + 1).collect();
+        let (manifest, chunks) = manager
+            .split_bytes(&data, "data.bin".into(), "merkle".into(), Priority::Normal)
+            .unwrap();
+        // Any chunk proves on its own, parity included
+        for chunk in &chunks {
+            IntegrityVerifier::verify_chunk_proof(chunk, &manifest).unwrap();
+        }
+
+        // A proof from a tree of another size doesn't carry over
+        let mut resized = chunks[0].clone();
+        resized.metadata.merkle_proof.as_mut().unwrap().leaf_count += 1;
+        assert!(matches!(
+            IntegrityVerifier::verify_chunk_proof(&resized, &manifest),
+            Err(IntegrityError::MerkleProofInvalid { .. })
+        ));
+
+        // Two chunks of six: one intact, one moved to another position
+        let mut partial = vec![chunks[1].clone(), chunks[5].clone()];
+        partial[1].metadata.sequence_number = 4;
+        let summary = IntegrityVerifier::verify_partial(&manifest, &partial).unwrap();
+        assert_eq!((summary.passed, summary.failed), (1, 1));
+        assert_eq!(summary.failed_chunks[0].sequence_number, 4);
+
+        let mut unrooted = manifest.clone();
+        unrooted.merkle_root = None;
+        assert!(IntegrityVerifier::verify_partial(&unrooted, &partial).is_err());
+    }
+
     #[test]
     fn test_create_and_verify_check() {
         let data = b"integrity check test data";
@@ -1,10 +1,20 @@
 pub mod api;
 pub mod chunk;
+pub mod client;
+pub mod config;
 pub mod coordinator;
+pub mod daemon;
+pub mod failover;
+#[cfg(feature = "fault-injection")]
+pub mod fault;
+pub mod hooks;
 pub mod integrity;
+pub mod logging;
 pub mod metrics;
 pub mod network;
 pub mod priority;
 pub mod relay;
 pub mod session;
 pub mod sync;
+
+pub use config::{CoordinatorBuilder, ResilientConfig};
@@ -0,0 +1,18 @@
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum LogError {
+    #[error("Invalid log filter: {0}")]
+    InvalidFilter(String),
+
+    #[error("Logging is already set up in this process")]
+    AlreadyInitialized,
+
+    #[error("Logging is not set up in this process")]
+    NotInitialized,
+
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+}
+
+pub type LogResult<T> = Result<T, LogError>;
@@ -0,0 +1,143 @@
+//! Per-module level filters
+//!
+//! A filter is a comma-separated list of directives in the form most Rust
+//! services already use: a bare level sets the default, and
+//! `target=level` sets the level for a module and everything under it,
+//! e.g. `info,chunkstream_pro::network=debug,quinn=warn`. The most specific
+//! target wins.
+
+use super::error::{LogError, LogResult};
+use std::fmt;
+use tracing::level_filters::LevelFilter;
+use tracing::Level;
+
+/// Most directives one filter may hold
+pub const MAX_DIRECTIVES: usize = 64;
+
+/// Levels by module path
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogFilter {
+    default: LevelFilter,
+    /// Longest target first, so the first match is the most specific
+    directives: Vec<(String, LevelFilter)>,
+}
+
+impl Default for LogFilter {
+    /// Everything at `info`
+    fn default() -> Self {
+        Self {
+            default: LevelFilter::INFO,
+            directives: Vec::new(),
+        }
+    }
+}
+
+impl LogFilter {
+    pub fn parse(spec: &str) -> LogResult<Self> {
+        let mut filter = Self::default();
+        for directive in spec.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            match directive.split_once('=') {
+                Some((target, level)) => {
+                    let target = target.trim();
+                    if target.is_empty() {
+                        return Err(LogError::InvalidFilter(format!(
+                            "{directive:?} names no target"
+                        )));
+                    }
+                    let level = parse_level(level.trim())?;
+                    filter.directives.retain(|(t, _)| t != target);
+                    filter.directives.push((target.to_string(), level));
+                }
+                None => filter.default = parse_level(directive)?,
+            }
+        }
+        if filter.directives.len() > MAX_DIRECTIVES {
+            return Err(LogError::InvalidFilter(format!(
+                "more than {MAX_DIRECTIVES} directives"
+            )));
+        }
+        filter
+            .directives
+            .sort_by(|a, b| b.0.len().cmp(&a.0.len()).then(a.0.cmp(&b.0)));
+        Ok(filter)
+    }
+
+    /// Level that applies to `target`
+    pub fn level_for(&self, target: &str) -> LevelFilter {
+        self.directives
+            .iter()
+            .find(|(t, _)| {
+                target
+                    .strip_prefix(t.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+            })
+            .map_or(self.default, |(_, level)| *level)
+    }
+
+    pub fn enabled(&self, target: &str, level: &Level) -> bool {
+        self.level_for(target) >= *level
+    }
+
+    /// Most verbose level any target gets
+    pub fn max_level(&self) -> LevelFilter {
+        self.directives
+            .iter()
+            .map(|(_, level)| *level)
+            .fold(self.default, LevelFilter::max)
+    }
+}
+
+impl fmt::Display for LogFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", level_name(self.default))?;
+        let mut directives: Vec<_> = self.directives.iter().collect();
+        directives.sort_by(|a, b| a.0.cmp(&b.0));
+        for (target, level) in directives {
+            write!(f, ",{}={}", target, level_name(*level))?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_level(s: &str) -> LogResult<LevelFilter> {
+    s.parse()
+        .map_err(|_| LogError::InvalidFilter(format!("unknown level {s:?}")))
+}
+
+fn level_name(level: LevelFilter) -> String {
+    level.to_string().to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_most_specific_target_wins() {
+        let filter =
+            LogFilter::parse("warn, chunkstream_pro=info,chunkstream_pro::network=debug").unwrap();
+        assert_eq!(filter.level_for("quinn::endpoint"), LevelFilter::WARN);
+        assert_eq!(
+            filter.level_for("chunkstream_pro::coordinator"),
+            LevelFilter::INFO
+        );
+        assert_eq!(
+            filter.level_for("chunkstream_pro::network::quic_transport"),
+            LevelFilter::DEBUG
+        );
+        // Only whole path segments match
+        assert_eq!(filter.level_for("chunkstream_prox"), LevelFilter::WARN);
+        assert!(filter.enabled("chunkstream_pro::network", &Level::DEBUG));
+        assert!(!filter.enabled("chunkstream_pro::network", &Level::TRACE));
+        assert_eq!(filter.max_level(), LevelFilter::DEBUG);
+        assert_eq!(
+            filter.to_string(),
+            "warn,chunkstream_pro=info,chunkstream_pro::network=debug"
+        );
+
+        assert_eq!(LogFilter::parse("").unwrap(), LogFilter::default());
+        assert!(LogFilter::parse("loud").is_err());
+        assert!(LogFilter::parse("=debug").is_err());
+        assert!(LogFilter::parse("quinn=off").unwrap().max_level() == LevelFilter::INFO);
+    }
+}
@@ -0,0 +1,87 @@
+//! Log output for the daemons and embedding applications
+//!
+//! The library only emits `tracing` events; nothing is printed until a
+//! process calls [`init`]. That installs a subscriber that filters events by
+//! module ([`LogFilter`]), formats them as text or JSON, and writes them to
+//! stderr or a size-rotated file. The filter can be changed afterwards
+//! through the returned [`LogHandle`], or [`handle`] from anywhere in the
+//! process, such as the REST API.
+
+pub mod error;
+pub mod filter;
+mod subscriber;
+pub mod types;
+pub mod writer;
+
+pub use error::{LogError, LogResult};
+pub use filter::{LogFilter, MAX_DIRECTIVES};
+pub use types::{LogConfig, LogFormat};
+pub use writer::RotatingFile;
+
+use std::sync::{Arc, OnceLock};
+use subscriber::LogSubscriber;
+
+/// The subscriber [`init`] installed
+static HANDLE: OnceLock<LogHandle> = OnceLock::new();
+
+/// Runtime control of the installed subscriber
+#[derive(Clone)]
+pub struct LogHandle {
+    subscriber: Arc<LogSubscriber>,
+}
+
+impl std::fmt::Debug for LogHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LogHandle")
+            .field("filter", &self.filter().to_string())
+            .field("format", &self.format())
+            .finish()
+    }
+}
+
+impl LogHandle {
+    pub fn filter(&self) -> LogFilter {
+        self.subscriber.filter()
+    }
+
+    /// Replace the level directives; takes effect for the next event
+    pub fn set_filter(&self, directives: &str) -> LogResult<LogFilter> {
+        let filter = LogFilter::parse(directives)?;
+        self.subscriber.set_filter(filter.clone());
+        Ok(filter)
+    }
+
+    pub fn format(&self) -> LogFormat {
+        self.subscriber.format()
+    }
+}
+
+/// Install the process-wide subscriber described by `config`
+///
+/// Fails if the filter doesn't parse, the log file can't be opened, or
+/// this or another subscriber is already installed.
+pub fn init(config: &LogConfig) -> LogResult<LogHandle> {
+    let filter = LogFilter::parse(&config.filter)?;
+    let sink: Box<dyn std::io::Write + Send> = match &config.file {
+        Some(path) => Box::new(RotatingFile::open(
+            path,
+            config.max_file_bytes,
+            config.max_files,
+        )?),
+        None => Box::new(std::io::stderr()),
+    };
+    let subscriber = Arc::new(LogSubscriber::new(filter, config.format, sink));
+    let handle = LogHandle {
+        subscriber: subscriber.clone(),
+    };
+    if HANDLE.get().is_some() || tracing::subscriber::set_global_default(subscriber).is_err() {
+        return Err(LogError::AlreadyInitialized);
+    }
+    let _ = HANDLE.set(handle.clone());
+    Ok(handle)
+}
+
+/// The handle of the subscriber [`init`] installed, if it has been called
+pub fn handle() -> Option<LogHandle> {
+    HANDLE.get().cloned()
+}
@@ -0,0 +1,357 @@
+//! The subscriber that formats and writes events
+//!
+//! Each event becomes one line: human-readable text, or a JSON object with
+//! the event's fields and the spans it happened in. The filter is checked
+//! on every event, so it can be changed while the process runs.
+
+use super::filter::LogFilter;
+use super::types::LogFormat;
+use parking_lot::{Mutex, RwLock};
+use serde_json::{Map, Value};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::{self, Write as _};
+use std::io::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tracing::field::{Field, Visit};
+use tracing::level_filters::LevelFilter;
+use tracing::span::{Attributes, Id, Record};
+use tracing::subscriber::Interest;
+use tracing::{Event, Metadata, Subscriber};
+
+thread_local! {
+    /// Spans entered on this thread, innermost last
+    static ENTERED: RefCell<Vec<u64>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Name and fields of an open span
+#[derive(Debug)]
+struct SpanData {
+    metadata: &'static Metadata<'static>,
+    fields: Vec<(&'static str, Value)>,
+    refs: usize,
+}
+
+/// Writes filtered events to a sink in one format
+pub(crate) struct LogSubscriber {
+    filter: RwLock<LogFilter>,
+    format: LogFormat,
+    sink: Mutex<Box<dyn Write + Send>>,
+    spans: RwLock<HashMap<u64, SpanData>>,
+    next_span: AtomicU64,
+}
+
+impl LogSubscriber {
+    pub(crate) fn new(filter: LogFilter, format: LogFormat, sink: Box<dyn Write + Send>) -> Self {
+        Self {
+            filter: RwLock::new(filter),
+            format,
+            sink: Mutex::new(sink),
+            spans: RwLock::new(HashMap::new()),
+            next_span: AtomicU64::new(1),
+        }
+    }
+
+    pub(crate) fn filter(&self) -> LogFilter {
+        self.filter.read().clone()
+    }
+
+    pub(crate) fn set_filter(&self, filter: LogFilter) {
+        *self.filter.write() = filter;
+        // Callsites cache the max level hint; make them ask again
+        tracing::callsite::rebuild_interest_cache();
+    }
+
+    pub(crate) fn format(&self) -> LogFormat {
+        self.format
+    }
+
+    /// The event's line, without the trailing newline
+    fn format_event(&self, event: &Event<'_>) -> String {
+        let meta = event.metadata();
+        let mut fields = FieldVisitor::default();
+        event.record(&mut fields);
+        let timestamp = chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true);
+
+        let spans = self.spans.read();
+        let entered: Vec<&SpanData> = ENTERED.with(|entered| {
+            entered
+                .borrow()
+                .iter()
+                .filter_map(|id| spans.get(id))
+                .collect()
+        });
+
+        match self.format {
+            LogFormat::Pretty => {
+                let mut line = format!("{timestamp} {:>5} ", meta.level());
+                for span in &entered {
+                    line.push_str(span.metadata.name());
+                    if !span.fields.is_empty() {
+                        line.push('{');
+                        write_fields(&mut line, &span.fields, "");
+                        line.push('}');
+                    }
+                    line.push(':');
+                }
+                let _ = write!(line, "{}: {}", meta.target(), fields.message);
+                write_fields(&mut line, &fields.fields, " ");
+                line
+            }
+            LogFormat::Json => {
+                let mut object = Map::new();
+                object.insert("timestamp".into(), timestamp.into());
+                object.insert("level".into(), meta.level().as_str().into());
+                object.insert("target".into(), meta.target().into());
+                object.insert("message".into(), fields.message.into());
+                if !fields.fields.is_empty() {
+                    object.insert("fields".into(), to_object(fields.fields).into());
+                }
+                if !entered.is_empty() {
+                    let spans = entered.iter().map(|span| {
+                        let mut object = to_object(span.fields.clone());
+                        object.insert("name".into(), span.metadata.name().into());
+                        Value::Object(object)
+                    });
+                    object.insert("spans".into(), Value::Array(spans.collect()));
+                }
+                Value::Object(object).to_string()
+            }
+        }
+    }
+}
+
+impl Subscriber for LogSubscriber {
+    fn register_callsite(&self, _metadata: &'static Metadata<'static>) -> Interest {
+        // The filter can change, so every callsite is asked each time
+        Interest::sometimes()
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        self.filter
+            .read()
+            .enabled(metadata.target(), metadata.level())
+    }
+
+    fn max_level_hint(&self) -> Option<LevelFilter> {
+        Some(self.filter.read().max_level())
+    }
+
+    fn new_span(&self, attrs: &Attributes<'_>) -> Id {
+        let mut fields = FieldVisitor::default();
+        attrs.record(&mut fields);
+        let id = self.next_span.fetch_add(1, Ordering::Relaxed);
+        self.spans.write().insert(
+            id,
+            SpanData {
+                metadata: attrs.metadata(),
+                fields: fields.into_fields(),
+                refs: 1,
+            },
+        );
+        Id::from_u64(id)
+    }
+
+    fn record(&self, span: &Id, values: &Record<'_>) {
+        let mut fields = FieldVisitor::default();
+        values.record(&mut fields);
+        if let Some(data) = self.spans.write().get_mut(&span.into_u64()) {
+            data.fields.extend(fields.into_fields());
+        }
+    }
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut line = self.format_event(event);
+        line.push('\n');
+        // Nowhere to report a failed write to
+        let _ = self.sink.lock().write_all(line.as_bytes());
+    }
+
+    fn enter(&self, span: &Id) {
+        ENTERED.with(|entered| entered.borrow_mut().push(span.into_u64()));
+    }
+
+    fn exit(&self, span: &Id) {
+        ENTERED.with(|entered| {
+            let mut entered = entered.borrow_mut();
+            if let Some(at) = entered.iter().rposition(|id| *id == span.into_u64()) {
+                entered.remove(at);
+            }
+        });
+    }
+
+    fn clone_span(&self, span: &Id) -> Id {
+        if let Some(data) = self.spans.write().get_mut(&span.into_u64()) {
+            data.refs += 1;
+        }
+        span.clone()
+    }
+
+    fn try_close(&self, span: Id) -> bool {
+        let mut spans = self.spans.write();
+        let Some(data) = spans.get_mut(&span.into_u64()) else {
+            return false;
+        };
+        data.refs -= 1;
+        if data.refs == 0 {
+            spans.remove(&span.into_u64());
+            return true;
+        }
+        false
+    }
+}
+
+/// Collects the message and the other fields of an event or span
+#[derive(Default)]
+struct FieldVisitor {
+    message: String,
+    fields: Vec<(&'static str, Value)>,
+}
+
+impl FieldVisitor {
+    fn into_fields(mut self) -> Vec<(&'static str, Value)> {
+        if !self.message.is_empty() {
+            self.fields.insert(0, ("message", self.message.into()));
+        }
+        self.fields
+    }
+}
+
+impl Visit for FieldVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.message = format!("{value:?}");
+        } else {
+            self.fields
+                .push((field.name(), format!("{value:?}").into()));
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = value.to_string();
+        } else {
+            self.fields.push((field.name(), value.into()));
+        }
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.fields.push((field.name(), value.into()));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.fields.push((field.name(), value.into()));
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.fields.push((field.name(), value.into()));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.fields.push((field.name(), value.into()));
+    }
+}
+
+/// `key=value` pairs, each preceded by `separator` but the first
+fn write_fields(line: &mut String, fields: &[(&'static str, Value)], separator: &str) {
+    for (i, (name, value)) in fields.iter().enumerate() {
+        if i > 0 || !separator.is_empty() {
+            line.push_str(if i == 0 { separator } else { " " });
+        }
+        match value {
+            Value::String(s) => {
+                let _ = write!(line, "{name}={s}");
+            }
+            other => {
+                let _ = write!(line, "{name}={other}");
+            }
+        }
+    }
+}
+
+fn to_object(fields: Vec<(&'static str, Value)>) -> Map<String, Value> {
+    fields
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), value))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    /// Sink the test can read back
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Buffer {
+        fn lines(&self) -> Vec<String> {
+            String::from_utf8(self.0.lock().clone())
+                .unwrap()
+                .lines()
+                .map(str::to_string)
+                .collect()
+        }
+    }
+
+    fn subscriber(filter: &str, format: LogFormat) -> (Arc<LogSubscriber>, Buffer) {
+        let buffer = Buffer::default();
+        let filter = LogFilter::parse(filter).unwrap();
+        let subscriber = LogSubscriber::new(filter, format, Box::new(buffer.clone()));
+        (Arc::new(subscriber), buffer)
+    }
+
+    #[test]
+    fn test_json_lines_carry_fields_and_spans() {
+        let (subscriber, buffer) = subscriber("warn,transfers=debug", LogFormat::Json);
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!(target: "transfers", "transfer", session = "s1");
+            let _entered = span.enter();
+            tracing::debug!(target: "transfers", chunk = 7, resent = true, "Chunk sent");
+            tracing::info!(target: "quinn", "Hidden below warn");
+        });
+
+        let lines = buffer.lines();
+        assert_eq!(lines.len(), 1);
+        let line: Value = serde_json::from_str(&lines[0]).unwrap();
+        assert_eq!(line["level"], "DEBUG");
+        assert_eq!(line["target"], "transfers");
+        assert_eq!(line["message"], "Chunk sent");
+        assert_eq!(line["fields"]["chunk"], 7);
+        assert_eq!(line["fields"]["resent"], true);
+        assert_eq!(line["spans"][0]["name"], "transfer");
+        assert_eq!(line["spans"][0]["session"], "s1");
+    }
+
+    #[test]
+    fn test_filter_changes_apply_at_once() {
+        let (subscriber, buffer) = subscriber("info", LogFormat::Pretty);
+        tracing::subscriber::with_default(subscriber.clone(), || {
+            tracing::debug!(target: "net", "Before");
+            subscriber.set_filter(LogFilter::parse("info,net=debug").unwrap());
+            tracing::debug!(target: "net", peer = "10.0.0.2:5001", "After");
+        });
+
+        let lines = buffer.lines();
+        assert_eq!(lines.len(), 1);
+        assert!(
+            lines[0].ends_with("DEBUG net: After peer=10.0.0.2:5001"),
+            "{}",
+            lines[0]
+        );
+    }
+}
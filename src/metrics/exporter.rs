@@ -2,8 +2,10 @@
 //!
 //! Exposes metrics via HTTP for Prometheus scraping.
 
+use crate::metrics::latency::{Stage, LATENCY_BUCKETS_SECONDS};
 use crate::metrics::recorder::init_metrics;
-use metrics_exporter_prometheus::{PrometheusBuilder, PrometheusHandle};
+use crate::metrics::sampling::{configure_sampling, SamplingConfig};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
 use std::net::SocketAddr;
 use std::sync::OnceLock;
 
@@ -21,6 +23,9 @@ pub struct MetricsConfig {
 
     /// Whether to include process metrics
     pub include_process_metrics: bool,
+
+    /// Sampling of per-chunk metrics
+    pub sampling: SamplingConfig,
 }
 
 impl Default for MetricsConfig {
@@ -29,6 +34,7 @@ impl Default for MetricsConfig {
             listen_addr: "0.0.0.0:9090".parse().unwrap(),
             endpoint: "/metrics".to_string(),
             include_process_metrics: true,
+            sampling: SamplingConfig::default(),
         }
     }
 }
@@ -55,10 +61,21 @@ pub fn start_metrics_server(
 
     // Try to set up the prometheus exporter
     if let Some(handle) = PROMETHEUS_HANDLE.get() {
+        configure_sampling(config.sampling);
         return Ok(handle);
     }
 
-    let builder = PrometheusBuilder::new();
+    // Stage latencies render as histograms, so percentiles can be
+    // aggregated across instances
+    let mut builder = PrometheusBuilder::new();
+    for stage in Stage::ALL {
+        builder = builder
+            .set_buckets_for_metric(
+                Matcher::Full(stage.metric_name().to_string()),
+                &LATENCY_BUCKETS_SECONDS,
+            )
+            .map_err(|e| MetricsError::SetupFailed(e.to_string()))?;
+    }
 
     // Install the recorder
     let handle = builder
@@ -68,6 +85,7 @@ pub fn start_metrics_server(
 
     // Store the handle
     let _ = PROMETHEUS_HANDLE.set(handle);
+    configure_sampling(config.sampling);
 
     Ok(PROMETHEUS_HANDLE.get().unwrap())
 }
@@ -147,6 +165,18 @@ impl MetricsBuilder {
         self
     }
 
+    /// Record one in `every` chunk sent/received events
+    pub fn chunk_sample_every(mut self, every: u64) -> Self {
+        self.config.sampling.chunk_sample_every = every;
+        self
+    }
+
+    /// Enable/disable the `transfer_id` label on chunk metrics
+    pub fn per_transfer_labels(mut self, enabled: bool) -> Self {
+        self.config.sampling.per_transfer_labels = enabled;
+        self
+    }
+
     /// Build and start the metrics server
     pub fn build(self) -> Result<&'static PrometheusHandle, MetricsError> {
         start_metrics_server(self.config)
@@ -0,0 +1,255 @@
+//! Latency histograms for the stages of a transfer
+//!
+//! Splitting a file, erasure coding it, waiting in the queue, sending each
+//! chunk and rebuilding the file on the receiver are each timed. Every
+//! observation goes to a Prometheus histogram (the exporter gives these
+//! metrics [`LATENCY_BUCKETS_SECONDS`], so `/metrics` shows `_bucket`
+//! series rather than a summary) and to an in-process histogram with the
+//! same buckets, which [`latency_summary`] reports as JSON whether or not
+//! the exporter runs.
+//!
+//! Queue wait and send time happen once per chunk. The in-process
+//! histograms count every chunk; the exporter only sees the chunks the
+//! sampler picks (see `metrics::sampling`).
+
+use crate::metrics::sampling::SAMPLER;
+use metrics::histogram;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Upper bounds (seconds) of the stage latency buckets; a final bucket holds
+/// anything longer
+pub const LATENCY_BUCKETS_SECONDS: [f64; 18] = [
+    0.0001, 0.00025, 0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5,
+    5.0, 10.0, 30.0, 60.0,
+];
+
+/// A timed stage of a transfer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Stage {
+    /// Splitting a file's data into chunks, erasure coding included
+    Split,
+    /// Erasure coding one group of data shards
+    Encode,
+    /// Time a chunk spent queued before it was dequeued to send
+    QueueWait,
+    /// Sending one chunk, retries included
+    Send,
+    /// Decoding and writing a received file
+    Reconstruct,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 5] = [
+        Stage::Split,
+        Stage::Encode,
+        Stage::QueueWait,
+        Stage::Send,
+        Stage::Reconstruct,
+    ];
+
+    /// Name of the stage's Prometheus histogram
+    pub fn metric_name(self) -> &'static str {
+        match self {
+            Stage::Split => "resilient_split_duration_seconds",
+            Stage::Encode => "resilient_encode_duration_seconds",
+            Stage::QueueWait => "resilient_queue_wait_seconds",
+            Stage::Send => "resilient_chunk_send_duration_seconds",
+            Stage::Reconstruct => "resilient_reconstruct_duration_seconds",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Stage::Split => "Time to split a file into erasure coded chunks",
+            Stage::Encode => "Time to erasure code one group of data shards",
+            Stage::QueueWait => "Time a chunk waited in the priority queue",
+            Stage::Send => "Time to send one chunk, retries included",
+            Stage::Reconstruct => "Time to decode and write a received file",
+        }
+    }
+
+    /// Whether the stage happens once per chunk, and so goes through the sampler
+    fn per_chunk(self) -> bool {
+        matches!(self, Stage::QueueWait | Stage::Send)
+    }
+}
+
+/// Counts per [`LATENCY_BUCKETS_SECONDS`] bucket, updated without locks
+#[derive(Debug)]
+struct StageHistogram {
+    buckets: [AtomicU64; LATENCY_BUCKETS_SECONDS.len() + 1],
+    sum_micros: AtomicU64,
+    max_micros: AtomicU64,
+    /// Observations offered to the exporter, for sampling
+    seen: AtomicU64,
+}
+
+impl StageHistogram {
+    const fn new() -> Self {
+        Self {
+            buckets: [const { AtomicU64::new(0) }; LATENCY_BUCKETS_SECONDS.len() + 1],
+            sum_micros: AtomicU64::new(0),
+            max_micros: AtomicU64::new(0),
+            seen: AtomicU64::new(0),
+        }
+    }
+
+    fn record(&self, duration: Duration) {
+        let micros = duration.as_micros().min(u64::MAX as u128) as u64;
+        self.buckets[bucket_of(duration)].fetch_add(1, Ordering::Relaxed);
+        self.sum_micros.fetch_add(micros, Ordering::Relaxed);
+        self.max_micros.fetch_max(micros, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self, stage: Stage) -> StageLatency {
+        let counts: Vec<u64> = self
+            .buckets
+            .iter()
+            .map(|count| count.load(Ordering::Relaxed))
+            .collect();
+        let count: u64 = counts.iter().sum();
+        let max_ms = self.max_micros.load(Ordering::Relaxed) as f64 / 1000.0;
+        let mean_ms = if count == 0 {
+            0.0
+        } else {
+            self.sum_micros.load(Ordering::Relaxed) as f64 / 1000.0 / count as f64
+        };
+        StageLatency {
+            stage,
+            count,
+            mean_ms,
+            p50_ms: percentile_ms(&counts, max_ms, 0.50),
+            p95_ms: percentile_ms(&counts, max_ms, 0.95),
+            p99_ms: percentile_ms(&counts, max_ms, 0.99),
+            max_ms,
+            buckets: LATENCY_BUCKETS_SECONDS
+                .iter()
+                .map(|&le| Some(le * 1000.0))
+                .chain([None])
+                .zip(&counts)
+                .map(|(le_ms, &count)| LatencyBucket { le_ms, count })
+                .collect(),
+        }
+    }
+}
+
+static HISTOGRAMS: [StageHistogram; Stage::ALL.len()] =
+    [const { StageHistogram::new() }; Stage::ALL.len()];
+
+/// One bucket of a stage histogram
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LatencyBucket {
+    /// Upper bound; `None` for the bucket above the largest bound
+    pub le_ms: Option<f64>,
+    /// Observations in this bucket alone (not cumulative)
+    pub count: u64,
+}
+
+/// Latency distribution of one stage since startup
+///
+/// Percentiles are bucket upper bounds, capped at the largest value seen.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StageLatency {
+    pub stage: Stage,
+    pub count: u64,
+    pub mean_ms: f64,
+    pub p50_ms: f64,
+    pub p95_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: f64,
+    pub buckets: Vec<LatencyBucket>,
+}
+
+/// Record how long one run of `stage` took
+pub fn record_stage(stage: Stage, duration: Duration) {
+    let histogram = &HISTOGRAMS[stage as usize];
+    histogram.record(duration);
+
+    if stage.per_chunk() {
+        let every = SAMPLER.config().chunk_sample_every.max(1);
+        if !histogram
+            .seen
+            .fetch_add(1, Ordering::Relaxed)
+            .is_multiple_of(every)
+        {
+            return;
+        }
+    }
+    histogram!(stage.metric_name()).record(duration.as_secs_f64());
+}
+
+/// Latency distribution of every stage, in [`Stage::ALL`] order
+pub fn latency_summary() -> Vec<StageLatency> {
+    Stage::ALL
+        .iter()
+        .map(|&stage| HISTOGRAMS[stage as usize].snapshot(stage))
+        .collect()
+}
+
+fn bucket_of(duration: Duration) -> usize {
+    let secs = duration.as_secs_f64();
+    LATENCY_BUCKETS_SECONDS
+        .iter()
+        .position(|&bound| secs <= bound)
+        .unwrap_or(LATENCY_BUCKETS_SECONDS.len())
+}
+
+/// Value (ms) below which `fraction` of the observations fall
+fn percentile_ms(counts: &[u64], max_ms: f64, fraction: f64) -> f64 {
+    let total: u64 = counts.iter().sum();
+    if total == 0 {
+        return 0.0;
+    }
+    let rank = ((total as f64 * fraction).ceil() as u64).max(1);
+    let mut seen = 0;
+    for (bucket, count) in counts.iter().enumerate() {
+        seen += count;
+        if seen >= rank {
+            return LATENCY_BUCKETS_SECONDS
+                .get(bucket)
+                .map_or(max_ms, |&bound| (bound * 1000.0).min(max_ms));
+        }
+    }
+    max_ms
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_percentiles_follow_buckets() {
+        let histogram = StageHistogram::new();
+        for _ in 0..90 {
+            histogram.record(Duration::from_micros(800));
+        }
+        for _ in 0..10 {
+            histogram.record(Duration::from_millis(40));
+        }
+
+        let latency = histogram.snapshot(Stage::Send);
+        assert_eq!(latency.count, 100);
+        assert_eq!(latency.p50_ms, 1.0);
+        assert_eq!(latency.p95_ms, 40.0);
+        assert_eq!(latency.max_ms, 40.0);
+        assert!((latency.mean_ms - 4.72).abs() < 1e-9);
+        assert_eq!(latency.buckets.len(), LATENCY_BUCKETS_SECONDS.len() + 1);
+        assert_eq!(latency.buckets[3].count, 90);
+        assert_eq!(latency.buckets.last().unwrap().le_ms, None);
+
+        histogram.record(Duration::from_secs(90));
+        assert_eq!(histogram.snapshot(Stage::Send).p99_ms, 50.0);
+        assert_eq!(
+            histogram
+                .snapshot(Stage::Send)
+                .buckets
+                .last()
+                .unwrap()
+                .count,
+            1
+        );
+    }
+}
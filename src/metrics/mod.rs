@@ -8,11 +8,17 @@
 //! - Erasure coding efficiency
 //! - Network conditions (latency, loss rate)
 //! - Queue depths and priorities
+//! - Latency of each transfer stage (split, encode, queue wait, send,
+//!   reconstruct)
 
 pub mod exporter;
+pub mod latency;
 pub mod recorder;
+pub mod sampling;
 
 pub use exporter::{start_metrics_server, MetricsConfig};
+pub use latency::{latency_summary, record_stage, Stage, StageLatency};
 pub use recorder::{
     record_chunk_received, record_chunk_sent, record_transfer_complete, TransferMetrics,
 };
+pub use sampling::{configure_sampling, sampling, SamplingConfig};
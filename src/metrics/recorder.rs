@@ -2,6 +2,9 @@
 //!
 //! Records various metrics about transfer performance and health.
 
+use crate::metrics::latency::Stage;
+use crate::metrics::sampling::{ChunkEvent, SAMPLER};
+use crate::session::{MaintenanceReport, StorageStats};
 use metrics::{counter, describe_counter, describe_gauge, describe_histogram, gauge, histogram};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::{Duration, Instant};
@@ -28,6 +31,48 @@ pub fn init_metrics() {
         "resilient_chunks_recovered_total",
         "Total number of chunks recovered via erasure coding"
     );
+    describe_counter!(
+        "resilient_chunks_corrupted_total",
+        "Total number of received chunks discarded for a checksum mismatch"
+    );
+    describe_counter!(
+        "resilient_chunk_send_failures_total",
+        "Chunk sends that failed and were left for retry"
+    );
+    describe_counter!(
+        "resilient_retry_decisions_total",
+        "Chunk sends retried on the spot or left to parity, by decision"
+    );
+    describe_counter!(
+        "resilient_shards_retransmitted_total",
+        "Shards resent because the receiver was short of a decodable FEC group"
+    );
+    describe_counter!(
+        "resilient_retransmitted_bytes_total",
+        "Bytes resent because the receiver was short of a decodable FEC group"
+    );
+    describe_counter!(
+        "resilient_shards_regenerated_total",
+        "Resent shards the sender no longer held and rebuilt from the rest of the group"
+    );
+
+    describe_counter!(
+        "resilient_file_repairs_total",
+        "Delivered files found damaged on re-verification, by outcome"
+    );
+    describe_counter!(
+        "resilient_repair_fetched_bytes_total",
+        "Bytes fetched from senders to patch damaged delivered files"
+    );
+
+    describe_counter!(
+        "resilient_wire_raw_bytes_total",
+        "Serialized size of patches, signatures and manifests before compression"
+    );
+    describe_counter!(
+        "resilient_wire_encoded_bytes_total",
+        "Size of patches, signatures and manifests as framed on the wire"
+    );
 
     // Byte counters
     describe_counter!("resilient_bytes_sent_total", "Total bytes sent");
@@ -60,6 +105,26 @@ pub fn init_metrics() {
         "resilient_storage_used_bytes",
         "Current storage usage in bytes"
     );
+    describe_gauge!(
+        "resilient_session_db_bytes",
+        "Session database size on disk, write-ahead log included"
+    );
+    describe_gauge!(
+        "resilient_session_db_free_bytes",
+        "Session database space freed by deletes and not yet reclaimed"
+    );
+    describe_gauge!(
+        "resilient_session_db_rows",
+        "Rows per session database table"
+    );
+    describe_counter!(
+        "resilient_session_db_reclaimed_bytes_total",
+        "Bytes returned to the file system by session database maintenance"
+    );
+    describe_gauge!(
+        "resilient_chunk_sample_every",
+        "Chunk sent/received events are recorded one in this many"
+    );
 
     // Histograms
     describe_histogram!(
@@ -78,39 +143,157 @@ pub fn init_metrics() {
         "resilient_erasure_overhead_ratio",
         "Ratio of parity shards to data shards"
     );
+    describe_histogram!(
+        "resilient_pacing_delay_seconds",
+        "Time chunk writes waited on the send pacer"
+    );
+    describe_gauge!(
+        "resilient_pacing_rate_bytes_per_second",
+        "Current chunk pacing rate"
+    );
+    describe_histogram!(
+        "resilient_peer_shaping_delay_seconds",
+        "Time a sender's next stream was held back for going over its receive rate"
+    );
+
+    describe_histogram!(
+        "resilient_reorder_depth",
+        "How far behind the highest received sequence a late chunk arrived"
+    );
+
+    // Queue fairness
+    describe_gauge!(
+        "resilient_queue_oldest_wait_seconds",
+        "Wait of the oldest queued chunk per priority"
+    );
+    describe_counter!(
+        "resilient_queue_starvation_alerts_total",
+        "Priority classes that waited past the starvation threshold"
+    );
+    describe_histogram!(
+        "resilient_chunk_delivery_latency_seconds",
+        "Time from enqueue to delivery per priority"
+    );
+    describe_counter!(
+        "resilient_chunk_deadline_misses_total",
+        "Chunks delivered after their deadline per priority"
+    );
+    describe_counter!(
+        "resilient_relay_chunks_reinjected_total",
+        "Relayed chunks an audit found missing and stored on a relay again"
+    );
+
+    // Reconstruction writes
+    describe_counter!(
+        "resilient_disk_written_bytes_total",
+        "Bytes of reconstructed files written to disk"
+    );
+    describe_histogram!(
+        "resilient_disk_write_duration_seconds",
+        "Time one write of a reconstructed file took"
+    );
+    describe_histogram!(
+        "resilient_disk_sync_duration_seconds",
+        "Time one fsync of a reconstructed file took"
+    );
+    describe_histogram!(
+        "resilient_disk_write_throttle_seconds",
+        "Time reconstruction writes waited to stay under the write rate limit"
+    );
+
+    // Transfer stage latencies (see `metrics::latency`)
+    for stage in Stage::ALL {
+        describe_histogram!(stage.metric_name(), stage.description());
+    }
 }
 
 // ============== Chunk Operations ==============
+//
+// Sent, received and duration events go through the sampler (see
+// `metrics::sampling`); failures are always recorded.
 
 /// Record a chunk being sent
 pub fn record_chunk_sent(transfer_id: &str, chunk_size: usize, priority: &str) {
-    counter!("resilient_chunks_sent_total", "transfer_id" => transfer_id.to_string(), "priority" => priority.to_string()).increment(1);
-    counter!("resilient_bytes_sent_total", "transfer_id" => transfer_id.to_string())
-        .increment(chunk_size as u64);
+    let Some(weight) = SAMPLER.sample(ChunkEvent::Sent, Some(transfer_id)) else {
+        return;
+    };
+    let transfer_id = SAMPLER.transfer_label(transfer_id);
+    counter!("resilient_chunks_sent_total", "transfer_id" => transfer_id.clone(), "priority" => priority.to_string()).increment(weight);
+    counter!("resilient_bytes_sent_total", "transfer_id" => transfer_id)
+        .increment(chunk_size as u64 * weight);
 }
 
 /// Record a chunk being received
 pub fn record_chunk_received(transfer_id: &str, chunk_size: usize) {
-    counter!("resilient_chunks_received_total", "transfer_id" => transfer_id.to_string())
+    let Some(weight) = SAMPLER.sample(ChunkEvent::Received, Some(transfer_id)) else {
+        return;
+    };
+    let transfer_id = SAMPLER.transfer_label(transfer_id);
+    counter!("resilient_chunks_received_total", "transfer_id" => transfer_id.clone())
+        .increment(weight);
+    counter!("resilient_bytes_received_total", "transfer_id" => transfer_id)
+        .increment(chunk_size as u64 * weight);
+}
+
+/// Record a chunk send that failed
+pub fn record_chunk_failed(transfer_id: &str) {
+    counter!("resilient_chunk_send_failures_total", "transfer_id" => SAMPLER.transfer_label(transfer_id))
         .increment(1);
-    counter!("resilient_bytes_received_total", "transfer_id" => transfer_id.to_string())
-        .increment(chunk_size as u64);
+}
+
+/// Record whether a chunk's send is retried on the spot or left to parity
+pub fn record_retry_decision(transfer_id: &str, decision: &'static str) {
+    counter!(
+        "resilient_retry_decisions_total",
+        "transfer_id" => SAMPLER.transfer_label(transfer_id),
+        "decision" => decision
+    )
+    .increment(1);
 }
 
 /// Record a chunk being lost
 pub fn record_chunk_lost(transfer_id: &str) {
-    counter!("resilient_chunks_lost_total", "transfer_id" => transfer_id.to_string()).increment(1);
+    counter!("resilient_chunks_lost_total", "transfer_id" => SAMPLER.transfer_label(transfer_id))
+        .increment(1);
+}
+
+/// Record a received chunk discarded for a checksum mismatch
+pub fn record_chunk_corrupted(transfer_id: &str) {
+    counter!("resilient_chunks_corrupted_total", "transfer_id" => SAMPLER.transfer_label(transfer_id))
+        .increment(1);
+}
+
+/// Record shards resent to complete a receiver's FEC group
+pub fn record_shards_retransmitted(transfer_id: &str, shards: usize, bytes: u64) {
+    let transfer_id = SAMPLER.transfer_label(transfer_id);
+    counter!("resilient_shards_retransmitted_total", "transfer_id" => transfer_id.clone())
+        .increment(shards as u64);
+    counter!("resilient_retransmitted_bytes_total", "transfer_id" => transfer_id).increment(bytes);
+}
+
+/// Record shards rebuilt by the sender so they could be resent
+pub fn record_shards_regenerated(transfer_id: &str, shards: usize) {
+    counter!("resilient_shards_regenerated_total", "transfer_id" => SAMPLER.transfer_label(transfer_id))
+        .increment(shards as u64);
+}
+
+/// Record a damaged delivered file being repaired (or not)
+pub fn record_file_repair(outcome: &'static str, fetched_bytes: u64) {
+    counter!("resilient_file_repairs_total", "outcome" => outcome).increment(1);
+    counter!("resilient_repair_fetched_bytes_total").increment(fetched_bytes);
 }
 
 /// Record a chunk being recovered via erasure coding
 pub fn record_chunk_recovered(transfer_id: &str) {
-    counter!("resilient_chunks_recovered_total", "transfer_id" => transfer_id.to_string())
+    counter!("resilient_chunks_recovered_total", "transfer_id" => SAMPLER.transfer_label(transfer_id))
         .increment(1);
 }
 
-/// Record chunk transfer duration
+/// Record chunk transfer duration, if this chunk is sampled
 pub fn record_chunk_duration(duration: Duration) {
-    histogram!("resilient_chunk_transfer_duration_seconds").record(duration.as_secs_f64());
+    if SAMPLER.sample(ChunkEvent::Duration, None).is_some() {
+        histogram!("resilient_chunk_transfer_duration_seconds").record(duration.as_secs_f64());
+    }
 }
 
 // ============== Transfer Operations ==============
@@ -130,6 +313,7 @@ pub fn record_transfer_complete(transfer_id: &str, duration: Duration, bytes_tra
     counter!("resilient_transfers_completed_total", "transfer_id" => transfer_id.to_string())
         .increment(1);
     gauge!("resilient_active_transfers").decrement(1.0);
+    SAMPLER.forget(transfer_id);
 
     histogram!("resilient_transfer_duration_seconds").record(duration.as_secs_f64());
 
@@ -145,6 +329,12 @@ pub fn record_transfer_complete(transfer_id: &str, duration: Duration, bytes_tra
 pub fn record_transfer_failed(transfer_id: &str, reason: &str) {
     counter!("resilient_transfers_failed_total", "transfer_id" => transfer_id.to_string(), "reason" => reason.to_string()).increment(1);
     gauge!("resilient_active_transfers").decrement(1.0);
+    SAMPLER.forget(transfer_id);
+}
+
+/// Record chunks of a relayed transfer re-injected after an audit
+pub fn record_relay_chunks_reinjected(count: u64) {
+    counter!("resilient_relay_chunks_reinjected_total").increment(count);
 }
 
 // ============== Queue Metrics ==============
@@ -154,6 +344,35 @@ pub fn set_queue_depth(priority: &str, depth: usize) {
     gauge!("resilient_queue_depth", "priority" => priority.to_string()).set(depth as f64);
 }
 
+/// Update the oldest-wait gauge for a priority class
+pub fn set_queue_oldest_wait(priority: &str, wait: Duration) {
+    gauge!("resilient_queue_oldest_wait_seconds", "priority" => priority.to_string())
+        .set(wait.as_secs_f64());
+}
+
+/// Record a priority class crossing the starvation threshold
+pub fn record_starvation_alert(priority: &str) {
+    counter!("resilient_queue_starvation_alerts_total", "priority" => priority.to_string())
+        .increment(1);
+}
+
+/// Record how long a delivered chunk took from enqueue
+pub fn record_chunk_delivery_latency(priority: &str, latency: Duration) {
+    histogram!("resilient_chunk_delivery_latency_seconds", "priority" => priority.to_string())
+        .record(latency.as_secs_f64());
+}
+
+/// Record a chunk delivered after its deadline
+pub fn record_deadline_miss(priority: &str) {
+    counter!("resilient_chunk_deadline_misses_total", "priority" => priority.to_string())
+        .increment(1);
+}
+
+/// Record how many sequence numbers a late chunk arrived behind
+pub fn record_reorder_depth(depth: u32) {
+    histogram!("resilient_reorder_depth").record(depth as f64);
+}
+
 // ============== Erasure Coding Metrics ==============
 
 /// Record erasure coding configuration
@@ -169,6 +388,42 @@ pub fn set_storage_used(bytes: u64) {
     gauge!("resilient_storage_used_bytes").set(bytes as f64);
 }
 
+/// Update the session database size and row gauges
+pub fn set_session_db_stats(stats: &StorageStats) {
+    gauge!("resilient_session_db_bytes").set(stats.file_bytes.unwrap_or(stats.db_bytes) as f64);
+    gauge!("resilient_session_db_free_bytes").set((stats.free_pages * stats.page_size) as f64);
+    for (table, rows) in [
+        ("sessions", stats.sessions),
+        ("session_timeseries", stats.timeseries),
+        ("transfer_profiles", stats.profiles),
+        ("inbound_transfers", stats.inbound_transfers),
+        ("inbound_groups", stats.inbound_groups),
+    ] {
+        gauge!("resilient_session_db_rows", "table" => table).set(rows as f64);
+    }
+}
+
+/// Record a session database maintenance pass
+pub fn record_session_db_maintenance(report: &MaintenanceReport) {
+    counter!("resilient_session_db_reclaimed_bytes_total").increment(report.reclaimed_bytes);
+}
+
+/// Record one write of a reconstructed file
+pub fn record_disk_write(bytes: u64, duration: Duration) {
+    counter!("resilient_disk_written_bytes_total").increment(bytes);
+    histogram!("resilient_disk_write_duration_seconds").record(duration.as_secs_f64());
+}
+
+/// Record one fsync of a reconstructed file
+pub fn record_disk_sync(duration: Duration) {
+    histogram!("resilient_disk_sync_duration_seconds").record(duration.as_secs_f64());
+}
+
+/// Record time a reconstruction write waited on the write rate limit
+pub fn record_disk_throttle(delay: Duration) {
+    histogram!("resilient_disk_write_throttle_seconds").record(delay.as_secs_f64());
+}
+
 // ============== Network Metrics ==============
 
 /// Record network latency observation
@@ -182,6 +437,30 @@ pub fn record_packet_loss_rate(rate: f64) {
     histogram!("resilient_packet_loss_rate").record(rate);
 }
 
+/// Record a control message's size before and after wire compression
+pub fn record_wire_payload(kind: &str, raw_bytes: usize, encoded_bytes: usize) {
+    counter!("resilient_wire_raw_bytes_total", "kind" => kind.to_string())
+        .increment(raw_bytes as u64);
+    counter!("resilient_wire_encoded_bytes_total", "kind" => kind.to_string())
+        .increment(encoded_bytes as u64);
+}
+
+/// Record time a chunk write waited on the pacer
+pub fn record_pacing_delay(delay: Duration) {
+    histogram!("resilient_pacing_delay_seconds").record(delay.as_secs_f64());
+}
+
+/// Update the pacing rate gauge
+pub fn set_pacing_rate(bytes_per_second: u64) {
+    gauge!("resilient_pacing_rate_bytes_per_second").set(bytes_per_second as f64);
+}
+
+/// Record time a sender's next stream was held back by receive shaping
+pub fn record_peer_shaping_delay(peer: &str, delay: Duration) {
+    histogram!("resilient_peer_shaping_delay_seconds", "peer" => peer.to_string())
+        .record(delay.as_secs_f64());
+}
+
 /// Helper struct to time operations and record duration
 pub struct TransferMetrics {
     transfer_id: String,
@@ -0,0 +1,227 @@
+//! Sampling of per-chunk metrics
+//!
+//! A large transfer moves 100k+ chunks a minute, and recording each one
+//! costs more than the send. With sampling on, only every Nth successful
+//! chunk event is recorded and its counters are incremented by N, so totals
+//! stay close to the truth while the recorder sees 1/N of the traffic.
+//! Failures (lost, corrupt, failed sends) are rare and always recorded
+//! exactly.
+//!
+//! Per-transfer labels can also be dropped, which keeps the number of
+//! series flat no matter how many transfers run.
+//!
+//! Each [`ChunkEvent`] is counted on its own, and per transfer while
+//! per-transfer labels are on, so every series is sampled at 1/N whatever
+//! else is recorded in between.
+
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::LazyLock;
+
+/// Label value used in place of the transfer id when labels are off
+pub const ALL_TRANSFERS: &str = "all";
+
+/// Transfers counted separately at once; past this the per-transfer counts
+/// start over, so transfers that never report finishing can't pile up
+const MAX_COUNTED_TRANSFERS: usize = 4096;
+
+/// Kind of per-chunk event, each sampled on its own count
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChunkEvent {
+    Sent,
+    Received,
+    Duration,
+}
+
+impl ChunkEvent {
+    const COUNT: usize = 3;
+}
+
+/// How chunk-level metrics are sampled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SamplingConfig {
+    /// Record every Nth successful chunk event (1 = all of them)
+    pub chunk_sample_every: u64,
+    /// Label chunk metrics with their transfer id
+    pub per_transfer_labels: bool,
+}
+
+impl Default for SamplingConfig {
+    fn default() -> Self {
+        Self {
+            chunk_sample_every: 1,
+            per_transfer_labels: true,
+        }
+    }
+}
+
+/// Decides which chunk events are recorded
+#[derive(Debug)]
+pub struct ChunkSampler {
+    every: AtomicU64,
+    per_transfer_labels: AtomicBool,
+    /// Events seen of each kind, across transfers
+    seen: [AtomicU64; ChunkEvent::COUNT],
+    /// Events seen of each kind, by transfer id
+    seen_by_transfer: LazyLock<DashMap<String, [u64; ChunkEvent::COUNT]>>,
+}
+
+impl ChunkSampler {
+    pub const fn new() -> Self {
+        Self {
+            every: AtomicU64::new(1),
+            per_transfer_labels: AtomicBool::new(true),
+            seen: [const { AtomicU64::new(0) }; ChunkEvent::COUNT],
+            seen_by_transfer: LazyLock::new(DashMap::new),
+        }
+    }
+
+    pub fn configure(&self, config: SamplingConfig) {
+        self.every
+            .store(config.chunk_sample_every.max(1), Ordering::Relaxed);
+        self.per_transfer_labels
+            .store(config.per_transfer_labels, Ordering::Relaxed);
+    }
+
+    pub fn config(&self) -> SamplingConfig {
+        SamplingConfig {
+            chunk_sample_every: self.every.load(Ordering::Relaxed),
+            per_transfer_labels: self.per_transfer_labels.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Weight to record an `event` of `transfer_id` with, or `None` to
+    /// skip it
+    pub fn sample(&self, event: ChunkEvent, transfer_id: Option<&str>) -> Option<u64> {
+        let every = self.every.load(Ordering::Relaxed).max(1);
+        if every == 1 {
+            return Some(1);
+        }
+        let transfer_id = transfer_id.filter(|_| self.per_transfer_labels.load(Ordering::Relaxed));
+        let seen = match transfer_id {
+            Some(transfer_id) => self.next_for_transfer(event, transfer_id),
+            None => self.seen[event as usize].fetch_add(1, Ordering::Relaxed),
+        };
+        seen.is_multiple_of(every).then_some(every)
+    }
+
+    /// Stop counting events of a finished transfer
+    pub fn forget(&self, transfer_id: &str) {
+        self.seen_by_transfer.remove(transfer_id);
+    }
+
+    fn next_for_transfer(&self, event: ChunkEvent, transfer_id: &str) -> u64 {
+        if let Some(mut seen) = self.seen_by_transfer.get_mut(transfer_id) {
+            let next = seen[event as usize];
+            seen[event as usize] += 1;
+            return next;
+        }
+        if self.seen_by_transfer.len() >= MAX_COUNTED_TRANSFERS {
+            self.seen_by_transfer.clear();
+        }
+        let mut seen = self
+            .seen_by_transfer
+            .entry(transfer_id.to_string())
+            .or_default();
+        let next = seen[event as usize];
+        seen[event as usize] += 1;
+        next
+    }
+
+    /// Value of the `transfer_id` label for `transfer_id`
+    pub fn transfer_label(&self, transfer_id: &str) -> String {
+        if self.per_transfer_labels.load(Ordering::Relaxed) {
+            transfer_id.to_string()
+        } else {
+            ALL_TRANSFERS.to_string()
+        }
+    }
+}
+
+impl Default for ChunkSampler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Sampler behind the recorder's chunk metrics
+pub(crate) static SAMPLER: ChunkSampler = ChunkSampler::new();
+
+/// Apply `config` to chunk metrics recorded from now on
+pub fn configure_sampling(config: SamplingConfig) {
+    SAMPLER.configure(config);
+    metrics::gauge!("resilient_chunk_sample_every").set(SAMPLER.config().chunk_sample_every as f64);
+}
+
+/// Sampling in effect
+pub fn sampling() -> SamplingConfig {
+    SAMPLER.config()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_samples_every_nth_with_weight() {
+        let sampler = ChunkSampler::new();
+        assert!((0..5).all(|_| sampler.sample(ChunkEvent::Sent, None) == Some(1)));
+
+        sampler.configure(SamplingConfig {
+            chunk_sample_every: 4,
+            per_transfer_labels: false,
+        });
+        let weights: Vec<u64> = (0..12)
+            .filter_map(|_| sampler.sample(ChunkEvent::Sent, Some("t-1")))
+            .collect();
+        assert_eq!(weights, vec![4, 4, 4]);
+        // Extrapolated total matches the events seen
+        assert_eq!(weights.iter().sum::<u64>(), 12);
+        assert_eq!(sampler.transfer_label("t-1"), ALL_TRANSFERS);
+    }
+
+    #[test]
+    fn test_zero_rate_records_everything() {
+        let sampler = ChunkSampler::new();
+        sampler.configure(SamplingConfig {
+            chunk_sample_every: 0,
+            per_transfer_labels: true,
+        });
+        assert_eq!(sampler.sample(ChunkEvent::Sent, None), Some(1));
+        assert_eq!(sampler.transfer_label("t-1"), "t-1");
+    }
+
+    #[test]
+    fn test_interleaved_events_are_sampled_apart() {
+        let sampler = ChunkSampler::new();
+        sampler.configure(SamplingConfig {
+            chunk_sample_every: 2,
+            per_transfer_labels: false,
+        });
+        // Sent and received chunks alternate; each still gets every other
+        let (mut sent, mut received) = (0, 0);
+        for _ in 0..10 {
+            sent += sampler.sample(ChunkEvent::Sent, Some("t-1")).unwrap_or(0);
+            received += sampler
+                .sample(ChunkEvent::Received, Some("t-1"))
+                .unwrap_or(0);
+        }
+        assert_eq!((sent, received), (10, 10));
+
+        // With labels on, two transfers interleaved are counted apart too
+        sampler.configure(SamplingConfig {
+            chunk_sample_every: 2,
+            per_transfer_labels: true,
+        });
+        let (mut first, mut second) = (0, 0);
+        for _ in 0..10 {
+            first += sampler.sample(ChunkEvent::Sent, Some("t-1")).unwrap_or(0);
+            second += sampler.sample(ChunkEvent::Sent, Some("t-2")).unwrap_or(0);
+        }
+        assert_eq!((first, second), (10, 10));
+
+        sampler.forget("t-1");
+        assert_eq!(sampler.seen_by_transfer.len(), 1);
+    }
+}
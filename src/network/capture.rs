@@ -0,0 +1,408 @@
+//! Debug capture of QUIC connections
+//!
+//! Two kinds, both off until switched on at runtime for one peer or for
+//! all of them:
+//!
+//! - A TLS key log in the NSS format Wireshark reads, so a packet capture
+//!   of the connection can be decrypted. Keys are logged at the handshake,
+//!   so this applies to connections opened after it is switched on. An
+//!   inbound connection's peer is only known after its handshake, so its
+//!   keys are logged only while capture is on for all peers. Without a
+//!   configured path the `SSLKEYLOGFILE` variable is used.
+//! - An event log per connection: quinn's path statistics sampled at an
+//!   interval, one JSON object per line, until the connection closes or
+//!   capture is switched off. Applies to open connections too.
+
+use crate::network::error::{NetworkError, NetworkResult};
+use parking_lot::{Mutex, RwLock};
+use quinn::Connection;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Where captures are written
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaptureConfig {
+    /// Key log file; `SSLKEYLOGFILE` when unset
+    pub keylog_path: Option<PathBuf>,
+    /// Directory event logs are written to, one file per connection; event
+    /// capture is unavailable when unset
+    pub event_dir: Option<PathBuf>,
+    /// How often an event log samples its connection
+    pub sample_interval: Duration,
+}
+
+impl Default for CaptureConfig {
+    fn default() -> Self {
+        Self {
+            keylog_path: None,
+            event_dir: None,
+            sample_interval: Duration::from_secs(1),
+        }
+    }
+}
+
+/// What is captured for a peer
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CaptureFlags {
+    pub keylog: bool,
+    pub events: bool,
+}
+
+impl CaptureFlags {
+    pub fn any(&self) -> bool {
+        self.keylog || self.events
+    }
+}
+
+/// Current capture settings
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CaptureSettings {
+    pub keylog_path: Option<PathBuf>,
+    pub event_dir: Option<PathBuf>,
+    /// Applies to peers without a setting of their own
+    pub all: CaptureFlags,
+    pub peers: BTreeMap<SocketAddr, CaptureFlags>,
+}
+
+/// Runtime capture switches, shared by every connection of a transport
+#[derive(Debug)]
+pub struct DebugCapture {
+    keylog_path: Option<PathBuf>,
+    event_dir: Option<PathBuf>,
+    sample_interval: Duration,
+    all: RwLock<CaptureFlags>,
+    peers: RwLock<HashMap<SocketAddr, CaptureFlags>>,
+    keylog: Arc<KeyLogFile>,
+}
+
+impl DebugCapture {
+    pub fn new(config: &CaptureConfig) -> Self {
+        let keylog_path = config
+            .keylog_path
+            .clone()
+            .or_else(|| std::env::var_os("SSLKEYLOGFILE").map(PathBuf::from))
+            .filter(|path| !path.as_os_str().is_empty());
+        Self {
+            keylog: Arc::new(KeyLogFile::new(keylog_path.clone())),
+            keylog_path,
+            event_dir: config.event_dir.clone(),
+            sample_interval: config.sample_interval,
+            all: RwLock::new(CaptureFlags::default()),
+            peers: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// What is captured for `peer`
+    pub fn flags(&self, peer: SocketAddr) -> CaptureFlags {
+        self.peers
+            .read()
+            .get(&peer)
+            .copied()
+            .unwrap_or_else(|| *self.all.read())
+    }
+
+    /// Set what is captured for `peer`, or for every peer without a setting
+    /// of its own
+    ///
+    /// Fails if a capture is asked for that has nowhere to be written.
+    pub fn set(&self, peer: Option<SocketAddr>, flags: CaptureFlags) -> NetworkResult<()> {
+        if flags.keylog && self.keylog_path.is_none() {
+            return Err(NetworkError::CaptureUnavailable(
+                "no key log path configured and SSLKEYLOGFILE is not set".into(),
+            ));
+        }
+        if flags.events && self.event_dir.is_none() {
+            return Err(NetworkError::CaptureUnavailable(
+                "no event capture directory configured".into(),
+            ));
+        }
+        match peer {
+            Some(peer) => {
+                self.peers.write().insert(peer, flags);
+            }
+            None => *self.all.write() = flags,
+        }
+        Ok(())
+    }
+
+    /// Drop `peer`'s own setting, so the all-peers one applies again;
+    /// `false` if it had none
+    pub fn clear(&self, peer: SocketAddr) -> bool {
+        self.peers.write().remove(&peer).is_some()
+    }
+
+    /// Switch every capture off
+    pub fn reset(&self) {
+        self.peers.write().clear();
+        *self.all.write() = CaptureFlags::default();
+    }
+
+    pub fn settings(&self) -> CaptureSettings {
+        CaptureSettings {
+            keylog_path: self.keylog_path.clone(),
+            event_dir: self.event_dir.clone(),
+            all: *self.all.read(),
+            peers: self
+                .peers
+                .read()
+                .iter()
+                .map(|(peer, flags)| (*peer, *flags))
+                .collect(),
+        }
+    }
+
+    /// Key log for a new outbound connection to `peer`, if one is wanted
+    pub(crate) fn client_key_log(&self, peer: SocketAddr) -> Option<Arc<dyn rustls::KeyLog>> {
+        self.flags(peer)
+            .keylog
+            .then(|| self.keylog.clone() as Arc<dyn rustls::KeyLog>)
+    }
+
+    /// Key log for inbound connections, which logs while capture is on for
+    /// all peers
+    pub(crate) fn server_key_log(self: &Arc<Self>) -> Arc<dyn rustls::KeyLog> {
+        Arc::new(ServerKeyLog {
+            capture: Arc::downgrade(self),
+        })
+    }
+
+    /// Sample `conn` into an event log while capture is on for its peer,
+    /// until it closes
+    pub(crate) fn spawn_event_log(self: &Arc<Self>, conn: Connection) {
+        let Some(dir) = self.event_dir.clone() else {
+            return;
+        };
+        let capture = Arc::downgrade(self);
+        let interval = self.sample_interval;
+        tokio::spawn(async move {
+            let mut log: Option<EventLog> = None;
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                tokio::select! {
+                    reason = conn.closed() => {
+                        if let Some(log) = log.as_mut() {
+                            log.write(serde_json::json!({
+                                "event": "closed",
+                                "reason": reason.to_string(),
+                            }));
+                        }
+                        break;
+                    }
+                    _ = ticker.tick() => {}
+                }
+                let Some(capture) = capture.upgrade() else {
+                    break;
+                };
+                let peer = conn.remote_address();
+                if !capture.flags(peer).events {
+                    if let Some(mut log) = log.take() {
+                        log.write(serde_json::json!({ "event": "capture_stopped" }));
+                    }
+                    continue;
+                }
+                if log.is_none() {
+                    match EventLog::create(&dir, peer) {
+                        Ok(created) => log = Some(created),
+                        Err(e) => {
+                            tracing::warn!(%peer, error = %e, "Could not start event capture");
+                            continue;
+                        }
+                    }
+                }
+                if let Some(log) = log.as_mut() {
+                    log.write(path_event(&conn));
+                }
+            }
+        });
+    }
+}
+
+/// One JSON line of `conn`'s current path statistics
+fn path_event(conn: &Connection) -> serde_json::Value {
+    let stats = conn.stats();
+    serde_json::json!({
+        "event": "path",
+        "rtt_ms": stats.path.rtt.as_secs_f64() * 1000.0,
+        "cwnd": stats.path.cwnd,
+        "current_mtu": stats.path.current_mtu,
+        "sent_packets": stats.path.sent_packets,
+        "lost_packets": stats.path.lost_packets,
+        "lost_bytes": stats.path.lost_bytes,
+        "congestion_events": stats.path.congestion_events,
+        "black_holes_detected": stats.path.black_holes_detected,
+        "udp_tx_bytes": stats.udp_tx.bytes,
+        "udp_rx_bytes": stats.udp_rx.bytes,
+        "stream_frames_tx": stats.frame_tx.stream,
+        "stream_frames_rx": stats.frame_rx.stream,
+    })
+}
+
+/// Event log file of one connection
+struct EventLog {
+    file: File,
+    path: PathBuf,
+}
+
+impl EventLog {
+    fn create(dir: &Path, peer: SocketAddr) -> std::io::Result<Self> {
+        std::fs::create_dir_all(dir)?;
+        let name = format!(
+            "{}-{}.jsonl",
+            chrono::Utc::now().format("%Y%m%dT%H%M%S%.3fZ"),
+            peer.to_string().replace([':', '[', ']'], "_")
+        );
+        let path = dir.join(name);
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        tracing::info!(%peer, path = %path.display(), "Capturing connection events");
+        Ok(Self { file, path })
+    }
+
+    fn write(&mut self, mut event: serde_json::Value) {
+        event["time_ms"] = chrono::Utc::now().timestamp_millis().into();
+        if let Err(e) = writeln!(self.file, "{event}") {
+            tracing::warn!(path = %self.path.display(), error = %e, "Event capture write failed");
+        }
+    }
+}
+
+/// Appends TLS secrets to the key log file, opening it on first use
+#[derive(Debug)]
+struct KeyLogFile {
+    path: Option<PathBuf>,
+    file: Mutex<Option<File>>,
+}
+
+impl KeyLogFile {
+    fn new(path: Option<PathBuf>) -> Self {
+        Self {
+            path,
+            file: Mutex::new(None),
+        }
+    }
+}
+
+impl rustls::KeyLog for KeyLogFile {
+    fn log(&self, label: &str, client_random: &[u8], secret: &[u8]) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let mut file = self.file.lock();
+        if file.is_none() {
+            match OpenOptions::new().create(true).append(true).open(path) {
+                Ok(opened) => *file = Some(opened),
+                Err(e) => {
+                    tracing::warn!(path = %path.display(), error = %e, "Could not open key log");
+                    return;
+                }
+            }
+        }
+        if let Some(file) = file.as_mut() {
+            let line = format!(
+                "{label} {} {}\n",
+                hex::encode(client_random),
+                hex::encode(secret)
+            );
+            if let Err(e) = file.write_all(line.as_bytes()) {
+                tracing::warn!(path = %path.display(), error = %e, "Key log write failed");
+            }
+        }
+    }
+}
+
+/// Key log of the listening endpoint, gated on the all-peers setting
+#[derive(Debug)]
+struct ServerKeyLog {
+    capture: std::sync::Weak<DebugCapture>,
+}
+
+impl rustls::KeyLog for ServerKeyLog {
+    fn log(&self, label: &str, client_random: &[u8], secret: &[u8]) {
+        if let Some(capture) = self.capture.upgrade() {
+            if capture.all.read().keylog {
+                capture.keylog.log(label, client_random, secret);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustls::KeyLog;
+
+    #[test]
+    fn test_peer_settings_override_all() {
+        let dir = tempfile::tempdir().unwrap();
+        let capture = DebugCapture::new(&CaptureConfig {
+            keylog_path: Some(dir.path().join("keys.log")),
+            event_dir: Some(dir.path().join("events")),
+            ..Default::default()
+        });
+        let peer: SocketAddr = "10.0.0.5:5000".parse().unwrap();
+        let other: SocketAddr = "10.0.0.6:5000".parse().unwrap();
+        assert!(!capture.flags(peer).any());
+
+        let both = CaptureFlags {
+            keylog: true,
+            events: true,
+        };
+        capture.set(Some(peer), both).unwrap();
+        assert_eq!(capture.flags(peer), both);
+        assert!(!capture.flags(other).any());
+        assert!(capture.client_key_log(peer).is_some());
+        assert!(capture.client_key_log(other).is_none());
+
+        capture
+            .set(
+                None,
+                CaptureFlags {
+                    events: true,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert!(capture.flags(other).events);
+        assert!(capture.clear(peer));
+        assert!(!capture.flags(peer).keylog);
+        assert_eq!(capture.settings().peers.len(), 0);
+
+        // Secrets go out in the NSS key log format
+        capture
+            .keylog
+            .log("CLIENT_TRAFFIC_SECRET_0", &[0xab; 4], &[0x01, 0x02]);
+        let logged = std::fs::read_to_string(dir.path().join("keys.log")).unwrap();
+        assert_eq!(logged, "CLIENT_TRAFFIC_SECRET_0 abababab 0102\n");
+    }
+
+    #[test]
+    fn test_capture_needs_somewhere_to_write() {
+        let capture = DebugCapture::new(&CaptureConfig {
+            keylog_path: Some(PathBuf::new()),
+            ..Default::default()
+        });
+        let peer: SocketAddr = "10.0.0.5:5000".parse().unwrap();
+        for flags in [
+            CaptureFlags {
+                keylog: true,
+                events: false,
+            },
+            CaptureFlags {
+                keylog: false,
+                events: true,
+            },
+        ] {
+            assert!(matches!(
+                capture.set(Some(peer), flags),
+                Err(NetworkError::CaptureUnavailable(_))
+            ));
+        }
+        assert!(capture.set(Some(peer), CaptureFlags::default()).is_ok());
+    }
+}
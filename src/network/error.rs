@@ -40,6 +40,36 @@ pub enum NetworkError {
 
     #[error("Max retries exceeded ({0} attempts)")]
     MaxRetriesExceeded(u32),
+
+    #[error("Send cancelled")]
+    Cancelled,
+
+    #[error("Local address {addr} is unavailable (interface down or address removed?): {reason}")]
+    LocalAddressUnavailable {
+        addr: std::net::SocketAddr,
+        reason: String,
+    },
+
+    #[error("Chunk {sequence_number} of {file_id} failed its checksum")]
+    CorruptChunk {
+        file_id: String,
+        sequence_number: u32,
+    },
+
+    #[error("Capture unavailable: {0}")]
+    CaptureUnavailable(String),
+
+    #[error("Chunk payload of {size} bytes exceeds the {limit} byte per-stream limit")]
+    ChunkTooLarge { size: u64, limit: u64 },
+
+    #[error(
+        "Receive memory budget exceeded: requested {requested} bytes with {in_use}/{limit} in use"
+    )]
+    MemoryBudgetExceeded {
+        requested: usize,
+        in_use: usize,
+        limit: usize,
+    },
 }
 
 impl From<quinn::ConnectionError> for NetworkError {
@@ -0,0 +1,209 @@
+//! Flow-control window tuning
+//!
+//! Quinn's default windows are sized for about 100 Mbit/s at 100 ms. On
+//! long, fat links such as satellite paths they cap throughput well below
+//! what the path carries. [`WindowTuner`] grows a connection's send and
+//! receive windows to a multiple of its measured bandwidth-delay product.
+
+use std::time::{Duration, Instant};
+
+/// Quinn's default per-stream receive window
+pub const DEFAULT_STREAM_RECEIVE_WINDOW: u64 = 1_250_000;
+
+/// Quinn's default send window
+pub const DEFAULT_SEND_WINDOW: u64 = 8 * DEFAULT_STREAM_RECEIVE_WINDOW;
+
+/// Flow-control windows and how they are tuned
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FlowControlConfig {
+    /// Grow windows to follow each connection's bandwidth-delay product
+    pub auto_tune: bool,
+    /// Bytes the peer may have in flight on one stream; fixed for the
+    /// connection's lifetime
+    pub stream_receive_window: u64,
+    /// Bytes the peer may have in flight on the whole connection, and where
+    /// tuning starts (None = unlimited, not tuned)
+    pub receive_window: Option<u64>,
+    /// Unacknowledged bytes buffered for sending, and where tuning starts
+    pub send_window: u64,
+    /// Largest window tuning grows to
+    pub max_window: u64,
+    /// Windows are kept at this multiple of the measured BDP
+    pub bdp_multiplier: f64,
+    /// How often each connection is measured
+    pub tune_interval: Duration,
+}
+
+impl Default for FlowControlConfig {
+    fn default() -> Self {
+        Self {
+            auto_tune: true,
+            stream_receive_window: DEFAULT_STREAM_RECEIVE_WINDOW,
+            receive_window: None,
+            send_window: DEFAULT_SEND_WINDOW,
+            max_window: 256 * 1024 * 1024,
+            bdp_multiplier: 2.0,
+            tune_interval: Duration::from_secs(1),
+        }
+    }
+}
+
+/// One measurement of a connection
+#[derive(Debug, Clone, Copy)]
+pub struct WindowSample {
+    pub at: Instant,
+    pub rtt: Duration,
+    /// Congestion window in bytes
+    pub cwnd: u64,
+    /// Bytes sent and received on the connection so far
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+}
+
+/// Windows to raise after a sample; `None` leaves a window as it is
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WindowUpdate {
+    pub send_window: Option<u64>,
+    pub receive_window: Option<u64>,
+}
+
+impl WindowUpdate {
+    pub fn is_empty(&self) -> bool {
+        self.send_window.is_none() && self.receive_window.is_none()
+    }
+}
+
+/// Bandwidth-delay product in bytes
+pub fn bdp(bytes_per_sec: f64, rtt: Duration) -> u64 {
+    (bytes_per_sec * rtt.as_secs_f64()) as u64
+}
+
+/// Tracks one connection's windows and grows them as its BDP grows
+///
+/// Windows only grow. A window the path fills keeps the measured BDP at the
+/// window, so keeping windows at `bdp_multiplier` times the BDP lets the
+/// congestion controller probe past them until the path, not flow control,
+/// is the limit.
+#[derive(Debug)]
+pub struct WindowTuner {
+    config: FlowControlConfig,
+    send_window: u64,
+    receive_window: Option<u64>,
+    last: Option<WindowSample>,
+}
+
+impl WindowTuner {
+    pub fn new(config: FlowControlConfig) -> Self {
+        Self {
+            config,
+            send_window: config.send_window,
+            receive_window: config.receive_window,
+            last: None,
+        }
+    }
+
+    pub fn send_window(&self) -> u64 {
+        self.send_window
+    }
+
+    pub fn receive_window(&self) -> Option<u64> {
+        self.receive_window
+    }
+
+    /// Feed a measurement; returns the windows that should grow
+    pub fn observe(&mut self, sample: WindowSample) -> WindowUpdate {
+        let Some(last) = self.last.replace(sample) else {
+            return WindowUpdate::default();
+        };
+        let elapsed = sample.at.saturating_duration_since(last.at).as_secs_f64();
+        if elapsed <= 0.0 || sample.rtt.is_zero() {
+            return WindowUpdate::default();
+        }
+        let sent_rate = sample.bytes_sent.saturating_sub(last.bytes_sent) as f64 / elapsed;
+        let received_rate =
+            sample.bytes_received.saturating_sub(last.bytes_received) as f64 / elapsed;
+
+        let mut update = WindowUpdate::default();
+        let send_bdp = bdp(sent_rate, sample.rtt).max(sample.cwnd);
+        if let Some(window) = self.grown(self.send_window, send_bdp) {
+            self.send_window = window;
+            update.send_window = Some(window);
+        }
+        if let Some(current) = self.receive_window {
+            if let Some(window) = self.grown(current, bdp(received_rate, sample.rtt)) {
+                self.receive_window = Some(window);
+                update.receive_window = Some(window);
+            }
+        }
+        update
+    }
+
+    /// `current` raised to cover `bdp`, if that makes it larger
+    fn grown(&self, current: u64, bdp: u64) -> Option<u64> {
+        let target = ((bdp as f64 * self.config.bdp_multiplier) as u64).min(self.config.max_window);
+        (target > current).then_some(target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(at: Instant, rtt_ms: u64, cwnd: u64, sent: u64, received: u64) -> WindowSample {
+        WindowSample {
+            at,
+            rtt: Duration::from_millis(rtt_ms),
+            cwnd,
+            bytes_sent: sent,
+            bytes_received: received,
+        }
+    }
+
+    #[test]
+    fn test_windows_grow_with_bdp_and_never_shrink() {
+        let mut tuner = WindowTuner::new(FlowControlConfig {
+            receive_window: Some(DEFAULT_SEND_WINDOW),
+            ..Default::default()
+        });
+        let start = Instant::now();
+        assert!(tuner.observe(sample(start, 600, 0, 0, 0)).is_empty());
+
+        // 50 MB/s at 600 ms is a 30 MB BDP, beyond the 10 MB defaults
+        let second = start + Duration::from_secs(1);
+        let update = tuner.observe(sample(second, 600, 12_000, 50_000_000, 50_000_000));
+        assert_eq!(update.send_window, Some(60_000_000));
+        assert_eq!(update.receive_window, Some(60_000_000));
+
+        // An idle second leaves the windows where they are
+        let update = tuner.observe(sample(
+            second + Duration::from_secs(1),
+            600,
+            12_000,
+            50_000_000,
+            50_000_000,
+        ));
+        assert!(update.is_empty());
+        assert_eq!(tuner.send_window(), 60_000_000);
+    }
+
+    #[test]
+    fn test_tuning_is_capped() {
+        let mut tuner = WindowTuner::new(FlowControlConfig {
+            max_window: 16_000_000,
+            ..Default::default()
+        });
+        let start = Instant::now();
+        tuner.observe(sample(start, 0, 0, 0, 0));
+        // cwnd alone is enough to grow the send window
+        let update = tuner.observe(sample(
+            start + Duration::from_secs(1),
+            800,
+            40_000_000,
+            0,
+            0,
+        ));
+        assert_eq!(update.send_window, Some(16_000_000));
+        // Unlimited receive windows aren't tuned
+        assert_eq!(update.receive_window, None);
+    }
+}
@@ -0,0 +1,247 @@
+//! Global in-flight memory budget for the receive path
+//!
+//! Every chunk read off a QUIC stream (and every chunk buffered while a file
+//! waits for reconstruction) is charged against one shared budget. Reads that
+//! would overflow the hard limit fail, and once usage crosses the high
+//! watermark the receive loop stops accepting new streams until memory is
+//! released, letting QUIC flow control push back on the sender.
+
+use crate::network::error::{NetworkError, NetworkResult};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::sync::Notify;
+
+/// Shared accounting of receive-path memory
+#[derive(Debug)]
+pub struct MemoryBudget {
+    limit: usize,
+    high_watermark: usize,
+    in_use: AtomicUsize,
+    peak: AtomicUsize,
+    pauses: AtomicU64,
+    released: Notify,
+}
+
+/// Snapshot of budget usage
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MemoryBudgetStats {
+    pub limit: usize,
+    pub high_watermark: usize,
+    pub in_use: usize,
+    pub peak: usize,
+    /// Times the receive loop paused because usage was above the watermark
+    pub backpressure_pauses: u64,
+}
+
+impl MemoryBudget {
+    /// Create a budget of `limit` bytes, pausing at `watermark_ratio` of it
+    pub fn new(limit: usize, watermark_ratio: f64) -> Arc<Self> {
+        let ratio = watermark_ratio.clamp(0.0, 1.0);
+        Arc::new(Self {
+            limit,
+            high_watermark: (limit as f64 * ratio) as usize,
+            in_use: AtomicUsize::new(0),
+            peak: AtomicUsize::new(0),
+            pauses: AtomicU64::new(0),
+            released: Notify::new(),
+        })
+    }
+
+    /// Reserve `bytes`, failing if the hard limit would be exceeded
+    pub fn try_reserve(self: &Arc<Self>, bytes: usize) -> NetworkResult<MemoryReservation> {
+        let mut current = self.in_use.load(Ordering::Acquire);
+        loop {
+            let next = current.saturating_add(bytes);
+            if next > self.limit {
+                return Err(NetworkError::MemoryBudgetExceeded {
+                    requested: bytes,
+                    in_use: current,
+                    limit: self.limit,
+                });
+            }
+            match self.in_use.compare_exchange_weak(
+                current,
+                next,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => {
+                    self.peak.fetch_max(next, Ordering::Relaxed);
+                    return Ok(MemoryReservation {
+                        budget: self.clone(),
+                        bytes,
+                    });
+                }
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
+    /// An empty reservation that can later be grown
+    pub fn empty_reservation(self: &Arc<Self>) -> MemoryReservation {
+        MemoryReservation {
+            budget: self.clone(),
+            bytes: 0,
+        }
+    }
+
+    /// Wait until usage drops below the high watermark
+    pub async fn wait_for_capacity(&self) {
+        let mut paused = false;
+        loop {
+            // Register before checking so a release in between isn't missed
+            let notified = self.released.notified();
+            if !self.above_watermark() {
+                return;
+            }
+            if !paused {
+                paused = true;
+                self.pauses.fetch_add(1, Ordering::Relaxed);
+                tracing::debug!(
+                    in_use = self.in_use(),
+                    watermark = self.high_watermark,
+                    "receive memory above watermark, pausing stream accept"
+                );
+            }
+            notified.await;
+        }
+    }
+
+    /// Whether usage is at or above the high watermark
+    pub fn above_watermark(&self) -> bool {
+        self.in_use() >= self.high_watermark
+    }
+
+    /// Bytes currently reserved
+    pub fn in_use(&self) -> usize {
+        self.in_use.load(Ordering::Acquire)
+    }
+
+    /// Usage snapshot
+    pub fn stats(&self) -> MemoryBudgetStats {
+        MemoryBudgetStats {
+            limit: self.limit,
+            high_watermark: self.high_watermark,
+            in_use: self.in_use(),
+            peak: self.peak.load(Ordering::Relaxed),
+            backpressure_pauses: self.pauses.load(Ordering::Relaxed),
+        }
+    }
+
+    fn grow(&self, bytes: usize) {
+        let next = self.in_use.fetch_add(bytes, Ordering::AcqRel) + bytes;
+        self.peak.fetch_max(next, Ordering::Relaxed);
+    }
+
+    fn release(&self, bytes: usize) {
+        if bytes == 0 {
+            return;
+        }
+        self.in_use.fetch_sub(bytes, Ordering::AcqRel);
+        self.released.notify_waiters();
+    }
+}
+
+/// Bytes charged against a [`MemoryBudget`], returned on drop
+#[derive(Debug)]
+pub struct MemoryReservation {
+    budget: Arc<MemoryBudget>,
+    bytes: usize,
+}
+
+impl MemoryReservation {
+    /// Bytes held by this reservation
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    /// Charge more bytes to the budget
+    ///
+    /// Unlike [`MemoryBudget::try_reserve`] this never fails: it's used for
+    /// data that is already in memory, and the watermark pause is what keeps
+    /// it bounded.
+    pub fn grow(&mut self, bytes: usize) {
+        self.budget.grow(bytes);
+        self.bytes += bytes;
+    }
+
+    /// Shrink or grow the reservation to exactly `bytes`
+    pub fn resize(&mut self, bytes: usize) {
+        if bytes > self.bytes {
+            self.grow(bytes - self.bytes);
+        } else {
+            self.budget.release(self.bytes - bytes);
+            self.bytes = bytes;
+        }
+    }
+
+    /// Fold another reservation into this one
+    pub fn merge(&mut self, mut other: MemoryReservation) {
+        self.bytes += std::mem::take(&mut other.bytes);
+    }
+}
+
+impl Drop for MemoryReservation {
+    fn drop(&mut self) {
+        self.budget.release(self.bytes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_reserve_and_release_tracks_peak() {
+        let budget = MemoryBudget::new(1000, 0.8);
+        let a = budget.try_reserve(400).unwrap();
+        let mut b = budget.try_reserve(300).unwrap();
+        assert_eq!(budget.in_use(), 700);
+
+        b.resize(100);
+        assert_eq!(budget.in_use(), 500);
+        drop(a);
+        drop(b);
+
+        let stats = budget.stats();
+        assert_eq!(stats.in_use, 0);
+        assert_eq!(stats.peak, 700);
+    }
+
+    #[test]
+    fn test_reserve_over_limit_fails() {
+        let budget = MemoryBudget::new(1000, 0.8);
+        let _held = budget.try_reserve(900).unwrap();
+        let err = budget.try_reserve(200).unwrap_err();
+        assert!(matches!(
+            err,
+            NetworkError::MemoryBudgetExceeded {
+                requested: 200,
+                in_use: 900,
+                limit: 1000
+            }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_capacity_resumes_after_release() {
+        let budget = MemoryBudget::new(1000, 0.5);
+        let held = budget.try_reserve(600).unwrap();
+        assert!(budget.above_watermark());
+
+        let waiter = {
+            let budget = budget.clone();
+            tokio::spawn(async move { budget.wait_for_capacity().await })
+        };
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!waiter.is_finished());
+
+        drop(held);
+        tokio::time::timeout(Duration::from_secs(1), waiter)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(budget.stats().backpressure_pauses, 1);
+    }
+}
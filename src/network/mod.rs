@@ -1,14 +1,30 @@
+pub mod capture;
 pub mod error;
+pub mod flow_control;
+pub mod memory_budget;
 pub mod multipath;
+pub mod pacer;
+pub mod peer_shaper;
+pub mod probe;
 pub mod quic_transport;
 pub mod rate_limiter;
 pub mod types;
+pub mod wire;
 
+pub use capture::{CaptureConfig, CaptureFlags, CaptureSettings, DebugCapture};
 pub use error::{NetworkError, NetworkResult};
+pub use flow_control::{FlowControlConfig, WindowTuner};
+pub use memory_budget::{MemoryBudget, MemoryBudgetStats, MemoryReservation};
 pub use multipath::MultiPathManager;
-pub use quic_transport::QuicTransport;
+pub use pacer::{ChunkPacer, PacerConfig, PacerStats};
+pub use peer_shaper::{PeerRate, PeerShaper, PeerShapingConfig};
+pub use probe::LinkReport;
+pub use quic_transport::{QuicTransport, ReceivedChunk};
 pub use rate_limiter::TransferRateLimiter;
 pub use types::{
-    ConnectionConfig, NetworkPath, NetworkStats, PathMetrics, PathStatus, QuicPathStats,
-    SessionStatus, TransferDirection, TransferSession,
+    ChunkNack, ConnectionConfig, ConnectionDirection, ConnectionInfo, FileOffer, GroupFeedback,
+    NegotiatedParams, NetworkPath, NetworkStats, OfferReply, PathMetrics, PathStatus,
+    QuicPathStats, ReceiverFeedback, ReceiverStats, RepairReply, RepairRequest, SessionStatus,
+    TransferDirection, TransferSession,
 };
+pub use wire::{Capabilities, WireCodec, WirePayload};
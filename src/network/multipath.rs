@@ -91,7 +91,7 @@ impl MultiPathManager {
             return None;
         }
 
-        match priority {
+        match priority.class() {
             Priority::Critical => {
                 // Use lowest latency path
                 active_paths
@@ -99,7 +99,7 @@ impl MultiPathManager {
                     .min_by_key(|p| p.metrics.rtt_ms)
                     .map(|p| (*p).clone())
             }
-            Priority::High | Priority::Normal => {
+            Priority::High | Priority::Normal | Priority::Level(_) => {
                 // Use highest bandwidth path
                 active_paths
                     .iter()
@@ -0,0 +1,230 @@
+//! Chunk send pacing
+//!
+//! Writing chunks to QUIC back-to-back produces bursts that overflow the
+//! small buffers of constrained links (e.g. 1 Mbps satellite or radio
+//! uplinks) even when the average rate is fine. [`ChunkPacer`] is a token
+//! bucket that spaces chunk writes at the bottleneck bandwidth, either a
+//! configured rate or one estimated from the connection's congestion window.
+
+use crate::metrics::recorder;
+use crate::network::types::QuicPathStats;
+use parking_lot::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Pacing settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PacerConfig {
+    /// Bottleneck bandwidth in bytes per second (0 = no fixed rate)
+    pub rate_bytes_per_sec: u64,
+    /// Bytes that may be sent back-to-back before pacing kicks in
+    pub burst_bytes: usize,
+    /// Follow the measured path bandwidth (cwnd / RTT), capped by
+    /// `rate_bytes_per_sec` when that is set
+    pub adaptive: bool,
+}
+
+impl Default for PacerConfig {
+    fn default() -> Self {
+        Self {
+            rate_bytes_per_sec: 0,
+            burst_bytes: 64 * 1024,
+            adaptive: false,
+        }
+    }
+}
+
+impl PacerConfig {
+    /// Whether this config paces at all
+    pub fn is_enabled(&self) -> bool {
+        self.rate_bytes_per_sec > 0 || self.adaptive
+    }
+}
+
+/// Pacer counters
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PacerStats {
+    pub rate_bytes_per_sec: u64,
+    pub burst_bytes: usize,
+    /// Chunks that went through the pacer
+    pub paced_chunks: u64,
+    /// Chunks that had to wait for tokens
+    pub delayed_chunks: u64,
+    /// Total time spent waiting
+    pub total_delay_ms: u64,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    /// Available bytes; negative while paying off an oversized chunk
+    tokens: f64,
+    last_refill: Instant,
+    rate: f64,
+}
+
+/// Token-bucket pacer for chunk writes
+#[derive(Debug)]
+pub struct ChunkPacer {
+    config: PacerConfig,
+    bucket: Mutex<Bucket>,
+    paced_chunks: AtomicU64,
+    delayed_chunks: AtomicU64,
+    delay_us: AtomicU64,
+}
+
+impl ChunkPacer {
+    pub fn new(config: PacerConfig) -> Self {
+        Self {
+            config,
+            bucket: Mutex::new(Bucket {
+                tokens: config.burst_bytes as f64,
+                last_refill: Instant::now(),
+                rate: config.rate_bytes_per_sec as f64,
+            }),
+            paced_chunks: AtomicU64::new(0),
+            delayed_chunks: AtomicU64::new(0),
+            delay_us: AtomicU64::new(0),
+        }
+    }
+
+    /// Wait until `bytes` may be written
+    ///
+    /// A chunk larger than the burst only waits for a full bucket; the rest
+    /// is taken as debt, so the following write waits `bytes / rate`.
+    pub async fn pace(&self, bytes: usize) {
+        let wait = {
+            let mut bucket = self.bucket.lock();
+            if bucket.rate <= 0.0 {
+                return;
+            }
+            self.refill(&mut bucket);
+            let needed = bytes.min(self.config.burst_bytes) as f64;
+            let wait = if bucket.tokens >= needed {
+                None
+            } else {
+                Some(Duration::from_secs_f64(
+                    (needed - bucket.tokens) / bucket.rate,
+                ))
+            };
+            bucket.tokens -= bytes as f64;
+            wait
+        };
+
+        self.paced_chunks.fetch_add(1, Ordering::Relaxed);
+        if let Some(wait) = wait {
+            self.delayed_chunks.fetch_add(1, Ordering::Relaxed);
+            self.delay_us
+                .fetch_add(wait.as_micros() as u64, Ordering::Relaxed);
+            recorder::record_pacing_delay(wait);
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Change the pacing rate (bytes per second, 0 disables pacing)
+    pub fn set_rate(&self, rate_bytes_per_sec: u64) {
+        let mut bucket = self.bucket.lock();
+        self.refill(&mut bucket);
+        bucket.rate = rate_bytes_per_sec as f64;
+        recorder::set_pacing_rate(rate_bytes_per_sec);
+    }
+
+    /// Feed a path measurement; adaptive pacers follow cwnd / RTT
+    pub fn observe_path(&self, stats: &QuicPathStats) {
+        if !self.config.adaptive || stats.rtt_ms <= 0.0 || stats.cwnd == 0 {
+            return;
+        }
+        let mut estimate = (stats.cwnd as f64 / (stats.rtt_ms / 1000.0)) as u64;
+        if self.config.rate_bytes_per_sec > 0 {
+            estimate = estimate.min(self.config.rate_bytes_per_sec);
+        }
+        self.set_rate(estimate);
+    }
+
+    /// Current pacing rate in bytes per second
+    pub fn rate(&self) -> u64 {
+        self.bucket.lock().rate as u64
+    }
+
+    pub fn config(&self) -> PacerConfig {
+        self.config
+    }
+
+    pub fn stats(&self) -> PacerStats {
+        PacerStats {
+            rate_bytes_per_sec: self.rate(),
+            burst_bytes: self.config.burst_bytes,
+            paced_chunks: self.paced_chunks.load(Ordering::Relaxed),
+            delayed_chunks: self.delayed_chunks.load(Ordering::Relaxed),
+            total_delay_ms: self.delay_us.load(Ordering::Relaxed) / 1000,
+        }
+    }
+
+    fn refill(&self, bucket: &mut Bucket) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(bucket.last_refill).as_secs_f64();
+        bucket.last_refill = now;
+        bucket.tokens = (bucket.tokens + elapsed * bucket.rate).min(self.config.burst_bytes as f64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_disabled_pacer_does_not_wait() {
+        let pacer = ChunkPacer::new(PacerConfig::default());
+        let start = Instant::now();
+        for _ in 0..100 {
+            pacer.pace(1024 * 1024).await;
+        }
+        assert!(start.elapsed() < Duration::from_millis(50));
+        assert_eq!(pacer.stats().paced_chunks, 0);
+    }
+
+    #[tokio::test]
+    async fn test_pacer_spaces_writes_beyond_burst() {
+        // 100 KB/s with a 10 KB burst: three 10 KB chunks need ~200ms
+        let pacer = ChunkPacer::new(PacerConfig {
+            rate_bytes_per_sec: 100_000,
+            burst_bytes: 10_000,
+            adaptive: false,
+        });
+
+        let start = Instant::now();
+        for _ in 0..3 {
+            pacer.pace(10_000).await;
+        }
+        let elapsed = start.elapsed();
+
+        assert!(elapsed >= Duration::from_millis(180), "{elapsed:?}");
+        assert!(elapsed < Duration::from_secs(2));
+        let stats = pacer.stats();
+        assert_eq!(stats.paced_chunks, 3);
+        assert_eq!(stats.delayed_chunks, 2);
+    }
+
+    #[test]
+    fn test_adaptive_rate_follows_path_capped_by_config() {
+        let pacer = ChunkPacer::new(PacerConfig {
+            rate_bytes_per_sec: 50_000,
+            burst_bytes: 16 * 1024,
+            adaptive: true,
+        });
+
+        // 12 KB window over 200ms RTT = 60 KB/s, capped at 50 KB/s
+        pacer.observe_path(&QuicPathStats {
+            rtt_ms: 200.0,
+            cwnd: 12_000,
+            ..Default::default()
+        });
+        assert_eq!(pacer.rate(), 50_000);
+
+        pacer.observe_path(&QuicPathStats {
+            rtt_ms: 400.0,
+            cwnd: 12_000,
+            ..Default::default()
+        });
+        assert_eq!(pacer.rate(), 30_000);
+    }
+}
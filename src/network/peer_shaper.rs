@@ -0,0 +1,399 @@
+//! Per-sender receive-rate shaping
+//!
+//! A receiver serving many field units can have its link filled by one
+//! aggressive sender. [`PeerShaper`] keeps a token bucket per source address
+//! and holds back the next stream from a sender that is over its rate, so
+//! QUIC flow control slows that sender alone. When a total rate is set it is
+//! shared max-min fairly between the senders active at the time: a sender
+//! capped below an equal share leaves the rest to the others.
+
+use crate::metrics::recorder;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, Instant};
+
+/// How long a measured rate is averaged over
+const RATE_WINDOW: Duration = Duration::from_secs(1);
+
+/// Receive-rate limits per sender
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeerShapingConfig {
+    /// Rate any one sender may send at (bytes/s, 0 = unlimited)
+    pub default_rate_bytes_per_sec: u64,
+    /// Rates for particular senders, in place of the default (0 =
+    /// unlimited)
+    pub overrides: HashMap<IpAddr, u64>,
+    /// Rate shared between all active senders (bytes/s, 0 = unlimited)
+    pub total_rate_bytes_per_sec: u64,
+    /// Bytes a sender may deliver back-to-back before shaping applies
+    pub burst_bytes: usize,
+    /// A sender quiet for this long stops taking a share of the total
+    pub idle_timeout: Duration,
+}
+
+impl Default for PeerShapingConfig {
+    fn default() -> Self {
+        Self {
+            default_rate_bytes_per_sec: 0,
+            overrides: HashMap::new(),
+            total_rate_bytes_per_sec: 0,
+            burst_bytes: 256 * 1024,
+            idle_timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+impl PeerShapingConfig {
+    /// Whether any sender is limited at all
+    pub fn is_enabled(&self) -> bool {
+        self.default_rate_bytes_per_sec > 0
+            || self.total_rate_bytes_per_sec > 0
+            || self.overrides.values().any(|&rate| rate > 0)
+    }
+
+    /// Rate `peer` is capped at on its own, or `None` when unlimited
+    pub fn limit_for(&self, peer: IpAddr) -> Option<u64> {
+        let limit = self
+            .overrides
+            .get(&peer)
+            .copied()
+            .unwrap_or(self.default_rate_bytes_per_sec);
+        (limit > 0).then_some(limit)
+    }
+}
+
+/// One sender's shaping state, as reported in network metrics
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PeerRate {
+    pub peer: IpAddr,
+    /// Rate the sender is held to right now, its own cap or its share of
+    /// the total (None = unlimited)
+    pub limit_bytes_per_sec: Option<u64>,
+    /// Rate measured over the last second
+    pub rate_bytes_per_sec: u64,
+    pub bytes_received: u64,
+    /// Streams held back until the sender was under its rate
+    pub delayed_streams: u64,
+    /// Total time streams were held back
+    pub delay_ms: u64,
+    /// Whether the sender counts towards the fair share
+    pub active: bool,
+}
+
+#[derive(Debug)]
+struct PeerState {
+    /// Bytes the sender may deliver; negative while paying off a chunk
+    tokens: f64,
+    last_refill: Instant,
+    last_seen: Instant,
+    bytes_received: u64,
+    delayed_streams: u64,
+    delay_us: u64,
+    window_start: Instant,
+    window_bytes: u64,
+    measured_rate: u64,
+}
+
+impl PeerState {
+    fn new(now: Instant, burst: usize) -> Self {
+        Self {
+            tokens: burst as f64,
+            last_refill: now,
+            last_seen: now,
+            bytes_received: 0,
+            delayed_streams: 0,
+            delay_us: 0,
+            window_start: now,
+            window_bytes: 0,
+            measured_rate: 0,
+        }
+    }
+}
+
+/// Split `total` between senders capped at `caps` (None = unlimited), max-min
+/// fairly: no sender gets more than its cap, and what a capped sender leaves
+/// goes to the others equally
+pub fn fair_shares(total: u64, caps: &[Option<u64>]) -> Vec<u64> {
+    let mut order: Vec<usize> = (0..caps.len()).collect();
+    order.sort_by_key(|&i| caps[i].unwrap_or(u64::MAX));
+    let mut shares = vec![0; caps.len()];
+    let mut remaining = total;
+    for (done, &i) in order.iter().enumerate() {
+        let equal = remaining / (caps.len() - done) as u64;
+        shares[i] = caps[i].map_or(equal, |cap| cap.min(equal));
+        remaining -= shares[i];
+    }
+    shares
+}
+
+/// Token buckets limiting each sender's receive rate
+#[derive(Debug)]
+pub struct PeerShaper {
+    config: PeerShapingConfig,
+    peers: Mutex<HashMap<IpAddr, PeerState>>,
+}
+
+impl PeerShaper {
+    pub fn new(config: PeerShapingConfig) -> Self {
+        Self {
+            config,
+            peers: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn config(&self) -> &PeerShapingConfig {
+        &self.config
+    }
+
+    /// Wait until `peer` is back under its rate before taking another of
+    /// its streams
+    pub async fn wait_turn(&self, peer: SocketAddr) {
+        if !self.config.is_enabled() {
+            return;
+        }
+        let wait = {
+            let mut peers = self.peers.lock();
+            let now = Instant::now();
+            self.prune(&mut peers, now);
+            let limits = self.limits(&peers, now, Some(peer.ip()));
+            let state = peers
+                .entry(peer.ip())
+                .or_insert_with(|| PeerState::new(now, self.config.burst_bytes));
+            let rate = limits.get(&peer.ip()).copied().flatten();
+            self.refill(state, now, rate);
+            match rate {
+                Some(rate) if state.tokens < 0.0 => {
+                    let wait = Duration::from_secs_f64(-state.tokens / rate.max(1) as f64);
+                    state.delayed_streams += 1;
+                    state.delay_us += wait.as_micros() as u64;
+                    Some(wait)
+                }
+                _ => None,
+            }
+        };
+        if let Some(wait) = wait {
+            recorder::record_peer_shaping_delay(&peer.ip().to_string(), wait);
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Charge `bytes` received from `peer` against its rate
+    pub fn charge(&self, peer: SocketAddr, bytes: usize) {
+        let mut peers = self.peers.lock();
+        let now = Instant::now();
+        self.prune(&mut peers, now);
+        let limits = self.limits(&peers, now, Some(peer.ip()));
+        let state = peers
+            .entry(peer.ip())
+            .or_insert_with(|| PeerState::new(now, self.config.burst_bytes));
+        self.refill(state, now, limits.get(&peer.ip()).copied().flatten());
+        state.tokens -= bytes as f64;
+        state.last_seen = now;
+        state.bytes_received += bytes as u64;
+        state.window_bytes += bytes as u64;
+        let elapsed = now.saturating_duration_since(state.window_start);
+        if elapsed >= RATE_WINDOW {
+            state.measured_rate = (state.window_bytes as f64 / elapsed.as_secs_f64()) as u64;
+            state.window_start = now;
+            state.window_bytes = 0;
+        }
+    }
+
+    /// Every sender seen since it last went idle, with the rate it is held
+    /// to and the rate it sends at
+    pub fn peers(&self) -> Vec<PeerRate> {
+        let peers = self.peers.lock();
+        let now = Instant::now();
+        let limits = self.limits(&peers, now, None);
+        let mut rates: Vec<_> = peers
+            .iter()
+            .map(|(&peer, state)| {
+                let active = self.is_active(state, now);
+                PeerRate {
+                    peer,
+                    limit_bytes_per_sec: limits
+                        .get(&peer)
+                        .copied()
+                        .unwrap_or_else(|| self.config.limit_for(peer)),
+                    rate_bytes_per_sec: if active { state.measured_rate } else { 0 },
+                    bytes_received: state.bytes_received,
+                    delayed_streams: state.delayed_streams,
+                    delay_ms: state.delay_us / 1000,
+                    active,
+                }
+            })
+            .collect();
+        rates.sort_by_key(|rate| rate.peer);
+        rates
+    }
+
+    fn is_active(&self, state: &PeerState, now: Instant) -> bool {
+        now.saturating_duration_since(state.last_seen) < self.config.idle_timeout
+    }
+
+    /// Forget senders that went idle, unless they still owe for their last
+    /// chunk; a receiver serving many field units would otherwise keep
+    /// every address it ever saw
+    fn prune(&self, peers: &mut HashMap<IpAddr, PeerState>, now: Instant) {
+        let total = (self.config.total_rate_bytes_per_sec > 0)
+            .then_some(self.config.total_rate_bytes_per_sec);
+        peers.retain(|&peer, state| {
+            if self.is_active(state, now) {
+                return true;
+            }
+            // An uncapped sender refills no faster than the whole total
+            let idle = now.saturating_duration_since(state.last_refill);
+            self.config
+                .limit_for(peer)
+                .or(total)
+                .is_some_and(|rate| state.tokens + idle.as_secs_f64() * (rate as f64) < 0.0)
+        });
+    }
+
+    /// Rate each active sender (and `joining`, about to be) is held to
+    fn limits(
+        &self,
+        peers: &HashMap<IpAddr, PeerState>,
+        now: Instant,
+        joining: Option<IpAddr>,
+    ) -> HashMap<IpAddr, Option<u64>> {
+        let mut active: Vec<IpAddr> = peers
+            .iter()
+            .filter(|(_, state)| self.is_active(state, now))
+            .map(|(&peer, _)| peer)
+            .collect();
+        if let Some(peer) = joining.filter(|peer| !active.contains(peer)) {
+            active.push(peer);
+        }
+        let caps: Vec<_> = active.iter().map(|&p| self.config.limit_for(p)).collect();
+        if self.config.total_rate_bytes_per_sec == 0 {
+            return active.into_iter().zip(caps).collect();
+        }
+        let shares = fair_shares(self.config.total_rate_bytes_per_sec, &caps);
+        active
+            .into_iter()
+            .zip(shares.into_iter().map(|share| Some(share.max(1))))
+            .collect()
+    }
+
+    fn refill(&self, state: &mut PeerState, now: Instant, rate: Option<u64>) {
+        let elapsed = now
+            .saturating_duration_since(state.last_refill)
+            .as_secs_f64();
+        state.last_refill = now;
+        let burst = self.config.burst_bytes as f64;
+        state.tokens = match rate {
+            Some(rate) => (state.tokens + elapsed * rate as f64).min(burst),
+            None => burst,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(last: u8) -> SocketAddr {
+        SocketAddr::from(([10, 0, 0, last], 5000))
+    }
+
+    #[test]
+    fn test_fair_shares_give_capped_leftovers_to_others() {
+        assert_eq!(fair_shares(900, &[None, None, None]), vec![300, 300, 300]);
+        // The capped sender's unused 250 is split between the other two
+        assert_eq!(
+            fair_shares(900, &[Some(50), None, Some(1000)]),
+            vec![50, 425, 425]
+        );
+        assert_eq!(fair_shares(900, &[]), Vec::<u64>::new());
+    }
+
+    #[tokio::test]
+    async fn test_sender_over_its_rate_waits() {
+        // 100 KB/s with a 10 KB burst: the 40 KB past the burst is ~400ms,
+        // less the last chunk's, which is only paid by the next stream
+        let shaper = PeerShaper::new(PeerShapingConfig {
+            default_rate_bytes_per_sec: 100_000,
+            burst_bytes: 10_000,
+            ..Default::default()
+        });
+        let start = Instant::now();
+        for _ in 0..5 {
+            shaper.wait_turn(addr(1)).await;
+            shaper.charge(addr(1), 10_000);
+        }
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(250), "{elapsed:?}");
+        assert!(elapsed < Duration::from_secs(2));
+
+        // Another sender has its own bucket
+        let start = Instant::now();
+        shaper.wait_turn(addr(2)).await;
+        assert!(start.elapsed() < Duration::from_millis(50));
+
+        let peers = shaper.peers();
+        assert_eq!(peers.len(), 2);
+        assert_eq!(peers[0].bytes_received, 50_000);
+        assert_eq!(peers[0].delayed_streams, 3);
+        assert_eq!(peers[0].limit_bytes_per_sec, Some(100_000));
+    }
+
+    #[test]
+    fn test_total_rate_is_shared_between_active_senders() {
+        let shaper = PeerShaper::new(PeerShapingConfig {
+            total_rate_bytes_per_sec: 1_000_000,
+            overrides: HashMap::from([(addr(3).ip(), 100_000)]),
+            ..Default::default()
+        });
+        shaper.charge(addr(1), 1);
+        assert_eq!(shaper.peers()[0].limit_bytes_per_sec, Some(1_000_000));
+
+        shaper.charge(addr(2), 1);
+        shaper.charge(addr(3), 1);
+        let limits: Vec<_> = shaper
+            .peers()
+            .iter()
+            .map(|p| p.limit_bytes_per_sec)
+            .collect();
+        assert_eq!(limits, vec![Some(450_000), Some(450_000), Some(100_000)]);
+    }
+
+    #[tokio::test]
+    async fn test_idle_senders_are_forgotten_once_paid_off() {
+        let shaper = PeerShaper::new(PeerShapingConfig {
+            default_rate_bytes_per_sec: 1_000_000,
+            overrides: HashMap::from([(addr(2).ip(), 1_000)]),
+            burst_bytes: 1_000,
+            idle_timeout: Duration::from_millis(20),
+            ..Default::default()
+        });
+        shaper.charge(addr(1), 1_000);
+        // Owes 10s at its 1 KB/s cap
+        shaper.charge(addr(2), 11_000);
+        assert_eq!(shaper.peers().len(), 2);
+
+        tokio::time::sleep(Duration::from_millis(40)).await;
+        shaper.charge(addr(3), 1);
+        let peers: Vec<_> = shaper.peers().iter().map(|p| p.peer).collect();
+        assert_eq!(peers, vec![addr(2).ip(), addr(3).ip()]);
+
+        // The debtor still waits out its debt when it comes back
+        let start = Instant::now();
+        let wait =
+            tokio::time::timeout(Duration::from_millis(100), shaper.wait_turn(addr(2))).await;
+        assert!(wait.is_err(), "{:?}", start.elapsed());
+    }
+
+    #[tokio::test]
+    async fn test_unlimited_shaper_does_not_wait() {
+        let shaper = PeerShaper::new(PeerShapingConfig::default());
+        let start = Instant::now();
+        for _ in 0..100 {
+            shaper.wait_turn(addr(1)).await;
+        }
+        assert!(start.elapsed() < Duration::from_millis(50));
+        assert!(shaper.peers().is_empty());
+    }
+}
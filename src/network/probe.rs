@@ -0,0 +1,119 @@
+//! Link probing
+//!
+//! Before committing a large transfer an operator can measure the path to a
+//! receiver by streaming synthetic chunks for a few seconds. The resulting
+//! [`LinkReport`] carries goodput, RTT and loss taken from QUIC's own
+//! counters, plus recommendations for chunk size and parity.
+
+use crate::chunk::{AdaptiveErasureConfig, Chunk, ChunkMetadata, Priority};
+use crate::integrity::ChecksumType;
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+
+/// File id carried by probe chunks; receivers discard these
+pub const PROBE_FILE_ID: &str = "__link_probe__";
+
+/// Payload size of each probe chunk
+pub const PROBE_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Smallest and largest chunk size a report recommends
+const MIN_RECOMMENDED_CHUNK: usize = 64 * 1024;
+const MAX_RECOMMENDED_CHUNK: usize = 4 * 1024 * 1024;
+
+/// Measured characteristics of the path to a receiver
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LinkReport {
+    pub remote_addr: Option<SocketAddr>,
+    pub duration_ms: u64,
+    /// Probe chunks acknowledged by the receiver
+    pub chunks_sent: u64,
+    /// Probe chunks that could not be sent
+    pub send_failures: u64,
+    pub bytes_sent: u64,
+    /// Acknowledged payload bytes per second
+    pub goodput_bytes_per_sec: u64,
+    pub rtt_ms: f64,
+    /// Packet loss seen by QUIC during the probe (0.0 - 1.0)
+    pub loss_rate: f64,
+    /// Congestion window at the end of the probe
+    pub cwnd: u64,
+}
+
+impl LinkReport {
+    /// Chunk size close to the bandwidth-delay product, so one chunk keeps
+    /// the path busy for about a round trip
+    pub fn recommended_chunk_size(&self) -> usize {
+        let bdp = (self.goodput_bytes_per_sec as f64 * self.rtt_ms / 1000.0) as usize;
+        bdp.clamp(MIN_RECOMMENDED_CHUNK, MAX_RECOMMENDED_CHUNK)
+            .next_power_of_two()
+            .min(MAX_RECOMMENDED_CHUNK)
+    }
+
+    /// Parity shards for the measured loss under `config`
+    pub fn recommended_parity(&self, config: &AdaptiveErasureConfig) -> usize {
+        config.parity_for_loss_rate(self.loss_rate as f32)
+    }
+}
+
+/// Whether a received chunk is probe traffic rather than file data
+pub fn is_probe_chunk(chunk: &Chunk) -> bool {
+    chunk.metadata.file_id == PROBE_FILE_ID
+}
+
+/// Build the `sequence`-th probe chunk
+pub(crate) fn probe_chunk(sequence: u32, size: usize) -> Chunk {
+    let data = Bytes::from(vec![0u8; size]);
+    Chunk {
+        metadata: ChunkMetadata {
+            chunk_id: sequence as u64,
+            file_id: PROBE_FILE_ID.to_string(),
+            sequence_number: sequence,
+            total_chunks: 0,
+            data_size: size,
+            checksum: *blake3::hash(&data).as_bytes(),
+            is_parity: false,
+            priority: Priority::Normal,
+            created_at: chrono::Utc::now().timestamp(),
+            file_size: 0,
+            file_checksum: [0u8; 32],
+            data_chunks: 0,
+            zero_runs: Vec::new(),
+            attributes: None,
+            checksum_algorithm: ChecksumType::Blake3,
+            merkle_proof: None,
+            trace: None,
+            compression: None,
+        },
+        data,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recommendations() {
+        // 10 MB/s over 100ms RTT: 1 MB in flight
+        let report = LinkReport {
+            goodput_bytes_per_sec: 10 * 1024 * 1024,
+            rtt_ms: 100.0,
+            loss_rate: 0.12,
+            ..Default::default()
+        };
+        assert_eq!(report.recommended_chunk_size(), 1024 * 1024);
+        assert_eq!(
+            report.recommended_parity(&AdaptiveErasureConfig::default()),
+            15
+        );
+
+        // A slow, short link bottoms out at the minimum
+        let slow = LinkReport {
+            goodput_bytes_per_sec: 100 * 1024,
+            rtt_ms: 20.0,
+            ..Default::default()
+        };
+        assert_eq!(slow.recommended_chunk_size(), MIN_RECOMMENDED_CHUNK);
+    }
+}
@@ -1,14 +1,79 @@
 use crate::chunk::Chunk;
+use crate::integrity::IntegrityVerifier;
+use crate::metrics::recorder;
+use crate::network::capture::DebugCapture;
 use crate::network::error::{NetworkError, NetworkResult};
-use crate::network::types::{ConnectionConfig, NetworkStats, QuicPathStats};
+use crate::network::flow_control::{FlowControlConfig, WindowSample, WindowTuner};
+use crate::network::memory_budget::{MemoryBudget, MemoryReservation};
+use crate::network::pacer::ChunkPacer;
+use crate::network::peer_shaper::{PeerRate, PeerShaper};
+use crate::network::probe::{self, LinkReport};
+use crate::network::rate_limiter::TransferRateLimiter;
+use crate::network::types::{
+    ChunkNack, ConnectionConfig, ConnectionDirection, ConnectionInfo, FileOffer, GroupFeedback,
+    NegotiatedParams, NetworkStats, OfferReply, QuicPathStats, ReceiverFeedback, ReceiverStats,
+    RepairReply, RepairRequest,
+};
+use crate::network::wire::{self, WireCodec, WirePayload};
 use backoff::{backoff::Backoff, ExponentialBackoff};
 use bytes::Bytes;
 use dashmap::DashMap;
-use quinn::{Connection, Endpoint, RecvStream, ServerConfig};
+use quinn::{Connection, Endpoint, RecvStream, SendStream, ServerConfig, TransportConfig, VarInt};
 use std::net::SocketAddr;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio_util::sync::CancellationToken;
+
+/// Default largest chunk payload accepted on a single stream (see
+/// [`ConnectionConfig::max_chunk_size`])
+pub const MAX_CHUNK_STREAM_SIZE: usize = 10 * 1024 * 1024;
+
+/// How long a sender waits for the receiver to answer a file offer
+pub const OFFER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Largest encoded offer or offer reply
+const MAX_OFFER_SIZE: usize = 64 * 1024;
+
+/// Largest encoded chunk metadata (trace hops, attributes and zero runs
+/// included) a receiver reads off a chunk stream
+pub const MAX_CHUNK_METADATA_SIZE: usize = 256 * 1024;
+
+/// Largest encoded receiver feedback message (a group report lists every
+/// received sequence number)
+const MAX_FEEDBACK_SIZE: usize = 1024 * 1024;
+
+/// Largest encoded repair request or reply; a reply carries every damaged
+/// block of the file
+const MAX_REPAIR_FRAME: usize = wire::MAX_DECODED_FRAME;
+
+/// How long a receiver waits for the sender to answer a repair request
+pub const REPAIR_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Error code a chunk stream is reset with when its send is cancelled
+pub const STREAM_CANCELLED: u32 = 0x43;
+
+/// Error code a receiver stops a chunk stream with when the payload is over
+/// its size limit
+pub const CHUNK_TOO_LARGE: u32 = 0x44;
+
+/// A chunk read off a stream, with the receive memory charged for it
+///
+/// The charge is returned when `memory` is dropped, so keep it alongside
+/// the chunk until the chunk is written out (or fold it into the
+/// reservation of whatever buffers the chunk).
+#[derive(Debug)]
+pub struct ReceivedChunk {
+    pub chunk: Chunk,
+    pub memory: MemoryReservation,
+}
+
+impl ReceivedChunk {
+    /// The chunk alone, releasing its memory charge
+    pub fn into_chunk(self) -> Chunk {
+        self.chunk
+    }
+}
 
 pub struct QuicTransport {
     endpoint: Endpoint,
@@ -16,11 +81,82 @@ pub struct QuicTransport {
     stats: Arc<parking_lot::RwLock<NetworkStats>>,
     /// Whether TLS certificate verification is skipped (INSECURE)
     insecure_mode: bool,
+    /// Default local address for outbound connections
+    client_bind_addr: Option<SocketAddr>,
+    /// In-flight memory budget shared by all receive streams
+    memory: Arc<MemoryBudget>,
+    /// Largest chunk payload accepted on one stream
+    max_chunk_size: usize,
+    /// Rate limits and pacing applied before each chunk write
+    limiter: TransferRateLimiter,
+    /// Receive-rate limits applied to each sender's streams
+    shaper: Arc<PeerShaper>,
+    /// Transport parameters of outbound connections
+    transport_config: Arc<TransportConfig>,
+    /// Window tuning applied to every connection
+    flow_control: FlowControlConfig,
+    /// Key log and event capture switches
+    capture: Arc<DebugCapture>,
+    /// Latest stats report from each receiver
+    receiver_stats: Arc<DashMap<SocketAddr, ReceiverStats>>,
+    /// Session sending over each connection, by quinn's connection id
+    sessions: Arc<DashMap<usize, String>>,
+    /// Chunk bytes awaiting acknowledgement on each connection
+    in_flight: Arc<DashMap<usize, u64>>,
 }
 
 impl QuicTransport {
     /// Create new QUIC transport with self-signed certificate
     pub async fn new(config: ConnectionConfig) -> NetworkResult<Self> {
+        Self::warn_if_insecure(&config);
+        let transport_config = Arc::new(Self::make_transport_config(&config)?);
+        let capture = Arc::new(DebugCapture::new(&config.capture));
+        let (server_config, _server_cert) =
+            Self::make_server_config(transport_config.clone(), &capture)?;
+        let endpoint = Endpoint::server(server_config, config.bind_addr)
+            .map_err(|e| NetworkError::ConnectionFailed(e.to_string()))?;
+        Ok(Self::with_endpoint(
+            endpoint,
+            config,
+            transport_config,
+            capture,
+        ))
+    }
+
+    /// Create a QUIC transport on a UDP socket that is already bound, such
+    /// as one passed in by systemd socket activation
+    ///
+    /// `config.bind_addr` is ignored; the socket's own address is used.
+    pub async fn with_socket(
+        config: ConnectionConfig,
+        socket: std::net::UdpSocket,
+    ) -> NetworkResult<Self> {
+        Self::warn_if_insecure(&config);
+        let transport_config = Arc::new(Self::make_transport_config(&config)?);
+        let capture = Arc::new(DebugCapture::new(&config.capture));
+        let (server_config, _server_cert) =
+            Self::make_server_config(transport_config.clone(), &capture)?;
+        let runtime = quinn::default_runtime()
+            .ok_or_else(|| NetworkError::QuicError("No async runtime for QUIC".into()))?;
+        socket
+            .set_nonblocking(true)
+            .map_err(|e| NetworkError::ConnectionFailed(e.to_string()))?;
+        let endpoint = Endpoint::new(
+            quinn::EndpointConfig::default(),
+            Some(server_config),
+            socket,
+            runtime,
+        )
+        .map_err(|e| NetworkError::ConnectionFailed(e.to_string()))?;
+        Ok(Self::with_endpoint(
+            endpoint,
+            config,
+            transport_config,
+            capture,
+        ))
+    }
+
+    fn warn_if_insecure(config: &ConnectionConfig) {
         if config.insecure_skip_verify {
             tracing::warn!(
                 "SECURITY WARNING: TLS certificate verification is DISABLED. \
@@ -28,53 +164,129 @@ impl QuicTransport {
                  Set insecure_skip_verify=false and use proper certificates in production."
             );
         }
+    }
 
-        let (endpoint, _server_cert) = Self::make_server_endpoint(config.bind_addr)?;
-
-        Ok(Self {
+    fn with_endpoint(
+        endpoint: Endpoint,
+        config: ConnectionConfig,
+        transport_config: Arc<TransportConfig>,
+        capture: Arc<DebugCapture>,
+    ) -> Self {
+        Self {
             endpoint,
             connections: Arc::new(DashMap::new()),
             stats: Arc::new(parking_lot::RwLock::new(NetworkStats::default())),
             insecure_mode: config.insecure_skip_verify,
-        })
+            client_bind_addr: config.client_bind_addr,
+            memory: MemoryBudget::new(config.receive_memory_limit, config.receive_high_watermark),
+            max_chunk_size: config.max_chunk_size,
+            limiter: Self::make_limiter(&config),
+            shaper: Arc::new(PeerShaper::new(config.peer_shaping.clone())),
+            transport_config,
+            flow_control: config.flow_control,
+            capture,
+            receiver_stats: Arc::new(DashMap::new()),
+            sessions: Arc::new(DashMap::new()),
+            in_flight: Arc::new(DashMap::new()),
+        }
+    }
+
+    fn make_limiter(config: &ConnectionConfig) -> TransferRateLimiter {
+        let limiter = TransferRateLimiter::unlimited();
+        if config.pacing.is_enabled() {
+            limiter.with_pacer(Arc::new(ChunkPacer::new(config.pacing)))
+        } else {
+            limiter
+        }
     }
 
-    /// Create server endpoint with self-signed certificate
-    fn make_server_endpoint(bind_addr: SocketAddr) -> NetworkResult<(Endpoint, Vec<u8>)> {
+    /// Stream limits, timeouts and flow-control windows for both directions
+    fn make_transport_config(config: &ConnectionConfig) -> NetworkResult<TransportConfig> {
+        let window = |bytes: u64| {
+            VarInt::from_u64(bytes).map_err(|_| {
+                NetworkError::QuicError(format!("flow-control window {bytes} is too large"))
+            })
+        };
+        let flow = &config.flow_control;
+        let idle_timeout = config
+            .max_idle_timeout
+            .try_into()
+            .map_err(|_| NetworkError::QuicError("max_idle_timeout is too large".into()))?;
+
+        let mut transport_config = TransportConfig::default();
+        transport_config
+            .max_concurrent_uni_streams(config.max_concurrent_streams.into())
+            .max_idle_timeout(Some(idle_timeout))
+            .keep_alive_interval(Some(config.keep_alive_interval))
+            .stream_receive_window(window(flow.stream_receive_window)?)
+            .receive_window(match flow.receive_window {
+                Some(bytes) => window(bytes)?,
+                None => VarInt::MAX,
+            })
+            .send_window(flow.send_window);
+        Ok(transport_config)
+    }
+
+    /// Create server config with self-signed certificate
+    ///
+    /// Handshake secrets go to the capture's key log while it is on for all
+    /// peers.
+    fn make_server_config(
+        transport_config: Arc<TransportConfig>,
+        capture: &Arc<DebugCapture>,
+    ) -> NetworkResult<(ServerConfig, Vec<u8>)> {
         let cert = rcgen::generate_simple_self_signed(vec!["localhost".into()])
             .map_err(|e| NetworkError::CertificateError(e.to_string()))?;
         let cert_der = cert.cert.der().to_vec();
         let priv_key = rustls::pki_types::PrivateKeyDer::try_from(cert.key_pair.serialize_der())
             .map_err(|e| NetworkError::CertificateError(e.to_string()))?;
 
-        let mut server_config = ServerConfig::with_single_cert(
+        // As quinn's `ServerConfig::with_single_cert`, plus the key log
+        let mut crypto = rustls::ServerConfig::builder_with_provider(Arc::new(
+            rustls::crypto::ring::default_provider(),
+        ))
+        .with_protocol_versions(&[&rustls::version::TLS13])
+        .map_err(|e| NetworkError::CertificateError(e.to_string()))?
+        .with_no_client_auth()
+        .with_single_cert(
             vec![rustls::pki_types::CertificateDer::from(cert_der.clone())],
             priv_key,
         )
         .map_err(|e| NetworkError::CertificateError(e.to_string()))?;
+        crypto.max_early_data_size = u32::MAX;
+        crypto.key_log = capture.server_key_log();
 
-        let transport_config = Arc::get_mut(&mut server_config.transport)
-            .ok_or_else(|| NetworkError::QuicError("Failed to get transport config".into()))?;
-
-        transport_config
-            .max_concurrent_uni_streams(100_u32.into())
-            .max_idle_timeout(Some(Duration::from_secs(60).try_into().unwrap()))
-            .keep_alive_interval(Some(Duration::from_secs(5)));
-
-        let endpoint = Endpoint::server(server_config, bind_addr)
-            .map_err(|e| NetworkError::ConnectionFailed(e.to_string()))?;
+        let mut server_config = ServerConfig::with_crypto(Arc::new(
+            quinn::crypto::rustls::QuicServerConfig::try_from(crypto)
+                .map_err(|e| NetworkError::CertificateError(e.to_string()))?,
+        ));
+        server_config.transport = transport_config;
 
-        Ok((endpoint, cert_der))
+        Ok((server_config, cert_der))
     }
 
     /// Create client endpoint
     /// If `insecure` is true, accepts any certificate (for testing with self-signed certs)
     /// If `insecure` is false, uses system root certificates for verification
-    fn make_client_endpoint(insecure: bool) -> NetworkResult<Endpoint> {
-        let mut endpoint = Endpoint::client("0.0.0.0:0".parse().unwrap())
-            .map_err(|e| NetworkError::ConnectionFailed(e.to_string()))?;
+    /// `key_log`, if set, receives the connection's handshake secrets
+    fn make_client_endpoint(
+        insecure: bool,
+        bind_addr: SocketAddr,
+        transport_config: Arc<TransportConfig>,
+        key_log: Option<Arc<dyn rustls::KeyLog>>,
+    ) -> NetworkResult<Endpoint> {
+        let mut endpoint = Endpoint::client(bind_addr).map_err(|e| {
+            if e.kind() == std::io::ErrorKind::AddrNotAvailable {
+                NetworkError::LocalAddressUnavailable {
+                    addr: bind_addr,
+                    reason: e.to_string(),
+                }
+            } else {
+                NetworkError::ConnectionFailed(e.to_string())
+            }
+        })?;
 
-        let crypto = if insecure {
+        let mut crypto = if insecure {
             // INSECURE: Skip certificate verification (for testing only)
             rustls::ClientConfig::builder()
                 .dangerous()
@@ -111,18 +323,16 @@ impl QuicTransport {
                 .with_no_client_auth()
         };
 
+        if let Some(key_log) = key_log {
+            crypto.key_log = key_log;
+        }
+
         let mut client_config = quinn::ClientConfig::new(Arc::new(
             quinn::crypto::rustls::QuicClientConfig::try_from(crypto)
                 .map_err(|e| NetworkError::CertificateError(e.to_string()))?,
         ));
 
-        let mut transport_config = quinn::TransportConfig::default();
-        transport_config
-            .max_concurrent_uni_streams(100_u32.into())
-            .max_idle_timeout(Some(Duration::from_secs(60).try_into().unwrap()))
-            .keep_alive_interval(Some(Duration::from_secs(5)));
-
-        client_config.transport_config(Arc::new(transport_config));
+        client_config.transport_config(transport_config);
         endpoint.set_default_client_config(client_config);
 
         Ok(endpoint)
@@ -130,23 +340,66 @@ impl QuicTransport {
 
     /// Connect to remote endpoint
     pub async fn connect(&self, remote_addr: SocketAddr) -> NetworkResult<Connection> {
-        let endpoint = Self::make_client_endpoint(self.insecure_mode)?;
+        self.connect_from(remote_addr, None).await
+    }
+
+    /// Connect to remote endpoint through a specific local address
+    ///
+    /// Pins the connection to the interface owning `local_addr`. With `None`
+    /// the configured `client_bind_addr` is used, falling back to letting the
+    /// OS pick the route.
+    pub async fn connect_from(
+        &self,
+        remote_addr: SocketAddr,
+        local_addr: Option<SocketAddr>,
+    ) -> NetworkResult<Connection> {
+        let bind_addr = match local_addr.or(self.client_bind_addr) {
+            Some(local) => {
+                Self::validate_local_addr(local, Some(remote_addr))?;
+                local
+            }
+            None => "0.0.0.0:0".parse().unwrap(),
+        };
+        let endpoint = Self::make_client_endpoint(
+            self.insecure_mode,
+            bind_addr,
+            self.transport_config.clone(),
+            self.capture.client_key_log(remote_addr),
+        )?;
 
         let conn = endpoint
             .connect(remote_addr, "localhost")
             .map_err(|e| NetworkError::ConnectionFailed(e.to_string()))?
             .await?;
 
-        let conn_id = format!("{remote_addr}");
-        self.connections.insert(conn_id.clone(), conn.clone());
+        self.track(format!("{remote_addr}"), &conn);
+        Ok(conn)
+    }
 
-        // Update stats
-        {
-            let mut stats = self.stats.write();
-            stats.active_connections = self.connections.len();
+    /// Check that `local_addr` can be used for outbound traffic
+    ///
+    /// Fails if the address family doesn't match `remote_addr` or if no local
+    /// interface currently owns the address.
+    pub fn validate_local_addr(
+        local_addr: SocketAddr,
+        remote_addr: Option<SocketAddr>,
+    ) -> NetworkResult<()> {
+        if let Some(remote) = remote_addr {
+            if local_addr.is_ipv4() != remote.is_ipv4() {
+                return Err(NetworkError::InvalidAddress(format!(
+                    "local address {local_addr} and remote address {remote} use different IP versions"
+                )));
+            }
         }
 
-        Ok(conn)
+        // Bind an ephemeral probe socket on the same IP to see if the interface exists
+        let probe = SocketAddr::new(local_addr.ip(), 0);
+        std::net::UdpSocket::bind(probe).map(|_| ()).map_err(|e| {
+            NetworkError::LocalAddressUnavailable {
+                addr: local_addr,
+                reason: e.to_string(),
+            }
+        })
     }
 
     /// Accept incoming connection
@@ -159,42 +412,256 @@ impl QuicTransport {
 
         let conn = incoming.await?;
 
-        let conn_id = format!("{}", conn.remote_address());
+        self.track(format!("{}", conn.remote_address()), &conn);
+        Ok(conn)
+    }
+
+    /// Remember `conn`, forgetting connections that have since closed
+    ///
+    /// Accepted connections are keyed by the peer's ephemeral port, so
+    /// without pruning a long-running receiver would hold every connection
+    /// it ever saw.
+    fn track(&self, conn_id: String, conn: &Connection) {
         self.connections.insert(conn_id, conn.clone());
+        self.connection_count();
+        if self.flow_control.auto_tune {
+            self.spawn_window_tuner(conn.clone());
+        }
+        self.capture.spawn_event_log(conn.clone());
+    }
 
-        // Update stats
-        {
-            let mut stats = self.stats.write();
-            stats.active_connections = self.connections.len();
+    /// Key log and event capture switches, changeable at runtime
+    pub fn capture(&self) -> &Arc<DebugCapture> {
+        &self.capture
+    }
+
+    /// Grow `conn`'s flow-control windows with its BDP until it closes
+    fn spawn_window_tuner(&self, conn: Connection) {
+        let config = self.flow_control;
+        let stats = self.stats.clone();
+        tokio::spawn(async move {
+            let mut tuner = WindowTuner::new(config);
+            let mut ticker = tokio::time::interval(config.tune_interval);
+            loop {
+                tokio::select! {
+                    _ = conn.closed() => break,
+                    _ = ticker.tick() => {}
+                }
+                let update = tuner.observe(Self::window_sample(&conn));
+                if update.is_empty() {
+                    continue;
+                }
+                if let Some(window) = update.send_window {
+                    conn.set_send_window(window);
+                }
+                if let Some(window) = update.receive_window {
+                    conn.set_receive_window(VarInt::from_u64(window).unwrap_or(VarInt::MAX));
+                }
+                tracing::debug!(
+                    remote = %conn.remote_address(),
+                    send_window = tuner.send_window(),
+                    receive_window = ?tuner.receive_window(),
+                    rtt_ms = conn.rtt().as_millis() as u64,
+                    "grew flow-control windows"
+                );
+                let mut stats = stats.write();
+                stats.flow_window_adjustments += 1;
+                stats.max_send_window = stats.max_send_window.max(tuner.send_window());
+            }
+        });
+    }
+
+    fn window_sample(conn: &Connection) -> WindowSample {
+        let stats = conn.stats();
+        WindowSample {
+            at: Instant::now(),
+            rtt: stats.path.rtt,
+            cwnd: stats.path.cwnd,
+            bytes_sent: stats.udp_tx.bytes,
+            bytes_received: stats.udp_rx.bytes,
         }
+    }
 
-        Ok(conn)
+    /// Connections currently open
+    pub fn connection_count(&self) -> usize {
+        self.connections.retain(|_, c| c.close_reason().is_none());
+        let open = self.connections.len();
+        self.stats.write().active_connections = open;
+        let ids: std::collections::HashSet<usize> =
+            self.connections.iter().map(|c| c.stable_id()).collect();
+        self.sessions.retain(|id, _| ids.contains(id));
+        self.in_flight.retain(|id, _| ids.contains(id));
+        open
+    }
+
+    /// Note that `session_id` sends over `conn`, for [`Self::connections`]
+    pub fn label_connection(&self, conn: &Connection, session_id: &str) {
+        self.sessions
+            .insert(conn.stable_id(), session_id.to_string());
+    }
+
+    /// Every open connection with its path stats, read live from quinn
+    pub fn connections(&self) -> Vec<ConnectionInfo> {
+        self.connection_count();
+        let mut connections: Vec<_> = self
+            .connections
+            .iter()
+            .map(|entry| self.connection_info(entry.value()))
+            .collect();
+        connections.sort_by_key(|c| c.id);
+        connections
+    }
+
+    fn connection_info(&self, conn: &Connection) -> ConnectionInfo {
+        let id = conn.stable_id();
+        let handshake = conn
+            .handshake_data()
+            .and_then(|data| data.downcast::<quinn::crypto::rustls::HandshakeData>().ok());
+        ConnectionInfo {
+            id,
+            remote_addr: conn.remote_address(),
+            direction: match conn.side() {
+                quinn::Side::Client => ConnectionDirection::Outbound,
+                quinn::Side::Server => ConnectionDirection::Inbound,
+            },
+            session_id: self.sessions.get(&id).map(|s| s.clone()),
+            path: Self::connection_stats(conn),
+            min_rtt_ms: conn.stats().path.min_rtt.as_secs_f64() * 1000.0,
+            bytes_in_flight: self.in_flight.get(&id).map_or(0, |b| *b),
+            negotiated: NegotiatedParams {
+                alpn: handshake
+                    .as_ref()
+                    .and_then(|h| h.protocol.as_ref())
+                    .map(|p| String::from_utf8_lossy(p).into_owned()),
+                server_name: handshake.and_then(|h| h.server_name),
+                max_datagram_size: conn.max_datagram_size(),
+            },
+        }
+    }
+
+    /// Accept the next incoming uni stream, respecting the memory watermark
+    /// and the sender's receive rate
+    ///
+    /// While receive memory is above the high watermark, or the sender is
+    /// over its rate, no new streams are accepted, so QUIC stream limits
+    /// stall the sender until chunks are released. Chunks read from the
+    /// stream should be [charged](PeerShaper::charge) to the sender.
+    pub async fn accept_uni(&self, conn: &Connection) -> NetworkResult<RecvStream> {
+        self.memory.wait_for_capacity().await;
+        self.shaper.wait_turn(conn.remote_address()).await;
+        Ok(conn.accept_uni().await?)
+    }
+
+    /// Per-sender receive-rate shaping
+    pub fn peer_shaper(&self) -> &Arc<PeerShaper> {
+        &self.shaper
+    }
+
+    /// Each sender's receive rate and the rate it is held to
+    pub fn peer_rates(&self) -> Vec<PeerRate> {
+        self.shaper.peers()
+    }
+
+    /// Send pacer, when pacing is configured
+    pub fn pacer(&self) -> Option<&Arc<ChunkPacer>> {
+        self.limiter.pacer()
+    }
+
+    /// Receive-path memory budget
+    pub fn memory_budget(&self) -> &Arc<MemoryBudget> {
+        &self.memory
+    }
+
+    /// Largest chunk payload accepted on one stream
+    pub fn max_chunk_size(&self) -> usize {
+        self.max_chunk_size
     }
 
     /// Send chunk over QUIC stream
     pub async fn send_chunk(&self, conn: &Connection, chunk: &Chunk) -> NetworkResult<()> {
-        let mut send_stream = conn.open_uni().await?;
+        self.send_chunk_until(conn, chunk, &CancellationToken::new())
+            .await
+    }
+
+    /// Send chunk over QUIC stream, giving up as soon as `cancel` fires
+    ///
+    /// A cancelled send resets its stream with [`STREAM_CANCELLED`] so the
+    /// receiver drops the partial chunk instead of waiting for the rest.
+    pub async fn send_chunk_until(
+        &self,
+        conn: &Connection,
+        chunk: &Chunk,
+        cancel: &CancellationToken,
+    ) -> NetworkResult<()> {
+        #[cfg(feature = "fault-injection")]
+        crate::fault::check(crate::fault::FaultPoint::QuicSend)
+            .map_err(|fault| NetworkError::SendFailed(fault.to_string()))?;
 
         // Serialize metadata
         let metadata_bytes = bincode::serialize(&chunk.metadata)?;
+        if metadata_bytes.len() > MAX_CHUNK_METADATA_SIZE {
+            return Err(NetworkError::SendFailed(format!(
+                "chunk metadata of {} bytes exceeds the {} byte limit",
+                metadata_bytes.len(),
+                MAX_CHUNK_METADATA_SIZE
+            )));
+        }
 
-        // Send metadata length
-        send_stream
-            .write_u32(metadata_bytes.len() as u32)
-            .await
-            .map_err(|e| NetworkError::SendFailed(e.to_string()))?;
+        // Space writes so constrained links don't see bursts
+        let mut send_stream = tokio::select! {
+            biased;
+            _ = cancel.cancelled() => return Err(NetworkError::Cancelled),
+            stream = async {
+                self.limiter
+                    .wait_for_send(metadata_bytes.len() + chunk.data.len())
+                    .await;
+                conn.open_uni().await
+            } => stream?,
+        };
 
-        // Send metadata
-        send_stream
-            .write_all(&metadata_bytes)
-            .await
-            .map_err(|e| NetworkError::SendFailed(e.to_string()))?;
+        let in_flight = InFlight::add(
+            &self.in_flight,
+            conn.stable_id(),
+            (metadata_bytes.len() + chunk.data.len()) as u64,
+        );
+        tokio::select! {
+            biased;
+            _ = cancel.cancelled() => {
+                let _ = send_stream.reset(STREAM_CANCELLED.into());
+                return Err(NetworkError::Cancelled);
+            }
+            written = Self::write_chunk(&mut send_stream, &metadata_bytes, &chunk.data) => written?,
+        }
+        drop(in_flight);
+
+        // Update stats
+        {
+            let mut stats = self.stats.write();
+            stats.total_bytes_sent += (metadata_bytes.len() + chunk.data.len()) as u64;
+            stats.chunks_sent += 1;
+        }
+
+        if let Some(pacer) = self.limiter.pacer() {
+            pacer.observe_path(&Self::connection_stats(conn));
+        }
+
+        Ok(())
+    }
 
-        // Send data
+    /// Write one chunk's framing and payload, then wait for the peer to
+    /// acknowledge it
+    async fn write_chunk(
+        send_stream: &mut SendStream,
+        metadata: &[u8],
+        data: &[u8],
+    ) -> NetworkResult<()> {
+        // Metadata length, metadata, then data
         send_stream
-            .write_all(&chunk.data)
+            .write_u32(metadata.len() as u32)
             .await
             .map_err(|e| NetworkError::SendFailed(e.to_string()))?;
+        send_stream.write_all(metadata).await?;
+        send_stream.write_all(data).await?;
 
         // Finish stream
         send_stream
@@ -204,25 +671,36 @@ impl QuicTransport {
             .stopped()
             .await
             .map_err(|e| NetworkError::SendFailed(e.to_string()))?;
-
-        // Update stats
-        {
-            let mut stats = self.stats.write();
-            stats.total_bytes_sent += (metadata_bytes.len() + chunk.data.len()) as u64;
-            stats.chunks_sent += 1;
-        }
-
         Ok(())
     }
 
     /// Receive chunk from QUIC stream
-    pub async fn receive_chunk(&self, mut recv_stream: RecvStream) -> NetworkResult<Chunk> {
+    ///
+    /// The chunk comes back with its memory reservation; see
+    /// [`ReceivedChunk`].
+    pub async fn receive_chunk(&self, mut recv_stream: RecvStream) -> NetworkResult<ReceivedChunk> {
         // Read metadata length
         let metadata_len = recv_stream
             .read_u32()
             .await
             .map_err(|e| NetworkError::ReceiveFailed(e.to_string()))?
             as usize;
+        if metadata_len > MAX_CHUNK_METADATA_SIZE {
+            let _ = recv_stream.stop(CHUNK_TOO_LARGE.into());
+            return Err(NetworkError::ReceiveFailed(format!(
+                "chunk metadata of {} bytes exceeds the {} byte limit",
+                metadata_len, MAX_CHUNK_METADATA_SIZE
+            )));
+        }
+
+        // Charge everything before allocating it, so a flood of streams
+        // can't allocate past the budget
+        let reserve = |recv_stream: &mut RecvStream, bytes| {
+            self.memory.try_reserve(bytes).inspect_err(|_| {
+                let _ = recv_stream.stop(0u32.into());
+            })
+        };
+        let mut reservation = reserve(&mut recv_stream, metadata_len)?;
 
         // Read metadata
         let mut metadata_bytes = vec![0u8; metadata_len];
@@ -230,13 +708,32 @@ impl QuicTransport {
             .read_exact(&mut metadata_bytes)
             .await
             .map_err(|e| NetworkError::ReceiveFailed(e.to_string()))?;
-        let metadata = bincode::deserialize(&metadata_bytes)?;
+        let metadata: crate::chunk::ChunkMetadata = bincode::deserialize(&metadata_bytes)?;
 
-        // Read remaining data (max 10MB for safety)
-        let data = recv_stream
-            .read_to_end(10 * 1024 * 1024)
-            .await
-            .map_err(|e| NetworkError::ReceiveFailed(e.to_string()))?;
+        let too_large = |size: usize| NetworkError::ChunkTooLarge {
+            size: size as u64,
+            limit: self.max_chunk_size as u64,
+        };
+        if metadata.data_size > self.max_chunk_size {
+            let _ = recv_stream.stop(CHUNK_TOO_LARGE.into());
+            return Err(too_large(metadata.data_size));
+        }
+
+        // Charge the declared payload size up front, then settle to the
+        // real size
+        reservation.merge(reserve(&mut recv_stream, metadata.data_size)?);
+
+        let data = match recv_stream.read_to_end(self.max_chunk_size).await {
+            Ok(data) => data,
+            // The payload ran past what its metadata declared; the rest is
+            // never read, so only a lower bound on its size is known
+            Err(quinn::ReadToEndError::TooLong) => {
+                let _ = recv_stream.stop(CHUNK_TOO_LARGE.into());
+                return Err(too_large(self.max_chunk_size + 1));
+            }
+            Err(e) => return Err(NetworkError::ReceiveFailed(e.to_string())),
+        };
+        reservation.resize(metadata_len + data.len());
 
         // Update stats
         {
@@ -245,101 +742,412 @@ impl QuicTransport {
             stats.chunks_received += 1;
         }
 
-        Ok(Chunk {
-            metadata,
-            data: Bytes::from(data),
+        Ok(ReceivedChunk {
+            chunk: Chunk {
+                metadata,
+                data: Bytes::from(data),
+            },
+            memory: reservation,
         })
     }
 
-    /// Send chunk with automatic retry using exponential backoff (backoff crate)
-    pub async fn send_with_backoff(&self, conn: &Connection, chunk: &Chunk) -> NetworkResult<()> {
-        let mut backoff = ExponentialBackoff {
-            initial_interval: Duration::from_millis(100),
-            max_interval: Duration::from_secs(2),
-            max_elapsed_time: Some(Duration::from_secs(30)),
-            ..Default::default()
-        };
-
-        loop {
-            match self.send_chunk(conn, chunk).await {
-                Ok(_) => return Ok(()),
-                Err(e) => {
-                    match backoff.next_backoff() {
-                        Some(duration) => {
-                            tracing::warn!("Send failed, retrying in {:?}: {}", duration, e);
+    /// Receive a chunk and check its checksum before handing it on
+    ///
+    /// A corrupted chunk is discarded and reported as
+    /// [`NetworkError::CorruptChunk`]; the caller should [`Self::send_nack`]
+    /// so the sender resends it.
+    pub async fn receive_verified_chunk(
+        &self,
+        recv_stream: RecvStream,
+    ) -> NetworkResult<ReceivedChunk> {
+        let received = self.receive_chunk(recv_stream).await?;
+        let chunk = &received.chunk;
+        if IntegrityVerifier::verify_chunk(chunk).is_err() {
+            self.stats.write().chunks_corrupted += 1;
+            recorder::record_chunk_corrupted(&chunk.metadata.file_id);
+            return Err(NetworkError::CorruptChunk {
+                file_id: chunk.metadata.file_id.clone(),
+                sequence_number: chunk.metadata.sequence_number,
+            });
+        }
+        recorder::record_chunk_received(&chunk.metadata.file_id, chunk.data.len());
+        Ok(received)
+    }
 
-                            // Update retry stats
-                            {
-                                let mut stats = self.stats.write();
-                                stats.retransmissions += 1;
-                            }
+    /// Ask the sender on `conn` to resend a chunk
+    pub async fn send_nack(&self, conn: &Connection, nack: &ChunkNack) -> NetworkResult<()> {
+        self.send_feedback(conn, &ReceiverFeedback::Nack(nack.clone()))
+            .await?;
+        self.stats.write().nacks_sent += 1;
+        Ok(())
+    }
 
-                            tokio::time::sleep(duration).await;
-                        }
-                        None => {
-                            // Max elapsed time exceeded
-                            return Err(NetworkError::MaxRetriesExceeded(0));
-                        }
-                    }
-                }
-            }
-        }
+    /// Tell the sender on `conn` which shards of a file's group arrived
+    pub async fn send_group_feedback(
+        &self,
+        conn: &Connection,
+        feedback: &GroupFeedback,
+    ) -> NetworkResult<()> {
+        self.send_feedback(conn, &ReceiverFeedback::Group(feedback.clone()))
+            .await?;
+        self.stats.write().group_reports_sent += 1;
+        Ok(())
     }
 
-    /// Send chunk with automatic retry
-    pub async fn send_with_retry(
+    /// Tell the sender on `conn` what this end has seen of it so far
+    pub async fn send_receiver_stats(
         &self,
         conn: &Connection,
-        chunk: &Chunk,
-        max_retries: u32,
+        stats: &ReceiverStats,
     ) -> NetworkResult<()> {
-        let mut attempts = 0;
-        let mut backoff = Duration::from_millis(100);
+        self.send_feedback(conn, &ReceiverFeedback::Stats(*stats))
+            .await?;
+        self.stats.write().stats_reports_sent += 1;
+        Ok(())
+    }
 
-        loop {
-            match self.send_chunk(conn, chunk).await {
-                Ok(_) => return Ok(()),
-                Err(e) if attempts < max_retries => {
-                    tracing::warn!(
-                        "Send failed (attempt {}/{}): {}",
-                        attempts + 1,
-                        max_retries,
-                        e
-                    );
+    async fn send_feedback(
+        &self,
+        conn: &Connection,
+        feedback: &ReceiverFeedback,
+    ) -> NetworkResult<()> {
+        let mut send_stream = conn.open_uni().await?;
+        send_stream
+            .write_all(&bincode::serialize(feedback)?)
+            .await?;
+        send_stream
+            .finish()
+            .map_err(|e| NetworkError::SendFailed(e.to_string()))?;
+        Ok(())
+    }
 
-                    // Update retry stats
-                    {
-                        let mut stats = self.stats.write();
-                        stats.retransmissions += 1;
-                    }
+    /// Wait for the next NACK, group report or stats report a receiver
+    /// sends back on `conn`
+    ///
+    /// Stats reports are also kept; see [`Self::receiver_stats`].
+    pub async fn receive_feedback(&self, conn: &Connection) -> NetworkResult<ReceiverFeedback> {
+        let mut recv_stream = conn.accept_uni().await?;
+        let feedback = recv_stream
+            .read_to_end(MAX_FEEDBACK_SIZE)
+            .await
+            .map_err(|e| NetworkError::ReceiveFailed(e.to_string()))?;
+        let feedback: ReceiverFeedback = bincode::deserialize(&feedback)?;
 
-                    tokio::time::sleep(backoff).await;
-                    backoff *= 2;
-                    attempts += 1;
-                }
-                Err(_e) => {
-                    return Err(NetworkError::MaxRetriesExceeded(max_retries));
-                }
-            }
+        if let ReceiverFeedback::Stats(report) = &feedback {
+            self.receiver_stats.insert(conn.remote_address(), *report);
         }
+        let mut stats = self.stats.write();
+        match feedback {
+            ReceiverFeedback::Nack(_) => stats.nacks_received += 1,
+            ReceiverFeedback::Group(_) => stats.group_reports_received += 1,
+            ReceiverFeedback::Stats(_) => stats.stats_reports_received += 1,
+        }
+        Ok(feedback)
     }
 
-    /// Get local address
-    pub fn local_addr(&self) -> NetworkResult<SocketAddr> {
-        self.endpoint.local_addr().map_err(NetworkError::IoError)
+    /// Latest stats report from the receiver at `peer`
+    pub fn receiver_stats(&self, peer: SocketAddr) -> Option<ReceiverStats> {
+        self.receiver_stats.get(&peer).map(|entry| *entry)
     }
 
-    /// Get network statistics
-    pub fn stats(&self) -> NetworkStats {
-        self.stats.read().clone()
+    /// Latest stats report from every receiver that has sent one
+    pub fn receiver_reports(&self) -> Vec<(SocketAddr, ReceiverStats)> {
+        let mut reports: Vec<_> = self
+            .receiver_stats
+            .iter()
+            .map(|entry| (*entry.key(), *entry.value()))
+            .collect();
+        reports.sort_by_key(|(peer, _)| *peer);
+        reports
     }
 
-    /// Extract real QUIC path stats from a live connection
-    pub fn connection_stats(conn: &Connection) -> QuicPathStats {
-        let stats = conn.stats();
-        let path = stats.path;
-        let loss_rate = if path.sent_packets > 0 {
-            path.lost_packets as f64 / path.sent_packets as f64
+    /// Stream synthetic chunks of `chunk_size` bytes for `duration` and
+    /// measure the path from QUIC's counters
+    pub async fn probe_link(
+        &self,
+        conn: &Connection,
+        duration: Duration,
+        chunk_size: usize,
+    ) -> NetworkResult<LinkReport> {
+        let before = Self::connection_stats(conn);
+        let start = Instant::now();
+        let mut report = LinkReport {
+            remote_addr: Some(conn.remote_address()),
+            ..Default::default()
+        };
+
+        let mut sequence = 0u32;
+        while start.elapsed() < duration {
+            let chunk = probe::probe_chunk(sequence, chunk_size);
+            sequence = sequence.wrapping_add(1);
+            match self.send_chunk(conn, &chunk).await {
+                Ok(()) => {
+                    report.chunks_sent += 1;
+                    report.bytes_sent += chunk_size as u64;
+                }
+                Err(_) if conn.close_reason().is_some() => {
+                    return Err(NetworkError::ConnectionClosed(
+                        "connection closed during link probe".into(),
+                    ));
+                }
+                Err(_) => report.send_failures += 1,
+            }
+        }
+
+        let elapsed = start.elapsed();
+        let after = Self::connection_stats(conn);
+        let sent = after.sent_packets.saturating_sub(before.sent_packets);
+        let lost = after.lost_packets.saturating_sub(before.lost_packets);
+
+        report.duration_ms = elapsed.as_millis() as u64;
+        report.goodput_bytes_per_sec =
+            (report.bytes_sent as f64 / elapsed.as_secs_f64().max(0.001)) as u64;
+        report.rtt_ms = after.rtt_ms;
+        report.loss_rate = if sent > 0 {
+            lost as f64 / sent as f64
+        } else {
+            0.0
+        };
+        report.cwnd = after.cwnd;
+        Ok(report)
+    }
+
+    /// Announce a file on a bidirectional stream and wait for the answer
+    ///
+    /// Receivers that predate the handshake never answer; after
+    /// [`OFFER_TIMEOUT`] the offer is treated as accepted.
+    pub async fn offer_file(
+        &self,
+        conn: &Connection,
+        offer: &FileOffer,
+    ) -> NetworkResult<OfferReply> {
+        let exchange = async {
+            let (mut send_stream, mut recv_stream) = conn.open_bi().await?;
+            send_stream.write_all(&bincode::serialize(offer)?).await?;
+            send_stream
+                .finish()
+                .map_err(|e| NetworkError::SendFailed(e.to_string()))?;
+
+            let reply = recv_stream
+                .read_to_end(MAX_OFFER_SIZE)
+                .await
+                .map_err(|e| NetworkError::ReceiveFailed(e.to_string()))?;
+            Ok::<_, NetworkError>(bincode::deserialize(&reply)?)
+        };
+
+        match tokio::time::timeout(OFFER_TIMEOUT, exchange).await {
+            Ok(reply) => reply,
+            Err(_) => Ok(OfferReply::Send),
+        }
+    }
+
+    /// Wait for the next file offer on a connection
+    ///
+    /// The returned stream carries the answer; see [`Self::answer_offer`].
+    pub async fn accept_offer(conn: &Connection) -> NetworkResult<(FileOffer, SendStream)> {
+        let (send_stream, mut recv_stream) = conn.accept_bi().await?;
+        let offer = recv_stream
+            .read_to_end(MAX_OFFER_SIZE)
+            .await
+            .map_err(|e| NetworkError::ReceiveFailed(e.to_string()))?;
+        Ok((bincode::deserialize(&offer)?, send_stream))
+    }
+
+    /// Answer an offer taken from [`Self::accept_offer`]
+    pub async fn answer_offer(mut send_stream: SendStream, reply: OfferReply) -> NetworkResult<()> {
+        send_stream.write_all(&bincode::serialize(&reply)?).await?;
+        send_stream
+            .finish()
+            .map_err(|e| NetworkError::SendFailed(e.to_string()))?;
+        // Let the reply reach the sender before the stream is dropped
+        let _ = send_stream.stopped().await;
+        Ok(())
+    }
+
+    /// Write a patch, signature or manifest to `send_stream` and finish it,
+    /// compressed with `codec` (see [`wire`](crate::network::wire))
+    pub async fn write_payload<T: WirePayload>(
+        send_stream: &mut SendStream,
+        value: &T,
+        codec: WireCodec,
+    ) -> NetworkResult<()> {
+        send_stream.write_all(&wire::encode(value, codec)?).await?;
+        send_stream
+            .finish()
+            .map_err(|e| NetworkError::SendFailed(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Read a payload written by [`Self::write_payload`], up to `max_len`
+    /// bytes on the wire
+    pub async fn read_payload<T: WirePayload>(
+        recv_stream: &mut RecvStream,
+        max_len: usize,
+    ) -> NetworkResult<T> {
+        let frame = recv_stream
+            .read_to_end(max_len)
+            .await
+            .map_err(|e| NetworkError::ReceiveFailed(e.to_string()))?;
+        wire::decode(&frame)
+    }
+
+    /// Ask the sender on `conn` for a patch that restores a damaged file
+    ///
+    /// Times out after [`REPAIR_TIMEOUT`]. Requests go out LZ4-compressed:
+    /// any sender that answers them decodes LZ4.
+    pub async fn request_repair(
+        &self,
+        conn: &Connection,
+        request: &RepairRequest,
+    ) -> NetworkResult<RepairReply> {
+        let exchange = async {
+            let (mut send_stream, mut recv_stream) = conn.open_bi().await?;
+            Self::write_payload(&mut send_stream, request, WireCodec::Lz4).await?;
+            Self::read_payload(&mut recv_stream, MAX_REPAIR_FRAME).await
+        };
+        tokio::time::timeout(REPAIR_TIMEOUT, exchange)
+            .await
+            .map_err(|_| NetworkError::Timeout(REPAIR_TIMEOUT))?
+    }
+
+    /// Wait for the next repair request on a connection
+    ///
+    /// The returned stream carries the answer; see [`Self::answer_repair`].
+    pub async fn accept_repair(conn: &Connection) -> NetworkResult<(RepairRequest, SendStream)> {
+        let (send_stream, mut recv_stream) = conn.accept_bi().await?;
+        let request = Self::read_payload(&mut recv_stream, MAX_REPAIR_FRAME).await?;
+        Ok((request, send_stream))
+    }
+
+    /// Answer a request taken from [`Self::accept_repair`], compressed with
+    /// the codec it asked for
+    pub async fn answer_repair(
+        mut send_stream: SendStream,
+        codec: WireCodec,
+        reply: &RepairReply,
+    ) -> NetworkResult<()> {
+        Self::write_payload(&mut send_stream, reply, codec).await?;
+        // Let the reply reach the receiver before the stream is dropped
+        let _ = send_stream.stopped().await;
+        Ok(())
+    }
+
+    /// Send chunk with automatic retry using exponential backoff (backoff crate)
+    pub async fn send_with_backoff(&self, conn: &Connection, chunk: &Chunk) -> NetworkResult<()> {
+        let mut backoff = ExponentialBackoff {
+            initial_interval: Duration::from_millis(100),
+            max_interval: Duration::from_secs(2),
+            max_elapsed_time: Some(Duration::from_secs(30)),
+            ..Default::default()
+        };
+
+        loop {
+            match self.send_chunk(conn, chunk).await {
+                Ok(_) => return Ok(()),
+                Err(e) => {
+                    match backoff.next_backoff() {
+                        Some(duration) => {
+                            tracing::warn!("Send failed, retrying in {:?}: {}", duration, e);
+
+                            // Update retry stats
+                            {
+                                let mut stats = self.stats.write();
+                                stats.retransmissions += 1;
+                            }
+
+                            tokio::time::sleep(duration).await;
+                        }
+                        None => {
+                            // Max elapsed time exceeded
+                            return Err(NetworkError::MaxRetriesExceeded(0));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Send chunk with automatic retry
+    pub async fn send_with_retry(
+        &self,
+        conn: &Connection,
+        chunk: &Chunk,
+        max_retries: u32,
+    ) -> NetworkResult<()> {
+        self.send_with_retry_until(conn, chunk, max_retries, &CancellationToken::new())
+            .await
+    }
+
+    /// Send chunk with automatic retry until `cancel` fires, which stops
+    /// both the attempt in flight and any backoff
+    pub async fn send_with_retry_until(
+        &self,
+        conn: &Connection,
+        chunk: &Chunk,
+        max_retries: u32,
+        cancel: &CancellationToken,
+    ) -> NetworkResult<()> {
+        let mut attempts = 0;
+        let mut backoff = Duration::from_millis(100);
+
+        loop {
+            match self.send_chunk_until(conn, chunk, cancel).await {
+                Ok(_) => return Ok(()),
+                Err(NetworkError::Cancelled) => return Err(NetworkError::Cancelled),
+                Err(e) if attempts < max_retries => {
+                    tracing::warn!(
+                        "Send failed (attempt {}/{}): {}",
+                        attempts + 1,
+                        max_retries,
+                        e
+                    );
+
+                    // Update retry stats
+                    {
+                        let mut stats = self.stats.write();
+                        stats.retransmissions += 1;
+                    }
+
+                    tokio::select! {
+                        _ = cancel.cancelled() => return Err(NetworkError::Cancelled),
+                        _ = tokio::time::sleep(backoff) => {}
+                    }
+                    backoff *= 2;
+                    attempts += 1;
+                }
+                Err(_e) => {
+                    return Err(NetworkError::MaxRetriesExceeded(max_retries));
+                }
+            }
+        }
+    }
+
+    /// Get local address
+    pub fn local_addr(&self) -> NetworkResult<SocketAddr> {
+        self.endpoint.local_addr().map_err(NetworkError::IoError)
+    }
+
+    /// Get network statistics
+    pub fn stats(&self) -> NetworkStats {
+        let memory = self.memory.stats();
+        let pacing = self.pacer().map(|p| p.stats()).unwrap_or_default();
+        NetworkStats {
+            receive_memory_in_use: memory.in_use,
+            receive_memory_peak: memory.peak,
+            receive_backpressure_pauses: memory.backpressure_pauses,
+            pacing_rate_bytes_per_sec: pacing.rate_bytes_per_sec,
+            paced_chunks_delayed: pacing.delayed_chunks,
+            pacing_delay_ms: pacing.total_delay_ms,
+            ..self.stats.read().clone()
+        }
+    }
+
+    /// Extract real QUIC path stats from a live connection
+    pub fn connection_stats(conn: &Connection) -> QuicPathStats {
+        let stats = conn.stats();
+        let path = stats.path;
+        let loss_rate = if path.sent_packets > 0 {
+            path.lost_packets as f64 / path.sent_packets as f64
         } else {
             0.0
         };
@@ -413,10 +1221,34 @@ impl rustls::client::danger::ServerCertVerifier for SkipServerVerification {
     }
 }
 
+/// Bytes counted against a connection until the chunk is acknowledged,
+/// sent or abandoned
+struct InFlight<'a> {
+    counts: &'a DashMap<usize, u64>,
+    id: usize,
+    bytes: u64,
+}
+
+impl<'a> InFlight<'a> {
+    fn add(counts: &'a DashMap<usize, u64>, id: usize, bytes: u64) -> Self {
+        *counts.entry(id).or_default() += bytes;
+        Self { counts, id, bytes }
+    }
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        if let Some(mut count) = self.counts.get_mut(&self.id) {
+            *count = count.saturating_sub(self.bytes);
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::chunk::{ChunkMetadata, Priority};
+    use crate::chunk::{ChunkMetadata, FileManifest, Priority, ZeroRun};
+    use crate::network::wire::Capabilities;
 
     // Initialize crypto provider once for all tests
     fn init_crypto() {
@@ -442,6 +1274,12 @@ mod tests {
                 file_size: data.len() as u64,
                 file_checksum: [0u8; 32],
                 data_chunks: 1,
+                zero_runs: Vec::new(),
+                attributes: None,
+                checksum_algorithm: Default::default(),
+                merkle_proof: None,
+                trace: None,
+                compression: None,
             },
             data: Bytes::from(data.to_vec()),
         }
@@ -464,6 +1302,17 @@ mod tests {
         assert!(addr.is_ok());
     }
 
+    #[tokio::test]
+    async fn test_transport_on_inherited_socket() {
+        init_crypto();
+        let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = socket.local_addr().unwrap();
+        let transport = QuicTransport::with_socket(ConnectionConfig::default(), socket)
+            .await
+            .unwrap();
+        assert_eq!(transport.local_addr().unwrap(), addr);
+    }
+
     #[tokio::test]
     async fn test_send_receive_chunk() {
         init_crypto();
@@ -479,8 +1328,15 @@ mod tests {
         let server_task = tokio::spawn(async move {
             let conn = server_clone.accept().await.unwrap();
             let stream = conn.accept_uni().await.unwrap();
-            let chunk = server_clone.receive_chunk(stream).await.unwrap();
-            assert_eq!(chunk.data, b"test data" as &[u8]);
+            let received = server_clone.receive_chunk(stream).await.unwrap();
+            assert_eq!(received.chunk.data, b"test data" as &[u8]);
+
+            // The chunk stays charged until its reservation is dropped
+            let stats = server_clone.stats();
+            assert!(stats.receive_memory_in_use >= received.chunk.data.len());
+            assert!(stats.receive_memory_peak >= received.chunk.data.len());
+            drop(received);
+            assert_eq!(server_clone.stats().receive_memory_in_use, 0);
         });
 
         // Client sends chunk
@@ -499,6 +1355,302 @@ mod tests {
             .unwrap();
     }
 
+    #[tokio::test]
+    async fn test_offer_already_have() {
+        init_crypto();
+        let config = ConnectionConfig {
+            bind_addr: "127.0.0.1:0".parse().unwrap(),
+            ..Default::default()
+        };
+        let server = Arc::new(QuicTransport::new(config).await.unwrap());
+        let server_addr = server.local_addr().unwrap();
+        let offer = FileOffer {
+            file_id: "file-1".into(),
+            filename: "report.pdf".into(),
+            total_size: 1024,
+            checksum: [7u8; 32],
+            capabilities: Capabilities::local(),
+            max_chunk_size: 1024,
+            checksum_algorithm: Default::default(),
+        };
+
+        let server_clone = server.clone();
+        let expected = offer.clone();
+        let server_task = tokio::spawn(async move {
+            let conn = server_clone.accept().await.unwrap();
+            let (received, send_stream) = QuicTransport::accept_offer(&conn).await.unwrap();
+            assert_eq!(received, expected);
+            QuicTransport::answer_offer(send_stream, OfferReply::AlreadyHave)
+                .await
+                .unwrap();
+        });
+
+        let client = QuicTransport::new(ConnectionConfig::default())
+            .await
+            .unwrap();
+        let conn = client.connect(server_addr).await.unwrap();
+        let reply = client.offer_file(&conn, &offer).await.unwrap();
+        assert_eq!(reply, OfferReply::AlreadyHave);
+
+        tokio::time::timeout(Duration::from_secs(5), server_task)
+            .await
+            .unwrap()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_offer_negotiates_compressed_payloads() {
+        init_crypto();
+        let config = ConnectionConfig {
+            bind_addr: "127.0.0.1:0".parse().unwrap(),
+            ..Default::default()
+        };
+        let server = Arc::new(QuicTransport::new(config).await.unwrap());
+        let server_addr = server.local_addr().unwrap();
+        let manifest = FileManifest {
+            file_id: "file-1".into(),
+            filename: "survey.tif".into(),
+            total_size: 10 * 1024 * 1024,
+            chunk_size: 1024,
+            total_chunks: 10 * 1024,
+            data_chunks: 10 * 1024,
+            parity_chunks: 0,
+            priority: Priority::Normal,
+            checksum: [9u8; 32],
+            // A sparse file's run list is what makes manifests large
+            zero_runs: (0..512)
+                .map(|i| ZeroRun {
+                    offset: i * 8192,
+                    length: 4096,
+                })
+                .collect(),
+            attributes: None,
+            checksum_algorithm: Default::default(),
+            erasure_profile: Default::default(),
+            schedule: None,
+            merkle_root: None,
+            compression: None,
+        };
+
+        let server_clone = server.clone();
+        let server_task = tokio::spawn(async move {
+            let conn = server_clone.accept().await.unwrap();
+            let (offer, send_stream) = QuicTransport::accept_offer(&conn).await.unwrap();
+            assert_eq!(offer.codec(), WireCodec::Zstd);
+            QuicTransport::answer_offer(send_stream, OfferReply::Accept(Capabilities::local()))
+                .await
+                .unwrap();
+            let (_, mut recv_stream) = conn.accept_bi().await.unwrap();
+            QuicTransport::read_payload::<FileManifest>(&mut recv_stream, 1024 * 1024)
+                .await
+                .unwrap()
+        });
+
+        let client = QuicTransport::new(ConnectionConfig::default())
+            .await
+            .unwrap();
+        let conn = client.connect(server_addr).await.unwrap();
+        let reply = client
+            .offer_file(&conn, &FileOffer::from_manifest(&manifest))
+            .await
+            .unwrap();
+        assert_eq!(reply.codec(), WireCodec::Zstd);
+        // Peers that predate negotiation get raw frames
+        assert_eq!(OfferReply::Send.codec(), WireCodec::None);
+
+        let (mut send_stream, _recv_stream) = conn.open_bi().await.unwrap();
+        QuicTransport::write_payload(&mut send_stream, &manifest, reply.codec())
+            .await
+            .unwrap();
+        let received = tokio::time::timeout(Duration::from_secs(5), server_task)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(received.file_id, manifest.file_id);
+        assert_eq!(received.zero_runs, manifest.zero_runs);
+    }
+
+    #[tokio::test]
+    async fn test_receiver_stats_reach_sender() {
+        init_crypto();
+        let config = ConnectionConfig {
+            bind_addr: "127.0.0.1:0".parse().unwrap(),
+            ..Default::default()
+        };
+        let server = Arc::new(QuicTransport::new(config).await.unwrap());
+        let server_addr = server.local_addr().unwrap();
+        let report = ReceiverStats {
+            chunks_received: 90,
+            bytes_received: 90 * 1024,
+            checksum_failures: 10,
+            decode_successes: 1,
+            decode_failures: 0,
+        };
+
+        let server_clone = server.clone();
+        let server_task = tokio::spawn(async move {
+            let conn = server_clone.accept().await.unwrap();
+            server_clone
+                .send_receiver_stats(&conn, &report)
+                .await
+                .unwrap();
+            conn.closed().await;
+        });
+
+        let client = QuicTransport::new(ConnectionConfig::default())
+            .await
+            .unwrap();
+        let conn = client.connect(server_addr).await.unwrap();
+        assert!(client.receiver_stats(server_addr).is_none());
+
+        let feedback = tokio::time::timeout(Duration::from_secs(5), client.receive_feedback(&conn))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(feedback, ReceiverFeedback::Stats(report));
+        assert_eq!(client.receiver_stats(server_addr), Some(report));
+        assert_eq!(client.receiver_reports(), vec![(server_addr, report)]);
+        assert_eq!(client.stats().stats_reports_received, 1);
+        assert!((report.corruption_rate() - 0.1).abs() < 1e-9);
+
+        conn.close(0u32.into(), b"done");
+        server_task.await.unwrap();
+        assert_eq!(server.stats().stats_reports_sent, 1);
+    }
+
+    #[tokio::test]
+    async fn test_connections_list_live_path_stats() {
+        init_crypto();
+        let config = ConnectionConfig {
+            bind_addr: "127.0.0.1:0".parse().unwrap(),
+            ..Default::default()
+        };
+        let server = Arc::new(QuicTransport::new(config).await.unwrap());
+        let server_addr = server.local_addr().unwrap();
+        let server_clone = server.clone();
+        let accepted = tokio::spawn(async move { server_clone.accept().await.unwrap() });
+
+        let client = QuicTransport::new(ConnectionConfig::default())
+            .await
+            .unwrap();
+        let conn = client.connect(server_addr).await.unwrap();
+        client.label_connection(&conn, "session-1");
+        let inbound = accepted.await.unwrap();
+
+        let listed = client.connections();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].id, conn.stable_id());
+        assert_eq!(listed[0].remote_addr, server_addr);
+        assert_eq!(listed[0].direction, ConnectionDirection::Outbound);
+        assert_eq!(listed[0].session_id.as_deref(), Some("session-1"));
+        assert_eq!(listed[0].bytes_in_flight, 0);
+        assert!(listed[0].path.cwnd > 0);
+
+        let listed = server.connections();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].direction, ConnectionDirection::Inbound);
+        assert_eq!(listed[0].session_id, None);
+        assert_eq!(
+            listed[0].negotiated.server_name.as_deref(),
+            Some("localhost")
+        );
+
+        conn.close(0u32.into(), b"done");
+        inbound.closed().await;
+        assert!(server.connections().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_corrupt_chunk_is_nacked() {
+        init_crypto();
+        let config = ConnectionConfig {
+            bind_addr: "127.0.0.1:0".parse().unwrap(),
+            ..Default::default()
+        };
+        let server = Arc::new(QuicTransport::new(config).await.unwrap());
+        let server_addr = server.local_addr().unwrap();
+
+        let server_clone = server.clone();
+        let server_task = tokio::spawn(async move {
+            let conn = server_clone.accept().await.unwrap();
+            let stream = server_clone.accept_uni(&conn).await.unwrap();
+            match server_clone.receive_verified_chunk(stream).await {
+                Err(NetworkError::CorruptChunk {
+                    file_id,
+                    sequence_number,
+                }) => {
+                    let nack = ChunkNack {
+                        file_id,
+                        sequence_number,
+                    };
+                    server_clone.send_nack(&conn, &nack).await.unwrap();
+                }
+                other => panic!("expected a corrupt chunk, got {other:?}"),
+            }
+            // Keep the connection up until the client has read the NACK
+            conn.closed().await;
+        });
+
+        let client = QuicTransport::new(ConnectionConfig::default())
+            .await
+            .unwrap();
+        let conn = client.connect(server_addr).await.unwrap();
+        // Test chunks carry a zero checksum, which never matches
+        client
+            .send_chunk(&conn, &create_test_chunk(b"damaged"))
+            .await
+            .unwrap();
+
+        let feedback = tokio::time::timeout(Duration::from_secs(5), client.receive_feedback(&conn))
+            .await
+            .unwrap()
+            .unwrap();
+        let ReceiverFeedback::Nack(nack) = feedback else {
+            panic!("expected a NACK, got {feedback:?}");
+        };
+        assert_eq!(nack.file_id, "test-file");
+        assert_eq!(nack.sequence_number, 0);
+        assert_eq!(client.stats().nacks_received, 1);
+        assert_eq!(server.stats().chunks_corrupted, 1);
+
+        conn.close(0u32.into(), b"done");
+        server_task.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_probe_link_reports_goodput() {
+        init_crypto();
+        let config = ConnectionConfig {
+            bind_addr: "127.0.0.1:0".parse().unwrap(),
+            ..Default::default()
+        };
+        let server = Arc::new(QuicTransport::new(config).await.unwrap());
+        let server_addr = server.local_addr().unwrap();
+
+        let server_clone = server.clone();
+        tokio::spawn(async move {
+            let conn = server_clone.accept().await.unwrap();
+            while let Ok(stream) = server_clone.accept_uni(&conn).await {
+                let chunk = server_clone.receive_chunk(stream).await.unwrap();
+                assert!(probe::is_probe_chunk(&chunk.chunk));
+            }
+        });
+
+        let client = QuicTransport::new(ConnectionConfig::default())
+            .await
+            .unwrap();
+        let conn = client.connect(server_addr).await.unwrap();
+        let report = client
+            .probe_link(&conn, Duration::from_millis(200), 16 * 1024)
+            .await
+            .unwrap();
+
+        assert!(report.chunks_sent > 0);
+        assert_eq!(report.bytes_sent, report.chunks_sent * 16 * 1024);
+        assert!(report.goodput_bytes_per_sec > 0);
+        assert_eq!(report.remote_addr, Some(server_addr));
+    }
+
     #[tokio::test]
     async fn test_stats() {
         init_crypto();
@@ -511,6 +1663,189 @@ mod tests {
         assert_eq!(stats.total_bytes_sent, 0);
     }
 
+    #[tokio::test]
+    async fn test_receive_over_memory_budget_fails() {
+        init_crypto();
+        let config = ConnectionConfig {
+            bind_addr: "127.0.0.1:0".parse().unwrap(),
+            receive_memory_limit: 16,
+            ..Default::default()
+        };
+        let server = Arc::new(QuicTransport::new(config).await.unwrap());
+        let server_addr = server.local_addr().unwrap();
+
+        let server_clone = server.clone();
+        let server_task = tokio::spawn(async move {
+            let conn = server_clone.accept().await.unwrap();
+            let stream = server_clone.accept_uni(&conn).await.unwrap();
+            server_clone.receive_chunk(stream).await
+        });
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let client = QuicTransport::new(ConnectionConfig::default())
+            .await
+            .unwrap();
+        let conn = client.connect(server_addr).await.unwrap();
+        let _ = client
+            .send_chunk(&conn, &create_test_chunk(&[7u8; 1024]))
+            .await;
+
+        let result = tokio::time::timeout(Duration::from_secs(5), server_task)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(matches!(
+            result,
+            Err(NetworkError::MemoryBudgetExceeded { .. })
+        ));
+        assert_eq!(server.stats().receive_memory_in_use, 0);
+    }
+
+    #[tokio::test]
+    async fn test_receive_rejects_oversized_metadata() {
+        init_crypto();
+        let config = ConnectionConfig {
+            bind_addr: "127.0.0.1:0".parse().unwrap(),
+            ..Default::default()
+        };
+        let server = Arc::new(QuicTransport::new(config).await.unwrap());
+        let server_addr = server.local_addr().unwrap();
+
+        let server_clone = server.clone();
+        let server_task = tokio::spawn(async move {
+            let conn = server_clone.accept().await.unwrap();
+            let stream = server_clone.accept_uni(&conn).await.unwrap();
+            server_clone.receive_chunk(stream).await
+        });
+
+        // A length prefix claiming 4 GB of metadata, and nothing behind it
+        let client = QuicTransport::new(ConnectionConfig::default())
+            .await
+            .unwrap();
+        let conn = client.connect(server_addr).await.unwrap();
+        let mut stream = conn.open_uni().await.unwrap();
+        stream.write_u32(u32::MAX).await.unwrap();
+        stream.finish().unwrap();
+
+        let result = tokio::time::timeout(Duration::from_secs(5), server_task)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(matches!(result, Err(NetworkError::ReceiveFailed(_))));
+        let stats = server.stats();
+        assert_eq!(
+            (stats.receive_memory_in_use, stats.receive_memory_peak),
+            (0, 0)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_receive_rejects_oversized_chunk() {
+        init_crypto();
+        let config = ConnectionConfig {
+            bind_addr: "127.0.0.1:0".parse().unwrap(),
+            max_chunk_size: 512,
+            ..Default::default()
+        };
+        let server = Arc::new(QuicTransport::new(config).await.unwrap());
+        let server_addr = server.local_addr().unwrap();
+
+        let server_clone = server.clone();
+        let server_task = tokio::spawn(async move {
+            let conn = server_clone.accept().await.unwrap();
+            let stream = server_clone.accept_uni(&conn).await.unwrap();
+            server_clone.receive_chunk(stream).await
+        });
+
+        let client = QuicTransport::new(ConnectionConfig::default())
+            .await
+            .unwrap();
+        let conn = client.connect(server_addr).await.unwrap();
+        let _ = client
+            .send_chunk(&conn, &create_test_chunk(&[7u8; 1024]))
+            .await;
+
+        let result = tokio::time::timeout(Duration::from_secs(5), server_task)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(matches!(
+            result,
+            Err(NetworkError::ChunkTooLarge {
+                size: 1024,
+                limit: 512
+            })
+        ));
+        assert_eq!(server.stats().receive_memory_in_use, 0);
+
+        let offer = FileOffer {
+            file_id: "file-1".into(),
+            filename: "big.bin".into(),
+            total_size: 4096,
+            checksum: [7u8; 32],
+            capabilities: Capabilities::local(),
+            max_chunk_size: 1024,
+            checksum_algorithm: Default::default(),
+        };
+        let accept = OfferReply::Accept(Capabilities::local());
+        assert_eq!(
+            offer.negotiate_chunk_size(server.max_chunk_size(), accept),
+            OfferReply::ChunkTooLarge {
+                max_chunk_size: 512
+            }
+        );
+        assert_eq!(offer.negotiate_chunk_size(1024, accept), accept);
+    }
+
+    #[tokio::test]
+    async fn test_connect_from_local_addr() {
+        init_crypto();
+        let config = ConnectionConfig {
+            bind_addr: "127.0.0.1:0".parse().unwrap(),
+            ..Default::default()
+        };
+        let server = Arc::new(QuicTransport::new(config).await.unwrap());
+        let server_addr = server.local_addr().unwrap();
+
+        let server_clone = server.clone();
+        let server_task = tokio::spawn(async move {
+            let conn = server_clone.accept().await.unwrap();
+            conn.remote_address()
+        });
+
+        let client = QuicTransport::new(ConnectionConfig::default())
+            .await
+            .unwrap();
+        let local: SocketAddr = "127.0.0.1:0".parse().unwrap();
+        let _conn = client.connect_from(server_addr, Some(local)).await.unwrap();
+
+        let remote_seen = tokio::time::timeout(Duration::from_secs(5), server_task)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(remote_seen.ip(), local.ip());
+    }
+
+    #[test]
+    fn test_validate_local_addr() {
+        let local: SocketAddr = "127.0.0.1:0".parse().unwrap();
+        assert!(QuicTransport::validate_local_addr(local, None).is_ok());
+
+        // Address family mismatch
+        let v6_remote: SocketAddr = "[::1]:5001".parse().unwrap();
+        assert!(matches!(
+            QuicTransport::validate_local_addr(local, Some(v6_remote)),
+            Err(NetworkError::InvalidAddress(_))
+        ));
+
+        // TEST-NET-1 is never assigned to a local interface
+        let missing: SocketAddr = "192.0.2.1:0".parse().unwrap();
+        assert!(matches!(
+            QuicTransport::validate_local_addr(missing, None),
+            Err(NetworkError::LocalAddressUnavailable { .. })
+        ));
+    }
+
     #[tokio::test]
     async fn test_send_with_retry() {
         init_crypto();
@@ -537,4 +1872,59 @@ mod tests {
         let result = client.send_with_retry(&conn, &chunk, 3).await;
         assert!(result.is_ok());
     }
+
+    #[tokio::test]
+    async fn test_cancel_interrupts_send_in_flight() {
+        init_crypto();
+        let config = ConnectionConfig {
+            bind_addr: "127.0.0.1:0".parse().unwrap(),
+            ..Default::default()
+        };
+        let server = Arc::new(QuicTransport::new(config).await.unwrap());
+        let server_addr = server.local_addr().unwrap();
+
+        // The receiver takes the stream but never reads it, so the send
+        // can't finish on its own
+        let (reset_tx, reset_rx) = tokio::sync::oneshot::channel();
+        let server_clone = server.clone();
+        tokio::spawn(async move {
+            let conn = server_clone.accept().await.unwrap();
+            let mut stream = conn.accept_uni().await.unwrap();
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            let mut buf = vec![0u8; 64 * 1024];
+            let reset = loop {
+                match stream.read(&mut buf).await {
+                    Ok(Some(_)) => continue,
+                    Ok(None) => break None,
+                    Err(quinn::ReadError::Reset(code)) => break Some(code.into_inner()),
+                    Err(_) => break None,
+                }
+            };
+            let _ = reset_tx.send(reset);
+        });
+
+        let client = QuicTransport::new(ConnectionConfig::default())
+            .await
+            .unwrap();
+        let conn = client.connect(server_addr).await.unwrap();
+        let chunk = create_test_chunk(&[1u8; 8 * 1024 * 1024]);
+
+        let cancel = CancellationToken::new();
+        let trigger = cancel.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            trigger.cancel();
+        });
+        let started = Instant::now();
+        let result = client
+            .send_with_retry_until(&conn, &chunk, 3, &cancel)
+            .await;
+        assert!(matches!(result, Err(NetworkError::Cancelled)), "{result:?}");
+        assert!(started.elapsed() < Duration::from_secs(2));
+        assert_eq!(reset_rx.await.unwrap(), Some(STREAM_CANCELLED as u64));
+
+        // Already cancelled: nothing is sent
+        let result = client.send_chunk_until(&conn, &chunk, &cancel).await;
+        assert!(matches!(result, Err(NetworkError::Cancelled)));
+    }
 }
@@ -1,5 +1,6 @@
 //! Rate limiting for network operations using the governor crate
 
+use crate::network::pacer::ChunkPacer;
 use governor::{
     clock::DefaultClock,
     state::{InMemoryState, NotKeyed},
@@ -16,6 +17,8 @@ pub struct TransferRateLimiter {
     chunks_limiter: Arc<RateLimiter<NotKeyed, InMemoryState, DefaultClock>>,
     /// Whether rate limiting is enabled
     enabled: bool,
+    /// Optional pacer that spaces writes within the allowed rate
+    pacer: Option<Arc<ChunkPacer>>,
 }
 
 impl TransferRateLimiter {
@@ -46,6 +49,28 @@ impl TransferRateLimiter {
             bytes_limiter: Arc::new(bytes_limiter),
             chunks_limiter: Arc::new(chunks_limiter),
             enabled: bytes_per_second > 0 || chunks_per_second > 0,
+            pacer: None,
+        }
+    }
+
+    /// Attach a pacer consulted by [`wait_for_send`](Self::wait_for_send)
+    pub fn with_pacer(mut self, pacer: Arc<ChunkPacer>) -> Self {
+        self.pacer = Some(pacer);
+        self
+    }
+
+    /// The attached pacer, if any
+    pub fn pacer(&self) -> Option<&Arc<ChunkPacer>> {
+        self.pacer.as_ref()
+    }
+
+    /// Wait until a chunk of `bytes` may be written: rate limits first,
+    /// then pacing to avoid bursts
+    pub async fn wait_for_send(&self, bytes: usize) {
+        self.wait_for_chunk().await;
+        self.wait_for_bytes(bytes).await;
+        if let Some(pacer) = &self.pacer {
+            pacer.pace(bytes).await;
         }
     }
 
@@ -61,7 +86,7 @@ impl TransferRateLimiter {
         }
 
         // Convert to KB units (minimum 1)
-        let kb_units = ((bytes + 1023) / 1024).max(1) as u32;
+        let kb_units = bytes.div_ceil(1024).max(1) as u32;
 
         for _ in 0..kb_units {
             self.bytes_limiter.until_ready().await;
@@ -95,6 +120,7 @@ impl Clone for TransferRateLimiter {
             bytes_limiter: self.bytes_limiter.clone(),
             chunks_limiter: self.chunks_limiter.clone(),
             enabled: self.enabled,
+            pacer: self.pacer.clone(),
         }
     }
 }
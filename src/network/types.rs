@@ -1,3 +1,11 @@
+use crate::chunk::FileManifest;
+use crate::integrity::ChecksumType;
+use crate::network::capture::CaptureConfig;
+use crate::network::flow_control::FlowControlConfig;
+use crate::network::pacer::PacerConfig;
+use crate::network::peer_shaper::PeerShapingConfig;
+use crate::network::quic_transport::MAX_CHUNK_STREAM_SIZE;
+use crate::network::wire::{Capabilities, WireCodec};
 use serde::{Deserialize, Serialize};
 use std::net::SocketAddr;
 use std::time::{Duration, Instant};
@@ -76,6 +84,24 @@ pub struct ConnectionConfig {
     /// This should ONLY be used for testing with self-signed certificates.
     /// In production, set this to false and provide proper certificates.
     pub insecure_skip_verify: bool,
+    /// Local address outbound connections are bound to (pins traffic to one
+    /// uplink on multi-homed hosts). `None` lets the OS pick the route.
+    pub client_bind_addr: Option<SocketAddr>,
+    /// Hard cap on chunk data held in memory on the receive path (bytes)
+    pub receive_memory_limit: usize,
+    /// Fraction of `receive_memory_limit` above which new streams aren't accepted
+    pub receive_high_watermark: f64,
+    /// Largest chunk payload accepted on one stream; senders are told when
+    /// they offer a file
+    pub max_chunk_size: usize,
+    /// Spacing of chunk writes on the send path
+    pub pacing: PacerConfig,
+    /// QUIC flow-control windows and their tuning
+    pub flow_control: FlowControlConfig,
+    /// Where key logs and connection event logs go when switched on
+    pub capture: CaptureConfig,
+    /// Receive-rate limits per sender
+    pub peer_shaping: PeerShapingConfig,
 }
 
 impl Default for ConnectionConfig {
@@ -89,6 +115,14 @@ impl Default for ConnectionConfig {
             // Default to insecure for backward compatibility with self-signed certs
             // TODO: Change to false when proper certificate management is implemented
             insecure_skip_verify: true,
+            client_bind_addr: None,
+            receive_memory_limit: 256 * 1024 * 1024,
+            receive_high_watermark: 0.8,
+            max_chunk_size: MAX_CHUNK_STREAM_SIZE,
+            pacing: PacerConfig::default(),
+            flow_control: FlowControlConfig::default(),
+            capture: CaptureConfig::default(),
+            peer_shaping: PeerShapingConfig::default(),
         }
     }
 }
@@ -123,10 +157,40 @@ pub struct NetworkStats {
     pub chunks_received: u64,
     pub retransmissions: u64,
     pub active_connections: usize,
+    /// Bytes currently charged to the receive memory budget
+    pub receive_memory_in_use: usize,
+    /// Highest receive memory usage seen
+    pub receive_memory_peak: usize,
+    /// Times stream acceptance paused above the memory watermark
+    pub receive_backpressure_pauses: u64,
+    /// Current send pacing rate in bytes per second (0 = not paced)
+    pub pacing_rate_bytes_per_sec: u64,
+    /// Chunks that waited on the pacer
+    pub paced_chunks_delayed: u64,
+    /// Total time chunk writes waited on the pacer
+    pub pacing_delay_ms: u64,
+    /// Times a connection's flow-control windows were grown
+    pub flow_window_adjustments: u64,
+    /// Largest send window any connection was tuned to
+    pub max_send_window: u64,
+    /// Received chunks discarded for a checksum mismatch
+    pub chunks_corrupted: u64,
+    /// Resend requests sent for corrupted chunks
+    pub nacks_sent: u64,
+    /// Resend requests received from a receiver
+    pub nacks_received: u64,
+    /// FEC group reports sent to a sender
+    pub group_reports_sent: u64,
+    /// FEC group reports received from a receiver
+    pub group_reports_received: u64,
+    /// Stats reports sent to a sender
+    pub stats_reports_sent: u64,
+    /// Stats reports received from a receiver
+    pub stats_reports_received: u64,
 }
 
 /// Real QUIC connection stats from quinn, captured after transfers
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct QuicPathStats {
     /// Round-trip time in milliseconds
     pub rtt_ms: f64,
@@ -145,3 +209,241 @@ pub struct QuicPathStats {
     /// Packet loss rate (lost / sent)
     pub loss_rate: f64,
 }
+
+/// Which end opened a connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectionDirection {
+    Outbound,
+    Inbound,
+}
+
+/// What a connection's handshake settled on
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NegotiatedParams {
+    /// ALPN protocol, if one was agreed
+    pub alpn: Option<String>,
+    /// Server name the client asked for; only known on inbound connections
+    pub server_name: Option<String>,
+    /// Largest datagram the peer takes, if it takes datagrams at all
+    pub max_datagram_size: Option<usize>,
+}
+
+/// One open QUIC connection with its live path stats
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConnectionInfo {
+    /// quinn's id for the connection, unique among open connections
+    pub id: usize,
+    pub remote_addr: SocketAddr,
+    pub direction: ConnectionDirection,
+    /// Transfer sending over the connection, when known
+    pub session_id: Option<String>,
+    pub path: QuicPathStats,
+    /// Smallest RTT seen on the path, in milliseconds
+    pub min_rtt_ms: f64,
+    /// Chunk bytes written and not yet acknowledged by the peer
+    pub bytes_in_flight: u64,
+    pub negotiated: NegotiatedParams,
+}
+
+/// File announced by a sender before any of its chunks are sent
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileOffer {
+    pub file_id: String,
+    pub filename: String,
+    pub total_size: u64,
+    /// Checksum of the whole file, hashed with `checksum_algorithm`
+    pub checksum: [u8; 32],
+    /// Wire codecs the sender decodes
+    pub capabilities: Capabilities,
+    /// Largest chunk payload the sender will put on a stream
+    pub max_chunk_size: u64,
+    /// Algorithm behind `checksum`; Blake3 from senders that predate it
+    #[serde(default)]
+    pub checksum_algorithm: ChecksumType,
+}
+
+impl FileOffer {
+    pub fn from_manifest(manifest: &FileManifest) -> Self {
+        Self {
+            file_id: manifest.file_id.clone(),
+            filename: manifest.filename.clone(),
+            total_size: manifest.total_size,
+            checksum: manifest.checksum,
+            capabilities: Capabilities::local(),
+            max_chunk_size: manifest.chunk_size as u64,
+            checksum_algorithm: manifest.checksum_algorithm,
+        }
+    }
+
+    /// Codec for messages the receiver sends back to this sender
+    pub fn codec(&self) -> WireCodec {
+        WireCodec::negotiate(Capabilities::local(), self.capabilities)
+    }
+
+    /// Reply for a receiver taking chunks of up to `max_chunk_size` bytes:
+    /// `accept` if the offered chunks fit, otherwise the limit they must fit
+    pub fn negotiate_chunk_size(&self, max_chunk_size: usize, accept: OfferReply) -> OfferReply {
+        if self.max_chunk_size > max_chunk_size as u64 {
+            OfferReply::ChunkTooLarge {
+                max_chunk_size: max_chunk_size as u64,
+            }
+        } else {
+            accept
+        }
+    }
+}
+
+/// Receiver's request to patch a delivered file that no longer matches its
+/// checksum
+///
+/// `signature` describes the receiver's damaged copy; the sender answers
+/// with a [`DeltaPatch`](crate::sync::DeltaPatch) carrying only the blocks
+/// that differ from the original.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RepairRequest {
+    pub file_id: String,
+    /// Checksum the file had when delivered, hashed with `checksum_algorithm`
+    pub checksum: [u8; 32],
+    pub signature: crate::sync::FileSignature,
+    /// Wire codecs the receiver decodes
+    pub capabilities: Capabilities,
+    /// Algorithm behind `checksum`; Blake3 from receivers that predate it
+    #[serde(default)]
+    pub checksum_algorithm: ChecksumType,
+}
+
+impl RepairRequest {
+    /// Codec for the sender's reply
+    pub fn codec(&self) -> WireCodec {
+        WireCodec::negotiate(Capabilities::local(), self.capabilities)
+    }
+}
+
+/// Sender's answer to a [`RepairRequest`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum RepairReply {
+    Patch(crate::sync::DeltaPatch),
+    /// The sender no longer holds the file as delivered
+    Unavailable(String),
+}
+
+/// Receiver's request to resend a chunk it discarded as corrupt
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChunkNack {
+    pub file_id: String,
+    pub sequence_number: u32,
+}
+
+/// Receiver's report of which shards of a file's FEC group it holds
+///
+/// A file is encoded as one Reed-Solomon group, so the receiver can rebuild
+/// it from any `data_chunks` of its `total_chunks` shards.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GroupFeedback {
+    pub file_id: String,
+    pub data_chunks: u32,
+    pub total_chunks: u32,
+    /// Sequence numbers received intact
+    pub received: Vec<u32>,
+}
+
+impl GroupFeedback {
+    /// Whether enough shards arrived to rebuild the file
+    pub fn is_decodable(&self) -> bool {
+        self.received.len() >= self.data_chunks as usize
+    }
+
+    /// Shards needed on top of what arrived before the file can be rebuilt
+    pub fn shortfall(&self) -> u32 {
+        (self.data_chunks as usize).saturating_sub(self.received.len()) as u32
+    }
+
+    /// Sequence numbers that haven't arrived
+    pub fn missing(&self) -> Vec<u32> {
+        let received: std::collections::HashSet<u32> = self.received.iter().copied().collect();
+        (0..self.total_chunks)
+            .filter(|seq| !received.contains(seq))
+            .collect()
+    }
+}
+
+/// What a receiver has seen on one connection, counted from its start
+///
+/// Sent back periodically so the sender sees corruption and decoding at the
+/// far end, not just its own counters.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReceiverStats {
+    /// Chunks that arrived intact
+    pub chunks_received: u64,
+    pub bytes_received: u64,
+    /// Chunks discarded for a checksum mismatch
+    pub checksum_failures: u64,
+    /// Files rebuilt from their chunks
+    pub decode_successes: u64,
+    /// Rebuild attempts that failed and waited for more chunks
+    pub decode_failures: u64,
+}
+
+impl ReceiverStats {
+    /// Share of arriving chunks that failed their checksum
+    pub fn corruption_rate(&self) -> f64 {
+        let arrived = self.chunks_received + self.checksum_failures;
+        if arrived == 0 {
+            0.0
+        } else {
+            self.checksum_failures as f64 / arrived as f64
+        }
+    }
+
+    /// Counts since `earlier`, a previous report from the same connection
+    pub fn since(&self, earlier: &Self) -> Self {
+        Self {
+            chunks_received: self.chunks_received.saturating_sub(earlier.chunks_received),
+            bytes_received: self.bytes_received.saturating_sub(earlier.bytes_received),
+            checksum_failures: self
+                .checksum_failures
+                .saturating_sub(earlier.checksum_failures),
+            decode_successes: self
+                .decode_successes
+                .saturating_sub(earlier.decode_successes),
+            decode_failures: self.decode_failures.saturating_sub(earlier.decode_failures),
+        }
+    }
+}
+
+/// Message a receiver sends back to the sender on its own streams
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ReceiverFeedback {
+    Nack(ChunkNack),
+    Group(GroupFeedback),
+    /// Only sent to senders that offered [`Capabilities::RECEIVER_STATS`]
+    Stats(ReceiverStats),
+}
+
+/// Receiver's answer to a [`FileOffer`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OfferReply {
+    /// Send the chunks
+    Send,
+    /// An identical file is already here; skip the transfer
+    AlreadyHave,
+    /// Send the chunks; the receiver decodes these wire codecs
+    Accept(Capabilities),
+    /// The offered chunks are larger than the receiver accepts on a stream
+    ChunkTooLarge { max_chunk_size: u64 },
+}
+
+impl OfferReply {
+    /// Codec for messages the sender sends on to this receiver
+    pub fn codec(&self) -> WireCodec {
+        match self {
+            OfferReply::Accept(capabilities) => {
+                WireCodec::negotiate(Capabilities::local(), *capabilities)
+            }
+            OfferReply::Send | OfferReply::AlreadyHave | OfferReply::ChunkTooLarge { .. } => {
+                WireCodec::None
+            }
+        }
+    }
+}
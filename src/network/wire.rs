@@ -0,0 +1,299 @@
+//! Compression of control messages on the wire
+//!
+//! Delta patches, file signatures and manifests (alone or inside repair
+//! requests and replies) are bincode-encoded and can run to megabytes, yet
+//! compress very well: a patch is mostly small `Copy` instructions and a
+//! signature is a regular array of block hashes. Each is
+//! sent as a frame whose first byte names the codec of the body, so a reader
+//! needs no state to decode it. Which codecs a writer may use is negotiated
+//! during the file offer: each side advertises [`Capabilities`] and the
+//! writer picks the best codec both understand.
+//!
+//! Codecs are added as [`WireCodec`] variants with a capability bit each;
+//! zstd is preferred over LZ4 when both peers have it.
+
+use crate::chunk::FileManifest;
+use crate::metrics::recorder;
+use crate::network::error::{NetworkError, NetworkResult};
+use crate::network::types::{RepairReply, RepairRequest};
+use crate::sync::{DeltaPatch, FileSignature};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+
+/// Largest decoded frame body accepted, whatever the frame claims
+pub const MAX_DECODED_FRAME: usize = 256 * 1024 * 1024;
+
+/// Bodies shorter than this are sent raw; compressing them saves nothing
+const MIN_COMPRESS_LEN: usize = 256;
+
+/// zstd level for frame bodies; the default trades little speed for a
+/// much smaller patch than LZ4
+const ZSTD_LEVEL: i32 = zstd::DEFAULT_COMPRESSION_LEVEL;
+
+/// Codecs and optional messages a peer can decode, as a bit set
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Capabilities(u32);
+
+impl Capabilities {
+    /// Raw frames only
+    pub const NONE: Self = Self(0);
+    /// LZ4 block compression
+    pub const LZ4: Self = Self(1);
+    /// Receiver stats reports ([`ReceiverFeedback::Stats`](crate::network::ReceiverFeedback::Stats))
+    pub const RECEIVER_STATS: Self = Self(2);
+    /// zstd compression
+    pub const ZSTD: Self = Self(4);
+
+    /// Everything this build can decode
+    pub fn local() -> Self {
+        Self(Self::LZ4.0 | Self::RECEIVER_STATS.0 | Self::ZSTD.0)
+    }
+
+    pub fn bits(self) -> u32 {
+        self.0
+    }
+
+    pub fn from_bits(bits: u32) -> Self {
+        Self(bits)
+    }
+
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn intersection(self, other: Self) -> Self {
+        Self(self.0 & other.0)
+    }
+}
+
+/// How a frame body is encoded
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WireCodec {
+    #[default]
+    None,
+    Lz4,
+    Zstd,
+}
+
+impl WireCodec {
+    /// Codecs in order of preference
+    const PREFERRED: [WireCodec; 2] = [WireCodec::Zstd, WireCodec::Lz4];
+
+    /// Best codec both sides can decode; `None` when they share none
+    pub fn negotiate(local: Capabilities, peer: Capabilities) -> Self {
+        let shared = local.intersection(peer);
+        Self::PREFERRED
+            .into_iter()
+            .find(|codec| shared.contains(codec.capability()))
+            .unwrap_or(WireCodec::None)
+    }
+
+    /// Capability bit a peer sets to receive this codec
+    pub fn capability(self) -> Capabilities {
+        match self {
+            WireCodec::None => Capabilities::NONE,
+            WireCodec::Lz4 => Capabilities::LZ4,
+            WireCodec::Zstd => Capabilities::ZSTD,
+        }
+    }
+
+    fn tag(self) -> u8 {
+        match self {
+            WireCodec::None => 0,
+            WireCodec::Lz4 => 1,
+            WireCodec::Zstd => 2,
+        }
+    }
+
+    fn from_tag(tag: u8) -> NetworkResult<Self> {
+        match tag {
+            0 => Ok(WireCodec::None),
+            1 => Ok(WireCodec::Lz4),
+            2 => Ok(WireCodec::Zstd),
+            other => Err(NetworkError::SerializationError(format!(
+                "unknown wire codec {other}"
+            ))),
+        }
+    }
+
+    fn compress(self, body: &[u8]) -> NetworkResult<Vec<u8>> {
+        match self {
+            WireCodec::None => Ok(body.to_vec()),
+            WireCodec::Lz4 => Ok(lz4_flex::compress_prepend_size(body)),
+            WireCodec::Zstd => Ok(zstd::bulk::compress(body, ZSTD_LEVEL)?),
+        }
+    }
+
+    fn decompress(self, body: &[u8]) -> NetworkResult<Vec<u8>> {
+        match self {
+            WireCodec::None => Ok(body.to_vec()),
+            WireCodec::Lz4 => {
+                // The size prefix comes from the peer; check it before allocating
+                let claimed = body
+                    .get(..4)
+                    .map(|len| u32::from_le_bytes(len.try_into().unwrap()) as usize)
+                    .unwrap_or(0);
+                if claimed > MAX_DECODED_FRAME {
+                    return Err(NetworkError::SerializationError(format!(
+                        "frame claims {claimed} bytes, limit is {MAX_DECODED_FRAME}"
+                    )));
+                }
+                lz4_flex::decompress_size_prepended(body)
+                    .map_err(|e| NetworkError::SerializationError(e.to_string()))
+            }
+            WireCodec::Zstd => {
+                // Stream the body out rather than trusting the size in the
+                // frame header, stopping one byte past the limit
+                let mut decoded = Vec::new();
+                zstd::stream::read::Decoder::new(body)?
+                    .take(MAX_DECODED_FRAME as u64 + 1)
+                    .read_to_end(&mut decoded)
+                    .map_err(|e| NetworkError::SerializationError(e.to_string()))?;
+                if decoded.len() > MAX_DECODED_FRAME {
+                    return Err(NetworkError::SerializationError(format!(
+                        "frame decodes past the {MAX_DECODED_FRAME} byte limit"
+                    )));
+                }
+                Ok(decoded)
+            }
+        }
+    }
+}
+
+/// A message that may be compressed on the wire
+pub trait WirePayload: Serialize + DeserializeOwned {
+    /// Metric label for this kind of message
+    const KIND: &'static str;
+}
+
+impl WirePayload for DeltaPatch {
+    const KIND: &'static str = "delta_patch";
+}
+
+impl WirePayload for FileSignature {
+    const KIND: &'static str = "file_signature";
+}
+
+impl WirePayload for FileManifest {
+    const KIND: &'static str = "file_manifest";
+}
+
+impl WirePayload for RepairRequest {
+    const KIND: &'static str = "repair_request";
+}
+
+impl WirePayload for RepairReply {
+    const KIND: &'static str = "repair_reply";
+}
+
+/// Serialize `value` into a frame, compressed with `codec` when that makes
+/// it smaller
+pub fn encode<T: WirePayload>(value: &T, codec: WireCodec) -> NetworkResult<Vec<u8>> {
+    let body = bincode::serialize(value)?;
+    let compressed = (codec != WireCodec::None && body.len() >= MIN_COMPRESS_LEN)
+        .then(|| codec.compress(&body))
+        .transpose()?
+        .filter(|compressed| compressed.len() < body.len());
+
+    let (codec, encoded) = match compressed {
+        Some(compressed) => (codec, compressed),
+        None => (WireCodec::None, body.clone()),
+    };
+    let mut frame = Vec::with_capacity(1 + encoded.len());
+    frame.push(codec.tag());
+    frame.extend_from_slice(&encoded);
+
+    recorder::record_wire_payload(T::KIND, body.len(), frame.len());
+    Ok(frame)
+}
+
+/// Read a frame written by [`encode`], whatever codec it used
+pub fn decode<T: WirePayload>(frame: &[u8]) -> NetworkResult<T> {
+    let (&tag, body) = frame
+        .split_first()
+        .ok_or_else(|| NetworkError::SerializationError("empty wire frame".into()))?;
+    let body = WireCodec::from_tag(tag)?.decompress(body)?;
+    Ok(bincode::deserialize(&body)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sync::{DeltaBuilder, SignatureBuilder};
+
+    #[test]
+    fn test_negotiates_shared_codec() {
+        assert_eq!(
+            WireCodec::negotiate(Capabilities::local(), Capabilities::local()),
+            WireCodec::Zstd
+        );
+        // Peers without zstd fall back to LZ4
+        assert_eq!(
+            WireCodec::negotiate(Capabilities::local(), Capabilities::LZ4),
+            WireCodec::Lz4
+        );
+        assert_eq!(
+            WireCodec::negotiate(Capabilities::local(), Capabilities::NONE),
+            WireCodec::None
+        );
+        // Bits this build doesn't know are ignored
+        assert_eq!(
+            WireCodec::negotiate(Capabilities::NONE, Capabilities::from_bits(u32::MAX)),
+            WireCodec::None
+        );
+    }
+
+    #[test]
+    fn test_patch_and_signature_round_trip_compressed() {
+        let source: Vec<u8> = (0..256 * 1024).map(|i| (i / 7 % 251) as u8).collect();
+        let mut target = source.clone();
+        target[100_000..100_016].copy_from_slice(&[0xAA; 16]);
+
+        let signature = SignatureBuilder::new()
+            .block_size(1024)
+            .build_from_bytes(&source);
+        let patch = DeltaBuilder::new()
+            .block_size(1024)
+            .build_from_data(&source, &target);
+
+        let raw = encode(&patch, WireCodec::None).unwrap();
+        let compressed = encode(&patch, WireCodec::Lz4).unwrap();
+        assert_eq!(compressed[0], WireCodec::Lz4.tag());
+        assert!(compressed.len() < raw.len());
+        let decoded: DeltaPatch = decode(&compressed).unwrap();
+        assert_eq!(decoded.apply(&source).unwrap(), target);
+
+        let frame = encode(&signature, WireCodec::Lz4).unwrap();
+        let decoded: FileSignature = decode(&frame).unwrap();
+        assert_eq!(decoded.blocks.len(), signature.blocks.len());
+        assert_eq!(decoded.file_hash, signature.file_hash);
+
+        let zstd = encode(&patch, WireCodec::Zstd).unwrap();
+        assert_eq!(zstd[0], WireCodec::Zstd.tag());
+        assert!(zstd.len() < raw.len());
+        let decoded: DeltaPatch = decode(&zstd).unwrap();
+        assert_eq!(decoded.apply(&source).unwrap(), target);
+
+        let frame = encode(&signature, WireCodec::Zstd).unwrap();
+        let decoded: FileSignature = decode(&frame).unwrap();
+        assert_eq!(decoded.blocks.len(), signature.blocks.len());
+        assert_eq!(decoded.file_hash, signature.file_hash);
+    }
+
+    #[test]
+    fn test_rejects_bad_frames() {
+        assert!(decode::<FileSignature>(&[]).is_err());
+        assert!(decode::<FileSignature>(&[9, 1, 2, 3]).is_err());
+
+        // A size prefix past the limit is refused before allocating
+        let mut frame = vec![WireCodec::Lz4.tag()];
+        frame.extend_from_slice(&u32::MAX.to_le_bytes());
+        assert!(decode::<FileSignature>(&frame).is_err());
+
+        // A zstd body that isn't a zstd frame
+        let frame = [WireCodec::Zstd.tag(), 1, 2, 3, 4, 5, 6, 7, 8];
+        assert!(decode::<FileSignature>(&frame).is_err());
+    }
+}
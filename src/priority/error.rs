@@ -5,6 +5,14 @@ pub enum QueueError {
     #[error("Queue is full (capacity: {0})")]
     QueueFull(usize),
 
+    #[error("Queue byte budget exhausted ({queued_bytes} of {budget} bytes queued)")]
+    ByteBudgetExceeded { queued_bytes: u64, budget: u64 },
+
+    #[error(
+        "Shedding Normal-priority chunks under memory pressure (RSS {rss_bytes} of {limit} bytes)"
+    )]
+    MemoryPressure { rss_bytes: u64, limit: u64 },
+
     #[error("Queue is empty")]
     QueueEmpty,
 
@@ -21,4 +29,15 @@ pub enum QueueError {
     Timeout(std::time::Duration),
 }
 
+impl QueueError {
+    /// The queue turned the chunk away for now; it can be offered again
+    /// once chunks are taken or memory is released
+    pub fn is_backpressure(&self) -> bool {
+        matches!(
+            self,
+            Self::QueueFull(_) | Self::ByteBudgetExceeded { .. } | Self::MemoryPressure { .. }
+        )
+    }
+}
+
 pub type QueueResult<T> = Result<T, QueueError>;
@@ -1,7 +1,15 @@
 pub mod error;
+pub mod pressure;
 pub mod queue;
+pub mod starvation;
 pub mod types;
 
 pub use error::{QueueError, QueueResult};
+pub use pressure::{MemoryMonitor, RssSampler, DEFAULT_SHED_WATERMARK};
 pub use queue::PriorityQueue;
-pub use types::{BandwidthAllocation, QueueStats, QueuedChunk};
+pub use starvation::{AlertSink, StarvationAlert, StarvationMonitor, StarvationPolicy};
+pub use types::{
+    BandwidthAllocation, LatencyStats, LevelStats, QueueSnapshot, QueueStats, QueuedChunk,
+    QueuedFile, ScheduledChunk, SloViolation, WaitStats, DEFAULT_PRIORITY_LEVELS,
+    MAX_PRIORITY_LEVELS, WAIT_BUCKETS_MS,
+};
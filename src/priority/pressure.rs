@@ -0,0 +1,132 @@
+//! Process memory pressure for the send queue
+//!
+//! The queue's chunk and byte limits only cover what it holds itself. A
+//! [`MemoryMonitor`] watches the whole process's resident set instead and,
+//! once it nears a configured limit, has the queue turn away Normal-priority
+//! chunks. Critical and High chunks are still accepted, and shed chunks stay
+//! with their session window until memory is released, so this slows bulk
+//! transfers down rather than failing them.
+
+use parking_lot::Mutex;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Fraction of the RSS limit above which Normal chunks are shed by default
+pub const DEFAULT_SHED_WATERMARK: f64 = 0.9;
+
+/// How long an RSS reading is reused before the monitor samples again
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Reads the process's resident set size in bytes
+pub type RssSampler = Arc<dyn Fn() -> Option<u64> + Send + Sync>;
+
+/// Watches process RSS against a limit
+pub struct MemoryMonitor {
+    limit: u64,
+    shed_at: u64,
+    sampler: RssSampler,
+    /// Last reading and when it was taken
+    last: Mutex<Option<(Instant, u64)>>,
+}
+
+impl std::fmt::Debug for MemoryMonitor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MemoryMonitor")
+            .field("limit", &self.limit)
+            .field("shed_at", &self.shed_at)
+            .finish()
+    }
+}
+
+impl MemoryMonitor {
+    /// Shed Normal chunks once RSS passes `watermark` of `limit` bytes
+    pub fn new(limit: u64, watermark: f64) -> Self {
+        Self::with_sampler(limit, watermark, Arc::new(process_rss_bytes))
+    }
+
+    /// Monitor reading RSS from `sampler` instead of the OS
+    pub fn with_sampler(limit: u64, watermark: f64, sampler: RssSampler) -> Self {
+        Self {
+            limit,
+            shed_at: (limit as f64 * watermark.clamp(0.0, 1.0)) as u64,
+            sampler,
+            last: Mutex::new(None),
+        }
+    }
+
+    pub fn limit(&self) -> u64 {
+        self.limit
+    }
+
+    /// RSS above which Normal chunks are shed
+    pub fn shed_at(&self) -> u64 {
+        self.shed_at
+    }
+
+    /// Current RSS, sampled at most every [`SAMPLE_INTERVAL`]; `None` where
+    /// the platform doesn't report it
+    pub fn rss_bytes(&self) -> Option<u64> {
+        let mut last = self.last.lock();
+        if let Some((at, rss)) = *last {
+            if at.elapsed() < SAMPLE_INTERVAL {
+                return Some(rss);
+            }
+        }
+        let rss = (self.sampler)()?;
+        *last = Some((Instant::now(), rss));
+        Some(rss)
+    }
+
+    /// RSS, if it is high enough that Normal chunks should be shed
+    pub fn shedding(&self) -> Option<u64> {
+        self.rss_bytes().filter(|&rss| rss >= self.shed_at)
+    }
+}
+
+/// Resident set size of this process, from `/proc/self/statm`
+#[cfg(target_os = "linux")]
+pub fn process_rss_bytes() -> Option<u64> {
+    // Second field is resident pages; 4 KiB pages are all Linux uses on the
+    // platforms we ship
+    const PAGE_SIZE: u64 = 4096;
+    let statm = std::fs::read_to_string("/proc/self/statm").ok()?;
+    let pages: u64 = statm.split_whitespace().nth(1)?.parse().ok()?;
+    Some(pages * PAGE_SIZE)
+}
+
+/// Resident set size of this process; not available on this platform
+#[cfg(not(target_os = "linux"))]
+pub fn process_rss_bytes() -> Option<u64> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    #[test]
+    fn test_sheds_above_watermark() {
+        let rss = Arc::new(AtomicU64::new(500));
+        let reading = rss.clone();
+        let monitor = MemoryMonitor::with_sampler(
+            1000,
+            0.9,
+            Arc::new(move || Some(reading.load(Ordering::Relaxed))),
+        );
+        assert_eq!(monitor.shed_at(), 900);
+        assert_eq!(monitor.shedding(), None);
+
+        // Readings are cached briefly
+        rss.store(950, Ordering::Relaxed);
+        assert_eq!(monitor.rss_bytes(), Some(500));
+        std::thread::sleep(SAMPLE_INTERVAL);
+        assert_eq!(monitor.shedding(), Some(950));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_reads_process_rss() {
+        assert!(process_rss_bytes().unwrap() > 0);
+    }
+}
@@ -1,34 +1,130 @@
 use crate::chunk::{Chunk, Priority};
+use crate::metrics::latency::{record_stage, Stage};
+use crate::metrics::recorder;
 use crate::priority::error::{QueueError, QueueResult};
-use crate::priority::types::{BandwidthAllocation, QueueStats, QueuedChunk};
+use crate::priority::pressure::MemoryMonitor;
+use crate::priority::starvation::priority_label;
+use crate::priority::types::{
+    BandwidthAllocation, QueueSnapshot, QueueStats, QueuedChunk, QueuedFile, ScheduledChunk,
+    SloViolation, DEFAULT_PRIORITY_LEVELS, MAX_PRIORITY_LEVELS,
+};
 use parking_lot::RwLock;
-use std::collections::BinaryHeap;
+use std::collections::{BinaryHeap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 const MAX_RETRIES: u32 = 5;
 
 pub struct PriorityQueue {
-    queues: [Arc<RwLock<BinaryHeap<QueuedChunk>>>; 3],
+    /// One heap per level, most urgent first
+    queues: Vec<Arc<RwLock<BinaryHeap<QueuedChunk>>>>,
     stats: Arc<RwLock<QueueStats>>,
     max_capacity: usize,
+    /// Most chunk data queued at once (0 = no limit)
+    max_bytes: u64,
+    queued_bytes: Arc<AtomicU64>,
+    /// Sheds Normal-priority enqueues when the process nears its RSS limit
+    memory: Option<Arc<MemoryMonitor>>,
+    /// Enqueue-to-delivery target per level, giving each chunk a deadline
+    latency_targets: Vec<Option<Duration>>,
 }
 
 impl PriorityQueue {
     pub fn new(max_capacity: usize) -> Self {
         Self {
-            queues: [
-                Arc::new(RwLock::new(BinaryHeap::new())),
-                Arc::new(RwLock::new(BinaryHeap::new())),
-                Arc::new(RwLock::new(BinaryHeap::new())),
-            ],
+            queues: Vec::new(),
             stats: Arc::new(RwLock::new(QueueStats::default())),
             max_capacity,
+            max_bytes: 0,
+            queued_bytes: Arc::new(AtomicU64::new(0)),
+            memory: None,
+            latency_targets: Vec::new(),
         }
+        .with_levels(DEFAULT_PRIORITY_LEVELS)
+    }
+
+    /// Queue chunks at `levels` priority levels instead of three
+    ///
+    /// `Critical`, `High` and `Normal` stay levels 0 to 2, and chunks with a
+    /// level past the last one join it. The count is kept between 3 and
+    /// [`MAX_PRIORITY_LEVELS`]. Call this before anything is queued; it
+    /// starts the queue and its statistics afresh.
+    pub fn with_levels(mut self, levels: usize) -> Self {
+        let levels = levels.clamp(DEFAULT_PRIORITY_LEVELS, MAX_PRIORITY_LEVELS);
+        self.queues = (0..levels)
+            .map(|_| Arc::new(RwLock::new(BinaryHeap::new())))
+            .collect();
+        self.stats = Arc::new(RwLock::new(QueueStats::with_levels(levels)));
+        self.queued_bytes = Arc::new(AtomicU64::new(0));
+        self.latency_targets = vec![None; levels];
+        self
+    }
+
+    /// Number of priority levels
+    pub fn levels(&self) -> usize {
+        self.queues.len()
+    }
+
+    /// Also limit the chunk data queued to `bytes` (0 = no limit)
+    ///
+    /// Chunk counts alone say little about memory when chunk sizes vary.
+    /// A chunk larger than the whole budget is still accepted into an empty
+    /// queue, so it can't stall its transfer.
+    pub fn with_byte_budget(mut self, bytes: u64) -> Self {
+        self.max_bytes = bytes;
+        self
+    }
+
+    /// Turn Normal-priority chunks away while `monitor` reports pressure
+    pub fn with_memory_monitor(mut self, monitor: MemoryMonitor) -> Self {
+        self.memory = Some(Arc::new(monitor));
+        self
+    }
+
+    /// Give chunks of `priority` a deadline `target` after they are enqueued
+    ///
+    /// Deliveries past the deadline count as misses in [`QueueStats`]; for
+    /// Critical chunks [`record_delivery`](Self::record_delivery) also
+    /// reports an [`SloViolation`].
+    pub fn with_latency_target(mut self, priority: Priority, target: Option<Duration>) -> Self {
+        let idx = self.priority_to_index(priority);
+        self.latency_targets[idx] = target;
+        self
+    }
+
+    pub fn latency_target(&self, priority: Priority) -> Option<Duration> {
+        self.latency_targets[self.priority_to_index(priority)]
+    }
+
+    pub fn byte_budget(&self) -> u64 {
+        self.max_bytes
+    }
+
+    pub fn memory_monitor(&self) -> Option<&MemoryMonitor> {
+        self.memory.as_deref()
+    }
+
+    /// Chunk data currently queued
+    pub fn queued_bytes(&self) -> u64 {
+        self.queued_bytes.load(Ordering::Acquire)
     }
 
     /// Enqueue chunk with priority
+    ///
+    /// Its deadline is the class's latency target from now, if it has one.
     pub fn enqueue(&self, chunk: Chunk) -> QueueResult<()> {
+        self.push(chunk, None)
+    }
+
+    /// Enqueue a chunk that should reach the receiver by `deadline`,
+    /// whatever its class's target
+    pub fn enqueue_with_deadline(&self, chunk: Chunk, deadline: Instant) -> QueueResult<()> {
+        self.push(chunk, Some(deadline))
+    }
+
+    /// Queue `chunk`, due by `deadline` or else by its class's target
+    fn push(&self, chunk: Chunk, deadline: Option<Instant>) -> QueueResult<()> {
         let priority_idx = self.priority_to_index(chunk.metadata.priority);
 
         // Check capacity
@@ -36,99 +132,181 @@ impl PriorityQueue {
             return Err(QueueError::QueueFull(self.max_capacity));
         }
 
+        let bytes = chunk.data.len() as u64;
+        let queued_bytes = self.queued_bytes();
+        if self.max_bytes > 0 && queued_bytes > 0 && queued_bytes + bytes > self.max_bytes {
+            return Err(QueueError::ByteBudgetExceeded {
+                queued_bytes,
+                budget: self.max_bytes,
+            });
+        }
+
+        // Soft backpressure: bulk traffic waits, urgent traffic doesn't
+        if chunk.metadata.priority.class() == Priority::Normal {
+            if let Some(monitor) = &self.memory {
+                if let Some(rss_bytes) = monitor.shedding() {
+                    self.stats.write().shed_enqueues += 1;
+                    return Err(QueueError::MemoryPressure {
+                        rss_bytes,
+                        limit: monitor.limit(),
+                    });
+                }
+            }
+        }
+
         let queued = QueuedChunk::new(chunk, priority_idx);
+        let deadline = deadline.or_else(|| {
+            self.latency_targets[priority_idx].map(|target| queued.enqueued_at + target)
+        });
+        let queued = queued.with_deadline(deadline);
+        self.queued_bytes.fetch_add(bytes, Ordering::AcqRel);
 
         {
             let mut queue = self.queues[priority_idx].write();
             queue.push(queued);
         }
 
-        // Update stats
-        {
-            let mut stats = self.stats.write();
-            stats.total_enqueued += 1;
-            match priority_idx {
-                0 => stats.critical_pending += 1,
-                1 => stats.high_pending += 1,
-                2 => stats.normal_pending += 1,
-                _ => {}
-            }
-        }
+        self.stats.write().record_enqueue(priority_idx);
 
         Ok(())
     }
 
     /// Dequeue next chunk (priority-ordered)
     pub fn dequeue(&self) -> QueueResult<Chunk> {
-        // Try queues in priority order: Critical -> High -> Normal
-        for priority_idx in 0..3 {
-            let mut queue = self.queues[priority_idx].write();
-
-            if let Some(queued) = queue.pop() {
-                let wait_time_ms = queued.wait_time().as_millis() as u64;
-
-                // Update stats
-                {
-                    let mut stats = self.stats.write();
-                    stats.total_processed += 1;
+        // Try queues in priority order, most urgent level first
+        for priority_idx in 0..self.levels() {
+            if let Some(chunk) = self.pop(priority_idx) {
+                return Ok(chunk);
+            }
+        }
 
-                    // Update average wait time
-                    if stats.avg_wait_time_ms == 0 {
-                        stats.avg_wait_time_ms = wait_time_ms;
-                    } else {
-                        stats.avg_wait_time_ms = (stats.avg_wait_time_ms + wait_time_ms) / 2;
-                    }
+        Err(QueueError::QueueEmpty)
+    }
 
-                    // Update max wait time
-                    if wait_time_ms > stats.max_wait_time_ms {
-                        stats.max_wait_time_ms = wait_time_ms;
-                    }
+    /// Dequeue from specific priority level
+    pub fn dequeue_priority(&self, priority: Priority) -> QueueResult<Chunk> {
+        self.pop(self.priority_to_index(priority))
+            .ok_or(QueueError::QueueEmpty)
+    }
 
-                    match priority_idx {
-                        0 => stats.critical_pending = stats.critical_pending.saturating_sub(1),
-                        1 => stats.high_pending = stats.high_pending.saturating_sub(1),
-                        2 => stats.normal_pending = stats.normal_pending.saturating_sub(1),
-                        _ => {}
-                    }
-                }
+    /// Dequeue the next chunk of one file (priority-ordered)
+    ///
+    /// Chunks of other files stay queued. Transfers share the queue, so each
+    /// worker takes only its own chunks.
+    pub fn dequeue_file(&self, file_id: &str) -> QueueResult<Chunk> {
+        self.dequeue_file_queued(file_id).map(|queued| queued.chunk)
+    }
 
-                return Ok(queued.chunk);
+    /// Like [`dequeue_file`](Self::dequeue_file), keeping the enqueue time
+    /// and deadline to pass to [`record_delivery`](Self::record_delivery)
+    pub fn dequeue_file_queued(&self, file_id: &str) -> QueueResult<QueuedChunk> {
+        for priority_idx in 0..self.levels() {
+            if let Some(queued) = self.pop_file(priority_idx, file_id) {
+                return Ok(queued);
             }
         }
 
         Err(QueueError::QueueEmpty)
     }
 
-    /// Dequeue from specific priority level
-    pub fn dequeue_priority(&self, priority: Priority) -> QueueResult<Chunk> {
-        let priority_idx = self.priority_to_index(priority);
-        let mut queue = self.queues[priority_idx].write();
-
-        if let Some(queued) = queue.pop() {
-            let wait_time_ms = queued.wait_time().as_millis() as u64;
+    /// Record that a dequeued chunk reached the receiver
+    ///
+    /// Returns the violation when a Critical chunk arrived after its
+    /// deadline; misses in other classes are only counted.
+    pub fn record_delivery(&self, queued: &QueuedChunk) -> Option<SloViolation> {
+        let priority = self.index_to_priority(queued.priority_idx);
+        let latency = queued.wait_time();
+        let missed = queued
+            .deadline
+            .is_some_and(|deadline| Instant::now() > deadline);
+        let latency_ms = latency.as_millis() as u64;
+        self.stats
+            .write()
+            .record_delivery(queued.priority_idx, latency_ms, missed);
+
+        let label = priority_label(priority);
+        recorder::record_chunk_delivery_latency(label, latency);
+        if !missed {
+            return None;
+        }
+        recorder::record_deadline_miss(label);
+        (priority.class() == Priority::Critical).then(|| SloViolation {
+            file_id: queued.chunk.metadata.file_id.clone(),
+            sequence_number: queued.chunk.metadata.sequence_number,
+            priority,
+            latency_ms,
+            target_ms: queued.target().unwrap_or_default().as_millis() as u64,
+        })
+    }
+
+    /// Drop every queued chunk of a file; returns how many were dropped
+    pub fn remove_file(&self, file_id: &str) -> usize {
+        let mut removed = 0;
+        for priority_idx in 0..self.levels() {
+            let dropped = {
+                let mut queue = self.queues[priority_idx].write();
+                let before = queue.len();
+                let mut bytes = 0;
+                queue.retain(|q| {
+                    let keep = q.chunk.metadata.file_id != file_id;
+                    if !keep {
+                        bytes += q.chunk.data.len() as u64;
+                    }
+                    keep
+                });
+                self.queued_bytes.fetch_sub(bytes, Ordering::AcqRel);
+                before - queue.len()
+            };
 
-            // Update stats
-            {
-                let mut stats = self.stats.write();
-                stats.total_processed += 1;
-                stats.avg_wait_time_ms = (stats.avg_wait_time_ms + wait_time_ms) / 2;
+            let mut stats = self.stats.write();
+            let pending = &mut stats.levels[priority_idx].pending;
+            *pending = pending.saturating_sub(dropped);
+            removed += dropped;
+        }
+        removed
+    }
 
-                if wait_time_ms > stats.max_wait_time_ms {
-                    stats.max_wait_time_ms = wait_time_ms;
-                }
+    fn pop(&self, priority_idx: usize) -> Option<Chunk> {
+        let queued = self.queues[priority_idx].write().pop()?;
+        Some(self.taken(priority_idx, queued).chunk)
+    }
 
-                match priority_idx {
-                    0 => stats.critical_pending = stats.critical_pending.saturating_sub(1),
-                    1 => stats.high_pending = stats.high_pending.saturating_sub(1),
-                    2 => stats.normal_pending = stats.normal_pending.saturating_sub(1),
-                    _ => {}
-                }
+    /// Pop the first chunk of `file_id` in one class; scans the class unless
+    /// that chunk is already on top
+    fn pop_file(&self, priority_idx: usize, file_id: &str) -> Option<QueuedChunk> {
+        let queued = {
+            let mut queue = self.queues[priority_idx].write();
+            if queue
+                .peek()
+                .is_some_and(|q| q.chunk.metadata.file_id == file_id)
+            {
+                queue.pop()
+            } else {
+                let mut items = std::mem::take(&mut *queue).into_vec();
+                let first = items
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, q)| q.chunk.metadata.file_id == file_id)
+                    .max_by(|a, b| a.1.cmp(b.1))
+                    .map(|(i, _)| i);
+                let queued = first.map(|i| items.swap_remove(i));
+                *queue = items.into();
+                queued
             }
+        }?;
+        Some(self.taken(priority_idx, queued))
+    }
 
-            Ok(queued.chunk)
-        } else {
-            Err(QueueError::QueueEmpty)
-        }
+    fn taken(&self, priority_idx: usize, queued: QueuedChunk) -> QueuedChunk {
+        let wait_time = queued.wait_time();
+        record_stage(Stage::QueueWait, wait_time);
+        let wait_time_ms = wait_time.as_millis() as u64;
+        self.queued_bytes
+            .fetch_sub(queued.chunk.data.len() as u64, Ordering::AcqRel);
+        self.stats
+            .write()
+            .record_dequeue(priority_idx, wait_time_ms);
+        queued
     }
 
     /// Re-enqueue failed chunk with retry count
@@ -149,7 +327,90 @@ impl PriorityQueue {
 
     /// Get queue statistics
     pub fn stats(&self) -> QueueStats {
-        self.stats.read().clone()
+        let mut stats = self.stats.read().clone();
+        stats.queued_bytes = self.queued_bytes();
+        for (idx, level) in stats.levels.iter_mut().enumerate() {
+            level.wait.oldest_wait_ms = self.oldest_wait_ms(idx);
+        }
+        stats
+    }
+
+    /// Age of the longest-waiting chunk in one class
+    ///
+    /// The heaps are ordered by sequence number, so this scans the class.
+    fn oldest_wait_ms(&self, priority_idx: usize) -> u64 {
+        self.queues[priority_idx]
+            .read()
+            .iter()
+            .map(|q| q.wait_time().as_millis() as u64)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Queued chunks grouped by file, and the next `next` chunks in
+    /// dequeue order, without dequeuing anything
+    ///
+    /// Each level is read under its own lock, so a snapshot taken while
+    /// chunks move may be off by those chunks.
+    pub fn inspect(&self, next: usize) -> QueueSnapshot {
+        let now = chrono::Utc::now().timestamp();
+        let mut files: HashMap<String, QueuedFile> = HashMap::new();
+        let mut scheduled = Vec::new();
+
+        for (priority_idx, queue) in self.queues.iter().enumerate() {
+            let queue = queue.read();
+            for queued in queue.iter() {
+                let metadata = &queued.chunk.metadata;
+                let wait_ms = queued.wait_time().as_millis() as u64;
+                let file = files
+                    .entry(metadata.file_id.clone())
+                    .or_insert_with(|| QueuedFile {
+                        file_id: metadata.file_id.clone(),
+                        session_id: None,
+                        pending: vec![0; self.levels()],
+                        queued_bytes: 0,
+                        oldest_enqueued_at: now,
+                        oldest_wait_ms: 0,
+                    });
+                file.pending[priority_idx] += 1;
+                file.queued_bytes += queued.chunk.data.len() as u64;
+                if wait_ms >= file.oldest_wait_ms {
+                    file.oldest_wait_ms = wait_ms;
+                    file.oldest_enqueued_at = now - (wait_ms / 1000) as i64;
+                }
+            }
+
+            if scheduled.len() < next {
+                // Heap order: the greatest chunk is dequeued first
+                let mut level: Vec<&QueuedChunk> = queue.iter().collect();
+                level.sort_by(|a, b| b.cmp(a));
+                scheduled.extend(
+                    level
+                        .into_iter()
+                        .take(next - scheduled.len())
+                        .map(|queued| ScheduledChunk {
+                            wait_ms: queued.wait_time().as_millis() as u64,
+                            file_id: queued.chunk.metadata.file_id.clone(),
+                            session_id: None,
+                            sequence_number: queued.chunk.metadata.sequence_number,
+                            priority: self.index_to_priority(priority_idx),
+                            is_parity: queued.chunk.metadata.is_parity,
+                            bytes: queued.chunk.data.len(),
+                        }),
+                );
+            }
+        }
+
+        let mut files: Vec<QueuedFile> = files.into_values().collect();
+        files.sort_by(|a, b| {
+            b.oldest_wait_ms
+                .cmp(&a.oldest_wait_ms)
+                .then_with(|| a.file_id.cmp(&b.file_id))
+        });
+        QueueSnapshot {
+            files,
+            next: scheduled,
+        }
     }
 
     /// Get pending count for specific priority
@@ -173,33 +434,25 @@ impl PriorityQueue {
         for queue in &self.queues {
             queue.write().clear();
         }
+        self.queued_bytes.store(0, Ordering::Release);
 
-        let mut stats = self.stats.write();
-        stats.critical_pending = 0;
-        stats.high_pending = 0;
-        stats.normal_pending = 0;
+        for level in &mut self.stats.write().levels {
+            level.pending = 0;
+        }
     }
 
     /// Allocate bandwidth based on current queue state
     pub fn allocate_bandwidth(&self, total_bps: u64) -> BandwidthAllocation {
-        let stats = self.stats.read();
-        BandwidthAllocation::new(
-            total_bps,
-            stats.critical_pending,
-            stats.high_pending,
-            stats.normal_pending,
-        )
+        let pending: Vec<usize> = self.stats.read().levels.iter().map(|l| l.pending).collect();
+        BandwidthAllocation::new(total_bps, &pending)
     }
 
     /// Peek at next chunk without removing it
     pub fn peek(&self) -> Option<Priority> {
-        for priority_idx in 0..3 {
-            let queue = self.queues[priority_idx].read();
-            if !queue.is_empty() {
-                return Some(self.index_to_priority(priority_idx));
-            }
-        }
-        None
+        self.queues
+            .iter()
+            .position(|queue| !queue.read().is_empty())
+            .map(|priority_idx| self.index_to_priority(priority_idx))
     }
 
     /// Get capacity information
@@ -212,32 +465,24 @@ impl PriorityQueue {
 
     // Helper functions
     fn priority_to_index(&self, priority: Priority) -> usize {
-        match priority {
-            Priority::Critical => 0,
-            Priority::High => 1,
-            Priority::Normal => 2,
-        }
+        (priority.level() as usize).min(self.levels() - 1)
     }
 
     fn index_to_priority(&self, index: usize) -> Priority {
-        match index {
-            0 => Priority::Critical,
-            1 => Priority::High,
-            _ => Priority::Normal,
-        }
+        Priority::from_level(index as u8)
     }
 }
 
 impl Clone for PriorityQueue {
     fn clone(&self) -> Self {
         Self {
-            queues: [
-                self.queues[0].clone(),
-                self.queues[1].clone(),
-                self.queues[2].clone(),
-            ],
+            queues: self.queues.clone(),
             stats: self.stats.clone(),
             max_capacity: self.max_capacity,
+            max_bytes: self.max_bytes,
+            queued_bytes: self.queued_bytes.clone(),
+            memory: self.memory.clone(),
+            latency_targets: self.latency_targets.clone(),
         }
     }
 }
@@ -263,6 +508,12 @@ mod tests {
                 file_size: 1024 * 100,
                 file_checksum: [0u8; 32],
                 data_chunks: 80,
+                zero_runs: Vec::new(),
+                attributes: None,
+                checksum_algorithm: Default::default(),
+                merkle_proof: None,
+                trace: None,
+                compression: None,
             },
             data: Bytes::from(vec![0u8; 1024]),
         }
@@ -358,6 +609,60 @@ mod tests {
         assert!(matches!(result, Err(QueueError::QueueFull(_))));
     }
 
+    #[test]
+    fn test_byte_budget() {
+        // Room for two of the 1 KiB test chunks
+        let queue = PriorityQueue::new(1000).with_byte_budget(2500);
+
+        queue.enqueue(create_test_chunk(Priority::High, 0)).unwrap();
+        queue.enqueue(create_test_chunk(Priority::High, 1)).unwrap();
+        assert_eq!(queue.queued_bytes(), 2048);
+
+        let result = queue.enqueue(create_test_chunk(Priority::Critical, 2));
+        assert!(matches!(
+            result,
+            Err(QueueError::ByteBudgetExceeded {
+                queued_bytes: 2048,
+                budget: 2500
+            })
+        ));
+
+        queue.dequeue().unwrap();
+        assert_eq!(queue.stats().queued_bytes, 1024);
+        queue.enqueue(create_test_chunk(Priority::High, 2)).unwrap();
+        queue.clear();
+        assert_eq!(queue.queued_bytes(), 0);
+
+        // An oversized chunk still goes into an empty queue
+        let queue = PriorityQueue::new(1000).with_byte_budget(100);
+        queue
+            .enqueue(create_test_chunk(Priority::Normal, 0))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_memory_pressure_sheds_normal_priority() {
+        let monitor = MemoryMonitor::with_sampler(1000, 0.9, Arc::new(|| Some(950)));
+        let queue = PriorityQueue::new(1000).with_memory_monitor(monitor);
+
+        let result = queue.enqueue(create_test_chunk(Priority::Normal, 0));
+        assert!(matches!(
+            result,
+            Err(QueueError::MemoryPressure {
+                rss_bytes: 950,
+                limit: 1000
+            })
+        ));
+        assert!(result.unwrap_err().is_backpressure());
+
+        queue.enqueue(create_test_chunk(Priority::High, 1)).unwrap();
+        queue
+            .enqueue(create_test_chunk(Priority::Critical, 2))
+            .unwrap();
+        assert_eq!(queue.total_pending(), 2);
+        assert_eq!(queue.stats().shed_enqueues, 1);
+    }
+
     #[test]
     fn test_dequeue_empty() {
         let queue = PriorityQueue::new(1000);
@@ -378,17 +683,79 @@ mod tests {
             .unwrap();
 
         let stats = queue.stats();
-        assert_eq!(stats.critical_pending, 1);
-        assert_eq!(stats.high_pending, 1);
-        assert_eq!(stats.normal_pending, 1);
+        assert_eq!(stats.pending(Priority::Critical), 1);
+        assert_eq!(stats.pending(Priority::High), 1);
+        assert_eq!(stats.pending(Priority::Normal), 1);
         assert_eq!(stats.total_enqueued, 3);
 
         queue.dequeue().unwrap();
         let stats = queue.stats();
-        assert_eq!(stats.critical_pending, 0);
+        assert_eq!(stats.pending(Priority::Critical), 0);
         assert_eq!(stats.total_processed, 1);
     }
 
+    #[test]
+    fn test_per_priority_wait_stats() {
+        let queue = PriorityQueue::new(1000);
+        queue
+            .enqueue(create_test_chunk(Priority::Normal, 0))
+            .unwrap();
+        queue
+            .enqueue(create_test_chunk(Priority::Critical, 1))
+            .unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(20));
+
+        let stats = queue.stats();
+        assert!(stats.wait(Priority::Normal).oldest_wait_ms >= 20);
+        assert_eq!(stats.wait(Priority::High).oldest_wait_ms, 0);
+
+        queue.dequeue().unwrap();
+        let stats = queue.stats();
+        let critical = stats.wait(Priority::Critical);
+        assert_eq!(critical.oldest_wait_ms, 0);
+        assert_eq!(critical.histogram.iter().sum::<u64>(), 1);
+        assert!(critical.p95_wait_ms >= 20);
+        assert!(stats.wait(Priority::Normal).histogram.is_empty());
+    }
+
+    #[test]
+    fn test_deadline_misses_and_slo_violations() {
+        let queue = PriorityQueue::new(1000)
+            .with_latency_target(Priority::Critical, Some(Duration::from_millis(20)));
+        queue
+            .enqueue(create_test_chunk(Priority::Critical, 0))
+            .unwrap();
+        queue
+            .enqueue(create_test_chunk(Priority::Critical, 1))
+            .unwrap();
+        // No target for High, but this chunk brings its own deadline
+        queue
+            .enqueue_with_deadline(create_test_chunk(Priority::High, 2), Instant::now())
+            .unwrap();
+
+        let on_time = queue.dequeue_file_queued("test-file").unwrap();
+        assert_eq!(on_time.target(), Some(Duration::from_millis(20)));
+        assert!(queue.record_delivery(&on_time).is_none());
+
+        std::thread::sleep(Duration::from_millis(30));
+        let late = queue.dequeue_file_queued("test-file").unwrap();
+        let violation = queue.record_delivery(&late).unwrap();
+        assert_eq!(violation.sequence_number, 1);
+        assert_eq!(violation.target_ms, 20);
+        assert!(violation.latency_ms >= 30);
+
+        // Misses outside the Critical class are counted, not reported
+        let high = queue.dequeue_file_queued("test-file").unwrap();
+        assert!(queue.record_delivery(&high).is_none());
+
+        let stats = queue.stats();
+        assert_eq!(stats.latency(Priority::Critical).delivered, 2);
+        assert_eq!(stats.latency(Priority::Critical).deadline_misses, 1);
+        assert!(stats.latency(Priority::Critical).p99_ms >= 30);
+        assert_eq!(stats.latency(Priority::High).deadline_misses, 1);
+        assert_eq!(stats.latency(Priority::Normal).delivered, 0);
+    }
+
     #[test]
     fn test_bandwidth_allocation() {
         let queue = PriorityQueue::new(1000);
@@ -405,9 +772,9 @@ mod tests {
         let allocation = queue.allocate_bandwidth(1_000_000);
 
         // Should allocate: 50% critical, 30% high, 20% normal
-        assert_eq!(allocation.critical_bps, 500_000);
-        assert_eq!(allocation.high_bps, 300_000);
-        assert_eq!(allocation.normal_bps, 200_000);
+        assert_eq!(allocation.for_priority(Priority::Critical), 500_000);
+        assert_eq!(allocation.for_priority(Priority::High), 300_000);
+        assert_eq!(allocation.for_priority(Priority::Normal), 200_000);
         assert_eq!(allocation.total_bps, 1_000_000);
     }
 
@@ -422,14 +789,60 @@ mod tests {
 
         let allocation = queue.allocate_bandwidth(1_000_000);
 
-        // Critical and high bandwidth should be redistributed to others
-        // When critical and high are empty, their bandwidth gets split
-        // Critical's 500k gets split: 250k to high, 250k to normal
-        // High's (300k + 250k) gets split: 275k to critical, 275k to normal
-        // So normal gets: 200k (base) + 250k (from critical) + 275k (from high) = 725k
-        // But order matters in redistribution, so we just check it's significantly more
-        assert!(allocation.normal_bps > 200_000);
-        assert!(allocation.critical_bps < 500_000 || allocation.critical_bps == 0);
+        // Critical and high bandwidth should be redistributed to normal
+        assert_eq!(allocation.for_priority(Priority::Normal), 1_000_000);
+        assert_eq!(allocation.for_priority(Priority::Critical), 0);
+        assert_eq!(allocation.for_priority(Priority::High), 0);
+    }
+
+    #[test]
+    fn test_custom_levels() {
+        let queue = PriorityQueue::new(1000).with_levels(10);
+        assert_eq!(queue.levels(), 10);
+
+        queue
+            .enqueue(create_test_chunk(Priority::Level(7), 0))
+            .unwrap();
+        queue
+            .enqueue(create_test_chunk(Priority::Normal, 1))
+            .unwrap();
+        queue
+            .enqueue(create_test_chunk(Priority::Level(3), 2))
+            .unwrap();
+        // Past the last level, so queued at it
+        queue
+            .enqueue(create_test_chunk(Priority::Level(200), 3))
+            .unwrap();
+
+        let stats = queue.stats();
+        assert_eq!(stats.levels.len(), 10);
+        assert_eq!(stats.pending(Priority::Level(9)), 1);
+        assert_eq!(stats.total_pending(), 4);
+
+        // Idle levels give their share to busy ones
+        let allocation = queue.allocate_bandwidth(1_000_000);
+        assert_eq!(allocation.levels.len(), 10);
+        assert_eq!(allocation.for_priority(Priority::Level(3)), 250_000);
+        assert_eq!(allocation.for_priority(Priority::Level(5)), 0);
+        assert_eq!(allocation.levels.iter().sum::<u64>(), 1_000_000);
+
+        // Named levels first, then the rest in level order
+        let order: Vec<_> = std::iter::from_fn(|| queue.dequeue().ok())
+            .map(|c| c.metadata.priority)
+            .collect();
+        assert_eq!(
+            order,
+            [
+                Priority::Normal,
+                Priority::Level(3),
+                Priority::Level(7),
+                Priority::Level(200)
+            ]
+        );
+        assert_eq!(queue.stats().wait(Priority::Level(3)).histogram.len(), 11);
+
+        // Fewer than the named three isn't possible
+        assert_eq!(PriorityQueue::new(10).with_levels(1).levels(), 3);
     }
 
     #[test]
@@ -472,6 +885,70 @@ mod tests {
         assert!(queue.is_empty());
     }
 
+    #[test]
+    fn test_dequeue_and_remove_by_file() {
+        let queue = PriorityQueue::new(1000);
+        let of_file = |file_id: &str, priority, seq| {
+            let mut chunk = create_test_chunk(priority, seq);
+            chunk.metadata.file_id = file_id.to_string();
+            chunk
+        };
+
+        queue.enqueue(of_file("a", Priority::Normal, 0)).unwrap();
+        queue.enqueue(of_file("b", Priority::Normal, 1)).unwrap();
+        queue.enqueue(of_file("b", Priority::Normal, 3)).unwrap();
+        queue.enqueue(of_file("a", Priority::High, 2)).unwrap();
+        queue.enqueue(of_file("b", Priority::High, 5)).unwrap();
+
+        // Priority first, then sequence, skipping the other file
+        let b = queue.dequeue_file("b").unwrap();
+        assert_eq!(b.metadata.sequence_number, 5);
+        let b = queue.dequeue_file("b").unwrap();
+        assert_eq!(b.metadata.sequence_number, 1);
+        assert_eq!(queue.total_pending(), 3);
+
+        assert_eq!(queue.remove_file("a"), 2);
+        assert_eq!(queue.stats().total_pending(), 1);
+        assert!(queue.dequeue_file("a").is_err());
+        assert_eq!(queue.dequeue().unwrap().metadata.sequence_number, 3);
+    }
+
+    #[test]
+    fn test_inspect_groups_by_file_without_dequeuing() {
+        let queue = PriorityQueue::new(1000);
+        let of_file = |file_id: &str, priority, seq| {
+            let mut chunk = create_test_chunk(priority, seq);
+            chunk.metadata.file_id = file_id.to_string();
+            chunk
+        };
+
+        queue.enqueue(of_file("a", Priority::Normal, 0)).unwrap();
+        std::thread::sleep(Duration::from_millis(5));
+        queue.enqueue(of_file("b", Priority::Normal, 1)).unwrap();
+        queue.enqueue(of_file("b", Priority::High, 4)).unwrap();
+        queue.enqueue(of_file("b", Priority::High, 2)).unwrap();
+
+        let snapshot = queue.inspect(3);
+        assert_eq!(queue.total_pending(), 4);
+
+        // Longest-waiting file first
+        let files: Vec<_> = snapshot.files.iter().map(|f| f.file_id.as_str()).collect();
+        assert_eq!(files, ["a", "b"]);
+        assert_eq!(snapshot.files[0].pending, [0, 0, 1]);
+        assert_eq!(snapshot.files[1].pending, [0, 2, 1]);
+        assert_eq!(snapshot.files[1].total_pending(), 3);
+        assert!(snapshot.files[0].oldest_wait_ms >= 5);
+
+        // Same order as dequeue
+        let next: Vec<_> = snapshot
+            .next
+            .iter()
+            .map(|c| (c.file_id.as_str(), c.sequence_number))
+            .collect();
+        assert_eq!(next, [("b", 2), ("b", 4), ("a", 0)]);
+        assert_eq!(queue.dequeue().unwrap().metadata.sequence_number, 2);
+    }
+
     #[test]
     fn test_peek() {
         let queue = PriorityQueue::new(1000);
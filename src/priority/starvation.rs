@@ -0,0 +1,267 @@
+//! Starvation alerts
+//!
+//! Strict priority ordering means a steady stream of critical chunks can
+//! hold normal ones back indefinitely. [`StarvationMonitor`] watches the
+//! oldest wait of each level and raises a [`StarvationAlert`] to the
+//! configured sinks when it passes a threshold, once per episode: a level
+//! alerts again only after it has drained back under the threshold.
+
+use crate::chunk::Priority;
+use crate::metrics::recorder;
+use crate::priority::queue::PriorityQueue;
+use crate::priority::types::QueueStats;
+use serde::{Deserialize, Serialize};
+use std::sync::Weak;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+const PRIORITIES: [Priority; 3] = [Priority::Critical, Priority::High, Priority::Normal];
+
+/// How long a webhook may take to accept an alert
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Where starvation alerts go
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertSink {
+    /// A `tracing` warning
+    Log,
+    /// `resilient_queue_starvation_alerts_total` and the oldest-wait gauge
+    Metric,
+    /// JSON POST to a plain `http://` URL
+    Webhook(String),
+}
+
+/// When and where to raise starvation alerts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StarvationPolicy {
+    /// Oldest wait in a class above which it counts as starving
+    pub threshold: Duration,
+    pub check_interval: Duration,
+    pub sinks: Vec<AlertSink>,
+}
+
+impl Default for StarvationPolicy {
+    fn default() -> Self {
+        Self {
+            threshold: Duration::from_secs(30),
+            check_interval: Duration::from_secs(5),
+            sinks: vec![AlertSink::Log, AlertSink::Metric],
+        }
+    }
+}
+
+/// A priority level whose oldest chunk has waited past the threshold
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StarvationAlert {
+    pub priority: Priority,
+    pub oldest_wait_ms: u64,
+    pub threshold_ms: u64,
+    pub pending: usize,
+}
+
+/// Tracks which levels are starving and dispatches alerts
+#[derive(Debug)]
+pub struct StarvationMonitor {
+    policy: StarvationPolicy,
+    starving: Vec<bool>,
+}
+
+impl StarvationMonitor {
+    pub fn new(policy: StarvationPolicy) -> Self {
+        Self {
+            policy,
+            starving: Vec::new(),
+        }
+    }
+
+    /// Alerts newly raised by `stats`
+    pub fn check(&mut self, stats: &QueueStats) -> Vec<StarvationAlert> {
+        let threshold_ms = self.policy.threshold.as_millis() as u64;
+        let mut alerts = Vec::new();
+        self.starving.resize(stats.levels.len(), false);
+
+        for (idx, level) in stats.levels.iter().enumerate() {
+            let oldest_wait_ms = level.wait.oldest_wait_ms;
+            let starving = oldest_wait_ms > threshold_ms;
+            if starving && !self.starving[idx] {
+                alerts.push(StarvationAlert {
+                    priority: Priority::from_level(idx as u8),
+                    oldest_wait_ms,
+                    threshold_ms,
+                    pending: level.pending,
+                });
+            }
+            self.starving[idx] = starving;
+        }
+
+        alerts
+    }
+
+    /// Send one alert to every sink
+    pub async fn dispatch(&self, alert: &StarvationAlert) {
+        for sink in &self.policy.sinks {
+            match sink {
+                AlertSink::Log => tracing::warn!(
+                    "{:?} queue starving: oldest chunk waited {}ms (threshold {}ms, {} pending)",
+                    alert.priority,
+                    alert.oldest_wait_ms,
+                    alert.threshold_ms,
+                    alert.pending
+                ),
+                AlertSink::Metric => {
+                    recorder::record_starvation_alert(priority_label(alert.priority))
+                }
+                AlertSink::Webhook(url) => {
+                    if let Err(e) = post_webhook(url, alert).await {
+                        tracing::warn!("Starvation webhook {} failed: {}", url, e);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Check `queue` every interval until it is dropped
+    pub async fn run(mut self, queue: Weak<PriorityQueue>) {
+        loop {
+            tokio::time::sleep(self.policy.check_interval).await;
+            let Some(queue) = queue.upgrade() else {
+                return;
+            };
+            let stats = queue.stats();
+            drop(queue);
+
+            if self.policy.sinks.contains(&AlertSink::Metric) {
+                // The gauge is per class; a class's levels report their oldest
+                let mut oldest_ms = [0u64; PRIORITIES.len()];
+                for (idx, level) in stats.levels.iter().enumerate() {
+                    let class = idx.min(PRIORITIES.len() - 1);
+                    oldest_ms[class] = oldest_ms[class].max(level.wait.oldest_wait_ms);
+                }
+                for (priority, ms) in PRIORITIES.into_iter().zip(oldest_ms) {
+                    recorder::set_queue_oldest_wait(
+                        priority_label(priority),
+                        Duration::from_millis(ms),
+                    );
+                }
+            }
+            for alert in self.check(&stats) {
+                self.dispatch(&alert).await;
+            }
+        }
+    }
+}
+
+/// Metric label of the class `priority` falls in
+pub(crate) fn priority_label(priority: Priority) -> &'static str {
+    match priority.class() {
+        Priority::Critical => "critical",
+        Priority::High => "high",
+        Priority::Normal | Priority::Level(_) => "normal",
+    }
+}
+
+/// POST `alert` as JSON with a bare HTTP/1.1 request
+async fn post_webhook(url: &str, alert: &StarvationAlert) -> std::io::Result<()> {
+    let invalid = |reason: &str| std::io::Error::new(std::io::ErrorKind::InvalidInput, reason);
+    let rest = url
+        .strip_prefix("http://")
+        .ok_or_else(|| invalid("only http:// webhooks are supported"))?;
+    let (authority, path) = match rest.find('/') {
+        Some(i) => (&rest[..i], &rest[i..]),
+        None => (rest, "/"),
+    };
+    let addr = if authority.contains(':') {
+        authority.to_string()
+    } else {
+        format!("{authority}:80")
+    };
+
+    let body = serde_json::to_vec(alert)?;
+    let request = async {
+        let mut stream = TcpStream::connect(addr).await?;
+        let head = format!(
+            "POST {path} HTTP/1.1\r\nHost: {authority}\r\nContent-Type: application/json\r\n\
+             Content-Length: {}\r\nConnection: close\r\n\r\n",
+            body.len()
+        );
+        stream.write_all(head.as_bytes()).await?;
+        stream.write_all(&body).await?;
+
+        let mut status = [0u8; 12];
+        stream.read_exact(&mut status).await?;
+        match &status[9..10] {
+            b"2" => Ok(()),
+            _ => Err(std::io::Error::other(format!(
+                "webhook answered {}",
+                String::from_utf8_lossy(&status[9..12])
+            ))),
+        }
+    };
+
+    tokio::time::timeout(WEBHOOK_TIMEOUT, request)
+        .await
+        .map_err(|_| std::io::Error::new(std::io::ErrorKind::TimedOut, "webhook timed out"))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    fn stats_with_oldest(normal_oldest_ms: u64) -> QueueStats {
+        let mut stats = QueueStats::default();
+        stats.levels[2].pending = 4;
+        stats.levels[2].wait.oldest_wait_ms = normal_oldest_ms;
+        stats
+    }
+
+    #[test]
+    fn test_alerts_once_per_episode() {
+        let mut monitor = StarvationMonitor::new(StarvationPolicy {
+            threshold: Duration::from_secs(1),
+            ..Default::default()
+        });
+
+        assert!(monitor.check(&stats_with_oldest(500)).is_empty());
+        let alerts = monitor.check(&stats_with_oldest(1_500));
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].priority, Priority::Normal);
+        assert_eq!(alerts[0].pending, 4);
+
+        // Still starving: no repeat until the class recovers
+        assert!(monitor.check(&stats_with_oldest(2_500)).is_empty());
+        assert!(monitor.check(&stats_with_oldest(0)).is_empty());
+        assert_eq!(monitor.check(&stats_with_oldest(1_200)).len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_webhook_receives_alert() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/alerts", listener.local_addr().unwrap());
+
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = vec![0u8; 4096];
+            let n = socket.read(&mut request).await.unwrap();
+            socket
+                .write_all(b"HTTP/1.1 204 No Content\r\n\r\n")
+                .await
+                .unwrap();
+            String::from_utf8_lossy(&request[..n]).to_string()
+        });
+
+        let alert = StarvationAlert {
+            priority: Priority::High,
+            oldest_wait_ms: 45_000,
+            threshold_ms: 30_000,
+            pending: 12,
+        };
+        post_webhook(&url, &alert).await.unwrap();
+
+        let request = server.await.unwrap();
+        assert!(request.starts_with("POST /alerts HTTP/1.1"));
+        assert!(request.contains(r#""oldest_wait_ms":45000"#));
+    }
+}
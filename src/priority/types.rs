@@ -1,6 +1,6 @@
-use crate::chunk::Chunk;
+use crate::chunk::{Chunk, Priority};
 use serde::{Deserialize, Serialize};
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 #[derive(Debug, Clone)]
 pub struct QueuedChunk {
@@ -8,6 +8,8 @@ pub struct QueuedChunk {
     pub enqueued_at: Instant,
     pub retry_count: u32,
     pub priority_idx: usize,
+    /// When the chunk should have reached the receiver by
+    pub deadline: Option<Instant>,
 }
 
 impl QueuedChunk {
@@ -17,12 +19,24 @@ impl QueuedChunk {
             enqueued_at: Instant::now(),
             retry_count: 0,
             priority_idx,
+            deadline: None,
         }
     }
 
-    pub fn wait_time(&self) -> std::time::Duration {
+    pub fn with_deadline(mut self, deadline: Option<Instant>) -> Self {
+        self.deadline = deadline;
+        self
+    }
+
+    pub fn wait_time(&self) -> Duration {
         self.enqueued_at.elapsed()
     }
+
+    /// Time the chunk was given from enqueue to its deadline
+    pub fn target(&self) -> Option<Duration> {
+        self.deadline
+            .map(|deadline| deadline.saturating_duration_since(self.enqueued_at))
+    }
 }
 
 impl PartialEq for QueuedChunk {
@@ -52,20 +66,290 @@ impl Ord for QueuedChunk {
     }
 }
 
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+/// Upper bounds (ms) of the wait histogram buckets; a final bucket holds
+/// anything longer
+pub const WAIT_BUCKETS_MS: [u64; 10] = [
+    10, 50, 100, 500, 1_000, 5_000, 10_000, 30_000, 60_000, 300_000,
+];
+
+/// How long chunks of one priority class wait in the queue
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WaitStats {
+    /// Age of the oldest chunk still queued
+    pub oldest_wait_ms: u64,
+    /// 95th percentile wait of dequeued chunks (bucket upper bound)
+    pub p95_wait_ms: u64,
+    pub max_wait_ms: u64,
+    /// Dequeued chunks per [`WAIT_BUCKETS_MS`] bucket, plus the overflow bucket
+    pub histogram: Vec<u64>,
+}
+
+impl WaitStats {
+    /// Record the wait of a dequeued chunk
+    pub fn record(&mut self, wait_ms: u64) {
+        if self.histogram.is_empty() {
+            self.histogram = vec![0; WAIT_BUCKETS_MS.len() + 1];
+        }
+        self.histogram[bucket_of(&WAIT_BUCKETS_MS, wait_ms)] += 1;
+        self.max_wait_ms = self.max_wait_ms.max(wait_ms);
+        self.p95_wait_ms = self.percentile(0.95);
+    }
+
+    /// Wait below which `fraction` of dequeued chunks fall, to bucket precision
+    pub fn percentile(&self, fraction: f64) -> u64 {
+        bucket_percentile(
+            &WAIT_BUCKETS_MS,
+            &self.histogram,
+            self.max_wait_ms,
+            fraction,
+        )
+    }
+}
+
+/// How long chunks of one priority class take from enqueue to delivery
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LatencyStats {
+    pub delivered: u64,
+    /// Percentiles to [`WAIT_BUCKETS_MS`] bucket precision
+    pub p50_ms: u64,
+    pub p95_ms: u64,
+    pub p99_ms: u64,
+    pub max_ms: u64,
+    /// Chunks delivered after their deadline
+    pub deadline_misses: u64,
+    /// Delivered chunks per [`WAIT_BUCKETS_MS`] bucket, plus the overflow bucket
+    pub histogram: Vec<u64>,
+}
+
+impl LatencyStats {
+    /// Record the latency of a delivered chunk
+    pub fn record(&mut self, latency_ms: u64, missed_deadline: bool) {
+        if self.histogram.is_empty() {
+            self.histogram = vec![0; WAIT_BUCKETS_MS.len() + 1];
+        }
+        self.histogram[bucket_of(&WAIT_BUCKETS_MS, latency_ms)] += 1;
+        self.delivered += 1;
+        self.max_ms = self.max_ms.max(latency_ms);
+        if missed_deadline {
+            self.deadline_misses += 1;
+        }
+        self.p50_ms = self.percentile(0.50);
+        self.p95_ms = self.percentile(0.95);
+        self.p99_ms = self.percentile(0.99);
+    }
+
+    /// Latency below which `fraction` of delivered chunks fall
+    pub fn percentile(&self, fraction: f64) -> u64 {
+        bucket_percentile(&WAIT_BUCKETS_MS, &self.histogram, self.max_ms, fraction)
+    }
+}
+
+/// Bucket of `ms` in a histogram with upper `bounds` and an overflow bucket
+pub(crate) fn bucket_of(bounds: &[u64], ms: u64) -> usize {
+    bounds
+        .iter()
+        .position(|&bound| ms <= bound)
+        .unwrap_or(bounds.len())
+}
+
+/// Value below which `fraction` of a histogram with upper `bounds` falls,
+/// capped at the largest value seen
+pub(crate) fn bucket_percentile(bounds: &[u64], histogram: &[u64], max: u64, fraction: f64) -> u64 {
+    let total: u64 = histogram.iter().sum();
+    if total == 0 {
+        return 0;
+    }
+    let rank = ((total as f64 * fraction).ceil() as u64).max(1);
+    let mut seen = 0;
+    for (bucket, count) in histogram.iter().enumerate() {
+        seen += count;
+        if seen >= rank {
+            return bounds.get(bucket).map_or(max, |&bound| bound.min(max));
+        }
+    }
+    max
+}
+
+/// A Critical chunk delivered after its deadline
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SloViolation {
+    pub file_id: String,
+    pub sequence_number: u32,
+    pub priority: Priority,
+    /// Enqueue to delivery
+    pub latency_ms: u64,
+    /// Enqueue to deadline
+    pub target_ms: u64,
+}
+
+/// What one file (and so one transfer) has waiting in the queue
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QueuedFile {
+    pub file_id: String,
+    /// Session sending the file, filled in by the coordinator
+    #[serde(default)]
+    pub session_id: Option<String>,
+    /// Chunks waiting per level, most urgent first
+    pub pending: Vec<usize>,
+    pub queued_bytes: u64,
+    /// When the longest-waiting chunk was enqueued (unix seconds)
+    pub oldest_enqueued_at: i64,
+    pub oldest_wait_ms: u64,
+}
+
+impl QueuedFile {
+    pub fn total_pending(&self) -> usize {
+        self.pending.iter().sum()
+    }
+}
+
+/// A chunk near the front of the queue
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScheduledChunk {
+    pub file_id: String,
+    /// Session sending the chunk, filled in by the coordinator
+    #[serde(default)]
+    pub session_id: Option<String>,
+    pub sequence_number: u32,
+    pub priority: Priority,
+    pub is_parity: bool,
+    pub bytes: usize,
+    pub wait_ms: u64,
+}
+
+/// Contents of the queue at one moment, taken without dequeuing anything
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QueueSnapshot {
+    /// Files with chunks queued, longest-waiting first
+    pub files: Vec<QueuedFile>,
+    /// Chunks in the order [`dequeue`](crate::priority::PriorityQueue::dequeue)
+    /// would return them
+    pub next: Vec<ScheduledChunk>,
+}
+
+/// Queue levels used unless configured otherwise: one per named priority
+pub const DEFAULT_PRIORITY_LEVELS: usize = 3;
+
+/// Most levels a queue can have; a chunk's level is a `u8`
+pub const MAX_PRIORITY_LEVELS: usize = 256;
+
+/// Chunks of one priority level: how many wait, for how long, and how long
+/// they take to arrive
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LevelStats {
+    pub pending: usize,
+    pub wait: WaitStats,
+    /// Enqueue to delivery, for chunks the sender reported delivered
+    pub latency: LatencyStats,
+}
+
+static EMPTY_LEVEL: LevelStats = LevelStats {
+    pending: 0,
+    wait: WaitStats {
+        oldest_wait_ms: 0,
+        p95_wait_ms: 0,
+        max_wait_ms: 0,
+        histogram: Vec::new(),
+    },
+    latency: LatencyStats {
+        delivered: 0,
+        p50_ms: 0,
+        p95_ms: 0,
+        p99_ms: 0,
+        max_ms: 0,
+        deadline_misses: 0,
+        histogram: Vec::new(),
+    },
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct QueueStats {
-    pub critical_pending: usize,
-    pub high_pending: usize,
-    pub normal_pending: usize,
     pub total_processed: u64,
     pub total_enqueued: u64,
     pub avg_wait_time_ms: u64,
     pub max_wait_time_ms: u64,
+    /// Chunk data currently queued
+    #[serde(default)]
+    pub queued_bytes: u64,
+    /// Normal-priority enqueues turned away under memory pressure
+    #[serde(default)]
+    pub shed_enqueues: u64,
+    /// One entry per queue level, most urgent first
+    #[serde(default)]
+    pub levels: Vec<LevelStats>,
+}
+
+impl Default for QueueStats {
+    fn default() -> Self {
+        Self::with_levels(DEFAULT_PRIORITY_LEVELS)
+    }
 }
 
 impl QueueStats {
+    /// Empty statistics for a queue with `levels` levels
+    pub fn with_levels(levels: usize) -> Self {
+        Self {
+            total_processed: 0,
+            total_enqueued: 0,
+            avg_wait_time_ms: 0,
+            max_wait_time_ms: 0,
+            queued_bytes: 0,
+            shed_enqueues: 0,
+            levels: vec![LevelStats::default(); levels],
+        }
+    }
+
     pub fn total_pending(&self) -> usize {
-        self.critical_pending + self.high_pending + self.normal_pending
+        self.levels.iter().map(|level| level.pending).sum()
+    }
+
+    /// Statistics of the level `priority` is queued at
+    ///
+    /// Levels past the last one share it, as they do in the queue.
+    pub fn level(&self, priority: Priority) -> &LevelStats {
+        let idx = (priority.level() as usize).min(self.levels.len().saturating_sub(1));
+        self.levels.get(idx).unwrap_or(&EMPTY_LEVEL)
+    }
+
+    /// Chunks waiting at `priority`
+    pub fn pending(&self, priority: Priority) -> usize {
+        self.level(priority).pending
+    }
+
+    /// Wait statistics for `priority`
+    pub fn wait(&self, priority: Priority) -> &WaitStats {
+        &self.level(priority).wait
+    }
+
+    /// Delivery latency statistics for `priority`
+    pub fn latency(&self, priority: Priority) -> &LatencyStats {
+        &self.level(priority).latency
+    }
+
+    pub(crate) fn record_enqueue(&mut self, level: usize) {
+        self.total_enqueued += 1;
+        self.levels[level].pending += 1;
+    }
+
+    /// Account for a chunk at `level` reaching the receiver `latency_ms`
+    /// after it was enqueued
+    pub(crate) fn record_delivery(&mut self, level: usize, latency_ms: u64, missed: bool) {
+        self.levels[level].latency.record(latency_ms, missed);
+    }
+
+    /// Account for a chunk at `level` leaving the queue after `wait_ms`
+    pub(crate) fn record_dequeue(&mut self, level: usize, wait_ms: u64) {
+        self.total_processed += 1;
+        if self.avg_wait_time_ms == 0 {
+            self.avg_wait_time_ms = wait_ms;
+        } else {
+            self.avg_wait_time_ms = (self.avg_wait_time_ms + wait_ms) / 2;
+        }
+        self.max_wait_time_ms = self.max_wait_time_ms.max(wait_ms);
+
+        let level = &mut self.levels[level];
+        level.wait.record(wait_ms);
+        level.pending = level.pending.saturating_sub(1);
     }
 
     pub fn processing_rate(&self) -> f64 {
@@ -77,59 +361,100 @@ impl QueueStats {
     }
 }
 
+/// Bandwidth per queue level
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BandwidthAllocation {
-    pub critical_bps: u64,
-    pub high_bps: u64,
-    pub normal_bps: u64,
+    /// One entry per level, most urgent first
+    pub levels: Vec<u64>,
     pub total_bps: u64,
 }
 
 impl BandwidthAllocation {
-    pub fn new(
-        total_bps: u64,
-        critical_pending: usize,
-        high_pending: usize,
-        normal_pending: usize,
-    ) -> Self {
-        // Default ratios: Critical 50%, High 30%, Normal 20%
-        let mut critical_bps = total_bps / 2;
-        let mut high_bps = total_bps * 3 / 10;
-        let mut normal_bps = total_bps / 5;
+    /// Split `total_bps` between levels with `pending` chunks each
+    ///
+    /// Critical gets 50%, High 30% and the Normal class 20%, shared equally
+    /// by the levels from 2 down. Idle levels' shares go to the busy ones in
+    /// equal parts.
+    pub fn new(total_bps: u64, pending: &[usize]) -> Self {
+        let count = pending.len();
+        let mut levels: Vec<u64> = (0..count)
+            .map(|level| match level {
+                0 => total_bps / 2,
+                1 => total_bps * 3 / 10,
+                _ => total_bps / 5 / (count as u64 - 2),
+            })
+            .collect();
 
         // Redistribute unused bandwidth
-        if critical_pending == 0 {
-            high_bps += critical_bps / 2;
-            normal_bps += critical_bps / 2;
-            critical_bps = 0;
+        let busy = pending.iter().filter(|&&waiting| waiting > 0).count() as u64;
+        let unused: u64 = levels
+            .iter()
+            .zip(pending)
+            .filter(|(_, &waiting)| waiting == 0)
+            .map(|(bps, _)| bps)
+            .sum();
+        if let Some(extra) = unused.checked_div(busy) {
+            for (bps, &waiting) in levels.iter_mut().zip(pending) {
+                if waiting == 0 {
+                    *bps = 0;
+                } else {
+                    *bps += extra;
+                }
+            }
         }
 
-        if high_pending == 0 {
-            critical_bps += high_bps / 2;
-            normal_bps += high_bps / 2;
-            high_bps = 0;
-        }
+        Self { levels, total_bps }
+    }
 
-        if normal_pending == 0 {
-            critical_bps += normal_bps / 2;
-            high_bps += normal_bps / 2;
-            normal_bps = 0;
-        }
+    pub fn get_allocation(&self, priority_idx: usize) -> u64 {
+        self.levels.get(priority_idx).copied().unwrap_or(0)
+    }
 
-        Self {
-            critical_bps,
-            high_bps,
-            normal_bps,
-            total_bps,
+    /// Bandwidth for the level `priority` is queued at
+    pub fn for_priority(&self, priority: Priority) -> u64 {
+        let last = self.levels.len().saturating_sub(1);
+        self.get_allocation((priority.level() as usize).min(last))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wait_percentile() {
+        let mut wait = WaitStats::default();
+        for _ in 0..95 {
+            wait.record(5);
+        }
+        for _ in 0..5 {
+            wait.record(2_000);
         }
+        assert_eq!(wait.percentile(0.95), 10);
+        // Capped at the largest wait actually seen
+        assert_eq!(wait.percentile(0.99), 2_000);
+        assert_eq!(wait.p95_wait_ms, 10);
+
+        wait.record(400_000);
+        assert_eq!(wait.histogram[WAIT_BUCKETS_MS.len()], 1);
+        assert_eq!(wait.percentile(1.0), 400_000);
     }
 
-    pub fn get_allocation(&self, priority_idx: usize) -> u64 {
-        match priority_idx {
-            0 => self.critical_bps,
-            1 => self.high_bps,
-            2 => self.normal_bps,
-            _ => 0,
+    #[test]
+    fn test_latency_percentiles() {
+        let mut latency = LatencyStats::default();
+        for _ in 0..90 {
+            latency.record(8, false);
+        }
+        for _ in 0..9 {
+            latency.record(80, false);
         }
+        latency.record(700, true);
+        assert_eq!(latency.delivered, 100);
+        assert_eq!(latency.deadline_misses, 1);
+        assert_eq!(latency.p50_ms, 10);
+        assert_eq!(latency.p95_ms, 100);
+        assert_eq!(latency.p99_ms, 100);
+        assert_eq!(latency.percentile(1.0), 700);
     }
 }
@@ -0,0 +1,142 @@
+//! Hop-level forward error correction
+//!
+//! Loss on each hop of a sender→relay→receiver path compounds. Rather than
+//! passing shards through untouched, a relay can decode a group once enough
+//! shards have arrived, recover the data shards lost on the inbound hop, and
+//! re-encode with parity sized for the outbound hop.
+
+use crate::chunk::ErasureCoder;
+use crate::relay::types::{FecShardInfo, RelayError, RelayResult};
+use bytes::Bytes;
+
+/// Result of re-encoding one FEC group
+#[derive(Debug, Clone)]
+pub struct ReencodedGroup {
+    /// Shards for the next hop, data first then parity
+    pub shards: Vec<(FecShardInfo, Vec<u8>)>,
+
+    /// Data shards that were missing on arrival and reconstructed here
+    pub repaired: usize,
+}
+
+/// Whether enough shards of a group are present to decode it
+pub fn can_decode(received: &[(FecShardInfo, Vec<u8>)]) -> bool {
+    match received.first() {
+        Some((info, _)) => received.len() >= info.data_shards,
+        None => false,
+    }
+}
+
+/// Decode a group from whatever shards arrived and re-encode it with
+/// `new_parity` parity shards
+pub fn reencode_group(
+    received: &[(FecShardInfo, Vec<u8>)],
+    new_parity: usize,
+) -> RelayResult<ReencodedGroup> {
+    let (template, _) = received
+        .first()
+        .ok_or_else(|| RelayError::Storage("empty FEC group".into()))?;
+    let data_shards = template.data_shards;
+    let total = data_shards + template.parity_shards;
+
+    let mut slots: Vec<Option<Bytes>> = vec![None; total];
+    for (info, data) in received {
+        if info.group_id != template.group_id
+            || info.data_shards != data_shards
+            || info.parity_shards != template.parity_shards
+            || info.shard_index >= total
+        {
+            return Err(RelayError::Storage(format!(
+                "shard {} does not belong to group {}",
+                info.chunk_id(),
+                template.group_id
+            )));
+        }
+        slots[info.shard_index] = Some(Bytes::from(data.clone()));
+    }
+
+    let repaired = slots[..data_shards].iter().filter(|s| s.is_none()).count();
+
+    let decoder = ErasureCoder::new(data_shards, template.parity_shards)
+        .map_err(|e| RelayError::Storage(e.to_string()))?;
+    let data = decoder
+        .decode(slots)
+        .map_err(|e| RelayError::Storage(e.to_string()))?;
+
+    let encoder = ErasureCoder::new(data_shards, new_parity.max(1))
+        .map_err(|e| RelayError::Storage(e.to_string()))?;
+    let encoded = encoder
+        .encode(data)
+        .map_err(|e| RelayError::Storage(e.to_string()))?;
+
+    let shards = encoded
+        .into_iter()
+        .enumerate()
+        .map(|(shard_index, bytes)| {
+            (
+                FecShardInfo {
+                    group_id: template.group_id.clone(),
+                    shard_index,
+                    data_shards,
+                    parity_shards: encoder.parity_shards(),
+                },
+                bytes.to_vec(),
+            )
+        })
+        .collect();
+
+    Ok(ReencodedGroup { shards, repaired })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_group(data_shards: usize, parity_shards: usize) -> Vec<(FecShardInfo, Vec<u8>)> {
+        let data: Vec<Bytes> = (0..data_shards)
+            .map(|i| Bytes::from(vec![i as u8 + 1; 64]))
+            .collect();
+        let coder = ErasureCoder::new(data_shards, parity_shards).unwrap();
+        coder
+            .encode(data)
+            .unwrap()
+            .into_iter()
+            .enumerate()
+            .map(|(shard_index, b)| {
+                (
+                    FecShardInfo {
+                        group_id: "g".into(),
+                        shard_index,
+                        data_shards,
+                        parity_shards,
+                    },
+                    b.to_vec(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_reencode_repairs_lost_data_shards() {
+        let mut group = make_group(4, 2);
+        // Lose two data shards on the inbound hop
+        group.retain(|(info, _)| info.shard_index != 0 && info.shard_index != 2);
+        assert!(can_decode(&group));
+
+        let result = reencode_group(&group, 3).unwrap();
+        assert_eq!(result.repaired, 2);
+        assert_eq!(result.shards.len(), 7);
+        assert_eq!(result.shards[0].1, vec![1u8; 64]);
+        assert_eq!(result.shards[2].1, vec![3u8; 64]);
+        assert!(result.shards[6].0.is_parity());
+        assert_eq!(result.shards[6].0.parity_shards, 3);
+    }
+
+    #[test]
+    fn test_insufficient_shards() {
+        let mut group = make_group(4, 2);
+        group.truncate(3);
+        assert!(!can_decode(&group));
+        assert!(reencode_group(&group, 2).is_err());
+    }
+}
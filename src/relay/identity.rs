@@ -0,0 +1,377 @@
+//! Relay node identity and peer authorization
+//!
+//! Each relay holds an Ed25519 keypair. It proves its identity by signing
+//! its `Hello`, and signs every `Store` it sends, so a relay can tell which
+//! node handed it a chunk. A node's key is pinned the first time a signed
+//! `Hello` from it is accepted; later messages claiming the same node id
+//! must carry the same key. [`AccessPolicy`] decides which identities may
+//! store chunks at all.
+
+use crate::chunk::Chunk;
+use crate::relay::types::{RelayError, RelayResult, RouteInfo};
+use ring::rand::SystemRandom;
+use ring::signature::{Ed25519KeyPair, KeyPair, UnparsedPublicKey, ED25519};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+/// How far a `Hello` timestamp may be from this node's clock
+pub const MAX_HELLO_SKEW: Duration = Duration::from_secs(300);
+
+/// Domain separators, so a signature over one message can't pass as another
+const HELLO_CONTEXT: &[u8] = b"resilient-relay-hello-v1\0";
+const STORE_CONTEXT: &[u8] = b"resilient-relay-store-v2\0";
+
+/// A node's Ed25519 public key, hex encoded in config and JSON
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodePublicKey([u8; 32]);
+
+impl NodePublicKey {
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    fn verify(&self, message: &[u8], signature: &[u8]) -> bool {
+        UnparsedPublicKey::new(&ED25519, &self.0)
+            .verify(message, signature)
+            .is_ok()
+    }
+}
+
+impl fmt::Display for NodePublicKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&hex::encode(self.0))
+    }
+}
+
+impl fmt::Debug for NodePublicKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "NodePublicKey({self})")
+    }
+}
+
+impl FromStr for NodePublicKey {
+    type Err = RelayError;
+
+    fn from_str(s: &str) -> RelayResult<Self> {
+        let bytes = hex::decode(s)
+            .ok()
+            .and_then(|b| <[u8; 32]>::try_from(b).ok())
+            .ok_or_else(|| {
+                RelayError::InvalidConfig(format!("{s:?} is not a 32-byte hex public key"))
+            })?;
+        Ok(Self(bytes))
+    }
+}
+
+impl Serialize for NodePublicKey {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for NodePublicKey {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Signature and key proving a `Hello` came from the node it names
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HelloProof {
+    pub public_key: NodePublicKey,
+    /// Unix seconds when the `Hello` was signed
+    pub timestamp: i64,
+    pub signature: Vec<u8>,
+}
+
+impl HelloProof {
+    /// Check the signature and that the timestamp is within
+    /// [`MAX_HELLO_SKEW`] of `now`
+    pub fn verify(&self, node_id: &str, addr: SocketAddr, now: i64) -> bool {
+        now.abs_diff(self.timestamp) <= MAX_HELLO_SKEW.as_secs()
+            && self.public_key.verify(
+                &hello_message(node_id, addr, self.timestamp),
+                &self.signature,
+            )
+    }
+}
+
+/// Sender identity on a `Store`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StoreAuth {
+    /// Node that sent the chunk; its key must already be pinned
+    pub node_id: String,
+    pub signature: Vec<u8>,
+}
+
+impl StoreAuth {
+    pub fn verify(
+        &self,
+        key: &NodePublicKey,
+        chunk_id: &str,
+        route: &RouteInfo,
+        chunk: &Chunk,
+    ) -> bool {
+        key.verify(
+            &store_message(&self.node_id, chunk_id, route, chunk),
+            &self.signature,
+        )
+    }
+}
+
+/// This relay's keypair
+pub struct NodeIdentity {
+    keypair: Ed25519KeyPair,
+}
+
+impl NodeIdentity {
+    /// A fresh keypair, lost when the process exits
+    pub fn generate() -> RelayResult<Self> {
+        Self::from_pkcs8(generate_pkcs8()?.as_ref())
+    }
+
+    pub fn from_pkcs8(pkcs8: &[u8]) -> RelayResult<Self> {
+        let keypair = Ed25519KeyPair::from_pkcs8(pkcs8)
+            .map_err(|e| RelayError::InvalidConfig(format!("relay identity key: {e}")))?;
+        Ok(Self { keypair })
+    }
+
+    /// Load the keypair at `path`, creating it on first start
+    ///
+    /// The file is PKCS#8 and readable by the owner only.
+    pub fn load_or_generate(path: &Path) -> RelayResult<Self> {
+        if path.exists() {
+            return Self::from_pkcs8(&std::fs::read(path)?);
+        }
+        let pkcs8 = generate_pkcs8()?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        write_private(path, pkcs8.as_ref())?;
+        Self::from_pkcs8(pkcs8.as_ref())
+    }
+
+    pub fn public_key(&self) -> NodePublicKey {
+        let mut key = [0u8; 32];
+        key.copy_from_slice(self.keypair.public_key().as_ref());
+        NodePublicKey(key)
+    }
+
+    /// Proof for a `Hello` from `node_id` at `addr`, signed now
+    pub fn sign_hello(&self, node_id: &str, addr: SocketAddr) -> HelloProof {
+        let timestamp = chrono::Utc::now().timestamp();
+        HelloProof {
+            public_key: self.public_key(),
+            timestamp,
+            signature: self.sign(&hello_message(node_id, addr, timestamp)),
+        }
+    }
+
+    /// Identity for a `Store` of `chunk_id` sent by `node_id`
+    pub fn sign_store(
+        &self,
+        node_id: &str,
+        chunk_id: &str,
+        route: &RouteInfo,
+        chunk: &Chunk,
+    ) -> StoreAuth {
+        StoreAuth {
+            node_id: node_id.to_string(),
+            signature: self.sign(&store_message(node_id, chunk_id, route, chunk)),
+        }
+    }
+
+    fn sign(&self, message: &[u8]) -> Vec<u8> {
+        self.keypair.sign(message).as_ref().to_vec()
+    }
+}
+
+impl fmt::Debug for NodeIdentity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NodeIdentity")
+            .field("public_key", &self.public_key())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Which peers may store chunks on a relay
+///
+/// Entries in both lists are node ids or hex public keys. The denylist
+/// wins; an empty allowlist admits any node with a valid signature.
+/// Unsigned stores are accepted only when `require_auth` is off and the
+/// allowlist is empty, which keeps relays on a trusted LAN working as
+/// before.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccessPolicy {
+    /// Reject unsigned `Hello` and `Store` messages
+    #[serde(default)]
+    pub require_auth: bool,
+    #[serde(default)]
+    pub allowlist: Vec<String>,
+    #[serde(default)]
+    pub denylist: Vec<String>,
+    /// Keypair file (None = `node_key.pk8` under the persistence path, or a
+    /// new key on each start without one)
+    #[serde(default)]
+    pub identity_path: Option<PathBuf>,
+}
+
+impl AccessPolicy {
+    /// Whether unsigned messages are turned away
+    pub fn requires_signature(&self) -> bool {
+        self.require_auth || !self.allowlist.is_empty()
+    }
+
+    /// Whether the node `node_id` holding `key` may store chunks here
+    pub fn permits(&self, node_id: &str, key: &NodePublicKey) -> bool {
+        let key = key.to_string();
+        let listed = |list: &[String]| {
+            list.iter()
+                .any(|entry| entry == node_id || entry.eq_ignore_ascii_case(&key))
+        };
+        !listed(&self.denylist) && (self.allowlist.is_empty() || listed(&self.allowlist))
+    }
+
+    /// Whether an unauthenticated node may store chunks here
+    pub fn permits_unsigned(&self, node_id: &str) -> bool {
+        !self.requires_signature() && !self.denylist.iter().any(|entry| entry == node_id)
+    }
+}
+
+fn generate_pkcs8() -> RelayResult<ring::pkcs8::Document> {
+    Ed25519KeyPair::generate_pkcs8(&SystemRandom::new())
+        .map_err(|_| RelayError::InvalidConfig("failed to generate relay identity key".into()))
+}
+
+#[cfg(unix)]
+fn write_private(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+
+    std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)?
+        .write_all(data)
+}
+
+#[cfg(not(unix))]
+fn write_private(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    std::fs::write(path, data)
+}
+
+fn hello_message(node_id: &str, addr: SocketAddr, timestamp: i64) -> Vec<u8> {
+    let mut message = HELLO_CONTEXT.to_vec();
+    message.extend_from_slice(node_id.as_bytes());
+    message.push(0);
+    message.extend_from_slice(addr.to_string().as_bytes());
+    message.push(0);
+    message.extend_from_slice(&timestamp.to_le_bytes());
+    message
+}
+
+/// Covers what the sender decides about a chunk; hops and TTL change in
+/// transit and are left out
+fn store_message(node_id: &str, chunk_id: &str, route: &RouteInfo, chunk: &Chunk) -> Vec<u8> {
+    let mut message = STORE_CONTEXT.to_vec();
+    for field in [
+        node_id,
+        chunk_id,
+        &route.transfer_id,
+        &route.destination.to_string(),
+    ] {
+        message.extend_from_slice(field.as_bytes());
+        message.push(0);
+    }
+    message.push(route.priority);
+    message.extend_from_slice(&chunk.metadata.sequence_number.to_le_bytes());
+    message.push(chunk.metadata.is_parity as u8);
+    message.extend_from_slice(&chunk.metadata.checksum);
+    message.extend_from_slice(blake3::hash(&chunk.data).as_bytes());
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::relay::types::test_chunk;
+
+    fn route() -> RouteInfo {
+        RouteInfo::new("sender", "127.0.0.1:8000".parse().unwrap(), "transfer-1", 1)
+    }
+
+    #[test]
+    fn test_signatures_bind_identity_and_content() {
+        let identity = NodeIdentity::generate().unwrap();
+        let addr: SocketAddr = "127.0.0.1:9000".parse().unwrap();
+        let now = chrono::Utc::now().timestamp();
+
+        let proof = identity.sign_hello("relay-a", addr);
+        assert!(proof.verify("relay-a", addr, now));
+        assert!(!proof.verify("relay-b", addr, now));
+        assert!(!proof.verify("relay-a", addr, now + 3600));
+
+        let chunk = test_chunk(b"data".to_vec());
+        let auth = identity.sign_store("relay-a", "chunk-1", &route(), &chunk);
+        let key = identity.public_key();
+        assert!(auth.verify(&key, "chunk-1", &route(), &chunk));
+        assert!(!auth.verify(&key, "chunk-1", &route(), &test_chunk(b"dat4".to_vec())));
+        assert!(!auth.verify(&key, "chunk-2", &route(), &chunk));
+        let other = NodeIdentity::generate().unwrap().public_key();
+        assert!(!auth.verify(&other, "chunk-1", &route(), &chunk));
+
+        // The chunk's place in its transfer is covered too
+        let mut moved = chunk.clone();
+        moved.metadata.sequence_number += 1;
+        assert!(!auth.verify(&key, "chunk-1", &route(), &moved));
+
+        // Hops don't invalidate the signature
+        let mut hopped = route();
+        hopped.add_hop("relay-b");
+        assert!(auth.verify(&key, "chunk-1", &hopped, &chunk));
+    }
+
+    #[test]
+    fn test_identity_persists() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("keys/node_key.pk8");
+        let first = NodeIdentity::load_or_generate(&path).unwrap();
+        let second = NodeIdentity::load_or_generate(&path).unwrap();
+        assert_eq!(first.public_key(), second.public_key());
+
+        let key = first.public_key();
+        assert_eq!(key.to_string().parse::<NodePublicKey>().unwrap(), key);
+        assert!("abcd".parse::<NodePublicKey>().is_err());
+    }
+
+    #[test]
+    fn test_access_policy() {
+        let key = NodeIdentity::generate().unwrap().public_key();
+        let open = AccessPolicy::default();
+        assert!(open.permits("anyone", &key));
+        assert!(open.permits_unsigned("anyone"));
+
+        let listed = AccessPolicy {
+            allowlist: vec!["relay-a".into(), key.to_string()],
+            denylist: vec!["relay-b".into()],
+            ..Default::default()
+        };
+        assert!(listed.permits("relay-a", &NodeIdentity::generate().unwrap().public_key()));
+        assert!(listed.permits("renamed", &key));
+        assert!(!listed.permits("relay-b", &key));
+        assert!(!listed.permits_unsigned("relay-a"));
+
+        let deny_only = AccessPolicy {
+            denylist: vec![key.to_string()],
+            ..Default::default()
+        };
+        assert!(!deny_only.permits("relay-c", &key));
+        assert!(deny_only.permits_unsigned("relay-c"));
+    }
+}
@@ -0,0 +1,227 @@
+//! How a relay node reaches other nodes
+//!
+//! Everything a [`RelayNode`] sends on goes through its [`RelayLink`], and a
+//! send only counts once the other side has answered. Forwarding counters
+//! and per-hop loss therefore reflect what actually crossed the network. A
+//! node without a link sends nothing; its chunks wait for their destination
+//! to pull them.
+//!
+//! [`QuicLink`] carries one bincode-encoded [`RelayMessage`] per
+//! bidirectional stream, and [`serve`] answers those streams with
+//! [`RelayNode::handle_message`].
+
+use crate::network::{NetworkError, QuicTransport};
+use crate::relay::node::RelayNode;
+use crate::relay::types::{RelayError, RelayMessage, RelayResult};
+use futures::future::BoxFuture;
+use quinn::{Connection, RecvStream, SendStream};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+/// How long a send waits for the other node's answer
+pub const SEND_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Largest encoded message or answer; a pull answer carries many chunks
+const MAX_MESSAGE_SIZE: usize = 64 * 1024 * 1024;
+
+/// Carries relay messages to other nodes
+pub trait RelayLink: Send + Sync {
+    /// Send `message` to the node at `addr` and wait for its answer
+    ///
+    /// Fails with [`RelayError::Network`] if the message may not have
+    /// arrived, and with [`RelayError::Rejected`] if the node refused it.
+    fn send<'a>(
+        &'a self,
+        addr: SocketAddr,
+        message: RelayMessage,
+    ) -> BoxFuture<'a, RelayResult<Option<RelayMessage>>>;
+}
+
+/// [`RelayLink`] over QUIC, keeping one connection per node
+pub struct QuicLink {
+    transport: Arc<QuicTransport>,
+    connections: parking_lot::Mutex<HashMap<SocketAddr, Connection>>,
+}
+
+impl QuicLink {
+    pub fn new(transport: Arc<QuicTransport>) -> Self {
+        Self {
+            transport,
+            connections: parking_lot::Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Open connection to `addr`, connecting if there is none
+    async fn connection(&self, addr: SocketAddr) -> RelayResult<Connection> {
+        let open = self.connections.lock().get(&addr).cloned();
+        if let Some(conn) = open.filter(|c| c.close_reason().is_none()) {
+            return Ok(conn);
+        }
+        let conn = self.transport.connect(addr).await.map_err(network)?;
+        self.connections.lock().insert(addr, conn.clone());
+        Ok(conn)
+    }
+
+    async fn exchange(
+        &self,
+        addr: SocketAddr,
+        message: &RelayMessage,
+    ) -> RelayResult<Option<RelayMessage>> {
+        let conn = self.connection(addr).await?;
+        let (mut send_stream, mut recv_stream) = conn
+            .open_bi()
+            .await
+            .map_err(|e| RelayError::Network(e.to_string()))?;
+        write_frame(&mut send_stream, message).await?;
+        let answer: Result<Option<RelayMessage>, String> = read_frame(&mut recv_stream).await?;
+        answer.map_err(RelayError::Rejected)
+    }
+}
+
+impl RelayLink for QuicLink {
+    fn send<'a>(
+        &'a self,
+        addr: SocketAddr,
+        message: RelayMessage,
+    ) -> BoxFuture<'a, RelayResult<Option<RelayMessage>>> {
+        Box::pin(async move {
+            match tokio::time::timeout(SEND_TIMEOUT, self.exchange(addr, &message)).await {
+                Ok(answer) => answer,
+                Err(_) => Err(RelayError::Network(format!(
+                    "{addr} did not answer within {SEND_TIMEOUT:?}"
+                ))),
+            }
+        })
+    }
+}
+
+/// Answer relay messages arriving on `transport` with `node`
+///
+/// Runs until the transport's endpoint closes.
+pub fn serve(node: Arc<RelayNode>, transport: Arc<QuicTransport>) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            let conn = match transport.accept().await {
+                Ok(conn) => conn,
+                Err(NetworkError::ConnectionClosed(_)) => break,
+                Err(e) => {
+                    tracing::debug!(node_id = %node.node_id(), "relay handshake failed: {}", e);
+                    continue;
+                }
+            };
+            let node = node.clone();
+            tokio::spawn(async move {
+                while let Ok((send_stream, recv_stream)) = conn.accept_bi().await {
+                    tokio::spawn(answer(node.clone(), send_stream, recv_stream));
+                }
+            });
+        }
+    })
+}
+
+/// Handle the message on one stream and write the node's answer back
+async fn answer(node: Arc<RelayNode>, mut send_stream: SendStream, mut recv_stream: RecvStream) {
+    let message: RelayMessage = match read_frame(&mut recv_stream).await {
+        Ok(message) => message,
+        Err(e) => {
+            tracing::debug!(node_id = %node.node_id(), "unreadable relay message: {}", e);
+            return;
+        }
+    };
+    let answer = node
+        .handle_message(message)
+        .await
+        .map_err(|e| e.to_string());
+    if write_frame(&mut send_stream, &answer).await.is_ok() {
+        // Let the answer reach the sender before the stream is dropped
+        let _ = send_stream.stopped().await;
+    }
+}
+
+async fn write_frame<T: serde::Serialize>(
+    send_stream: &mut SendStream,
+    value: &T,
+) -> RelayResult<()> {
+    let frame = bincode::serialize(value).map_err(|e| RelayError::Network(e.to_string()))?;
+    send_stream
+        .write_all(&frame)
+        .await
+        .map_err(|e| RelayError::Network(e.to_string()))?;
+    send_stream
+        .finish()
+        .map_err(|e| RelayError::Network(e.to_string()))
+}
+
+async fn read_frame<T: serde::de::DeserializeOwned>(
+    recv_stream: &mut RecvStream,
+) -> RelayResult<T> {
+    let frame = recv_stream
+        .read_to_end(MAX_MESSAGE_SIZE)
+        .await
+        .map_err(|e| RelayError::Network(e.to_string()))?;
+    bincode::deserialize(&frame).map_err(|e| RelayError::Network(e.to_string()))
+}
+
+fn network(e: NetworkError) -> RelayError {
+    RelayError::Network(e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::ConnectionConfig;
+    use crate::relay::node::RelayNodeBuilder;
+    use crate::relay::types::{test_chunk, RouteInfo};
+
+    async fn transport() -> Arc<QuicTransport> {
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        let config = ConnectionConfig {
+            bind_addr: "127.0.0.1:0".parse().unwrap(),
+            ..Default::default()
+        };
+        Arc::new(QuicTransport::new(config).await.unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_store_over_quic_is_acknowledged() {
+        let server = transport().await;
+        let addr = server.local_addr().unwrap();
+        let remote = Arc::new(
+            RelayNodeBuilder::new()
+                .node_id("remote")
+                .listen_addr(addr)
+                .build()
+                .unwrap(),
+        );
+        let serving = serve(remote.clone(), server);
+
+        let local = RelayNodeBuilder::new().node_id("local").build().unwrap();
+        let link = QuicLink::new(transport().await);
+        let peers = link.send(addr, local.hello()).await.unwrap();
+        assert!(matches!(peers, Some(RelayMessage::PeerList { .. })));
+
+        let route = RouteInfo::new("local", "127.0.0.1:5001".parse().unwrap(), "t-1", 2);
+        let store = local.store_message("c-1".into(), route, test_chunk(vec![7; 64]));
+
+        let answer = link.send(addr, store).await.unwrap();
+        assert!(
+            matches!(answer, Some(RelayMessage::Ack { ref chunk_id, .. }) if chunk_id == "c-1")
+        );
+        assert_eq!(remote.holdings("t-1").held.len(), 1);
+
+        // The remote's refusal comes back as a rejection, not a lost message
+        let mut damaged = test_chunk(vec![7; 64]);
+        damaged.data = vec![8; 64].into();
+        let route = RouteInfo::new("local", "127.0.0.1:5001".parse().unwrap(), "t-1", 2);
+        let store = local.store_message("c-2".into(), route, damaged);
+        assert!(matches!(
+            link.send(addr, store).await,
+            Err(RelayError::Rejected(_))
+        ));
+
+        serving.abort();
+    }
+}
@@ -0,0 +1,691 @@
+//! Relay mesh simulation
+//!
+//! Runs a file's chunks from a sender, through a mesh of real
+//! [`RelayNode`]s, to a receiver, with loss, latency, jitter and bandwidth
+//! set per link. Time is simulated, so a run over slow links finishes
+//! immediately and the same seed gives the same result.
+//!
+//! Each relay sends a chunk on towards the receiver along the fewest
+//! remaining hops, preferring the less lossy link on a tie. A chunk sits in
+//! the relay's storage until its outgoing link has carried it, so a relay
+//! whose outgoing link is slower than its incoming one fills up; the
+//! [`MeshReport`] records how far. A chunk lost on a link is gone; whether
+//! the file survives is up to its parity.
+
+use crate::chunk::Chunk;
+use crate::relay::node::RelayNode;
+use crate::relay::types::{ForwardingPolicy, RelayConfig, RelayError, RelayResult, RouteInfo};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::hash_map::Entry;
+use std::collections::{BinaryHeap, HashMap, VecDeque};
+use std::net::SocketAddr;
+
+/// Name of the sending endpoint in [`MeshLink`]s
+pub const SENDER: &str = "sender";
+
+/// Name of the receiving endpoint in [`MeshLink`]s
+pub const RECEIVER: &str = "receiver";
+
+/// Conditions on one link, as for the test harness's lossy channel
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LinkProfile {
+    /// Chance each chunk is lost (0.0 - 1.0)
+    pub loss_rate: f32,
+    pub latency_ms: u64,
+    /// Extra latency, drawn uniformly from `0..jitter_ms`
+    pub jitter_ms: u64,
+    /// 0 = unlimited
+    pub bandwidth_bps: u64,
+}
+
+impl Default for LinkProfile {
+    fn default() -> Self {
+        Self {
+            loss_rate: 0.0,
+            latency_ms: 10,
+            jitter_ms: 0,
+            bandwidth_bps: 0,
+        }
+    }
+}
+
+/// One-way link between two mesh members
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MeshLink {
+    /// [`SENDER`] or a relay id
+    pub from: String,
+    /// [`RECEIVER`] or a relay id
+    pub to: String,
+    #[serde(flatten)]
+    pub profile: LinkProfile,
+}
+
+/// Relays and the links between them
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MeshScenario {
+    pub relays: Vec<String>,
+    pub links: Vec<MeshLink>,
+    /// Storage on each relay; chunks that don't fit are dropped
+    #[serde(default = "default_relay_storage")]
+    pub relay_storage_bytes: u64,
+    /// Fixes the random draws, for repeatable runs
+    #[serde(default)]
+    pub seed: Option<u64>,
+}
+
+fn default_relay_storage() -> u64 {
+    64 * 1024 * 1024
+}
+
+impl MeshScenario {
+    /// Relays in a line, each link with the same profile
+    pub fn chain(relays: usize, profile: LinkProfile) -> Self {
+        let ids: Vec<String> = (1..=relays).map(|n| format!("relay-{n}")).collect();
+        let stops: Vec<&str> = std::iter::once(SENDER)
+            .chain(ids.iter().map(String::as_str))
+            .chain(std::iter::once(RECEIVER))
+            .collect();
+        Self {
+            links: stops
+                .windows(2)
+                .map(|pair| MeshLink {
+                    from: pair[0].to_string(),
+                    to: pair[1].to_string(),
+                    profile,
+                })
+                .collect(),
+            relays: ids,
+            relay_storage_bytes: default_relay_storage(),
+            seed: None,
+        }
+    }
+}
+
+/// Chunks carried over one link
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HopReport {
+    pub from: String,
+    pub to: String,
+    pub sent: u64,
+    pub lost: u64,
+    pub loss_rate: f64,
+}
+
+/// What one relay went through
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RelayReport {
+    pub node_id: String,
+    pub chunks_received: u64,
+    pub chunks_forwarded: u64,
+    /// Refused, mostly for lack of storage
+    pub chunks_dropped: u64,
+    pub peak_storage_bytes: u64,
+    pub peak_stored_chunks: u64,
+}
+
+/// Time from a chunk leaving the sender to reaching the receiver
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LatencySummary {
+    pub min_ms: f64,
+    pub mean_ms: f64,
+    pub p95_ms: f64,
+    pub max_ms: f64,
+}
+
+/// Outcome of one run through the mesh
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MeshReport {
+    pub total_chunks: u32,
+    pub data_chunks: u32,
+    pub delivered_chunks: u32,
+    /// Enough chunks arrived to rebuild the file
+    pub recoverable: bool,
+    /// When the receiver could first rebuild the file
+    pub completion_ms: Option<f64>,
+    pub latency: LatencySummary,
+    /// Relays chunks crossed on the way, fewest to most
+    pub path_lengths: Vec<usize>,
+    pub hops: Vec<HopReport>,
+    pub relays: Vec<RelayReport>,
+}
+
+/// Where a link ends
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Stop {
+    Sender,
+    Relay(usize),
+    Receiver,
+}
+
+#[derive(Debug)]
+struct Link {
+    from: Stop,
+    to: Stop,
+    profile: LinkProfile,
+    /// When the link finishes what it is already carrying, in µs
+    busy_until: u64,
+    sent: u64,
+    lost: u64,
+}
+
+/// A chunk on its way through the mesh
+#[derive(Debug)]
+struct InFlight {
+    chunk_id: String,
+    route: RouteInfo,
+    chunk: Chunk,
+    /// When the sender started sending it, in µs
+    sent_at: u64,
+}
+
+#[derive(Debug)]
+enum Event {
+    /// `link` has finished carrying the chunk
+    Carried {
+        link: usize,
+        chunk: InFlight,
+    },
+    Arrived {
+        at: Stop,
+        chunk: InFlight,
+    },
+}
+
+#[derive(Debug, Default)]
+struct Peaks {
+    bytes: u64,
+    chunks: u64,
+}
+
+/// A mesh of relays ready to carry a file
+pub struct MeshSimulation {
+    scenario: MeshScenario,
+    nodes: Vec<RelayNode>,
+    /// Link each stop forwards on; `None` when it can't reach the receiver
+    next_link: HashMap<Stop, usize>,
+    links: Vec<Link>,
+}
+
+impl MeshSimulation {
+    /// Build the relays and pick each one's next hop
+    ///
+    /// Fails if a link names an unknown relay or the sender can't reach the
+    /// receiver.
+    pub fn new(scenario: MeshScenario) -> RelayResult<Self> {
+        let invalid = |reason: String| RelayError::InvalidConfig(format!("mesh: {reason}"));
+
+        let mut index = HashMap::new();
+        for (i, id) in scenario.relays.iter().enumerate() {
+            if id == SENDER || id == RECEIVER || index.insert(id.as_str(), i).is_some() {
+                return Err(invalid(format!("duplicate or reserved relay id '{id}'")));
+            }
+        }
+        let stop = |id: &str, end: &str, stop: Stop| -> RelayResult<Stop> {
+            if id == end {
+                return Ok(stop);
+            }
+            index
+                .get(id)
+                .map(|&i| Stop::Relay(i))
+                .ok_or_else(|| invalid(format!("link names unknown relay '{id}'")))
+        };
+
+        let mut links = Vec::with_capacity(scenario.links.len());
+        for link in &scenario.links {
+            if !(0.0..=1.0).contains(&link.profile.loss_rate) {
+                return Err(invalid(format!(
+                    "loss_rate {} on {} -> {} is outside 0.0..=1.0",
+                    link.profile.loss_rate, link.from, link.to
+                )));
+            }
+            links.push(Link {
+                from: stop(&link.from, SENDER, Stop::Sender)?,
+                to: stop(&link.to, RECEIVER, Stop::Receiver)?,
+                profile: link.profile,
+                busy_until: 0,
+                sent: 0,
+                lost: 0,
+            });
+        }
+
+        let next_link = route_towards_receiver(&links);
+        if !next_link.contains_key(&Stop::Sender) {
+            return Err(invalid("no path from sender to receiver".into()));
+        }
+
+        let max_hops = (scenario.relays.len() + 1).min(u8::MAX as usize) as u8;
+        let nodes = scenario
+            .relays
+            .iter()
+            .enumerate()
+            .map(|(i, id)| {
+                RelayNode::new(RelayConfig {
+                    node_id: id.clone(),
+                    listen_addr: SocketAddr::from(([127, 0, 0, 1], 20_000 + i as u16)),
+                    max_storage_bytes: scenario.relay_storage_bytes,
+                    // The simulation moves chunks itself
+                    policy: ForwardingPolicy {
+                        forward_immediately: false,
+                        max_hops,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+            })
+            .collect::<RelayResult<_>>()?;
+
+        Ok(Self {
+            scenario,
+            nodes,
+            next_link,
+            links,
+        })
+    }
+
+    /// Send `chunks` through the mesh; `data_chunks` of them are needed to
+    /// rebuild the file
+    pub async fn run(mut self, chunks: &[Chunk], data_chunks: u32) -> MeshReport {
+        let mut rng = match self.scenario.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        let destination = SocketAddr::from(([127, 0, 0, 1], 5001));
+        let ttl = (self.nodes.len() + 2).min(u8::MAX as usize) as u8;
+
+        let mut queue = EventQueue::default();
+        for chunk in chunks {
+            let mut route = RouteInfo::new(
+                SENDER,
+                destination,
+                chunk.metadata.file_id.clone(),
+                chunk.metadata.priority.level(),
+            )
+            .with_sequence(chunk.metadata.sequence_number);
+            route.ttl = ttl;
+            let in_flight = InFlight {
+                chunk_id: format!(
+                    "{}:{}",
+                    chunk.metadata.file_id, chunk.metadata.sequence_number
+                ),
+                route,
+                chunk: chunk.clone(),
+                sent_at: 0,
+            };
+            self.send_from(Stop::Sender, in_flight, 0, &mut queue);
+        }
+
+        let mut peaks: Vec<Peaks> = self.nodes.iter().map(|_| Peaks::default()).collect();
+        let mut dropped = vec![0u64; self.nodes.len()];
+        let mut arrivals: Vec<u64> = Vec::new();
+        let mut path_lengths: Vec<usize> = Vec::new();
+        let mut completion = None;
+
+        while let Some((now, event)) = queue.pop() {
+            match event {
+                Event::Carried { link, mut chunk } => {
+                    let Link {
+                        from, to, profile, ..
+                    } = self.links[link];
+                    // The chunk leaves the relay's storage once it is on the wire
+                    if let Stop::Relay(i) = from {
+                        let pulled = self.nodes[i]
+                            .deliver_to(destination, std::slice::from_ref(&chunk.chunk_id))
+                            .await;
+                        let Some(pulled) = pulled.into_iter().next() else {
+                            continue;
+                        };
+                        chunk.route = pulled.route;
+                        chunk.chunk = pulled.chunk;
+                    }
+
+                    let link_state = &mut self.links[link];
+                    link_state.sent += 1;
+                    if rng.gen::<f32>() < profile.loss_rate {
+                        link_state.lost += 1;
+                        continue;
+                    }
+                    let jitter = match profile.jitter_ms {
+                        0 => 0,
+                        jitter => rng.gen_range(0..jitter * 1000),
+                    };
+                    queue.push(
+                        now + profile.latency_ms * 1000 + jitter,
+                        Event::Arrived { at: to, chunk },
+                    );
+                }
+                Event::Arrived {
+                    at: Stop::Receiver,
+                    chunk,
+                } => {
+                    arrivals.push(now - chunk.sent_at);
+                    path_lengths.push(chunk.route.hop_count());
+                    if arrivals.len() as u32 == data_chunks {
+                        completion = Some(now);
+                    }
+                }
+                Event::Arrived {
+                    at: Stop::Relay(i),
+                    chunk,
+                } => {
+                    let node = &self.nodes[i];
+                    if node
+                        .receive_chunk(
+                            chunk.chunk_id.clone(),
+                            chunk.route.clone(),
+                            chunk.chunk.clone(),
+                        )
+                        .await
+                        .is_err()
+                    {
+                        dropped[i] += 1;
+                        continue;
+                    }
+                    let storage = node.storage_stats();
+                    peaks[i].bytes = peaks[i].bytes.max(storage.used_bytes);
+                    peaks[i].chunks = peaks[i].chunks.max(storage.total_chunks);
+                    self.send_from(Stop::Relay(i), chunk, now, &mut queue);
+                }
+                Event::Arrived {
+                    at: Stop::Sender, ..
+                } => {}
+            }
+        }
+
+        let relays = self
+            .nodes
+            .iter()
+            .zip(peaks)
+            .zip(dropped)
+            .map(|((node, peak), dropped)| {
+                let stats = node.stats();
+                RelayReport {
+                    node_id: node.node_id().to_string(),
+                    chunks_received: stats.chunks_received,
+                    chunks_forwarded: stats.chunks_forwarded,
+                    chunks_dropped: dropped,
+                    peak_storage_bytes: peak.bytes,
+                    peak_stored_chunks: peak.chunks,
+                }
+            })
+            .collect();
+
+        let hops = self
+            .scenario
+            .links
+            .iter()
+            .zip(&self.links)
+            .map(|(named, link)| HopReport {
+                from: named.from.clone(),
+                to: named.to.clone(),
+                sent: link.sent,
+                lost: link.lost,
+                loss_rate: if link.sent == 0 {
+                    0.0
+                } else {
+                    link.lost as f64 / link.sent as f64
+                },
+            })
+            .collect();
+
+        path_lengths.sort_unstable();
+        path_lengths.dedup();
+        let delivered = arrivals.len() as u32;
+        MeshReport {
+            total_chunks: chunks.len() as u32,
+            data_chunks,
+            delivered_chunks: delivered,
+            recoverable: delivered >= data_chunks,
+            completion_ms: completion.map(|us| us as f64 / 1000.0),
+            latency: summarize(arrivals),
+            path_lengths,
+            hops,
+            relays,
+        }
+    }
+
+    /// Put a chunk on the link `from` forwards on, behind whatever that
+    /// link is already carrying
+    fn send_from(&mut self, from: Stop, chunk: InFlight, now: u64, queue: &mut EventQueue) {
+        let Some(&link) = self.next_link.get(&from) else {
+            return;
+        };
+        let state = &mut self.links[link];
+        let start = now.max(state.busy_until);
+        let mut chunk = chunk;
+        if from == Stop::Sender {
+            chunk.sent_at = start;
+        }
+        let transmit = (chunk.chunk.data.len() as u64 * 8 * 1_000_000)
+            .checked_div(state.profile.bandwidth_bps)
+            .unwrap_or(0);
+        state.busy_until = start + transmit;
+        queue.push(start + transmit, Event::Carried { link, chunk });
+    }
+}
+
+/// Pick, for every stop that can reach the receiver, the link on a path
+/// with the fewest remaining hops, the one most likely to get a chunk
+/// through on a tie
+fn route_towards_receiver(links: &[Link]) -> HashMap<Stop, usize> {
+    // Breadth-first back from the receiver, so stops come out nearest first
+    let mut distance: HashMap<Stop, usize> = HashMap::from([(Stop::Receiver, 0)]);
+    let mut order = Vec::new();
+    let mut frontier = VecDeque::from([Stop::Receiver]);
+    while let Some(stop) = frontier.pop_front() {
+        let next = distance[&stop] + 1;
+        for link in links.iter().filter(|l| l.to == stop) {
+            if let Entry::Vacant(entry) = distance.entry(link.from) {
+                entry.insert(next);
+                order.push(link.from);
+                frontier.push_back(link.from);
+            }
+        }
+    }
+
+    // Chance a chunk leaving each stop reaches the receiver
+    let mut delivery: HashMap<Stop, f64> = HashMap::from([(Stop::Receiver, 1.0)]);
+    let mut next_link = HashMap::new();
+    for stop in order {
+        let best = links
+            .iter()
+            .enumerate()
+            .filter(|(_, l)| l.from == stop && distance.get(&l.to) == Some(&(distance[&stop] - 1)))
+            .map(|(i, l)| (i, (1.0 - l.profile.loss_rate as f64) * delivery[&l.to]))
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+        if let Some((i, odds)) = best {
+            next_link.insert(stop, i);
+            delivery.insert(stop, odds);
+        }
+    }
+    next_link
+}
+
+fn summarize(mut arrivals_us: Vec<u64>) -> LatencySummary {
+    if arrivals_us.is_empty() {
+        return LatencySummary::default();
+    }
+    arrivals_us.sort_unstable();
+    let ms = |us: u64| us as f64 / 1000.0;
+    let p95 = arrivals_us[((arrivals_us.len() - 1) as f64 * 0.95).round() as usize];
+    LatencySummary {
+        min_ms: ms(arrivals_us[0]),
+        mean_ms: ms(arrivals_us.iter().sum::<u64>()) / arrivals_us.len() as f64,
+        p95_ms: ms(p95),
+        max_ms: ms(*arrivals_us.last().unwrap()),
+    }
+}
+
+/// Events in time order, first in first out at equal times
+#[derive(Default)]
+struct EventQueue {
+    order: BinaryHeap<Reverse<(u64, u64)>>,
+    events: HashMap<u64, Event>,
+    next: u64,
+}
+
+impl EventQueue {
+    fn push(&mut self, at: u64, event: Event) {
+        self.order.push(Reverse((at, self.next)));
+        self.events.insert(self.next, event);
+        self.next += 1;
+    }
+
+    fn pop(&mut self) -> Option<(u64, Event)> {
+        let Reverse((at, id)) = self.order.pop()?;
+        self.events.remove(&id).map(|event| (at, event))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::{ChunkMetadata, Priority};
+    use crate::integrity::IntegrityVerifier;
+    use bytes::Bytes;
+
+    fn chunks(count: u32, size: usize) -> Vec<Chunk> {
+        (0..count)
+            .map(|seq| Chunk {
+                metadata: ChunkMetadata {
+                    chunk_id: seq as u64,
+                    file_id: "mesh".into(),
+                    sequence_number: seq,
+                    total_chunks: count,
+                    data_size: size,
+                    checksum: IntegrityVerifier::calculate_checksum(&vec![7u8; size]),
+                    is_parity: false,
+                    priority: Priority::Normal,
+                    created_at: 0,
+                    file_size: 0,
+                    file_checksum: [0; 32],
+                    data_chunks: count,
+                    zero_runs: Vec::new(),
+                    attributes: None,
+                    checksum_algorithm: Default::default(),
+                    merkle_proof: None,
+                    trace: None,
+                    compression: None,
+                },
+                data: Bytes::from(vec![7u8; size]),
+            })
+            .collect()
+    }
+
+    fn link(from: &str, to: &str, loss_rate: f32, latency_ms: u64) -> MeshLink {
+        MeshLink {
+            from: from.into(),
+            to: to.into(),
+            profile: LinkProfile {
+                loss_rate,
+                latency_ms,
+                ..Default::default()
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn test_chain_delivers_with_summed_latency() {
+        let profile = LinkProfile {
+            latency_ms: 20,
+            ..Default::default()
+        };
+        let mesh = MeshSimulation::new(MeshScenario::chain(3, profile)).unwrap();
+        let report = mesh.run(&chunks(10, 1024), 8).await;
+
+        assert!(report.recoverable);
+        assert_eq!(report.delivered_chunks, 10);
+        assert_eq!(report.path_lengths, [3]);
+        // Four links of 20 ms each
+        assert_eq!(report.latency.min_ms, 80.0);
+        assert_eq!(report.latency.max_ms, 80.0);
+        assert_eq!(report.completion_ms, Some(80.0));
+        assert!(report.hops.iter().all(|h| h.sent == 10 && h.lost == 0));
+        assert!(report
+            .relays
+            .iter()
+            .all(|r| r.chunks_received == 10 && r.chunks_forwarded == 10));
+    }
+
+    #[tokio::test]
+    async fn test_slow_link_fills_relay_storage() {
+        let mut scenario = MeshScenario::chain(1, LinkProfile::default());
+        // 8 Mbit/s out of the relay: each 10 KB chunk takes 10 ms to send
+        scenario.links[1].profile.bandwidth_bps = 8_000_000;
+        let mesh = MeshSimulation::new(scenario).unwrap();
+        let report = mesh.run(&chunks(20, 10_000), 20).await;
+
+        assert_eq!(report.delivered_chunks, 20);
+        assert_eq!(report.relays[0].peak_stored_chunks, 20);
+        assert_eq!(report.relays[0].peak_storage_bytes, 200_000);
+        // The last chunk waits behind the other nineteen
+        assert_eq!(report.latency.max_ms, 10.0 + 200.0 + 10.0);
+    }
+
+    #[tokio::test]
+    async fn test_routes_around_lossy_relay_and_reports_hop_loss() {
+        let scenario = MeshScenario {
+            relays: vec!["north".into(), "south".into(), "east".into()],
+            links: vec![
+                link(SENDER, "north", 0.0, 5),
+                link(SENDER, "south", 0.0, 5),
+                link("north", RECEIVER, 0.5, 5),
+                link("south", RECEIVER, 0.0, 5),
+                // Longer way round; never chosen
+                link(SENDER, "east", 0.0, 5),
+                link("east", "north", 0.0, 5),
+            ],
+            relay_storage_bytes: default_relay_storage(),
+            seed: Some(7),
+        };
+        let mesh = MeshSimulation::new(scenario).unwrap();
+        let report = mesh.run(&chunks(40, 512), 30).await;
+
+        // Equal hop counts, so the sender picks the clean path via south
+        assert_eq!(report.delivered_chunks, 40);
+        assert_eq!(report.hops[1].sent, 40);
+        assert_eq!(report.hops[0].sent, 0);
+        assert_eq!(report.relays[0].chunks_received, 0);
+
+        // A lossy last hop shows up in the hop report
+        let scenario = MeshScenario {
+            seed: Some(7),
+            ..MeshScenario::chain(
+                1,
+                LinkProfile {
+                    loss_rate: 0.5,
+                    ..Default::default()
+                },
+            )
+        };
+        let report = MeshSimulation::new(scenario)
+            .unwrap()
+            .run(&chunks(200, 512), 150)
+            .await;
+        assert!(!report.recoverable);
+        assert_eq!(report.hops[0].sent, 200);
+        assert_eq!(report.hops[1].sent, 200 - report.hops[0].lost);
+        assert!(report
+            .hops
+            .iter()
+            .all(|h| h.loss_rate > 0.3 && h.loss_rate < 0.7));
+    }
+
+    #[test]
+    fn test_rejects_unreachable_receiver() {
+        let scenario = MeshScenario {
+            relays: vec!["island".into()],
+            links: vec![link(SENDER, "island", 0.0, 5)],
+            relay_storage_bytes: default_relay_storage(),
+            seed: None,
+        };
+        assert!(MeshSimulation::new(scenario).is_err());
+
+        let mut scenario = MeshScenario::chain(1, LinkProfile::default());
+        scenario.links[0].to = "nowhere".into();
+        assert!(MeshSimulation::new(scenario).is_err());
+    }
+}
@@ -8,11 +8,28 @@
 //! - Priority-aware forwarding
 //! - Automatic retry with exponential backoff
 //! - Mesh network support for multi-hop delivery
+//! - Pull delivery for receivers that come online late
+//! - Signed peer identities and an allow/deny list for stores
+//! - Relay messages carried over QUIC
 
+pub mod fec;
+pub mod identity;
+pub mod link;
+pub mod mesh;
 pub mod node;
+pub mod pull;
+pub mod segment;
 pub mod storage;
 pub mod types;
 
+pub use identity::{AccessPolicy, NodeIdentity, NodePublicKey};
+pub use link::{QuicLink, RelayLink};
+pub use mesh::{MeshReport, MeshScenario, MeshSimulation};
 pub use node::RelayNode;
-pub use storage::{RelayStorage, StoredChunk};
-pub use types::{ForwardingPolicy, RelayConfig, RelayError, RelayResult, RelayStats, RouteInfo};
+pub use pull::RelayPuller;
+pub use storage::{CompactionReport, RelayStorage, ScanReport, StoredChunk};
+pub use types::{
+    AvailableChunks, DestinationQuotas, DestinationUsage, ExpiredNotice, ExpiryReason,
+    FecShardInfo, FloodPolicy, ForwardingPolicy, HopFecPolicy, PolicyUpdate, PulledChunk,
+    QuotaBreach, RelayConfig, RelayError, RelayResult, RelayStats, RouteInfo, TransferHoldings,
+};
@@ -2,24 +2,54 @@
 //!
 //! A relay node stores and forwards chunks between disconnected parties.
 
-use crate::relay::storage::RelayStorage;
+use crate::chunk::{Chunk, HopStage};
+use crate::integrity::IntegrityVerifier;
+use crate::relay::fec;
+use crate::relay::identity::{AccessPolicy, HelloProof, NodeIdentity, NodePublicKey, StoreAuth};
+use crate::relay::link::RelayLink;
+use crate::relay::storage::{CompactionReport, RelayStorage, ScanReport, StoredChunk};
 use crate::relay::types::{
-    ForwardingPolicy, PeerInfo, RelayConfig, RelayError, RelayMessage, RelayResult, RelayStats,
-    RouteInfo,
+    AvailableChunks, DestinationLatency, DestinationQuotas, DestinationUsage, ExpiredNotice,
+    ExpiryReason, FecShardInfo, FloodPolicy, ForwardLatency, ForwardingPolicy, PeerInfo,
+    PolicyUpdate, PulledChunk, RelayConfig, RelayError, RelayMessage, RelayResult, RelayStats,
+    RouteInfo, TransferHoldings,
 };
-use parking_lot::RwLock;
-use std::collections::HashMap;
+use parking_lot::{Mutex, RwLock};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
-use std::sync::Arc;
-use std::time::Duration;
-use tokio::sync::mpsc;
+use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant, SystemTime};
+use tokio::sync::{mpsc, Notify};
+use tokio::task::JoinHandle;
+use tokio::time::MissedTickBehavior;
+
+/// File under `persistence_path` holding the peer table and counters
+const STATE_FILE: &str = "node_state.json";
+
+/// File under `persistence_path` holding the node's keypair, unless
+/// `access.identity_path` says otherwise
+const IDENTITY_FILE: &str = "node_key.pk8";
+
+/// Transfers whose delivered chunks are remembered for audits
+const DELIVERED_LOG_TRANSFERS: usize = 1024;
+
+/// Weight of the latest send when updating a hop's loss rate
+const LOSS_SMOOTHING: f32 = 0.2;
 
 /// A store-and-forward relay node
 pub struct RelayNode {
     /// Node configuration
     config: RelayConfig,
 
+    /// Forwarding policy in effect; starts from `config.policy` and can be
+    /// changed at runtime
+    policy: RwLock<ForwardingPolicy>,
+
     /// Chunk storage
     storage: Arc<RelayStorage>,
 
@@ -29,11 +59,80 @@ pub struct RelayNode {
     /// Known peers
     peers: RwLock<HashMap<String, PeerInfo>>,
 
-    /// Running state
+    /// Whether the maintenance scheduler is running
     running: AtomicBool,
 
+    /// Held while a maintenance cycle runs, so cycles never overlap
+    cycle_lock: tokio::sync::Mutex<()>,
+
+    /// Maintenance task started by `start`
+    scheduler: Mutex<Option<Scheduler>>,
+
     /// Event sender for async operations
     event_tx: Option<mpsc::Sender<RelayEvent>>,
+
+    /// Carries messages to peers and destinations; without one nothing is
+    /// sent and chunks wait to be pulled
+    link: Option<Arc<dyn RelayLink>>,
+
+    /// Observed loss rate per next-hop address (drives hop FEC parity)
+    hop_loss: RwLock<HashMap<SocketAddr, f32>>,
+
+    /// FEC groups already re-encoded here, with when; late shards are
+    /// redundant until the group's chunks would have expired
+    reencoded_groups: Mutex<HashMap<String, Instant>>,
+
+    /// Peer relays holding copies of critical chunks replicated from here
+    replicas: RwLock<HashMap<String, Vec<String>>>,
+
+    /// Chunk ids flooded or taken from a flood here, with when they were seen
+    flooded: Mutex<HashMap<String, Instant>>,
+
+    /// Keypair this node signs its messages with
+    identity: NodeIdentity,
+
+    /// Chunks handed to their destination, for senders auditing a transfer
+    delivered: RwLock<DeliveredLog>,
+}
+
+/// A running maintenance task and the signal that stops it
+struct Scheduler {
+    stop: Arc<Notify>,
+    handle: JoinHandle<()>,
+}
+
+/// Sequence numbers delivered per transfer, for the most recent transfers
+#[derive(Debug, Default)]
+struct DeliveredLog {
+    by_transfer: HashMap<String, HashSet<u32>>,
+    order: VecDeque<String>,
+}
+
+impl DeliveredLog {
+    fn record(&mut self, transfer_id: &str, sequence_number: u32) {
+        if !self.by_transfer.contains_key(transfer_id) {
+            if self.order.len() >= DELIVERED_LOG_TRANSFERS {
+                if let Some(oldest) = self.order.pop_front() {
+                    self.by_transfer.remove(&oldest);
+                }
+            }
+            self.order.push_back(transfer_id.to_string());
+        }
+        self.by_transfer
+            .entry(transfer_id.to_string())
+            .or_default()
+            .insert(sequence_number);
+    }
+
+    fn sequences(&self, transfer_id: &str) -> Vec<u32> {
+        let mut sequences: Vec<u32> = self
+            .by_transfer
+            .get(transfer_id)
+            .map(|s| s.iter().copied().collect())
+            .unwrap_or_default();
+        sequences.sort_unstable();
+        sequences
+    }
 }
 
 struct RelayStatsInner {
@@ -43,6 +142,33 @@ struct RelayStatsInner {
     chunks_dropped: AtomicU64,
     bytes_received: AtomicU64,
     bytes_forwarded: AtomicU64,
+    hop_fec_groups: AtomicU64,
+    hop_fec_repairs: AtomicU64,
+    chunks_pulled: AtomicU64,
+    replicas_created: AtomicU64,
+    duplicates_discarded: AtomicU64,
+    chunks_flooded: AtomicU64,
+    flood_copies_sent: AtomicU64,
+    flood_duplicates_suppressed: AtomicU64,
+    expiry_notices_sent: AtomicU64,
+    unauthorized_stores: AtomicU64,
+    rejected_hellos: AtomicU64,
+    quota_rejections: AtomicU64,
+    quota_evictions: AtomicU64,
+    corrupt_chunks: AtomicU64,
+    maintenance_cycles: AtomicU64,
+    maintenance_skipped: AtomicU64,
+    last_cycle_ms: AtomicU64,
+    max_cycle_ms: AtomicU64,
+    total_cycle_ms: AtomicU64,
+    latency: Mutex<LatencyLog>,
+}
+
+/// Store to forward latency, overall and per next hop
+#[derive(Default)]
+struct LatencyLog {
+    overall: ForwardLatency,
+    destinations: HashMap<SocketAddr, ForwardLatency>,
 }
 
 impl Default for RelayStatsInner {
@@ -54,28 +180,176 @@ impl Default for RelayStatsInner {
             chunks_dropped: AtomicU64::new(0),
             bytes_received: AtomicU64::new(0),
             bytes_forwarded: AtomicU64::new(0),
+            hop_fec_groups: AtomicU64::new(0),
+            hop_fec_repairs: AtomicU64::new(0),
+            chunks_pulled: AtomicU64::new(0),
+            replicas_created: AtomicU64::new(0),
+            duplicates_discarded: AtomicU64::new(0),
+            chunks_flooded: AtomicU64::new(0),
+            flood_copies_sent: AtomicU64::new(0),
+            flood_duplicates_suppressed: AtomicU64::new(0),
+            expiry_notices_sent: AtomicU64::new(0),
+            unauthorized_stores: AtomicU64::new(0),
+            rejected_hellos: AtomicU64::new(0),
+            quota_rejections: AtomicU64::new(0),
+            quota_evictions: AtomicU64::new(0),
+            corrupt_chunks: AtomicU64::new(0),
+            maintenance_cycles: AtomicU64::new(0),
+            maintenance_skipped: AtomicU64::new(0),
+            last_cycle_ms: AtomicU64::new(0),
+            max_cycle_ms: AtomicU64::new(0),
+            total_cycle_ms: AtomicU64::new(0),
+            latency: Mutex::new(LatencyLog::default()),
         }
     }
 }
 
+impl RelayStatsInner {
+    /// Continue counting from a previous run's totals
+    fn restore(&self, stats: &RelayStats) {
+        self.chunks_received
+            .store(stats.chunks_received, Ordering::Relaxed);
+        self.chunks_forwarded
+            .store(stats.chunks_forwarded, Ordering::Relaxed);
+        self.chunks_expired
+            .store(stats.chunks_expired, Ordering::Relaxed);
+        self.chunks_dropped
+            .store(stats.chunks_dropped, Ordering::Relaxed);
+        self.bytes_received
+            .store(stats.bytes_received, Ordering::Relaxed);
+        self.bytes_forwarded
+            .store(stats.bytes_forwarded, Ordering::Relaxed);
+        self.hop_fec_groups
+            .store(stats.hop_fec_groups, Ordering::Relaxed);
+        self.hop_fec_repairs
+            .store(stats.hop_fec_repairs, Ordering::Relaxed);
+        self.chunks_pulled
+            .store(stats.chunks_pulled, Ordering::Relaxed);
+        self.replicas_created
+            .store(stats.replicas_created, Ordering::Relaxed);
+        self.duplicates_discarded
+            .store(stats.duplicates_discarded, Ordering::Relaxed);
+        self.chunks_flooded
+            .store(stats.chunks_flooded, Ordering::Relaxed);
+        self.flood_copies_sent
+            .store(stats.flood_copies_sent, Ordering::Relaxed);
+        self.flood_duplicates_suppressed
+            .store(stats.flood_duplicates_suppressed, Ordering::Relaxed);
+        self.expiry_notices_sent
+            .store(stats.expiry_notices_sent, Ordering::Relaxed);
+        self.unauthorized_stores
+            .store(stats.unauthorized_stores, Ordering::Relaxed);
+        self.rejected_hellos
+            .store(stats.rejected_hellos, Ordering::Relaxed);
+        self.quota_rejections
+            .store(stats.quota_rejections, Ordering::Relaxed);
+        self.quota_evictions
+            .store(stats.quota_evictions, Ordering::Relaxed);
+        self.corrupt_chunks
+            .store(stats.corrupt_chunks, Ordering::Relaxed);
+        self.maintenance_cycles
+            .store(stats.maintenance_cycles, Ordering::Relaxed);
+        self.maintenance_skipped
+            .store(stats.maintenance_skipped, Ordering::Relaxed);
+        self.last_cycle_ms
+            .store(stats.last_cycle_ms, Ordering::Relaxed);
+        self.max_cycle_ms
+            .store(stats.max_cycle_ms, Ordering::Relaxed);
+        self.total_cycle_ms
+            .store(stats.total_cycle_ms, Ordering::Relaxed);
+        *self.latency.lock() = LatencyLog {
+            overall: stats.forward_latency.clone(),
+            destinations: stats
+                .destination_latency
+                .iter()
+                .map(|d| (d.destination, d.latency.clone()))
+                .collect(),
+        };
+    }
+
+    fn record_cycle(&self, elapsed: Duration) {
+        let ms = elapsed.as_millis() as u64;
+        self.maintenance_cycles.fetch_add(1, Ordering::Relaxed);
+        self.last_cycle_ms.store(ms, Ordering::Relaxed);
+        self.max_cycle_ms.fetch_max(ms, Ordering::Relaxed);
+        self.total_cycle_ms.fetch_add(ms, Ordering::Relaxed);
+    }
+
+    /// Record a chunk stored at `stored_at` leaving for `destination`
+    fn record_forward(&self, destination: SocketAddr, stored_at: SystemTime) {
+        let ms = stored_at.elapsed().unwrap_or_default().as_millis() as u64;
+        let mut latency = self.latency.lock();
+        latency.overall.record(ms);
+        latency
+            .destinations
+            .entry(destination)
+            .or_default()
+            .record(ms);
+    }
+
+    /// Overall latency and latency per next hop, sorted by address
+    fn latency(&self) -> (ForwardLatency, Vec<DestinationLatency>) {
+        let latency = self.latency.lock();
+        let mut destinations: Vec<DestinationLatency> = latency
+            .destinations
+            .iter()
+            .map(|(&destination, latency)| DestinationLatency {
+                destination,
+                latency: latency.clone(),
+            })
+            .collect();
+        destinations.sort_by_key(|d| d.destination);
+        (latency.overall.clone(), destinations)
+    }
+}
+
+/// What a relay keeps across restarts besides its chunks
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct NodeState {
+    #[serde(default)]
+    peers: Vec<PeerInfo>,
+    #[serde(default)]
+    stats: RelayStats,
+}
+
 /// Events emitted by the relay node
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum RelayEvent {
     /// Chunk received and stored
-    ChunkStored { chunk_id: String, size: usize },
+    ChunkStored {
+        chunk_id: String,
+        size: usize,
+        /// The chunk's trace id (the sender's session id), if traced
+        #[serde(default)]
+        trace_id: Option<String>,
+    },
 
     /// Chunk forwarded successfully
     ChunkForwarded {
         chunk_id: String,
         destination: SocketAddr,
+        #[serde(default)]
+        trace_id: Option<String>,
     },
 
     /// Chunk expired before delivery
     ChunkExpired { chunk_id: String },
 
+    /// An expiry notice reached the last relay before its origin; the
+    /// origin's coordinator picks it up from here
+    ChunkUndeliverable { notice: ExpiredNotice },
+
     /// Peer connected
     PeerConnected { node_id: String },
 
+    /// A store was turned away by the access policy
+    StoreRejected {
+        chunk_id: String,
+        /// Signed sender, when the store had one
+        node_id: Option<String>,
+        reason: String,
+    },
+
     /// Peer disconnected
     PeerDisconnected { node_id: String },
 
@@ -83,26 +357,76 @@ pub enum RelayEvent {
     Error { message: String },
 }
 
+impl RelayEvent {
+    /// Trace id of the chunk the event is about, if it carries one
+    pub fn trace_id(&self) -> Option<&str> {
+        match self {
+            RelayEvent::ChunkStored { trace_id, .. }
+            | RelayEvent::ChunkForwarded { trace_id, .. } => trace_id.as_deref(),
+            _ => None,
+        }
+    }
+}
+
 impl RelayNode {
     /// Create a new relay node
     pub fn new(config: RelayConfig) -> RelayResult<Self> {
-        let storage = Arc::new(RelayStorage::new(
-            config.max_storage_bytes,
-            config.max_hold_time,
-        ));
+        let mut storage = RelayStorage::new(config.max_storage_bytes, config.max_hold_time);
+        if let Some(dir) = &config.persistence_path {
+            storage = storage.with_persistence(dir)?;
+        }
+        let storage = Arc::new(storage.with_quotas(config.quotas.clone()));
 
         let mut peers = HashMap::new();
         for peer in &config.peers {
             peers.insert(peer.node_id.clone(), peer.clone());
         }
 
+        let stats = RelayStatsInner::default();
+        if let Some(dir) = &config.persistence_path {
+            let state = load_state(&dir.join(STATE_FILE))?;
+            stats.restore(&state.stats);
+            for peer in state.peers {
+                peers
+                    .entry(peer.node_id.clone())
+                    .and_modify(|known: &mut PeerInfo| known.last_seen = peer.last_seen)
+                    .or_insert(peer);
+            }
+        }
+
+        let policy = match &config.policy_path {
+            Some(path) if path.exists() => load_policy(path)?,
+            _ => config.policy.clone(),
+        };
+
+        let identity_path = config.access.identity_path.clone().or_else(|| {
+            config
+                .persistence_path
+                .as_ref()
+                .map(|d| d.join(IDENTITY_FILE))
+        });
+        let identity = match identity_path {
+            Some(path) => NodeIdentity::load_or_generate(&path)?,
+            None => NodeIdentity::generate()?,
+        };
+
         Ok(Self {
             config,
+            policy: RwLock::new(policy),
             storage,
-            stats: Arc::new(RelayStatsInner::default()),
+            stats: Arc::new(stats),
             peers: RwLock::new(peers),
             running: AtomicBool::new(false),
+            cycle_lock: tokio::sync::Mutex::new(()),
+            scheduler: Mutex::new(None),
             event_tx: None,
+            link: None,
+            hop_loss: RwLock::new(HashMap::new()),
+            reencoded_groups: Mutex::new(HashMap::new()),
+            replicas: RwLock::new(HashMap::new()),
+            flooded: Mutex::new(HashMap::new()),
+            identity,
+            delivered: RwLock::new(DeliveredLog::default()),
         })
     }
 
@@ -112,6 +436,12 @@ impl RelayNode {
         self
     }
 
+    /// Send messages to peers and destinations over `link`
+    pub fn with_link(mut self, link: Arc<dyn RelayLink>) -> Self {
+        self.link = Some(link);
+        self
+    }
+
     /// Get the node ID
     pub fn node_id(&self) -> &str {
         &self.config.node_id
@@ -122,36 +452,158 @@ impl RelayNode {
         self.config.listen_addr
     }
 
+    /// Public half of this node's keypair, for peers' allowlists
+    pub fn public_key(&self) -> NodePublicKey {
+        self.identity.public_key()
+    }
+
+    /// Signed `Hello` introducing this node to a peer
+    pub fn hello(&self) -> RelayMessage {
+        RelayMessage::Hello {
+            node_id: self.config.node_id.clone(),
+            addr: self.config.listen_addr,
+            proof: Some(
+                self.identity
+                    .sign_hello(&self.config.node_id, self.config.listen_addr),
+            ),
+        }
+    }
+
+    /// `Store` of a chunk signed by this node, for handing it to a peer
+    pub fn store_message(&self, chunk_id: String, route: RouteInfo, chunk: Chunk) -> RelayMessage {
+        let auth = self
+            .identity
+            .sign_store(&self.config.node_id, &chunk_id, &route, &chunk);
+        RelayMessage::Store {
+            chunk_id,
+            route,
+            chunk,
+            auth: Some(auth),
+        }
+    }
+
+    /// The forwarding policy currently in effect
+    pub fn policy(&self) -> ForwardingPolicy {
+        self.policy.read().clone()
+    }
+
+    /// Change forwarding policy fields at runtime
+    ///
+    /// The new policy applies to chunks received from now on and to the next
+    /// maintenance cycle. It is saved to `policy_path` when one is configured,
+    /// and the in-memory policy is left untouched if saving fails.
+    pub fn update_policy(&self, update: &PolicyUpdate) -> RelayResult<ForwardingPolicy> {
+        let mut policy = self.policy.write();
+        let mut updated = policy.clone();
+        update.apply_to(&mut updated);
+
+        if let Some(path) = &self.config.policy_path {
+            save_policy(path, &updated)?;
+        }
+
+        tracing::info!(node_id = %self.config.node_id, ?update, "forwarding policy updated");
+        *policy = updated.clone();
+        Ok(updated)
+    }
+
     /// Receive and store a chunk for forwarding
+    ///
+    /// The chunk is checked against its own checksum first, so damage on
+    /// the way in is caught at this hop rather than at the receiver.
     pub async fn receive_chunk(
         &self,
         chunk_id: String,
         route: RouteInfo,
-        data: Vec<u8>,
+        chunk: Chunk,
     ) -> RelayResult<()> {
+        if let Err(e) = IntegrityVerifier::verify_chunk(&chunk) {
+            self.stats.corrupt_chunks.fetch_add(1, Ordering::Relaxed);
+            self.stats.chunks_dropped.fetch_add(1, Ordering::Relaxed);
+            tracing::warn!(node_id = %self.config.node_id, chunk_id, "dropping corrupt chunk: {}", e);
+            self.notify_origin(&chunk_id, &route, ExpiryReason::Corrupted)
+                .await;
+            return Err(RelayError::Corrupt(chunk_id));
+        }
+
         // Check TTL
         if route.is_expired() {
             self.stats.chunks_dropped.fetch_add(1, Ordering::Relaxed);
+            self.notify_origin(&chunk_id, &route, ExpiryReason::HopLimit)
+                .await;
             return Err(RelayError::ChunkExpired(chunk_id));
         }
 
+        let policy = self.policy();
+
         // Check hop limit
-        if route.hop_count() >= self.config.policy.max_hops as usize {
+        if route.hop_count() >= policy.max_hops as usize {
             self.stats.chunks_dropped.fetch_add(1, Ordering::Relaxed);
+            self.notify_origin(&chunk_id, &route, ExpiryReason::HopLimit)
+                .await;
             return Err(RelayError::ChunkExpired(format!(
                 "{}: max hops exceeded",
                 chunk_id
             )));
         }
 
-        let size = data.len();
+        // Flooded copies come in over every path; only the first is kept
+        if route.flood_ttl.is_some() {
+            let window = policy.flood.clone().unwrap_or_default().suppression_window;
+            if !self.note_flooded(&chunk_id, window) {
+                self.stats.chunks_received.fetch_add(1, Ordering::Relaxed);
+                self.stats
+                    .flood_duplicates_suppressed
+                    .fetch_add(1, Ordering::Relaxed);
+                return Ok(());
+            }
+        }
+
+        let size = chunk.data.len();
+
+        // Shards of a group we've already re-encoded add nothing
+        let fec_group = match (&policy.hop_fec, &route.fec) {
+            (Some(_), Some(info)) => Some(info.group_id.clone()),
+            _ => None,
+        };
+        if let Some(group_id) = &fec_group {
+            if self.was_reencoded(group_id) {
+                self.stats.chunks_received.fetch_add(1, Ordering::Relaxed);
+                return Ok(());
+            }
+        }
+
+        // Replicated chunks can reach the same relay more than once
+        if self.storage.contains(&chunk_id) {
+            self.stats.chunks_received.fetch_add(1, Ordering::Relaxed);
+            self.stats
+                .duplicates_discarded
+                .fetch_add(1, Ordering::Relaxed);
+            return Ok(());
+        }
+
+        let replicate = policy.replication_factor > 1 && route.is_critical() && !route.replica;
 
-        // Add this node to the route
+        // Add this node to the route, and to the chunk's trace
         let mut route = route;
         route.add_hop(&self.config.node_id);
+        let mut chunk = chunk;
+        chunk
+            .metadata
+            .record_hop(&self.config.node_id, HopStage::Relayed);
+        let trace_id = chunk.metadata.trace_id().map(str::to_string);
 
         // Store the chunk
-        self.storage.store(chunk_id.clone(), route, data)?;
+        let evicted = match self.storage.store(chunk_id.clone(), route.clone(), chunk) {
+            Ok(evicted) => evicted,
+            Err(e @ RelayError::QuotaExceeded { .. }) => {
+                self.stats.quota_rejections.fetch_add(1, Ordering::Relaxed);
+                self.stats.chunks_dropped.fetch_add(1, Ordering::Relaxed);
+                tracing::debug!(node_id = %self.config.node_id, chunk_id, "{}", e);
+                return Err(e);
+            }
+            Err(e) => return Err(e),
+        };
+        self.drop_evicted(evicted).await;
 
         // Update stats
         self.stats.chunks_received.fetch_add(1, Ordering::Relaxed);
@@ -159,35 +611,392 @@ impl RelayNode {
             .bytes_received
             .fetch_add(size as u64, Ordering::Relaxed);
 
+        tracing::debug!(
+            node_id = %self.config.node_id,
+            chunk_id,
+            trace_id = trace_id.as_deref().unwrap_or("-"),
+            hops = route.hop_count(),
+            "stored chunk"
+        );
+
         // Emit event
         self.emit_event(RelayEvent::ChunkStored {
             chunk_id: chunk_id.clone(),
             size,
+            trace_id,
         })
         .await;
 
+        // FEC shards are held until the whole group can be re-encoded
+        if let Some(group_id) = fec_group {
+            if let Some(new_ids) = self.reencode_fec_group(&group_id)? {
+                if policy.forward_immediately {
+                    for id in new_ids {
+                        let _ = self.try_forward_chunk(&id).await;
+                    }
+                }
+            }
+            return Ok(());
+        }
+
+        // Critical chunks get copies on peer relays before this one moves on
+        if replicate {
+            self.replicate_chunk(&chunk_id, policy.replication_factor - 1)
+                .await;
+        }
+
         // Forward immediately if policy allows
-        if self.config.policy.forward_immediately {
+        if policy.forward_immediately {
             let _ = self.try_forward_chunk(&chunk_id).await;
         }
 
         Ok(())
     }
 
+    /// Account for chunks storage evicted to keep within a quota
+    async fn drop_evicted(&self, evicted: Vec<StoredChunk>) {
+        for chunk in evicted {
+            self.stats.quota_evictions.fetch_add(1, Ordering::Relaxed);
+            self.stats.chunks_dropped.fetch_add(1, Ordering::Relaxed);
+            self.replicas.write().remove(&chunk.chunk_id);
+            self.notify_origin(&chunk.chunk_id, &chunk.route, ExpiryReason::QuotaEvicted)
+                .await;
+        }
+    }
+
+    /// Storage held for each destination, with its quota
+    pub fn destination_usage(&self) -> Vec<DestinationUsage> {
+        self.storage.destination_usage()
+    }
+
+    /// Per-destination quotas in effect
+    pub fn destination_quotas(&self) -> DestinationQuotas {
+        self.storage.quotas()
+    }
+
+    /// Change per-destination quotas for chunks stored from now on
+    pub fn set_destination_quotas(&self, quotas: DestinationQuotas) {
+        tracing::info!(node_id = %self.config.node_id, ?quotas, "destination quotas updated");
+        self.storage.set_quotas(quotas);
+    }
+
+    /// Store copies of a chunk on up to `copies` peer relays
+    ///
+    /// Returns how many peers acknowledged a copy.
+    async fn replicate_chunk(&self, chunk_id: &str, copies: usize) -> usize {
+        let Some(chunk) = self.storage.get(chunk_id) else {
+            return 0;
+        };
+
+        let mut peer_list: Vec<PeerInfo> = self.peers.read().values().cloned().collect();
+        peer_list.sort_by_key(|p| p.priority);
+
+        let mut route = chunk.route.clone();
+        route.replica = true;
+
+        let mut holders = Vec::new();
+        for peer in peer_list {
+            if holders.len() == copies {
+                break;
+            }
+            if route.hops.contains(&peer.node_id) {
+                continue;
+            }
+            let store =
+                self.store_message(chunk_id.to_string(), route.clone(), chunk.chunk.clone());
+            if let Ok(Some(RelayMessage::Ack { .. })) = self.send_to(peer.addr, store).await {
+                self.touch_peer(&peer.node_id);
+                holders.push(peer.node_id);
+            }
+        }
+
+        if holders.len() < copies {
+            tracing::warn!(
+                chunk_id,
+                wanted = copies,
+                placed = holders.len(),
+                "not enough peers to fully replicate critical chunk"
+            );
+        }
+
+        let placed = holders.len();
+        self.stats
+            .replicas_created
+            .fetch_add(placed as u64, Ordering::Relaxed);
+        if placed > 0 {
+            self.replicas.write().insert(chunk_id.to_string(), holders);
+        }
+        placed
+    }
+
+    /// Peer relays holding a replica of `chunk_id` placed by this node
+    pub fn replica_holders(&self, chunk_id: &str) -> Vec<String> {
+        self.replicas
+            .read()
+            .get(chunk_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Tell the relays holding replicas of a delivered chunk to drop them
+    async fn release_replicas(&self, chunk_id: &str) {
+        let Some(holders) = self.replicas.write().remove(chunk_id) else {
+            return;
+        };
+        for node_id in holders {
+            let addr = self.peers.read().get(&node_id).map(|p| p.addr);
+            if let Some(addr) = addr {
+                let delivered = RelayMessage::Delivered {
+                    chunk_id: chunk_id.to_string(),
+                };
+                // A holder that misses this drops its copy when it expires
+                let _ = self.send_to(addr, delivered).await;
+            }
+        }
+    }
+
+    /// Decode and re-encode an FEC group once enough shards are stored
+    ///
+    /// Returns the chunk ids of the re-encoded shards, or `None` if the group
+    /// can't be decoded yet.
+    fn reencode_fec_group(&self, group_id: &str) -> RelayResult<Option<Vec<String>>> {
+        let Some(policy) = self.policy.read().hop_fec.clone() else {
+            return Ok(None);
+        };
+
+        let stored = self.storage.get_group(group_id);
+        let shards: Vec<_> = stored
+            .iter()
+            .filter_map(|c| c.route.fec.clone().map(|f| (f, c.chunk.data.to_vec())))
+            .collect();
+        if !fec::can_decode(&shards) {
+            return Ok(None);
+        }
+
+        // Size parity for the hop the group is about to cross, never giving
+        // it less protection than it arrived with
+        let route = stored[0].route.clone();
+        let loss = self.next_hop_loss(&route);
+        let inbound = &shards[0].0;
+        let parity = policy
+            .parity_for(inbound.data_shards, loss)
+            .max(inbound.parity_shards);
+        let reencoded = fec::reencode_group(&shards, parity)?;
+
+        for chunk in &stored {
+            self.storage.remove(&chunk.chunk_id);
+        }
+
+        let mut new_ids = Vec::with_capacity(reencoded.shards.len());
+        for (info, data) in reencoded.shards {
+            let id = info.chunk_id();
+            let chunk = reencoded_chunk(&stored, &info, data);
+            let mut shard_route = route.clone();
+            shard_route.fec = Some(info);
+            // Shards replace the group's own chunks, already within quota
+            self.storage
+                .store_replacement(id.clone(), shard_route, chunk)?;
+            new_ids.push(id);
+        }
+
+        self.reencoded_groups
+            .lock()
+            .insert(group_id.to_string(), Instant::now());
+        self.stats.hop_fec_groups.fetch_add(1, Ordering::Relaxed);
+        self.stats
+            .hop_fec_repairs
+            .fetch_add(reencoded.repaired as u64, Ordering::Relaxed);
+
+        Ok(Some(new_ids))
+    }
+
+    /// Whether `group_id` was re-encoded here within the chunk hold time
+    fn was_reencoded(&self, group_id: &str) -> bool {
+        self.reencoded_groups
+            .lock()
+            .get(group_id)
+            .is_some_and(|at| at.elapsed() < self.config.max_hold_time)
+    }
+
+    /// Record the loss rate observed towards a next-hop address
+    ///
+    /// Sends over the node's link keep this up to date; this overrides it,
+    /// e.g. with a rate measured out of band.
+    pub fn record_hop_loss(&self, addr: SocketAddr, loss_rate: f32) {
+        self.hop_loss
+            .write()
+            .insert(addr, loss_rate.clamp(0.0, 1.0));
+    }
+
+    /// Loss rate observed towards `addr` (0.0 if unknown)
+    pub fn observed_loss(&self, addr: SocketAddr) -> f32 {
+        self.hop_loss.read().get(&addr).copied().unwrap_or(0.0)
+    }
+
+    /// Worst loss rate among the hops a chunk on `route` may be sent over
+    fn next_hop_loss(&self, route: &RouteInfo) -> f32 {
+        let mut hops = Vec::new();
+        if self.policy.read().prefer_direct {
+            hops.push(route.destination);
+        }
+        hops.extend(
+            self.peers
+                .read()
+                .values()
+                .filter(|p| !route.hops.contains(&p.node_id))
+                .map(|p| p.addr),
+        );
+        hops.into_iter()
+            .map(|addr| self.observed_loss(addr))
+            .fold(0.0, f32::max)
+    }
+
+    /// Send `message` to `addr` over the link, folding the outcome into
+    /// the hop's loss rate
+    ///
+    /// A refusal still means the message got there, so only network
+    /// failures count as loss.
+    async fn send_to(
+        &self,
+        addr: SocketAddr,
+        message: RelayMessage,
+    ) -> RelayResult<Option<RelayMessage>> {
+        let Some(link) = &self.link else {
+            return Err(RelayError::NoRoute(addr.to_string()));
+        };
+        let result = link.send(addr, message).await;
+        let lost = if matches!(result, Err(RelayError::Network(_))) {
+            1.0
+        } else {
+            0.0
+        };
+        let mut hop_loss = self.hop_loss.write();
+        let loss = hop_loss.entry(addr).or_insert(lost);
+        *loss += (lost - *loss) * LOSS_SMOOTHING;
+        if let Err(e) = &result {
+            tracing::debug!(node_id = %self.config.node_id, %addr, "send failed: {}", e);
+        }
+        result
+    }
+
+    /// Chunks held for `destination`, grouped by transfer
+    pub fn available_for(&self, destination: SocketAddr) -> Vec<AvailableChunks> {
+        let mut by_transfer: BTreeMap<String, AvailableChunks> = BTreeMap::new();
+        for chunk in self.storage.get_for_destination(&destination.to_string()) {
+            let entry = by_transfer
+                .entry(chunk.route.transfer_id.clone())
+                .or_insert_with(|| AvailableChunks {
+                    transfer_id: chunk.route.transfer_id.clone(),
+                    chunk_ids: Vec::new(),
+                    bytes: 0,
+                });
+            entry.bytes += chunk.size() as u64;
+            entry.chunk_ids.push(chunk.chunk_id);
+        }
+
+        by_transfer
+            .into_values()
+            .map(|mut transfer| {
+                transfer.chunk_ids.sort();
+                transfer
+            })
+            .collect()
+    }
+
+    /// Hand over chunks a receiver pulled, removing them from storage
+    ///
+    /// Ids that are unknown, expired or addressed to another destination are
+    /// skipped, so a receiver can only drain its own chunks.
+    pub async fn deliver_to(
+        &self,
+        destination: SocketAddr,
+        chunk_ids: &[String],
+    ) -> Vec<PulledChunk> {
+        let mut delivered = Vec::new();
+
+        for chunk_id in chunk_ids {
+            let chunk = match self.storage.get(chunk_id) {
+                Some(c) if c.route.destination == destination && !c.is_expired() => c,
+                _ => continue,
+            };
+
+            self.storage.remove(chunk_id);
+            self.record_delivered(&chunk);
+            self.stats.record_forward(destination, chunk.stored_at);
+            self.stats.chunks_forwarded.fetch_add(1, Ordering::Relaxed);
+            self.stats.chunks_pulled.fetch_add(1, Ordering::Relaxed);
+            self.stats
+                .bytes_forwarded
+                .fetch_add(chunk.size() as u64, Ordering::Relaxed);
+
+            self.emit_event(RelayEvent::ChunkForwarded {
+                chunk_id: chunk.chunk_id.clone(),
+                destination,
+                trace_id: chunk.trace_id(),
+            })
+            .await;
+            self.release_replicas(chunk_id).await;
+
+            delivered.push(PulledChunk {
+                chunk_id: chunk.chunk_id,
+                route: chunk.route,
+                chunk: chunk.chunk,
+            });
+        }
+
+        delivered
+    }
+
+    /// What this relay holds of a transfer and has already delivered
+    pub fn holdings(&self, transfer_id: &str) -> TransferHoldings {
+        TransferHoldings {
+            node_id: self.config.node_id.clone(),
+            transfer_id: transfer_id.to_string(),
+            held: self.storage.sequences_for_transfer(transfer_id),
+            delivered: self.delivered.read().sequences(transfer_id),
+        }
+    }
+
+    fn record_delivered(&self, chunk: &StoredChunk) {
+        self.delivered.write().record(
+            &chunk.route.transfer_id,
+            chunk.chunk.metadata.sequence_number,
+        );
+    }
+
     /// Try to forward a specific chunk
+    ///
+    /// Without a link the chunk stays put, and the attempt doesn't count
+    /// towards its retries.
     pub async fn try_forward_chunk(&self, chunk_id: &str) -> RelayResult<bool> {
         let chunk = match self.storage.get(chunk_id) {
             Some(c) => c,
             None => return Err(RelayError::ChunkNotFound(chunk_id.to_string())),
         };
+        if self.link.is_none() {
+            return Ok(false);
+        }
 
-        // Try direct delivery first if policy prefers it
-        if self.config.policy.prefer_direct {
-            if self.try_direct_delivery(&chunk).await? {
-                return Ok(true);
+        // Critical chunks are flooded while their hop budget lasts
+        let flood = self.policy.read().flood.clone();
+        if let Some(flood) = flood {
+            if chunk.route.is_critical() && !chunk.route.replica {
+                let remaining = chunk.route.flood_ttl.unwrap_or(flood.ttl);
+                if remaining > 0 {
+                    let delivered = self.flood_chunk(&chunk, remaining, &flood).await;
+                    if !delivered {
+                        self.storage.record_attempt(chunk_id);
+                    }
+                    return Ok(delivered);
+                }
             }
         }
 
+        // Try direct delivery first if policy prefers it
+        if self.policy.read().prefer_direct && self.try_direct_delivery(&chunk).await? {
+            return Ok(true);
+        }
+
         // Try relay through peers
         if self.try_relay_delivery(&chunk).await? {
             return Ok(true);
@@ -206,11 +1015,15 @@ impl RelayNode {
     ) -> RelayResult<bool> {
         let destination = chunk.route.destination;
 
-        // In a real implementation, this would attempt QUIC connection
-        // For now, we simulate the attempt
-        let success = self.simulate_connection(destination).await;
-
-        if success {
+        let handover = RelayMessage::Deliver {
+            chunks: vec![PulledChunk {
+                chunk_id: chunk.chunk_id.clone(),
+                route: chunk.route.clone(),
+                chunk: chunk.chunk.clone(),
+            }],
+        };
+        if self.send_to(destination, handover).await.is_ok() {
+            self.stats.record_forward(destination, chunk.stored_at);
             self.stats.chunks_forwarded.fetch_add(1, Ordering::Relaxed);
             self.stats
                 .bytes_forwarded
@@ -218,12 +1031,15 @@ impl RelayNode {
 
             // Remove from storage
             self.storage.remove(&chunk.chunk_id);
+            self.record_delivered(chunk);
 
             self.emit_event(RelayEvent::ChunkForwarded {
                 chunk_id: chunk.chunk_id.clone(),
                 destination,
+                trace_id: chunk.trace_id(),
             })
             .await;
+            self.release_replicas(&chunk.chunk_id).await;
 
             return Ok(true);
         }
@@ -236,10 +1052,8 @@ impl RelayNode {
         &self,
         chunk: &crate::relay::storage::StoredChunk,
     ) -> RelayResult<bool> {
-        let peers = self.peers.read();
-
-        // Sort peers by priority
-        let mut peer_list: Vec<_> = peers.values().collect();
+        // Snapshot peers so the lock isn't held across awaits
+        let mut peer_list: Vec<PeerInfo> = self.peers.read().values().cloned().collect();
         peer_list.sort_by_key(|p| p.priority);
 
         for peer in peer_list {
@@ -249,7 +1063,7 @@ impl RelayNode {
             }
 
             // Try to forward to this peer
-            if self.forward_to_peer(peer, chunk).await? {
+            if self.forward_to_peer(&peer, chunk).await? {
                 return Ok(true);
             }
         }
@@ -263,10 +1077,14 @@ impl RelayNode {
         peer: &PeerInfo,
         chunk: &crate::relay::storage::StoredChunk,
     ) -> RelayResult<bool> {
-        // In a real implementation, this would send via QUIC
-        let success = self.simulate_connection(peer.addr).await;
-
-        if success {
+        let store = self.store_message(
+            chunk.chunk_id.clone(),
+            chunk.route.clone(),
+            chunk.chunk.clone(),
+        );
+        if self.send_to(peer.addr, store).await.is_ok() {
+            self.touch_peer(&peer.node_id);
+            self.stats.record_forward(peer.addr, chunk.stored_at);
             self.stats.chunks_forwarded.fetch_add(1, Ordering::Relaxed);
             self.stats
                 .bytes_forwarded
@@ -278,22 +1096,159 @@ impl RelayNode {
             self.emit_event(RelayEvent::ChunkForwarded {
                 chunk_id: chunk.chunk_id.clone(),
                 destination: peer.addr,
+                trace_id: chunk.trace_id(),
             })
             .await;
 
+            // Replicas now live on independently of this node
+            self.replicas.write().remove(&chunk.chunk_id);
+
             return Ok(true);
         }
 
         Ok(false)
     }
 
-    /// Simulate a connection attempt (placeholder for real QUIC)
-    async fn simulate_connection(&self, _addr: SocketAddr) -> bool {
-        // In tests, always succeed
-        // In real implementation, attempt actual connection
+    /// Send a chunk to its destination and every peer not yet on its route
+    ///
+    /// Returns whether any copy went out.
+    async fn flood_chunk(&self, chunk: &StoredChunk, remaining: u8, flood: &FloodPolicy) -> bool {
+        self.note_flooded(&chunk.chunk_id, flood.suppression_window);
+
+        let destination = chunk.route.destination;
+        let mut targets = vec![destination];
+        let mut peer_list: Vec<PeerInfo> = self.peers.read().values().cloned().collect();
+        peer_list.sort_by_key(|p| p.priority);
+        peer_list.retain(|p| !chunk.route.hops.contains(&p.node_id) && p.addr != destination);
+        targets.extend(peer_list.iter().map(|p| p.addr));
+
+        // Peers flood on what's left of the hop budget
+        let mut route = chunk.route.clone();
+        route.flood_ttl = Some(remaining - 1);
+
+        let mut reached_destination = false;
+        let mut copies = 0u64;
+        for (i, addr) in targets.into_iter().enumerate() {
+            if i == 0 {
+                let handover = RelayMessage::Deliver {
+                    chunks: vec![PulledChunk {
+                        chunk_id: chunk.chunk_id.clone(),
+                        route: chunk.route.clone(),
+                        chunk: chunk.chunk.clone(),
+                    }],
+                };
+                if self.send_to(addr, handover).await.is_err() {
+                    continue;
+                }
+                reached_destination = true;
+            } else {
+                let store =
+                    self.store_message(chunk.chunk_id.clone(), route.clone(), chunk.chunk.clone());
+                let Ok(Some(RelayMessage::Ack { .. })) = self.send_to(addr, store).await else {
+                    continue;
+                };
+                self.touch_peer(&peer_list[i - 1].node_id);
+                copies += 1;
+            }
+            self.stats.record_forward(addr, chunk.stored_at);
+            self.stats
+                .bytes_forwarded
+                .fetch_add(chunk.size() as u64, Ordering::Relaxed);
+        }
+
+        if !reached_destination && copies == 0 {
+            return false;
+        }
+
+        tracing::debug!(
+            node_id = %self.config.node_id,
+            chunk_id = %chunk.chunk_id,
+            remaining,
+            copies,
+            "flooded critical chunk"
+        );
+        self.stats.chunks_forwarded.fetch_add(1, Ordering::Relaxed);
+        self.stats.chunks_flooded.fetch_add(1, Ordering::Relaxed);
+        self.stats
+            .flood_copies_sent
+            .fetch_add(copies, Ordering::Relaxed);
+
+        self.storage.remove(&chunk.chunk_id);
+        self.emit_event(RelayEvent::ChunkForwarded {
+            chunk_id: chunk.chunk_id.clone(),
+            destination,
+            trace_id: chunk.trace_id(),
+        })
+        .await;
+
+        if reached_destination {
+            self.record_delivered(chunk);
+            self.release_replicas(&chunk.chunk_id).await;
+        } else {
+            // Replicas now live on independently of this node
+            self.replicas.write().remove(&chunk.chunk_id);
+        }
+        true
+    }
+
+    /// Remember a flooded chunk id, forgetting those older than `window`
+    ///
+    /// Returns false if the id was already remembered.
+    fn note_flooded(&self, chunk_id: &str, window: Duration) -> bool {
+        let now = Instant::now();
+        let mut flooded = self.flooded.lock();
+        flooded.retain(|_, seen| now.duration_since(*seen) < window);
+        if flooded.contains_key(chunk_id) {
+            return false;
+        }
+        flooded.insert(chunk_id.to_string(), now);
         true
     }
 
+    /// Tell the origin of a chunk this relay is dropping
+    async fn notify_origin(&self, chunk_id: &str, route: &RouteInfo, reason: ExpiryReason) {
+        tracing::debug!(
+            node_id = %self.config.node_id,
+            chunk_id,
+            transfer_id = %route.transfer_id,
+            ?reason,
+            "dropping chunk, notifying origin"
+        );
+        let notice = ExpiredNotice::new(chunk_id, route, &self.config.node_id, reason);
+        self.pass_notice(notice).await;
+    }
+
+    /// Send an expiry notice one hop closer to its origin
+    ///
+    /// Relays on the return path this node doesn't know are skipped. Once
+    /// no relay is left, the notice is handed to this node's event listener,
+    /// which is the origin's coordinator when this is its first hop.
+    async fn pass_notice(&self, mut notice: ExpiredNotice) {
+        while !notice.return_path.is_empty() {
+            let next = notice.return_path.remove(0);
+            let addr = self.peers.read().get(&next).map(|p| p.addr);
+            let Some(addr) = addr else {
+                continue;
+            };
+            let expired = RelayMessage::Expired {
+                notice: notice.clone(),
+            };
+            if self.send_to(addr, expired).await.is_ok() {
+                self.touch_peer(&next);
+                self.stats
+                    .expiry_notices_sent
+                    .fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+        }
+
+        self.stats
+            .expiry_notices_sent
+            .fetch_add(1, Ordering::Relaxed);
+        self.emit_event(RelayEvent::ChunkUndeliverable { notice })
+            .await;
+    }
+
     /// Add a peer
     pub fn add_peer(&self, peer: PeerInfo) {
         self.peers.write().insert(peer.node_id.clone(), peer);
@@ -309,34 +1264,297 @@ impl RelayNode {
         self.peers.read().values().cloned().collect()
     }
 
-    /// Run cleanup and forwarding cycle
-    pub async fn maintenance_cycle(&self) {
-        // Clean up expired chunks
-        let expired = self.storage.cleanup_expired();
-        for chunk_id in expired {
-            self.stats.chunks_expired.fetch_add(1, Ordering::Relaxed);
-            self.emit_event(RelayEvent::ChunkExpired { chunk_id }).await;
+    /// Mark a known peer as just contacted
+    fn touch_peer(&self, node_id: &str) {
+        if let Some(peer) = self.peers.write().get_mut(node_id) {
+            peer.touch();
         }
+    }
 
-        // Try to forward pending chunks
-        let pending = self
-            .storage
-            .get_pending(100, self.config.policy.retry_cooldown);
-
-        for chunk in pending {
+    /// Add a peer heard of through another relay, keeping the most recent
+    /// sighting when it is already known
+    ///
+    /// Keys are only pinned from a peer's own signed `Hello`, never from
+    /// another relay's say-so.
+    fn learn_peer(&self, mut peer: PeerInfo) {
+        peer.public_key = None;
+        let seen = *peer
+            .last_seen
+            .get_or_insert_with(|| chrono::Utc::now().timestamp());
+        let mut peers = self.peers.write();
+        match peers.get_mut(&peer.node_id) {
+            Some(known) => {
+                known.addr = peer.addr;
+                known.last_seen = known.last_seen.max(Some(seen));
+            }
+            None => {
+                peers.insert(peer.node_id.clone(), peer);
+            }
+        }
+    }
+
+    /// Check a `Hello` against the access policy and pin the sender's key
+    fn accept_hello(
+        &self,
+        node_id: String,
+        addr: SocketAddr,
+        proof: Option<HelloProof>,
+    ) -> RelayResult<()> {
+        let access = &self.config.access;
+        let pinned = self.peers.read().get(&node_id).and_then(|p| p.public_key);
+        let now = chrono::Utc::now().timestamp();
+        let rejection = match &proof {
+            None if pinned.is_some() => Some("unsigned Hello for a node with a pinned key"),
+            None if !access.permits_unsigned(&node_id) => Some("unsigned Hello"),
+            None => None,
+            Some(proof) if !proof.verify(&node_id, addr, now) => Some("bad Hello signature"),
+            Some(proof) if pinned.is_some_and(|key| key != proof.public_key) => {
+                Some("Hello key doesn't match the pinned key")
+            }
+            Some(proof) if !access.permits(&node_id, &proof.public_key) => Some("node not allowed"),
+            Some(_) => None,
+        };
+        if let Some(reason) = rejection {
+            self.stats.rejected_hellos.fetch_add(1, Ordering::Relaxed);
+            tracing::warn!(node_id = %self.config.node_id, peer = %node_id, %addr, reason, "rejected Hello");
+            return Err(RelayError::Unauthorized(format!("{node_id}: {reason}")));
+        }
+
+        let mut peers = self.peers.write();
+        let peer = peers
+            .entry(node_id.clone())
+            .or_insert_with(|| PeerInfo::new(node_id, addr));
+        peer.addr = addr;
+        if let Some(proof) = proof {
+            peer.public_key = Some(proof.public_key);
+        }
+        peer.touch();
+        Ok(())
+    }
+
+    /// Check a `Store` against the access policy
+    async fn authorize_store(
+        &self,
+        auth: Option<&StoreAuth>,
+        chunk_id: &str,
+        route: &RouteInfo,
+        chunk: &Chunk,
+    ) -> RelayResult<()> {
+        let access = &self.config.access;
+        let rejection = match auth {
+            None if access.permits_unsigned(&route.source) => return Ok(()),
+            None => "unsigned store".to_string(),
+            Some(auth) => {
+                let key = self
+                    .peers
+                    .read()
+                    .get(&auth.node_id)
+                    .and_then(|p| p.public_key);
+                match key {
+                    None => format!("{} has not sent a signed Hello", auth.node_id),
+                    Some(key) if !auth.verify(&key, chunk_id, route, chunk) => {
+                        format!("bad store signature from {}", auth.node_id)
+                    }
+                    Some(key) if !access.permits(&auth.node_id, &key) => {
+                        format!("{} is not allowed to store", auth.node_id)
+                    }
+                    Some(_) => return Ok(()),
+                }
+            }
+        };
+
+        self.stats
+            .unauthorized_stores
+            .fetch_add(1, Ordering::Relaxed);
+        tracing::warn!(node_id = %self.config.node_id, chunk_id, reason = %rejection, "rejected store");
+        self.emit_event(RelayEvent::StoreRejected {
+            chunk_id: chunk_id.to_string(),
+            node_id: auth.map(|a| a.node_id.clone()),
+            reason: rejection.clone(),
+        })
+        .await;
+        Err(RelayError::Unauthorized(rejection))
+    }
+
+    /// Drop learned peers not seen within `peer_expiry`
+    ///
+    /// Returns the ids of the removed peers.
+    pub fn expire_peers(&self) -> Vec<String> {
+        if self.config.peer_expiry.is_zero() {
+            return Vec::new();
+        }
+        let now = chrono::Utc::now().timestamp();
+        let configured: HashSet<&str> = self
+            .config
+            .peers
+            .iter()
+            .map(|p| p.node_id.as_str())
+            .collect();
+
+        let mut peers = self.peers.write();
+        let stale: Vec<String> = peers
+            .values()
+            .filter(|p| {
+                !configured.contains(p.node_id.as_str()) && p.is_stale(self.config.peer_expiry, now)
+            })
+            .map(|p| p.node_id.clone())
+            .collect();
+        for node_id in &stale {
+            peers.remove(node_id);
+        }
+        stale
+    }
+
+    /// Save the peer table and cumulative stats under `persistence_path`
+    ///
+    /// Runs at the end of every maintenance cycle; call it on shutdown to
+    /// keep counts since the last cycle.
+    pub fn save_state(&self) -> RelayResult<()> {
+        let Some(dir) = &self.config.persistence_path else {
+            return Ok(());
+        };
+        let state = NodeState {
+            peers: self.get_peers(),
+            stats: self.stats(),
+        };
+        save_json(&dir.join(STATE_FILE), &state)
+    }
+
+    /// Run maintenance every `forward_interval` plus up to
+    /// `forward_jitter`, until `stop` is called or the node is dropped
+    ///
+    /// Does nothing if the scheduler is already running.
+    pub fn start(self: &Arc<Self>) {
+        if self.running.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let stop = Arc::new(Notify::new());
+        let handle = tokio::spawn(run_scheduler(
+            Arc::downgrade(self),
+            self.config.forward_interval,
+            self.config.forward_jitter,
+            stop.clone(),
+        ));
+        *self.scheduler.lock() = Some(Scheduler { stop, handle });
+        tracing::info!(
+            node_id = %self.config.node_id,
+            interval_ms = self.config.forward_interval.as_millis() as u64,
+            jitter_ms = self.config.forward_jitter.as_millis() as u64,
+            "relay maintenance scheduler started"
+        );
+    }
+
+    /// Stop the maintenance scheduler, letting a cycle in progress finish
+    pub async fn stop(&self) {
+        let scheduler = self.scheduler.lock().take();
+        if let Some(scheduler) = scheduler {
+            scheduler.stop.notify_one();
+            let _ = scheduler.handle.await;
+        }
+        self.running.store(false, Ordering::SeqCst);
+    }
+
+    /// Whether the maintenance scheduler is running
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::SeqCst)
+    }
+
+    /// Run cleanup and forwarding cycle, waiting for one already in
+    /// progress to finish first
+    pub async fn maintenance_cycle(&self) {
+        let _guard = self.cycle_lock.lock().await;
+        self.run_cycle().await;
+    }
+
+    /// Scheduled cycle; skipped rather than queued behind a slow one
+    async fn scheduled_cycle(&self) {
+        match self.cycle_lock.try_lock() {
+            Ok(_guard) => self.run_cycle().await,
+            Err(_) => {
+                self.stats
+                    .maintenance_skipped
+                    .fetch_add(1, Ordering::Relaxed);
+                tracing::debug!(node_id = %self.config.node_id, "maintenance cycle still running, skipping");
+            }
+        }
+    }
+
+    async fn run_cycle(&self) {
+        let started = Instant::now();
+
+        // Clean up expired chunks
+        let expired = self.storage.cleanup_expired();
+        for chunk in expired {
+            self.stats.chunks_expired.fetch_add(1, Ordering::Relaxed);
+            self.replicas.write().remove(&chunk.chunk_id);
+            self.notify_origin(&chunk.chunk_id, &chunk.route, ExpiryReason::Expired)
+                .await;
+            self.emit_event(RelayEvent::ChunkExpired {
+                chunk_id: chunk.chunk_id,
+            })
+            .await;
+        }
+
+        // Late shards of groups re-encoded that long ago have expired too
+        let hold_time = self.config.max_hold_time;
+        self.reencoded_groups
+            .lock()
+            .retain(|_, at| at.elapsed() < hold_time);
+
+        // Try to forward pending chunks under the policy in effect now
+        let cooldown = self.policy.read().retry_cooldown;
+        let pending = self.storage.get_pending(100, cooldown);
+
+        for chunk in pending {
             if chunk.forward_attempts < self.config.max_forward_retries {
                 let _ = self.try_forward_chunk(&chunk.chunk_id).await;
             } else {
                 // Max retries exceeded, drop chunk
                 self.storage.remove(&chunk.chunk_id);
+                self.replicas.write().remove(&chunk.chunk_id);
                 self.stats.chunks_dropped.fetch_add(1, Ordering::Relaxed);
+                self.notify_origin(
+                    &chunk.chunk_id,
+                    &chunk.route,
+                    ExpiryReason::RetriesExhausted,
+                )
+                .await;
+            }
+        }
+
+        for node_id in self.expire_peers() {
+            tracing::info!(node_id = %self.config.node_id, peer = %node_id, "stale peer aged out");
+            self.emit_event(RelayEvent::PeerDisconnected { node_id })
+                .await;
+        }
+
+        let threshold = self.config.compaction_threshold;
+        if threshold > 0 && self.storage.stats().loose_files >= threshold {
+            match self.storage.compact() {
+                Ok(report) => tracing::info!(
+                    node_id = %self.config.node_id,
+                    chunks = report.chunks_packed,
+                    segments = report.segments_written,
+                    files_removed = report.files_removed,
+                    "compacted relay storage"
+                ),
+                Err(e) => {
+                    tracing::warn!(node_id = %self.config.node_id, "relay storage compaction failed: {}", e)
+                }
             }
         }
+
+        self.stats.record_cycle(started.elapsed());
+
+        if let Err(e) = self.save_state() {
+            tracing::warn!(node_id = %self.config.node_id, "failed to save relay state: {}", e);
+        }
     }
 
     /// Get current statistics
     pub fn stats(&self) -> RelayStats {
         let storage_stats = self.storage.stats();
+        let (forward_latency, destination_latency) = self.stats.latency();
 
         RelayStats {
             chunks_received: self.stats.chunks_received.load(Ordering::Relaxed),
@@ -348,7 +1566,31 @@ impl RelayNode {
             storage_used: storage_stats.used_bytes,
             stored_chunks: storage_stats.total_chunks,
             active_peers: self.peers.read().len() as u64,
-            avg_forward_latency_ms: 0, // Would need timing tracking
+            avg_forward_latency_ms: forward_latency.avg_ms(),
+            forward_latency,
+            destination_latency,
+            hop_fec_groups: self.stats.hop_fec_groups.load(Ordering::Relaxed),
+            hop_fec_repairs: self.stats.hop_fec_repairs.load(Ordering::Relaxed),
+            chunks_pulled: self.stats.chunks_pulled.load(Ordering::Relaxed),
+            replicas_created: self.stats.replicas_created.load(Ordering::Relaxed),
+            duplicates_discarded: self.stats.duplicates_discarded.load(Ordering::Relaxed),
+            chunks_flooded: self.stats.chunks_flooded.load(Ordering::Relaxed),
+            flood_copies_sent: self.stats.flood_copies_sent.load(Ordering::Relaxed),
+            flood_duplicates_suppressed: self
+                .stats
+                .flood_duplicates_suppressed
+                .load(Ordering::Relaxed),
+            expiry_notices_sent: self.stats.expiry_notices_sent.load(Ordering::Relaxed),
+            unauthorized_stores: self.stats.unauthorized_stores.load(Ordering::Relaxed),
+            rejected_hellos: self.stats.rejected_hellos.load(Ordering::Relaxed),
+            quota_rejections: self.stats.quota_rejections.load(Ordering::Relaxed),
+            quota_evictions: self.stats.quota_evictions.load(Ordering::Relaxed),
+            corrupt_chunks: self.stats.corrupt_chunks.load(Ordering::Relaxed),
+            maintenance_cycles: self.stats.maintenance_cycles.load(Ordering::Relaxed),
+            maintenance_skipped: self.stats.maintenance_skipped.load(Ordering::Relaxed),
+            last_cycle_ms: self.stats.last_cycle_ms.load(Ordering::Relaxed),
+            max_cycle_ms: self.stats.max_cycle_ms.load(Ordering::Relaxed),
+            total_cycle_ms: self.stats.total_cycle_ms.load(Ordering::Relaxed),
         }
     }
 
@@ -357,15 +1599,28 @@ impl RelayNode {
         self.storage.stats()
     }
 
+    /// What the startup scan of `persistence_path` found
+    pub fn storage_scan(&self) -> Option<ScanReport> {
+        self.storage.scan_report()
+    }
+
+    /// Pack stored chunk files into segments now
+    pub fn compact_storage(&self) -> RelayResult<CompactionReport> {
+        self.storage.compact()
+    }
+
     /// Handle incoming relay message
     pub async fn handle_message(&self, message: RelayMessage) -> RelayResult<Option<RelayMessage>> {
         match message {
             RelayMessage::Store {
                 chunk_id,
                 route,
-                data,
+                chunk,
+                auth,
             } => {
-                self.receive_chunk(chunk_id.clone(), route, data).await?;
+                self.authorize_store(auth.as_ref(), &chunk_id, &route, &chunk)
+                    .await?;
+                self.receive_chunk(chunk_id.clone(), route, chunk).await?;
                 Ok(Some(RelayMessage::Ack {
                     chunk_id,
                     node_id: self.config.node_id.clone(),
@@ -381,9 +1636,12 @@ impl RelayNode {
                 }))
             }
 
-            RelayMessage::Hello { node_id, addr } => {
-                let peer = PeerInfo::new(node_id, addr);
-                self.add_peer(peer);
+            RelayMessage::Hello {
+                node_id,
+                addr,
+                proof,
+            } => {
+                self.accept_hello(node_id, addr, proof)?;
                 Ok(Some(RelayMessage::PeerList {
                     peers: self.get_peers(),
                 }))
@@ -392,13 +1650,69 @@ impl RelayNode {
             RelayMessage::PeerList { peers } => {
                 for peer in peers {
                     if peer.node_id != self.config.node_id {
-                        self.add_peer(peer);
+                        self.learn_peer(peer);
                     }
                 }
                 Ok(None)
             }
 
-            RelayMessage::Ack { .. } | RelayMessage::Status { .. } => Ok(None),
+            RelayMessage::QueryDestination { destination } => Ok(Some(RelayMessage::Available {
+                node_id: self.config.node_id.clone(),
+                transfers: self.available_for(destination),
+            })),
+
+            RelayMessage::Pull {
+                destination,
+                chunk_ids,
+            } => Ok(Some(RelayMessage::Deliver {
+                chunks: self.deliver_to(destination, &chunk_ids).await,
+            })),
+
+            RelayMessage::UpdatePolicy { update } => {
+                let policy = if update.is_empty() {
+                    self.policy()
+                } else {
+                    self.update_policy(&update)?
+                };
+                Ok(Some(RelayMessage::Policy { policy }))
+            }
+
+            RelayMessage::Delivered { chunk_id } => {
+                if self.storage.remove(&chunk_id).is_some() {
+                    self.stats
+                        .duplicates_discarded
+                        .fetch_add(1, Ordering::Relaxed);
+                }
+                Ok(None)
+            }
+
+            RelayMessage::Expired { notice } => {
+                self.pass_notice(notice).await;
+                Ok(None)
+            }
+
+            RelayMessage::QueryUsage => Ok(Some(RelayMessage::Usage {
+                node_id: self.config.node_id.clone(),
+                destinations: self.destination_usage(),
+            })),
+
+            RelayMessage::QueryTransfer { transfer_id } => Ok(Some(RelayMessage::Holdings {
+                holdings: self.holdings(&transfer_id),
+            })),
+
+            RelayMessage::QueryStats => Ok(Some(RelayMessage::Stats {
+                node_id: self.config.node_id.clone(),
+                stats: self.stats(),
+            })),
+
+            RelayMessage::Ack { .. }
+            | RelayMessage::Status { .. }
+            | RelayMessage::Available { .. }
+            | RelayMessage::Deliver { .. }
+            | RelayMessage::Policy { .. }
+            | RelayMessage::Usage { .. }
+            | RelayMessage::Holdings { .. }
+            | RelayMessage::Stats { .. } => Ok(None),
         }
     }
 
@@ -410,6 +1724,83 @@ impl RelayNode {
     }
 }
 
+/// Drive `maintenance_cycle` on `node` until stopped or the node is dropped
+async fn run_scheduler(
+    node: Weak<RelayNode>,
+    interval: Duration,
+    jitter: Duration,
+    stop: Arc<Notify>,
+) {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let mut rng = StdRng::from_entropy();
+    loop {
+        let delay = rng.gen_range(Duration::ZERO..=jitter);
+        tokio::select! {
+            _ = stop.notified() => break,
+            _ = async {
+                ticker.tick().await;
+                tokio::time::sleep(delay).await;
+            } => {}
+        }
+        let Some(node) = node.upgrade() else { break };
+        node.scheduled_cycle().await;
+    }
+}
+
+/// A re-encoded shard as a chunk, with metadata taken from the stored
+/// shard it replaces (or any shard of the group, for new parity)
+fn reencoded_chunk(stored: &[StoredChunk], info: &FecShardInfo, data: Vec<u8>) -> Chunk {
+    let template = stored
+        .iter()
+        .find(|c| {
+            c.route
+                .fec
+                .as_ref()
+                .is_some_and(|f| f.shard_index == info.shard_index)
+        })
+        .unwrap_or(&stored[0]);
+    let mut metadata = template.chunk.metadata.clone();
+    metadata.is_parity = info.is_parity();
+    metadata.data_size = data.len();
+    metadata.checksum = metadata.checksum_algorithm.digest(&data);
+    Chunk {
+        metadata,
+        data: data.into(),
+    }
+}
+
+/// Read a policy saved by [`RelayNode::update_policy`]
+fn load_policy(path: &Path) -> RelayResult<ForwardingPolicy> {
+    let data = std::fs::read(path)?;
+    serde_json::from_slice(&data)
+        .map_err(|e| RelayError::InvalidConfig(format!("{}: {}", path.display(), e)))
+}
+
+/// Save a policy, replacing the previous file atomically
+fn save_policy(path: &Path, policy: &ForwardingPolicy) -> RelayResult<()> {
+    save_json(path, policy)
+}
+
+/// Read the state saved by [`RelayNode::save_state`]; empty on first start
+fn load_state(path: &Path) -> RelayResult<NodeState> {
+    if !path.exists() {
+        return Ok(NodeState::default());
+    }
+    let data = std::fs::read(path)?;
+    serde_json::from_slice(&data)
+        .map_err(|e| RelayError::Storage(format!("{}: {}", path.display(), e)))
+}
+
+/// Write `value` as JSON, replacing the previous file atomically
+fn save_json<T: Serialize>(path: &Path, value: &T) -> RelayResult<()> {
+    let data = serde_json::to_vec_pretty(value).map_err(|e| RelayError::Storage(e.to_string()))?;
+    let tmp = path.with_extension("tmp");
+    std::fs::write(&tmp, data)?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
 /// Builder for relay nodes
 pub struct RelayNodeBuilder {
     config: RelayConfig,
@@ -452,6 +1843,46 @@ impl RelayNodeBuilder {
         self
     }
 
+    pub fn policy_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config.policy_path = Some(path.into());
+        self
+    }
+
+    pub fn persistence_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config.persistence_path = Some(path.into());
+        self
+    }
+
+    pub fn compaction_threshold(mut self, files: u64) -> Self {
+        self.config.compaction_threshold = files;
+        self
+    }
+
+    pub fn forward_interval(mut self, interval: Duration) -> Self {
+        self.config.forward_interval = interval;
+        self
+    }
+
+    pub fn forward_jitter(mut self, jitter: Duration) -> Self {
+        self.config.forward_jitter = jitter;
+        self
+    }
+
+    pub fn peer_expiry(mut self, expiry: Duration) -> Self {
+        self.config.peer_expiry = expiry;
+        self
+    }
+
+    pub fn quotas(mut self, quotas: DestinationQuotas) -> Self {
+        self.config.quotas = quotas;
+        self
+    }
+
+    pub fn access(mut self, access: AccessPolicy) -> Self {
+        self.config.access = access;
+        self
+    }
+
     pub fn build(self) -> RelayResult<RelayNode> {
         RelayNode::new(self.config)
     }
@@ -466,14 +1897,64 @@ impl Default for RelayNodeBuilder {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::relay::types::{test_chunk, QuotaBreach};
+    use futures::future::BoxFuture;
+
+    /// Link on which every node answers, except at addresses marked down
+    #[derive(Default)]
+    struct TestLink {
+        down: Mutex<HashSet<SocketAddr>>,
+        sent: Mutex<Vec<(SocketAddr, RelayMessage)>>,
+    }
+
+    impl TestLink {
+        fn take_down(&self, addr: SocketAddr) {
+            self.down.lock().insert(addr);
+        }
+
+        /// Addresses messages were delivered to, in order
+        fn sent_to(&self) -> Vec<SocketAddr> {
+            self.sent.lock().iter().map(|(addr, _)| *addr).collect()
+        }
+    }
+
+    impl RelayLink for TestLink {
+        fn send<'a>(
+            &'a self,
+            addr: SocketAddr,
+            message: RelayMessage,
+        ) -> BoxFuture<'a, RelayResult<Option<RelayMessage>>> {
+            Box::pin(async move {
+                if self.down.lock().contains(&addr) {
+                    return Err(RelayError::Network(format!("{addr} unreachable")));
+                }
+                let answer = match &message {
+                    RelayMessage::Store { chunk_id, .. } => Some(RelayMessage::Ack {
+                        chunk_id: chunk_id.clone(),
+                        node_id: addr.to_string(),
+                    }),
+                    _ => None,
+                };
+                self.sent.lock().push((addr, message));
+                Ok(answer)
+            })
+        }
+    }
+
+    /// `node` sending over a fresh [`TestLink`]
+    fn linked(node: RelayNode) -> (RelayNode, Arc<TestLink>) {
+        let link = Arc::new(TestLink::default());
+        (node.with_link(link.clone()), link)
+    }
 
     fn create_test_node() -> RelayNode {
-        RelayNodeBuilder::new()
+        let node = RelayNodeBuilder::new()
             .node_id("test-node")
             .listen_addr("127.0.0.1:9000".parse().unwrap())
             .max_storage(1024 * 1024)
             .build()
-            .unwrap()
+            .unwrap();
+        linked(node).0
     }
 
     #[tokio::test]
@@ -482,16 +1963,119 @@ mod tests {
 
         let route = RouteInfo::new("source", "127.0.0.1:8000".parse().unwrap(), "transfer-1", 1);
 
-        node.receive_chunk("chunk-1".into(), route, vec![1, 2, 3, 4])
+        node.receive_chunk("chunk-1".into(), route, test_chunk(vec![1, 2, 3, 4]))
             .await
             .unwrap();
 
         let stats = node.stats();
         assert_eq!(stats.chunks_received, 1);
-        // With simulate_connection returning true, it should be forwarded
         assert_eq!(stats.chunks_forwarded, 1);
     }
 
+    #[tokio::test]
+    async fn test_without_link_chunks_wait_to_be_pulled() {
+        let node = RelayNodeBuilder::new().node_id("unlinked").build().unwrap();
+        let dest: SocketAddr = "127.0.0.1:8000".parse().unwrap();
+        let route = RouteInfo::new("source", dest, "transfer-1", 1);
+        node.receive_chunk("chunk-1".into(), route, test_chunk(vec![1, 2, 3, 4]))
+            .await
+            .unwrap();
+        for _ in 0..5 {
+            node.maintenance_cycle().await;
+        }
+
+        let stats = node.stats();
+        assert_eq!(stats.chunks_forwarded, 0);
+        assert_eq!(stats.chunks_dropped, 0);
+        assert_eq!(node.storage.get("chunk-1").unwrap().forward_attempts, 0);
+        assert_eq!(node.deliver_to(dest, &["chunk-1".into()]).await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_hop_loss_follows_send_outcomes() {
+        let (node, link) = linked(
+            RelayNodeBuilder::new()
+                .node_id("lossy")
+                .add_peer(PeerInfo::new("peer-1", "127.0.0.1:9101".parse().unwrap()))
+                .build()
+                .unwrap(),
+        );
+        let dest: SocketAddr = "127.0.0.1:8000".parse().unwrap();
+        let peer: SocketAddr = "127.0.0.1:9101".parse().unwrap();
+        link.take_down(dest);
+
+        for i in 0..3 {
+            let route = RouteInfo::new("source", dest, "transfer-1", 1);
+            node.receive_chunk(format!("chunk-{i}"), route, test_chunk(vec![i; 4]))
+                .await
+                .unwrap();
+        }
+
+        // Every direct attempt failed and fell back to the peer
+        assert!(node.observed_loss(dest) > 0.9);
+        assert_eq!(node.observed_loss(peer), 0.0);
+        assert_eq!(link.sent_to(), vec![peer; 3]);
+        assert_eq!(node.stats().chunks_forwarded, 3);
+
+        // A reachable hop recovers as sends get through
+        link.down.lock().clear();
+        let before = node.observed_loss(dest);
+        let route = RouteInfo::new("source", dest, "transfer-1", 1);
+        node.receive_chunk("chunk-3".into(), route, test_chunk(vec![3; 4]))
+            .await
+            .unwrap();
+        assert!(node.observed_loss(dest) < before);
+    }
+
+    #[tokio::test]
+    async fn test_scheduler_runs_until_stopped() {
+        let node = Arc::new(
+            RelayNodeBuilder::new()
+                .node_id("scheduled")
+                .forward_interval(Duration::from_millis(20))
+                .forward_jitter(Duration::from_millis(5))
+                .policy(ForwardingPolicy {
+                    forward_immediately: false,
+                    ..Default::default()
+                })
+                .build()
+                .unwrap()
+                .with_link(Arc::new(TestLink::default())),
+        );
+        let route = RouteInfo::new("source", "127.0.0.1:8000".parse().unwrap(), "transfer-1", 1);
+        node.receive_chunk("chunk-1".into(), route, test_chunk(vec![1, 2, 3]))
+            .await
+            .unwrap();
+
+        node.start();
+        assert!(node.is_running());
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        node.stop().await;
+        assert!(!node.is_running());
+
+        let stats = node.stats();
+        assert!(stats.maintenance_cycles >= 2);
+        assert!(stats.max_cycle_ms >= stats.last_cycle_ms);
+        assert_eq!(stats.stored_chunks, 0);
+        assert_eq!(stats.chunks_forwarded, 1);
+
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert_eq!(node.stats().maintenance_cycles, stats.maintenance_cycles);
+    }
+
+    #[tokio::test]
+    async fn test_scheduled_cycle_skips_overlap() {
+        let node = create_test_node();
+        let guard = node.cycle_lock.lock().await;
+        node.scheduled_cycle().await;
+        drop(guard);
+        node.scheduled_cycle().await;
+
+        let stats = node.stats();
+        assert_eq!(stats.maintenance_skipped, 1);
+        assert_eq!(stats.maintenance_cycles, 1);
+    }
+
     #[tokio::test]
     async fn test_peer_management() {
         let node = create_test_node();
@@ -516,7 +2100,8 @@ mod tests {
         let message = RelayMessage::Store {
             chunk_id: "chunk-1".into(),
             route,
-            data: vec![1, 2, 3, 4],
+            chunk: test_chunk(vec![1, 2, 3, 4]),
+            auth: None,
         };
 
         let response = node.handle_message(message).await.unwrap();
@@ -524,6 +2109,122 @@ mod tests {
         assert!(matches!(response, Some(RelayMessage::Ack { .. })));
     }
 
+    #[tokio::test]
+    async fn test_trace_records_relay_hop() {
+        let dest: SocketAddr = "127.0.0.1:8000".parse().unwrap();
+        let (tx, mut rx) = mpsc::channel(16);
+        let node = RelayNodeBuilder::new()
+            .node_id("relay-a")
+            .policy(ForwardingPolicy {
+                forward_immediately: false,
+                ..Default::default()
+            })
+            .build()
+            .unwrap()
+            .with_events(tx);
+
+        let mut chunk = test_chunk(vec![1; 16]);
+        chunk.metadata.trace = Some(crate::chunk::ChunkTrace::new("session-1"));
+        chunk
+            .metadata
+            .record_hop("sender", crate::chunk::HopStage::Sent);
+        let route = RouteInfo::new("sender", dest, "transfer-1", 1);
+        node.receive_chunk("chunk-1".into(), route, chunk)
+            .await
+            .unwrap();
+
+        let stored = node.storage.get("chunk-1").unwrap();
+        let trace = stored.chunk.metadata.trace.unwrap();
+        assert_eq!(
+            trace.to_string(),
+            "session-1: sender(Sent) -> relay-a(Relayed)"
+        );
+        assert!(matches!(
+            rx.try_recv().unwrap(),
+            RelayEvent::ChunkStored { trace_id: Some(id), .. } if id == "session-1"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_corrupt_chunk_is_dropped_at_the_hop() {
+        let dest: SocketAddr = "127.0.0.1:8000".parse().unwrap();
+        let (tx, mut rx) = mpsc::channel(16);
+        let node = create_test_node().with_events(tx);
+        let route = RouteInfo::new("sender", dest, "transfer-1", 1).with_sequence(7);
+
+        let mut chunk = test_chunk(vec![5; 16]);
+        chunk.data = vec![6; 16].into();
+        assert!(matches!(
+            node.receive_chunk("chunk-1".into(), route, chunk).await,
+            Err(RelayError::Corrupt(_))
+        ));
+        assert!(node.storage.get("chunk-1").is_none());
+
+        let stats = node.stats();
+        assert_eq!(stats.corrupt_chunks, 1);
+        assert_eq!(stats.chunks_dropped, 1);
+        let notice = std::iter::from_fn(|| rx.try_recv().ok())
+            .find_map(|event| match event {
+                RelayEvent::ChunkUndeliverable { notice } => Some(notice),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(notice.reason, ExpiryReason::Corrupted);
+        assert_eq!(notice.sequence_number, Some(7));
+    }
+
+    #[tokio::test]
+    async fn test_quota_eviction_notifies_origin() {
+        let dest: SocketAddr = "127.0.0.1:8000".parse().unwrap();
+        let (tx, mut rx) = mpsc::channel(16);
+        let node = RelayNodeBuilder::new()
+            .node_id("quota-node")
+            .policy(ForwardingPolicy {
+                forward_immediately: false,
+                ..Default::default()
+            })
+            .quotas(DestinationQuotas {
+                default_bytes: 8,
+                on_breach: QuotaBreach::EvictOldest,
+                ..Default::default()
+            })
+            .build()
+            .unwrap()
+            .with_events(tx);
+        let route = |seq| RouteInfo::new("sender", dest, "transfer-1", 2).with_sequence(seq);
+
+        for (seq, id) in ["chunk-0", "chunk-1", "chunk-2"].into_iter().enumerate() {
+            node.receive_chunk(id.into(), route(seq as u32), test_chunk(vec![0; 4]))
+                .await
+                .unwrap();
+        }
+        assert!(node
+            .receive_chunk("big".into(), route(3), test_chunk(vec![0; 9]))
+            .await
+            .is_err());
+
+        let Some(RelayMessage::Usage { destinations, .. }) =
+            node.handle_message(RelayMessage::QueryUsage).await.unwrap()
+        else {
+            panic!("expected a usage reply");
+        };
+        assert_eq!(destinations[0].bytes, 8);
+        assert_eq!(destinations[0].quota, Some(8));
+
+        let stats = node.stats();
+        assert_eq!(stats.quota_evictions, 1);
+        assert_eq!(stats.quota_rejections, 1);
+        let mut notices = Vec::new();
+        while let Ok(event) = rx.try_recv() {
+            if let RelayEvent::ChunkUndeliverable { notice } = event {
+                notices.push(notice);
+            }
+        }
+        assert_eq!(notices.len(), 1);
+        assert_eq!(notices[0].chunk_id, "chunk-0");
+        assert_eq!(notices[0].reason, ExpiryReason::QuotaEvicted);
+    }
+
     #[tokio::test]
     async fn test_handle_message_hello() {
         let node = create_test_node();
@@ -531,6 +2232,7 @@ mod tests {
         let message = RelayMessage::Hello {
             node_id: "peer-1".into(),
             addr: "192.168.1.100:9000".parse().unwrap(),
+            proof: None,
         };
 
         let response = node.handle_message(message).await.unwrap();
@@ -539,6 +2241,111 @@ mod tests {
         assert_eq!(node.get_peers().len(), 1);
     }
 
+    #[tokio::test]
+    async fn test_stores_need_an_allowed_signed_identity() {
+        let dest: SocketAddr = "127.0.0.1:8000".parse().unwrap();
+        let build = |id: &str, port: u16| {
+            RelayNodeBuilder::new()
+                .node_id(id)
+                .listen_addr(SocketAddr::from(([127, 0, 0, 1], port)))
+                .build()
+                .unwrap()
+        };
+        let trusted = build("trusted", 9101);
+        let stranger = build("stranger", 9102);
+        let (tx, mut rx) = mpsc::channel(16);
+        let relay = RelayNodeBuilder::new()
+            .node_id("field-relay")
+            .access(AccessPolicy {
+                require_auth: true,
+                allowlist: vec![trusted.public_key().to_string()],
+                ..Default::default()
+            })
+            .build()
+            .unwrap()
+            .with_events(tx);
+        let route = || RouteInfo::new("sender", dest, "transfer-1", 1);
+
+        // Unsigned, unknown key and unlisted key are all turned away
+        let unsigned = RelayMessage::Store {
+            chunk_id: "chunk-1".into(),
+            route: route(),
+            chunk: test_chunk(vec![1; 8]),
+            auth: None,
+        };
+        assert!(matches!(
+            relay.handle_message(unsigned).await,
+            Err(RelayError::Unauthorized(_))
+        ));
+        let early = trusted.store_message("chunk-1".into(), route(), test_chunk(vec![1; 8]));
+        assert!(relay.handle_message(early).await.is_err());
+        assert!(relay.handle_message(stranger.hello()).await.is_err());
+        let unsigned_hello = RelayMessage::Hello {
+            node_id: "stranger".into(),
+            addr: stranger.listen_addr(),
+            proof: None,
+        };
+        assert!(relay.handle_message(unsigned_hello).await.is_err());
+        let stranger_store =
+            stranger.store_message("chunk-2".into(), route(), test_chunk(vec![2; 8]));
+        assert!(relay.handle_message(stranger_store).await.is_err());
+
+        // The allowlisted node introduces itself, then can store
+        relay.handle_message(trusted.hello()).await.unwrap();
+        let reply = relay
+            .handle_message(trusted.store_message(
+                "chunk-1".into(),
+                route(),
+                test_chunk(vec![1; 8]),
+            ))
+            .await
+            .unwrap();
+        assert!(matches!(reply, Some(RelayMessage::Ack { .. })));
+
+        // A store whose data was changed in transit fails its signature
+        let RelayMessage::Store {
+            chunk_id,
+            route,
+            auth,
+            ..
+        } = trusted.store_message("chunk-3".into(), route(), test_chunk(vec![3; 8]))
+        else {
+            unreachable!()
+        };
+        let tampered = RelayMessage::Store {
+            chunk_id,
+            route,
+            chunk: test_chunk(vec![4; 8]),
+            auth,
+        };
+        assert!(relay.handle_message(tampered).await.is_err());
+
+        // Another node can't take over the trusted node's id
+        let impostor = RelayMessage::Hello {
+            node_id: "trusted".into(),
+            addr: stranger.listen_addr(),
+            proof: Some(
+                stranger
+                    .identity
+                    .sign_hello("trusted", stranger.listen_addr()),
+            ),
+        };
+        assert!(relay.handle_message(impostor).await.is_err());
+
+        let stats = relay.stats();
+        assert_eq!(stats.unauthorized_stores, 4);
+        assert_eq!(stats.rejected_hellos, 3);
+        assert_eq!(stats.chunks_received, 1);
+
+        let mut rejected = 0;
+        while let Ok(event) = rx.try_recv() {
+            if matches!(event, RelayEvent::StoreRejected { .. }) {
+                rejected += 1;
+            }
+        }
+        assert_eq!(rejected, 4);
+    }
+
     #[tokio::test]
     async fn test_ttl_enforcement() {
         let node = create_test_node();
@@ -550,12 +2357,594 @@ mod tests {
         route.ttl = 0;
 
         let result = node
-            .receive_chunk("chunk-1".into(), route, vec![1, 2, 3, 4])
+            .receive_chunk("chunk-1".into(), route, test_chunk(vec![1, 2, 3, 4]))
             .await;
 
         assert!(matches!(result, Err(RelayError::ChunkExpired(_))));
     }
 
+    #[tokio::test]
+    async fn test_dropped_chunk_notifies_origin() {
+        let (tx, mut rx) = mpsc::channel(8);
+        let (node, link) = linked(
+            RelayNodeBuilder::new()
+                .node_id("test-node")
+                .build()
+                .unwrap()
+                .with_events(tx),
+        );
+        let relay_1: SocketAddr = "192.168.1.100:9000".parse().unwrap();
+        node.add_peer(PeerInfo::new("relay-1", relay_1));
+
+        // The notice goes back to the previous relay
+        let mut route =
+            RouteInfo::new("sender", "127.0.0.1:8000".parse().unwrap(), "transfer-1", 1)
+                .with_sequence(4);
+        route.add_hop("relay-1");
+        route.ttl = 0;
+        assert!(node
+            .receive_chunk("chunk-4".into(), route, test_chunk(vec![1, 2, 3]))
+            .await
+            .is_err());
+        assert_eq!(node.stats().expiry_notices_sent, 1);
+        assert!(rx.try_recv().is_err());
+        match link.sent.lock().as_slice() {
+            [(addr, RelayMessage::Expired { notice })] => {
+                assert_eq!(*addr, relay_1);
+                assert_eq!(notice.sequence_number, Some(4));
+            }
+            other => panic!("expected one expiry notice, got {other:?}"),
+        }
+
+        // A notice whose remaining relays this node doesn't know ends here
+        let mut route =
+            RouteInfo::new("sender", "127.0.0.1:8000".parse().unwrap(), "transfer-1", 1)
+                .with_sequence(5);
+        route.add_hop("relay-0");
+        route.add_hop("test-node");
+        route.add_hop("relay-2");
+        let mut notice = ExpiredNotice::new("chunk-5", &route, "relay-2", ExpiryReason::Expired);
+        // As relay-2 sends it to us
+        assert_eq!(notice.return_path.remove(0), "test-node");
+        assert!(node
+            .handle_message(RelayMessage::Expired { notice })
+            .await
+            .unwrap()
+            .is_none());
+
+        match rx.recv().await {
+            Some(RelayEvent::ChunkUndeliverable { notice }) => {
+                assert_eq!(notice.sequence_number, Some(5));
+                assert_eq!(notice.dropped_by, "relay-2");
+                assert!(notice.return_path.is_empty());
+            }
+            other => panic!("expected an undeliverable chunk, got {other:?}"),
+        }
+        assert_eq!(node.stats().expiry_notices_sent, 2);
+
+        // With the previous relay unreachable, this node's listener is told
+        link.take_down(relay_1);
+        let mut route =
+            RouteInfo::new("sender", "127.0.0.1:8000".parse().unwrap(), "transfer-1", 1)
+                .with_sequence(6);
+        route.add_hop("relay-1");
+        route.ttl = 0;
+        assert!(node
+            .receive_chunk("chunk-6".into(), route, test_chunk(vec![1, 2, 3]))
+            .await
+            .is_err());
+        assert!(matches!(
+            rx.recv().await,
+            Some(RelayEvent::ChunkUndeliverable { notice }) if notice.sequence_number == Some(6)
+        ));
+        assert_eq!(node.stats().expiry_notices_sent, 3);
+        assert_eq!(link.sent.lock().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_query_and_pull_by_destination() {
+        let node = RelayNodeBuilder::new()
+            .node_id("pull-node")
+            .policy(ForwardingPolicy {
+                forward_immediately: false,
+                ..Default::default()
+            })
+            .build()
+            .unwrap();
+        let dest: SocketAddr = "127.0.0.1:8000".parse().unwrap();
+        let other: SocketAddr = "127.0.0.1:8001".parse().unwrap();
+
+        for (id, addr, transfer) in [
+            ("a-1", dest, "transfer-a"),
+            ("a-2", dest, "transfer-a"),
+            ("b-1", dest, "transfer-b"),
+            ("x-1", other, "transfer-x"),
+        ] {
+            let route = RouteInfo::new("source", addr, transfer, 1);
+            node.receive_chunk(id.into(), route, test_chunk(vec![0u8; 10]))
+                .await
+                .unwrap();
+        }
+
+        let reply = node
+            .handle_message(RelayMessage::QueryDestination { destination: dest })
+            .await
+            .unwrap();
+        let transfers = match reply {
+            Some(RelayMessage::Available { transfers, .. }) => transfers,
+            other => panic!("unexpected reply: {:?}", other),
+        };
+        assert_eq!(transfers.len(), 2);
+        assert_eq!(transfers[0].transfer_id, "transfer-a");
+        assert_eq!(transfers[0].chunk_ids, vec!["a-1", "a-2"]);
+        assert_eq!(transfers[0].bytes, 20);
+
+        // Chunks for another destination are not handed over
+        let reply = node
+            .handle_message(RelayMessage::Pull {
+                destination: dest,
+                chunk_ids: vec!["a-1".into(), "a-2".into(), "x-1".into()],
+            })
+            .await
+            .unwrap();
+        match reply {
+            Some(RelayMessage::Deliver { chunks }) => assert_eq!(chunks.len(), 2),
+            other => panic!("unexpected reply: {:?}", other),
+        }
+
+        let stats = node.stats();
+        assert_eq!(stats.chunks_pulled, 2);
+        assert_eq!(stats.chunks_forwarded, 2);
+        assert_eq!(stats.bytes_forwarded, 20);
+        assert_eq!(stats.stored_chunks, 2);
+        assert_eq!(node.available_for(dest).len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_forward_latency_by_destination() {
+        let node = RelayNodeBuilder::new()
+            .node_id("latency-node")
+            .policy(ForwardingPolicy {
+                forward_immediately: false,
+                ..Default::default()
+            })
+            .build()
+            .unwrap();
+        let near: SocketAddr = "127.0.0.1:8000".parse().unwrap();
+        let far: SocketAddr = "127.0.0.1:8001".parse().unwrap();
+        for (id, addr) in [("near-1", near), ("far-1", far)] {
+            let route = RouteInfo::new("source", addr, "transfer-1", 1);
+            node.receive_chunk(id.into(), route, test_chunk(vec![0u8; 10]))
+                .await
+                .unwrap();
+        }
+
+        node.deliver_to(near, &["near-1".to_string()]).await;
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        node.deliver_to(far, &["far-1".to_string()]).await;
+
+        let Some(RelayMessage::Stats { stats, .. }) =
+            node.handle_message(RelayMessage::QueryStats).await.unwrap()
+        else {
+            panic!("expected a stats reply");
+        };
+        assert_eq!(stats.forward_latency.forwarded, 2);
+        assert!(stats.avg_forward_latency_ms >= 75);
+        assert!(stats.forward_latency.max_ms >= 150);
+        assert_eq!(stats.forward_latency.p99_ms, stats.forward_latency.max_ms);
+
+        let [near_latency, far_latency] = &stats.destination_latency[..] else {
+            panic!("expected two destinations");
+        };
+        assert_eq!(near_latency.destination, near);
+        assert!(near_latency.latency.ewma_ms < 150.0);
+        assert_eq!(far_latency.destination, far);
+        assert!(far_latency.latency.ewma_ms >= 150.0);
+        assert_eq!(far_latency.latency.p50_ms, far_latency.latency.max_ms);
+    }
+
+    #[tokio::test]
+    async fn test_policy_update_persists_across_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("policy.json");
+        let node = RelayNodeBuilder::new()
+            .node_id("policy-node")
+            .policy_path(&path)
+            .build()
+            .unwrap();
+
+        let reply = node
+            .handle_message(RelayMessage::UpdatePolicy {
+                update: PolicyUpdate {
+                    forward_immediately: Some(false),
+                    max_hops: Some(2),
+                    retry_cooldown: Some(Duration::from_secs(60)),
+                    ..Default::default()
+                },
+            })
+            .await
+            .unwrap();
+        assert!(matches!(
+            reply,
+            Some(RelayMessage::Policy { policy }) if policy.max_hops == 2
+        ));
+
+        // Held rather than forwarded under the new policy
+        let dest: SocketAddr = "127.0.0.1:8000".parse().unwrap();
+        let route = RouteInfo::new("source", dest, "transfer-1", 1);
+        node.receive_chunk("chunk-1".into(), route, test_chunk(vec![1, 2, 3]))
+            .await
+            .unwrap();
+        assert_eq!(node.stats().stored_chunks, 1);
+
+        let restarted = RelayNodeBuilder::new()
+            .node_id("policy-node")
+            .policy_path(&path)
+            .build()
+            .unwrap();
+        let policy = restarted.policy();
+        assert!(!policy.forward_immediately);
+        assert_eq!(policy.max_hops, 2);
+        assert_eq!(policy.retry_cooldown, Duration::from_secs(60));
+        assert!(policy.prefer_direct);
+    }
+
+    #[tokio::test]
+    async fn test_critical_chunks_are_replicated() {
+        let holding = ForwardingPolicy {
+            forward_immediately: false,
+            replication_factor: 3,
+            ..Default::default()
+        };
+        let (node, link) = linked(
+            RelayNodeBuilder::new()
+                .node_id("origin")
+                .policy(holding.clone())
+                .add_peer(PeerInfo::new("peer-1", "127.0.0.1:9101".parse().unwrap()))
+                .add_peer(PeerInfo::new("peer-2", "127.0.0.1:9102".parse().unwrap()))
+                .add_peer(PeerInfo::new("peer-3", "127.0.0.1:9103".parse().unwrap()))
+                .build()
+                .unwrap(),
+        );
+        let dest: SocketAddr = "127.0.0.1:8000".parse().unwrap();
+        let peer = |port: u16| SocketAddr::from(([127, 0, 0, 1], port));
+        // Only peers that acknowledge the store hold a replica
+        link.take_down(peer(9101));
+
+        let critical = RouteInfo::new("source", dest, "transfer-1", 0);
+        node.receive_chunk(
+            "critical-1".into(),
+            critical.clone(),
+            test_chunk(vec![1; 8]),
+        )
+        .await
+        .unwrap();
+        let normal = RouteInfo::new("source", dest, "transfer-1", 2);
+        node.receive_chunk("normal-1".into(), normal, test_chunk(vec![2; 8]))
+            .await
+            .unwrap();
+
+        let mut holders = node.replica_holders("critical-1");
+        holders.sort();
+        assert_eq!(holders, ["peer-2", "peer-3"]);
+        assert!(node.replica_holders("normal-1").is_empty());
+        assert_eq!(node.stats().replicas_created, 2);
+        let mut sent = link.sent_to();
+        sent.sort();
+        assert_eq!(sent, [peer(9102), peer(9103)]);
+
+        // Delivering the original tells the holders to drop their copies
+        node.deliver_to(dest, &["critical-1".to_string()]).await;
+        assert!(node.replica_holders("critical-1").is_empty());
+        let mut released: Vec<_> = link
+            .sent
+            .lock()
+            .iter()
+            .filter(|(_, m)| matches!(m, RelayMessage::Delivered { chunk_id } if chunk_id == "critical-1"))
+            .map(|(addr, _)| *addr)
+            .collect();
+        released.sort();
+        assert_eq!(released, [peer(9102), peer(9103)]);
+
+        // A replica holder doesn't replicate further and drops its copy
+        // once told the chunk was delivered
+        let holder = RelayNodeBuilder::new()
+            .node_id("peer-1")
+            .policy(holding)
+            .add_peer(PeerInfo::new("peer-2", "127.0.0.1:9102".parse().unwrap()))
+            .build()
+            .unwrap();
+        let mut replica = critical;
+        replica.replica = true;
+        for _ in 0..2 {
+            holder
+                .receive_chunk("critical-1".into(), replica.clone(), test_chunk(vec![1; 8]))
+                .await
+                .unwrap();
+        }
+        assert_eq!(holder.stats().replicas_created, 0);
+        assert_eq!(holder.stats().stored_chunks, 1);
+
+        holder
+            .handle_message(RelayMessage::Delivered {
+                chunk_id: "critical-1".into(),
+            })
+            .await
+            .unwrap();
+        let stats = holder.stats();
+        assert_eq!(stats.stored_chunks, 0);
+        assert_eq!(stats.duplicates_discarded, 2);
+    }
+
+    #[tokio::test]
+    async fn test_critical_chunks_are_flooded() {
+        let flooding = ForwardingPolicy {
+            flood: Some(FloodPolicy::default()),
+            ..Default::default()
+        };
+        let (node, link) = linked(
+            RelayNodeBuilder::new()
+                .node_id("origin")
+                .policy(flooding.clone())
+                .add_peer(PeerInfo::new("peer-1", "127.0.0.1:9101".parse().unwrap()))
+                .add_peer(PeerInfo::new("peer-2", "127.0.0.1:9102".parse().unwrap()))
+                .add_peer(PeerInfo::new("peer-3", "127.0.0.1:9103".parse().unwrap()))
+                .build()
+                .unwrap(),
+        );
+        let dest: SocketAddr = "127.0.0.1:8000".parse().unwrap();
+        // Copies only count once a peer acknowledges them
+        link.take_down("127.0.0.1:9103".parse().unwrap());
+
+        let critical = RouteInfo::new("source", dest, "transfer-1", 0);
+        node.receive_chunk(
+            "critical-1".into(),
+            critical.clone(),
+            test_chunk(vec![1; 8]),
+        )
+        .await
+        .unwrap();
+        let normal = RouteInfo::new("source", dest, "transfer-1", 2);
+        node.receive_chunk("normal-1".into(), normal, test_chunk(vec![2; 8]))
+            .await
+            .unwrap();
+
+        let stats = node.stats();
+        assert_eq!(stats.chunks_flooded, 1);
+        assert_eq!(stats.flood_copies_sent, 2);
+        assert_eq!(stats.chunks_forwarded, 2);
+        assert_eq!(stats.stored_chunks, 0);
+
+        // Peers get the chunk with one hop less to flood on
+        let flood_ttls: Vec<_> = link
+            .sent
+            .lock()
+            .iter()
+            .filter_map(|(_, m)| match m {
+                RelayMessage::Store {
+                    chunk_id, route, ..
+                } if chunk_id == "critical-1" => Some(route.flood_ttl),
+                _ => None,
+            })
+            .collect();
+        let ttl = FloodPolicy::default().ttl;
+        assert_eq!(flood_ttls, [Some(ttl - 1), Some(ttl - 1)]);
+
+        // A peer keeps the first flooded copy and drops the rest
+        let peer = RelayNodeBuilder::new()
+            .node_id("peer-1")
+            .policy(ForwardingPolicy {
+                forward_immediately: false,
+                ..flooding
+            })
+            .add_peer(PeerInfo::new("peer-2", "127.0.0.1:9102".parse().unwrap()))
+            .build()
+            .unwrap()
+            .with_link(Arc::new(TestLink::default()));
+        let mut copy = critical;
+        copy.add_hop("origin");
+        copy.flood_ttl = Some(1);
+        for _ in 0..3 {
+            peer.receive_chunk("critical-1".into(), copy.clone(), test_chunk(vec![1; 8]))
+                .await
+                .unwrap();
+        }
+        let stats = peer.stats();
+        assert_eq!(stats.stored_chunks, 1);
+        assert_eq!(stats.flood_duplicates_suppressed, 2);
+
+        // Once forwarded, late copies are still recognised
+        assert!(peer.try_forward_chunk("critical-1").await.unwrap());
+        peer.receive_chunk("critical-1".into(), copy, test_chunk(vec![1; 8]))
+            .await
+            .unwrap();
+        let stats = peer.stats();
+        assert_eq!(stats.stored_chunks, 0);
+        assert_eq!(stats.chunks_flooded, 1);
+        assert_eq!(stats.flood_copies_sent, 1);
+        assert_eq!(stats.flood_duplicates_suppressed, 3);
+    }
+
+    #[tokio::test]
+    async fn test_hop_fec_reencodes_group() {
+        use crate::chunk::ErasureCoder;
+        use crate::relay::types::{FecShardInfo, HopFecPolicy};
+
+        let policy = ForwardingPolicy {
+            forward_immediately: false,
+            hop_fec: Some(HopFecPolicy::default()),
+            ..Default::default()
+        };
+        let node = RelayNodeBuilder::new()
+            .node_id("fec-node")
+            .policy(policy)
+            .build()
+            .unwrap();
+        let dest: SocketAddr = "127.0.0.1:8000".parse().unwrap();
+        node.record_hop_loss(dest, 0.5);
+
+        let data: Vec<bytes::Bytes> = (0..4u8).map(|i| bytes::Bytes::from(vec![i; 32])).collect();
+        let shards = ErasureCoder::new(4, 2).unwrap().encode(data).unwrap();
+
+        // Shard 1 is lost on the inbound hop
+        for (index, shard) in shards.iter().enumerate() {
+            if index == 1 {
+                continue;
+            }
+            let route = RouteInfo::new("source", dest, "transfer-1", 1).with_fec(FecShardInfo {
+                group_id: "group-1".into(),
+                shard_index: index,
+                data_shards: 4,
+                parity_shards: 2,
+            });
+            node.receive_chunk(format!("in-{index}"), route, test_chunk(shard.clone()))
+                .await
+                .unwrap();
+        }
+
+        let stats = node.stats();
+        assert_eq!(stats.hop_fec_groups, 1);
+        assert_eq!(stats.hop_fec_repairs, 1);
+
+        // 50% loss * 4 data * 1.5 safety = 3 parity shards for the next hop
+        let group = node.storage.get_group("group-1");
+        assert_eq!(group.len(), 7);
+        let repaired = group.iter().find(|c| c.chunk_id == "group-1:1").unwrap();
+        assert_eq!(repaired.chunk.data, vec![1u8; 32]);
+
+        // Each new shard carries metadata that checks out on the next hop
+        for shard in &group {
+            let is_parity = shard.route.fec.as_ref().unwrap().is_parity();
+            assert_eq!(shard.chunk.metadata.is_parity, is_parity);
+            assert!(IntegrityVerifier::verify_chunk(&shard.chunk).is_ok());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_hop_fec_keeps_inbound_parity_and_forgets_old_groups() {
+        use crate::chunk::ErasureCoder;
+        use crate::relay::types::{FecShardInfo, HopFecPolicy};
+
+        let node = RelayNodeBuilder::new()
+            .node_id("fec-node")
+            .max_hold_time(Duration::from_millis(50))
+            .policy(ForwardingPolicy {
+                forward_immediately: false,
+                hop_fec: Some(HopFecPolicy::default()),
+                ..Default::default()
+            })
+            .build()
+            .unwrap();
+        let dest: SocketAddr = "127.0.0.1:8000".parse().unwrap();
+
+        let data: Vec<bytes::Bytes> = (0..4u8).map(|i| bytes::Bytes::from(vec![i; 32])).collect();
+        let shards = ErasureCoder::new(4, 3).unwrap().encode(data).unwrap();
+        let route = |index| {
+            RouteInfo::new("source", dest, "transfer-1", 1).with_fec(FecShardInfo {
+                group_id: "group-1".into(),
+                shard_index: index,
+                data_shards: 4,
+                parity_shards: 3,
+            })
+        };
+        for (index, shard) in shards.iter().enumerate().take(4) {
+            node.receive_chunk(
+                format!("in-{index}"),
+                route(index),
+                test_chunk(shard.clone()),
+            )
+            .await
+            .unwrap();
+        }
+
+        // A clean next hop would only need one parity shard, but the group
+        // arrived with three and leaves with three
+        assert_eq!(node.storage.get_group("group-1").len(), 7);
+        assert!(node.was_reencoded("group-1"));
+
+        // Once the group's chunks would have expired, it's forgotten
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        node.maintenance_cycle().await;
+        assert!(node.reencoded_groups.lock().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_peers_and_stats_survive_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let build = || {
+            RelayNodeBuilder::new()
+                .node_id("persistent-node")
+                .persistence_path(dir.path())
+                .build()
+                .unwrap()
+                .with_link(Arc::new(TestLink::default()))
+        };
+
+        let node = build();
+        let route = RouteInfo::new("source", "127.0.0.1:8000".parse().unwrap(), "transfer-1", 1);
+        node.receive_chunk("chunk-1".into(), route, test_chunk(vec![1, 2, 3, 4]))
+            .await
+            .unwrap();
+        node.handle_message(RelayMessage::Hello {
+            node_id: "peer-1".into(),
+            addr: "192.168.1.100:9000".parse().unwrap(),
+            proof: None,
+        })
+        .await
+        .unwrap();
+        node.maintenance_cycle().await;
+        drop(node);
+
+        let restarted = build();
+        let peers = restarted.get_peers();
+        assert_eq!(peers.len(), 1);
+        assert_eq!(peers[0].node_id, "peer-1");
+        assert!(peers[0].last_seen.is_some());
+        // Reachability is rediscovered, not trusted from the last run
+        assert!(!peers[0].reachable);
+
+        let stats = restarted.stats();
+        assert_eq!(stats.chunks_received, 1);
+        assert_eq!(stats.bytes_received, 4);
+        assert_eq!(stats.chunks_forwarded, 1);
+        assert_eq!(stats.forward_latency.forwarded, 1);
+        assert_eq!(stats.destination_latency.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_stale_learned_peers_age_out() {
+        let node = RelayNodeBuilder::new()
+            .node_id("aging-node")
+            .peer_expiry(Duration::from_secs(3600))
+            .add_peer(PeerInfo::new(
+                "configured",
+                "127.0.0.1:9101".parse().unwrap(),
+            ))
+            .build()
+            .unwrap();
+        let two_hours_ago = chrono::Utc::now().timestamp() - 7200;
+
+        let mut stale = PeerInfo::new("stale", "127.0.0.1:9102".parse().unwrap());
+        stale.last_seen = Some(two_hours_ago);
+        let mut configured = PeerInfo::new("configured", "127.0.0.1:9101".parse().unwrap());
+        configured.last_seen = Some(two_hours_ago);
+        node.handle_message(RelayMessage::PeerList {
+            peers: vec![
+                stale,
+                configured,
+                PeerInfo::new("fresh", "127.0.0.1:9103".parse().unwrap()),
+            ],
+        })
+        .await
+        .unwrap();
+        assert_eq!(node.get_peers().len(), 3);
+
+        node.maintenance_cycle().await;
+        let mut remaining: Vec<String> = node.get_peers().into_iter().map(|p| p.node_id).collect();
+        remaining.sort();
+        assert_eq!(remaining, ["configured", "fresh"]);
+    }
+
     #[test]
     fn test_builder() {
         let node = RelayNodeBuilder::new()
@@ -0,0 +1,132 @@
+//! Receiver side of the relay pull flow
+//!
+//! A receiver that comes online after relays have been buffering chunks for
+//! it asks each known relay what it holds for its address, then pulls the
+//! chunks it doesn't have yet. Relays drop pulled chunks from storage, so a
+//! chunk is handed over once even when several relays hold a copy.
+
+use crate::relay::node::RelayNode;
+use crate::relay::types::{AvailableChunks, PulledChunk, RelayError, RelayMessage, RelayResult};
+use std::collections::HashSet;
+use std::net::SocketAddr;
+
+/// Pulls buffered chunks from relays on behalf of one destination
+#[derive(Debug)]
+pub struct RelayPuller {
+    destination: SocketAddr,
+    received: HashSet<String>,
+}
+
+impl RelayPuller {
+    pub fn new(destination: SocketAddr) -> Self {
+        Self {
+            destination,
+            received: HashSet::new(),
+        }
+    }
+
+    /// Message asking a relay what it holds for this destination
+    pub fn query(&self) -> RelayMessage {
+        RelayMessage::QueryDestination {
+            destination: self.destination,
+        }
+    }
+
+    /// Pull request for advertised chunks not yet received
+    pub fn pull_request(&self, transfers: &[AvailableChunks]) -> Option<RelayMessage> {
+        let chunk_ids: Vec<String> = transfers
+            .iter()
+            .flat_map(|t| t.chunk_ids.iter())
+            .filter(|id| !self.received.contains(*id))
+            .cloned()
+            .collect();
+
+        if chunk_ids.is_empty() {
+            return None;
+        }
+
+        Some(RelayMessage::Pull {
+            destination: self.destination,
+            chunk_ids,
+        })
+    }
+
+    /// Record delivered chunks, dropping any already received
+    pub fn accept(&mut self, chunks: Vec<PulledChunk>) -> Vec<PulledChunk> {
+        chunks
+            .into_iter()
+            .filter(|c| self.received.insert(c.chunk_id.clone()))
+            .collect()
+    }
+
+    /// Run the query / pull exchange against one relay
+    pub async fn pull_from(&mut self, relay: &RelayNode) -> RelayResult<Vec<PulledChunk>> {
+        let transfers = match relay.handle_message(self.query()).await? {
+            Some(RelayMessage::Available { transfers, .. }) => transfers,
+            other => return Err(unexpected_reply(other)),
+        };
+
+        let request = match self.pull_request(&transfers) {
+            Some(request) => request,
+            None => return Ok(Vec::new()),
+        };
+
+        match relay.handle_message(request).await? {
+            Some(RelayMessage::Deliver { chunks }) => Ok(self.accept(chunks)),
+            other => Err(unexpected_reply(other)),
+        }
+    }
+
+    /// Number of distinct chunks received so far
+    pub fn received_count(&self) -> usize {
+        self.received.len()
+    }
+}
+
+fn unexpected_reply(reply: Option<RelayMessage>) -> RelayError {
+    RelayError::Network(format!("unexpected relay reply: {:?}", reply))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::relay::node::RelayNodeBuilder;
+    use crate::relay::types::{test_chunk, ForwardingPolicy, RouteInfo};
+
+    fn holding_relay(node_id: &str) -> RelayNode {
+        RelayNodeBuilder::new()
+            .node_id(node_id)
+            .policy(ForwardingPolicy {
+                forward_immediately: false,
+                ..Default::default()
+            })
+            .build()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_pull_skips_chunks_already_received() {
+        let dest: SocketAddr = "127.0.0.1:8000".parse().unwrap();
+        let relay_a = holding_relay("relay-a");
+        let relay_b = holding_relay("relay-b");
+
+        for relay in [&relay_a, &relay_b] {
+            for id in ["chunk-1", "chunk-2"] {
+                let route = RouteInfo::new("source", dest, "transfer-1", 1);
+                relay
+                    .receive_chunk(id.into(), route, test_chunk(vec![0u8; 16]))
+                    .await
+                    .unwrap();
+            }
+        }
+
+        let mut puller = RelayPuller::new(dest);
+        assert_eq!(puller.pull_from(&relay_a).await.unwrap().len(), 2);
+        assert!(puller.pull_from(&relay_b).await.unwrap().is_empty());
+        assert_eq!(puller.received_count(), 2);
+
+        // Relay B was never asked for its copies, so it keeps them
+        assert_eq!(relay_a.stats().stored_chunks, 0);
+        assert_eq!(relay_b.stats().stored_chunks, 2);
+    }
+}
@@ -0,0 +1,320 @@
+//! On-disk layout of persisted relay chunks
+//!
+//! A stored chunk is first written to its own `<chunk_id>.chunk` file as a
+//! record: a magic tag, the BLAKE3 hash of the payload, then the bincode
+//! payload. Files from before records had a header are bare bincode and are
+//! still read, without the checksum. Records from before chunks kept their
+//! metadata (bare or `RCK1`) are upgraded as they are read, with metadata
+//! rebuilt from the route and a fresh checksum of the data.
+//!
+//! One file per chunk runs a small filesystem out of inodes long before it
+//! runs out of space, so compaction packs loose files into numbered
+//! `<n>.segment` files of length-prefixed records. Removing a chunk that
+//! lives in a segment appends its id to `<n>.dead`, which keeps it gone
+//! across restarts; a segment whose chunks are all dead is deleted.
+
+use crate::chunk::{Chunk, ChunkMetadata, Priority};
+use crate::integrity::IntegrityVerifier;
+use crate::relay::storage::{system_time_serde, StoredChunk};
+use crate::relay::types::{RelayError, RelayResult, RouteInfo};
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Leads every record written with a checksum
+const RECORD_MAGIC: &[u8; 4] = b"RCK2";
+/// Checksummed records holding a [`LegacyStoredChunk`]
+const LEGACY_RECORD_MAGIC: &[u8; 4] = b"RCK1";
+const HEADER_LEN: usize = RECORD_MAGIC.len() + 32;
+
+pub(crate) const CHUNK_EXT: &str = "chunk";
+pub(crate) const SEGMENT_EXT: &str = "segment";
+pub(crate) const TOMBSTONE_EXT: &str = "dead";
+
+/// Serialize `chunk` as a checksummed record
+pub(crate) fn encode_record(chunk: &StoredChunk) -> RelayResult<Vec<u8>> {
+    let payload = bincode::serialize(chunk).map_err(|e| RelayError::Storage(e.to_string()))?;
+    let mut record = Vec::with_capacity(HEADER_LEN + payload.len());
+    record.extend_from_slice(RECORD_MAGIC);
+    record.extend_from_slice(blake3::hash(&payload).as_bytes());
+    record.extend_from_slice(&payload);
+    Ok(record)
+}
+
+/// Read a record back; `None` if it doesn't decode or fails its checksum
+pub(crate) fn decode_record(record: &[u8]) -> Option<StoredChunk> {
+    if let Some(rest) = record.strip_prefix(RECORD_MAGIC) {
+        return bincode::deserialize(checked_payload(rest)?).ok();
+    }
+    let payload = match record.strip_prefix(LEGACY_RECORD_MAGIC) {
+        Some(rest) => checked_payload(rest)?,
+        // Written before records had a header
+        None => record,
+    };
+    bincode::deserialize::<LegacyStoredChunk>(payload)
+        .ok()
+        .map(LegacyStoredChunk::upgrade)
+}
+
+/// The payload after a record's hash, if it matches
+fn checked_payload(rest: &[u8]) -> Option<&[u8]> {
+    if rest.len() < 32 {
+        return None;
+    }
+    let (hash, payload) = rest.split_at(32);
+    (blake3::hash(payload).as_bytes() == hash).then_some(payload)
+}
+
+/// A stored chunk as persisted before chunks kept their metadata
+#[derive(Deserialize)]
+struct LegacyStoredChunk {
+    chunk_id: String,
+    route: RouteInfo,
+    data: Vec<u8>,
+    #[serde(with = "system_time_serde")]
+    stored_at: SystemTime,
+    #[serde(with = "system_time_serde")]
+    expires_at: SystemTime,
+    forward_attempts: u32,
+}
+
+impl LegacyStoredChunk {
+    /// Rebuild the chunk's metadata from what the route knows
+    fn upgrade(self) -> StoredChunk {
+        let route = &self.route;
+        let metadata = ChunkMetadata {
+            chunk_id: 0,
+            file_id: route.transfer_id.clone(),
+            sequence_number: route.sequence_number.unwrap_or(0),
+            total_chunks: 0,
+            data_size: self.data.len(),
+            checksum: IntegrityVerifier::calculate_checksum(&self.data),
+            is_parity: route.fec.as_ref().is_some_and(|fec| fec.is_parity()),
+            priority: match route.priority {
+                0 => Priority::Critical,
+                1 => Priority::High,
+                _ => Priority::Normal,
+            },
+            created_at: self
+                .stored_at
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs() as i64),
+            file_size: 0,
+            file_checksum: [0u8; 32],
+            data_chunks: 0,
+            zero_runs: Vec::new(),
+            attributes: None,
+            checksum_algorithm: Default::default(),
+            merkle_proof: None,
+            trace: None,
+            compression: None,
+        };
+        StoredChunk {
+            chunk_id: self.chunk_id,
+            route: self.route,
+            chunk: Chunk {
+                metadata,
+                data: self.data.into(),
+            },
+            stored_at: self.stored_at,
+            expires_at: self.expires_at,
+            forward_attempts: self.forward_attempts,
+            last_attempt: None,
+        }
+    }
+}
+
+/// Records of a segment file, and how many were unreadable
+///
+/// A torn final record, from a crash while the segment was written, counts
+/// as one unreadable record.
+pub(crate) fn read_segment(path: &Path) -> RelayResult<(Vec<StoredChunk>, u64)> {
+    let data = std::fs::read(path)?;
+    let mut chunks = Vec::new();
+    let mut corrupt = 0;
+    let mut rest = data.as_slice();
+    while !rest.is_empty() {
+        let Some((len, tail)) = rest.split_first_chunk::<4>() else {
+            corrupt += 1;
+            break;
+        };
+        let len = u32::from_le_bytes(*len) as usize;
+        if tail.len() < len {
+            corrupt += 1;
+            break;
+        }
+        let (record, tail) = tail.split_at(len);
+        match decode_record(record) {
+            Some(chunk) => chunks.push(chunk),
+            None => corrupt += 1,
+        }
+        rest = tail;
+    }
+    Ok((chunks, corrupt))
+}
+
+/// Append a length-prefixed record to a segment being built
+pub(crate) fn push_segment_record(segment: &mut Vec<u8>, record: &[u8]) {
+    segment.extend_from_slice(&(record.len() as u32).to_le_bytes());
+    segment.extend_from_slice(record);
+}
+
+/// Ids removed from a segment
+pub(crate) fn read_tombstones(path: &Path) -> RelayResult<HashSet<String>> {
+    if !path.exists() {
+        return Ok(HashSet::new());
+    }
+    let data = std::fs::read_to_string(path)?;
+    Ok(data.lines().map(str::to_string).collect())
+}
+
+/// Record that `chunk_id` was removed from a segment
+pub(crate) fn append_tombstone(path: &Path, chunk_id: &str) -> RelayResult<()> {
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    writeln!(file, "{chunk_id}")?;
+    Ok(())
+}
+
+/// Write `data` to `path` through a temporary file, so a crash leaves
+/// either the old file or the new one
+pub(crate) fn write_atomic(path: &Path, data: &[u8]) -> RelayResult<()> {
+    let tmp = temp_path(path);
+    std::fs::write(&tmp, data)?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// Where [`write_atomic`] stages `path`
+fn temp_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".tmp");
+    path.with_file_name(name)
+}
+
+/// A staging file [`write_atomic`] left behind
+pub(crate) fn is_stale_temp(path: &Path) -> bool {
+    let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+        return false;
+    };
+    [CHUNK_EXT, SEGMENT_EXT]
+        .iter()
+        .any(|ext| name.ends_with(&format!(".{ext}.tmp")))
+}
+
+pub(crate) fn chunk_path(dir: &Path, chunk_id: &str) -> PathBuf {
+    dir.join(format!("{chunk_id}.{CHUNK_EXT}"))
+}
+
+pub(crate) fn segment_path(dir: &Path, segment: u64) -> PathBuf {
+    dir.join(format!("{segment}.{SEGMENT_EXT}"))
+}
+
+pub(crate) fn tombstone_path(dir: &Path, segment: u64) -> PathBuf {
+    dir.join(format!("{segment}.{TOMBSTONE_EXT}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::relay::types::test_chunk;
+    use serde::Serialize;
+    use std::time::Duration;
+
+    fn route() -> RouteInfo {
+        RouteInfo::new("source", "127.0.0.1:8000".parse().unwrap(), "transfer-1", 1)
+    }
+
+    fn chunk(id: &str) -> StoredChunk {
+        StoredChunk::new(
+            id.into(),
+            route(),
+            test_chunk(vec![7; 64]),
+            Duration::from_secs(60),
+        )
+    }
+
+    /// How [`LegacyStoredChunk`] was written
+    #[derive(Serialize)]
+    struct Legacy {
+        chunk_id: String,
+        route: RouteInfo,
+        data: Vec<u8>,
+        stored_at: u64,
+        expires_at: u64,
+        forward_attempts: u32,
+    }
+
+    fn legacy(id: &str) -> Vec<u8> {
+        let mut route = route();
+        route.sequence_number = Some(3);
+        bincode::serialize(&Legacy {
+            chunk_id: id.into(),
+            route,
+            data: vec![7; 64],
+            stored_at: 1_700_000_000,
+            expires_at: 1_700_000_060,
+            forward_attempts: 2,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_records_detect_corruption() {
+        let mut record = encode_record(&chunk("a")).unwrap();
+        assert_eq!(decode_record(&record).unwrap().chunk_id, "a");
+
+        let last = record.len() - 1;
+        record[last] ^= 0xff;
+        assert!(decode_record(&record).is_none());
+
+        // Bare bincode from before records had a header
+        let bare = decode_record(&legacy("b")).unwrap();
+        assert_eq!(bare.chunk_id, "b");
+        assert_eq!(bare.forward_attempts, 2);
+    }
+
+    #[test]
+    fn test_legacy_records_are_upgraded() {
+        let payload = legacy("c");
+        let mut record = LEGACY_RECORD_MAGIC.to_vec();
+        record.extend_from_slice(blake3::hash(&payload).as_bytes());
+        record.extend_from_slice(&payload);
+
+        let upgraded = decode_record(&record).unwrap();
+        assert_eq!(upgraded.chunk_id, "c");
+        assert_eq!(upgraded.chunk.data, vec![7; 64]);
+        assert_eq!(upgraded.chunk.metadata.file_id, "transfer-1");
+        assert_eq!(upgraded.chunk.metadata.sequence_number, 3);
+        assert_eq!(upgraded.chunk.metadata.priority, Priority::High);
+        assert!(IntegrityVerifier::verify_chunk(&upgraded.chunk).is_ok());
+
+        // Upgraded chunks are written back in the current format
+        let rewritten = encode_record(&upgraded).unwrap();
+        assert!(rewritten.starts_with(RECORD_MAGIC));
+        assert_eq!(decode_record(&rewritten).unwrap().chunk_id, "c");
+    }
+
+    #[test]
+    fn test_torn_segment_keeps_complete_records() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let mut segment = Vec::new();
+        for id in ["a", "b"] {
+            push_segment_record(&mut segment, &encode_record(&chunk(id)).unwrap());
+        }
+        segment.truncate(segment.len() - 10);
+        let path = segment_path(dir.path(), 1);
+        write_atomic(&path, &segment).unwrap();
+
+        let (chunks, corrupt) = read_segment(&path).unwrap();
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].chunk_id, "a");
+        assert_eq!(corrupt, 1);
+        assert!(!is_stale_temp(&path));
+        assert!(is_stale_temp(&temp_path(&path)));
+    }
+}
@@ -1,11 +1,24 @@
 //! Relay storage for store-and-forward functionality
 //!
-//! Provides persistent storage for chunks waiting to be forwarded.
-
-use crate::relay::types::{RelayError, RelayResult, RouteInfo};
-use parking_lot::RwLock;
+//! Provides persistent storage for chunks waiting to be forwarded. With
+//! persistence on, the directory is scanned when storage opens: corrupt,
+//! expired and orphaned files are removed and the rest loaded back, with
+//! the outcome kept as a [`ScanReport`]. [`RelayStorage::compact`] packs
+//! chunk files into segments; see [`segment`](crate::relay::segment) for
+//! the layout.
+
+use crate::chunk::Chunk;
+use crate::relay::segment::{
+    self, append_tombstone, chunk_path, decode_record, encode_record, is_stale_temp,
+    push_segment_record, read_segment, read_tombstones, segment_path, tombstone_path, write_atomic,
+};
+use crate::relay::types::{
+    DestinationQuotas, DestinationUsage, QuotaBreach, RelayError, RelayResult, RouteInfo,
+};
+use parking_lot::{Mutex, RwLock};
 use serde::{Deserialize, Serialize};
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::net::SocketAddr;
 use std::path::{Path, PathBuf};
 use std::time::{Duration, Instant, SystemTime};
 
@@ -18,8 +31,8 @@ pub struct StoredChunk {
     /// Routing information
     pub route: RouteInfo,
 
-    /// Chunk data
-    pub data: Vec<u8>,
+    /// The chunk with its metadata, verified when it arrived
+    pub chunk: Chunk,
 
     /// When the chunk was stored
     #[serde(with = "system_time_serde")]
@@ -37,7 +50,7 @@ pub struct StoredChunk {
     pub last_attempt: Option<Instant>,
 }
 
-mod system_time_serde {
+pub(crate) mod system_time_serde {
     use serde::{Deserialize, Deserializer, Serialize, Serializer};
     use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
@@ -59,13 +72,18 @@ mod system_time_serde {
 }
 
 impl StoredChunk {
+    /// Trace id the sender gave the chunk, if any
+    pub fn trace_id(&self) -> Option<String> {
+        self.chunk.metadata.trace_id().map(str::to_string)
+    }
+
     /// Create a new stored chunk
-    pub fn new(chunk_id: String, route: RouteInfo, data: Vec<u8>, hold_time: Duration) -> Self {
+    pub fn new(chunk_id: String, route: RouteInfo, chunk: Chunk, hold_time: Duration) -> Self {
         let now = SystemTime::now();
         Self {
             chunk_id,
             route,
-            data,
+            chunk,
             stored_at: now,
             expires_at: now + hold_time,
             forward_attempts: 0,
@@ -85,7 +103,7 @@ impl StoredChunk {
 
     /// Get size in bytes
     pub fn size(&self) -> usize {
-        self.data.len()
+        self.chunk.data.len()
     }
 
     /// Check if enough time has passed since last attempt
@@ -104,10 +122,116 @@ impl StoredChunk {
 }
 
 /// Priority key for ordering chunks (lower = higher priority)
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+///
+/// The chunk id breaks ties so chunks stored in the same millisecond don't
+/// overwrite each other in the index.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 struct PriorityKey {
     priority: u8,
     stored_millis: u64,
+    chunk_id: String,
+}
+
+impl PriorityKey {
+    fn for_chunk(chunk: &StoredChunk) -> Self {
+        Self {
+            priority: chunk.priority(),
+            stored_millis: chunk
+                .stored_at
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64,
+            chunk_id: chunk.chunk_id.clone(),
+        }
+    }
+}
+
+/// Compaction starts a new segment once one reaches this size
+pub const SEGMENT_TARGET_BYTES: usize = 64 * 1024 * 1024;
+
+/// Where a persisted chunk lives
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Location {
+    /// Its own `.chunk` file
+    Loose,
+    Segment(u64),
+}
+
+/// Records of a segment still in use, and those that are not
+#[derive(Debug, Clone, Copy, Default)]
+struct SegmentUsage {
+    live: usize,
+    /// Removed, expired, duplicated or unreadable
+    dead: usize,
+}
+
+/// What is on disk, for persisted storage
+#[derive(Debug, Default)]
+struct DiskIndex {
+    locations: HashMap<String, Location>,
+    segments: BTreeMap<u64, SegmentUsage>,
+    next_segment: u64,
+}
+
+impl DiskIndex {
+    fn loose_files(&self) -> u64 {
+        self.locations
+            .values()
+            .filter(|l| **l == Location::Loose)
+            .count() as u64
+    }
+
+    /// One record of `segment` is no longer in use; deletes the segment
+    /// once none is, returning whether it did
+    fn release(&mut self, dir: &Path, segment: u64) -> bool {
+        let Some(usage) = self.segments.get_mut(&segment) else {
+            return false;
+        };
+        usage.live = usage.live.saturating_sub(1);
+        usage.dead += 1;
+        if usage.live > 0 {
+            return false;
+        }
+        self.segments.remove(&segment);
+        let _ = std::fs::remove_file(segment_path(dir, segment));
+        let _ = std::fs::remove_file(tombstone_path(dir, segment));
+        true
+    }
+}
+
+/// Outcome of the startup scan of a persistence directory
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScanReport {
+    /// Chunks loaded back into storage
+    pub loaded: u64,
+    /// Records that failed to decode or to match their checksum
+    pub corrupt: u64,
+    /// Chunks past their hold time
+    pub expired: u64,
+    /// Leftovers from interrupted writes: staging files, tombstones of
+    /// deleted segments, and second copies of chunks already loaded
+    pub orphans: u64,
+    /// Chunks that no longer fit within the capacity
+    pub over_capacity: u64,
+    /// Segment files still in use
+    pub segments: u64,
+}
+
+impl ScanReport {
+    /// Chunks or files the scan discarded
+    pub fn discarded(&self) -> u64 {
+        self.corrupt + self.expired + self.orphans + self.over_capacity
+    }
+}
+
+/// Outcome of one [`RelayStorage::compact`] run
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompactionReport {
+    /// Chunks written into new segments
+    pub chunks_packed: u64,
+    pub segments_written: u64,
+    /// Chunk files and emptied segments deleted
+    pub files_removed: u64,
 }
 
 /// Storage backend for relay chunks
@@ -121,6 +245,9 @@ pub struct RelayStorage {
     /// Per-destination chunk lists
     destination_index: RwLock<HashMap<String, Vec<String>>>,
 
+    /// Chunk ids of each FEC group's stored shards
+    group_index: RwLock<HashMap<String, Vec<String>>>,
+
     /// Maximum storage capacity
     max_bytes: u64,
 
@@ -130,8 +257,20 @@ pub struct RelayStorage {
     /// Optional persistence path
     persistence_path: Option<PathBuf>,
 
+    /// Where each persisted chunk lives on disk
+    disk: Mutex<DiskIndex>,
+
+    /// Result of the scan made when persistence was enabled
+    last_scan: RwLock<Option<ScanReport>>,
+
     /// Default hold time
     default_hold_time: Duration,
+
+    /// Per-destination byte limits
+    quotas: RwLock<DestinationQuotas>,
+
+    /// Bytes held per destination, kept alongside `used_bytes`
+    destination_bytes: RwLock<HashMap<String, u64>>,
 }
 
 impl RelayStorage {
@@ -141,25 +280,96 @@ impl RelayStorage {
             chunks: RwLock::new(HashMap::new()),
             priority_index: RwLock::new(BTreeMap::new()),
             destination_index: RwLock::new(HashMap::new()),
+            group_index: RwLock::new(HashMap::new()),
             max_bytes,
             used_bytes: RwLock::new(0),
             persistence_path: None,
+            disk: Mutex::new(DiskIndex::default()),
+            last_scan: RwLock::new(None),
             default_hold_time,
+            quotas: RwLock::new(DestinationQuotas::default()),
+            destination_bytes: RwLock::new(HashMap::new()),
         }
     }
 
+    /// Limit how much each destination may hold
+    ///
+    /// Applies to chunks stored from now on; chunks already over a new,
+    /// lower quota stay until forwarded or expired.
+    pub fn with_quotas(self, quotas: DestinationQuotas) -> Self {
+        self.set_quotas(quotas);
+        self
+    }
+
+    pub fn set_quotas(&self, quotas: DestinationQuotas) {
+        *self.quotas.write() = quotas;
+    }
+
+    pub fn quotas(&self) -> DestinationQuotas {
+        self.quotas.read().clone()
+    }
+
     /// Create storage with persistence
+    ///
+    /// Scans the directory first; see [`scan_report`](Self::scan_report).
     pub fn with_persistence(mut self, path: impl AsRef<Path>) -> RelayResult<Self> {
         let path = path.as_ref().to_path_buf();
         std::fs::create_dir_all(&path)?;
+        let report = self.scan(&path)?;
+        if report.discarded() > 0 {
+            tracing::warn!(
+                dir = %path.display(),
+                loaded = report.loaded,
+                corrupt = report.corrupt,
+                expired = report.expired,
+                orphans = report.orphans,
+                over_capacity = report.over_capacity,
+                "relay storage scan discarded files"
+            );
+        }
+        *self.last_scan.write() = Some(report);
         self.persistence_path = Some(path);
-        self.load_persisted()?;
         Ok(self)
     }
 
+    /// What the startup scan found, if storage is persisted
+    pub fn scan_report(&self) -> Option<ScanReport> {
+        self.last_scan.read().clone()
+    }
+
     /// Store a chunk
-    pub fn store(&self, chunk_id: String, route: RouteInfo, data: Vec<u8>) -> RelayResult<()> {
-        let size = data.len() as u64;
+    ///
+    /// Returns the chunks evicted to keep the destination within its quota,
+    /// if its policy is [`QuotaBreach::EvictOldest`].
+    pub fn store(
+        &self,
+        chunk_id: String,
+        route: RouteInfo,
+        chunk: Chunk,
+    ) -> RelayResult<Vec<StoredChunk>> {
+        self.store_checked(chunk_id, route, chunk, true)
+    }
+
+    /// Store a chunk that replaces ones already counted against its
+    /// destination, such as a re-encoded FEC shard, without a quota check
+    pub(crate) fn store_replacement(
+        &self,
+        chunk_id: String,
+        route: RouteInfo,
+        chunk: Chunk,
+    ) -> RelayResult<()> {
+        self.store_checked(chunk_id, route, chunk, false)
+            .map(|_| ())
+    }
+
+    fn store_checked(
+        &self,
+        chunk_id: String,
+        route: RouteInfo,
+        chunk: Chunk,
+        check_quota: bool,
+    ) -> RelayResult<Vec<StoredChunk>> {
+        let size = chunk.data.len() as u64;
 
         // Check capacity
         {
@@ -169,42 +379,129 @@ impl RelayStorage {
             }
         }
 
-        let chunk = StoredChunk::new(
-            chunk_id.clone(),
-            route.clone(),
-            data,
-            self.default_hold_time,
-        );
+        let evicted = if check_quota {
+            self.make_room(&route, size)?
+        } else {
+            Vec::new()
+        };
 
-        let priority_key = PriorityKey {
-            priority: chunk.priority(),
-            stored_millis: chunk
-                .stored_at
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap_or_default()
-                .as_millis() as u64,
+        let chunk = StoredChunk::new(chunk_id, route, chunk, self.default_hold_time);
+
+        // Persist if enabled
+        let record = match self.persistence_path {
+            Some(_) => Some((chunk.chunk_id.clone(), encode_record(&chunk)?)),
+            None => None,
         };
+        self.insert(chunk);
+        if let Some((chunk_id, record)) = record {
+            self.persist_chunk(&chunk_id, &record)?;
+        }
 
-        let dest_key = route.destination.to_string();
-        let chunk_id_for_persist = chunk_id.clone();
+        Ok(evicted)
+    }
 
-        // Update indices
-        {
-            let mut chunks = self.chunks.write();
-            let mut priority_idx = self.priority_index.write();
-            let mut dest_idx = self.destination_index.write();
-            let mut used = self.used_bytes.write();
+    /// Check `size` more bytes for `route`'s destination against its
+    /// quota, evicting to fit when the policy allows
+    fn make_room(&self, route: &RouteInfo, size: u64) -> RelayResult<Vec<StoredChunk>> {
+        let destination = route.destination;
+        let (limit, on_breach) = {
+            let quotas = self.quotas.read();
+            match quotas.limit_for(destination) {
+                Some(limit) => (limit, quotas.on_breach),
+                None => return Ok(Vec::new()),
+            }
+        };
+        let exceeded = RelayError::QuotaExceeded { destination, limit };
+        let held = self.destination_usage_bytes(&destination.to_string());
+        if held + size <= limit {
+            return Ok(Vec::new());
+        }
+        if on_breach == QuotaBreach::Reject || size > limit {
+            return Err(exceeded);
+        }
 
-            chunks.insert(chunk_id.clone(), chunk);
-            priority_idx.insert(priority_key, chunk_id.clone());
-            dest_idx.entry(dest_key).or_default().push(chunk_id);
-            *used += size;
+        // Oldest first among chunks no more important than the new one
+        let mut candidates: Vec<StoredChunk> = self
+            .get_for_destination(&destination.to_string())
+            .into_iter()
+            .filter(|c| c.priority() >= route.priority)
+            .collect();
+        candidates.sort_by_key(|c| (c.stored_at, c.chunk_id.clone()));
+
+        let mut freed = 0;
+        let mut victims = Vec::new();
+        for chunk in candidates {
+            if held - freed + size <= limit {
+                break;
+            }
+            freed += chunk.size() as u64;
+            victims.push(chunk.chunk_id);
+        }
+        if held - freed + size > limit {
+            return Err(exceeded);
         }
+        Ok(victims.iter().filter_map(|id| self.remove(id)).collect())
+    }
 
-        // Persist if enabled
-        self.persist_chunk(&chunk_id_for_persist)?;
+    fn destination_usage_bytes(&self, destination: &str) -> u64 {
+        self.destination_bytes
+            .read()
+            .get(destination)
+            .copied()
+            .unwrap_or(0)
+    }
 
-        Ok(())
+    /// Storage held for each destination, largest first
+    pub fn destination_usage(&self) -> Vec<DestinationUsage> {
+        let quotas = self.quotas.read().clone();
+        let mut counts: HashMap<SocketAddr, (u64, u64)> = HashMap::new();
+        for chunk in self.chunks.read().values() {
+            let entry = counts.entry(chunk.route.destination).or_default();
+            entry.0 += 1;
+            entry.1 += chunk.size() as u64;
+        }
+        let mut usage: Vec<DestinationUsage> = counts
+            .into_iter()
+            .map(|(destination, (chunks, bytes))| DestinationUsage {
+                destination,
+                chunks,
+                bytes,
+                quota: quotas.limit_for(destination),
+            })
+            .collect();
+        usage.sort_by(|a, b| {
+            b.bytes
+                .cmp(&a.bytes)
+                .then_with(|| a.destination.cmp(&b.destination))
+        });
+        usage
+    }
+
+    /// Add a chunk to the in-memory indices
+    fn insert(&self, chunk: StoredChunk) {
+        let priority_key = PriorityKey::for_chunk(&chunk);
+        let dest_key = chunk.route.destination.to_string();
+        let chunk_id = chunk.chunk_id.clone();
+        let group_id = chunk.route.fec.as_ref().map(|f| f.group_id.clone());
+
+        let mut chunks = self.chunks.write();
+        let mut priority_idx = self.priority_index.write();
+        let mut dest_idx = self.destination_index.write();
+        let mut used = self.used_bytes.write();
+        let mut dest_bytes = self.destination_bytes.write();
+
+        *used += chunk.size() as u64;
+        *dest_bytes.entry(dest_key.clone()).or_default() += chunk.size() as u64;
+        chunks.insert(chunk_id.clone(), chunk);
+        priority_idx.insert(priority_key, chunk_id.clone());
+        if let Some(group_id) = group_id {
+            let mut group_idx = self.group_index.write();
+            group_idx
+                .entry(group_id)
+                .or_default()
+                .push(chunk_id.clone());
+        }
+        dest_idx.entry(dest_key).or_default().push(chunk_id);
     }
 
     /// Get a chunk by ID
@@ -214,24 +511,18 @@ impl RelayStorage {
 
     /// Remove a chunk (after successful forwarding)
     pub fn remove(&self, chunk_id: &str) -> Option<StoredChunk> {
-        let mut chunks = self.chunks.write();
-        let mut used = self.used_bytes.write();
+        let removed = self.chunks.write().remove(chunk_id);
 
-        if let Some(chunk) = chunks.remove(chunk_id) {
-            *used = used.saturating_sub(chunk.size() as u64);
+        if let Some(chunk) = removed {
+            {
+                let mut used = self.used_bytes.write();
+                *used = used.saturating_sub(chunk.size() as u64);
+            }
 
             // Clean up indices
             {
                 let mut priority_idx = self.priority_index.write();
-                let priority_key = PriorityKey {
-                    priority: chunk.priority(),
-                    stored_millis: chunk
-                        .stored_at
-                        .duration_since(std::time::UNIX_EPOCH)
-                        .unwrap_or_default()
-                        .as_millis() as u64,
-                };
-                priority_idx.remove(&priority_key);
+                priority_idx.remove(&PriorityKey::for_chunk(&chunk));
             }
 
             {
@@ -240,6 +531,23 @@ impl RelayStorage {
                 if let Some(list) = dest_idx.get_mut(&dest_key) {
                     list.retain(|id| id != chunk_id);
                 }
+                let mut dest_bytes = self.destination_bytes.write();
+                if let Some(bytes) = dest_bytes.get_mut(&dest_key) {
+                    *bytes = bytes.saturating_sub(chunk.size() as u64);
+                    if *bytes == 0 {
+                        dest_bytes.remove(&dest_key);
+                    }
+                }
+            }
+
+            if let Some(fec) = &chunk.route.fec {
+                let mut group_idx = self.group_index.write();
+                if let Some(list) = group_idx.get_mut(&fec.group_id) {
+                    list.retain(|id| id != chunk_id);
+                    if list.is_empty() {
+                        group_idx.remove(&fec.group_id);
+                    }
+                }
             }
 
             // Remove persisted file
@@ -273,6 +581,11 @@ impl RelayStorage {
         result
     }
 
+    /// Whether a chunk is stored
+    pub fn contains(&self, chunk_id: &str) -> bool {
+        self.chunks.read().contains_key(chunk_id)
+    }
+
     /// Get chunks for a specific destination
     pub fn get_for_destination(&self, dest: &str) -> Vec<StoredChunk> {
         let chunks = self.chunks.read();
@@ -290,6 +603,36 @@ impl RelayStorage {
             .unwrap_or_default()
     }
 
+    /// Get every stored shard of an FEC group
+    pub fn get_group(&self, group_id: &str) -> Vec<StoredChunk> {
+        let chunks = self.chunks.read();
+        let group_idx = self.group_index.read();
+
+        group_idx
+            .get(group_id)
+            .map(|ids| {
+                ids.iter()
+                    .filter_map(|id| chunks.get(id))
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Sequence numbers of the unexpired chunks of a transfer, sorted
+    pub fn sequences_for_transfer(&self, transfer_id: &str) -> Vec<u32> {
+        let mut sequences: Vec<u32> = self
+            .chunks
+            .read()
+            .values()
+            .filter(|c| c.route.transfer_id == transfer_id && !c.is_expired())
+            .map(|c| c.chunk.metadata.sequence_number)
+            .collect();
+        sequences.sort_unstable();
+        sequences.dedup();
+        sequences
+    }
+
     /// Record a forward attempt for a chunk
     pub fn record_attempt(&self, chunk_id: &str) {
         let mut chunks = self.chunks.write();
@@ -298,8 +641,8 @@ impl RelayStorage {
         }
     }
 
-    /// Remove expired chunks
-    pub fn cleanup_expired(&self) -> Vec<String> {
+    /// Remove expired chunks, returning them
+    pub fn cleanup_expired(&self) -> Vec<StoredChunk> {
         let mut expired = Vec::new();
 
         {
@@ -311,15 +654,17 @@ impl RelayStorage {
             }
         }
 
-        for id in &expired {
-            self.remove(id);
-        }
-
-        expired
+        expired.iter().filter_map(|id| self.remove(id)).collect()
     }
 
     /// Get current storage statistics
     pub fn stats(&self) -> StorageStats {
+        // Compaction reads chunks while holding the disk index, so the
+        // index is never locked while chunks are
+        let (loose_files, segment_files) = {
+            let disk = self.disk.lock();
+            (disk.loose_files(), disk.segments.len() as u64)
+        };
         let chunks = self.chunks.read();
 
         let mut by_priority = [0u64; 3]; // critical, high, normal
@@ -343,17 +688,106 @@ impl RelayStorage {
             max_bytes: self.max_bytes,
             chunks_by_priority: by_priority,
             destinations: by_destination.len() as u64,
+            loose_files,
+            segment_files,
+        }
+    }
+
+    /// Pack chunk files into segments
+    ///
+    /// Segments mostly made of removed chunks are rewritten along the way,
+    /// so their space comes back. Does nothing without persistence.
+    pub fn compact(&self) -> RelayResult<CompactionReport> {
+        let mut report = CompactionReport::default();
+        let Some(ref dir) = self.persistence_path else {
+            return Ok(report);
+        };
+
+        let mut disk = self.disk.lock();
+        let rewrite: HashSet<u64> = disk
+            .segments
+            .iter()
+            .filter(|(_, usage)| usage.dead > 0 && usage.dead >= usage.live)
+            .map(|(segment, _)| *segment)
+            .collect();
+        let mut ids: Vec<String> = disk
+            .locations
+            .iter()
+            .filter(|(_, location)| match location {
+                Location::Loose => true,
+                Location::Segment(segment) => rewrite.contains(segment),
+            })
+            .map(|(id, _)| id.clone())
+            .collect();
+        ids.sort();
+
+        let mut segment = Vec::new();
+        let mut batch = Vec::new();
+        for id in ids {
+            // Removed since the index was read; its file goes with it
+            let Some(record) = self.chunks.read().get(&id).map(encode_record).transpose()? else {
+                continue;
+            };
+            push_segment_record(&mut segment, &record);
+            batch.push(id);
+            if segment.len() >= SEGMENT_TARGET_BYTES {
+                Self::write_segment(dir, &mut disk, &segment, &mut batch, &mut report)?;
+                segment.clear();
+            }
+        }
+        if !batch.is_empty() {
+            Self::write_segment(dir, &mut disk, &segment, &mut batch, &mut report)?;
+        }
+        Ok(report)
+    }
+
+    /// Write one new segment holding `batch`, then drop the files it
+    /// replaces
+    fn write_segment(
+        dir: &Path,
+        disk: &mut DiskIndex,
+        segment: &[u8],
+        batch: &mut Vec<String>,
+        report: &mut CompactionReport,
+    ) -> RelayResult<()> {
+        let number = disk.next_segment;
+        disk.next_segment += 1;
+        write_atomic(&segment_path(dir, number), segment)?;
+        disk.segments.insert(
+            number,
+            SegmentUsage {
+                live: batch.len(),
+                dead: 0,
+            },
+        );
+
+        report.segments_written += 1;
+        report.chunks_packed += batch.len() as u64;
+        for id in batch.drain(..) {
+            match disk.locations.insert(id.clone(), Location::Segment(number)) {
+                Some(Location::Loose) => {
+                    let _ = std::fs::remove_file(chunk_path(dir, &id));
+                    report.files_removed += 1;
+                }
+                Some(Location::Segment(old)) if disk.release(dir, old) => {
+                    report.files_removed += 1;
+                }
+                _ => {}
+            }
         }
+        Ok(())
     }
 
     /// Persist a chunk to disk
-    fn persist_chunk(&self, chunk_id: &str) -> RelayResult<()> {
+    fn persist_chunk(&self, chunk_id: &str, record: &[u8]) -> RelayResult<()> {
         if let Some(ref path) = self.persistence_path {
-            if let Some(chunk) = self.chunks.read().get(chunk_id) {
-                let file_path = path.join(format!("{}.chunk", chunk_id));
-                let data =
-                    bincode::serialize(chunk).map_err(|e| RelayError::Storage(e.to_string()))?;
-                std::fs::write(file_path, data)?;
+            let mut disk = self.disk.lock();
+            write_atomic(&chunk_path(path, chunk_id), record)?;
+            // A copy left in a segment would come back on restart
+            if let Some(Location::Segment(old)) =
+                disk.locations.insert(chunk_id.to_string(), Location::Loose)
+            {
+                Self::bury(path, &mut disk, old, chunk_id);
             }
         }
         Ok(())
@@ -362,38 +796,152 @@ impl RelayStorage {
     /// Remove persisted chunk file
     fn remove_persisted(&self, chunk_id: &str) {
         if let Some(ref path) = self.persistence_path {
-            let file_path = path.join(format!("{}.chunk", chunk_id));
-            let _ = std::fs::remove_file(file_path);
+            let mut disk = self.disk.lock();
+            match disk.locations.remove(chunk_id) {
+                Some(Location::Loose) => {
+                    let _ = std::fs::remove_file(chunk_path(path, chunk_id));
+                }
+                Some(Location::Segment(segment)) => Self::bury(path, &mut disk, segment, chunk_id),
+                None => {}
+            }
         }
     }
 
-    /// Load persisted chunks on startup
-    fn load_persisted(&self) -> RelayResult<()> {
-        if let Some(ref path) = self.persistence_path {
-            for entry in std::fs::read_dir(path)? {
-                let entry = entry?;
-                let file_path = entry.path();
-
-                if file_path.extension().map(|e| e == "chunk").unwrap_or(false) {
-                    if let Ok(data) = std::fs::read(&file_path) {
-                        if let Ok(chunk) = bincode::deserialize::<StoredChunk>(&data) {
-                            // Skip expired chunks
-                            if !chunk.is_expired() {
-                                let _ = self.store(
-                                    chunk.chunk_id.clone(),
-                                    chunk.route.clone(),
-                                    chunk.data.clone(),
-                                );
-                            } else {
-                                // Remove expired persisted chunk
-                                let _ = std::fs::remove_file(file_path);
-                            }
-                        }
+    /// Mark `chunk_id` removed from `segment`
+    fn bury(dir: &Path, disk: &mut DiskIndex, segment: u64, chunk_id: &str) {
+        if disk.release(dir, segment) {
+            return;
+        }
+        if let Err(e) = append_tombstone(&tombstone_path(dir, segment), chunk_id) {
+            tracing::warn!(
+                segment,
+                chunk_id,
+                "failed to record removed relay chunk: {}",
+                e
+            );
+        }
+    }
+
+    /// Check the persistence directory and load what is sound
+    ///
+    /// Segments are read before loose files: a chunk file next to a
+    /// segment holding the same chunk was left by a compaction that stopped
+    /// before cleaning up.
+    fn scan(&self, dir: &Path) -> RelayResult<ScanReport> {
+        let mut report = ScanReport::default();
+        let mut loose = Vec::new();
+        let mut segments = Vec::new();
+        let mut tombstones = Vec::new();
+
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if is_stale_temp(&path) {
+                std::fs::remove_file(&path)?;
+                report.orphans += 1;
+                continue;
+            }
+            let number = || path.file_stem()?.to_str()?.parse::<u64>().ok();
+            match path.extension().and_then(|e| e.to_str()) {
+                Some(segment::CHUNK_EXT) => loose.push(path),
+                Some(segment::SEGMENT_EXT) => {
+                    if let Some(number) = number() {
+                        segments.push(number);
+                    }
+                }
+                Some(segment::TOMBSTONE_EXT) => {
+                    if let Some(number) = number() {
+                        tombstones.push(number);
                     }
                 }
+                _ => {}
             }
         }
-        Ok(())
+
+        segments.sort_unstable();
+        for number in segments {
+            let dead = read_tombstones(&tombstone_path(dir, number))?;
+            let (chunks, corrupt) = read_segment(&segment_path(dir, number))?;
+            report.corrupt += corrupt;
+            let mut usage = SegmentUsage {
+                live: 0,
+                dead: dead.len() + corrupt as usize,
+            };
+            for chunk in chunks {
+                if dead.contains(&chunk.chunk_id) {
+                    continue;
+                }
+                if self.load(chunk, Location::Segment(number), &mut report) {
+                    usage.live += 1;
+                } else {
+                    usage.dead += 1;
+                }
+            }
+
+            let mut disk = self.disk.lock();
+            disk.next_segment = disk.next_segment.max(number + 1);
+            if usage.live == 0 {
+                std::fs::remove_file(segment_path(dir, number))?;
+                let _ = std::fs::remove_file(tombstone_path(dir, number));
+            } else {
+                disk.segments.insert(number, usage);
+                report.segments += 1;
+            }
+        }
+
+        for number in tombstones {
+            let path = tombstone_path(dir, number);
+            if path.exists() && !self.disk.lock().segments.contains_key(&number) {
+                std::fs::remove_file(path)?;
+                report.orphans += 1;
+            }
+        }
+
+        for path in loose {
+            let chunk = std::fs::read(&path)
+                .ok()
+                .and_then(|data| decode_record(&data));
+            let keep = match chunk {
+                None => {
+                    report.corrupt += 1;
+                    false
+                }
+                Some(chunk) if path.file_stem() != Some(chunk.chunk_id.as_ref()) => {
+                    report.orphans += 1;
+                    false
+                }
+                Some(chunk) => self.load(chunk, Location::Loose, &mut report),
+            };
+            if !keep {
+                std::fs::remove_file(&path)?;
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Take a chunk found on disk back into storage, with its original
+    /// hold time; returns whether it was kept
+    fn load(&self, chunk: StoredChunk, location: Location, report: &mut ScanReport) -> bool {
+        if chunk.is_expired() {
+            report.expired += 1;
+            return false;
+        }
+        if self.contains(&chunk.chunk_id) {
+            report.orphans += 1;
+            return false;
+        }
+        if *self.used_bytes.read() + chunk.size() as u64 > self.max_bytes {
+            report.over_capacity += 1;
+            return false;
+        }
+
+        self.disk
+            .lock()
+            .locations
+            .insert(chunk.chunk_id.clone(), location);
+        self.insert(chunk);
+        report.loaded += 1;
+        true
     }
 }
 
@@ -405,6 +953,10 @@ pub struct StorageStats {
     pub max_bytes: u64,
     pub chunks_by_priority: [u64; 3],
     pub destinations: u64,
+    /// Persisted chunks in files of their own
+    pub loose_files: u64,
+    /// Persisted segment files
+    pub segment_files: u64,
 }
 
 impl StorageStats {
@@ -436,6 +988,7 @@ pub struct RelayStorageBuilder {
     max_bytes: u64,
     hold_time: Duration,
     persistence_path: Option<PathBuf>,
+    quotas: DestinationQuotas,
 }
 
 impl RelayStorageBuilder {
@@ -444,9 +997,15 @@ impl RelayStorageBuilder {
             max_bytes: 1024 * 1024 * 1024,                // 1GB default
             hold_time: Duration::from_secs(24 * 60 * 60), // 24 hours
             persistence_path: None,
+            quotas: DestinationQuotas::default(),
         }
     }
 
+    pub fn quotas(mut self, quotas: DestinationQuotas) -> Self {
+        self.quotas = quotas;
+        self
+    }
+
     pub fn max_bytes(mut self, bytes: u64) -> Self {
         self.max_bytes = bytes;
         self
@@ -465,11 +1024,12 @@ impl RelayStorageBuilder {
     pub fn build(self) -> RelayResult<RelayStorage> {
         let storage = RelayStorage::new(self.max_bytes, self.hold_time);
 
-        if let Some(path) = self.persistence_path {
-            storage.with_persistence(path)
+        let storage = if let Some(path) = self.persistence_path {
+            storage.with_persistence(path)?
         } else {
-            Ok(storage)
-        }
+            storage
+        };
+        Ok(storage.with_quotas(self.quotas))
     }
 }
 
@@ -482,6 +1042,7 @@ impl Default for RelayStorageBuilder {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::relay::types::{test_chunk, FecShardInfo};
     use std::net::SocketAddr;
 
     fn test_route() -> RouteInfo {
@@ -493,16 +1054,73 @@ mod tests {
         )
     }
 
+    #[test]
+    fn test_destination_quota_reject_and_evict() {
+        let near: SocketAddr = "127.0.0.1:8000".parse().unwrap();
+        let far: SocketAddr = "127.0.0.1:8001".parse().unwrap();
+        let mut quotas = DestinationQuotas {
+            default_bytes: 10,
+            ..Default::default()
+        };
+        quotas.overrides.insert(far, 100);
+        let storage = RelayStorageBuilder::new().quotas(quotas).build().unwrap();
+        let route = |dest, priority| RouteInfo::new("source", dest, "transfer-1", priority);
+
+        storage
+            .store("a".into(), route(near, 2), test_chunk(vec![0; 4]))
+            .unwrap();
+        storage
+            .store("b".into(), route(near, 2), test_chunk(vec![0; 4]))
+            .unwrap();
+        assert!(matches!(
+            storage.store("c".into(), route(near, 2), test_chunk(vec![0; 4])),
+            Err(RelayError::QuotaExceeded { limit: 10, .. })
+        ));
+        // Another destination has its own allowance
+        storage
+            .store("d".into(), route(far, 2), test_chunk(vec![0; 40]))
+            .unwrap();
+
+        let usage = storage.destination_usage();
+        assert_eq!(usage[0].destination, far);
+        assert_eq!(usage[0].quota, Some(100));
+        assert_eq!((usage[1].chunks, usage[1].bytes), (2, 8));
+
+        storage.set_quotas(DestinationQuotas {
+            on_breach: QuotaBreach::EvictOldest,
+            ..storage.quotas()
+        });
+        let ids = |evicted: Vec<StoredChunk>| -> Vec<String> {
+            evicted.into_iter().map(|c| c.chunk_id).collect()
+        };
+        // Each chunk evicts the destination's oldest chunks of the same or
+        // lower priority
+        let evicted = storage.store("e".into(), route(near, 1), test_chunk(vec![0; 4]));
+        assert_eq!(ids(evicted.unwrap()), ["a"]);
+        let evicted = storage.store("f".into(), route(near, 2), test_chunk(vec![0; 4]));
+        assert_eq!(ids(evicted.unwrap()), ["b"]);
+
+        // A more important chunk isn't evicted for a less important one
+        assert!(storage
+            .store("g".into(), route(near, 2), test_chunk(vec![0; 8]))
+            .is_err());
+        assert!(storage
+            .store("h".into(), route(near, 1), test_chunk(vec![0; 11]))
+            .is_err());
+        assert!(storage.contains("e") && storage.contains("f"));
+        assert_eq!(storage.destination_usage()[1].bytes, 8);
+    }
+
     #[test]
     fn test_store_and_retrieve() {
         let storage = RelayStorage::new(1024 * 1024, Duration::from_secs(60));
 
         storage
-            .store("chunk-1".into(), test_route(), vec![1, 2, 3, 4])
+            .store("chunk-1".into(), test_route(), test_chunk(vec![1, 2, 3, 4]))
             .unwrap();
 
         let chunk = storage.get("chunk-1").unwrap();
-        assert_eq!(chunk.data, vec![1, 2, 3, 4]);
+        assert_eq!(chunk.chunk.data, vec![1, 2, 3, 4]);
     }
 
     #[test]
@@ -510,7 +1128,7 @@ mod tests {
         let storage = RelayStorage::new(1024 * 1024, Duration::from_secs(60));
 
         storage
-            .store("chunk-1".into(), test_route(), vec![1, 2, 3, 4])
+            .store("chunk-1".into(), test_route(), test_chunk(vec![1, 2, 3, 4]))
             .unwrap();
 
         let removed = storage.remove("chunk-1").unwrap();
@@ -519,16 +1137,66 @@ mod tests {
         assert!(storage.get("chunk-1").is_none());
     }
 
+    #[test]
+    fn test_get_group_follows_stores_and_removes() {
+        let storage = RelayStorage::new(1024 * 1024, Duration::from_secs(60));
+        let shard = |group: &str, index| {
+            let mut route = test_route();
+            route.fec = Some(FecShardInfo {
+                group_id: group.into(),
+                shard_index: index,
+                data_shards: 2,
+                parity_shards: 1,
+            });
+            route
+        };
+
+        for index in 0..3 {
+            let id = format!("g1:{}", index);
+            storage
+                .store(id, shard("g1", index), test_chunk(vec![index as u8; 4]))
+                .unwrap();
+        }
+        storage
+            .store("g2:0".into(), shard("g2", 0), test_chunk(vec![9; 4]))
+            .unwrap();
+        storage
+            .store("plain".into(), test_route(), test_chunk(vec![1; 4]))
+            .unwrap();
+
+        assert_eq!(storage.get_group("g1").len(), 3);
+        storage.remove("g1:1");
+        let mut ids: Vec<_> = storage
+            .get_group("g1")
+            .into_iter()
+            .map(|c| c.chunk_id)
+            .collect();
+        ids.sort();
+        assert_eq!(ids, ["g1:0", "g1:2"]);
+
+        storage.remove("g2:0");
+        assert!(storage.get_group("g2").is_empty());
+        assert!(storage.group_index.read().get("g2").is_none());
+    }
+
     #[test]
     fn test_capacity_limit() {
         let storage = RelayStorage::new(10, Duration::from_secs(60));
 
         storage
-            .store("chunk-1".into(), test_route(), vec![1, 2, 3, 4, 5])
+            .store(
+                "chunk-1".into(),
+                test_route(),
+                test_chunk(vec![1, 2, 3, 4, 5]),
+            )
             .unwrap();
 
         // This should exceed capacity
-        let result = storage.store("chunk-2".into(), test_route(), vec![1, 2, 3, 4, 5, 6]);
+        let result = storage.store(
+            "chunk-2".into(),
+            test_route(),
+            test_chunk(vec![1, 2, 3, 4, 5, 6]),
+        );
 
         assert!(matches!(result, Err(RelayError::CapacityExceeded)));
     }
@@ -548,13 +1216,13 @@ mod tests {
         route_normal.priority = 2;
 
         storage
-            .store("chunk-normal".into(), route_normal, vec![1])
+            .store("chunk-normal".into(), route_normal, test_chunk(vec![1]))
             .unwrap();
         storage
-            .store("chunk-high".into(), route_high, vec![2])
+            .store("chunk-high".into(), route_high, test_chunk(vec![2]))
             .unwrap();
         storage
-            .store("chunk-critical".into(), route_critical, vec![3])
+            .store("chunk-critical".into(), route_critical, test_chunk(vec![3]))
             .unwrap();
 
         let pending = storage.get_pending(3, Duration::ZERO);
@@ -569,10 +1237,10 @@ mod tests {
         let storage = RelayStorage::new(1024 * 1024, Duration::from_secs(60));
 
         storage
-            .store("chunk-1".into(), test_route(), vec![1, 2, 3, 4])
+            .store("chunk-1".into(), test_route(), test_chunk(vec![1, 2, 3, 4]))
             .unwrap();
         storage
-            .store("chunk-2".into(), test_route(), vec![5, 6, 7, 8])
+            .store("chunk-2".into(), test_route(), test_chunk(vec![5, 6, 7, 8]))
             .unwrap();
 
         let stats = storage.stats();
@@ -585,7 +1253,7 @@ mod tests {
         let storage = RelayStorage::new(1024 * 1024, Duration::from_millis(1));
 
         storage
-            .store("chunk-1".into(), test_route(), vec![1, 2, 3, 4])
+            .store("chunk-1".into(), test_route(), test_chunk(vec![1, 2, 3, 4]))
             .unwrap();
 
         // Wait for expiration
@@ -593,8 +1261,124 @@ mod tests {
 
         let expired = storage.cleanup_expired();
         assert_eq!(expired.len(), 1);
-        assert_eq!(expired[0], "chunk-1");
+        assert_eq!(expired[0].chunk_id, "chunk-1");
 
         assert!(storage.get("chunk-1").is_none());
     }
+
+    fn files_with(dir: &Path, ext: &str) -> usize {
+        std::fs::read_dir(dir)
+            .unwrap()
+            .filter(|e| e.as_ref().unwrap().path().extension() == Some(ext.as_ref()))
+            .count()
+    }
+
+    #[test]
+    fn test_startup_scan_discards_bad_files() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let open = || {
+            RelayStorage::new(1024 * 1024, Duration::from_secs(60))
+                .with_persistence(dir.path())
+                .unwrap()
+        };
+
+        let storage = open();
+        for id in ["chunk-1", "chunk-2", "chunk-3"] {
+            storage
+                .store(id.into(), test_route(), test_chunk(vec![9; 16]))
+                .unwrap();
+        }
+        assert_eq!(storage.scan_report(), Some(ScanReport::default()));
+        drop(storage);
+
+        // A truncated write, a torn staging file, a copy under the wrong
+        // name and a chunk whose hold time ran out while the relay was down
+        let good = std::fs::read(dir.path().join("chunk-1.chunk")).unwrap();
+        std::fs::write(dir.path().join("chunk-2.chunk"), &good[..good.len() - 4]).unwrap();
+        std::fs::write(dir.path().join("chunk-4.chunk.tmp"), &good[..8]).unwrap();
+        std::fs::write(dir.path().join("copy.chunk"), &good).unwrap();
+        let stale = StoredChunk::new(
+            "old".into(),
+            test_route(),
+            test_chunk(vec![1]),
+            Duration::ZERO,
+        );
+        std::fs::write(dir.path().join("old.chunk"), encode_record(&stale).unwrap()).unwrap();
+
+        let storage = open();
+        let report = storage.scan_report().unwrap();
+        assert_eq!(
+            report,
+            ScanReport {
+                loaded: 2,
+                corrupt: 1,
+                expired: 1,
+                orphans: 2,
+                over_capacity: 0,
+                segments: 0,
+            }
+        );
+        assert!(storage.contains("chunk-1") && storage.contains("chunk-3"));
+        assert!(!storage.contains("chunk-2"));
+        assert_eq!(files_with(dir.path(), "chunk"), 2);
+        assert_eq!(files_with(dir.path(), "tmp"), 0);
+    }
+
+    #[test]
+    fn test_compaction_packs_files_and_survives_restart() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let open = || {
+            RelayStorage::new(1024 * 1024, Duration::from_secs(60))
+                .with_persistence(dir.path())
+                .unwrap()
+        };
+
+        let storage = open();
+        for i in 0..5 {
+            storage
+                .store(format!("chunk-{i}"), test_route(), test_chunk(vec![i; 32]))
+                .unwrap();
+        }
+        let report = storage.compact().unwrap();
+        assert_eq!(
+            report,
+            CompactionReport {
+                chunks_packed: 5,
+                segments_written: 1,
+                files_removed: 5,
+            }
+        );
+        let stats = storage.stats();
+        assert_eq!((stats.loose_files, stats.segment_files), (0, 1));
+        assert_eq!(files_with(dir.path(), "chunk"), 0);
+
+        // Removals from the segment stay removed across a restart
+        storage.remove("chunk-0");
+        storage.remove("chunk-1");
+        storage
+            .store("chunk-5".into(), test_route(), test_chunk(vec![5; 32]))
+            .unwrap();
+        drop(storage);
+
+        let storage = open();
+        let report = storage.scan_report().unwrap();
+        assert_eq!((report.loaded, report.segments), (4, 1));
+        assert!(!storage.contains("chunk-0") && !storage.contains("chunk-1"));
+        assert_eq!(storage.get("chunk-3").unwrap().chunk.data, vec![3; 32]);
+
+        // A mostly dead segment is rewritten with the loose chunk
+        storage.remove("chunk-2");
+        let report = storage.compact().unwrap();
+        assert_eq!(report.chunks_packed, 3);
+        assert_eq!(report.files_removed, 2);
+        assert_eq!(files_with(dir.path(), "segment"), 1);
+        assert_eq!(files_with(dir.path(), "dead"), 0);
+
+        for id in ["chunk-3", "chunk-4", "chunk-5"] {
+            storage.remove(id);
+        }
+        assert_eq!(files_with(dir.path(), "segment"), 0);
+        drop(storage);
+        assert_eq!(open().scan_report().unwrap().loaded, 0);
+    }
 }
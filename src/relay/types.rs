@@ -1,7 +1,12 @@
 //! Relay types and configuration
 
+use crate::chunk::Chunk;
+use crate::priority::types::{bucket_of, bucket_percentile};
+use crate::relay::identity::{AccessPolicy, HelloProof, NodePublicKey, StoreAuth};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::time::Duration;
 use thiserror::Error;
 
@@ -17,6 +22,9 @@ pub enum RelayError {
     #[error("Network error: {0}")]
     Network(String),
 
+    #[error("Rejected by peer: {0}")]
+    Rejected(String),
+
     #[error("Chunk not found: {0}")]
     ChunkNotFound(String),
 
@@ -26,12 +34,21 @@ pub enum RelayError {
     #[error("Storage capacity exceeded")]
     CapacityExceeded,
 
+    #[error("Quota of {limit} bytes exceeded for destination {destination}")]
+    QuotaExceeded { destination: SocketAddr, limit: u64 },
+
     #[error("Chunk expired: {0}")]
     ChunkExpired(String),
 
     #[error("Invalid configuration: {0}")]
     InvalidConfig(String),
 
+    #[error("Unauthorized: {0}")]
+    Unauthorized(String),
+
+    #[error("Chunk failed verification: {0}")]
+    Corrupt(String),
+
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
 }
@@ -54,6 +71,11 @@ pub struct RelayConfig {
     /// How often to attempt forwarding stored chunks
     pub forward_interval: Duration,
 
+    /// Up to this much random delay is added to each scheduled cycle, so
+    /// relays started together don't forward in lockstep
+    #[serde(default = "default_forward_jitter")]
+    pub forward_jitter: Duration,
+
     /// Maximum retry attempts for forwarding
     pub max_forward_retries: u32,
 
@@ -62,6 +84,48 @@ pub struct RelayConfig {
 
     /// Forwarding policy
     pub policy: ForwardingPolicy,
+
+    /// Where runtime policy changes are saved (None = not persisted)
+    ///
+    /// A policy saved here overrides `policy` when the node starts.
+    #[serde(default)]
+    pub policy_path: Option<PathBuf>,
+
+    /// Directory where stored chunks, the peer table and cumulative stats
+    /// are kept across restarts (None = memory only)
+    #[serde(default)]
+    pub persistence_path: Option<PathBuf>,
+
+    /// Pack stored chunks into segment files once this many have files of
+    /// their own (0 = never)
+    #[serde(default = "default_compaction_threshold")]
+    pub compaction_threshold: u64,
+
+    /// Learned peers not seen for this long are dropped (zero = never);
+    /// peers from `peers` are always kept
+    #[serde(default = "default_peer_expiry")]
+    pub peer_expiry: Duration,
+
+    /// Which peers may store chunks here, and where this node's keypair
+    /// is kept
+    #[serde(default)]
+    pub access: AccessPolicy,
+
+    /// How much of the storage each destination may hold
+    #[serde(default)]
+    pub quotas: DestinationQuotas,
+}
+
+fn default_forward_jitter() -> Duration {
+    Duration::from_secs(3)
+}
+
+fn default_peer_expiry() -> Duration {
+    Duration::from_secs(7 * 24 * 60 * 60)
+}
+
+fn default_compaction_threshold() -> u64 {
+    1024
 }
 
 impl Default for RelayConfig {
@@ -72,13 +136,142 @@ impl Default for RelayConfig {
             max_storage_bytes: 1024 * 1024 * 1024, // 1GB
             max_hold_time: Duration::from_secs(24 * 60 * 60), // 24 hours
             forward_interval: Duration::from_secs(30),
+            forward_jitter: default_forward_jitter(),
             max_forward_retries: 10,
             peers: Vec::new(),
             policy: ForwardingPolicy::default(),
+            policy_path: None,
+            persistence_path: None,
+            compaction_threshold: default_compaction_threshold(),
+            peer_expiry: default_peer_expiry(),
+            access: AccessPolicy::default(),
+            quotas: DestinationQuotas::default(),
+        }
+    }
+}
+
+/// What happens to a chunk that would take its destination past quota
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuotaBreach {
+    /// Refuse the new chunk
+    #[default]
+    Reject,
+    /// Make room by dropping the destination's own oldest chunks of the
+    /// same or lower priority; refuse the chunk if that isn't enough
+    EvictOldest,
+}
+
+/// Per-destination byte limits on relay storage, so one destination's
+/// backlog can't fill the relay
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DestinationQuotas {
+    /// Bytes any one destination may hold (0 = no limit)
+    #[serde(default)]
+    pub default_bytes: u64,
+
+    /// Limits for particular destinations, in place of the default
+    /// (0 = no limit)
+    #[serde(default)]
+    pub overrides: HashMap<SocketAddr, u64>,
+
+    #[serde(default)]
+    pub on_breach: QuotaBreach,
+}
+
+impl DestinationQuotas {
+    /// Bytes `destination` may hold, or `None` when unlimited
+    pub fn limit_for(&self, destination: SocketAddr) -> Option<u64> {
+        let limit = self
+            .overrides
+            .get(&destination)
+            .copied()
+            .unwrap_or(self.default_bytes);
+        (limit > 0).then_some(limit)
+    }
+}
+
+/// Storage held for one destination
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DestinationUsage {
+    pub destination: SocketAddr,
+    pub chunks: u64,
+    pub bytes: u64,
+    /// Quota in effect (None = unlimited)
+    pub quota: Option<u64>,
+}
+
+/// Upper bounds (ms) of the forward latency histogram buckets; a final
+/// bucket holds anything longer. Chunks can wait hours for a link, so the
+/// range runs well past the send queue's.
+pub const FORWARD_LATENCY_BUCKETS_MS: [u64; 12] = [
+    10, 100, 500, 1_000, 5_000, 30_000, 60_000, 300_000, 1_800_000, 3_600_000, 21_600_000,
+    86_400_000,
+];
+
+/// Weight of the newest chunk in [`ForwardLatency::ewma_ms`]
+pub const FORWARD_LATENCY_EWMA_WEIGHT: f64 = 0.1;
+
+/// How long forwarded chunks were held between being stored and leaving
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ForwardLatency {
+    pub forwarded: u64,
+    /// Moving average weighted towards recent chunks
+    pub ewma_ms: f64,
+    pub total_ms: u64,
+    /// Percentiles to [`FORWARD_LATENCY_BUCKETS_MS`] bucket precision
+    pub p50_ms: u64,
+    pub p95_ms: u64,
+    pub p99_ms: u64,
+    pub max_ms: u64,
+    /// Forwarded chunks per bucket, plus the overflow bucket
+    pub histogram: Vec<u64>,
+}
+
+impl ForwardLatency {
+    /// Record the latency of a forwarded chunk
+    pub fn record(&mut self, latency_ms: u64) {
+        if self.histogram.is_empty() {
+            self.histogram = vec![0; FORWARD_LATENCY_BUCKETS_MS.len() + 1];
         }
+        self.histogram[bucket_of(&FORWARD_LATENCY_BUCKETS_MS, latency_ms)] += 1;
+        self.ewma_ms = if self.forwarded == 0 {
+            latency_ms as f64
+        } else {
+            self.ewma_ms + FORWARD_LATENCY_EWMA_WEIGHT * (latency_ms as f64 - self.ewma_ms)
+        };
+        self.forwarded += 1;
+        self.total_ms = self.total_ms.saturating_add(latency_ms);
+        self.max_ms = self.max_ms.max(latency_ms);
+        self.p50_ms = self.percentile(0.50);
+        self.p95_ms = self.percentile(0.95);
+        self.p99_ms = self.percentile(0.99);
+    }
+
+    /// Mean latency over every forwarded chunk
+    pub fn avg_ms(&self) -> u64 {
+        self.total_ms.checked_div(self.forwarded).unwrap_or(0)
+    }
+
+    /// Latency below which `fraction` of forwarded chunks fall
+    pub fn percentile(&self, fraction: f64) -> u64 {
+        bucket_percentile(
+            &FORWARD_LATENCY_BUCKETS_MS,
+            &self.histogram,
+            self.max_ms,
+            fraction,
+        )
     }
 }
 
+/// Forward latency of chunks handed to one address: a receiver, for direct
+/// and pulled delivery, or the next relay
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DestinationLatency {
+    pub destination: SocketAddr,
+    pub latency: ForwardLatency,
+}
+
 /// Information about a peer relay node
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PeerInfo {
@@ -98,6 +291,15 @@ pub struct PeerInfo {
     /// Last successful contact time
     #[serde(skip)]
     pub last_contact: Option<std::time::Instant>,
+
+    /// Unix timestamp this peer was last heard from, directly or via
+    /// another relay's peer list
+    #[serde(default)]
+    pub last_seen: Option<i64>,
+
+    /// Key pinned from the peer's first signed `Hello`
+    #[serde(default)]
+    pub public_key: Option<NodePublicKey>,
 }
 
 impl PeerInfo {
@@ -109,6 +311,8 @@ impl PeerInfo {
             priority: 100,
             reachable: false,
             last_contact: None,
+            last_seen: None,
+            public_key: None,
         }
     }
 
@@ -117,6 +321,23 @@ impl PeerInfo {
         self.priority = priority;
         self
     }
+
+    /// Record successful contact now
+    pub fn touch(&mut self) {
+        self.reachable = true;
+        self.last_contact = Some(std::time::Instant::now());
+        self.last_seen = Some(chrono::Utc::now().timestamp());
+    }
+
+    /// Whether the peer was last seen longer than `expiry` before `now`
+    ///
+    /// Peers never seen have nothing to age from and don't go stale.
+    pub fn is_stale(&self, expiry: Duration, now: i64) -> bool {
+        match self.last_seen {
+            Some(seen) => now.saturating_sub(seen) > expiry.as_secs() as i64,
+            None => false,
+        }
+    }
 }
 
 /// Policy for forwarding chunks through the relay network
@@ -136,6 +357,20 @@ pub struct ForwardingPolicy {
 
     /// Minimum delay between forward attempts to same destination
     pub retry_cooldown: Duration,
+
+    /// Re-encode FEC groups at this hop (None = forward shards untouched)
+    #[serde(default)]
+    pub hop_fec: Option<HopFecPolicy>,
+
+    /// Relays that hold a copy of each critical chunk, counting the first
+    /// one to receive it (0 or 1 = no replication)
+    #[serde(default)]
+    pub replication_factor: usize,
+
+    /// Send critical chunks to every peer at once (None = to the first
+    /// peer that takes them)
+    #[serde(default)]
+    pub flood: Option<FloodPolicy>,
 }
 
 impl Default for ForwardingPolicy {
@@ -146,10 +381,135 @@ impl Default for ForwardingPolicy {
             prefer_direct: true,
             priority_aware: true,
             retry_cooldown: Duration::from_secs(5),
+            hop_fec: None,
+            replication_factor: 1,
+            flood: None,
         }
     }
 }
 
+/// Runtime change to a [`ForwardingPolicy`]; `None` fields are left as is
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PolicyUpdate {
+    pub forward_immediately: Option<bool>,
+    pub max_hops: Option<u8>,
+    pub prefer_direct: Option<bool>,
+    pub retry_cooldown: Option<Duration>,
+}
+
+impl PolicyUpdate {
+    /// Whether the update changes nothing
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Apply the set fields to `policy`
+    pub fn apply_to(&self, policy: &mut ForwardingPolicy) {
+        if let Some(forward_immediately) = self.forward_immediately {
+            policy.forward_immediately = forward_immediately;
+        }
+        if let Some(max_hops) = self.max_hops {
+            policy.max_hops = max_hops;
+        }
+        if let Some(prefer_direct) = self.prefer_direct {
+            policy.prefer_direct = prefer_direct;
+        }
+        if let Some(retry_cooldown) = self.retry_cooldown {
+            policy.retry_cooldown = retry_cooldown;
+        }
+    }
+}
+
+/// Flooding of critical chunks, for meshes too partitioned to trust any one
+/// path
+///
+/// A flooded chunk goes to its destination and to every peer not already on
+/// its route, rather than to the first that takes it. Each copy carries what
+/// is left of `ttl`, and a relay drops copies of a chunk id it has flooded
+/// or taken within `suppression_window`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FloodPolicy {
+    /// Relay hops over which copies are flooded on, counting the first
+    pub ttl: u8,
+
+    /// How long a flooded chunk id is remembered for dropping copies
+    pub suppression_window: Duration,
+}
+
+impl Default for FloodPolicy {
+    fn default() -> Self {
+        Self {
+            ttl: 3,
+            suppression_window: Duration::from_secs(600),
+        }
+    }
+}
+
+/// Per-hop FEC re-encoding settings
+///
+/// When enabled, the relay holds shards of an FEC group until it can decode
+/// the group, repairs any missing data shards, and re-encodes with parity
+/// sized for the next hop's observed loss.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HopFecPolicy {
+    /// Parity shards used on a clean next hop
+    pub min_parity_shards: usize,
+
+    /// Upper bound on parity shards regardless of loss
+    pub max_parity_shards: usize,
+
+    /// Multiplier applied to the observed loss rate when sizing parity
+    pub safety_factor: f32,
+}
+
+impl Default for HopFecPolicy {
+    fn default() -> Self {
+        Self {
+            min_parity_shards: 1,
+            max_parity_shards: 32,
+            safety_factor: 1.5,
+        }
+    }
+}
+
+impl HopFecPolicy {
+    /// Parity shards to use for `data_shards` over a hop with `loss_rate`
+    pub fn parity_for(&self, data_shards: usize, loss_rate: f32) -> usize {
+        let expected_losses =
+            (data_shards as f32 * loss_rate.clamp(0.0, 1.0) * self.safety_factor).ceil() as usize;
+        expected_losses.clamp(self.min_parity_shards, self.max_parity_shards.max(1))
+    }
+}
+
+/// Position of a relayed chunk within an erasure-coded group
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FecShardInfo {
+    /// Identifier shared by every shard of the group
+    pub group_id: String,
+
+    /// Index of this shard (data shards first, then parity)
+    pub shard_index: usize,
+
+    /// Number of data shards in the group
+    pub data_shards: usize,
+
+    /// Number of parity shards in the group
+    pub parity_shards: usize,
+}
+
+impl FecShardInfo {
+    /// Whether this shard carries parity rather than data
+    pub fn is_parity(&self) -> bool {
+        self.shard_index >= self.data_shards
+    }
+
+    /// Chunk id used for a shard of a re-encoded group
+    pub fn chunk_id(&self) -> String {
+        format!("{}:{}", self.group_id, self.shard_index)
+    }
+}
+
 /// Routing information for a chunk
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RouteInfo {
@@ -170,6 +530,24 @@ pub struct RouteInfo {
 
     /// Time-to-live in hops
     pub ttl: u8,
+
+    /// FEC group membership, for hop-level re-encoding
+    #[serde(default)]
+    pub fec: Option<FecShardInfo>,
+
+    /// Redundant copy placed by another relay; never replicated further
+    #[serde(default)]
+    pub replica: bool,
+
+    /// Position of the chunk in its transfer, so the origin can resend it
+    /// if a relay gives up on it
+    #[serde(default)]
+    pub sequence_number: Option<u32>,
+
+    /// Hops a flooded copy may still be flooded on; `None` for chunks that
+    /// weren't flooded
+    #[serde(default)]
+    pub flood_ttl: Option<u8>,
 }
 
 impl RouteInfo {
@@ -187,9 +565,25 @@ impl RouteInfo {
             hops: Vec::new(),
             priority,
             ttl: 10,
+            fec: None,
+            replica: false,
+            sequence_number: None,
+            flood_ttl: None,
         }
     }
 
+    /// Tag the chunk with its sequence number in the transfer
+    pub fn with_sequence(mut self, sequence_number: u32) -> Self {
+        self.sequence_number = Some(sequence_number);
+        self
+    }
+
+    /// Tag the chunk as a shard of an FEC group
+    pub fn with_fec(mut self, fec: FecShardInfo) -> Self {
+        self.fec = Some(fec);
+        self
+    }
+
     /// Add a hop to the route
     pub fn add_hop(&mut self, node_id: &str) {
         self.hops.push(node_id.to_string());
@@ -207,6 +601,66 @@ impl RouteInfo {
     pub fn hop_count(&self) -> usize {
         self.hops.len()
     }
+
+    /// Whether the chunk was sent at `Priority::Critical`
+    pub fn is_critical(&self) -> bool {
+        self.priority == crate::chunk::Priority::Critical.level()
+    }
+}
+
+/// Why a relay gave up on a chunk
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExpiryReason {
+    /// Held past `max_hold_time` without a delivery
+    Expired,
+    /// Every one of `max_forward_retries` attempts failed
+    RetriesExhausted,
+    /// Arrived with its TTL or the hop limit used up
+    HopLimit,
+    /// Evicted to keep its destination within quota
+    QuotaEvicted,
+    /// Arrived with data that doesn't match its checksum
+    Corrupted,
+}
+
+/// Word sent back towards the origin that a relay dropped a chunk
+///
+/// Delivery is best-effort: each relay on the way passes it to the previous
+/// hop it knows, and a notice that can't go further is lost.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExpiredNotice {
+    pub chunk_id: String,
+    pub transfer_id: String,
+    pub sequence_number: Option<u32>,
+    /// Node the chunk came from
+    pub origin: String,
+    /// Relay that dropped the chunk
+    pub dropped_by: String,
+    pub reason: ExpiryReason,
+    /// Relays between the dropping one and the origin, nearest first
+    pub return_path: Vec<String>,
+}
+
+impl ExpiredNotice {
+    /// Notice for a chunk on `route` dropped by `node_id`
+    pub fn new(chunk_id: &str, route: &RouteInfo, node_id: &str, reason: ExpiryReason) -> Self {
+        Self {
+            chunk_id: chunk_id.to_string(),
+            transfer_id: route.transfer_id.clone(),
+            sequence_number: route.sequence_number,
+            origin: route.source.clone(),
+            dropped_by: node_id.to_string(),
+            reason,
+            return_path: route
+                .hops
+                .iter()
+                .rev()
+                .filter(|hop| *hop != node_id)
+                .cloned()
+                .collect(),
+        }
+    }
 }
 
 /// Statistics for a relay node
@@ -241,6 +695,93 @@ pub struct RelayStats {
 
     /// Average forward latency in milliseconds
     pub avg_forward_latency_ms: u64,
+
+    /// Store to forward latency of every chunk that left this relay
+    #[serde(default)]
+    pub forward_latency: ForwardLatency,
+
+    /// Store to forward latency per next hop, sorted by address
+    #[serde(default)]
+    pub destination_latency: Vec<DestinationLatency>,
+
+    /// FEC groups decoded and re-encoded at this hop
+    #[serde(default)]
+    pub hop_fec_groups: u64,
+
+    /// Data shards lost on the inbound hop and repaired here
+    #[serde(default)]
+    pub hop_fec_repairs: u64,
+
+    /// Chunks handed to receivers that pulled them
+    #[serde(default)]
+    pub chunks_pulled: u64,
+
+    /// Copies of critical chunks placed on peer relays
+    #[serde(default)]
+    pub replicas_created: u64,
+
+    /// Stored copies dropped because another relay delivered the chunk, or
+    /// because the same chunk arrived twice
+    #[serde(default)]
+    pub duplicates_discarded: u64,
+
+    /// Critical chunks sent to every peer at once
+    #[serde(default)]
+    pub chunks_flooded: u64,
+
+    /// Copies sent to peers while flooding
+    #[serde(default)]
+    pub flood_copies_sent: u64,
+
+    /// Flooded copies dropped because their chunk id was already seen here
+    #[serde(default)]
+    pub flood_duplicates_suppressed: u64,
+
+    /// Expiry notices a previous relay acknowledged, or handed to this
+    /// node's listener when no relay on the way back answered
+    #[serde(default)]
+    pub expiry_notices_sent: u64,
+
+    /// Store attempts turned away by the access policy
+    #[serde(default)]
+    pub unauthorized_stores: u64,
+
+    /// `Hello` messages with a bad or missing signature, or from a denied
+    /// node
+    #[serde(default)]
+    pub rejected_hellos: u64,
+
+    /// Chunks refused because their destination was at quota
+    #[serde(default)]
+    pub quota_rejections: u64,
+
+    /// Chunks dropped to make room within their destination's quota
+    #[serde(default)]
+    pub quota_evictions: u64,
+
+    /// Chunks that arrived failing their checksum
+    #[serde(default)]
+    pub corrupt_chunks: u64,
+
+    /// Maintenance cycles completed
+    #[serde(default)]
+    pub maintenance_cycles: u64,
+
+    /// Scheduled cycles skipped because the previous one was still running
+    #[serde(default)]
+    pub maintenance_skipped: u64,
+
+    /// Duration of the most recent maintenance cycle in milliseconds
+    #[serde(default)]
+    pub last_cycle_ms: u64,
+
+    /// Longest maintenance cycle in milliseconds
+    #[serde(default)]
+    pub max_cycle_ms: u64,
+
+    /// Time spent in maintenance cycles in milliseconds
+    #[serde(default)]
+    pub total_cycle_ms: u64,
 }
 
 impl RelayStats {
@@ -253,6 +794,13 @@ impl RelayStats {
         self.chunks_forwarded as f64 / total as f64 * 100.0
     }
 
+    /// Average maintenance cycle duration in milliseconds
+    pub fn avg_cycle_ms(&self) -> u64 {
+        self.total_cycle_ms
+            .checked_div(self.maintenance_cycles)
+            .unwrap_or(0)
+    }
+
     /// Calculate storage utilization percentage
     pub fn storage_utilization(&self, max_bytes: u64) -> f64 {
         if max_bytes == 0 {
@@ -277,13 +825,21 @@ impl std::fmt::Display for RelayStats {
 }
 
 /// Message types for relay protocol
+// Most messages are stores, so boxing the chunk would only add an allocation
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum RelayMessage {
     /// Store a chunk for forwarding
+    ///
+    /// The chunk travels with its metadata, so each relay checks it against
+    /// its checksum before taking it.
     Store {
         chunk_id: String,
         route: RouteInfo,
-        data: Vec<u8>,
+        chunk: Chunk,
+        /// Signed sender identity (see [`identity`](crate::relay::identity))
+        #[serde(default)]
+        auth: Option<StoreAuth>,
     },
 
     /// Acknowledge receipt of a chunk
@@ -300,10 +856,126 @@ pub enum RelayMessage {
     },
 
     /// Peer discovery
-    Hello { node_id: String, addr: SocketAddr },
+    Hello {
+        node_id: String,
+        addr: SocketAddr,
+        #[serde(default)]
+        proof: Option<HelloProof>,
+    },
 
     /// Peer list exchange
     PeerList { peers: Vec<PeerInfo> },
+
+    /// Ask which chunks are held for a destination
+    QueryDestination { destination: SocketAddr },
+
+    /// Response to a destination query, grouped by transfer
+    Available {
+        node_id: String,
+        transfers: Vec<AvailableChunks>,
+    },
+
+    /// Request delivery of held chunks
+    Pull {
+        destination: SocketAddr,
+        chunk_ids: Vec<String>,
+    },
+
+    /// Chunks handed over in response to a pull
+    Deliver { chunks: Vec<PulledChunk> },
+
+    /// A chunk this relay holds a replica of has reached its destination
+    Delivered { chunk_id: String },
+
+    /// A relay further along dropped a chunk; pass it on towards the origin
+    Expired { notice: ExpiredNotice },
+
+    /// Admin: change the forwarding policy (an empty update just reads it)
+    UpdatePolicy { update: PolicyUpdate },
+
+    /// The forwarding policy now in effect
+    Policy { policy: ForwardingPolicy },
+
+    /// Ask how much storage each destination holds
+    QueryUsage,
+
+    /// Response to a usage query
+    Usage {
+        node_id: String,
+        destinations: Vec<DestinationUsage>,
+    },
+
+    /// Ask which chunks of a transfer are held or were delivered
+    QueryTransfer { transfer_id: String },
+
+    /// Response to a transfer query
+    Holdings { holdings: TransferHoldings },
+
+    /// Ask for the relay's statistics, forward latency included
+    QueryStats,
+
+    /// Response to a statistics query
+    Stats { node_id: String, stats: RelayStats },
+}
+
+/// What a relay knows about the chunks of one transfer
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransferHoldings {
+    pub node_id: String,
+    pub transfer_id: String,
+    /// Sequence numbers of chunks stored here, sorted
+    pub held: Vec<u32>,
+    /// Sequence numbers of chunks this relay handed to the destination,
+    /// sorted; only kept for recent transfers
+    pub delivered: Vec<u32>,
+}
+
+/// Chunks a relay holds for one transfer
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AvailableChunks {
+    pub transfer_id: String,
+    pub chunk_ids: Vec<String>,
+    pub bytes: u64,
+}
+
+/// A chunk delivered to a receiver over the pull flow
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PulledChunk {
+    pub chunk_id: String,
+    pub route: RouteInfo,
+    pub chunk: Chunk,
+}
+
+/// A checksummed chunk of `data` for relay tests
+#[cfg(test)]
+pub(crate) fn test_chunk(data: impl Into<bytes::Bytes>) -> Chunk {
+    use crate::chunk::{ChunkMetadata, Priority};
+    use crate::integrity::IntegrityVerifier;
+
+    let data = data.into();
+    Chunk {
+        metadata: ChunkMetadata {
+            chunk_id: 0,
+            file_id: "transfer-1".to_string(),
+            sequence_number: 0,
+            total_chunks: 1,
+            data_size: data.len(),
+            checksum: IntegrityVerifier::calculate_checksum(&data),
+            is_parity: false,
+            priority: Priority::High,
+            created_at: chrono::Utc::now().timestamp(),
+            file_size: data.len() as u64,
+            file_checksum: [0u8; 32],
+            data_chunks: 1,
+            zero_runs: Vec::new(),
+            attributes: None,
+            checksum_algorithm: Default::default(),
+            merkle_proof: None,
+            trace: None,
+            compression: None,
+        },
+        data,
+    }
 }
 
 #[cfg(test)]
@@ -336,16 +1008,66 @@ mod tests {
         assert_eq!(route.ttl, 8);
     }
 
+    #[test]
+    fn test_expired_notice_return_path() {
+        let mut route =
+            RouteInfo::new("sender", "127.0.0.1:8000".parse().unwrap(), "transfer-1", 1)
+                .with_sequence(7);
+        route.add_hop("relay-1");
+        route.add_hop("relay-2");
+        route.add_hop("relay-3");
+
+        let notice = ExpiredNotice::new("chunk-7", &route, "relay-3", ExpiryReason::Expired);
+        assert_eq!(notice.sequence_number, Some(7));
+        assert_eq!(notice.origin, "sender");
+        assert_eq!(notice.return_path, ["relay-2", "relay-1"]);
+    }
+
+    #[test]
+    fn test_destination_quota_limits() {
+        let near: SocketAddr = "10.0.0.1:5001".parse().unwrap();
+        let far: SocketAddr = "10.0.0.2:5001".parse().unwrap();
+        let mut quotas = DestinationQuotas::default();
+        assert_eq!(quotas.limit_for(near), None);
+
+        quotas.default_bytes = 1024;
+        quotas.overrides.insert(far, 0);
+        assert_eq!(quotas.limit_for(near), Some(1024));
+        assert_eq!(quotas.limit_for(far), None);
+    }
+
     #[test]
     fn test_relay_stats() {
-        let mut stats = RelayStats::default();
-        stats.chunks_forwarded = 90;
-        stats.chunks_expired = 5;
-        stats.chunks_dropped = 5;
+        let stats = RelayStats {
+            chunks_forwarded: 90,
+            chunks_expired: 5,
+            chunks_dropped: 5,
+            ..Default::default()
+        };
 
         assert!((stats.success_rate() - 90.0).abs() < 0.1);
     }
 
+    #[test]
+    fn test_forward_latency() {
+        let mut latency = ForwardLatency::default();
+        for ms in [20, 40, 60, 80, 7_200_000] {
+            latency.record(ms);
+        }
+        assert_eq!(latency.forwarded, 5);
+        assert_eq!(latency.avg_ms(), 1_440_040);
+        // Percentiles report the bucket bound, capped at the worst seen
+        assert_eq!(latency.p50_ms, 100);
+        assert_eq!(latency.p99_ms, 7_200_000);
+        // The first chunk seeds the moving average; later ones move it by
+        // their weight
+        let mut expected = 20.0;
+        for ms in [40.0, 60.0, 80.0, 7_200_000.0] {
+            expected += (ms - expected) * FORWARD_LATENCY_EWMA_WEIGHT;
+        }
+        assert!((latency.ewma_ms - expected).abs() < 1e-6);
+    }
+
     #[test]
     fn test_peer_info() {
         let peer = PeerInfo::new("peer-1", "192.168.1.100:9000".parse().unwrap()).with_priority(50);
@@ -24,6 +24,7 @@ pub enum SessionError {
     IoError(#[from] std::io::Error),
 }
 
+#[cfg(feature = "sqlite")]
 impl From<sqlx::Error> for SessionError {
     fn from(err: sqlx::Error) -> Self {
         SessionError::DatabaseError(err.to_string())
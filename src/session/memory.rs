@@ -0,0 +1,636 @@
+//! Sessions kept in process memory
+//!
+//! [`MemoryRepository`] holds sessions, profiles and benchmark reports in
+//! maps and never opens a database, so a short-lived process such as a
+//! one-off CLI send starts without SQLite. Everything is lost when the
+//! process exits; use `SessionStore` when transfers must survive a restart.
+//!
+//! A long-running process would otherwise keep every session it ever ran,
+//! so finished sessions are dropped once they are older than a TTL or
+//! outnumber a cap. Sessions still in progress are never dropped.
+
+use super::error::{SessionError, SessionResult};
+use super::repository::SessionRepository;
+use super::types::{
+    BenchmarkRecord, ProgressSample, ResumeInfo, SessionPage, SessionQuery, SessionSort,
+    SessionState, SessionStatus, TransferProfile,
+};
+use futures::future::BoxFuture;
+use parking_lot::RwLock;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+/// Finished sessions kept by default
+pub const DEFAULT_MAX_FINISHED: usize = 10_000;
+
+/// How long a finished session is kept by default
+pub const DEFAULT_FINISHED_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Debug, Default)]
+struct Tables {
+    sessions: HashMap<String, SessionState>,
+    timeseries: HashMap<String, Vec<ProgressSample>>,
+    profiles: BTreeMap<String, TransferProfile>,
+    /// Oldest first
+    benchmarks: Vec<BenchmarkRecord>,
+}
+
+/// [`SessionRepository`] backed by in-memory maps
+#[derive(Debug)]
+pub struct MemoryRepository {
+    tables: RwLock<Tables>,
+    closed: AtomicBool,
+    max_finished: usize,
+    finished_ttl: Duration,
+}
+
+impl Default for MemoryRepository {
+    fn default() -> Self {
+        Self {
+            tables: RwLock::default(),
+            closed: AtomicBool::new(false),
+            max_finished: DEFAULT_MAX_FINISHED,
+            finished_ttl: DEFAULT_FINISHED_TTL,
+        }
+    }
+}
+
+impl MemoryRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keep at most `max` finished sessions, dropping the least recently
+    /// updated first
+    pub fn with_max_finished(mut self, max: usize) -> Self {
+        self.max_finished = max;
+        self
+    }
+
+    /// Drop finished sessions not updated for `ttl`
+    pub fn with_finished_ttl(mut self, ttl: Duration) -> Self {
+        self.finished_ttl = ttl;
+        self
+    }
+
+    /// Sessions held
+    pub fn len(&self) -> usize {
+        self.tables.read().sessions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn check_open(&self) -> SessionResult<()> {
+        if self.closed.load(Ordering::Acquire) {
+            return Err(SessionError::DatabaseError(
+                "session repository is closed".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Fail like [`SessionStore`](super::SessionStore) writes do when closed
+    /// or when a fault is injected
+    fn check_writable(&self) -> SessionResult<()> {
+        self.check_open()?;
+        #[cfg(feature = "fault-injection")]
+        crate::fault::check(crate::fault::FaultPoint::SessionWrite)
+            .map_err(|fault| SessionError::DatabaseError(fault.to_string()))?;
+        Ok(())
+    }
+
+    /// Apply `change` to a stored session and stamp it as updated
+    fn update(
+        &self,
+        session_id: &str,
+        change: impl FnOnce(&mut SessionState),
+    ) -> SessionResult<()> {
+        self.check_writable()?;
+        let mut tables = self.tables.write();
+        let state = tables
+            .sessions
+            .get_mut(session_id)
+            .ok_or_else(|| SessionError::NotFound(session_id.to_string()))?;
+        change(state);
+        state.updated_at = chrono::Utc::now().timestamp();
+        // Chunk updates are frequent; only a session finishing can push
+        // the finished count over the cap
+        if state.status.is_terminal() {
+            self.evict(&mut tables);
+        }
+        Ok(())
+    }
+
+    /// Drop finished sessions past the TTL, then the oldest beyond the cap
+    fn evict(&self, tables: &mut Tables) {
+        let now = chrono::Utc::now().timestamp();
+        let ttl = i64::try_from(self.finished_ttl.as_secs()).unwrap_or(i64::MAX);
+        let mut finished: Vec<(i64, String)> = tables
+            .sessions
+            .values()
+            .filter(|state| state.status.is_terminal())
+            .map(|state| (state.updated_at, state.session_id.clone()))
+            .collect();
+        finished.sort();
+        let expired = finished
+            .iter()
+            .take_while(|(updated_at, _)| now.saturating_sub(*updated_at) >= ttl)
+            .count();
+        let over = finished.len().saturating_sub(self.max_finished);
+        for (_, session_id) in finished.into_iter().take(expired.max(over)) {
+            tables.sessions.remove(&session_id);
+            tables.timeseries.remove(&session_id);
+        }
+    }
+
+    fn matches(query: &SessionQuery, state: &SessionState) -> bool {
+        // Any `Failed` reason (or repair count) matches, as in SessionStore
+        let status_matches = query.status.as_ref().is_none_or(|status| {
+            std::mem::discriminant(status) == std::mem::discriminant(&state.status)
+        });
+        status_matches
+            && query
+                .file_id
+                .as_ref()
+                .is_none_or(|file_id| *file_id == state.file_id)
+            && query
+                .tags
+                .iter()
+                .all(|(key, value)| state.options.tags.get(key) == Some(value))
+    }
+}
+
+impl SessionRepository for MemoryRepository {
+    fn save<'a>(&'a self, state: &'a SessionState) -> BoxFuture<'a, SessionResult<()>> {
+        Box::pin(async move {
+            self.check_writable()?;
+            let mut state = state.clone();
+            state.updated_at = chrono::Utc::now().timestamp();
+            let mut tables = self.tables.write();
+            tables.sessions.insert(state.session_id.clone(), state);
+            self.evict(&mut tables);
+            Ok(())
+        })
+    }
+
+    fn load<'a>(
+        &'a self,
+        session_id: &'a str,
+    ) -> BoxFuture<'a, SessionResult<Option<SessionState>>> {
+        Box::pin(async move {
+            self.check_open()?;
+            Ok(self.tables.read().sessions.get(session_id).cloned())
+        })
+    }
+
+    fn mark_chunk_completed_with_bytes<'a>(
+        &'a self,
+        session_id: &'a str,
+        chunk_number: u32,
+        bytes_transferred: u64,
+    ) -> BoxFuture<'a, SessionResult<()>> {
+        Box::pin(async move {
+            self.update(session_id, |state| {
+                state.record_chunk_completed(chunk_number, bytes_transferred)
+            })
+        })
+    }
+
+    fn mark_skipped_duplicate<'a>(
+        &'a self,
+        session_id: &'a str,
+    ) -> BoxFuture<'a, SessionResult<()>> {
+        Box::pin(async move {
+            self.update(session_id, |state| {
+                state.metrics.skipped_duplicate = true;
+                state.status = SessionStatus::Completed;
+            })
+        })
+    }
+
+    fn mark_chunk_failed<'a>(
+        &'a self,
+        session_id: &'a str,
+        chunk_number: u32,
+    ) -> BoxFuture<'a, SessionResult<()>> {
+        Box::pin(async move { self.update(session_id, |state| state.mark_failed(chunk_number)) })
+    }
+
+    fn mark_chunk_nacked<'a>(
+        &'a self,
+        session_id: &'a str,
+        chunk_number: u32,
+    ) -> BoxFuture<'a, SessionResult<()>> {
+        Box::pin(async move { self.update(session_id, |state| state.mark_lost(&[chunk_number])) })
+    }
+
+    fn mark_chunks_lost<'a>(
+        &'a self,
+        session_id: &'a str,
+        chunk_numbers: &'a [u32],
+    ) -> BoxFuture<'a, SessionResult<()>> {
+        Box::pin(async move { self.update(session_id, |state| state.mark_lost(chunk_numbers)) })
+    }
+
+    fn update_status<'a>(
+        &'a self,
+        session_id: &'a str,
+        status: SessionStatus,
+    ) -> BoxFuture<'a, SessionResult<()>> {
+        Box::pin(async move { self.update(session_id, |state| state.status = status) })
+    }
+
+    fn get_resume_info<'a>(
+        &'a self,
+        session_id: &'a str,
+    ) -> BoxFuture<'a, SessionResult<ResumeInfo>> {
+        Box::pin(async move {
+            self.check_open()?;
+            let tables = self.tables.read();
+            let state = tables
+                .sessions
+                .get(session_id)
+                .ok_or_else(|| SessionError::NotFound(session_id.to_string()))?;
+            Ok(ResumeInfo::from_state(state))
+        })
+    }
+
+    fn query<'a>(&'a self, query: &'a SessionQuery) -> BoxFuture<'a, SessionResult<SessionPage>> {
+        Box::pin(async move {
+            self.check_open()?;
+            let mut sessions: Vec<SessionState> = self
+                .tables
+                .read()
+                .sessions
+                .values()
+                .filter(|state| Self::matches(query, state))
+                .cloned()
+                .collect();
+            let total = sessions.len() as u64;
+            // Ties broken by id, as SessionSort::order_by does
+            match query.sort {
+                SessionSort::UpdatedDesc => sessions.sort_by(|a, b| {
+                    (Reverse(a.updated_at), &a.session_id)
+                        .cmp(&(Reverse(b.updated_at), &b.session_id))
+                }),
+                SessionSort::UpdatedAsc => sessions.sort_by(|a, b| {
+                    (a.updated_at, &a.session_id).cmp(&(b.updated_at, &b.session_id))
+                }),
+                SessionSort::CreatedDesc => sessions.sort_by(|a, b| {
+                    (Reverse(a.created_at), &a.session_id)
+                        .cmp(&(Reverse(b.created_at), &b.session_id))
+                }),
+                SessionSort::CreatedAsc => sessions.sort_by(|a, b| {
+                    (a.created_at, &a.session_id).cmp(&(b.created_at, &b.session_id))
+                }),
+            }
+            let page = sessions.into_iter().skip(query.offset as usize);
+            let sessions = match query.limit {
+                Some(limit) => page.take(limit as usize).collect(),
+                None => page.collect(),
+            };
+            Ok(SessionPage { sessions, total })
+        })
+    }
+
+    fn save_timeseries<'a>(
+        &'a self,
+        session_id: &'a str,
+        samples: &'a [ProgressSample],
+    ) -> BoxFuture<'a, SessionResult<()>> {
+        Box::pin(async move {
+            self.check_writable()?;
+            self.tables
+                .write()
+                .timeseries
+                .insert(session_id.to_string(), samples.to_vec());
+            Ok(())
+        })
+    }
+
+    fn load_timeseries<'a>(
+        &'a self,
+        session_id: &'a str,
+    ) -> BoxFuture<'a, SessionResult<Vec<ProgressSample>>> {
+        Box::pin(async move {
+            self.check_open()?;
+            Ok(self
+                .tables
+                .read()
+                .timeseries
+                .get(session_id)
+                .cloned()
+                .unwrap_or_default())
+        })
+    }
+
+    fn save_profile<'a>(
+        &'a self,
+        profile: &'a TransferProfile,
+    ) -> BoxFuture<'a, SessionResult<TransferProfile>> {
+        Box::pin(async move {
+            self.check_writable()?;
+            let now = chrono::Utc::now().timestamp();
+            let mut tables = self.tables.write();
+            // Replacing a profile keeps its original creation time
+            let created_at = tables
+                .profiles
+                .get(&profile.name)
+                .map_or(now, |existing| existing.created_at);
+            let stored = TransferProfile {
+                created_at,
+                updated_at: now,
+                ..profile.clone()
+            };
+            tables.profiles.insert(stored.name.clone(), stored.clone());
+            Ok(stored)
+        })
+    }
+
+    fn load_profile<'a>(
+        &'a self,
+        name: &'a str,
+    ) -> BoxFuture<'a, SessionResult<Option<TransferProfile>>> {
+        Box::pin(async move {
+            self.check_open()?;
+            Ok(self.tables.read().profiles.get(name).cloned())
+        })
+    }
+
+    fn list_profiles(&self) -> BoxFuture<'_, SessionResult<Vec<TransferProfile>>> {
+        Box::pin(async move {
+            self.check_open()?;
+            Ok(self.tables.read().profiles.values().cloned().collect())
+        })
+    }
+
+    fn delete_profile<'a>(&'a self, name: &'a str) -> BoxFuture<'a, SessionResult<bool>> {
+        Box::pin(async move {
+            self.check_writable()?;
+            Ok(self.tables.write().profiles.remove(name).is_some())
+        })
+    }
+
+    fn save_benchmark<'a>(
+        &'a self,
+        label: Option<&'a str>,
+        report: &'a serde_json::Value,
+    ) -> BoxFuture<'a, SessionResult<BenchmarkRecord>> {
+        Box::pin(async move {
+            self.check_writable()?;
+            let record = BenchmarkRecord {
+                id: uuid::Uuid::new_v4().to_string(),
+                label: label.map(str::to_string),
+                created_at: chrono::Utc::now().timestamp(),
+                report: report.clone(),
+            };
+            self.tables.write().benchmarks.push(record.clone());
+            Ok(record)
+        })
+    }
+
+    fn load_benchmark<'a>(
+        &'a self,
+        id: &'a str,
+    ) -> BoxFuture<'a, SessionResult<Option<BenchmarkRecord>>> {
+        Box::pin(async move {
+            self.check_open()?;
+            let tables = self.tables.read();
+            Ok(tables.benchmarks.iter().find(|b| b.id == id).cloned())
+        })
+    }
+
+    fn list_benchmarks(&self, limit: u32) -> BoxFuture<'_, SessionResult<Vec<BenchmarkRecord>>> {
+        Box::pin(async move {
+            self.check_open()?;
+            let tables = self.tables.read();
+            Ok(tables
+                .benchmarks
+                .iter()
+                .rev()
+                .take(limit as usize)
+                .cloned()
+                .collect())
+        })
+    }
+
+    fn delete_benchmark<'a>(&'a self, id: &'a str) -> BoxFuture<'a, SessionResult<bool>> {
+        Box::pin(async move {
+            self.check_writable()?;
+            let mut tables = self.tables.write();
+            let before = tables.benchmarks.len();
+            tables.benchmarks.retain(|b| b.id != id);
+            Ok(tables.benchmarks.len() < before)
+        })
+    }
+
+    fn ping(&self) -> BoxFuture<'_, SessionResult<()>> {
+        Box::pin(async move { self.check_open() })
+    }
+
+    fn close(&self) -> BoxFuture<'_, ()> {
+        Box::pin(async move {
+            self.closed.store(true, Ordering::Release);
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::{FileManifest, Priority};
+    use crate::session::SessionSearch;
+
+    fn manifest(filename: &str) -> FileManifest {
+        FileManifest {
+            file_id: filename.into(),
+            filename: filename.into(),
+            total_size: 4096,
+            chunk_size: 1024,
+            total_chunks: 6,
+            data_chunks: 4,
+            parity_chunks: 2,
+            priority: Priority::Normal,
+            checksum: [0u8; 32],
+            zero_runs: Vec::new(),
+            attributes: None,
+            checksum_algorithm: Default::default(),
+            erasure_profile: Default::default(),
+            schedule: None,
+            merkle_root: None,
+            compression: None,
+        }
+    }
+
+    fn session(id: &str, filename: &str) -> SessionState {
+        SessionState::new(id.into(), filename.into(), manifest(filename))
+    }
+
+    #[tokio::test]
+    async fn test_chunk_progress_and_resume() {
+        let repo = MemoryRepository::new();
+        repo.save(&session("s1", "survey.bin")).await.unwrap();
+
+        repo.mark_chunk_completed_with_bytes("s1", 0, 1024)
+            .await
+            .unwrap();
+        repo.mark_chunk_completed_with_bytes("s1", 1, 1024)
+            .await
+            .unwrap();
+        repo.mark_chunk_failed("s1", 2).await.unwrap();
+        repo.mark_chunks_lost("s1", &[1]).await.unwrap();
+        repo.update_status("s1", SessionStatus::Paused)
+            .await
+            .unwrap();
+
+        let state = repo.load("s1").await.unwrap().unwrap();
+        assert_eq!(state.completed_chunks.len(), 1);
+        assert!(state.failed_chunks.contains(&1) && state.failed_chunks.contains(&2));
+        assert_eq!(state.metrics.bytes_transferred, 2048);
+        let resume = repo.get_resume_info("s1").await.unwrap();
+        assert!(resume.can_resume);
+
+        assert!(matches!(
+            repo.mark_chunk_failed("missing", 0).await,
+            Err(SessionError::NotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_query_filters_sorts_and_pages() {
+        let repo = MemoryRepository::new();
+        for (id, name) in [("a", "one.bin"), ("b", "two.bin"), ("c", "three.bin")] {
+            let mut state = session(id, name);
+            state.created_at = match id {
+                "a" => 30,
+                "b" => 10,
+                _ => 20,
+            };
+            if id != "b" {
+                state.options.tags.insert("site".into(), "north".into());
+            }
+            repo.save(&state).await.unwrap();
+        }
+        repo.update_status("c", SessionStatus::Failed("link down".into()))
+            .await
+            .unwrap();
+
+        let page = repo
+            .query(&SessionQuery {
+                sort: SessionSort::CreatedAsc,
+                limit: Some(2),
+                offset: 1,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(page.total, 3);
+        let ids: Vec<_> = page
+            .sessions
+            .iter()
+            .map(|s| s.session_id.as_str())
+            .collect();
+        assert_eq!(ids, ["c", "a"]);
+
+        let failed = repo
+            .query(&SessionQuery {
+                status: Some(SessionStatus::Failed(String::new())),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(failed.total, 1);
+
+        let tagged = repo
+            .query(&SessionQuery {
+                tags: [("site".to_string(), "north".to_string())].into(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(tagged.total, 2);
+
+        let found = repo
+            .search(&SessionSearch {
+                filename: Some("T*".into()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        let mut ids: Vec<_> = found.iter().map(|s| s.session_id.as_str()).collect();
+        ids.sort();
+        assert_eq!(ids, ["b", "c"]);
+    }
+
+    #[tokio::test]
+    async fn test_finished_sessions_are_evicted() {
+        let repo = MemoryRepository::new().with_max_finished(2);
+        for id in ["a", "b", "c"] {
+            repo.save(&session(id, "survey.bin")).await.unwrap();
+            let sample = ProgressSample {
+                timestamp_ms: 0,
+                bytes_transferred: 1024,
+                speed_bps: 1024,
+                loss_rate: 0.0,
+            };
+            repo.save_timeseries(id, &[sample]).await.unwrap();
+        }
+        repo.save(&session("active", "survey.bin")).await.unwrap();
+        for id in ["a", "b", "c"] {
+            repo.update_status(id, SessionStatus::Completed)
+                .await
+                .unwrap();
+        }
+        // The least recently finished goes, and the running session stays
+        assert!(repo.load("a").await.unwrap().is_none());
+        assert!(repo.load_timeseries("a").await.unwrap().is_empty());
+        assert_eq!(repo.len(), 3);
+
+        let repo = MemoryRepository::new().with_finished_ttl(Duration::ZERO);
+        repo.save(&session("done", "survey.bin")).await.unwrap();
+        repo.save(&session("running", "survey.bin")).await.unwrap();
+        repo.update_status("running", SessionStatus::Active)
+            .await
+            .unwrap();
+        repo.update_status("done", SessionStatus::Failed("link down".into()))
+            .await
+            .unwrap();
+        assert!(repo.load("done").await.unwrap().is_none());
+        assert!(repo.load("running").await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_profiles_benchmarks_and_close() {
+        let repo = MemoryRepository::new();
+        let profile = TransferProfile::new("field");
+        let first = repo.save_profile(&profile).await.unwrap();
+        let again = repo.save_profile(&profile).await.unwrap();
+        assert_eq!(again.created_at, first.created_at);
+        assert_eq!(repo.list_profiles().await.unwrap().len(), 1);
+        assert!(repo.delete_profile("field").await.unwrap());
+        assert!(!repo.delete_profile("field").await.unwrap());
+
+        let older = repo
+            .save_benchmark(Some("before"), &serde_json::json!({"mbps": 10}))
+            .await
+            .unwrap();
+        let newer = repo
+            .save_benchmark(None, &serde_json::json!({"mbps": 12}))
+            .await
+            .unwrap();
+        let listed = repo.list_benchmarks(10).await.unwrap();
+        assert_eq!(listed[0].id, newer.id);
+        assert_eq!(listed[1].id, older.id);
+        assert!(repo.delete_benchmark(&older.id).await.unwrap());
+        assert!(repo.load_benchmark(&older.id).await.unwrap().is_none());
+
+        repo.ping().await.unwrap();
+        repo.close().await;
+        assert!(repo.ping().await.is_err());
+        assert!(repo.save(&session("s1", "survey.bin")).await.is_err());
+    }
+}
@@ -1,7 +1,22 @@
 pub mod error;
+pub mod memory;
+pub mod repository;
+#[cfg(feature = "sqlite")]
 pub mod store;
 pub mod types;
+pub mod write_behind;
 
 pub use error::{SessionError, SessionResult};
+pub use memory::MemoryRepository;
+pub use repository::SessionRepository;
+#[cfg(feature = "sqlite")]
 pub use store::SessionStore;
-pub use types::{ResumeInfo, SessionState, SessionStatus, SessionSummary, TransferMetrics};
+pub(crate) use types::validate_tags;
+pub use types::{
+    BenchmarkRecord, InboundTransfer, JournalMode, MaintenanceReport, ProgressSample, ResumeInfo,
+    SessionBackend, SessionPage, SessionQuery, SessionSearch, SessionSort, SessionState,
+    SessionStatus, SessionStoreOptions, SessionSummary, StorageStats, SynchronousLevel,
+    TransferMetrics, TransferOptions, TransferProfile, TransferTags, MAX_TAG_KEY_LEN,
+    MAX_TAG_VALUE_LEN, MAX_TRANSFER_TAGS,
+};
+pub use write_behind::{WriteBehindPolicy, WriteBehindRepository};
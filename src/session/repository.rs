@@ -0,0 +1,378 @@
+//! Storage the coordinator keeps sessions and profiles in
+//!
+//! [`SessionStore`] keeps them in SQLite, and
+//! [`MemoryRepository`](super::MemoryRepository) in process memory for
+//! runs that need nothing to outlive them. Embedders with a database of their
+//! own implement [`SessionRepository`] and hand it to
+//! [`TransferCoordinator::new`](crate::coordinator::TransferCoordinator::new)
+//! instead. Either can be wrapped in a
+//! [`WriteBehindRepository`](super::WriteBehindRepository) to take chunk
+//! updates off the transfer loop.
+
+use super::error::{SessionError, SessionResult};
+#[cfg(feature = "sqlite")]
+use super::store::SessionStore;
+use super::types::{
+    BenchmarkRecord, MaintenanceReport, ProgressSample, ResumeInfo, SessionPage, SessionQuery,
+    SessionSearch, SessionState, SessionStatus, StorageStats, TransferProfile,
+};
+use futures::future::BoxFuture;
+
+/// Sessions and transfer profiles, as the coordinator reads and writes them
+///
+/// Methods return boxed futures so implementations can talk to a database
+/// or service asynchronously. Chunk updates are called once per chunk while
+/// a transfer runs, so they should be cheap.
+pub trait SessionRepository: Send + Sync {
+    /// Insert or replace a session
+    fn save<'a>(&'a self, state: &'a SessionState) -> BoxFuture<'a, SessionResult<()>>;
+
+    fn load<'a>(
+        &'a self,
+        session_id: &'a str,
+    ) -> BoxFuture<'a, SessionResult<Option<SessionState>>>;
+
+    /// Record a chunk as acknowledged, adding `bytes_transferred` to the total
+    fn mark_chunk_completed_with_bytes<'a>(
+        &'a self,
+        session_id: &'a str,
+        chunk_number: u32,
+        bytes_transferred: u64,
+    ) -> BoxFuture<'a, SessionResult<()>>;
+
+    /// Record that the receiver already had the file
+    fn mark_skipped_duplicate<'a>(
+        &'a self,
+        session_id: &'a str,
+    ) -> BoxFuture<'a, SessionResult<()>>;
+
+    fn mark_chunk_failed<'a>(
+        &'a self,
+        session_id: &'a str,
+        chunk_number: u32,
+    ) -> BoxFuture<'a, SessionResult<()>>;
+
+    fn mark_chunk_nacked<'a>(
+        &'a self,
+        session_id: &'a str,
+        chunk_number: u32,
+    ) -> BoxFuture<'a, SessionResult<()>>;
+
+    /// Record shards the receiver reported lost in transit
+    fn mark_chunks_lost<'a>(
+        &'a self,
+        session_id: &'a str,
+        chunk_numbers: &'a [u32],
+    ) -> BoxFuture<'a, SessionResult<()>>;
+
+    fn update_status<'a>(
+        &'a self,
+        session_id: &'a str,
+        status: SessionStatus,
+    ) -> BoxFuture<'a, SessionResult<()>>;
+
+    fn get_resume_info<'a>(
+        &'a self,
+        session_id: &'a str,
+    ) -> BoxFuture<'a, SessionResult<ResumeInfo>>;
+
+    fn query<'a>(&'a self, query: &'a SessionQuery) -> BoxFuture<'a, SessionResult<SessionPage>>;
+
+    /// Sessions whose file matches `search`, most recently updated first
+    ///
+    /// By default every session is read and filtered with
+    /// [`SessionSearch::matches`]; storage that can index file names and
+    /// checksums should do better.
+    fn search<'a>(
+        &'a self,
+        search: &'a SessionSearch,
+    ) -> BoxFuture<'a, SessionResult<Vec<SessionState>>> {
+        Box::pin(async move {
+            let page = self.query(&SessionQuery::default()).await?;
+            let matching = page.sessions.into_iter().filter(|s| search.matches(s));
+            Ok(match search.limit {
+                Some(limit) => matching.take(limit as usize).collect(),
+                None => matching.collect(),
+            })
+        })
+    }
+
+    /// Replace a session's saved throughput samples; called once its
+    /// transfer stops running
+    fn save_timeseries<'a>(
+        &'a self,
+        session_id: &'a str,
+        samples: &'a [ProgressSample],
+    ) -> BoxFuture<'a, SessionResult<()>>;
+
+    /// A session's saved throughput samples, oldest first (empty if none)
+    fn load_timeseries<'a>(
+        &'a self,
+        session_id: &'a str,
+    ) -> BoxFuture<'a, SessionResult<Vec<ProgressSample>>>;
+
+    /// Insert or replace a profile, returning it as stored
+    fn save_profile<'a>(
+        &'a self,
+        profile: &'a TransferProfile,
+    ) -> BoxFuture<'a, SessionResult<TransferProfile>>;
+
+    fn load_profile<'a>(
+        &'a self,
+        name: &'a str,
+    ) -> BoxFuture<'a, SessionResult<Option<TransferProfile>>>;
+
+    fn list_profiles(&self) -> BoxFuture<'_, SessionResult<Vec<TransferProfile>>>;
+
+    /// Remove a profile; `false` if there was none
+    fn delete_profile<'a>(&'a self, name: &'a str) -> BoxFuture<'a, SessionResult<bool>>;
+
+    /// Store a benchmark report under a new id
+    ///
+    /// Storage without a place for reports keeps the default, which
+    /// refuses them; the other benchmark methods then find none.
+    fn save_benchmark<'a>(
+        &'a self,
+        label: Option<&'a str>,
+        report: &'a serde_json::Value,
+    ) -> BoxFuture<'a, SessionResult<BenchmarkRecord>> {
+        let _ = (label, report);
+        Box::pin(async {
+            Err(SessionError::DatabaseError(
+                "benchmark reports are not kept by this storage".to_string(),
+            ))
+        })
+    }
+
+    fn load_benchmark<'a>(
+        &'a self,
+        id: &'a str,
+    ) -> BoxFuture<'a, SessionResult<Option<BenchmarkRecord>>> {
+        let _ = id;
+        Box::pin(async { Ok(None) })
+    }
+
+    /// The `limit` most recent benchmark reports, newest first
+    fn list_benchmarks(&self, limit: u32) -> BoxFuture<'_, SessionResult<Vec<BenchmarkRecord>>> {
+        let _ = limit;
+        Box::pin(async { Ok(Vec::new()) })
+    }
+
+    /// Remove a benchmark report; `false` if there was none
+    fn delete_benchmark<'a>(&'a self, id: &'a str) -> BoxFuture<'a, SessionResult<bool>> {
+        let _ = id;
+        Box::pin(async { Ok(false) })
+    }
+
+    /// Cheap round trip, used by readiness checks
+    fn ping(&self) -> BoxFuture<'_, SessionResult<()>>;
+
+    /// Size of the backing storage and the rows it holds; `None` by default,
+    /// for storage with nothing to report
+    fn storage_stats(&self) -> BoxFuture<'_, SessionResult<Option<StorageStats>>> {
+        Box::pin(async { Ok(None) })
+    }
+
+    /// Reclaim space left by deleted rows, freeing at most `vacuum_pages`
+    /// pages (0 for all), and refresh query statistics if `analyze` is set
+    ///
+    /// Called periodically by the coordinator. `None` by default, for
+    /// storage that needs no maintenance.
+    fn maintain(
+        &self,
+        _vacuum_pages: u32,
+        _analyze: bool,
+    ) -> BoxFuture<'_, SessionResult<Option<MaintenanceReport>>> {
+        Box::pin(async { Ok(None) })
+    }
+
+    /// Write out anything held back; nothing by default, for storage that
+    /// writes as it goes
+    fn flush(&self) -> BoxFuture<'_, SessionResult<()>> {
+        Box::pin(async { Ok(()) })
+    }
+
+    /// Release the backing storage; later calls fail
+    fn close(&self) -> BoxFuture<'_, ()>;
+}
+
+#[cfg(feature = "sqlite")]
+impl SessionRepository for SessionStore {
+    fn save<'a>(&'a self, state: &'a SessionState) -> BoxFuture<'a, SessionResult<()>> {
+        Box::pin(SessionStore::save(self, state))
+    }
+
+    fn load<'a>(
+        &'a self,
+        session_id: &'a str,
+    ) -> BoxFuture<'a, SessionResult<Option<SessionState>>> {
+        Box::pin(SessionStore::load(self, session_id))
+    }
+
+    fn mark_chunk_completed_with_bytes<'a>(
+        &'a self,
+        session_id: &'a str,
+        chunk_number: u32,
+        bytes_transferred: u64,
+    ) -> BoxFuture<'a, SessionResult<()>> {
+        Box::pin(SessionStore::mark_chunk_completed_with_bytes(
+            self,
+            session_id,
+            chunk_number,
+            bytes_transferred,
+        ))
+    }
+
+    fn mark_skipped_duplicate<'a>(
+        &'a self,
+        session_id: &'a str,
+    ) -> BoxFuture<'a, SessionResult<()>> {
+        Box::pin(SessionStore::mark_skipped_duplicate(self, session_id))
+    }
+
+    fn mark_chunk_failed<'a>(
+        &'a self,
+        session_id: &'a str,
+        chunk_number: u32,
+    ) -> BoxFuture<'a, SessionResult<()>> {
+        Box::pin(SessionStore::mark_chunk_failed(
+            self,
+            session_id,
+            chunk_number,
+        ))
+    }
+
+    fn mark_chunk_nacked<'a>(
+        &'a self,
+        session_id: &'a str,
+        chunk_number: u32,
+    ) -> BoxFuture<'a, SessionResult<()>> {
+        Box::pin(SessionStore::mark_chunk_nacked(
+            self,
+            session_id,
+            chunk_number,
+        ))
+    }
+
+    fn mark_chunks_lost<'a>(
+        &'a self,
+        session_id: &'a str,
+        chunk_numbers: &'a [u32],
+    ) -> BoxFuture<'a, SessionResult<()>> {
+        Box::pin(SessionStore::mark_chunks_lost(
+            self,
+            session_id,
+            chunk_numbers,
+        ))
+    }
+
+    fn update_status<'a>(
+        &'a self,
+        session_id: &'a str,
+        status: SessionStatus,
+    ) -> BoxFuture<'a, SessionResult<()>> {
+        Box::pin(SessionStore::update_status(self, session_id, status))
+    }
+
+    fn get_resume_info<'a>(
+        &'a self,
+        session_id: &'a str,
+    ) -> BoxFuture<'a, SessionResult<ResumeInfo>> {
+        Box::pin(SessionStore::get_resume_info(self, session_id))
+    }
+
+    fn query<'a>(&'a self, query: &'a SessionQuery) -> BoxFuture<'a, SessionResult<SessionPage>> {
+        Box::pin(SessionStore::query(self, query))
+    }
+
+    fn search<'a>(
+        &'a self,
+        search: &'a SessionSearch,
+    ) -> BoxFuture<'a, SessionResult<Vec<SessionState>>> {
+        Box::pin(SessionStore::search(self, search))
+    }
+
+    fn save_timeseries<'a>(
+        &'a self,
+        session_id: &'a str,
+        samples: &'a [ProgressSample],
+    ) -> BoxFuture<'a, SessionResult<()>> {
+        Box::pin(SessionStore::save_timeseries(self, session_id, samples))
+    }
+
+    fn load_timeseries<'a>(
+        &'a self,
+        session_id: &'a str,
+    ) -> BoxFuture<'a, SessionResult<Vec<ProgressSample>>> {
+        Box::pin(SessionStore::load_timeseries(self, session_id))
+    }
+
+    fn save_profile<'a>(
+        &'a self,
+        profile: &'a TransferProfile,
+    ) -> BoxFuture<'a, SessionResult<TransferProfile>> {
+        Box::pin(SessionStore::save_profile(self, profile))
+    }
+
+    fn load_profile<'a>(
+        &'a self,
+        name: &'a str,
+    ) -> BoxFuture<'a, SessionResult<Option<TransferProfile>>> {
+        Box::pin(SessionStore::load_profile(self, name))
+    }
+
+    fn list_profiles(&self) -> BoxFuture<'_, SessionResult<Vec<TransferProfile>>> {
+        Box::pin(SessionStore::list_profiles(self))
+    }
+
+    fn delete_profile<'a>(&'a self, name: &'a str) -> BoxFuture<'a, SessionResult<bool>> {
+        Box::pin(SessionStore::delete_profile(self, name))
+    }
+
+    fn save_benchmark<'a>(
+        &'a self,
+        label: Option<&'a str>,
+        report: &'a serde_json::Value,
+    ) -> BoxFuture<'a, SessionResult<BenchmarkRecord>> {
+        Box::pin(SessionStore::save_benchmark(self, label, report))
+    }
+
+    fn load_benchmark<'a>(
+        &'a self,
+        id: &'a str,
+    ) -> BoxFuture<'a, SessionResult<Option<BenchmarkRecord>>> {
+        Box::pin(SessionStore::load_benchmark(self, id))
+    }
+
+    fn list_benchmarks(&self, limit: u32) -> BoxFuture<'_, SessionResult<Vec<BenchmarkRecord>>> {
+        Box::pin(SessionStore::list_benchmarks(self, limit))
+    }
+
+    fn delete_benchmark<'a>(&'a self, id: &'a str) -> BoxFuture<'a, SessionResult<bool>> {
+        Box::pin(SessionStore::delete_benchmark(self, id))
+    }
+
+    fn ping(&self) -> BoxFuture<'_, SessionResult<()>> {
+        Box::pin(SessionStore::ping(self))
+    }
+
+    fn storage_stats(&self) -> BoxFuture<'_, SessionResult<Option<StorageStats>>> {
+        Box::pin(async { SessionStore::storage_stats(self).await.map(Some) })
+    }
+
+    fn maintain(
+        &self,
+        vacuum_pages: u32,
+        analyze: bool,
+    ) -> BoxFuture<'_, SessionResult<Option<MaintenanceReport>>> {
+        Box::pin(async move {
+            SessionStore::maintain(self, vacuum_pages, analyze)
+                .await
+                .map(Some)
+        })
+    }
+
+    fn close(&self) -> BoxFuture<'_, ()> {
+        Box::pin(SessionStore::close(self))
+    }
+}
@@ -1,6 +1,21 @@
+use crate::chunk::FileManifest;
 use crate::session::error::{SessionError, SessionResult};
-use crate::session::types::{ResumeInfo, SessionState, SessionStatus, SessionSummary};
+use crate::session::types::{
+    BenchmarkRecord, InboundTransfer, JournalMode, MaintenanceReport, ProgressSample, ResumeInfo,
+    SessionPage, SessionQuery, SessionSearch, SessionSort, SessionState, SessionStatus,
+    SessionStoreOptions, SessionSummary, StorageStats, SynchronousLevel, TransferMetrics,
+    TransferOptions, TransferProfile,
+};
+use sqlx::sqlite::{
+    SqliteAutoVacuum, SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions, SqliteRow,
+    SqliteSynchronous,
+};
 use sqlx::{Row, SqlitePool};
+use std::str::FromStr;
+use std::time::Instant;
+
+/// `PRAGMA auto_vacuum` value of a database that vacuums incrementally
+const AUTO_VACUUM_INCREMENTAL: i64 = 2;
 
 pub struct SessionStore {
     pool: SqlitePool,
@@ -9,7 +24,22 @@ pub struct SessionStore {
 impl SessionStore {
     /// Create new session store with SQLite database
     pub async fn new(db_path: &str) -> SessionResult<Self> {
-        let pool = SqlitePool::connect(db_path).await?;
+        Self::with_options(db_path, SessionStoreOptions::default()).await
+    }
+
+    /// Create a session store with explicit journal, sync and pool settings
+    pub async fn with_options(db_path: &str, options: SessionStoreOptions) -> SessionResult<Self> {
+        // New databases vacuum incrementally; older ones are converted by
+        // their first maintenance pass
+        let connect = SqliteConnectOptions::from_str(db_path)?
+            .auto_vacuum(SqliteAutoVacuum::Incremental)
+            .journal_mode(journal_mode(options.journal_mode))
+            .synchronous(synchronous(options.synchronous))
+            .busy_timeout(options.busy_timeout);
+        let pool = SqlitePoolOptions::new()
+            .max_connections(options.max_connections)
+            .connect_with(connect)
+            .await?;
 
         // Initialize schema
         sqlx::query(
@@ -25,7 +55,10 @@ impl SessionStore {
                 updated_at INTEGER NOT NULL,
                 receiver_addr TEXT,
                 file_path TEXT,
-                metrics TEXT
+                metrics TEXT,
+                options TEXT,
+                filename TEXT,
+                checksum TEXT
             )
             "#,
         )
@@ -52,10 +85,121 @@ impl SessionStore {
         let _ = sqlx::query("ALTER TABLE sessions ADD COLUMN metrics TEXT")
             .execute(&pool)
             .await;
+        let _ = sqlx::query("ALTER TABLE sessions ADD COLUMN options TEXT")
+            .execute(&pool)
+            .await;
+        let _ = sqlx::query("ALTER TABLE sessions ADD COLUMN filename TEXT")
+            .execute(&pool)
+            .await;
+        let _ = sqlx::query("ALTER TABLE sessions ADD COLUMN checksum TEXT")
+            .execute(&pool)
+            .await;
+        Self::index_files(&pool).await?;
+
+        // File lookups for `search`; LIKE only uses an index with NOCASE
+        sqlx::query(
+            "CREATE INDEX IF NOT EXISTS idx_sessions_filename ON sessions(filename COLLATE NOCASE)",
+        )
+        .execute(&pool)
+        .await?;
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_sessions_checksum ON sessions(checksum)")
+            .execute(&pool)
+            .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS transfer_profiles (
+                name TEXT PRIMARY KEY,
+                profile TEXT NOT NULL,
+                created_at INTEGER NOT NULL,
+                updated_at INTEGER NOT NULL
+            )
+            "#,
+        )
+        .execute(&pool)
+        .await?;
+
+        // Uploaded benchmark reports, as JSON
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS benchmark_reports (
+                id TEXT PRIMARY KEY,
+                label TEXT,
+                report TEXT NOT NULL,
+                created_at INTEGER NOT NULL
+            )
+            "#,
+        )
+        .execute(&pool)
+        .await?;
+        sqlx::query(
+            "CREATE INDEX IF NOT EXISTS idx_benchmark_reports_created ON benchmark_reports(created_at)",
+        )
+        .execute(&pool)
+        .await?;
+
+        // Receiver side: files still arriving and which chunks are on disk
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS inbound_transfers (
+                transfer_id TEXT PRIMARY KEY,
+                manifest TEXT NOT NULL,
+                group_size INTEGER NOT NULL,
+                created_at INTEGER NOT NULL,
+                updated_at INTEGER NOT NULL
+            )
+            "#,
+        )
+        .execute(&pool)
+        .await?;
+
+        // Throughput samples of transfers that have stopped running
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS session_timeseries (
+                session_id TEXT PRIMARY KEY,
+                samples TEXT NOT NULL
+            )
+            "#,
+        )
+        .execute(&pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS inbound_groups (
+                transfer_id TEXT NOT NULL,
+                group_index INTEGER NOT NULL,
+                bitmap BLOB NOT NULL,
+                PRIMARY KEY (transfer_id, group_index)
+            )
+            "#,
+        )
+        .execute(&pool)
+        .await?;
 
         Ok(Self { pool })
     }
 
+    /// Fill in the file name and checksum columns of sessions saved before
+    /// they existed
+    async fn index_files(pool: &SqlitePool) -> SessionResult<()> {
+        let rows = sqlx::query("SELECT session_id, manifest FROM sessions WHERE filename IS NULL")
+            .fetch_all(pool)
+            .await?;
+        for row in rows {
+            let manifest: FileManifest =
+                serde_json::from_str(&row.try_get::<String, _>("manifest")?)?;
+            sqlx::query("UPDATE sessions SET filename = ?, checksum = ? WHERE session_id = ?")
+                .bind(&manifest.filename)
+                .bind(hex::encode(manifest.checksum))
+                .bind(row.try_get::<String, _>("session_id")?)
+                .execute(pool)
+                .await?;
+        }
+        Ok(())
+    }
+
     /// Create session store with in-memory database (for testing)
     pub async fn new_in_memory() -> SessionResult<Self> {
         Self::new("sqlite::memory:").await
@@ -63,18 +207,22 @@ impl SessionStore {
 
     /// Save or update session state
     pub async fn save(&self, state: &SessionState) -> SessionResult<()> {
+        #[cfg(feature = "fault-injection")]
+        inject_write_fault()?;
+
         let manifest_json = serde_json::to_string(&state.manifest)?;
         let completed_json = serde_json::to_string(&state.completed_chunks)?;
         let failed_json = serde_json::to_string(&state.failed_chunks)?;
         let status_json = serde_json::to_string(&state.status)?;
         let receiver_addr_str = state.receiver_addr.map(|a| a.to_string());
         let metrics_json = serde_json::to_string(&state.metrics)?;
+        let options_json = serde_json::to_string(&state.options)?;
 
         sqlx::query(
             r#"
             INSERT OR REPLACE INTO sessions
-            (session_id, file_id, manifest, completed_chunks, failed_chunks, status, created_at, updated_at, receiver_addr, file_path, metrics)
-            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            (session_id, file_id, manifest, completed_chunks, failed_chunks, status, created_at, updated_at, receiver_addr, file_path, metrics, options, filename, checksum)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
             "#
         )
         .bind(&state.session_id)
@@ -88,6 +236,9 @@ impl SessionStore {
         .bind(receiver_addr_str)
         .bind(&state.file_path)
         .bind(metrics_json)
+        .bind(options_json)
+        .bind(&state.manifest.filename)
+        .bind(hex::encode(state.manifest.checksum))
         .execute(&self.pool)
         .await?;
 
@@ -101,38 +252,7 @@ impl SessionStore {
             .fetch_optional(&self.pool)
             .await?;
 
-        if let Some(row) = row {
-            // Parse receiver_addr from string
-            let receiver_addr_str: Option<String> = row.try_get("receiver_addr").ok().flatten();
-            let receiver_addr =
-                receiver_addr_str.and_then(|s| s.parse::<std::net::SocketAddr>().ok());
-
-            // Parse metrics, default if not present
-            let metrics: crate::session::types::TransferMetrics = row
-                .try_get::<String, _>("metrics")
-                .ok()
-                .and_then(|s| serde_json::from_str(&s).ok())
-                .unwrap_or_default();
-
-            let state = SessionState {
-                session_id: row.try_get("session_id")?,
-                file_id: row.try_get("file_id")?,
-                manifest: serde_json::from_str(&row.try_get::<String, _>("manifest")?)?,
-                completed_chunks: serde_json::from_str(
-                    &row.try_get::<String, _>("completed_chunks")?,
-                )?,
-                failed_chunks: serde_json::from_str(&row.try_get::<String, _>("failed_chunks")?)?,
-                status: serde_json::from_str(&row.try_get::<String, _>("status")?)?,
-                created_at: row.try_get("created_at")?,
-                updated_at: row.try_get("updated_at")?,
-                receiver_addr,
-                file_path: row.try_get("file_path").ok().flatten(),
-                metrics,
-            };
-            Ok(Some(state))
-        } else {
-            Ok(None)
-        }
+        row.map(|row| Self::state_from_row(&row)).transpose()
     }
 
     /// Mark chunk as completed
@@ -146,17 +266,9 @@ impl SessionStore {
             .await?
             .ok_or_else(|| SessionError::NotFound(session_id.to_string()))?;
 
-        state.mark_completed(chunk_number);
-
         // Record bytes transferred for speed calculation
         let chunk_size = state.manifest.chunk_size as u64;
-        state.record_bytes_transferred(chunk_size);
-
-        // Auto-complete session if all chunks are done
-        if state.is_complete() && !state.status.is_completed() {
-            state.status = SessionStatus::Completed;
-        }
-
+        state.record_chunk_completed(chunk_number, chunk_size);
         self.save(&state).await
     }
 
@@ -172,14 +284,21 @@ impl SessionStore {
             .await?
             .ok_or_else(|| SessionError::NotFound(session_id.to_string()))?;
 
-        state.mark_completed(chunk_number);
-        state.record_bytes_transferred(bytes_transferred);
+        state.record_chunk_completed(chunk_number, bytes_transferred);
+        self.save(&state).await
+    }
 
-        // Auto-complete session if all chunks are done
-        if state.is_complete() && !state.status.is_completed() {
-            state.status = SessionStatus::Completed;
-        }
+    /// Complete a session without sending anything because the receiver
+    /// already had an identical file
+    pub async fn mark_skipped_duplicate(&self, session_id: &str) -> SessionResult<()> {
+        let mut state = self
+            .load(session_id)
+            .await?
+            .ok_or_else(|| SessionError::NotFound(session_id.to_string()))?;
 
+        state.metrics.skipped_duplicate = true;
+        state.status = SessionStatus::Completed;
+        state.updated_at = chrono::Utc::now().timestamp();
         self.save(&state).await
     }
 
@@ -198,6 +317,36 @@ impl SessionStore {
         self.save(&state).await
     }
 
+    /// Take back a chunk the receiver reported corrupt
+    ///
+    /// The chunk no longer counts as delivered and is failed until a resend
+    /// succeeds.
+    pub async fn mark_chunk_nacked(
+        &self,
+        session_id: &str,
+        chunk_number: u32,
+    ) -> SessionResult<()> {
+        self.mark_chunks_lost(session_id, &[chunk_number]).await
+    }
+
+    /// Take back chunks the receiver reports it never got
+    ///
+    /// They no longer count as delivered and are failed until a resend
+    /// succeeds.
+    pub async fn mark_chunks_lost(
+        &self,
+        session_id: &str,
+        chunk_numbers: &[u32],
+    ) -> SessionResult<()> {
+        let mut state = self
+            .load(session_id)
+            .await?
+            .ok_or_else(|| SessionError::NotFound(session_id.to_string()))?;
+
+        state.mark_lost(chunk_numbers);
+        self.save(&state).await
+    }
+
     /// Update session status
     pub async fn update_status(
         &self,
@@ -230,39 +379,9 @@ impl SessionStore {
             .fetch_all(&self.pool)
             .await?;
 
-        let mut summaries = Vec::new();
-        for row in rows {
-            // Parse receiver_addr from string
-            let receiver_addr_str: Option<String> = row.try_get("receiver_addr").ok().flatten();
-            let receiver_addr =
-                receiver_addr_str.and_then(|s| s.parse::<std::net::SocketAddr>().ok());
-
-            // Parse metrics, default if not present
-            let metrics: crate::session::types::TransferMetrics = row
-                .try_get::<String, _>("metrics")
-                .ok()
-                .and_then(|s| serde_json::from_str(&s).ok())
-                .unwrap_or_default();
-
-            let state = SessionState {
-                session_id: row.try_get("session_id")?,
-                file_id: row.try_get("file_id")?,
-                manifest: serde_json::from_str(&row.try_get::<String, _>("manifest")?)?,
-                completed_chunks: serde_json::from_str(
-                    &row.try_get::<String, _>("completed_chunks")?,
-                )?,
-                failed_chunks: serde_json::from_str(&row.try_get::<String, _>("failed_chunks")?)?,
-                status: serde_json::from_str(&row.try_get::<String, _>("status")?)?,
-                created_at: row.try_get("created_at")?,
-                updated_at: row.try_get("updated_at")?,
-                receiver_addr,
-                file_path: row.try_get("file_path").ok().flatten(),
-                metrics,
-            };
-            summaries.push(SessionSummary::from_state(&state));
-        }
-
-        Ok(summaries)
+        rows.iter()
+            .map(|row| Ok(SessionSummary::from_state(&Self::state_from_row(row)?)))
+            .collect()
     }
 
     /// List sessions by status
@@ -277,51 +396,474 @@ impl SessionStore {
             .fetch_all(&self.pool)
             .await?;
 
-        let mut summaries = Vec::new();
-        for row in rows {
-            // Parse receiver_addr from string
-            let receiver_addr_str: Option<String> = row.try_get("receiver_addr").ok().flatten();
-            let receiver_addr =
-                receiver_addr_str.and_then(|s| s.parse::<std::net::SocketAddr>().ok());
-
-            // Parse metrics, default if not present
-            let metrics: crate::session::types::TransferMetrics = row
-                .try_get::<String, _>("metrics")
-                .ok()
-                .and_then(|s| serde_json::from_str(&s).ok())
-                .unwrap_or_default();
-
-            let state = SessionState {
-                session_id: row.try_get("session_id")?,
-                file_id: row.try_get("file_id")?,
-                manifest: serde_json::from_str(&row.try_get::<String, _>("manifest")?)?,
-                completed_chunks: serde_json::from_str(
-                    &row.try_get::<String, _>("completed_chunks")?,
-                )?,
-                failed_chunks: serde_json::from_str(&row.try_get::<String, _>("failed_chunks")?)?,
-                status: serde_json::from_str(&row.try_get::<String, _>("status")?)?,
-                created_at: row.try_get("created_at")?,
-                updated_at: row.try_get("updated_at")?,
-                receiver_addr,
-                file_path: row.try_get("file_path").ok().flatten(),
-                metrics,
+        rows.iter()
+            .map(|row| Ok(SessionSummary::from_state(&Self::state_from_row(row)?)))
+            .collect()
+    }
+
+    /// List sessions with filtering, sorting and pagination
+    pub async fn query(&self, query: &SessionQuery) -> SessionResult<SessionPage> {
+        // Variants with data serialize as {"Variant":...}, so match them by prefix
+        let status_pattern = match &query.status {
+            Some(SessionStatus::Failed(_)) => Some(r#"{"Failed":%"#.to_string()),
+            Some(SessionStatus::CompletedWithRepairs { .. }) => {
+                Some(r#"{"CompletedWithRepairs":%"#.to_string())
+            }
+            Some(SessionStatus::PartiallyDelivered { .. }) => {
+                Some(r#"{"PartiallyDelivered":%"#.to_string())
+            }
+            Some(status) => Some(serde_json::to_string(status)?),
+            None => None,
+        };
+        let mut conditions = Vec::new();
+        let mut binds: Vec<&str> = Vec::new();
+        if let Some(pattern) = &status_pattern {
+            conditions.push("status LIKE ?".to_string());
+            binds.push(pattern);
+        }
+        if let Some(file_id) = &query.file_id {
+            conditions.push("file_id = ?".to_string());
+            binds.push(file_id);
+        }
+        // Tag keys are limited to characters that are safe in a JSON path
+        let tag_paths: Vec<String> = query
+            .tags
+            .keys()
+            .map(|key| format!(r#"$.tags."{key}""#))
+            .collect();
+        for (path, value) in tag_paths.iter().zip(query.tags.values()) {
+            conditions.push("json_extract(options, ?) = ?".to_string());
+            binds.push(path);
+            binds.push(value);
+        }
+        let filter = if conditions.is_empty() {
+            String::new()
+        } else {
+            format!("WHERE {}", conditions.join(" AND "))
+        };
+
+        let count_sql = format!("SELECT COUNT(*) as count FROM sessions {filter}");
+        let mut count_query = sqlx::query(&count_sql);
+        for bind in &binds {
+            count_query = count_query.bind(*bind);
+        }
+        let total: i64 = count_query.fetch_one(&self.pool).await?.try_get("count")?;
+
+        let sql = format!(
+            "SELECT * FROM sessions {filter} ORDER BY {} LIMIT ? OFFSET ?",
+            query.sort.order_by()
+        );
+        let mut page_query = sqlx::query(&sql);
+        for bind in &binds {
+            page_query = page_query.bind(*bind);
+        }
+        // SQLite treats a negative LIMIT as "no limit"
+        let limit = query.limit.map(i64::from).unwrap_or(-1);
+        let rows = page_query
+            .bind(limit)
+            .bind(i64::from(query.offset))
+            .fetch_all(&self.pool)
+            .await?;
+
+        let sessions = rows
+            .iter()
+            .map(Self::state_from_row)
+            .collect::<SessionResult<Vec<_>>>()?;
+
+        Ok(SessionPage {
+            sessions,
+            total: total as u64,
+        })
+    }
+
+    /// Sessions that carried a file with the given name or checksum, most
+    /// recently updated first
+    pub async fn search(&self, search: &SessionSearch) -> SessionResult<Vec<SessionState>> {
+        let mut conditions = Vec::new();
+        let name_pattern = search.filename.as_deref().map(|name| {
+            conditions.push(r"filename LIKE ? ESCAPE '\'");
+            let (name, prefix) = match name.strip_suffix('*') {
+                Some(prefix) => (prefix, true),
+                None => (name, false),
             };
-            summaries.push(SessionSummary::from_state(&state));
+            let mut pattern = name
+                .replace('\\', r"\\")
+                .replace('%', r"\%")
+                .replace('_', r"\_");
+            if prefix {
+                pattern.push('%');
+            }
+            pattern
+        });
+        let checksum = search.checksum.map(|checksum| {
+            conditions.push("checksum = ?");
+            hex::encode(checksum)
+        });
+        let filter = if conditions.is_empty() {
+            String::new()
+        } else {
+            format!("WHERE {}", conditions.join(" AND "))
+        };
+
+        let sql = format!(
+            "SELECT * FROM sessions {filter} ORDER BY {} LIMIT ?",
+            SessionSort::UpdatedDesc.order_by()
+        );
+        let mut query = sqlx::query(&sql);
+        if let Some(pattern) = name_pattern {
+            query = query.bind(pattern);
+        }
+        if let Some(checksum) = checksum {
+            query = query.bind(checksum);
         }
+        let rows = query
+            .bind(search.limit.map(i64::from).unwrap_or(-1))
+            .fetch_all(&self.pool)
+            .await?;
 
-        Ok(summaries)
+        rows.iter().map(Self::state_from_row).collect()
     }
 
     /// Delete session
     pub async fn delete(&self, session_id: &str) -> SessionResult<bool> {
+        #[cfg(feature = "fault-injection")]
+        inject_write_fault()?;
+
         let result = sqlx::query("DELETE FROM sessions WHERE session_id = ?")
             .bind(session_id)
             .execute(&self.pool)
             .await?;
+        sqlx::query("DELETE FROM session_timeseries WHERE session_id = ?")
+            .bind(session_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Replace a session's saved throughput samples
+    pub async fn save_timeseries(
+        &self,
+        session_id: &str,
+        samples: &[ProgressSample],
+    ) -> SessionResult<()> {
+        #[cfg(feature = "fault-injection")]
+        inject_write_fault()?;
+
+        sqlx::query(
+            "INSERT OR REPLACE INTO session_timeseries (session_id, samples) VALUES (?, ?)",
+        )
+        .bind(session_id)
+        .bind(serde_json::to_string(samples)?)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// A session's saved throughput samples, oldest first (empty if none)
+    pub async fn load_timeseries(&self, session_id: &str) -> SessionResult<Vec<ProgressSample>> {
+        let row = sqlx::query("SELECT samples FROM session_timeseries WHERE session_id = ?")
+            .bind(session_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        match row {
+            Some(row) => Ok(serde_json::from_str(&row.try_get::<String, _>("samples")?)?),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Create or replace a transfer profile; returns it with its timestamps
+    ///
+    /// Replacing a profile keeps its original creation time.
+    pub async fn save_profile(&self, profile: &TransferProfile) -> SessionResult<TransferProfile> {
+        #[cfg(feature = "fault-injection")]
+        inject_write_fault()?;
+
+        let now = chrono::Utc::now().timestamp();
+        sqlx::query(
+            r#"
+            INSERT INTO transfer_profiles (name, profile, created_at, updated_at)
+            VALUES (?, ?, ?, ?)
+            ON CONFLICT(name) DO UPDATE SET profile = excluded.profile, updated_at = excluded.updated_at
+            "#,
+        )
+        .bind(&profile.name)
+        .bind(serde_json::to_string(profile)?)
+        .bind(now)
+        .bind(now)
+        .execute(&self.pool)
+        .await?;
+
+        self.load_profile(&profile.name)
+            .await?
+            .ok_or_else(|| SessionError::NotFound(profile.name.clone()))
+    }
+
+    pub async fn load_profile(&self, name: &str) -> SessionResult<Option<TransferProfile>> {
+        let row = sqlx::query("SELECT * FROM transfer_profiles WHERE name = ?")
+            .bind(name)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        row.as_ref().map(Self::profile_from_row).transpose()
+    }
+
+    /// Every profile, by name
+    pub async fn list_profiles(&self) -> SessionResult<Vec<TransferProfile>> {
+        let rows = sqlx::query("SELECT * FROM transfer_profiles ORDER BY name")
+            .fetch_all(&self.pool)
+            .await?;
+
+        rows.iter().map(Self::profile_from_row).collect()
+    }
+
+    pub async fn delete_profile(&self, name: &str) -> SessionResult<bool> {
+        #[cfg(feature = "fault-injection")]
+        inject_write_fault()?;
+
+        let result = sqlx::query("DELETE FROM transfer_profiles WHERE name = ?")
+            .bind(name)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    fn profile_from_row(row: &SqliteRow) -> SessionResult<TransferProfile> {
+        let mut profile: TransferProfile =
+            serde_json::from_str(&row.try_get::<String, _>("profile")?)?;
+        profile.name = row.try_get("name")?;
+        profile.created_at = row.try_get("created_at")?;
+        profile.updated_at = row.try_get("updated_at")?;
+        Ok(profile)
+    }
+
+    /// Store a benchmark report under a new id
+    pub async fn save_benchmark(
+        &self,
+        label: Option<&str>,
+        report: &serde_json::Value,
+    ) -> SessionResult<BenchmarkRecord> {
+        #[cfg(feature = "fault-injection")]
+        inject_write_fault()?;
+
+        let record = BenchmarkRecord {
+            id: uuid::Uuid::new_v4().to_string(),
+            label: label.map(str::to_string),
+            created_at: chrono::Utc::now().timestamp(),
+            report: report.clone(),
+        };
+        sqlx::query(
+            "INSERT INTO benchmark_reports (id, label, report, created_at) VALUES (?, ?, ?, ?)",
+        )
+        .bind(&record.id)
+        .bind(&record.label)
+        .bind(serde_json::to_string(report)?)
+        .bind(record.created_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(record)
+    }
+
+    pub async fn load_benchmark(&self, id: &str) -> SessionResult<Option<BenchmarkRecord>> {
+        let row = sqlx::query("SELECT * FROM benchmark_reports WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        row.as_ref().map(Self::benchmark_from_row).transpose()
+    }
+
+    /// The `limit` most recent benchmark reports, newest first
+    pub async fn list_benchmarks(&self, limit: u32) -> SessionResult<Vec<BenchmarkRecord>> {
+        let rows = sqlx::query(
+            "SELECT * FROM benchmark_reports ORDER BY created_at DESC, rowid DESC LIMIT ?",
+        )
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.iter().map(Self::benchmark_from_row).collect()
+    }
+
+    pub async fn delete_benchmark(&self, id: &str) -> SessionResult<bool> {
+        #[cfg(feature = "fault-injection")]
+        inject_write_fault()?;
+
+        let result = sqlx::query("DELETE FROM benchmark_reports WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    fn benchmark_from_row(row: &SqliteRow) -> SessionResult<BenchmarkRecord> {
+        Ok(BenchmarkRecord {
+            id: row.try_get("id")?,
+            label: row.try_get("label")?,
+            created_at: row.try_get("created_at")?,
+            report: serde_json::from_str(&row.try_get::<String, _>("report")?)?,
+        })
+    }
+
+    /// Create or replace an inbound transfer with all its group bitmaps
+    pub async fn save_inbound(&self, transfer: &InboundTransfer) -> SessionResult<()> {
+        #[cfg(feature = "fault-injection")]
+        inject_write_fault()?;
+
+        let mut tx = self.pool.begin().await?;
+        sqlx::query(
+            r#"
+            INSERT OR REPLACE INTO inbound_transfers
+            (transfer_id, manifest, group_size, created_at, updated_at)
+            VALUES (?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&transfer.transfer_id)
+        .bind(serde_json::to_string(&transfer.manifest)?)
+        .bind(transfer.group_size as i64)
+        .bind(transfer.created_at)
+        .bind(transfer.updated_at)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query("DELETE FROM inbound_groups WHERE transfer_id = ?")
+            .bind(&transfer.transfer_id)
+            .execute(&mut *tx)
+            .await?;
+        for (group, bitmap) in transfer.groups.iter().enumerate() {
+            sqlx::query(
+                "INSERT INTO inbound_groups (transfer_id, group_index, bitmap) VALUES (?, ?, ?)",
+            )
+            .bind(&transfer.transfer_id)
+            .bind(group as i64)
+            .bind(bitmap)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Record chunks of an inbound transfer as on disk
+    ///
+    /// Only the bitmaps of the groups `sequence_numbers` fall in are rewritten.
+    pub async fn record_inbound_chunks(
+        &self,
+        transfer_id: &str,
+        sequence_numbers: &[u32],
+    ) -> SessionResult<()> {
+        #[cfg(feature = "fault-injection")]
+        inject_write_fault()?;
+
+        let mut transfer = self
+            .load_inbound(transfer_id)
+            .await?
+            .ok_or_else(|| SessionError::NotFound(transfer_id.to_string()))?;
+        let group_size = transfer.group_size;
+        let mut touched: Vec<u32> = sequence_numbers
+            .iter()
+            .filter(|&&seq| transfer.mark_received(seq))
+            .map(|&seq| seq / group_size)
+            .collect();
+        touched.sort_unstable();
+        touched.dedup();
+        if touched.is_empty() {
+            return Ok(());
+        }
+
+        let mut tx = self.pool.begin().await?;
+        for group in touched {
+            sqlx::query(
+                "UPDATE inbound_groups SET bitmap = ? WHERE transfer_id = ? AND group_index = ?",
+            )
+            .bind(&transfer.groups[group as usize])
+            .bind(transfer_id)
+            .bind(group as i64)
+            .execute(&mut *tx)
+            .await?;
+        }
+        sqlx::query("UPDATE inbound_transfers SET updated_at = ? WHERE transfer_id = ?")
+            .bind(transfer.updated_at)
+            .bind(transfer_id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    pub async fn load_inbound(&self, transfer_id: &str) -> SessionResult<Option<InboundTransfer>> {
+        let row = sqlx::query("SELECT * FROM inbound_transfers WHERE transfer_id = ?")
+            .bind(transfer_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        match row {
+            Some(row) => Ok(Some(self.inbound_from_row(&row).await?)),
+            None => Ok(None),
+        }
+    }
 
+    /// Every inbound transfer, oldest first
+    pub async fn list_inbound(&self) -> SessionResult<Vec<InboundTransfer>> {
+        let rows = sqlx::query("SELECT * FROM inbound_transfers ORDER BY created_at")
+            .fetch_all(&self.pool)
+            .await?;
+        let mut transfers = Vec::with_capacity(rows.len());
+        for row in &rows {
+            transfers.push(self.inbound_from_row(row).await?);
+        }
+        Ok(transfers)
+    }
+
+    /// Forget an inbound transfer, once it is reconstructed or abandoned
+    pub async fn delete_inbound(&self, transfer_id: &str) -> SessionResult<bool> {
+        #[cfg(feature = "fault-injection")]
+        inject_write_fault()?;
+
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM inbound_groups WHERE transfer_id = ?")
+            .bind(transfer_id)
+            .execute(&mut *tx)
+            .await?;
+        let result = sqlx::query("DELETE FROM inbound_transfers WHERE transfer_id = ?")
+            .bind(transfer_id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
         Ok(result.rows_affected() > 0)
     }
 
+    async fn inbound_from_row(&self, row: &SqliteRow) -> SessionResult<InboundTransfer> {
+        let transfer_id: String = row.try_get("transfer_id")?;
+        let group_size: i64 = row.try_get("group_size")?;
+        let mut transfer = InboundTransfer::new(
+            transfer_id,
+            serde_json::from_str(&row.try_get::<String, _>("manifest")?)?,
+            group_size as u32,
+        );
+        transfer.created_at = row.try_get("created_at")?;
+        transfer.updated_at = row.try_get("updated_at")?;
+
+        let groups =
+            sqlx::query("SELECT group_index, bitmap FROM inbound_groups WHERE transfer_id = ?")
+                .bind(&transfer.transfer_id)
+                .fetch_all(&self.pool)
+                .await?;
+        for group in groups {
+            let index: i64 = group.try_get("group_index")?;
+            let bitmap: Vec<u8> = group.try_get("bitmap")?;
+            // A bitmap that doesn't fit the manifest is ignored; its chunks
+            // are asked for again
+            if let Some(slot) = transfer.groups.get_mut(index as usize) {
+                if slot.len() == bitmap.len() {
+                    *slot = bitmap;
+                }
+            }
+        }
+        Ok(transfer)
+    }
+
     /// Clean up old sessions
     pub async fn cleanup_old_sessions(&self, days: i64) -> SessionResult<u64> {
         let cutoff = chrono::Utc::now().timestamp() - (days * 86400);
@@ -339,11 +881,15 @@ impl SessionStore {
             let status: SessionStatus = serde_json::from_str(&status_str)?;
 
             // Only delete completed or failed sessions
-            if matches!(status, SessionStatus::Completed | SessionStatus::Failed(_)) {
+            if status.is_terminal() {
                 let result = sqlx::query("DELETE FROM sessions WHERE session_id = ?")
                     .bind(&session_id)
                     .execute(&self.pool)
                     .await?;
+                sqlx::query("DELETE FROM session_timeseries WHERE session_id = ?")
+                    .bind(&session_id)
+                    .execute(&self.pool)
+                    .await?;
                 deleted += result.rows_affected();
             }
         }
@@ -360,6 +906,147 @@ impl SessionStore {
         Ok(row.try_get("count")?)
     }
 
+    /// Database size and row counts per table
+    pub async fn storage_stats(&self) -> SessionResult<StorageStats> {
+        let page_size = self.pragma("page_size").await?;
+        let page_count = self.pragma("page_count").await?;
+        let row = sqlx::query(
+            r#"
+            SELECT
+                (SELECT COUNT(*) FROM sessions) AS sessions,
+                (SELECT COUNT(*) FROM session_timeseries) AS timeseries,
+                (SELECT COUNT(*) FROM transfer_profiles) AS profiles,
+                (SELECT COUNT(*) FROM benchmark_reports) AS benchmarks,
+                (SELECT COUNT(*) FROM inbound_transfers) AS inbound_transfers,
+                (SELECT COUNT(*) FROM inbound_groups) AS inbound_groups
+            "#,
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        let count =
+            |column: &str| -> SessionResult<u64> { Ok(row.try_get::<i64, _>(column)? as u64) };
+
+        Ok(StorageStats {
+            page_size,
+            page_count,
+            free_pages: self.pragma("freelist_count").await?,
+            db_bytes: page_size * page_count,
+            file_bytes: self.file_bytes().await?,
+            incremental_vacuum: self.vacuums_incrementally().await?,
+            sessions: count("sessions")?,
+            timeseries: count("timeseries")?,
+            profiles: count("profiles")?,
+            benchmarks: count("benchmarks")?,
+            inbound_transfers: count("inbound_transfers")?,
+            inbound_groups: count("inbound_groups")?,
+        })
+    }
+
+    /// Return free pages to the file system and refresh the query planner's
+    /// statistics
+    ///
+    /// `vacuum_pages` caps the pages freed in one pass (0 frees them all),
+    /// so a large backlog doesn't hold the write lock for long. A database
+    /// created before incremental vacuum was enabled is rewritten in full
+    /// once, which needs as much free disk as the database takes.
+    pub async fn maintain(
+        &self,
+        vacuum_pages: u32,
+        analyze: bool,
+    ) -> SessionResult<MaintenanceReport> {
+        let started_at = chrono::Utc::now().timestamp();
+        let start = Instant::now();
+        let page_size = self.pragma("page_size").await?;
+        let before = self.pragma("page_count").await?;
+
+        let converted = !self.vacuums_incrementally().await?;
+        if converted {
+            // auto_vacuum only changes on a full rewrite, which has to run on
+            // the connection that set it
+            let mut conn = self.pool.acquire().await?;
+            sqlx::query("PRAGMA auto_vacuum = INCREMENTAL")
+                .execute(&mut *conn)
+                .await?;
+            sqlx::query("VACUUM").execute(&mut *conn).await?;
+        } else {
+            sqlx::query(&format!("PRAGMA incremental_vacuum({vacuum_pages})"))
+                .execute(&self.pool)
+                .await?;
+        }
+        if analyze {
+            sqlx::query("ANALYZE").execute(&self.pool).await?;
+        }
+        // Shrink the write-ahead log too; a no-op in other journal modes
+        sqlx::query("PRAGMA wal_checkpoint(TRUNCATE)")
+            .execute(&self.pool)
+            .await?;
+
+        let freed_pages = before.saturating_sub(self.pragma("page_count").await?);
+        Ok(MaintenanceReport {
+            started_at,
+            duration_ms: start.elapsed().as_millis() as u64,
+            converted,
+            freed_pages,
+            reclaimed_bytes: freed_pages * page_size,
+            analyzed: analyze,
+        })
+    }
+
+    /// A pragma that reads back one integer
+    async fn pragma(&self, name: &str) -> SessionResult<u64> {
+        let value: i64 = sqlx::query_scalar(&format!("PRAGMA {name}"))
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(value.max(0) as u64)
+    }
+
+    /// Whether the database is set up for incremental vacuum
+    async fn vacuums_incrementally(&self) -> SessionResult<bool> {
+        // A connection only rereads the setting from the file header when it
+        // starts a read, so one made before a conversion would report the old
+        // value without one
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("SELECT COUNT(*) FROM sqlite_master")
+            .execute(&mut *tx)
+            .await?;
+        let mode: i64 = sqlx::query_scalar("PRAGMA auto_vacuum")
+            .fetch_one(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(mode == AUTO_VACUUM_INCREMENTAL)
+    }
+
+    /// Size of the database file and its write-ahead log, if on disk
+    async fn file_bytes(&self) -> SessionResult<Option<u64>> {
+        let rows = sqlx::query("PRAGMA database_list")
+            .fetch_all(&self.pool)
+            .await?;
+        let Some(path) = rows.iter().find_map(|row| {
+            let name: String = row.try_get("name").ok()?;
+            let file: String = row.try_get("file").ok()?;
+            (name == "main" && !file.is_empty()).then_some(file)
+        }) else {
+            return Ok(None);
+        };
+
+        let size = |path: String| async move {
+            match tokio::fs::metadata(&path).await {
+                Ok(meta) => Ok(meta.len()),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(0),
+                Err(e) => Err(e),
+            }
+        };
+        Ok(Some(
+            size(path.clone()).await? + size(format!("{path}-wal")).await?,
+        ))
+    }
+
+    /// Round trip to the database, for health checks
+    pub async fn ping(&self) -> SessionResult<()> {
+        sqlx::query("SELECT 1").execute(&self.pool).await?;
+        Ok(())
+    }
+
     /// Check if session exists
     pub async fn exists(&self, session_id: &str) -> SessionResult<bool> {
         let row = sqlx::query("SELECT COUNT(*) as count FROM sessions WHERE session_id = ?")
@@ -371,16 +1058,78 @@ impl SessionStore {
         Ok(count > 0)
     }
 
+    /// Build a session from a `sessions` row, tolerating columns added by later migrations
+    fn state_from_row(row: &SqliteRow) -> SessionResult<SessionState> {
+        // Parse receiver_addr from string
+        let receiver_addr_str: Option<String> = row.try_get("receiver_addr").ok().flatten();
+        let receiver_addr = receiver_addr_str.and_then(|s| s.parse::<std::net::SocketAddr>().ok());
+
+        // Parse metrics, default if not present
+        let metrics: TransferMetrics = row
+            .try_get::<String, _>("metrics")
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default();
+
+        let options: TransferOptions = row
+            .try_get::<String, _>("options")
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default();
+
+        Ok(SessionState {
+            session_id: row.try_get("session_id")?,
+            file_id: row.try_get("file_id")?,
+            manifest: serde_json::from_str(&row.try_get::<String, _>("manifest")?)?,
+            completed_chunks: serde_json::from_str(&row.try_get::<String, _>("completed_chunks")?)?,
+            failed_chunks: serde_json::from_str(&row.try_get::<String, _>("failed_chunks")?)?,
+            status: serde_json::from_str(&row.try_get::<String, _>("status")?)?,
+            created_at: row.try_get("created_at")?,
+            updated_at: row.try_get("updated_at")?,
+            receiver_addr,
+            file_path: row.try_get("file_path").ok().flatten(),
+            metrics,
+            options,
+        })
+    }
+
     /// Close database connection
     pub async fn close(&self) {
         self.pool.close().await;
     }
 }
 
+#[cfg(feature = "fault-injection")]
+fn inject_write_fault() -> SessionResult<()> {
+    crate::fault::check(crate::fault::FaultPoint::SessionWrite)
+        .map_err(|fault| SessionError::DatabaseError(fault.to_string()))
+}
+
+fn journal_mode(mode: JournalMode) -> SqliteJournalMode {
+    match mode {
+        JournalMode::Delete => SqliteJournalMode::Delete,
+        JournalMode::Truncate => SqliteJournalMode::Truncate,
+        JournalMode::Persist => SqliteJournalMode::Persist,
+        JournalMode::Memory => SqliteJournalMode::Memory,
+        JournalMode::Wal => SqliteJournalMode::Wal,
+        JournalMode::Off => SqliteJournalMode::Off,
+    }
+}
+
+fn synchronous(level: SynchronousLevel) -> SqliteSynchronous {
+    match level {
+        SynchronousLevel::Off => SqliteSynchronous::Off,
+        SynchronousLevel::Normal => SqliteSynchronous::Normal,
+        SynchronousLevel::Full => SqliteSynchronous::Full,
+        SynchronousLevel::Extra => SqliteSynchronous::Extra,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::chunk::{FileManifest, Priority};
+    use crate::session::types::SessionSort;
 
     fn create_test_manifest() -> FileManifest {
         FileManifest {
@@ -393,7 +1142,204 @@ mod tests {
             parity_chunks: 3,
             priority: Priority::Normal,
             checksum: [0u8; 32],
+            zero_runs: Vec::new(),
+            attributes: None,
+            checksum_algorithm: Default::default(),
+            erasure_profile: Default::default(),
+            schedule: None,
+            merkle_root: None,
+            compression: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_inbound_transfer_survives_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let url = format!(
+            "sqlite://{}?mode=rwc",
+            dir.path().join("inbound.db").display()
+        );
+        let store = SessionStore::new(&url).await.unwrap();
+
+        // 13 chunks in groups of 4: the last group has a single chunk
+        let transfer = InboundTransfer::new("t1".into(), create_test_manifest(), 4);
+        assert_eq!(transfer.groups.len(), 4);
+        store.save_inbound(&transfer).await.unwrap();
+        store
+            .record_inbound_chunks("t1", &[0, 1, 2, 3, 6, 12, 12, 40])
+            .await
+            .unwrap();
+        assert!(store.record_inbound_chunks("missing", &[0]).await.is_err());
+        store.close().await;
+
+        let store = SessionStore::new(&url).await.unwrap();
+        let loaded = store.load_inbound("t1").await.unwrap().unwrap();
+        assert_eq!(loaded.manifest.file_id, "test-file");
+        assert_eq!(loaded.received_sequences(), [0, 1, 2, 3, 6, 12]);
+        assert_eq!(loaded.missing_sequences(), [4, 5, 7, 8, 9, 10, 11]);
+        assert_eq!(loaded.complete_groups(), 2);
+        assert_eq!(loaded.shortfall(), 4);
+
+        assert_eq!(store.list_inbound().await.unwrap().len(), 1);
+        assert!(store.delete_inbound("t1").await.unwrap());
+        assert!(store.load_inbound("t1").await.unwrap().is_none());
+        assert!(!store.delete_inbound("t1").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_transfer_profiles_round_trip() {
+        let store = SessionStore::new_in_memory().await.unwrap();
+        let mut profile = TransferProfile::new("nightly");
+        profile.priority = Some(Priority::Critical);
+        profile.receiver_addr = Some("10.0.0.9:5001".parse().unwrap());
+        profile.options.rate_limit_bytes_per_sec = Some(1_000_000);
+
+        let saved = store.save_profile(&profile).await.unwrap();
+        assert_eq!(saved.options, profile.options);
+        assert_eq!(saved.priority, Some(Priority::Critical));
+
+        // Replacing keeps the creation time
+        sqlx::query("UPDATE transfer_profiles SET created_at = 1")
+            .execute(&store.pool)
+            .await
+            .unwrap();
+        profile.options.chunk_size = Some(128 * 1024);
+        let replaced = store.save_profile(&profile).await.unwrap();
+        assert_eq!(replaced.created_at, 1);
+        assert_eq!(replaced.options.chunk_size, Some(128 * 1024));
+
+        store
+            .save_profile(&TransferProfile::new("adhoc"))
+            .await
+            .unwrap();
+        let names: Vec<String> = store
+            .list_profiles()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|p| p.name)
+            .collect();
+        assert_eq!(names, vec!["adhoc", "nightly"]);
+
+        assert!(store.delete_profile("nightly").await.unwrap());
+        assert!(!store.delete_profile("nightly").await.unwrap());
+        assert!(store.load_profile("nightly").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_benchmark_reports_newest_first() {
+        let store = SessionStore::new_in_memory().await.unwrap();
+        let mut ids = Vec::new();
+        for run in 0..3 {
+            let report = serde_json::json!({ "run": run });
+            ids.push(store.save_benchmark(None, &report).await.unwrap().id);
+        }
+
+        let listed = store.list_benchmarks(2).await.unwrap();
+        assert_eq!(listed.len(), 2);
+        assert_eq!(listed[0].id, ids[2]);
+        assert_eq!(listed[1].report["run"], 1);
+        assert_eq!(store.storage_stats().await.unwrap().benchmarks, 3);
+
+        assert!(store.delete_benchmark(&ids[0]).await.unwrap());
+        assert!(store.load_benchmark(&ids[0]).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_options_applied_to_file_database() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let url = format!(
+            "sqlite://{}?mode=rwc",
+            temp_dir.path().join("sessions.db").display()
+        );
+        let store = SessionStore::new(&url).await.unwrap();
+
+        let mode: String = sqlx::query_scalar("PRAGMA journal_mode")
+            .fetch_one(&store.pool)
+            .await
+            .unwrap();
+        assert_eq!(mode, "wal");
+        // NORMAL
+        let sync: i64 = sqlx::query_scalar("PRAGMA synchronous")
+            .fetch_one(&store.pool)
+            .await
+            .unwrap();
+        assert_eq!(sync, 1);
+        let timeout: i64 = sqlx::query_scalar("PRAGMA busy_timeout")
+            .fetch_one(&store.pool)
+            .await
+            .unwrap();
+        assert_eq!(timeout, 5000);
+    }
+
+    #[tokio::test]
+    async fn test_maintenance_reclaims_deleted_sessions() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let url = format!(
+            "sqlite://{}?mode=rwc",
+            temp_dir.path().join("sessions.db").display()
+        );
+        let store = SessionStore::new(&url).await.unwrap();
+        let empty = store.storage_stats().await.unwrap();
+        assert!(empty.incremental_vacuum);
+        assert_eq!(empty.sessions, 0);
+
+        let mut state = SessionState::new("s".into(), "f".into(), create_test_manifest());
+        state.completed_chunks = (0..5000).collect();
+        for n in 0..20 {
+            state.session_id = format!("session-{n}");
+            store.save(&state).await.unwrap();
+        }
+        let full = store.storage_stats().await.unwrap();
+        assert_eq!(full.sessions, 20);
+        assert!(full.file_bytes.unwrap() > empty.file_bytes.unwrap());
+
+        for n in 0..20 {
+            store.delete(&format!("session-{n}")).await.unwrap();
         }
+        let deleted = store.storage_stats().await.unwrap();
+        assert_eq!(deleted.sessions, 0);
+        assert!(deleted.free_pages > 0);
+
+        let report = store.maintain(0, true).await.unwrap();
+        assert!(!report.converted);
+        assert!(report.analyzed);
+        // ANALYZE takes a page or two back for its statistics
+        assert!(report.freed_pages > 0 && report.freed_pages <= deleted.free_pages);
+        assert_eq!(
+            report.reclaimed_bytes,
+            report.freed_pages * deleted.page_size
+        );
+        let after = store.storage_stats().await.unwrap();
+        assert_eq!(after.free_pages, 0);
+        assert!(after.file_bytes.unwrap() < full.file_bytes.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_maintenance_converts_older_databases() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let url = format!(
+            "sqlite://{}?mode=rwc",
+            temp_dir.path().join("sessions.db").display()
+        );
+        // A database created without incremental vacuum
+        let pool = SqlitePoolOptions::new().connect(&url).await.unwrap();
+        sqlx::query("CREATE TABLE legacy (id INTEGER)")
+            .execute(&pool)
+            .await
+            .unwrap();
+        pool.close().await;
+
+        let store = SessionStore::new(&url).await.unwrap();
+        assert!(!store.storage_stats().await.unwrap().incremental_vacuum);
+        let report = store.maintain(100, false).await.unwrap();
+        assert!(report.converted);
+        assert!(!report.analyzed);
+        assert!(store.storage_stats().await.unwrap().incremental_vacuum);
+        assert!(!store.maintain(100, false).await.unwrap().converted);
+
+        let memory = SessionStore::new_in_memory().await.unwrap();
+        assert_eq!(memory.storage_stats().await.unwrap().file_bytes, None);
     }
 
     #[tokio::test]
@@ -464,6 +1410,48 @@ mod tests {
         assert_eq!(loaded.status, SessionStatus::Completed);
     }
 
+    #[tokio::test]
+    async fn test_mark_skipped_duplicate() {
+        let store = SessionStore::new_in_memory().await.unwrap();
+        let state = SessionState::new(
+            "test-session".to_string(),
+            "test-file".to_string(),
+            create_test_manifest(),
+        );
+        store.save(&state).await.unwrap();
+
+        store.mark_skipped_duplicate("test-session").await.unwrap();
+
+        let loaded = store.load("test-session").await.unwrap().unwrap();
+        assert_eq!(loaded.status, SessionStatus::Completed);
+        assert!(loaded.metrics.skipped_duplicate);
+        assert_eq!(loaded.metrics.bytes_transferred, 0);
+        assert_eq!(loaded.progress_percent(), 100.0);
+    }
+
+    #[tokio::test]
+    async fn test_mark_chunk_nacked() {
+        let store = SessionStore::new_in_memory().await.unwrap();
+        let state = SessionState::new(
+            "test-session".to_string(),
+            "test-file".to_string(),
+            create_test_manifest(),
+        );
+        store.save(&state).await.unwrap();
+
+        store.mark_chunk_completed("test-session", 3).await.unwrap();
+        store.mark_chunk_nacked("test-session", 3).await.unwrap();
+        let loaded = store.load("test-session").await.unwrap().unwrap();
+        assert!(!loaded.completed_chunks.contains(&3));
+        assert!(loaded.failed_chunks.contains(&3));
+
+        // A successful resend clears the failure
+        store.mark_chunk_completed("test-session", 3).await.unwrap();
+        let loaded = store.load("test-session").await.unwrap().unwrap();
+        assert!(loaded.completed_chunks.contains(&3));
+        assert!(loaded.failed_chunks.is_empty());
+    }
+
     #[tokio::test]
     async fn test_mark_chunk_failed() {
         let store = SessionStore::new_in_memory().await.unwrap();
@@ -565,6 +1553,154 @@ mod tests {
         assert_eq!(paused.len(), 1);
     }
 
+    #[tokio::test]
+    async fn test_query_filters_sorts_and_pages() {
+        let store = SessionStore::new_in_memory().await.unwrap();
+
+        for i in 0..5 {
+            let mut state = SessionState::new(
+                format!("session-{}", i),
+                format!("file-{}", i % 2),
+                create_test_manifest(),
+            );
+            state.created_at = 1_000 + i;
+            state.status = if i == 4 {
+                SessionStatus::Failed("disk full".into())
+            } else {
+                SessionStatus::Completed
+            };
+            store.save(&state).await.unwrap();
+        }
+
+        let page = store
+            .query(&SessionQuery {
+                status: Some(SessionStatus::Completed),
+                file_id: None,
+                tags: Default::default(),
+                sort: SessionSort::CreatedDesc,
+                limit: Some(2),
+                offset: 1,
+            })
+            .await
+            .unwrap();
+        assert_eq!(page.total, 4);
+        let ids: Vec<_> = page
+            .sessions
+            .iter()
+            .map(|s| s.session_id.as_str())
+            .collect();
+        assert_eq!(ids, vec!["session-2", "session-1"]);
+
+        let failed = store
+            .query(&SessionQuery {
+                status: SessionStatus::from_kind("failed"),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(failed.total, 1);
+        assert_eq!(
+            failed.sessions[0].status,
+            SessionStatus::Failed("disk full".into())
+        );
+
+        let all = store.query(&SessionQuery::default()).await.unwrap();
+        assert_eq!(all.total, 5);
+        assert_eq!(all.sessions.len(), 5);
+
+        let odd = store
+            .query(&SessionQuery {
+                status: Some(SessionStatus::Completed),
+                file_id: Some("file-1".into()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(odd.total, 2);
+        assert!(odd.sessions.iter().all(|s| s.file_id == "file-1"));
+    }
+
+    #[tokio::test]
+    async fn test_search_by_filename_and_checksum() {
+        let store = SessionStore::new_in_memory().await.unwrap();
+        for (i, name) in ["site_survey.tif", "site-survey.tif", "Site_Survey.TIF"]
+            .iter()
+            .enumerate()
+        {
+            let mut manifest = create_test_manifest();
+            manifest.filename = name.to_string();
+            manifest.checksum = [i as u8; 32];
+            let mut state = SessionState::new(format!("session-{i}"), name.to_string(), manifest);
+            state.created_at = 1_000 + i as i64;
+            store.save(&state).await.unwrap();
+        }
+
+        let ids = |found: Vec<SessionState>| {
+            let mut ids: Vec<String> = found.into_iter().map(|s| s.session_id).collect();
+            ids.sort();
+            ids
+        };
+        let by_name = |name: &str| SessionSearch {
+            filename: Some(name.to_string()),
+            ..Default::default()
+        };
+
+        // Case doesn't matter, and `_` is not a wildcard
+        let found = store.search(&by_name("SITE_SURVEY.tif")).await.unwrap();
+        assert_eq!(ids(found), ["session-0", "session-2"]);
+        let found = store.search(&by_name("site*")).await.unwrap();
+        assert_eq!(found.len(), 3);
+        assert!(store.search(&by_name("survey*")).await.unwrap().is_empty());
+
+        let search = SessionSearch {
+            filename: Some("site*".into()),
+            checksum: Some([1; 32]),
+            limit: None,
+        };
+        let found = store.search(&search).await.unwrap();
+        assert_eq!(ids(found.clone()), ["session-1"]);
+        assert!(found.iter().all(|s| search.matches(s)));
+
+        let limited = SessionSearch {
+            limit: Some(2),
+            ..by_name("site*")
+        };
+        assert_eq!(store.search(&limited).await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_search_indexes_sessions_saved_before_upgrade() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let url = format!(
+            "sqlite://{}?mode=rwc",
+            temp_dir.path().join("sessions.db").display()
+        );
+        let store = SessionStore::new(&url).await.unwrap();
+        let mut manifest = create_test_manifest();
+        manifest.checksum = [7; 32];
+        store
+            .save(&SessionState::new("old".into(), "file".into(), manifest))
+            .await
+            .unwrap();
+        // As an older version left it
+        sqlx::query("UPDATE sessions SET filename = NULL, checksum = NULL")
+            .execute(&store.pool)
+            .await
+            .unwrap();
+        store.close().await;
+
+        let store = SessionStore::new(&url).await.unwrap();
+        let found = store
+            .search(&SessionSearch {
+                checksum: Some([7; 32]),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].session_id, "old");
+    }
+
     #[tokio::test]
     async fn test_delete() {
         let store = SessionStore::new_in_memory().await.unwrap();
@@ -583,6 +1719,53 @@ mod tests {
         assert_eq!(store.count().await.unwrap(), 0);
     }
 
+    #[tokio::test]
+    async fn test_timeseries_round_trip() {
+        let store = SessionStore::new_in_memory().await.unwrap();
+        let state = SessionState::new(
+            "test-session".to_string(),
+            "test-file".to_string(),
+            create_test_manifest(),
+        );
+        store.save(&state).await.unwrap();
+        assert!(store
+            .load_timeseries("test-session")
+            .await
+            .unwrap()
+            .is_empty());
+
+        let samples = [
+            ProgressSample {
+                timestamp_ms: 1_000,
+                bytes_transferred: 4096,
+                speed_bps: 4096,
+                loss_rate: 0.0,
+            },
+            ProgressSample {
+                timestamp_ms: 2_000,
+                bytes_transferred: 12288,
+                speed_bps: 8192,
+                loss_rate: 0.25,
+            },
+        ];
+        store
+            .save_timeseries("test-session", &samples)
+            .await
+            .unwrap();
+        assert_eq!(
+            store.load_timeseries("test-session").await.unwrap(),
+            samples
+        );
+
+        // Deleting the session takes its samples with it
+        store.delete("test-session").await.unwrap();
+        assert!(store
+            .load_timeseries("test-session")
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
     #[tokio::test]
     async fn test_exists() {
         let store = SessionStore::new_in_memory().await.unwrap();
@@ -644,4 +1827,82 @@ mod tests {
         assert!(!store.exists("old-session").await.unwrap());
         assert!(store.exists("active-session").await.unwrap());
     }
+
+    #[tokio::test]
+    async fn test_query_filters_by_tags() {
+        let store = SessionStore::new_in_memory().await.unwrap();
+        for (id, incident, unit) in [
+            ("s1", "42", "alpha"),
+            ("s2", "42", "bravo"),
+            ("s3", "7", "alpha"),
+        ] {
+            let mut state = SessionState::new(id.into(), id.into(), create_test_manifest());
+            state.options.tags = [
+                ("incident_id".to_string(), incident.to_string()),
+                ("originating.unit".to_string(), unit.to_string()),
+            ]
+            .into();
+            store.save(&state).await.unwrap();
+        }
+        store
+            .save(&SessionState::new(
+                "untagged".into(),
+                "untagged".into(),
+                create_test_manifest(),
+            ))
+            .await
+            .unwrap();
+
+        let tagged = |tags: &[(&str, &str)]| SessionQuery {
+            tags: tags
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            sort: SessionSort::CreatedAsc,
+            ..Default::default()
+        };
+        let ids = |page: SessionPage| -> Vec<String> {
+            let mut ids: Vec<_> = page.sessions.into_iter().map(|s| s.session_id).collect();
+            ids.sort();
+            ids
+        };
+
+        let page = store
+            .query(&tagged(&[("incident_id", "42")]))
+            .await
+            .unwrap();
+        assert_eq!(page.total, 2);
+        assert_eq!(ids(page), ["s1", "s2"]);
+
+        let page = store
+            .query(&tagged(&[
+                ("incident_id", "42"),
+                ("originating.unit", "alpha"),
+            ]))
+            .await
+            .unwrap();
+        assert_eq!(ids(page), ["s1"]);
+
+        let page = store
+            .query(&tagged(&[("incident_id", "99")]))
+            .await
+            .unwrap();
+        assert_eq!(page.total, 0);
+    }
+
+    #[tokio::test]
+    async fn test_options_roundtrip() {
+        let store = SessionStore::new_in_memory().await.unwrap();
+        let mut state = SessionState::new(
+            "opts-session".to_string(),
+            "test-file".to_string(),
+            create_test_manifest(),
+        );
+        state.options.local_bind_addr = Some("10.0.0.5:0".parse().unwrap());
+
+        store.save(&state).await.unwrap();
+
+        let loaded = store.load("opts-session").await.unwrap().unwrap();
+        assert_eq!(loaded.options, state.options);
+    }
 }
@@ -1,7 +1,8 @@
-use crate::chunk::FileManifest;
+use crate::chunk::{FileManifest, Priority};
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
 use std::net::SocketAddr;
+use std::time::Duration;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub enum SessionStatus {
@@ -9,6 +10,15 @@ pub enum SessionStatus {
     Active,
     Paused,
     Completed,
+    /// Delivered, with `repaired_chunks` left for the receiver to rebuild from parity
+    CompletedWithRepairs {
+        repaired_chunks: u32,
+    },
+    /// Part delivered; a relay holds the remaining chunks
+    PartiallyDelivered {
+        delivered_chunks: u32,
+        held_by_relay: u32,
+    },
     Failed(String),
 }
 
@@ -22,10 +32,140 @@ impl SessionStatus {
     }
 
     pub fn is_completed(&self) -> bool {
-        matches!(self, SessionStatus::Completed)
+        matches!(
+            self,
+            SessionStatus::Completed | SessionStatus::CompletedWithRepairs { .. }
+        )
+    }
+
+    /// Nothing more will happen to this session
+    pub fn is_terminal(&self) -> bool {
+        self.is_completed() || matches!(self, SessionStatus::Failed(_))
+    }
+
+    /// Delivered or delivering, but not cleanly
+    pub fn is_degraded(&self) -> bool {
+        matches!(
+            self,
+            SessionStatus::CompletedWithRepairs { .. } | SessionStatus::PartiallyDelivered { .. }
+        )
+    }
+
+    /// Lowercase status name without any failure reason
+    pub fn kind(&self) -> &'static str {
+        match self {
+            SessionStatus::Initializing => "initializing",
+            SessionStatus::Active => "active",
+            SessionStatus::Paused => "paused",
+            SessionStatus::Completed => "completed",
+            SessionStatus::CompletedWithRepairs { .. } => "completed_with_repairs",
+            SessionStatus::PartiallyDelivered { .. } => "partially_delivered",
+            SessionStatus::Failed(_) => "failed",
+        }
+    }
+
+    /// Parse a status name as produced by [`SessionStatus::kind`]
+    ///
+    /// Variants with data get zeroed fields; filters match any value of them.
+    pub fn from_kind(kind: &str) -> Option<Self> {
+        match kind.to_ascii_lowercase().as_str() {
+            "initializing" => Some(SessionStatus::Initializing),
+            "active" => Some(SessionStatus::Active),
+            "paused" => Some(SessionStatus::Paused),
+            "completed" => Some(SessionStatus::Completed),
+            "completed_with_repairs" => {
+                Some(SessionStatus::CompletedWithRepairs { repaired_chunks: 0 })
+            }
+            "partially_delivered" => Some(SessionStatus::PartiallyDelivered {
+                delivered_chunks: 0,
+                held_by_relay: 0,
+            }),
+            "failed" => Some(SessionStatus::Failed(String::new())),
+            _ => None,
+        }
     }
 }
 
+/// Sort order for session listings
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionSort {
+    #[default]
+    UpdatedDesc,
+    UpdatedAsc,
+    CreatedDesc,
+    CreatedAsc,
+}
+
+impl SessionSort {
+    /// `ORDER BY` clause for [`SessionStore`](crate::session::SessionStore)
+    #[cfg(feature = "sqlite")]
+    pub(crate) fn order_by(&self) -> &'static str {
+        match self {
+            SessionSort::UpdatedDesc => "updated_at DESC, session_id",
+            SessionSort::UpdatedAsc => "updated_at ASC, session_id",
+            SessionSort::CreatedDesc => "created_at DESC, session_id",
+            SessionSort::CreatedAsc => "created_at ASC, session_id",
+        }
+    }
+}
+
+/// Filter, sort and page options for [`SessionStore::query`](crate::session::SessionStore::query)
+#[derive(Debug, Clone, Default)]
+pub struct SessionQuery {
+    /// Only sessions in this status (any `Failed` reason matches)
+    pub status: Option<SessionStatus>,
+    /// Only sessions for this file (the path it was sent from)
+    pub file_id: Option<String>,
+    /// Only sessions carrying every one of these tags
+    pub tags: TransferTags,
+    pub sort: SessionSort,
+    /// Page size (`None` returns everything after `offset`)
+    pub limit: Option<u32>,
+    pub offset: u32,
+}
+
+/// Sessions that carried a file, for [`SessionStore::search`](crate::session::SessionStore::search)
+///
+/// Both criteria must match when both are given.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SessionSearch {
+    /// File name, ignoring ASCII case; a trailing `*` matches any name
+    /// starting with the rest
+    pub filename: Option<String>,
+    /// File checksum from the manifest
+    pub checksum: Option<[u8; 32]>,
+    /// Most sessions returned, most recently updated first (`None` for all)
+    pub limit: Option<u32>,
+}
+
+impl SessionSearch {
+    /// Whether `state` carried a file matching the search
+    pub fn matches(&self, state: &SessionState) -> bool {
+        let filename = &state.manifest.filename;
+        let name_matches = match self.filename.as_deref() {
+            None => true,
+            Some(pattern) => match pattern.strip_suffix('*') {
+                Some(prefix) => filename
+                    .get(..prefix.len())
+                    .is_some_and(|start| start.eq_ignore_ascii_case(prefix)),
+                None => filename.eq_ignore_ascii_case(pattern),
+            },
+        };
+        name_matches
+            && self
+                .checksum
+                .is_none_or(|checksum| checksum == state.manifest.checksum)
+    }
+}
+
+/// One page of sessions plus the number matching the filter
+#[derive(Debug, Clone)]
+pub struct SessionPage {
+    pub sessions: Vec<SessionState>,
+    pub total: u64,
+}
+
 /// Transfer metrics for calculating speed
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct TransferMetrics {
@@ -41,6 +181,12 @@ pub struct TransferMetrics {
     pub window_start_ms: i64,
     /// Current speed in bytes per second (rolling average)
     pub current_speed_bps: u64,
+    /// Skipped because the receiver already had an identical file
+    #[serde(default)]
+    pub skipped_duplicate: bool,
+    /// Chunks relays reported dropping before delivery
+    #[serde(default)]
+    pub relay_expired_chunks: u32,
 }
 
 impl TransferMetrics {
@@ -53,6 +199,8 @@ impl TransferMetrics {
             bytes_in_window: 0,
             window_start_ms: now,
             current_speed_bps: 0,
+            skipped_duplicate: false,
+            relay_expired_chunks: 0,
         }
     }
 
@@ -86,14 +234,294 @@ impl TransferMetrics {
 
     /// Estimate remaining time in seconds
     pub fn estimated_remaining_secs(&self, remaining_bytes: u64) -> Option<u64> {
-        if self.current_speed_bps > 0 {
-            Some(remaining_bytes / self.current_speed_bps)
+        remaining_bytes.checked_div(self.current_speed_bps)
+    }
+}
+
+/// One point of a transfer's throughput-over-time series
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ProgressSample {
+    /// Unix timestamp millis
+    pub timestamp_ms: i64,
+    /// Bytes sent so far
+    pub bytes_transferred: u64,
+    /// Send rate since the previous sample (bytes/s)
+    pub speed_bps: u64,
+    /// Share of QUIC packets lost since the previous sample
+    pub loss_rate: f64,
+}
+
+/// Most tags one transfer may carry
+pub const MAX_TRANSFER_TAGS: usize = 32;
+
+/// Longest tag key, in bytes
+pub const MAX_TAG_KEY_LEN: usize = 64;
+
+/// Longest tag value, in bytes
+pub const MAX_TAG_VALUE_LEN: usize = 256;
+
+/// Key/value labels on a transfer, e.g. `incident_id` or `originating_unit`
+pub type TransferTags = BTreeMap<String, String>;
+
+/// Check tags against the limits above
+///
+/// Keys are ASCII letters, digits, `_`, `-` and `.`, so they can be used
+/// as listing filters as they are; values may not contain control
+/// characters.
+pub fn validate_tags(tags: &TransferTags) -> Result<(), String> {
+    if tags.len() > MAX_TRANSFER_TAGS {
+        return Err(format!(
+            "{} tags, at most {MAX_TRANSFER_TAGS} allowed",
+            tags.len()
+        ));
+    }
+    for (key, value) in tags {
+        if key.is_empty() || key.len() > MAX_TAG_KEY_LEN {
+            return Err(format!(
+                "tag key {key:?} must be 1 to {MAX_TAG_KEY_LEN} bytes"
+            ));
+        }
+        if !key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+        {
+            return Err(format!(
+                "tag key {key:?} may only contain letters, digits, '_', '-' and '.'"
+            ));
+        }
+        if value.len() > MAX_TAG_VALUE_LEN {
+            return Err(format!(
+                "value of tag {key:?} is over {MAX_TAG_VALUE_LEN} bytes"
+            ));
+        }
+        if value.chars().any(char::is_control) {
+            return Err(format!("value of tag {key:?} contains control characters"));
+        }
+    }
+    Ok(())
+}
+
+/// Per-transfer options chosen by the caller and kept for resume
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransferOptions {
+    /// Local address to send from (pins the transfer to one uplink)
+    #[serde(default)]
+    pub local_bind_addr: Option<SocketAddr>,
+    /// Bytes per data chunk, instead of the coordinator's default
+    #[serde(default)]
+    pub chunk_size: Option<usize>,
+    /// Reed-Solomon shard counts, instead of the coordinator's defaults
+    #[serde(default)]
+    pub data_shards: Option<usize>,
+    #[serde(default)]
+    pub parity_shards: Option<usize>,
+    /// Cap on this transfer's send rate (bytes/s)
+    #[serde(default)]
+    pub rate_limit_bytes_per_sec: Option<u64>,
+    /// Start even if the coordinator's duplicate policy would refuse it
+    #[serde(default)]
+    pub allow_duplicate: bool,
+    /// Caller's labels for the transfer, kept with the session and sent
+    /// with its start, completion and failure events
+    #[serde(default)]
+    pub tags: TransferTags,
+}
+
+impl TransferOptions {
+    /// These options, with anything unset taken from `fallback`
+    pub fn or(self, fallback: TransferOptions) -> Self {
+        Self {
+            local_bind_addr: self.local_bind_addr.or(fallback.local_bind_addr),
+            chunk_size: self.chunk_size.or(fallback.chunk_size),
+            data_shards: self.data_shards.or(fallback.data_shards),
+            parity_shards: self.parity_shards.or(fallback.parity_shards),
+            rate_limit_bytes_per_sec: self
+                .rate_limit_bytes_per_sec
+                .or(fallback.rate_limit_bytes_per_sec),
+            allow_duplicate: self.allow_duplicate || fallback.allow_duplicate,
+            tags: fallback.tags.into_iter().chain(self.tags).collect(),
+        }
+    }
+
+    /// Whether the transfer is split differently from the defaults
+    pub fn overrides_layout(&self) -> bool {
+        self.chunk_size.is_some() || self.data_shards.is_some() || self.parity_shards.is_some()
+    }
+}
+
+/// Named set of transfer settings, referenced when starting a transfer
+///
+/// Settings given with the transfer itself take precedence; anything left
+/// unset in both falls back to the coordinator's defaults.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransferProfile {
+    /// Taken from the URL when a profile is saved by name
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub priority: Option<Priority>,
+    #[serde(default)]
+    pub receiver_addr: Option<SocketAddr>,
+    #[serde(flatten)]
+    pub options: TransferOptions,
+    /// Unix timestamps, set by the store
+    #[serde(default)]
+    pub created_at: i64,
+    #[serde(default)]
+    pub updated_at: i64,
+}
+
+impl TransferProfile {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            description: None,
+            priority: None,
+            receiver_addr: None,
+            options: TransferOptions::default(),
+            created_at: 0,
+            updated_at: 0,
+        }
+    }
+}
+
+/// A benchmark report kept for the dashboard's historical curves
+///
+/// The report is stored as uploaded, so reports from older and newer
+/// benchmark runs can be listed side by side.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BenchmarkRecord {
+    /// Assigned by the store
+    pub id: String,
+    pub label: Option<String>,
+    /// Unix timestamp, set by the store
+    pub created_at: i64,
+    pub report: serde_json::Value,
+}
+
+/// Where the coordinator keeps sessions
+///
+/// Builds without the `sqlite` feature default to [`Memory`](Self::Memory).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SessionBackend {
+    /// `SessionStore`, in a file or in memory; needs the `sqlite` feature
+    Sqlite,
+    /// [`MemoryRepository`](crate::session::MemoryRepository): no database
+    /// is opened, and nothing outlives the process
+    Memory,
+}
+
+impl Default for SessionBackend {
+    fn default() -> Self {
+        if cfg!(feature = "sqlite") {
+            Self::Sqlite
         } else {
-            None
+            Self::Memory
         }
     }
 }
 
+impl std::str::FromStr for SessionBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "sqlite" => Ok(Self::Sqlite),
+            "memory" => Ok(Self::Memory),
+            other => Err(format!(
+                "unknown session backend {other:?} (expected sqlite or memory)"
+            )),
+        }
+    }
+}
+
+/// SQLite journal mode of the session database
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JournalMode {
+    Delete,
+    Truncate,
+    Persist,
+    Memory,
+    /// Readers don't block the writer; the right choice for concurrent updates
+    Wal,
+    Off,
+}
+
+/// How often SQLite syncs the session database to disk
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SynchronousLevel {
+    Off,
+    /// Durable across application crashes; with WAL, safe against corruption
+    /// on power loss too
+    Normal,
+    Full,
+    Extra,
+}
+
+/// Connection settings applied when a [`SessionStore`](crate::session::SessionStore) opens
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SessionStoreOptions {
+    /// Ignored for in-memory databases, which always journal in memory
+    pub journal_mode: JournalMode,
+    pub synchronous: SynchronousLevel,
+    /// How long a connection waits on another's write lock before failing
+    /// with SQLITE_BUSY
+    pub busy_timeout: Duration,
+    pub max_connections: u32,
+}
+
+impl Default for SessionStoreOptions {
+    fn default() -> Self {
+        Self {
+            journal_mode: JournalMode::Wal,
+            synchronous: SynchronousLevel::Normal,
+            busy_timeout: Duration::from_secs(5),
+            max_connections: 8,
+        }
+    }
+}
+
+/// Size of the session database and the rows it holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StorageStats {
+    pub page_size: u64,
+    pub page_count: u64,
+    /// Pages freed by deletes and not yet returned to the file system
+    pub free_pages: u64,
+    /// Bytes in use by the database, free pages included
+    pub db_bytes: u64,
+    /// Bytes on disk, write-ahead log included; `None` in memory
+    pub file_bytes: Option<u64>,
+    /// Whether free pages can be reclaimed without rewriting the file
+    pub incremental_vacuum: bool,
+    pub sessions: u64,
+    pub timeseries: u64,
+    pub profiles: u64,
+    pub benchmarks: u64,
+    pub inbound_transfers: u64,
+    pub inbound_groups: u64,
+}
+
+/// What one maintenance pass over the session database did
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MaintenanceReport {
+    /// Unix time the pass started
+    pub started_at: i64,
+    pub duration_ms: u64,
+    /// The database was rewritten once to enable incremental vacuum
+    pub converted: bool,
+    /// Free pages returned to the file system
+    pub freed_pages: u64,
+    pub reclaimed_bytes: u64,
+    /// Query planner statistics were refreshed
+    pub analyzed: bool,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SessionState {
     pub session_id: String,
@@ -111,6 +539,9 @@ pub struct SessionState {
     /// Transfer metrics for speed calculation
     #[serde(default)]
     pub metrics: TransferMetrics,
+    /// Options the transfer was started with
+    #[serde(default)]
+    pub options: TransferOptions,
 }
 
 impl SessionState {
@@ -128,6 +559,7 @@ impl SessionState {
             receiver_addr: None,
             file_path: None,
             metrics: TransferMetrics::new(),
+            options: TransferOptions::default(),
         }
     }
 
@@ -152,6 +584,7 @@ impl SessionState {
             receiver_addr,
             file_path,
             metrics: TransferMetrics::new(),
+            options: TransferOptions::default(),
         }
     }
 
@@ -167,6 +600,9 @@ impl SessionState {
     }
 
     pub fn progress_percent(&self) -> f32 {
+        if self.metrics.skipped_duplicate {
+            return 100.0;
+        }
         let total = self.manifest.total_chunks as f32;
         if total == 0.0 {
             return 0.0;
@@ -196,6 +632,25 @@ impl SessionState {
         self.failed_chunks.insert(chunk_number);
         self.updated_at = chrono::Utc::now().timestamp();
     }
+
+    /// Record a chunk as acknowledged with `bytes` sent, completing the
+    /// session once enough chunks are in
+    pub fn record_chunk_completed(&mut self, chunk_number: u32, bytes: u64) {
+        self.mark_completed(chunk_number);
+        self.record_bytes_transferred(bytes);
+        if self.is_complete() && !self.status.is_completed() {
+            self.status = SessionStatus::Completed;
+        }
+    }
+
+    /// Take back chunks that never arrived intact; they are failed until a
+    /// resend succeeds
+    pub fn mark_lost(&mut self, chunk_numbers: &[u32]) {
+        for &chunk_number in chunk_numbers {
+            self.completed_chunks.remove(&chunk_number);
+            self.mark_failed(chunk_number);
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -254,3 +709,98 @@ impl SessionSummary {
         }
     }
 }
+
+/// What a receiver holds of a file whose chunks are still arriving
+///
+/// Chunks are tracked in groups of `group_size` consecutive sequence
+/// numbers, one bitmap per group, with bit `n` of group `g` set once chunk
+/// `g * group_size + n` is on disk. Kept in the session store so a receiver
+/// that restarts can tell a sender exactly what it still needs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InboundTransfer {
+    pub transfer_id: String,
+    pub manifest: FileManifest,
+    pub group_size: u32,
+    pub groups: Vec<Vec<u8>>,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+impl InboundTransfer {
+    pub fn new(transfer_id: String, manifest: FileManifest, group_size: u32) -> Self {
+        let group_size = group_size.max(1);
+        let group_count = manifest.total_chunks.div_ceil(group_size);
+        let now = chrono::Utc::now().timestamp();
+        Self {
+            transfer_id,
+            groups: vec![vec![0; (group_size as usize).div_ceil(8)]; group_count as usize],
+            manifest,
+            group_size,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    /// Group holding `sequence_number`, with the chunk's bit within it
+    pub fn position(&self, sequence_number: u32) -> (u32, u32) {
+        (
+            sequence_number / self.group_size,
+            sequence_number % self.group_size,
+        )
+    }
+
+    /// Record a chunk as on disk; false if it already was or is out of range
+    pub fn mark_received(&mut self, sequence_number: u32) -> bool {
+        if sequence_number >= self.manifest.total_chunks || self.is_received(sequence_number) {
+            return false;
+        }
+        let (group, bit) = self.position(sequence_number);
+        self.groups[group as usize][bit as usize / 8] |= 1 << (bit % 8);
+        self.updated_at = chrono::Utc::now().timestamp();
+        true
+    }
+
+    pub fn is_received(&self, sequence_number: u32) -> bool {
+        let (group, bit) = self.position(sequence_number);
+        self.groups
+            .get(group as usize)
+            .and_then(|bitmap| bitmap.get(bit as usize / 8))
+            .is_some_and(|byte| byte & (1 << (bit % 8)) != 0)
+    }
+
+    pub fn received_sequences(&self) -> Vec<u32> {
+        (0..self.manifest.total_chunks)
+            .filter(|&seq| self.is_received(seq))
+            .collect()
+    }
+
+    pub fn missing_sequences(&self) -> Vec<u32> {
+        (0..self.manifest.total_chunks)
+            .filter(|&seq| !self.is_received(seq))
+            .collect()
+    }
+
+    pub fn received(&self) -> u32 {
+        self.groups
+            .iter()
+            .flatten()
+            .map(|byte| byte.count_ones())
+            .sum()
+    }
+
+    /// Groups with every chunk on disk
+    pub fn complete_groups(&self) -> u32 {
+        (0..self.groups.len() as u32)
+            .filter(|&group| {
+                let start = group * self.group_size;
+                let end = (start + self.group_size).min(self.manifest.total_chunks);
+                (start..end).all(|seq| self.is_received(seq))
+            })
+            .count() as u32
+    }
+
+    /// Further chunks needed before the file can be decoded
+    pub fn shortfall(&self) -> u32 {
+        self.manifest.data_chunks.saturating_sub(self.received())
+    }
+}
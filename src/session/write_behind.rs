@@ -0,0 +1,528 @@
+//! Chunk progress held in memory and written out behind the transfer
+//!
+//! Each chunk a transfer settles updates its session, which against SQLite
+//! is a read and a write in the transfer loop. [`WriteBehindRepository`]
+//! keeps running sessions in memory instead: chunk updates change the
+//! cached copy and return, and a background task writes changed sessions
+//! out at least every `max_lag`, sooner once `max_dirty` of them wait.
+//!
+//! Saves and status changes, a pause included, write the cached progress
+//! and then themselves straight through, and drop the session from the
+//! cache. Queries and searches flush first, so they never see stale rows.
+//! A crash loses at most `max_lag` of chunk progress, which a resume sends
+//! again.
+
+use super::error::{SessionError, SessionResult};
+use super::repository::SessionRepository;
+use super::types::{
+    BenchmarkRecord, MaintenanceReport, ProgressSample, ResumeInfo, SessionPage, SessionQuery,
+    SessionSearch, SessionState, SessionStatus, StorageStats, TransferProfile,
+};
+use futures::future::BoxFuture;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+
+/// How far the stored sessions may fall behind the cached ones
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WriteBehindPolicy {
+    /// Longest a chunk update waits to be written
+    pub max_lag: Duration,
+    /// Changed sessions that trigger a flush before `max_lag` is up
+    pub max_dirty: usize,
+}
+
+impl Default for WriteBehindPolicy {
+    fn default() -> Self {
+        Self {
+            max_lag: Duration::from_secs(1),
+            max_dirty: 256,
+        }
+    }
+}
+
+struct Cached {
+    state: SessionState,
+    /// Changed since it was last written
+    dirty: bool,
+}
+
+struct Shared<R> {
+    inner: R,
+    policy: WriteBehindPolicy,
+    sessions: Mutex<HashMap<String, Cached>>,
+    /// Held while writing to `inner`, so an older copy of a session never
+    /// lands after a newer one
+    writes: tokio::sync::Mutex<()>,
+    wake: Notify,
+}
+
+impl<R: SessionRepository> Shared<R> {
+    /// Write every changed session, keeping those that failed for next time
+    async fn flush(&self) -> SessionResult<()> {
+        let _writes = self.writes.lock().await;
+        let pending: Vec<SessionState> = self
+            .sessions
+            .lock()
+            .values_mut()
+            .filter(|cached| cached.dirty)
+            .map(|cached| {
+                cached.dirty = false;
+                cached.state.clone()
+            })
+            .collect();
+
+        let mut result = Ok(());
+        for state in pending {
+            if let Err(e) = self.inner.save(&state).await {
+                if let Some(cached) = self.sessions.lock().get_mut(&state.session_id) {
+                    cached.dirty = true;
+                }
+                result = Err(e);
+            }
+        }
+        result
+    }
+
+    /// Take `session_id` out of the cache, writing its progress if changed,
+    /// then run `write`
+    async fn write_through<T>(
+        &self,
+        session_id: &str,
+        write: BoxFuture<'_, SessionResult<T>>,
+    ) -> SessionResult<T> {
+        let _writes = self.writes.lock().await;
+        let cached = self.sessions.lock().remove(session_id);
+        if let Some(cached) = cached.filter(|cached| cached.dirty) {
+            if let Err(e) = self.inner.save(&cached.state).await {
+                self.sessions
+                    .lock()
+                    .entry(session_id.to_string())
+                    .or_insert(cached);
+                return Err(e);
+            }
+        }
+        write.await
+    }
+
+    async fn run(&self) {
+        loop {
+            tokio::select! {
+                _ = tokio::time::sleep(self.policy.max_lag) => {}
+                _ = self.wake.notified() => {}
+            }
+            if let Err(e) = self.flush().await {
+                tracing::warn!(error = %e, "Session write-behind flush failed, retrying");
+            }
+        }
+    }
+}
+
+/// Session storage that caches chunk progress and writes it out in the
+/// background
+///
+/// Must be created inside a Tokio runtime.
+pub struct WriteBehindRepository<R> {
+    shared: Arc<Shared<R>>,
+    flusher: JoinHandle<()>,
+}
+
+impl<R: SessionRepository + 'static> WriteBehindRepository<R> {
+    pub fn new(inner: R, policy: WriteBehindPolicy) -> Self {
+        let shared = Arc::new(Shared {
+            inner,
+            policy,
+            sessions: Mutex::new(HashMap::new()),
+            writes: tokio::sync::Mutex::new(()),
+            wake: Notify::new(),
+        });
+        let flusher = tokio::spawn({
+            let shared = shared.clone();
+            async move { shared.run().await }
+        });
+        Self { shared, flusher }
+    }
+}
+
+impl<R> WriteBehindRepository<R> {
+    pub fn inner(&self) -> &R {
+        &self.shared.inner
+    }
+
+    pub fn policy(&self) -> WriteBehindPolicy {
+        self.shared.policy
+    }
+
+    /// Sessions changed since they were last written
+    pub fn dirty(&self) -> usize {
+        self.shared
+            .sessions
+            .lock()
+            .values()
+            .filter(|cached| cached.dirty)
+            .count()
+    }
+}
+
+impl<R: SessionRepository> WriteBehindRepository<R> {
+    /// Apply `change` to the cached session, loading it on first use
+    fn update<'a>(
+        &'a self,
+        session_id: &'a str,
+        change: impl FnOnce(&mut SessionState) + Send + 'a,
+    ) -> BoxFuture<'a, SessionResult<()>> {
+        Box::pin(async move {
+            let mut change = Some(change);
+            loop {
+                {
+                    let mut sessions = self.shared.sessions.lock();
+                    if let Some(cached) = sessions.get_mut(session_id) {
+                        if let Some(change) = change.take() {
+                            change(&mut cached.state);
+                        }
+                        cached.dirty = true;
+                        let dirty = sessions.values().filter(|cached| cached.dirty).count();
+                        if dirty >= self.shared.policy.max_dirty {
+                            self.shared.wake.notify_one();
+                        }
+                        return Ok(());
+                    }
+                }
+                // Not while a write-through is storing what was cached
+                let _writes = self.shared.writes.lock().await;
+                if self.shared.sessions.lock().contains_key(session_id) {
+                    continue;
+                }
+                let state = self
+                    .shared
+                    .inner
+                    .load(session_id)
+                    .await?
+                    .ok_or_else(|| SessionError::NotFound(session_id.to_string()))?;
+                self.shared
+                    .sessions
+                    .lock()
+                    .entry(session_id.to_string())
+                    .or_insert(Cached {
+                        state,
+                        dirty: false,
+                    });
+            }
+        })
+    }
+
+    fn cached(&self, session_id: &str) -> Option<SessionState> {
+        self.shared
+            .sessions
+            .lock()
+            .get(session_id)
+            .map(|cached| cached.state.clone())
+    }
+}
+
+impl<R> Drop for WriteBehindRepository<R> {
+    fn drop(&mut self) {
+        self.flusher.abort();
+    }
+}
+
+impl<R: SessionRepository + 'static> SessionRepository for WriteBehindRepository<R> {
+    fn save<'a>(&'a self, state: &'a SessionState) -> BoxFuture<'a, SessionResult<()>> {
+        Box::pin(async move {
+            let _writes = self.shared.writes.lock().await;
+            self.shared.sessions.lock().remove(&state.session_id);
+            self.shared.inner.save(state).await
+        })
+    }
+
+    fn load<'a>(
+        &'a self,
+        session_id: &'a str,
+    ) -> BoxFuture<'a, SessionResult<Option<SessionState>>> {
+        match self.cached(session_id) {
+            Some(state) => Box::pin(async { Ok(Some(state)) }),
+            None => self.shared.inner.load(session_id),
+        }
+    }
+
+    fn mark_chunk_completed_with_bytes<'a>(
+        &'a self,
+        session_id: &'a str,
+        chunk_number: u32,
+        bytes_transferred: u64,
+    ) -> BoxFuture<'a, SessionResult<()>> {
+        self.update(session_id, move |state| {
+            state.record_chunk_completed(chunk_number, bytes_transferred)
+        })
+    }
+
+    fn mark_skipped_duplicate<'a>(
+        &'a self,
+        session_id: &'a str,
+    ) -> BoxFuture<'a, SessionResult<()>> {
+        Box::pin(self.shared.write_through(
+            session_id,
+            self.shared.inner.mark_skipped_duplicate(session_id),
+        ))
+    }
+
+    fn mark_chunk_failed<'a>(
+        &'a self,
+        session_id: &'a str,
+        chunk_number: u32,
+    ) -> BoxFuture<'a, SessionResult<()>> {
+        self.update(session_id, move |state| state.mark_failed(chunk_number))
+    }
+
+    fn mark_chunk_nacked<'a>(
+        &'a self,
+        session_id: &'a str,
+        chunk_number: u32,
+    ) -> BoxFuture<'a, SessionResult<()>> {
+        self.update(session_id, move |state| state.mark_lost(&[chunk_number]))
+    }
+
+    fn mark_chunks_lost<'a>(
+        &'a self,
+        session_id: &'a str,
+        chunk_numbers: &'a [u32],
+    ) -> BoxFuture<'a, SessionResult<()>> {
+        self.update(session_id, move |state| state.mark_lost(chunk_numbers))
+    }
+
+    fn update_status<'a>(
+        &'a self,
+        session_id: &'a str,
+        status: SessionStatus,
+    ) -> BoxFuture<'a, SessionResult<()>> {
+        Box::pin(self.shared.write_through(
+            session_id,
+            self.shared.inner.update_status(session_id, status),
+        ))
+    }
+
+    fn get_resume_info<'a>(
+        &'a self,
+        session_id: &'a str,
+    ) -> BoxFuture<'a, SessionResult<ResumeInfo>> {
+        match self.cached(session_id) {
+            Some(state) => Box::pin(async move { Ok(ResumeInfo::from_state(&state)) }),
+            None => self.shared.inner.get_resume_info(session_id),
+        }
+    }
+
+    fn query<'a>(&'a self, query: &'a SessionQuery) -> BoxFuture<'a, SessionResult<SessionPage>> {
+        Box::pin(async move {
+            self.shared.flush().await?;
+            self.shared.inner.query(query).await
+        })
+    }
+
+    fn search<'a>(
+        &'a self,
+        search: &'a SessionSearch,
+    ) -> BoxFuture<'a, SessionResult<Vec<SessionState>>> {
+        Box::pin(async move {
+            self.shared.flush().await?;
+            self.shared.inner.search(search).await
+        })
+    }
+
+    fn save_timeseries<'a>(
+        &'a self,
+        session_id: &'a str,
+        samples: &'a [ProgressSample],
+    ) -> BoxFuture<'a, SessionResult<()>> {
+        self.shared.inner.save_timeseries(session_id, samples)
+    }
+
+    fn load_timeseries<'a>(
+        &'a self,
+        session_id: &'a str,
+    ) -> BoxFuture<'a, SessionResult<Vec<ProgressSample>>> {
+        self.shared.inner.load_timeseries(session_id)
+    }
+
+    fn save_profile<'a>(
+        &'a self,
+        profile: &'a TransferProfile,
+    ) -> BoxFuture<'a, SessionResult<TransferProfile>> {
+        self.shared.inner.save_profile(profile)
+    }
+
+    fn load_profile<'a>(
+        &'a self,
+        name: &'a str,
+    ) -> BoxFuture<'a, SessionResult<Option<TransferProfile>>> {
+        self.shared.inner.load_profile(name)
+    }
+
+    fn list_profiles(&self) -> BoxFuture<'_, SessionResult<Vec<TransferProfile>>> {
+        self.shared.inner.list_profiles()
+    }
+
+    fn delete_profile<'a>(&'a self, name: &'a str) -> BoxFuture<'a, SessionResult<bool>> {
+        self.shared.inner.delete_profile(name)
+    }
+
+    fn save_benchmark<'a>(
+        &'a self,
+        label: Option<&'a str>,
+        report: &'a serde_json::Value,
+    ) -> BoxFuture<'a, SessionResult<BenchmarkRecord>> {
+        self.shared.inner.save_benchmark(label, report)
+    }
+
+    fn load_benchmark<'a>(
+        &'a self,
+        id: &'a str,
+    ) -> BoxFuture<'a, SessionResult<Option<BenchmarkRecord>>> {
+        self.shared.inner.load_benchmark(id)
+    }
+
+    fn list_benchmarks(&self, limit: u32) -> BoxFuture<'_, SessionResult<Vec<BenchmarkRecord>>> {
+        self.shared.inner.list_benchmarks(limit)
+    }
+
+    fn delete_benchmark<'a>(&'a self, id: &'a str) -> BoxFuture<'a, SessionResult<bool>> {
+        self.shared.inner.delete_benchmark(id)
+    }
+
+    fn ping(&self) -> BoxFuture<'_, SessionResult<()>> {
+        self.shared.inner.ping()
+    }
+
+    fn storage_stats(&self) -> BoxFuture<'_, SessionResult<Option<StorageStats>>> {
+        self.shared.inner.storage_stats()
+    }
+
+    fn maintain(
+        &self,
+        vacuum_pages: u32,
+        analyze: bool,
+    ) -> BoxFuture<'_, SessionResult<Option<MaintenanceReport>>> {
+        self.shared.inner.maintain(vacuum_pages, analyze)
+    }
+
+    fn flush(&self) -> BoxFuture<'_, SessionResult<()>> {
+        Box::pin(self.shared.flush())
+    }
+
+    fn close(&self) -> BoxFuture<'_, ()> {
+        Box::pin(async move {
+            if let Err(e) = self.shared.flush().await {
+                tracing::warn!(error = %e, "Session progress lost on close");
+            }
+            self.flusher.abort();
+            self.shared.inner.close().await
+        })
+    }
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+    use super::*;
+    use crate::chunk::{FileManifest, Priority};
+    use crate::session::SessionStore;
+
+    fn manifest() -> FileManifest {
+        FileManifest {
+            file_id: "survey.bin".into(),
+            filename: "survey.bin".into(),
+            total_size: 4096,
+            chunk_size: 1024,
+            total_chunks: 6,
+            data_chunks: 4,
+            parity_chunks: 2,
+            priority: Priority::Normal,
+            checksum: [0u8; 32],
+            zero_runs: Vec::new(),
+            attributes: None,
+            checksum_algorithm: Default::default(),
+            erasure_profile: Default::default(),
+            schedule: None,
+            merkle_root: None,
+            compression: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_chunk_updates_written_behind_and_on_status_change() {
+        let store = WriteBehindRepository::new(
+            SessionStore::new_in_memory().await.unwrap(),
+            WriteBehindPolicy {
+                max_lag: Duration::from_secs(3600),
+                max_dirty: 1000,
+            },
+        );
+        let state = SessionState::new("s1".into(), "survey.bin".into(), manifest());
+        store.save(&state).await.unwrap();
+
+        store
+            .mark_chunk_completed_with_bytes("s1", 0, 1024)
+            .await
+            .unwrap();
+        store.mark_chunk_failed("s1", 1).await.unwrap();
+        assert_eq!(store.dirty(), 1);
+
+        // Reads see the cache; the database is behind
+        let cached = store.load("s1").await.unwrap().unwrap();
+        assert!(cached.completed_chunks.contains(&0));
+        assert!(cached.failed_chunks.contains(&1));
+        let stored = store.inner().load("s1").await.unwrap().unwrap();
+        assert!(stored.completed_chunks.is_empty());
+
+        // A pause writes the progress, then the status
+        store
+            .update_status("s1", SessionStatus::Paused)
+            .await
+            .unwrap();
+        assert_eq!(store.dirty(), 0);
+        let stored = store.inner().load("s1").await.unwrap().unwrap();
+        assert!(stored.completed_chunks.contains(&0));
+        assert!(stored.failed_chunks.contains(&1));
+        assert_eq!(stored.status, SessionStatus::Paused);
+
+        // Queries flush first
+        store
+            .mark_chunk_completed_with_bytes("s1", 2, 1024)
+            .await
+            .unwrap();
+        let page = store.query(&SessionQuery::default()).await.unwrap();
+        assert_eq!(page.sessions[0].completed_chunks.len(), 2);
+        assert!(matches!(
+            store.mark_chunk_failed("missing", 0).await,
+            Err(SessionError::NotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_flusher_writes_within_max_lag() {
+        let store = WriteBehindRepository::new(
+            SessionStore::new_in_memory().await.unwrap(),
+            WriteBehindPolicy {
+                max_lag: Duration::from_millis(20),
+                ..Default::default()
+            },
+        );
+        let state = SessionState::new("s1".into(), "survey.bin".into(), manifest());
+        store.save(&state).await.unwrap();
+        store
+            .mark_chunk_completed_with_bytes("s1", 3, 1024)
+            .await
+            .unwrap();
+
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while store.dirty() > 0 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        let stored = store.inner().load("s1").await.unwrap().unwrap();
+        assert!(stored.completed_chunks.contains(&3));
+    }
+}
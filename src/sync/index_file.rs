@@ -0,0 +1,97 @@
+//! JSON index files kept beside received files
+//!
+//! The repair index, the quarantine's stages and the retention index are
+//! each a small JSON file rewritten whole on every change. [`IndexFile`]
+//! writes them off the async runtime and durably: the new contents go to a
+//! temporary file that is fsynced before being renamed over the old one,
+//! and the directory is fsynced after, so a crash leaves either the old
+//! index or the new one.
+
+use serde::Serialize;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// One index file, saved from snapshots of its owner's state
+#[derive(Debug)]
+pub(crate) struct IndexFile {
+    path: PathBuf,
+    // Held while a snapshot is taken and written, so a later snapshot is
+    // never overwritten by an earlier one
+    saving: tokio::sync::Mutex<()>,
+}
+
+impl IndexFile {
+    pub(crate) fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            saving: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    /// Write what `snapshot` returns, taken once earlier saves have landed
+    pub(crate) async fn save<T: Serialize>(
+        &self,
+        snapshot: impl FnOnce() -> T,
+    ) -> std::io::Result<()> {
+        let _saving = self.saving.lock().await;
+        let data = serde_json::to_vec_pretty(&snapshot()).map_err(std::io::Error::other)?;
+        let path = self.path.clone();
+        tokio::task::spawn_blocking(move || write_atomic(&path, &data))
+            .await
+            .map_err(std::io::Error::other)?
+    }
+}
+
+/// Replace the file at `path` with `data`
+fn write_atomic(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    let tmp = path.with_extension("tmp");
+    let mut file = std::fs::File::create(&tmp)?;
+    file.write_all(data)?;
+    file.sync_all()?;
+    drop(file);
+    std::fs::rename(&tmp, path)?;
+
+    // The rename only survives a crash once the directory is on disk
+    #[cfg(unix)]
+    {
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        std::fs::File::open(dir)?.sync_all()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_saves_land_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".index.json");
+        let index = Arc::new(IndexFile::new(&path));
+        let state = Arc::new(parking_lot::Mutex::new(Vec::new()));
+
+        let saves: Vec<_> = (0..20)
+            .map(|i| {
+                let index = index.clone();
+                let state = state.clone();
+                tokio::spawn(async move {
+                    state.lock().push(i);
+                    index.save(|| state.lock().clone()).await.unwrap();
+                })
+            })
+            .collect();
+        for save in saves {
+            save.await.unwrap();
+        }
+
+        let saved: Vec<u32> = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        assert_eq!(saved.len(), 20);
+        assert_eq!(saved, *state.lock());
+        assert!(!dir.path().join(".index.tmp").exists());
+    }
+}
@@ -1,12 +1,26 @@
 //! Delta synchronization module
 //!
 //! Provides rsync-style delta transfer capabilities using rolling checksums
-//! and strong hashes for efficient block-level file synchronization.
+//! and strong hashes for efficient block-level file synchronization, and
+//! uses them to repair delivered files that have since been damaged.
+//! Delivered files are also aged out under a retention policy.
 
 pub mod delta;
+mod index_file;
+pub mod repair;
+pub mod retention;
 pub mod rolling_hash;
 pub mod signature;
 
-pub use delta::{DeltaBuilder, DeltaInstruction, DeltaPatch};
+pub use delta::{DeltaBuilder, DeltaError, DeltaInstruction, DeltaPatch};
+pub(crate) use index_file::IndexFile;
+pub use repair::{
+    FileCheck, FileRepairer, RepairError, RepairIndex, RepairResult, ScrubReport, StoredFile,
+    REPAIR_INDEX_FILE,
+};
+pub use retention::{
+    FileRetentionPolicy, RetainedFile, RetentionCandidate, RetentionCleaner, RetentionError,
+    RetentionEvent, RetentionReason, RetentionReport, RetentionResult, RETENTION_INDEX_FILE,
+};
 pub use rolling_hash::{Adler32Rolling, RollingHash};
 pub use signature::{BlockSignature, FileSignature, SignatureBuilder};
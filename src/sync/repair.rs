@@ -0,0 +1,510 @@
+//! Anti-entropy repair of delivered files
+//!
+//! Files sit on a receiver long after their transfer, and disks can corrupt
+//! them without anyone reading them. [`RepairIndex`] remembers every
+//! verified delivery: where the file is, its checksum and who sent it.
+//! [`FileRepairer`] re-hashes those files periodically; for one that no
+//! longer matches it sends the sender a signature of the damaged copy,
+//! blocked by the transfer's chunk size. The sender answers with a delta
+//! patch carrying only the chunks that differ, which is applied to a copy
+//! and swapped in once the result has the delivered checksum.
+//!
+//! Senders answer with
+//! [`TransferCoordinator::serve_repairs`](crate::coordinator::TransferCoordinator::serve_repairs).
+
+use crate::integrity::{ChecksumType, IntegrityVerifier};
+use crate::metrics::recorder;
+use crate::network::{Capabilities, NetworkError, QuicTransport, RepairReply, RepairRequest};
+use crate::sync::{DeltaError, DeltaPatch, IndexFile, SignatureBuilder};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::task::JoinHandle;
+
+/// File name of the index under a receiver's save directory
+pub const REPAIR_INDEX_FILE: &str = ".repair-index.json";
+
+#[derive(Error, Debug)]
+pub enum RepairError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Network error: {0}")]
+    Network(#[from] NetworkError),
+
+    #[error("Patch failed: {0}")]
+    Delta(#[from] DeltaError),
+
+    #[error("Sender cannot repair {file_id}: {reason}")]
+    Unavailable { file_id: String, reason: String },
+
+    #[error("Patch for {0} doesn't rebuild the delivered file")]
+    WrongTarget(String),
+
+    #[error("Repair index {path}: {reason}")]
+    Index { path: PathBuf, reason: String },
+}
+
+pub type RepairResult<T> = Result<T, RepairError>;
+
+/// A delivered file kept in the [`RepairIndex`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StoredFile {
+    pub path: PathBuf,
+    /// The sender's id for the file, sent back with repair requests
+    pub file_id: String,
+    /// Checksum the file was delivered with, hashed with `checksum_algorithm`
+    pub checksum: [u8; 32],
+    /// The transfer's checksum algorithm; Blake3 for older index entries
+    #[serde(default)]
+    pub checksum_algorithm: ChecksumType,
+    /// Sender's QUIC address
+    pub source: SocketAddr,
+    /// Signature block size; the transfer's chunk size
+    pub block_size: usize,
+    /// Unix time the file last checked out intact
+    pub verified_at: i64,
+}
+
+/// Delivered files to re-verify, kept as JSON when opened from a path
+#[derive(Debug)]
+pub struct RepairIndex {
+    index: Option<IndexFile>,
+    files: Mutex<BTreeMap<PathBuf, StoredFile>>,
+}
+
+impl RepairIndex {
+    /// Load the index at `path`, or start an empty one there
+    pub fn open(path: impl Into<PathBuf>) -> RepairResult<Self> {
+        let path = path.into();
+        let files = if path.exists() {
+            let data = std::fs::read(&path)?;
+            let list: Vec<StoredFile> =
+                serde_json::from_slice(&data).map_err(|e| RepairError::Index {
+                    path: path.clone(),
+                    reason: e.to_string(),
+                })?;
+            list.into_iter().map(|f| (f.path.clone(), f)).collect()
+        } else {
+            BTreeMap::new()
+        };
+        Ok(Self {
+            index: Some(IndexFile::new(path)),
+            files: Mutex::new(files),
+        })
+    }
+
+    /// An index that isn't saved
+    pub fn in_memory() -> Self {
+        Self {
+            index: None,
+            files: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Add a delivered file, replacing any earlier record for its path
+    pub async fn record(&self, file: StoredFile) -> RepairResult<()> {
+        self.files.lock().insert(file.path.clone(), file);
+        self.save().await
+    }
+
+    /// Stop tracking `path`; false if it wasn't tracked
+    pub async fn remove(&self, path: &Path) -> RepairResult<bool> {
+        let removed = self.files.lock().remove(path).is_some();
+        if removed {
+            self.save().await?;
+        }
+        Ok(removed)
+    }
+
+    pub fn files(&self) -> Vec<StoredFile> {
+        self.files.lock().values().cloned().collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.lock().is_empty()
+    }
+
+    async fn mark_verified(&self, path: &Path) -> RepairResult<()> {
+        if let Some(file) = self.files.lock().get_mut(path) {
+            file.verified_at = chrono::Utc::now().timestamp();
+        }
+        self.save().await
+    }
+
+    async fn save(&self) -> RepairResult<()> {
+        let Some(index) = &self.index else {
+            return Ok(());
+        };
+        index
+            .save(|| self.files.lock().values().cloned().collect::<Vec<_>>())
+            .await?;
+        Ok(())
+    }
+}
+
+/// What checking one file found
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileCheck {
+    Intact,
+    /// Damaged and patched; `fetched_bytes` came from the sender
+    Repaired {
+        fetched_bytes: u64,
+    },
+    /// Deleted since delivery; dropped from the index
+    Removed,
+}
+
+/// Totals from one pass over the index
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ScrubReport {
+    pub checked: usize,
+    pub intact: usize,
+    pub repaired: usize,
+    /// Bytes fetched from senders across all repairs
+    pub fetched_bytes: u64,
+    pub removed: usize,
+    /// Files found damaged that couldn't be repaired, and why
+    pub failed: Vec<(PathBuf, String)>,
+}
+
+/// Re-verifies delivered files and patches damaged ones from their senders
+pub struct FileRepairer {
+    index: Arc<RepairIndex>,
+    transport: Arc<QuicTransport>,
+}
+
+impl FileRepairer {
+    pub fn new(index: Arc<RepairIndex>, transport: Arc<QuicTransport>) -> Self {
+        Self { index, transport }
+    }
+
+    /// Check every indexed file once, repairing those that fail
+    pub async fn scrub(&self) -> ScrubReport {
+        let mut report = ScrubReport::default();
+        for file in self.index.files() {
+            report.checked += 1;
+            match self.check(&file).await {
+                Ok(FileCheck::Intact) => report.intact += 1,
+                Ok(FileCheck::Repaired { fetched_bytes }) => {
+                    report.repaired += 1;
+                    report.fetched_bytes += fetched_bytes;
+                }
+                Ok(FileCheck::Removed) => report.removed += 1,
+                Err(e) => {
+                    tracing::warn!("Could not repair {}: {}", file.path.display(), e);
+                    recorder::record_file_repair("failed", 0);
+                    report.failed.push((file.path.clone(), e.to_string()));
+                }
+            }
+        }
+        report
+    }
+
+    /// Verify one file, repairing it if its checksum no longer matches
+    pub async fn check(&self, file: &StoredFile) -> RepairResult<FileCheck> {
+        if !file.path.exists() {
+            self.index.remove(&file.path).await?;
+            return Ok(FileCheck::Removed);
+        }
+        let checksum =
+            IntegrityVerifier::calculate_file_checksum_with(&file.path, file.checksum_algorithm)
+                .await
+                .map_err(|e| std::io::Error::other(e.to_string()))?;
+        if checksum == file.checksum {
+            self.index.mark_verified(&file.path).await?;
+            return Ok(FileCheck::Intact);
+        }
+
+        tracing::warn!(
+            "{} no longer matches its checksum, asking {} for a patch",
+            file.path.display(),
+            file.source
+        );
+        let fetched_bytes = self.repair(file).await?;
+        self.index.mark_verified(&file.path).await?;
+        recorder::record_file_repair("repaired", fetched_bytes);
+        Ok(FileCheck::Repaired { fetched_bytes })
+    }
+
+    /// Fetch the chunks of `file` that differ from what was delivered and
+    /// patch them in, returning the bytes fetched
+    pub async fn repair(&self, file: &StoredFile) -> RepairResult<u64> {
+        let path = file.path.clone();
+        let block_size = file.block_size.max(1);
+        let signature = tokio::task::spawn_blocking(move || {
+            SignatureBuilder::new()
+                .block_size(block_size)
+                .build_from_reader(&mut std::fs::File::open(path)?)
+        })
+        .await
+        .map_err(std::io::Error::other)??;
+
+        let request = RepairRequest {
+            file_id: file.file_id.clone(),
+            checksum: file.checksum,
+            checksum_algorithm: file.checksum_algorithm,
+            signature,
+            capabilities: Capabilities::local(),
+        };
+        let conn = self.transport.connect(file.source).await?;
+        let reply = self.transport.request_repair(&conn, &request).await;
+        conn.close(0u32.into(), b"repair done");
+        let patch = match reply? {
+            RepairReply::Patch(patch) => patch,
+            RepairReply::Unavailable(reason) => {
+                return Err(RepairError::Unavailable {
+                    file_id: file.file_id.clone(),
+                    reason,
+                })
+            }
+        };
+
+        let fetched = patch.stats().inserted_bytes;
+        let file = file.clone();
+        tokio::task::spawn_blocking(move || apply_in_place(&patch, &file))
+            .await
+            .map_err(std::io::Error::other)??;
+        Ok(fetched)
+    }
+
+    /// Scrub the index every `interval` until the task is aborted
+    pub fn spawn(self, interval: Duration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut tick = tokio::time::interval(interval);
+            tick.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            // The first tick is immediate; files were just verified on delivery
+            tick.tick().await;
+            loop {
+                tick.tick().await;
+                let report = self.scrub().await;
+                if report.repaired > 0 || !report.failed.is_empty() {
+                    tracing::info!(
+                        "Repair pass: {} checked, {} repaired ({} bytes fetched), {} failed",
+                        report.checked,
+                        report.repaired,
+                        report.fetched_bytes,
+                        report.failed.len()
+                    );
+                }
+            }
+        })
+    }
+}
+
+/// Apply `patch` to `file`, writing beside it and swapping the result in
+/// only once it has the delivered checksum
+fn apply_in_place(patch: &DeltaPatch, file: &StoredFile) -> RepairResult<()> {
+    let path = &file.path;
+    let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(".repair");
+    let tmp = path.with_file_name(tmp_name);
+
+    let applied = (|| {
+        let mut source = std::fs::File::open(path)?;
+        let mut target = std::io::BufWriter::new(std::fs::File::create(&tmp)?);
+        patch.apply_streaming(&mut source, &mut target)?;
+        let target = target.into_inner().map_err(|e| e.into_error())?;
+        target.sync_all()?;
+
+        // The patch proves itself against a Blake3 hash; the file must still
+        // match the checksum it was delivered with
+        let mut hasher = file.checksum_algorithm.hasher();
+        let mut rebuilt = std::fs::File::open(&tmp)?;
+        let mut buffer = vec![0u8; 64 * 1024];
+        loop {
+            let n = std::io::Read::read(&mut rebuilt, &mut buffer)?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buffer[..n]);
+        }
+        if hasher.finalize() != file.checksum {
+            return Err(RepairError::WrongTarget(file.file_id.clone()));
+        }
+        Ok::<_, RepairError>(())
+    })();
+    if let Err(e) = applied {
+        let _ = std::fs::remove_file(&tmp);
+        return Err(e);
+    }
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+    use super::*;
+    use crate::chunk::ChunkManager;
+    use crate::coordinator::TransferCoordinator;
+    use crate::network::ConnectionConfig;
+    use crate::priority::PriorityQueue;
+    use crate::session::SessionStore;
+
+    async fn transport() -> Arc<QuicTransport> {
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        Arc::new(
+            QuicTransport::new(ConnectionConfig {
+                bind_addr: "127.0.0.1:0".parse().unwrap(),
+                ..Default::default()
+            })
+            .await
+            .unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_index_round_trips() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(REPAIR_INDEX_FILE);
+        let index = RepairIndex::open(&path).unwrap();
+        let file = StoredFile {
+            path: dir.path().join("a.bin"),
+            file_id: "/data/a.bin".into(),
+            checksum: [7; 32],
+            checksum_algorithm: ChecksumType::Sha256,
+            source: "10.0.0.2:5000".parse().unwrap(),
+            block_size: 4096,
+            verified_at: 0,
+        };
+        index.record(file.clone()).await.unwrap();
+
+        let reopened = RepairIndex::open(&path).unwrap();
+        assert_eq!(reopened.files(), vec![file.clone()]);
+        assert!(reopened.remove(&file.path).await.unwrap());
+        assert!(!reopened.remove(&file.path).await.unwrap());
+        assert!(RepairIndex::open(&path).unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_damaged_file_is_patched_from_sender() {
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        let dir = tempfile::tempdir().unwrap();
+        let block_size = 16 * 1024;
+        let original: Vec<u8> = (0..40 * block_size as u32)
+            .map(|i| (i.wrapping_mul(2_654_435_761) >> 24) as u8)
+            .collect();
+        let sent = dir.path().join("sent.bin");
+        std::fs::write(&sent, &original).unwrap();
+
+        let sender = TransferCoordinator::new(
+            ChunkManager::new(block_size, 4, 2).unwrap(),
+            QuicTransport::new(ConnectionConfig {
+                bind_addr: "127.0.0.1:0".parse().unwrap(),
+                ..Default::default()
+            })
+            .await
+            .unwrap(),
+            PriorityQueue::new(1000),
+            SessionStore::new_in_memory().await.unwrap(),
+        );
+        let serving = sender.receive().serve_repairs();
+
+        // Two chunks of the delivered copy rot on disk
+        let delivered = dir.path().join("received.bin");
+        let mut damaged = original.clone();
+        damaged[3 * block_size + 100] ^= 0xFF;
+        damaged[30 * block_size..30 * block_size + 10].fill(0);
+        std::fs::write(&delivered, &damaged).unwrap();
+
+        let index = Arc::new(RepairIndex::in_memory());
+        let file = StoredFile {
+            path: delivered.clone(),
+            file_id: sent.to_string_lossy().to_string(),
+            checksum: IntegrityVerifier::calculate_checksum(&original),
+            checksum_algorithm: ChecksumType::Blake3,
+            source: sender.transport().local_addr().unwrap(),
+            block_size,
+            verified_at: 0,
+        };
+        index.record(file.clone()).await.unwrap();
+        let repairer = FileRepairer::new(index.clone(), transport().await);
+
+        let report = repairer.scrub().await;
+        assert_eq!(report.repaired, 1, "{report:?}");
+        // Only the damaged chunks came over the wire
+        assert_eq!(report.fetched_bytes, 2 * block_size as u64);
+        assert_eq!(std::fs::read(&delivered).unwrap(), original);
+        assert!(index.files()[0].verified_at > 0);
+
+        let report = repairer.scrub().await;
+        assert_eq!((report.intact, report.repaired), (1, 0));
+
+        // A sender whose copy changed refuses rather than sending other data
+        std::fs::write(&delivered, &damaged).unwrap();
+        std::fs::write(&sent, b"something else").unwrap();
+        assert!(matches!(
+            repairer.check(&file).await,
+            Err(RepairError::Unavailable { .. })
+        ));
+        assert_eq!(std::fs::read(&delivered).unwrap(), damaged);
+
+        std::fs::remove_file(&delivered).unwrap();
+        assert_eq!(repairer.check(&file).await.unwrap(), FileCheck::Removed);
+        assert!(index.is_empty());
+        serving.abort();
+    }
+    #[tokio::test]
+    async fn test_files_delivered_with_other_checksums_are_repaired() {
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        let dir = tempfile::tempdir().unwrap();
+        let block_size = 8 * 1024;
+        let original: Vec<u8> = (0..12 * block_size as u32)
+            .map(|i| (i.wrapping_mul(2_246_822_519) >> 24) as u8)
+            .collect();
+        let sent = dir.path().join("sent.bin");
+        std::fs::write(&sent, &original).unwrap();
+
+        let sender = TransferCoordinator::new(
+            ChunkManager::new(block_size, 4, 2).unwrap(),
+            QuicTransport::new(ConnectionConfig {
+                bind_addr: "127.0.0.1:0".parse().unwrap(),
+                ..Default::default()
+            })
+            .await
+            .unwrap(),
+            PriorityQueue::new(1000),
+            SessionStore::new_in_memory().await.unwrap(),
+        );
+        let serving = sender.receive().serve_repairs();
+        let index = Arc::new(RepairIndex::in_memory());
+        let repairer = FileRepairer::new(index.clone(), transport().await);
+
+        for algorithm in [ChecksumType::Sha256, ChecksumType::Crc32] {
+            let delivered = dir.path().join(format!("received-{algorithm:?}.bin"));
+            std::fs::write(&delivered, &original).unwrap();
+            let file = StoredFile {
+                path: delivered.clone(),
+                file_id: sent.to_string_lossy().to_string(),
+                checksum: algorithm.digest(&original),
+                checksum_algorithm: algorithm,
+                source: sender.transport().local_addr().unwrap(),
+                block_size,
+                verified_at: 0,
+            };
+            index.record(file.clone()).await.unwrap();
+            assert_eq!(repairer.check(&file).await.unwrap(), FileCheck::Intact);
+
+            let mut damaged = original.clone();
+            damaged[5 * block_size + 7] ^= 0xFF;
+            std::fs::write(&delivered, &damaged).unwrap();
+            assert_eq!(
+                repairer.check(&file).await.unwrap(),
+                FileCheck::Repaired {
+                    fetched_bytes: block_size as u64
+                }
+            );
+            assert_eq!(std::fs::read(&delivered).unwrap(), original);
+        }
+        serving.abort();
+    }
+}
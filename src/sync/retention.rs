@@ -0,0 +1,436 @@
+//! Retention of delivered files
+//!
+//! Left alone, a receiver keeps every file it rebuilds. A [`FileRetentionPolicy`]
+//! bounds its save directory by age and total size, with priorities whose
+//! files are always kept. [`RetentionCleaner`] applies the policy every
+//! interval and broadcasts a [`RetentionEvent`] for each file it deletes;
+//! [`RetentionCleaner::plan`] reports what a pass would delete without
+//! touching anything.
+//!
+//! Deliveries are recorded with their priority and arrival time in an index
+//! next to the files, since a rebuilt file carries its sender's mtime.
+//! Files the index doesn't know, such as ones copied in by hand, are aged
+//! by mtime and have no priority. Dotfiles and directories are the
+//! receiver's own bookkeeping and are never deleted.
+
+use crate::chunk::Priority;
+use crate::sync::IndexFile;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use thiserror::Error;
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+
+/// File name of the index under a receiver's save directory
+pub const RETENTION_INDEX_FILE: &str = ".retention.json";
+
+#[derive(Error, Debug)]
+pub enum RetentionError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Retention index {path}: {reason}")]
+    Index { path: PathBuf, reason: String },
+}
+
+pub type RetentionResult<T> = Result<T, RetentionError>;
+
+/// How long delivered files are kept, and how much of them
+///
+/// The default keeps everything.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FileRetentionPolicy {
+    /// Delete files delivered longer ago than this (secs, 0 = no limit)
+    pub max_age_secs: u64,
+    /// Delete the oldest files while the directory holds more than this
+    /// (0 = no limit)
+    pub max_total_bytes: u64,
+    /// Priorities whose files are kept whatever their age; they still count
+    /// towards `max_total_bytes`
+    pub keep_priorities: Vec<Priority>,
+    /// How often the cleaner runs (secs)
+    pub interval_secs: u64,
+}
+
+impl Default for FileRetentionPolicy {
+    fn default() -> Self {
+        Self {
+            max_age_secs: 0,
+            max_total_bytes: 0,
+            keep_priorities: Vec::new(),
+            interval_secs: 3600,
+        }
+    }
+}
+
+impl FileRetentionPolicy {
+    /// Whether the policy ever deletes anything
+    pub fn is_enabled(&self) -> bool {
+        self.max_age_secs > 0 || self.max_total_bytes > 0
+    }
+
+    fn keeps(&self, priority: Option<Priority>) -> bool {
+        priority.is_some_and(|p| self.keep_priorities.contains(&p))
+    }
+}
+
+/// What the index knows of a delivered file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetainedFile {
+    pub priority: Priority,
+    /// Unix time the file was delivered
+    pub received_at: i64,
+}
+
+impl RetainedFile {
+    /// A file of `priority` delivered just now
+    pub fn now(priority: Priority) -> Self {
+        Self {
+            priority,
+            received_at: chrono::Utc::now().timestamp(),
+        }
+    }
+}
+
+/// Which limit a file is deleted for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RetentionReason {
+    MaxAge,
+    MaxTotalBytes,
+}
+
+/// A file a pass deletes, or would delete
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetentionCandidate {
+    pub filename: String,
+    pub path: PathBuf,
+    pub size: u64,
+    /// Seconds since delivery (or last modification, if not indexed)
+    pub age_secs: u64,
+    pub priority: Option<Priority>,
+    pub reason: RetentionReason,
+}
+
+/// What one pass found and deleted
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetentionReport {
+    /// Nothing was deleted; `deleted` is what would have been
+    pub dry_run: bool,
+    pub scanned: usize,
+    pub total_bytes: u64,
+    pub deleted: Vec<RetentionCandidate>,
+    /// Bytes left once the deletions are done
+    pub kept_bytes: u64,
+    /// Files that couldn't be deleted, and why
+    pub failed: Vec<(PathBuf, String)>,
+}
+
+/// A file the cleaner deleted
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetentionEvent {
+    pub filename: String,
+    pub path: PathBuf,
+    pub size: u64,
+    pub reason: RetentionReason,
+    /// Unix time of the deletion
+    pub at: i64,
+}
+
+/// Applies a [`FileRetentionPolicy`] to one directory
+#[derive(Debug)]
+pub struct RetentionCleaner {
+    dir: PathBuf,
+    policy: FileRetentionPolicy,
+    files: Mutex<BTreeMap<String, RetainedFile>>,
+    index: IndexFile,
+    events: broadcast::Sender<RetentionEvent>,
+}
+
+impl RetentionCleaner {
+    /// Clean `dir` under `policy`, loading the index kept there
+    pub fn open(dir: impl Into<PathBuf>, policy: FileRetentionPolicy) -> RetentionResult<Self> {
+        let dir = dir.into();
+        let index = dir.join(RETENTION_INDEX_FILE);
+        let files = if index.exists() {
+            let data = std::fs::read(&index)?;
+            serde_json::from_slice(&data).map_err(|e| RetentionError::Index {
+                path: index.clone(),
+                reason: e.to_string(),
+            })?
+        } else {
+            BTreeMap::new()
+        };
+        Ok(Self {
+            dir,
+            policy,
+            files: Mutex::new(files),
+            index: IndexFile::new(index),
+            events: broadcast::channel(256).0,
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn policy(&self) -> &FileRetentionPolicy {
+        &self.policy
+    }
+
+    /// Deletions from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<RetentionEvent> {
+        self.events.subscribe()
+    }
+
+    /// Note a file delivered into the directory as `filename`
+    pub async fn record(&self, filename: &str, file: RetainedFile) -> RetentionResult<()> {
+        self.files.lock().insert(filename.to_string(), file);
+        self.save().await
+    }
+
+    /// What a pass would delete now, deleting nothing
+    pub fn plan(&self) -> RetentionResult<RetentionReport> {
+        let files = self.files.lock();
+        let now = chrono::Utc::now().timestamp();
+
+        let mut found = Vec::new();
+        for entry in std::fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            let filename = entry.file_name().to_string_lossy().to_string();
+            let meta = entry.metadata()?;
+            if filename.starts_with('.') || !meta.is_file() {
+                continue;
+            }
+            let retained = files.get(&filename);
+            let received_at = retained.map_or_else(
+                || {
+                    meta.modified()
+                        .ok()
+                        .and_then(|t| t.duration_since(SystemTime::UNIX_EPOCH).ok())
+                        .map_or(now, |d| d.as_secs() as i64)
+                },
+                |f| f.received_at,
+            );
+            found.push(RetentionCandidate {
+                path: entry.path(),
+                filename,
+                size: meta.len(),
+                age_secs: (now - received_at).max(0) as u64,
+                priority: retained.map(|f| f.priority),
+                reason: RetentionReason::MaxAge,
+            });
+        }
+
+        let total_bytes: u64 = found.iter().map(|f| f.size).sum();
+        let mut report = RetentionReport {
+            dry_run: true,
+            scanned: found.len(),
+            total_bytes,
+            kept_bytes: total_bytes,
+            ..Default::default()
+        };
+
+        // Oldest first, so a size limit deletes those before newer ones
+        found.sort_by(|a, b| {
+            b.age_secs
+                .cmp(&a.age_secs)
+                .then(a.filename.cmp(&b.filename))
+        });
+        let policy = &self.policy;
+        let mut over_budget = policy.max_total_bytes > 0 && total_bytes > policy.max_total_bytes;
+        let expired =
+            |f: &RetentionCandidate| policy.max_age_secs > 0 && f.age_secs > policy.max_age_secs;
+        // Expired files go first whatever their place in the order
+        let (mut due, rest): (Vec<_>, Vec<_>) = found
+            .into_iter()
+            .filter(|f| !policy.keeps(f.priority))
+            .partition(expired);
+        report.kept_bytes -= due.iter().map(|f| f.size).sum::<u64>();
+        over_budget &= report.kept_bytes > policy.max_total_bytes;
+        for mut file in rest {
+            if !over_budget {
+                break;
+            }
+            file.reason = RetentionReason::MaxTotalBytes;
+            report.kept_bytes -= file.size;
+            over_budget = report.kept_bytes > policy.max_total_bytes;
+            due.push(file);
+        }
+        report.deleted = due;
+        Ok(report)
+    }
+
+    /// Delete what the policy says is due, reporting each deletion
+    pub async fn clean(&self) -> RetentionResult<RetentionReport> {
+        let mut report = self.plan()?;
+        report.dry_run = false;
+
+        let mut deleted = Vec::with_capacity(report.deleted.len());
+        for file in std::mem::take(&mut report.deleted) {
+            match std::fs::remove_file(&file.path) {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => {
+                    tracing::warn!("Could not delete {}: {}", file.path.display(), e);
+                    report.kept_bytes += file.size;
+                    report.failed.push((file.path.clone(), e.to_string()));
+                    continue;
+                }
+            }
+            tracing::info!(
+                path = %file.path.display(),
+                size = file.size,
+                reason = ?file.reason,
+                "deleted received file"
+            );
+            let _ = self.events.send(RetentionEvent {
+                filename: file.filename.clone(),
+                path: file.path.clone(),
+                size: file.size,
+                reason: file.reason,
+                at: chrono::Utc::now().timestamp(),
+            });
+            deleted.push(file);
+        }
+        report.deleted = deleted;
+
+        // Forget deleted files, and any others gone since they were recorded
+        let forgotten = {
+            let mut files = self.files.lock();
+            let before = files.len();
+            files.retain(|name, _| self.dir.join(name).exists());
+            files.len() != before
+        };
+        if forgotten {
+            self.save().await?;
+        }
+        Ok(report)
+    }
+
+    /// Clean every policy interval until the task is aborted
+    pub fn spawn(self: Arc<Self>) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut tick =
+                tokio::time::interval(Duration::from_secs(self.policy.interval_secs.max(1)));
+            tick.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                tick.tick().await;
+                match self.clean().await {
+                    Ok(report) if !report.deleted.is_empty() || !report.failed.is_empty() => {
+                        tracing::info!(
+                            "Retention pass: {} of {} file(s) deleted, {} bytes kept, {} failed",
+                            report.deleted.len(),
+                            report.scanned,
+                            report.kept_bytes,
+                            report.failed.len()
+                        );
+                    }
+                    Ok(_) => {}
+                    Err(e) => tracing::warn!("Retention pass failed: {}", e),
+                }
+            }
+        })
+    }
+
+    /// Write the index as it stands
+    async fn save(&self) -> RetentionResult<()> {
+        self.index.save(|| self.files.lock().clone()).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const DAY: i64 = 86_400;
+
+    async fn deliver(
+        cleaner: &RetentionCleaner,
+        name: &str,
+        size: usize,
+        age: i64,
+        priority: Priority,
+    ) {
+        std::fs::write(cleaner.dir().join(name), vec![0u8; size]).unwrap();
+        let file = RetainedFile {
+            priority,
+            received_at: chrono::Utc::now().timestamp() - age,
+        };
+        cleaner.record(name, file).await.unwrap();
+    }
+
+    fn names(report: &RetentionReport) -> Vec<(&str, RetentionReason)> {
+        report
+            .deleted
+            .iter()
+            .map(|f| (f.filename.as_str(), f.reason))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_plan_by_age_and_size() {
+        let dir = TempDir::new().unwrap();
+        let policy = FileRetentionPolicy {
+            max_age_secs: 7 * DAY as u64,
+            max_total_bytes: 250,
+            keep_priorities: vec![Priority::Critical],
+            ..Default::default()
+        };
+        let cleaner = RetentionCleaner::open(dir.path(), policy).unwrap();
+        deliver(&cleaner, "old.bin", 100, 10 * DAY, Priority::Normal).await;
+        deliver(&cleaner, "orders.pdf", 100, 30 * DAY, Priority::Critical).await;
+        deliver(&cleaner, "tile-1.png", 100, 3 * DAY, Priority::Normal).await;
+        deliver(&cleaner, "tile-2.png", 100, DAY, Priority::High).await;
+        std::fs::create_dir(dir.path().join("quarantine")).unwrap();
+
+        let report = cleaner.plan().unwrap();
+        assert!(report.dry_run);
+        assert_eq!((report.scanned, report.total_bytes), (4, 400));
+        // The critical file is older but exempt; it still takes up budget
+        assert_eq!(
+            names(&report),
+            vec![
+                ("old.bin", RetentionReason::MaxAge),
+                ("tile-1.png", RetentionReason::MaxTotalBytes),
+            ]
+        );
+        assert_eq!(report.kept_bytes, 200);
+        assert!(dir.path().join("old.bin").exists());
+    }
+
+    #[tokio::test]
+    async fn test_clean_deletes_and_reports() {
+        let dir = TempDir::new().unwrap();
+        let policy = FileRetentionPolicy {
+            max_age_secs: DAY as u64,
+            ..Default::default()
+        };
+        let cleaner = RetentionCleaner::open(dir.path(), policy.clone()).unwrap();
+        let mut events = cleaner.subscribe();
+        deliver(&cleaner, "report.csv", 10, 2 * DAY, Priority::Normal).await;
+        deliver(&cleaner, "fresh.csv", 10, 0, Priority::Normal).await;
+
+        let report = cleaner.clean().await.unwrap();
+        assert!(!report.dry_run);
+        assert_eq!(
+            names(&report),
+            vec![("report.csv", RetentionReason::MaxAge)]
+        );
+        assert!(!dir.path().join("report.csv").exists());
+        assert!(dir.path().join(RETENTION_INDEX_FILE).exists());
+        assert_eq!(events.recv().await.unwrap().filename, "report.csv");
+
+        // The index survives a restart, minus the deleted file
+        let reopened = RetentionCleaner::open(dir.path(), policy).unwrap();
+        assert_eq!(reopened.files.lock().len(), 1);
+        assert!(reopened.plan().unwrap().deleted.is_empty());
+    }
+}
@@ -75,8 +75,8 @@ impl Adler32Rolling {
     ///
     /// - b_old = n*x1 + (n-1)*x2 + ... + 1*xn + n (the +n comes from the n 1's in the a values)
     /// - b_new = n*x2 + (n-1)*x3 + ... + 1*new + n
-    ///         = (n-1)*x2 + (n-2)*x3 + ... + 0*xn + x2 + x3 + ... + xn + new + n
-    ///         = b_old - n*x1 - (x2+x3+...+xn) - (a_old - 1 - x1) + (x2+...+xn+new) + n
+    ///   = (n-1)*x2 + (n-2)*x3 + ... + 0*xn + x2 + x3 + ... + xn + new + n
+    ///   = b_old - n*x1 - (x2+x3+...+xn) - (a_old - 1 - x1) + (x2+...+xn+new) + n
     ///
     /// Simplified: b_new = b_old - n*x1 - a_old + 1 + a_new
     ///                   = b_old + a_new - a_old - n*x1 + 1
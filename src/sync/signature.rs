@@ -86,7 +86,7 @@ impl FileSignature {
     }
 
     /// Build a lookup table for fast weak hash matching
-    pub fn build_lookup(&self) -> SignatureLookup {
+    pub fn build_lookup(&self) -> SignatureLookup<'_> {
         let mut lookup: HashMap<u32, Vec<usize>> = HashMap::new();
 
         for (idx, block) in self.blocks.iter().enumerate() {
@@ -160,12 +160,9 @@ impl SignatureBuilder {
 
         let mut blocks = Vec::new();
         let mut offset = 0u64;
-        let mut index = 0u32;
-
-        for chunk in data.chunks(self.block_size) {
-            blocks.push(BlockSignature::new(index, offset, chunk));
+        for (index, chunk) in data.chunks(self.block_size).enumerate() {
+            blocks.push(BlockSignature::new(index as u32, offset, chunk));
             offset += chunk.len() as u64;
-            index += 1;
         }
 
         FileSignature {
@@ -9,11 +9,13 @@
 #[path = "simulation/mod.rs"]
 mod simulation;
 
-use chunkstream_pro::chunk::{ChunkManager, Priority};
+use bytes::Bytes;
+use chunkstream_pro::chunk::{ChunkManager, ErasureCoder, Priority};
 use chunkstream_pro::integrity::IntegrityVerifier;
 use simulation::{
-    BenchmarkResult, LossyChannel, LossyChannelConfig, MetricsCollector, TestMatrixParams,
+    BenchmarkResult, LossyChannel, LossyChannelConfig, MetricsCollector, Sent, TestMatrixParams,
 };
+use std::time::Instant;
 use tempfile::TempDir;
 use tokio::fs;
 
@@ -73,15 +75,15 @@ async fn test_erasure_recovery_at_loss_rate(
     for chunk in &chunks {
         collector.chunk_sent();
         let chunk_data = chunk.data.to_vec();
-        match channel.send(&chunk_data).await {
-            Ok(data) => {
+        match channel.send(&chunk_data).await.map(Sent::delivered) {
+            Ok(Some(data)) => {
                 collector.first_byte_received();
                 // Re-create chunk with received data
                 let mut received_chunk = chunk.clone();
                 received_chunk.data = data.into();
                 received_chunks.push(received_chunk);
             }
-            Err(_) => {
+            _ => {
                 collector.chunk_lost();
             }
         }
@@ -301,7 +303,7 @@ async fn find_max_tolerable_loss() {
     let iterations = 5;
 
     // Test with more parity for higher loss tolerance
-    for (data_shards, parity_shards) in vec![(50, 10), (50, 15), (50, 20), (40, 20)] {
+    for (data_shards, parity_shards) in [(50, 10), (50, 15), (50, 20), (40, 20)] {
         let theoretical_tolerance =
             parity_shards as f64 / (data_shards + parity_shards) as f64 * 100.0;
 
@@ -352,3 +354,60 @@ async fn find_max_tolerable_loss() {
         );
     }
 }
+
+/// Reconstruction time of one large group by decode worker count
+#[tokio::test]
+async fn benchmark_parallel_decode() {
+    println!("\n========================================");
+    println!("BENCHMARK: Parallel Group Decode");
+    println!("========================================\n");
+
+    let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let (data_shards, parity_shards) = (16, 4);
+    let shard_len = 1024 * 1024; // 16 MB group
+    let coder = ErasureCoder::new(data_shards, parity_shards).unwrap();
+    let data: Vec<Bytes> = (0..data_shards)
+        .map(|i| {
+            Bytes::from(
+                (0..shard_len)
+                    .map(|b| (b * 31 + i) as u8)
+                    .collect::<Vec<_>>(),
+            )
+        })
+        .collect();
+    let encoded = coder.encode(data.clone()).unwrap();
+
+    // Lose every parity shard's worth of data shards
+    let mut held: Vec<Option<Bytes>> = encoded.into_iter().map(Some).collect();
+    for slot in held.iter_mut().take(parity_shards) {
+        *slot = None;
+    }
+
+    println!(
+        "{:<10} | {:<12} | {:<15} | {:<10}",
+        "Workers", "Duration ms", "Throughput MB/s", "Speedup"
+    );
+    println!("{}", "-".repeat(55));
+
+    let mut worker_counts: Vec<usize> = std::iter::successors(Some(1), |w| Some(w * 2))
+        .take_while(|&w| w < cores)
+        .collect();
+    worker_counts.push(cores);
+
+    let mut baseline = None;
+    for workers in worker_counts {
+        let started = Instant::now();
+        let decoded = coder.decode_parallel(held.clone(), workers).unwrap();
+        let elapsed = started.elapsed();
+        assert_eq!(decoded, data);
+
+        let baseline = *baseline.get_or_insert(elapsed);
+        println!(
+            "{:>10} | {:>12} | {:>15.1} | {:>9.2}x",
+            workers,
+            elapsed.as_millis(),
+            (data_shards * shard_len) as f64 / elapsed.as_secs_f64() / (1024.0 * 1024.0),
+            baseline.as_secs_f64() / elapsed.as_secs_f64()
+        );
+    }
+}
@@ -0,0 +1,298 @@
+//! Chaos tests: the coordinator under injected subsystem failures
+//!
+//! Run with: cargo test --features fault-injection --test fault_injection
+
+use chunkstream_pro::chunk::{ChunkManager, Priority};
+use chunkstream_pro::coordinator::{RetransmitPolicy, TransferCoordinator, TransferState};
+use chunkstream_pro::fault::{self, FaultConfig};
+use chunkstream_pro::network::{ConnectionConfig, QuicTransport};
+use chunkstream_pro::priority::PriorityQueue;
+use chunkstream_pro::session::SessionStore;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tempfile::TempDir;
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, Duration, Instant};
+
+/// The injector is process-wide, so tests take turns with it
+static INJECTOR_LOCK: Mutex<()> = Mutex::const_new(());
+
+async fn coordinator() -> TransferCoordinator {
+    TransferCoordinator::new(
+        ChunkManager::new(64 * 1024, 4, 2).unwrap(),
+        QuicTransport::new(ConnectionConfig {
+            bind_addr: "127.0.0.1:0".parse().unwrap(),
+            ..Default::default()
+        })
+        .await
+        .unwrap(),
+        PriorityQueue::new(1000),
+        SessionStore::new_in_memory().await.unwrap(),
+    )
+}
+
+async fn write_file(dir: &Path, name: &str, size: usize) -> PathBuf {
+    let path = dir.join(name);
+    let data: Vec<u8> = (0..size).map(|i| (i % 251) as u8).collect();
+    tokio::fs::write(&path, data).await.unwrap();
+    path
+}
+
+/// Poll until the transfer reaches a terminal state
+async fn settle(coordinator: &TransferCoordinator, session_id: &str) -> TransferState {
+    let deadline = Instant::now() + Duration::from_secs(30);
+    loop {
+        let state = coordinator.get_recent_state(session_id);
+        match state {
+            Some(state) if state.is_terminal() => return state,
+            _ if Instant::now() > deadline => {
+                panic!("transfer {session_id} never settled: {state:?}")
+            }
+            _ => sleep(Duration::from_millis(20)).await,
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_transfers_settle_under_session_and_state_faults() {
+    let _turn = INJECTOR_LOCK.lock().await;
+    let dir = TempDir::new().unwrap();
+    let coordinator = coordinator().await;
+
+    fault::injector()
+        .configure(FaultConfig {
+            session_write: 0.05,
+            state_transition: 0.05,
+            ..Default::default()
+        })
+        .unwrap();
+
+    let mut started = Vec::new();
+    let mut refused = 0;
+    for n in 0..12 {
+        let file = write_file(dir.path(), &format!("chaos-{n}.bin"), 300 * 1024).await;
+        match coordinator.send_file(file, Priority::Normal, None).await {
+            Ok(session_id) => started.push(session_id),
+            // Failing up front is a fine outcome, as long as it is reported
+            Err(_) => refused += 1,
+        }
+    }
+
+    let mut failed = 0;
+    for session_id in &started {
+        if let TransferState::Failed { .. } = settle(&coordinator, session_id).await {
+            failed += 1;
+        }
+    }
+    let stats = fault::injector().stats();
+    fault::injector().reset();
+
+    println!(
+        "{} started, {} refused, {} failed; {} session and {} state faults injected",
+        started.len(),
+        refused,
+        failed,
+        stats.session_write.injected,
+        stats.state_transition.injected
+    );
+    assert!(stats.session_write.checked > 0 && stats.state_transition.checked > 0);
+    // No transfer is left holding a slot
+    assert!(coordinator.list_active().is_empty());
+
+    // With the faults gone the coordinator carries on as normal
+    let file = write_file(dir.path(), "after.bin", 300 * 1024).await;
+    let session_id = coordinator
+        .send_file(file, Priority::Normal, None)
+        .await
+        .unwrap();
+    assert!(settle(&coordinator, &session_id).await.is_completed());
+}
+
+#[tokio::test]
+async fn test_every_fault_fails_the_transfer_cleanly() {
+    let _turn = INJECTOR_LOCK.lock().await;
+    let dir = TempDir::new().unwrap();
+    let coordinator = coordinator().await;
+    let file = write_file(dir.path(), "doomed.bin", 300 * 1024).await;
+
+    // Starting the state machine fails, before the transfer is registered
+    fault::injector()
+        .configure(FaultConfig {
+            state_transition: 1.0,
+            ..Default::default()
+        })
+        .unwrap();
+    assert!(coordinator
+        .send_file(file.clone(), Priority::High, None)
+        .await
+        .is_err());
+    fault::injector().reset();
+    assert!(coordinator.list_active().is_empty());
+
+    // The failed attempt doesn't block sending the same file again
+    let session_id = coordinator
+        .send_file(file, Priority::High, None)
+        .await
+        .unwrap();
+    assert!(settle(&coordinator, &session_id).await.is_completed());
+}
+
+/// Receiver that takes whatever arrives and never reports back
+async fn drain_receiver() -> (SocketAddr, JoinHandle<()>) {
+    rustls::crypto::ring::default_provider()
+        .install_default()
+        .ok();
+    let receiver = Arc::new(
+        QuicTransport::new(ConnectionConfig {
+            bind_addr: "127.0.0.1:0".parse().unwrap(),
+            ..Default::default()
+        })
+        .await
+        .unwrap(),
+    );
+    let addr = receiver.local_addr().unwrap();
+    let drain = tokio::spawn(async move {
+        let conn = receiver.accept().await.unwrap();
+        while let Ok(stream) = conn.accept_uni().await {
+            if receiver.receive_chunk(stream).await.is_err() {
+                break;
+            }
+        }
+    });
+    (addr, drain)
+}
+
+/// Quick retry passes, no waiting for group reports, and every failed send
+/// retried rather than left to parity
+fn retry_policy(failed_chunk_retries: u32) -> RetransmitPolicy {
+    RetransmitPolicy {
+        budget_per_group: 0,
+        failed_chunk_retries,
+        adaptive_retries: false,
+        retry_backoff: Duration::from_millis(10),
+        max_retry_backoff: Duration::from_millis(20),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn test_quic_send_faults_are_retried() {
+    let _turn = INJECTOR_LOCK.lock().await;
+    let dir = TempDir::new().unwrap();
+    let (receiver_addr, drain) = drain_receiver().await;
+
+    let coordinator = coordinator().await;
+    coordinator.set_retransmit_policy(RetransmitPolicy {
+        adaptive_retries: false,
+        ..Default::default()
+    });
+    let file = write_file(dir.path(), "retried.bin", 300 * 1024).await;
+
+    fault::injector()
+        .configure(FaultConfig {
+            quic_send: 0.3,
+            ..Default::default()
+        })
+        .unwrap();
+    let session_id = coordinator
+        .send_file(file, Priority::High, Some(receiver_addr))
+        .await
+        .unwrap();
+    let state = settle(&coordinator, &session_id).await;
+    let stats = fault::injector().stats();
+    fault::injector().reset();
+    drain.abort();
+
+    assert!(stats.quic_send.injected > 0);
+    assert!(coordinator.transport().stats().retransmissions >= stats.quic_send.injected);
+    // Four retries each, with parity behind them
+    assert!(state.is_completed(), "{state:?}");
+}
+
+#[tokio::test]
+async fn test_failed_chunks_retried_once_sends_recover() {
+    let _turn = INJECTOR_LOCK.lock().await;
+    let dir = TempDir::new().unwrap();
+    let (receiver_addr, drain) = drain_receiver().await;
+
+    let coordinator = coordinator().await;
+    coordinator.set_retransmit_policy(RetransmitPolicy {
+        retry_backoff: Duration::from_millis(200),
+        max_retry_backoff: Duration::from_millis(200),
+        ..retry_policy(3)
+    });
+    let file = write_file(dir.path(), "outage.bin", 200 * 1024).await;
+
+    // Every send fails until the first retry pass is under way
+    fault::injector()
+        .configure(FaultConfig {
+            quic_send: 1.0,
+            ..Default::default()
+        })
+        .unwrap();
+    let session_id = coordinator
+        .send_file(file, Priority::High, Some(receiver_addr))
+        .await
+        .unwrap();
+    let total_chunks = coordinator
+        .get_progress(&session_id)
+        .await
+        .unwrap()
+        .total_chunks;
+    while coordinator
+        .get_progress(&session_id)
+        .await
+        .unwrap()
+        .failed_chunks
+        .len()
+        < total_chunks as usize
+    {
+        sleep(Duration::from_millis(10)).await;
+    }
+    fault::injector().reset();
+
+    let state = settle(&coordinator, &session_id).await;
+    drain.abort();
+    assert_eq!(state, TransferState::Completed);
+    let progress = coordinator.get_progress(&session_id).await.unwrap();
+    assert!(progress.failed_chunks.is_empty());
+    assert_eq!(progress.completed_chunks, total_chunks);
+}
+
+#[tokio::test]
+async fn test_transfer_fails_only_when_retries_run_out() {
+    let _turn = INJECTOR_LOCK.lock().await;
+    let dir = TempDir::new().unwrap();
+    let (receiver_addr, drain) = drain_receiver().await;
+
+    let coordinator = coordinator().await;
+    coordinator.set_retransmit_policy(retry_policy(2));
+    let file = write_file(dir.path(), "dead-link.bin", 100 * 1024).await;
+
+    fault::injector()
+        .configure(FaultConfig {
+            quic_send: 1.0,
+            ..Default::default()
+        })
+        .unwrap();
+    let session_id = coordinator
+        .send_file(file, Priority::High, Some(receiver_addr))
+        .await
+        .unwrap();
+    let state = settle(&coordinator, &session_id).await;
+    let stats = fault::injector().stats();
+    fault::injector().reset();
+    drain.abort();
+
+    assert!(matches!(state, TransferState::Failed { .. }), "{state:?}");
+    let progress = coordinator.get_progress(&session_id).await.unwrap();
+    let all: Vec<u32> = (0..progress.total_chunks).collect();
+    assert_eq!(progress.failed_chunks, all);
+    // Each chunk: the first send and two retry passes, four tries apiece
+    assert_eq!(
+        stats.quic_send.injected,
+        progress.total_chunks as u64 * 3 * 4
+    );
+}
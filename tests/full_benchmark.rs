@@ -9,7 +9,7 @@ mod simulation;
 use chunkstream_pro::chunk::{ChunkManager, Priority};
 use chunkstream_pro::integrity::IntegrityVerifier;
 use simulation::{
-    BenchmarkReport, BenchmarkResult, LossyChannel, LossyChannelConfig, MetricsCollector,
+    BenchmarkReport, BenchmarkResult, LossyChannel, LossyChannelConfig, MetricsCollector, Sent,
 };
 use std::path::Path;
 use tempfile::TempDir;
@@ -69,14 +69,14 @@ async fn run_benchmark(
     for chunk in &chunks {
         collector.chunk_sent();
         let chunk_data = chunk.data.to_vec();
-        match channel.send(&chunk_data).await {
-            Ok(data) => {
+        match channel.send(&chunk_data).await.map(Sent::delivered) {
+            Ok(Some(data)) => {
                 collector.first_byte_received();
                 let mut received_chunk = chunk.clone();
                 received_chunk.data = data.into();
                 received_chunks.push(received_chunk);
             }
-            Err(_) => {
+            _ => {
                 collector.chunk_lost();
             }
         }
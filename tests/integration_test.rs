@@ -1,9 +1,15 @@
+#![cfg(feature = "sqlite")]
+
 use chunkstream_pro::chunk::{Chunk, ChunkManager, Priority};
 use chunkstream_pro::coordinator::TransferCoordinator;
-use chunkstream_pro::integrity::IntegrityVerifier;
-use chunkstream_pro::network::{ConnectionConfig, QuicTransport};
+use chunkstream_pro::integrity::{ChecksumType, IntegrityVerifier};
+use chunkstream_pro::network::{
+    Capabilities, ConnectionConfig, FileOffer, GroupFeedback, OfferReply, QuicTransport,
+    ReceivedChunk,
+};
 use chunkstream_pro::priority::PriorityQueue;
 use chunkstream_pro::session::{SessionState, SessionStore};
+use std::collections::HashMap;
 #[allow(unused_imports)]
 use std::path::PathBuf;
 use std::sync::Arc;
@@ -70,7 +76,11 @@ async fn test_full_transfer_workflow() {
         loop {
             match conn.accept_uni().await {
                 Ok(recv_stream) => {
-                    match receiver_transport_clone.receive_chunk(recv_stream).await {
+                    match receiver_transport_clone
+                        .receive_chunk(recv_stream)
+                        .await
+                        .map(ReceivedChunk::into_chunk)
+                    {
                         Ok(chunk) => {
                             println!(
                                 "🔵 Receiver: Chunk {}/{} received",
@@ -90,6 +100,13 @@ async fn test_full_transfer_workflow() {
                                         - chunk.metadata.data_chunks,
                                     checksum: chunk.metadata.file_checksum,
                                     priority: chunk.metadata.priority,
+                                    zero_runs: chunk.metadata.zero_runs.clone(),
+                                    attributes: chunk.metadata.attributes.clone(),
+                                    checksum_algorithm: chunk.metadata.checksum_algorithm,
+                                    erasure_profile: Default::default(),
+                                    schedule: None,
+                                    merkle_root: None,
+                                    compression: None,
                                 });
                             }
 
@@ -148,11 +165,9 @@ async fn test_full_transfer_workflow() {
     let sender_chunk_manager = ChunkManager::new(256 * 1024, 10, 3).unwrap();
     let session_store = SessionStore::new_in_memory().await.unwrap();
     let priority_queue = PriorityQueue::new(1000);
-    let verifier = IntegrityVerifier;
 
     let coordinator = TransferCoordinator::new(
         sender_chunk_manager,
-        verifier,
         sender_transport,
         priority_queue,
         session_store,
@@ -359,6 +374,12 @@ async fn test_priority_queue() {
             file_checksum: [0u8; 32],
             priority: Priority::Normal,
             created_at: chrono::Utc::now().timestamp(),
+            zero_runs: Vec::new(),
+            attributes: None,
+            checksum_algorithm: Default::default(),
+            merkle_proof: None,
+            trace: None,
+            compression: None,
         },
         data: vec![0u8; 256].into(),
     };
@@ -377,6 +398,12 @@ async fn test_priority_queue() {
             file_checksum: [0u8; 32],
             priority: Priority::Critical,
             created_at: chrono::Utc::now().timestamp(),
+            zero_runs: Vec::new(),
+            attributes: None,
+            checksum_algorithm: Default::default(),
+            merkle_proof: None,
+            trace: None,
+            compression: None,
         },
         data: vec![0u8; 256].into(),
     };
@@ -395,6 +422,12 @@ async fn test_priority_queue() {
             file_checksum: [0u8; 32],
             priority: Priority::High,
             created_at: chrono::Utc::now().timestamp(),
+            zero_runs: Vec::new(),
+            attributes: None,
+            checksum_algorithm: Default::default(),
+            merkle_proof: None,
+            trace: None,
+            compression: None,
         },
         data: vec![0u8; 256].into(),
     };
@@ -438,6 +471,13 @@ async fn test_session_persistence() {
         parity_chunks: 3,
         checksum: [0u8; 32],
         priority: Priority::High,
+        zero_runs: Vec::new(),
+        attributes: None,
+        checksum_algorithm: Default::default(),
+        erasure_profile: Default::default(),
+        schedule: None,
+        merkle_root: None,
+        compression: None,
     };
 
     let session = SessionState::new(
@@ -464,3 +504,266 @@ async fn test_session_persistence() {
 
     println!("✅ Session persistence test PASSED!\n");
 }
+
+/// Losses beyond what parity covers are reported back and the missing
+/// shards resent
+#[tokio::test]
+async fn test_lost_shards_are_retransmitted() {
+    rustls::crypto::ring::default_provider()
+        .install_default()
+        .ok();
+
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = temp_dir.path().join("lossy.bin");
+    let test_data: Vec<u8> = (0..1024 * 1024).map(|i| (i % 253) as u8).collect();
+    fs::write(&test_file, &test_data).await.unwrap();
+
+    let receiver_config = ConnectionConfig {
+        bind_addr: "127.0.0.1:0".parse().unwrap(),
+        ..Default::default()
+    };
+    let receiver_transport = Arc::new(QuicTransport::new(receiver_config).await.unwrap());
+    let receiver_addr = receiver_transport.local_addr().unwrap();
+    let receiver_output = temp_dir.path().join("received_lossy.bin");
+
+    let output = receiver_output.clone();
+    let receiver_handle = tokio::spawn(async move {
+        let conn = receiver_transport.accept().await.unwrap();
+        let chunk_manager = ChunkManager::new(256 * 1024, 10, 3).unwrap();
+        let mut chunks: HashMap<u32, Chunk> = HashMap::new();
+        let mut arrivals = 0;
+        let mut reported = None;
+
+        loop {
+            let stream =
+                match tokio::time::timeout(Duration::from_millis(300), conn.accept_uni()).await {
+                    Ok(stream) => stream.unwrap(),
+                    Err(_) => {
+                        // Quiet sender: report what arrived
+                        let Some(chunk) = chunks.values().next() else {
+                            continue;
+                        };
+                        if reported != Some(chunks.len()) {
+                            reported = Some(chunks.len());
+                            let feedback = GroupFeedback {
+                                file_id: chunk.metadata.file_id.clone(),
+                                data_chunks: chunk.metadata.data_chunks,
+                                total_chunks: chunk.metadata.total_chunks,
+                                received: chunks.keys().copied().collect(),
+                            };
+                            receiver_transport
+                                .send_group_feedback(&conn, &feedback)
+                                .await
+                                .unwrap();
+                        }
+                        continue;
+                    }
+                };
+            let chunk = receiver_transport
+                .receive_chunk(stream)
+                .await
+                .unwrap()
+                .into_chunk();
+            arrivals += 1;
+            // The first three chunks are lost, one more than parity covers
+            if arrivals <= 3 {
+                continue;
+            }
+            chunks.insert(chunk.metadata.sequence_number, chunk.clone());
+
+            let meta = &chunk.metadata;
+            if chunks.len() < meta.data_chunks as usize {
+                continue;
+            }
+            let manifest = chunkstream_pro::chunk::FileManifest {
+                file_id: meta.file_id.clone(),
+                filename: "lossy.bin".to_string(),
+                total_size: meta.file_size,
+                chunk_size: chunk.data.len(),
+                total_chunks: meta.total_chunks,
+                data_chunks: meta.data_chunks,
+                parity_chunks: meta.total_chunks - meta.data_chunks,
+                checksum: meta.file_checksum,
+                priority: meta.priority,
+                zero_runs: meta.zero_runs.clone(),
+                attributes: meta.attributes.clone(),
+                checksum_algorithm: Default::default(),
+                erasure_profile: Default::default(),
+                schedule: None,
+                merkle_root: None,
+                compression: None,
+            };
+            chunk_manager
+                .reconstruct_file(&manifest, chunks.values().cloned().collect(), &output)
+                .await
+                .unwrap();
+
+            let feedback = GroupFeedback {
+                file_id: meta.file_id.clone(),
+                data_chunks: meta.data_chunks,
+                total_chunks: meta.total_chunks,
+                received: chunks.keys().copied().collect(),
+            };
+            receiver_transport
+                .send_group_feedback(&conn, &feedback)
+                .await
+                .unwrap();
+            // The connection stays up until the sender has settled
+            return (arrivals, conn);
+        }
+    });
+
+    let coordinator = TransferCoordinator::new(
+        ChunkManager::new(256 * 1024, 10, 3).unwrap(),
+        QuicTransport::new(ConnectionConfig {
+            bind_addr: "127.0.0.1:0".parse().unwrap(),
+            ..Default::default()
+        })
+        .await
+        .unwrap(),
+        PriorityQueue::new(1000),
+        SessionStore::new_in_memory().await.unwrap(),
+    );
+    let session_id = coordinator
+        .send_file(test_file.clone(), Priority::High, Some(receiver_addr))
+        .await
+        .unwrap();
+
+    // 4 data + 2 parity shards: three lost, so exactly one is resent
+    let (arrivals, _conn) = tokio::time::timeout(Duration::from_secs(30), receiver_handle)
+        .await
+        .expect("Receiver timed out")
+        .unwrap();
+    assert_eq!(arrivals, 7);
+    assert_eq!(fs::read(&receiver_output).await.unwrap(), test_data);
+
+    for _ in 0..50 {
+        let progress = coordinator.get_progress(&session_id).await.unwrap();
+        if progress.status.is_terminal() {
+            break;
+        }
+        sleep(Duration::from_millis(100)).await;
+    }
+    let progress = coordinator.get_progress(&session_id).await.unwrap();
+    assert!(progress.status.is_completed(), "{:?}", progress.status);
+    assert!(coordinator.transport().stats().group_reports_received >= 1);
+}
+
+/// Send a file checksummed with `algorithm` and return the offer and
+/// manifest the receiver saw, with the reconstructed contents
+async fn transfer_with_checksum(
+    algorithm: ChecksumType,
+) -> (FileOffer, chunkstream_pro::chunk::FileManifest, Vec<u8>) {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = temp_dir.path().join("report.bin");
+    let test_data: Vec<u8> = (0..300 * 1024).map(|i| (i % 241) as u8).collect();
+    fs::write(&test_file, &test_data).await.unwrap();
+
+    let receiver_transport = Arc::new(
+        QuicTransport::new(ConnectionConfig {
+            bind_addr: "127.0.0.1:0".parse().unwrap(),
+            ..Default::default()
+        })
+        .await
+        .unwrap(),
+    );
+    let receiver_addr = receiver_transport.local_addr().unwrap();
+    let output = temp_dir.path().join("received.bin");
+
+    let receiver_output = output.clone();
+    let receiver_handle = tokio::spawn(async move {
+        let conn = receiver_transport.accept().await.unwrap();
+        let (offer, reply) = QuicTransport::accept_offer(&conn).await.unwrap();
+        QuicTransport::answer_offer(reply, OfferReply::Accept(Capabilities::local()))
+            .await
+            .unwrap();
+
+        let chunk_manager = ChunkManager::new(64 * 1024, 4, 2).unwrap();
+        let mut chunks = Vec::new();
+        loop {
+            let stream = conn.accept_uni().await.unwrap();
+            let chunk = receiver_transport
+                .receive_chunk(stream)
+                .await
+                .unwrap()
+                .into_chunk();
+            chunks.push(chunk.clone());
+            let meta = &chunk.metadata;
+            if chunks.len() < meta.data_chunks as usize {
+                continue;
+            }
+            let manifest = chunkstream_pro::chunk::FileManifest {
+                file_id: meta.file_id.clone(),
+                filename: offer.filename.clone(),
+                total_size: meta.file_size,
+                chunk_size: chunk.data.len(),
+                total_chunks: meta.total_chunks,
+                data_chunks: meta.data_chunks,
+                parity_chunks: meta.total_chunks - meta.data_chunks,
+                checksum: meta.file_checksum,
+                priority: meta.priority,
+                zero_runs: meta.zero_runs.clone(),
+                attributes: meta.attributes.clone(),
+                checksum_algorithm: meta.checksum_algorithm,
+                erasure_profile: Default::default(),
+                schedule: None,
+                merkle_root: None,
+                compression: None,
+            };
+            chunk_manager
+                .reconstruct_file(&manifest, chunks, &receiver_output)
+                .await
+                .unwrap();
+            return (offer, manifest);
+        }
+    });
+
+    let coordinator = TransferCoordinator::new(
+        ChunkManager::new(64 * 1024, 4, 2)
+            .unwrap()
+            .with_checksum_algorithm(algorithm),
+        QuicTransport::new(ConnectionConfig {
+            bind_addr: "127.0.0.1:0".parse().unwrap(),
+            ..Default::default()
+        })
+        .await
+        .unwrap(),
+        PriorityQueue::new(1000),
+        SessionStore::new_in_memory().await.unwrap(),
+    );
+    coordinator
+        .send_file(test_file, Priority::High, Some(receiver_addr))
+        .await
+        .unwrap();
+
+    let (offer, manifest) = tokio::time::timeout(Duration::from_secs(30), receiver_handle)
+        .await
+        .expect("receiver timed out")
+        .unwrap();
+    let received = fs::read(&output).await.unwrap();
+    assert_eq!(received, test_data);
+    (offer, manifest, received)
+}
+
+/// Files checksummed with SHA-256 or CRC32 verify at the receiver against
+/// the algorithm the offer and chunks name, not against Blake3
+#[tokio::test]
+async fn test_non_blake3_checksums_verify_end_to_end() {
+    rustls::crypto::ring::default_provider()
+        .install_default()
+        .ok();
+
+    for algorithm in [ChecksumType::Sha256, ChecksumType::Crc32] {
+        let (offer, manifest, received) = transfer_with_checksum(algorithm).await;
+
+        assert_eq!(offer.checksum_algorithm, algorithm);
+        assert_eq!(offer.checksum, manifest.checksum);
+        assert_eq!(manifest.checksum_algorithm, algorithm);
+        IntegrityVerifier::verify_file_data(&received, &manifest).unwrap();
+        // A receiver hashing with Blake3 regardless would report a mismatch
+        assert_ne!(
+            IntegrityVerifier::calculate_checksum(&received),
+            manifest.checksum
+        );
+    }
+}
@@ -0,0 +1,129 @@
+//! Receive-side reordering and duplication under simulated jitter
+//!
+//! Chunks go through a [`LossyChannel`] that delivers them out of order
+//! and sometimes twice, into the sequence-window assembler and on-disk
+//! spool the receiver uses, and the file is then reconstructed from what
+//! was spooled and buffered.
+
+#[path = "simulation/mod.rs"]
+mod simulation;
+
+use chunkstream_pro::chunk::{
+    ChunkManager, ChunkSpool, Priority, ReorderConfig, ReorderStats, SequenceAssembler,
+};
+use chunkstream_pro::integrity::IntegrityVerifier;
+use simulation::{LossyChannel, LossyChannelConfig};
+use tempfile::TempDir;
+
+const FILE_SIZE: usize = 400 * 1024;
+const CHUNK_SIZE: usize = 2 * 1024;
+
+/// Send a file through `channel` and rebuild it; returns the reorder stats
+/// and whether the rebuilt file matches
+async fn transfer(channel: LossyChannelConfig, reorder: ReorderConfig) -> (ReorderStats, bool) {
+    let dir = TempDir::new().unwrap();
+    let source = dir.path().join("source.bin");
+    let data: Vec<u8> = (0..FILE_SIZE).map(|i| (i * 7 % 251) as u8).collect();
+    tokio::fs::write(&source, &data).await.unwrap();
+
+    let manager = ChunkManager::new(CHUNK_SIZE, 50, 10).unwrap();
+    let (manifest, chunks) = manager
+        .split_file(&source, "reorder-sim".into(), Priority::Normal)
+        .await
+        .unwrap();
+
+    let channel = LossyChannel::new(channel);
+    let payloads: Vec<Vec<u8>> = chunks.iter().map(|c| c.data.to_vec()).collect();
+    let arrivals = channel.deliver_chunks(&payloads).await;
+
+    let mut assembler = SequenceAssembler::new(manifest.total_chunks, reorder);
+    let mut spool = ChunkSpool::create(dir.path().join("partial.spool"))
+        .await
+        .unwrap();
+    for (index, payload) in arrivals {
+        let mut chunk = chunks[index].clone();
+        chunk.data = payload.into();
+        assembler.insert(chunk);
+        spool.append(&assembler.take_ready()).await.unwrap();
+        assert!(assembler.buffered().count() <= reorder.window);
+    }
+
+    let mut received = spool.read_all().await.unwrap();
+    received.extend(assembler.buffered().cloned());
+    assert_eq!(received.len() as u64, assembler.received());
+
+    let output = dir.path().join("output.bin");
+    let rebuilt = manager
+        .reconstruct_file(&manifest, received, &output)
+        .await
+        .is_ok()
+        && IntegrityVerifier::calculate_checksum(&tokio::fs::read(&output).await.unwrap())
+            == IntegrityVerifier::calculate_checksum(&data);
+
+    println!("{}", channel.stats().summary());
+    println!("{:?}", assembler.stats());
+    (assembler.stats().clone(), rebuilt)
+}
+
+#[tokio::test]
+async fn test_in_order_link_flushes_every_group() {
+    let reorder = ReorderConfig {
+        window: 64,
+        group_size: 8,
+    };
+    let (stats, rebuilt) = transfer(LossyChannelConfig::perfect(), reorder).await;
+
+    assert!(rebuilt);
+    assert_eq!(stats.reordered, 0);
+    assert_eq!(stats.forced_flushes, 0);
+    assert_eq!(stats.buffered, 0);
+    // 200 data + 40 parity chunks in groups of 8
+    assert_eq!(stats.groups_flushed, 30);
+}
+
+#[tokio::test]
+async fn test_high_jitter_link_reassembles() {
+    let reorder = ReorderConfig {
+        window: 64,
+        group_size: 8,
+    };
+    let (stats, rebuilt) = transfer(LossyChannelConfig::high_jitter(), reorder).await;
+
+    assert!(rebuilt);
+    assert!(stats.reordered > 0);
+    assert!(stats.max_depth > 0);
+    assert!(stats.peak_buffered <= reorder.window);
+}
+
+#[tokio::test]
+async fn test_duplicates_are_dropped() {
+    let channel = LossyChannelConfig {
+        duplicate_rate: 0.2,
+        reorder_rate: 0.2,
+        ..Default::default()
+    };
+    let (stats, rebuilt) = transfer(channel, ReorderConfig::default()).await;
+
+    assert!(rebuilt);
+    assert!(stats.duplicates > 0);
+    assert_eq!(stats.in_order + stats.reordered, 240);
+}
+
+#[tokio::test]
+async fn test_small_window_spills_late_chunks_to_disk() {
+    let channel = LossyChannelConfig {
+        reorder_rate: 0.5,
+        reorder_distance: 48,
+        ..Default::default()
+    };
+    let reorder = ReorderConfig {
+        window: 4,
+        group_size: 8,
+    };
+    let (stats, rebuilt) = transfer(channel, reorder).await;
+
+    assert!(rebuilt);
+    assert!(stats.forced_flushes > 0);
+    assert!(stats.late_chunks > 0);
+    assert!(stats.peak_buffered <= 4);
+}
@@ -1,14 +1,16 @@
 //! Simulated lossy network channel for in-process testing
 //!
 //! This simulates network conditions like packet loss, latency, jitter,
-//! and bandwidth limits without requiring external tools like tc/netem.
+//! bandwidth limits, duplication and reordering without requiring external
+//! tools like tc/netem.
 
 #![allow(dead_code)]
 
 use rand::Rng;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 use tokio::time::sleep;
 
@@ -29,6 +31,8 @@ pub struct LossyChannelConfig {
     pub duplicate_rate: f32,
     /// Reorder rate (0.0 - 1.0) - chance of out-of-order delivery
     pub reorder_rate: f32,
+    /// Most packets a reordered packet can fall behind
+    pub reorder_distance: usize,
 }
 
 impl Default for LossyChannelConfig {
@@ -41,6 +45,7 @@ impl Default for LossyChannelConfig {
             corruption_rate: 0.0,
             duplicate_rate: 0.0,
             reorder_rate: 0.0,
+            reorder_distance: 8,
         }
     }
 }
@@ -106,6 +111,16 @@ impl LossyChannelConfig {
         }
     }
 
+    /// Create a high-jitter link where chunks routinely overtake each other
+    pub fn high_jitter() -> Self {
+        Self {
+            loss_rate: 0.02,
+            reorder_rate: 0.3,
+            reorder_distance: 32,
+            ..Default::default()
+        }
+    }
+
     /// Create custom loss rate config
     pub fn with_loss(loss_rate: f32) -> Self {
         Self {
@@ -165,12 +180,13 @@ impl ChannelStats {
 
     pub fn summary(&self) -> String {
         format!(
-            "Sent: {}, Lost: {} ({:.1}%), Corrupted: {}, Duplicated: {}, Avg Latency: {:.1}ms",
+            "Sent: {}, Lost: {} ({:.1}%), Corrupted: {}, Duplicated: {}, Reordered: {}, Avg Latency: {:.1}ms",
             self.packets_sent.load(Ordering::Relaxed),
             self.packets_lost.load(Ordering::Relaxed),
             self.actual_loss_rate() * 100.0,
             self.packets_corrupted.load(Ordering::Relaxed),
             self.packets_duplicated.load(Ordering::Relaxed),
+            self.packets_reordered.load(Ordering::Relaxed),
             self.average_latency_ms()
         )
     }
@@ -196,10 +212,50 @@ impl std::fmt::Display for ChannelError {
 
 impl std::error::Error for ChannelError {}
 
+/// A packet reaching the far end of the channel
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Arrival {
+    /// Which send the packet came from (see [`Sent::packet`])
+    pub packet: u64,
+    pub data: Vec<u8>,
+}
+
+/// What one [`LossyChannel::send`] delivered
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sent {
+    /// Number of this send on the channel, counting from 0
+    pub packet: u64,
+    /// Packets arriving as this one is sent, in arrival order: this packet
+    /// unless it was held back, copies of it, and earlier packets that were
+    /// held back behind it
+    pub arrivals: Vec<Arrival>,
+}
+
+impl Sent {
+    /// This packet's data, if it arrived with its own send
+    pub fn delivered(self) -> Option<Vec<u8>> {
+        let packet = self.packet;
+        self.arrivals
+            .into_iter()
+            .find(|arrival| arrival.packet == packet)
+            .map(|arrival| arrival.data)
+    }
+}
+
+/// A packet held back to arrive with a later send
+struct Held {
+    /// Send it arrives with
+    due: u64,
+    arrival: Arrival,
+}
+
 /// A simulated lossy network channel
 pub struct LossyChannel {
     config: LossyChannelConfig,
     stats: Arc<ChannelStats>,
+    /// Reordered packets and duplicates not yet arrived, in the order they
+    /// were held
+    held: Mutex<Vec<Held>>,
 }
 
 impl LossyChannel {
@@ -208,6 +264,7 @@ impl LossyChannel {
         Self {
             config,
             stats: Arc::new(ChannelStats::new()),
+            held: Mutex::new(Vec::new()),
         }
     }
 
@@ -222,12 +279,19 @@ impl LossyChannel {
     }
 
     /// Simulate sending data through the lossy channel
-    /// Returns the data if successful, or an error if the packet was lost/corrupted
-    pub async fn send(&self, data: &[u8]) -> Result<Vec<u8>, ChannelError> {
+    ///
+    /// Each send is one time slot. A lost packet is an error; otherwise the
+    /// returned [`Sent`] lists what arrives during this slot. With
+    /// `reorder_rate` set, a packet may be held back behind up to
+    /// `reorder_distance` later sends. With `duplicate_rate` set, a packet
+    /// may arrive a second time, up to `reorder_distance` sends after the
+    /// first copy. Held packets arrive with the first successful send at or
+    /// after their slot, or from [`flush`](Self::flush).
+    pub async fn send(&self, data: &[u8]) -> Result<Sent, ChannelError> {
         use rand::SeedableRng;
         let mut rng = rand::rngs::StdRng::from_entropy();
 
-        self.stats.packets_sent.fetch_add(1, Ordering::Relaxed);
+        let packet = self.stats.packets_sent.fetch_add(1, Ordering::Relaxed);
         self.stats
             .bytes_sent
             .fetch_add(data.len() as u64, Ordering::Relaxed);
@@ -259,8 +323,9 @@ impl LossyChannel {
         }
 
         // 3. Simulate bandwidth limit
-        if self.config.bandwidth_bps > 0 {
-            let transfer_time_ms = (data.len() as u64 * 8 * 1000) / self.config.bandwidth_bps;
+        if let Some(transfer_time_ms) =
+            (data.len() as u64 * 8 * 1000).checked_div(self.config.bandwidth_bps)
+        {
             if transfer_time_ms > 0 {
                 sleep(Duration::from_millis(transfer_time_ms)).await;
             }
@@ -278,7 +343,57 @@ impl LossyChannel {
             }
         }
 
-        Ok(result)
+        // 5. Simulate reordering: hold the packet back behind later ones
+        let distance = self.config.reorder_distance as u64;
+        let mut due = packet;
+        if distance > 0 && rng.gen::<f32>() < self.config.reorder_rate {
+            self.stats.packets_reordered.fetch_add(1, Ordering::Relaxed);
+            due += rng.gen_range(1..=distance);
+        }
+
+        // 6. Simulate duplication: a second copy, no earlier than the first
+        let copy_due = (rng.gen::<f32>() < self.config.duplicate_rate).then(|| {
+            self.stats
+                .packets_duplicated
+                .fetch_add(1, Ordering::Relaxed);
+            due + rng.gen_range(0..=distance)
+        });
+
+        let mut held = self.held.lock().unwrap();
+        let arrival = Arrival {
+            packet,
+            data: result,
+        };
+        if let Some(copy_due) = copy_due {
+            held.push(Held {
+                due,
+                arrival: arrival.clone(),
+            });
+            held.push(Held {
+                due: copy_due,
+                arrival,
+            });
+        } else {
+            held.push(Held { due, arrival });
+        }
+        let arrivals = Self::take_due(&mut held, Some(packet));
+        Ok(Sent { packet, arrivals })
+    }
+
+    /// Deliver every packet still held back, in the order they are due
+    pub fn flush(&self) -> Vec<Arrival> {
+        Self::take_due(&mut self.held.lock().unwrap(), None)
+    }
+
+    /// Remove the packets due by slot `now` (all of them for `None`)
+    fn take_due(held: &mut Vec<Held>, now: Option<u64>) -> Vec<Arrival> {
+        let (mut due, rest): (Vec<_>, Vec<_>) = std::mem::take(held)
+            .into_iter()
+            .partition(|h| now.is_none_or(|now| h.due <= now));
+        *held = rest;
+        // Stable: packets due at the same slot keep the order they were held
+        due.sort_by_key(|h| h.due);
+        due.into_iter().map(|h| h.arrival).collect()
     }
 
     /// Simulate sending data with automatic retry on loss
@@ -286,11 +401,11 @@ impl LossyChannel {
         &self,
         data: &[u8],
         max_retries: u32,
-    ) -> Result<Vec<u8>, ChannelError> {
+    ) -> Result<Sent, ChannelError> {
         let mut attempts = 0;
         loop {
             match self.send(data).await {
-                Ok(result) => return Ok(result),
+                Ok(sent) => return Ok(sent),
                 Err(ChannelError::PacketLost) if attempts < max_retries => {
                     attempts += 1;
                     // Exponential backoff
@@ -303,7 +418,7 @@ impl LossyChannel {
     }
 
     /// Simulate sending multiple chunks and return which ones succeeded
-    pub async fn send_chunks(&self, chunks: &[Vec<u8>]) -> Vec<Result<Vec<u8>, ChannelError>> {
+    pub async fn send_chunks(&self, chunks: &[Vec<u8>]) -> Vec<Result<Sent, ChannelError>> {
         let mut results = Vec::with_capacity(chunks.len());
         for chunk in chunks {
             results.push(self.send(chunk).await);
@@ -311,6 +426,29 @@ impl LossyChannel {
         results
     }
 
+    /// Send `chunks` in order and return what arrives, in arrival order
+    ///
+    /// Each entry is the chunk's index and its (possibly corrupted) data,
+    /// reordered and duplicated as [`send`](Self::send) describes. Packets
+    /// still held back after the last chunk are flushed.
+    pub async fn deliver_chunks(&self, chunks: &[Vec<u8>]) -> Vec<(usize, Vec<u8>)> {
+        let mut index_of = HashMap::new();
+        let mut arrivals = Vec::with_capacity(chunks.len());
+        for (index, chunk) in chunks.iter().enumerate() {
+            if let Ok(sent) = self.send(chunk).await {
+                index_of.insert(sent.packet, index);
+                arrivals.extend(sent.arrivals);
+            }
+        }
+        arrivals.extend(self.flush());
+
+        // Packets from earlier sends on the channel aren't ours to report
+        arrivals
+            .into_iter()
+            .filter_map(|arrival| Some((*index_of.get(&arrival.packet)?, arrival.data)))
+            .collect()
+    }
+
     /// Reset statistics
     pub fn reset_stats(&self) {
         self.stats.packets_sent.store(0, Ordering::Relaxed);
@@ -334,7 +472,7 @@ mod tests {
 
         let result = channel.send(&data).await;
         assert!(result.is_ok());
-        assert_eq!(result.unwrap(), data);
+        assert_eq!(result.unwrap().delivered(), Some(data));
 
         let stats = channel.stats();
         assert_eq!(stats.packets_sent.load(Ordering::Relaxed), 1);
@@ -399,6 +537,106 @@ mod tests {
         assert!(loss_rate > 0.10 && loss_rate < 0.30);
     }
 
+    #[tokio::test]
+    async fn test_reordered_delivery() {
+        let config = LossyChannelConfig {
+            reorder_rate: 0.5,
+            reorder_distance: 4,
+            ..Default::default()
+        };
+        let channel = LossyChannel::new(config);
+        let chunks: Vec<Vec<u8>> = (0..200u8).map(|i| vec![i]).collect();
+
+        let arrived = channel.deliver_chunks(&chunks).await;
+        assert_eq!(arrived.len(), chunks.len());
+        assert!(arrived.iter().all(|(index, data)| data == &chunks[*index]));
+
+        // Out of order, but never by more than the configured distance
+        assert!(arrived.windows(2).any(|w| w[0].0 > w[1].0));
+        for (position, (index, _)) in arrived.iter().enumerate() {
+            assert!(*index + 4 >= position);
+        }
+        assert!(channel.stats().packets_reordered.load(Ordering::Relaxed) > 0);
+    }
+
+    #[tokio::test]
+    async fn test_duplicated_delivery() {
+        let config = LossyChannelConfig {
+            duplicate_rate: 0.25,
+            ..Default::default()
+        };
+        let channel = LossyChannel::new(config);
+        let chunks: Vec<Vec<u8>> = (0..200u8).map(|i| vec![i]).collect();
+
+        let arrived = channel.deliver_chunks(&chunks).await;
+        let duplicated = channel.stats().packets_duplicated.load(Ordering::Relaxed);
+        assert!(duplicated > 0);
+        assert_eq!(arrived.len(), chunks.len() + duplicated as usize);
+        assert!(arrived.iter().all(|(index, data)| data == &chunks[*index]));
+
+        // Every chunk still arrives, each at most twice
+        let mut copies = [0u32; 200];
+        for (index, _) in &arrived {
+            copies[*index] += 1;
+        }
+        assert!(copies.iter().all(|&n| n == 1 || n == 2));
+    }
+
+    #[tokio::test]
+    async fn test_send_holds_reordered_packets_back() {
+        let config = LossyChannelConfig {
+            reorder_rate: 1.0,
+            reorder_distance: 1,
+            ..Default::default()
+        };
+        let channel = LossyChannel::new(config);
+
+        // Each packet arrives one send late, behind the next one
+        let first = channel.send(&[0]).await.unwrap();
+        assert!(first.arrivals.is_empty());
+        let second = channel.send(&[1]).await.unwrap();
+        assert_eq!(
+            second.arrivals,
+            vec![Arrival {
+                packet: first.packet,
+                data: vec![0]
+            }]
+        );
+        assert_eq!(second.delivered(), None);
+        assert_eq!(
+            channel.flush(),
+            vec![Arrival {
+                packet: 1,
+                data: vec![1]
+            }]
+        );
+        assert!(channel.flush().is_empty());
+        assert_eq!(channel.stats().packets_reordered.load(Ordering::Relaxed), 2);
+    }
+
+    #[tokio::test]
+    async fn test_send_duplicates_packets() {
+        let config = LossyChannelConfig {
+            duplicate_rate: 1.0,
+            reorder_distance: 0,
+            ..Default::default()
+        };
+        let channel = LossyChannel::new(config);
+
+        // With no distance to spread them, both copies arrive at once
+        let sent = channel.send(&[7, 7]).await.unwrap();
+        assert_eq!(sent.arrivals.len(), 2);
+        assert!(sent
+            .arrivals
+            .iter()
+            .all(|arrival| arrival.packet == sent.packet && arrival.data == [7, 7]));
+        assert_eq!(
+            channel.stats().packets_duplicated.load(Ordering::Relaxed),
+            1
+        );
+        assert!(channel.flush().is_empty());
+    }
+
     #[tokio::test]
     async fn test_send_with_retry() {
         let config = LossyChannelConfig {
@@ -3,6 +3,9 @@
 //! This module provides a simulated lossy network channel that can be used
 //! to test the resilience of the file transfer system under various network conditions.
 
+// Each test binary pulls in only the helpers it needs.
+#![allow(dead_code, unused_imports)]
+
 pub mod lossy_channel;
 pub mod metrics;
 pub mod network_profile;
@@ -15,7 +18,7 @@ pub use report_generator::BenchmarkReport;
 
 // Re-export for use in other tests (allow unused for now)
 #[allow(unused_imports)]
-pub use lossy_channel::{ChannelError, ChannelStats};
+pub use lossy_channel::{Arrival, ChannelError, ChannelStats, Sent};
 #[allow(unused_imports)]
 pub use metrics::{BenchmarkMetrics, BenchmarkSummary};
 #[allow(unused_imports)]
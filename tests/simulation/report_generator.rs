@@ -240,7 +240,7 @@ impl BenchmarkReport {
             ));
         }
 
-        md.push_str("\n");
+        md.push('\n');
 
         let verdict = if self.claim_validation.validated {
             "CLAIM VALIDATED"
@@ -272,7 +272,7 @@ impl BenchmarkReport {
         for (loss, throughput) in &self.performance_curves.throughput_vs_loss {
             md.push_str(&format!("| {:.0}% | {:.2} |\n", loss * 100.0, throughput));
         }
-        md.push_str("\n");
+        md.push('\n');
 
         md.push_str("### Throughput vs File Size\n\n");
         md.push_str("| File Size | Throughput (MB/s) |\n");
@@ -280,7 +280,7 @@ impl BenchmarkReport {
         for (size, throughput) in &self.performance_curves.throughput_vs_file_size {
             md.push_str(&format!("| {} | {:.2} |\n", format_size(*size), throughput));
         }
-        md.push_str("\n");
+        md.push('\n');
 
         // System Info
         md.push_str("## System Information\n\n");
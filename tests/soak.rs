@@ -0,0 +1,313 @@
+//! Soak test: transfers started, cancelled, paused and resumed for as long as
+//! asked against a lossy loopback receiver
+//!
+//! Fails if process memory, open file descriptors, the coordinator's maps or
+//! the number of live tasks keep growing once transfers settle.
+//!
+//! Run with: cargo test --release --features soak --test soak -- --nocapture
+//!
+//! `SOAK_DURATION_SECS` sets how long to run (default 300; use hours before a
+//! release), `SOAK_CONCURRENCY` the transfers per round (default 8) and
+//! `SOAK_LOSS` the share of chunk streams the receiver drops (default 0.05).
+
+use chunkstream_pro::chunk::{ChunkManager, Priority};
+use chunkstream_pro::coordinator::{
+    ResourceUsage, RetentionPolicy, RetransmitPolicy, TransferCoordinator,
+};
+use chunkstream_pro::network::{
+    Capabilities, ConnectionConfig, NetworkError, OfferReply, QuicTransport,
+};
+use chunkstream_pro::priority::pressure::process_rss_bytes;
+use chunkstream_pro::priority::PriorityQueue;
+use chunkstream_pro::session::SessionStore;
+use rand::Rng;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tempfile::TempDir;
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, Duration, Instant};
+
+/// Longest a round may take to settle before it counts as stuck
+const SETTLE_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Time a cancelled transfer's worker gets to stop
+const WIND_DOWN: Duration = Duration::from_millis(250);
+
+/// Growth from the start of the run to its end that still counts as noise
+const RSS_ALLOWANCE: u64 = 64 * 1024 * 1024;
+const FD_ALLOWANCE: u64 = 16;
+const TASK_ALLOWANCE: u64 = 8;
+
+fn env_or<T: std::str::FromStr>(name: &str, default: T) -> T {
+    std::env::var(name)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default)
+}
+
+/// Receiver that answers offers and takes chunks, dropping a share of them
+async fn lossy_receiver(loss: f64) -> (Arc<QuicTransport>, SocketAddr, JoinHandle<()>) {
+    rustls::crypto::ring::default_provider()
+        .install_default()
+        .ok();
+    let receiver = Arc::new(
+        QuicTransport::new(ConnectionConfig {
+            bind_addr: "127.0.0.1:0".parse().unwrap(),
+            ..Default::default()
+        })
+        .await
+        .unwrap(),
+    );
+    let addr = receiver.local_addr().unwrap();
+    let accepting = receiver.clone();
+    let accept = tokio::spawn(async move {
+        loop {
+            let conn = match accepting.accept().await {
+                Ok(conn) => conn,
+                // Handshakes cut short by a cancelled transfer are expected
+                Err(NetworkError::ConnectionClosed(_)) => break,
+                Err(_) => continue,
+            };
+            let offers = conn.clone();
+            tokio::spawn(async move {
+                while let Ok((_, reply)) = QuicTransport::accept_offer(&offers).await {
+                    let accept = OfferReply::Accept(Capabilities::local());
+                    if QuicTransport::answer_offer(reply, accept).await.is_err() {
+                        break;
+                    }
+                }
+            });
+            let receiver = accepting.clone();
+            tokio::spawn(async move {
+                while let Ok(mut stream) = conn.accept_uni().await {
+                    if rand::thread_rng().gen_bool(loss) {
+                        let _ = stream.stop(0u32.into());
+                    } else if receiver.receive_chunk(stream).await.is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+    });
+    (receiver, addr, accept)
+}
+
+async fn coordinator(dir: &Path, concurrency: usize) -> TransferCoordinator {
+    let url = format!("sqlite://{}?mode=rwc", dir.join("sessions.db").display());
+    let coordinator = TransferCoordinator::new(
+        ChunkManager::new(64 * 1024, 4, 2).unwrap(),
+        QuicTransport::new(ConnectionConfig {
+            bind_addr: "127.0.0.1:0".parse().unwrap(),
+            ..Default::default()
+        })
+        .await
+        .unwrap(),
+        PriorityQueue::new(10_000),
+        SessionStore::new(&url).await.unwrap(),
+    );
+    // Keep a few rounds of finished transfers, swept often
+    coordinator.set_retention(RetentionPolicy {
+        max_entries: concurrency * 4,
+        max_age: Duration::from_secs(30),
+        sweep_interval: Duration::from_secs(1),
+    });
+    // Quick retry passes and no waiting for group reports
+    coordinator.set_retransmit_policy(RetransmitPolicy {
+        budget_per_group: 0,
+        retry_backoff: Duration::from_millis(10),
+        max_retry_backoff: Duration::from_millis(50),
+        ..Default::default()
+    });
+    coordinator
+}
+
+async fn write_file(dir: &Path, name: &str, size: usize, seed: usize) -> PathBuf {
+    let path = dir.join(name);
+    let data: Vec<u8> = (0..size).map(|i| ((i + seed) % 251) as u8).collect();
+    tokio::fs::write(&path, data).await.unwrap();
+    path
+}
+
+/// Open file descriptors of this process
+fn open_fds() -> Option<u64> {
+    std::fs::read_dir("/proc/self/fd")
+        .ok()
+        .map(|dir| dir.count() as u64)
+}
+
+/// Resource readings taken once a round has settled
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    rss: Option<u64>,
+    fds: Option<u64>,
+    tasks: u64,
+    usage: ResourceUsage,
+    receiver_connections: usize,
+}
+
+impl Sample {
+    fn take(coordinator: &TransferCoordinator, receiver: &QuicTransport) -> Self {
+        Self {
+            rss: process_rss_bytes(),
+            fds: open_fds(),
+            tasks: tokio::runtime::Handle::current()
+                .metrics()
+                .num_alive_tasks() as u64,
+            usage: coordinator.resource_usage(),
+            receiver_connections: receiver.connection_count(),
+        }
+    }
+}
+
+/// Fail if `series` ends consistently above where it started
+///
+/// Compares the highest reading in the first quarter of the run with the
+/// lowest in the last, so a single spike at either end doesn't count.
+fn assert_bounded(name: &str, series: &[u64], allowance: u64) {
+    let quarter = (series.len() / 4).max(1);
+    let early = series[..quarter].iter().max().unwrap();
+    let late = series[series.len() - quarter..].iter().min().unwrap();
+    assert!(
+        late.saturating_sub(*early) <= allowance,
+        "{name} grew from {early} to {late} (allowed {allowance}): {series:?}"
+    );
+}
+
+/// Wait until no transfer is running or waiting for a slot
+async fn settle(coordinator: &TransferCoordinator, round: usize) {
+    let deadline = Instant::now() + SETTLE_TIMEOUT;
+    loop {
+        let usage = coordinator.resource_usage();
+        if usage.active_transfers == 0 && usage.pending_transfers == 0 {
+            // Cancelled transfers leave the active set at once; give their
+            // workers a moment to wind down before taking readings
+            sleep(WIND_DOWN).await;
+            return;
+        }
+        assert!(
+            Instant::now() < deadline,
+            "round {round} never settled: {usage:?}"
+        );
+        sleep(Duration::from_millis(20)).await;
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_soak_start_cancel_resume_without_leaks() {
+    let duration = Duration::from_secs(env_or("SOAK_DURATION_SECS", 300));
+    let concurrency = env_or("SOAK_CONCURRENCY", 8usize);
+    let loss = env_or("SOAK_LOSS", 0.05f64);
+
+    let dir = TempDir::new().unwrap();
+    let (receiver, receiver_addr, accept) = lossy_receiver(loss).await;
+    let coordinator = Arc::new(coordinator(dir.path(), concurrency).await);
+
+    let started = Instant::now();
+    let mut samples = Vec::new();
+    let mut round = 0;
+    while started.elapsed() < duration {
+        let mut workers = Vec::new();
+        for slot in 0..concurrency {
+            let size = rand::thread_rng().gen_range(16 * 1024..512 * 1024);
+            // A fresh name each round, so a mapping left behind by a
+            // finished transfer isn't hidden by the next one reusing it
+            let file =
+                write_file(dir.path(), &format!("soak-{round}-{slot}.bin"), size, round).await;
+            let coordinator = coordinator.clone();
+            workers.push(tokio::spawn(async move {
+                let priority = match slot % 3 {
+                    0 => Priority::Critical,
+                    1 => Priority::High,
+                    _ => Priority::Normal,
+                };
+                let Ok(session_id) = coordinator
+                    .send_file(file, priority, Some(receiver_addr))
+                    .await
+                else {
+                    return;
+                };
+                let delay = Duration::from_millis(rand::thread_rng().gen_range(0..200));
+                // Transfers may finish before being cancelled or paused,
+                // so those calls are allowed to fail
+                match slot % 4 {
+                    0 => {
+                        sleep(delay).await;
+                        let _ = coordinator.cancel_transfer(&session_id).await;
+                    }
+                    1 => {
+                        sleep(delay).await;
+                        if coordinator.pause_transfer(&session_id).await.is_ok() {
+                            sleep(delay).await;
+                            if coordinator.resume_transfer(&session_id).await.is_err() {
+                                let _ = coordinator.cancel_transfer(&session_id).await;
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }));
+        }
+        for worker in workers {
+            worker.await.unwrap();
+        }
+        settle(&coordinator, round).await;
+        coordinator.evict_finished();
+        for slot in 0..concurrency {
+            let _ =
+                tokio::fs::remove_file(dir.path().join(format!("soak-{round}-{slot}.bin"))).await;
+        }
+
+        let sample = Sample::take(&coordinator, &receiver);
+        if round % 50 == 0 {
+            println!(
+                "[{:>6}s] round {round}: {sample:?}",
+                started.elapsed().as_secs()
+            );
+        }
+        samples.push(sample);
+        round += 1;
+    }
+    accept.abort();
+
+    println!(
+        "{round} rounds of {concurrency} transfers in {:?}",
+        started.elapsed()
+    );
+    assert!(round > 0);
+    let last = samples.last().unwrap().usage;
+    assert_eq!(last.active_transfers, 0);
+    assert_eq!(last.relay_resends, 0);
+    assert_eq!(last.audit_suspects, 0);
+    assert_eq!(last.sampled_sessions, 0);
+    assert!(last.recent_transfers <= concurrency * 4, "{last:?}");
+
+    let series = |f: fn(&Sample) -> u64| samples.iter().map(f).collect::<Vec<_>>();
+    assert_bounded(
+        "queued chunks",
+        &series(|s| s.usage.queued_chunks as u64),
+        0,
+    );
+    assert_bounded(
+        "file sessions",
+        &series(|s| s.usage.file_sessions as u64),
+        0,
+    );
+    assert_bounded(
+        "sender connections",
+        &series(|s| s.usage.connections as u64),
+        0,
+    );
+    assert_bounded(
+        "receiver connections",
+        &series(|s| s.receiver_connections as u64),
+        concurrency as u64,
+    );
+    assert_bounded("live tasks", &series(|s| s.tasks), TASK_ALLOWANCE);
+    if samples.iter().all(|s| s.fds.is_some()) {
+        assert_bounded("open fds", &series(|s| s.fds.unwrap()), FD_ALLOWANCE);
+    }
+    if samples.iter().all(|s| s.rss.is_some()) {
+        assert_bounded("RSS bytes", &series(|s| s.rss.unwrap()), RSS_ALLOWANCE);
+    }
+}
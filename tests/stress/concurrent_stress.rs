@@ -8,7 +8,7 @@ mod simulation;
 use chunkstream_pro::chunk::{ChunkManager, Priority};
 use chunkstream_pro::integrity::IntegrityVerifier;
 use chunkstream_pro::priority::PriorityQueue;
-use simulation::{LossyChannel, LossyChannelConfig};
+use simulation::{LossyChannel, LossyChannelConfig, Sent};
 use std::sync::Arc;
 use std::time::Instant;
 use tempfile::TempDir;
@@ -55,7 +55,12 @@ async fn run_single_transfer(
 
     for chunk in &chunks {
         let chunk_data = chunk.data.to_vec();
-        if let Ok(data) = channel.send(&chunk_data).await {
+        if let Some(data) = channel
+            .send(&chunk_data)
+            .await
+            .ok()
+            .and_then(Sent::delivered)
+        {
             let mut received_chunk = chunk.clone();
             received_chunk.data = data.into();
             received_chunks.push(received_chunk);
@@ -210,7 +215,7 @@ async fn stress_priority_queue_concurrent() {
                 }
                 last_priority = Priority::High;
             }
-            Priority::Normal => {
+            Priority::Normal | Priority::Level(_) => {
                 normal_count += 1;
                 last_priority = Priority::Normal;
             }
@@ -258,7 +263,7 @@ async fn stress_rapid_queue_cycles() {
 
         // Dequeue some
         for _ in 0..5 {
-            queue.dequeue();
+            let _ = queue.dequeue();
         }
     }
 
@@ -293,6 +298,12 @@ fn create_dummy_chunk(id: usize, priority: Priority) -> chunkstream_pro::chunk::
             file_size: 102400,
             file_checksum: [0u8; 32],
             data_chunks: 50,
+            zero_runs: Vec::new(),
+            attributes: None,
+            checksum_algorithm: Default::default(),
+            merkle_proof: None,
+            trace: None,
+            compression: None,
         },
         data: Bytes::from(vec![0u8; 1024]),
     }
@@ -319,16 +330,9 @@ async fn stress_priority_mixed_loss() {
     // Run transfers for each priority
     for priority in [Priority::Critical, Priority::High, Priority::Normal] {
         for i in 0..transfers_per_priority {
-            let (success, duration, _) = run_single_transfer(
-                file_size,
-                loss_rate,
-                i + match priority {
-                    Priority::Critical => 0,
-                    Priority::High => 100,
-                    Priority::Normal => 200,
-                },
-            )
-            .await;
+            let (success, duration, _) =
+                run_single_transfer(file_size, loss_rate, i + 100 * priority.level() as usize)
+                    .await;
             results.push((priority, success, duration));
         }
     }
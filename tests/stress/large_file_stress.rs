@@ -7,7 +7,7 @@ mod simulation;
 
 use chunkstream_pro::chunk::{ChunkManager, Priority};
 use chunkstream_pro::integrity::IntegrityVerifier;
-use simulation::{LossyChannel, LossyChannelConfig};
+use simulation::{LossyChannel, LossyChannelConfig, Sent};
 use std::time::Instant;
 use tempfile::TempDir;
 use tokio::fs;
@@ -45,7 +45,12 @@ async fn test_large_file(
 
     for chunk in &chunks {
         let chunk_data = chunk.data.to_vec();
-        if let Ok(data) = channel.send(&chunk_data).await {
+        if let Some(data) = channel
+            .send(&chunk_data)
+            .await
+            .ok()
+            .and_then(Sent::delivered)
+        {
             let mut received_chunk = chunk.clone();
             received_chunk.data = data.into();
             received_chunks.push(received_chunk);
@@ -95,7 +100,7 @@ async fn stress_large_file_sizes() {
     println!("========================================\n");
 
     let file_sizes = vec![
-        1 * 1024 * 1024,  // 1 MB
+        1024 * 1024,      // 1 MB
         5 * 1024 * 1024,  // 5 MB
         10 * 1024 * 1024, // 10 MB
         25 * 1024 * 1024, // 25 MB
@@ -107,10 +112,10 @@ async fn stress_large_file_sizes() {
     println!("Configuration: 50+10 shards, 512KB chunks, 10% packet loss\n");
 
     println!(
-        "{:<12} | {:<10} | {:<12} | {:<15} | {:<10}",
-        "File Size", "Chunks", "Received", "Duration", "Success"
+        "{:<12} | {:<10} | {:<12} | {:<15} | {:<13} | {:<10}",
+        "File Size", "Chunks", "Received", "Duration", "Throughput", "Success"
     );
-    println!("{}", "-".repeat(65));
+    println!("{}", "-".repeat(81));
 
     for file_size in file_sizes {
         let (success, duration_ms, total, received) =
@@ -123,12 +128,13 @@ async fn stress_large_file_sizes() {
         };
 
         println!(
-            "{:<12} | {:>10} | {:>6}/{:<5} | {:>10}ms | {:>10}",
+            "{:<12} | {:>10} | {:>6}/{:<5} | {:>10}ms | {:>8.2} MB/s | {:>10}",
             format_size(file_size),
             total,
             received,
             total,
             duration_ms,
+            throughput_mbps,
             if success { "PASS" } else { "FAIL" }
         );
     }
@@ -228,8 +234,8 @@ async fn stress_memory_estimation() {
     println!("STRESS TEST: Memory Usage Estimation");
     println!("==========================================\n");
 
-    let file_sizes = vec![
-        1 * 1024 * 1024,  // 1 MB
+    let file_sizes: Vec<usize> = vec![
+        1024 * 1024,      // 1 MB
         10 * 1024 * 1024, // 10 MB
         50 * 1024 * 1024, // 50 MB
     ];
@@ -246,7 +252,7 @@ async fn stress_memory_estimation() {
         let parity_shards = 10;
 
         // Estimate: file data + chunks (data + parity) + overhead
-        let num_chunks = (file_size + chunk_size - 1) / chunk_size;
+        let num_chunks = file_size.div_ceil(chunk_size);
         let total_shards = num_chunks * (data_shards + parity_shards);
         let estimated_peak = file_size + (total_shards * chunk_size / data_shards);
         let overhead = (estimated_peak as f64 / file_size as f64 - 1.0) * 100.0;
@@ -7,7 +7,7 @@ mod simulation;
 
 use chunkstream_pro::chunk::{ChunkManager, Priority};
 use chunkstream_pro::integrity::IntegrityVerifier;
-use simulation::{BenchmarkResult, LossyChannel, LossyChannelConfig, MetricsCollector};
+use simulation::{LossyChannel, LossyChannelConfig, Sent};
 use tempfile::TempDir;
 use tokio::fs;
 
@@ -52,7 +52,12 @@ async fn test_recovery_at_loss_rate(
 
         for chunk in &chunks {
             let chunk_data = chunk.data.to_vec();
-            if let Ok(data) = channel.send(&chunk_data).await {
+            if let Some(data) = channel
+                .send(&chunk_data)
+                .await
+                .ok()
+                .and_then(Sent::delivered)
+            {
                 let mut received_chunk = chunk.clone();
                 received_chunk.data = data.into();
                 received_chunks.push(received_chunk);
@@ -5,7 +5,9 @@
 //! - Large file transfers (100MB+)
 //! - Memory pressure scenarios
 //! - Rapid connection/disconnection cycles
+//! - Session store write contention
 
 pub mod max_packet_loss;
 pub mod large_file_stress;
 pub mod concurrent_stress;
+pub mod session_store_stress;
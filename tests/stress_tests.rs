@@ -2,6 +2,9 @@
 //!
 //! Run with: cargo test --test stress_tests -- --nocapture
 
+// Each stress module includes its own copy of the simulation helpers.
+#![allow(clippy::duplicate_mod, unused_imports)]
+
 #[path = "simulation/mod.rs"]
 mod simulation;
 
@@ -14,7 +17,13 @@ mod large_file_stress;
 #[path = "stress/concurrent_stress.rs"]
 mod concurrent_stress;
 
+#[cfg(feature = "sqlite")]
+#[path = "stress/session_store_stress.rs"]
+mod session_store_stress;
+
 // Re-export tests from submodules
 pub use concurrent_stress::*;
 pub use large_file_stress::*;
 pub use max_packet_loss::*;
+#[cfg(feature = "sqlite")]
+pub use session_store_stress::*;